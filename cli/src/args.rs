@@ -0,0 +1,106 @@
+//! Command line argument parsing
+//!
+//! Arguments are positional words naming the command, options of the form
+//! `--name value` or `--name=value`, and flags of the form `--name`. Which
+//! options are flags is fixed (see `FLAGS`); every other option takes a
+//! value. Options may appear anywhere after the program name.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::{CliError, Result};
+
+/// Options that take no value
+const FLAGS: [&str; 4] = ["json", "force", "force-weak-password", "help"];
+
+/// Parsed command line arguments
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Positional words, in order
+    positionals: Vec<String>,
+    
+    /// Options and their values; flags have no value
+    options: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    /// Parses arguments, not including the program name
+    pub fn parse<I, S>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().map(Into::into);
+        
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                parsed.positionals.push(arg);
+                continue;
+            };
+            
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None if FLAGS.contains(&name) => (name.to_string(), None),
+                None => {
+                    let value = args.next().ok_or_else(|| CliError::Usage(format!("--{} needs a value", name)))?;
+                    (name.to_string(), Some(value))
+                }
+            };
+            
+            if FLAGS.contains(&name.as_str()) && value.is_some() {
+                return Err(CliError::Usage(format!("--{} takes no value", name)));
+            }
+            if parsed.options.insert(name.clone(), value).is_some() {
+                return Err(CliError::Usage(format!("--{} given more than once", name)));
+            }
+        }
+        
+        Ok(parsed)
+    }
+    
+    /// Takes the next positional word
+    pub fn command(&mut self) -> Option<String> {
+        if self.positionals.is_empty() {
+            None
+        } else {
+            Some(self.positionals.remove(0))
+        }
+    }
+    
+    /// Takes a flag, returning whether it was given
+    pub fn flag(&mut self, name: &str) -> bool {
+        self.options.remove(name).is_some()
+    }
+    
+    /// Takes an option's value
+    pub fn value(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name).flatten()
+    }
+    
+    /// Takes an option's value, failing if it's missing
+    pub fn required(&mut self, name: &str) -> Result<String> {
+        self.value(name).ok_or_else(|| CliError::Usage(format!("missing --{}", name)))
+    }
+    
+    /// Takes an option's value and parses it
+    pub fn parsed<T: FromStr>(&mut self, name: &str) -> Result<Option<T>>
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.value(name)
+            .map(|value| value.parse().map_err(|e| CliError::Usage(format!("invalid --{} {}: {}", name, value, e))))
+            .transpose()
+    }
+    
+    /// Fails if any arguments weren't taken by the command
+    pub fn finish(self) -> Result<()> {
+        if let Some(word) = self.positionals.first() {
+            return Err(CliError::Usage(format!("unexpected argument {}", word)));
+        }
+        if let Some(name) = self.options.keys().min() {
+            return Err(CliError::Usage(format!("unknown option --{}", name)));
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,253 @@
+//! JSON-RPC client for a node
+//!
+//! Talks to a node's RPC server (see `node::rpc`) over plain HTTP, one
+//! connection per call. `RpcClient` implements `ChainClient`, so a
+//! `WalletApi` connected to it queries and submits through the node, and
+//! `FilterSource`, so a watch-only wallet can sync by block filters (see
+//! `wallet::filter_sync`).
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use ctb_core::transaction::Transaction;
+use ctb_core::block_filter::BlockFilter;
+use ctb_core::confirmation::{ChainTip, ConfirmationStatus};
+use ctb_core::fee_market::{EstimatedBlocks, FeeHistogram, FeeRate};
+use ctb_core::paging::{Page, PageRequest};
+use ctb_core::{BlockchainError, Bytes, Hash, TxHash};
+
+use node::eth::EXECUTION_REVERTED;
+
+use smartcontracts::FunctionABI;
+
+use wallet::api::{CallOutcome, ChainClient, GasEstimate, TransactionRecord};
+use wallet::filter_sync::{FilterHeaders, FilterSource, FilteredBlock};
+
+/// Address of a node's RPC server when none is given
+pub const DEFAULT_RPC_ADDR: &str = "127.0.0.1:8545";
+
+/// Prefix of the message of a call that reverted with a reason
+const REVERT_PREFIX: &str = "execution reverted: ";
+
+/// Time to wait for a node to answer
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error calling a node
+#[derive(Debug, Error)]
+pub enum RpcError {
+    #[error("Cannot reach node at {addr}: {source}")]
+    Connection { addr: String, source: io::Error },
+    
+    #[error("Node answered with HTTP {0}")]
+    Http(String),
+    
+    #[error("Invalid response from node: {0}")]
+    InvalidResponse(String),
+    
+    #[error("{message} (code {code})")]
+    Response { code: i64, message: String, data: Value },
+}
+
+/// Client of a node's JSON-RPC server
+pub struct RpcClient {
+    addr: String,
+    next_id: AtomicU64,
+    
+    /// Bearer token sent with every call, if any
+    auth_token: Option<String>,
+}
+
+impl RpcClient {
+    /// Creates a client of the node at an address such as `127.0.0.1:8545`
+    pub fn new(addr: &str) -> Self {
+        let addr = addr.strip_prefix("http://").unwrap_or(addr).trim_end_matches('/');
+        Self { addr: addr.to_string(), next_id: AtomicU64::new(1), auth_token: None }
+    }
+    
+    /// Sends a token with every call, for nodes whose privileged methods need one (see `node::access`)
+    pub fn with_auth_token(mut self, token: String) -> Self {
+        self.auth_token = Some(token);
+        self
+    }
+    
+    /// Gets the address of the node
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+    
+    /// Calls a method, returning its result
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        
+        let response = self.post(&request).map_err(|source| RpcError::Connection { addr: self.addr.clone(), source })?;
+        let response = String::from_utf8(response).map_err(|e| RpcError::InvalidResponse(e.to_string()))?;
+        
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| RpcError::InvalidResponse("truncated HTTP response".to_string()))?;
+        let status = head.lines().next().and_then(|line| line.split_once(' ')).map(|(_, status)| status).unwrap_or(head);
+        if !status.starts_with("200") {
+            return Err(RpcError::Http(status.to_string()));
+        }
+        
+        let mut body: Value = serde_json::from_str(body).map_err(|e| RpcError::InvalidResponse(e.to_string()))?;
+        if let Some(error) = body.get("error") {
+            return Err(RpcError::Response {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or("unknown error").to_string(),
+                data: error.get("data").cloned().unwrap_or_default(),
+            });
+        }
+        body.get_mut("result")
+            .map(Value::take)
+            .ok_or_else(|| RpcError::InvalidResponse("response has neither result nor error".to_string()))
+    }
+    
+    /// Sends a request body and reads the whole response
+    fn post(&self, body: &str) -> io::Result<Vec<u8>> {
+        let mut stream = TcpStream::connect(&self.addr)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        
+        let authorization = self.auth_token.as_ref().map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+            self.addr,
+            body.len(),
+            authorization,
+            body
+        )?;
+        
+        // The server closes the connection after responding
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        Ok(response)
+    }
+    
+    /// Calls a method and deserializes its result
+    fn call_as<T: serde::de::DeserializeOwned>(&self, method: &str, params: Value) -> ctb_core::Result<T> {
+        let result = self.call(method, params).map_err(chain_error)?;
+        serde_json::from_value(result).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+}
+
+impl ChainClient for RpcClient {
+    fn call_contract(
+        &self,
+        contract: &str,
+        selector: &[u8; 4],
+        arguments: &[u8],
+        sender: &str,
+    ) -> ctb_core::Result<CallOutcome> {
+        let params = json!([contract, Bytes::from(&selector[..]), Bytes::from(arguments), sender]);
+        match self.call("genx_call", params) {
+            Ok(output) => {
+                let output: Bytes = serde_json::from_value(output).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+                Ok(CallOutcome::Returned(output.into_vec()))
+            }
+            Err(RpcError::Response { code: EXECUTION_REVERTED, message, data }) => {
+                let data: Bytes = serde_json::from_value(data).unwrap_or_default();
+                let reason = message.strip_prefix(REVERT_PREFIX).map(str::to_string);
+                Ok(CallOutcome::Reverted { reason, data: data.into_vec() })
+            }
+            Err(e) => Err(chain_error(e)),
+        }
+    }
+    
+    fn get_contract_abi(&self, contract: &str) -> ctb_core::Result<Option<Vec<FunctionABI>>> {
+        self.call_as("genx_getContractAbi", json!([contract]))
+    }
+    
+    fn estimate_gas(&self, tx: &Transaction) -> ctb_core::Result<GasEstimate> {
+        let estimate: Value = self.call_as("genx_estimateGas", json!([tx]))?;
+        match estimate.get("gas").and_then(Value::as_u64) {
+            Some(gas) => Ok(GasEstimate::Gas(gas)),
+            None => Ok(GasEstimate::Reverted(estimate["reverted"].as_str().map(str::to_string))),
+        }
+    }
+    
+    fn next_base_fee(&self) -> u64 {
+        // The trait can't report failure, so an unreachable node reads as a zero fee
+        self.call_as("genx_nextBaseFee", json!([])).unwrap_or_default()
+    }
+    
+    fn fee_histogram(&self) -> ctb_core::Result<FeeHistogram> {
+        self.call_as("genx_feeHistogram", json!([]))
+    }
+    
+    fn estimate_inclusion(&self, rate: FeeRate) -> ctb_core::Result<EstimatedBlocks> {
+        self.call_as("genx_estimateInclusion", json!([rate]))
+    }
+    
+    fn get_balance(&self, address: &str) -> ctb_core::Result<u64> {
+        self.call_as("genx_getBalance", json!([address]))
+    }
+    
+    fn get_nonce(&self, address: &str) -> ctb_core::Result<u64> {
+        self.call_as("genx_getNonce", json!([address]))
+    }
+    
+    fn submit_transaction(&self, tx: &Transaction) -> ctb_core::Result<TxHash> {
+        self.call_as("genx_sendTransaction", json!([tx]))
+    }
+    
+    fn get_history(&self, address: &str, limit: usize) -> ctb_core::Result<Vec<TransactionRecord>> {
+        self.call_as("genx_getTransactionHistory", json!([address, limit]))
+    }
+    
+    fn get_history_page(&self, address: &str, request: &PageRequest) -> ctb_core::Result<Page<TransactionRecord>> {
+        self.call_as("genx_listTransactionHistory", json!([address, request]))
+    }
+    
+    fn get_transaction(&self, tx_id: &TxHash) -> ctb_core::Result<Option<TransactionRecord>> {
+        self.call_as("genx_getTransaction", json!([tx_id]))
+    }
+    
+    fn address_possibly_used(&self, address: &str) -> ctb_core::Result<bool> {
+        self.call_as("genx_addressPossiblyUsed", json!([address]))
+    }
+    
+    fn address_first_used(&self, address: &str) -> ctb_core::Result<Option<u64>> {
+        self.call_as("genx_getAddressFirstUse", json!([address]))
+    }
+    
+    fn chain_tip(&self) -> ctb_core::Result<ChainTip> {
+        // `genx_status` gives the tip's `height` and `finalized_height` among the rest
+        self.call_as("genx_status", json!([]))
+    }
+    
+    fn get_transaction_status(&self, tx_id: &TxHash, safe_confirmations: u64) -> ctb_core::Result<ConfirmationStatus> {
+        self.call_as("genx_getTransactionStatus", json!([tx_id, safe_confirmations]))
+    }
+}
+
+impl FilterSource for RpcClient {
+    fn get_filter_headers(&self, start: u64, count: u64) -> ctb_core::Result<Vec<Hash>> {
+        let headers: FilterHeaders = self.call_as("genx_getFilterHeaders", json!([start, count]))?;
+        Ok(headers.headers)
+    }
+    
+    fn get_filters(&self, start: u64, count: u64) -> ctb_core::Result<Vec<BlockFilter>> {
+        self.call_as("genx_getFilters", json!([start, count]))
+    }
+    
+    fn get_block(&self, height: u64) -> ctb_core::Result<FilteredBlock> {
+        self.call_as("genx_getBlockWithReceipts", json!([height]))
+    }
+}
+
+fn chain_error(e: RpcError) -> BlockchainError {
+    BlockchainError::StateError(format!("RPC error: {}", e))
+}
\ No newline at end of file
@@ -0,0 +1,65 @@
+//! `genx genesis`: creating a node's genesis block
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use ctb_core::genesis::create_genesis_block;
+use node::NodeConfig;
+
+use crate::args::Args;
+use crate::{CliError, Output, Result};
+
+/// Runs a `genesis` subcommand
+pub fn run(mut args: Args) -> Result<Output> {
+    match args.command().as_deref() {
+        Some("init") => init(args),
+        Some(command) => Err(CliError::Usage(format!("unknown genesis command {}", command))),
+        None => Err(CliError::Usage("genesis needs a command: init".to_string())),
+    }
+}
+
+/// `genesis init --config <file> [--force]`
+///
+/// Writes the genesis block to the data directory the configuration
+/// names, creating the directory. A configuration file that doesn't exist
+/// is created with the default configuration.
+fn init(mut args: Args) -> Result<Output> {
+    let config_path = PathBuf::from(args.required("config")?);
+    let force = args.flag("force");
+    args.finish()?;
+    
+    let config = if config_path.exists() {
+        super::load_config(&config_path)?
+    } else {
+        let config = NodeConfig::default();
+        let contents = serde_json::to_string_pretty(&config).map_err(|e| CliError::Config(e.to_string()))?;
+        fs::write(&config_path, contents)?;
+        config
+    };
+    
+    let genesis_path = super::genesis_path(&config);
+    if genesis_path.exists() && !force {
+        return Err(CliError::Config(format!(
+            "{} already exists; pass --force to overwrite it",
+            genesis_path.display()
+        )));
+    }
+    
+    let genesis = create_genesis_block()?;
+    let hash = genesis.hash()?;
+    fs::create_dir_all(&config.data_dir)?;
+    let contents = serde_json::to_string_pretty(&genesis).map_err(|e| CliError::Config(e.to_string()))?;
+    fs::write(&genesis_path, contents)?;
+    
+    Ok(Output::new(
+        format!("Wrote genesis block {} to {}", hash, genesis_path.display()),
+        json!({
+            "config": config_path,
+            "data_dir": config.data_dir,
+            "genesis": genesis_path,
+            "hash": hash,
+        }),
+    ))
+}
\ No newline at end of file
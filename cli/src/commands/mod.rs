@@ -0,0 +1,28 @@
+//! Subcommands of the `genx` command line
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ::node::NodeConfig;
+
+use crate::{CliError, Result};
+
+pub mod genesis;
+pub mod node;
+pub mod verify;
+pub mod wallet;
+
+/// Name of the genesis file in a node's data directory
+const GENESIS_FILE: &str = "genesis.json";
+
+/// Reads a node configuration file
+fn load_config(path: &Path) -> Result<NodeConfig> {
+    let config = fs::read_to_string(path)
+        .map_err(|e| CliError::Config(format!("cannot read {}: {}", path.display(), e)))?;
+    serde_json::from_str(&config).map_err(|e| CliError::Config(format!("{}: {}", path.display(), e)))
+}
+
+/// Gets the path of a node's genesis file
+fn genesis_path(config: &NodeConfig) -> PathBuf {
+    Path::new(&config.data_dir).join(GENESIS_FILE)
+}
\ No newline at end of file
@@ -0,0 +1,147 @@
+//! `genx node`: running a node and querying its status
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use ctb_core::block::Block;
+use ctb_core::chain::Blockchain;
+use node::signer::{self, SignerBackend};
+use node::Node;
+
+use crate::args::Args;
+use crate::client::{RpcClient, DEFAULT_RPC_ADDR};
+use crate::{CliError, Output, Result, SIGNER_PASSWORD_ENV};
+
+/// Runs a `node` subcommand
+pub fn run(mut args: Args) -> Result<Output> {
+    match args.command().as_deref() {
+        Some("run") => run_node(args),
+        Some("status") => status(args),
+        Some(command) => Err(CliError::Usage(format!("unknown node command {}", command))),
+        None => Err(CliError::Usage("node needs a command: run or status".to_string())),
+    }
+}
+
+/// `node run --config <file> [--repair]`
+///
+/// Boots a node from its configuration and the genesis block written by
+/// `genesis init`, then runs it until interrupted by Ctrl-C or SIGTERM. On
+/// Unix, SIGHUP reloads the configuration file (see `node::reload`).
+/// `--repair` repairs a data directory that fails its startup integrity
+/// check, as `integrity_config.auto_repair` does (see `node::integrity`).
+/// A validator whose consensus key is in a keystore unlocks it with the
+/// password in `GENX_SIGNER_PASSWORD`, or prompted for. With `dev_mode`
+/// set, the node runs a development chain from its fixed genesis block
+/// instead, needing no genesis file (see `node::dev`).
+fn run_node(mut args: Args) -> Result<Output> {
+    let config_path = PathBuf::from(args.required("config")?);
+    let repair = args.flag("repair");
+    args.finish()?;
+    
+    let config = super::load_config(&config_path)?;
+    let node_id = config.node_id.clone();
+    let blockchain = if config.dev_mode {
+        node::dev::genesis(&config.consensus_params)?
+    } else {
+        let genesis_path = super::genesis_path(&config);
+        let genesis = fs::read_to_string(&genesis_path).map_err(|e| {
+            CliError::Config(format!(
+                "cannot read {}: {}; run `genx genesis init --config {}` first",
+                genesis_path.display(),
+                e,
+                config_path.display()
+            ))
+        })?;
+        let genesis: Block = serde_json::from_str(&genesis)
+            .map_err(|e| CliError::Config(format!("{}: {}", genesis_path.display(), e)))?;
+        Blockchain::new(genesis)?
+    };
+    
+    let keystore = if config.is_validator && config.signer_config.backend == SignerBackend::Keystore {
+        let password = super::wallet::read_secret(SIGNER_PASSWORD_ENV, "Keystore password")?;
+        signer::open(&config.signer_config, None, Some(&password)).map_err(|e| CliError::Config(e.to_string()))?
+    } else {
+        None
+    };
+    
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let mut node = Node::new(config, blockchain);
+        node.set_config_path(config_path.clone());
+        if let Some(keystore) = keystore {
+            node.set_signer(keystore);
+        }
+        if repair {
+            node.repair_data_dir();
+        }
+        node.start().await?;
+        
+        while let Signal::Reload = next_signal().await? {
+            match node.reload_config(&config_path) {
+                Ok(report) if report.rejected.is_empty() => {}
+                Ok(report) => eprintln!("Restart the node to change {}", report.rejected.join(", ")),
+                Err(e) => eprintln!("Failed to reload the configuration: {}", e),
+            }
+        }
+        node.stop();
+        Ok::<_, CliError>(())
+    })?;
+    
+    Ok(Output::new(format!("Node {} stopped", node_id), json!({ "node_id": node_id, "stopped": true })))
+}
+
+/// What a signal sent to a running node asks for
+#[cfg_attr(not(unix), allow(dead_code))]
+enum Signal {
+    /// Stop the node, on Ctrl-C or SIGTERM
+    Shutdown,
+    
+    /// Reload the configuration file, on SIGHUP
+    Reload,
+}
+
+/// Waits for Ctrl-C, or SIGTERM or SIGHUP on Unix
+async fn next_signal() -> std::io::Result<Signal> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.map(|()| Signal::Shutdown),
+            _ = terminate.recv() => Ok(Signal::Shutdown),
+            _ = hangup.recv() => Ok(Signal::Reload),
+        }
+    }
+    
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.map(|()| Signal::Shutdown)
+}
+
+/// `node status [--rpc <addr>]`
+fn status(mut args: Args) -> Result<Output> {
+    let rpc = args.value("rpc").unwrap_or_else(|| DEFAULT_RPC_ADDR.to_string());
+    args.finish()?;
+    
+    let status = RpcClient::new(&rpc).call("genx_status", json!([]))?;
+    let field = |name: &str| match &status[name] {
+        Value::String(value) => value.clone(),
+        Value::Null => "none".to_string(),
+        value => value.to_string(),
+    };
+    
+    let text = format!(
+        "Node:             {}\nState:            {}\nHeight:           {}\nLatest block:     {}\nFinalized height: {}\nPeers:            {}\nChain ID:         {}",
+        field("node_id"),
+        field("state"),
+        field("height"),
+        field("latest_hash"),
+        field("finalized_height"),
+        field("peer_count"),
+        field("chain_id"),
+    );
+    Ok(Output::new(text, status))
+}
\ No newline at end of file
@@ -0,0 +1,311 @@
+//! `genx wallet`: managing a wallet file and sending from its accounts
+
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use ctb_core::paging::{self, PageRequest, MAX_PAGE_LIMIT};
+use ctb_core::signature::SignatureScheme;
+use ctb_core::units::{format_genx, Amount};
+use ctb_core::Address;
+use wallet::api::WalletApi;
+use wallet::password::PasswordPolicy;
+use wallet::{Account, AccountSort};
+
+use crate::args::Args;
+use crate::client::{RpcClient, DEFAULT_RPC_ADDR};
+use crate::{CliError, Output, Result, NEW_PASSWORD_ENV, PASSWORD_ENV, RPC_TOKEN_ENV};
+
+/// Wallet file used when `--wallet` isn't given
+const DEFAULT_WALLET_PATH: &str = "wallet.json";
+
+/// Transactions `wallet history` lists when `--limit` isn't given
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+/// Runs a `wallet` subcommand
+pub fn run(mut args: Args) -> Result<Output> {
+    let path = PathBuf::from(args.value("wallet").unwrap_or_else(|| DEFAULT_WALLET_PATH.to_string()));
+    
+    match args.command().as_deref() {
+        Some("create") => create(path, args),
+        Some("unlock") => unlock(path, args),
+        Some("change-password") => change_password(path, args),
+        Some("new-account") => new_account(path, args),
+        Some("list") => list(path, args),
+        Some("send") => send(path, args),
+        Some("balance") => balance(path, args),
+        Some("history") => history(path, args),
+        Some(command) => Err(CliError::Usage(format!("unknown wallet command {}", command))),
+        None => Err(CliError::Usage(
+            "wallet needs a command: create, unlock, change-password, new-account, list, send, balance or history".to_string(),
+        )),
+    }
+}
+
+/// `wallet create [--label <label>] [--scheme <scheme>] [--force-weak-password]`
+fn create(path: PathBuf, mut args: Args) -> Result<Output> {
+    let label = args.value("label").unwrap_or_else(|| "default".to_string());
+    let scheme = scheme(&mut args)?;
+    let policy = password_policy(&mut args);
+    args.finish()?;
+    
+    if path.exists() {
+        return Err(CliError::Usage(format!("{} already exists", path.display())));
+    }
+    
+    let api = WalletApi::create_wallet_with_policy(path.clone(), &read_password()?, &policy)?;
+    let address = api.create_account_with_scheme(&label, scheme)?;
+    
+    Ok(Output::new(
+        format!("Created wallet {} with account {}", path.display(), address),
+        json!({ "wallet": path, "address": address, "scheme": scheme }),
+    ))
+}
+
+/// `wallet unlock`: checks the password
+fn unlock(path: PathBuf, args: Args) -> Result<Output> {
+    args.finish()?;
+    
+    let api = open(&path, true)?;
+    let accounts = api.list_accounts(AccountSort::CreatedAt, &PageRequest::new(1))?.total_estimate.unwrap_or(0);
+    
+    Ok(Output::new(
+        format!("Unlocked {} ({} accounts)", path.display(), accounts),
+        json!({ "wallet": path, "unlocked": true, "accounts": accounts }),
+    ))
+}
+
+/// `wallet change-password [--force-weak-password]`
+fn change_password(path: PathBuf, mut args: Args) -> Result<Output> {
+    let policy = password_policy(&mut args);
+    args.finish()?;
+    
+    let api = WalletApi::load_wallet(path.clone())?;
+    let current = read_password()?;
+    let new = read_secret(NEW_PASSWORD_ENV, "New wallet password")?;
+    api.change_password(&current, &new, &policy)?;
+    
+    Ok(Output::new(
+        format!("Changed the password of {}", path.display()),
+        json!({ "wallet": path, "changed": true }),
+    ))
+}
+
+/// `wallet new-account [--label <label>] [--scheme <scheme>]`
+fn new_account(path: PathBuf, mut args: Args) -> Result<Output> {
+    let label = args.value("label").unwrap_or_default();
+    let scheme = scheme(&mut args)?;
+    args.finish()?;
+    
+    let api = open(&path, true)?;
+    let address = api.create_account_with_scheme(&label, scheme)?;
+    
+    Ok(Output::new(
+        format!("Created account {}", address),
+        json!({ "address": address, "scheme": scheme }),
+    ))
+}
+
+/// `wallet list`
+fn list(path: PathBuf, args: Args) -> Result<Output> {
+    args.finish()?;
+    
+    let api = open(&path, false)?;
+    let accounts = paging::collect_pages(MAX_PAGE_LIMIT, |request| api.list_accounts(AccountSort::CreatedAt, request))?;
+    
+    let text = accounts
+        .iter()
+        .map(|account| {
+            let default = if account.is_default { " (default)" } else { "" };
+            format!("{}  {}{}", account.address, account.label, default)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    
+    Ok(Output::new(
+        if accounts.is_empty() { "No accounts".to_string() } else { text },
+        Value::Array(accounts.iter().map(account_json).collect()),
+    ))
+}
+
+/// `wallet send --to <address> --amount <amount> [--fee <fee>] [--from <address>] [--rpc <addr>]`
+///
+/// The amount and fee are in GENX, such as `12.5`.
+fn send(path: PathBuf, mut args: Args) -> Result<Output> {
+    let to = args.parsed::<Address>("to")?.ok_or_else(|| CliError::Usage("missing --to".to_string()))?;
+    let amount = args.parsed::<Amount>("amount")?.ok_or_else(|| CliError::Usage("missing --amount".to_string()))?;
+    let fee = args.parsed::<Amount>("fee")?.unwrap_or_default();
+    let from = args.parsed::<Address>("from")?;
+    let client = rpc_client(&mut args);
+    args.finish()?;
+    
+    let mut api = open(&path, true)?;
+    api.set_client(Arc::new(client));
+    let from = match from {
+        Some(from) => from,
+        None => Address::new(default_address(&api)?).map_err(|e| CliError::Usage(e.to_string()))?,
+    };
+    
+    let tx = api.create_transaction(&from, &to, amount, fee, None)?;
+    let id = api.send_transaction(&tx)?;
+    
+    Ok(Output::new(
+        format!("Sent {} from {} to {}\nTransaction {}", amount, from, to, id),
+        json!({
+            "tx_id": id,
+            "from": from,
+            "to": to,
+            "amount": amount,
+            "amount_genx": format_genx(amount.base_units()),
+            "fee": fee,
+            "fee_genx": format_genx(fee.base_units()),
+        }),
+    ))
+}
+
+/// `wallet balance [--address <address>] [--rpc <addr>]`
+fn balance(path: PathBuf, mut args: Args) -> Result<Output> {
+    let address = args.value("address");
+    let client = rpc_client(&mut args);
+    args.finish()?;
+    
+    let mut api = open(&path, false)?;
+    api.set_client(Arc::new(client));
+    let address = match address {
+        Some(address) => address,
+        None => default_address(&api)?,
+    };
+    
+    let balance = api.get_balance(&address)?;
+    Ok(Output::new(
+        format!("{}: {}", address, balance),
+        json!({ "address": address, "balance": balance, "balance_genx": format_genx(balance.base_units()) }),
+    ))
+}
+
+/// `wallet history [--address <address>] [--limit <n>] [--cursor <cursor>] [--rpc <addr>]`
+///
+/// Lists a page of history, and the cursor of the next page if there's one.
+fn history(path: PathBuf, mut args: Args) -> Result<Output> {
+    let address = args.value("address");
+    let limit = args.parsed::<usize>("limit")?.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let cursor = args.parsed("cursor")?;
+    let client = rpc_client(&mut args);
+    args.finish()?;
+    
+    let mut api = open(&path, false)?;
+    api.set_client(Arc::new(client));
+    let address = match address {
+        Some(address) => address,
+        None => default_address(&api)?,
+    };
+    
+    let page = api.list_history(&address, &PageRequest::new(limit).after(cursor))?;
+    let mut text = page
+        .items
+        .iter()
+        .map(|record| {
+            let tx = &record.transaction;
+            format!(
+                "#{}  {}  {} -> {}  {}{}",
+                record.block_height,
+                tx.id,
+                tx.sender,
+                tx.recipient,
+                Amount::from_base_units(tx.amount),
+                if record.success { "" } else { "  (failed)" },
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Some(cursor) = &page.next_cursor {
+        text.push_str(&format!("\nMore with --cursor {}", cursor));
+    }
+    
+    Ok(Output::new(
+        if page.items.is_empty() { format!("No transactions for {}", address) } else { text },
+        serde_json::to_value(&page).map_err(io::Error::from)?,
+    ))
+}
+
+/// Loads a wallet, unlocking it if the command needs its keys
+///
+/// Commands that don't need the keys only read the wallet, so they open it
+/// read-only and may run while another process has it open.
+fn open(path: &Path, unlock: bool) -> Result<WalletApi> {
+    if !unlock {
+        return Ok(WalletApi::load_wallet_readonly(path.to_path_buf())?);
+    }
+    let api = WalletApi::load_wallet(path.to_path_buf())?;
+    api.unlock(&read_password()?)?;
+    Ok(api)
+}
+
+fn default_address(api: &WalletApi) -> Result<String> {
+    api.get_default_account()?
+        .map(|account| account.address)
+        .ok_or_else(|| CliError::Usage("the wallet has no default account; pass --address".to_string()))
+}
+
+/// Connects to the node given by `--rpc`, authorizing with `GENX_RPC_TOKEN` if set
+fn rpc_client(args: &mut Args) -> RpcClient {
+    let client = RpcClient::new(&args.value("rpc").unwrap_or_else(|| DEFAULT_RPC_ADDR.to_string()));
+    match std::env::var(RPC_TOKEN_ENV) {
+        Ok(token) if !token.is_empty() => client.with_auth_token(token),
+        _ => client,
+    }
+}
+
+fn scheme(args: &mut Args) -> Result<SignatureScheme> {
+    match args.value("scheme") {
+        None => Ok(SignatureScheme::default()),
+        Some(name) => SignatureScheme::ALL
+            .into_iter()
+            .find(|scheme| scheme.to_string() == name)
+            .ok_or_else(|| CliError::Usage(format!("unknown scheme {}; use ed25519 or secp256k1", name))),
+    }
+}
+
+/// Gets the policy new passwords must satisfy, none with `--force-weak-password`
+fn password_policy(args: &mut Args) -> PasswordPolicy {
+    if args.flag("force-weak-password") {
+        PasswordPolicy::none()
+    } else {
+        PasswordPolicy::default()
+    }
+}
+
+fn account_json(account: &Account) -> Value {
+    json!({
+        "address": account.address,
+        "label": account.label,
+        "scheme": SignatureScheme::from_address(&account.address),
+        "default": account.is_default,
+        "created_at": account.created_at,
+    })
+}
+
+/// Reads the wallet password from `GENX_WALLET_PASSWORD`, or prompts for it
+fn read_password() -> Result<String> {
+    read_secret(PASSWORD_ENV, "Wallet password")
+}
+
+/// Reads a password from an environment variable, or prompts for it
+pub(super) fn read_secret(env: &str, prompt: &str) -> Result<String> {
+    if let Ok(password) = std::env::var(env) {
+        return Ok(password);
+    }
+    
+    eprint!("{}: ", prompt);
+    io::stderr().flush()?;
+    
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err(CliError::Usage(format!("no password given; type one or set {}", env)));
+    }
+    Ok(password)
+}
\ No newline at end of file
@@ -0,0 +1,182 @@
+//! Command line for the GENX blockchain
+//!
+//! The `genx` binary runs a node, creates its genesis block and manages
+//! wallets:
+//!
+//! ```text
+//! genx node run --config <file> [--repair]
+//! genx node status [--rpc <addr>]
+//! genx genesis init --config <file> [--force]
+//! genx verify (--config <file> | --data-dir <dir>) [--from <height>] [--to <height>]
+//! genx wallet create [--label <label>] [--scheme <scheme>] [--force-weak-password]
+//! genx wallet unlock
+//! genx wallet change-password [--force-weak-password]
+//! genx wallet new-account [--label <label>] [--scheme <scheme>]
+//! genx wallet list
+//! genx wallet send --to <address> --amount <amount> [--fee <fee>] [--from <address>] [--rpc <addr>]
+//! genx wallet balance [--address <address>] [--rpc <addr>]
+//! genx wallet history [--address <address>] [--limit <n>] [--cursor <cursor>] [--rpc <addr>]
+//! ```
+//!
+//! Wallet commands work on the file given by `--wallet` (`wallet.json` by
+//! default) and read its password from `GENX_WALLET_PASSWORD`, prompting
+//! for it if that isn't set; a new password is read from
+//! `GENX_WALLET_NEW_PASSWORD` the same way. New passwords must satisfy the
+//! wallet's password policy (see `wallet::password`) unless
+//! `--force-weak-password` is given, which is meant for test environments.
+//! Commands that need chain state talk to a node over JSON-RPC (see
+//! `client`), sending the token in `GENX_RPC_TOKEN`, if set, to nodes that
+//! require one to submit transactions. Amounts are given and shown in GENX, such as `12.5` (see
+//! `ctb_core::units`). With `--json`, every command prints a JSON value instead
+//! of text, where amounts are in base units and each has a `_genx` twin in
+//! GENX.
+
+use std::io::{self, Write};
+
+use serde_json::Value;
+use thiserror::Error;
+
+use ctb_core::BlockchainError;
+use wallet::WalletError;
+
+pub mod args;
+pub mod client;
+mod commands;
+
+use args::Args;
+use client::RpcError;
+
+/// Environment variable wallet commands read the password from
+pub const PASSWORD_ENV: &str = "GENX_WALLET_PASSWORD";
+
+/// Environment variable `wallet change-password` reads the new password from
+pub const NEW_PASSWORD_ENV: &str = "GENX_WALLET_NEW_PASSWORD";
+
+/// Environment variable wallet commands read the node's RPC authorization token from
+pub const RPC_TOKEN_ENV: &str = "GENX_RPC_TOKEN";
+
+/// Environment variable `node run` reads the password of the validator's keystore from
+pub const SIGNER_PASSWORD_ENV: &str = "GENX_SIGNER_PASSWORD";
+
+/// Usage summary printed by `--help`
+pub const USAGE: &str = "\
+Usage: genx [--json] <command>
+
+Node:
+  node run --config <file>             Run a node until interrupted; SIGHUP reloads the config
+      [--repair]                       Repair a corrupt data directory instead of refusing to start
+  node status [--rpc <addr>]           Show the status of a running node
+  genesis init --config <file>         Write the genesis block to the node's data directory
+      [--force]                        Overwrite an existing genesis block
+  verify --config <file>               Replay and check the blocks saved in the node's data directory
+      [--data-dir <dir>]               Check a data directory instead, with the default parameters
+      [--from <height>] [--to <height>]
+                                       Check only the blocks in a range
+
+Wallet (--wallet <file>, default wallet.json):
+  wallet create                        Create a wallet and its first account
+      [--label <label>] [--scheme ed25519|secp256k1]
+      [--force-weak-password]          Accept a weak password, for test environments
+  wallet unlock                        Check the wallet password
+  wallet change-password               Change the wallet password
+      [--force-weak-password]          Accept a weak new password
+  wallet new-account                   Add an account
+      [--label <label>] [--scheme ed25519|secp256k1]
+  wallet list                          List accounts
+  wallet send --to <address> --amount <genx>
+      [--fee <genx>] [--from <address>] Send GENX from an account, the default one if not given
+  wallet balance [--address <address>] Show an account's balance
+  wallet history [--address <address>] [--limit <n>] [--cursor <cursor>]
+                                       Show an account's recent transactions
+
+Commands that query a node take --rpc <addr> (default 127.0.0.1:8545).
+The wallet password is read from GENX_WALLET_PASSWORD or prompted for,
+and a new one from GENX_WALLET_NEW_PASSWORD. Wallet commands send the
+token in GENX_RPC_TOKEN to nodes that require one.";
+
+/// Command line error
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}")]
+    Usage(String),
+    
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    
+    #[error("Wallet error: {0}")]
+    Wallet(#[from] WalletError),
+    
+    #[error("Blockchain error: {0}")]
+    Blockchain(#[from] BlockchainError),
+    
+    #[error("RPC error: {0}")]
+    Rpc(#[from] RpcError),
+    
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Result type for commands
+pub type Result<T> = std::result::Result<T, CliError>;
+
+/// What a command prints, as text and as JSON
+pub struct Output {
+    /// Text printed by default
+    pub text: String,
+    
+    /// Value printed with `--json`
+    pub json: Value,
+    
+    /// Problem the command found, which makes it fail once the output is printed
+    pub failure: Option<String>,
+}
+
+impl Output {
+    /// Creates output from its text and JSON forms
+    pub fn new(text: impl Into<String>, json: Value) -> Self {
+        Self { text: text.into(), json, failure: None }
+    }
+    
+    /// Marks the output as reporting a problem, so the command fails after printing it
+    pub fn failing(mut self, failure: impl Into<String>) -> Self {
+        self.failure = Some(failure.into());
+        self
+    }
+}
+
+/// Runs a command line, not including the program name, writing its output to `out`
+pub fn run<I, S>(args: I, out: &mut dyn Write) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut args = Args::parse(args)?;
+    let json = args.flag("json");
+    
+    if args.flag("help") {
+        writeln!(out, "{}", USAGE)?;
+        return Ok(());
+    }
+    
+    let output = match args.command().as_deref() {
+        Some("node") => commands::node::run(args)?,
+        Some("genesis") => commands::genesis::run(args)?,
+        Some("wallet") => commands::wallet::run(args)?,
+        Some("verify") => commands::verify::run(args)?,
+        Some(command) => return Err(CliError::Usage(format!("unknown command {}", command))),
+        None => return Err(CliError::Usage("no command given".to_string())),
+    };
+    
+    if json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&output.json).map_err(io::Error::from)?)?;
+    } else {
+        writeln!(out, "{}", output.text)?;
+    }
+    match output.failure {
+        Some(failure) => Err(CliError::Failed(failure)),
+        None => Ok(()),
+    }
+}
\ No newline at end of file
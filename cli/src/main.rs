@@ -0,0 +1,20 @@
+//! Entry point of the `genx` command line
+
+use std::process::ExitCode;
+
+use genx::CliError;
+
+fn main() -> ExitCode {
+    match genx::run(std::env::args().skip(1), &mut std::io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(message)) => {
+            eprintln!("genx: {}", message);
+            eprintln!("Run `genx --help` for usage.");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("genx: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,179 @@
+//! Times packing and evicting from a pool of 100,000 pending transactions
+//!
+//! Run with `cargo bench -p consensus --bench mempool`. Compares the
+//! indexed `Mempool` with the vector the consensus engine used to keep,
+//! which it sorted by gas price on every block attempt and scanned to
+//! evict. Transactions are unsigned contract calls from 10,000 senders
+//! with pseudo-random gas prices, since neither pool checks signatures.
+
+use std::cmp::Reverse;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use consensus::mempool::Mempool;
+use consensus::ConsensusParams;
+use ctb_core::ordering::OrderKey;
+use ctb_core::transaction::Transaction;
+
+/// Transactions in the pool
+const TRANSACTIONS: usize = 100_000;
+
+/// Distinct senders of the transactions
+const SENDERS: usize = 10_000;
+
+/// Most transactions packed into a block, as in the consensus engine
+const MAX_BLOCK_TRANSACTIONS: usize = 1000;
+
+/// Gas each transaction reserves
+const GAS_LIMIT: u64 = 21_000;
+
+/// Base fee of the block being packed
+const BASE_FEE: u64 = 10;
+
+/// Transactions evicted in the eviction measurement
+const EVICTIONS: usize = 1000;
+
+/// Times each measurement is repeated, keeping the fastest
+const ROUNDS: usize = 5;
+
+fn main() {
+    let transactions = synthetic_transactions(TRANSACTIONS);
+    let gas_limit = ConsensusParams::default().block_gas_limit;
+    
+    let insert = fastest(|| {
+        let mut pool = Mempool::new(TRANSACTIONS);
+        let start = Instant::now();
+        for tx in &transactions {
+            pool.insert(Arc::clone(tx)).unwrap();
+        }
+        start.elapsed()
+    });
+    
+    let mut pool = Mempool::new(TRANSACTIONS);
+    for tx in &transactions {
+        pool.insert(Arc::clone(tx)).unwrap();
+    }
+    
+    let indexed_pack = fastest(|| {
+        let mut pool = pool.clone();
+        let start = Instant::now();
+        let packed = pack_indexed(&mut pool, gas_limit);
+        let time = start.elapsed();
+        assert_eq!(packed, MAX_BLOCK_TRANSACTIONS.min((gas_limit / GAS_LIMIT) as usize));
+        time
+    });
+    let vec_pack = fastest(|| {
+        let mut pending = transactions.clone();
+        let start = Instant::now();
+        let packed = pack_vec(&mut pending, gas_limit);
+        let time = start.elapsed();
+        assert_eq!(packed, MAX_BLOCK_TRANSACTIONS.min((gas_limit / GAS_LIMIT) as usize));
+        time
+    });
+    
+    let indexed_evict = fastest(|| {
+        let mut pool = pool.clone();
+        let start = Instant::now();
+        for _ in 0..EVICTIONS {
+            pool.evict_worst().unwrap();
+        }
+        start.elapsed()
+    });
+    let vec_evict = fastest(|| {
+        let mut pending = transactions.clone();
+        let start = Instant::now();
+        for _ in 0..EVICTIONS {
+            evict_vec(&mut pending);
+        }
+        start.elapsed()
+    });
+    
+    println!("{} pending transactions from {} senders", TRANSACTIONS, SENDERS);
+    println!("  admitting all:          {:>10.3} ms", millis(insert));
+    println!("  {:<23} {:>13} {:>13}", "", "indexed (ms)", "vector (ms)");
+    println!("  {:<23} {:>13.3} {:>13.3}", "packing a block", millis(indexed_pack), millis(vec_pack));
+    println!("  {:<23} {:>13.3} {:>13.3}", format!("evicting {}", EVICTIONS), millis(indexed_evict), millis(vec_evict));
+    println!("  packing speedup:        {:>10.1}x", vec_pack.as_secs_f64() / indexed_pack.as_secs_f64());
+}
+
+/// Packs a block the way the consensus engine does
+fn pack_indexed(pool: &mut Mempool, gas_limit: u64) -> usize {
+    let mut gas_reserved = 0;
+    let mut packed = 0;
+    let mut last = None;
+    while packed < MAX_BLOCK_TRANSACTIONS {
+        let Some(tx) = pool.pop_best(gas_limit - gas_reserved, BASE_FEE, last) else {
+            break;
+        };
+        gas_reserved += tx.gas_limit;
+        last = Some(OrderKey::of(&tx));
+        packed += 1;
+    }
+    packed
+}
+
+/// Packs a block the way the consensus engine did, sorting the whole pool first
+fn pack_vec(pending: &mut Vec<Arc<Transaction>>, gas_limit: u64) -> usize {
+    pending.sort_by_key(|tx| Reverse(tx.gas_price));
+    
+    let mut packed = 0;
+    let mut gas_reserved = 0;
+    let mut full = false;
+    let mut remaining = Vec::new();
+    for tx in pending.drain(..) {
+        if tx.is_metered() && tx.gas_price < BASE_FEE {
+            remaining.push(tx);
+            continue;
+        }
+        
+        full = full || packed >= MAX_BLOCK_TRANSACTIONS || gas_reserved + tx.gas_limit > gas_limit;
+        if full {
+            remaining.push(tx);
+        } else {
+            gas_reserved += tx.gas_limit;
+            packed += 1;
+        }
+    }
+    *pending = remaining;
+    packed
+}
+
+/// Removes the lowest priced transaction from a vector pool
+fn evict_vec(pending: &mut Vec<Arc<Transaction>>) {
+    let worst = pending
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, tx)| tx.gas_price)
+        .map(|(index, _)| index)
+        .unwrap();
+    pending.remove(worst);
+}
+
+/// Creates contract calls with gas prices from a fixed pseudo-random sequence
+fn synthetic_transactions(count: usize) -> Vec<Arc<Transaction>> {
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    (0..count)
+        .map(|i| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let gas_price = 1 + seed % 1000;
+            
+            let sender = format!("GENX_BENCH_SENDER_{}", i % SENDERS);
+            let data = (i as u64).to_be_bytes().to_vec();
+            let tx = Transaction::new_contract_call(sender, "GENX_BENCH_CONTRACT".to_string(), 0, data, GAS_LIMIT, gas_price)
+                .unwrap();
+            Arc::new(tx)
+        })
+        .collect()
+}
+
+/// Runs a measurement `ROUNDS` times and returns the fastest
+fn fastest(mut run: impl FnMut() -> Duration) -> Duration {
+    (0..ROUNDS).map(|_| run()).min().unwrap()
+}
+
+/// Converts a duration to milliseconds
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
\ No newline at end of file
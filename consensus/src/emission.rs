@@ -0,0 +1,67 @@
+//! Schedule of the GENX that block rewards will mint
+//!
+//! The reward of a block depends on its height alone (see
+//! `ctb_core::rewards::block_reward`): it starts at `INITIAL_BLOCK_REWARD` and
+//! halves every `HALVING_INTERVAL` blocks, so the subsidy any future block
+//! mints is known in advance. Blocks may forfeit their reward by leaving
+//! out their coinbase transactions, so the schedule is the most that will
+//! be minted; what was actually minted is `Blockchain::emission_between`.
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::rewards::{block_reward, HALVING_INTERVAL};
+
+/// Blocks between two halvings, all minting the same reward
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmissionEra {
+    /// Halvings before the era, 0 for the first
+    pub halvings: u64,
+    
+    /// Height of the era's first block scheduled, the starting height in the first era listed
+    pub start_height: u64,
+    
+    /// Height of the era's last block
+    pub end_height: u64,
+    
+    /// Reward of each block in the era
+    pub block_reward: u64,
+    
+    /// Rewards of the era's blocks from `start_height` on
+    pub emission: u64,
+    
+    /// Rewards of all the eras listed up to this one
+    pub cumulative_emission: u64,
+}
+
+/// Gets the subsidy the blocks from `from_height` on will mint, era by era
+///
+/// Lists the era of `from_height` and the `halvings` eras after it,
+/// stopping before the first era whose blocks mint nothing.
+pub fn emission_schedule(from_height: u64, halvings: u64) -> Vec<EmissionEra> {
+    let first = from_height / HALVING_INTERVAL;
+    let mut cumulative_emission = 0u64;
+    let mut eras = Vec::new();
+    for era in first..=first.saturating_add(halvings) {
+        let start_height = era.saturating_mul(HALVING_INTERVAL).max(from_height);
+        let reward = block_reward(start_height);
+        if reward == 0 {
+            break;
+        }
+        let end_height = era.saturating_add(1).saturating_mul(HALVING_INTERVAL) - 1;
+        let emission = reward.saturating_mul(end_height - start_height + 1);
+        cumulative_emission = cumulative_emission.saturating_add(emission);
+        eras.push(EmissionEra { halvings: era, start_height, end_height, block_reward: reward, emission, cumulative_emission });
+    }
+    eras
+}
+
+/// Gets the subsidy the blocks from `from_height` on will mint, until rewards run out
+pub fn remaining_emission(from_height: u64) -> u64 {
+    emission_schedule(from_height, u64::MAX).last().map_or(0, |era| era.cumulative_emission)
+}
+
+/// Gets the height of the first block after `height` with a smaller reward, if rewards haven't run out
+pub fn next_halving_height(height: u64) -> Option<u64> {
+    let next = (height / HALVING_INTERVAL).checked_add(1)?.checked_mul(HALVING_INTERVAL)?;
+    (block_reward(height) > 0).then_some(next)
+}
\ No newline at end of file
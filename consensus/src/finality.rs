@@ -3,13 +3,23 @@
 //! This module implements the finality rules that determine when blocks
 //! are considered irreversible in the blockchain.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
 
-use core::block::Block;
-use core::{BlockchainError, Hash, Result};
+use serde::{Deserialize, Serialize};
 
+use ctb_core::block::Block;
+use ctb_core::hashing::{HashDomain, Hasher};
+use ctb_core::paging::{Page, PageRequest, SortOrder};
+use ctb_core::rlp::{self, RlpItem};
+use ctb_core::signature::{self, SignatureError, SignatureScheme};
+use ctb_core::wire::{self, Wire, WireError};
+use ctb_core::{BlockHash, BlockchainError, Bytes, Hash, Result};
+
+use crate::signer::{BlockSigner, SignerError};
 use crate::validator::Validator;
-use crate::ConsensusParams;
+use crate::{ConsensusError, ConsensusParams};
 
 /// Represents a checkpoint in the blockchain
 #[derive(Debug, Clone)]
@@ -18,7 +28,7 @@ pub struct Checkpoint {
     pub height: u64,
     
     /// Hash of the checkpoint block
-    pub block_hash: Hash,
+    pub block_hash: BlockHash,
     
     /// Validators who have voted for this checkpoint
     pub votes: HashSet<String>,
@@ -27,16 +37,136 @@ pub struct Checkpoint {
     pub finalized: bool,
 }
 
+/// A validator's signed vote for a checkpoint, as gossiped between nodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalityVote {
+    /// Height of the checkpoint block
+    pub height: u64,
+    
+    /// Hash of the checkpoint block
+    pub block_hash: BlockHash,
+    
+    /// Address of the voting validator
+    pub validator: String,
+    
+    /// Validator's signature over the vote
+    pub signature: Bytes,
+}
+
+impl FinalityVote {
+    /// Creates a vote for a checkpoint signed with a validator's consensus key
+    pub fn sign(
+        height: u64,
+        block_hash: BlockHash,
+        validator: String,
+        scheme: SignatureScheme,
+        secret_key: &[u8],
+    ) -> Result<Self> {
+        let signature = signature::sign(scheme, secret_key, &Self::signing_hash(height, &block_hash))
+            .map_err(|e| ConsensusError::ValidatorError(e.to_string()))?;
+        Ok(Self { height, block_hash, validator, signature: Bytes(signature) })
+    }
+    
+    /// Creates a vote for a checkpoint signed by a validator's signer
+    pub fn sign_with(height: u64, block_hash: BlockHash, validator: String, signer: &dyn BlockSigner) -> std::result::Result<Self, SignerError> {
+        let signature = signer.sign_finality_vote(height, &block_hash)?;
+        Ok(Self { height, block_hash, validator, signature })
+    }
+    
+    /// Gets the message a vote for a checkpoint signs the hash of: the RLP list of the height and the block hash
+    pub fn signing_message(height: u64, block_hash: &BlockHash) -> Vec<u8> {
+        rlp::encode(&RlpItem::List(vec![
+            RlpItem::uint(height as u128),
+            wire::hash(&block_hash.0),
+        ]))
+    }
+    
+    /// Gets the hash a vote for a checkpoint signs
+    ///
+    /// That's the hash of the `signing_message` in the `FinalityVote`
+    /// domain, so a vote's signature can't pass for a transaction's.
+    pub fn signing_hash(height: u64, block_hash: &BlockHash) -> Hash {
+        let mut hasher = Hasher::for_domain(HashDomain::FinalityVote);
+        hasher.update(&Self::signing_message(height, block_hash));
+        hasher.finalize()
+    }
+    
+    /// Verifies the vote's signature by the consensus key an address encodes
+    pub fn verify(&self, consensus_key: &str) -> std::result::Result<(), SignatureError> {
+        signature::verify(consensus_key, &Self::signing_hash(self.height, &self.block_hash), &self.signature)
+    }
+}
+
+impl Wire for FinalityVote {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            RlpItem::uint(self.height as u128),
+            wire::hash(&self.block_hash.0),
+            wire::string(&self.validator),
+            wire::bytes(&self.signature),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> std::result::Result<Self, WireError> {
+        let fields = wire::fields(item, 4)?;
+        Ok(Self {
+            height: fields[0].as_u64()?,
+            block_hash: BlockHash(wire::decode_hash(&fields[1])?),
+            validator: wire::decode_string(&fields[2])?,
+            signature: wire::decode_bytes(&fields[3])?,
+        })
+    }
+}
+
+/// A finalized checkpoint, compacted to the block it finalized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalizedCheckpoint {
+    /// Height of the checkpoint block
+    pub height: u64,
+    
+    /// Hash of the checkpoint block
+    pub block_hash: BlockHash,
+}
+
+/// What a finality manager holds, for monitoring its memory use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct FinalityMemoryStats {
+    /// Checkpoints still collecting votes
+    pub pending_checkpoints: usize,
+    
+    /// Finalized checkpoints kept with their votes, those within the horizon
+    pub recent_finalized: usize,
+    
+    /// Finalized checkpoints recorded, each by its height and hash
+    pub finalized_records: usize,
+    
+    /// Votes held by the pending and recent finalized checkpoints
+    pub votes: usize,
+}
+
 /// Manages the finality of blocks in the blockchain
+///
+/// Once a checkpoint is finalized, checkpoints at or below its height that
+/// weren't, on competing branches or never voted through, are dropped, and
+/// votes for them are refused from then on. Finalized checkpoints more than
+/// `finality_horizon` blocks below the latest are kept only as their height
+/// and hash, and no more than `max_pending_checkpoints` checkpoints collect
+/// votes at once, so votes for bogus heights can't grow it without bound.
 pub struct FinalityManager {
     /// Consensus parameters
     params: ConsensusParams,
     
-    /// Checkpoints indexed by height
-    checkpoints: HashMap<u64, Checkpoint>,
+    /// Pending and recent finalized checkpoints, indexed by height
+    checkpoints: BTreeMap<u64, Vec<Checkpoint>>,
+    
+    /// Hashes of the finalized checkpoint blocks, indexed by height
+    finalized: BTreeMap<u64, BlockHash>,
     
     /// The latest finalized checkpoint height
     latest_finalized_height: u64,
+    
+    /// File the finalized checkpoints are saved to, if they're saved at all
+    store: Option<PathBuf>,
 }
 
 impl FinalityManager {
@@ -44,11 +174,18 @@ impl FinalityManager {
     pub fn new(params: ConsensusParams) -> Self {
         Self {
             params,
-            checkpoints: HashMap::new(),
+            checkpoints: BTreeMap::new(),
+            finalized: BTreeMap::new(),
             latest_finalized_height: 0,
+            store: None,
         }
     }
     
+    /// Gets the consensus parameters the manager was created with
+    pub fn params(&self) -> &ConsensusParams {
+        &self.params
+    }
+    
     /// Initializes the finality manager with the genesis block
     pub fn initialize_with_genesis(&mut self, genesis_block: &Block) -> Result<()> {
         let genesis_hash = genesis_block.hash()?;
@@ -64,46 +201,113 @@ impl FinalityManager {
             finalized: true,
         };
         
-        self.checkpoints.insert(0, checkpoint);
-        self.latest_finalized_height = 0;
+        self.checkpoints.insert(0, vec![checkpoint]);
+        self.finalized.insert(0, genesis_hash);
         
         Ok(())
     }
     
+    /// Saves the finalized checkpoints to `path` from now on, first restoring those saved there
+    pub fn set_store(&mut self, path: PathBuf) -> Result<()> {
+        if path.exists() {
+            let saved: Vec<FinalizedCheckpoint> = serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+            for checkpoint in saved {
+                self.finalized.insert(checkpoint.height, checkpoint.block_hash);
+                self.latest_finalized_height = self.latest_finalized_height.max(checkpoint.height);
+            }
+            self.prune();
+        }
+        self.store = Some(path);
+        Ok(())
+    }
+    
+    /// Saves the finalized checkpoints, replacing the file atomically
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.store else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&self.finalized_checkpoints().collect::<Vec<_>>())
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+    
     /// Adds a vote for a checkpoint from a validator
-    pub fn add_checkpoint_vote(&mut self, height: u64, block_hash: Hash, validator: &Validator) -> Result<bool> {
+    ///
+    /// Votes for a block at or below the latest finalized checkpoint other
+    /// than a finalized one are refused, and so are votes opening a
+    /// checkpoint above every pending one when `max_pending_checkpoints`
+    /// are pending; one opened below makes room by dropping the highest.
+    pub fn add_checkpoint_vote(&mut self, height: u64, block_hash: BlockHash, validator: &Validator) -> Result<bool> {
         // Check if this is a valid checkpoint height
-        if height % self.params.checkpoint_interval != 0 {
-            return Err(BlockchainError::StateError(
-                format!("Invalid checkpoint height: {}", height)
-            ).into());
+        if !height.is_multiple_of(self.params.checkpoint_interval) {
+            return Err(ConsensusError::InvalidCheckpointHeight { height }.into());
         }
         
-        // Get or create the checkpoint
-        let checkpoint = self.checkpoints.entry(height).or_insert_with(|| Checkpoint {
-            height,
-            block_hash,
-            votes: HashSet::new(),
-            finalized: false,
-        });
-        
-        // Check that the block hash matches
-        if checkpoint.block_hash != block_hash {
-            return Err(BlockchainError::StateError(
-                format!("Checkpoint hash mismatch at height {}", height)
-            ).into());
-        }
+        // Get or open the checkpoint; one finalized past the horizon takes no more votes
+        let Some(checkpoint) = self.open_checkpoint(height, block_hash)? else {
+            return Ok(true);
+        };
         
         // Add the validator's vote
         checkpoint.votes.insert(validator.address.clone());
         
         // Check if the checkpoint can be finalized
-        self.try_finalize_checkpoint(height)
+        self.try_finalize_checkpoint(height, block_hash)
+    }
+    
+    /// Gets the checkpoint of a block, opening it if it's new and above the latest finalized one
+    ///
+    /// Gets nothing for a finalized block whose checkpoint was compacted.
+    fn open_checkpoint(&mut self, height: u64, block_hash: BlockHash) -> Result<Option<&mut Checkpoint>> {
+        if height <= self.latest_finalized_height {
+            if self.finalized.get(&height) != Some(&block_hash) {
+                return Err(ConsensusError::CheckpointMismatch { height }.into());
+            }
+            return Ok(self.checkpoint_mut(height, &block_hash));
+        }
+        
+        if self.checkpoint_mut(height, &block_hash).is_none() {
+            let pending = self.checkpoints.range(self.latest_finalized_height + 1..).map(|(_, bucket)| bucket.len()).sum::<usize>();
+            if pending >= self.params.max_pending_checkpoints {
+                match self.checkpoints.last_entry() {
+                    Some(mut highest) if *highest.key() > height => {
+                        highest.get_mut().pop();
+                        if highest.get().is_empty() {
+                            highest.remove();
+                        }
+                    }
+                    _ => return Err(ConsensusError::TooManyCheckpoints { height }.into()),
+                }
+            }
+        }
+        
+        let bucket = self.checkpoints.entry(height).or_default();
+        let index = match bucket.iter().position(|cp| cp.block_hash == block_hash) {
+            Some(index) => index,
+            None => {
+                bucket.push(Checkpoint {
+                    height,
+                    block_hash,
+                    votes: HashSet::new(),
+                    finalized: false,
+                });
+                bucket.len() - 1
+            }
+        };
+        Ok(Some(&mut bucket[index]))
     }
     
     /// Tries to finalize a checkpoint if it has enough votes
-    fn try_finalize_checkpoint(&mut self, height: u64) -> Result<bool> {
-        let checkpoint = match self.checkpoints.get_mut(&height) {
+    fn try_finalize_checkpoint(&mut self, height: u64, block_hash: BlockHash) -> Result<bool> {
+        let checkpoint = match self.checkpoint_mut(height, &block_hash) {
             Some(cp) => cp,
             None => return Ok(false),
         };
@@ -113,27 +317,38 @@ impl FinalityManager {
             return Ok(true);
         }
         
-        // Calculate the total stake of validators who voted
-        let total_stake = 0; // In a real implementation, we would sum the stake of all validators
-        
-        // Calculate the threshold stake required for finality
-        let threshold_stake = 0; // In a real implementation, this would be a percentage of total stake
-        
         // Check if we have enough votes for finality
         if checkpoint.votes.len() >= 2 { // Simplified for now, should use stake-weighted voting
             checkpoint.finalized = true;
+            self.finalized.insert(height, block_hash);
             
             // Update the latest finalized height if this is newer
             if height > self.latest_finalized_height {
                 self.latest_finalized_height = height;
             }
             
+            self.prune();
+            if let Err(e) = self.save() {
+                log::error!("Failed to save the finalized checkpoints: {}", e);
+            }
             return Ok(true);
         }
         
         Ok(false)
     }
     
+    /// Drops the checkpoints finalization left behind, and the votes of finalized ones past the horizon
+    fn prune(&mut self) {
+        let finalized_height = self.latest_finalized_height;
+        let horizon = finalized_height.saturating_sub(self.params.finality_horizon);
+        self.checkpoints.retain(|&height, bucket| {
+            if height <= finalized_height {
+                bucket.retain(|checkpoint| checkpoint.finalized && height >= horizon);
+            }
+            !bucket.is_empty()
+        });
+    }
+    
     /// Gets the latest finalized checkpoint height
     pub fn get_latest_finalized_height(&self) -> u64 {
         self.latest_finalized_height
@@ -145,29 +360,77 @@ impl FinalityManager {
         height <= self.latest_finalized_height
     }
     
-    /// Gets all checkpoints
-    pub fn get_checkpoints(&self) -> &HashMap<u64, Checkpoint> {
-        &self.checkpoints
+    /// Gets the pending and recent finalized checkpoints, lowest first
+    #[deprecated(note = "use `list_checkpoints` to page through them")]
+    pub fn get_checkpoints(&self) -> impl Iterator<Item = &Checkpoint> {
+        self.checkpoints.values().flatten()
+    }
+    
+    /// Gets a page of the pending and recent finalized checkpoints, lowest first by default
+    ///
+    /// Checkpoints are keyed by height and block hash, since competing
+    /// blocks at a height each have their own.
+    pub fn list_checkpoints(&self, request: &PageRequest) -> Result<Page<&Checkpoint>> {
+        const LISTING: &str = "checkpoints";
+        let order = request.order_or(SortOrder::Ascending);
+        let after: Option<(u64, BlockHash)> = request.start_after(LISTING)?;
+        let range = match (order, &after) {
+            (SortOrder::Ascending, Some((height, _))) => self.checkpoints.range(height..),
+            (SortOrder::Descending, Some((height, _))) => self.checkpoints.range(..=height),
+            (_, None) => self.checkpoints.range(..),
+        };
+        let mut entries: Vec<((u64, BlockHash), &Checkpoint)> = range
+            .flat_map(|(&height, checkpoints)| checkpoints.iter().map(move |checkpoint| ((height, checkpoint.block_hash), checkpoint)))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| match order {
+            SortOrder::Ascending => a.cmp(b),
+            SortOrder::Descending => b.cmp(a),
+        });
+        let entries = entries.into_iter().filter(|(key, _)| match (&after, order) {
+            (None, _) => true,
+            (Some(after), SortOrder::Ascending) => key > after,
+            (Some(after), SortOrder::Descending) => key < after,
+        });
+        Ok(Page::from_sorted(entries, LISTING, request.page_limit(), None))
+    }
+    
+    /// Gets the checkpoint of a block, if it's pending or recently finalized
+    pub fn get_checkpoint(&self, height: u64, block_hash: &BlockHash) -> Option<&Checkpoint> {
+        self.checkpoints.get(&height)?.iter().find(|checkpoint| checkpoint.block_hash == *block_hash)
+    }
+    
+    fn checkpoint_mut(&mut self, height: u64, block_hash: &BlockHash) -> Option<&mut Checkpoint> {
+        self.checkpoints.get_mut(&height)?.iter_mut().find(|checkpoint| checkpoint.block_hash == *block_hash)
+    }
+    
+    /// Gets every finalized checkpoint, lowest first
+    pub fn finalized_checkpoints(&self) -> impl Iterator<Item = FinalizedCheckpoint> + '_ {
+        self.finalized.iter().map(|(&height, &block_hash)| FinalizedCheckpoint { height, block_hash })
+    }
+    
+    /// Counts what the manager holds
+    pub fn memory_stats(&self) -> FinalityMemoryStats {
+        let mut stats = FinalityMemoryStats { finalized_records: self.finalized.len(), ..Default::default() };
+        for checkpoint in self.checkpoints.values().flatten() {
+            if checkpoint.finalized {
+                stats.recent_finalized += 1;
+            } else {
+                stats.pending_checkpoints += 1;
+            }
+            stats.votes += checkpoint.votes.len();
+        }
+        stats
     }
     
     /// Creates a new checkpoint at the given height
-    pub fn create_checkpoint(&mut self, height: u64, block_hash: Hash) -> Result<()> {
+    pub fn create_checkpoint(&mut self, height: u64, block_hash: BlockHash) -> Result<()> {
         // Check if this is a valid checkpoint height
-        if height % self.params.checkpoint_interval != 0 {
-            return Err(BlockchainError::StateError(
-                format!("Invalid checkpoint height: {}", height)
-            ).into());
+        if !height.is_multiple_of(self.params.checkpoint_interval) {
+            return Err(ConsensusError::InvalidCheckpointHeight { height }.into());
         }
         
         // Create the checkpoint
-        let checkpoint = Checkpoint {
-            height,
-            block_hash,
-            votes: HashSet::new(),
-            finalized: false,
-        };
-        
-        self.checkpoints.insert(height, checkpoint);
+        self.open_checkpoint(height, block_hash)?;
         
         Ok(())
     }
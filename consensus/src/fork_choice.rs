@@ -0,0 +1,120 @@
+//! Stake-weighted fork choice
+//!
+//! Under proof of stake a branch can be made long without much stake, so
+//! competing branches are compared by weight rather than length: each
+//! block weighs as much as the stake of the validator that produced it,
+//! and a branch as much as its blocks above the fork. A block only weighs
+//! anything if the validator its header names is in the active set of the
+//! block's epoch, as `ValidatorSelection` chooses it, and signed the header
+//! with its consensus key; naming a heavy validator lends a forged block
+//! nothing. Blocks below the
+//! fork are shared, so this orders branches as summing from the last
+//! finalized checkpoint would. The heavier branch wins, and of two as heavy
+//! the one whose tip has the lower hash.
+//!
+//! Stakes are those in the state at the fork, the last one both branches
+//! share, standing in for the snapshot the epoch started with: the same
+//! for both, so neither can gain weight by staking in its own blocks.
+//! Finalized blocks are never replaced, however heavy the branch.
+//!
+//! The chain's advertised weight sums its blocks' weights the same way,
+//! each with the stakes in the state before it.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use ctb_core::block::Block;
+use ctb_core::chain::Blockchain;
+use ctb_core::fork_choice::ForkChoiceRule;
+use ctb_core::state::State;
+use ctb_core::validator::epoch_of;
+use ctb_core::validator_set::{ValidatorSelection, ValidatorSet};
+use ctb_core::{BlockHash, Result};
+
+use crate::finality::FinalityManager;
+use crate::ConsensusError;
+
+/// Weight of a branch and the hash of its tip, which breaks ties
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchWeight {
+    /// Sum of the stakes of the validators that produced the branch's blocks
+    pub weight: u128,
+    
+    /// Hash of the branch's last block
+    pub tip_hash: BlockHash,
+}
+
+impl BranchWeight {
+    /// Weighs blocks with the stakes in a state of the validators `selection` makes active
+    pub fn of<'a>(stakes: &State, selection: &ValidatorSelection, blocks: impl IntoIterator<Item = &'a Block>) -> Result<Self> {
+        let mut weight = 0u128;
+        let mut tip_hash = BlockHash::default();
+        let mut active: Option<ValidatorSet> = None;
+        for block in blocks {
+            // Chosen once per epoch the blocks span
+            let epoch = epoch_of(block.header().height);
+            if active.as_ref().is_none_or(|set| set.epoch != epoch) {
+                active = Some(selection.select(stakes, epoch));
+            }
+            weight += active.as_ref().map_or(0, |set| producer_stake(set, block));
+            tip_hash = block.hash()?;
+        }
+        Ok(Self { weight, tip_hash })
+    }
+    
+    /// Checks whether this branch is preferred to another: heavier, or as heavy with a lower tip hash
+    pub fn beats(&self, other: &BranchWeight) -> bool {
+        (self.weight, std::cmp::Reverse(self.tip_hash)) > (other.weight, std::cmp::Reverse(other.tip_hash))
+    }
+}
+
+/// Gets the stake of the active validator that produced a block, or nothing if
+/// the validator the header names isn't active or didn't sign it
+fn producer_stake(active: &ValidatorSet, block: &Block) -> u128 {
+    let header = block.header();
+    match active.member(&header.validator) {
+        Some(member) if header.verify_signature(&member.consensus_key).is_ok() => u128::from(member.stake),
+        _ => 0,
+    }
+}
+
+/// Fork choice by the stake behind each branch, never reverting finalized blocks
+pub struct ForkChoice {
+    finality: Arc<Mutex<FinalityManager>>,
+    
+    /// How each epoch's active validators are chosen, from the finality manager's parameters
+    selection: ValidatorSelection,
+}
+
+impl ForkChoice {
+    /// Creates a fork choice respecting the checkpoints finalized by `finality`
+    pub fn new(finality: Arc<Mutex<FinalityManager>>) -> Self {
+        let selection = finality.lock().unwrap().params().validator_selection();
+        Self { finality, selection }
+    }
+}
+
+impl ForkChoiceRule for ForkChoice {
+    fn prefers(&self, chain: &Blockchain, fork_height: u64, branch: &[Arc<Block>]) -> Result<bool> {
+        let finalized_height = self.finality.lock().unwrap().get_latest_finalized_height();
+        if fork_height < finalized_height {
+            return Err(ConsensusError::RevertsFinalized { fork_height, finalized_height }.into());
+        }
+        
+        let stakes = chain.state_at(fork_height)?;
+        let current = (fork_height + 1..=chain.get_latest_height()).filter_map(|height| chain.get_block_by_height(height));
+        let current = BranchWeight::of(&stakes.state, &self.selection, current)?;
+        let candidate = BranchWeight::of(&stakes.state, &self.selection, branch.iter().map(Arc::as_ref))?;
+        Ok(candidate.beats(&current))
+    }
+    
+    fn block_weight(&self, state: &State, block: &Block) -> u128 {
+        producer_stake(&self.selection.select(state, epoch_of(block.header().height)), block)
+    }
+}
+
+impl fmt::Debug for ForkChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForkChoice").finish_non_exhaustive()
+    }
+}
\ No newline at end of file
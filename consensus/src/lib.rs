@@ -3,24 +3,50 @@
 //! This module implements a Proof of Stake (PoS) consensus mechanism
 //! for validator selection, block production, and finality.
 
-use std::collections::{HashMap, HashSet};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
 
-use core::block::Block;
-use core::chain::Blockchain;
-use core::transaction::Transaction;
-use core::{BlockchainError, Result};
-use rand::{Rng, SeedableRng};
-use rand::rngs::StdRng;
+use ctb_core::block::Block;
+use ctb_core::fee_market::{self, BlockSpace, EstimatedBlocks, FeeRate};
+use ctb_core::chain::Blockchain;
+use ctb_core::governance::GovernedParameter;
+use ctb_core::ordering::OrderKey;
+use ctb_core::state::State;
+use ctb_core::transaction::{Transaction, TransactionType, MAX_DATA_SIZE, MAX_DEPLOY_DATA_SIZE};
+use ctb_core::units::{Amount, GENX};
+use ctb_core::validator::epoch_of;
+use ctb_core::validator_set::{self as committed, ValidatorSelection};
+use ctb_core::{Address, BlockchainError, ErrorContext, Result, WithContext};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub mod pos;
 pub mod validator;
+pub mod emission;
 pub mod finality;
+pub mod fork_choice;
+pub mod mempool;
+pub mod signer;
+pub mod slots;
+pub mod uptime;
+pub mod validator_set;
+
+pub use emission::emission_schedule;
+
+use finality::FinalityVote;
+use mempool::{Mempool, MempoolError};
+use signer::{BlockSigner, SignerError};
+use slots::SlotClock;
+
+/// Most transactions a block includes besides its coinbase
+pub const MAX_BLOCK_TRANSACTIONS: u64 = 1000;
 
 /// Consensus error types
+///
+/// Numbered from 2000, see `error_code`. Where the engine's API returns a
+/// `BlockchainError`, these convert into `BlockchainError::Consensus`,
+/// keeping their code.
 #[derive(Debug, Error)]
 pub enum ConsensusError {
     #[error("Blockchain error: {0}")]
@@ -32,8 +58,69 @@ pub enum ConsensusError {
     #[error("Consensus timeout: {0}")]
     Timeout(String),
     
-    #[error("Insufficient stake: {0}")]
-    InsufficientStake(String),
+    #[error("Insufficient stake of {address}: {available} < {required}")]
+    InsufficientStake { address: String, required: u64, available: u64 },
+    
+    #[error("No active validators")]
+    NoActiveValidators,
+    
+    #[error("Invalid checkpoint height: {height}")]
+    InvalidCheckpointHeight { height: u64 },
+    
+    #[error("Checkpoint hash mismatch at height {height}")]
+    CheckpointMismatch { height: u64 },
+    
+    #[error("Branch forking off after height {fork_height} would revert blocks finalized up to height {finalized_height}")]
+    RevertsFinalized { fork_height: u64, finalized_height: u64 },
+    
+    #[error("Mempool error: {0}")]
+    MempoolError(#[from] MempoolError),
+    
+    #[error("No validator set committed for epoch {epoch}")]
+    UnknownValidatorSet { epoch: u64 },
+    
+    #[error("Invalid vote signature from {validator}")]
+    InvalidVoteSignature { validator: String },
+    
+    #[error("Invalid validator set proof: {0}")]
+    InvalidValidatorSetProof(String),
+    
+    #[error("Too many pending checkpoints to open one at height {height}")]
+    TooManyCheckpoints { height: u64 },
+    
+    #[error("Signer error: {0}")]
+    SignerError(#[from] SignerError),
+}
+
+impl ConsensusError {
+    /// Gets the stable numeric code of the error; wrapped errors keep theirs
+    pub fn error_code(&self) -> u32 {
+        match self {
+            ConsensusError::BlockchainError(e) => e.error_code(),
+            ConsensusError::ValidatorError(_) => 2000,
+            ConsensusError::Timeout(_) => 2001,
+            ConsensusError::InsufficientStake { .. } => 2002,
+            ConsensusError::NoActiveValidators => 2003,
+            ConsensusError::InvalidCheckpointHeight { .. } => 2004,
+            ConsensusError::CheckpointMismatch { .. } => 2005,
+            ConsensusError::RevertsFinalized { .. } => 2006,
+            ConsensusError::MempoolError(e) => e.error_code(),
+            ConsensusError::UnknownValidatorSet { .. } => 2007,
+            ConsensusError::InvalidVoteSignature { .. } => 2008,
+            ConsensusError::InvalidValidatorSetProof(_) => 2009,
+            ConsensusError::TooManyCheckpoints { .. } => 2010,
+            ConsensusError::SignerError(e) => e.error_code(),
+        }
+    }
+}
+
+impl From<ConsensusError> for BlockchainError {
+    fn from(e: ConsensusError) -> Self {
+        match e {
+            ConsensusError::BlockchainError(e) => e,
+            e => BlockchainError::Consensus { code: e.error_code(), message: e.to_string() },
+        }
+    }
 }
 
 /// Consensus parameters for the PoS mechanism
@@ -54,23 +141,107 @@ pub struct ConsensusParams {
     /// Percentage of validators required for finality
     pub finality_threshold: f64,
     
+    /// Blocks below the latest finalized checkpoint within which finalized
+    /// checkpoints keep their votes; older ones keep only their hash
+    pub finality_horizon: u64,
+    
+    /// Most checkpoints collecting votes at once
+    pub max_pending_checkpoints: usize,
+    
     /// Slashing percentage for malicious behavior
     pub slashing_percentage: f64,
+    
+    /// Maximum total gas the transactions of a block may consume
+    pub block_gas_limit: u64,
+    
+    /// Largest data of a transaction admitted to the mempool, other than a
+    /// contract deployment; only stricter than `MAX_DATA_SIZE` has effect
+    pub max_relay_data_size: usize,
+    
+    /// Largest data of a contract deployment admitted to the mempool; only
+    /// stricter than `MAX_DEPLOY_DATA_SIZE` has effect
+    pub max_relay_deploy_data_size: usize,
+    
+    /// Most transactions the mempool holds at once
+    pub mempool_capacity: usize,
+    
+    /// Height from which blocks must order their transactions canonically,
+    /// see `ctb_core::ordering`
+    pub transaction_ordering_height: Option<u64>,
+    
+    /// Height from which the first block of each epoch commits to its
+    /// validator set, see `ctb_core::validator_set`
+    pub validator_set_height: Option<u64>,
 }
 
 impl Default for ConsensusParams {
     fn default() -> Self {
         Self {
-            min_stake: 1000 * 100_000_000, // 1000 GENX
+            min_stake: 1000 * GENX,
             block_time: 5, // 5 seconds
             validator_set_size: 21,
             checkpoint_interval: 100,
             finality_threshold: 0.67, // 2/3 majority
+            finality_horizon: 1000,
+            max_pending_checkpoints: 64,
             slashing_percentage: 0.10, // 10% slashing
+            block_gas_limit: ctb_core::genesis::get_block_gas_limit(),
+            max_relay_data_size: MAX_DATA_SIZE,
+            max_relay_deploy_data_size: MAX_DEPLOY_DATA_SIZE,
+            mempool_capacity: mempool::DEFAULT_CAPACITY,
+            transaction_ordering_height: ctb_core::genesis::get_transaction_ordering_height(),
+            validator_set_height: ctb_core::genesis::get_validator_set_height(),
         }
     }
 }
 
+impl ConsensusParams {
+    /// Gets the limits the mempool starts with
+    pub fn mempool_limits(&self) -> MempoolLimits {
+        MempoolLimits {
+            capacity: self.mempool_capacity,
+            max_relay_data_size: self.max_relay_data_size,
+            max_relay_deploy_data_size: self.max_relay_deploy_data_size,
+        }
+    }
+    
+    /// Gets how the validators of an epoch are chosen
+    pub fn validator_selection(&self) -> ValidatorSelection {
+        ValidatorSelection { min_stake: self.min_stake, set_size: self.validator_set_size }
+    }
+    
+    /// Gets the parameters in effect for the block at a height, with the
+    /// values governance set in place of the configured ones, see `ctb_core::governance`
+    pub fn governed(&self, state: &State, height: u64) -> Self {
+        let selection = self.validator_selection().governed(state, height);
+        Self {
+            min_stake: selection.min_stake,
+            validator_set_size: selection.set_size,
+            block_time: state.governed_value(GovernedParameter::BlockTime, height).unwrap_or(self.block_time),
+            ..self.clone()
+        }
+    }
+    
+    /// Gets the clock numbering a chain's slots, with the block time as governed for its next block
+    pub fn slot_clock(&self, blockchain: &Blockchain) -> SlotClock {
+        let genesis_time = blockchain.get_block_by_height(0).map_or(0, |genesis| genesis.header().timestamp);
+        governed_clock(SlotClock::new(genesis_time, self.block_time), self, blockchain)
+    }
+}
+
+/// Limits on the transactions the mempool admits, which can change while the node runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolLimits {
+    /// Most transactions held at once
+    pub capacity: usize,
+    
+    /// See `ConsensusParams::max_relay_data_size`
+    pub max_relay_data_size: usize,
+    
+    /// See `ConsensusParams::max_relay_deploy_data_size`
+    pub max_relay_deploy_data_size: usize,
+}
+
 /// Manages the consensus process for the blockchain
 pub struct ConsensusEngine {
     /// Reference to the blockchain
@@ -82,22 +253,47 @@ pub struct ConsensusEngine {
     /// Current active validators
     active_validators: Vec<validator::Validator>,
     
-    /// Pending transactions (mempool)
-    pending_transactions: Vec<Transaction>,
+    /// Pending transactions
+    mempool: Mempool,
+    
+    /// Slots of the chain, numbered from its genesis block
+    clock: SlotClock,
     
-    /// Last block production time
-    last_block_time: Instant,
+    /// Address of the validator this node produces blocks for, if only one
+    local_validator: Option<String>,
+    
+    /// Votes for the blocks committing to validator sets, indexed by epoch
+    validator_set_votes: HashMap<u64, Vec<FinalityVote>>,
+    
+    /// Signer of the blocks produced, with the consensus key of the local validator
+    signer: Option<Arc<dyn BlockSigner>>,
 }
 
 impl ConsensusEngine {
     /// Creates a new consensus engine with the given blockchain and parameters
     pub fn new(blockchain: Arc<Mutex<Blockchain>>, params: ConsensusParams) -> Self {
+        let clock = {
+            let mut chain = blockchain.lock().unwrap();
+            
+            // Blocks from other validators are held to the same gas limit, ordering and validator sets
+            chain.set_block_gas_limit(params.block_gas_limit);
+            chain.set_transaction_ordering_height(params.transaction_ordering_height);
+            chain.set_validator_set_height(params.validator_set_height);
+            chain.set_validator_selection(params.validator_selection());
+            
+            let genesis_time = chain.get_block_by_height(0).map_or(0, |genesis| genesis.header().timestamp);
+            SlotClock::new(genesis_time, params.block_time)
+        };
+        
         Self {
             blockchain,
+            mempool: Mempool::new(params.mempool_capacity),
             params,
             active_validators: Vec::new(),
-            pending_transactions: Vec::new(),
-            last_block_time: Instant::now(),
+            clock,
+            local_validator: None,
+            validator_set_votes: HashMap::new(),
+            signer: None,
         }
     }
     
@@ -106,34 +302,52 @@ impl ConsensusEngine {
         // Update the active validator set
         self.update_validator_set()?;
         
-        // Initialize the last block time
-        self.last_block_time = Instant::now();
-        
         Ok(())
     }
     
+    /// Only produces blocks in the slots of the given validator from now on
+    ///
+    /// Without a local validator, blocks are produced for whichever
+    /// validator proposes each slot.
+    pub fn set_local_validator(&mut self, address: String) {
+        self.local_validator = Some(address);
+    }
+    
+    /// Signs the blocks produced from now on with a signer, see `signer`
+    ///
+    /// Without one, blocks are produced unsigned.
+    pub fn set_signer(&mut self, signer: Arc<dyn BlockSigner>) {
+        self.signer = Some(signer);
+    }
+    
+    /// Gets the signer of the blocks produced, which also signs the local validator's finality votes
+    pub fn signer(&self) -> Option<Arc<dyn BlockSigner>> {
+        self.signer.clone()
+    }
+    
+    /// Gets the clock numbering the chain's slots
+    pub fn slot_clock(&self) -> SlotClock {
+        self.clock
+    }
+    
     /// Updates the active validator set based on stake
     pub fn update_validator_set(&mut self) -> Result<()> {
         let blockchain = self.blockchain.lock().unwrap();
         let state = blockchain.get_state();
         let state = state.lock().unwrap();
+        let params = self.params.governed(&state, blockchain.get_latest_height() + 1);
         
-        // Get all validators and their stakes
-        let validators = state.get_validators();
+        // Get the registered validators and their stakes
+        let mut validators = state.get_validators();
         
         // Sort validators by stake (descending)
-        let mut sorted_validators: Vec<_> = validators.iter().collect();
-        sorted_validators.sort_by(|a, b| b.1.cmp(a.1));
+        validators.sort_by_key(|(_, stake)| Reverse(*stake));
         
         // Select the top validators based on stake
         let mut active_validators = Vec::new();
-        for (address, stake) in sorted_validators {
-            if *stake >= self.params.min_stake && active_validators.len() < self.params.validator_set_size {
-                active_validators.push(validator::Validator {
-                    address: address.clone(),
-                    stake: *stake,
-                    last_block_produced: 0,
-                });
+        for (info, stake) in validators {
+            if stake >= params.min_stake && active_validators.len() < params.validator_set_size {
+                active_validators.push(validator::Validator::from_registry(info, stake));
             }
         }
         
@@ -142,117 +356,326 @@ impl ConsensusEngine {
         Ok(())
     }
     
-    /// Selects the next validator to produce a block
-    pub fn select_next_validator(&self) -> Result<&validator::Validator> {
-        if self.active_validators.is_empty() {
-            return Err(ConsensusError::ValidatorError("No active validators".to_string()).into());
-        }
-        
-        // Get the latest block height
+    /// Gets the active validators, by stake
+    pub fn active_validators(&self) -> &[validator::Validator] {
+        &self.active_validators
+    }
+    
+    /// Gets an active validator by its address
+    pub fn active_validator(&self, address: &str) -> Option<&validator::Validator> {
+        self.active_validators.iter().find(|validator| validator.address == address)
+    }
+    
+    /// Selects the validator that proposes the block of a slot
+    pub fn proposer_for_slot(&self, slot: u64) -> Result<&validator::Validator> {
+        slots::select_proposer(&self.active_validators, slot)
+            .ok_or_else(|| ConsensusError::NoActiveValidators.into())
+    }
+    
+    /// Records a finality vote for a block committing to an epoch's validator set
+    ///
+    /// The vote must be for the epoch's first block as the chain has it,
+    /// from a validator of the previous epoch's committed set and signed by
+    /// its consensus key; it's kept for `validator_set_proof`. Returns
+    /// whether it's new.
+    pub fn add_validator_set_vote(&mut self, vote: FinalityVote) -> Result<bool> {
+        let epoch = epoch_of(vote.height);
         let blockchain = self.blockchain.lock().unwrap();
-        let latest_block = blockchain.get_latest_block().ok_or_else(|| {
-            BlockchainError::StateError("No blocks in the chain".to_string())
+        let (Some(_), Some(block), Some(prev_set)) = (
+            blockchain.get_validator_set(epoch),
+            blockchain.get_block_by_height(vote.height).filter(|_| vote.height == committed::first_height(epoch)),
+            epoch.checked_sub(1).and_then(|prev| blockchain.get_validator_set(prev)),
+        ) else {
+            return Err(ConsensusError::UnknownValidatorSet { epoch }.into());
+        };
+        if block.hash()? != vote.block_hash {
+            return Err(ConsensusError::CheckpointMismatch { height: vote.height }.into());
+        }
+        let member = prev_set.member(&vote.validator).ok_or_else(|| {
+            ConsensusError::ValidatorError(format!("{} isn't a validator of epoch {}", vote.validator, prev_set.epoch))
         })?;
+        vote.verify(&member.consensus_key)
+            .map_err(|_| ConsensusError::InvalidVoteSignature { validator: vote.validator.clone() })?;
         
-        let height = latest_block.header.height;
-        
-        // Use a deterministic random selection weighted by stake
-        let seed = height.to_le_bytes();
-        let mut rng = StdRng::from_seed([0u8; 32]); // Use the seed properly in a real implementation
-        
-        // Calculate total stake of active validators
-        let total_stake: u64 = self.active_validators.iter().map(|v| v.stake).sum();
-        
-        // Select a validator based on stake weight
-        let selection_point = rng.gen_range(0..total_stake);
-        let mut cumulative_stake = 0;
+        let votes = self.validator_set_votes.entry(epoch).or_default();
+        votes.retain(|recorded| recorded.block_hash == vote.block_hash);
+        if votes.iter().any(|recorded| recorded.validator == vote.validator) {
+            return Ok(false);
+        }
+        votes.push(vote);
+        Ok(true)
+    }
+    
+    /// Gets the proof of an epoch's validator set for light clients, see `validator_set`
+    ///
+    /// The proof holds every vote recorded for the epoch's first block with
+    /// `add_validator_set_vote`, which may not yet be enough for it to verify.
+    pub fn validator_set_proof(&self, epoch: u64) -> Result<validator_set::ValidatorSetProof> {
+        let blockchain = self.blockchain.lock().unwrap();
+        let (Some(set), Some(block)) = (
+            blockchain.get_validator_set(epoch),
+            blockchain.get_block_by_height(committed::first_height(epoch)),
+        ) else {
+            return Err(ConsensusError::UnknownValidatorSet { epoch }.into());
+        };
+        let block_hash = block.hash()?;
+        let votes = self.validator_set_votes.get(&epoch).map_or(&[][..], Vec::as_slice);
+        Ok(validator_set::ValidatorSetProof {
+            validator_set: (*set).clone(),
+            header: block.header().clone(),
+            votes: votes.iter().filter(|vote| vote.block_hash == block_hash).cloned().collect(),
+        })
+    }
+    
+    /// Adds a transaction to the pending pool
+    ///
+    /// Fails if the transaction is already pending, could never fit in a
+    /// block, carries more data than this node relays, is denied by the
+    /// execution policy in the next block (see `ctb_core::execution_policy`),
+    /// has a nonce its sender already used, costs more than its sender's
+    /// confirmed balance leaves after the
+    /// sender's pending transactions and the deposit it must be able to lock
+    /// (see `DepositRates::hold`), or pays too little to displace any when
+    /// the pool is full.
+    pub fn add_transaction(&mut self, transaction: impl Into<Arc<Transaction>>) -> Result<()> {
+        let transaction = transaction.into();
+        if transaction.gas_limit > self.params.block_gas_limit {
+            return Err(BlockchainError::BlockGasExceeded { gas: transaction.gas_limit, limit: self.params.block_gas_limit });
+        }
         
-        for validator in &self.active_validators {
-            cumulative_stake += validator.stake;
-            if cumulative_stake > selection_point {
-                return Ok(validator);
-            }
+        let relay_limit = if transaction.tx_type == TransactionType::ContractDeploy {
+            self.params.max_relay_deploy_data_size
+        } else {
+            self.params.max_relay_data_size
+        };
+        let data_limit = relay_limit.min(transaction.max_data_size());
+        if transaction.data_len() > data_limit {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Transaction data of {} bytes exceeds the relay limit of {} bytes",
+                transaction.data_len(),
+                data_limit
+            )));
         }
         
-        // Fallback to the first validator (should never happen)
-        Ok(&self.active_validators[0])
+        let hold = ctb_core::genesis::get_storage_deposit_rates().hold(&transaction);
+        let sender = Address::new(transaction.sender.as_str()).map_err(BlockchainError::from)?;
+        let balance = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let state = blockchain.get_state();
+            state.lock().unwrap().check_execution_policy(&transaction, blockchain.get_latest_height() + 1)?;
+            let next_nonce = blockchain.get_nonce(&sender)?;
+            if transaction.nonce < next_nonce {
+                return Err(BlockchainError::InvalidNonce {
+                    address: sender.into_string(),
+                    expected: next_nonce,
+                    got: transaction.nonce,
+                });
+            }
+            blockchain.get_balance(&sender)?.base_units().saturating_sub(hold)
+        };
+        self.mempool.insert_funded(transaction, balance).map_err(ConsensusError::from)?;
+        Ok(())
     }
     
-    /// Adds a transaction to the pending pool
-    pub fn add_transaction(&mut self, transaction: Transaction) {
-        self.pending_transactions.push(transaction);
+    /// Gets the transactions waiting to be included in a block
+    pub fn mempool(&self) -> &Mempool {
+        &self.mempool
+    }
+    
+    /// Predicts how many blocks a transaction paying `rate` waits for, given the pending pool
+    ///
+    /// See `ctb_core::fee_market::estimate_inclusion`; the latest
+    /// `FULLNESS_WINDOW` blocks stand for how much gas the coming ones use.
+    pub fn estimate_inclusion(&self, rate: FeeRate) -> EstimatedBlocks {
+        let blockchain = self.blockchain.lock().unwrap();
+        let latest = blockchain.get_latest_height();
+        let from = latest.saturating_sub(fee_market::FULLNESS_WINDOW - 1).max(1);
+        let space = BlockSpace {
+            gas_limit: blockchain.get_block_gas_limit(),
+            max_transactions: MAX_BLOCK_TRANSACTIONS,
+            next_base_fee: blockchain.next_base_fee(),
+            recent_gas_used: (from..=latest).filter_map(|height| blockchain.get_block_gas_used(height)).collect(),
+        };
+        fee_market::estimate_inclusion(self.mempool.fee_histogram(), rate, &space)
+    }
+    
+    /// Gets the limits on the transactions the pending pool admits
+    pub fn mempool_limits(&self) -> MempoolLimits {
+        self.params.mempool_limits()
+    }
+    
+    /// Changes the limits on the transactions the pending pool admits
+    ///
+    /// New data size limits only apply to transactions added from now on,
+    /// but a smaller capacity evicts the lowest priority pending
+    /// transactions straight away; they are returned.
+    pub fn set_mempool_limits(&mut self, limits: MempoolLimits) -> Vec<Arc<Transaction>> {
+        self.params.max_relay_data_size = limits.max_relay_data_size;
+        self.params.max_relay_deploy_data_size = limits.max_relay_deploy_data_size;
+        self.params.mempool_capacity = limits.capacity;
+        self.mempool.set_capacity(limits.capacity)
+    }
+    
+    /// Removes the transactions of a block added to the chain from the pending pool
+    ///
+    /// Pending transactions of the block's senders with nonces it used, and
+    /// then those their new balances no longer cover, latest first, are
+    /// dropped too and returned.
+    pub fn on_block_connected(&mut self, block: &Block) -> Vec<Arc<Transaction>> {
+        self.mempool.on_block_connected(block);
+        
+        let senders: BTreeSet<&str> = block.transactions.iter().map(|tx| tx.sender.as_str()).collect();
+        let blockchain = self.blockchain.lock().unwrap();
+        let mut dropped = Vec::new();
+        for sender in senders {
+            if self.mempool.pending_outflow(sender) == 0 {
+                continue;
+            }
+            let address = Address::new(sender).ok();
+            let next_nonce = address.as_ref().and_then(|sender| blockchain.get_nonce(sender).ok()).unwrap_or(0);
+            let balance = address
+                .and_then(|sender| blockchain.get_balance(&sender).ok())
+                .map_or(0, Amount::base_units);
+            dropped.extend(self.mempool.drop_stale(sender, next_nonce));
+            dropped.extend(self.mempool.drop_unfunded(sender, balance));
+        }
+        self.clock = governed_clock(self.clock, &self.params, &blockchain);
+        dropped
     }
     
     /// Produces a new block if it's time
     pub fn try_produce_block(&mut self) -> Result<Option<Block>> {
+        self.try_produce_block_at(ctb_core::current_timestamp())
+    }
+    
+    /// Produces a new block if it's time at `now`, a Unix timestamp in seconds
+    ///
+    /// It's time once `block_time`, as governed for the next block, has
+    /// passed since the latest block's timestamp. The block is produced for
+    /// the proposer of the slot `now` falls in, or not at all if that isn't
+    /// the local validator. It and its coinbase transactions are stamped
+    /// `now`, so it doesn't depend on when it was produced. With a signer,
+    /// the block is signed; if the signer can't sign it, or its key isn't
+    /// the proposer's consensus key, the slot is skipped.
+    pub fn try_produce_block_at(&mut self, now: u64) -> Result<Option<Block>> {
+        // Get the latest block
+        let blockchain = self.blockchain.lock().unwrap();
+        let latest_block = blockchain.get_latest_block().ok_or(BlockchainError::UnknownBlock { height: 0 })?;
+        self.clock = governed_clock(self.clock, &self.params, &blockchain);
+        
         // Check if it's time to produce a new block
-        let elapsed = self.last_block_time.elapsed();
-        if elapsed < Duration::from_secs(self.params.block_time) {
+        if now < self.clock.earliest_block_time(latest_block.header().timestamp) {
             return Ok(None);
         }
         
-        // Select the next validator
-        let validator = self.select_next_validator()?.clone();
-        
-        // Get the latest block
-        let blockchain = self.blockchain.lock().unwrap();
-        let latest_block = blockchain.get_latest_block().ok_or_else(|| {
-            BlockchainError::StateError("No blocks in the chain".to_string())
-        })?;
+        // Select the proposer of the current slot
+        let validator = self.proposer_for_slot(self.clock.slot_at(now))?.clone();
+        if self.local_validator.as_ref().is_some_and(|local| *local != validator.address) {
+            return Ok(None);
+        }
         
-        let height = latest_block.header.height;
+        let height = latest_block.header().height;
         let prev_hash = latest_block.hash()?;
+        let base_fee = blockchain.next_base_fee();
         
         // Select transactions for the new block
         let mut block_transactions = Vec::new();
         
-        // Add a coinbase transaction for the validator reward
-        let reward = self.calculate_block_reward(height);
-        let coinbase = Transaction::new_coinbase(validator.address.clone(), reward)?;
-        block_transactions.push(coinbase);
-        
-        // Add pending transactions (up to a limit)
-        let max_transactions = 1000; // Arbitrary limit for now
-        let mut added = 0;
+        // Add the coinbase transactions splitting the reward between the validator's payout address and the treasury
+        let payout_address = blockchain.get_state().lock().unwrap().get_payout_address(&validator.address, height + 1);
+        let mut coinbases = blockchain
+            .reward_schedule()
+            .coinbase_transactions(height + 1, &payout_address)
+            .with_ctx(ErrorContext::height(height + 1))?;
+        for coinbase in &mut coinbases {
+            coinbase.timestamp = now;
+            coinbase.id = coinbase.calculate_hash()?;
+        }
+        let coinbase_count = coinbases.len();
+        block_transactions.extend(coinbases);
         
-        let mut remaining_transactions = Vec::new();
-        for tx in self.pending_transactions.drain(..) {
-            if added < max_transactions {
-                block_transactions.push(tx);
-                added += 1;
-            } else {
-                remaining_transactions.push(tx);
+        // Add pending transactions (up to a limit) in the canonical order,
+        // highest gas price first, reserving each one's gas limit so the
+        // block can't exceed the gas limit however they execute. Flat-fee
+        // transfers use no gas and have no gas price, so they come last, and
+        // underpriced transactions wait for the base fee to fall. A sender's
+        // transactions must have its next nonces: those whose nonces are used
+        // are dropped, and those after a missing nonce wait for it.
+        let mut gas_reserved = 0u64;
+        let mut last = None;
+        let mut next_nonces: HashMap<String, u64> = HashMap::new();
+        let mut waiting = Vec::new();
+        while ((block_transactions.len() - coinbase_count) as u64) < MAX_BLOCK_TRANSACTIONS {
+            let Some(tx) = self.mempool.pop_best(self.params.block_gas_limit - gas_reserved, base_fee, last) else {
+                break;
+            };
+            let next_nonce = next_nonces.entry(tx.sender.clone()).or_insert_with(|| {
+                Address::new(tx.sender.as_str()).ok().and_then(|sender| blockchain.get_nonce(&sender).ok()).unwrap_or(0)
+            });
+            match tx.nonce.cmp(next_nonce) {
+                Ordering::Less => {
+                    log::debug!("Dropping transaction {}, whose nonce {} is used", tx.id, tx.nonce);
+                    continue;
+                }
+                Ordering::Greater => {
+                    waiting.push(tx);
+                    continue;
+                }
+                Ordering::Equal => *next_nonce += 1,
             }
+            gas_reserved += tx.gas_limit;
+            last = Some(OrderKey::of(&tx));
+            // Only copied if the submitter still holds the transaction
+            block_transactions.push(Arc::unwrap_or_clone(tx));
+        }
+        for tx in waiting {
+            let _ = self.mempool.insert(tx);
         }
-        
-        self.pending_transactions = remaining_transactions;
         
         // Create the new block
-        let new_block = Block::new(
+        let mut new_block = Block::new(
             height + 1,
             prev_hash,
             block_transactions,
-            validator.address,
-        )?;
+            validator.address.clone(),
+            base_fee,
+        )
+        .with_ctx(ErrorContext::height(height + 1))?;
+        new_block.header_mut().timestamp = now;
+        new_block.header_mut().validator_set_hash = blockchain.next_validator_set().map(|set| set.hash());
+        drop(blockchain);
         
-        // Update the last block time
-        self.last_block_time = Instant::now();
-        
-        Ok(Some(new_block))
-    }
-    
-    /// Calculates the block reward for a given height
-    fn calculate_block_reward(&self, height: u64) -> u64 {
-        // Implement a deflationary model similar to Bitcoin
-        // Initial reward is 50 GENX, halving every 210,000 blocks
-        let initial_reward = 50 * 100_000_000; // 50 GENX with 8 decimal places
-        let halving_interval = 210_000;
-        
-        let halvings = height / halving_interval;
-        if halvings >= 64 { // After 64 halvings, reward is effectively 0
-            return 0;
+        // Remote signers may take a while, so the chain isn't held meanwhile
+        if let Some(signer) = &self.signer {
+            let signed = signer.public_key().and_then(|key| {
+                if key != validator.consensus_key {
+                    return Err(SignerError::Rejected(format!(
+                        "signer key {} isn't the consensus key of {}, {}",
+                        key, validator.address, validator.consensus_key
+                    )));
+                }
+                signer.sign_block_header(new_block.header())
+            });
+            match signed {
+                Ok(signature) => new_block.header_mut().signature = Some(signature),
+                Err(e) => {
+                    log::warn!("Skipping the slot of block {}: {}", height + 1, e);
+                    // The transactions wait for the next block
+                    for tx in new_block.transactions.into_iter().skip(coinbase_count) {
+                        let _ = self.mempool.insert(tx);
+                    }
+                    return Ok(None);
+                }
+            }
         }
         
-        initial_reward >> halvings
+        Ok(Some(new_block))
     }
+}
+
+/// Gets the clock numbering the slots of the chain's next block, whose block time governance may have changed
+fn governed_clock(clock: SlotClock, params: &ConsensusParams, blockchain: &Blockchain) -> SlotClock {
+    let state = blockchain.get_state();
+    let block_time = params.governed(&state.lock().unwrap(), blockchain.get_latest_height() + 1).block_time;
+    clock.with_block_time(block_time)
 }
\ No newline at end of file
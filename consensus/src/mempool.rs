@@ -0,0 +1,333 @@
+//! Pool of transactions waiting to be included in a block
+//!
+//! The mempool indexes the transactions it holds three ways:
+//!
+//! - by transaction ID, for lookup and removal;
+//! - by sender, in sequence order: by nonce, then timestamp. Only the
+//!   first is ready to be included, and removing it promotes the next.
+//!   Once a block moves the sender's nonce on, `drop_stale` drops those
+//!   whose nonces it used.
+//! - by priority, both the ready transactions, for packing blocks, and
+//!   all of them, for eviction. Priority is the canonical order of
+//!   transactions in a block (see `ctb_core::ordering`).
+//!
+//! It also keeps a histogram of the fees its transactions pay (see
+//! `ctb_core::fee_market::FeeHistogram`), for estimating when a transaction
+//! paying a rate will be included, and each sender's pending outflow, the
+//! most its transactions can take from its balance together.
+//! `insert_funded` refuses a transaction the sender's balance can't cover
+//! on top of that, and once a block changes the balance `drop_unfunded`
+//! drops the sender's latest transactions until the rest are covered again.
+//!
+//! Transactions paying for gas come first, highest gas price first,
+//! followed by flat-fee transfers, highest fee first. Ties go to the
+//! transaction with the lowest ID, so every node packs them the same way.
+//!
+//! Every operation takes O(log n) time for a pool of n transactions,
+//! except `on_block_connected`, which takes O(k log n) for a block of k
+//! transactions, and `drop_unfunded` and `drop_stale`, O(d log n) to drop d.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use ctb_core::block::Block;
+use ctb_core::fee_market::FeeHistogram;
+use ctb_core::ordering::OrderKey;
+use ctb_core::transaction::Transaction;
+use ctb_core::TxHash;
+use thiserror::Error;
+
+/// Transactions a pool holds unless configured otherwise
+pub const DEFAULT_CAPACITY: usize = 50_000;
+
+/// Error admitting a transaction to the mempool
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MempoolError {
+    #[error("Transaction {0} is already in the mempool")]
+    Duplicate(TxHash),
+    
+    #[error("Mempool is full and transaction {0} pays too little to replace any")]
+    Full(TxHash),
+    
+    #[error("Transaction {id} needs {required} along with its sender's pending transactions, but the sender has {balance}")]
+    InsufficientFunds { id: TxHash, required: u64, balance: u64 },
+}
+
+impl MempoolError {
+    /// Gets the stable numeric code of the error, see `ConsensusError::error_code`
+    pub fn error_code(&self) -> u32 {
+        match self {
+            MempoolError::Duplicate(_) => 2100,
+            MempoolError::Full(_) => 2101,
+            MempoolError::InsufficientFunds { .. } => 2102,
+        }
+    }
+}
+
+/// Position of a transaction among its sender's: its nonce, timestamp and ID
+type Sequence = (u64, u64, TxHash);
+
+#[derive(Debug, Clone)]
+struct Entry {
+    tx: Arc<Transaction>,
+    priority: OrderKey,
+    sequence: Sequence,
+}
+
+/// Indexed pool of pending transactions
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    /// Most transactions held at once
+    capacity: usize,
+    
+    /// All transactions, by ID
+    entries: HashMap<TxHash, Entry>,
+    
+    /// Each sender's transactions, lowest nonce first
+    senders: HashMap<String, BTreeSet<Sequence>>,
+    
+    /// Most each sender's transactions can take from its balance
+    outflows: HashMap<String, u64>,
+    
+    /// Earliest transaction of each sender, lowest priority first
+    ready: BTreeSet<OrderKey>,
+    
+    /// All transactions, lowest priority first
+    by_priority: BTreeSet<OrderKey>,
+    
+    /// All transactions, by the rate they pay
+    fees: FeeHistogram,
+}
+
+impl Mempool {
+    /// Creates a pool holding at most `capacity` transactions
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            senders: HashMap::new(),
+            outflows: HashMap::new(),
+            ready: BTreeSet::new(),
+            by_priority: BTreeSet::new(),
+            fees: FeeHistogram::new(),
+        }
+    }
+    
+    /// Gets the number of transactions in the pool
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    /// Checks whether the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    /// Checks whether a transaction is in the pool, in O(1)
+    pub fn contains(&self, id: &TxHash) -> bool {
+        self.entries.contains_key(id)
+    }
+    
+    /// Gets a transaction in the pool, in O(1)
+    pub fn get(&self, id: &TxHash) -> Option<&Arc<Transaction>> {
+        self.entries.get(id).map(|entry| &entry.tx)
+    }
+    
+    /// Iterates over the transactions in the pool, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Transaction>> {
+        self.entries.values().map(|entry| &entry.tx)
+    }
+    
+    /// Gets the most transactions the pool holds at once
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    /// Changes the most transactions the pool holds at once
+    ///
+    /// A pool over its new capacity evicts its lowest priority
+    /// transactions, which are returned.
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<Arc<Transaction>> {
+        self.capacity = capacity;
+        let mut evicted = Vec::new();
+        while self.entries.len() > self.capacity {
+            evicted.extend(self.evict_worst());
+        }
+        evicted
+    }
+    
+    /// Gets the number of transactions ready to be included
+    pub fn ready_count(&self) -> usize {
+        self.ready.len()
+    }
+    
+    /// Gets the histogram of the fees the transactions pay
+    pub fn fee_histogram(&self) -> &FeeHistogram {
+        &self.fees
+    }
+    
+    /// Gets the most a sender's pending transactions can take from its balance together
+    pub fn pending_outflow(&self, sender: &str) -> u64 {
+        self.outflows.get(sender).copied().unwrap_or(0)
+    }
+    
+    /// Adds a transaction to the pool if its sender's `balance` covers it along with the sender's pending ones
+    pub fn insert_funded(&mut self, tx: impl Into<Arc<Transaction>>, balance: u64) -> Result<(), MempoolError> {
+        let tx = tx.into();
+        if self.entries.contains_key(&tx.id) {
+            return Err(MempoolError::Duplicate(tx.id));
+        }
+        
+        let required = self.pending_outflow(&tx.sender).saturating_add(tx.max_cost());
+        if required > balance {
+            return Err(MempoolError::InsufficientFunds { id: tx.id, required, balance });
+        }
+        self.insert(tx)
+    }
+    
+    /// Adds a transaction to the pool
+    ///
+    /// When the pool is full, the lowest priority transaction is evicted
+    /// to make room, unless the new one has lower priority still.
+    pub fn insert(&mut self, tx: impl Into<Arc<Transaction>>) -> Result<(), MempoolError> {
+        let tx = tx.into();
+        if self.entries.contains_key(&tx.id) {
+            return Err(MempoolError::Duplicate(tx.id));
+        }
+        
+        let priority = OrderKey::of(&tx);
+        if self.entries.len() >= self.capacity {
+            match self.by_priority.first() {
+                Some(worst) if *worst < priority => {
+                    self.evict_worst();
+                }
+                _ => return Err(MempoolError::Full(tx.id)),
+            }
+        }
+        
+        // The sender's first transaction is the only one ready
+        let sequence = (tx.nonce, tx.timestamp, tx.id);
+        let queue = self.senders.entry(tx.sender.clone()).or_default();
+        let previous_head = queue.first().copied();
+        queue.insert(sequence);
+        if queue.first() == Some(&sequence) {
+            if let Some((_, _, head)) = previous_head {
+                self.ready.remove(&self.entries[&head].priority);
+            }
+            self.ready.insert(priority);
+        }
+        
+        self.by_priority.insert(priority);
+        self.fees.add(&tx);
+        *self.outflows.entry(tx.sender.clone()).or_default() += tx.max_cost();
+        self.entries.insert(tx.id, Entry { tx, priority, sequence });
+        Ok(())
+    }
+    
+    /// Removes a transaction from the pool, promoting its sender's next one if it was ready
+    pub fn remove(&mut self, id: &TxHash) -> Option<Arc<Transaction>> {
+        let entry = self.entries.remove(id)?;
+        self.by_priority.remove(&entry.priority);
+        self.fees.remove(&entry.tx);
+        let was_ready = self.ready.remove(&entry.priority);
+        if let Some(outflow) = self.outflows.get_mut(&entry.tx.sender) {
+            *outflow -= entry.tx.max_cost();
+        }
+        
+        if let Some(queue) = self.senders.get_mut(&entry.tx.sender) {
+            queue.remove(&entry.sequence);
+            match queue.first() {
+                Some(&(_, _, next)) if was_ready => {
+                    self.ready.insert(self.entries[&next].priority);
+                }
+                Some(_) => {}
+                None => {
+                    self.senders.remove(&entry.tx.sender);
+                    self.outflows.remove(&entry.tx.sender);
+                }
+            }
+        }
+        
+        Some(entry.tx)
+    }
+    
+    /// Removes and returns the best transaction ready for a block
+    ///
+    /// `after` is the key of the transaction popped before it for the same
+    /// block, if any: only transactions that come after it in the canonical
+    /// order are considered, so the block is packed in that order even when
+    /// removing a transaction readies its sender's next, better paying one.
+    /// That one waits for the next block.
+    ///
+    /// Metered transactions whose gas price is below `base_fee` wait for it
+    /// to fall, and flat-fee transfers are considered after the rest. Returns
+    /// `None` when the best candidate reserves more gas than `gas_budget`,
+    /// as the block is then full.
+    pub fn pop_best(&mut self, gas_budget: u64, base_fee: u64, after: Option<OrderKey>) -> Option<Arc<Transaction>> {
+        let mut best = match after {
+            Some(after) => *self.ready.range(..after).next_back()?,
+            None => *self.ready.last()?,
+        };
+        if best.metered && best.rate < base_fee {
+            // Every other metered transaction pays less still
+            best = *self.ready.range(..OrderKey::LOWEST_METERED).next_back()?;
+        }
+        
+        let Reverse(id) = best.id;
+        if self.entries[&id].tx.gas_limit > gas_budget {
+            return None;
+        }
+        self.remove(&id)
+    }
+    
+    /// Removes and returns the lowest priority transaction
+    pub fn evict_worst(&mut self) -> Option<Arc<Transaction>> {
+        let Reverse(id) = self.by_priority.first()?.id;
+        self.remove(&id)
+    }
+    
+    /// Removes the transactions a newly connected block included
+    pub fn on_block_connected(&mut self, block: &Block) {
+        for tx in &block.transactions {
+            self.remove(&tx.id);
+        }
+    }
+    
+    /// Drops a sender's latest transactions until its `balance` covers the rest, returning them
+    ///
+    /// Called once a block has left the sender with less than its pending
+    /// transactions need, such as when a conflicting spend from another
+    /// node was confirmed. The earliest are kept, as they're included first.
+    pub fn drop_unfunded(&mut self, sender: &str, balance: u64) -> Vec<Arc<Transaction>> {
+        let mut dropped = Vec::new();
+        while self.pending_outflow(sender) > balance {
+            let Some(&(_, _, latest)) = self.senders.get(sender).and_then(|queue| queue.last()) else {
+                break;
+            };
+            dropped.extend(self.remove(&latest));
+        }
+        dropped
+    }
+    
+    /// Drops a sender's transactions with nonces below `next_nonce`, returning them
+    ///
+    /// Called once a block has moved the sender's nonce on, as those
+    /// transactions, or others using the same nonces, are in the chain.
+    pub fn drop_stale(&mut self, sender: &str, next_nonce: u64) -> Vec<Arc<Transaction>> {
+        let mut dropped = Vec::new();
+        while let Some(&(nonce, _, first)) = self.senders.get(sender).and_then(|queue| queue.first()) {
+            if nonce >= next_nonce {
+                break;
+            }
+            dropped.extend(self.remove(&first));
+        }
+        dropped
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
\ No newline at end of file
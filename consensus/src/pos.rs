@@ -2,21 +2,34 @@
 //!
 //! This module implements the core PoS algorithm for validator selection,
 //! block production, and rewards distribution.
+//!
+//! Validators are jailed for an epoch (see `ctb_core::validator::EPOCH_LENGTH`)
+//! after one in which they kept less than `MIN_UPTIME` of their slots or
+//! missed `MAX_MISSED_STREAK` of them in a row. Their uptime comes from the
+//! chain alone (see `uptime`), so every node jails the same validators.
 
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
-
-use rand::{Rng, SeedableRng};
-use rand::rngs::StdRng;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
 
-use core::block::Block;
-use core::transaction::Transaction;
-use core::{BlockchainError, Result};
+use ctb_core::block::Block;
+use ctb_core::chain::Blockchain;
+use ctb_core::paging::{self, Page, PageRequest};
+use ctb_core::validator::{epoch_of, ValidatorSort};
+use ctb_core::validator_set::first_height;
+use ctb_core::Result;
 
+use crate::slots::{self, SlotClock};
+use crate::uptime::{UptimeCache, UptimeStats};
 use crate::validator::{Validator, ValidatorStatus};
 use crate::ConsensusError;
 use crate::ConsensusParams;
 
+/// Percentage of its slots below which a validator is jailed for the next epoch
+pub const MIN_UPTIME: f64 = 50.0;
+
+/// Slots in a row a validator may miss before it's jailed for the next epoch
+pub const MAX_MISSED_STREAK: u64 = 10;
+
 /// Manages the Proof of Stake consensus mechanism
 pub struct PoSConsensus {
     /// Consensus parameters
@@ -31,8 +44,11 @@ pub struct PoSConsensus {
     /// Current epoch number
     current_epoch: u64,
     
-    /// Epoch start time
-    epoch_start: Instant,
+    /// Uptime of the completed epochs
+    uptime: UptimeCache,
+    
+    /// Validators jailed for the epoch of the latest block
+    jailed: BTreeSet<String>,
 }
 
 /// Metrics tracking validator performance
@@ -51,6 +67,36 @@ pub struct ValidatorMetrics {
     last_seen: u64,
 }
 
+impl ValidatorMetrics {
+    /// Gets the number of blocks produced in the current epoch
+    pub fn blocks_produced(&self) -> u64 {
+        self.blocks_produced
+    }
+    
+    /// Gets the number of blocks missed in the current epoch
+    pub fn blocks_missed(&self) -> u64 {
+        self.blocks_missed
+    }
+    
+    /// Gets the uptime percentage (0-100)
+    pub fn uptime(&self) -> f64 {
+        self.uptime
+    }
+    
+    /// Gets the timestamp the validator was last seen producing a block
+    pub fn last_seen(&self) -> u64 {
+        self.last_seen
+    }
+    
+    /// Recomputes uptime from the blocks produced and missed
+    fn update_uptime(&mut self) {
+        let total_blocks = self.blocks_produced + self.blocks_missed;
+        if total_blocks > 0 {
+            self.uptime = (self.blocks_produced as f64 / total_blocks as f64) * 100.0;
+        }
+    }
+}
+
 impl PoSConsensus {
     /// Creates a new PoS consensus instance
     pub fn new(params: ConsensusParams) -> Self {
@@ -59,7 +105,8 @@ impl PoSConsensus {
             active_validators: Vec::new(),
             validator_metrics: HashMap::new(),
             current_epoch: 0,
-            epoch_start: Instant::now(),
+            uptime: UptimeCache::new(),
+            jailed: BTreeSet::new(),
         }
     }
     
@@ -67,7 +114,7 @@ impl PoSConsensus {
     pub fn update_validator_set(&mut self, validators: Vec<Validator>) {
         // Sort validators by stake (descending)
         let mut sorted = validators;
-        sorted.sort_by(|a, b| b.stake.cmp(&a.stake));
+        sorted.sort_by_key(|validator| std::cmp::Reverse(validator.stake));
         
         // Take the top validators with sufficient stake
         self.active_validators = sorted.into_iter()
@@ -75,6 +122,9 @@ impl PoSConsensus {
             .take(self.params.validator_set_size)
             .collect();
         
+        // Slots of epochs without a committed set were chosen from the old validators
+        self.uptime.clear();
+        
         // Initialize metrics for new validators
         for validator in &self.active_validators {
             if !self.validator_metrics.contains_key(&validator.address) {
@@ -82,53 +132,48 @@ impl PoSConsensus {
                     blocks_produced: 0,
                     blocks_missed: 0,
                     uptime: 100.0,
-                    last_seen: core::current_timestamp(),
+                    last_seen: ctb_core::current_timestamp(),
                 });
             }
         }
     }
     
-    /// Selects the next validator to produce a block
-    pub fn select_validator(&self, block_height: u64) -> Result<Validator> {
-        if self.active_validators.is_empty() {
-            return Err(ConsensusError::ValidatorError("No active validators".to_string()).into());
-        }
-        
-        // Use a deterministic random selection weighted by stake
-        // The seed is derived from the block height for determinism
-        let seed = block_height.to_le_bytes();
-        let mut seed_array = [0u8; 32];
-        for (i, byte) in seed.iter().enumerate() {
-            if i < 32 {
-                seed_array[i] = *byte;
-            }
-        }
-        
-        let mut rng = StdRng::from_seed(seed_array);
-        
-        // Calculate total stake of active validators
-        let total_stake: u64 = self.active_validators.iter().map(|v| v.stake).sum();
-        
-        // Select a validator based on stake weight
-        let selection_point = rng.gen_range(0..total_stake);
-        let mut cumulative_stake = 0;
+    /// Selects the validator that proposes the block of a slot
+    pub fn select_validator(&self, slot: u64) -> Result<Validator> {
+        slots::select_proposer(&self.active_validators, slot)
+            .cloned()
+            .ok_or_else(|| ConsensusError::NoActiveValidators.into())
+    }
+    
+    /// Records a block added to the chain on a parent made at `parent_time`
+    ///
+    /// Starts a new epoch if the block's slot is in one, then charges the
+    /// proposer of every slot between the parent and the block with a missed
+    /// block, at most an epoch's worth, and credits the block's validator.
+    /// Only the chain is consulted, so every node with the same validators
+    /// agrees. Returns the missed slots and their proposers.
+    pub fn record_block(&mut self, clock: &SlotClock, parent_time: u64, block: &Block) -> Vec<(u64, String)> {
+        self.check_epoch_transition(clock.epoch_of(clock.slot_at(block.header().timestamp)));
         
-        for validator in &self.active_validators {
-            cumulative_stake += validator.stake;
-            if cumulative_stake > selection_point {
-                return Ok(validator.clone());
-            }
+        let missed_slots = clock.missed_slots(parent_time, block.header().timestamp);
+        let first_counted = missed_slots.start.max(missed_slots.end.saturating_sub(slots::SLOTS_PER_EPOCH));
+        let missed: Vec<_> = (first_counted..missed_slots.end)
+            .filter_map(|slot| Some((slot, slots::select_proposer(&self.active_validators, slot)?.address.clone())))
+            .collect();
+        for (_, proposer) in &missed {
+            self.record_missed_block(proposer);
         }
         
-        // Fallback to the first validator (should never happen)
-        Ok(self.active_validators[0].clone())
+        self.record_block_production(&block.header().validator, block.header().height);
+        missed
     }
     
     /// Records that a validator produced a block
     pub fn record_block_production(&mut self, validator_address: &str, block_height: u64) {
         if let Some(metrics) = self.validator_metrics.get_mut(validator_address) {
             metrics.blocks_produced += 1;
-            metrics.last_seen = core::current_timestamp();
+            metrics.last_seen = ctb_core::current_timestamp();
+            metrics.update_uptime();
         }
         
         // Find the validator and update its last block produced
@@ -141,37 +186,66 @@ impl PoSConsensus {
     pub fn record_missed_block(&mut self, validator_address: &str) {
         if let Some(metrics) = self.validator_metrics.get_mut(validator_address) {
             metrics.blocks_missed += 1;
-            
-            // Update uptime
-            let total_blocks = metrics.blocks_produced + metrics.blocks_missed;
-            if total_blocks > 0 {
-                metrics.uptime = (metrics.blocks_produced as f64 / total_blocks as f64) * 100.0;
-            }
+            metrics.update_uptime();
         }
     }
     
-    /// Calculates the block reward for a given height
-    pub fn calculate_block_reward(&self, height: u64) -> u64 {
-        // Implement a deflationary model similar to Bitcoin
-        // Initial reward is 50 GENX, halving every 210,000 blocks
-        let initial_reward = 50 * 100_000_000; // 50 GENX with 8 decimal places
-        let halving_interval = 210_000;
-        
-        let halvings = height / halving_interval;
-        if halvings >= 64 { // After 64 halvings, reward is effectively 0
-            return 0;
+    /// Computes every validator's uptime over the blocks at a range of heights, see `uptime::compute`
+    ///
+    /// Proposers are chosen from the active validators for epochs without a
+    /// committed validator set, and completed epochs are cached.
+    pub fn uptime(&mut self, blockchain: &Blockchain, heights: Range<u64>) -> HashMap<String, UptimeStats> {
+        let clock = self.params.slot_clock(blockchain);
+        self.uptime.compute(blockchain, &clock, &self.active_validators, heights)
+    }
+    
+    /// Jails the validators whose uptime fell short in the epoch before the latest block's
+    ///
+    /// Jailed validators stay jailed for the rest of that epoch and are
+    /// released when the next starts, unless they fell short again. Returns
+    /// the validators that weren't jailed before.
+    pub fn update_jailing(&mut self, blockchain: &Blockchain) -> Vec<String> {
+        let jailed: BTreeSet<String> = match epoch_of(blockchain.get_latest_height()).checked_sub(1) {
+            Some(previous) => self
+                .uptime(blockchain, first_height(previous)..first_height(previous + 1))
+                .into_iter()
+                .filter(|(_, stats)| stats.uptime() < MIN_UPTIME || stats.longest_missed_streak >= MAX_MISSED_STREAK)
+                .map(|(validator, _)| validator)
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        let newly_jailed = jailed.difference(&self.jailed).cloned().collect();
+        self.jailed = jailed;
+        newly_jailed
+    }
+    
+    /// Checks whether a validator is jailed for the epoch of the latest block
+    pub fn is_jailed(&self, validator_address: &str) -> bool {
+        self.jailed.contains(validator_address)
+    }
+    
+    /// Gets the status of a validator: jailed, active or inactive
+    pub fn validator_status(&self, validator_address: &str) -> ValidatorStatus {
+        if self.is_jailed(validator_address) {
+            ValidatorStatus::Jailed
+        } else if self.active_validators.iter().any(|v| v.address == validator_address) {
+            ValidatorStatus::Active
+        } else {
+            ValidatorStatus::Inactive
         }
-        
-        initial_reward >> halvings
     }
     
-    /// Checks if it's time to start a new epoch
-    pub fn check_epoch_transition(&mut self) -> bool {
-        let epoch_duration = Duration::from_secs(self.params.block_time * 100); // 100 blocks per epoch
-        
-        if self.epoch_start.elapsed() >= epoch_duration {
-            self.current_epoch += 1;
-            self.epoch_start = Instant::now();
+    /// Calculates the block reward for a given height, before the treasury's share
+    pub fn calculate_block_reward(&self, height: u64) -> u64 {
+        ctb_core::rewards::block_reward(height)
+    }
+    
+    /// Starts the given epoch if it's later than the current one
+    ///
+    /// Epochs follow slots (see `slots`), so they start on every node at the same block.
+    pub fn check_epoch_transition(&mut self, epoch: u64) -> bool {
+        if epoch > self.current_epoch {
+            self.current_epoch = epoch;
             
             // Reset block production metrics for the new epoch
             for metrics in self.validator_metrics.values_mut() {
@@ -185,6 +259,18 @@ impl PoSConsensus {
         false
     }
     
+    /// Gets the validators active in the current epoch, highest stake first
+    pub fn get_active_validators(&self) -> &[Validator] {
+        &self.active_validators
+    }
+    
+    /// Gets a page of the validators active in the current epoch
+    pub fn list_validators(&self, sort: ValidatorSort, request: &PageRequest) -> Result<Page<&Validator>> {
+        paging::paginate(&self.active_validators, sort.listing(), request, sort.default_order(), |validator| {
+            sort.key(&validator.address, validator.stake)
+        })
+    }
+    
     /// Gets validator performance metrics
     pub fn get_validator_metrics(&self) -> &HashMap<String, ValidatorMetrics> {
         &self.validator_metrics
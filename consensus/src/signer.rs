@@ -0,0 +1,152 @@
+//! Signing blocks and finality votes with a validator's consensus key
+//!
+//! The consensus engine signs the blocks it produces, and nodes their
+//! finality votes, through a `BlockSigner`, never with a key of their own.
+//! `LocalSigner` holds the secret key in memory; nodes also sign with a key
+//! kept in an encrypted keystore or held by a remote signer (see the
+//! node's `signer` module), so the key never needs to be in the
+//! configuration.
+//!
+//! A block is signed over `block_signing_hash`, the hash of its header
+//! without the signature, and a vote over `FinalityVote::signing_hash`.
+//! Either verifies with the consensus key the validator registered.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use ctb_core::block::BlockHeader;
+use ctb_core::signature::{self, SignatureError, SignatureScheme};
+use ctb_core::{BlockHash, Bytes, Hash};
+
+use crate::finality::FinalityVote;
+
+/// What a signer is asked to sign
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningKind {
+    BlockHeader,
+    FinalityVote,
+}
+
+impl SigningKind {
+    /// Gets the kind's name, as remote signers are asked for it
+    pub fn name(&self) -> &'static str {
+        match self {
+            SigningKind::BlockHeader => "block_header",
+            SigningKind::FinalityVote => "finality_vote",
+        }
+    }
+    
+    /// Gets the kind a name names
+    pub fn from_name(name: &str) -> Option<Self> {
+        [SigningKind::BlockHeader, SigningKind::FinalityVote].into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+impl fmt::Display for SigningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigningKind::BlockHeader => write!(f, "block header"),
+            SigningKind::FinalityVote => write!(f, "finality vote"),
+        }
+    }
+}
+
+/// Reason a signer didn't sign
+///
+/// Numbered from 2200, see `error_code`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignerError {
+    #[error("Signer unavailable: {0}")]
+    Unavailable(String),
+    
+    #[error("Signer refused to sign a second {kind} at height {height}")]
+    DoubleSign { kind: SigningKind, height: u64 },
+    
+    #[error("Signer rejected the request: {0}")]
+    Rejected(String),
+    
+    #[error("Signer is locked")]
+    Locked,
+    
+    #[error("Signing failed: {0}")]
+    Signature(#[from] SignatureError),
+}
+
+impl SignerError {
+    /// Gets the stable numeric code of the error
+    pub fn error_code(&self) -> u32 {
+        match self {
+            SignerError::Unavailable(_) => 2200,
+            SignerError::DoubleSign { .. } => 2201,
+            SignerError::Rejected(_) => 2202,
+            SignerError::Locked => 2203,
+            SignerError::Signature(_) => 2204,
+        }
+    }
+}
+
+/// Signs with a validator's consensus key, wherever it's kept
+pub trait BlockSigner: Send + Sync {
+    /// Gets the address encoding the consensus key, which signatures verify with
+    fn public_key(&self) -> Result<String, SignerError>;
+    
+    /// Signs a block header, over `block_signing_hash`
+    fn sign_block_header(&self, header: &BlockHeader) -> Result<Bytes, SignerError>;
+    
+    /// Signs a vote for a checkpoint, over `FinalityVote::signing_hash`
+    fn sign_finality_vote(&self, height: u64, block_hash: &BlockHash) -> Result<Bytes, SignerError>;
+}
+
+/// Gets the hash a block's signature signs: that of its header without the signature
+pub fn block_signing_hash(header: &BlockHeader) -> ctb_core::Result<Hash> {
+    header.signing_hash()
+}
+
+/// Signer holding the secret key in memory
+pub struct LocalSigner {
+    scheme: SignatureScheme,
+    secret_key: Vec<u8>,
+    public_key: String,
+}
+
+impl LocalSigner {
+    /// Creates a signer with a secret key of a scheme
+    pub fn new(scheme: SignatureScheme, secret_key: Vec<u8>) -> Result<Self, SignerError> {
+        let public_key = signature::address_of(scheme, &secret_key)?;
+        Ok(Self { scheme, secret_key, public_key })
+    }
+    
+    /// Creates a signer with a secret key in hex, as `NodeConfig::validator_key` holds it
+    pub fn from_hex(scheme: SignatureScheme, secret_key: &str) -> Result<Self, SignerError> {
+        let secret_key = hex::decode(secret_key.trim_start_matches("0x")).map_err(|_| SignatureError::InvalidSecretKey)?;
+        Self::new(scheme, secret_key)
+    }
+    
+    fn sign(&self, hash: &Hash) -> Result<Bytes, SignerError> {
+        Ok(Bytes(signature::sign(self.scheme, &self.secret_key, hash)?))
+    }
+}
+
+impl fmt::Debug for LocalSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalSigner").field("public_key", &self.public_key).finish_non_exhaustive()
+    }
+}
+
+impl BlockSigner for LocalSigner {
+    fn public_key(&self) -> Result<String, SignerError> {
+        Ok(self.public_key.clone())
+    }
+    
+    fn sign_block_header(&self, header: &BlockHeader) -> Result<Bytes, SignerError> {
+        let hash = block_signing_hash(header).map_err(|e| SignerError::Rejected(e.to_string()))?;
+        self.sign(&hash)
+    }
+    
+    fn sign_finality_vote(&self, height: u64, block_hash: &BlockHash) -> Result<Bytes, SignerError> {
+        self.sign(&FinalityVote::signing_hash(height, block_hash))
+    }
+}
\ No newline at end of file
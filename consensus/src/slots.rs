@@ -0,0 +1,97 @@
+//! Slot timing derived from chain time
+//!
+//! Time is divided into slots of `block_time` seconds numbered from the
+//! genesis block's timestamp, and each slot has one proposer chosen
+//! deterministically from the active validators. A block may be produced
+//! once its slot has started and `block_time` has passed since its parent's
+//! timestamp, so pacing depends only on the chain and the wall clock, never
+//! on when the node started.
+//!
+//! A block's slot is the one its timestamp falls in. The slots between a
+//! block and its parent passed without a block, so every node can tell from
+//! the chain alone which proposers missed theirs.
+
+use std::ops::Range;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::validator::Validator;
+
+/// Number of slots in an epoch
+pub const SLOTS_PER_EPOCH: u64 = 100;
+
+/// Numbers the slots of a chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotClock {
+    /// Timestamp of the genesis block, when slot 0 starts
+    genesis_time: u64,
+    
+    /// Target seconds between blocks
+    block_time: u64,
+}
+
+impl SlotClock {
+    /// Creates a clock for a chain whose genesis block was made at `genesis_time`
+    pub fn new(genesis_time: u64, block_time: u64) -> Self {
+        Self { genesis_time, block_time }
+    }
+    
+    /// Gets a clock of the same chain with another block time
+    pub fn with_block_time(&self, block_time: u64) -> Self {
+        Self { block_time, ..*self }
+    }
+    
+    /// Gets the length of a slot in seconds; a block time of 0 still gives 1-second slots
+    pub fn slot_duration(&self) -> u64 {
+        self.block_time.max(1)
+    }
+    
+    /// Gets the slot a time falls in; times before genesis fall in slot 0
+    pub fn slot_at(&self, timestamp: u64) -> u64 {
+        timestamp.saturating_sub(self.genesis_time) / self.slot_duration()
+    }
+    
+    /// Gets the time a slot starts
+    pub fn slot_start(&self, slot: u64) -> u64 {
+        self.genesis_time.saturating_add(slot.saturating_mul(self.slot_duration()))
+    }
+    
+    /// Gets the epoch a slot falls in
+    pub fn epoch_of(&self, slot: u64) -> u64 {
+        slot / SLOTS_PER_EPOCH
+    }
+    
+    /// Gets the earliest time a block may be produced on a parent made at `parent_time`
+    pub fn earliest_block_time(&self, parent_time: u64) -> u64 {
+        parent_time.saturating_add(self.block_time)
+    }
+    
+    /// Gets the slots that passed without a block between a parent and its child
+    pub fn missed_slots(&self, parent_time: u64, block_time: u64) -> Range<u64> {
+        let parent_slot = self.slot_at(parent_time);
+        let block_slot = self.slot_at(block_time);
+        (parent_slot + 1)..block_slot.max(parent_slot + 1)
+    }
+}
+
+/// Chooses the proposer of a slot from the active validators, weighted by stake
+///
+/// Every node with the same validators in the same order chooses the same
+/// proposer. Returns `None` if there are no validators or none has stake.
+pub fn select_proposer(validators: &[Validator], slot: u64) -> Option<&Validator> {
+    let total_stake: u64 = validators.iter().map(|v| v.stake).sum();
+    if total_stake == 0 {
+        return None;
+    }
+    
+    let mut seed = [0u8; 32];
+    seed[..8].copy_from_slice(&slot.to_le_bytes());
+    let selection_point = StdRng::from_seed(seed).gen_range(0..total_stake);
+    
+    let mut cumulative_stake = 0;
+    validators.iter().find(|validator| {
+        cumulative_stake += validator.stake;
+        cumulative_stake > selection_point
+    })
+}
\ No newline at end of file
@@ -0,0 +1,254 @@
+//! Validator uptime derived from the chain alone
+//!
+//! Each slot has one proposer (see `slots`), and a block's slot is the one
+//! its timestamp falls in, so the blocks stored say which proposers kept
+//! their slots and which missed them: the slots between a block and its
+//! parent passed without a block. `compute` walks the blocks of a range of
+//! heights and counts each validator's produced and missed slots and its
+//! streaks of missed ones. Nothing observed locally goes in, so every node
+//! with the same chain computes the same numbers.
+//!
+//! The proposers of a block's slots are chosen from the validator set its
+//! epoch's first block committed to (see `ctb_core::validator_set`), or from
+//! the validators given on chains that don't commit to their sets. Like
+//! `PoSConsensus::record_block`, at most an epoch's worth of slots
+//! (`slots::SLOTS_PER_EPOCH`) are charged for a single gap, so a halted
+//! chain doesn't count against its validators without end. Missed slots
+//! are counted in the range of the block that ends their gap.
+//!
+//! Completed epochs never change unless a reorganization replaces their
+//! blocks, so an `UptimeCache` keeps each one's numbers, checked against
+//! the hash of its last block, and only walks the blocks of the rest.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::chain::Blockchain;
+use ctb_core::validator::epoch_of;
+use ctb_core::validator_set::first_height;
+use ctb_core::BlockHash;
+
+use crate::slots::{self, SlotClock};
+use crate::validator::Validator;
+
+/// Most completed epochs an `UptimeCache` keeps, dropping the oldest first
+pub const MAX_CACHED_EPOCHS: usize = 1024;
+
+/// Slots a validator kept and missed in a range of blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UptimeStats {
+    /// Slots the validator produced a block in
+    pub produced: u64,
+    
+    /// Slots the validator was the proposer of that passed without a block
+    pub missed: u64,
+    
+    /// Most of the validator's slots missed in a row
+    pub longest_missed_streak: u64,
+    
+    /// Slots missed in a row before the validator's first block in the range
+    pub leading_missed: u64,
+    
+    /// Slots missed in a row after the validator's last block in the range,
+    /// its current streak if the range ends at the tip
+    pub trailing_missed: u64,
+}
+
+impl UptimeStats {
+    /// Gets the number of the validator's slots
+    pub fn slots(&self) -> u64 {
+        self.produced + self.missed
+    }
+    
+    /// Gets the percentage of its slots the validator produced a block in, 100 if it had none
+    pub fn uptime(&self) -> f64 {
+        match self.slots() {
+            0 => 100.0,
+            slots => self.produced as f64 / slots as f64 * 100.0,
+        }
+    }
+    
+    /// Records the validator's next slot
+    pub fn record(&mut self, produced: bool) {
+        if produced {
+            self.produced += 1;
+            self.trailing_missed = 0;
+            return;
+        }
+        
+        self.missed += 1;
+        self.trailing_missed += 1;
+        self.longest_missed_streak = self.longest_missed_streak.max(self.trailing_missed);
+        if self.produced == 0 {
+            self.leading_missed += 1;
+        }
+    }
+    
+    /// Adds the stats of the blocks right after these
+    pub fn merge(&mut self, later: &UptimeStats) {
+        self.longest_missed_streak = self
+            .longest_missed_streak
+            .max(later.longest_missed_streak)
+            .max(self.trailing_missed + later.leading_missed);
+        if self.produced == 0 {
+            self.leading_missed = self.missed + later.leading_missed;
+        }
+        self.trailing_missed = match later.produced {
+            0 => self.trailing_missed + later.missed,
+            _ => later.trailing_missed,
+        };
+        self.produced += later.produced;
+        self.missed += later.missed;
+    }
+}
+
+/// Computes every validator's uptime over the blocks at a range of heights
+///
+/// `validators` are the proposers of the epochs whose first block didn't
+/// commit to a validator set, in the order the consensus engine chooses
+/// from them, highest stake first. The genesis block has no slot, and
+/// heights past the tip are ignored. Validators without a slot in the
+/// range are left out.
+pub fn compute(
+    blockchain: &Blockchain,
+    clock: &SlotClock,
+    validators: &[Validator],
+    heights: Range<u64>,
+) -> HashMap<String, UptimeStats> {
+    let mut stats: HashMap<String, UptimeStats> = HashMap::new();
+    let from = heights.start.max(1);
+    let Some(mut parent_time) = blockchain.get_block_by_height(from - 1).map(|parent| parent.header().timestamp) else {
+        return stats;
+    };
+    
+    let mut schedule: Option<(u64, Vec<Validator>)> = None;
+    for height in from..heights.end {
+        let Some(block) = blockchain.get_block_by_height(height) else {
+            break;
+        };
+        let epoch = epoch_of(height);
+        if schedule.as_ref().is_none_or(|(scheduled, _)| *scheduled != epoch) {
+            schedule = Some((epoch, proposers(blockchain, epoch, validators)));
+        }
+        let proposers = schedule.as_ref().map_or(validators, |(_, proposers)| proposers.as_slice());
+        
+        let missed_slots = clock.missed_slots(parent_time, block.header().timestamp);
+        let first_counted = missed_slots.start.max(missed_slots.end.saturating_sub(slots::SLOTS_PER_EPOCH));
+        for slot in first_counted..missed_slots.end {
+            if let Some(proposer) = slots::select_proposer(proposers, slot) {
+                stats.entry(proposer.address.clone()).or_default().record(false);
+            }
+        }
+        stats.entry(block.header().validator.clone()).or_default().record(true);
+        parent_time = block.header().timestamp;
+    }
+    stats
+}
+
+/// Gets the proposers of an epoch's slots: its committed validator set, or `validators` if there's none
+fn proposers(blockchain: &Blockchain, epoch: u64, validators: &[Validator]) -> Vec<Validator> {
+    match blockchain.get_validator_set(epoch) {
+        Some(set) => set
+            .members
+            .iter()
+            .map(|member| Validator {
+                consensus_key: member.consensus_key.clone(),
+                ..Validator::new(member.operator.clone(), member.stake)
+            })
+            .collect(),
+        None => validators.to_vec(),
+    }
+}
+
+/// Adds the stats of the blocks right after those of `stats`
+fn merge_all(stats: &mut HashMap<String, UptimeStats>, later: &HashMap<String, UptimeStats>) {
+    for (validator, later) in later {
+        stats.entry(validator.clone()).or_default().merge(later);
+    }
+}
+
+/// A completed epoch's uptime, as computed for the chain ending in its last block
+#[derive(Debug, Clone)]
+struct CachedEpoch {
+    /// Hash of the epoch's last block
+    last_hash: BlockHash,
+    
+    /// Clock the slots were numbered with
+    clock: SlotClock,
+    
+    stats: HashMap<String, UptimeStats>,
+}
+
+/// Keeps the uptime of completed epochs, see the module documentation
+#[derive(Debug, Default)]
+pub struct UptimeCache {
+    epochs: BTreeMap<u64, CachedEpoch>,
+}
+
+impl UptimeCache {
+    /// Creates an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Computes every validator's uptime like `compute`, from the cache for the completed epochs the range spans
+    pub fn compute(
+        &mut self,
+        blockchain: &Blockchain,
+        clock: &SlotClock,
+        validators: &[Validator],
+        heights: Range<u64>,
+    ) -> HashMap<String, UptimeStats> {
+        let latest = blockchain.get_latest_height();
+        let (from, to) = (heights.start.max(1), heights.end.min(latest + 1));
+        let mut stats = HashMap::new();
+        let mut start = from;
+        while start < to {
+            let epoch = epoch_of(start);
+            let epoch_start = first_height(epoch).max(1);
+            let epoch_end = first_height(epoch + 1);
+            let end = epoch_end.min(to);
+            
+            let completed = start == epoch_start && end == epoch_end && epoch_end <= latest + 1;
+            if completed {
+                merge_all(&mut stats, self.epoch(blockchain, clock, validators, epoch));
+            } else {
+                merge_all(&mut stats, &compute(blockchain, clock, validators, start..end));
+            }
+            start = end;
+        }
+        stats
+    }
+    
+    /// Gets the uptime of a completed epoch, computing it if it isn't cached for the chain as it is
+    fn epoch(
+        &mut self,
+        blockchain: &Blockchain,
+        clock: &SlotClock,
+        validators: &[Validator],
+        epoch: u64,
+    ) -> &HashMap<String, UptimeStats> {
+        let last_height = first_height(epoch + 1) - 1;
+        let last_hash = blockchain.get_block_by_height(last_height).and_then(|block| block.hash().ok()).unwrap_or_default();
+        let fresh = self.epochs.get(&epoch).is_some_and(|cached| cached.last_hash == last_hash && cached.clock == *clock);
+        if !fresh {
+            self.epochs.remove(&epoch);
+            while self.epochs.len() >= MAX_CACHED_EPOCHS {
+                self.epochs.pop_first();
+            }
+        }
+        let cached = self.epochs.entry(epoch).or_insert_with(|| CachedEpoch {
+            last_hash,
+            clock: *clock,
+            stats: compute(blockchain, clock, validators, first_height(epoch).max(1)..last_height + 1),
+        });
+        &cached.stats
+    }
+    
+    /// Forgets every epoch, as when the validators the proposers are chosen from change
+    pub fn clear(&mut self) {
+        self.epochs.clear();
+    }
+}
\ No newline at end of file
@@ -4,7 +4,12 @@
 //! for the Proof of Stake consensus mechanism.
 
 use serde::{Deserialize, Serialize};
-use core::{BlockchainError, Result};
+use ctb_core::state::State;
+use ctb_core::validator::ValidatorInfo;
+use ctb_core::units::Amount;
+use ctb_core::{Address, BlockchainError, Result};
+
+use crate::ConsensusError;
 
 /// Represents a validator in the blockchain network
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +20,9 @@ pub struct Validator {
     /// Amount of GENX tokens staked by this validator
     pub stake: u64,
     
+    /// Address of the key this validator signs blocks with
+    pub consensus_key: String,
+    
     /// Height of the last block produced by this validator
     pub last_block_produced: u64,
 }
@@ -39,12 +47,23 @@ impl Validator {
     /// Creates a new validator with the given address and stake
     pub fn new(address: String, stake: u64) -> Self {
         Self {
+            consensus_key: address.clone(),
             address,
             stake,
             last_block_produced: 0,
         }
     }
     
+    /// Creates a validator from its entry in the state's registry
+    pub fn from_registry(info: &ValidatorInfo, stake: u64) -> Self {
+        Self {
+            address: info.operator.clone(),
+            stake,
+            consensus_key: info.consensus_key.clone(),
+            last_block_produced: 0,
+        }
+    }
+    
     /// Updates the validator's stake
     pub fn update_stake(&mut self, new_stake: u64) {
         self.stake = new_stake;
@@ -79,19 +98,25 @@ impl ValidatorManager {
     }
     
     /// Registers a new validator
-    pub fn register_validator(&mut self, address: String, stake: u64) -> Result<()> {
+    pub fn register_validator(&mut self, address: Address, stake: Amount) -> Result<()> {
+        self.register(address.into_string(), stake.base_units())
+    }
+    
+    /// Registers a new validator at an unchecked address, with a stake in base units
+    #[deprecated(note = "use `ValidatorManager::register_validator` with an `Address` and an `Amount`")]
+    pub fn register_validator_untyped(&mut self, address: String, stake: u64) -> Result<()> {
+        self.register(address, stake)
+    }
+    
+    fn register(&mut self, address: String, stake: u64) -> Result<()> {
         // Check if the validator already exists
         if self.validators.iter().any(|v| v.address == address) {
-            return Err(BlockchainError::StateError(
-                format!("Validator {} already registered", address)
-            ));
+            return Err(BlockchainError::ValidatorExists { address });
         }
         
         // Check if the stake is sufficient
         if stake < self.min_stake {
-            return Err(BlockchainError::StateError(
-                format!("Insufficient stake: {} < {}", stake, self.min_stake)
-            ));
+            return Err(ConsensusError::InsufficientStake { address, required: self.min_stake, available: stake }.into());
         }
         
         // Add the validator
@@ -101,13 +126,39 @@ impl ValidatorManager {
         Ok(())
     }
     
+    /// Replaces the validators with those registered in the state, at their current stakes
+    ///
+    /// Validators that were already known keep their block production record.
+    pub fn load_registry(&mut self, state: &State) {
+        let previous = std::mem::take(&mut self.validators);
+        self.validators = state
+            .get_validators()
+            .into_iter()
+            .map(|(info, stake)| {
+                let mut validator = Validator::from_registry(info, stake);
+                if let Some(known) = previous.iter().find(|v| v.address == validator.address) {
+                    validator.last_block_produced = known.last_block_produced;
+                }
+                validator
+            })
+            .collect();
+    }
+    
     /// Updates a validator's stake
-    pub fn update_validator_stake(&mut self, address: &str, new_stake: u64) -> Result<()> {
+    pub fn update_validator_stake(&mut self, address: &Address, new_stake: Amount) -> Result<()> {
+        self.set_stake(address, new_stake.base_units())
+    }
+    
+    /// Updates the stake of a validator at an unchecked address, in base units
+    #[deprecated(note = "use `ValidatorManager::update_validator_stake` with an `Address` and an `Amount`")]
+    pub fn update_validator_stake_untyped(&mut self, address: &str, new_stake: u64) -> Result<()> {
+        self.set_stake(address, new_stake)
+    }
+    
+    fn set_stake(&mut self, address: &str, new_stake: u64) -> Result<()> {
         // Find the validator
         let validator = self.validators.iter_mut().find(|v| v.address == address)
-            .ok_or_else(|| BlockchainError::StateError(
-                format!("Validator {} not found", address)
-            ))?;
+            .ok_or_else(|| BlockchainError::UnknownValidator { address: address.to_string() })?;
         
         // Update the stake
         validator.update_stake(new_stake);
@@ -119,7 +170,7 @@ impl ValidatorManager {
     pub fn get_active_validators(&self) -> Vec<Validator> {
         // Sort validators by stake (descending)
         let mut sorted = self.validators.clone();
-        sorted.sort_by(|a, b| b.stake.cmp(&a.stake));
+        sorted.sort_by_key(|validator| std::cmp::Reverse(validator.stake));
         
         // Take the top validators with sufficient stake
         sorted.into_iter()
@@ -132,9 +183,7 @@ impl ValidatorManager {
     pub fn slash_validator(&mut self, address: &str, slash_percentage: f64) -> Result<u64> {
         // Find the validator
         let validator = self.validators.iter_mut().find(|v| v.address == address)
-            .ok_or_else(|| BlockchainError::StateError(
-                format!("Validator {} not found", address)
-            ))?;
+            .ok_or_else(|| BlockchainError::UnknownValidator { address: address.to_string() })?;
         
         // Calculate the slash amount
         let slash_amount = (validator.stake as f64 * slash_percentage) as u64;
@@ -151,9 +200,7 @@ impl ValidatorManager {
         self.validators.retain(|v| v.address != address);
         
         if self.validators.len() == initial_len {
-            return Err(BlockchainError::StateError(
-                format!("Validator {} not found", address)
-            ));
+            return Err(BlockchainError::UnknownValidator { address: address.to_string() });
         }
         
         Ok(())
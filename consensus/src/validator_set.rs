@@ -0,0 +1,77 @@
+//! Proofs of validator set changes for light clients
+//!
+//! The first block of each epoch commits to the validators active in it
+//! (see `ctb_core::validator_set`). A light client that trusts one epoch's set
+//! can follow the sets of the epochs after it without replaying blocks: the
+//! proof of an epoch's set is the set, the header of the block committing
+//! to it, and finality votes for that block signed by the consensus keys of
+//! the previous epoch's validators. `verify_validator_set_transition`
+//! accepts it once the voters hold at least two thirds of the previous
+//! set's stake, and the set it returns verifies the next proof in turn.
+
+use std::collections::HashSet;
+
+use ctb_core::block::BlockHeader;
+use ctb_core::validator_set::{self, ValidatorSet};
+use ctb_core::hashing;
+
+use crate::finality::FinalityVote;
+use crate::ConsensusError;
+
+/// Proof that the validators of an epoch endorsed the next epoch's set
+#[derive(Debug, Clone)]
+pub struct ValidatorSetProof {
+    /// Validator set of the epoch
+    pub validator_set: ValidatorSet,
+    
+    /// Header of the epoch's first block, which commits to the set
+    pub header: BlockHeader,
+    
+    /// Finality votes for the block by validators of the previous epoch
+    pub votes: Vec<FinalityVote>,
+}
+
+/// Checks a proof of the next epoch's validator set against the set of the epoch before it
+///
+/// The proof's header must start the next epoch and commit to the proof's
+/// set, and its votes must be for that header's block, each from a
+/// different member of `prev_set` and signed by its consensus key. Those
+/// members must hold at least two thirds of `prev_set`'s stake. Returns the
+/// proven set.
+pub fn verify_validator_set_transition(prev_set: &ValidatorSet, proof: &ValidatorSetProof) -> Result<ValidatorSet, ConsensusError> {
+    let invalid = |reason: String| ConsensusError::InvalidValidatorSetProof(reason);
+    let epoch = proof.validator_set.epoch;
+    if Some(epoch) != prev_set.epoch.checked_add(1) {
+        return Err(invalid(format!("Set of epoch {} doesn't follow epoch {}", epoch, prev_set.epoch)));
+    }
+    if proof.header.height != validator_set::first_height(epoch) {
+        return Err(invalid(format!("Block {} doesn't start epoch {}", proof.header.height, epoch)));
+    }
+    if proof.header.validator_set_hash != Some(proof.validator_set.hash()) {
+        return Err(invalid(format!("Block {} doesn't commit to the set", proof.header.height)));
+    }
+    
+    let block_hash = hashing::hash_block_header(&proof.header)?;
+    let mut voters = HashSet::new();
+    let mut endorsed = 0u64;
+    for vote in &proof.votes {
+        if vote.height != proof.header.height || vote.block_hash != block_hash {
+            return Err(invalid(format!("Vote of {} is for another block", vote.validator)));
+        }
+        let member = prev_set.member(&vote.validator).ok_or_else(|| {
+            invalid(format!("{} isn't a validator of epoch {}", vote.validator, prev_set.epoch))
+        })?;
+        if !voters.insert(&vote.validator) {
+            return Err(invalid(format!("{} voted more than once", vote.validator)));
+        }
+        vote.verify(&member.consensus_key)
+            .map_err(|_| ConsensusError::InvalidVoteSignature { validator: vote.validator.clone() })?;
+        endorsed = endorsed.saturating_add(member.stake);
+    }
+    
+    let total = prev_set.total_stake();
+    if total == 0 || u128::from(endorsed) * 3 < u128::from(total) * 2 {
+        return Err(invalid(format!("Votes hold {} of the {} stake of epoch {}, less than two thirds", endorsed, total, prev_set.epoch)));
+    }
+    Ok(proof.validator_set.clone())
+}
\ No newline at end of file
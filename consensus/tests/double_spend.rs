@@ -0,0 +1,143 @@
+//! Checks the mempool never holds more spending from a sender than its balance covers
+//!
+//! Run with `cargo test -p consensus --test double_spend`. Has alice send
+//! overlapping transfers to an engine's mempool: those her confirmed balance
+//! covers together are admitted, and one more that it doesn't is refused.
+//! Then connects a block confirming either one of them, which leaves the
+//! rest funded, or a conflicting spend of most of her balance from another
+//! node, which drops the one it replaced and her latest ones until what's
+//! left is covered again.
+
+use std::sync::{Arc, Mutex};
+
+use consensus::{ConsensusEngine, ConsensusParams};
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::chain::Blockchain;
+use ctb_core::transaction::{Transaction, TransactionType};
+use ctb_core::units::GENX;
+use ctb_core::{Address, TxHash};
+
+/// Seed of the chains built
+const SEED: u64 = 131;
+
+/// Fee of each transfer
+const FEE: u64 = 1_000;
+
+/// An engine on a chain, and a copy of the chain with the same keys to sign and build blocks with
+struct Setup {
+    engine: ConsensusEngine,
+    blockchain: Arc<Mutex<Blockchain>>,
+    signer: TestChain,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let blockchain = Arc::new(Mutex::new(TestChain::new(SEED).into_blockchain()));
+        let engine = ConsensusEngine::new(blockchain.clone(), ConsensusParams::default());
+        Self { engine, blockchain, signer: TestChain::new(SEED) }
+    }
+    
+    /// Makes a transfer from alice with a nonce, signed
+    ///
+    /// It's stamped with the next block's time, so that block can include it as it is.
+    fn transfer(&self, to: &str, amount: u64, nonce: u64) -> Transaction {
+        let (sender, recipient) = (self.signer.address("alice"), self.signer.address(to));
+        let mut tx = Transaction::new_with_type(TransactionType::Transfer, sender, recipient, amount, FEE, None, 0, 0).unwrap();
+        let config = TestChainConfig::default();
+        tx.timestamp = config.genesis_timestamp + (self.signer.height() + 1) * config.block_interval;
+        let mut tx = tx.with_nonce(nonce).unwrap();
+        self.signer.account("alice").sign(&mut tx).unwrap();
+        tx
+    }
+    
+    /// Admits alice's transfers to the mempool, returning their IDs
+    fn send(&mut self, transfers: &[(&str, u64)]) -> Vec<TxHash> {
+        let transactions: Vec<Transaction> = transfers.iter().enumerate().map(|(nonce, (to, amount))| self.transfer(to, *amount, nonce as u64)).collect();
+        for tx in &transactions {
+            self.engine.add_transaction(tx.clone()).unwrap();
+        }
+        transactions.iter().map(|tx| tx.id).collect()
+    }
+    
+    /// Connects the next block, built on the copy, returning the IDs of the transactions it left unfunded or stale
+    fn connect(&mut self, build: impl FnOnce(&mut Self) -> Block) -> Vec<TxHash> {
+        let block = build(self);
+        self.signer.add_block(block.clone());
+        self.blockchain.lock().unwrap().add_block(block.clone()).unwrap();
+        self.engine.on_block_connected(&block).iter().map(|tx| tx.id).collect()
+    }
+    
+    /// Gets alice's pending outflow
+    fn outflow(&self) -> u64 {
+        self.engine.mempool().pending_outflow(&self.signer.address("alice"))
+    }
+    
+    /// Gets alice's confirmed balance
+    fn balance(&self) -> u64 {
+        let alice = Address::new(self.signer.address("alice")).unwrap();
+        self.signer.blockchain().get_balance(&alice).unwrap().base_units()
+    }
+}
+
+/// Checks overlapping sends the balance covers together are admitted, and one more it doesn't is refused
+#[test]
+fn check_overlapping_sends() {
+    let mut setup = Setup::new();
+    let funds = TestChainConfig::default().accounts[0].1;
+    assert_eq!(setup.balance(), funds);
+    
+    let ids = setup.send(&[("bob", 400 * GENX), ("carol", 300 * GENX), ("bob", 200 * GENX)]);
+    assert_eq!(setup.engine.mempool().len(), ids.len());
+    assert_eq!(setup.outflow(), 900 * GENX + 3 * FEE);
+    
+    // Each fits alone, but not on top of the others
+    let overspend = setup.transfer("carol", 100 * GENX, 3);
+    let error = setup.engine.add_transaction(overspend.clone()).unwrap_err().to_string();
+    assert!(error.contains("pending transactions"), "{}", error);
+    assert!(!setup.engine.mempool().contains(&overspend.id));
+    assert_eq!(setup.outflow(), 900 * GENX + 3 * FEE);
+    
+    // What's left fits exactly
+    let rest = setup.transfer("carol", funds - 900 * GENX - 4 * FEE, 3);
+    setup.engine.add_transaction(rest).unwrap();
+    assert_eq!(setup.outflow(), funds);
+}
+
+/// Checks confirming one of the sends leaves the others pending and funded
+#[test]
+fn check_confirmed_send() {
+    let mut setup = Setup::new();
+    let ids = setup.send(&[("bob", 400 * GENX), ("carol", 300 * GENX)]);
+    let first = setup.engine.mempool().get(&ids[0]).unwrap().as_ref().clone();
+    
+    let dropped = setup.connect(|setup| setup.signer.next_block(|b| b.transaction(first)));
+    assert_eq!(setup.signer.blocks().last().unwrap().transactions.iter().filter(|tx| tx.id == ids[0]).count(), 1);
+    assert!(dropped.is_empty(), "{:?}", dropped);
+    assert!(!setup.engine.mempool().contains(&ids[0]));
+    assert!(setup.engine.mempool().contains(&ids[1]));
+    assert_eq!(setup.outflow(), 300 * GENX + FEE);
+    assert!(setup.outflow() <= setup.balance());
+}
+
+/// Checks a conflicting spend confirmed from another node drops what it replaced, and the latest sends it leaves unfunded
+#[test]
+fn check_conflicting_spend() {
+    let mut setup = Setup::new();
+    let ids = setup.send(&[("bob", 400 * GENX), ("carol", 200 * GENX), ("bob", 100 * GENX)]);
+    
+    // The same nonce as the first, spending 750 elsewhere
+    let dropped = setup.connect(|setup| setup.signer.next_block(|b| b.transfer("alice", "carol", 750 * GENX)));
+    let left = setup.balance();
+    assert!((200 * GENX + FEE..300 * GENX + 2 * FEE).contains(&left), "{}", left);
+    
+    // The replaced one goes as stale, then the latest until the rest is covered
+    assert_eq!(dropped, vec![ids[0], ids[2]]);
+    assert_eq!(setup.engine.mempool().len(), 1);
+    assert!(setup.engine.mempool().contains(&ids[1]));
+    assert_eq!(setup.outflow(), 200 * GENX + FEE);
+    
+    // Past what's left, a new send is refused
+    let error = setup.engine.add_transaction(setup.transfer("bob", left, 2)).unwrap_err().to_string();
+    assert!(error.contains("pending transactions"), "{}", error);
+}
\ No newline at end of file
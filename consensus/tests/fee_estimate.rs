@@ -0,0 +1,198 @@
+//! Checks the mempool's fee histogram follows its transactions, and inclusion estimates from it wait for those paying more
+//!
+//! Run with `cargo test -p consensus --test fee_estimate`. Fills a mempool
+//! with strata of contract calls at three gas prices and transfers at two
+//! flat fees, each from its own sender, and checks the histogram holds
+//! each stratum in its bucket, metered and highest rates first. It keeps
+//! matching a histogram counted afresh as transactions are removed and
+//! evicted. Then checks how many blocks a rate in or above each stratum is
+//! estimated to wait, against the gas and transactions ahead of it, and
+//! against a base fee falling under empty blocks or holding at the target.
+
+use consensus::mempool::Mempool;
+use ctb_core::fee_market::{estimate_inclusion, gas_target, BlockSpace, FeeBucket, FeeHistogram, FeeRate, MAX_ESTIMATED_BLOCKS};
+use ctb_core::transaction::{Transaction, TransactionType};
+
+/// Gas limit of each block
+const GAS_LIMIT: u64 = 10_000_000;
+
+/// Most transactions a block includes besides its coinbase
+const MAX_TRANSACTIONS: u64 = 1000;
+
+/// Base fee of the next block
+const BASE_FEE: u64 = 10;
+
+/// Gas each contract call reserves
+const CALL_GAS: u64 = 200_000;
+
+/// Call data of each contract call, a function selector
+const SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+/// Contract calls in the pool, as their gas price and number
+const CALLS: [(u64, u64); 3] = [(100, 30), (50, 100), (20, 20)];
+
+/// Transfers in the pool, as their flat fee and number
+const TRANSFERS: [(u64, u64); 2] = [(5_000, 3_850), (1_000, 1_000)];
+
+/// Creates a contract call from a sender of its own paying a gas price
+fn call(index: u64, gas_price: u64) -> Transaction {
+    Transaction::new_contract_call(format!("GENX_CALLER_{}", index), "GENX_CONTRACT".to_string(), 0, SELECTOR.to_vec(), CALL_GAS, gas_price)
+        .unwrap()
+}
+
+/// Creates a transfer from a sender of its own paying a flat fee
+fn transfer(index: u64, fee: u64) -> Transaction {
+    Transaction::new_with_type(
+        TransactionType::Transfer,
+        format!("GENX_SENDER_{}", index),
+        "GENX_SHOP".to_string(),
+        1,
+        fee,
+        None,
+        0,
+        0,
+    )
+    .unwrap()
+}
+
+/// Fills a mempool with every stratum
+fn stratified() -> Mempool {
+    let mut mempool = Mempool::new(10_000);
+    let mut index = 0;
+    for (gas_price, count) in CALLS {
+        for _ in 0..count {
+            mempool.insert(call(index, gas_price)).unwrap();
+            index += 1;
+        }
+    }
+    for (fee, count) in TRANSFERS {
+        for _ in 0..count {
+            mempool.insert(transfer(index, fee)).unwrap();
+            index += 1;
+        }
+    }
+    mempool
+}
+
+/// Counts a mempool's transactions into a histogram afresh
+fn recounted(mempool: &Mempool) -> FeeHistogram {
+    let mut histogram = FeeHistogram::new();
+    for tx in mempool.iter() {
+        histogram.add(tx);
+    }
+    histogram
+}
+
+/// Block space of blocks that lately used a given amount of gas
+fn space(gas_used: u64) -> BlockSpace {
+    BlockSpace { gas_limit: GAS_LIMIT, max_transactions: MAX_TRANSACTIONS, next_base_fee: BASE_FEE, recent_gas_used: vec![gas_used; 5] }
+}
+
+/// Checks every stratum lands in its bucket, and the histogram keeps up as transactions leave
+#[test]
+fn check_histogram() {
+    let mut mempool = stratified();
+    let histogram = mempool.fee_histogram().clone();
+    let bucket = |metered, min_rate, max_rate, transactions: u64| FeeBucket {
+        metered,
+        min_rate,
+        max_rate,
+        transactions,
+        gas: if metered { transactions * CALL_GAS } else { 0 },
+        bytes: if metered { transactions * SELECTOR.len() as u64 } else { 0 },
+    };
+    assert_eq!(
+        histogram.buckets(),
+        [
+            bucket(true, 64, 127, 30),
+            bucket(true, 32, 63, 100),
+            bucket(true, 16, 31, 20),
+            bucket(false, 4_096, 8_191, 3_850),
+            bucket(false, 512, 1_023, 1_000),
+        ]
+    );
+    assert_eq!(histogram.transactions(), mempool.len() as u64);
+    assert_eq!(histogram, recounted(&mempool));
+    
+    // The buckets survive the trip through a node's report
+    let reported: Vec<FeeBucket> = serde_json::from_str(&serde_json::to_string(&histogram).unwrap()).unwrap();
+    assert_eq!(FeeHistogram::from(reported), histogram);
+    
+    // Removing the highest stratum and half of the next empties one bucket and halves another
+    let leaving: Vec<_> = mempool.iter().filter(|tx| tx.gas_price >= 100 || (tx.gas_price == 50 && tx.sender.ends_with('0'))).map(|tx| tx.id).collect();
+    for id in &leaving {
+        mempool.remove(id).unwrap();
+    }
+    let buckets = mempool.fee_histogram().buckets();
+    assert_eq!(buckets[0], bucket(true, 32, 63, 90));
+    assert_eq!(buckets.len(), 4);
+    assert_eq!(*mempool.fee_histogram(), recounted(&mempool));
+    
+    // Shrinking the pool evicts the lowest fees first, and they leave the histogram too
+    let evicted = mempool.set_capacity(mempool.len() - 1_200);
+    assert_eq!(evicted.len(), 1_200);
+    assert_eq!(*mempool.fee_histogram(), recounted(&mempool));
+    assert_eq!(mempool.fee_histogram().buckets().last(), Some(&bucket(false, 4_096, 8_191, 3_650)));
+    
+    // An emptied pool leaves an empty histogram
+    let ids: Vec<_> = mempool.iter().map(|tx| tx.id).collect();
+    for id in &ids {
+        mempool.remove(id);
+    }
+    assert_eq!(*mempool.fee_histogram(), FeeHistogram::new());
+}
+
+/// Checks a rate waits for the gas or transaction count ahead of it, its own bucket included
+#[test]
+fn check_blocks_ahead() {
+    let mempool = stratified();
+    let histogram = mempool.fee_histogram();
+    let at_target = space(gas_target(GAS_LIMIT));
+    let blocks = |rate| estimate_inclusion(histogram, rate, &at_target).blocks;
+    
+    // Above every call, or among the 6M gas of the highest, a call makes the next block
+    let above = estimate_inclusion(histogram, FeeRate::GasPrice(200), &at_target);
+    assert_eq!((above.blocks, above.transactions_ahead, above.gas_ahead), (Some(1), 0, 0));
+    assert_eq!(blocks(FeeRate::GasPrice(100)), Some(1));
+    
+    // Below them, it waits for 26M and then 30M gas to fill blocks of 10M
+    let middle = estimate_inclusion(histogram, FeeRate::GasPrice(50), &at_target);
+    assert_eq!((middle.blocks, middle.transactions_ahead, middle.gas_ahead), (Some(3), 130, 26 * 1_000_000));
+    assert_eq!(blocks(FeeRate::GasPrice(20)), Some(4));
+    
+    // A rate anywhere in a bucket waits for all of it
+    assert_eq!(blocks(FeeRate::GasPrice(63)), blocks(FeeRate::GasPrice(32)));
+    
+    // Transfers come after every call, the calls' 30M gas outlasting their count
+    assert_eq!(blocks(FeeRate::FlatFee(20_000)), Some(4));
+    
+    // Among the transfers their number tells, 1000 to a block
+    let transfers = estimate_inclusion(histogram, FeeRate::FlatFee(5_000), &at_target);
+    assert_eq!((transfers.blocks, transfers.transactions_ahead), (Some(5), 4_000));
+    assert_eq!(blocks(FeeRate::FlatFee(1_000)), Some(6));
+    assert_eq!(blocks(FeeRate::FlatFee(1)), Some(6));
+    
+    // Waits past the horizon aren't estimated
+    let cramped = BlockSpace { gas_limit: 20_000, ..at_target.clone() };
+    assert!(30 * 1_000_000 / cramped.gas_limit > MAX_ESTIMATED_BLOCKS);
+    assert_eq!(estimate_inclusion(histogram, FeeRate::GasPrice(20), &cramped).blocks, None);
+}
+
+/// Checks a gas price under the base fee waits for it to fall, which it does only under emptier blocks
+#[test]
+fn check_base_fee_wait() {
+    let mempool = stratified();
+    let histogram = mempool.fee_histogram();
+    
+    // At the target the base fee holds, so a lower gas price is never included
+    let held = estimate_inclusion(histogram, FeeRate::GasPrice(5), &space(gas_target(GAS_LIMIT)));
+    assert_eq!((held.blocks, held.base_fee_wait), (None, None));
+    
+    // Under empty blocks it falls by 1 a block, 10 to 5, before the 30M gas ahead takes 3 more
+    let falling = estimate_inclusion(histogram, FeeRate::GasPrice(5), &space(0));
+    assert_eq!((falling.blocks, falling.base_fee_wait, falling.gas_ahead), (Some(9), Some(5), 30 * 1_000_000));
+    
+    // A gas price at the base fee, or a flat fee, doesn't wait for it
+    assert_eq!(estimate_inclusion(histogram, FeeRate::GasPrice(BASE_FEE), &space(0)).base_fee_wait, Some(0));
+    assert_eq!(estimate_inclusion(histogram, FeeRate::FlatFee(1_000), &space(GAS_LIMIT)).base_fee_wait, Some(0));
+}
\ No newline at end of file
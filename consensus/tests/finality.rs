@@ -0,0 +1,164 @@
+//! Checks finalizing a checkpoint purges those competing with it, and pending ones stay bounded
+//!
+//! Run with `cargo test -p consensus --test finality`. Opens competing
+//! checkpoints at several heights, finalizes one of them and checks those
+//! at or below it are purged and refuse votes, while those above it keep
+//! collecting. A manager restored from the saved checkpoints has the same
+//! finalized records, and finalized checkpoints past the horizon are kept
+//! only as their height and hash. Then checks votes for bogus heights can't
+//! open more than `max_pending_checkpoints` checkpoints.
+
+use consensus::finality::{FinalityManager, FinalityMemoryStats, FinalizedCheckpoint};
+use consensus::validator::Validator;
+use consensus::{ConsensusError, ConsensusParams};
+use ctb_core::block::Block;
+use ctb_core::units::GENX;
+use ctb_core::{BlockHash, BlockchainError};
+
+/// Blocks between checkpoints
+const INTERVAL: u64 = 100;
+
+/// Blocks below the latest finalized checkpoint that finalized ones keep their votes for
+const HORIZON: u64 = 200;
+
+/// Most checkpoints collecting votes at once
+const MAX_PENDING: usize = 10;
+
+/// Competing blocks at each checkpoint height
+const COMPETING: u8 = 3;
+
+/// A saved finality manager, its file removed once the test is done
+struct Saved {
+    manager: FinalityManager,
+    genesis: BlockHash,
+    path: std::path::PathBuf,
+}
+
+impl Drop for Saved {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Saved {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("genx-finality-{}-{}.json", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        let genesis = Block::genesis(Vec::new(), 0).unwrap();
+        let mut manager = FinalityManager::new(params());
+        manager.initialize_with_genesis(&genesis).unwrap();
+        manager.set_store(path.clone()).unwrap();
+        Self { manager, genesis: genesis.hash().unwrap(), path }
+    }
+    
+    /// Votes for a block from a validator
+    fn vote(&mut self, height: u64, block: u8, validator: &str) -> ctb_core::Result<bool> {
+        self.manager.add_checkpoint_vote(height, hash(height, block), &Validator::new(validator.to_string(), 1_000 * GENX))
+    }
+    
+    /// Finalizes a block with two votes
+    fn finalize(&mut self, height: u64, block: u8) {
+        self.vote(height, block, "GENX_A").unwrap();
+        assert!(self.vote(height, block, "GENX_B").unwrap());
+    }
+}
+
+/// Parameters with a short horizon and few pending checkpoints
+fn params() -> ConsensusParams {
+    ConsensusParams {
+        checkpoint_interval: INTERVAL,
+        finality_horizon: HORIZON,
+        max_pending_checkpoints: MAX_PENDING,
+        ..ConsensusParams::default()
+    }
+}
+
+/// Hash of one of the competing blocks at a height
+fn hash(height: u64, block: u8) -> BlockHash {
+    let mut hash = [block; 32];
+    hash[..8].copy_from_slice(&height.to_be_bytes());
+    BlockHash(hash)
+}
+
+/// Checks an error is a consensus error
+fn is(error: &BlockchainError, expected: ConsensusError) -> bool {
+    matches!(error, BlockchainError::Consensus { code, .. } if *code == expected.error_code())
+}
+
+/// Checks finalizing a checkpoint purges those competing with it at or below its height, and survives a restart
+#[test]
+fn check_competing_purged() {
+    let mut saved = Saved::new("competing");
+    for height in [INTERVAL, 2 * INTERVAL, 3 * INTERVAL] {
+        for block in 0..COMPETING {
+            assert!(!saved.vote(height, block, "GENX_A").unwrap());
+        }
+    }
+    let opened = FinalityMemoryStats { pending_checkpoints: 9, recent_finalized: 1, finalized_records: 1, votes: 10 };
+    assert_eq!(saved.manager.memory_stats(), opened);
+    
+    // Only the checkpoints above the finalized one are left pending
+    saved.finalize(2 * INTERVAL, 1);
+    let finalized = FinalityMemoryStats { pending_checkpoints: 3, recent_finalized: 2, finalized_records: 2, votes: 6 };
+    assert_eq!(saved.manager.memory_stats(), finalized);
+    assert!(saved.manager.get_checkpoint(2 * INTERVAL, &hash(2 * INTERVAL, 0)).is_none());
+    assert!(saved.manager.get_checkpoint(INTERVAL, &hash(INTERVAL, 1)).is_none());
+    assert!(saved.manager.get_checkpoint(3 * INTERVAL, &hash(3 * INTERVAL, 2)).is_some());
+    
+    // Purged checkpoints take no more votes, nor do new ones at their heights
+    for (height, block) in [(2 * INTERVAL, 0), (INTERVAL, 1), (INTERVAL, 7)] {
+        let error = saved.vote(height, block, "GENX_C").unwrap_err();
+        assert!(is(&error, ConsensusError::CheckpointMismatch { height }), "{:?}", error);
+    }
+    assert!(saved.vote(2 * INTERVAL, 1, "GENX_C").unwrap());
+    assert_eq!(saved.manager.memory_stats().votes, 7);
+    
+    // The finalized records survive a restart, without the purged ones
+    let expected = vec![
+        FinalizedCheckpoint { height: 0, block_hash: saved.genesis },
+        FinalizedCheckpoint { height: 2 * INTERVAL, block_hash: hash(2 * INTERVAL, 1) },
+    ];
+    let mut restored = FinalityManager::new(params());
+    restored.set_store(saved.path.clone()).unwrap();
+    assert_eq!(restored.finalized_checkpoints().collect::<Vec<_>>(), expected);
+    assert_eq!(restored.get_latest_finalized_height(), 2 * INTERVAL);
+    assert!(restored.is_finalized(2 * INTERVAL - 1));
+    assert_eq!(restored.memory_stats(), FinalityMemoryStats { finalized_records: 2, ..Default::default() });
+    
+    // Those above kept collecting, and past the horizon finalized checkpoints are only their height and hash
+    assert!(saved.vote(3 * INTERVAL, 2, "GENX_C").unwrap());
+    assert_eq!(saved.manager.memory_stats(), FinalityMemoryStats { pending_checkpoints: 0, recent_finalized: 2, finalized_records: 3, votes: 5 });
+    saved.finalize(5 * INTERVAL, 0);
+    let stats = saved.manager.memory_stats();
+    assert_eq!((stats.pending_checkpoints, stats.recent_finalized, stats.finalized_records), (0, 2, 4));
+    
+    // A compacted checkpoint still takes votes, without keeping them
+    assert!(saved.manager.get_checkpoint(2 * INTERVAL, &hash(2 * INTERVAL, 1)).is_none());
+    assert!(saved.vote(2 * INTERVAL, 1, "GENX_D").unwrap());
+    assert_eq!(saved.manager.memory_stats(), stats);
+}
+
+/// Checks votes at bogus heights can't open more than `MAX_PENDING` checkpoints
+#[test]
+fn check_pending_bounded() {
+    let mut saved = Saved::new("bounded");
+    let heights: Vec<u64> = (1..=MAX_PENDING as u64).map(|checkpoint| checkpoint * 1_000 * INTERVAL).collect();
+    for &height in &heights {
+        saved.vote(height, 0, "GENX_SPAMMER").unwrap();
+    }
+    assert_eq!(saved.manager.memory_stats().pending_checkpoints, MAX_PENDING);
+    
+    // Higher still is refused
+    let highest = heights[MAX_PENDING - 1];
+    let error = saved.vote(highest + INTERVAL, 0, "GENX_SPAMMER").unwrap_err();
+    assert!(is(&error, ConsensusError::TooManyCheckpoints { height: highest + INTERVAL }), "{:?}", error);
+    let error = saved.vote(highest, 1, "GENX_SPAMMER").unwrap_err();
+    assert!(is(&error, ConsensusError::TooManyCheckpoints { height: highest }), "{:?}", error);
+    
+    // The next honest checkpoint displaces the highest
+    saved.vote(INTERVAL, 0, "GENX_A").unwrap();
+    assert_eq!(saved.manager.memory_stats().pending_checkpoints, MAX_PENDING);
+    assert!(saved.manager.get_checkpoint(highest, &hash(highest, 0)).is_none());
+    saved.finalize(INTERVAL, 0);
+    assert_eq!(saved.manager.memory_stats().pending_checkpoints, MAX_PENDING - 1);
+}
\ No newline at end of file
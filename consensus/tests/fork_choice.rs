@@ -0,0 +1,160 @@
+//! Checks competing branches are chosen by the stake behind them
+//!
+//! Run with `cargo test -p consensus --test fork_choice`. Builds a chain
+//! with a validator staking little and one staking much, forks it after
+//! block 1 into a long branch of the light validator's blocks and shorter
+//! ones of the heavy validator's, and checks the heavier branch wins, the
+//! lighter one can't win back, ties go to the lower tip hash and no branch
+//! replaces a finalized block, however heavy. Blocks whose header the
+//! validator it names didn't sign, or naming a validator that isn't
+//! active, weigh nothing.
+
+use std::sync::{Arc, Mutex};
+
+use consensus::finality::FinalityManager;
+use consensus::fork_choice::{BranchWeight, ForkChoice};
+use consensus::validator::Validator;
+use consensus::ConsensusParams;
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::units::GENX;
+use ctb_core::{BlockHash, BlockchainError};
+
+/// Seed of the chain built
+const SEED: u64 = 11;
+
+/// Height both branches fork from
+const FORK: u64 = 1;
+
+/// Stake of the light validator
+const LIGHT_STAKE: u64 = 10 * GENX;
+
+/// Stake of the heavy validator
+const HEAVY_STAKE: u64 = 1_000 * GENX;
+
+/// The chain on the light branch, with the branches built off it
+struct Forks {
+    chain: TestChain,
+    finality: Arc<Mutex<FinalityManager>>,
+    /// Two blocks of the heavy validator
+    heavy: Vec<Arc<Block>>,
+    /// Three blocks of the heavy validator
+    heavier: Vec<Arc<Block>>,
+    /// Five blocks of the light validator, the chain's current branch
+    light: Vec<Arc<Block>>,
+}
+
+/// Parameters making both validators active, and checkpoints every other block
+fn params() -> ConsensusParams {
+    ConsensusParams { checkpoint_interval: 2, min_stake: LIGHT_STAKE, ..ConsensusParams::default() }
+}
+
+fn build() -> Forks {
+    let config = TestChainConfig {
+        validators: vec![("light".to_string(), LIGHT_STAKE), ("heavy".to_string(), HEAVY_STAKE)],
+        ..TestChainConfig::default()
+    };
+    let mut chain = TestChain::with_config(SEED, config);
+    let finality = Arc::new(Mutex::new(FinalityManager::new(params())));
+    chain.blockchain_mut().set_fork_choice(Arc::new(ForkChoice::new(Arc::clone(&finality))));
+    chain.with_block(|b| b.proposed_by("light"));
+    
+    // Two blocks of the heavy validator, three more, then five of the light one
+    let heavy = branch(&mut chain, "heavy", 2);
+    chain.blockchain_mut().rollback_to(FORK).unwrap();
+    chain.with_block(|b| b.proposed_by("heavy").transfer("alice", "bob", GENX));
+    let mut heavier = vec![chain.blockchain().get_shared_block(FORK + 1).unwrap()];
+    heavier.extend(branch(&mut chain, "heavy", 2));
+    chain.blockchain_mut().rollback_to(FORK).unwrap();
+    let light = branch(&mut chain, "light", 5);
+    Forks { chain, finality, heavy, heavier, light }
+}
+
+/// Adds blocks proposed by a validator, returning them
+fn branch(chain: &mut TestChain, validator: &str, count: u64) -> Vec<Arc<Block>> {
+    (0..count)
+        .map(|_| {
+            chain.with_block(|b| b.proposed_by(validator));
+            chain.blockchain().get_shared_block(chain.height()).unwrap()
+        })
+        .collect()
+}
+
+/// Checks the shorter branch with more stake replaces the longer one, which can't win back
+#[test]
+fn check_heavier_wins() {
+    let Forks { mut chain, heavy, light, .. } = build();
+    assert_eq!(chain.height(), FORK + 5);
+    assert!(chain.blockchain().prefers_branch(FORK, &heavy).unwrap(), "the heavier branch isn't preferred");
+    
+    let (record, _) = chain.blockchain_mut().reorganize(FORK, heavy.to_vec()).unwrap();
+    assert_eq!(record.depth, 5);
+    assert_eq!(chain.height(), FORK + 2);
+    let tip = heavy.last().unwrap().hash().unwrap();
+    assert_eq!(chain.blockchain().get_latest_block().unwrap().hash().unwrap(), tip);
+    
+    let error = chain.blockchain_mut().reorganize(FORK, light.to_vec()).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::BranchNotPreferred { fork_height: FORK }), "{}", error);
+    assert_eq!(error.error_code(), 1021);
+    assert_eq!(chain.blockchain().get_latest_block().unwrap().hash().unwrap(), tip);
+}
+
+/// Checks branches as heavy are ordered by their tips' hashes, the lower winning
+#[test]
+fn check_tie() {
+    let Forks { chain, heavy, .. } = build();
+    let stakes = chain.blockchain().state_at(FORK).unwrap();
+    let weight = BranchWeight::of(&stakes.state, &params().validator_selection(), [heavy[0].as_ref()]).unwrap();
+    assert_eq!(weight.weight, u128::from(HEAVY_STAKE));
+    
+    let higher = BranchWeight { weight: weight.weight, tip_hash: BlockHash([0xff; 32]) };
+    let lower = BranchWeight { weight: weight.weight, tip_hash: BlockHash([0; 32]) };
+    assert!(weight.beats(&higher) && !higher.beats(&weight));
+    assert!(lower.beats(&weight) && !weight.beats(&lower));
+    assert!(!weight.beats(&weight));
+}
+
+/// Checks a heavier branch forking below the finalized height is refused
+#[test]
+fn check_finalized() {
+    let Forks { mut chain, finality, heavy, heavier, .. } = build();
+    chain.blockchain_mut().reorganize(FORK, heavy.clone()).unwrap();
+    let finalized = heavy[0].hash().unwrap();
+    for voter in ["a", "b"] {
+        let validator = Validator { address: voter.to_string(), stake: 1, consensus_key: String::new(), last_block_produced: 0 };
+        finality.lock().unwrap().add_checkpoint_vote(FORK + 1, finalized, &validator).unwrap();
+    }
+    assert_eq!(finality.lock().unwrap().get_latest_finalized_height(), FORK + 1);
+    
+    let tip = chain.blockchain().get_latest_block().unwrap().hash().unwrap();
+    let error = chain.blockchain_mut().reorganize(FORK, heavier).unwrap_err();
+    assert!(
+        matches!(error.root(), BlockchainError::Consensus { code: 2006, message } if message.contains("finalized")),
+        "{}",
+        error
+    );
+    assert_eq!(chain.blockchain().get_latest_block().unwrap().hash().unwrap(), tip);
+}
+
+/// Checks a block weighs nothing unless an active validator signed it
+#[test]
+fn check_forged() {
+    let Forks { chain, heavy, .. } = build();
+    let stakes = chain.blockchain().state_at(FORK).unwrap();
+    let selection = params().validator_selection();
+    let weigh = |block: &Block| BranchWeight::of(&stakes.state, &selection, [block]).unwrap().weight;
+    assert_eq!(weigh(&heavy[0]), u128::from(HEAVY_STAKE));
+    
+    // Signed by the light validator in the heavy one's name
+    let mut forged = heavy[0].as_ref().clone();
+    chain.account("light").sign_header(forged.header_mut()).unwrap();
+    assert_eq!(weigh(&forged), 0);
+    
+    let mut unsigned = heavy[0].as_ref().clone();
+    unsigned.header_mut().signature = None;
+    assert_eq!(weigh(&unsigned), 0);
+    
+    // Signed, but by a validator staking too little to be active
+    let selection = ConsensusParams { min_stake: HEAVY_STAKE + 1, ..params() }.validator_selection();
+    assert_eq!(BranchWeight::of(&stakes.state, &selection, [heavy[0].as_ref()]).unwrap().weight, 0);
+}
\ No newline at end of file
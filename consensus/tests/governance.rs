@@ -0,0 +1,120 @@
+//! Checks governance proposals change chain parameters at their activation height, or not at all
+//!
+//! Run with `cargo test -p consensus --test governance`. Builds a chain of
+//! three validators staking the same, where alice proposes a longer block
+//! time and validators vote on it. Two of them approving passes it: the
+//! block time as governed stays the configured one until the activation
+//! height, and an engine producing the block at that height waits the new
+//! block time for it. One of them voting alone misses quorum, which burns
+//! alice's deposit and leaves the parameters untouched.
+
+use std::sync::{Arc, Mutex};
+
+use consensus::{ConsensusEngine, ConsensusParams};
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::governance::{GovernedParameter, ProposalStatus, ProposalSubmission, ProposalVote};
+use ctb_core::transaction::Transaction;
+use ctb_core::units::GENX;
+use ctb_core::Address;
+
+/// Seed of the chains built
+const SEED: u64 = 173;
+
+/// Deposit of the proposal
+const DEPOSIT: u64 = 100 * GENX;
+
+/// Block time proposed, longer than the configured one
+const BLOCK_TIME: u64 = 12;
+
+/// Blocks voting lasts
+const VOTING_PERIOD: u64 = 3;
+
+/// Height the proposal is submitted at
+const SUBMITTED_AT: u64 = 2;
+
+/// Height the change activates at, a few blocks after voting ends
+const ACTIVATION_HEIGHT: u64 = SUBMITTED_AT + VOTING_PERIOD + 3;
+
+/// Builds a chain where the validators are funded, alice proposes `BLOCK_TIME` and `voters` approve it, up to the block before activation
+fn vote(voters: &[&str]) -> TestChain {
+    let validators = ["v1", "v2", "v3"].iter().map(|name| (name.to_string(), 1_000 * GENX)).collect();
+    let mut chain = TestChain::with_config(SEED, TestChainConfig { validators, ..TestChainConfig::default() });
+    let fee = chain.config().transfer_fee;
+    chain.with_block(|b| b.transfer("alice", "v1", GENX).transfer("alice", "v2", GENX).transfer("alice", "v3", GENX));
+    
+    let submission = ProposalSubmission {
+        parameter: GovernedParameter::BlockTime,
+        value: BLOCK_TIME,
+        voting_period: VOTING_PERIOD,
+        activation_height: ACTIVATION_HEIGHT,
+    };
+    let proposal = Transaction::new_submit_proposal(chain.address("alice"), &submission, DEPOSIT, fee).unwrap();
+    chain.with_block(|b| b.transaction(proposal));
+    assert_eq!(chain.height(), SUBMITTED_AT);
+    
+    let votes: Vec<Transaction> = voters.iter().map(|voter| {
+        Transaction::new_vote(chain.address(voter), &ProposalVote { proposal_id: 1, approve: true }, fee).unwrap()
+    }).collect();
+    chain.with_block(|b| {
+        for vote in votes {
+            b.transaction(vote);
+        }
+        b
+    });
+    chain.with_empty_blocks(ACTIVATION_HEIGHT - 1 - chain.height());
+    chain
+}
+
+/// Gets alice's balance
+fn alice_balance(chain: &TestChain) -> u64 {
+    chain.blockchain().get_balance(&Address::new(chain.address("alice")).unwrap()).unwrap().base_units()
+}
+
+/// Gets the block time as governed for the block at a height
+fn block_time(chain: &TestChain, height: u64) -> u64 {
+    let state = chain.blockchain().get_state();
+    let state = state.lock().unwrap();
+    ConsensusParams::default().governed(&state, height).block_time
+}
+
+/// Checks a passed proposal changes the block time from its activation height, and the engine paces the block there by it
+#[test]
+fn check_passed() {
+    let chain = vote(&["v1", "v2"]);
+    let proposal = chain.blockchain().get_state().lock().unwrap().get_proposal(1).cloned().unwrap();
+    assert_eq!(proposal.status, ProposalStatus::Passed);
+    assert_eq!(proposal.votes.len(), 2);
+    assert_eq!(alice_balance(&chain), chain.expected_balance("alice") + DEPOSIT, "the deposit is refunded");
+    
+    let configured = ConsensusParams::default().block_time;
+    assert_ne!(configured, BLOCK_TIME);
+    assert_eq!(block_time(&chain, ACTIVATION_HEIGHT - 1), configured);
+    assert_eq!(block_time(&chain, ACTIVATION_HEIGHT), BLOCK_TIME);
+    
+    // The block at the activation height waits the new block time after the one before
+    let tip_time = chain.blocks().last().unwrap().header().timestamp;
+    let mut engine = ConsensusEngine::new(Arc::new(Mutex::new(chain.into_blockchain())), ConsensusParams::default());
+    engine.initialize().unwrap();
+    assert!(engine.try_produce_block_at(tip_time + configured).unwrap().is_none());
+    assert!(engine.try_produce_block_at(tip_time + BLOCK_TIME - 1).unwrap().is_none());
+    let block = engine.try_produce_block_at(tip_time + BLOCK_TIME).unwrap().unwrap();
+    assert_eq!(block.header().height, ACTIVATION_HEIGHT);
+}
+
+/// Checks a proposal missing quorum burns its deposit and changes nothing
+#[test]
+fn check_no_quorum() {
+    let mut chain = vote(&["v3"]);
+    let proposal = chain.blockchain().get_state().lock().unwrap().get_proposal(1).cloned().unwrap();
+    assert_eq!(proposal.status, ProposalStatus::NoQuorum);
+    assert_eq!(alice_balance(&chain), chain.expected_balance("alice"), "the deposit is burned");
+    
+    chain.with_empty_blocks(2);
+    let configured = ConsensusParams::default().block_time;
+    for height in [ACTIVATION_HEIGHT - 1, ACTIVATION_HEIGHT, chain.height() + 1] {
+        assert_eq!(block_time(&chain, height), configured);
+    }
+    let state = chain.blockchain().get_state();
+    let state = state.lock().unwrap();
+    assert!(GovernedParameter::ALL.iter().all(|parameter| state.governed_value(*parameter, u64::MAX).is_none()));
+}
\ No newline at end of file
@@ -0,0 +1,111 @@
+//! Checks blocks must order their transactions canonically, and the blocks produced always do
+//!
+//! Run with `cargo test -p consensus --test ordering`. With the ordering
+//! rule active from the first block, builds a block of two transfers paying
+//! the same fee with the higher ID first, re-signed by its proposer, and
+//! checks the chain refuses it while taking the same transfers the other
+//! way round. Then has a validator's engine produce blocks from a mempool
+//! of transfers paying mixed and equal fees, one of them waiting on its
+//! sender's earlier nonce, and checks every block produced is in the
+//! canonical order and added.
+
+use std::sync::{Arc, Mutex};
+
+use consensus::signer::LocalSigner;
+use consensus::{ConsensusEngine, ConsensusParams};
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::ordering;
+use ctb_core::transaction::{Transaction, TransactionType};
+use ctb_core::units::GENX;
+use ctb_core::BlockchainError;
+
+/// Seed of the chains built
+const SEED: u64 = 157;
+
+/// Height the ordering rule is active from
+const ORDERING_HEIGHT: u64 = 1;
+
+/// Creates a chain with the ordering rule active
+fn ordered_chain() -> TestChain {
+    let mut chain = TestChain::new(SEED);
+    chain.blockchain_mut().set_transaction_ordering_height(Some(ORDERING_HEIGHT));
+    chain
+}
+
+/// Rebuilds a block with its transactions in another order, signed again by its proposer
+fn reordered(chain: &TestChain, block: &Block, transactions: Vec<Transaction>) -> Block {
+    let header = block.header();
+    let mut reordered = Block::new(header.height, header.prev_hash, transactions, header.validator.clone(), header.base_fee).unwrap();
+    let new_header = reordered.header_mut();
+    new_header.timestamp = header.timestamp;
+    new_header.validator_set_hash = header.validator_set_hash;
+    let proposer = chain.validators().iter().find(|validator| validator.address == header.validator).unwrap();
+    proposer.sign_header(new_header).unwrap();
+    reordered
+}
+
+/// Checks a block with two transfers paying the same fee in descending ID order is refused
+#[test]
+fn check_equal_fees_by_id() {
+    let mut chain = ordered_chain();
+    let block = chain.next_block(|b| b.transfer("alice", "carol", GENX).transfer("bob", "carol", GENX));
+    let count = block.transactions.len();
+    let (first, second) = (&block.transactions[count - 2], &block.transactions[count - 1]);
+    assert_eq!(first.fee, second.fee);
+    assert!(first.id < second.id, "the builder orders them lowest ID first");
+    ordering::check_order(&block.transactions).unwrap();
+    
+    // Swapped, the block is refused whoever signs it
+    let mut swapped = block.transactions.clone();
+    swapped.swap(count - 2, count - 1);
+    let wrong = reordered(&chain, &block, swapped);
+    let error = chain.blockchain_mut().add_block(wrong).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::InvalidBlock(message) if message.contains("must come before")), "{:?}", error);
+    assert_eq!(chain.height(), 0);
+    
+    // The canonical order is taken, re-signed all the same
+    let right = reordered(&chain, &block, block.transactions.clone());
+    chain.add_block(right);
+    assert_eq!(chain.height(), 1);
+    chain.assert_balances();
+}
+
+/// Checks every block a validator's engine produces from a mixed mempool is in the canonical order
+#[test]
+fn check_produced_validate() {
+    let keys = ordered_chain();
+    let blockchain = Arc::new(Mutex::new(ordered_chain().into_blockchain()));
+    let params = ConsensusParams { transaction_ordering_height: Some(ORDERING_HEIGHT), ..ConsensusParams::default() };
+    let block_time = params.block_time;
+    let validator = &keys.validators()[0];
+    let mut engine = ConsensusEngine::new(blockchain.clone(), params);
+    engine.set_signer(Arc::new(LocalSigner::new(validator.scheme, validator.secret_key.clone()).unwrap()));
+    engine.set_local_validator(validator.address.to_string());
+    engine.initialize().unwrap();
+    
+    // Mixed fees, two pairs of them equal, and alice's best paying one waiting on her first
+    let genesis_time = keys.config().genesis_timestamp;
+    let transfer = |from: &str, fee: u64, nonce: u64| {
+        let mut tx = Transaction::new_with_type(TransactionType::Transfer, keys.address(from), keys.address("carol"), GENX, fee, None, 0, 0).unwrap();
+        tx.timestamp = genesis_time;
+        let mut tx = tx.with_nonce(nonce).unwrap();
+        keys.account(from).sign(&mut tx).unwrap();
+        tx
+    };
+    let transfers = [transfer("alice", 2_000, 0), transfer("bob", 2_000, 0), transfer("carol", 1_000, 0), transfer("bob", 1_000, 1), transfer("alice", 9_000, 1), transfer("carol", 3_000, 1)];
+    for tx in &transfers {
+        engine.add_transaction(tx.clone()).unwrap();
+    }
+    
+    let mut included = 0;
+    for slot in 1..=3 {
+        let block = engine.try_produce_block_at(genesis_time + slot * block_time).unwrap().unwrap();
+        ordering::check_order(&block.transactions).unwrap_or_else(|e| panic!("block {}: {}", slot, e));
+        included += block.transactions.iter().filter(|tx| transfers.iter().any(|transfer| transfer.id == tx.id)).count();
+        blockchain.lock().unwrap().add_block(block.clone()).unwrap();
+        engine.on_block_connected(&block);
+    }
+    assert_eq!(included, transfers.len());
+    assert!(engine.mempool().is_empty());
+}
\ No newline at end of file
@@ -0,0 +1,138 @@
+//! Checks block production is paced by chain time and missed slots follow from the chain
+//!
+//! Run with `cargo test -p consensus --test slots`. Steps a validator's
+//! engine through the wall clock a second at a time, restarting it after
+//! several slots of downtime mid-epoch, and checks it never produces a
+//! block before `block_time` has passed since the latest one, nor more
+//! than one to catch up. Then builds a chain of three validators that
+//! skips some slots and checks a node following it block by block and one
+//! replaying it later charge the same proposers with the same missed slots.
+
+use std::sync::{Arc, Mutex};
+
+use consensus::pos::PoSConsensus;
+use consensus::signer::LocalSigner;
+use consensus::slots::{self, SlotClock, SLOTS_PER_EPOCH};
+use consensus::validator::Validator;
+use consensus::{ConsensusEngine, ConsensusParams};
+use ctb_core::chain::Blockchain;
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::units::GENX;
+
+/// Seed of the chains built
+const SEED: u64 = 89;
+
+/// Blocks the chain has when the validator first starts
+const START_HEIGHT: u64 = 30;
+
+/// Seconds the validator runs before and after its restart
+const RUN_SECONDS: u64 = 60;
+
+/// Seconds the validator is down, several slots and a bit
+const DOWNTIME: u64 = 7 * 5 + 3;
+
+/// Slots walked by the chain of three validators, the last one producing a block
+const SLOTS: u64 = 151;
+
+/// Creates an engine producing the blocks of the chain's only validator, as a node starting up would
+fn engine(chain: &TestChain, blockchain: &Arc<Mutex<Blockchain>>) -> ConsensusEngine {
+    let validator = &chain.validators()[0];
+    let mut engine = ConsensusEngine::new(blockchain.clone(), ConsensusParams::default());
+    engine.set_signer(Arc::new(LocalSigner::new(validator.scheme, validator.secret_key.clone()).unwrap()));
+    engine.set_local_validator(validator.address.to_string());
+    engine.initialize().unwrap();
+    engine
+}
+
+/// Steps an engine through the seconds from `from` on, adding the blocks it produces, and returns their timestamps
+fn run(engine: &mut ConsensusEngine, blockchain: &Arc<Mutex<Blockchain>>, from: u64, seconds: u64) -> Vec<u64> {
+    let mut produced = Vec::new();
+    for now in from..from + seconds {
+        if let Some(block) = engine.try_produce_block_at(now).unwrap() {
+            assert_eq!(block.header().timestamp, now);
+            blockchain.lock().unwrap().add_block(block.clone()).unwrap();
+            engine.on_block_connected(&block);
+            produced.push(now);
+        }
+    }
+    produced
+}
+
+/// Checks a validator restarted after downtime produces one block straight away, then one a slot
+#[test]
+fn check_restart_no_burst() {
+    let mut chain = TestChain::new(SEED);
+    chain.with_empty_blocks(START_HEIGHT);
+    let block_time = ConsensusParams::default().block_time;
+    let tip_time = chain.blocks().last().unwrap().header().timestamp;
+    let keys = TestChain::new(SEED);
+    let blockchain = Arc::new(Mutex::new(chain.into_blockchain()));
+    
+    let mut first = engine(&keys, &blockchain);
+    let before = run(&mut first, &blockchain, tip_time, RUN_SECONDS);
+    let expected: Vec<u64> = (1..=RUN_SECONDS / block_time).map(|slot| tip_time + slot * block_time).filter(|&time| time < tip_time + RUN_SECONDS).collect();
+    assert_eq!(before, expected);
+    drop(first);
+    
+    // Mid-epoch, not on an epoch's boundary
+    let clock = ConsensusParams::default().slot_clock(&blockchain.lock().unwrap());
+    let restart = *before.last().unwrap() + DOWNTIME;
+    assert_ne!(clock.slot_at(restart) % SLOTS_PER_EPOCH, 0);
+    
+    let mut restarted = engine(&keys, &blockchain);
+    let after = run(&mut restarted, &blockchain, restart, RUN_SECONDS);
+    assert_eq!(after[0], restart, "the first slot after the downtime is taken straight away");
+    for pair in after.windows(2) {
+        assert_eq!(pair[1] - pair[0], block_time, "blocks {:?} are a block time apart", pair);
+    }
+    assert_eq!(after.len() as u64, RUN_SECONDS.div_ceil(block_time));
+}
+
+/// Gets the registered validators, as a node tracks them
+fn registered(chain: &TestChain) -> Vec<Validator> {
+    let state = chain.blockchain().get_state();
+    let state = state.lock().unwrap();
+    state.get_validators().into_iter().map(|(info, stake)| Validator::from_registry(info, stake)).collect()
+}
+
+/// Checks two nodes, one following the chain and one replaying it, charge the slots the chain skipped to the same proposers
+#[test]
+fn check_missed_slots_agree() {
+    let config = TestChainConfig {
+        validators: vec![("v1".to_string(), 1_000 * GENX), ("v2".to_string(), 2_000 * GENX), ("v3".to_string(), 3_000 * GENX)],
+        ..TestChainConfig::default()
+    };
+    let mut chain = TestChain::with_config(SEED, config);
+    let clock: SlotClock = ConsensusParams::default().slot_clock(chain.blockchain());
+    let mut following = PoSConsensus::new(ConsensusParams::default());
+    following.update_validator_set(registered(&chain));
+    
+    let mut skipped = Vec::new();
+    let mut charged_following = Vec::new();
+    for slot in 1..=SLOTS {
+        let proposer = slots::select_proposer(following.get_active_validators(), slot).unwrap().address.clone();
+        if slot % 6 == 0 || (40..47).contains(&slot) {
+            skipped.push((slot, proposer));
+            continue;
+        }
+        let parent_time = chain.blocks().last().unwrap().header().timestamp;
+        chain.with_block(|b| b.at(clock.slot_start(slot)).proposed_by(&proposer));
+        charged_following.extend(following.record_block(&clock, parent_time, chain.blocks().last().unwrap()));
+    }
+    assert_eq!(charged_following, skipped);
+    
+    let mut replaying = PoSConsensus::new(ConsensusParams::default());
+    replaying.update_validator_set(registered(&chain));
+    let blocks = chain.blocks();
+    let charged_replaying: Vec<(u64, String)> = blocks
+        .windows(2)
+        .flat_map(|pair| replaying.record_block(&clock, pair[0].header().timestamp, pair[1]))
+        .collect();
+    assert_eq!(charged_replaying, skipped);
+    
+    for (address, metrics) in following.get_validator_metrics() {
+        let replayed = &replaying.get_validator_metrics()[address];
+        assert_eq!(replayed.blocks_missed(), metrics.blocks_missed(), "{}", address);
+        assert_eq!(replayed.blocks_produced(), metrics.blocks_produced(), "{}", address);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,250 @@
+//! Checks validator uptime is derived exactly from the gaps between blocks
+//!
+//! Run with `cargo test -p consensus --test uptime`. Builds a chain of three
+//! validators with different stakes by walking its slots in order: each
+//! slot's proposer produces a block in it unless the plan has the slot
+//! pass without one. The plan takes one validator offline for an epoch,
+//! skips every seventh slot and halts the chain for longer than an epoch.
+//! Each check builds the chain, then checks every validator's produced and missed slots and streaks
+//! against the plan, that cached epochs agree with walking the blocks and
+//! are replaced when their blocks are, and that validators are jailed for
+//! the epoch after one they fell short in.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+
+use consensus::pos::{PoSConsensus, MAX_MISSED_STREAK, MIN_UPTIME};
+use consensus::slots::{self, SlotClock, SLOTS_PER_EPOCH};
+use consensus::uptime::{self, UptimeStats};
+use consensus::validator::{Validator, ValidatorStatus};
+use consensus::ConsensusParams;
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::units::GENX;
+use ctb_core::validator::epoch_of;
+use ctb_core::validator_set::first_height;
+
+/// Seed of the chain built
+const SEED: u64 = 3;
+
+/// Slots walked, the last one producing a block
+const SLOTS: u64 = 841;
+
+/// Slots that all pass without a block, more than an epoch's worth
+const OUTAGE: Range<u64> = 420..540;
+
+/// Validator offline while the chain's next block is in epoch 1
+const OFFLINE: &str = "v1";
+
+/// A slot as the plan had it go
+#[derive(Debug, Clone)]
+struct Slot {
+    /// Validator that proposes the slot
+    proposer: String,
+    
+    /// Whether a block was produced in it
+    produced: bool,
+    
+    /// Height of the block produced in it, or that ended its gap
+    height: u64,
+}
+
+/// Chain built by the plan
+struct Built {
+    chain: TestChain,
+    
+    /// Validators tracked as the node tracks them, told of each block
+    pos: PoSConsensus,
+    
+    /// Slots that count, in order
+    plan: Vec<Slot>,
+    
+    /// Validators jailed at the first block of each epoch
+    jailed: BTreeMap<u64, BTreeSet<String>>,
+    
+    /// Height of the block that ended the outage
+    outage_height: u64,
+}
+
+/// Builds the chain by the plan
+fn build() -> Built {
+    let config = TestChainConfig {
+        validators: vec![("v1".to_string(), 1_000 * GENX), ("v2".to_string(), 2_000 * GENX), ("v3".to_string(), 3_000 * GENX)],
+        ..TestChainConfig::default()
+    };
+    let mut chain = TestChain::with_config(SEED, config);
+    let mut pos = PoSConsensus::new(ConsensusParams::default());
+    pos.update_validator_set(registered(&chain));
+    let clock = ConsensusParams::default().slot_clock(chain.blockchain());
+    let offline = chain.address(OFFLINE);
+    
+    let mut plan = Vec::new();
+    let mut jailed = BTreeMap::new();
+    let mut outage_height = 0;
+    let mut gap: Vec<String> = Vec::new();
+    for slot in 1..=SLOTS {
+        let proposer = slots::select_proposer(pos.get_active_validators(), slot).unwrap().address.clone();
+        let height = chain.height() + 1;
+        let skipped = OUTAGE.contains(&slot) || slot % 7 == 0 || (proposer == offline && epoch_of(height) == 1);
+        if skipped {
+            gap.push(proposer);
+            continue;
+        }
+        
+        if slot >= OUTAGE.end && outage_height == 0 {
+            outage_height = height;
+        }
+        
+        // Only the last epoch's worth of a gap counts
+        let uncounted = gap.len().saturating_sub(SLOTS_PER_EPOCH as usize);
+        for proposer in gap.drain(..).skip(uncounted) {
+            plan.push(Slot { proposer, produced: false, height });
+        }
+        plan.push(Slot { proposer: proposer.clone(), produced: true, height });
+        add_block(&mut chain, &clock, slot, &proposer);
+        
+        pos.update_jailing(chain.blockchain());
+        if height == first_height(epoch_of(height)) {
+            let validators = pos.get_active_validators().iter().map(|validator| validator.address.clone());
+            jailed.insert(epoch_of(height), validators.filter(|address| pos.is_jailed(address)).collect());
+        }
+    }
+    Built { chain, pos, plan, jailed, outage_height }
+}
+
+/// Adds a block produced in a slot by its proposer
+fn add_block(chain: &mut TestChain, clock: &SlotClock, slot: u64, proposer: &str) {
+    chain.with_block(|b| b.at(clock.slot_start(slot)).proposed_by(proposer));
+}
+
+/// Gets the registered validators, as the node tracks them
+fn registered(chain: &TestChain) -> Vec<Validator> {
+    let state = chain.blockchain().get_state();
+    let state = state.lock().unwrap();
+    state.get_validators().into_iter().map(|(info, stake)| Validator::from_registry(info, stake)).collect()
+}
+
+/// Gets every validator's uptime over the blocks at a range of heights, as the plan had it
+fn expected(plan: &[Slot], heights: Range<u64>) -> HashMap<String, UptimeStats> {
+    let mut stats: HashMap<String, UptimeStats> = HashMap::new();
+    for slot in plan.iter().filter(|slot| heights.contains(&slot.height)) {
+        stats.entry(slot.proposer.clone()).or_default().record(slot.produced);
+    }
+    stats
+}
+
+/// Checks the counts match the plan over the whole chain, each epoch and ranges across epochs
+#[test]
+fn check_counts() {
+    let built = &mut build();
+    let Built { chain, pos, plan, .. } = built;
+    let tip = chain.height();
+    assert!(epoch_of(tip) > epoch_of(built.outage_height), "the chain reaches the epoch after the outage's");
+    
+    let whole = pos.uptime(chain.blockchain(), 0..tip + 1);
+    assert_eq!(whole, expected(plan, 0..tip + 1));
+    assert_eq!(whole.len(), 3);
+    let slots: u64 = whole.values().map(UptimeStats::slots).sum();
+    let produced: u64 = whole.values().map(|stats| stats.produced).sum();
+    assert_eq!(produced, tip, "every block but genesis produced a slot");
+    assert_eq!(slots, SLOTS - (OUTAGE.end - OUTAGE.start - SLOTS_PER_EPOCH), "only an epoch's worth of the outage counts");
+    
+    for epoch in 0..=epoch_of(tip) {
+        let heights = first_height(epoch)..first_height(epoch + 1).min(tip + 1);
+        assert_eq!(pos.uptime(chain.blockchain(), heights.clone()), expected(plan, heights), "epoch {}", epoch);
+    }
+    for heights in [50..250, 99..101, 150..151, 230..tip + 1, 300..300] {
+        assert_eq!(pos.uptime(chain.blockchain(), heights.clone()), expected(plan, heights.clone()), "heights {:?}", heights);
+    }
+    
+    // The offline validator kept none of its slots in epoch 1, missing them all in a row
+    let offline = chain.address(OFFLINE);
+    let epoch_1 = pos.uptime(chain.blockchain(), first_height(1)..first_height(2))[&offline];
+    assert_eq!(epoch_1.produced, 0);
+    assert!(epoch_1.missed > 0);
+    assert_eq!(epoch_1.longest_missed_streak, epoch_1.missed);
+    assert_eq!((epoch_1.leading_missed, epoch_1.trailing_missed), (epoch_1.missed, epoch_1.missed));
+    assert_eq!(epoch_1.uptime(), 0.0);
+}
+
+/// Checks the cached epochs agree with walking the blocks, whatever ranges were asked for first
+#[test]
+fn check_cache() {
+    let built = &mut build();
+    let Built { chain, pos, .. } = built;
+    let clock = ConsensusParams::default().slot_clock(chain.blockchain());
+    let validators = pos.get_active_validators().to_vec();
+    let tip = chain.height();
+    for heights in [0..tip + 1, 1..first_height(3), 120..380, first_height(2)..first_height(3)] {
+        let walked = uptime::compute(chain.blockchain(), &clock, &validators, heights.clone());
+        assert_eq!(pos.uptime(chain.blockchain(), heights.clone()), walked, "heights {:?}", heights);
+        assert_eq!(pos.uptime(chain.blockchain(), heights.clone()), walked, "heights {:?} again", heights);
+    }
+    
+    // Merging the stats of consecutive ranges gives those of the whole
+    let offline = chain.address(OFFLINE);
+    let mut merged = UptimeStats::default();
+    for start in (0..tip + 1).step_by(37) {
+        let part = uptime::compute(chain.blockchain(), &clock, &validators, start..(start + 37).min(tip + 1));
+        merged.merge(&part.get(&offline).copied().unwrap_or_default());
+    }
+    assert_eq!(merged, uptime::compute(chain.blockchain(), &clock, &validators, 0..tip + 1)[&offline]);
+}
+
+/// Checks validators were jailed for exactly the epochs after those they fell short in
+#[test]
+fn check_jailing() {
+    let built = &build();
+    let shortfall = |stats: &UptimeStats| stats.uptime() < MIN_UPTIME || stats.longest_missed_streak >= MAX_MISSED_STREAK;
+    for (&epoch, jailed) in &built.jailed {
+        let expected: BTreeSet<String> = match epoch.checked_sub(1) {
+            Some(previous) => expected(&built.plan, first_height(previous)..first_height(epoch))
+                .into_iter()
+                .filter(|(_, stats)| shortfall(stats))
+                .map(|(validator, _)| validator)
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        assert_eq!(jailed, &expected, "epoch {}", epoch);
+    }
+    
+    let offline = built.chain.address(OFFLINE);
+    assert!(built.jailed[&2].contains(&offline), "offline through epoch 1");
+    assert_eq!(built.jailed[&(epoch_of(built.outage_height) + 1)].len(), 3, "everyone missed the outage's slots");
+    let released = epoch_of(built.chain.height());
+    assert!(released > epoch_of(built.outage_height) + 1);
+    assert!(built.jailed[&released].is_empty(), "released once they kept their slots again");
+    let status = if built.pos.is_jailed(&offline) { ValidatorStatus::Jailed } else { ValidatorStatus::Active };
+    assert_eq!(built.pos.validator_status(&offline), status);
+}
+
+/// Checks a cached epoch whose blocks a reorganization replaced is computed again
+#[test]
+fn check_reorganization() {
+    let built = &mut build();
+    let Built { chain, pos, .. } = built;
+    
+    // A completed epoch, replaced from its middle within the rollback depth
+    let fork_height = chain.height() - 60;
+    let epoch = epoch_of(fork_height);
+    let heights = first_height(epoch)..first_height(epoch + 1);
+    assert!(fork_height > heights.start && heights.end <= chain.height() + 1, "epoch {} is completed", epoch);
+    let before = pos.uptime(chain.blockchain(), heights.clone());
+    
+    // Every proposer keeps its slots on the new branch
+    let clock = ConsensusParams::default().slot_clock(chain.blockchain());
+    let fork_time = chain.blockchain().get_block_by_height(fork_height).unwrap().header().timestamp;
+    chain.blockchain_mut().rollback_to(fork_height).unwrap();
+    let mut slot = clock.slot_at(fork_time);
+    while chain.height() < heights.end + 5 {
+        slot += 1;
+        let proposer = slots::select_proposer(pos.get_active_validators(), slot).unwrap().address.clone();
+        add_block(chain, &clock, slot, &proposer);
+    }
+    
+    let after = pos.uptime(chain.blockchain(), heights.clone());
+    assert_ne!(after, before);
+    assert_eq!(after, uptime::compute(chain.blockchain(), &clock, pos.get_active_validators(), heights.clone()));
+    let replaced = pos.uptime(chain.blockchain(), fork_height + 1..heights.end);
+    assert_eq!(replaced.values().map(|stats| stats.missed).sum::<u64>(), 0);
+    assert_eq!(replaced.values().map(|stats| stats.produced).sum::<u64>(), heights.end - fork_height - 1);
+}
\ No newline at end of file
@@ -0,0 +1,142 @@
+//! Checks proofs of epoch validator sets chain from one epoch to the next
+//!
+//! Run with `cargo test -p consensus --test validator_sets`. Builds a chain
+//! of four validators committing to its validator sets from the first
+//! epoch, changing the stakes before each later epoch starts so each set
+//! differs from the one before. The validators of each epoch vote for the
+//! block committing to the next one's set, and a light client trusting only
+//! the first epoch's set follows every later set from the proofs. Then
+//! checks a proof whose votes hold less than two thirds of the previous
+//! set's stake, or that skips an epoch, is rejected.
+
+use std::sync::{Arc, Mutex};
+
+use consensus::finality::FinalityVote;
+use consensus::validator_set::{verify_validator_set_transition, ValidatorSetProof};
+use consensus::{ConsensusEngine, ConsensusError, ConsensusParams};
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::units::{Amount, GENX};
+use ctb_core::validator::EPOCH_LENGTH;
+use ctb_core::validator_set::{first_height, ValidatorSet};
+use ctb_core::BlockchainError;
+
+/// Seed of the chains built
+const SEED: u64 = 163;
+
+/// Last epoch whose set is proven
+const LAST_EPOCH: u64 = 4;
+
+/// Configures four validators, the last staking nothing at first
+fn config() -> TestChainConfig {
+    let validators = [("v1", 1_000 * GENX), ("v2", 1_000 * GENX), ("v3", 1_000 * GENX), ("v4", 0)];
+    TestChainConfig {
+        validators: validators.iter().map(|(name, stake)| (name.to_string(), *stake)).collect(),
+        ..TestChainConfig::default()
+    }
+}
+
+/// Builds the chain up to the first block of `LAST_EPOCH`, changing stakes before epochs 2 to 4
+///
+/// v4 joins epoch 2 with the most stake, v1 leaves epoch 3 and v2 doubles
+/// its stake for epoch 4. Returns an engine on the chain, and the chain's
+/// keys.
+fn setup() -> (ConsensusEngine, TestChain) {
+    let mut chain = TestChain::with_config(SEED, config());
+    chain.blockchain_mut().set_validator_set_height(Some(1));
+    let changes = [(2, "v4", 3_000 * GENX), (3, "v1", 0), (4, "v2", 2_000 * GENX)];
+    for (epoch, validator, stake) in changes {
+        chain.with_empty_blocks(first_height(epoch) - 1 - chain.height());
+        let address = chain.address(validator).parse().unwrap();
+        chain.blockchain().get_state().lock().unwrap().update_validator_stake(address, Amount::from_base_units(stake));
+    }
+    chain.with_empty_blocks(1);
+    assert_eq!(chain.height(), first_height(LAST_EPOCH));
+    
+    let keys = TestChain::with_config(SEED, config());
+    let params = ConsensusParams { validator_set_height: Some(1), ..ConsensusParams::default() };
+    let engine = ConsensusEngine::new(Arc::new(Mutex::new(chain.into_blockchain())), params);
+    (engine, keys)
+}
+
+/// Gets the set an epoch's first block committed to
+fn committed(engine: &ConsensusEngine, epoch: u64) -> ValidatorSet {
+    let proof = engine.validator_set_proof(epoch).unwrap();
+    proof.validator_set
+}
+
+/// Signs votes for the block committing to an epoch's set, by validators
+fn votes(engine: &ConsensusEngine, keys: &TestChain, epoch: u64, voters: &[&str]) -> Vec<FinalityVote> {
+    let header = engine.validator_set_proof(epoch).unwrap().header;
+    let block_hash = ctb_core::hashing::hash_block_header(&header).unwrap();
+    voters.iter().map(|name| {
+        let validator = keys.account(name);
+        FinalityVote::sign(header.height, block_hash, validator.address.to_string(), validator.scheme, &validator.secret_key).unwrap()
+    }).collect()
+}
+
+/// Gets the names of the members of a set, in its order
+fn names(keys: &TestChain, set: &ValidatorSet) -> Vec<String> {
+    set.members.iter().map(|member| keys.validators().iter().find(|validator| validator.address == member.operator).unwrap().name.clone()).collect()
+}
+
+/// Checks a light client trusting the first epoch's set follows each later one from its proof
+#[test]
+fn check_transitions() {
+    let (mut engine, keys) = setup();
+    let sets: Vec<ValidatorSet> = (1..=LAST_EPOCH).map(|epoch| committed(&engine, epoch)).collect();
+    let members: Vec<Vec<String>> = sets.iter().map(|set| names(&keys, set)).collect();
+    let expected: [&[&str]; 4] = [&["v1", "v2", "v3"], &["v1", "v2", "v3", "v4"], &["v2", "v3", "v4"], &["v2", "v3", "v4"]];
+    for (members, expected) in members.iter().zip(expected) {
+        let mut sorted = members.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+    }
+    assert_eq!(members[1][0], "v4", "sets are ordered by stake");
+    assert_eq!(members[3][..2], ["v4", "v2"]);
+    
+    let mut trusted = sets[0].clone();
+    for epoch in 2..=LAST_EPOCH {
+        // Every member of the previous set votes; anyone else, such as the newcomer to epoch 2, is refused
+        let prev_members = names(&keys, &trusted);
+        let voters: Vec<&str> = prev_members.iter().map(String::as_str).collect();
+        for vote in votes(&engine, &keys, epoch, &voters) {
+            assert!(engine.add_validator_set_vote(vote).unwrap());
+        }
+        for outsider in ["v4", "alice"].into_iter().filter(|name| !voters.contains(name)) {
+            let error = engine.add_validator_set_vote(votes(&engine, &keys, epoch, &[outsider]).remove(0)).unwrap_err();
+            assert!(matches!(error, BlockchainError::Consensus { .. }), "{:?}", error);
+        }
+        
+        let proof = engine.validator_set_proof(epoch).unwrap();
+        assert_eq!(proof.votes.len(), voters.len());
+        trusted = verify_validator_set_transition(&trusted, &proof).unwrap();
+        assert_eq!(trusted, sets[epoch as usize - 1]);
+        assert_eq!(proof.header.height, epoch * EPOCH_LENGTH);
+    }
+}
+
+/// Checks a proof signed by less than two thirds of the previous set's stake, or skipping an epoch, is rejected
+#[test]
+fn check_insufficient_votes() {
+    let (engine, keys) = setup();
+    let (first, second) = (committed(&engine, 1), committed(&engine, 2));
+    let proof = |voters: &[&str]| ValidatorSetProof { votes: votes(&engine, &keys, 2, voters), ..engine.validator_set_proof(2).unwrap() };
+    
+    // A third of the stake, then just two thirds
+    let error = verify_validator_set_transition(&first, &proof(&["v1"])).unwrap_err();
+    assert!(matches!(&error, ConsensusError::InvalidValidatorSetProof(reason) if reason.contains("less than two thirds")), "{:?}", error);
+    assert_eq!(verify_validator_set_transition(&first, &proof(&["v1", "v3"])).unwrap(), second);
+    
+    // Votes by a newcomer don't count for the previous set
+    let error = verify_validator_set_transition(&first, &proof(&["v1", "v4"])).unwrap_err();
+    assert!(matches!(error, ConsensusError::InvalidValidatorSetProof(_)), "{:?}", error);
+    
+    // Nor does a vote counted twice
+    let error = verify_validator_set_transition(&first, &proof(&["v1", "v1"])).unwrap_err();
+    assert!(matches!(&error, ConsensusError::InvalidValidatorSetProof(reason) if reason.contains("more than once")), "{:?}", error);
+    
+    // A proof of epoch 3 doesn't follow epoch 1, however many voted
+    let skipping = ValidatorSetProof { votes: votes(&engine, &keys, 3, &["v1", "v2", "v3"]), ..engine.validator_set_proof(3).unwrap() };
+    let error = verify_validator_set_transition(&first, &skipping).unwrap_err();
+    assert!(matches!(&error, ConsensusError::InvalidValidatorSetProof(reason) if reason.contains("doesn't follow")), "{:?}", error);
+}
\ No newline at end of file
@@ -0,0 +1,102 @@
+//! Times hashing blocks with and without the cached hash
+//!
+//! Run with `cargo bench -p core --bench block_hashing`. Reports the time
+//! to hash a block of 1000 transfers from its header and to get its cached
+//! hash, then the time to find a block by hash in a chain of 1000 blocks,
+//! as the explorer API does, first computing every hash and then with them
+//! cached.
+
+use std::time::{Duration, Instant};
+
+use core::block::Block;
+use core::chain::Blockchain;
+use core::transaction::Transaction;
+use core::units::Amount;
+use core::{Address, BlockHash};
+
+/// Transactions in the benchmark block
+const TRANSACTIONS: usize = 1000;
+
+/// Blocks in the benchmark chain, after the genesis block
+const CHAIN_LENGTH: u64 = 1000;
+
+/// Times each measurement is repeated, keeping the fastest
+const ROUNDS: usize = 10;
+
+fn main() {
+    let recipient = Address::new("GENX_BENCH_RECIPIENT").unwrap();
+    let unit = Amount::from_base_units(1);
+    let transactions = (0..TRANSACTIONS)
+        .map(|i| {
+            let sender = Address::new(format!("GENX_BENCH_SENDER_{}", i)).unwrap();
+            Transaction::new(sender, recipient.clone(), unit, unit, None).unwrap()
+        })
+        .collect();
+    let block = Block::new(1, BlockHash::default(), transactions, "GENX_BENCH_VALIDATOR".to_string(), 0).unwrap();
+    block.hash().unwrap();
+    
+    let computed = fastest(|| {
+        let start = Instant::now();
+        block.compute_hash().unwrap();
+        start.elapsed()
+    });
+    let cached = fastest(|| {
+        let start = Instant::now();
+        block.hash().unwrap();
+        start.elapsed()
+    });
+    
+    let chain = empty_chain(CHAIN_LENGTH);
+    let target = chain.get_block_by_height(0).unwrap().compute_hash().unwrap();
+    
+    // Copies of the chain's blocks keep the hashes the chain cached when adding them
+    let blocks: Vec<Block> = (0..=CHAIN_LENGTH)
+        .map(|height| Block::clone(chain.get_block_by_height(height).unwrap()))
+        .collect();
+    let uncached_scan = fastest(|| {
+        let blocks: Vec<Block> = blocks.iter().map(uncached_copy).collect();
+        let start = Instant::now();
+        find_by_hash(&blocks, &target);
+        start.elapsed()
+    });
+    find_by_hash(&blocks, &target);
+    let cached_scan = fastest(|| {
+        let start = Instant::now();
+        find_by_hash(&blocks, &target);
+        start.elapsed()
+    });
+    
+    println!("block of {} transactions", TRANSACTIONS);
+    println!("  hash computed:         {:>10.3} us", computed.as_secs_f64() * 1e6);
+    println!("  hash cached:           {:>10.3} us", cached.as_secs_f64() * 1e6);
+    println!("chain of {} blocks, finding the genesis block by hash", CHAIN_LENGTH);
+    println!("  hashes computed:       {:>10.3} ms", uncached_scan.as_secs_f64() * 1000.0);
+    println!("  hashes cached:         {:>10.3} ms", cached_scan.as_secs_f64() * 1000.0);
+    println!("  speedup from cache:    {:>10.1}x", uncached_scan.as_secs_f64() / cached_scan.as_secs_f64());
+}
+
+/// Builds a chain of empty blocks
+fn empty_chain(length: u64) -> Blockchain {
+    let mut chain = core::genesis::initialize_blockchain().unwrap();
+    for height in 1..=length {
+        let prev_hash = chain.get_latest_block().unwrap().hash().unwrap();
+        let block = Block::new(height, prev_hash, Vec::new(), "GENX_BENCH_VALIDATOR".to_string(), chain.next_base_fee()).unwrap();
+        chain.add_block(block).unwrap();
+    }
+    chain
+}
+
+/// Copies a block without its cached hash, as when it's first received
+fn uncached_copy(block: &Block) -> Block {
+    serde_json::from_value(serde_json::to_value(block).unwrap()).unwrap()
+}
+
+/// Scans blocks from the latest down for one with the given hash
+fn find_by_hash<'a>(blocks: &'a [Block], hash: &BlockHash) -> Option<&'a Block> {
+    blocks.iter().rev().find(|block| block.hash().is_ok_and(|block_hash| block_hash == *hash))
+}
+
+/// Runs a measurement `ROUNDS` times and returns the fastest
+fn fastest(mut run: impl FnMut() -> Duration) -> Duration {
+    (0..ROUNDS).map(|_| run()).min().unwrap()
+}
\ No newline at end of file
@@ -0,0 +1,93 @@
+//! Times validating a block of 1000 signed transfers
+//!
+//! Run with `cargo bench -p core --features testutil --bench block_validation`,
+//! adding `parallel` to the features to validate the transactions across
+//! all cores.
+//! Reports the time to validate the block from scratch and with every
+//! signature already in the verified transaction cache, as when its
+//! transactions passed through the mempool. With `parallel`, it also
+//! compares validating on one thread and on all of them. Last, it counts
+//! the signatures verified admitting the transactions one by one, as the
+//! mempool does, and then importing the block with the same cache.
+
+use std::time::{Duration, Instant};
+
+use core::block::Block;
+use core::chainbuilder::{TestChain, TestChainConfig};
+use core::verified::VerifiedTxCache;
+
+/// Transactions in the benchmark block
+const TRANSACTIONS: usize = 1000;
+
+/// Times each measurement is repeated, keeping the fastest
+const ROUNDS: usize = 10;
+
+/// Seed the senders' keys are derived from
+const SEED: u64 = 1;
+
+fn main() {
+    let block = signed_block(TRANSACTIONS);
+    block.validate().expect("benchmark block is valid");
+    
+    let uncached = fastest(|| block.validate().unwrap());
+    
+    let cache = VerifiedTxCache::default();
+    block.validate_cached(&cache).unwrap();
+    let cached = fastest(|| block.validate_cached(&cache).unwrap());
+    
+    let mode = if cfg!(feature = "parallel") { "parallel" } else { "sequential" };
+    println!("{} transactions, {} validation", TRANSACTIONS, mode);
+    println!("  signatures verified:   {:>10.3} ms", uncached.as_secs_f64() * 1000.0);
+    println!("  signatures cached:     {:>10.3} ms", cached.as_secs_f64() * 1000.0);
+    println!("  speedup from cache:    {:>10.1}x", uncached.as_secs_f64() / cached.as_secs_f64());
+    
+    #[cfg(feature = "parallel")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let one_thread = fastest(|| pool.install(|| block.validate().unwrap()));
+        println!("  on one thread:         {:>10.3} ms", one_thread.as_secs_f64() * 1000.0);
+        println!(
+            "  speedup on {:>2} threads: {:>9.1}x",
+            rayon::current_num_threads(),
+            one_thread.as_secs_f64() / uncached.as_secs_f64()
+        );
+    }
+    
+    let cache = VerifiedTxCache::default();
+    for tx in &block.transactions {
+        tx.validate_cached(&cache).unwrap();
+    }
+    let admitted = cache.stats();
+    block.validate_cached(&cache).unwrap();
+    let imported = cache.stats();
+    println!("  signatures verified at admission: {:>6}", admitted.misses);
+    println!("  signatures verified at import:    {:>6}", imported.misses - admitted.misses);
+}
+
+/// Builds a block of transfers, each signed by its own ed25519 key
+fn signed_block(count: usize) -> Block {
+    let config = TestChainConfig {
+        accounts: (0..count).map(|i| (format!("sender{}", i), 1_000_000)).collect(),
+        transfer_fee: 1,
+        ..TestChainConfig::default()
+    };
+    let chain = TestChain::with_config(SEED, config);
+    chain.next_block(|b| {
+        for i in 0..count {
+            b.transfer(&format!("sender{}", i), "GENX_BENCH_RECIPIENT", 1 + i as u64);
+        }
+        b
+    })
+}
+
+/// Runs a measurement `ROUNDS` times and returns the fastest
+fn fastest(mut run: impl FnMut()) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
\ No newline at end of file
@@ -0,0 +1,170 @@
+//! Account addresses
+//!
+//! Accounts are named by strings: the address of a key (see
+//! `signature::SignatureScheme::address`), a contract address, or the name
+//! of a system account such as `GENX_DEVELOPMENT_FUND`. Public APIs taking
+//! an account take an `Address`, which is checked to be well formed when
+//! it's created, so a label or an amount can't be passed where an account
+//! was meant. Transactions and the state keep addresses as plain strings.
+//!
+//! An address is 1 to `MAX_ADDRESS_LEN` ASCII letters, digits, `_`, `-`
+//! and `.`. It displays, parses and serializes as that string.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::signature::{self, SignatureScheme};
+
+/// Longest address, in bytes
+pub const MAX_ADDRESS_LEN: usize = 128;
+
+/// Errors reading an address
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AddressError {
+    #[error("Address is empty")]
+    Empty,
+    
+    #[error("Address {0} is longer than {MAX_ADDRESS_LEN} bytes")]
+    TooLong(String),
+    
+    #[error("Address {address} contains {character:?}: expected letters, digits, '_', '-' or '.'")]
+    InvalidCharacter { address: String, character: char },
+}
+
+impl AddressError {
+    /// Gets the stable numeric code of the error
+    pub fn error_code(&self) -> u32 {
+        match self {
+            AddressError::Empty => 1110,
+            AddressError::TooLong(_) => 1111,
+            AddressError::InvalidCharacter { .. } => 1112,
+        }
+    }
+}
+
+/// Address of an account, checked to be well formed
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(String);
+
+impl Address {
+    /// Checks an address, taking it if it's well formed
+    pub fn new(address: impl Into<String>) -> Result<Self, AddressError> {
+        let address = address.into();
+        if address.is_empty() {
+            return Err(AddressError::Empty);
+        }
+        if address.len() > MAX_ADDRESS_LEN {
+            return Err(AddressError::TooLong(address));
+        }
+        if let Some(character) = address.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))) {
+            return Err(AddressError::InvalidCharacter { address, character });
+        }
+        Ok(Self(address))
+    }
+    
+    /// Gets the address as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    
+    /// Gets the address as an owned string
+    pub fn into_string(self) -> String {
+        self.0
+    }
+    
+    /// Gets the scheme of the key the address encodes, if it's the address of a key
+    pub fn scheme(&self) -> Option<SignatureScheme> {
+        signature::parse_address(&self.0).map(|(scheme, _)| scheme)
+    }
+}
+
+impl Deref for Address {
+    type Target = str;
+    
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Address {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Address {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Address {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Address {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Address {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<&str> for Address {
+    type Error = AddressError;
+    
+    fn try_from(address: &str) -> Result<Self, Self::Error> {
+        Self::new(address)
+    }
+}
+
+impl TryFrom<String> for Address {
+    type Error = AddressError;
+    
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        Self::new(address)
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> Self {
+        address.0
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::new(String::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,185 @@
+//! Bloom filters of the addresses each epoch's blocks use
+//!
+//! Wallets recovering accounts with a gap limit, and compliance checks,
+//! need a cheap answer to whether an address ever appeared on the chain,
+//! without an index of every address's transactions. The chain keeps an
+//! `AddressBloom` per epoch (see `validator::EPOCH_LENGTH`) of the senders
+//! and recipients of the transactions in the epoch's blocks, the coinbase
+//! `COINBASE` sender aside, and saves them with its blocks (see
+//! `block_store`).
+//!
+//! A bloom never misses an address it holds, but claims one it doesn't
+//! with a probability of about `BloomParams::false_positive_rate` at most: it's sized
+//! for `BloomParams::capacity` addresses, and rebuilt twice as large from
+//! the epoch's blocks whenever the epoch uses more. An address no bloom
+//! holds was never used; one some bloom holds may have been, which the
+//! blocks of the epochs whose blooms hold it confirm (see
+//! `Blockchain::address_first_used`).
+//!
+//! Each address is hashed in its own domain (see `hashing`), and the first
+//! two 64-bit words of the hash are combined into the bloom's `hashes` bit
+//! positions by double hashing.
+
+use std::collections::BTreeSet;
+use std::f64::consts::LN_2;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::hashing::{self, HashDomain};
+use crate::{BlockchainError, Bytes, Result};
+
+/// Most bit positions an address sets
+const MAX_HASHES: u32 = 32;
+
+/// Size and accuracy of the blooms
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BloomParams {
+    /// Distinct addresses an epoch's bloom is sized for, before it's rebuilt larger
+    pub capacity: u64,
+    
+    /// Probability a bloom holding `capacity` addresses claims one it doesn't hold
+    pub false_positive_rate: f64,
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        Self { capacity: 10_000, false_positive_rate: 0.001 }
+    }
+}
+
+impl BloomParams {
+    /// Checks the blooms can be sized for the parameters
+    pub fn validate(&self) -> Result<()> {
+        if self.capacity == 0 {
+            return Err(BlockchainError::StateError("Address blooms must hold at least one address".to_string()));
+        }
+        if !(self.false_positive_rate > 0.0 && self.false_positive_rate < 1.0) {
+            return Err(BlockchainError::StateError(format!(
+                "False positive rate of address blooms must be between 0 and 1, not {}",
+                self.false_positive_rate
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Gets the bits of a bloom holding `capacity` addresses at the false positive rate, a whole number of bytes
+    fn bits(&self) -> u64 {
+        let bits = -(self.capacity as f64) * self.false_positive_rate.ln() / (LN_2 * LN_2);
+        (bits.ceil() as u64).max(8).div_ceil(8) * 8
+    }
+    
+    /// Gets the bit positions each address sets to keep to the false positive rate
+    fn hashes(&self) -> u32 {
+        let hashes = (self.bits() as f64 / self.capacity as f64 * LN_2).round() as u32;
+        hashes.clamp(1, MAX_HASHES)
+    }
+}
+
+/// Bloom filter of the addresses an epoch's blocks use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBloom {
+    /// Epoch whose blocks the bloom holds the addresses of
+    pub epoch: u64,
+    
+    /// Distinct addresses the bloom is sized for
+    capacity: u64,
+    
+    /// Bit positions each address sets
+    hashes: u32,
+    
+    /// Addresses inserted that the bloom didn't already seem to hold
+    items: u64,
+    
+    bits: Bytes,
+}
+
+impl AddressBloom {
+    /// Creates an empty bloom for an epoch
+    pub fn new(epoch: u64, params: &BloomParams) -> Self {
+        Self {
+            epoch,
+            capacity: params.capacity,
+            hashes: params.hashes(),
+            items: 0,
+            bits: Bytes(vec![0; (params.bits() / 8) as usize]),
+        }
+    }
+    
+    /// Builds the bloom of an epoch's blocks, sized for twice their addresses if that's more than the capacity
+    pub fn for_blocks<'a>(epoch: u64, params: &BloomParams, blocks: impl IntoIterator<Item = &'a Block>) -> Self {
+        let addresses: BTreeSet<&str> = blocks.into_iter().flat_map(block_addresses).collect();
+        let params = BloomParams { capacity: params.capacity.max(addresses.len() as u64 * 2), ..*params };
+        let mut bloom = Self::new(epoch, &params);
+        for address in addresses {
+            bloom.insert(address);
+        }
+        bloom
+    }
+    
+    /// Adds an address, returning whether the bloom didn't already seem to hold it
+    pub fn insert(&mut self, address: &str) -> bool {
+        let mut added = false;
+        for position in self.positions(address) {
+            let (byte, mask) = ((position / 8) as usize, 1u8 << (position % 8));
+            added |= self.bits.0[byte] & mask == 0;
+            self.bits.0[byte] |= mask;
+        }
+        if added {
+            self.items += 1;
+        }
+        added
+    }
+    
+    /// Checks whether the bloom may hold an address
+    ///
+    /// Never false for an address it holds. A bloom without bits, as one
+    /// read from a damaged file, may hold anything.
+    pub fn contains(&self, address: &str) -> bool {
+        self.positions(address)
+            .all(|position| self.bits.0.get((position / 8) as usize).is_none_or(|byte| byte & (1 << (position % 8)) != 0))
+    }
+    
+    /// Gets the number of addresses the bloom holds, at least as far as it can tell
+    pub fn items(&self) -> u64 {
+        self.items
+    }
+    
+    /// Checks whether the bloom holds more addresses than it's sized for, so it should be rebuilt larger
+    pub fn is_full(&self) -> bool {
+        self.items > self.capacity
+    }
+    
+    /// Estimates the probability the bloom claims an address it doesn't hold, from the bits set
+    pub fn false_positive_rate(&self) -> f64 {
+        let set: u32 = self.bits.0.iter().map(|byte| byte.count_ones()).sum();
+        (set as f64 / self.bit_count() as f64).powi(self.hashes as i32)
+    }
+    
+    fn bit_count(&self) -> u64 {
+        self.bits.0.len() as u64 * 8
+    }
+    
+    /// Gets the bit positions an address sets
+    fn positions(&self, address: &str) -> impl Iterator<Item = u64> {
+        let hash = hashing::hash_in(HashDomain::AddressBloom, address.as_bytes());
+        let first = u64::from_le_bytes(hash[..8].try_into().unwrap());
+        let second = u64::from_le_bytes(hash[8..16].try_into().unwrap()) | 1;
+        let bit_count = self.bit_count().max(1);
+        (0..u64::from(self.hashes)).map(move |i| first.wrapping_add(i.wrapping_mul(second)) % bit_count)
+    }
+}
+
+/// Gets the addresses a block uses: the senders and recipients of its transactions, but `COINBASE`
+pub fn block_addresses(block: &Block) -> BTreeSet<&str> {
+    let mut addresses = BTreeSet::new();
+    for tx in &block.transactions {
+        if tx.sender != "COINBASE" {
+            addresses.insert(tx.sender.as_str());
+        }
+        if !tx.recipient.is_empty() {
+            addresses.insert(tx.recipient.as_str());
+        }
+    }
+    addresses
+}
\ No newline at end of file
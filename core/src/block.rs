@@ -5,18 +5,30 @@
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::OnceLock;
 
-use crate::{calculate_hash, current_timestamp, Hash, Result, BlockchainError};
+use crate::hashing::{self, HashDomain};
+use crate::signature;
+use crate::{current_timestamp, BlockHash, Bytes, Hash, Result, BlockchainError};
 use crate::transaction::Transaction;
+use crate::verified::VerifiedTxCache;
 
 /// Represents a block in the blockchain
+///
+/// The block's hash is computed from its header on first use and cached,
+/// so the header can only be changed through `header_mut`, which discards
+/// the cached hash.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     /// Block header containing metadata
-    pub header: BlockHeader,
+    header: BlockHeader,
     
     /// Transactions included in this block
     pub transactions: Vec<Transaction>,
+    
+    /// Hash of the header, once computed
+    #[serde(skip)]
+    hash: OnceLock<BlockHash>,
 }
 
 /// Block header containing metadata about the block
@@ -32,25 +44,51 @@ pub struct BlockHeader {
     pub timestamp: u64,
     
     /// Hash of the previous block in the chain
-    pub prev_hash: Hash,
+    pub prev_hash: BlockHash,
     
     /// Merkle root of all transactions in the block
+    #[serde(with = "crate::types::hex_serde")]
     pub merkle_root: Hash,
     
     /// Validator who created this block (in PoS)
     pub validator: String,
     
+    /// Minimum gas price of contract transactions in this block, see `fee_market`
+    pub base_fee: u64,
+    
     /// Validator's signature of the block
-    pub signature: Option<Vec<u8>>,
+    pub signature: Option<Bytes>,
+    
+    /// Hash of the validator set of the epoch this block starts, see `validator_set`
+    ///
+    /// Only set on the first block of an epoch, once the chain commits to
+    /// validator sets, and left out of the hashed JSON otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::types::hex_serde::option")]
+    pub validator_set_hash: Option<Hash>,
+}
+
+impl BlockHeader {
+    /// Gets the hash the validator's signature signs: that of the header without the signature
+    pub fn signing_hash(&self) -> Result<Hash> {
+        hashing::hash_block_header(&BlockHeader { signature: None, ..self.clone() }).map(|hash| hash.0)
+    }
+    
+    /// Checks the header is signed with a validator's consensus key
+    pub fn verify_signature(&self, consensus_key: &str) -> Result<()> {
+        let invalid = |reason: String| BlockchainError::InvalidBlockSignature { validator: self.validator.clone(), reason };
+        let header_signature = self.signature.as_ref().ok_or_else(|| invalid("the block isn't signed".to_string()))?;
+        signature::verify(consensus_key, &self.signing_hash()?, &header_signature.0).map_err(|e| invalid(e.to_string()))
+    }
 }
 
 impl Block {
     /// Creates a new block with the given parameters
     pub fn new(
         height: u64,
-        prev_hash: Hash,
+        prev_hash: BlockHash,
         transactions: Vec<Transaction>,
         validator: String,
+        base_fee: u64,
     ) -> Result<Self> {
         // Calculate merkle root from transactions
         let merkle_root = Self::calculate_merkle_root(&transactions)?;
@@ -62,61 +100,133 @@ impl Block {
             prev_hash,
             merkle_root,
             validator,
+            base_fee,
             signature: None,
+            validator_set_hash: None,
         };
         
         Ok(Self {
             header,
             transactions,
+            hash: OnceLock::new(),
         })
     }
     
+    /// Assembles a block from a header and transactions as received, without checking them
+    pub(crate) fn from_parts(header: BlockHeader, transactions: Vec<Transaction>) -> Self {
+        Self {
+            header,
+            transactions,
+            hash: OnceLock::new(),
+        }
+    }
+    
     /// Creates the genesis block with initial GENX distribution
-    pub fn genesis(initial_distribution: Vec<Transaction>) -> Result<Self> {
-        let empty_hash = [0u8; 32];
-        Self::new(0, empty_hash, initial_distribution, "Genesis".to_string())
+    pub fn genesis(initial_distribution: Vec<Transaction>, base_fee: u64) -> Result<Self> {
+        Self::new(0, BlockHash::default(), initial_distribution, "Genesis".to_string(), base_fee)
+    }
+    
+    /// Gets the block header
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+    
+    /// Gets the block header for modification, discarding the cached hash
+    pub fn header_mut(&mut self) -> &mut BlockHeader {
+        self.hash.take();
+        &mut self.header
     }
     
-    /// Calculates the hash of this block
-    pub fn hash(&self) -> Result<Hash> {
-        calculate_hash(&self.header)
+    /// Gets the hash of this block, computing it on first use
+    pub fn hash(&self) -> Result<BlockHash> {
+        if let Some(hash) = self.hash.get() {
+            return Ok(*hash);
+        }
+        let hash = self.compute_hash()?;
+        Ok(*self.hash.get_or_init(|| hash))
+    }
+    
+    /// Calculates the hash of this block from its header, ignoring the cached one
+    pub fn compute_hash(&self) -> Result<BlockHash> {
+        hashing::hash_block_header(&self.header)
     }
     
     /// Calculates the merkle root of the transactions
-    fn calculate_merkle_root(transactions: &[Transaction]) -> Result<Hash> {
+    pub(crate) fn calculate_merkle_root(transactions: &[Transaction]) -> Result<Hash> {
         if transactions.is_empty() {
             return Ok([0u8; 32]); // Empty merkle root for empty transactions
         }
         
         // For simplicity, we'll just hash all transactions together
         // In a production system, this would be a proper Merkle tree
-        calculate_hash(transactions)
+        hashing::hash_json_in(HashDomain::TransactionRoot, transactions)
     }
     
     /// Validates the block structure and contents
     pub fn validate(&self) -> Result<()> {
+        self.validate_with(None)
+    }
+    
+    /// Validates the block, skipping the signatures of transactions the cache holds as verified
+    ///
+    /// Transactions whose signatures verify are added to the cache.
+    pub fn validate_cached(&self, cache: &VerifiedTxCache) -> Result<()> {
+        self.validate_with(Some(cache))
+    }
+    
+    fn validate_with(&self, cache: Option<&VerifiedTxCache>) -> Result<()> {
         // Validate merkle root
         let calculated_root = Self::calculate_merkle_root(&self.transactions)?;
         if calculated_root != self.header.merkle_root {
-            return Err(BlockchainError::InvalidBlock("Invalid merkle root".to_string()));
+            return Err(BlockchainError::InvalidMerkleRoot);
         }
         
-        // Validate each transaction
+        self.validate_transactions(cache)
+    }
+    
+    /// Validates each transaction on its own, across all cores
+    ///
+    /// Transactions are checked independently of each other and of the state,
+    /// so their order only decides which error is reported: that of the
+    /// first invalid transaction, as when validating sequentially.
+    ///
+    /// Signatures are verified one at a time rather than with ed25519 batch
+    /// verification: batches accept some signatures `verify_strict` rejects,
+    /// which would let nodes built with and without it disagree on blocks.
+    #[cfg(feature = "parallel")]
+    fn validate_transactions(&self, cache: Option<&VerifiedTxCache>) -> Result<()> {
+        use rayon::prelude::*;
+        
+        match self.transactions.par_iter().find_map_first(|tx| validate_transaction(tx, cache).err()) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    
+    /// Validates each transaction on its own, in order
+    #[cfg(not(feature = "parallel"))]
+    fn validate_transactions(&self, cache: Option<&VerifiedTxCache>) -> Result<()> {
         for tx in &self.transactions {
-            tx.validate()?;
+            validate_transaction(tx, cache)?;
         }
-        
         Ok(())
     }
 }
 
+fn validate_transaction(tx: &Transaction, cache: Option<&VerifiedTxCache>) -> Result<()> {
+    match cache {
+        Some(cache) => tx.validate_cached(cache),
+        None => tx.validate(),
+    }
+}
+
 impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "Block #{} [{}] with {} transactions",
             self.header.height,
-            hex::encode(&self.hash().unwrap_or([0u8; 32])),
+            self.hash().unwrap_or_default(),
             self.transactions.len()
         )
     }
@@ -0,0 +1,249 @@
+//! Compact filters of the addresses each block touches
+//!
+//! Light wallets find the blocks relevant to them without downloading every
+//! block: a node serves a small probabilistic filter per block, the wallet
+//! tests its own addresses against it, and only fetches the blocks that
+//! match. Filters are Golomb-coded sets in the style of BIP 158. A filter
+//! never misses an address it holds, but matches one it doesn't with a
+//! probability of about `1 / FILTER_M`, so a wallet must check the blocks
+//! it fetches and discard those that turn out to be irrelevant.
+//!
+//! A block's filter holds the senders and recipients of its transactions,
+//! the contracts its deployments create, and the addresses of the contracts
+//! that emitted its logs. Coinbase transactions' `COINBASE` sender isn't
+//! included.
+//!
+//! Each item is hashed in its own domain (see `hashing`), keyed by the block's hash so that
+//! false positives differ between blocks, and mapped uniformly onto
+//! `0..N * FILTER_M` for the filter's `N` items. The sorted values are
+//! encoded as Golomb-Rice deltas with `FILTER_P` remainder bits.
+//!
+//! Filters chain into headers like blocks do: a filter's header is the hash
+//! of the filter's hash and the previous filter's header, the one before
+//! the genesis block's being zero. Comparing the headers of a tip with
+//! several nodes shows whether any of them serves a forged filter.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::hashing::{self, HashDomain, Hasher};
+use crate::receipt::Log;
+use crate::rlp::RlpItem;
+use crate::transaction::TransactionType;
+use crate::wire::{self, Wire, WireError};
+use crate::{BlockHash, Bytes, Hash};
+
+/// Remainder bits of each Golomb-Rice coded delta
+pub const FILTER_P: u8 = 19;
+
+/// Inverse of the false positive rate of a filter lookup
+pub const FILTER_M: u64 = 784_931;
+
+/// Filter of the addresses a block touches
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockFilter {
+    /// Hash of the block the filter is for, which keys its items' hashes
+    pub block_hash: BlockHash,
+    
+    /// Number of distinct items in the filter
+    pub items: u32,
+    
+    /// Golomb-Rice coded set of the items' hashes
+    pub data: Bytes,
+}
+
+impl BlockFilter {
+    /// Builds the filter of a block, given the logs its transactions emitted
+    pub fn for_block<'a>(block: &Block, block_hash: &BlockHash, logs: impl IntoIterator<Item = &'a Log>) -> Self {
+        let mut addresses = BTreeSet::new();
+        for tx in &block.transactions {
+            if tx.sender != "COINBASE" {
+                addresses.insert(tx.sender.clone());
+            }
+            if !tx.recipient.is_empty() {
+                addresses.insert(tx.recipient.clone());
+            }
+            if tx.tx_type == TransactionType::ContractDeploy {
+                addresses.insert(tx.contract_address());
+            }
+        }
+        addresses.extend(logs.into_iter().map(|log| log.address.clone()));
+        
+        Self::build(block_hash, addresses.iter().map(String::as_bytes))
+    }
+    
+    /// Builds a filter of arbitrary items for a block
+    pub fn build<'a>(block_hash: &BlockHash, items: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let items: BTreeSet<&[u8]> = items.into_iter().collect();
+        let count = items.len() as u64;
+        let mut values: Vec<u64> = items.into_iter().map(|item| hash_to_range(block_hash, item, count)).collect();
+        values.sort_unstable();
+        
+        let mut writer = BitWriter::default();
+        let mut last = 0;
+        for value in values {
+            writer.write_golomb(value - last);
+            last = value;
+        }
+        Self { block_hash: *block_hash, items: count as u32, data: Bytes(writer.finish()) }
+    }
+    
+    /// Checks whether the filter may hold any of the given items
+    ///
+    /// Never false for an item it holds, and rarely true for others. A
+    /// filter whose data is cut short matches everything, so the block is
+    /// fetched rather than missed.
+    pub fn matches_any<'a>(&self, items: impl IntoIterator<Item = &'a [u8]>) -> bool {
+        if self.items == 0 {
+            return false;
+        }
+        let count = u64::from(self.items);
+        let mut targets: Vec<u64> = items.into_iter().map(|item| hash_to_range(&self.block_hash, item, count)).collect();
+        if targets.is_empty() {
+            return false;
+        }
+        targets.sort_unstable();
+        
+        // Both lists are sorted, so they're merged in one pass
+        let mut reader = BitReader::new(&self.data.0);
+        let mut value = 0u64;
+        let mut targets = targets.into_iter().peekable();
+        for _ in 0..self.items {
+            let Some(delta) = reader.read_golomb() else {
+                return true;
+            };
+            value = value.saturating_add(delta);
+            while let Some(&target) = targets.peek() {
+                if target < value {
+                    targets.next();
+                } else {
+                    break;
+                }
+            }
+            match targets.peek() {
+                Some(&target) if target == value => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+        false
+    }
+    
+    /// Checks whether the filter may hold any of the given addresses
+    pub fn matches_addresses<'a>(&self, addresses: impl IntoIterator<Item = &'a String>) -> bool {
+        self.matches_any(addresses.into_iter().map(String::as_bytes))
+    }
+    
+    /// Gets the hash of the filter's encoding
+    pub fn hash(&self) -> Hash {
+        hashing::hash_in(HashDomain::BlockFilter, &self.to_bytes())
+    }
+    
+    /// Gets the filter's header, chaining it to the header of the previous block's filter
+    pub fn header(&self, previous: &Hash) -> Hash {
+        let mut hasher = Hasher::for_domain(HashDomain::FilterHeader);
+        hasher.update(&self.hash()).update(previous);
+        hasher.finalize()
+    }
+}
+
+impl Wire for BlockFilter {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            wire::hash(&self.block_hash.0),
+            RlpItem::uint(u128::from(self.items)),
+            wire::bytes(&self.data),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self, WireError> {
+        let fields = wire::fields(item, 3)?;
+        Ok(Self {
+            block_hash: BlockHash(wire::decode_hash(&fields[0])?),
+            items: u32::try_from(fields[1].as_u64()?).map_err(|_| WireError::InvalidValue("filter item count"))?,
+            data: wire::decode_bytes(&fields[2])?,
+        })
+    }
+}
+
+/// Maps an item's keyed hash uniformly onto `0..count * FILTER_M`
+fn hash_to_range(block_hash: &BlockHash, item: &[u8], count: u64) -> u64 {
+    let mut hasher = Hasher::for_domain(HashDomain::FilterItem);
+    hasher.update(&block_hash.0[..16]).update(item);
+    let digest = hasher.finalize();
+    
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&digest[..8]);
+    let range = count.saturating_mul(FILTER_M);
+    ((u128::from(u64::from_be_bytes(word)) * u128::from(range)) >> 64) as u64
+}
+
+/// Writes bits most significant first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    
+    /// Bits used of the last byte, 0 if it's full or there is none
+    used: u8,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.used == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> self.used;
+        }
+        self.used = (self.used + 1) % 8;
+    }
+    
+    /// Writes a value as a unary quotient and `FILTER_P` remainder bits
+    fn write_golomb(&mut self, value: u64) {
+        for _ in 0..value >> FILTER_P {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+        for bit in (0..FILTER_P).rev() {
+            self.write_bit((value >> bit) & 1 == 1);
+        }
+    }
+    
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads bits most significant first
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+    
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Some(bit)
+    }
+    
+    /// Reads a value written by `BitWriter::write_golomb`, if the data holds one
+    fn read_golomb(&mut self) -> Option<u64> {
+        let mut quotient = 0u64;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let mut remainder = 0u64;
+        for _ in 0..FILTER_P {
+            remainder = (remainder << 1) | u64::from(self.read_bit()?);
+        }
+        quotient.checked_mul(1 << FILTER_P)?.checked_add(remainder)
+    }
+}
\ No newline at end of file
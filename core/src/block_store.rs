@@ -0,0 +1,163 @@
+//! Blocks saved to a node's data directory
+//!
+//! A store is a directory holding each block in its canonical binary
+//! encoding (see `wire`), one file per height, and `tip.json`, which names
+//! the latest block and the root of the state after it (see
+//! `state_sync::state_root`). Files are replaced atomically. Blocks are
+//! written before the tip moves up to them and the tip moves down before
+//! rolled back blocks are removed, so the blocks up to the tip are always
+//! a whole chain; anything stored above the tip is left over from an
+//! interrupted write and ignored.
+//!
+//! Next to the blocks is the address bloom of each epoch (see
+//! `address_bloom`), as JSON, one file per epoch. A block's addresses are
+//! added to its epoch's bloom before the tip moves up to it, and blooms are
+//! rebuilt after blocks are rolled back, so a stored bloom holds at least
+//! the addresses of the epoch's blocks up to the tip.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::address_bloom::AddressBloom;
+use crate::block::Block;
+use crate::wire::Wire;
+use crate::{BlockHash, BlockchainError, Hash, Result};
+
+/// Directory of the block store in a node's data directory
+pub const BLOCKS_DIR: &str = "blocks";
+
+/// Name of the file recording the tip
+const TIP_FILE: &str = "tip.json";
+
+/// Latest block of a store and the state after it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredTip {
+    /// Height of the latest block
+    pub height: u64,
+    
+    /// Hash of the latest block
+    pub block_hash: BlockHash,
+    
+    /// Root of the state after the latest block
+    #[serde(with = "crate::types::hex_serde")]
+    pub state_root: Hash,
+}
+
+/// Directory the blocks of a chain are saved to
+#[derive(Debug, Clone)]
+pub struct BlockStore {
+    dir: PathBuf,
+}
+
+impl BlockStore {
+    /// Opens the store in a directory, creating it if it doesn't exist
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+    
+    /// Opens an existing store without creating anything, as for reading it
+    pub fn open_existing(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(BlockchainError::StateError(format!("No block store at {}", dir.display())));
+        }
+        Ok(Self { dir })
+    }
+    
+    /// Gets the directory of the store
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    
+    /// Gets the path a block is saved at
+    pub fn block_path(&self, height: u64) -> PathBuf {
+        self.dir.join(format!("{}.block", height))
+    }
+    
+    /// Saves a block, replacing any saved at its height
+    pub fn put_block(&self, block: &Block) -> Result<()> {
+        write_atomically(&self.block_path(block.header().height), &block.to_bytes())
+    }
+    
+    /// Reads the block saved at a height, if any
+    ///
+    /// Fails if the file can't be read or doesn't hold a block.
+    pub fn get_block(&self, height: u64) -> Result<Option<Block>> {
+        let path = self.block_path(height);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let block = Block::from_bytes(&fs::read(&path)?)
+            .map_err(|e| BlockchainError::SerializationError(format!("{}: {}", path.display(), e)))?;
+        Ok(Some(block))
+    }
+    
+    /// Removes the block saved at a height, if any
+    pub fn remove_block(&self, height: u64) -> Result<()> {
+        match fs::remove_file(self.block_path(height)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Gets the path the address bloom of an epoch is saved at
+    pub fn bloom_path(&self, epoch: u64) -> PathBuf {
+        self.dir.join(format!("{}.bloom", epoch))
+    }
+    
+    /// Saves the address bloom of an epoch, replacing any saved for it
+    pub fn put_bloom(&self, bloom: &AddressBloom) -> Result<()> {
+        let contents = serde_json::to_vec(bloom).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        write_atomically(&self.bloom_path(bloom.epoch), &contents)
+    }
+    
+    /// Reads the address bloom saved for an epoch, if any
+    pub fn get_bloom(&self, epoch: u64) -> Result<Option<AddressBloom>> {
+        let path = self.bloom_path(epoch);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bloom = serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| BlockchainError::SerializationError(format!("{}: {}", path.display(), e)))?;
+        Ok(Some(bloom))
+    }
+    
+    /// Removes the address bloom saved for an epoch, if any
+    pub fn remove_bloom(&self, epoch: u64) -> Result<()> {
+        match fs::remove_file(self.bloom_path(epoch)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Reads the tip, if one was saved
+    pub fn tip(&self) -> Result<Option<StoredTip>> {
+        let path = self.dir.join(TIP_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let tip = serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| BlockchainError::SerializationError(format!("{}: {}", path.display(), e)))?;
+        Ok(Some(tip))
+    }
+    
+    /// Replaces the tip
+    pub fn set_tip(&self, tip: &StoredTip) -> Result<()> {
+        let contents = serde_json::to_string_pretty(tip)
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        write_atomically(&self.dir.join(TIP_FILE), contents.as_bytes())
+    }
+}
+
+/// Writes a file through a temporary one, so it's never left half written
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
\ No newline at end of file
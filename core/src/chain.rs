@@ -3,28 +3,224 @@
 //! This module manages the blockchain state, including adding blocks
 //! and validating the entire chain.
 
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
 
-use crate::{BlockchainError, Hash, Result};
-use crate::block::Block;
-use crate::state::State;
+use serde::{Deserialize, Serialize};
+
+use crate::{current_timestamp, Address, BlockHash, BlockchainError, ErrorContext, Result, TxHash, WithContext};
+use crate::address_bloom::{self, AddressBloom, BloomParams};
+use crate::block::{Block, BlockHeader};
+use crate::block_store::{BlockStore, StoredTip};
+use crate::executor::ContractExecutor;
+use crate::fee_market;
+use crate::fork_choice::{ForkChoiceRule, LongestChain};
+use crate::ordering;
+use crate::paging::{Page, PageRequest, SortOrder};
+use crate::receipt::{IndexedLog, LogFilter, Receipt};
+use crate::replay::{self, VerificationReport, VerifyOptions};
+use crate::rewards::RewardSchedule;
+use crate::state::{BlockUndo, State, StateAccess, StateSnapshot};
+use crate::state_diff::{self, BalanceChange, StateChanges, StateDiff};
+use crate::state_sync;
 use crate::transaction::Transaction;
+use crate::units::Amount;
+use crate::validator::epoch_of;
+use crate::validator_set::{self, ValidatorSelection, ValidatorSet};
+use crate::verified::VerifiedTxCache;
+use crate::wire::Wire;
+
+/// Number of most recent blocks that can be rolled back
+pub const MAX_ROLLBACK_DEPTH: u64 = 128;
+
+/// Most blocks `Blockchain::state_at` undoes to reconstruct a past state unless configured otherwise
+pub const DEFAULT_MAX_STATE_DEPTH: u64 = MAX_ROLLBACK_DEPTH;
+
+/// Number of most recent reorganizations the chain remembers
+pub const MAX_REORG_HISTORY: usize = 100;
+
+/// Record of the chain switching to a competing branch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReorgRecord {
+    /// Hash of the latest block before the switch
+    pub old_tip: BlockHash,
+    
+    /// Hash of the latest block after the switch
+    pub new_tip: BlockHash,
+    
+    /// Height of the last block both branches share
+    pub fork_height: u64,
+    
+    /// Number of blocks removed from the old branch
+    pub depth: u64,
+    
+    /// Number of transactions in the removed blocks that the new branch doesn't include
+    pub dropped_txs: usize,
+    
+    /// When the switch happened
+    pub timestamp: u64,
+}
+
+/// What pruning a chain's receipts and logs dropped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    /// Number of receipts dropped
+    pub receipts: u64,
+    
+    /// Number of logs dropped from the log index
+    pub logs: u64,
+    
+    /// Size of what was dropped in its binary encoding (see `wire`)
+    pub bytes: u64,
+}
+
+/// Shared handle to the latest state snapshot of a chain
+///
+/// Cloning the handle is cheap, and every clone sees the snapshots the chain
+/// publishes as blocks are added and rolled back. Reading one never waits
+/// for a block being applied.
+#[derive(Debug, Clone)]
+pub struct SnapshotHandle(Arc<RwLock<StateSnapshot>>);
+
+impl SnapshotHandle {
+    /// Gets the latest snapshot
+    pub fn latest(&self) -> StateSnapshot {
+        self.0.read().unwrap().clone()
+    }
+    
+    fn publish(&self, snapshot: StateSnapshot) {
+        *self.0.write().unwrap() = snapshot;
+    }
+}
+
+/// Observer of the blocks a chain adds and rolls back
+///
+/// Listeners are called while the chain is being modified, after the state
+/// has been updated, so they must not call back into the chain.
+pub trait ChainListener: Send + Sync + fmt::Debug {
+    /// Called after a block is added, with the logs its transactions emitted
+    fn block_added(&self, block: &Block, block_hash: &BlockHash, logs: &[IndexedLog]);
+    
+    /// Called after a block is rolled back, with the logs it had emitted
+    ///
+    /// When several blocks are rolled back the newest comes first.
+    fn block_removed(&self, block: &Block, block_hash: &BlockHash, logs: &[IndexedLog]);
+    
+    /// Called after `block_added` and `block_removed` with the balances the block changed, by address
+    ///
+    /// For a rolled back block the changes are reversed: `old` is the
+    /// balance the block had left and `new` the one restored. They're read
+    /// from the block's undo record, so transfers made by contracts are
+    /// included. Does nothing unless implemented.
+    fn balances_changed(&self, _block: &Block, _block_hash: &BlockHash, _changes: &[BalanceChange], _removed: bool) {}
+}
 
 /// Represents the blockchain and its current state
 #[derive(Debug)]
 pub struct Blockchain {
     /// All blocks in the chain, indexed by height
-    blocks: HashMap<u64, Block>,
+    ///
+    /// Blocks are shared so callers can keep one after releasing the chain.
+    blocks: HashMap<u64, Arc<Block>>,
+    
+    /// Height of each block in the chain, indexed by block hash
+    block_heights: HashMap<BlockHash, u64>,
     
     /// The current state of the blockchain (account balances, etc.)
     state: Arc<Mutex<State>>,
     
     /// The hash of the latest block in the chain
-    latest_hash: Hash,
+    latest_hash: BlockHash,
     
     /// The height of the latest block in the chain
     latest_height: u64,
+    
+    /// Receipts of the applied transactions not pruned, indexed by transaction ID
+    receipts: HashMap<TxHash, Receipt>,
+    
+    /// Logs emitted in each block not pruned, indexed by block height
+    block_logs: HashMap<u64, Vec<IndexedLog>>,
+    
+    /// Height of the block each applied transaction was included in, indexed by transaction ID
+    ///
+    /// Kept when receipts are pruned, so transactions can still be found.
+    tx_heights: HashMap<TxHash, u64>,
+    
+    /// Height below which receipts and logs have been pruned, zero if none have
+    pruned_height: u64,
+    
+    /// State changes of the most recent blocks, indexed by block height
+    block_undo: HashMap<u64, BlockUndo>,
+    
+    /// Most blocks undone to reconstruct a past state
+    max_state_depth: u64,
+    
+    /// Gas used by each block, indexed by block height
+    block_gas_used: HashMap<u64, u64>,
+    
+    /// Maximum total gas the transactions of a block may consume
+    block_gas_limit: u64,
+    
+    /// Height from which blocks must order their transactions canonically, if any
+    transaction_ordering_height: Option<u64>,
+    
+    /// Height from which epochs' first blocks commit to their validator sets, if any
+    validator_set_height: Option<u64>,
+    
+    /// How the validators of an epoch are chosen
+    validator_selection: ValidatorSelection,
+    
+    /// Validator sets the chain's blocks committed to, indexed by epoch
+    validator_sets: BTreeMap<u64, Arc<ValidatorSet>>,
+    
+    /// How block rewards are split between validators and the treasury
+    rewards: RewardSchedule,
+    
+    /// Engine that executes contract transactions, if any
+    contract_executor: Option<Arc<Mutex<dyn ContractExecutor>>>,
+    
+    /// Rule deciding whether a competing branch replaces blocks of the chain
+    fork_choice: Arc<dyn ForkChoiceRule>,
+    
+    /// Snapshot of the state after the latest block
+    snapshot: SnapshotHandle,
+    
+    /// Observers notified of added and rolled back blocks
+    listeners: Vec<Arc<dyn ChainListener>>,
+    
+    /// Transactions whose signatures have verified, shared with the mempool
+    verified_txs: Arc<VerifiedTxCache>,
+    
+    /// Latest timestamp among the blocks up to each height, indexed by height
+    ///
+    /// Block timestamps aren't required to increase, so each entry is the
+    /// running maximum. That keeps the index sorted for binary search, and
+    /// a block stamped earlier than one of its ancestors counts as made at
+    /// the ancestor's time.
+    block_times: Vec<u64>,
+    
+    /// Weight of the chain up to each height by the fork choice rule, indexed by height
+    chain_weights: Vec<u128>,
+    
+    /// Most recent reorganizations, oldest first
+    reorg_history: VecDeque<ReorgRecord>,
+    
+    /// File the reorganization history is saved to, if it's saved at all
+    reorg_log: Option<PathBuf>,
+    
+    /// Store the blocks are saved to, if they're saved at all
+    block_store: Option<BlockStore>,
+    
+    /// Blooms of the addresses each epoch's blocks use, indexed by epoch
+    address_blooms: BTreeMap<u64, AddressBloom>,
+    
+    /// Size and accuracy of the address blooms
+    bloom_params: BloomParams,
 }
 
 impl Blockchain {
@@ -40,63 +236,1043 @@ impl Blockchain {
         let mut state = State::new();
         state.apply_block(&genesis_block)?;
         
+        let snapshot = SnapshotHandle(Arc::new(RwLock::new(StateSnapshot {
+            block_height: 0,
+            block_timestamp: genesis_block.header().timestamp,
+            state: Arc::new(state.clone()),
+        })));
+        
         // Create the blockchain
+        let block_times = vec![genesis_block.header().timestamp];
+        let chain_weights = vec![0];
+        let bloom_params = BloomParams::default();
+        let mut address_blooms = BTreeMap::new();
+        address_blooms.insert(0, AddressBloom::for_blocks(0, &bloom_params, [&genesis_block]));
         let mut blocks = HashMap::new();
-        blocks.insert(0, genesis_block);
+        blocks.insert(0, Arc::new(genesis_block));
+        let mut block_heights = HashMap::new();
+        block_heights.insert(genesis_hash, 0);
         
         Ok(Self {
             blocks,
+            block_heights,
             state: Arc::new(Mutex::new(state)),
             latest_hash: genesis_hash,
             latest_height: 0,
+            receipts: HashMap::new(),
+            block_logs: HashMap::new(),
+            tx_heights: HashMap::new(),
+            pruned_height: 0,
+            block_undo: HashMap::new(),
+            max_state_depth: DEFAULT_MAX_STATE_DEPTH,
+            block_gas_used: HashMap::new(),
+            block_gas_limit: crate::genesis::get_block_gas_limit(),
+            transaction_ordering_height: crate::genesis::get_transaction_ordering_height(),
+            validator_set_height: crate::genesis::get_validator_set_height(),
+            validator_selection: ValidatorSelection::default(),
+            validator_sets: BTreeMap::new(),
+            rewards: crate::genesis::reward_schedule()?,
+            contract_executor: None,
+            fork_choice: Arc::new(LongestChain),
+            snapshot,
+            listeners: Vec::new(),
+            verified_txs: Arc::new(VerifiedTxCache::default()),
+            block_times,
+            chain_weights,
+            reorg_history: VecDeque::new(),
+            reorg_log: None,
+            block_store: None,
+            address_blooms,
+            bloom_params,
         })
     }
     
+    /// Rebuilds a chain by replaying stored blocks, starting with the genesis block
+    ///
+    /// Contract transactions are executed again through `executor`, which
+    /// reconstructs the deployed contracts and their storage.
+    pub fn from_blocks(
+        blocks: impl IntoIterator<Item = Block>,
+        executor: Option<Arc<Mutex<dyn ContractExecutor>>>,
+    ) -> Result<Self> {
+        let mut blocks = blocks.into_iter();
+        let genesis_block = blocks.next().ok_or_else(|| {
+            BlockchainError::StateError("Cannot rebuild a chain without a genesis block".to_string())
+        })?;
+        
+        let mut chain = Self::new(genesis_block)?;
+        if let Some(executor) = executor {
+            chain.set_contract_executor(executor);
+        }
+        
+        for block in blocks {
+            chain.add_block(block)?;
+        }
+        
+        Ok(chain)
+    }
+    
+    /// Sets the engine used to execute contract transactions in new blocks
+    pub fn set_contract_executor(&mut self, executor: Arc<Mutex<dyn ContractExecutor>>) {
+        self.contract_executor = Some(executor);
+    }
+    
+    /// Sets the rule deciding whether a competing branch replaces blocks of the chain, `LongestChain` by default
+    ///
+    /// The chain's weight is weighed again by the new rule, each block with
+    /// the latest state rather than the one before it, which is gone.
+    pub fn set_fork_choice(&mut self, fork_choice: Arc<dyn ForkChoiceRule>) {
+        let state = self.state.lock().unwrap().clone();
+        let mut weight = 0u128;
+        self.chain_weights = std::iter::once(0)
+            .chain((1..=self.latest_height).map(|height| {
+                weight += self.blocks.get(&height).map_or(0, |block| fork_choice.block_weight(&state, block));
+                weight
+            }))
+            .collect();
+        self.fork_choice = fork_choice;
+    }
+    
+    /// Registers an observer of the blocks added and rolled back from now on
+    pub fn add_listener(&mut self, listener: Arc<dyn ChainListener>) {
+        self.listeners.push(listener);
+    }
+    
+    /// Sets the maximum total gas the transactions of a new block may consume
+    pub fn set_block_gas_limit(&mut self, block_gas_limit: u64) {
+        self.block_gas_limit = block_gas_limit;
+    }
+    
+    /// Gets the block gas limit
+    pub fn get_block_gas_limit(&self) -> u64 {
+        self.block_gas_limit
+    }
+    
+    /// Sets the height from which blocks must order their transactions canonically, see `ordering`
+    pub fn set_transaction_ordering_height(&mut self, height: Option<u64>) {
+        self.transaction_ordering_height = height;
+    }
+    
+    /// Checks whether the block at a height must order its transactions canonically
+    pub fn orders_transactions(&self, height: u64) -> bool {
+        self.transaction_ordering_height.is_some_and(|activation| height >= activation)
+    }
+    
+    /// Sets the height from which epochs' first blocks commit to their validator sets, see `validator_set`
+    pub fn set_validator_set_height(&mut self, height: Option<u64>) {
+        self.validator_set_height = height;
+    }
+    
+    /// Sets how the validators of an epoch are chosen
+    pub fn set_validator_selection(&mut self, selection: ValidatorSelection) {
+        self.validator_selection = selection;
+    }
+    
+    /// Checks whether the block at a height must commit to a validator set
+    pub fn commits_validator_set(&self, height: u64) -> bool {
+        height > 0
+            && validator_set::first_height(epoch_of(height)) == height
+            && self.validator_set_height.is_some_and(|activation| height >= activation)
+    }
+    
+    /// Gets the validator set the next block must commit to, if it starts an epoch that does
+    pub fn next_validator_set(&self) -> Option<ValidatorSet> {
+        let height = self.latest_height + 1;
+        self.commits_validator_set(height)
+            .then(|| self.validator_selection.select(&self.state.lock().unwrap(), epoch_of(height)))
+    }
+    
+    /// Gets the validator set an epoch's first block committed to, if it's in the chain and did
+    pub fn get_validator_set(&self, epoch: u64) -> Option<Arc<ValidatorSet>> {
+        self.validator_sets.get(&epoch).cloned()
+    }
+    
+    /// Gets the treasury rules, which come from `genesis::reward_schedule`
+    pub fn reward_schedule(&self) -> &RewardSchedule {
+        &self.rewards
+    }
+    
     /// Adds a new block to the chain
-    pub fn add_block(&mut self, block: Block) -> Result<()> {
-        // Validate the block
-        block.validate()?;
+    ///
+    /// The block is applied inside a state checkpoint, which is reverted
+    /// unless every transaction applies and the block's total gas stays within
+    /// the limit. The block's changes are kept so it can be rolled back later.
+    /// A block already shared, such as one also being broadcast, is stored
+    /// without being copied. Errors carry the block's height and hash, and
+    /// those of a transaction the transaction's index and ID, see `context`.
+    pub fn add_block(&mut self, block: impl Into<Arc<Block>>) -> Result<()> {
+        let block = block.into();
+        let context = ErrorContext::block(&block);
+        self.connect_block(block).with_ctx(context)
+    }
+    
+    /// Checks a block's header is signed by its validator, which must be registered once any is
+    ///
+    /// A block naming a registered validator is signed with that validator's
+    /// consensus key. Until the first validator registers there's no key to
+    /// check against, so a chain starting from a genesis block registering
+    /// none takes blocks from anyone.
+    fn check_producer(&self, header: &BlockHeader) -> Result<()> {
+        let state = self.state.lock().unwrap();
+        match state.get_validator(&header.validator) {
+            Some(validator) => header.verify_signature(&validator.consensus_key),
+            None if state.get_validators().is_empty() => Ok(()),
+            None => Err(BlockchainError::UnknownValidator { address: header.validator.clone() }),
+        }
+    }
+    
+    /// Checks a block extends the chain and applies it, see `add_block`
+    fn connect_block(&mut self, block: Arc<Block>) -> Result<()> {
+        // Validate the block, skipping signatures verified when the transactions were admitted
+        block.validate_cached(&self.verified_txs)?;
         
         // Check that the block's height is one more than the current height
-        if block.header.height != self.latest_height + 1 {
-            return Err(BlockchainError::InvalidBlock(
-                format!("Invalid block height: expected {}, got {}", 
-                        self.latest_height + 1, block.header.height)
-            ));
+        if block.header().height != self.latest_height + 1 {
+            return Err(BlockchainError::UnexpectedHeight {
+                expected: self.latest_height + 1,
+                got: block.header().height,
+            });
         }
         
         // Check that the block's prev_hash matches the latest hash
-        if block.header.prev_hash != self.latest_hash {
-            return Err(BlockchainError::InvalidBlock(
-                "Block's previous hash doesn't match the latest hash".to_string()
-            ));
+        if block.header().prev_hash != self.latest_hash {
+            return Err(BlockchainError::ParentMismatch);
         }
         
-        // Apply the block to the state
-        {
+        // Check that the base fee follows from the parent block
+        let base_fee = self.next_base_fee();
+        if block.header().base_fee != base_fee {
+            return Err(BlockchainError::BaseFeeMismatch { expected: base_fee, got: block.header().base_fee });
+        }
+        
+        // Check that the block's producer may produce it
+        self.check_producer(block.header())?;
+        
+        // Check that the block mints no more than its reward, and pays it where the rules and the validator registry say
+        let payout_address = self.state.lock().unwrap().get_payout_address(&block.header().validator, block.header().height);
+        self.rewards.check_block(&block, &payout_address)?;
+        
+        // Check that the transactions are in the canonical order, once that's required
+        if self.orders_transactions(block.header().height) {
+            ordering::check_order(&block.transactions)?;
+        }
+        
+        // Check that the block commits to the validator set its epoch starts with, if it must, and to none otherwise
+        let validator_set = self.next_validator_set();
+        let expected = validator_set.as_ref().map(ValidatorSet::hash);
+        if block.header().validator_set_hash != expected {
+            return Err(BlockchainError::InvalidBlock(match expected {
+                Some(hash) => format!("Block must commit to validator set 0x{}", hex::encode(hash)),
+                None => format!("Block {} must not commit to a validator set", block.header().height),
+            }));
+        }
+        
+        // Apply the block to the state, executing contract transactions
+        let block_weight = self.fork_choice.block_weight(&self.state.lock().unwrap(), &block);
+        let (receipts, undo, state_after) = {
             let mut state = self.state.lock().unwrap();
-            state.apply_block(&block)?;
+            state.checkpoint();
+            
+            let applied = match &self.contract_executor {
+                Some(executor) => {
+                    let mut executor = executor.lock().unwrap();
+                    state.apply_block_with_executor(&block, Some(&mut *executor))
+                }
+                None => state.apply_block_with_executor(&block, None),
+            };
+            let applied = applied.and_then(|receipts| {
+                let gas_used = receipts.last().map_or(0, |receipt| receipt.cumulative_gas_used);
+                if gas_used > self.block_gas_limit {
+                    return Err(BlockchainError::BlockGasExceeded { gas: gas_used, limit: self.block_gas_limit });
+                }
+                Ok(receipts)
+            });
+            
+            match applied {
+                Ok(receipts) => (receipts, state.commit_block(), state.clone()),
+                Err(e) => {
+                    state.revert();
+                    return Err(e);
+                }
+            }
+        };
+        
+        let gas_used = receipts.last().map_or(0, |receipt| receipt.cumulative_gas_used);
+        self.block_gas_used.insert(block.header().height, gas_used);
+        
+        // Index the emitted logs by block, then store the receipts
+        let logs: Vec<IndexedLog> = receipts
+            .iter()
+            .flat_map(|receipt| {
+                receipt.logs.iter().map(move |log| IndexedLog {
+                    block_height: receipt.block_height,
+                    tx_id: receipt.tx_id,
+                    log: log.clone(),
+                })
+            })
+            .collect();
+        if !logs.is_empty() {
+            self.block_logs.insert(block.header().height, logs);
+        }
+        
+        for receipt in receipts {
+            self.tx_heights.insert(receipt.tx_id, receipt.block_height);
+            self.receipts.insert(receipt.tx_id, receipt);
         }
         
         // Update the blockchain
         let block_hash = block.hash()?;
-        let block_height = block.header.height;
+        let block_height = block.header().height;
+        let epoch = epoch_of(block_height);
+        self.index_addresses(&block);
         
+        if let Some(store) = &self.block_store {
+            let tip = StoredTip { height: block_height, block_hash, state_root: state_sync::state_root(&state_after) };
+            let saved = store
+                .put_block(&block)
+                .and_then(|()| store.put_bloom(&self.address_blooms[&epoch]))
+                .and_then(|()| store.set_tip(&tip));
+            if let Err(e) = saved {
+                log::error!("Failed to save block {} to {}: {}", block_height, store.dir().display(), e);
+            }
+        }
+        
+        self.snapshot.publish(StateSnapshot {
+            block_height,
+            block_timestamp: block.header().timestamp,
+            state: Arc::new(state_after),
+        });
+        let block_time = self.block_times.last().map_or(block.header().timestamp, |&time| time.max(block.header().timestamp));
+        self.block_times.push(block_time);
+        self.chain_weights.push(self.chain_weight() + block_weight);
         self.blocks.insert(block_height, block);
+        self.block_heights.insert(block_hash, block_height);
         self.latest_hash = block_hash;
         self.latest_height = block_height;
+        if let Some(validator_set) = validator_set {
+            self.validator_sets.insert(validator_set.epoch, Arc::new(validator_set));
+        }
+        
+        self.block_undo.insert(block_height, undo);
+        if let Some(expired) = block_height.checked_sub(MAX_ROLLBACK_DEPTH) {
+            self.block_undo.remove(&expired);
+        }
+        
+        let block = &self.blocks[&block_height];
+        let logs = self.block_logs.get(&block_height).map_or(&[][..], Vec::as_slice);
+        for listener in &self.listeners {
+            listener.block_added(block, &block_hash, logs);
+        }
+        if !self.listeners.is_empty() {
+            let changes = state_diff::balance_changes(&self.block_undo[&block_height], &self.snapshot.latest().state);
+            for listener in &self.listeners {
+                listener.balances_changed(block, &block_hash, &changes, false);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Removes every block above `height`, undoing their state changes
+    ///
+    /// Used when reorganizing onto a competing fork. Only the last
+    /// `MAX_ROLLBACK_DEPTH` blocks can be rolled back. Returns the removed
+    /// blocks in chain order, so their transactions can be resubmitted.
+    pub fn rollback_to(&mut self, height: u64) -> Result<Vec<Arc<Block>>> {
+        if height > self.latest_height {
+            return Err(BlockchainError::InvalidBlock(
+                format!("Cannot roll back to height {} above the latest height {}", height, self.latest_height)
+            ));
+        }
+        
+        if (height + 1..=self.latest_height).any(|h| !self.block_undo.contains_key(&h)) {
+            return Err(BlockchainError::BeyondRollbackDepth { max_depth: MAX_ROLLBACK_DEPTH });
+        }
+        
+        // Listeners are told the hashes of the removed blocks, newest first
+        let removed_hashes = (height + 1..=self.latest_height)
+            .rev()
+            .filter_map(|h| self.blocks.get(&h))
+            .map(|block| block.hash())
+            .collect::<Result<Vec<_>>>()?;
+        
+        let mut removed = Vec::new();
+        let mut removed_logs = Vec::new();
+        let mut removed_changes = Vec::new();
+        let state_after = {
+            let mut state = self.state.lock().unwrap();
+            for h in (height + 1..=self.latest_height).rev() {
+                let mut changes = Vec::new();
+                if let Some(undo) = self.block_undo.remove(&h) {
+                    if !self.listeners.is_empty() {
+                        changes = state_diff::balance_changes(&undo, &state).iter().map(BalanceChange::reversed).collect();
+                    }
+                    state.rollback_block(undo);
+                }
+                let logs = self.block_logs.remove(&h).unwrap_or_default();
+                self.block_gas_used.remove(&h);
+                if let Some(block) = self.blocks.remove(&h) {
+                    for tx in &block.transactions {
+                        self.receipts.remove(&tx.id);
+                        self.tx_heights.remove(&tx.id);
+                    }
+                    removed.push(block);
+                    removed_logs.push(logs);
+                    removed_changes.push(changes);
+                }
+            }
+            state.clone()
+        };
+        for hash in &removed_hashes {
+            self.block_heights.remove(hash);
+        }
+        
+        let latest = self.blocks.get(&height).cloned().ok_or(BlockchainError::UnknownBlock { height })?;
+        self.latest_hash = latest.hash()?;
+        self.latest_height = height;
+        // Blocks added from here on keep their receipts
+        self.pruned_height = self.pruned_height.min(height + 1);
+        self.block_times.truncate(height as usize + 1);
+        self.chain_weights.truncate(height as usize + 1);
+        self.validator_sets.retain(|&epoch, _| validator_set::first_height(epoch) <= height);
+        let rebuilt = self.rebuild_blooms(epoch_of(height));
+        
+        if let Some(store) = &self.block_store {
+            let tip = StoredTip { height, block_hash: self.latest_hash, state_root: state_sync::state_root(&state_after) };
+            let saved = store
+                .set_tip(&tip)
+                .and_then(|()| removed.iter().try_for_each(|block| store.remove_block(block.header().height)))
+                .and_then(|()| {
+                    rebuilt.iter().try_for_each(|&epoch| match self.address_blooms.get(&epoch) {
+                        Some(bloom) => store.put_bloom(bloom),
+                        None => store.remove_bloom(epoch),
+                    })
+                });
+            if let Err(e) = saved {
+                log::error!("Failed to roll back the blocks saved to {}: {}", store.dir().display(), e);
+            }
+        }
+        
+        self.snapshot.publish(StateSnapshot {
+            block_height: height,
+            block_timestamp: latest.header().timestamp,
+            state: Arc::new(state_after),
+        });
+        
+        for (((block, block_hash), logs), changes) in removed.iter().zip(&removed_hashes).zip(&removed_logs).zip(&removed_changes) {
+            for listener in &self.listeners {
+                listener.block_removed(block, block_hash, logs);
+                listener.balances_changed(block, block_hash, changes, true);
+            }
+        }
+        
+        removed.reverse();
+        Ok(removed)
+    }
+    
+    /// Checks whether a competing branch forking off after `fork_height` is preferred to the chain's blocks above it
+    ///
+    /// The chain's fork choice rule decides (see `set_fork_choice`). Fails
+    /// if the branch doesn't replace any block, or the rule rejects it.
+    pub fn prefers_branch(&self, fork_height: u64, blocks: &[Arc<Block>]) -> Result<bool> {
+        if fork_height >= self.latest_height || blocks.is_empty() {
+            return Err(BlockchainError::InvalidBlock(format!(
+                "Branch of {} blocks from height {} doesn't replace blocks of the chain at height {}",
+                blocks.len(), fork_height, self.latest_height
+            )));
+        }
+        self.fork_choice.prefers(self, fork_height, blocks)
+    }
+    
+    /// Switches to a competing branch that forks off after `fork_height`
+    ///
+    /// The branch must be preferred to the chain's blocks above the fork by
+    /// the fork choice rule, the longer branch unless set otherwise (see
+    /// `prefers_branch`). Blocks above the fork are rolled back and the
+    /// branch's blocks added in their place; if any of them is invalid the
+    /// old blocks are restored and the error is returned. Returns the record
+    /// of the switch, which is also added to the history, and the
+    /// transactions of the removed blocks that the new branch doesn't
+    /// include, so they can be returned to the mempool.
+    pub fn reorganize(&mut self, fork_height: u64, blocks: Vec<Arc<Block>>) -> Result<(ReorgRecord, Vec<Transaction>)> {
+        if !self.prefers_branch(fork_height, &blocks)? {
+            return Err(BlockchainError::BranchNotPreferred { fork_height });
+        }
+        
+        let old_tip = self.latest_hash;
+        let removed = self.rollback_to(fork_height)?;
+        for block in &blocks {
+            if let Err(e) = self.add_block(Arc::clone(block)) {
+                self.rollback_to(fork_height)?;
+                for block in removed {
+                    self.add_block(block)?;
+                }
+                return Err(e);
+            }
+        }
+        
+        let included: HashSet<TxHash> = blocks.iter().flat_map(|block| block.transactions.iter().map(|tx| tx.id)).collect();
+        let dropped: Vec<Transaction> = removed
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| tx.sender != "COINBASE" && !included.contains(&tx.id))
+            .cloned()
+            .collect();
+        
+        let record = ReorgRecord {
+            old_tip,
+            new_tip: self.latest_hash,
+            fork_height,
+            depth: removed.len() as u64,
+            dropped_txs: dropped.len(),
+            timestamp: current_timestamp(),
+        };
+        if record.depth > 2 {
+            log::warn!(
+                "Reorganized {} blocks deep at height {}: {} -> {}, {} transactions dropped",
+                record.depth, fork_height, old_tip, record.new_tip, record.dropped_txs
+            );
+        } else {
+            log::info!("Reorganized {} blocks deep at height {}: {} -> {}", record.depth, fork_height, old_tip, record.new_tip);
+        }
+        
+        self.reorg_history.push_back(record.clone());
+        if self.reorg_history.len() > MAX_REORG_HISTORY {
+            self.reorg_history.pop_front();
+        }
+        if let Err(e) = self.save_reorg_history() {
+            log::error!("Failed to save the reorganization history: {}", e);
+        }
         
+        Ok((record, dropped))
+    }
+    
+    /// Gets up to `limit` of the most recent reorganizations, newest first
+    pub fn reorg_history(&self, limit: usize) -> Vec<ReorgRecord> {
+        self.reorg_history.iter().rev().take(limit).cloned().collect()
+    }
+    
+    /// Saves the reorganization history to a file from now on, first loading any saved there
+    pub fn set_reorg_log(&mut self, path: PathBuf) -> Result<()> {
+        if path.exists() {
+            let saved: Vec<ReorgRecord> = serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+            let keep = saved.len().saturating_sub(MAX_REORG_HISTORY);
+            self.reorg_history = saved.into_iter().skip(keep).collect();
+        }
+        self.reorg_log = Some(path);
+        Ok(())
+    }
+    
+    /// Saves the reorganization history, replacing the file atomically
+    fn save_reorg_history(&self) -> Result<()> {
+        let Some(path) = &self.reorg_log else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&self.reorg_history)
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)?;
         Ok(())
     }
     
+    /// Saves the blocks to a store in `dir` from now on, first saving those the chain has
+    ///
+    /// The store then mirrors the chain: blocks are saved as they're added
+    /// and removed as they're rolled back, moving its tip along.
+    pub fn set_block_store(&mut self, dir: PathBuf) -> Result<()> {
+        let store = BlockStore::open(dir)?;
+        for height in 0..=self.latest_height {
+            if let Some(block) = self.blocks.get(&height) {
+                store.put_block(block)?;
+            }
+        }
+        for bloom in self.address_blooms.values() {
+            store.put_bloom(bloom)?;
+        }
+        
+        let state_root = state_sync::state_root(&self.state.lock().unwrap());
+        store.set_tip(&StoredTip { height: self.latest_height, block_hash: self.latest_hash, state_root })?;
+        self.block_store = Some(store);
+        Ok(())
+    }
+    
+    /// Verifies the blocks a node saved to its data directory, replaying them offline
+    ///
+    /// See `replay` for what's checked; the chain itself isn't involved.
+    pub fn verify_data_dir(data_dir: &Path, options: &VerifyOptions) -> Result<VerificationReport> {
+        replay::verify_data_dir(data_dir, options)
+    }
+    
+    /// Gets the base fee the next block must carry
+    pub fn next_base_fee(&self) -> u64 {
+        match self.blocks.get(&self.latest_height) {
+            Some(latest) => fee_market::next_base_fee(
+                latest.header().base_fee,
+                self.block_gas_used.get(&self.latest_height).copied().unwrap_or(0),
+                self.block_gas_limit,
+            ),
+            None => crate::genesis::get_initial_base_fee(),
+        }
+    }
+    
+    /// Gets the gas used by the block at a height
+    pub fn get_block_gas_used(&self, height: u64) -> Option<u64> {
+        self.block_gas_used.get(&height).copied()
+    }
+    
     /// Gets a block by its height
     pub fn get_block_by_height(&self, height: u64) -> Option<&Block> {
-        self.blocks.get(&height)
+        self.blocks.get(&height).map(Arc::as_ref)
+    }
+    
+    /// Gets a shared handle to a block by its height, to keep it without copying
+    pub fn get_shared_block(&self, height: u64) -> Option<Arc<Block>> {
+        self.blocks.get(&height).cloned()
+    }
+    
+    /// Gets the height of a block of the chain by its hash
+    ///
+    /// Blocks that were rolled back, or never added, aren't found.
+    pub fn get_height_by_hash(&self, hash: &BlockHash) -> Option<u64> {
+        self.block_heights.get(hash).copied()
+    }
+    
+    /// Gets a block of the chain by its hash
+    pub fn get_block_by_hash(&self, hash: &BlockHash) -> Option<&Block> {
+        self.get_height_by_hash(hash).and_then(|height| self.get_block_by_height(height))
+    }
+    
+    /// Gets the receipt of a transaction, if it hasn't been pruned
+    pub fn get_receipt(&self, tx_id: &TxHash) -> Option<&Receipt> {
+        self.receipts.get(tx_id)
+    }
+    
+    /// Gets the receipt of a transaction, or `None` if the transaction isn't in the chain
+    ///
+    /// Fails with `BlockchainError::Pruned` if its receipt was pruned.
+    pub fn find_receipt(&self, tx_id: &TxHash) -> Result<Option<&Receipt>> {
+        match self.tx_heights.get(tx_id) {
+            Some(&height) if height < self.pruned_height => Err(BlockchainError::Pruned(self.pruned_height)),
+            _ => Ok(self.receipts.get(tx_id)),
+        }
+    }
+    
+    /// Gets the receipts of the block at a height, in the order of its transactions
+    ///
+    /// Fails with `BlockchainError::Pruned` if they were pruned.
+    pub fn get_block_receipts(&self, height: u64) -> Result<Vec<&Receipt>> {
+        if height < self.pruned_height {
+            return Err(BlockchainError::Pruned(self.pruned_height));
+        }
+        let block = self.blocks.get(&height).ok_or(BlockchainError::UnknownBlock { height })?;
+        Ok(block
+            .transactions
+            .iter()
+            .filter_map(|tx| self.receipts.get(&tx.id))
+            .filter(|receipt| receipt.block_height == height)
+            .collect())
+    }
+    
+    /// Gets the height of the block a transaction was included in
+    pub fn get_transaction_height(&self, tx_id: &TxHash) -> Option<u64> {
+        self.tx_heights.get(tx_id).copied()
+    }
+    
+    /// Gets the logs matching a filter, in block order
+    ///
+    /// A filter without a first block starts at the lowest block whose logs
+    /// are kept. One whose first block is lower fails with
+    /// `BlockchainError::Pruned`.
+    pub fn get_logs(&self, filter: &LogFilter) -> Result<Vec<IndexedLog>> {
+        let from = match filter.from_block {
+            Some(from) if from < self.pruned_height => return Err(BlockchainError::Pruned(self.pruned_height)),
+            Some(from) => from,
+            None => self.pruned_height,
+        };
+        let to = filter.to_block.unwrap_or(self.latest_height).min(self.latest_height);
+        
+        Ok((from..=to)
+            .filter_map(|height| self.block_logs.get(&height))
+            .flatten()
+            .filter(|entry| filter.matches_indexed(entry))
+            .cloned()
+            .collect())
+    }
+    
+    /// Gets a page of the logs matching a filter, oldest first by default
+    ///
+    /// Logs are keyed by the height of their block and their position among
+    /// its logs. The filter's range and pruning apply as in `get_logs`.
+    pub fn list_logs(&self, filter: &LogFilter, request: &PageRequest) -> Result<Page<IndexedLog>> {
+        const LISTING: &str = "logs";
+        let from = match filter.from_block {
+            Some(from) if from < self.pruned_height => return Err(BlockchainError::Pruned(self.pruned_height)),
+            Some(from) => from,
+            None => self.pruned_height,
+        };
+        let to = filter.to_block.unwrap_or(self.latest_height).min(self.latest_height);
+        let order = request.order_or(SortOrder::Ascending);
+        let after: Option<(u64, usize)> = request.start_after(LISTING)?;
+        
+        // Only the blocks from the cursor's on are read
+        let heights: Box<dyn Iterator<Item = u64>> = match (order, after) {
+            (SortOrder::Ascending, Some((height, _))) => Box::new(from.max(height)..=to),
+            (SortOrder::Ascending, None) => Box::new(from..=to),
+            (SortOrder::Descending, Some((height, _))) => Box::new((from..=to.min(height)).rev()),
+            (SortOrder::Descending, None) => Box::new((from..=to).rev()),
+        };
+        let entries = heights
+            .filter_map(|height| self.block_logs.get(&height).map(|logs| (height, logs)))
+            .flat_map(move |(height, logs)| {
+                let positions: Box<dyn Iterator<Item = (usize, &IndexedLog)>> = match order {
+                    SortOrder::Ascending => Box::new(logs.iter().enumerate()),
+                    SortOrder::Descending => Box::new(logs.iter().enumerate().rev()),
+                };
+                positions.map(move |(position, entry)| ((height, position), entry))
+            })
+            .filter(|(key, _)| match (after, order) {
+                (None, _) => true,
+                (Some(after), SortOrder::Ascending) => *key > after,
+                (Some(after), SortOrder::Descending) => *key < after,
+            })
+            .filter(|(_, entry)| filter.matches_indexed(entry))
+            .map(|(key, entry)| (key, entry.clone()));
+        Ok(Page::from_sorted(entries, LISTING, request.page_limit(), None))
+    }
+    
+    /// Gets the height below which receipts and logs have been pruned, zero if none have
+    pub fn pruned_height(&self) -> u64 {
+        self.pruned_height
+    }
+    
+    /// Drops the receipts and logs of the blocks below `height`
+    ///
+    /// Queries for them fail with `BlockchainError::Pruned` from then on,
+    /// though their transactions can still be found by ID. Heights already
+    /// pruned are skipped, and the latest block's receipts are always kept.
+    pub fn prune_receipts(&mut self, height: u64) -> PruneStats {
+        let height = height.min(self.latest_height);
+        let mut stats = PruneStats::default();
+        
+        for h in self.pruned_height..height {
+            let transactions = self.blocks.get(&h).map_or(&[][..], |block| &block.transactions[..]);
+            for tx in transactions {
+                // A later transaction with the same ID, such as a coinbase, keeps its receipt
+                if let Entry::Occupied(entry) = self.receipts.entry(tx.id) {
+                    if entry.get().block_height == h {
+                        stats.receipts += 1;
+                        stats.bytes += entry.remove().to_bytes().len() as u64;
+                    }
+                }
+            }
+            for entry in self.block_logs.remove(&h).unwrap_or_default() {
+                stats.logs += 1;
+                stats.bytes += entry.log.to_bytes().len() as u64;
+            }
+        }
+        
+        self.pruned_height = self.pruned_height.max(height);
+        stats
     }
     
     /// Gets the latest block in the chain
     pub fn get_latest_block(&self) -> Option<&Block> {
-        self.blocks.get(&self.latest_height)
+        self.blocks.get(&self.latest_height).map(Arc::as_ref)
+    }
+    
+    /// Gets the height of the latest block in the chain
+    pub fn get_latest_height(&self) -> u64 {
+        self.latest_height
+    }
+    
+    /// Gets the hash of the latest block in the chain
+    pub fn get_latest_hash(&self) -> BlockHash {
+        self.latest_hash
+    }
+    
+    /// Gets the weight of the chain by its fork choice rule, its length unless set otherwise
+    pub fn chain_weight(&self) -> u128 {
+        self.chain_weights.last().copied().unwrap_or(0)
+    }
+    
+    /// Gets the GENX the blocks from `start_height` to `end_height`, both included, minted
+    ///
+    /// Sums the coinbase transactions the blocks actually hold, so a block
+    /// that forfeited its reward adds nothing; the genesis block's are the
+    /// genesis allocation. Fails with `BlockchainError::UnknownBlock` if a
+    /// height isn't in the chain, and is zero if `end_height` is below
+    /// `start_height`.
+    pub fn emission_between(&self, start_height: u64, end_height: u64) -> Result<u64> {
+        let mut emitted = 0u64;
+        for height in start_height..=end_height {
+            let block = self.blocks.get(&height).ok_or(BlockchainError::UnknownBlock { height })?;
+            let minted = block.transactions.iter().filter(|tx| tx.sender == "COINBASE").map(|tx| tx.amount);
+            emitted = minted.fold(emitted, u64::saturating_add);
+        }
+        Ok(emitted)
+    }
+    
+    /// Gets the most recent transactions sent or received by an address, newest first
+    ///
+    /// Returns each transaction with the height of its block. Blocks are
+    /// scanned from the latest down until `limit` transactions are found.
+    pub fn get_transactions_for_address(&self, address: &str, limit: usize) -> Vec<(u64, &Transaction)> {
+        self.address_transactions(address, SortOrder::Descending, None)
+            .map(|(_, entry)| entry)
+            .take(limit)
+            .collect()
+    }
+    
+    /// Gets a page of the transactions sent or received by an address, newest first by default
+    ///
+    /// Transactions are keyed by the height of their block and their index
+    /// in it, and returned with the height. Blocks are scanned from the
+    /// cursor's on, so no total is estimated.
+    pub fn list_transactions_for_address(&self, address: &str, request: &PageRequest) -> Result<Page<(u64, &Transaction)>> {
+        const LISTING: &str = "address_transactions";
+        let after = request.start_after(LISTING)?;
+        let entries = self.address_transactions(address, request.order_or(SortOrder::Descending), after);
+        Ok(Page::from_sorted(entries, LISTING, request.page_limit(), None))
+    }
+    
+    /// Iterates over an address's transactions keyed by height and index, those after `after` in `order`
+    fn address_transactions<'a: 'b, 'b>(
+        &'a self,
+        address: &'b str,
+        order: SortOrder,
+        after: Option<(u64, usize)>,
+    ) -> impl Iterator<Item = ((u64, usize), (u64, &'a Transaction))> + 'b {
+        let heights: Box<dyn Iterator<Item = u64>> = match (order, after) {
+            (SortOrder::Ascending, Some((height, _))) => Box::new(height..=self.latest_height),
+            (SortOrder::Ascending, None) => Box::new(0..=self.latest_height),
+            (SortOrder::Descending, Some((height, _))) => Box::new((0..=height.min(self.latest_height)).rev()),
+            (SortOrder::Descending, None) => Box::new((0..=self.latest_height).rev()),
+        };
+        heights
+            .filter_map(|height| self.blocks.get(&height).map(|block| (height, block)))
+            .flat_map(move |(height, block)| {
+                let transactions: Box<dyn Iterator<Item = (usize, &Transaction)>> = match order {
+                    SortOrder::Ascending => Box::new(block.transactions.iter().enumerate()),
+                    SortOrder::Descending => Box::new(block.transactions.iter().enumerate().rev()),
+                };
+                transactions
+                    .filter(move |(_, tx)| tx.sender == address || tx.recipient == address)
+                    .map(move |(index, tx)| ((height, index), (height, tx)))
+            })
+            .filter(move |(key, _)| match (after, order) {
+                (None, _) => true,
+                (Some(after), SortOrder::Ascending) => *key > after,
+                (Some(after), SortOrder::Descending) => *key < after,
+            })
+    }
+    
+    /// Sets the size and accuracy of the address blooms, rebuilding them
+    ///
+    /// See `address_bloom`. Blooms saved to a block store are replaced as
+    /// the next block of their epoch is added.
+    pub fn set_bloom_params(&mut self, params: BloomParams) -> Result<()> {
+        params.validate()?;
+        self.bloom_params = params;
+        self.rebuild_blooms(0);
+        Ok(())
+    }
+    
+    /// Gets the address bloom of an epoch, if the chain has blocks in it
+    pub fn address_bloom(&self, epoch: u64) -> Option<&AddressBloom> {
+        self.address_blooms.get(&epoch)
+    }
+    
+    /// Checks whether an address may have sent or received a transaction in any block
+    ///
+    /// Only the epochs' address blooms are read, so it's cheap but may be
+    /// true of an address never used, at most at the blooms' false positive
+    /// rate for each epoch (see `address_bloom`). It's never false of an
+    /// address used; `address_first_used` confirms a true answer.
+    pub fn address_possibly_used(&self, address: &Address) -> bool {
+        self.address_blooms.values().any(|bloom| bloom.contains(address))
+    }
+    
+    /// Gets the height of the first block an address sent or received a transaction in, if any
+    ///
+    /// Only the blocks of the epochs whose address blooms hold the address
+    /// are scanned, which is slower than `address_possibly_used` but exact.
+    pub fn address_first_used(&self, address: &Address) -> Option<u64> {
+        self.address_blooms
+            .values()
+            .filter(|bloom| bloom.contains(address))
+            .flat_map(|bloom| validator_set::first_height(bloom.epoch)..validator_set::first_height(bloom.epoch + 1))
+            .take_while(|height| *height <= self.latest_height)
+            .find(|height| {
+                self.blocks.get(height).is_some_and(|block| address_bloom::block_addresses(block).contains(address.as_str()))
+            })
+    }
+    
+    /// Adds the addresses a block uses to its epoch's bloom, rebuilding the bloom larger once it's full
+    fn index_addresses(&mut self, block: &Block) {
+        let epoch = epoch_of(block.header().height);
+        let params = self.bloom_params;
+        let bloom = self.address_blooms.entry(epoch).or_insert_with(|| AddressBloom::new(epoch, &params));
+        for address in address_bloom::block_addresses(block) {
+            bloom.insert(address);
+        }
+        if bloom.is_full() {
+            let epoch_blocks = self.epoch_blocks(epoch).chain([block]);
+            let rebuilt = AddressBloom::for_blocks(epoch, &params, epoch_blocks);
+            self.address_blooms.insert(epoch, rebuilt);
+        }
+    }
+    
+    /// Rebuilds the address blooms of the epochs from `from_epoch` on from their blocks, returning the epochs
+    ///
+    /// Epochs left without blocks lose their blooms.
+    fn rebuild_blooms(&mut self, from_epoch: u64) -> Vec<u64> {
+        let epochs: Vec<u64> = self.address_blooms.range(from_epoch..).map(|(&epoch, _)| epoch).collect();
+        for &epoch in &epochs {
+            if validator_set::first_height(epoch) > self.latest_height {
+                self.address_blooms.remove(&epoch);
+            } else {
+                let bloom = AddressBloom::for_blocks(epoch, &self.bloom_params, self.epoch_blocks(epoch));
+                self.address_blooms.insert(epoch, bloom);
+            }
+        }
+        epochs
+    }
+    
+    /// Iterates over the chain's blocks in an epoch, in order
+    fn epoch_blocks(&self, epoch: u64) -> impl Iterator<Item = &Block> {
+        (validator_set::first_height(epoch)..validator_set::first_height(epoch + 1))
+            .map_while(|height| self.blocks.get(&height).map(|block| &**block))
+    }
+    
+    /// Gets the height of the latest block made at or before `timestamp`
+    ///
+    /// That's the chain's tip as of `timestamp`: the latest block for
+    /// timestamps after it, and `None` for those before the genesis block.
+    /// Takes O(log n) time for a chain of n blocks.
+    pub fn find_block_by_time(&self, timestamp: u64) -> Option<u64> {
+        let count = self.block_times.partition_point(|&time| time <= timestamp);
+        count.checked_sub(1).map(|height| height as u64)
+    }
+    
+    /// Gets the heights of the blocks made from `from` to `to`, both included
+    ///
+    /// The range is empty if no block falls between them, including when
+    /// `from` is after `to`.
+    pub fn heights_in_time_range(&self, from: u64, to: u64) -> Range<u64> {
+        let start = self.block_times.partition_point(|&time| time < from) as u64;
+        let end = self.block_times.partition_point(|&time| time <= to) as u64;
+        start..end.max(start)
+    }
+    
+    /// Gets the blocks made from `from` to `to`, both included, oldest first
+    ///
+    /// Heights in the range whose blocks aren't stored are skipped.
+    pub fn get_blocks_in_time_range(&self, from: u64, to: u64) -> impl Iterator<Item = &Block> {
+        self.heights_in_time_range(from, to).filter_map(|height| self.get_block_by_height(height))
+    }
+    
+    /// Gets the transactions of the blocks made from `from` to `to`, both included, oldest first
+    ///
+    /// Returns each transaction with the height of its block and its index
+    /// in the block.
+    pub fn get_transactions_in_time_range(&self, from: u64, to: u64) -> impl Iterator<Item = (u64, usize, &Transaction)> {
+        self.get_blocks_in_time_range(from, to).flat_map(|block| {
+            let height = block.header().height;
+            block.transactions.iter().enumerate().map(move |(index, tx)| (height, index, tx))
+        })
+    }
+    
+    /// Lists the changes to balances, stakes and contract storage from the
+    /// state after `from_height` to the state after `to_height`
+    ///
+    /// With an address, only the changes to that account, validator or
+    /// contract are listed. Both heights must be within the last
+    /// `MAX_ROLLBACK_DEPTH` blocks.
+    pub fn state_diff(&self, from_height: u64, to_height: u64, address: Option<&str>) -> Result<StateDiff> {
+        Ok(StateDiff {
+            from_height,
+            to_height,
+            changes: self.state_changes(from_height, to_height, address)?.collect(),
+        })
+    }
+    
+    /// Like `state_diff`, but builds the changes one at a time as they're iterated over
+    pub fn state_changes(&self, from_height: u64, to_height: u64, address: Option<&str>) -> Result<StateChanges> {
+        if from_height > to_height || to_height > self.latest_height {
+            return Err(BlockchainError::InvalidBlock(format!(
+                "Invalid height range {}..{} for a chain of height {}",
+                from_height, to_height, self.latest_height
+            )));
+        }
+        
+        if (from_height + 1..=self.latest_height).any(|h| !self.block_undo.contains_key(&h)) {
+            return Err(BlockchainError::BeyondRollbackDepth { max_depth: MAX_ROLLBACK_DEPTH });
+        }
+        
+        // The first change after a height recorded the value at that height
+        let mut old = BTreeMap::new();
+        for h in from_height + 1..=to_height {
+            state_diff::record_first(&mut old, self.block_undo[&h].previous_values(), address);
+        }
+        let mut later = BTreeMap::new();
+        for h in to_height + 1..=self.latest_height {
+            state_diff::record_first(&mut later, self.block_undo[&h].previous_values(), address);
+        }
+        
+        Ok(StateChanges::new(old, later, self.snapshot.latest().state))
+    }
+    
+    /// Sets the most blocks `state_at` undoes to reconstruct a past state
+    ///
+    /// Undo records are only kept for the last `MAX_ROLLBACK_DEPTH` blocks,
+    /// so a larger depth reaches no further.
+    pub fn set_max_state_depth(&mut self, depth: u64) {
+        self.max_state_depth = depth;
+    }
+    
+    /// Gets the oldest height whose state `state_at` can reconstruct
+    pub fn oldest_state_height(&self) -> u64 {
+        let mut oldest = self.latest_height;
+        while oldest > 0 && self.latest_height - oldest < self.max_state_depth && self.block_undo.contains_key(&oldest) {
+            oldest -= 1;
+        }
+        oldest
+    }
+    
+    /// Gets the state after the block at a height
+    ///
+    /// Past states are reconstructed from the latest snapshot by undoing
+    /// the blocks after `height`, newest first, which is why only the
+    /// latest `max_state_depth` blocks are reachable; older heights fail
+    /// with `BlockchainError::StateUnavailable`. The returned snapshot
+    /// shares nothing with the live state and can't be written to.
+    pub fn state_at(&self, height: u64) -> Result<StateSnapshot> {
+        if height > self.latest_height {
+            return Err(BlockchainError::UnknownBlock { height });
+        }
+        
+        let latest = self.snapshot.latest();
+        if height == latest.block_height {
+            return Ok(latest);
+        }
+        
+        let oldest = self.oldest_state_height();
+        if height < oldest {
+            return Err(BlockchainError::StateUnavailable { height, oldest });
+        }
+        
+        let mut state = (*latest.state).clone();
+        for h in (height + 1..=self.latest_height).rev() {
+            state.rollback_block(self.block_undo[&h].clone());
+        }
+        let block_timestamp = self.get_block_by_height(height).map_or(0, |block| block.header().timestamp);
+        Ok(StateSnapshot { block_height: height, block_timestamp, state: Arc::new(state) })
     }
     
     /// Gets the current state of the blockchain
@@ -104,51 +1280,148 @@ impl Blockchain {
         self.state.clone()
     }
     
+    /// Gets a snapshot of the state after the latest block
+    pub fn snapshot(&self) -> StateSnapshot {
+        self.snapshot.latest()
+    }
+    
+    /// Changes the current state outside of any block, publishing the snapshot it leaves
+    ///
+    /// For test and development chains, such as to stake validators or set
+    /// balances: no block records the change, so other nodes never see it,
+    /// and states read with `state_at` at past heights keep it.
+    pub fn override_state<R>(&mut self, change: impl FnOnce(&mut State) -> R) -> R {
+        let mut state = self.state.lock().unwrap();
+        let result = change(&mut state);
+        let latest = self.snapshot.latest();
+        self.snapshot.publish(StateSnapshot { state: Arc::new(state.clone()), ..latest });
+        result
+    }
+    
+    /// Gets the cache of verified transactions that block validation consults
+    ///
+    /// Validating transactions admitted to the mempool with it saves
+    /// verifying their signatures again when their block arrives.
+    pub fn verified_tx_cache(&self) -> Arc<VerifiedTxCache> {
+        self.verified_txs.clone()
+    }
+    
+    /// Gets a handle that follows the latest state snapshot
+    ///
+    /// Lets readers get snapshots without locking the blockchain.
+    pub fn snapshot_handle(&self) -> SnapshotHandle {
+        self.snapshot.clone()
+    }
+    
     /// Gets the balance of an account
-    pub fn get_balance(&self, address: &str) -> Result<u64> {
+    pub fn get_balance(&self, address: &Address) -> Result<Amount> {
         let state = self.state.lock().unwrap();
         Ok(state.get_balance(address))
     }
     
+    /// Gets the balance of an unchecked address, in base units
+    #[deprecated(note = "use `Blockchain::get_balance` with an `Address`")]
+    pub fn get_balance_untyped(&self, address: &str) -> Result<u64> {
+        let state = self.state.lock().unwrap();
+        Ok(StateAccess::get_balance(&*state, address))
+    }
+    
+    /// Gets the nonce an account's next transaction must have, see `State::get_nonce`
+    pub fn get_nonce(&self, address: &Address) -> Result<u64> {
+        let state = self.state.lock().unwrap();
+        Ok(state.get_nonce(address))
+    }
+    
+    /// Applies a block to a copy of the state after the latest block, leaving the chain as it is
+    ///
+    /// Only executes the transactions, as `add_block` does once the block
+    /// passes its other checks; the block gas limit isn't checked. Returns
+    /// the receipts and the state the block would leave.
+    pub fn apply_to_copy(&self, block: &Block) -> Result<(Vec<Receipt>, State)> {
+        let mut state = self.state.lock().unwrap().clone();
+        let receipts = match &self.contract_executor {
+            Some(executor) => {
+                let mut executor = executor.lock().unwrap();
+                state.apply_block_with_executor(block, Some(&mut *executor))?
+            }
+            None => state.apply_block_with_executor(block, None)?,
+        };
+        Ok((receipts, state))
+    }
+    
     /// Validates the entire blockchain
+    ///
+    /// Blocks are replayed from a fresh state, running contract transactions
+    /// through the executor as they were when the blocks were added. Every
+    /// signature is verified again, whether or not the verified transaction
+    /// cache holds it.
     pub fn validate_chain(&self) -> Result<()> {
         // Start with a fresh state
         let mut state = State::new();
         
         // Validate each block in order
         for height in 0..=self.latest_height {
-            let block = self.blocks.get(&height).ok_or_else(|| {
-                BlockchainError::StateError(format!("Missing block at height {}", height))
-            })?;
+            let block = self.blocks.get(&height).ok_or(BlockchainError::UnknownBlock { height })?;
             
             // Validate the block
             block.validate()?;
             
             // Apply the block to the state
-            state.apply_block(block)?;
+            match &self.contract_executor {
+                Some(executor) => {
+                    let mut executor = executor.lock().unwrap();
+                    state.apply_block_with_executor(block, Some(&mut *executor))?;
+                }
+                None => {
+                    state.apply_block_with_executor(block, None)?;
+                }
+            }
         }
         
         Ok(())
     }
     
-    /// Creates a new transaction and adds it to the mempool
+    /// Creates a transfer with the sender's next nonce, checking that the sender's balance covers it
     pub fn create_transaction(
         &self,
-        sender: String,
-        recipient: String,
-        amount: u64,
-        fee: u64,
+        sender: Address,
+        recipient: Address,
+        amount: Amount,
+        fee: Amount,
         data: Option<Vec<u8>>,
     ) -> Result<Transaction> {
         // Check that the sender has sufficient balance
         let sender_balance = self.get_balance(&sender)?;
-        if sender_balance < amount + fee {
-            return Err(BlockchainError::InvalidTransaction(
-                format!("Insufficient balance: {} < {}", sender_balance, amount + fee)
-            ));
+        let required = amount.base_units().saturating_add(fee.base_units());
+        if sender_balance.base_units() < required {
+            return Err(BlockchainError::InsufficientBalance {
+                address: sender.into_string(),
+                required,
+                available: sender_balance.base_units(),
+            });
         }
         
         // Create the transaction
-        Transaction::new(sender, recipient, amount, fee, data)
+        let nonce = self.get_nonce(&sender)?;
+        Transaction::new(sender, recipient, amount, fee, data)?.with_nonce(nonce)
+    }
+    
+    /// Creates a transfer from unchecked addresses and amounts in base units
+    #[deprecated(note = "use `Blockchain::create_transaction` with an `Address` and an `Amount`")]
+    pub fn create_transaction_untyped(
+        &self,
+        sender: String,
+        recipient: String,
+        amount: u64,
+        fee: u64,
+        data: Option<Vec<u8>>,
+    ) -> Result<Transaction> {
+        self.create_transaction(
+            Address::new(sender)?,
+            Address::new(recipient)?,
+            Amount::from_base_units(amount),
+            Amount::from_base_units(fee),
+            data,
+        )
     }
 }
\ No newline at end of file
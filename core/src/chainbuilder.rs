@@ -0,0 +1,594 @@
+//! Deterministic test chains
+//!
+//! Built with the `testutil` feature, for this workspace's tests and those
+//! of crates building on it. A `TestChain` starts from a genesis block
+//! funding the accounts and registering the validators of its
+//! `TestChainConfig`, whose keys are derived from a seed, and grows a block
+//! at a time:
+//!
+//! ```ignore
+//! let mut chain = TestChain::new(7);
+//! chain.with_block(|b| b.transfer("alice", "bob", 5 * GENX).deploy("carol", payload));
+//! chain.assert_balances();
+//! ```
+//!
+//! Nothing depends on the clock: block `n` is stamped `block_interval`
+//! seconds after block `n - 1`, starting from `genesis_timestamp`, and its
+//! transactions carry its timestamp. Two chains made from the same seed
+//! and configuration, with the same blocks, are byte-identical.
+//!
+//! The builder stamps, signs and prices each transaction: transfers pay
+//! the configured flat fee, contract transactions the block's base fee per
+//! gas. Each gets its sender's next nonce, in the order they're added; a
+//! block ordering transactions canonically must then pay no more for a
+//! sender's later transactions than for its earlier ones. A transaction
+//! that would still repeat an earlier transaction's ID, such as a coinbase,
+//! is stamped a second later instead. Blocks are proposed
+//! by the validators in turn, unless a block names its proposer and time
+//! (see `BlockBuilder::proposed_by` and `BlockBuilder::at`), pay their
+//! rewards as `rewards` requires and are signed with their proposer's key,
+//! if it's one of the chain's, as the chain requires of registered
+//! validators. Once the
+//! chain orders transactions canonically (see `ordering`), each block's
+//! are sorted into that order.
+//!
+//! The builder also keeps the balances it expects. Transfers, fees and
+//! rewards are followed exactly; contract transactions are charged for the
+//! gas their receipts say they used. Storage deposits and transfers made by
+//! contracts aren't followed, so tests running a contract executor compare
+//! only the balances of accounts those leave alone. Validators' stakes
+//! can't be moved by transactions yet, so they're set in the state
+//! directly after genesis.
+//!
+//! Builder methods panic saying what went wrong, like the checks of
+//! `testutil`, so they work under any test harness.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::block::{Block, BlockHeader};
+use crate::chain::Blockchain;
+use crate::genesis;
+use crate::ordering::OrderKey;
+use crate::receipt::Receipt;
+use crate::rewards::COINBASE;
+use crate::signature::{self, SignatureScheme};
+use crate::transaction::{Transaction, TransactionType};
+use crate::units::{Amount, GENX};
+use crate::validator::ValidatorRegistration;
+use crate::{Address, BlockchainError, Bytes, Result, TxHash};
+
+/// Accounts, validators and prices of a test chain
+#[derive(Debug, Clone)]
+pub struct TestChainConfig {
+    /// Accounts funded at genesis, by name, with their balances in base units
+    pub accounts: Vec<(String, u64)>,
+    
+    /// Validators registered at genesis, by name, with their stakes in base units
+    ///
+    /// No transaction records the stakes; they're set in the state after
+    /// the genesis block. Validators staking nothing aren't, so a chain of
+    /// them has the state its blocks make, as a replay of them rebuilds it.
+    pub validators: Vec<(String, u64)>,
+    
+    /// Scheme of the accounts' and validators' keys
+    pub scheme: SignatureScheme,
+    
+    /// Timestamp of the genesis block
+    pub genesis_timestamp: u64,
+    
+    /// Seconds between blocks
+    pub block_interval: u64,
+    
+    /// Flat fee of each transfer, in base units
+    pub transfer_fee: u64,
+    
+    /// Gas limit of each contract transaction
+    pub contract_gas_limit: u64,
+}
+
+impl Default for TestChainConfig {
+    fn default() -> Self {
+        Self {
+            accounts: ["alice", "bob", "carol"].iter().map(|name| (name.to_string(), 1_000 * GENX)).collect(),
+            validators: vec![("validator".to_string(), 1_000 * GENX)],
+            scheme: SignatureScheme::Ed25519,
+            genesis_timestamp: 1_700_000_000,
+            block_interval: 5,
+            transfer_fee: 1_000,
+            contract_gas_limit: 1_000_000,
+        }
+    }
+}
+
+/// Account of a test chain, with its key
+#[derive(Debug, Clone)]
+pub struct TestAccount {
+    /// Name the account was configured with
+    pub name: String,
+    
+    /// Address of the account's key
+    pub address: Address,
+    
+    /// Scheme of the key
+    pub scheme: SignatureScheme,
+    
+    /// Secret key, derived from the chain's seed
+    pub secret_key: Vec<u8>,
+}
+
+impl TestAccount {
+    /// Derives an account's key from the chain's random number generator
+    fn generate(name: &str, scheme: SignatureScheme, rng: &mut StdRng) -> Self {
+        let secret_key = rng.gen::<[u8; 32]>().to_vec();
+        let address = signature::address_of(scheme, &secret_key)
+            .unwrap_or_else(|e| panic!("Can't derive the key of test account {}: {}", name, e));
+        let address = Address::new(address).expect("key addresses are well formed");
+        Self { name: name.to_string(), address, scheme, secret_key }
+    }
+    
+    /// Gets the public key
+    pub fn public_key(&self) -> Vec<u8> {
+        signature::parse_address(&self.address).map(|(_, key)| key).expect("the address encodes the key")
+    }
+    
+    /// Signs a transaction sent by the account
+    pub fn sign(&self, tx: &mut Transaction) -> Result<()> {
+        tx.sign(&self.secret_key)
+    }
+    
+    /// Signs a block header as a validator using the account's key would, such as for slashing evidence
+    pub fn sign_header(&self, header: &mut BlockHeader) -> Result<()> {
+        let header_signature = signature::sign(self.scheme, &self.secret_key, &header.signing_hash()?)
+            .map_err(|e| BlockchainError::InvalidTransaction(e.to_string()))?;
+        header.signature = Some(Bytes(header_signature));
+        Ok(())
+    }
+}
+
+/// Chain built block by block from a seed, see the module documentation
+pub struct TestChain {
+    blockchain: Blockchain,
+    config: TestChainConfig,
+    
+    /// Accounts, then validators, in the order they were configured
+    accounts: Vec<TestAccount>,
+    
+    /// Balances the builder expects, by address
+    expected: BTreeMap<String, u64>,
+    
+    /// Addresses of the contracts deployed, oldest first
+    contracts: Vec<String>,
+    
+    /// IDs of every transaction in the chain
+    tx_ids: HashSet<TxHash>,
+}
+
+impl TestChain {
+    /// Creates a chain with the default configuration
+    pub fn new(seed: u64) -> Self {
+        Self::with_config(seed, TestChainConfig::default())
+    }
+    
+    /// Creates a chain with a configuration
+    ///
+    /// Keys are derived from the seed in the order the accounts and then the
+    /// validators are configured.
+    pub fn with_config(seed: u64, config: TestChainConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let accounts: Vec<TestAccount> = config
+            .accounts
+            .iter()
+            .map(|(name, _)| name)
+            .chain(config.validators.iter().map(|(name, _)| name))
+            .map(|name| TestAccount::generate(name, config.scheme, &mut rng))
+            .collect();
+        
+        let genesis = genesis_block(&config, &accounts).unwrap_or_else(|e| panic!("Can't build the genesis block: {}", e));
+        let blockchain = Blockchain::new(genesis.clone()).unwrap_or_else(|e| panic!("Invalid genesis block: {}", e));
+        let mut chain = Self {
+            blockchain,
+            config,
+            accounts,
+            expected: BTreeMap::new(),
+            contracts: Vec::new(),
+            tx_ids: HashSet::new(),
+        };
+        for tx in &genesis.transactions {
+            chain.tx_ids.insert(tx.id);
+            if tx.sender == COINBASE {
+                chain.credit(&tx.recipient, tx.amount);
+            }
+        }
+        
+        {
+            let state = chain.blockchain.get_state();
+            let mut state = state.lock().unwrap();
+            for (validator, (_, stake)) in chain.validators().iter().zip(&chain.config.validators).filter(|(_, (_, stake))| *stake > 0) {
+                state.update_validator_stake(validator.address.clone(), Amount::from_base_units(*stake));
+            }
+        }
+        chain
+    }
+    
+    /// Builds the next block and adds it to the chain
+    ///
+    /// Panics if the chain rejects the block.
+    pub fn with_block(
+        &mut self,
+        build: impl for<'a, 'b> FnOnce(&'b mut BlockBuilder<'a>) -> &'b mut BlockBuilder<'a>,
+    ) -> &mut Self {
+        let block = self.next_block(build);
+        self.add_block(block)
+    }
+    
+    /// Adds blocks holding nothing but their rewards
+    pub fn with_empty_blocks(&mut self, count: u64) -> &mut Self {
+        for _ in 0..count {
+            self.with_block(|b| b);
+        }
+        self
+    }
+    
+    /// Builds the next block without adding it, for tests of blocks the chain should reject
+    pub fn next_block(
+        &self,
+        build: impl for<'a, 'b> FnOnce(&'b mut BlockBuilder<'a>) -> &'b mut BlockBuilder<'a>,
+    ) -> Block {
+        let mut builder = BlockBuilder::new(self);
+        build(&mut builder);
+        builder.build().unwrap_or_else(|e| panic!("Can't build block {}: {}", self.height() + 1, e))
+    }
+    
+    /// Adds a block built with `next_block`, following its effects on the expected balances
+    ///
+    /// Panics if the chain rejects the block.
+    pub fn add_block(&mut self, block: Block) -> &mut Self {
+        let height = block.header().height;
+        self.blockchain.add_block(block.clone()).unwrap_or_else(|e| panic!("Block {} was rejected: {}", height, e));
+        
+        let payout_address = self.blockchain.get_state().lock().unwrap().get_payout_address(&block.header().validator, height);
+        for tx in &block.transactions {
+            self.tx_ids.insert(tx.id);
+            let receipt = self.blockchain.get_receipt(&tx.id).cloned().unwrap_or_else(|| Receipt::new(tx.id, height));
+            self.follow(tx, &receipt, block.header().base_fee, &payout_address);
+        }
+        self
+    }
+    
+    /// Follows a transaction's effects on the expected balances
+    fn follow(&mut self, tx: &Transaction, receipt: &Receipt, base_fee: u64, payout_address: &str) {
+        if tx.sender == COINBASE {
+            self.credit(&tx.recipient, tx.amount);
+            return;
+        }
+        
+        match tx.tx_type {
+            TransactionType::ContractDeploy | TransactionType::ContractCall => {
+                let fee = tx.fee_for_gas(receipt.gas_used);
+                let burned = receipt.gas_used.min(tx.gas_limit).saturating_mul(base_fee).min(fee);
+                self.debit(&tx.sender, fee);
+                self.credit(payout_address, fee - burned);
+                
+                let recipient = match tx.tx_type {
+                    TransactionType::ContractDeploy => receipt.contract_address.clone(),
+                    _ => Some(tx.recipient.clone()),
+                };
+                if let (true, Some(recipient)) = (receipt.success, recipient) {
+                    self.debit(&tx.sender, tx.amount);
+                    self.credit(&recipient, tx.amount);
+                }
+                if let (TransactionType::ContractDeploy, Some(contract)) = (tx.tx_type, &receipt.contract_address) {
+                    self.contracts.push(contract.clone());
+                }
+            }
+            TransactionType::RegisterValidator
+            | TransactionType::EditValidator
+            | TransactionType::Vote
+            | TransactionType::SubmitEvidence
+            | TransactionType::CancelSlash
+            | TransactionType::SetExecutionPolicy => {
+                self.debit(&tx.sender, tx.fee);
+            }
+            TransactionType::SubmitProposal => {
+                self.debit(&tx.sender, tx.amount + tx.fee);
+            }
+            TransactionType::Transfer | TransactionType::Stake | TransactionType::Unstake => {
+                self.debit(&tx.sender, tx.amount + tx.fee);
+                self.credit(&tx.recipient, tx.amount);
+            }
+        }
+    }
+    
+    fn credit(&mut self, address: &str, amount: u64) {
+        let balance = self.expected.entry(address.to_string()).or_default();
+        *balance = balance.saturating_add(amount);
+    }
+    
+    fn debit(&mut self, address: &str, amount: u64) {
+        let balance = self.expected.entry(address.to_string()).or_default();
+        *balance = balance.saturating_sub(amount);
+    }
+    
+    /// Gets the chain
+    pub fn blockchain(&self) -> &Blockchain {
+        &self.blockchain
+    }
+    
+    /// Gets the chain for changes of its own, such as setting a contract executor
+    pub fn blockchain_mut(&mut self) -> &mut Blockchain {
+        &mut self.blockchain
+    }
+    
+    /// Takes the chain
+    pub fn into_blockchain(self) -> Blockchain {
+        self.blockchain
+    }
+    
+    /// Gets the configuration
+    pub fn config(&self) -> &TestChainConfig {
+        &self.config
+    }
+    
+    /// Gets the height of the latest block
+    pub fn height(&self) -> u64 {
+        self.blockchain.get_latest_height()
+    }
+    
+    /// Gets the blocks from genesis on
+    pub fn blocks(&self) -> Vec<&Block> {
+        (0..=self.height()).filter_map(|height| self.blockchain.get_block_by_height(height)).collect()
+    }
+    
+    /// Gets the configured accounts
+    pub fn accounts(&self) -> &[TestAccount] {
+        &self.accounts[..self.config.accounts.len()]
+    }
+    
+    /// Gets the configured validators, whose keys are both their operator and consensus keys
+    pub fn validators(&self) -> &[TestAccount] {
+        &self.accounts[self.config.accounts.len()..]
+    }
+    
+    /// Gets an account or validator by name
+    ///
+    /// Panics if there's none of that name.
+    pub fn account(&self, name: &str) -> &TestAccount {
+        self.find(name).unwrap_or_else(|| panic!("No test account is named {}", name))
+    }
+    
+    fn find(&self, name: &str) -> Option<&TestAccount> {
+        self.accounts.iter().find(|account| account.name == name)
+    }
+    
+    /// Gets the address of an account or validator by name, or takes `who` as an address
+    pub fn address(&self, who: &str) -> String {
+        self.find(who).map_or_else(|| who.to_string(), |account| account.address.to_string())
+    }
+    
+    /// Gets the addresses of the contracts deployed, oldest first
+    pub fn contracts(&self) -> &[String] {
+        &self.contracts
+    }
+    
+    /// Gets the balance expected of an account, by name or address
+    pub fn expected_balance(&self, who: &str) -> u64 {
+        self.expected.get(&self.address(who)).copied().unwrap_or(0)
+    }
+    
+    /// Gets the balances expected, by address
+    pub fn expected_balances(&self) -> &BTreeMap<String, u64> {
+        &self.expected
+    }
+    
+    /// Checks that every expected balance is the chain's
+    pub fn assert_balances(&self) {
+        let state = self.blockchain.get_state();
+        let state = state.lock().unwrap();
+        for (address, &expected) in &self.expected {
+            let address = Address::new(address.as_str()).unwrap_or_else(|e| panic!("Expected a balance of {}: {}", address, e));
+            let balance = state.get_balance(&address).base_units();
+            assert_eq!(balance, expected, "Balance of {} at height {}", address, self.height());
+        }
+    }
+}
+
+/// Transactions of the next block of a `TestChain`
+pub struct BlockBuilder<'a> {
+    chain: &'a TestChain,
+    height: u64,
+    timestamp: u64,
+    base_fee: u64,
+    transactions: Vec<Transaction>,
+    
+    /// Address of the validator proposing the block, if not the one whose turn it is
+    proposer: Option<String>,
+    
+    /// IDs of the transactions added so far
+    ids: HashSet<TxHash>,
+}
+
+impl<'a> BlockBuilder<'a> {
+    fn new(chain: &'a TestChain) -> Self {
+        let height = chain.height() + 1;
+        Self {
+            chain,
+            height,
+            timestamp: chain.config.genesis_timestamp + height * chain.config.block_interval,
+            base_fee: chain.blockchain.next_base_fee(),
+            transactions: Vec::new(),
+            proposer: None,
+            ids: HashSet::new(),
+        }
+    }
+    
+    /// Gets the height of the block
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+    
+    /// Gets the timestamp of the block
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+    
+    /// Stamps the block with another timestamp, such as to leave slots empty before it
+    ///
+    /// Transactions added before are stamped with the previous one, so this goes first.
+    pub fn at(&mut self, timestamp: u64) -> &mut Self {
+        self.timestamp = timestamp;
+        self
+    }
+    
+    /// Has a validator, by name or address, propose the block instead of the one whose turn it is
+    pub fn proposed_by(&mut self, validator: &str) -> &mut Self {
+        self.proposer = Some(self.chain.address(validator));
+        self
+    }
+    
+    /// Gets the base fee of the block, which contract transactions pay per gas
+    pub fn base_fee(&self) -> u64 {
+        self.base_fee
+    }
+    
+    /// Adds a transfer between accounts, by name or address, paying the configured fee
+    pub fn transfer(&mut self, from: &str, to: &str, amount: u64) -> &mut Self {
+        let tx = Transaction::new_with_type(
+            TransactionType::Transfer,
+            self.chain.address(from),
+            self.chain.address(to),
+            amount,
+            self.chain.config.transfer_fee,
+            None,
+            0,
+            0,
+        );
+        self.transaction(tx.unwrap_or_else(|e| panic!("Can't create a transfer from {}: {}", from, e)))
+    }
+    
+    /// Adds a contract deployment, `payload` being its init code, ABI and constructor arguments
+    pub fn deploy(&mut self, from: &str, payload: impl Into<Vec<u8>>) -> &mut Self {
+        let tx = Transaction::new_contract_deploy(
+            self.chain.address(from),
+            0,
+            payload.into(),
+            self.chain.config.contract_gas_limit,
+            self.base_fee,
+        );
+        self.transaction(tx.unwrap_or_else(|e| panic!("Can't create a deployment from {}: {}", from, e)))
+    }
+    
+    /// Adds a call of a contract with call data
+    pub fn call(&mut self, from: &str, contract: &str, data: impl Into<Vec<u8>>) -> &mut Self {
+        let tx = Transaction::new_contract_call(
+            self.chain.address(from),
+            contract.to_string(),
+            0,
+            data.into(),
+            self.chain.config.contract_gas_limit,
+            self.base_fee,
+        );
+        self.transaction(tx.unwrap_or_else(|e| panic!("Can't create a call from {}: {}", from, e)))
+    }
+    
+    /// Adds any transaction, stamped with the block's timestamp and its sender's next nonce
+    ///
+    /// It's signed if its sender is an account of the chain.
+    pub fn transaction(&mut self, mut tx: Transaction) -> &mut Self {
+        if tx.sender != COINBASE {
+            let sent = self.transactions.iter().filter(|sent| sent.sender == tx.sender).count() as u64;
+            tx.nonce = self.chain.blockchain.get_state().lock().unwrap().nonce_of(&tx.sender) + sent;
+        }
+        let taken = |id: &TxHash| self.chain.tx_ids.contains(id) || self.ids.contains(id);
+        let mut tx = stamp(tx, self.timestamp, taken).unwrap_or_else(|e| panic!("Can't stamp a transaction: {}", e));
+        if let Some(account) = self.chain.accounts.iter().find(|account| account.address == tx.sender) {
+            account.sign(&mut tx).unwrap_or_else(|e| panic!("{} can't sign: {}", account.name, e));
+        }
+        self.ids.insert(tx.id);
+        self.transactions.push(tx);
+        self
+    }
+    
+    /// Assembles the block, proposed by the validator whose turn it is unless it names another
+    fn build(self) -> Result<Block> {
+        let validators = self.chain.validators();
+        let validator = match (&self.proposer, validators.len()) {
+            (Some(proposer), _) => proposer.clone(),
+            (None, 0) => "GENX_TEST_VALIDATOR".to_string(),
+            (None, count) => validators[((self.height - 1) % count as u64) as usize].address.to_string(),
+        };
+        
+        let blockchain = &self.chain.blockchain;
+        let payout_address = blockchain.get_state().lock().unwrap().get_payout_address(&validator, self.height);
+        let mut transactions: Vec<Transaction> = Vec::new();
+        for coinbase in blockchain.reward_schedule().coinbase_transactions(self.height, &payout_address)? {
+            let taken = |id: &TxHash| {
+                self.chain.tx_ids.contains(id) || self.ids.contains(id) || transactions.iter().any(|tx| tx.id == *id)
+            };
+            let coinbase = stamp(coinbase, self.timestamp, taken)?;
+            transactions.push(coinbase);
+        }
+        
+        let mut rest = self.transactions;
+        if blockchain.orders_transactions(self.height) {
+            rest.sort_by_key(|tx| Reverse(OrderKey::of(tx)));
+        }
+        transactions.extend(rest);
+        
+        let prev_hash = blockchain.get_latest_hash();
+        let mut block = Block::new(self.height, prev_hash, transactions, validator, self.base_fee)?;
+        let header = block.header_mut();
+        header.timestamp = self.timestamp;
+        header.validator_set_hash = blockchain.next_validator_set().map(|set| set.hash());
+        if let Some(proposer) = self.chain.accounts.iter().find(|account| account.address == header.validator) {
+            proposer.sign_header(header)?;
+        }
+        Ok(block)
+    }
+}
+
+/// Builds the genesis block funding the accounts and registering the validators
+fn genesis_block(config: &TestChainConfig, accounts: &[TestAccount]) -> Result<Block> {
+    let timestamp = config.genesis_timestamp;
+    let mut ids = HashSet::new();
+    let mut transactions = Vec::new();
+    for (account, (_, balance)) in accounts.iter().zip(&config.accounts) {
+        let funding = Transaction::new_coinbase(account.address.to_string(), *balance)?;
+        let funding = stamp(funding, timestamp, |id| ids.contains(id))?;
+        ids.insert(funding.id);
+        transactions.push(funding);
+    }
+    for validator in &accounts[config.accounts.len()..] {
+        let registration = ValidatorRegistration {
+            moniker: validator.name.clone(),
+            website: String::new(),
+            commission_rate: 0,
+            consensus_key: validator.address.to_string(),
+            payout_address: None,
+        };
+        let registration = Transaction::new_register_validator(validator.address.to_string(), &registration, 0)?;
+        let mut registration = stamp(registration, timestamp, |id| ids.contains(id))?;
+        ids.insert(registration.id);
+        validator.sign(&mut registration)?;
+        transactions.push(registration);
+    }
+    
+    let mut block = Block::genesis(transactions, genesis::get_initial_base_fee())?;
+    block.header_mut().timestamp = timestamp;
+    Ok(block)
+}
+
+/// Stamps an unsigned transaction with a timestamp, a second later while its ID is `taken`
+fn stamp(mut tx: Transaction, timestamp: u64, taken: impl Fn(&TxHash) -> bool) -> Result<Transaction> {
+    tx.timestamp = timestamp;
+    tx.signature = None;
+    tx.id = tx.calculate_hash()?;
+    while taken(&tx.id) {
+        tx.timestamp += 1;
+        tx.id = tx.calculate_hash()?;
+    }
+    Ok(tx)
+}
\ No newline at end of file
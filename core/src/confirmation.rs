@@ -0,0 +1,117 @@
+//! How settled a transaction is
+//!
+//! "Confirmed" means one block to some users, six to others, and finality
+//! to the most careful. A `ConfirmationStatus` says which of these a
+//! transaction has reached, judged from the chain's tip and its latest
+//! finalized height (a `ChainTip`):
+//!
+//! - `Pending`: in no block of the chain, or in a block the chain no
+//!   longer has, as after a reorganization.
+//! - `Included`: in a block with fewer confirmations than the caller's
+//!   threshold, the block itself counting as one.
+//! - `Safe`: in a block with at least the threshold's confirmations.
+//! - `Finalized`: in a block at or below the finalized height, which no
+//!   reorganization can revert, however few confirmations it has.
+//!
+//! The threshold is chosen by whoever asks: each wallet has its own, and
+//! RPC and REST callers pass theirs, `DEFAULT_SAFE_CONFIRMATIONS` if not.
+//! Statuses only move forward as blocks are added, except that a
+//! reorganization can demote an `Included` transaction back to `Pending`.
+
+use serde::{Deserialize, Serialize};
+
+/// Confirmations a transaction needs to be safe when the caller doesn't say
+pub const DEFAULT_SAFE_CONFIRMATIONS: u64 = 6;
+
+/// Heights of the chain's latest block and latest finalized block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ChainTip {
+    /// Height of the latest block
+    pub height: u64,
+    
+    /// Height of the latest finalized block
+    pub finalized_height: u64,
+}
+
+impl ChainTip {
+    /// Creates a tip at a height with a finalized height
+    pub fn new(height: u64, finalized_height: u64) -> Self {
+        Self { height, finalized_height }
+    }
+    
+    /// Judges a transaction included at a height, or in no block, needing `safe_confirmations` to be safe
+    ///
+    /// A height above the tip is that of a block the chain no longer has,
+    /// so the transaction is pending again. A threshold of zero counts as one.
+    pub fn status(&self, included_at: Option<u64>, safe_confirmations: u64) -> ConfirmationStatus {
+        let Some(height) = included_at.filter(|height| *height <= self.height) else {
+            return ConfirmationStatus::Pending;
+        };
+        let confirmations = self.height - height + 1;
+        if height <= self.finalized_height {
+            ConfirmationStatus::Finalized { height, confirmations }
+        } else if confirmations >= safe_confirmations.max(1) {
+            ConfirmationStatus::Safe { height, confirmations }
+        } else {
+            ConfirmationStatus::Included { height, confirmations }
+        }
+    }
+}
+
+/// How settled a transaction is, see the module documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ConfirmationStatus {
+    /// In no block of the chain
+    #[default]
+    Pending,
+    
+    /// In the block at `height`, with fewer confirmations than needed to be safe
+    Included { height: u64, confirmations: u64 },
+    
+    /// In the block at `height`, with enough confirmations to be safe
+    Safe { height: u64, confirmations: u64 },
+    
+    /// In the block at `height`, which is finalized
+    Finalized { height: u64, confirmations: u64 },
+}
+
+impl ConfirmationStatus {
+    /// Gets the height of the block the transaction is in, if any
+    pub fn height(&self) -> Option<u64> {
+        match self {
+            Self::Pending => None,
+            Self::Included { height, .. } | Self::Safe { height, .. } | Self::Finalized { height, .. } => Some(*height),
+        }
+    }
+    
+    /// Gets the number of blocks from the transaction's to the tip, both included, zero if pending
+    pub fn confirmations(&self) -> u64 {
+        match self {
+            Self::Pending => 0,
+            Self::Included { confirmations, .. } | Self::Safe { confirmations, .. } | Self::Finalized { confirmations, .. } => {
+                *confirmations
+            }
+        }
+    }
+    
+    /// Checks whether the transaction is in a block of the chain
+    pub fn is_included(&self) -> bool {
+        !matches!(self, Self::Pending)
+    }
+    
+    /// Checks whether the transaction is safe or finalized
+    pub fn is_safe(&self) -> bool {
+        matches!(self, Self::Safe { .. } | Self::Finalized { .. })
+    }
+    
+    /// Checks whether the transaction is finalized
+    pub fn is_finalized(&self) -> bool {
+        matches!(self, Self::Finalized { .. })
+    }
+    
+    /// Checks whether two statuses are at the same stage, whatever their confirmations
+    pub fn same_stage(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
\ No newline at end of file
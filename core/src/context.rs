@@ -0,0 +1,130 @@
+//! Where in the chain an error happened
+//!
+//! An error deep in applying a block, such as an insufficient balance, says
+//! what went wrong but not where. Each caller on the way up that knows where
+//! attaches an `ErrorContext` with `with_ctx`, filling in what it knows:
+//! `State::apply_block_with_executor` the transaction and its index in the
+//! block, `Blockchain::add_block` the block's height and hash, and the
+//! node's block import and block production likewise. The contexts merge
+//! into one, fields already set kept, and the error's message ends with it:
+//!
+//! ```text
+//! Insufficient balance: 5 < 10 (block 42 0x…, transaction #7 0x…)
+//! ```
+//!
+//! The error keeps the code of the one the context was attached to, which
+//! `BlockchainError::root` gets back for matching on, and the RPC reports
+//! the context with it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::{BlockHash, BlockchainError, TxHash};
+
+/// The block and transaction an error happened in, as far as known
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorContext {
+    /// Height of the block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+    
+    /// Hash of the block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<BlockHash>,
+    
+    /// ID of the transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_id: Option<TxHash>,
+    
+    /// Position of the transaction in its block, the coinbase transactions first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_index: Option<usize>,
+}
+
+impl ErrorContext {
+    /// Creates the context of a block, by height alone where its hash can't be computed
+    pub fn block(block: &Block) -> Self {
+        Self { height: Some(block.header().height), block_hash: block.hash().ok(), ..Self::default() }
+    }
+    
+    /// Creates the context of a block not made yet
+    pub fn height(height: u64) -> Self {
+        Self { height: Some(height), ..Self::default() }
+    }
+    
+    /// Creates the context of a transaction at an index of its block
+    pub fn transaction(tx_index: usize, tx_id: TxHash) -> Self {
+        Self { tx_id: Some(tx_id), tx_index: Some(tx_index), ..Self::default() }
+    }
+    
+    /// Fills in the fields this context is missing from an outer one
+    fn merge(&mut self, outer: ErrorContext) {
+        self.height = self.height.or(outer.height);
+        self.block_hash = self.block_hash.or(outer.block_hash);
+        self.tx_id = self.tx_id.or(outer.tx_id);
+        self.tx_index = self.tx_index.or(outer.tx_index);
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        match (self.height, self.block_hash) {
+            (Some(height), Some(hash)) => parts.push(format!("block {} {}", height, hash)),
+            (Some(height), None) => parts.push(format!("block {}", height)),
+            (None, Some(hash)) => parts.push(format!("block {}", hash)),
+            (None, None) => {}
+        }
+        match (self.tx_index, self.tx_id) {
+            (Some(index), Some(id)) => parts.push(format!("transaction #{} {}", index, id)),
+            (Some(index), None) => parts.push(format!("transaction #{}", index)),
+            (None, Some(id)) => parts.push(format!("transaction {}", id)),
+            (None, None) => {}
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl BlockchainError {
+    /// Attaches where the error happened, merging with any context it already has
+    pub fn with_ctx(self, context: ErrorContext) -> Self {
+        match self {
+            BlockchainError::WithContext { context: mut inner, source } => {
+                inner.merge(context);
+                BlockchainError::WithContext { context: inner, source }
+            }
+            source => BlockchainError::WithContext { context: Box::new(context), source: Box::new(source) },
+        }
+    }
+    
+    /// Gets where the error happened, if anyone said
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            BlockchainError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+    
+    /// Gets the error without its context, to match on
+    pub fn root(&self) -> &BlockchainError {
+        match self {
+            BlockchainError::WithContext { source, .. } => source,
+            e => e,
+        }
+    }
+}
+
+/// Attaches an `ErrorContext` to the error of a result
+pub trait WithContext {
+    /// Attaches where the error happened, if it's an error
+    fn with_ctx(self, context: ErrorContext) -> Self;
+}
+
+impl<T> WithContext for crate::Result<T> {
+    fn with_ctx(self, context: ErrorContext) -> Self {
+        self.map_err(|e| e.with_ctx(context))
+    }
+}
\ No newline at end of file
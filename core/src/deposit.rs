@@ -0,0 +1,95 @@
+//! Refundable deposits for contract storage
+//!
+//! Every node keeps what contracts store for as long as it exists, so
+//! storing something locks part of the payer's GENX until it's cleared:
+//! `DepositRates::per_slot` for each storage slot that goes from empty to
+//! set, and `DepositRates::per_code_byte` for each byte of a contract's
+//! code. The payer is the sender of the transaction that stored it. Clearing
+//! the slot, or the contract self-destructing, refunds the deposit to that
+//! payer, whichever transaction does it.
+//!
+//! Locked amounts leave the payer's balance but stay in the supply. The
+//! state tracks them by contract and slot, and how much each account has
+//! locked altogether (see `State::get_locked_balance`). The rates are set at
+//! genesis (see `genesis::get_storage_deposit_rates`).
+//!
+//! The code a deployment stores isn't known until its init code has run, so
+//! a ContractDeploy is only executed if its sender can lock the deposit for
+//! as many bytes as the deployment carries, on top of its value and highest
+//! fee (see `DepositRates::hold`). An execution storing more than its
+//! sender's balance can cover fails, still paying its fee.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::transaction::{Transaction, TransactionType};
+
+/// Amounts locked for what contracts store, in GENX base units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositRates {
+    /// Locked for each storage slot written
+    pub per_slot: u64,
+    
+    /// Locked for each byte of deployed code
+    pub per_code_byte: u64,
+}
+
+impl DepositRates {
+    /// Rates locking nothing
+    pub fn none() -> Self {
+        Self { per_slot: 0, per_code_byte: 0 }
+    }
+    
+    /// Gets the deposit for storing code of the given size
+    pub fn code(&self, code_size: usize) -> u64 {
+        self.per_code_byte.saturating_mul(code_size as u64)
+    }
+    
+    /// Gets what a transaction's sender must be able to lock before it's executed
+    ///
+    /// That's the code deposit for the whole payload of a ContractDeploy, and
+    /// nothing for other transactions.
+    pub fn hold(&self, tx: &Transaction) -> u64 {
+        if tx.tx_type == TransactionType::ContractDeploy {
+            self.code(tx.data_len())
+        } else {
+            0
+        }
+    }
+}
+
+/// A deposit locked for something a contract stores
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageDeposit {
+    /// Account the deposit is refunded to
+    pub payer: String,
+    
+    /// Amount locked
+    pub amount: u64,
+}
+
+/// Deposits locked for a contract's code and storage
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractDeposits {
+    /// Deposit for the contract's code, if any was locked
+    pub code: Option<StorageDeposit>,
+    
+    /// Deposits for storage slots, by slot
+    pub slots: HashMap<Vec<u8>, StorageDeposit>,
+}
+
+impl ContractDeposits {
+    /// Gets the total locked for the contract
+    pub fn total(&self) -> u64 {
+        self.code
+            .iter()
+            .chain(self.slots.values())
+            .fold(0u64, |total, deposit| total.saturating_add(deposit.amount))
+    }
+    
+    /// Checks whether no deposit is locked for the contract
+    pub fn is_empty(&self) -> bool {
+        self.code.is_none() && self.slots.is_empty()
+    }
+}
\ No newline at end of file
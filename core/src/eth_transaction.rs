@@ -0,0 +1,324 @@
+//! Signed Ethereum transactions
+//!
+//! Ethereum tooling, such as ethers.js `signTransaction`, signs transactions
+//! itself and hands a node the serialized result. This module decodes
+//! legacy transactions, with or without EIP-155 replay protection, and
+//! EIP-1559 transactions, and imports them as GENX transactions:
+//!
+//! | Ethereum                       | GENX                                                  |
+//! |--------------------------------|-------------------------------------------------------|
+//! | sender, recovered              | `sender`: the secp256k1 address of the signing key    |
+//! | `nonce`                        | `nonce`                                               |
+//! | `to`                           | `recipient`, chosen by the caller; must map to `to`   |
+//! | `value`                        | `amount`, in units of 10^-18 GENX                     |
+//! | `data`                         | `data`                                                |
+//! | `gasLimit`                     | `gas_limit`                                           |
+//! | `gasPrice` or `maxFeePerGas`   | `gas_price`, in units of 10^-18 GENX, rounded down    |
+//! | `r`, `s`, `v` or `yParity`     | `signature`: `r`, `s` and the recovery ID             |
+//!
+//! GENX amounts have 8 decimals, so a value must be a whole multiple of
+//! `WEI_PER_UNIT`; a gas price is a cap and is rounded down instead.
+//! Transactions with data are contract calls and others are transfers,
+//! which pay `gas_limit * gas_price` as their flat fee. Contract creation
+//! isn't supported, as GENX deployments carry a `DeployPayload` rather than
+//! bare init code. The EIP-1559 priority fee and access lists have no GENX
+//! counterpart and are ignored; the chain ID is left to the caller to
+//! check.
+//!
+//! An imported transaction keeps the raw transaction in `eth_raw` and its
+//! ID is the Ethereum transaction hash. `Transaction::validate` decodes the
+//! raw transaction again, checks that the GENX fields match it and verifies
+//! the signature over the Ethereum signing hash with the sender's key.
+
+use thiserror::Error;
+
+use crate::hashing::keccak256;
+use crate::rlp::{self, RlpError, RlpItem};
+use crate::secp256k1;
+use crate::signature::{self, SignatureError, SignatureScheme};
+use crate::transaction::{to_evm_address, Transaction, TransactionType};
+use crate::units::DECIMALS;
+use crate::{current_timestamp, Bytes, TxHash};
+
+/// Units of 10^-18 GENX, Ethereum's wei, in the smallest GENX amount
+pub const WEI_PER_UNIT: u128 = 10u128.pow(18 - DECIMALS);
+
+/// Type byte of EIP-1559 transactions
+const EIP1559_TYPE: u8 = 0x02;
+
+/// Error decoding or importing an Ethereum transaction
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EthTransactionError {
+    #[error("Invalid RLP: {0}")]
+    Rlp(#[from] RlpError),
+    
+    #[error("Unsupported transaction type {0:#04x}")]
+    UnsupportedType(u8),
+    
+    #[error("Malformed transaction: {0}")]
+    Malformed(String),
+    
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(#[from] SignatureError),
+    
+    #[error("Contract creation is not supported")]
+    ContractCreation,
+    
+    #[error("Value {0} is not a whole GENX amount")]
+    InvalidValue(u128),
+    
+    #[error("Recipient {recipient} does not map to 0x{to}")]
+    RecipientMismatch { recipient: String, to: String },
+    
+    #[error("Transaction field {0} does not match the Ethereum transaction")]
+    FieldMismatch(&'static str),
+}
+
+/// Result type for Ethereum transaction operations
+pub type Result<T> = std::result::Result<T, EthTransactionError>;
+
+/// Envelope of an Ethereum transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EthTransactionType {
+    /// Untyped transaction with a single gas price
+    Legacy,
+    
+    /// Type 2 transaction with a base fee cap and priority fee
+    Eip1559,
+}
+
+/// Decoded, signed Ethereum transaction
+#[derive(Debug, Clone)]
+pub struct EthTransaction {
+    /// Envelope the transaction came in
+    pub tx_type: EthTransactionType,
+    
+    /// Chain the transaction is signed for; `None` for legacy transactions without EIP-155
+    pub chain_id: Option<u64>,
+    
+    /// Sender's nonce
+    pub nonce: u64,
+    
+    /// Gas price in wei; for EIP-1559 transactions, the maximum fee per gas
+    pub gas_price: u128,
+    
+    /// Maximum priority fee per gas in wei, for EIP-1559 transactions
+    pub max_priority_fee_per_gas: Option<u128>,
+    
+    /// Maximum gas the transaction may use
+    pub gas_limit: u64,
+    
+    /// Recipient, or `None` for contract creation
+    pub to: Option<[u8; 20]>,
+    
+    /// Value transferred in wei
+    pub value: u128,
+    
+    /// Call data
+    pub data: Vec<u8>,
+    
+    /// Sender's signature of the signing hash
+    pub signature: secp256k1::Signature,
+    
+    /// Hash the sender signed
+    signing_hash: [u8; 32],
+    
+    /// The raw transaction
+    raw: Vec<u8>,
+}
+
+impl EthTransaction {
+    /// Decodes a raw signed transaction
+    pub fn decode(raw: &[u8]) -> Result<Self> {
+        match raw.first() {
+            None => Err(RlpError::UnexpectedEnd.into()),
+            Some(&EIP1559_TYPE) => Self::decode_eip1559(raw),
+            Some(&byte) if byte >= 0xc0 => Self::decode_legacy(raw),
+            Some(&byte) => Err(EthTransactionError::UnsupportedType(byte)),
+        }
+    }
+    
+    /// `rlp([nonce, gasPrice, gasLimit, to, value, data, v, r, s])`
+    fn decode_legacy(raw: &[u8]) -> Result<Self> {
+        let item = rlp::decode(raw)?;
+        let fields = fields(&item, 9)?;
+        
+        let v = fields[6].as_u64()?;
+        let (chain_id, recovery_id) = match v {
+            27 | 28 => (None, (v - 27) as u8),
+            35.. => (Some((v - 35) / 2), ((v - 35) % 2) as u8),
+            _ => return Err(EthTransactionError::Malformed(format!("invalid v {}", v))),
+        };
+        
+        // EIP-155 transactions sign the chain ID in place of the signature
+        let mut unsigned = fields[..6].to_vec();
+        if let Some(chain_id) = chain_id {
+            unsigned.extend([RlpItem::uint(chain_id as u128), RlpItem::uint(0), RlpItem::uint(0)]);
+        }
+        let signing_hash = keccak256(&rlp::encode(&RlpItem::List(unsigned)));
+        
+        Ok(Self {
+            tx_type: EthTransactionType::Legacy,
+            chain_id,
+            nonce: fields[0].as_u64()?,
+            gas_price: fields[1].as_uint()?,
+            max_priority_fee_per_gas: None,
+            gas_limit: fields[2].as_u64()?,
+            to: recipient(&fields[3])?,
+            value: fields[4].as_uint()?,
+            data: fields[5].as_bytes()?.to_vec(),
+            signature: signature(&fields[7], &fields[8], recovery_id)?,
+            signing_hash,
+            raw: raw.to_vec(),
+        })
+    }
+    
+    /// `0x02 || rlp([chainId, nonce, maxPriorityFeePerGas, maxFeePerGas, gasLimit, to, value, data, accessList, yParity, r, s])`
+    fn decode_eip1559(raw: &[u8]) -> Result<Self> {
+        let item = rlp::decode(&raw[1..])?;
+        let fields = fields(&item, 12)?;
+        
+        fields[8].as_list()?;
+        let recovery_id = match fields[9].as_u64()? {
+            parity @ (0 | 1) => parity as u8,
+            parity => return Err(EthTransactionError::Malformed(format!("invalid y parity {}", parity))),
+        };
+        
+        let mut unsigned = vec![EIP1559_TYPE];
+        unsigned.extend(rlp::encode(&RlpItem::List(fields[..9].to_vec())));
+        
+        Ok(Self {
+            tx_type: EthTransactionType::Eip1559,
+            chain_id: Some(fields[0].as_u64()?),
+            nonce: fields[1].as_u64()?,
+            gas_price: fields[3].as_uint()?,
+            max_priority_fee_per_gas: Some(fields[2].as_uint()?),
+            gas_limit: fields[4].as_u64()?,
+            to: recipient(&fields[5])?,
+            value: fields[6].as_uint()?,
+            data: fields[7].as_bytes()?.to_vec(),
+            signature: signature(&fields[10], &fields[11], recovery_id)?,
+            signing_hash: keccak256(&unsigned),
+            raw: raw.to_vec(),
+        })
+    }
+    
+    /// Gets the Ethereum transaction hash
+    pub fn hash(&self) -> TxHash {
+        TxHash(keccak256(&self.raw))
+    }
+    
+    /// Gets the raw transaction
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+    
+    /// Gets the hash the sender signed
+    pub fn signing_hash(&self) -> [u8; 32] {
+        self.signing_hash
+    }
+    
+    /// Recovers the key that signed the transaction
+    pub fn recover_sender(&self) -> Result<secp256k1::PublicKey> {
+        self.signature.recover(&self.signing_hash).ok_or(EthTransactionError::InvalidSignature(
+            SignatureError::InvalidSignature,
+        ))
+    }
+    
+    /// Imports the transaction as a GENX transaction sent to `recipient`
+    ///
+    /// `recipient` is the GENX account the transaction's `to` names, such as
+    /// a contract address; its EVM address must be `to`. The sender is the
+    /// secp256k1 account of the recovered key.
+    pub fn to_transaction(&self, recipient: String) -> Result<Transaction> {
+        let to = self.to.ok_or(EthTransactionError::ContractCreation)?;
+        if to_evm_address(&recipient) != to {
+            return Err(EthTransactionError::RecipientMismatch { recipient, to: hex::encode(to) });
+        }
+        
+        let sender = SignatureScheme::Secp256k1.address(&self.recover_sender()?.to_compressed())?;
+        let signature = self.signature.to_bytes();
+        // Recovery accepts high `s` values, verification doesn't
+        signature::verify(&sender, &self.signing_hash, &signature)?;
+        
+        if !self.value.is_multiple_of(WEI_PER_UNIT) {
+            return Err(EthTransactionError::InvalidValue(self.value));
+        }
+        let amount = u64::try_from(self.value / WEI_PER_UNIT).map_err(|_| EthTransactionError::InvalidValue(self.value))?;
+        let gas_price = u64::try_from(self.gas_price / WEI_PER_UNIT)
+            .map_err(|_| EthTransactionError::Malformed(format!("gas price {} is too large", self.gas_price)))?;
+        
+        let (tx_type, fee, data) = if self.data.is_empty() {
+            (TransactionType::Transfer, self.gas_limit.saturating_mul(gas_price), None)
+        } else {
+            (TransactionType::ContractCall, 0, Some(Bytes::from(self.data.clone())))
+        };
+        
+        Ok(Transaction {
+            id: self.hash(),
+            tx_type,
+            timestamp: current_timestamp(),
+            nonce: self.nonce,
+            sender,
+            recipient,
+            amount,
+            fee,
+            data,
+            gas_limit: self.gas_limit,
+            gas_price,
+            signature: Some(Bytes::from(signature.to_vec())),
+            eth_raw: Some(Bytes::from(self.raw.clone())),
+            memo: None,
+        })
+    }
+}
+
+/// Checks a transaction imported from Ethereum against the raw transaction it carries
+///
+/// Every field but the timestamp must be what importing the raw transaction
+/// gives, which verifies its signature against the sender's key.
+pub(crate) fn verify_import(tx: &Transaction, raw: &[u8]) -> Result<()> {
+    let eth = EthTransaction::decode(raw)?;
+    let expected = eth.to_transaction(tx.recipient.clone())?;
+    
+    let fields = [
+        ("id", tx.id == expected.id),
+        ("tx_type", tx.tx_type == expected.tx_type),
+        ("sender", tx.sender == expected.sender),
+        ("nonce", tx.nonce == expected.nonce),
+        ("amount", tx.amount == expected.amount),
+        ("fee", tx.fee == expected.fee),
+        ("data", tx.data == expected.data),
+        ("gas_limit", tx.gas_limit == expected.gas_limit),
+        ("gas_price", tx.gas_price == expected.gas_price),
+        ("signature", tx.signature == expected.signature),
+    ];
+    match fields.into_iter().find(|(_, matches)| !matches) {
+        Some((field, _)) => Err(EthTransactionError::FieldMismatch(field)),
+        None => Ok(()),
+    }
+}
+
+/// Gets the fields of a transaction list, which must have `count` of them
+fn fields(item: &RlpItem, count: usize) -> Result<&[RlpItem]> {
+    let fields = item.as_list()?;
+    if fields.len() != count {
+        return Err(EthTransactionError::Malformed(format!("expected {} fields, got {}", count, fields.len())));
+    }
+    Ok(fields)
+}
+
+/// Reads the `to` field: empty for contract creation, otherwise 20 bytes
+fn recipient(item: &RlpItem) -> Result<Option<[u8; 20]>> {
+    match item.as_bytes()? {
+        [] => Ok(None),
+        to => to
+            .try_into()
+            .map(Some)
+            .map_err(|_| EthTransactionError::Malformed(format!("recipient of {} bytes", to.len()))),
+    }
+}
+
+fn signature(r: &RlpItem, s: &RlpItem, recovery_id: u8) -> Result<secp256k1::Signature> {
+    Ok(secp256k1::Signature::from_parts(r.as_word()?, s.as_word()?, recovery_id)?)
+}
\ No newline at end of file
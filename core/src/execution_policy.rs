@@ -0,0 +1,220 @@
+//! Who may deploy and call contracts, for permissioned networks
+//!
+//! Private networks restrict contract execution with an `ExecutionPolicy`,
+//! part of the state so every node enforces it alike while applying blocks:
+//!
+//! - Deployment is open to anyone, or only to the addresses of an
+//!   allowlist. A block deploying from any other address is invalid.
+//! - A contract may have an allowlist of the addresses that may call it,
+//!   which only its creator sets.
+//! - Contracts on the kill switch list have their execution disabled from
+//!   the height they were put on it for.
+//!
+//! A call a contract's allowlist or the kill switch denies is still
+//! included, but reverts with the policy's reason without running,
+//! consuming its whole gas limit, so denied calls cost what failing
+//! before execution starts does. Only the contracts transactions call
+//! directly are checked; calls between contracts are the engine's.
+//!
+//! The policy starts as `genesis` configures it, open on the public chain
+//! (see `genesis::get_execution_policy`), and changes with
+//! `SetExecutionPolicy` transactions carrying a `PolicyUpdate`. Only the
+//! policy's admins may change the deployment allowlist, the kill switch
+//! and the admins themselves; a block carrying an update its sender may
+//! not make is invalid. Each update's receipt logs it from
+//! `POLICY_ADDRESS`, so changes can be followed with log filters.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing;
+use crate::receipt::Log;
+use crate::{BlockchainError, Hash, Result};
+
+/// Address the logs of policy updates are emitted from
+pub const POLICY_ADDRESS: &str = "GENX_EXECUTION_POLICY";
+
+/// Who may deploy and call contracts, see the module documentation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionPolicy {
+    /// Addresses that may change the deployment allowlist, the kill switch and the admins
+    pub admins: BTreeSet<String>,
+    
+    /// Addresses that may deploy contracts, or `None` if anyone may
+    pub deployers: Option<BTreeSet<String>>,
+    
+    /// Addresses that may call each contract with an allowlist (contract address -> callers)
+    pub callers: BTreeMap<String, BTreeSet<String>>,
+    
+    /// Contracts whose execution is disabled (contract address -> height it's disabled from)
+    pub disabled: BTreeMap<String, u64>,
+}
+
+/// Why the policy denies a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum PolicyViolation {
+    /// The sender isn't on the deployment allowlist
+    NotDeployer,
+    
+    /// The sender isn't on the contract's call allowlist
+    NotCaller { contract: String },
+    
+    /// The contract's execution is disabled
+    Disabled { contract: String, since: u64 },
+    
+    /// The sender isn't an admin of the policy
+    NotAdmin,
+    
+    /// The sender didn't create the contract whose allowlist it sets
+    NotCreator { contract: String },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotDeployer => f.write_str("not allowed to deploy contracts"),
+            Self::NotCaller { contract } => write!(f, "not allowed to call {}", contract),
+            Self::Disabled { contract, since } => write!(f, "{} is disabled since height {}", contract, since),
+            Self::NotAdmin => f.write_str("not an admin of the execution policy"),
+            Self::NotCreator { contract } => write!(f, "not the creator of {}", contract),
+        }
+    }
+}
+
+/// Payload of a `SetExecutionPolicy` transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "update")]
+pub enum PolicyUpdate {
+    /// Restricts deployment to an allowlist, or opens it to anyone with `None`; admins only
+    SetDeployers { deployers: Option<BTreeSet<String>> },
+    
+    /// Restricts calls to a contract to an allowlist, or lifts the restriction with `None`; its creator only
+    SetCallers { contract: String, callers: Option<BTreeSet<String>> },
+    
+    /// Disables a contract's execution from a later height on; admins only
+    Disable { contract: String, activation_height: u64 },
+    
+    /// Enables a disabled contract again at once; admins only
+    Enable { contract: String },
+    
+    /// Replaces the admins; admins only
+    SetAdmins { admins: BTreeSet<String> },
+}
+
+impl ExecutionPolicy {
+    /// Checks whether the policy restricts nothing, as on the public chain
+    pub fn is_open(&self) -> bool {
+        *self == Self::default()
+    }
+    
+    /// Checks an address may deploy contracts
+    pub fn check_deploy(&self, sender: &str) -> std::result::Result<(), PolicyViolation> {
+        match &self.deployers {
+            Some(deployers) if !deployers.contains(sender) => Err(PolicyViolation::NotDeployer),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Checks an address may call a contract in the block at a height
+    pub fn check_call(&self, sender: &str, contract: &str, height: u64) -> std::result::Result<(), PolicyViolation> {
+        if let Some(&since) = self.disabled.get(contract) {
+            if height >= since {
+                return Err(PolicyViolation::Disabled { contract: contract.to_string(), since });
+            }
+        }
+        match self.callers.get(contract) {
+            Some(callers) if !callers.contains(sender) => Err(PolicyViolation::NotCaller { contract: contract.to_string() }),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Checks an address may make an update in the block at a height
+    ///
+    /// `creator` is the creator of the contract whose allowlist the update
+    /// sets, if it names a deployed one.
+    pub fn check_update(
+        &self,
+        sender: &str,
+        update: &PolicyUpdate,
+        creator: Option<&str>,
+        height: u64,
+    ) -> Result<()> {
+        let violation = match update {
+            PolicyUpdate::SetCallers { contract, .. } if creator != Some(sender) => {
+                Some(PolicyViolation::NotCreator { contract: contract.clone() })
+            }
+            PolicyUpdate::SetCallers { .. } => None,
+            _ if !self.admins.contains(sender) => Some(PolicyViolation::NotAdmin),
+            _ => None,
+        };
+        if let Some(violation) = violation {
+            return Err(BlockchainError::PolicyDenied { sender: sender.to_string(), violation });
+        }
+        
+        if let PolicyUpdate::Disable { contract, activation_height } = update {
+            if *activation_height <= height {
+                return Err(BlockchainError::InvalidTransaction(format!(
+                    "{} must be disabled from a height after {}, not {}",
+                    contract, height, activation_height
+                )));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Makes an update, which must have passed `check_update`
+    pub fn apply(&mut self, update: &PolicyUpdate) {
+        match update {
+            PolicyUpdate::SetDeployers { deployers } => self.deployers = deployers.clone(),
+            PolicyUpdate::SetCallers { contract, callers: Some(callers) } => {
+                self.callers.insert(contract.clone(), callers.clone());
+            }
+            PolicyUpdate::SetCallers { contract, callers: None } => {
+                self.callers.remove(contract);
+            }
+            PolicyUpdate::Disable { contract, activation_height } => {
+                self.disabled.insert(contract.clone(), *activation_height);
+            }
+            PolicyUpdate::Enable { contract } => {
+                self.disabled.remove(contract);
+            }
+            PolicyUpdate::SetAdmins { admins } => self.admins = admins.clone(),
+        }
+    }
+}
+
+impl PolicyUpdate {
+    /// Encodes the update as transaction data
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+    
+    /// Decodes an update from transaction data
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).map_err(|e| BlockchainError::InvalidTransaction(format!("Invalid policy update: {}", e)))
+    }
+    
+    /// Gets the name of the update, as its log's topic hashes it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SetDeployers { .. } => "set_deployers",
+            Self::SetCallers { .. } => "set_callers",
+            Self::Disable { .. } => "disable",
+            Self::Enable { .. } => "enable",
+            Self::SetAdmins { .. } => "set_admins",
+        }
+    }
+    
+    /// Gets the topic of the logs of updates with the same name as this one
+    pub fn topic(&self) -> Hash {
+        hashing::keccak256(format!("ExecutionPolicy.{}", self.name()).as_bytes())
+    }
+    
+    /// Gets the log recording the update in its receipt: its topic, and the update as data
+    pub fn log(&self) -> Result<Log> {
+        Ok(Log { address: POLICY_ADDRESS.to_string(), topics: vec![self.topic()], data: self.to_data()? })
+    }
+}
\ No newline at end of file
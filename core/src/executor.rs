@@ -0,0 +1,52 @@
+//! Contract execution hook for the Crypto Trust Bank blockchain
+//!
+//! The core crate doesn't know how to run contract code; this module defines
+//! the interface a contract engine implements so that contract transactions
+//! are executed as part of block application.
+
+use std::fmt;
+
+use crate::block::BlockHeader;
+use crate::receipt::Log;
+use crate::state::StateAccess;
+use crate::transaction::Transaction;
+use crate::Result;
+
+/// Result of executing a contract transaction
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionOutcome {
+    /// Whether execution succeeded; failed executions still pay their fee
+    pub success: bool,
+    
+    /// Gas consumed by the execution
+    pub gas_used: u64,
+    
+    /// Address of the contract created by a deployment or a call to the token factory
+    pub contract_address: Option<String>,
+    
+    /// Data returned by the execution
+    pub return_data: Vec<u8>,
+    
+    /// Event logs emitted by a successful execution
+    pub logs: Vec<Log>,
+    
+    /// Reason given by a reverted execution, if any
+    pub revert_reason: Option<String>,
+    
+    /// Contracts destroyed by a successful execution
+    pub destroyed_contracts: Vec<String>,
+}
+
+/// Executes contract transactions during block application
+///
+/// Executors keep no state of their own: deployed code and storage are
+/// written through `StateAccess`, so replaying the blocks rebuilds them.
+/// Returning an error rejects the whole block; a failed execution that should
+/// still be included (and charged) is reported with `success: false`.
+pub trait ContractExecutor: Send + fmt::Debug {
+    /// Executes a ContractDeploy transaction included in the block with the given header
+    fn deploy(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome>;
+    
+    /// Executes a call to the contract at the transaction's recipient
+    fn call(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome>;
+}
\ No newline at end of file
@@ -0,0 +1,339 @@
+//! Base fee adjustment for the Crypto Trust Bank blockchain
+//!
+//! Every block header carries a base fee: the minimum gas price a contract
+//! transaction in the block must pay. The base fee portion of each fee is
+//! burned, and only the rest (the tip) goes to the block's validator.
+//!
+//! Blocks target half of the block gas limit. When a block uses more gas than
+//! the target, the next block's base fee rises by up to 1/8; when it uses
+//! less, the base fee falls by up to 1/8, but never below `MIN_BASE_FEE`.
+//!
+//! To help senders choose what to pay, the pending transactions' fees are
+//! summed up in a `FeeHistogram`, which the mempool keeps up to date as
+//! transactions come and go, and `estimate_inclusion` predicts how many
+//! blocks a transaction paying a given rate waits for. Blocks take metered
+//! transactions first, highest gas price first, until the gas limit is
+//! reserved, then flat-fee transfers, highest fee first, up to a number of
+//! transactions (see `core::ordering`). A transaction waits for those paying
+//! more to fit in blocks ahead of it, and a gas price below the base fee
+//! also waits for the base fee to fall, which it does only while blocks use
+//! less gas than the target. The latest blocks' gas use stands for that of
+//! the coming ones.
+//!
+//! The histogram's buckets double in width, so a transaction is assumed to
+//! wait for every other in its bucket: estimates err on the long side.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::transaction::Transaction;
+
+/// Ratio between the block gas limit and the gas a block is expected to use
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
+/// Bounds the base fee change between consecutive blocks to 1/8
+pub const BASE_FEE_CHANGE_DENOMINATOR: u64 = 8;
+
+/// Lowest base fee a block can carry, so gas is never free
+pub const MIN_BASE_FEE: u64 = 1;
+
+/// Gets the gas a block is expected to use, given the block gas limit
+pub fn gas_target(block_gas_limit: u64) -> u64 {
+    block_gas_limit / ELASTICITY_MULTIPLIER
+}
+
+/// Computes the base fee of a block from its parent's base fee and gas used
+///
+/// A parent away from the target always moves the base fee by at least 1,
+/// so small base fees don't get stuck where 1/8 of them rounds to zero.
+/// A falling base fee stops at `MIN_BASE_FEE`.
+pub fn next_base_fee(parent_base_fee: u64, parent_gas_used: u64, block_gas_limit: u64) -> u64 {
+    let target = gas_target(block_gas_limit);
+    if target == 0 || parent_gas_used == target {
+        return parent_base_fee;
+    }
+    
+    // Widen to avoid overflowing on large base fees
+    let delta = |gas_delta: u64| {
+        (parent_base_fee as u128 * gas_delta as u128 / target as u128 / BASE_FEE_CHANGE_DENOMINATOR as u128) as u64
+    };
+    
+    if parent_gas_used > target {
+        parent_base_fee.saturating_add(delta(parent_gas_used - target).max(1))
+    } else {
+        parent_base_fee.saturating_sub(delta(target - parent_gas_used).max(1)).max(MIN_BASE_FEE)
+    }
+}
+
+/// Latest blocks whose gas use stands for that of the coming ones
+pub const FULLNESS_WINDOW: u64 = 20;
+
+/// Most blocks `estimate_inclusion` predicts a wait of
+pub const MAX_ESTIMATED_BLOCKS: u64 = 1024;
+
+/// What a transaction pays, in the terms blocks are packed by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeRate {
+    /// Gas price of a metered transaction
+    GasPrice(u64),
+    
+    /// Flat fee of a transfer
+    FlatFee(u64),
+}
+
+impl FeeRate {
+    /// Gets the rate a transaction pays
+    pub fn of(tx: &Transaction) -> Self {
+        if tx.is_metered() {
+            FeeRate::GasPrice(tx.gas_price)
+        } else {
+            FeeRate::FlatFee(tx.fee)
+        }
+    }
+    
+    /// Checks whether the rate is a gas price
+    pub fn is_metered(&self) -> bool {
+        matches!(self, FeeRate::GasPrice(_))
+    }
+    
+    /// Gets the gas price or flat fee
+    pub fn rate(&self) -> u64 {
+        match *self {
+            FeeRate::GasPrice(rate) | FeeRate::FlatFee(rate) => rate,
+        }
+    }
+}
+
+/// Pending transactions paying rates in a range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeBucket {
+    /// Whether the rates are gas prices rather than flat fees
+    pub metered: bool,
+    
+    /// Lowest rate in the bucket
+    pub min_rate: u64,
+    
+    /// Highest rate in the bucket
+    pub max_rate: u64,
+    
+    /// Number of transactions
+    pub transactions: u64,
+    
+    /// Gas the transactions reserve, their gas limits
+    pub gas: u64,
+    
+    /// Bytes of data and memos the transactions carry
+    pub bytes: u64,
+}
+
+/// Totals of the transactions in a bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Totals {
+    transactions: u64,
+    gas: u64,
+    bytes: u64,
+}
+
+impl Totals {
+    fn of(tx: &Transaction) -> Self {
+        Totals { transactions: 1, gas: tx.gas_limit, bytes: (tx.data_len() + tx.memo_len()) as u64 }
+    }
+}
+
+/// Pending transactions by the rate they pay
+///
+/// Rates are bucketed by powers of two: zero, then 1, 2 to 3, 4 to 7 and
+/// so on. Adding or removing a transaction takes O(log b) for b buckets,
+/// at most 65 of each kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "Vec<FeeBucket>", from = "Vec<FeeBucket>")]
+pub struct FeeHistogram {
+    /// Totals by whether they're metered and bucket
+    buckets: BTreeMap<(bool, u32), Totals>,
+}
+
+impl FeeHistogram {
+    /// Creates an empty histogram
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Counts a transaction
+    pub fn add(&mut self, tx: &Transaction) {
+        let totals = Totals::of(tx);
+        let bucket = self.buckets.entry(bucket_key(FeeRate::of(tx))).or_default();
+        bucket.transactions += totals.transactions;
+        bucket.gas = bucket.gas.saturating_add(totals.gas);
+        bucket.bytes = bucket.bytes.saturating_add(totals.bytes);
+    }
+    
+    /// Stops counting a transaction that was added
+    pub fn remove(&mut self, tx: &Transaction) {
+        let key = bucket_key(FeeRate::of(tx));
+        let Some(bucket) = self.buckets.get_mut(&key) else {
+            return;
+        };
+        let totals = Totals::of(tx);
+        bucket.transactions = bucket.transactions.saturating_sub(totals.transactions);
+        bucket.gas = bucket.gas.saturating_sub(totals.gas);
+        bucket.bytes = bucket.bytes.saturating_sub(totals.bytes);
+        if bucket.transactions == 0 {
+            self.buckets.remove(&key);
+        }
+    }
+    
+    /// Gets the number of transactions counted
+    pub fn transactions(&self) -> u64 {
+        self.buckets.values().map(|totals| totals.transactions).sum()
+    }
+    
+    /// Gets the non-empty buckets in the order blocks take them: metered first, highest rates first
+    pub fn buckets(&self) -> Vec<FeeBucket> {
+        self.buckets
+            .iter()
+            .rev()
+            .map(|(&(metered, index), totals)| {
+                let (min_rate, max_rate) = bucket_range(index);
+                FeeBucket {
+                    metered,
+                    min_rate,
+                    max_rate,
+                    transactions: totals.transactions,
+                    gas: totals.gas,
+                    bytes: totals.bytes,
+                }
+            })
+            .collect()
+    }
+    
+    /// Sums up the transactions a block would take before one paying `rate`, counting all of its bucket
+    ///
+    /// Flat-fee transfers come after every metered transaction.
+    pub fn ahead_of(&self, rate: FeeRate) -> FeeBucket {
+        let (metered, index) = bucket_key(rate);
+        let (min_rate, _) = bucket_range(index);
+        let mut ahead = FeeBucket { metered, min_rate, max_rate: u64::MAX, ..FeeBucket::default() };
+        for (_, totals) in self.buckets.range((metered, index)..) {
+            ahead.transactions += totals.transactions;
+            ahead.gas = ahead.gas.saturating_add(totals.gas);
+            ahead.bytes = ahead.bytes.saturating_add(totals.bytes);
+        }
+        ahead
+    }
+}
+
+impl From<FeeHistogram> for Vec<FeeBucket> {
+    fn from(histogram: FeeHistogram) -> Self {
+        histogram.buckets()
+    }
+}
+
+impl From<Vec<FeeBucket>> for FeeHistogram {
+    /// Rebuilds a histogram from its buckets, as a node reports them
+    fn from(buckets: Vec<FeeBucket>) -> Self {
+        let mut histogram = FeeHistogram::new();
+        for bucket in buckets {
+            let key = (bucket.metered, bucket_index(bucket.min_rate));
+            let totals = histogram.buckets.entry(key).or_default();
+            totals.transactions += bucket.transactions;
+            totals.gas = totals.gas.saturating_add(bucket.gas);
+            totals.bytes = totals.bytes.saturating_add(bucket.bytes);
+        }
+        histogram.buckets.retain(|_, totals| totals.transactions > 0);
+        histogram
+    }
+}
+
+/// Gets the bucket of a rate: 0 for zero, k + 1 for rates from 2^k to 2^(k+1) - 1
+fn bucket_index(rate: u64) -> u32 {
+    u64::BITS - rate.leading_zeros()
+}
+
+fn bucket_key(rate: FeeRate) -> (bool, u32) {
+    (rate.is_metered(), bucket_index(rate.rate()))
+}
+
+/// Gets the lowest and highest rates of a bucket
+fn bucket_range(index: u32) -> (u64, u64) {
+    match index {
+        0 => (0, 0),
+        64 => (1 << 63, u64::MAX),
+        _ => (1 << (index - 1), (1 << index) - 1),
+    }
+}
+
+/// Room in the coming blocks, as `estimate_inclusion` sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSpace {
+    /// Gas limit of each block
+    pub gas_limit: u64,
+    
+    /// Most transactions a block includes besides its coinbase
+    pub max_transactions: u64,
+    
+    /// Base fee of the next block
+    pub next_base_fee: u64,
+    
+    /// Gas used by the latest blocks, up to `FULLNESS_WINDOW` of them
+    pub recent_gas_used: Vec<u64>,
+}
+
+/// Prediction of when a transaction paying a rate is included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EstimatedBlocks {
+    /// Blocks until one includes the transaction, 1 for the next block, or
+    /// `None` if it isn't expected within `MAX_ESTIMATED_BLOCKS`
+    pub blocks: Option<u64>,
+    
+    /// Blocks until the base fee falls to the gas price, 0 if it's already
+    /// there, or `None` if it isn't falling
+    pub base_fee_wait: Option<u64>,
+    
+    /// Pending transactions taken first, the rate's own bucket included
+    pub transactions_ahead: u64,
+    
+    /// Gas those transactions reserve
+    pub gas_ahead: u64,
+}
+
+/// Predicts how many blocks a transaction paying `rate` waits for, given what's pending
+///
+/// Blocks are expected to use the average gas of `space.recent_gas_used`
+/// for the base fee, and the transactions ahead of the rate to fill them
+/// to their gas limit and transaction count.
+pub fn estimate_inclusion(histogram: &FeeHistogram, rate: FeeRate, space: &BlockSpace) -> EstimatedBlocks {
+    let ahead = histogram.ahead_of(rate);
+    let base_fee_wait = match rate {
+        FeeRate::GasPrice(gas_price) => base_fee_wait(gas_price, space),
+        FeeRate::FlatFee(_) => Some(0),
+    };
+    
+    // The transaction fits in the block after those ahead of it have taken
+    // up the gas limit or transaction count of the blocks before
+    let by_gas = ahead.gas.checked_div(space.gas_limit).unwrap_or(0);
+    let by_count = ahead.transactions.checked_div(space.max_transactions).unwrap_or(u64::MAX);
+    let blocks = base_fee_wait
+        .map(|wait| wait.saturating_add(1).saturating_add(by_gas.max(by_count)))
+        .filter(|&blocks| blocks <= MAX_ESTIMATED_BLOCKS);
+    
+    EstimatedBlocks { blocks, base_fee_wait, transactions_ahead: ahead.transactions, gas_ahead: ahead.gas }
+}
+
+/// Gets how many blocks the base fee takes to fall to a gas price, if it falls that far in `MAX_ESTIMATED_BLOCKS`
+fn base_fee_wait(gas_price: u64, space: &BlockSpace) -> Option<u64> {
+    let blocks = space.recent_gas_used.len() as u64;
+    let gas_used = space.recent_gas_used.iter().sum::<u64>().checked_div(blocks).unwrap_or(0);
+    let mut base_fee = space.next_base_fee;
+    let mut wait = 0;
+    while base_fee > gas_price {
+        let next = next_base_fee(base_fee, gas_used, space.gas_limit);
+        if next >= base_fee || wait == MAX_ESTIMATED_BLOCKS {
+            return None;
+        }
+        base_fee = next;
+        wait += 1;
+    }
+    Some(wait)
+}
\ No newline at end of file
@@ -0,0 +1,45 @@
+//! Choosing between competing branches of the chain
+//!
+//! When a node learns of a branch forking off below its tip,
+//! `Blockchain::reorganize` asks its `ForkChoiceRule` whether the branch
+//! should replace the blocks above the fork. Without one set, the longer
+//! branch wins (`LongestChain`). A proof of stake chain sets the consensus
+//! crate's stake-weighted rule instead, since with little stake a branch
+//! can be made long cheaply.
+//!
+//! The rule also weighs single blocks, which the chain sums into the weight
+//! it advertises to peers (`Blockchain::chain_weight`): a peer claiming a
+//! heavier chain than ours is worth syncing from, though only `prefers`
+//! decides whether its branch is taken.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::block::Block;
+use crate::chain::Blockchain;
+use crate::state::State;
+use crate::Result;
+
+/// Rule deciding whether a branch replaces the chain's blocks above the fork
+pub trait ForkChoiceRule: Send + Sync + fmt::Debug {
+    /// Checks whether `branch`, forking off after `fork_height`, is preferred to the chain's blocks above it
+    ///
+    /// The chain still holds its own blocks when asked. Returning an error
+    /// rejects the branch outright.
+    fn prefers(&self, chain: &Blockchain, fork_height: u64, branch: &[Arc<Block>]) -> Result<bool>;
+    
+    /// Weighs a block with the state before it, one by default, so a chain weighs as much as it's long
+    fn block_weight(&self, _state: &State, _block: &Block) -> u128 {
+        1
+    }
+}
+
+/// Prefers the longer branch
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LongestChain;
+
+impl ForkChoiceRule for LongestChain {
+    fn prefers(&self, chain: &Blockchain, fork_height: u64, branch: &[Arc<Block>]) -> Result<bool> {
+        Ok(fork_height + branch.len() as u64 > chain.get_latest_height())
+    }
+}
\ No newline at end of file
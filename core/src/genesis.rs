@@ -2,12 +2,19 @@
 //!
 //! This module defines the genesis block configuration and initial GENX distribution.
 
-use crate::{Result, BlockchainError};
+use crate::Result;
 use crate::block::Block;
+use crate::deposit::DepositRates;
+use crate::execution_policy::ExecutionPolicy;
+use crate::rewards::{RewardSchedule, TreasuryRule};
 use crate::transaction::Transaction;
+use crate::units::GENX;
+
+/// Decimal places of GENX: one GENX is 10 to this power base units (see `units`)
+pub const DECIMALS: u32 = 8;
 
 /// Maximum supply of GENX tokens (21 million)
-const MAX_SUPPLY: u64 = 21_000_000 * 100_000_000; // With 8 decimal places
+const MAX_SUPPLY: u64 = 21_000_000 * GENX;
 
 /// Percentage of tokens allocated to different purposes
 const GENESIS_ALLOCATION_PERCENT: u64 = 60;
@@ -15,42 +22,74 @@ const VALIDATOR_REWARDS_PERCENT: u64 = 20;
 const DEVELOPMENT_FUND_PERCENT: u64 = 10;
 const ECOSYSTEM_GROWTH_PERCENT: u64 = 10;
 
+// The allocations account for the whole supply
+const _: () = assert!(
+    GENESIS_ALLOCATION_PERCENT + VALIDATOR_REWARDS_PERCENT + DEVELOPMENT_FUND_PERCENT + ECOSYSTEM_GROWTH_PERCENT == 100
+);
+
+/// Maximum total gas the transactions of a single block may consume
+const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// Base fee of the genesis block, in GENX base units per gas
+const INITIAL_BASE_FEE: u64 = 1;
+
+/// Deposit locked for each contract storage slot written (0.001 GENX)
+const STORAGE_DEPOSIT_PER_SLOT: u64 = GENX / 1_000;
+
+/// Deposit locked for each byte of deployed contract code (0.00001 GENX)
+const STORAGE_DEPOSIT_PER_CODE_BYTE: u64 = GENX / 100_000;
+
 /// Addresses for initial token allocation
 const VALIDATOR_REWARDS_ADDRESS: &str = "GENX_VALIDATOR_REWARDS_POOL";
 const DEVELOPMENT_FUND_ADDRESS: &str = "GENX_DEVELOPMENT_FUND";
 const ECOSYSTEM_GROWTH_ADDRESS: &str = "GENX_ECOSYSTEM_GROWTH";
 
+/// Address receiving the treasury's share of block rewards
+const TREASURY_ADDRESS: &str = "GENX_TREASURY";
+
+/// Treasury's share of each block reward, in basis points (10%)
+const TREASURY_SHARE_BPS: u32 = 1_000;
+
+/// Changes to the treasury's address and share, by activation height
+///
+/// Each entry is a protocol upgrade taking effect from its height on, and
+/// must be added before the chain reaches it. Heights must increase.
+const TREASURY_UPGRADES: &[(u64, &str, u32)] = &[];
+
+/// Height from which blocks must order their transactions canonically, see `ordering`
+///
+/// A protocol upgrade, not scheduled yet: set it before the chain reaches
+/// the height.
+const TRANSACTION_ORDERING_HEIGHT: Option<u64> = None;
+
+/// Height from which the first block of each epoch commits to its validator
+/// set, see `validator_set`
+///
+/// A protocol upgrade, not scheduled yet: set it before the chain reaches
+/// the height.
+const VALIDATOR_SET_HEIGHT: Option<u64> = None;
+
 /// Creates the genesis block with initial GENX distribution
 pub fn create_genesis_block() -> Result<Block> {
     // Calculate token allocations
-    let genesis_allocation = (MAX_SUPPLY * GENESIS_ALLOCATION_PERCENT) / 100;
     let validator_rewards = (MAX_SUPPLY * VALIDATOR_REWARDS_PERCENT) / 100;
     let development_fund = (MAX_SUPPLY * DEVELOPMENT_FUND_PERCENT) / 100;
     let ecosystem_growth = (MAX_SUPPLY * ECOSYSTEM_GROWTH_PERCENT) / 100;
     
     // Create initial distribution transactions
-    let mut transactions = Vec::new();
-    
-    // Add validator rewards pool allocation
-    transactions.push(Transaction::new_coinbase(
-        VALIDATOR_REWARDS_ADDRESS.to_string(),
-        validator_rewards,
-    )?);
-    
-    // Add development fund allocation
-    transactions.push(Transaction::new_coinbase(
-        DEVELOPMENT_FUND_ADDRESS.to_string(),
-        development_fund,
-    )?);
-    
-    // Add ecosystem growth allocation
-    transactions.push(Transaction::new_coinbase(
-        ECOSYSTEM_GROWTH_ADDRESS.to_string(),
-        ecosystem_growth,
-    )?);
+    let transactions = vec![
+        // Validator rewards pool allocation
+        Transaction::new_coinbase(VALIDATOR_REWARDS_ADDRESS.to_string(), validator_rewards)?,
+        
+        // Development fund allocation
+        Transaction::new_coinbase(DEVELOPMENT_FUND_ADDRESS.to_string(), development_fund)?,
+        
+        // Ecosystem growth allocation
+        Transaction::new_coinbase(ECOSYSTEM_GROWTH_ADDRESS.to_string(), ecosystem_growth)?,
+    ];
     
     // Create the genesis block
-    Block::genesis(transactions)
+    Block::genesis(transactions, INITIAL_BASE_FEE)
 }
 
 /// Initializes the blockchain with the genesis block and initial state
@@ -59,11 +98,68 @@ pub fn initialize_blockchain() -> Result<crate::chain::Blockchain> {
     crate::chain::Blockchain::new(genesis_block)
 }
 
+/// Gets the treasury rules from genesis on, including every scheduled upgrade
+pub fn reward_schedule() -> Result<RewardSchedule> {
+    let genesis_rule = TreasuryRule {
+        activation_height: 0,
+        address: TREASURY_ADDRESS.to_string(),
+        share_bps: TREASURY_SHARE_BPS,
+    };
+    let upgrades = TREASURY_UPGRADES.iter().map(|&(activation_height, address, share_bps)| TreasuryRule {
+        activation_height,
+        address: address.to_string(),
+        share_bps,
+    });
+    
+    RewardSchedule::new(std::iter::once(genesis_rule).chain(upgrades).collect())
+}
+
 /// Gets the maximum supply of GENX tokens
 pub fn get_max_supply() -> u64 {
     MAX_SUPPLY
 }
 
+/// Gets the account holding the validator rewards pool allocated at genesis
+pub fn get_validator_rewards_address() -> &'static str {
+    VALIDATOR_REWARDS_ADDRESS
+}
+
+/// Gets the block gas limit the chain starts with
+pub fn get_block_gas_limit() -> u64 {
+    BLOCK_GAS_LIMIT
+}
+
+/// Gets the base fee of the genesis block
+pub fn get_initial_base_fee() -> u64 {
+    INITIAL_BASE_FEE
+}
+
+/// Gets the height from which blocks must order their transactions canonically, if scheduled
+pub fn get_transaction_ordering_height() -> Option<u64> {
+    TRANSACTION_ORDERING_HEIGHT
+}
+
+/// Gets the height from which epochs' first blocks commit to their validator sets, if scheduled
+pub fn get_validator_set_height() -> Option<u64> {
+    VALIDATOR_SET_HEIGHT
+}
+
+/// Gets the deposits locked for contract storage, see `deposit`
+pub fn get_storage_deposit_rates() -> DepositRates {
+    DepositRates {
+        per_slot: STORAGE_DEPOSIT_PER_SLOT,
+        per_code_byte: STORAGE_DEPOSIT_PER_CODE_BYTE,
+    }
+}
+
+/// Gets who may deploy and call contracts at genesis, see `execution_policy`
+///
+/// Open on the public chain, without admins to restrict it later. A
+/// permissioned network names its admins and deployers here.
+pub fn get_execution_policy() -> ExecutionPolicy {
+    ExecutionPolicy::default()
+}
+
 /// Gets the current circulating supply of GENX tokens
 pub fn get_circulating_supply(blockchain: &crate::chain::Blockchain) -> Result<u64> {
     let state = blockchain.get_state();
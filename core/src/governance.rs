@@ -0,0 +1,331 @@
+//! On-chain governance of chain parameters
+//!
+//! Anyone can propose changing a governed parameter by sending a
+//! `SubmitProposal` transaction naming the parameter, its new value, how
+//! many blocks voting lasts and the height the change activates at. The
+//! transaction's amount is the proposal's deposit, held until voting ends.
+//! Validators vote with `Vote` transactions, each vote weighing as much as
+//! the voter's stake when voting ends; a later vote by the same validator
+//! replaces its earlier one.
+//!
+//! Votes are tallied after the last block of the voting period. A proposal
+//! reaches quorum once validators holding `QUORUM_PERCENT` of all stake
+//! have voted, and passes if more than `THRESHOLD_PERCENT` of the stake that
+//! voted approves. The deposit is refunded if the proposal reached quorum
+//! and burned otherwise, so proposals nobody cares about cost their sender.
+//!
+//! A passed proposal changes its parameter from its activation height on,
+//! like the protocol upgrades activating at the heights in `genesis`:
+//! `State::governed_value` gives the value in effect at a height, which
+//! overrides the configured one. Only the parameters of `GovernedParameter`
+//! can be governed, each within its bounds.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::units::GENX;
+use crate::{BlockchainError, Result};
+
+/// Least deposit a proposal must carry
+pub const MIN_PROPOSAL_DEPOSIT: u64 = 100 * GENX;
+
+/// Fewest blocks voting on a proposal may last
+pub const MIN_VOTING_PERIOD: u64 = 1;
+
+/// Most blocks voting on a proposal may last
+pub const MAX_VOTING_PERIOD: u64 = 100_000;
+
+/// Percentage of all stake that must vote for a proposal to reach quorum
+pub const QUORUM_PERCENT: u64 = 40;
+
+/// Percentage of the stake voting that approval must exceed for a proposal to pass
+pub const THRESHOLD_PERCENT: u64 = 50;
+
+/// A chain parameter that proposals may change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernedParameter {
+    /// Target seconds between blocks
+    BlockTime,
+    
+    /// Most validators in an epoch's active set
+    ValidatorSetSize,
+    
+    /// Least stake a validator must hold to be active, in base units
+    MinStake,
+}
+
+impl GovernedParameter {
+    /// Every governed parameter
+    pub const ALL: [Self; 3] = [Self::BlockTime, Self::ValidatorSetSize, Self::MinStake];
+    
+    /// Gets the name of the parameter in proposals
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::BlockTime => "block_time",
+            Self::ValidatorSetSize => "validator_set_size",
+            Self::MinStake => "min_stake",
+        }
+    }
+    
+    /// Gets the values the parameter may be set to
+    pub fn bounds(&self) -> RangeInclusive<u64> {
+        match self {
+            Self::BlockTime => 1..=60,
+            Self::ValidatorSetSize => 1..=100,
+            Self::MinStake => GENX..=1_000_000 * GENX,
+        }
+    }
+    
+    /// Gets the tag of the parameter in the canonical state encoding
+    pub(crate) fn tag(&self) -> u64 {
+        match self {
+            Self::BlockTime => 0,
+            Self::ValidatorSetSize => 1,
+            Self::MinStake => 2,
+        }
+    }
+    
+    /// Gets the parameter with a tag of the canonical state encoding
+    pub(crate) fn from_tag(tag: u64) -> Option<Self> {
+        Self::ALL.into_iter().find(|parameter| parameter.tag() == tag)
+    }
+}
+
+impl fmt::Display for GovernedParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+impl FromStr for GovernedParameter {
+    type Err = BlockchainError;
+    
+    fn from_str(key: &str) -> Result<Self> {
+        Self::ALL.into_iter().find(|parameter| parameter.key() == key).ok_or_else(|| {
+            BlockchainError::InvalidTransaction(format!("Parameter {} can't be governed", key))
+        })
+    }
+}
+
+/// Payload of a `SubmitProposal` transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposalSubmission {
+    /// Parameter to change
+    pub parameter: GovernedParameter,
+    
+    /// Value to change it to
+    pub value: u64,
+    
+    /// Blocks voting lasts, starting with the block after the submission's
+    pub voting_period: u64,
+    
+    /// Height of the first block the change applies to, after voting ends
+    pub activation_height: u64,
+}
+
+/// Payload of a `Vote` transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposalVote {
+    /// Proposal voted on
+    pub proposal_id: u64,
+    
+    /// Whether the voter approves the change
+    pub approve: bool,
+}
+
+/// Where a proposal stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    /// Collecting votes
+    Voting,
+    
+    /// Passed, changing the parameter from the activation height
+    Passed,
+    
+    /// Reached quorum without enough approval
+    Rejected,
+    
+    /// Didn't reach quorum; the deposit was burned
+    NoQuorum,
+}
+
+impl ProposalStatus {
+    /// Gets the tag of the status in the canonical state encoding
+    pub(crate) fn tag(&self) -> u64 {
+        match self {
+            Self::Voting => 0,
+            Self::Passed => 1,
+            Self::Rejected => 2,
+            Self::NoQuorum => 3,
+        }
+    }
+    
+    /// Gets the status with a tag of the canonical state encoding
+    pub(crate) fn from_tag(tag: u64) -> Option<Self> {
+        [Self::Voting, Self::Passed, Self::Rejected, Self::NoQuorum].into_iter().find(|status| status.tag() == tag)
+    }
+}
+
+/// A proposal to change a governed parameter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposal {
+    /// Number of the proposal, counting from 1 in submission order
+    pub id: u64,
+    
+    /// Address that submitted the proposal and paid its deposit
+    pub proposer: String,
+    
+    /// Parameter to change
+    pub parameter: GovernedParameter,
+    
+    /// Value to change it to
+    pub value: u64,
+    
+    /// Amount held until voting ends
+    pub deposit: u64,
+    
+    /// Height of the block the proposal was submitted in
+    pub submitted_at: u64,
+    
+    /// Height of the last block votes are accepted in, after which they're tallied
+    pub voting_ends_at: u64,
+    
+    /// Height of the first block the change applies to if the proposal passes
+    pub activation_height: u64,
+    
+    /// Votes so far, approving or not, by validator
+    pub votes: BTreeMap<String, bool>,
+    
+    /// Where the proposal stands
+    pub status: ProposalStatus,
+}
+
+/// Outcome of tallying a proposal's votes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tally {
+    /// Stake of the validators that approve
+    pub approving: u64,
+    
+    /// Stake of the validators that voted
+    pub voting: u64,
+    
+    /// Stake of all validators
+    pub total: u64,
+}
+
+impl ProposalSubmission {
+    /// Encodes the submission as transaction data
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+    
+    /// Decodes a submission from transaction data and validates it
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let submission: Self = serde_json::from_slice(data).map_err(|e| {
+            BlockchainError::InvalidTransaction(format!("Invalid proposal: {}", e))
+        })?;
+        submission.validate()?;
+        Ok(submission)
+    }
+    
+    /// Checks the value is within the parameter's bounds and the voting period within its own
+    ///
+    /// Whether the activation height is late enough depends on the height
+    /// the proposal is submitted at, and is checked when it's applied.
+    pub fn validate(&self) -> Result<()> {
+        let bounds = self.parameter.bounds();
+        if !bounds.contains(&self.value) {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "{} must be from {} to {}, not {}",
+                self.parameter,
+                bounds.start(),
+                bounds.end(),
+                self.value
+            )));
+        }
+        if !(MIN_VOTING_PERIOD..=MAX_VOTING_PERIOD).contains(&self.voting_period) {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Voting period must be from {} to {} blocks, not {}",
+                MIN_VOTING_PERIOD, MAX_VOTING_PERIOD, self.voting_period
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ProposalVote {
+    /// Encodes the vote as transaction data
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+    
+    /// Decodes a vote from transaction data
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).map_err(|e| BlockchainError::InvalidTransaction(format!("Invalid vote: {}", e)))
+    }
+}
+
+impl Proposal {
+    /// Creates the proposal a submission made at the given height opens
+    ///
+    /// Fails unless the change activates after voting ends.
+    pub fn new(id: u64, proposer: String, submission: ProposalSubmission, deposit: u64, height: u64) -> Result<Self> {
+        let voting_ends_at = height.saturating_add(submission.voting_period);
+        if submission.activation_height <= voting_ends_at {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Proposal must activate after voting ends at height {}, not at {}",
+                voting_ends_at, submission.activation_height
+            )));
+        }
+        
+        Ok(Self {
+            id,
+            proposer,
+            parameter: submission.parameter,
+            value: submission.value,
+            deposit,
+            submitted_at: height,
+            voting_ends_at,
+            activation_height: submission.activation_height,
+            votes: BTreeMap::new(),
+            status: ProposalStatus::Voting,
+        })
+    }
+    
+    /// Tallies the votes, weighing each by the voter's stake
+    pub fn tally(&self, stake_of: impl Fn(&str) -> u64, total: u64) -> Tally {
+        let mut tally = Tally { approving: 0, voting: 0, total };
+        for (voter, &approve) in &self.votes {
+            let stake = stake_of(voter);
+            tally.voting = tally.voting.saturating_add(stake);
+            if approve {
+                tally.approving = tally.approving.saturating_add(stake);
+            }
+        }
+        tally
+    }
+}
+
+impl Tally {
+    /// Checks whether enough stake voted
+    pub fn reaches_quorum(&self) -> bool {
+        self.voting > 0 && self.voting as u128 * 100 >= self.total as u128 * QUORUM_PERCENT as u128
+    }
+    
+    /// Gets where a proposal with this tally stands once voting ends
+    pub fn outcome(&self) -> ProposalStatus {
+        if !self.reaches_quorum() {
+            ProposalStatus::NoQuorum
+        } else if self.approving as u128 * 100 > self.voting as u128 * THRESHOLD_PERCENT as u128 {
+            ProposalStatus::Passed
+        } else {
+            ProposalStatus::Rejected
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,294 @@
+//! Hash algorithms and the domains they hash in
+//!
+//! Everything the chain hashes belongs to a `HashDomain`, which names the
+//! algorithm used and a tag hashed before the data: the tag's bytes and a
+//! zero byte. No tag contains a zero byte, so no prefix starts another, and
+//! data hashed in one domain can't hash to what data of another does. A
+//! transaction ID can't be mistaken for a block hash, nor a merkle root for
+//! either.
+//!
+//! Which algorithm each domain uses is part of the protocol. Consensus
+//! domains use SHA-256 and changing one is a protocol upgrade: every ID and
+//! hash in it changes, and the golden vectors of `testutil` with them.
+//!
+//! Outside the domains are hashes fixed by something else:
+//!
+//! - `keccak256`, for whatever faces contracts and Ethereum, such as
+//!   selectors, event topics and the IDs of imported Ethereum transactions,
+//!   which must be the hashes Ethereum gives them.
+//! - `sha256`, for the `sha256` precompile and the HMAC of signing nonces.
+//! - `blake3`, for hashes that never leave a node or wallet, such as cache
+//!   keys and the hash a wallet keeps of its file. It's the fastest of the
+//!   three, and may change between releases.
+
+use std::io;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use crate::block::BlockHeader;
+use crate::transaction::Transaction;
+use crate::{BlockHash, BlockchainError, Hash, Result, TxHash};
+
+/// Hash function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// SHA-256, which consensus hashes use
+    Sha256,
+    
+    /// Keccak-256, as Ethereum and Solidity use it
+    Keccak256,
+    
+    /// BLAKE3, for hashes that never leave a node
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Gets the algorithm's name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Keccak256 => "keccak256",
+            Self::Blake3 => "blake3",
+        }
+    }
+}
+
+/// What a hash is of, which sets its algorithm and prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashDomain {
+    /// ID of a transaction, see `hash_tx_canonical`
+    Transaction,
+    
+    /// Hash of a block, see `hash_block_header`
+    BlockHeader,
+    
+    /// Root of a block's transactions
+    TransactionRoot,
+    
+    /// Root of a contract's storage
+    StorageRoot,
+    
+    /// Address of a deployed contract
+    ContractAddress,
+    
+    /// EVM address of an account that isn't a secp256k1 key or an EVM address
+    EvmAddress,
+    
+    /// Commitment to a transaction memo
+    MemoCommitment,
+    
+    /// Chunk of a state snapshot
+    StateChunk,
+    
+    /// Root of a state snapshot, over its chunks' hashes
+    StateRoot,
+    
+    /// Compact filter of a block
+    BlockFilter,
+    
+    /// Header chaining a block's filter to the previous block's
+    FilterHeader,
+    
+    /// Item of a compact filter, keyed by its block's hash
+    FilterItem,
+    
+    /// Validator set an epoch's first block commits to
+    ValidatorSet,
+    
+    /// Message a finality vote signs
+    FinalityVote,
+    
+    /// Address set in an epoch's address bloom, see `address_bloom`
+    AddressBloom,
+    
+    /// Message a node's announcement of its network address signs
+    AddressAnnouncement,
+}
+
+impl HashDomain {
+    /// Every domain
+    pub const ALL: [HashDomain; 16] = [
+        Self::Transaction,
+        Self::BlockHeader,
+        Self::TransactionRoot,
+        Self::StorageRoot,
+        Self::ContractAddress,
+        Self::EvmAddress,
+        Self::MemoCommitment,
+        Self::StateChunk,
+        Self::StateRoot,
+        Self::BlockFilter,
+        Self::FilterHeader,
+        Self::FilterItem,
+        Self::ValidatorSet,
+        Self::FinalityVote,
+        Self::AddressBloom,
+        Self::AddressAnnouncement,
+    ];
+    
+    /// Gets the algorithm the domain hashes with
+    pub fn algorithm(&self) -> HashAlgorithm {
+        // Every domain is consensus; changing one is a protocol upgrade
+        HashAlgorithm::Sha256
+    }
+    
+    /// Gets the tag hashed, followed by a zero byte, before the data
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Self::Transaction => "GENX_TX",
+            Self::BlockHeader => "GENX_BLOCK",
+            Self::TransactionRoot => "GENX_TX_ROOT",
+            Self::StorageRoot => "GENX_STORAGE_ROOT",
+            Self::ContractAddress => "GENX_CONTRACT_ADDRESS",
+            Self::EvmAddress => "GENX_EVM_ADDRESS",
+            Self::MemoCommitment => "GENX_MEMO",
+            Self::StateChunk => "GENX_STATE_CHUNK",
+            Self::StateRoot => "GENX_STATE_ROOT",
+            Self::BlockFilter => "GENX_FILTER",
+            Self::FilterHeader => "GENX_FILTER_HEADER",
+            Self::FilterItem => "GENX_FILTER_ITEM",
+            Self::ValidatorSet => "GENX_VALIDATOR_SET",
+            Self::FinalityVote => "GENX_FINALITY_VOTE",
+            Self::AddressBloom => "GENX_ADDRESS_BLOOM",
+            Self::AddressAnnouncement => "GENX_ADDRESS_ANNOUNCEMENT",
+        }
+    }
+}
+
+/// Incremental hash of data, in a domain or with a bare algorithm
+///
+/// Also an `io::Write`, so data can be serialized straight into it.
+#[derive(Debug, Clone)]
+pub struct Hasher {
+    state: HasherState,
+}
+
+#[derive(Debug, Clone)]
+enum HasherState {
+    Sha256(Sha256),
+    Keccak256(Box<Keccak256>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    /// Creates a hasher with nothing hashed yet, outside any domain
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        let state = match algorithm {
+            HashAlgorithm::Sha256 => HasherState::Sha256(Sha256::new()),
+            HashAlgorithm::Keccak256 => HasherState::Keccak256(Box::default()),
+            HashAlgorithm::Blake3 => HasherState::Blake3(Box::default()),
+        };
+        Self { state }
+    }
+    
+    /// Creates a hasher of a domain, with the domain's prefix hashed
+    pub fn for_domain(domain: HashDomain) -> Self {
+        let mut hasher = Self::new(domain.algorithm());
+        hasher.update(domain.tag().as_bytes());
+        hasher.update(&[0]);
+        hasher
+    }
+    
+    /// Gets the algorithm the hasher uses
+    pub fn algorithm(&self) -> HashAlgorithm {
+        match self.state {
+            HasherState::Sha256(_) => HashAlgorithm::Sha256,
+            HasherState::Keccak256(_) => HashAlgorithm::Keccak256,
+            HasherState::Blake3(_) => HashAlgorithm::Blake3,
+        }
+    }
+    
+    /// Hashes more data
+    pub fn update(&mut self, data: &[u8]) -> &mut Self {
+        match &mut self.state {
+            HasherState::Sha256(hasher) => hasher.update(data),
+            HasherState::Keccak256(hasher) => hasher.update(data),
+            HasherState::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+        self
+    }
+    
+    /// Gets the hash of everything hashed
+    pub fn finalize(self) -> Hash {
+        match self.state {
+            HasherState::Sha256(hasher) => hasher.finalize().into(),
+            HasherState::Keccak256(hasher) => hasher.finalize().into(),
+            HasherState::Blake3(hasher) => hasher.finalize().into(),
+        }
+    }
+}
+
+impl io::Write for Hasher {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.update(data);
+        Ok(data.len())
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes data in a domain
+pub fn hash_in(domain: HashDomain, data: &[u8]) -> Hash {
+    let mut hasher = Hasher::for_domain(domain);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Hashes the JSON serialization of a value in a domain
+pub fn hash_json_in<T: Serialize + ?Sized>(domain: HashDomain, value: &T) -> Result<Hash> {
+    let mut hasher = Hasher::for_domain(domain);
+    serde_json::to_writer(&mut hasher, value).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    Ok(hasher.finalize())
+}
+
+/// Gets the ID of a transaction from its contents
+///
+/// That's the hash of the transaction without its ID and signature, so
+/// signing doesn't change it. A transaction imported from Ethereum hashes
+/// to the Keccak-256 hash of the raw Ethereum transaction instead, the ID
+/// Ethereum gives it.
+pub fn hash_tx_canonical(tx: &Transaction) -> Result<TxHash> {
+    if let Some(raw) = &tx.eth_raw {
+        return Ok(TxHash(keccak256(raw)));
+    }
+    
+    let unsigned = Transaction {
+        id: TxHash::default(),
+        signature: None,
+        eth_raw: None,
+        ..tx.clone()
+    };
+    hash_json_in(HashDomain::Transaction, &unsigned).map(TxHash)
+}
+
+/// Gets the hash of the block with a header
+pub fn hash_block_header(header: &BlockHeader) -> Result<BlockHash> {
+    hash_json_in(HashDomain::BlockHeader, header).map(BlockHash)
+}
+
+/// Hashes data with Keccak-256, with no prefix
+pub fn keccak256(data: &[u8]) -> Hash {
+    let mut hasher = Hasher::new(HashAlgorithm::Keccak256);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Hashes data with SHA-256, with no prefix
+pub fn sha256(data: &[u8]) -> Hash {
+    let mut hasher = Hasher::new(HashAlgorithm::Sha256);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Hashes data with BLAKE3, with no prefix, for hashes that never leave a node
+pub fn blake3(data: &[u8]) -> Hash {
+    let mut hasher = Hasher::new(HashAlgorithm::Blake3);
+    hasher.update(data);
+    hasher.finalize()
+}
\ No newline at end of file
@@ -6,21 +6,65 @@
 //! - Genesis block configuration
 //! - State management
 
-use std::collections::HashMap;
-use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+pub mod address;
+pub mod address_bloom;
 pub mod block;
+pub mod block_filter;
+pub mod block_store;
 pub mod chain;
+#[cfg(feature = "testutil")]
+pub mod chainbuilder;
+pub mod confirmation;
+pub mod context;
+pub mod deposit;
+pub mod eth_transaction;
+pub mod execution_policy;
+pub mod executor;
+pub mod fee_market;
+pub mod fork_choice;
 pub mod genesis;
+pub mod governance;
+pub mod hashing;
+pub mod memo;
+pub mod ordering;
+pub mod paging;
+pub mod receipt;
+pub mod replay;
+pub mod rewards;
+pub mod rlp;
+pub mod secp256k1;
+pub mod signature;
+pub mod slashing;
 pub mod transaction;
 pub mod state;
+pub mod state_diff;
+pub mod state_sync;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+pub mod types;
+pub mod units;
+pub mod validator;
+pub mod validator_set;
+pub mod verified;
+pub mod wire;
+
+pub use address::Address;
+pub use context::{ErrorContext, WithContext};
+pub use types::{BlockHash, Bytes, EvmAddress, TxHash};
+pub use units::Amount;
 
 /// Blockchain error types
+///
+/// Every variant has a stable numeric code, see `error_code`. The crates
+/// built on this one number their own errors from a range each: consensus
+/// from 2000, contracts from 3000, the wallet from 4000 and the network
+/// from 5000, and errors of theirs wrapping one of these keep its code.
 #[derive(Debug, Error)]
 pub enum BlockchainError {
     #[error("Invalid block: {0}")]
@@ -37,6 +81,133 @@ pub enum BlockchainError {
     
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    
+    #[error("Receipts and logs below height {0} have been pruned")]
+    Pruned(u64),
+    
+    #[error("State at height {height} can't be reconstructed; the oldest available is at height {oldest}")]
+    StateUnavailable { height: u64, oldest: u64 },
+    
+    #[error("Insufficient balance in {address}: {available} < {required}")]
+    InsufficientBalance { address: String, required: u64, available: u64 },
+    
+    #[error("No block at height {height}")]
+    UnknownBlock { height: u64 },
+    
+    #[error("Invalid block height: expected {expected}, got {got}")]
+    UnexpectedHeight { expected: u64, got: u64 },
+    
+    #[error("Block's previous hash doesn't match the latest hash")]
+    ParentMismatch,
+    
+    #[error("Invalid merkle root")]
+    InvalidMerkleRoot,
+    
+    #[error("Invalid base fee: expected {expected}, got {got}")]
+    BaseFeeMismatch { expected: u64, got: u64 },
+    
+    #[error("Gas price {gas_price} is below the block's base fee {base_fee}")]
+    GasPriceBelowBaseFee { gas_price: u64, base_fee: u64 },
+    
+    #[error("Gas of {gas} exceeds the block gas limit {limit}")]
+    BlockGasExceeded { gas: u64, limit: u64 },
+    
+    #[error("Transaction {tx_id} is not signed")]
+    MissingSignature { tx_id: TxHash },
+    
+    #[error("Invalid signature on transaction {tx_id}: {reason}")]
+    InvalidSignature { tx_id: TxHash, reason: String },
+    
+    #[error("Transaction ID {tx_id} doesn't match its contents")]
+    InvalidTransactionId { tx_id: TxHash },
+    
+    #[error("Validator {address} is not registered")]
+    UnknownValidator { address: String },
+    
+    #[error("Validator {address} is already registered")]
+    ValidatorExists { address: String },
+    
+    #[error("Only the last {max_depth} blocks can be rolled back")]
+    BeyondRollbackDepth { max_depth: u64 },
+    
+    #[error("Branch forking off after height {fork_height} isn't preferred to the chain")]
+    BranchNotPreferred { fork_height: u64 },
+    
+    #[error("Proposal {id} doesn't exist")]
+    UnknownProposal { id: u64 },
+    
+    #[error("Invalid cursor {0}")]
+    InvalidCursor(String),
+    
+    #[error("Transaction {tx_id} isn't in the chain")]
+    UnknownTransaction { tx_id: TxHash },
+    
+    #[error("Evidence about height {height} expired at height {expired_at}")]
+    EvidenceExpired { height: u64, expired_at: u64 },
+    
+    #[error("Slash {id} doesn't exist")]
+    UnknownSlash { id: u64 },
+    
+    #[error("Execution policy denies {sender}: {violation}")]
+    PolicyDenied { sender: String, violation: execution_policy::PolicyViolation },
+    
+    #[error("Invalid nonce for {address}: expected {expected}, got {got}")]
+    InvalidNonce { address: String, expected: u64, got: u64 },
+    
+    #[error("Block isn't signed by validator {validator}: {reason}")]
+    InvalidBlockSignature { validator: String, reason: String },
+    
+    #[error("Invalid address: {0}")]
+    InvalidAddress(#[from] address::AddressError),
+    
+    /// An error of the consensus engine, which this crate can't name
+    #[error("Consensus error: {message}")]
+    Consensus { code: u32, message: String },
+    
+    /// An error with where it happened, see `context`
+    #[error("{source} ({context})")]
+    WithContext { context: Box<ErrorContext>, source: Box<BlockchainError> },
+}
+
+impl BlockchainError {
+    /// Gets the stable numeric code of the error, reported by the RPC alongside its message
+    pub fn error_code(&self) -> u32 {
+        match self {
+            BlockchainError::InvalidBlock(_) => 1000,
+            BlockchainError::InvalidTransaction(_) => 1001,
+            BlockchainError::StateError(_) => 1002,
+            BlockchainError::SerializationError(_) => 1003,
+            BlockchainError::IoError(_) => 1004,
+            BlockchainError::Pruned(_) => 1005,
+            BlockchainError::StateUnavailable { .. } => 1006,
+            BlockchainError::InsufficientBalance { .. } => 1007,
+            BlockchainError::UnknownBlock { .. } => 1008,
+            BlockchainError::UnexpectedHeight { .. } => 1009,
+            BlockchainError::ParentMismatch => 1010,
+            BlockchainError::InvalidMerkleRoot => 1011,
+            BlockchainError::BaseFeeMismatch { .. } => 1012,
+            BlockchainError::GasPriceBelowBaseFee { .. } => 1013,
+            BlockchainError::BlockGasExceeded { .. } => 1014,
+            BlockchainError::MissingSignature { .. } => 1015,
+            BlockchainError::InvalidSignature { .. } => 1016,
+            BlockchainError::InvalidTransactionId { .. } => 1017,
+            BlockchainError::UnknownValidator { .. } => 1018,
+            BlockchainError::ValidatorExists { .. } => 1019,
+            BlockchainError::BeyondRollbackDepth { .. } => 1020,
+            BlockchainError::BranchNotPreferred { .. } => 1021,
+            BlockchainError::UnknownProposal { .. } => 1022,
+            BlockchainError::InvalidCursor(_) => 1023,
+            BlockchainError::UnknownTransaction { .. } => 1024,
+            BlockchainError::EvidenceExpired { .. } => 1025,
+            BlockchainError::UnknownSlash { .. } => 1026,
+            BlockchainError::PolicyDenied { .. } => 1027,
+            BlockchainError::InvalidNonce { .. } => 1028,
+            BlockchainError::InvalidBlockSignature { .. } => 1029,
+            BlockchainError::InvalidAddress(e) => e.error_code(),
+            BlockchainError::Consensus { code, .. } => *code,
+            BlockchainError::WithContext { source, .. } => source.error_code(),
+        }
+    }
 }
 
 /// Result type for blockchain operations
@@ -50,7 +221,10 @@ pub fn hash_to_hex(hash: &Hash) -> String {
     hex::encode(hash)
 }
 
-/// Calculates the SHA-256 hash of the provided data
+/// Calculates the SHA-256 hash of the JSON serialization of the provided data
+///
+/// Hashes outside any domain; hash with `hashing::hash_json_in` instead.
+#[deprecated(note = "use a domain of `hashing`")]
 pub fn calculate_hash<T: Serialize>(data: &T) -> Result<Hash> {
     let serialized = serde_json::to_string(data)
         .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
@@ -0,0 +1,101 @@
+//! Memos attached to transactions
+//!
+//! Exchanges and other custodians tell their customers' deposits apart by
+//! a memo the payer attaches. A memo is kept apart from a transaction's
+//! `data`, which contracts read, and nothing executes it. It's either
+//! stored in plain, up to `MAX_MEMO_SIZE` bytes, or as a commitment: the
+//! hash of the memo, so the chain only holds the hash until the payer
+//! reveals the memo to whoever should see it, who checks it against the
+//! commitment with `MemoField::verify`.
+//!
+//! Commitments are hashes in their own domain, `HashDomain::MemoCommitment`,
+//! so they can't be mistaken for hashes of anything else. Anyone
+//! can hash guesses of a memo, so a memo that must stay private can't be
+//! one that's easily guessed, such as a short customer number alone; the
+//! payer adds a random suffix and reveals it with the rest.
+//!
+//! A memo is part of its transaction's ID, so the sender's signature covers
+//! it. Every byte stored costs `MEMO_FEE_PER_BYTE`: a flat fee must be at
+//! least `Transaction::memo_fee`, and contract transactions pay data gas
+//! for the memo's bytes as for their data.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{self, HashDomain};
+use crate::units::GENX;
+use crate::{BlockchainError, Hash, Result};
+
+/// Largest plain memo, in bytes
+pub const MAX_MEMO_SIZE: usize = 256;
+
+/// Least flat fee for each byte of memo a transaction stores, in base units
+pub const MEMO_FEE_PER_BYTE: u64 = GENX / 100_000;
+
+/// Memo of a transaction, in plain or committed to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoField {
+    /// Memo stored as it is
+    Plain(String),
+    
+    /// Hash of a memo kept off the chain, see `commit`
+    Committed(#[serde(with = "crate::types::hex_serde")] Hash),
+}
+
+impl MemoField {
+    /// Creates the commitment to a memo
+    pub fn commit(memo: &str) -> Self {
+        Self::Committed(commitment(memo))
+    }
+    
+    /// Gets the number of bytes the memo stores
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Plain(memo) => memo.len(),
+            Self::Committed(hash) => hash.len(),
+        }
+    }
+    
+    /// Checks whether the memo stores nothing, which only an empty plain memo does
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// Checks whether a memo revealed off the chain is this one
+    ///
+    /// A plain memo must be the same text, and a commitment the hash of it.
+    pub fn verify(&self, revealed: &str) -> bool {
+        match self {
+            Self::Plain(memo) => memo == revealed,
+            Self::Committed(hash) => *hash == commitment(revealed),
+        }
+    }
+    
+    /// Checks a plain memo is no longer than `MAX_MEMO_SIZE` bytes
+    pub fn validate(&self) -> Result<()> {
+        if self.len() > MAX_MEMO_SIZE {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Memo of {} bytes exceeds the {} byte limit",
+                self.len(),
+                MAX_MEMO_SIZE
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MemoField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plain(memo) => f.write_str(memo),
+            Self::Committed(hash) => write!(f, "committed 0x{}", hex::encode(hash)),
+        }
+    }
+}
+
+/// Hashes a memo into its commitment
+fn commitment(memo: &str) -> Hash {
+    hashing::hash_in(HashDomain::MemoCommitment, memo.as_bytes())
+}
\ No newline at end of file
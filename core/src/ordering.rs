@@ -0,0 +1,67 @@
+//! Canonical order of transactions within a block
+//!
+//! A validator free to order a block's transactions as it likes can place
+//! its own ahead of, or around, those it sees pending. From the activation
+//! height set with the genesis block (see
+//! `genesis::get_transaction_ordering_height`), a block's transactions must
+//! follow a single order every node can check instead:
+//!
+//! - the coinbase transactions first, as `rewards` requires;
+//! - then transactions paying for gas, highest gas price first;
+//! - then flat-fee transfers, highest fee first.
+//!
+//! Transactions paying the same are ordered by ID, lowest first. The mempool
+//! hands out transactions in this order (see `Mempool::pop_best` in the
+//! consensus crate), so blocks are produced in it without sorting.
+
+use std::cmp::Reverse;
+
+use crate::rewards::COINBASE;
+use crate::transaction::Transaction;
+use crate::{BlockchainError, Result, TxHash};
+
+/// Position of a transaction in the canonical order, the greatest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrderKey {
+    /// Whether the transaction pays for gas rather than a flat fee
+    pub metered: bool,
+    
+    /// Gas price of a metered transaction, or flat fee of a transfer
+    pub rate: u64,
+    
+    /// ID of the transaction, lowest greatest
+    pub id: Reverse<TxHash>,
+}
+
+impl OrderKey {
+    /// Lowest key of a metered transaction, above that of every flat-fee transfer
+    pub const LOWEST_METERED: Self = Self { metered: true, rate: 0, id: Reverse(TxHash([0xff; 32])) };
+    
+    /// Gets the key of a transaction
+    pub fn of(tx: &Transaction) -> Self {
+        Self {
+            metered: tx.is_metered(),
+            rate: if tx.is_metered() { tx.gas_price } else { tx.fee },
+            id: Reverse(tx.id),
+        }
+    }
+}
+
+/// Checks that a block's transactions follow the canonical order
+///
+/// Leading coinbase transactions are skipped; `rewards` checks where they
+/// may appear.
+pub fn check_order(transactions: &[Transaction]) -> Result<()> {
+    let start = transactions.iter().take_while(|tx| tx.sender == COINBASE).count();
+    for (index, pair) in transactions[start..].windows(2).enumerate() {
+        if OrderKey::of(&pair[1]) > OrderKey::of(&pair[0]) {
+            return Err(BlockchainError::InvalidBlock(format!(
+                "Transaction {} at position {} must come before {}",
+                pair[1].id,
+                start + index + 1,
+                pair[0].id
+            )));
+        }
+    }
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,243 @@
+//! Pagination of list APIs
+//!
+//! Every API returning a list that can grow without bound takes a
+//! `PageRequest` and returns a `Page`: up to `limit` items, the cursor the
+//! next page starts after, and an estimate of the total when one is cheap
+//! to get. Pages are keyed rather than counted: a cursor holds the sort key
+//! of the last item returned, and the next page starts with the first item
+//! sorted after it. Items inserted or removed while a client pages through
+//! a list therefore never make it skip or repeat the others; an item
+//! inserted before the cursor isn't seen, and one inserted after it is.
+//! Sort keys are unique within a listing, with the item's address, ID or
+//! position breaking ties. Listings sorted by something that changes, such
+//! as validators by stake, only hold to this for items whose key doesn't
+//! change meanwhile; one that moves may be seen twice or not at all.
+//!
+//! Cursors are opaque to clients: the key is serialized together with the
+//! name of the listing and sort it belongs to, and hex encoded. A cursor
+//! given to another listing, or to the same one sorted differently, fails
+//! with `BlockchainError::InvalidCursor`.
+//!
+//! Pages serialize as
+//! `{"items": [...], "next_cursor": "...", "total_estimate": n}`, with
+//! `next_cursor` null on the last page and `total_estimate` null when the
+//! total isn't known without reading the whole list.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockchainError, Result};
+
+/// Items a page holds when a request doesn't say
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+
+/// Most items a page holds
+pub const MAX_PAGE_LIMIT: usize = 1000;
+
+/// Position in a listing to continue from, opaque to clients
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cursor(String);
+
+impl Cursor {
+    /// Creates the cursor of the item with a sort key in a listing
+    pub fn new<K: Serialize>(listing: &str, key: &K) -> Self {
+        // Keys are plain numbers, strings and tuples of them, which always serialize
+        let encoded = serde_json::to_vec(&(listing, key)).unwrap_or_default();
+        Self(hex::encode(encoded))
+    }
+    
+    /// Gets the sort key the cursor holds, which must be of the listing
+    pub fn key<K: DeserializeOwned>(&self, listing: &str) -> Result<K> {
+        let invalid = || BlockchainError::InvalidCursor(self.0.clone());
+        let encoded = hex::decode(&self.0).map_err(|_| invalid())?;
+        let (owner, key): (String, K) = serde_json::from_slice(&encoded).map_err(|_| invalid())?;
+        if owner != listing {
+            return Err(invalid());
+        }
+        Ok(key)
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = BlockchainError;
+    
+    /// Takes a cursor as a client gives it back; whether it's valid is only known when it's used
+    fn from_str(text: &str) -> Result<Self> {
+        Ok(Self(text.to_string()))
+    }
+}
+
+/// Direction a listing is sorted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Smallest key first
+    Ascending,
+    
+    /// Largest key first
+    Descending,
+}
+
+/// Which page of a listing to get
+///
+/// Fields missing when deserializing take their default values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    /// Most items to return, at least 1 and at most `MAX_PAGE_LIMIT`
+    pub limit: usize,
+    
+    /// Cursor of the previous page, or `None` for the first page
+    pub cursor: Option<Cursor>,
+    
+    /// Direction to sort in, or `None` for the listing's usual one
+    pub order: Option<SortOrder>,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self { limit: DEFAULT_PAGE_LIMIT, cursor: None, order: None }
+    }
+}
+
+impl PageRequest {
+    /// Creates a request for the first page of up to `limit` items
+    pub fn new(limit: usize) -> Self {
+        Self { limit, ..Self::default() }
+    }
+    
+    /// Continues from a cursor, that of the previous page
+    pub fn after(mut self, cursor: Option<Cursor>) -> Self {
+        self.cursor = cursor;
+        self
+    }
+    
+    /// Sorts in a given direction
+    pub fn ordered(mut self, order: SortOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+    
+    /// Gets the number of items to return, `limit` brought within bounds
+    pub fn page_limit(&self) -> usize {
+        self.limit.clamp(1, MAX_PAGE_LIMIT)
+    }
+    
+    /// Gets the direction to sort in, `default` if the request doesn't say
+    pub fn order_or(&self, default: SortOrder) -> SortOrder {
+        self.order.unwrap_or(default)
+    }
+    
+    /// Gets the sort key the page starts after, if it isn't the first
+    pub fn start_after<K: DeserializeOwned>(&self, listing: &str) -> Result<Option<K>> {
+        self.cursor.as_ref().map(|cursor| cursor.key(listing)).transpose()
+    }
+}
+
+/// A page of a listing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// Items of the page, in the listing's order
+    pub items: Vec<T>,
+    
+    /// Cursor to request the next page with; `None` on the last page
+    pub next_cursor: Option<Cursor>,
+    
+    /// Number of items in the whole listing, if known
+    pub total_estimate: Option<u64>,
+}
+
+impl<T> Page<T> {
+    /// Takes a page from sorted items with their keys, those after the request's cursor
+    ///
+    /// The page holds up to `limit` items and has a cursor if any item follows them.
+    pub fn from_sorted<K: Serialize>(
+        entries: impl IntoIterator<Item = (K, T)>,
+        listing: &str,
+        limit: usize,
+        total_estimate: Option<u64>,
+    ) -> Self {
+        let mut entries = entries.into_iter().peekable();
+        let mut items = Vec::new();
+        let mut last_key = None;
+        while items.len() < limit {
+            let Some((key, item)) = entries.next() else {
+                break;
+            };
+            items.push(item);
+            last_key = Some(key);
+        }
+        let next_cursor = match (last_key, entries.peek()) {
+            (Some(key), Some(_)) => Some(Cursor::new(listing, &key)),
+            _ => None,
+        };
+        Self { items, next_cursor, total_estimate }
+    }
+    
+    /// Converts the page's items, keeping its cursor
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total_estimate: self.total_estimate,
+        }
+    }
+}
+
+/// Gets a page of a listing held in memory, sorted by the keys `key` gives
+///
+/// Keys must be unique within the listing. `default_order` applies when
+/// the request doesn't give one.
+pub fn paginate<T, K>(
+    items: impl IntoIterator<Item = T>,
+    listing: &str,
+    request: &PageRequest,
+    default_order: SortOrder,
+    key: impl Fn(&T) -> K,
+) -> Result<Page<T>>
+where
+    K: Ord + Serialize + DeserializeOwned,
+{
+    let order = request.order_or(default_order);
+    let start_after: Option<K> = request.start_after(listing)?;
+    
+    let mut entries: Vec<(K, T)> = items.into_iter().map(|item| (key(&item), item)).collect();
+    let total = entries.len() as u64;
+    entries.sort_by(|(a, _), (b, _)| match order {
+        SortOrder::Ascending => a.cmp(b),
+        SortOrder::Descending => b.cmp(a),
+    });
+    let entries = entries.into_iter().filter(|(key, _)| match (&start_after, order) {
+        (None, _) => true,
+        (Some(after), SortOrder::Ascending) => key > after,
+        (Some(after), SortOrder::Descending) => key < after,
+    });
+    Ok(Page::from_sorted(entries, listing, request.page_limit(), Some(total)))
+}
+
+/// Gets every item of a listing, a page of up to `limit` at a time
+///
+/// For callers that need a whole listing, such as exports, going through
+/// the same pages a client would.
+pub fn collect_pages<T, E>(limit: usize, mut fetch: impl FnMut(&PageRequest) -> std::result::Result<Page<T>, E>) -> std::result::Result<Vec<T>, E> {
+    let mut items = Vec::new();
+    let mut request = PageRequest::new(limit);
+    loop {
+        let page = fetch(&request)?;
+        items.extend(page.items);
+        match page.next_cursor {
+            Some(cursor) => request.cursor = Some(cursor),
+            None => return Ok(items),
+        }
+    }
+}
\ No newline at end of file
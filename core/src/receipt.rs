@@ -0,0 +1,135 @@
+//! Transaction receipts for the Crypto Trust Bank blockchain
+//!
+//! This module defines the Receipt structure recording the outcome of
+//! each transaction once it has been applied in a block, along with the
+//! event logs emitted by contracts and the filters used to query them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::memo::MemoField;
+use crate::{Hash, TxHash};
+
+/// Outcome of a transaction included in a block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Receipt {
+    /// ID of the transaction this receipt belongs to
+    pub tx_id: TxHash,
+    
+    /// Height of the block the transaction was included in
+    pub block_height: u64,
+    
+    /// Whether the transaction executed successfully
+    pub success: bool,
+    
+    /// Gas consumed by the transaction
+    pub gas_used: u64,
+    
+    /// Gas consumed by this and all earlier transactions in the block
+    pub cumulative_gas_used: u64,
+    
+    /// Address of the contract created by a deployment or a call to the token factory
+    pub contract_address: Option<String>,
+    
+    /// Event logs emitted by the transaction; empty if it failed
+    pub logs: Vec<Log>,
+    
+    /// Reason given by a reverted contract execution
+    pub revert_reason: Option<String>,
+    
+    /// Contracts destroyed by the transaction; their code and storage are gone
+    #[serde(default)]
+    pub destroyed_contracts: Vec<String>,
+    
+    /// Memo of the transaction, for readers of its receipt alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<MemoField>,
+}
+
+impl Receipt {
+    /// Creates a receipt for a transaction that involved no contract execution
+    pub fn new(tx_id: TxHash, block_height: u64) -> Self {
+        Self {
+            tx_id,
+            block_height,
+            success: true,
+            gas_used: 0,
+            cumulative_gas_used: 0,
+            contract_address: None,
+            logs: Vec::new(),
+            revert_reason: None,
+            destroyed_contracts: Vec::new(),
+            memo: None,
+        }
+    }
+}
+
+/// Event emitted by a contract during execution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Log {
+    /// Address of the contract that emitted the event
+    pub address: String,
+    
+    /// Indexed topics; for Solidity events the first is the event signature hash
+    #[serde(with = "crate::types::hex_serde::seq")]
+    pub topics: Vec<Hash>,
+    
+    /// Non-indexed event data
+    #[serde(with = "crate::types::hex_serde")]
+    pub data: Vec<u8>,
+}
+
+/// Log together with the transaction and block it was emitted in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedLog {
+    /// Height of the block containing the transaction
+    pub block_height: u64,
+    
+    /// ID of the transaction that emitted the log
+    pub tx_id: TxHash,
+    
+    /// The log itself
+    pub log: Log,
+}
+
+/// Criteria for querying logs
+///
+/// Unset fields match anything. `topics[i]` constrains the log's i-th
+/// topic; `None` entries act as wildcards.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogFilter {
+    /// First block to search (inclusive)
+    pub from_block: Option<u64>,
+    
+    /// Last block to search (inclusive)
+    pub to_block: Option<u64>,
+    
+    /// Only match logs emitted by this contract
+    pub address: Option<String>,
+    
+    /// Topics to match by position
+    #[serde(with = "crate::types::hex_serde::option_seq")]
+    pub topics: Vec<Option<Hash>>,
+}
+
+impl LogFilter {
+    /// Checks whether a log matches the address and topic criteria
+    pub fn matches(&self, log: &Log) -> bool {
+        if let Some(address) = &self.address {
+            if *address != log.address {
+                return false;
+            }
+        }
+        
+        self.topics.iter().enumerate().all(|(i, topic)| match topic {
+            Some(topic) => log.topics.get(i) == Some(topic),
+            None => true,
+        })
+    }
+    
+    /// Checks whether a log emitted in a block matches every criterion, including the block range
+    pub fn matches_indexed(&self, entry: &IndexedLog) -> bool {
+        self.from_block.is_none_or(|from| entry.block_height >= from)
+            && self.to_block.is_none_or(|to| entry.block_height <= to)
+            && self.matches(&entry.log)
+    }
+}
\ No newline at end of file
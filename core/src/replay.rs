@@ -0,0 +1,378 @@
+//! Verifying a node's saved blocks offline
+//!
+//! `verify_data_dir` replays the block store of a data directory (see
+//! `block_store`) from a fresh state, one block at a time, applying the
+//! rules `Blockchain::add_block` does: each block must extend the one
+//! before it, match its merkle root, carry valid transactions and the base
+//! fee the fee market sets, pay the reward the schedule and the validator
+//! registry say, and apply within the block gas limit. The root of the
+//! resulting state is then checked against the one the tip records.
+//!
+//! Blocks are read as they're replayed and dropped once applied, so only
+//! the state is held in memory. Blocks below the range being verified are
+//! still replayed to rebuild the state the range starts from, but their
+//! transactions aren't validated and they aren't counted as checked.
+//! Replay stops at the first block that breaks a rule, which the report
+//! names with the kind of failure.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::block_store::{BlockStore, StoredTip, BLOCKS_DIR};
+use crate::executor::ContractExecutor;
+use crate::fee_market;
+use crate::rewards::RewardSchedule;
+use crate::signature::SignatureScheme;
+use crate::state::State;
+use crate::state_sync;
+use crate::{BlockHash, BlockchainError, Hash, Result};
+
+/// What to verify and under which parameters
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// First height checked in full
+    pub from: u64,
+    
+    /// Last height checked, the tip's if not given
+    pub to: Option<u64>,
+    
+    /// Block gas limit the chain ran with
+    pub block_gas_limit: u64,
+    
+    /// Hash the genesis block must have, if known
+    pub genesis_hash: Option<BlockHash>,
+    
+    /// Engine contract transactions are executed again through, if any
+    ///
+    /// Without one only the balance effects of contract transactions are
+    /// applied, so the state root won't match a chain that ran contracts.
+    pub executor: Option<Arc<Mutex<dyn ContractExecutor>>>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            from: 0,
+            to: None,
+            block_gas_limit: crate::genesis::get_block_gas_limit(),
+            genesis_hash: None,
+            executor: None,
+        }
+    }
+}
+
+/// Rule a stored block breaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// No block is stored at the height
+    MissingBlock,
+    
+    /// The stored block can't be decoded
+    CorruptBlock,
+    
+    /// The block has another height or doesn't extend the block before it
+    Linkage,
+    
+    /// The genesis block isn't the one expected
+    Genesis,
+    
+    /// The block's transactions don't match its merkle root
+    MerkleRoot,
+    
+    /// A transaction is malformed or doesn't match its ID
+    Transaction,
+    
+    /// A transaction's signature doesn't verify
+    Signature,
+    
+    /// The block doesn't carry the base fee the fee market sets
+    BaseFee,
+    
+    /// The block mints more than its reward or pays it elsewhere
+    Reward,
+    
+    /// The block's transactions don't apply, or use more than the block gas limit
+    Execution,
+    
+    /// The tip names another block than the one stored at its height
+    TipHash,
+    
+    /// The replayed state doesn't have the root the tip records
+    StateRoot,
+}
+
+impl FailureKind {
+    /// Gets the kind's name, as serialized
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::MissingBlock => "missing_block",
+            FailureKind::CorruptBlock => "corrupt_block",
+            FailureKind::Linkage => "linkage",
+            FailureKind::Genesis => "genesis",
+            FailureKind::MerkleRoot => "merkle_root",
+            FailureKind::Transaction => "transaction",
+            FailureKind::Signature => "signature",
+            FailureKind::BaseFee => "base_fee",
+            FailureKind::Reward => "reward",
+            FailureKind::Execution => "execution",
+            FailureKind::TipHash => "tip_hash",
+            FailureKind::StateRoot => "state_root",
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// First block found breaking a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// Height of the block
+    pub height: u64,
+    
+    /// Rule the block breaks
+    pub kind: FailureKind,
+    
+    /// What was wrong
+    pub message: String,
+}
+
+impl Divergence {
+    fn new(height: u64, kind: FailureKind, message: impl fmt::Display) -> Self {
+        Self { height, kind, message: message.to_string() }
+    }
+}
+
+/// Outcome of verifying a data directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    /// First height checked in full
+    pub from: u64,
+    
+    /// Last height checked
+    pub to: u64,
+    
+    /// Tip the store records
+    pub tip: StoredTip,
+    
+    /// Blocks replayed, those below `from` included
+    pub blocks_replayed: u64,
+    
+    /// Blocks checked in full
+    pub blocks_checked: u64,
+    
+    /// Transactions validated in the blocks checked
+    pub transactions_verified: u64,
+    
+    /// Signatures verified in the blocks checked
+    pub signatures_verified: u64,
+    
+    /// Gas used by the blocks checked
+    pub gas_used: u64,
+    
+    /// Root of the state after the last block replayed
+    #[serde(with = "crate::types::hex_serde")]
+    pub state_root: Hash,
+    
+    /// First block breaking a rule, if any
+    pub divergence: Option<Divergence>,
+}
+
+impl VerificationReport {
+    /// Checks whether every block verified
+    pub fn is_valid(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Latest block replayed
+struct Parent {
+    hash: BlockHash,
+    base_fee: u64,
+    gas_used: u64,
+}
+
+/// Replays the block store of a data directory, verifying every block in `options`' range
+///
+/// Fails if the store or its tip can't be read, or the range is empty;
+/// blocks breaking the rules are reported rather than failing.
+pub fn verify_data_dir(data_dir: &Path, options: &VerifyOptions) -> Result<VerificationReport> {
+    let store = BlockStore::open_existing(data_dir.join(BLOCKS_DIR))?;
+    let tip = store.tip()?.ok_or_else(|| {
+        BlockchainError::StateError(format!("No tip recorded in {}", store.dir().display()))
+    })?;
+    
+    let to = options.to.map_or(tip.height, |to| to.min(tip.height));
+    if options.from > to {
+        return Err(BlockchainError::StateError(format!(
+            "Nothing to verify from height {} to {}, the tip is at {}",
+            options.from, to, tip.height
+        )));
+    }
+    
+    let mut replay = Replay {
+        store,
+        options,
+        rewards: crate::genesis::reward_schedule()?,
+        state: State::new(),
+        parent: None,
+        report: VerificationReport {
+            from: options.from,
+            to,
+            tip,
+            blocks_replayed: 0,
+            blocks_checked: 0,
+            transactions_verified: 0,
+            signatures_verified: 0,
+            gas_used: 0,
+            state_root: [0; 32],
+            divergence: None,
+        },
+    };
+    
+    let replayed = (0..=to).try_for_each(|height| replay.block(height));
+    replay.report.state_root = state_sync::state_root(&replay.state);
+    replay.report.divergence = replayed.and_then(|()| replay.check_tip()).err();
+    Ok(replay.report)
+}
+
+/// State of a replay in progress
+struct Replay<'a> {
+    store: BlockStore,
+    options: &'a VerifyOptions,
+    rewards: RewardSchedule,
+    state: State,
+    parent: Option<Parent>,
+    report: VerificationReport,
+}
+
+impl Replay<'_> {
+    /// Reads, checks and applies the block at a height
+    fn block(&mut self, height: u64) -> std::result::Result<(), Divergence> {
+        let checked = height >= self.options.from;
+        let block = match self.store.get_block(height) {
+            Ok(Some(block)) => block,
+            Ok(None) => return Err(Divergence::new(height, FailureKind::MissingBlock, "No block stored")),
+            Err(e) => return Err(Divergence::new(height, FailureKind::CorruptBlock, e)),
+        };
+        let header = block.header();
+        let hash = block.hash().map_err(|e| Divergence::new(height, FailureKind::CorruptBlock, e))?;
+        
+        if header.height != height {
+            return Err(Divergence::new(height, FailureKind::Linkage, format!("Block is for height {}", header.height)));
+        }
+        let expected_prev = self.parent.as_ref().map_or(BlockHash::default(), |parent| parent.hash);
+        if height > 0 && header.prev_hash != expected_prev {
+            return Err(Divergence::new(
+                height,
+                FailureKind::Linkage,
+                format!("Previous hash {} doesn't match block {} hash {}", header.prev_hash, height - 1, expected_prev),
+            ));
+        }
+        if let Some(genesis_hash) = self.options.genesis_hash.filter(|_| height == 0) {
+            if hash != genesis_hash {
+                return Err(Divergence::new(height, FailureKind::Genesis, format!("Hash {}, expected {}", hash, genesis_hash)));
+            }
+        }
+        
+        let merkle_root = Block::calculate_merkle_root(&block.transactions).map_err(|e| Divergence::new(height, FailureKind::MerkleRoot, e))?;
+        if merkle_root != header.merkle_root {
+            return Err(Divergence::new(height, FailureKind::MerkleRoot, "Transactions don't match the merkle root"));
+        }
+        if checked {
+            self.check_transactions(&block)?;
+        }
+        
+        // The genesis block follows no fee market or reward rules
+        if let Some(parent) = &self.parent {
+            let base_fee = fee_market::next_base_fee(parent.base_fee, parent.gas_used, self.options.block_gas_limit);
+            if header.base_fee != base_fee {
+                return Err(Divergence::new(
+                    height,
+                    FailureKind::BaseFee,
+                    format!("Base fee {}, expected {}", header.base_fee, base_fee),
+                ));
+            }
+            
+            let payout_address = self.state.get_payout_address(&header.validator, height);
+            self.rewards.check_block(&block, &payout_address).map_err(|e| Divergence::new(height, FailureKind::Reward, e))?;
+        }
+        
+        let receipts = match &self.options.executor {
+            Some(executor) => {
+                let mut executor = executor.lock().unwrap();
+                self.state.apply_block_with_executor(&block, Some(&mut *executor))
+            }
+            None => self.state.apply_block_with_executor(&block, None),
+        };
+        let receipts = receipts.map_err(|e| Divergence::new(height, FailureKind::Execution, e))?;
+        let gas_used = receipts.last().map_or(0, |receipt| receipt.cumulative_gas_used);
+        if height > 0 && gas_used > self.options.block_gas_limit {
+            return Err(Divergence::new(
+                height,
+                FailureKind::Execution,
+                format!("Block gas used {} exceeds the limit {}", gas_used, self.options.block_gas_limit),
+            ));
+        }
+        
+        self.report.blocks_replayed += 1;
+        if checked {
+            self.report.blocks_checked += 1;
+            self.report.gas_used += gas_used;
+        }
+        self.parent = Some(Parent { hash, base_fee: header.base_fee, gas_used });
+        Ok(())
+    }
+    
+    /// Validates every transaction of a block, telling bad signatures from other faults
+    fn check_transactions(&mut self, block: &Block) -> std::result::Result<(), Divergence> {
+        let height = block.header().height;
+        for tx in &block.transactions {
+            if let Err(e) = tx.validate() {
+                let kind = if tx.verify_signature().is_err() { FailureKind::Signature } else { FailureKind::Transaction };
+                return Err(Divergence::new(height, kind, format!("Transaction {}: {}", tx.id, e)));
+            }
+            
+            self.report.transactions_verified += 1;
+            if tx.eth_raw.is_some() || SignatureScheme::from_address(&tx.sender).is_some() {
+                self.report.signatures_verified += 1;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Checks the last block replayed and the state root against the tip, if the range reaches it
+    fn check_tip(&self) -> std::result::Result<(), Divergence> {
+        let tip = &self.report.tip;
+        if self.report.to != tip.height {
+            return Ok(());
+        }
+        
+        let hash = self.parent.as_ref().map_or(BlockHash::default(), |parent| parent.hash);
+        if hash != tip.block_hash {
+            return Err(Divergence::new(
+                tip.height,
+                FailureKind::TipHash,
+                format!("Tip names block {}, stored block is {}", tip.block_hash, hash),
+            ));
+        }
+        
+        let state_root = self.report.state_root;
+        if state_root != tip.state_root {
+            return Err(Divergence::new(
+                tip.height,
+                FailureKind::StateRoot,
+                format!("State root 0x{}, tip records 0x{}", hex::encode(state_root), hex::encode(tip.state_root)),
+            ));
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
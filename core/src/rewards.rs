@@ -0,0 +1,169 @@
+//! Block rewards and the treasury's share of them
+//!
+//! Every block after genesis may mint `block_reward` of its height in
+//! coinbase transactions at the start of the block: the first pays the
+//! block's validator, at its payout address (see `validator`), and the
+//! second the treasury. The treasury's share is a
+//! number of basis points of the reward, rounded down, and the validator
+//! gets the rest, so the two always add up to the reward. A share that comes
+//! to zero gets no coinbase transaction. A block may leave out its coinbase
+//! transactions altogether, forfeiting the reward, but never just the
+//! treasury's.
+//!
+//! The treasury's address and share are consensus rules. They're defined
+//! with the genesis block (see `genesis::reward_schedule`) and change only at
+//! the activation heights listed there, so every node expects the same
+//! split for every block.
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::transaction::Transaction;
+use crate::units::GENX;
+use crate::{BlockchainError, Result};
+
+/// Reward of the first block, 50 GENX
+pub const INITIAL_BLOCK_REWARD: u64 = 50 * GENX;
+
+/// Number of blocks after which the reward halves
+pub const HALVING_INTERVAL: u64 = 210_000;
+
+/// Basis points in a whole reward
+pub const BASIS_POINTS: u32 = 10_000;
+
+/// Sender of coinbase transactions
+pub(crate) const COINBASE: &str = "COINBASE";
+
+/// Gets the reward minted by the block at a height
+pub fn block_reward(height: u64) -> u64 {
+    let halvings = height / HALVING_INTERVAL;
+    if halvings >= 64 {
+        return 0;
+    }
+    INITIAL_BLOCK_REWARD >> halvings
+}
+
+/// The treasury's share of block rewards from an activation height on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreasuryRule {
+    /// Height of the first block the rule applies to
+    pub activation_height: u64,
+    
+    /// Account receiving the treasury's share
+    pub address: String,
+    
+    /// Treasury's share of each block reward, in basis points
+    pub share_bps: u32,
+}
+
+/// How the reward of a block is split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewardSplit {
+    /// Amount paid to the block's validator
+    pub validator: u64,
+    
+    /// Amount paid to the treasury
+    pub treasury: u64,
+}
+
+/// The treasury rules of a chain, by activation height
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardSchedule {
+    /// Rules in order of activation, the first active from genesis
+    rules: Vec<TreasuryRule>,
+}
+
+impl RewardSchedule {
+    /// Creates a schedule from rules in order of activation
+    ///
+    /// The first rule must activate at height 0 and each later one at a
+    /// greater height than the one before. Shares can't exceed
+    /// `BASIS_POINTS`.
+    pub fn new(rules: Vec<TreasuryRule>) -> Result<Self> {
+        if rules.first().map(|rule| rule.activation_height) != Some(0) {
+            return Err(BlockchainError::StateError(
+                "The first treasury rule must activate at genesis".to_string()
+            ));
+        }
+        if rules.windows(2).any(|pair| pair[1].activation_height <= pair[0].activation_height) {
+            return Err(BlockchainError::StateError(
+                "Treasury rules must activate at increasing heights".to_string()
+            ));
+        }
+        if let Some(rule) = rules.iter().find(|rule| rule.share_bps > BASIS_POINTS || rule.address.is_empty()) {
+            return Err(BlockchainError::StateError(format!(
+                "Invalid treasury rule at height {}: share {} bps to {:?}",
+                rule.activation_height, rule.share_bps, rule.address
+            )));
+        }
+        
+        Ok(Self { rules })
+    }
+    
+    /// Gets the rules in order of activation
+    pub fn rules(&self) -> &[TreasuryRule] {
+        &self.rules
+    }
+    
+    /// Gets the rule that applies to the block at a height
+    pub fn rule_at(&self, height: u64) -> &TreasuryRule {
+        let active = self.rules.partition_point(|rule| rule.activation_height <= height);
+        &self.rules[active - 1]
+    }
+    
+    /// Splits the reward of the block at a height
+    pub fn split(&self, height: u64) -> RewardSplit {
+        let reward = block_reward(height);
+        let share = self.rule_at(height).share_bps as u128;
+        let treasury = (reward as u128 * share / BASIS_POINTS as u128) as u64;
+        RewardSplit { validator: reward - treasury, treasury }
+    }
+    
+    /// Creates the coinbase transactions of the block at a height, paying the validator's share to `payout_address`
+    pub fn coinbase_transactions(&self, height: u64, payout_address: &str) -> Result<Vec<Transaction>> {
+        let split = self.split(height);
+        let payments = [(payout_address, split.validator), (self.rule_at(height).address.as_str(), split.treasury)];
+        
+        payments
+            .into_iter()
+            .filter(|&(_, amount)| amount > 0)
+            .map(|(recipient, amount)| Transaction::new_coinbase(recipient.to_string(), amount))
+            .collect()
+    }
+    
+    /// Checks that a block after genesis mints its reward as the rules require
+    ///
+    /// `payout_address` is where the block's validator is to be paid, see
+    /// `State::get_payout_address`.
+    pub fn check_block(&self, block: &Block, payout_address: &str) -> Result<()> {
+        let coinbase_count = block.transactions.iter().take_while(|tx| tx.sender == COINBASE).count();
+        let (coinbases, rest) = block.transactions.split_at(coinbase_count);
+        if rest.iter().any(|tx| tx.sender == COINBASE) {
+            return Err(BlockchainError::InvalidBlock(
+                "Coinbase transactions must come first in a block".to_string()
+            ));
+        }
+        if coinbases.is_empty() {
+            return Ok(());
+        }
+        
+        let header = block.header();
+        let expected = self.coinbase_transactions(header.height, payout_address)?;
+        let matches = coinbases.len() == expected.len()
+            && coinbases.iter().zip(&expected).all(|(tx, expected)| {
+                tx.recipient == expected.recipient && tx.amount == expected.amount && tx.fee == 0
+            });
+        if !matches {
+            let describe = |txs: &[Transaction]| {
+                txs.iter().map(|tx| format!("{} to {}", tx.amount, tx.recipient)).collect::<Vec<_>>().join(", ")
+            };
+            return Err(BlockchainError::InvalidBlock(format!(
+                "Coinbase pays {}, expected {}",
+                describe(coinbases),
+                describe(&expected)
+            )));
+        }
+        
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,260 @@
+//! Recursive Length Prefix encoding
+//!
+//! RLP is Ethereum's serialization for transactions and blocks. It encodes
+//! two kinds of item: byte strings and lists of items. Integers are byte
+//! strings holding the big-endian value without leading zeros, so zero is
+//! the empty string.
+//!
+//! Decoding accepts canonical encodings only, the one encoding `encode`
+//! produces for each item, so an item decodes and re-encodes to the same
+//! bytes. Malformed input is an error, never a panic, and lists may nest
+//! at most `MAX_DEPTH` deep. A list's items are counted before space is
+//! allocated for them, so decoding never holds more than the items take.
+
+use thiserror::Error;
+
+/// Deepest nesting of lists accepted when decoding
+pub const MAX_DEPTH: usize = 64;
+
+/// Error decoding RLP
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RlpError {
+    #[error("Input ends inside an item")]
+    UnexpectedEnd,
+    
+    #[error("{0} bytes follow the item")]
+    TrailingBytes(usize),
+    
+    #[error("Non-canonical encoding: {0}")]
+    NonCanonical(&'static str),
+    
+    #[error("Item length overflows")]
+    Overflow,
+    
+    #[error("Lists nest deeper than {} levels", MAX_DEPTH)]
+    TooDeep,
+    
+    #[error("Expected a byte string, got a list")]
+    ExpectedBytes,
+    
+    #[error("Expected a list, got a byte string")]
+    ExpectedList,
+    
+    #[error("Integer of {0} bytes is too large")]
+    IntegerTooLarge(usize),
+    
+    #[error("Integer has leading zero bytes")]
+    LeadingZeros,
+}
+
+/// Item of the RLP data model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RlpItem {
+    /// Byte string
+    Bytes(Vec<u8>),
+    
+    /// List of items
+    List(Vec<RlpItem>),
+}
+
+impl RlpItem {
+    /// Creates the item encoding an unsigned integer
+    pub fn uint(value: u128) -> Self {
+        let bytes = value.to_be_bytes();
+        let start = bytes.iter().position(|&byte| byte != 0).unwrap_or(bytes.len());
+        RlpItem::Bytes(bytes[start..].to_vec())
+    }
+    
+    /// Gets the bytes of a byte string
+    pub fn as_bytes(&self) -> Result<&[u8], RlpError> {
+        match self {
+            RlpItem::Bytes(bytes) => Ok(bytes),
+            RlpItem::List(_) => Err(RlpError::ExpectedBytes),
+        }
+    }
+    
+    /// Gets the items of a list
+    pub fn as_list(&self) -> Result<&[RlpItem], RlpError> {
+        match self {
+            RlpItem::List(items) => Ok(items),
+            RlpItem::Bytes(_) => Err(RlpError::ExpectedList),
+        }
+    }
+    
+    /// Reads a byte string as an unsigned integer
+    pub fn as_uint(&self) -> Result<u128, RlpError> {
+        let bytes = self.as_bytes()?;
+        if bytes.len() > 16 {
+            return Err(RlpError::IntegerTooLarge(bytes.len()));
+        }
+        if bytes.first() == Some(&0) {
+            return Err(RlpError::LeadingZeros);
+        }
+        
+        let mut value = [0u8; 16];
+        value[16 - bytes.len()..].copy_from_slice(bytes);
+        Ok(u128::from_be_bytes(value))
+    }
+    
+    /// Reads a byte string as an unsigned integer that fits in a `u64`
+    pub fn as_u64(&self) -> Result<u64, RlpError> {
+        let value = self.as_uint()?;
+        u64::try_from(value).map_err(|_| RlpError::IntegerTooLarge(self.as_bytes().map_or(0, <[u8]>::len)))
+    }
+    
+    /// Reads a byte string as a 32-byte big-endian word, left-padding it
+    pub fn as_word(&self) -> Result<[u8; 32], RlpError> {
+        let bytes = self.as_bytes()?;
+        if bytes.len() > 32 {
+            return Err(RlpError::IntegerTooLarge(bytes.len()));
+        }
+        if bytes.first() == Some(&0) {
+            return Err(RlpError::LeadingZeros);
+        }
+        
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(bytes);
+        Ok(word)
+    }
+}
+
+impl From<Vec<u8>> for RlpItem {
+    fn from(bytes: Vec<u8>) -> Self {
+        RlpItem::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for RlpItem {
+    fn from(bytes: &[u8]) -> Self {
+        RlpItem::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Vec<RlpItem>> for RlpItem {
+    fn from(items: Vec<RlpItem>) -> Self {
+        RlpItem::List(items)
+    }
+}
+
+/// Encodes an item
+pub fn encode(item: &RlpItem) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_into(item, &mut out);
+    out
+}
+
+fn encode_into(item: &RlpItem, out: &mut Vec<u8>) {
+    match item {
+        RlpItem::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => out.push(bytes[0]),
+        RlpItem::Bytes(bytes) => {
+            encode_length(bytes.len(), 0x80, out);
+            out.extend_from_slice(bytes);
+        }
+        RlpItem::List(items) => {
+            let mut payload = Vec::new();
+            for item in items {
+                encode_into(item, &mut payload);
+            }
+            encode_length(payload.len(), 0xc0, out);
+            out.extend_from_slice(&payload);
+        }
+    }
+}
+
+/// Writes the prefix of a payload, short form up to 55 bytes and long form beyond
+fn encode_length(len: usize, offset: u8, out: &mut Vec<u8>) {
+    if len <= 55 {
+        out.push(offset + len as u8);
+    } else {
+        let bytes = (len as u64).to_be_bytes();
+        let start = bytes.iter().position(|&byte| byte != 0).unwrap_or(bytes.len() - 1);
+        out.push(offset + 55 + (bytes.len() - start) as u8);
+        out.extend_from_slice(&bytes[start..]);
+    }
+}
+
+/// Decodes input holding exactly one item
+pub fn decode(data: &[u8]) -> Result<RlpItem, RlpError> {
+    let (item, rest) = decode_item(data)?;
+    if !rest.is_empty() {
+        return Err(RlpError::TrailingBytes(rest.len()));
+    }
+    Ok(item)
+}
+
+/// Decodes the item at the start of the input, returning it and the bytes after it
+pub fn decode_item(data: &[u8]) -> Result<(RlpItem, &[u8]), RlpError> {
+    decode_nested(data, 0)
+}
+
+fn decode_nested(data: &[u8], depth: usize) -> Result<(RlpItem, &[u8]), RlpError> {
+    let (&prefix, rest) = data.split_first().ok_or(RlpError::UnexpectedEnd)?;
+    
+    match prefix {
+        0x00..=0x7f => Ok((RlpItem::Bytes(vec![prefix]), rest)),
+        0x80..=0xbf => {
+            let (payload, rest) = split_payload(prefix, 0x80, rest)?;
+            if payload.len() == 1 && payload[0] < 0x80 {
+                return Err(RlpError::NonCanonical("single byte below 0x80 encoded as a string"));
+            }
+            Ok((RlpItem::Bytes(payload.to_vec()), rest))
+        }
+        0xc0..=0xff => {
+            if depth == MAX_DEPTH {
+                return Err(RlpError::TooDeep);
+            }
+            let (mut payload, rest) = split_payload(prefix, 0xc0, rest)?;
+            let mut items = Vec::with_capacity(count_items(payload)?);
+            while !payload.is_empty() {
+                let (item, remaining) = decode_nested(payload, depth + 1)?;
+                items.push(item);
+                payload = remaining;
+            }
+            Ok((RlpItem::List(items), rest))
+        }
+    }
+}
+
+/// Counts the items in the payload of a list, checking their lengths against it
+fn count_items(mut payload: &[u8]) -> Result<usize, RlpError> {
+    let mut count = 0;
+    while let Some((&prefix, rest)) = payload.split_first() {
+        payload = match prefix {
+            0x00..=0x7f => rest,
+            0x80..=0xbf => split_payload(prefix, 0x80, rest)?.1,
+            0xc0..=0xff => split_payload(prefix, 0xc0, rest)?.1,
+        };
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Splits off the payload whose length a prefix gives
+fn split_payload(prefix: u8, offset: u8, data: &[u8]) -> Result<(&[u8], &[u8]), RlpError> {
+    let short = prefix - offset;
+    let (len, data) = if short <= 55 {
+        (short as usize, data)
+    } else {
+        let len_bytes = (short - 55) as usize;
+        if data.len() < len_bytes {
+            return Err(RlpError::UnexpectedEnd);
+        }
+        let (len, data) = data.split_at(len_bytes);
+        if len[0] == 0 {
+            return Err(RlpError::NonCanonical("length has leading zero bytes"));
+        }
+        if len_bytes > std::mem::size_of::<usize>() {
+            return Err(RlpError::Overflow);
+        }
+        let len = len.iter().fold(0usize, |acc, &byte| (acc << 8) | byte as usize);
+        if len <= 55 {
+            return Err(RlpError::NonCanonical("long form used for a payload of at most 55 bytes"));
+        }
+        (len, data)
+    };
+    
+    if data.len() < len {
+        return Err(RlpError::UnexpectedEnd);
+    }
+    Ok(data.split_at(len))
+}
\ No newline at end of file
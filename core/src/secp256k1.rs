@@ -0,0 +1,175 @@
+//! secp256k1 ECDSA signatures
+//!
+//! Keys and signatures of the curve used by Bitcoin and Ethereum, on top of
+//! the `k256` crate: key generation, deterministic signing (RFC 6979 with
+//! HMAC-SHA256), verification and public key recovery. Messages are 32-byte
+//! hashes; hashing is left to the caller.
+//!
+//! Signatures are 65 bytes: `r`, `s` and the recovery ID. Signing always
+//! produces the lower of the two valid `s` values and verification rejects
+//! the higher one, so a signature can't be altered into another valid one.
+//! Recovery accepts either, like Ethereum's `ecrecover`.
+
+use k256::ecdsa::signature::hazmat::PrehashVerifier;
+use k256::ecdsa::{self, RecoveryId, SigningKey, VerifyingKey};
+use rand::{CryptoRng, RngCore};
+
+use crate::hashing;
+use crate::signature::SignatureError;
+
+/// Secret key: a scalar in `1..N`
+#[derive(Clone)]
+pub struct SecretKey(SigningKey);
+
+impl SecretKey {
+    /// Parses a 32-byte big-endian secret key
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        if bytes.len() != 32 {
+            return Err(SignatureError::InvalidSecretKey);
+        }
+        SigningKey::from_slice(bytes).map(Self).map_err(|_| SignatureError::InvalidSecretKey)
+    }
+    
+    /// Generates a random secret key
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        Self(SigningKey::random(rng))
+    }
+    
+    /// Gets the 32-byte big-endian encoding of the key
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+    
+    /// Derives the public key
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(*self.0.verifying_key())
+    }
+    
+    /// Signs a 32-byte message hash
+    pub fn sign(&self, hash: &[u8; 32]) -> Signature {
+        // Signing a 32-byte hash with a valid key can't fail, and k256 normalizes `s`
+        let (signature, recovery_id) = self.0.sign_prehash_recoverable(hash).expect("a 32-byte hash can always be signed");
+        let (r, s) = signature.split_bytes();
+        Signature { r: r.into(), s: s.into(), recovery_id: recovery_id.to_byte() }
+    }
+}
+
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+/// Public key: a point on the curve other than infinity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey(VerifyingKey);
+
+impl PublicKey {
+    /// Parses a 33-byte compressed or 65-byte uncompressed key
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        match (bytes.len(), bytes.first()) {
+            (33, Some(2 | 3)) | (65, Some(4)) => VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self)
+                .map_err(|_| SignatureError::InvalidPublicKey("not a secp256k1 point".to_string())),
+            _ => Err(SignatureError::InvalidPublicKey(format!(
+                "expected a 33-byte compressed or 65-byte uncompressed secp256k1 key, got {} bytes",
+                bytes.len()
+            ))),
+        }
+    }
+    
+    /// Gets the 33-byte compressed encoding: parity of y, then x
+    pub fn to_compressed(&self) -> [u8; 33] {
+        self.0.to_encoded_point(true).as_bytes().try_into().expect("a compressed point is 33 bytes")
+    }
+    
+    /// Gets the 65-byte uncompressed encoding: `0x04`, x, then y
+    pub fn to_uncompressed(&self) -> [u8; 65] {
+        self.0.to_encoded_point(false).as_bytes().try_into().expect("an uncompressed point is 65 bytes")
+    }
+    
+    /// Gets the Ethereum address of the key: the last 20 bytes of the Keccak-256 hash of x and y
+    pub fn evm_address(&self) -> [u8; 20] {
+        let hash = hashing::keccak256(&self.to_uncompressed()[1..]);
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hash[12..]);
+        address
+    }
+    
+    /// Verifies a signature of a 32-byte message hash
+    pub fn verify(&self, hash: &[u8; 32], signature: &Signature) -> bool {
+        // k256 refuses the high `s` itself
+        match signature.to_ecdsa() {
+            Some(signature) => self.0.verify_prehash(hash, &signature).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Recoverable ECDSA signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    r: [u8; 32],
+    s: [u8; 32],
+    recovery_id: u8,
+}
+
+impl Signature {
+    /// Builds a signature from its parts; the recovery ID must be 0 to 3
+    pub fn from_parts(r: [u8; 32], s: [u8; 32], recovery_id: u8) -> Result<Self, SignatureError> {
+        if recovery_id > 3 {
+            return Err(SignatureError::MalformedSignature(format!("invalid recovery ID {}", recovery_id)));
+        }
+        Ok(Self { r, s, recovery_id })
+    }
+    
+    /// Parses the 65-byte encoding: `r`, `s`, then the recovery ID
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        if bytes.len() != 65 {
+            return Err(SignatureError::MalformedSignature(format!(
+                "expected 65 bytes, got {}",
+                bytes.len()
+            )));
+        }
+        Self::from_parts(bytes[..32].try_into().unwrap(), bytes[32..64].try_into().unwrap(), bytes[64])
+    }
+    
+    /// Gets the 65-byte encoding
+    pub fn to_bytes(&self) -> [u8; 65] {
+        let mut bytes = [0u8; 65];
+        bytes[..32].copy_from_slice(&self.r);
+        bytes[32..64].copy_from_slice(&self.s);
+        bytes[64] = self.recovery_id;
+        bytes
+    }
+    
+    /// Gets the recovery ID
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+    
+    /// Recovers the public key that signed a 32-byte message hash
+    ///
+    /// Returns `None` if no key could have produced the signature.
+    pub fn recover(&self, hash: &[u8; 32]) -> Option<PublicKey> {
+        let signature = self.to_ecdsa()?;
+        let mut recovery_id = self.recovery_id;
+        
+        // k256 only recovers from the low `s`, which signs with R's other y
+        let signature = match signature.normalize_s() {
+            Some(low) => {
+                recovery_id ^= 1;
+                low
+            }
+            None => signature,
+        };
+        
+        let recovery_id = RecoveryId::from_byte(recovery_id)?;
+        VerifyingKey::recover_from_prehash(hash, &signature, recovery_id).ok().map(PublicKey)
+    }
+    
+    /// Converts to a k256 signature; `None` if `r` or `s` isn't in `1..N`
+    fn to_ecdsa(self) -> Option<ecdsa::Signature> {
+        ecdsa::Signature::from_scalars(self.r, self.s).ok()
+    }
+}
\ No newline at end of file
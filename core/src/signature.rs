@@ -0,0 +1,194 @@
+//! Signature schemes for account keys
+//!
+//! An account address encodes the public key that signs for it, behind a
+//! prefix naming the key's scheme:
+//!
+//! | Scheme      | Address                                  | Signature                       |
+//! |-------------|------------------------------------------|---------------------------------|
+//! | `ed25519`   | `GENX` + 32-byte public key in hex       | 64 bytes                        |
+//! | `secp256k1` | `GENXK1` + 33-byte compressed key in hex | 65 bytes: `r`, `s`, recovery ID |
+//!
+//! `K` is not a hex digit, so no address matches both. Addresses that
+//! encode no key, such as the genesis accounts, have no scheme.
+//!
+//! secp256k1 signs 32-byte hashes only; see the `secp256k1` module.
+
+use std::fmt;
+
+use ed25519_dalek::Signer;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::secp256k1;
+
+/// Error signing or verifying with an account key
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignatureError {
+    #[error("Address {0} does not encode a public key")]
+    InvalidAddress(String),
+    
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+    
+    #[error("Invalid secret key")]
+    InvalidSecretKey,
+    
+    #[error("Malformed signature: {0}")]
+    MalformedSignature(String),
+    
+    #[error("Expected a {expected}-byte {scheme} signature, got {actual} bytes")]
+    SchemeMismatch { scheme: SignatureScheme, expected: usize, actual: usize },
+    
+    #[error("{0} signs 32-byte hashes, got a {1}-byte message")]
+    InvalidMessage(SignatureScheme, usize),
+    
+    #[error("Signature does not match the key")]
+    InvalidSignature,
+}
+
+/// Algorithm an account key signs with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureScheme {
+    /// Ed25519, the original account scheme
+    #[default]
+    Ed25519,
+    
+    /// ECDSA over secp256k1, the curve of Bitcoin and Ethereum keys
+    Secp256k1,
+}
+
+impl SignatureScheme {
+    /// Every supported scheme
+    pub const ALL: [SignatureScheme; 2] = [SignatureScheme::Ed25519, SignatureScheme::Secp256k1];
+    
+    /// Gets the prefix of addresses for keys of this scheme
+    pub fn address_prefix(self) -> &'static str {
+        match self {
+            SignatureScheme::Ed25519 => "GENX",
+            SignatureScheme::Secp256k1 => "GENXK1",
+        }
+    }
+    
+    /// Gets the length of a public key as encoded in an address
+    pub fn public_key_len(self) -> usize {
+        match self {
+            SignatureScheme::Ed25519 => 32,
+            SignatureScheme::Secp256k1 => 33,
+        }
+    }
+    
+    /// Gets the length of a signature
+    pub fn signature_len(self) -> usize {
+        match self {
+            SignatureScheme::Ed25519 => 64,
+            SignatureScheme::Secp256k1 => 65,
+        }
+    }
+    
+    /// Builds the address of a public key
+    pub fn address(self, public_key: &[u8]) -> Result<String, SignatureError> {
+        if public_key.len() != self.public_key_len() {
+            return Err(SignatureError::InvalidPublicKey(format!(
+                "expected a {}-byte {} key, got {} bytes",
+                self.public_key_len(),
+                self,
+                public_key.len()
+            )));
+        }
+        Ok(format!("{}{}", self.address_prefix(), hex::encode(public_key)))
+    }
+    
+    /// Gets the scheme of the key an address encodes, if it encodes one
+    pub fn from_address(address: &str) -> Option<Self> {
+        parse_address(address).map(|(scheme, _)| scheme)
+    }
+}
+
+impl fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureScheme::Ed25519 => write!(f, "ed25519"),
+            SignatureScheme::Secp256k1 => write!(f, "secp256k1"),
+        }
+    }
+}
+
+/// Splits an address into the scheme and public key it encodes
+pub fn parse_address(address: &str) -> Option<(SignatureScheme, Vec<u8>)> {
+    SignatureScheme::ALL.into_iter().find_map(|scheme| {
+        let public_key = hex::decode(address.strip_prefix(scheme.address_prefix())?).ok()?;
+        (public_key.len() == scheme.public_key_len()).then_some((scheme, public_key))
+    })
+}
+
+/// Signs a message with a secret key of the given scheme
+pub fn sign(scheme: SignatureScheme, secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>, SignatureError> {
+    match scheme {
+        SignatureScheme::Ed25519 => {
+            let secret = ed25519_dalek::SecretKey::from_bytes(secret_key).map_err(|_| SignatureError::InvalidSecretKey)?;
+            let public = ed25519_dalek::PublicKey::from(&secret);
+            let keypair = ed25519_dalek::Keypair { secret, public };
+            Ok(keypair.sign(message).to_bytes().to_vec())
+        }
+        SignatureScheme::Secp256k1 => {
+            let hash = secp256k1_hash(message)?;
+            Ok(secp256k1::SecretKey::from_bytes(secret_key)?.sign(hash).to_bytes().to_vec())
+        }
+    }
+}
+
+/// Builds the address of the public key of a secret key
+pub fn address_of(scheme: SignatureScheme, secret_key: &[u8]) -> Result<String, SignatureError> {
+    let public_key = match scheme {
+        SignatureScheme::Ed25519 => {
+            let secret = ed25519_dalek::SecretKey::from_bytes(secret_key).map_err(|_| SignatureError::InvalidSecretKey)?;
+            ed25519_dalek::PublicKey::from(&secret).as_bytes().to_vec()
+        }
+        SignatureScheme::Secp256k1 => secp256k1::SecretKey::from_bytes(secret_key)?.public_key().to_compressed().to_vec(),
+    };
+    scheme.address(&public_key)
+}
+
+/// Verifies a signature by the key an address encodes
+///
+/// A signature of the wrong length for the address's scheme is rejected
+/// as a scheme mismatch.
+pub fn verify(address: &str, message: &[u8], signature: &[u8]) -> Result<(), SignatureError> {
+    let (scheme, public_key) = parse_address(address).ok_or_else(|| SignatureError::InvalidAddress(address.to_string()))?;
+    
+    if signature.len() != scheme.signature_len() {
+        return Err(SignatureError::SchemeMismatch {
+            scheme,
+            expected: scheme.signature_len(),
+            actual: signature.len(),
+        });
+    }
+    
+    let valid = match scheme {
+        SignatureScheme::Ed25519 => {
+            let public_key = ed25519_dalek::PublicKey::from_bytes(&public_key)
+                .map_err(|e| SignatureError::InvalidPublicKey(e.to_string()))?;
+            let signature = ed25519_dalek::Signature::try_from(signature)
+                .map_err(|e| SignatureError::MalformedSignature(e.to_string()))?;
+            public_key.verify_strict(message, &signature).is_ok()
+        }
+        SignatureScheme::Secp256k1 => {
+            let hash = secp256k1_hash(message)?;
+            let public_key = secp256k1::PublicKey::from_bytes(&public_key)?;
+            public_key.verify(hash, &secp256k1::Signature::from_bytes(signature)?)
+        }
+    };
+    
+    if valid {
+        Ok(())
+    } else {
+        Err(SignatureError::InvalidSignature)
+    }
+}
+
+fn secp256k1_hash(message: &[u8]) -> Result<&[u8; 32], SignatureError> {
+    message
+        .try_into()
+        .map_err(|_| SignatureError::InvalidMessage(SignatureScheme::Secp256k1, message.len()))
+}
\ No newline at end of file
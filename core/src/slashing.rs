@@ -0,0 +1,234 @@
+//! Slashing of validators that sign conflicting blocks
+//!
+//! Anyone can report a validator that signed two different blocks at the
+//! same height by sending a `SubmitEvidence` transaction carrying both
+//! headers. Evidence is only accepted for `EVIDENCE_WINDOW` blocks after the
+//! height it's about, so validators aren't answerable for old keys forever,
+//! and each offence is only punished once.
+//!
+//! Accepted evidence doesn't take the penalty at once. `SLASH_PERCENT` of
+//! the validator's stake is moved out of it into a pending slash, escrowed
+//! for `DISPUTE_PERIOD` blocks. Until then validators may approve cancelling
+//! it with `CancelSlash` transactions, as for evidence of a key that was
+//! stolen or of a signer bug the chain chooses to forgive. The slash is
+//! cancelled and the penalty returned to the stake as soon as validators
+//! holding more than two thirds of the stake, not counting the slashed
+//! validator, approve. Otherwise the penalty is burned after the last block
+//! of the dispute period, see `State::apply_block_with_executor`.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockHeader;
+use crate::signature;
+use crate::{BlockchainError, Result};
+
+/// Blocks after its height that evidence is accepted in
+pub const EVIDENCE_WINDOW: u64 = 100;
+
+/// Blocks a penalty is escrowed for, starting with the block after the evidence's
+pub const DISPUTE_PERIOD: u64 = 50;
+
+/// Percentage of its stake a validator loses for signing conflicting blocks
+pub const SLASH_PERCENT: u64 = 10;
+
+/// Gets the penalty for a validator holding a stake
+pub fn penalty(stake: u64) -> u64 {
+    (stake as u128 * SLASH_PERCENT as u128 / 100) as u64
+}
+
+/// Checks evidence about a height may be submitted in the block at another
+pub fn check_window(evidence_height: u64, height: u64) -> Result<()> {
+    if evidence_height >= height {
+        return Err(BlockchainError::InvalidTransaction(format!(
+            "Evidence about height {} can't be submitted at height {}",
+            evidence_height, height
+        )));
+    }
+    let expires_at = evidence_height.saturating_add(EVIDENCE_WINDOW);
+    if height > expires_at {
+        return Err(BlockchainError::EvidenceExpired { height: evidence_height, expired_at: expires_at });
+    }
+    Ok(())
+}
+
+/// Payload of a `SubmitEvidence` transaction: two headers signed by the same validator at the same height
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlashingEvidence {
+    /// One of the headers
+    pub first: BlockHeader,
+    
+    /// The other, differing from the first
+    pub second: BlockHeader,
+}
+
+/// Payload of a `CancelSlash` transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlashCancellation {
+    /// Slash the sender approves cancelling
+    pub slash_id: u64,
+}
+
+/// Where a slash stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlashStatus {
+    /// Escrowing the penalty until the dispute period ends
+    Pending,
+    
+    /// The dispute period ended; the penalty was burned
+    Applied,
+    
+    /// Cancelled by a supermajority; the penalty was returned to the stake
+    Cancelled,
+}
+
+impl SlashStatus {
+    /// Gets the tag of the status in the canonical state encoding
+    pub(crate) fn tag(&self) -> u64 {
+        match self {
+            Self::Pending => 0,
+            Self::Applied => 1,
+            Self::Cancelled => 2,
+        }
+    }
+    
+    /// Gets the status with a tag of the canonical state encoding
+    pub(crate) fn from_tag(tag: u64) -> Option<Self> {
+        [Self::Pending, Self::Applied, Self::Cancelled].into_iter().find(|status| status.tag() == tag)
+    }
+}
+
+/// A slash for signing conflicting blocks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slash {
+    /// Number of the slash, counting from 1 in submission order
+    pub id: u64,
+    
+    /// Operator address of the slashed validator
+    pub validator: String,
+    
+    /// Height the validator signed conflicting blocks at
+    pub height: u64,
+    
+    /// Address that submitted the evidence
+    pub reporter: String,
+    
+    /// Stake taken from the validator and escrowed
+    pub penalty: u64,
+    
+    /// Height of the block the evidence was submitted in
+    pub submitted_at: u64,
+    
+    /// Height of the last block the slash may be cancelled in, after which the penalty is burned
+    pub applies_at: u64,
+    
+    /// Validators that approve cancelling the slash
+    pub approvals: BTreeSet<String>,
+    
+    /// Where the slash stands
+    pub status: SlashStatus,
+}
+
+impl SlashingEvidence {
+    /// Encodes the evidence as transaction data
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+    
+    /// Decodes evidence from transaction data and validates it
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let evidence: Self = serde_json::from_slice(data).map_err(|e| {
+            BlockchainError::InvalidTransaction(format!("Invalid evidence: {}", e))
+        })?;
+        evidence.validate()?;
+        Ok(evidence)
+    }
+    
+    /// Gets the height the headers conflict at
+    pub fn height(&self) -> u64 {
+        self.first.height
+    }
+    
+    /// Gets the validator that signed both headers
+    pub fn validator(&self) -> &str {
+        &self.first.validator
+    }
+    
+    /// Checks the headers are signed and conflict: same height and validator, different contents
+    ///
+    /// Whether the signatures are the validator's depends on the key it
+    /// registered, and is checked by `verify` when the evidence is applied.
+    pub fn validate(&self) -> Result<()> {
+        if self.first.height != self.second.height || self.first.validator != self.second.validator {
+            return Err(BlockchainError::InvalidTransaction(
+                "Evidence headers must be at the same height and by the same validator".to_string(),
+            ));
+        }
+        if self.first.signature.is_none() || self.second.signature.is_none() {
+            return Err(BlockchainError::InvalidTransaction("Evidence headers must both be signed".to_string()));
+        }
+        if self.first.signing_hash()? == self.second.signing_hash()? {
+            return Err(BlockchainError::InvalidTransaction("Evidence headers don't conflict".to_string()));
+        }
+        Ok(())
+    }
+    
+    /// Checks both headers are signed by a consensus key
+    pub fn verify(&self, consensus_key: &str) -> Result<()> {
+        for header in [&self.first, &self.second] {
+            let hash = header.signing_hash()?;
+            let header_signature = header.signature.as_ref().map_or(&[][..], |signature| &signature.0[..]);
+            signature::verify(consensus_key, &hash, header_signature).map_err(|e| {
+                BlockchainError::InvalidTransaction(
+                    format!("Evidence header at height {} isn't signed by {}: {}", header.height, consensus_key, e)
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl SlashCancellation {
+    /// Encodes the cancellation as transaction data
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+    
+    /// Decodes a cancellation from transaction data
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).map_err(|e| BlockchainError::InvalidTransaction(format!("Invalid slash cancellation: {}", e)))
+    }
+}
+
+impl Slash {
+    /// Creates the pending slash evidence submitted at the given height opens
+    pub fn new(id: u64, reporter: String, evidence: &SlashingEvidence, penalty: u64, height: u64) -> Self {
+        Self {
+            id,
+            validator: evidence.validator().to_string(),
+            height: evidence.height(),
+            reporter,
+            penalty,
+            submitted_at: height,
+            applies_at: height.saturating_add(DISPUTE_PERIOD),
+            approvals: BTreeSet::new(),
+            status: SlashStatus::Pending,
+        }
+    }
+    
+    /// Checks whether the approvals cancel the slash, weighing each by the approver's stake
+    ///
+    /// `total` is the stake of all validators; the slashed validator's
+    /// stake counts neither towards it nor towards the approvals.
+    pub fn is_cancelled_by(&self, stake_of: impl Fn(&str) -> u64, total: u64) -> bool {
+        let eligible = total.saturating_sub(stake_of(&self.validator));
+        let approving = self
+            .approvals
+            .iter()
+            .filter(|approver| **approver != self.validator)
+            .fold(0u64, |approving, approver| approving.saturating_add(stake_of(approver)));
+        approving > 0 && approving as u128 * 3 > eligible as u128 * 2
+    }
+}
\ No newline at end of file
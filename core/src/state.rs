@@ -3,26 +3,265 @@
 //! This module manages the state of the blockchain, including account balances,
 //! smart contract state, and validator information.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
 
-use crate::{BlockchainError, Result};
-use crate::block::Block;
-use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{self, HashDomain};
+use crate::{Address, BlockchainError, ErrorContext, Hash, Result, WithContext};
+use crate::block::{Block, BlockHeader};
+use crate::deposit::{ContractDeposits, DepositRates, StorageDeposit};
+use crate::execution_policy::{ExecutionPolicy, PolicyUpdate};
+use crate::executor::{ContractExecutor, ExecutionOutcome};
+use crate::genesis;
+use crate::paging::{self, Page, PageRequest, SortOrder};
+use crate::governance::{GovernedParameter, Proposal, ProposalStatus, ProposalSubmission, ProposalVote};
+use crate::receipt::Receipt;
+use crate::rewards::COINBASE;
+use crate::rlp::{self, RlpItem};
+use crate::slashing::{self, Slash, SlashCancellation, SlashStatus, SlashingEvidence};
+use crate::state_diff::{StateKey, StateValue};
+use crate::transaction::{Transaction, TransactionType};
+use crate::units::Amount;
+use crate::validator::{ValidatorEdit, ValidatorInfo, ValidatorRegistration, ValidatorSort};
+use crate::wire::{self, WireError};
+
+/// Storage of a single contract (32-byte slot -> 32-byte value)
+pub type ContractStorage = HashMap<Vec<u8>, Vec<u8>>;
+
+/// Code and metadata of a deployed contract
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractAccount {
+    /// Runtime bytecode
+    pub code: Vec<u8>,
+    
+    /// Engine-defined metadata, such as the serialized ABI
+    pub metadata: Vec<u8>,
+    
+    /// Address of the account that deployed the contract
+    pub creator: String,
+    
+    /// Height of the block the contract was deployed in
+    pub deployed_at: u64,
+}
+
+/// The parts of the state that contract execution reads and writes
+///
+/// Contract engines work against this trait rather than `State` itself, so
+/// everything they persist lives in the state and is rebuilt by replaying blocks.
+pub trait StateAccess {
+    /// Gets the balance of an account
+    fn get_balance(&self, address: &str) -> u64;
+    
+    /// Moves funds between two accounts
+    fn transfer(&mut self, from: &str, to: &str, amount: u64) -> Result<()>;
+    
+    /// Gets the contract deployed at an address
+    ///
+    /// Deployed contracts never change, so they are shared rather than copied.
+    fn get_contract(&self, address: &str) -> Option<&Arc<ContractAccount>>;
+    
+    /// Stores a newly deployed contract
+    ///
+    /// While a transaction executes, its sender pays the deposit for the code.
+    fn insert_contract(&mut self, address: &str, contract: ContractAccount);
+    
+    /// Removes a contract's code and storage, returning the contract if there was one
+    ///
+    /// The account's balance is kept; the address becomes a plain account.
+    /// Deposits locked for the code and storage are refunded to their payers.
+    fn remove_contract(&mut self, address: &str) -> Option<Arc<ContractAccount>>;
+    
+    /// Lists the contracts deployed by an account, as deployment heights and addresses
+    ///
+    /// Contracts are ordered by deployment height and then address. The list
+    /// starts at `start`, or the first contract, and holds at most `limit`.
+    fn contracts_by_creator(&self, creator: &str, start: Option<(u64, &str)>, limit: usize) -> Vec<(u64, String)>;
+    
+    /// Lists all deployed contracts, as deployment heights and addresses
+    ///
+    /// Ordered and paginated like `contracts_by_creator`.
+    fn list_contracts(&self, start: Option<(u64, &str)>, limit: usize) -> Vec<(u64, String)>;
+    
+    /// Reads a slot of a contract's storage
+    fn get_storage(&self, address: &str, key: &[u8]) -> Option<&[u8]>;
+    
+    /// Gets all of a contract's storage
+    fn get_contract_storage(&self, address: &str) -> Option<&ContractStorage>;
+    
+    /// Writes a slot of a contract's storage; `None` clears it
+    ///
+    /// While a transaction executes, its sender pays the deposit for a slot
+    /// that was empty. Clearing a slot refunds its deposit to its payer.
+    fn set_storage(&mut self, address: &str, key: Vec<u8>, value: Option<Vec<u8>>);
+    
+    /// Starts recording changes so they can be undone, see `State::checkpoint`
+    fn checkpoint(&mut self);
+    
+    /// Undoes every change made since the latest checkpoint and discards it
+    fn revert(&mut self);
+    
+    /// Keeps the changes made since the latest checkpoint and discards it
+    fn commit(&mut self);
+}
+
+/// A change to the state, recorded with the value it replaced
+#[derive(Debug, Clone)]
+enum JournalEntry {
+    Balance { address: String, previous: Option<u64> },
+    Nonce { address: String, previous: Option<u64> },
+    ValidatorStake { validator: String, previous: Option<u64> },
+    Validator { operator: String, previous: Option<ValidatorInfo> },
+    Contract { address: String, previous: Option<Arc<ContractAccount>> },
+    Storage { address: String, key: Vec<u8>, previous: Option<Vec<u8>> },
+    StorageRemoved { address: String, previous: Arc<ContractStorage> },
+    Deposit { address: String, slot: Option<Vec<u8>>, previous: Option<StorageDeposit> },
+    DepositsRemoved { address: String, previous: Arc<ContractDeposits> },
+    Locked { address: String, previous: Option<u64> },
+    TotalSupply(u64),
+    TotalBurned(u64),
+    Proposal { id: u64, previous: Option<Proposal> },
+    Slash { id: u64, previous: Option<Slash> },
+    ExecutionPolicy(Box<ExecutionPolicy>),
+}
+
+/// Kinds of record in the canonical encoding of a state
+mod record {
+    pub const TOTAL_SUPPLY: u64 = 0;
+    pub const BALANCE: u64 = 1;
+    pub const STAKE: u64 = 2;
+    pub const VALIDATOR: u64 = 3;
+    pub const CONTRACT: u64 = 4;
+    pub const STORAGE: u64 = 5;
+    pub const CODE_DEPOSIT: u64 = 6;
+    pub const SLOT_DEPOSIT: u64 = 7;
+    pub const PROPOSAL: u64 = 8;
+    pub const TOTAL_BURNED: u64 = 9;
+    pub const SLASH: u64 = 10;
+    pub const POLICY_ADMINS: u64 = 11;
+    pub const DEPLOYERS: u64 = 12;
+    pub const CALLERS: u64 = 13;
+    pub const DISABLED: u64 = 14;
+    pub const NONCE: u64 = 15;
+}
+
+/// Changes made by an applied block, kept so the block can be rolled back
+///
+/// Produced by `State::commit_block` and consumed by `State::rollback_block`.
+#[derive(Debug, Clone, Default)]
+pub struct BlockUndo {
+    entries: Vec<JournalEntry>,
+}
+
+impl BlockUndo {
+    /// Lists the balances, stakes and storage slots the block changed, each
+    /// with the value it had before, in the order they were changed
+    pub(crate) fn previous_values(&self) -> impl Iterator<Item = (StateKey, StateValue)> + '_ {
+        self.entries.iter().flat_map(|entry| match entry {
+            JournalEntry::Balance { address, previous } => {
+                vec![(StateKey::Balance(address.clone()), StateValue::Amount(previous.unwrap_or(0)))]
+            }
+            JournalEntry::ValidatorStake { validator, previous } => {
+                vec![(StateKey::Stake(validator.clone()), StateValue::Amount(previous.unwrap_or(0)))]
+            }
+            JournalEntry::Storage { address, key, previous } => {
+                vec![(StateKey::Storage(address.clone(), key.clone()), StateValue::Slot(previous.clone()))]
+            }
+            JournalEntry::StorageRemoved { address, previous } => previous
+                .iter()
+                .map(|(key, value)| (StateKey::Storage(address.clone(), key.clone()), StateValue::Slot(Some(value.clone()))))
+                .collect(),
+            JournalEntry::Nonce { .. }
+            | JournalEntry::Validator { .. }
+            | JournalEntry::Contract { .. }
+            | JournalEntry::Deposit { .. }
+            | JournalEntry::DepositsRemoved { .. }
+            | JournalEntry::Locked { .. }
+            | JournalEntry::TotalSupply(_)
+            | JournalEntry::TotalBurned(_)
+            | JournalEntry::Proposal { .. }
+            | JournalEntry::Slash { .. }
+            | JournalEntry::ExecutionPolicy(_) => Vec::new(),
+        })
+    }
+}
+
+/// Account paying for what an executing transaction stores, see `deposit`
+#[derive(Debug, Clone)]
+struct Depositor {
+    /// Sender of the transaction
+    payer: String,
+    
+    /// Part of the payer's balance kept for the transaction's value, which deposits can't use
+    reserved: u64,
+    
+    /// Deposit the payer couldn't cover and what it had available, failing the execution
+    shortfall: Option<(u64, u64)>,
+}
 
 /// Represents the current state of the blockchain
+///
+/// Contracts and their storage are shared between clones and only copied
+/// when written, so cloning the state for a snapshot stays cheap.
 #[derive(Debug, Clone)]
 pub struct State {
     /// Account balances (address -> balance)
     balances: HashMap<String, u64>,
     
+    /// Nonces of the accounts' next transactions (address -> nonce), for accounts that sent any
+    nonces: HashMap<String, u64>,
+    
     /// Validator stakes (validator address -> staked amount)
     validator_stakes: HashMap<String, u64>,
     
-    /// Smart contract state (contract address -> state)
-    contract_states: HashMap<String, Vec<u8>>,
+    /// Registered validators (operator address -> metadata)
+    validators: HashMap<String, ValidatorInfo>,
+    
+    /// Deployed contracts (contract address -> code and metadata)
+    contracts: HashMap<String, Arc<ContractAccount>>,
+    
+    /// Smart contract storage (contract address -> storage)
+    contract_storage: HashMap<String, Arc<ContractStorage>>,
+    
+    /// Deployed contracts by creator (creator -> (deployment height, contract address))
+    contracts_by_creator: HashMap<String, BTreeSet<(u64, String)>>,
+    
+    /// Deployed contracts by deployment height (height -> contract addresses)
+    contracts_by_height: BTreeMap<u64, BTreeSet<String>>,
+    
+    /// Deposits locked for contracts' code and storage (contract address -> deposits)
+    deposits: HashMap<String, Arc<ContractDeposits>>,
+    
+    /// Amounts locked in storage deposits (payer -> total locked)
+    locked: HashMap<String, u64>,
+    
+    /// Rates of the deposits locked for what contracts store
+    deposit_rates: DepositRates,
+    
+    /// Account paying for what the executing transaction stores, if any
+    depositor: Option<Depositor>,
     
     /// Total supply of GENX tokens in circulation
     total_supply: u64,
+    
+    /// Total GENX burned, by fees, forfeited governance deposits and slashes
+    total_burned: u64,
+    
+    /// Governance proposals, passed or not (proposal ID -> proposal)
+    proposals: BTreeMap<u64, Proposal>,
+    
+    /// Slashes for signing conflicting blocks, pending or not (slash ID -> slash)
+    slashes: BTreeMap<u64, Slash>,
+    
+    /// Who may deploy and call contracts
+    execution_policy: ExecutionPolicy,
+    
+    /// Changes made since the outermost open checkpoint
+    journal: Vec<JournalEntry>,
+    
+    /// Journal length at each open checkpoint, innermost last
+    checkpoints: Vec<usize>,
 }
 
 impl State {
@@ -30,80 +269,1687 @@ impl State {
     pub fn new() -> Self {
         Self {
             balances: HashMap::new(),
+            nonces: HashMap::new(),
             validator_stakes: HashMap::new(),
-            contract_states: HashMap::new(),
+            validators: HashMap::new(),
+            contracts: HashMap::new(),
+            contract_storage: HashMap::new(),
+            contracts_by_creator: HashMap::new(),
+            contracts_by_height: BTreeMap::new(),
+            deposits: HashMap::new(),
+            locked: HashMap::new(),
+            deposit_rates: genesis::get_storage_deposit_rates(),
+            depositor: None,
             total_supply: 0,
+            total_burned: 0,
+            proposals: BTreeMap::new(),
+            slashes: BTreeMap::new(),
+            execution_policy: genesis::get_execution_policy(),
+            journal: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+    
+    /// Encodes the state canonically, as a sequence of RLP records
+    ///
+    /// Each record is a list of its kind and fields, using the conventions of
+    /// `wire`. The total supply comes first, followed by the total burned if
+    /// any was, then balances, nonces, stakes,
+    /// validators, contracts, storage slots, storage deposits, governance
+    /// proposals and slashes, each sorted by key, so equal states always encode to the
+    /// same bytes. The execution policy's restrictions follow, if it has any.
+    /// Open checkpoints aren't encoded.
+    pub fn encode_canonical(&self) -> Vec<u8> {
+        fn sorted<K: Ord, V>(map: &HashMap<K, V>) -> Vec<(&K, &V)> {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            entries
+        }
+        let uint = |value: u64| RlpItem::uint(value as u128);
+        
+        let mut records = vec![RlpItem::List(vec![uint(record::TOTAL_SUPPLY), uint(self.total_supply)])];
+        if self.total_burned > 0 {
+            records.push(RlpItem::List(vec![uint(record::TOTAL_BURNED), uint(self.total_burned)]));
+        }
+        for (address, balance) in sorted(&self.balances) {
+            records.push(RlpItem::List(vec![uint(record::BALANCE), wire::string(address), uint(*balance)]));
+        }
+        for (address, nonce) in sorted(&self.nonces) {
+            records.push(RlpItem::List(vec![uint(record::NONCE), wire::string(address), uint(*nonce)]));
+        }
+        for (validator, stake) in sorted(&self.validator_stakes) {
+            records.push(RlpItem::List(vec![uint(record::STAKE), wire::string(validator), uint(*stake)]));
+        }
+        for (_, info) in sorted(&self.validators) {
+            records.push(RlpItem::List(vec![
+                uint(record::VALIDATOR),
+                wire::string(&info.operator),
+                wire::string(&info.moniker),
+                wire::string(&info.website),
+                uint(info.commission_rate as u64),
+                wire::string(&info.consensus_key),
+                uint(info.registered_at),
+                uint(info.commission_changed_at),
+                wire::string(&info.payout_address),
+                wire::string(info.pending_payout_address.as_deref().unwrap_or_default()),
+                uint(info.payout_changed_at),
+            ]));
+        }
+        for (address, contract) in sorted(&self.contracts) {
+            records.push(RlpItem::List(vec![
+                uint(record::CONTRACT),
+                wire::string(address),
+                RlpItem::Bytes(contract.code.clone()),
+                RlpItem::Bytes(contract.metadata.clone()),
+                wire::string(&contract.creator),
+                uint(contract.deployed_at),
+            ]));
+        }
+        for (address, storage) in sorted(&self.contract_storage) {
+            for (key, value) in sorted(storage) {
+                records.push(RlpItem::List(vec![
+                    uint(record::STORAGE),
+                    wire::string(address),
+                    RlpItem::Bytes(key.clone()),
+                    RlpItem::Bytes(value.clone()),
+                ]));
+            }
+        }
+        for (address, deposits) in sorted(&self.deposits) {
+            if let Some(deposit) = &deposits.code {
+                records.push(RlpItem::List(vec![
+                    uint(record::CODE_DEPOSIT),
+                    wire::string(address),
+                    wire::string(&deposit.payer),
+                    uint(deposit.amount),
+                ]));
+            }
+            for (slot, deposit) in sorted(&deposits.slots) {
+                records.push(RlpItem::List(vec![
+                    uint(record::SLOT_DEPOSIT),
+                    wire::string(address),
+                    RlpItem::Bytes(slot.clone()),
+                    wire::string(&deposit.payer),
+                    uint(deposit.amount),
+                ]));
+            }
+        }
+        for proposal in self.proposals.values() {
+            let votes = proposal
+                .votes
+                .iter()
+                .map(|(voter, &approve)| RlpItem::List(vec![wire::string(voter), uint(approve as u64)]))
+                .collect();
+            records.push(RlpItem::List(vec![
+                uint(record::PROPOSAL),
+                uint(proposal.id),
+                wire::string(&proposal.proposer),
+                uint(proposal.parameter.tag()),
+                uint(proposal.value),
+                uint(proposal.deposit),
+                uint(proposal.submitted_at),
+                uint(proposal.voting_ends_at),
+                uint(proposal.activation_height),
+                RlpItem::List(votes),
+                uint(proposal.status.tag()),
+            ]));
+        }
+        for slash in self.slashes.values() {
+            records.push(RlpItem::List(vec![
+                uint(record::SLASH),
+                uint(slash.id),
+                wire::string(&slash.validator),
+                uint(slash.height),
+                wire::string(&slash.reporter),
+                uint(slash.penalty),
+                uint(slash.submitted_at),
+                uint(slash.applies_at),
+                RlpItem::List(slash.approvals.iter().map(|approver| wire::string(approver)).collect()),
+                uint(slash.status.tag()),
+            ]));
+        }
+        let addresses = |addresses: &BTreeSet<String>| RlpItem::List(addresses.iter().map(|address| wire::string(address)).collect());
+        let policy = &self.execution_policy;
+        if !policy.admins.is_empty() {
+            records.push(RlpItem::List(vec![uint(record::POLICY_ADMINS), addresses(&policy.admins)]));
+        }
+        if let Some(deployers) = &policy.deployers {
+            records.push(RlpItem::List(vec![uint(record::DEPLOYERS), addresses(deployers)]));
+        }
+        for (contract, callers) in &policy.callers {
+            records.push(RlpItem::List(vec![uint(record::CALLERS), wire::string(contract), addresses(callers)]));
+        }
+        for (contract, since) in &policy.disabled {
+            records.push(RlpItem::List(vec![uint(record::DISABLED), wire::string(contract), uint(*since)]));
+        }
+        
+        records.iter().flat_map(rlp::encode).collect()
+    }
+    
+    /// Decodes a state from its canonical encoding
+    ///
+    /// Records of a known kind with the wrong fields are an error. A record
+    /// repeating an earlier key replaces it.
+    pub fn decode_canonical(mut data: &[u8]) -> std::result::Result<Self, WireError> {
+        fn addresses(item: &RlpItem) -> std::result::Result<BTreeSet<String>, WireError> {
+            item.as_list()?.iter().map(wire::decode_string).collect()
+        }
+        
+        let mut state = State::new();
+        state.execution_policy = ExecutionPolicy::default();
+        while !data.is_empty() {
+            let (item, rest) = rlp::decode_item(data)?;
+            data = rest;
+            
+            let fields = item.as_list()?;
+            let kind = fields.first().ok_or(WireError::InvalidValue("state record"))?.as_u64()?;
+            match kind {
+                record::TOTAL_SUPPLY => {
+                    state.total_supply = wire::fields(&item, 2)?[1].as_u64()?;
+                }
+                record::TOTAL_BURNED => {
+                    state.total_burned = wire::fields(&item, 2)?[1].as_u64()?;
+                }
+                record::BALANCE => {
+                    let fields = wire::fields(&item, 3)?;
+                    state.balances.insert(wire::decode_string(&fields[1])?, fields[2].as_u64()?);
+                }
+                record::NONCE => {
+                    let fields = wire::fields(&item, 3)?;
+                    state.nonces.insert(wire::decode_string(&fields[1])?, fields[2].as_u64()?);
+                }
+                record::STAKE => {
+                    let fields = wire::fields(&item, 3)?;
+                    state.validator_stakes.insert(wire::decode_string(&fields[1])?, fields[2].as_u64()?);
+                }
+                record::VALIDATOR => {
+                    let fields = wire::fields(&item, 11)?;
+                    let pending_payout_address = wire::decode_string(&fields[9])?;
+                    let info = ValidatorInfo {
+                        operator: wire::decode_string(&fields[1])?,
+                        moniker: wire::decode_string(&fields[2])?,
+                        website: wire::decode_string(&fields[3])?,
+                        commission_rate: u32::try_from(fields[4].as_u64()?)
+                            .map_err(|_| WireError::InvalidValue("commission rate"))?,
+                        consensus_key: wire::decode_string(&fields[5])?,
+                        registered_at: fields[6].as_u64()?,
+                        commission_changed_at: fields[7].as_u64()?,
+                        payout_address: wire::decode_string(&fields[8])?,
+                        pending_payout_address: (!pending_payout_address.is_empty()).then_some(pending_payout_address),
+                        payout_changed_at: fields[10].as_u64()?,
+                    };
+                    state.validators.insert(info.operator.clone(), info);
+                }
+                record::CONTRACT => {
+                    let fields = wire::fields(&item, 6)?;
+                    let contract = ContractAccount {
+                        code: fields[2].as_bytes()?.to_vec(),
+                        metadata: fields[3].as_bytes()?.to_vec(),
+                        creator: wire::decode_string(&fields[4])?,
+                        deployed_at: fields[5].as_u64()?,
+                    };
+                    state.replace_contract(wire::decode_string(&fields[1])?, Some(Arc::new(contract)));
+                }
+                record::STORAGE => {
+                    let fields = wire::fields(&item, 4)?;
+                    Arc::make_mut(state.contract_storage.entry(wire::decode_string(&fields[1])?).or_default())
+                        .insert(fields[2].as_bytes()?.to_vec(), fields[3].as_bytes()?.to_vec());
+                }
+                record::CODE_DEPOSIT => {
+                    let fields = wire::fields(&item, 4)?;
+                    let deposit = StorageDeposit { payer: wire::decode_string(&fields[2])?, amount: fields[3].as_u64()? };
+                    Arc::make_mut(state.deposits.entry(wire::decode_string(&fields[1])?).or_default()).code = Some(deposit);
+                }
+                record::SLOT_DEPOSIT => {
+                    let fields = wire::fields(&item, 5)?;
+                    let deposit = StorageDeposit { payer: wire::decode_string(&fields[3])?, amount: fields[4].as_u64()? };
+                    Arc::make_mut(state.deposits.entry(wire::decode_string(&fields[1])?).or_default())
+                        .slots
+                        .insert(fields[2].as_bytes()?.to_vec(), deposit);
+                }
+                record::PROPOSAL => {
+                    let fields = wire::fields(&item, 11)?;
+                    let mut votes = BTreeMap::new();
+                    for vote in fields[9].as_list()? {
+                        let vote = wire::fields(vote, 2)?;
+                        let approve = match vote[1].as_u64()? {
+                            0 => false,
+                            1 => true,
+                            _ => return Err(WireError::InvalidValue("vote")),
+                        };
+                        votes.insert(wire::decode_string(&vote[0])?, approve);
+                    }
+                    let proposal = Proposal {
+                        id: fields[1].as_u64()?,
+                        proposer: wire::decode_string(&fields[2])?,
+                        parameter: GovernedParameter::from_tag(fields[3].as_u64()?)
+                            .ok_or(WireError::InvalidValue("governed parameter"))?,
+                        value: fields[4].as_u64()?,
+                        deposit: fields[5].as_u64()?,
+                        submitted_at: fields[6].as_u64()?,
+                        voting_ends_at: fields[7].as_u64()?,
+                        activation_height: fields[8].as_u64()?,
+                        votes,
+                        status: ProposalStatus::from_tag(fields[10].as_u64()?)
+                            .ok_or(WireError::InvalidValue("proposal status"))?,
+                    };
+                    state.proposals.insert(proposal.id, proposal);
+                }
+                record::SLASH => {
+                    let fields = wire::fields(&item, 10)?;
+                    let approvals = fields[8].as_list()?.iter().map(wire::decode_string).collect::<std::result::Result<_, _>>()?;
+                    let slash = Slash {
+                        id: fields[1].as_u64()?,
+                        validator: wire::decode_string(&fields[2])?,
+                        height: fields[3].as_u64()?,
+                        reporter: wire::decode_string(&fields[4])?,
+                        penalty: fields[5].as_u64()?,
+                        submitted_at: fields[6].as_u64()?,
+                        applies_at: fields[7].as_u64()?,
+                        approvals,
+                        status: SlashStatus::from_tag(fields[9].as_u64()?).ok_or(WireError::InvalidValue("slash status"))?,
+                    };
+                    state.slashes.insert(slash.id, slash);
+                }
+                record::POLICY_ADMINS => {
+                    state.execution_policy.admins = addresses(&wire::fields(&item, 2)?[1])?;
+                }
+                record::DEPLOYERS => {
+                    state.execution_policy.deployers = Some(addresses(&wire::fields(&item, 2)?[1])?);
+                }
+                record::CALLERS => {
+                    let fields = wire::fields(&item, 3)?;
+                    state.execution_policy.callers.insert(wire::decode_string(&fields[1])?, addresses(&fields[2])?);
+                }
+                record::DISABLED => {
+                    let fields = wire::fields(&item, 3)?;
+                    state.execution_policy.disabled.insert(wire::decode_string(&fields[1])?, fields[2].as_u64()?);
+                }
+                _ => return Err(WireError::InvalidValue("state record kind")),
+            }
+        }
+        
+        // What each account has locked follows from the deposits
+        for deposits in state.deposits.values() {
+            for deposit in deposits.code.iter().chain(deposits.slots.values()) {
+                let locked = state.locked.entry(deposit.payer.clone()).or_default();
+                *locked = locked.saturating_add(deposit.amount);
+            }
+        }
+        Ok(state)
+    }
+    
+    /// Opens a checkpoint that the changes made from now on can be reverted to
+    ///
+    /// Checkpoints nest: `revert` undoes the changes since the innermost one,
+    /// while `commit` merges them into the enclosing checkpoint. Changes are
+    /// only recorded while a checkpoint is open.
+    pub fn checkpoint(&mut self) {
+        self.checkpoints.push(self.journal.len());
+    }
+    
+    /// Undoes every change made since the innermost checkpoint and closes it
+    pub fn revert(&mut self) {
+        let checkpoint = self.checkpoints.pop().expect("revert without a checkpoint");
+        self.undo(checkpoint);
+    }
+    
+    /// Keeps the changes made since the innermost checkpoint and closes it
+    pub fn commit(&mut self) {
+        self.checkpoints.pop().expect("commit without a checkpoint");
+        if self.checkpoints.is_empty() {
+            self.journal.clear();
+        }
+    }
+    
+    /// Closes the outermost checkpoint, returning its changes so that
+    /// `rollback_block` can undo them later
+    pub fn commit_block(&mut self) -> BlockUndo {
+        let checkpoint = self.checkpoints.pop().expect("commit_block without a checkpoint");
+        debug_assert!(self.checkpoints.is_empty(), "commit_block with nested checkpoints open");
+        
+        BlockUndo { entries: self.journal.split_off(checkpoint) }
+    }
+    
+    /// Undoes the changes of a block committed with `commit_block`
+    ///
+    /// Blocks must be rolled back newest first.
+    pub fn rollback_block(&mut self, undo: BlockUndo) {
+        let checkpoint = self.journal.len();
+        self.journal.extend(undo.entries);
+        self.undo(checkpoint);
+    }
+    
+    /// Pops and undoes journal entries down to the given length
+    fn undo(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            match self.journal.pop() {
+                Some(JournalEntry::Balance { address, previous }) => {
+                    restore(&mut self.balances, address, previous);
+                }
+                Some(JournalEntry::Nonce { address, previous }) => {
+                    restore(&mut self.nonces, address, previous);
+                }
+                Some(JournalEntry::ValidatorStake { validator, previous }) => {
+                    restore(&mut self.validator_stakes, validator, previous);
+                }
+                Some(JournalEntry::Validator { operator, previous }) => {
+                    restore(&mut self.validators, operator, previous);
+                }
+                Some(JournalEntry::Contract { address, previous }) => {
+                    self.replace_contract(address, previous);
+                }
+                Some(JournalEntry::Storage { address, key, previous }) => {
+                    let storage = Arc::make_mut(self.contract_storage.entry(address.clone()).or_default());
+                    restore(storage, key, previous);
+                    if storage.is_empty() {
+                        self.contract_storage.remove(&address);
+                    }
+                }
+                Some(JournalEntry::StorageRemoved { address, previous }) => {
+                    self.contract_storage.insert(address, previous);
+                }
+                Some(JournalEntry::Deposit { address, slot, previous }) => {
+                    let deposits = Arc::make_mut(self.deposits.entry(address.clone()).or_default());
+                    match slot {
+                        Some(slot) => restore(&mut deposits.slots, slot, previous),
+                        None => deposits.code = previous,
+                    }
+                    if deposits.is_empty() {
+                        self.deposits.remove(&address);
+                    }
+                }
+                Some(JournalEntry::DepositsRemoved { address, previous }) => {
+                    self.deposits.insert(address, previous);
+                }
+                Some(JournalEntry::Locked { address, previous }) => {
+                    restore(&mut self.locked, address, previous);
+                }
+                Some(JournalEntry::TotalSupply(previous)) => self.total_supply = previous,
+                Some(JournalEntry::TotalBurned(previous)) => self.total_burned = previous,
+                Some(JournalEntry::Proposal { id, previous }) => match previous {
+                    Some(proposal) => {
+                        self.proposals.insert(id, proposal);
+                    }
+                    None => {
+                        self.proposals.remove(&id);
+                    }
+                },
+                Some(JournalEntry::Slash { id, previous }) => match previous {
+                    Some(slash) => {
+                        self.slashes.insert(id, slash);
+                    }
+                    None => {
+                        self.slashes.remove(&id);
+                    }
+                },
+                Some(JournalEntry::ExecutionPolicy(previous)) => self.execution_policy = *previous,
+                None => break,
+            }
+        }
+    }
+    
+    /// Records a change if a checkpoint is open
+    fn record(&mut self, entry: JournalEntry) {
+        if !self.checkpoints.is_empty() {
+            self.journal.push(entry);
+        }
+    }
+    
+    /// Sets or removes the contract at an address, returning the one it replaced
+    ///
+    /// Keeps the indexes of contracts by creator and by height in step with
+    /// the contracts themselves.
+    fn replace_contract(&mut self, address: String, contract: Option<Arc<ContractAccount>>) -> Option<Arc<ContractAccount>> {
+        let previous = match contract {
+            Some(contract) => self.contracts.insert(address.clone(), contract),
+            None => self.contracts.remove(&address),
+        };
+        
+        if let Some(previous) = &previous {
+            if let Some(contracts) = self.contracts_by_creator.get_mut(&previous.creator) {
+                contracts.remove(&(previous.deployed_at, address.clone()));
+                if contracts.is_empty() {
+                    self.contracts_by_creator.remove(&previous.creator);
+                }
+            }
+            if let Some(contracts) = self.contracts_by_height.get_mut(&previous.deployed_at) {
+                contracts.remove(&address);
+                if contracts.is_empty() {
+                    self.contracts_by_height.remove(&previous.deployed_at);
+                }
+            }
+        }
+        
+        if let Some(contract) = self.contracts.get(&address) {
+            self.contracts_by_creator
+                .entry(contract.creator.clone())
+                .or_default()
+                .insert((contract.deployed_at, address.clone()));
+            self.contracts_by_height.entry(contract.deployed_at).or_default().insert(address);
+        }
+        
+        previous
+    }
+    
+    /// Sets the balance of an account
+    fn set_balance(&mut self, address: &str, balance: u64) {
+        let previous = self.balances.insert(address.to_string(), balance);
+        self.record(JournalEntry::Balance { address: address.to_string(), previous });
+    }
+    
+    /// Sets the nonce of an account's next transaction
+    fn set_nonce(&mut self, address: &str, nonce: u64) {
+        let previous = self.nonces.insert(address.to_string(), nonce);
+        self.record(JournalEntry::Nonce { address: address.to_string(), previous });
+    }
+    
+    /// Adds to the balance of an account, failing if the balance would overflow
+    fn credit(&mut self, address: &str, amount: u64) -> Result<()> {
+        let balance = self.balance_of(address).checked_add(amount).ok_or_else(|| {
+            BlockchainError::StateError(format!("Crediting {} to {} overflows its balance", amount, address))
+        })?;
+        self.set_balance(address, balance);
+        Ok(())
+    }
+    
+    /// Subtracts from the balance of an account, failing if it doesn't cover the amount
+    fn debit(&mut self, address: &str, amount: u64) -> Result<()> {
+        let available = self.balance_of(address);
+        let balance = available.checked_sub(amount).ok_or_else(|| BlockchainError::InsufficientBalance {
+            address: address.to_string(),
+            required: amount,
+            available,
+        })?;
+        self.set_balance(address, balance);
+        Ok(())
+    }
+    
+    /// Gives back to an account an amount taken from balances earlier, a fee or a deposit
+    ///
+    /// Balances add up to at most the total supply, so returning part of
+    /// them can't overflow one.
+    fn restore(&mut self, address: &str, amount: u64) {
+        self.set_balance(address, self.balance_of(address).saturating_add(amount));
+    }
+    
+    /// Sets how much an account has locked in storage deposits
+    fn set_locked(&mut self, address: &str, locked: u64) {
+        let previous = if locked == 0 {
+            self.locked.remove(address)
+        } else {
+            self.locked.insert(address.to_string(), locked)
+        };
+        self.record(JournalEntry::Locked { address: address.to_string(), previous });
+    }
+    
+    /// Locks the deposit for a contract's code, or a slot of its storage,
+    /// from the sender of the executing transaction
+    ///
+    /// Nothing is locked outside of a transaction's execution. A sender that
+    /// can't cover the deposit fails the execution instead.
+    fn lock_deposit(&mut self, address: &str, slot: Option<Vec<u8>>, amount: u64) {
+        let Some(depositor) = &mut self.depositor else {
+            return;
+        };
+        if amount == 0 || depositor.shortfall.is_some() {
+            return;
         }
+        
+        let payer = depositor.payer.clone();
+        let balance = self.balances.get(&payer).copied().unwrap_or(0);
+        let available = balance.saturating_sub(depositor.reserved);
+        if available < amount {
+            depositor.shortfall = Some((amount, available));
+            return;
+        }
+        
+        // The amount is at most what's available, which is at most the balance
+        self.set_balance(&payer, balance - amount);
+        self.set_locked(&payer, self.locked_of(&payer).saturating_add(amount));
+        let deposits = Arc::make_mut(self.deposits.entry(address.to_string()).or_default());
+        let deposit = StorageDeposit { payer, amount };
+        let previous = match &slot {
+            Some(slot) => deposits.slots.insert(slot.clone(), deposit),
+            None => deposits.code.replace(deposit),
+        };
+        self.record(JournalEntry::Deposit { address: address.to_string(), slot, previous });
+    }
+    
+    /// Refunds the deposit locked for a slot of a contract's storage, if any
+    fn refund_slot_deposit(&mut self, address: &str, slot: &[u8]) {
+        let Some(deposits) = self.deposits.get_mut(address) else {
+            return;
+        };
+        let deposits = Arc::make_mut(deposits);
+        let Some(deposit) = deposits.slots.remove(slot) else {
+            return;
+        };
+        if deposits.is_empty() {
+            self.deposits.remove(address);
+        }
+        
+        self.record(JournalEntry::Deposit {
+            address: address.to_string(),
+            slot: Some(slot.to_vec()),
+            previous: Some(deposit.clone()),
+        });
+        self.refund(&deposit.payer, deposit.amount);
+    }
+    
+    /// Refunds every deposit locked for a contract's code and storage
+    fn refund_contract_deposits(&mut self, address: &str) {
+        let Some(deposits) = self.deposits.remove(address) else {
+            return;
+        };
+        
+        // Refunded once per payer, in address order
+        let mut refunds: BTreeMap<&str, u64> = BTreeMap::new();
+        for deposit in deposits.code.iter().chain(deposits.slots.values()) {
+            let refund = refunds.entry(deposit.payer.as_str()).or_default();
+            *refund = refund.saturating_add(deposit.amount);
+        }
+        for (payer, amount) in refunds {
+            self.refund(payer, amount);
+        }
+        self.record(JournalEntry::DepositsRemoved { address: address.to_string(), previous: deposits });
+    }
+    
+    /// Moves an amount an account had locked in deposits back to its balance
+    fn refund(&mut self, payer: &str, amount: u64) {
+        self.restore(payer, amount);
+        self.set_locked(payer, self.locked_of(payer).saturating_sub(amount));
     }
     
     /// Applies a block to the state
     pub fn apply_block(&mut self, block: &Block) -> Result<()> {
+        self.apply_block_with_executor(block, None)?;
+        Ok(())
+    }
+    
+    /// Applies a block to the state, running contract transactions through the executor
+    ///
+    /// Without an executor only the balance effects of contract transactions
+    /// are applied. Returns a receipt for every transaction in the block,
+    /// carrying the block's running gas total. A transaction's error carries
+    /// its index and ID, see `context`.
+    pub fn apply_block_with_executor(
+        &mut self,
+        block: &Block,
+        mut executor: Option<&mut dyn ContractExecutor>,
+    ) -> Result<Vec<Receipt>> {
+        let mut receipts = Vec::with_capacity(block.transactions.len());
+        let mut cumulative_gas_used = 0u64;
+        
         // Apply each transaction in the block
-        for tx in &block.transactions {
-            self.apply_transaction(tx)?;
+        for (index, tx) in block.transactions.iter().enumerate() {
+            let executor = executor.as_mut().map(|e| &mut **e as &mut dyn ContractExecutor);
+            let mut receipt = self
+                .apply_block_transaction(tx, block.header(), executor)
+                .with_ctx(ErrorContext::transaction(index, tx.id))?;
+            
+            cumulative_gas_used = cumulative_gas_used.saturating_add(receipt.gas_used);
+            receipt.cumulative_gas_used = cumulative_gas_used;
+            receipt.memo = tx.memo.clone();
+            receipts.push(receipt);
+        }
+        
+        // Proposals whose voting ended with the block are decided
+        self.tally_proposals(block.header().height);
+        
+        // Slashes whose dispute period ended with the block are applied
+        self.settle_slashes(block.header().height);
+        
+        Ok(receipts)
+    }
+    
+    /// Applies a transaction of a block, returning its receipt
+    fn apply_block_transaction(
+        &mut self,
+        tx: &Transaction,
+        header: &BlockHeader,
+        executor: Option<&mut dyn ContractExecutor>,
+    ) -> Result<Receipt> {
+        // Calls are either explicit or plain transactions sent to a deployed contract
+        let is_call = tx.tx_type == TransactionType::ContractCall
+            || self.contracts.contains_key(&tx.recipient);
+        
+        // The nonce is used up whether or not the transaction succeeds, as its fee is charged
+        self.check_nonce(tx)?;
+        self.use_nonce(tx);
+        
+        let receipt = match tx.tx_type {
+            TransactionType::ContractDeploy => self.apply_contract_deploy(tx, header, executor)?,
+            TransactionType::RegisterValidator | TransactionType::EditValidator => {
+                self.apply_validator_transaction(tx, header.height)?;
+                Receipt::new(tx.id, header.height)
+            }
+            TransactionType::SubmitProposal | TransactionType::Vote => {
+                self.apply_governance_transaction(tx, header.height)?;
+                Receipt::new(tx.id, header.height)
+            }
+            TransactionType::SubmitEvidence | TransactionType::CancelSlash => {
+                self.apply_slashing_transaction(tx, header.height)?;
+                Receipt::new(tx.id, header.height)
+            }
+            TransactionType::SetExecutionPolicy => self.apply_policy_update(tx, header.height)?,
+            _ if is_call => self.apply_contract_call(tx, header, executor)?,
+            _ => {
+                self.apply_transfer(tx)?;
+                Receipt::new(tx.id, header.height)
+            }
+        };
+        Ok(receipt)
+    }
+    
+    /// Applies a contract deployment transaction
+    fn apply_contract_deploy(
+        &mut self,
+        tx: &Transaction,
+        header: &BlockHeader,
+        executor: Option<&mut dyn ContractExecutor>,
+    ) -> Result<Receipt> {
+        // Deployments the policy denies make the block invalid
+        self.execution_policy
+            .check_deploy(&tx.sender)
+            .map_err(|violation| BlockchainError::PolicyDenied { sender: tx.sender.clone(), violation })?;
+        let max_fee = self.reserve_fee(tx, header)?;
+        
+        // The endowment only moves once the contract exists, so deposits can't use it
+        let outcome = self.execute_checkpointed(&tx.sender, tx.amount, |state| {
+            let outcome = match executor {
+                Some(executor) => executor.deploy(tx, header, state)?,
+                None => ExecutionOutcome {
+                    success: true,
+                    contract_address: Some(tx.contract_address()),
+                    ..Default::default()
+                },
+            };
+            
+            if let (true, Some(address)) = (outcome.success, &outcome.contract_address) {
+                state.transfer(&tx.sender, address, tx.amount)?;
+            }
+            Ok(outcome)
+        })?;
+        
+        // The fee is charged whether or not the deployment succeeds
+        self.settle_fee(tx, header, max_fee, outcome.gas_used);
+        
+        let contract_address = if outcome.success { outcome.contract_address } else { None };
+        
+        Ok(Receipt {
+            tx_id: tx.id,
+            block_height: header.height,
+            success: outcome.success,
+            gas_used: outcome.gas_used,
+            cumulative_gas_used: 0,
+            contract_address,
+            logs: outcome.logs,
+            revert_reason: outcome.revert_reason,
+            destroyed_contracts: outcome.destroyed_contracts,
+            memo: None,
+        })
+    }
+    
+    /// Applies a call to a deployed contract
+    ///
+    /// The fee is always charged. The value is moved before execution so the
+    /// contract can spend it, and moved back with everything else the call
+    /// changed if execution fails. A call the execution policy denies fails
+    /// without running, consuming its whole gas limit.
+    fn apply_contract_call(
+        &mut self,
+        tx: &Transaction,
+        header: &BlockHeader,
+        executor: Option<&mut dyn ContractExecutor>,
+    ) -> Result<Receipt> {
+        let max_fee = self.reserve_fee(tx, header)?;
+        
+        let outcome = match self.execution_policy.check_call(&tx.sender, &tx.recipient, header.height) {
+            Ok(()) => self.execute_checkpointed(&tx.sender, 0, |state| {
+                state.transfer(&tx.sender, &tx.recipient, tx.amount)?;
+                match executor {
+                    Some(executor) => executor.call(tx, header, state),
+                    None => Ok(ExecutionOutcome { success: true, ..Default::default() }),
+                }
+            })?,
+            Err(violation) => {
+                let denied = BlockchainError::PolicyDenied { sender: tx.sender.clone(), violation };
+                log::debug!("Call {} reverts: {}", tx.id, denied);
+                ExecutionOutcome {
+                    success: false,
+                    gas_used: tx.gas_limit,
+                    revert_reason: Some(denied.to_string()),
+                    ..Default::default()
+                }
+            }
+        };
+        
+        self.settle_fee(tx, header, max_fee, outcome.gas_used);
+        
+        // Calls can create contracts too, e.g. tokens made by the token factory
+        let contract_address = if outcome.success { outcome.contract_address } else { None };
+        
+        Ok(Receipt {
+            tx_id: tx.id,
+            block_height: header.height,
+            success: outcome.success,
+            gas_used: outcome.gas_used,
+            cumulative_gas_used: 0,
+            contract_address,
+            logs: outcome.logs,
+            revert_reason: outcome.revert_reason,
+            destroyed_contracts: outcome.destroyed_contracts,
+            memo: None,
+        })
+    }
+    
+    /// Runs a contract execution inside a checkpoint
+    ///
+    /// What the execution stores is paid for by `payer`, who must keep
+    /// `reserved` of its balance for the transaction's value. The execution's
+    /// changes are kept only if it succeeds and the payer covers its
+    /// deposits. A failed execution leaves no storage writes, transfers,
+    /// contracts, deposits or logs behind, and destroys no contracts.
+    fn execute_checkpointed(
+        &mut self,
+        payer: &str,
+        reserved: u64,
+        execute: impl FnOnce(&mut Self) -> Result<ExecutionOutcome>,
+    ) -> Result<ExecutionOutcome> {
+        self.checkpoint();
+        self.depositor = Some(Depositor { payer: payer.to_string(), reserved, shortfall: None });
+        let result = execute(self);
+        let shortfall = self.depositor.take().and_then(|depositor| {
+            let (required, available) = depositor.shortfall?;
+            Some(BlockchainError::InsufficientBalance { address: depositor.payer, required, available })
+        });
+        
+        match result {
+            Ok(outcome) if outcome.success && shortfall.is_none() => {
+                self.commit();
+                Ok(outcome)
+            }
+            Ok(outcome) => {
+                self.revert();
+                let revert_reason = match shortfall {
+                    Some(shortfall) if outcome.success => Some(format!("Storage deposit not covered: {}", shortfall)),
+                    _ => outcome.revert_reason,
+                };
+                Ok(ExecutionOutcome {
+                    success: false,
+                    logs: Vec::new(),
+                    revert_reason,
+                    destroyed_contracts: Vec::new(),
+                    ..outcome
+                })
+            }
+            Err(e) => {
+                self.revert();
+                Err(e)
+            }
         }
+    }
+    
+    /// Deducts the most a contract transaction can be charged, after checking
+    /// that its gas price covers the block's base fee and that the sender can
+    /// also cover its value and, for a deployment, the deposit for its code
+    /// (see `DepositRates::hold`)
+    ///
+    /// Returns the amount reserved, which `settle_fee` reconciles with the
+    /// gas actually used.
+    fn reserve_fee(&mut self, tx: &Transaction, header: &BlockHeader) -> Result<u64> {
+        if tx.gas_price < header.base_fee {
+            return Err(BlockchainError::GasPriceBelowBaseFee { gas_price: tx.gas_price, base_fee: header.base_fee });
+        }
+        
+        let max_fee = tx.max_fee();
+        let required = tx.amount.saturating_add(max_fee).saturating_add(self.deposit_rates.hold(tx));
         
+        let sender_balance = self.balance_of(&tx.sender);
+        if sender_balance < required {
+            return Err(BlockchainError::InsufficientBalance {
+                address: tx.sender.clone(),
+                required,
+                available: sender_balance,
+            });
+        }
+        
+        self.debit(&tx.sender, max_fee)?;
+        Ok(max_fee)
+    }
+    
+    /// Refunds the part of a reserved fee that the gas used didn't consume
+    ///
+    /// Of the fee charged, the base fee portion is burned and the tip is paid
+    /// to the payout address of the block's validator.
+    fn settle_fee(&mut self, tx: &Transaction, header: &BlockHeader, reserved: u64, gas_used: u64) {
+        let fee = tx.fee_for_gas(gas_used).min(reserved);
+        self.restore(&tx.sender, reserved - fee);
+        
+        let burned = gas_used.min(tx.gas_limit).saturating_mul(header.base_fee).min(fee);
+        self.burn(burned);
+        let payout_address = self.get_payout_address(&header.validator, header.height);
+        self.restore(&payout_address, fee - burned);
+    }
+    
+    /// Applies a validator registration or edit made at the given height
+    ///
+    /// The fee is charged only if the registry accepts the change.
+    fn apply_validator_transaction(&mut self, tx: &Transaction, height: u64) -> Result<()> {
+        let sender_balance = self.balance_of(&tx.sender);
+        if sender_balance < tx.fee {
+            return Err(BlockchainError::InsufficientBalance {
+                address: tx.sender.clone(),
+                required: tx.fee,
+                available: sender_balance,
+            });
+        }
+        
+        let data = tx.data.as_ref().map_or(&[][..], |data| &data.0[..]);
+        if tx.tx_type == TransactionType::RegisterValidator {
+            self.register(&tx.sender, ValidatorRegistration::from_data(data)?, height)?;
+        } else {
+            self.edit_validator(&tx.sender, ValidatorEdit::from_data(data)?, height)?;
+        }
+        
+        self.charge_fee(&tx.sender, tx.fee)?;
         Ok(())
     }
     
+    /// Applies a proposal submission or vote made at the given height
+    ///
+    /// A submission's deposit is taken from the sender along with the fee.
+    /// Only validators with stake may vote, and only while voting lasts.
+    fn apply_governance_transaction(&mut self, tx: &Transaction, height: u64) -> Result<()> {
+        let required = tx.amount.saturating_add(tx.fee);
+        let sender_balance = self.balance_of(&tx.sender);
+        if sender_balance < required {
+            return Err(BlockchainError::InsufficientBalance {
+                address: tx.sender.clone(),
+                required,
+                available: sender_balance,
+            });
+        }
+        
+        let data = tx.data.as_ref().map_or(&[][..], |data| &data.0[..]);
+        if tx.tx_type == TransactionType::SubmitProposal {
+            let id = self.proposals.keys().next_back().map_or(1, |last| last + 1);
+            let proposal = Proposal::new(id, tx.sender.clone(), ProposalSubmission::from_data(data)?, tx.amount, height)?;
+            self.set_proposal(proposal);
+        } else {
+            let vote = ProposalVote::from_data(data)?;
+            if self.stake_of(&tx.sender) == 0 {
+                return Err(BlockchainError::InvalidTransaction(format!("{} has no stake to vote with", tx.sender)));
+            }
+            let mut proposal = self
+                .proposals
+                .get(&vote.proposal_id)
+                .cloned()
+                .ok_or(BlockchainError::UnknownProposal { id: vote.proposal_id })?;
+            if proposal.status != ProposalStatus::Voting {
+                return Err(BlockchainError::InvalidTransaction(
+                    format!("Voting on proposal {} has ended", proposal.id)
+                ));
+            }
+            proposal.votes.insert(tx.sender.clone(), vote.approve);
+            self.set_proposal(proposal);
+        }
+        
+        self.debit(&tx.sender, tx.amount)?;
+        self.charge_fee(&tx.sender, tx.fee)?;
+        Ok(())
+    }
+    
+    /// Decides the proposals whose voting ends at a height, by the stakes validators hold then
+    ///
+    /// The deposit of a proposal that reached quorum is refunded to its
+    /// proposer; that of one that didn't is burned.
+    fn tally_proposals(&mut self, height: u64) {
+        let ending: Vec<_> = self
+            .proposals
+            .values()
+            .filter(|proposal| proposal.status == ProposalStatus::Voting && proposal.voting_ends_at <= height)
+            .cloned()
+            .collect();
+        if ending.is_empty() {
+            return;
+        }
+        
+        let total_stake = self.total_stake();
+        for mut proposal in ending {
+            let tally = proposal.tally(|voter| self.stake_of(voter), total_stake);
+            proposal.status = tally.outcome();
+            log::debug!(
+                "Proposal {} to set {} to {} is {:?}: {} of {} staked voted, {} approving",
+                proposal.id, proposal.parameter, proposal.value, proposal.status, tally.voting, tally.total, tally.approving
+            );
+            
+            if tally.reaches_quorum() {
+                self.restore(&proposal.proposer, proposal.deposit);
+            } else {
+                self.burn(proposal.deposit);
+            }
+            self.set_proposal(proposal);
+        }
+    }
+    
+    /// Applies evidence or a cancellation approval submitted at the given height
+    ///
+    /// Evidence must be within its window, about a registered validator with
+    /// stake, signed with its consensus key and about an offence not already
+    /// reported; the penalty is taken from the stake into the slash. Only
+    /// validators with stake may approve cancelling a slash, and only while
+    /// it's pending.
+    fn apply_slashing_transaction(&mut self, tx: &Transaction, height: u64) -> Result<()> {
+        let sender_balance = self.balance_of(&tx.sender);
+        if sender_balance < tx.fee {
+            return Err(BlockchainError::InsufficientBalance {
+                address: tx.sender.clone(),
+                required: tx.fee,
+                available: sender_balance,
+            });
+        }
+        
+        let data = tx.data.as_ref().map_or(&[][..], |data| &data.0[..]);
+        if tx.tx_type == TransactionType::SubmitEvidence {
+            let evidence = SlashingEvidence::from_data(data)?;
+            slashing::check_window(evidence.height(), height)?;
+            
+            let validator = evidence.validator();
+            let info = self
+                .validators
+                .get(validator)
+                .ok_or_else(|| BlockchainError::UnknownValidator { address: validator.to_string() })?;
+            evidence.verify(&info.consensus_key)?;
+            if self.slashes.values().any(|slash| slash.validator == validator && slash.height == evidence.height()) {
+                return Err(BlockchainError::InvalidTransaction(
+                    format!("{} was already reported for height {}", validator, evidence.height())
+                ));
+            }
+            
+            let stake = self.stake_of(validator);
+            let penalty = slashing::penalty(stake);
+            if penalty == 0 {
+                return Err(BlockchainError::InvalidTransaction(format!("{} has no stake to slash", validator)));
+            }
+            
+            let id = self.slashes.keys().next_back().map_or(1, |last| last + 1);
+            let slash = Slash::new(id, tx.sender.clone(), &evidence, penalty, height);
+            log::info!(
+                "Slash {} escrows {} of {} for signing conflicting blocks at height {} until height {}",
+                id, penalty, validator, slash.height, slash.applies_at
+            );
+            self.set_stake(validator.to_string(), stake - penalty);
+            self.set_slash(slash);
+        } else {
+            let cancellation = SlashCancellation::from_data(data)?;
+            if self.stake_of(&tx.sender) == 0 {
+                return Err(BlockchainError::InvalidTransaction(
+                    format!("{} has no stake to approve cancelling with", tx.sender)
+                ));
+            }
+            let mut slash = self
+                .slashes
+                .get(&cancellation.slash_id)
+                .cloned()
+                .ok_or(BlockchainError::UnknownSlash { id: cancellation.slash_id })?;
+            if slash.status != SlashStatus::Pending {
+                return Err(BlockchainError::InvalidTransaction(format!("Slash {} is no longer pending", slash.id)));
+            }
+            
+            slash.approvals.insert(tx.sender.clone());
+            if slash.is_cancelled_by(|validator| self.stake_of(validator), self.total_stake()) {
+                log::info!("Slash {} of {} is cancelled, returning {} to its stake", slash.id, slash.validator, slash.penalty);
+                self.set_stake(slash.validator.clone(), self.stake_of(&slash.validator).saturating_add(slash.penalty));
+                slash.status = SlashStatus::Cancelled;
+            }
+            self.set_slash(slash);
+        }
+        
+        self.charge_fee(&tx.sender, tx.fee)?;
+        Ok(())
+    }
+    
+    /// Burns the penalties of the pending slashes whose dispute period ends at a height
+    fn settle_slashes(&mut self, height: u64) {
+        let ending: Vec<_> = self
+            .slashes
+            .values()
+            .filter(|slash| slash.status == SlashStatus::Pending && slash.applies_at <= height)
+            .cloned()
+            .collect();
+        for mut slash in ending {
+            log::info!("Slash {} of {} is applied, burning {}", slash.id, slash.validator, slash.penalty);
+            self.burn(slash.penalty);
+            slash.status = SlashStatus::Applied;
+            self.set_slash(slash);
+        }
+    }
+    
+    /// Applies a change to the execution policy made at the given height
+    ///
+    /// The sender must be allowed to make the change, see
+    /// `ExecutionPolicy::check_update`. The receipt logs the change.
+    fn apply_policy_update(&mut self, tx: &Transaction, height: u64) -> Result<Receipt> {
+        let sender_balance = self.balance_of(&tx.sender);
+        if sender_balance < tx.fee {
+            return Err(BlockchainError::InsufficientBalance {
+                address: tx.sender.clone(),
+                required: tx.fee,
+                available: sender_balance,
+            });
+        }
+        
+        let update = PolicyUpdate::from_data(tx.data.as_ref().map_or(&[][..], |data| &data.0[..]))?;
+        self.check_policy_update(&tx.sender, &update, height)?;
+        log::info!("{} changes the execution policy at height {}: {:?}", tx.sender, height, update);
+        let mut policy = self.execution_policy.clone();
+        policy.apply(&update);
+        self.set_execution_policy(policy);
+        self.charge_fee(&tx.sender, tx.fee)?;
+        
+        let mut receipt = Receipt::new(tx.id, height);
+        receipt.logs.push(update.log()?);
+        Ok(receipt)
+    }
+    
+    /// Checks an address may make a change to the execution policy at a height
+    fn check_policy_update(&self, sender: &str, update: &PolicyUpdate, height: u64) -> Result<()> {
+        let creator = match update {
+            PolicyUpdate::SetCallers { contract, .. } => self.contracts.get(contract).map(|contract| contract.creator.as_str()),
+            _ => None,
+        };
+        self.execution_policy.check_update(sender, update, creator, height)
+    }
+    
+    /// Stores a new or updated slash
+    fn set_slash(&mut self, slash: Slash) {
+        let id = slash.id;
+        let previous = self.slashes.insert(id, slash);
+        self.record(JournalEntry::Slash { id, previous });
+    }
+    
+    /// Gets the stake of all validators
+    fn total_stake(&self) -> u64 {
+        self.validator_stakes.values().fold(0u64, |total, stake| total.saturating_add(*stake))
+    }
+    
+    /// Takes a flat fee from a sender and burns it
+    ///
+    /// Flat fees go to no one: only gas fees pay a tip, see `settle_fee`.
+    fn charge_fee(&mut self, sender: &str, fee: u64) -> Result<()> {
+        self.debit(sender, fee)?;
+        self.burn(fee);
+        Ok(())
+    }
+    
+    /// Takes an amount already debited out of the supply, counting it as burned
+    fn burn(&mut self, amount: u64) {
+        self.record(JournalEntry::TotalSupply(self.total_supply));
+        self.record(JournalEntry::TotalBurned(self.total_burned));
+        self.total_supply = self.total_supply.saturating_sub(amount);
+        self.total_burned = self.total_burned.saturating_add(amount);
+    }
+    
+    /// Stores a new or updated proposal
+    fn set_proposal(&mut self, proposal: Proposal) {
+        let id = proposal.id;
+        let previous = self.proposals.insert(id, proposal);
+        self.record(JournalEntry::Proposal { id, previous });
+    }
+    
     /// Applies a transaction to the state
+    ///
+    /// Fails unless the transaction has its sender's next nonce, see
+    /// `get_nonce`, which it then uses up. Coinbase transactions have no nonce.
     pub fn apply_transaction(&mut self, tx: &Transaction) -> Result<()> {
+        // Deployments, validator, governance and slashing transactions need the block context, see `apply_block_with_executor`
+        if matches!(
+            tx.tx_type,
+            TransactionType::ContractDeploy
+                | TransactionType::RegisterValidator
+                | TransactionType::EditValidator
+                | TransactionType::SubmitProposal
+                | TransactionType::Vote
+                | TransactionType::SubmitEvidence
+                | TransactionType::CancelSlash
+                | TransactionType::SetExecutionPolicy
+        ) {
+            return Err(BlockchainError::InvalidTransaction(
+                format!("{:?} transactions must be applied as part of a block", tx.tx_type)
+            ));
+        }
+        
+        self.check_nonce(tx)?;
+        self.apply_transfer(tx)?;
+        self.use_nonce(tx);
+        Ok(())
+    }
+    
+    /// Applies the balance effects of a transfer, changing nothing if it fails
+    fn apply_transfer(&mut self, tx: &Transaction) -> Result<()> {
         // Handle coinbase transactions differently
-        if tx.sender == "COINBASE" {
+        if tx.sender == COINBASE {
             // Coinbase transactions mint new tokens
-            *self.balances.entry(tx.recipient.clone()).or_insert(0) += tx.amount;
-            self.total_supply += tx.amount;
+            let total_supply = self.total_supply.checked_add(tx.amount).ok_or_else(|| {
+                BlockchainError::InvalidTransaction(format!("Minting {} overflows the total supply", tx.amount))
+            })?;
+            self.credit(&tx.recipient, tx.amount)?;
+            self.record(JournalEntry::TotalSupply(self.total_supply));
+            self.total_supply = total_supply;
             return Ok(());
         }
         
         // Check that the sender has sufficient balance
-        let sender_balance = self.get_balance(&tx.sender);
-        if sender_balance < tx.amount + tx.fee {
-            return Err(BlockchainError::InvalidTransaction(
-                format!("Insufficient balance: {} < {}", sender_balance, tx.amount + tx.fee)
-            ));
+        let required = tx.amount.checked_add(tx.fee).ok_or_else(|| {
+            BlockchainError::InvalidTransaction(format!("Amount of {} plus fee of {} overflows", tx.amount, tx.fee))
+        })?;
+        let sender_balance = self.balance_of(&tx.sender);
+        if sender_balance < required {
+            return Err(BlockchainError::InsufficientBalance {
+                address: tx.sender.clone(),
+                required,
+                available: sender_balance,
+            });
         }
         
-        // Update sender's balance
-        *self.balances.entry(tx.sender.clone()).or_insert(0) -= tx.amount + tx.fee;
+        // Update sender's balance, burning the fee
+        self.debit(&tx.sender, tx.amount)?;
+        self.charge_fee(&tx.sender, tx.fee)?;
         
         // Update recipient's balance
-        *self.balances.entry(tx.recipient.clone()).or_insert(0) += tx.amount;
+        self.credit(&tx.recipient, tx.amount)?;
         
-        // If there's a data payload, this might be a smart contract interaction
-        if let Some(data) = &tx.data {
-            // In a real implementation, this would execute the smart contract
-            // For now, we'll just store the data in the contract state
-            if !data.is_empty() {
-                self.contract_states.insert(tx.recipient.clone(), data.clone());
-            }
+        Ok(())
+    }
+    
+    /// Checks that a transaction has its sender's next nonce
+    fn check_nonce(&self, tx: &Transaction) -> Result<()> {
+        let expected = self.nonce_of(&tx.sender);
+        if tx.sender != COINBASE && tx.nonce != expected {
+            return Err(BlockchainError::InvalidNonce { address: tx.sender.clone(), expected, got: tx.nonce });
         }
-        
         Ok(())
     }
     
+    /// Moves the sender of a transaction on to its next nonce
+    fn use_nonce(&mut self, tx: &Transaction) {
+        if tx.sender != COINBASE {
+            self.set_nonce(&tx.sender, tx.nonce + 1);
+        }
+    }
+    
+    /// Gets the nonce an account's next transaction must have
+    ///
+    /// That's the number of transactions the account sent that the chain
+    /// applied, as each uses up the nonce it has.
+    pub fn get_nonce(&self, address: &Address) -> u64 {
+        self.nonce_of(address)
+    }
+    
+    pub(crate) fn nonce_of(&self, address: &str) -> u64 {
+        self.nonces.get(address).copied().unwrap_or(0)
+    }
+    
     /// Gets the balance of an account
-    pub fn get_balance(&self, address: &str) -> u64 {
+    ///
+    /// Amounts locked in storage deposits aren't part of it, see `get_locked_balance`.
+    pub fn get_balance(&self, address: &Address) -> Amount {
+        Amount::from_base_units(self.balance_of(address))
+    }
+    
+    /// Gets the balance of an unchecked address, in base units
+    #[deprecated(note = "use `State::get_balance` with an `Address`")]
+    pub fn get_balance_untyped(&self, address: &str) -> u64 {
+        self.balance_of(address)
+    }
+    
+    pub(crate) fn balance_of(&self, address: &str) -> u64 {
         *self.balances.get(address).unwrap_or(&0)
     }
     
+    /// Gets how much an account has locked in deposits for contract storage
+    pub fn get_locked_balance(&self, address: &Address) -> Amount {
+        Amount::from_base_units(self.locked_of(address))
+    }
+    
+    /// Gets how much an unchecked address has locked in deposits, in base units
+    #[deprecated(note = "use `State::get_locked_balance` with an `Address`")]
+    pub fn get_locked_balance_untyped(&self, address: &str) -> u64 {
+        self.locked_of(address)
+    }
+    
+    fn locked_of(&self, address: &str) -> u64 {
+        self.locked.get(address).copied().unwrap_or(0)
+    }
+    
+    /// Gets the deposits locked for a contract's code and storage
+    pub fn get_contract_deposits(&self, address: &str) -> Option<&ContractDeposits> {
+        self.deposits.get(address).map(|deposits| deposits.as_ref())
+    }
+    
+    /// Gets the rates of the deposits locked for contract storage
+    pub fn deposit_rates(&self) -> DepositRates {
+        self.deposit_rates
+    }
+    
+    /// Moves funds between two accounts
+    pub fn transfer(&mut self, from: &str, to: &str, amount: u64) -> Result<()> {
+        let balance = self.balance_of(from);
+        if balance < amount {
+            return Err(BlockchainError::InsufficientBalance {
+                address: from.to_string(),
+                required: amount,
+                available: balance,
+            });
+        }
+        
+        self.debit(from, amount)?;
+        self.credit(to, amount)?;
+        Ok(())
+    }
+    
+    /// Gets the contract deployed at an address
+    pub fn get_contract(&self, address: &str) -> Option<&Arc<ContractAccount>> {
+        self.contracts.get(address)
+    }
+    
+    /// Checks whether a contract is deployed at an address
+    pub fn is_contract(&self, address: &str) -> bool {
+        self.contracts.contains_key(address)
+    }
+    
+    /// Gets all deployed contracts
+    #[deprecated(note = "copies every contract; use `list_contracts` or `contracts_by_creator` to page through them")]
+    pub fn get_contracts(&self) -> &HashMap<String, Arc<ContractAccount>> {
+        &self.contracts
+    }
+    
+    /// Lists the contracts deployed by an account, see `StateAccess::contracts_by_creator`
+    pub fn contracts_by_creator(&self, creator: &str, start: Option<(u64, &str)>, limit: usize) -> Vec<(u64, String)> {
+        let Some(contracts) = self.contracts_by_creator.get(creator) else {
+            return Vec::new();
+        };
+        
+        let start = start.map_or((0, String::new()), |(height, address)| (height, address.to_string()));
+        contracts.range(start..).take(limit).cloned().collect()
+    }
+    
+    /// Lists all deployed contracts, see `StateAccess::list_contracts`
+    pub fn list_contracts(&self, start: Option<(u64, &str)>, limit: usize) -> Vec<(u64, String)> {
+        let (start_height, start_address) = start.unwrap_or((0, ""));
+        
+        self.contracts_by_height
+            .range(start_height..)
+            .flat_map(|(&height, contracts)| {
+                contracts
+                    .iter()
+                    .filter(move |address| height > start_height || address.as_str() >= start_address)
+                    .map(move |address| (height, address.clone()))
+            })
+            .take(limit)
+            .collect()
+    }
+    
+    /// Computes a hash committing to the storage of a contract
+    ///
+    /// Slots are hashed in key order, so equal storage always has the same root.
+    pub fn storage_root(&self, address: &str) -> Result<Hash> {
+        let mut slots: Vec<_> = self
+            .contract_storage
+            .get(address)
+            .map(|storage| storage.iter().collect())
+            .unwrap_or_default();
+        slots.sort();
+        
+        hashing::hash_json_in(HashDomain::StorageRoot, &slots)
+    }
+    
+    /// Gets the storage of a contract
+    pub fn get_contract_storage(&self, address: &str) -> Option<&ContractStorage> {
+        self.contract_storage.get(address).map(|storage| storage.as_ref())
+    }
+    
+    /// Gets the current value of a part of the state tracked by diffs
+    pub(crate) fn value_of(&self, key: &StateKey) -> StateValue {
+        match key {
+            StateKey::Balance(address) => StateValue::Amount(self.balance_of(address)),
+            StateKey::Stake(validator) => StateValue::Amount(self.stake_of(validator)),
+            StateKey::Storage(address, slot) => StateValue::Slot(
+                self.get_contract_storage(address).and_then(|storage| storage.get(slot)).cloned(),
+            ),
+        }
+    }
+    
     /// Gets the total supply of GENX tokens
     pub fn get_total_supply(&self) -> u64 {
         self.total_supply
     }
     
+    /// Gets the total GENX burned, by fees, forfeited governance deposits and slashes
+    pub fn get_total_burned(&self) -> u64 {
+        self.total_burned
+    }
+    
     /// Gets the stake of a validator
-    pub fn get_validator_stake(&self, validator: &str) -> u64 {
+    pub fn get_validator_stake(&self, validator: &Address) -> Amount {
+        Amount::from_base_units(self.stake_of(validator))
+    }
+    
+    /// Gets the stake of a validator at an unchecked address, in base units
+    #[deprecated(note = "use `State::get_validator_stake` with an `Address`")]
+    pub fn get_validator_stake_untyped(&self, validator: &str) -> u64 {
+        self.stake_of(validator)
+    }
+    
+    fn stake_of(&self, validator: &str) -> u64 {
         *self.validator_stakes.get(validator).unwrap_or(&0)
     }
     
-    /// Gets all validators and their stakes
-    pub fn get_validators(&self) -> &HashMap<String, u64> {
-        &self.validator_stakes
+    /// Gets the registered validators and their stakes, ordered by operator address
+    pub fn get_validators(&self) -> Vec<(&ValidatorInfo, u64)> {
+        let mut validators: Vec<_> = self
+            .validators
+            .values()
+            .map(|validator| (validator, self.stake_of(&validator.operator)))
+            .collect();
+        validators.sort_by(|a, b| a.0.operator.cmp(&b.0.operator));
+        validators
+    }
+    
+    /// Gets a page of the registered validators and their stakes
+    ///
+    /// A validator whose stake changes while a client pages by stake moves
+    /// in the listing, and may be returned twice or not at all.
+    pub fn list_validators(&self, sort: ValidatorSort, request: &PageRequest) -> Result<Page<(&ValidatorInfo, u64)>> {
+        let validators = self
+            .validators
+            .values()
+            .map(|validator| (validator, self.stake_of(&validator.operator)));
+        paging::paginate(validators, sort.listing(), request, sort.default_order(), |(validator, stake)| {
+            sort.key(&validator.operator, *stake)
+        })
+    }
+    
+    /// Gets a registered validator by its operator address
+    pub fn get_validator(&self, operator: &str) -> Option<&ValidatorInfo> {
+        self.validators.get(operator)
+    }
+    
+    /// Gets the address the block at a height pays a validator's rewards to
+    ///
+    /// That's the validator's payout address if it's registered, or else the
+    /// address itself.
+    pub fn get_payout_address(&self, validator: &str, height: u64) -> String {
+        self.validators
+            .get(validator)
+            .map_or(validator, |info| info.payout_address_at(height))
+            .to_string()
+    }
+    
+    /// Registers a validator operated by the given address
+    ///
+    /// Fails if the operator is already registered or another validator
+    /// signs with the same consensus key.
+    pub fn register_validator(&mut self, operator: &Address, registration: ValidatorRegistration, height: u64) -> Result<()> {
+        self.register(operator, registration, height)
+    }
+    
+    /// Registers a validator operated by an unchecked address
+    #[deprecated(note = "use `State::register_validator` with an `Address`")]
+    pub fn register_validator_untyped(&mut self, operator: &str, registration: ValidatorRegistration, height: u64) -> Result<()> {
+        self.register(operator, registration, height)
+    }
+    
+    fn register(&mut self, operator: &str, registration: ValidatorRegistration, height: u64) -> Result<()> {
+        if self.validators.contains_key(operator) {
+            return Err(BlockchainError::ValidatorExists { address: operator.to_string() });
+        }
+        if self.validators.values().any(|validator| validator.consensus_key == registration.consensus_key) {
+            return Err(BlockchainError::InvalidTransaction(
+                format!("Consensus key {} is already in use", registration.consensus_key)
+            ));
+        }
+        
+        let validator = ValidatorInfo::new(operator.to_string(), registration, height);
+        self.validators.insert(operator.to_string(), validator);
+        self.record(JournalEntry::Validator { operator: operator.to_string(), previous: None });
+        Ok(())
+    }
+    
+    /// Edits the metadata of the validator operated by the given address
+    pub fn edit_validator(&mut self, operator: &str, edit: ValidatorEdit, height: u64) -> Result<()> {
+        let validator = self.validators.get_mut(operator).ok_or_else(|| {
+            BlockchainError::UnknownValidator { address: operator.to_string() }
+        })?;
+        
+        let previous = validator.clone();
+        validator.apply_edit(edit, height)?;
+        self.record(JournalEntry::Validator { operator: operator.to_string(), previous: Some(previous) });
+        Ok(())
+    }
+    
+    /// Gets a governance proposal by its ID
+    pub fn get_proposal(&self, id: u64) -> Option<&Proposal> {
+        self.proposals.get(&id)
+    }
+    
+    /// Gets the governance proposals, ordered by ID
+    pub fn get_proposals(&self) -> impl Iterator<Item = &Proposal> + '_ {
+        self.proposals.values()
+    }
+    
+    /// Gets a page of the governance proposals, keyed by ID and oldest first by default
+    pub fn list_proposals(&self, request: &PageRequest) -> Result<Page<&Proposal>> {
+        const LISTING: &str = "proposals";
+        let order = request.order_or(SortOrder::Ascending);
+        let after: Option<u64> = request.start_after(LISTING)?;
+        let range = match (order, after) {
+            (SortOrder::Ascending, Some(id)) => self.proposals.range(id.saturating_add(1)..),
+            (SortOrder::Descending, Some(id)) => self.proposals.range(..id),
+            (_, None) => self.proposals.range(..),
+        };
+        let entries: Box<dyn Iterator<Item = (&u64, &Proposal)>> = match order {
+            SortOrder::Ascending => Box::new(range),
+            SortOrder::Descending => Box::new(range.rev()),
+        };
+        Ok(Page::from_sorted(entries, LISTING, request.page_limit(), Some(self.proposals.len() as u64)))
+    }
+    
+    /// Gets the value governance set a parameter to for the block at a height, if it did
+    ///
+    /// That's the value of the passed proposal activating last at or before
+    /// the height, or of the last submitted of those activating together.
+    pub fn governed_value(&self, parameter: GovernedParameter, height: u64) -> Option<u64> {
+        self.proposals
+            .values()
+            .filter(|proposal| {
+                proposal.status == ProposalStatus::Passed
+                    && proposal.parameter == parameter
+                    && proposal.activation_height <= height
+            })
+            .max_by_key(|proposal| (proposal.activation_height, proposal.id))
+            .map(|proposal| proposal.value)
+    }
+    
+    /// Gets a slash by ID
+    pub fn get_slash(&self, id: u64) -> Option<&Slash> {
+        self.slashes.get(&id)
+    }
+    
+    /// Gets the slashes, pending or not, ordered by ID
+    pub fn get_slashes(&self) -> impl Iterator<Item = &Slash> + '_ {
+        self.slashes.values()
+    }
+    
+    /// Gets who may deploy and call contracts
+    pub fn get_execution_policy(&self) -> &ExecutionPolicy {
+        &self.execution_policy
+    }
+    
+    /// Replaces the execution policy, as when configuring a permissioned network's genesis state
+    ///
+    /// Once the chain runs, the policy changes with `SetExecutionPolicy`
+    /// transactions instead, so every node makes the same changes.
+    pub fn set_execution_policy(&mut self, policy: ExecutionPolicy) {
+        let previous = std::mem::replace(&mut self.execution_policy, policy);
+        self.record(JournalEntry::ExecutionPolicy(Box::new(previous)));
+    }
+    
+    /// Checks the execution policy allows a transaction in the block at a height
+    ///
+    /// Deployments need their sender on the deployment allowlist, calls to
+    /// be allowed to reach their contract and policy updates to be their
+    /// sender's to make. For admitting transactions to the mempool; blocks
+    /// are checked as they're applied.
+    pub fn check_execution_policy(&self, tx: &Transaction, height: u64) -> Result<()> {
+        let denied = |violation| BlockchainError::PolicyDenied { sender: tx.sender.clone(), violation };
+        match tx.tx_type {
+            TransactionType::ContractDeploy => self.execution_policy.check_deploy(&tx.sender).map_err(denied),
+            TransactionType::SetExecutionPolicy => {
+                let update = PolicyUpdate::from_data(tx.data.as_ref().map_or(&[][..], |data| &data.0[..]))?;
+                self.check_policy_update(&tx.sender, &update, height)
+            }
+            _ if tx.tx_type == TransactionType::ContractCall || self.contracts.contains_key(&tx.recipient) => {
+                self.execution_policy.check_call(&tx.sender, &tx.recipient, height).map_err(denied)
+            }
+            _ => Ok(()),
+        }
     }
     
     /// Adds or updates a validator's stake
-    pub fn update_validator_stake(&mut self, validator: String, stake: u64) {
-        self.validator_stakes.insert(validator, stake);
+    pub fn update_validator_stake(&mut self, validator: Address, stake: Amount) {
+        self.set_stake(validator.into_string(), stake.base_units());
+    }
+    
+    /// Adds or updates the stake of a validator at an unchecked address, in base units
+    #[deprecated(note = "use `State::update_validator_stake` with an `Address` and an `Amount`")]
+    pub fn update_validator_stake_untyped(&mut self, validator: String, stake: u64) {
+        self.set_stake(validator, stake);
+    }
+    
+    /// Sets an account's balance outside of any transaction, minting or burning the difference
+    ///
+    /// For development chains, such as a devnet's `dev_setBalance`: nothing
+    /// on chain records the change. The total supply follows, so supply
+    /// accounting still adds up.
+    pub fn override_balance(&mut self, address: &Address, balance: Amount) {
+        let (previous, balance) = (self.balance_of(address.as_str()), balance.base_units());
+        self.record(JournalEntry::TotalSupply(self.total_supply));
+        self.total_supply = (self.total_supply + balance).saturating_sub(previous);
+        self.set_balance(address.as_str(), balance);
+    }
+    
+    fn set_stake(&mut self, validator: String, stake: u64) {
+        let previous = self.validator_stakes.insert(validator.clone(), stake);
+        self.record(JournalEntry::ValidatorStake { validator, previous });
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateAccess for State {
+    fn get_balance(&self, address: &str) -> u64 {
+        self.balance_of(address)
+    }
+    
+    fn transfer(&mut self, from: &str, to: &str, amount: u64) -> Result<()> {
+        State::transfer(self, from, to, amount)
+    }
+    
+    fn get_contract(&self, address: &str) -> Option<&Arc<ContractAccount>> {
+        State::get_contract(self, address)
+    }
+    
+    fn insert_contract(&mut self, address: &str, contract: ContractAccount) {
+        let deposit = self.deposit_rates.code(contract.code.len());
+        let previous = self.replace_contract(address.to_string(), Some(Arc::new(contract)));
+        self.record(JournalEntry::Contract { address: address.to_string(), previous });
+        self.lock_deposit(address, None, deposit);
+    }
+    
+    fn remove_contract(&mut self, address: &str) -> Option<Arc<ContractAccount>> {
+        let previous = self.replace_contract(address.to_string(), None)?;
+        self.record(JournalEntry::Contract { address: address.to_string(), previous: Some(previous.clone()) });
+        
+        if let Some(storage) = self.contract_storage.remove(address) {
+            self.record(JournalEntry::StorageRemoved { address: address.to_string(), previous: storage });
+        }
+        self.refund_contract_deposits(address);
+        Some(previous)
     }
+    
+    fn contracts_by_creator(&self, creator: &str, start: Option<(u64, &str)>, limit: usize) -> Vec<(u64, String)> {
+        State::contracts_by_creator(self, creator, start, limit)
+    }
+    
+    fn list_contracts(&self, start: Option<(u64, &str)>, limit: usize) -> Vec<(u64, String)> {
+        State::list_contracts(self, start, limit)
+    }
+    
+    fn get_storage(&self, address: &str, key: &[u8]) -> Option<&[u8]> {
+        self.contract_storage
+            .get(address)
+            .and_then(|storage| storage.get(key))
+            .map(|value| value.as_slice())
+    }
+    
+    fn get_contract_storage(&self, address: &str) -> Option<&ContractStorage> {
+        State::get_contract_storage(self, address)
+    }
+    
+    fn set_storage(&mut self, address: &str, key: Vec<u8>, value: Option<Vec<u8>>) {
+        let storage = Arc::make_mut(self.contract_storage.entry(address.to_string()).or_default());
+        let previous = match value {
+            Some(value) => storage.insert(key.clone(), value),
+            None => storage.remove(&key),
+        };
+        match (previous.is_some(), storage.contains_key(&key)) {
+            (false, true) => self.lock_deposit(address, Some(key.clone()), self.deposit_rates.per_slot),
+            (true, false) => self.refund_slot_deposit(address, &key),
+            _ => {}
+        }
+        self.record(JournalEntry::Storage { address: address.to_string(), key, previous });
+    }
+    
+    fn checkpoint(&mut self) {
+        State::checkpoint(self)
+    }
+    
+    fn revert(&mut self) {
+        State::revert(self)
+    }
+    
+    fn commit(&mut self) {
+        State::commit(self)
+    }
+}
+
+/// The state as of a block, shared read-only between threads
+///
+/// Published by the blockchain after every block it adds or rolls back, so
+/// queries can read a consistent state without waiting for block application.
+#[derive(Debug, Clone)]
+pub struct StateSnapshot {
+    /// Height of the block the snapshot was taken at
+    pub block_height: u64,
+    
+    /// Timestamp of the block the snapshot was taken at
+    pub block_timestamp: u64,
+    
+    /// The state after the block
+    pub state: Arc<State>,
+}
+
+/// Puts back the value a map held before a journaled change
+fn restore<K: std::hash::Hash + Eq, V>(map: &mut HashMap<K, V>, key: K, previous: Option<V>) {
+    match previous {
+        Some(value) => map.insert(key, value),
+        None => map.remove(&key),
+    };
 }
\ No newline at end of file
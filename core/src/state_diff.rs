@@ -0,0 +1,211 @@
+//! Differences between the states at two heights
+//!
+//! A diff is computed from the undo records the chain keeps for its recent
+//! blocks rather than by replaying them: the first change to a part of the
+//! state after a height recorded the value it had at that height. Only the
+//! last `MAX_ROLLBACK_DEPTH` blocks keep undo records, so only heights in
+//! that window can be compared.
+//!
+//! Balances, validator stakes and contract storage slots are tracked. A part
+//! of the state that was changed and then changed back is left out.
+//!
+//! The balances a single block changed are read from its undo record the
+//! same way, for chain listeners (see `ChainListener::balances_changed`).
+
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::{BlockUndo, State};
+use crate::Bytes;
+
+/// A part of the state tracked by diffs, in the order diffs list them
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum StateKey {
+    Balance(String),
+    Stake(String),
+    Storage(String, Vec<u8>),
+}
+
+impl StateKey {
+    /// Gets the account, validator or contract the part belongs to
+    fn address(&self) -> &str {
+        match self {
+            StateKey::Balance(address) | StateKey::Stake(address) | StateKey::Storage(address, _) => address,
+        }
+    }
+}
+
+/// The value of a part of the state; missing balances and stakes are 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum StateValue {
+    Amount(u64),
+    Slot(Option<Vec<u8>>),
+}
+
+impl StateValue {
+    fn amount(self) -> u64 {
+        match self {
+            StateValue::Amount(amount) => amount,
+            StateValue::Slot(_) => 0,
+        }
+    }
+    
+    fn slot(self) -> Option<Bytes> {
+        match self {
+            StateValue::Slot(value) => value.map(Bytes),
+            StateValue::Amount(_) => None,
+        }
+    }
+}
+
+/// A change to one part of the state between two heights
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StateChange {
+    /// An account's balance changed
+    Balance { address: String, old: u64, new: u64 },
+    
+    /// A validator's stake changed
+    Stake { validator: String, old: u64, new: u64 },
+    
+    /// A contract storage slot was written; `None` means the slot was empty
+    Storage { address: String, slot: Bytes, old: Option<Bytes>, new: Option<Bytes> },
+}
+
+impl StateChange {
+    /// Gets the account, validator or contract that changed
+    pub fn address(&self) -> &str {
+        match self {
+            StateChange::Balance { address, .. } | StateChange::Storage { address, .. } => address,
+            StateChange::Stake { validator, .. } => validator,
+        }
+    }
+    
+    /// Creates the change between two values of a part of the state, or `None` if they're equal
+    fn new(key: StateKey, old: StateValue, new: StateValue) -> Option<Self> {
+        if old == new {
+            return None;
+        }
+        
+        Some(match key {
+            StateKey::Balance(address) => StateChange::Balance { address, old: old.amount(), new: new.amount() },
+            StateKey::Stake(validator) => StateChange::Stake { validator, old: old.amount(), new: new.amount() },
+            StateKey::Storage(address, slot) => StateChange::Storage {
+                address,
+                slot: Bytes(slot),
+                old: old.slot(),
+                new: new.slot(),
+            },
+        })
+    }
+}
+
+/// How a block changed an account's balance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    /// Account whose balance changed
+    pub address: String,
+    
+    /// Balance before the block
+    pub old: u64,
+    
+    /// Balance after the block
+    pub new: u64,
+}
+
+impl BalanceChange {
+    /// Gets the change rolling the block back makes
+    pub fn reversed(&self) -> Self {
+        Self { address: self.address.clone(), old: self.new, new: self.old }
+    }
+}
+
+/// The changes from the state after one block to the state after another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiff {
+    /// Height of the block the old values are taken after
+    pub from_height: u64,
+    
+    /// Height of the block the new values are taken after
+    pub to_height: u64,
+    
+    /// Balance changes by address, then stake changes by validator, then
+    /// storage changes by contract and slot
+    pub changes: Vec<StateChange>,
+}
+
+/// Iterates over the changes between two heights in the order of `StateDiff::changes`
+///
+/// Created by `Blockchain::state_changes`. Only the parts of the state that
+/// were touched are collected up front; each change is built as it's reached,
+/// so large ranges can be read a page at a time.
+#[derive(Debug)]
+pub struct StateChanges {
+    /// Touched parts of the state with their values at the first height
+    old: btree_map::IntoIter<StateKey, StateValue>,
+    
+    /// Values at the second height of the parts changed after it
+    later: BTreeMap<StateKey, StateValue>,
+    
+    /// The latest state, holding the values of the parts not changed after the second height
+    latest: Arc<State>,
+}
+
+impl StateChanges {
+    /// Creates the iterator from the touched parts of the state
+    pub(crate) fn new(
+        old: BTreeMap<StateKey, StateValue>,
+        later: BTreeMap<StateKey, StateValue>,
+        latest: Arc<State>,
+    ) -> Self {
+        Self { old: old.into_iter(), later, latest }
+    }
+}
+
+impl Iterator for StateChanges {
+    type Item = StateChange;
+    
+    fn next(&mut self) -> Option<StateChange> {
+        for (key, old) in self.old.by_ref() {
+            let new = match self.later.remove(&key) {
+                Some(value) => value,
+                None => self.latest.value_of(&key),
+            };
+            if let Some(change) = StateChange::new(key, old, new) {
+                return Some(change);
+            }
+        }
+        None
+    }
+}
+
+/// Lists the balances a block changed, by address, from its undo record and the state it left
+pub(crate) fn balance_changes(undo: &BlockUndo, state: &State) -> Vec<BalanceChange> {
+    let mut old = BTreeMap::new();
+    record_first(&mut old, undo.previous_values().filter(|(key, _)| matches!(key, StateKey::Balance(_))), None);
+    old.into_iter()
+        .filter_map(|(key, old)| match key {
+            StateKey::Balance(address) => {
+                let (old, new) = (old.amount(), state.balance_of(&address));
+                (old != new).then_some(BalanceChange { address, old, new })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Records the first value seen for each part of the state, optionally only for one address
+pub(crate) fn record_first(
+    values: &mut BTreeMap<StateKey, StateValue>,
+    changes: impl Iterator<Item = (StateKey, StateValue)>,
+    address: Option<&str>,
+) {
+    for (key, value) in changes {
+        if address.is_none_or(|address| key.address() == address) {
+            values.entry(key).or_insert(value);
+        }
+    }
+}
\ No newline at end of file
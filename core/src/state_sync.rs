@@ -0,0 +1,207 @@
+//! State snapshots for syncing new nodes
+//!
+//! Rather than replaying every block, a new node can download the state at a
+//! recent height from its peers. A snapshot is the state's canonical encoding
+//! (see `State::encode_canonical`) split into chunks of `SNAPSHOT_CHUNK_SIZE`
+//! bytes, the last one possibly shorter. Its root is the hash of the chunk
+//! hashes in order (see `hashing::HashDomain::StateRoot`), so a manifest listing the chunk hashes can be
+//! checked against the root before any chunk is downloaded, and each chunk
+//! can then be checked on its own as it arrives.
+//!
+//! Block headers don't commit to state roots, so the root a node syncs to
+//! must come from a source it already trusts.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{self, HashDomain, Hasher};
+use crate::rlp::RlpItem;
+use crate::state::State;
+use crate::wire::{self, Wire, WireError};
+use crate::{BlockHash, BlockchainError, Hash, Result};
+
+/// Size of every snapshot chunk but the last
+pub const SNAPSHOT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Most chunks a snapshot may have
+pub const MAX_SNAPSHOT_CHUNKS: u32 = 16 * 1024;
+
+/// What a node says about a snapshot it serves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// Height of the block the state was taken after
+    pub height: u64,
+    
+    /// Hash of that block
+    pub block_hash: BlockHash,
+    
+    /// Root of the snapshot's chunks
+    #[serde(with = "crate::types::hex_serde")]
+    pub root: Hash,
+    
+    /// Number of chunks
+    pub chunk_count: u32,
+}
+
+/// The hashes of a snapshot's chunks, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotManifest {
+    /// Height of the block the state was taken after
+    pub height: u64,
+    
+    /// Hash of that block
+    pub block_hash: BlockHash,
+    
+    /// Hash of each chunk
+    pub chunk_hashes: Vec<Hash>,
+}
+
+impl SnapshotManifest {
+    /// Gets the snapshot's root
+    pub fn root(&self) -> Hash {
+        let mut hasher = Hasher::for_domain(HashDomain::StateRoot);
+        for hash in &self.chunk_hashes {
+            hasher.update(hash);
+        }
+        hasher.finalize()
+    }
+    
+    /// Gets the summary of the snapshot
+    pub fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            height: self.height,
+            block_hash: self.block_hash,
+            root: self.root(),
+            chunk_count: self.chunk_hashes.len() as u32,
+        }
+    }
+    
+    /// Checks that the manifest describes the snapshot `info` summarizes
+    pub fn matches(&self, info: &SnapshotInfo) -> bool {
+        self.info() == *info
+    }
+}
+
+/// A snapshot of the state, split into chunks ready to be served
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    manifest: SnapshotManifest,
+    chunks: Vec<Arc<[u8]>>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the state after the block at `height`
+    pub fn new(height: u64, block_hash: BlockHash, state: &State) -> Result<Self> {
+        // The encoding always holds the total supply, so there's at least one chunk
+        let encoded = state.encode_canonical();
+        let chunks: Vec<Arc<[u8]>> = encoded.chunks(SNAPSHOT_CHUNK_SIZE).map(Arc::from).collect();
+        if chunks.len() > MAX_SNAPSHOT_CHUNKS as usize {
+            return Err(BlockchainError::StateError(
+                format!("State of {} bytes is too large for a snapshot", encoded.len())
+            ));
+        }
+        
+        let manifest = SnapshotManifest {
+            height,
+            block_hash,
+            chunk_hashes: chunks.iter().map(|chunk| chunk_hash(chunk)).collect(),
+        };
+        Ok(Self { manifest, chunks })
+    }
+    
+    /// Gets the summary of the snapshot
+    pub fn info(&self) -> SnapshotInfo {
+        self.manifest.info()
+    }
+    
+    /// Gets the hashes of the snapshot's chunks
+    pub fn manifest(&self) -> &SnapshotManifest {
+        &self.manifest
+    }
+    
+    /// Gets a chunk by its index
+    pub fn chunk(&self, index: u32) -> Option<&[u8]> {
+        self.chunks.get(index as usize).map(|chunk| &chunk[..])
+    }
+}
+
+/// Gets the hash a manifest lists for a chunk
+pub fn chunk_hash(chunk: &[u8]) -> Hash {
+    hashing::hash_in(HashDomain::StateChunk, chunk)
+}
+
+/// Gets the root of the snapshot a state would have
+pub fn state_root(state: &State) -> Hash {
+    let chunk_hashes = state.encode_canonical().chunks(SNAPSHOT_CHUNK_SIZE).map(chunk_hash).collect();
+    SnapshotManifest { height: 0, block_hash: BlockHash::default(), chunk_hashes }.root()
+}
+
+/// Rebuilds the state from a snapshot's chunks, given in order
+///
+/// Every chunk is checked against the manifest before the state is decoded.
+pub fn restore_state(manifest: &SnapshotManifest, chunks: &[Vec<u8>]) -> Result<State> {
+    if chunks.len() != manifest.chunk_hashes.len() {
+        return Err(BlockchainError::StateError(format!(
+            "Snapshot has {} chunks, expected {}",
+            chunks.len(),
+            manifest.chunk_hashes.len()
+        )));
+    }
+    if let Some(index) = chunks.iter().zip(&manifest.chunk_hashes).position(|(chunk, hash)| chunk_hash(chunk) != *hash) {
+        return Err(BlockchainError::StateError(format!("Snapshot chunk {} doesn't match its hash", index)));
+    }
+    
+    State::decode_canonical(&chunks.concat())
+        .map_err(|e| BlockchainError::SerializationError(format!("Invalid snapshot: {}", e)))
+}
+
+impl Wire for SnapshotInfo {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            RlpItem::uint(self.height as u128),
+            wire::hash(&self.block_hash.0),
+            wire::hash(&self.root),
+            RlpItem::uint(self.chunk_count as u128),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> std::result::Result<Self, WireError> {
+        let fields = wire::fields(item, 4)?;
+        Ok(Self {
+            height: fields[0].as_u64()?,
+            block_hash: BlockHash(wire::decode_hash(&fields[1])?),
+            root: wire::decode_hash(&fields[2])?,
+            chunk_count: chunk_count(fields[3].as_u64()?)?,
+        })
+    }
+}
+
+impl Wire for SnapshotManifest {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            RlpItem::uint(self.height as u128),
+            wire::hash(&self.block_hash.0),
+            RlpItem::List(self.chunk_hashes.iter().map(wire::hash).collect()),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> std::result::Result<Self, WireError> {
+        let fields = wire::fields(item, 3)?;
+        let hashes = fields[2].as_list()?;
+        chunk_count(hashes.len() as u64)?;
+        Ok(Self {
+            height: fields[0].as_u64()?,
+            block_hash: BlockHash(wire::decode_hash(&fields[1])?),
+            chunk_hashes: hashes.iter().map(wire::decode_hash).collect::<std::result::Result<_, _>>()?,
+        })
+    }
+}
+
+/// Checks a decoded chunk count, which must be between 1 and `MAX_SNAPSHOT_CHUNKS`
+fn chunk_count(count: u64) -> std::result::Result<u32, WireError> {
+    u32::try_from(count)
+        .ok()
+        .filter(|count| (1..=MAX_SNAPSHOT_CHUNKS).contains(count))
+        .ok_or(WireError::InvalidValue("snapshot chunk count"))
+}
\ No newline at end of file
@@ -0,0 +1,748 @@
+//! Generators and checks for testing encodings
+//!
+//! Built with the `testutil` feature. A `Generator` makes random but valid
+//! transactions, blocks and receipts from a seed, so any failing case can be made
+//! again, and its `GenConfig` sets how large they get and how often a field
+//! takes an edge value instead: empty or maximum-size data, zero amounts
+//! where they're allowed, empty or maximum-length strings, and the extremes
+//! of integers. Other crates build their own types from the generator's
+//! primitives, as `wallet::testutil` and `node::testutil` do.
+//!
+//! The checks panic saying what went wrong, so they work under any test
+//! harness:
+//!
+//! - `assert_roundtrip_wire` and `assert_roundtrip_json` decode a value's
+//!   encoding and check that encoding it again gives back the same bytes;
+//! - `assert_hash_stable` checks the hash of a fixture's encoding against
+//!   the golden hash checked in with it, so that any change to an encoding
+//!   fails loudly until the golden hash is deliberately updated.
+//!
+//! `check_encodings` runs the round trips over generated values and
+//! `check_fixtures` checks this crate's fixtures. The crate's `encoding`
+//! test runs both.
+//!
+//! For input from peers and files, `decoders` lists the decoders a node
+//! runs on it and `assert_decodes` checks that one returns, rather than
+//! panicking, on some input. `fuzz_input` makes input close enough to valid
+//! encodings to get past their first checks, and `decode_regressions` holds
+//! inputs at the edges of the decoders that are checked on every run. The
+//! crate's `decoding` test feeds both to every decoder.
+
+use std::any::type_name;
+use std::panic;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::block::{Block, BlockHeader};
+use crate::eth_transaction::EthTransaction;
+use crate::hashing::{self, HashAlgorithm, HashDomain, Hasher};
+use crate::memo::{MemoField, MAX_MEMO_SIZE, MEMO_FEE_PER_BYTE};
+use crate::receipt::{Log, Receipt};
+use crate::rlp::{self, RlpItem, MAX_DEPTH};
+use crate::signature::SignatureScheme;
+use crate::state::State;
+use crate::state_sync::{SnapshotInfo, SnapshotManifest};
+use crate::transaction::{Transaction, TransactionType, MAX_DATA_SIZE, MAX_DEPLOY_DATA_SIZE};
+use crate::wire::Wire;
+use crate::{BlockHash, Bytes, TxHash};
+
+/// How large generated values get and how often they take edge values
+#[derive(Debug, Clone)]
+pub struct GenConfig {
+    /// Longest payload of a generated transaction, capped by its type's limit
+    pub max_data_len: usize,
+    
+    /// Longest generated string, such as an address or a validator name
+    pub max_string_len: usize,
+    
+    /// Most transactions in a generated block
+    pub max_transactions: usize,
+    
+    /// Probability of a field taking an edge value, from 0 to 1
+    pub edge_case_rate: f64,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        Self {
+            max_data_len: 256,
+            max_string_len: 64,
+            max_transactions: 8,
+            edge_case_rate: 0.2,
+        }
+    }
+}
+
+/// Makes random values from a seed
+pub struct Generator {
+    rng: StdRng,
+    config: GenConfig,
+}
+
+impl Generator {
+    /// Creates a generator with the default configuration
+    pub fn new(seed: u64) -> Self {
+        Self::with_config(seed, GenConfig::default())
+    }
+    
+    /// Creates a generator with a configuration
+    pub fn with_config(seed: u64, config: GenConfig) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), config }
+    }
+    
+    /// Gets the configuration
+    pub fn config(&self) -> &GenConfig {
+        &self.config
+    }
+    
+    /// Gets the random number generator, for values the generator doesn't make
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+    
+    /// Decides whether the next field takes an edge value
+    pub fn edge_case(&mut self) -> bool {
+        self.rng.gen_bool(self.config.edge_case_rate)
+    }
+    
+    /// Makes an integer, sometimes 0, 1 or the largest
+    pub fn u64(&mut self) -> u64 {
+        if self.edge_case() {
+            return [0, 1, u64::MAX][self.rng.gen_range(0..3)];
+        }
+        self.rng.gen()
+    }
+    
+    /// Makes a positive amount, sometimes 1 or the largest
+    pub fn amount(&mut self) -> u64 {
+        if self.edge_case() {
+            return [1, u64::MAX][self.rng.gen_range(0..2)];
+        }
+        self.rng.gen_range(1..=1_000_000_000_000)
+    }
+    
+    /// Makes up to `max_len` bytes, sometimes none or exactly `max_len`
+    pub fn bytes(&mut self, max_len: usize) -> Vec<u8> {
+        let len = if self.edge_case() {
+            [0, max_len][self.rng.gen_range(0..2)]
+        } else {
+            self.rng.gen_range(0..=max_len)
+        };
+        (0..len).map(|_| self.rng.gen()).collect()
+    }
+    
+    /// Makes a string of up to `max_string_len` characters, sometimes empty or of the maximum length
+    ///
+    /// Strings mix ASCII with multi-byte characters, so their byte lengths
+    /// can exceed their character counts.
+    pub fn string(&mut self) -> String {
+        const CHARS: &[char] = &['a', 'Z', '0', '_', ' ', '"', '\\', 'é', '€', '𝄞'];
+        let max_len = self.config.max_string_len;
+        let len = if self.edge_case() {
+            [0, max_len][self.rng.gen_range(0..2)]
+        } else {
+            self.rng.gen_range(0..=max_len)
+        };
+        (0..len).map(|_| CHARS[self.rng.gen_range(0..CHARS.len())]).collect()
+    }
+    
+    /// Makes a 32-byte hash
+    pub fn hash(&mut self) -> [u8; 32] {
+        self.rng.gen()
+    }
+    
+    /// Makes a key, returning its secret key and address
+    pub fn key(&mut self) -> (Vec<u8>, String) {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&self.hash()).expect("any 32 bytes are an Ed25519 secret key");
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let address = SignatureScheme::Ed25519
+            .address(public.as_bytes())
+            .expect("Ed25519 public keys are 32 bytes");
+        (secret.as_bytes().to_vec(), address)
+    }
+    
+    /// Makes an address that encodes no key, like those of genesis accounts
+    pub fn account_name(&mut self) -> String {
+        format!("TEST_{}", self.string())
+    }
+    
+    /// Makes a valid transaction of any type but validator and governance transactions
+    ///
+    /// The sender's key signs it, as only coinbase transactions may go unsigned.
+    pub fn transaction(&mut self) -> Transaction {
+        const TYPES: [TransactionType; 5] = [
+            TransactionType::Transfer,
+            TransactionType::ContractDeploy,
+            TransactionType::ContractCall,
+            TransactionType::Stake,
+            TransactionType::Unstake,
+        ];
+        let tx_type = TYPES[self.rng.gen_range(0..TYPES.len())];
+        
+        let (key, sender) = self.key();
+        
+        let mut tx = match tx_type {
+            TransactionType::ContractDeploy => {
+                let mut data = self.bytes(self.config.max_data_len.min(MAX_DEPLOY_DATA_SIZE));
+                if data.is_empty() {
+                    data.push(self.rng.gen());
+                }
+                let amount = if self.edge_case() { 0 } else { self.amount() };
+                let (gas_limit, gas_price) = (self.u64(), self.u64());
+                Transaction::new_with_type(tx_type, sender, String::new(), amount, 0, Some(data), gas_limit, gas_price)
+            }
+            TransactionType::ContractCall => {
+                let recipient = format!("0x{}", hex::encode(&self.hash()[..20]));
+                let amount = if self.edge_case() { 0 } else { self.amount() };
+                let data = self.data(MAX_DATA_SIZE);
+                let (gas_limit, gas_price) = (self.u64(), self.u64());
+                Transaction::new_with_type(tx_type, sender, recipient, amount, 0, data, gas_limit, gas_price)
+            }
+            _ => {
+                let recipient = self.account_name();
+                let (amount, fee) = (self.amount(), self.u64());
+                let data = self.data(MAX_DATA_SIZE);
+                Transaction::new_with_type(tx_type, sender, recipient, amount, fee, data, 0, 0)
+            }
+        }
+        .expect("generated transactions serialize");
+        
+        tx.timestamp = self.u64();
+        if self.rng.gen() {
+            tx.nonce = self.u64();
+        }
+        if self.rng.gen_ratio(1, 4) {
+            tx.memo = Some(self.memo());
+            if !tx.is_metered() {
+                tx.fee = tx.fee.max(tx.memo_fee());
+            }
+        }
+        tx.id = tx.calculate_hash().expect("generated transactions serialize");
+        tx.sign(&key).expect("generated keys sign");
+        tx
+    }
+    
+    /// Makes a block of up to `max_transactions` generated transactions
+    ///
+    /// The block's header is consistent with its transactions, but the block
+    /// doesn't belong to any chain.
+    pub fn block(&mut self) -> Block {
+        let max = self.config.max_transactions;
+        let count = if self.edge_case() {
+            [0, max][self.rng.gen_range(0..2)]
+        } else {
+            self.rng.gen_range(0..=max)
+        };
+        let transactions = (0..count).map(|_| self.transaction()).collect();
+        
+        let (height, prev_hash, validator, base_fee) = (self.u64(), BlockHash(self.hash()), self.string(), self.u64());
+        let mut block = Block::new(height, prev_hash, transactions, validator, base_fee)
+            .expect("generated transactions serialize");
+        
+        let timestamp = self.u64();
+        let signature = self.rng.gen_bool(0.5).then(|| Bytes(self.bytes(64)));
+        let validator_set_hash = self.rng.gen_bool(0.5).then(|| self.hash());
+        let header = block.header_mut();
+        header.timestamp = timestamp;
+        header.signature = signature;
+        header.validator_set_hash = validator_set_hash;
+        block
+    }
+    
+    /// Makes a receipt of a successful or failed transaction, with up to `max_transactions` logs if it succeeded
+    ///
+    /// The receipt doesn't belong to any transaction.
+    pub fn receipt(&mut self) -> Receipt {
+        let success = self.rng.gen();
+        let log_count = if success { self.rng.gen_range(0..=self.config.max_transactions) } else { 0 };
+        let logs = (0..log_count).map(|_| self.log()).collect();
+        let contract_address = self.rng.gen::<bool>().then(|| self.string());
+        let revert_reason = (!success && self.rng.gen()).then(|| self.string());
+        let destroyed_count = if success { self.rng.gen_range(0..=2) } else { 0 };
+        
+        Receipt {
+            tx_id: TxHash(self.hash()),
+            block_height: self.u64(),
+            success,
+            gas_used: self.u64(),
+            cumulative_gas_used: self.u64(),
+            contract_address,
+            logs,
+            revert_reason,
+            destroyed_contracts: (0..destroyed_count).map(|_| self.string()).collect(),
+            memo: self.rng.gen_ratio(1, 4).then(|| self.memo()),
+        }
+    }
+    
+    /// Makes a plain memo of up to `MAX_MEMO_SIZE` bytes or a commitment
+    fn memo(&mut self) -> MemoField {
+        if self.rng.gen() {
+            return MemoField::Committed(self.hash());
+        }
+        let mut memo = self.string();
+        while memo.len() > MAX_MEMO_SIZE {
+            memo.pop();
+        }
+        MemoField::Plain(memo)
+    }
+    
+    /// Makes a log with up to four topics
+    fn log(&mut self) -> Log {
+        let topic_count = self.rng.gen_range(0..=4);
+        Log {
+            address: self.string(),
+            topics: (0..topic_count).map(|_| self.hash()).collect(),
+            data: self.bytes(self.config.max_data_len),
+        }
+    }
+    
+    /// Changes one to four bytes of an input, or the length of it
+    ///
+    /// A byte changed is as often set to a length prefix as to any value,
+    /// so that the lengths the input gives stop matching it.
+    pub fn mutate(&mut self, mut input: Vec<u8>) -> Vec<u8> {
+        const PREFIXES: [u8; 8] = [0x80, 0xb7, 0xb8, 0xbf, 0xc0, 0xf7, 0xf8, 0xff];
+        for _ in 0..self.rng.gen_range(1..=4) {
+            let at = self.rng.gen_range(0..=input.len());
+            match self.rng.gen_range(0..5) {
+                0 if at < input.len() => input[at] = self.rng.gen(),
+                1 if at < input.len() => input[at] = PREFIXES[self.rng.gen_range(0..PREFIXES.len())],
+                2 => input.truncate(at),
+                3 => input.insert(at, self.rng.gen()),
+                _ if at < input.len() => {
+                    input.remove(at);
+                }
+                _ => {}
+            }
+        }
+        input
+    }
+    
+    /// Makes a payload of up to `max_data_len` bytes, capped at `limit`, or none
+    fn data(&mut self, limit: usize) -> Option<Vec<u8>> {
+        let max_len = self.config.max_data_len.min(limit);
+        self.rng.gen::<bool>().then(|| self.bytes(max_len))
+    }
+}
+
+/// Checks that a value decodes from its binary encoding and encodes back the same, returning the decoded value
+pub fn assert_roundtrip_wire<T: Wire>(value: &T) -> T {
+    let encoded = value.to_bytes();
+    let decoded = T::from_bytes(&encoded)
+        .unwrap_or_else(|e| panic!("Cannot decode the binary encoding of a {}: {}", type_name::<T>(), e));
+    if decoded.to_bytes() != encoded {
+        panic!("{} encodes differently after decoding: {}", type_name::<T>(), hex::encode(&encoded));
+    }
+    decoded
+}
+
+/// Checks that a value deserializes from its JSON and serializes back the same, returning the deserialized value
+pub fn assert_roundtrip_json<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    let encoded = serde_json::to_string(value)
+        .unwrap_or_else(|e| panic!("Cannot serialize a {}: {}", type_name::<T>(), e));
+    let decoded: T = serde_json::from_str(&encoded)
+        .unwrap_or_else(|e| panic!("Cannot deserialize a {} from {}: {}", type_name::<T>(), encoded, e));
+    let reencoded = serde_json::to_string(&decoded).expect("a deserialized value serializes");
+    if reencoded != encoded {
+        panic!("{} serializes differently after deserializing: {} became {}", type_name::<T>(), encoded, reencoded);
+    }
+    decoded
+}
+
+/// An encoding whose hash must never change by accident
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    /// What the fixture encodes
+    pub name: &'static str,
+    
+    /// The encoding
+    pub encoding: Vec<u8>,
+    
+    /// Hex SHA-256 hash the encoding had when the fixture was checked in
+    pub golden_hash: &'static str,
+}
+
+impl Fixture {
+    /// Gets the hex SHA-256 hash of the encoding
+    pub fn hash(&self) -> String {
+        hex::encode(hashing::sha256(&self.encoding))
+    }
+}
+
+/// Checks a fixture's encoding against its golden hash
+///
+/// A deliberate change to an encoding must update the golden hash too; the
+/// panic message gives the new one.
+pub fn assert_hash_stable(fixture: &Fixture) {
+    let hash = fixture.hash();
+    if hash != fixture.golden_hash {
+        panic!(
+            "Encoding of {} changed: hash {} instead of {}\nencoding: {}",
+            fixture.name,
+            hash,
+            fixture.golden_hash,
+            hex::encode(&fixture.encoding)
+        );
+    }
+}
+
+/// Secret key of the fixtures' signing account
+const FIXTURE_KEY: [u8; 32] = [7; 32];
+
+/// Gets the fixtures of this crate's consensus encodings
+///
+/// Every fixture is built from fixed values, signatures included, so its
+/// encoding only changes when the encoding does.
+pub fn fixtures() -> Vec<Fixture> {
+    let transfer = fixture_transaction(
+        TransactionType::Transfer,
+        "GENX_DEVELOPMENT_FUND",
+        "GENX_FIXTURE_RECIPIENT",
+        1_500_000_000,
+        1,
+        None,
+        (0, 0),
+    );
+    
+    let secret = ed25519_dalek::SecretKey::from_bytes(&FIXTURE_KEY).expect("fixture key is 32 bytes");
+    let signer = SignatureScheme::Ed25519
+        .address(ed25519_dalek::PublicKey::from(&secret).as_bytes())
+        .expect("Ed25519 public keys are 32 bytes");
+    let mut call = fixture_transaction(
+        TransactionType::ContractCall,
+        &signer,
+        "0x00000000000000000000000000000000000000c7",
+        0,
+        0,
+        Some(vec![0xa9, 0x05, 0x9c, 0xbb, 0, 1, 2, 3]),
+        (100_000, 7),
+    );
+    call.sign(&FIXTURE_KEY).expect("fixture key signs");
+    
+    let deploy = fixture_transaction(
+        TransactionType::ContractDeploy,
+        "GENX_DEVELOPMENT_FUND",
+        "",
+        0,
+        0,
+        Some(vec![0x60, 0x00, 0x60, 0x00, 0xf3]),
+        (1_000_000, 1),
+    );
+    
+    let mut block = Block::new(
+        42,
+        BlockHash([0x11; 32]),
+        vec![transfer.clone(), call.clone(), deploy.clone()],
+        "GENX_FIXTURE_VALIDATOR".to_string(),
+        7,
+    )
+    .expect("fixture transactions serialize");
+    let header = block.header_mut();
+    header.timestamp = 1_700_000_000;
+    header.signature = Some(Bytes(vec![0x22; 64]));
+    
+    let receipt = Receipt {
+        tx_id: call.id,
+        block_height: 42,
+        success: true,
+        gas_used: 23_456,
+        cumulative_gas_used: 44_456,
+        contract_address: None,
+        logs: vec![Log {
+            address: "0x00000000000000000000000000000000000000c7".to_string(),
+            topics: vec![[0x55; 32]],
+            data: vec![0, 0, 0, 1],
+        }],
+        revert_reason: None,
+        destroyed_contracts: Vec::new(),
+        memo: None,
+    };
+    
+    let memo_transfer = fixture_transaction(
+        TransactionType::Transfer,
+        "GENX_DEVELOPMENT_FUND",
+        "GENX_FIXTURE_RECIPIENT",
+        2_500_000_000,
+        MEMO_FEE_PER_BYTE * 32,
+        None,
+        (0, 0),
+    )
+    .with_memo(MemoField::commit("GENX_FIXTURE_MEMO"))
+    .expect("fixture transactions serialize");
+    
+    let mut fixtures = vec![
+        Fixture {
+            name: "transfer transaction (wire)",
+            encoding: transfer.to_bytes(),
+            golden_hash: "812eaf1e0b9354f0386fe8ba5ac9ff7465b5465049db4a028eef6209210197c0",
+        },
+        Fixture {
+            name: "transfer transaction (JSON)",
+            encoding: json(&transfer),
+            golden_hash: "a1752d112416d522c535dfba2667d5db4b7f5da3ff2d07f74a14b99138b85493",
+        },
+        Fixture {
+            name: "signed contract call (wire)",
+            encoding: call.to_bytes(),
+            golden_hash: "31baeac0d190e85943a883de3f17569133f992f48a09803f23b5cb59077b441f",
+        },
+        Fixture {
+            name: "contract deployment (wire)",
+            encoding: deploy.to_bytes(),
+            golden_hash: "faf360f9c0c5718f6f6ea1c46a69f1e44c53c8f9deca9cd36a2ab4021205af57",
+        },
+        Fixture {
+            name: "block (wire)",
+            encoding: block.to_bytes(),
+            golden_hash: "eae959831d51102d5f1d435abfb27f960ef4fc524fbebe4a97b428a66076e719",
+        },
+        Fixture {
+            name: "block (JSON)",
+            encoding: json(&block),
+            golden_hash: "769e0108d418e0cf7749c20c1921ec268dd6b9cb6b79890008a257f42a9297de",
+        },
+        Fixture {
+            name: "block hash",
+            encoding: block.hash().expect("fixture block hashes").0.to_vec(),
+            golden_hash: "b3ae2ec8ce6b215766f3a939220284ca09423c09f2ee9b12325a11d526cefa8f",
+        },
+        Fixture {
+            name: "receipt (wire)",
+            encoding: receipt.to_bytes(),
+            golden_hash: "63790ab264fcca23c96ad58ad2f3cbe87b07a2a6f6300ff4388d90d141d5360d",
+        },
+        Fixture {
+            name: "transfer with a committed memo (wire)",
+            encoding: memo_transfer.to_bytes(),
+            golden_hash: "cf7c8b1978ef0c240b12fd8eea106bc2fc24fd76014449c8953504acdc1d26a4",
+        },
+        Fixture {
+            name: "transfer with a committed memo (JSON)",
+            encoding: json(&memo_transfer),
+            golden_hash: "26a016804022038b0046cb387fb7be102b69188d39ba009c12baebd77952ab16",
+        },
+        Fixture {
+            name: "transfer transaction ID",
+            encoding: transfer.id.0.to_vec(),
+            golden_hash: "f85745a94e4ddfc30bb208f4c8ede77abf7aba5d0a9f088528520ab3aa9d040a",
+        },
+    ];
+    fixtures.extend(hash_fixtures());
+    fixtures
+}
+
+/// Data hashed in every domain and with every algorithm for the hash fixtures
+const HASH_FIXTURE_INPUT: &[u8] = b"GENX hash fixture";
+
+/// Golden hashes of `HASH_FIXTURE_INPUT` hashed in each domain
+const DOMAIN_GOLDEN_HASHES: [(HashDomain, &str); 16] = [
+    (HashDomain::Transaction, "94805fa12a6880f86d5f1e10571e465369aba9067c96ba9248f7e810d2551568"),
+    (HashDomain::BlockHeader, "62003a76beefd9b87327961ecdda3a4271b781cdc4d73b1d15b9313951450c82"),
+    (HashDomain::TransactionRoot, "e75e03a282516d1714470001ca1a4271c2a29a7ee52aab4046dee0b0da83a36d"),
+    (HashDomain::StorageRoot, "aeb3cdc2af42cda5bf0539ab9ae290980234570d0e2d6f2398ba54f94fc2f79c"),
+    (HashDomain::ContractAddress, "840a51b9a35a9a85a2b3696ba395349fbc82fabb78e020513bfeeb6fefaeed65"),
+    (HashDomain::EvmAddress, "e3b1c37b3904bb9e7d9c7cbb74a2eae97bb7805992c952b09d0763e34158a688"),
+    (HashDomain::MemoCommitment, "d0d3547d2119775f3bb215a2b49f63ce7ee6164a13452e47ebb5e1e82a7e9cbd"),
+    (HashDomain::StateChunk, "6f794a193ca932a371572204d578f13b471ba3e121a5601e1ced10ef9f9ba160"),
+    (HashDomain::StateRoot, "b086c43776c45648f1aa75f83f8fdeb236aae7d98fb3ec239d1e3c5b8aee03df"),
+    (HashDomain::BlockFilter, "051391a6a61c56cb9d081113d7ff41200de7b42acf6872dcb4a45998a765f855"),
+    (HashDomain::FilterHeader, "07a5268ffd5fe86f227bfd28acbd1821ad53611c3dbf50b1018aaf45c82f1732"),
+    (HashDomain::FilterItem, "3bb15d34c99aef0b9d00a85999b59b4d96a8f06c41d2764877abeb071495a7bf"),
+    (HashDomain::ValidatorSet, "8ea56e523ace4cd9243c2ddaee1beffde28bd4272e6ffa27eff4c2a352aaf491"),
+    (HashDomain::FinalityVote, "010fc4989a1014ddff96c280adf4d10124fbc078b98ade00806c8bf937ef0c1c"),
+    (HashDomain::AddressBloom, "c24d03df755efe58834f9624c3cf2fc27500ba19775a30bb99b3c9fba62d8cfb"),
+    (HashDomain::AddressAnnouncement, "5be598a4fdda0e1d3c13834a55d8f56a917bbfd2aadd994b8e8f62c716695eb7"),
+];
+
+/// Golden hashes of `HASH_FIXTURE_INPUT` hashed with each algorithm, with no prefix
+const ALGORITHM_GOLDEN_HASHES: [(HashAlgorithm, &str); 3] = [
+    (HashAlgorithm::Sha256, "f82fec0c6f6ca632a57c04a63fcd06a0c77b7fe83eb656a6565e034eebb56a32"),
+    (HashAlgorithm::Keccak256, "8fbcf68eaf1b85239042e884c740fef337b863a5e7f9e8262297d10684708e21"),
+    (HashAlgorithm::Blake3, "d239759404ff2d38d49ca1e24b726c7fc47629d77c8f033a149e5b76e378621a"),
+];
+
+/// Gets a fixture of the hash of `HASH_FIXTURE_INPUT` in each domain and with each algorithm
+fn hash_fixtures() -> Vec<Fixture> {
+    for domain in HashDomain::ALL {
+        assert!(
+            DOMAIN_GOLDEN_HASHES.iter().any(|(golden, _)| *golden == domain),
+            "No golden hash for the {} domain",
+            domain.tag()
+        );
+    }
+    
+    let domains = DOMAIN_GOLDEN_HASHES.iter().map(|(domain, golden_hash)| Fixture {
+        name: domain.tag(),
+        encoding: hashing::hash_in(*domain, HASH_FIXTURE_INPUT).to_vec(),
+        golden_hash,
+    });
+    let algorithms = ALGORITHM_GOLDEN_HASHES.iter().map(|(algorithm, golden_hash)| {
+        let mut hasher = Hasher::new(*algorithm);
+        hasher.update(HASH_FIXTURE_INPUT);
+        Fixture { name: algorithm.name(), encoding: hasher.finalize().to_vec(), golden_hash }
+    });
+    domains.chain(algorithms).collect()
+}
+
+/// Checks every fixture of this crate against its golden hash
+pub fn check_fixtures() {
+    for fixture in fixtures() {
+        assert_hash_stable(&fixture);
+    }
+}
+
+/// Round-trips `cases` generated transactions, blocks and receipts through both encodings
+///
+/// Each case uses its own seed, counting up from `seed`, so a failing case
+/// can be made again on its own with `Generator::new`.
+pub fn check_encodings(seed: u64, cases: u64) {
+    for case in seed..seed + cases {
+        let mut generator = Generator::new(case);
+        
+        let tx = generator.transaction();
+        tx.validate().unwrap_or_else(|e| panic!("Generated an invalid transaction with seed {}: {}", case, e));
+        let decoded = assert_roundtrip_wire(&tx);
+        assert_eq!(decoded.id, tx.id, "Transaction ID changed decoding seed {}", case);
+        assert_roundtrip_json(&tx);
+        
+        let block = generator.block();
+        let decoded = assert_roundtrip_wire(&block);
+        assert_eq!(decoded.hash().ok(), block.hash().ok(), "Block hash changed decoding seed {}", case);
+        assert_roundtrip_json(&block);
+        assert_roundtrip_wire(block.header());
+        assert_roundtrip_json(block.header());
+        
+        let receipt = generator.receipt();
+        assert_roundtrip_wire(&receipt);
+        assert_roundtrip_json(&receipt);
+    }
+}
+
+/// Decoder of untrusted input, by name
+pub type Decoder = (&'static str, fn(&[u8]));
+
+/// Gets the decoders of this crate that run on input from peers and files
+///
+/// Each decodes its input and, if that succeeds, checks the value as a
+/// node does before trusting it, ignoring the outcome.
+pub fn decoders() -> Vec<Decoder> {
+    vec![
+        ("rlp", |input| {
+            let _ = rlp::decode(input);
+        }),
+        ("transaction", |input| {
+            if let Ok(tx) = Transaction::from_bytes(input) {
+                let _ = tx.validate();
+            }
+        }),
+        ("block", |input| {
+            if let Ok(block) = Block::from_bytes(input) {
+                let _ = block.validate();
+                let _ = block.hash();
+            }
+        }),
+        ("block header", |input| {
+            let _ = BlockHeader::from_bytes(input);
+        }),
+        ("receipt", |input| {
+            let _ = Receipt::from_bytes(input);
+        }),
+        ("ethereum transaction", |input| {
+            if let Ok(eth) = EthTransaction::decode(input) {
+                let recipient = eth.to.map(|to| format!("0x{}", hex::encode(to))).unwrap_or_default();
+                let _ = eth.to_transaction(recipient);
+            }
+        }),
+        ("snapshot info", |input| {
+            let _ = SnapshotInfo::from_bytes(input);
+        }),
+        ("snapshot manifest", |input| {
+            let _ = SnapshotManifest::from_bytes(input);
+        }),
+        ("state", |input| {
+            let _ = State::decode_canonical(input);
+        }),
+    ]
+}
+
+/// Checks that a decoder returns on an input rather than panicking
+pub fn assert_decodes((name, decode): Decoder, input: &[u8]) {
+    if panic::catch_unwind(|| decode(input)).is_err() {
+        panic!("The {} decoder panicked on {}", name, hex::encode(input));
+    }
+}
+
+/// Makes input for the decoders: random bytes, or a generated value's encoding mutated
+pub fn fuzz_input(generator: &mut Generator) -> Vec<u8> {
+    let encoding = match generator.rng.gen_range(0..5) {
+        0 => {
+            let max_len = generator.config.max_data_len;
+            return generator.bytes(max_len);
+        }
+        1 => generator.transaction().to_bytes(),
+        2 => generator.block().to_bytes(),
+        3 => generator.block().header().to_bytes(),
+        _ => generator.receipt().to_bytes(),
+    };
+    generator.mutate(encoding)
+}
+
+/// Gets the inputs at the edges of the decoders, checked on every run
+pub fn decode_regressions() -> Vec<(&'static str, Vec<u8>)> {
+    let nested = (0..=MAX_DEPTH).fold(RlpItem::List(Vec::new()), |item, _| RlpItem::List(vec![item]));
+    let mut truncated = Generator::new(0).transaction().to_bytes();
+    truncated.pop();
+    let mut one_byte_items = vec![0xfa, 0x10, 0x00, 0x00];
+    one_byte_items.resize(one_byte_items.len() + 0x10_0000, 0);
+    
+    vec![
+        ("string claiming 2^64 - 1 bytes", [vec![0xbf], vec![0xff; 8]].concat()),
+        ("list claiming 2^64 - 1 bytes", [vec![0xff], vec![0xff; 8]].concat()),
+        ("length with a leading zero byte", [vec![0xb9, 0x00, 0x40], vec![0x61; 0x40]].concat()),
+        ("lists nested deeper than MAX_DEPTH", rlp::encode(&nested)),
+        ("list of a million one-byte strings", one_byte_items),
+        ("transaction missing its last byte", truncated),
+        ("ethereum transaction type alone", vec![0x02]),
+        ("ethereum transaction with no fields", vec![0x02, 0xc0]),
+        ("snapshot manifest of no chunks", rlp::encode(&RlpItem::List(vec![
+            RlpItem::uint(1),
+            RlpItem::Bytes(vec![0; 32]),
+            RlpItem::List(Vec::new()),
+        ]))),
+        ("state record of no kind", vec![0xc0]),
+    ]
+}
+
+/// Serializes a fixture to JSON
+fn json<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_json::to_vec(value).expect("fixtures serialize")
+}
+
+/// Builds a transaction with a fixed timestamp
+fn fixture_transaction(
+    tx_type: TransactionType,
+    sender: &str,
+    recipient: &str,
+    amount: u64,
+    fee: u64,
+    data: Option<Vec<u8>>,
+    (gas_limit, gas_price): (u64, u64),
+) -> Transaction {
+    let mut tx = Transaction::new_with_type(
+        tx_type,
+        sender.to_string(),
+        recipient.to_string(),
+        amount,
+        fee,
+        data,
+        gas_limit,
+        gas_price,
+    )
+    .expect("fixture transactions serialize");
+    tx.timestamp = 1_700_000_000;
+    tx.id = tx.calculate_hash().expect("fixture transactions serialize");
+    tx
+}
\ No newline at end of file
@@ -6,17 +6,91 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use crate::{calculate_hash, current_timestamp, Hash, Result, BlockchainError};
+
+use crate::eth_transaction;
+use crate::execution_policy::PolicyUpdate;
+use crate::hashing::{self, HashDomain, Hasher};
+use crate::governance::{self, ProposalSubmission, ProposalVote};
+use crate::memo::{MemoField, MEMO_FEE_PER_BYTE};
+use crate::rewards::COINBASE;
+use crate::signature::{self, SignatureScheme};
+use crate::slashing::{SlashCancellation, SlashingEvidence};
+use crate::validator::{ValidatorEdit, ValidatorRegistration};
+use crate::verified::VerifiedTxCache;
+use crate::units::Amount;
+use crate::{current_timestamp, Address, Bytes, Result, BlockchainError, TxHash};
+
+/// Prefix used for contract addresses
+pub const CONTRACT_ADDRESS_PREFIX: &str = "GENX_CONTRACT_";
+
+/// Largest data a transaction other than a contract deployment may carry, in bytes
+pub const MAX_DATA_SIZE: usize = 64 * 1024;
+
+/// Largest init code a contract deployment may carry, in bytes
+///
+/// Twice the default limit on deployed code, `smartcontracts::MAX_CODE_SIZE`.
+pub const MAX_INIT_CODE_SIZE: usize = 48 * 1024;
+
+/// Largest data a contract deployment may carry, in bytes
+///
+/// Deployment data is JSON holding the init code and constructor arguments,
+/// which take up to four bytes per byte, along with the contract's ABI.
+pub const MAX_DEPLOY_DATA_SIZE: usize = 4 * MAX_INIT_CODE_SIZE + 32 * 1024;
+
+/// Converts a blockchain address string to a 20-byte EVM address
+///
+/// `0x`-prefixed and contract addresses carrying 40 hex characters are
+/// decoded directly, and secp256k1 accounts map to the Ethereum address of
+/// their key, which is what `ecrecover` returns for their signatures. Any
+/// other address is mapped to the last 20 bytes of its hash in the
+/// `HashDomain::EvmAddress` domain.
+pub fn to_evm_address(address: &str) -> [u8; 20] {
+    let mut evm_address = [0u8; 20];
+    
+    if let Some((SignatureScheme::Secp256k1, public_key)) = signature::parse_address(address) {
+        if let Ok(public_key) = crate::secp256k1::PublicKey::from_bytes(&public_key) {
+            return public_key.evm_address();
+        }
+    }
+    
+    if let Some(hex_part) = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix(CONTRACT_ADDRESS_PREFIX))
+    {
+        if let Ok(bytes) = hex::decode(hex_part) {
+            if bytes.len() == 20 {
+                evm_address.copy_from_slice(&bytes);
+                return evm_address;
+            }
+        }
+    }
+    
+    let hash = hashing::hash_in(HashDomain::EvmAddress, address.as_bytes());
+    evm_address.copy_from_slice(&hash[12..]);
+    evm_address
+}
 
 /// Represents a transaction in the blockchain
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     /// Unique transaction ID (hash)
-    pub id: Hash,
+    pub id: TxHash,
+    
+    /// Kind of transaction
+    pub tx_type: TransactionType,
     
     /// Timestamp when the transaction was created
     pub timestamp: u64,
     
+    /// Number of transactions the sender sent before this one
+    ///
+    /// A transaction is only applied with the sender's next nonce, so it
+    /// can't be applied twice or before the ones sent earlier; see
+    /// `State::get_nonce`. Left out of the serialized form when zero, like
+    /// `eth_raw`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub nonce: u64,
+    
     /// Sender's address (public key)
     pub sender: String,
     
@@ -26,18 +100,42 @@ pub struct Transaction {
     /// Amount of GENX tokens to transfer
     pub amount: u64,
     
-    /// Transaction fee in GENX
+    /// Flat transaction fee in GENX; contract transactions pay for gas instead
     pub fee: u64,
     
     /// Optional data payload (for smart contracts)
-    pub data: Option<Vec<u8>>,
+    pub data: Option<Bytes>,
+    
+    /// Maximum gas the transaction may consume when executing contract code
+    pub gas_limit: u64,
+    
+    /// Price in GENX paid per unit of gas consumed
+    ///
+    /// Must cover the base fee of the block the transaction is included in;
+    /// the base fee portion is burned and the rest is the validator's tip.
+    pub gas_price: u64,
     
     /// Sender's signature of the transaction
-    pub signature: Option<Vec<u8>>,
+    pub signature: Option<Bytes>,
+    
+    /// Signed Ethereum transaction this transaction was imported from
+    ///
+    /// The ID of an imported transaction is the Ethereum transaction hash and
+    /// its signature is over the Ethereum signing hash; see `eth_transaction`.
+    /// Left out of the serialized form when absent, so the IDs of other
+    /// transactions don't change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eth_raw: Option<Bytes>,
+    
+    /// Memo for the recipient, in plain or committed to; see `memo`
+    ///
+    /// Left out of the serialized form when absent, like `eth_raw`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<MemoField>,
 }
 
 /// Different types of transactions in the system
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TransactionType {
     /// Regular transfer of GENX tokens
     Transfer,
@@ -53,29 +151,181 @@ pub enum TransactionType {
     
     /// Validator unstaking transaction
     Unstake,
+    
+    /// Registration of a validator, with its metadata and consensus key
+    RegisterValidator,
+    
+    /// Update of a registered validator's metadata
+    EditValidator,
+    
+    /// Proposal to change a governed chain parameter, see `governance`
+    SubmitProposal,
+    
+    /// Validator's vote on a proposal
+    Vote,
+    
+    /// Evidence of a validator signing conflicting blocks, see `slashing`
+    SubmitEvidence,
+    
+    /// Validator's approval of cancelling a pending slash
+    CancelSlash,
+    
+    /// Change to who may deploy and call contracts, see `execution_policy`
+    SetExecutionPolicy,
 }
 
 impl Transaction {
-    /// Creates a new transaction with the given parameters
+    /// Creates a transfer of `amount` from `sender` to `recipient`, paying `fee`
     pub fn new(
+        sender: Address,
+        recipient: Address,
+        amount: Amount,
+        fee: Amount,
+        data: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        Self::new_with_type(
+            TransactionType::Transfer,
+            sender.into_string(),
+            recipient.into_string(),
+            amount.base_units(),
+            fee.base_units(),
+            data,
+            0,
+            0,
+        )
+    }
+    
+    /// Creates a transfer from unchecked addresses and amounts in base units
+    #[deprecated(note = "use `Transaction::new` with an `Address` and an `Amount`")]
+    pub fn new_untyped(
+        sender: String,
+        recipient: String,
+        amount: u64,
+        fee: u64,
+        data: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        Self::new_with_type(TransactionType::Transfer, sender, recipient, amount, fee, data, 0, 0)
+    }
+    
+    /// Creates a contract deployment transaction
+    ///
+    /// `data` carries the deployment payload (init code, ABI and constructor
+    /// arguments) and `amount` is the value endowed to the new contract.
+    pub fn new_contract_deploy(
+        sender: String,
+        amount: u64,
+        data: Vec<u8>,
+        gas_limit: u64,
+        gas_price: u64,
+    ) -> Result<Self> {
+        Self::new_with_type(
+            TransactionType::ContractDeploy,
+            sender,
+            String::new(),
+            amount,
+            0,
+            Some(data),
+            gas_limit,
+            gas_price,
+        )
+    }
+    
+    /// Creates a contract call transaction
+    ///
+    /// `data` is the call data: the 4-byte function selector followed by the
+    /// ABI-encoded arguments.
+    pub fn new_contract_call(
+        sender: String,
+        contract_address: String,
+        amount: u64,
+        data: Vec<u8>,
+        gas_limit: u64,
+        gas_price: u64,
+    ) -> Result<Self> {
+        Self::new_with_type(
+            TransactionType::ContractCall,
+            sender,
+            contract_address,
+            amount,
+            0,
+            Some(data),
+            gas_limit,
+            gas_price,
+        )
+    }
+    
+    /// Creates a transaction registering the sender as a validator
+    pub fn new_register_validator(sender: String, registration: &ValidatorRegistration, fee: u64) -> Result<Self> {
+        let data = registration.to_data()?;
+        Self::new_with_type(TransactionType::RegisterValidator, sender, String::new(), 0, fee, Some(data), 0, 0)
+    }
+    
+    /// Creates a transaction editing the metadata of the sender's validator
+    pub fn new_edit_validator(sender: String, edit: &ValidatorEdit, fee: u64) -> Result<Self> {
+        let data = edit.to_data()?;
+        Self::new_with_type(TransactionType::EditValidator, sender, String::new(), 0, fee, Some(data), 0, 0)
+    }
+    
+    /// Creates a transaction proposing a parameter change, holding `deposit` until voting ends
+    pub fn new_submit_proposal(sender: String, submission: &ProposalSubmission, deposit: u64, fee: u64) -> Result<Self> {
+        let data = submission.to_data()?;
+        Self::new_with_type(TransactionType::SubmitProposal, sender, String::new(), deposit, fee, Some(data), 0, 0)
+    }
+    
+    /// Creates a transaction casting the sender's vote on a proposal
+    pub fn new_vote(sender: String, vote: &ProposalVote, fee: u64) -> Result<Self> {
+        let data = vote.to_data()?;
+        Self::new_with_type(TransactionType::Vote, sender, String::new(), 0, fee, Some(data), 0, 0)
+    }
+    
+    /// Creates a transaction reporting a validator that signed conflicting blocks
+    pub fn new_submit_evidence(sender: String, evidence: &SlashingEvidence, fee: u64) -> Result<Self> {
+        let data = evidence.to_data()?;
+        Self::new_with_type(TransactionType::SubmitEvidence, sender, String::new(), 0, fee, Some(data), 0, 0)
+    }
+    
+    /// Creates a transaction approving cancelling a pending slash
+    pub fn new_cancel_slash(sender: String, cancellation: &SlashCancellation, fee: u64) -> Result<Self> {
+        let data = cancellation.to_data()?;
+        Self::new_with_type(TransactionType::CancelSlash, sender, String::new(), 0, fee, Some(data), 0, 0)
+    }
+    
+    /// Creates a transaction changing the execution policy
+    pub fn new_set_execution_policy(sender: String, update: &PolicyUpdate, fee: u64) -> Result<Self> {
+        let data = update.to_data()?;
+        Self::new_with_type(TransactionType::SetExecutionPolicy, sender, String::new(), 0, fee, Some(data), 0, 0)
+    }
+    
+    /// Creates a new transaction of the given type
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_type(
+        tx_type: TransactionType,
         sender: String,
         recipient: String,
         amount: u64,
         fee: u64,
         data: Option<Vec<u8>>,
+        gas_limit: u64,
+        gas_price: u64,
     ) -> Result<Self> {
         let timestamp = current_timestamp();
         
         // Create transaction without ID and signature first
         let mut tx = Self {
-            id: [0u8; 32],
+            id: TxHash::default(),
+            tx_type,
             timestamp,
+            nonce: 0,
             sender,
             recipient,
             amount,
             fee,
-            data,
+            data: data.map(Bytes::from),
+            gas_limit,
+            gas_price,
             signature: None,
+            eth_raw: None,
+            memo: None,
         };
         
         // Calculate the transaction ID (hash)
@@ -84,63 +334,323 @@ impl Transaction {
         Ok(tx)
     }
     
+    /// Attaches a memo, computing the transaction's ID again to cover it
+    ///
+    /// Must be done before the transaction is signed.
+    pub fn with_memo(mut self, memo: MemoField) -> Result<Self> {
+        self.memo = Some(memo);
+        self.id = self.calculate_hash()?;
+        Ok(self)
+    }
+    
+    /// Sets the sender's nonce, computing the transaction's ID again to cover it
+    ///
+    /// Must be done before the transaction is signed.
+    pub fn with_nonce(mut self, nonce: u64) -> Result<Self> {
+        self.nonce = nonce;
+        self.id = self.calculate_hash()?;
+        Ok(self)
+    }
+    
     /// Calculates the hash of this transaction (excluding the signature)
-    pub fn calculate_hash(&self) -> Result<Hash> {
-        // Create a copy without the signature for hashing
-        let hash_tx = Self {
-            id: [0u8; 32],
-            timestamp: self.timestamp,
-            sender: self.sender.clone(),
-            recipient: self.recipient.clone(),
-            amount: self.amount,
-            fee: self.fee,
-            data: self.data.clone(),
-            signature: None,
-        };
+    ///
+    /// The hash is stored in `id` when the transaction is created, so this
+    /// only needs calling to check a transaction's ID against its contents.
+    ///
+    /// See `hashing::hash_tx_canonical`.
+    pub fn calculate_hash(&self) -> Result<TxHash> {
+        hashing::hash_tx_canonical(self)
+    }
+    
+    /// Checks whether the transaction pays for the gas it consumes rather than a flat fee
+    pub fn is_metered(&self) -> bool {
+        matches!(self.tx_type, TransactionType::ContractDeploy | TransactionType::ContractCall)
+    }
+    
+    /// Gets the size of the transaction's data, in bytes
+    pub fn data_len(&self) -> usize {
+        self.data.as_ref().map_or(0, |data| data.len())
+    }
+    
+    /// Gets the number of bytes the transaction's memo stores
+    pub fn memo_len(&self) -> usize {
+        self.memo.as_ref().map_or(0, MemoField::len)
+    }
+    
+    /// Gets the least flat fee that pays for the transaction's memo
+    pub fn memo_fee(&self) -> u64 {
+        MEMO_FEE_PER_BYTE.saturating_mul(self.memo_len() as u64)
+    }
+    
+    /// Gets the largest data a transaction of this type may carry
+    pub fn max_data_size(&self) -> usize {
+        if self.tx_type == TransactionType::ContractDeploy {
+            MAX_DEPLOY_DATA_SIZE
+        } else {
+            MAX_DATA_SIZE
+        }
+    }
+    
+    /// Gets the most the transaction can be charged in fees
+    pub fn max_fee(&self) -> u64 {
+        if self.is_metered() {
+            self.gas_limit.saturating_mul(self.gas_price)
+        } else {
+            self.fee
+        }
+    }
+    
+    /// Gets the most the transaction can take from its sender's balance, its amount and its highest fee
+    pub fn max_cost(&self) -> u64 {
+        self.amount.saturating_add(self.max_fee())
+    }
+    
+    /// Gets the fee charged for a transaction that consumed `gas_used`
+    pub fn fee_for_gas(&self, gas_used: u64) -> u64 {
+        if self.is_metered() {
+            gas_used.min(self.gas_limit).saturating_mul(self.gas_price)
+        } else {
+            self.fee
+        }
+    }
+    
+    /// Derives the address of the contract created by this transaction
+    ///
+    /// The address depends only on the sender and the transaction ID, so every
+    /// node replaying the same block arrives at the same address.
+    pub fn contract_address(&self) -> String {
+        let mut hasher = Hasher::for_domain(HashDomain::ContractAddress);
+        hasher.update(self.sender.as_bytes()).update(self.id.as_ref());
+        let hash = hasher.finalize();
         
-        calculate_hash(&hash_tx)
+        format!("{}{}", CONTRACT_ADDRESS_PREFIX, hex::encode(&hash[..20]))
     }
     
-    /// Signs the transaction with the provided private key
-    pub fn sign(&mut self, _private_key: &[u8]) -> Result<()> {
-        // In a real implementation, this would use ed25519 or similar
-        // to sign the transaction with the private key
-        // For now, we'll just set a dummy signature
-        self.signature = Some(vec![1, 2, 3, 4]);
+    /// Signs the transaction with the sender's private key
+    ///
+    /// The sender's address determines the signature scheme.
+    pub fn sign(&mut self, private_key: &[u8]) -> Result<()> {
+        let scheme = SignatureScheme::from_address(&self.sender).ok_or_else(|| {
+            BlockchainError::InvalidTransaction(format!("Sender {} has no signing key", self.sender))
+        })?;
+        
+        let signature = signature::sign(scheme, private_key, self.id.as_ref())
+            .map_err(|e| BlockchainError::InvalidTransaction(e.to_string()))?;
+        self.signature = Some(signature.into());
         Ok(())
     }
     
+    /// Verifies the sender's signature of the transaction ID
+    ///
+    /// The sender's address names the key and scheme to verify with. Coinbase
+    /// transactions aren't checked, as the block's validator answers for them;
+    /// any other sender whose address encodes no key, genesis accounts
+    /// included, can't sign and is refused. A transaction imported from Ethereum must match the
+    /// raw transaction it carries, whose signature is verified instead.
+    pub fn verify_signature(&self) -> Result<()> {
+        if let Some(raw) = &self.eth_raw {
+            return eth_transaction::verify_import(self, raw)
+                .map_err(|e| BlockchainError::InvalidTransaction(format!("Invalid Ethereum transaction: {}", e)));
+        }
+        
+        if self.sender == COINBASE {
+            return Ok(());
+        }
+        if SignatureScheme::from_address(&self.sender).is_none() {
+            return Err(BlockchainError::InvalidSignature {
+                tx_id: self.id,
+                reason: format!("Sender {} has no signing key", self.sender),
+            });
+        }
+        
+        let signature = self.signature.as_deref().ok_or(BlockchainError::MissingSignature { tx_id: self.id })?;
+        signature::verify(&self.sender, self.id.as_ref(), signature)
+            .map_err(|e| BlockchainError::InvalidSignature { tx_id: self.id, reason: e.to_string() })
+    }
+    
     /// Validates the transaction structure and signature
     pub fn validate(&self) -> Result<()> {
-        // Check that amount is positive
-        if self.amount == 0 {
+        self.validate_contents()?;
+        self.verify_signature()
+    }
+    
+    /// Validates the transaction, skipping the signature if the cache holds it as verified
+    ///
+    /// A transaction whose signature verifies is added to the cache.
+    pub fn validate_cached(&self, cache: &VerifiedTxCache) -> Result<()> {
+        self.validate_contents()?;
+        if !cache.contains(self) {
+            self.verify_signature()?;
+            cache.insert(self);
+        }
+        Ok(())
+    }
+    
+    /// Checks the transaction's memo, if any, is within its size and paid for
+    ///
+    /// Flat fees must cover `memo_fee`; contract transactions pay data gas
+    /// for the memo instead.
+    pub fn validate_memo(&self) -> Result<()> {
+        let Some(memo) = &self.memo else {
+            return Ok(());
+        };
+        memo.validate()?;
+        
+        // An imported transaction's signature covers the Ethereum transaction alone
+        if self.eth_raw.is_some() {
             return Err(BlockchainError::InvalidTransaction(
-                "Transaction amount must be positive".to_string(),
+                "Transactions imported from Ethereum can't carry a memo".to_string(),
             ));
         }
         
-        // Verify the transaction ID matches its contents
-        let calculated_id = self.calculate_hash()?;
-        if calculated_id != self.id {
+        if !self.is_metered() && self.fee < self.memo_fee() {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Fee of {} doesn't pay for a memo of {} bytes, which needs {}",
+                self.fee,
+                self.memo_len(),
+                self.memo_fee()
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Validates the transaction structure and that its ID matches its contents
+    fn validate_contents(&self) -> Result<()> {
+        // Every node stores the data forever, so its size is capped
+        if self.data_len() > self.max_data_size() {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Transaction data of {} bytes exceeds the {} byte limit",
+                self.data_len(),
+                self.max_data_size()
+            )));
+        }
+        
+        // Contract transactions pay for the gas they use, so a flat fee would be ambiguous
+        if self.is_metered() && self.fee != 0 {
             return Err(BlockchainError::InvalidTransaction(
-                "Invalid transaction ID".to_string(),
+                "Contract transactions pay for gas and must not set a flat fee".to_string(),
             ));
         }
         
-        // In a real implementation, we would verify the signature here
-        // using the sender's public key
+        self.validate_memo()?;
+        
+        match self.tx_type {
+            TransactionType::ContractDeploy => {
+                // Deployments create a new address, so they can't name a recipient
+                if !self.recipient.is_empty() {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Contract deployment must have an empty recipient".to_string(),
+                    ));
+                }
+                
+                if self.data.as_ref().is_none_or(|data| data.is_empty()) {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Contract deployment requires init code".to_string(),
+                    ));
+                }
+            }
+            TransactionType::ContractCall => {
+                if self.recipient.is_empty() {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Contract call must name the contract address".to_string(),
+                    ));
+                }
+            }
+            TransactionType::RegisterValidator | TransactionType::EditValidator => {
+                // These only change the registry; stake moves with Stake transactions
+                if self.amount != 0 || !self.recipient.is_empty() {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Validator transactions must have no amount or recipient".to_string(),
+                    ));
+                }
+                
+                let data = self.data.as_ref().map_or(&[][..], |data| &data.0[..]);
+                if self.tx_type == TransactionType::RegisterValidator {
+                    ValidatorRegistration::from_data(data)?;
+                } else {
+                    ValidatorEdit::from_data(data)?;
+                }
+            }
+            TransactionType::SubmitProposal | TransactionType::Vote => {
+                // Only a proposal's deposit moves, and it's held by the proposal
+                if !self.recipient.is_empty() {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Governance transactions must have no recipient".to_string(),
+                    ));
+                }
+                
+                let data = self.data.as_ref().map_or(&[][..], |data| &data.0[..]);
+                if self.tx_type == TransactionType::SubmitProposal {
+                    ProposalSubmission::from_data(data)?;
+                    if self.amount < governance::MIN_PROPOSAL_DEPOSIT {
+                        return Err(BlockchainError::InvalidTransaction(format!(
+                            "Proposal deposit must be at least {} base units",
+                            governance::MIN_PROPOSAL_DEPOSIT
+                        )));
+                    }
+                } else {
+                    ProposalVote::from_data(data)?;
+                    if self.amount != 0 {
+                        return Err(BlockchainError::InvalidTransaction("Votes must have no amount".to_string()));
+                    }
+                }
+            }
+            TransactionType::SubmitEvidence | TransactionType::CancelSlash => {
+                // Penalties move between stakes and escrow, never to or from the sender
+                if self.amount != 0 || !self.recipient.is_empty() {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Slashing transactions must have no amount or recipient".to_string(),
+                    ));
+                }
+                
+                let data = self.data.as_ref().map_or(&[][..], |data| &data.0[..]);
+                if self.tx_type == TransactionType::SubmitEvidence {
+                    SlashingEvidence::from_data(data)?;
+                } else {
+                    SlashCancellation::from_data(data)?;
+                }
+            }
+            TransactionType::SetExecutionPolicy => {
+                // Only the policy changes
+                if self.amount != 0 || !self.recipient.is_empty() {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Policy updates must have no amount or recipient".to_string(),
+                    ));
+                }
+                
+                let data = self.data.as_ref().map_or(&[][..], |data| &data.0[..]);
+                PolicyUpdate::from_data(data)?;
+            }
+            _ => {
+                // Check that amount is positive
+                if self.amount == 0 {
+                    return Err(BlockchainError::InvalidTransaction(
+                        "Transaction amount must be positive".to_string(),
+                    ));
+                }
+            }
+        }
+        
+        // Verify the transaction ID matches its contents
+        let calculated_id = self.calculate_hash()?;
+        if calculated_id != self.id {
+            return Err(BlockchainError::InvalidTransactionId { tx_id: self.id });
+        }
         
         Ok(())
     }
     
     /// Creates a coinbase transaction for block rewards
     pub fn new_coinbase(recipient: String, reward: u64) -> Result<Self> {
-        Self::new(
+        Self::new_with_type(
+            TransactionType::Transfer,
             "COINBASE".to_string(),
             recipient,
             reward,
             0, // No fee for coinbase
             None,
+            0,
+            0,
         )
     }
 }
@@ -150,10 +660,14 @@ impl fmt::Display for Transaction {
         write!(
             f,
             "TX [{}]: {} -> {} ({} GENX)",
-            hex::encode(&self.id),
+            hex::encode(self.id),
             self.sender,
             self.recipient,
             self.amount
         )
     }
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
 }
\ No newline at end of file
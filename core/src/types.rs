@@ -0,0 +1,386 @@
+//! Typed hashes, addresses and byte strings
+//!
+//! Binary values in blocks, transactions and receipts use these types rather
+//! than bare arrays and vectors. They display and parse as `0x`-prefixed hex
+//! and serialize the same way in human-readable formats such as JSON, so RPC
+//! clients get hex strings instead of arrays of integers. Binary formats
+//! keep the compact encoding of the underlying bytes.
+//!
+//! Fields that are produced as raw bytes throughout the code base, such as
+//! log topics, keep their plain types and serialize as hex through the
+//! `hex_serde` helpers instead.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+/// Error parsing a hex value
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum HexError {
+    #[error("Invalid hex: {0}")]
+    InvalidHex(String),
+    
+    #[error("Expected {expected} bytes, got {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+}
+
+/// Decodes hex, with or without a `0x` prefix
+fn decode(text: &str) -> Result<Vec<u8>, HexError> {
+    hex::decode(text.strip_prefix("0x").unwrap_or(text)).map_err(|e| HexError::InvalidHex(e.to_string()))
+}
+
+/// Defines a fixed-size byte value displayed as hex
+macro_rules! fixed_bytes {
+    ($(#[$meta:meta])* $name:ident, $len:expr) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(pub [u8; $len]);
+        
+        impl $name {
+            /// Number of bytes in the value
+            pub const LEN: usize = $len;
+            
+            /// Gets the underlying bytes
+            pub fn to_bytes(self) -> [u8; $len] {
+                self.0
+            }
+        }
+        
+        impl From<[u8; $len]> for $name {
+            fn from(bytes: [u8; $len]) -> Self {
+                Self(bytes)
+            }
+        }
+        
+        impl From<$name> for [u8; $len] {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+        
+        impl Deref for $name {
+            type Target = [u8; $len];
+            
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+        
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+        
+        impl Borrow<[u8; $len]> for $name {
+            fn borrow(&self) -> &[u8; $len] {
+                &self.0
+            }
+        }
+        
+        impl PartialEq<[u8; $len]> for $name {
+            fn eq(&self, other: &[u8; $len]) -> bool {
+                self.0 == *other
+            }
+        }
+        
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "0x{}", hex::encode(self.0))
+            }
+        }
+        
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(self, f)
+            }
+        }
+        
+        impl FromStr for $name {
+            type Err = HexError;
+            
+            fn from_str(text: &str) -> Result<Self, Self::Err> {
+                let bytes = decode(text)?;
+                let actual = bytes.len();
+                bytes
+                    .try_into()
+                    .map(Self)
+                    .map_err(|_| HexError::InvalidLength { expected: $len, actual })
+            }
+        }
+        
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.collect_str(self)
+                } else {
+                    self.0.serialize(serializer)
+                }
+            }
+        }
+        
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                if deserializer.is_human_readable() {
+                    String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+                } else {
+                    <[u8; $len]>::deserialize(deserializer).map(Self)
+                }
+            }
+        }
+    };
+}
+
+fixed_bytes!(
+    /// Hash identifying a transaction
+    TxHash,
+    32
+);
+
+fixed_bytes!(
+    /// Hash identifying a block
+    BlockHash,
+    32
+);
+
+fixed_bytes!(
+    /// 20-byte EVM address
+    EvmAddress,
+    20
+);
+
+/// 20-byte EVM address, under its former name
+#[deprecated(note = "renamed to `EvmAddress`; `crate::Address` is an account address")]
+pub type Address = EvmAddress;
+
+/// Arbitrary binary data, such as a transaction payload or signature
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Bytes {
+    /// Gets the underlying bytes
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl From<Bytes> for Vec<u8> {
+    fn from(bytes: Bytes) -> Self {
+        bytes.0
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+    
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Bytes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl PartialEq<Vec<u8>> for Bytes {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<[u8]> for Bytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.0 == other
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(&self.0))
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = HexError;
+    
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        decode(text).map(Self)
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        hex_serde::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        hex_serde::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Serde helpers that write raw bytes as hex in human-readable formats
+///
+/// Use with `#[serde(with = "crate::types::hex_serde")]` on byte arrays and
+/// vectors. Binary formats encode the field exactly as its type would.
+pub mod hex_serde {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]> + Serialize,
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&format!("0x{}", hex::encode(value)))
+        } else {
+            value.serialize(serializer)
+        }
+    }
+    
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: TryFrom<Vec<u8>> + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            from_hex(&String::deserialize(deserializer)?)
+        } else {
+            T::deserialize(deserializer)
+        }
+    }
+    
+    fn from_hex<T: TryFrom<Vec<u8>>, E: de::Error>(text: &str) -> Result<T, E> {
+        let bytes = super::decode(text).map_err(E::custom)?;
+        let actual = bytes.len();
+        T::try_from(bytes).map_err(|_| E::custom(format!("unexpected length {}", actual)))
+    }
+    
+    /// Like the parent module, for a list of values
+    pub mod seq {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        
+        pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: AsRef<[u8]> + Serialize,
+            S: Serializer,
+        {
+            if serializer.is_human_readable() {
+                serializer.collect_seq(values.iter().map(|value| format!("0x{}", hex::encode(value))))
+            } else {
+                values.serialize(serializer)
+            }
+        }
+        
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+        where
+            T: TryFrom<Vec<u8>> + Deserialize<'de>,
+            D: Deserializer<'de>,
+        {
+            if deserializer.is_human_readable() {
+                Vec::<String>::deserialize(deserializer)?
+                    .iter()
+                    .map(|text| super::from_hex(text))
+                    .collect()
+            } else {
+                Vec::<T>::deserialize(deserializer)
+            }
+        }
+    }
+    
+    /// Like the parent module, for an optional value
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        
+        pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: AsRef<[u8]> + Serialize,
+            S: Serializer,
+        {
+            if serializer.is_human_readable() {
+                value.as_ref().map(|value| format!("0x{}", hex::encode(value))).serialize(serializer)
+            } else {
+                value.serialize(serializer)
+            }
+        }
+        
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            T: TryFrom<Vec<u8>> + Deserialize<'de>,
+            D: Deserializer<'de>,
+        {
+            if deserializer.is_human_readable() {
+                Option::<String>::deserialize(deserializer)?.as_deref().map(super::from_hex).transpose()
+            } else {
+                Option::<T>::deserialize(deserializer)
+            }
+        }
+    }
+    
+    /// Like the parent module, for a list of optional values
+    pub mod option_seq {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        
+        pub fn serialize<T, S>(values: &[Option<T>], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: AsRef<[u8]> + Serialize,
+            S: Serializer,
+        {
+            if serializer.is_human_readable() {
+                serializer.collect_seq(
+                    values
+                        .iter()
+                        .map(|value| value.as_ref().map(|value| format!("0x{}", hex::encode(value)))),
+                )
+            } else {
+                values.serialize(serializer)
+            }
+        }
+        
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<Option<T>>, D::Error>
+        where
+            T: TryFrom<Vec<u8>> + Deserialize<'de>,
+            D: Deserializer<'de>,
+        {
+            if deserializer.is_human_readable() {
+                Vec::<Option<String>>::deserialize(deserializer)?
+                    .iter()
+                    .map(|text| text.as_deref().map(super::from_hex).transpose())
+                    .collect()
+            } else {
+                Vec::<Option<T>>::deserialize(deserializer)
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,148 @@
+//! GENX amounts and their decimal notation
+//!
+//! Balances, fees and stakes are counted in base units, the smallest amount
+//! that can be transferred. One GENX is `GENX` base units: 10 to the power
+//! of `DECIMALS`, which the genesis configuration fixes. People read and
+//! type amounts in GENX, as decimals with at most `DECIMALS` digits after
+//! the point, which `parse_genx` and `format_genx` convert from and to.
+//!
+//! APIs taking amounts from users take an `Amount`, so a count of base
+//! units can't be passed where GENX were meant or the other way round.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub use crate::genesis::DECIMALS;
+
+/// Base units in one GENX
+pub const GENX: u64 = 10u64.pow(DECIMALS);
+
+/// Errors reading an amount of GENX
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AmountError {
+    #[error("Amount is empty")]
+    Empty,
+    
+    #[error("Invalid amount {0}: expected digits with at most one decimal point")]
+    Invalid(String),
+    
+    #[error("Amount {amount} has more than {decimals} decimals")]
+    TooPrecise { amount: String, decimals: u32 },
+    
+    #[error("Amount {0} is too large")]
+    Overflow(String),
+}
+
+impl AmountError {
+    /// Gets the stable numeric code of the error
+    pub fn error_code(&self) -> u32 {
+        match self {
+            AmountError::Empty => 1100,
+            AmountError::Invalid(_) => 1101,
+            AmountError::TooPrecise { .. } => 1102,
+            AmountError::Overflow(_) => 1103,
+        }
+    }
+}
+
+/// Reads an amount of GENX, such as `12.5`, as base units
+///
+/// Digits after the point beyond `DECIMALS` may only be zeros.
+pub fn parse_genx(amount: &str) -> Result<u64, AmountError> {
+    if amount.is_empty() {
+        return Err(AmountError::Empty);
+    }
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) || amount.ends_with('.') {
+        return Err(AmountError::Invalid(amount.to_string()));
+    }
+    
+    let decimals = DECIMALS as usize;
+    let (kept, dropped) = fraction.split_at(fraction.len().min(decimals));
+    if dropped.bytes().any(|byte| byte != b'0') {
+        return Err(AmountError::TooPrecise { amount: amount.to_string(), decimals: DECIMALS });
+    }
+    
+    let overflow = || AmountError::Overflow(amount.to_string());
+    let whole: u64 = whole.parse().map_err(|_| overflow())?;
+    let fraction: u64 = format!("{:0<width$}", kept, width = decimals).parse().map_err(|_| overflow())?;
+    whole.checked_mul(GENX).and_then(|units| units.checked_add(fraction)).ok_or_else(overflow)
+}
+
+/// Writes base units as GENX, without trailing zeros after the point
+///
+/// Whole amounts have no point at all, so `150_000_000` is `1.5` and
+/// `200_000_000` is `2`.
+pub fn format_genx(units: u64) -> String {
+    let whole = units / GENX;
+    let fraction = units % GENX;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0width$}", fraction, width = DECIMALS as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// An amount of GENX, kept in base units
+///
+/// Serializes as its number of base units, and displays and parses as GENX.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    /// No GENX
+    pub const ZERO: Amount = Amount(0);
+    
+    /// Creates an amount of base units
+    pub const fn from_base_units(units: u64) -> Self {
+        Amount(units)
+    }
+    
+    /// Creates an amount of whole GENX, if it fits
+    pub fn from_genx(genx: u64) -> Option<Self> {
+        genx.checked_mul(GENX).map(Amount)
+    }
+    
+    /// Gets the amount in base units
+    pub const fn base_units(self) -> u64 {
+        self.0
+    }
+    
+    /// Adds two amounts, unless the sum overflows
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+    
+    /// Subtracts an amount, unless it's larger than this one
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} GENX", format_genx(self.0))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+    
+    /// Reads an amount of GENX, with or without a trailing ` GENX`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_suffix("GENX").map(str::trim_end).unwrap_or(s);
+        parse_genx(s).map(Amount)
+    }
+}
\ No newline at end of file
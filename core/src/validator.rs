@@ -0,0 +1,323 @@
+//! Validator registry for the Crypto Trust Bank blockchain
+//!
+//! Validators join by sending a `RegisterValidator` transaction naming the
+//! key they sign blocks with, which may differ from the address funding
+//! their stake, along with a moniker, a website or contact and the
+//! commission they take from delegators. `EditValidator` transactions update
+//! the metadata later.
+//!
+//! Commission rates are in basis points. So that delegators aren't caught
+//! out, a validator's rate may change at most once per epoch and by at most
+//! `MAX_COMMISSION_CHANGE` each time.
+//!
+//! Block rewards and fee tips are paid to the validator's payout address,
+//! so they needn't go to a key kept online. It's the operator's address
+//! unless the registration names another. A new payout address set by an
+//! edit is paid from the start of the next epoch, so every block of an
+//! epoch pays the same address.
+
+use serde::{Deserialize, Serialize};
+
+use crate::paging::SortOrder;
+use crate::signature;
+use crate::{BlockchainError, Result};
+
+/// Number of blocks in an epoch
+pub const EPOCH_LENGTH: u64 = 100;
+
+/// Highest commission rate, in basis points (100%)
+pub const MAX_COMMISSION_RATE: u32 = 10_000;
+
+/// Most a commission rate may change in one epoch, in basis points (1%)
+pub const MAX_COMMISSION_CHANGE: u32 = 100;
+
+/// Longest moniker, in bytes
+pub const MAX_MONIKER_LENGTH: usize = 64;
+
+/// Longest website or contact, in bytes
+pub const MAX_WEBSITE_LENGTH: usize = 140;
+
+/// Gets the epoch a block height falls in
+pub fn epoch_of(height: u64) -> u64 {
+    height / EPOCH_LENGTH
+}
+
+/// What listings of validators are sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidatorSort {
+    /// Stake, highest first by default, ties broken by address
+    #[default]
+    Stake,
+    
+    /// Address, in order by default
+    Address,
+}
+
+impl ValidatorSort {
+    /// Gets the name of the listing sorted this way, which its cursors are tied to
+    pub fn listing(&self) -> &'static str {
+        match self {
+            Self::Stake => "validators_by_stake",
+            Self::Address => "validators_by_address",
+        }
+    }
+    
+    /// Gets the direction the listing is sorted in unless a request says otherwise
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            Self::Stake => SortOrder::Descending,
+            Self::Address => SortOrder::Ascending,
+        }
+    }
+    
+    /// Gets the sort key of a validator with a stake
+    pub fn key(&self, address: &str, stake: u64) -> (u64, String) {
+        match self {
+            Self::Stake => (stake, address.to_string()),
+            Self::Address => (0, address.to_string()),
+        }
+    }
+}
+
+/// Payload of a `RegisterValidator` transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorRegistration {
+    /// Human-readable name
+    pub moniker: String,
+    
+    /// Website or contact
+    pub website: String,
+    
+    /// Commission taken from delegators, in basis points
+    pub commission_rate: u32,
+    
+    /// Address of the key the validator signs blocks with
+    pub consensus_key: String,
+    
+    /// Address rewards are paid to, the operator's if left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payout_address: Option<String>,
+}
+
+/// Payload of an `EditValidator` transaction; fields left out are unchanged
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidatorEdit {
+    /// New human-readable name
+    pub moniker: Option<String>,
+    
+    /// New website or contact
+    pub website: Option<String>,
+    
+    /// New commission rate, in basis points
+    pub commission_rate: Option<u32>,
+    
+    /// New payout address, paid from the next epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payout_address: Option<String>,
+}
+
+/// A registered validator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorInfo {
+    /// Address that registered the validator and funds its stake
+    pub operator: String,
+    
+    /// Human-readable name
+    pub moniker: String,
+    
+    /// Website or contact
+    pub website: String,
+    
+    /// Commission taken from delegators, in basis points
+    pub commission_rate: u32,
+    
+    /// Address of the key the validator signs blocks with
+    pub consensus_key: String,
+    
+    /// Height of the block the validator was registered in
+    pub registered_at: u64,
+    
+    /// Height of the block the commission rate was last set in
+    pub commission_changed_at: u64,
+    
+    /// Address rewards are paid to
+    pub payout_address: String,
+    
+    /// Payout address set by an edit, paid from the epoch after `payout_changed_at`
+    pub pending_payout_address: Option<String>,
+    
+    /// Height of the block the payout address was last set in
+    pub payout_changed_at: u64,
+}
+
+impl ValidatorRegistration {
+    /// Encodes the registration as transaction data
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+    
+    /// Decodes a registration from transaction data and validates it
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let registration: Self = serde_json::from_slice(data).map_err(|e| {
+            BlockchainError::InvalidTransaction(format!("Invalid validator registration: {}", e))
+        })?;
+        registration.validate()?;
+        Ok(registration)
+    }
+    
+    /// Checks the metadata and commission rate are within bounds and the consensus key is an address
+    pub fn validate(&self) -> Result<()> {
+        validate_moniker(&self.moniker)?;
+        validate_website(&self.website)?;
+        validate_commission_rate(self.commission_rate)?;
+        
+        if signature::parse_address(&self.consensus_key).is_none() {
+            return Err(BlockchainError::InvalidTransaction(
+                format!("Consensus key {} is not a public key address", self.consensus_key)
+            ));
+        }
+        if let Some(payout_address) = &self.payout_address {
+            validate_payout_address(payout_address)?;
+        }
+        Ok(())
+    }
+}
+
+impl ValidatorEdit {
+    /// Encodes the edit as transaction data
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+    
+    /// Decodes an edit from transaction data and validates it
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let edit: Self = serde_json::from_slice(data).map_err(|e| {
+            BlockchainError::InvalidTransaction(format!("Invalid validator edit: {}", e))
+        })?;
+        edit.validate()?;
+        Ok(edit)
+    }
+    
+    /// Checks the edit changes something and the new values are within bounds
+    ///
+    /// Whether the commission rate may change by this much is checked when
+    /// the edit is applied, against the validator's current rate.
+    pub fn validate(&self) -> Result<()> {
+        if self.moniker.is_none() && self.website.is_none() && self.commission_rate.is_none() && self.payout_address.is_none() {
+            return Err(BlockchainError::InvalidTransaction("Validator edit changes nothing".to_string()));
+        }
+        if let Some(moniker) = &self.moniker {
+            validate_moniker(moniker)?;
+        }
+        if let Some(website) = &self.website {
+            validate_website(website)?;
+        }
+        if let Some(commission_rate) = self.commission_rate {
+            validate_commission_rate(commission_rate)?;
+        }
+        if let Some(payout_address) = &self.payout_address {
+            validate_payout_address(payout_address)?;
+        }
+        Ok(())
+    }
+}
+
+impl ValidatorInfo {
+    /// Creates the registry entry for a registration made at the given height
+    pub fn new(operator: String, registration: ValidatorRegistration, height: u64) -> Self {
+        Self {
+            payout_address: registration.payout_address.unwrap_or_else(|| operator.clone()),
+            operator,
+            moniker: registration.moniker,
+            website: registration.website,
+            commission_rate: registration.commission_rate,
+            consensus_key: registration.consensus_key,
+            registered_at: height,
+            commission_changed_at: height,
+            pending_payout_address: None,
+            payout_changed_at: height,
+        }
+    }
+    
+    /// Gets the address the block at a height pays the validator's rewards to
+    pub fn payout_address_at(&self, height: u64) -> &str {
+        match &self.pending_payout_address {
+            Some(pending) if epoch_of(height) > epoch_of(self.payout_changed_at) => pending,
+            _ => &self.payout_address,
+        }
+    }
+    
+    /// Applies an edit made at the given height, enforcing the limits on commission changes
+    pub fn apply_edit(&mut self, edit: ValidatorEdit, height: u64) -> Result<()> {
+        if let Some(commission_rate) = edit.commission_rate.filter(|&rate| rate != self.commission_rate) {
+            if epoch_of(height) <= epoch_of(self.commission_changed_at) {
+                return Err(BlockchainError::InvalidTransaction(
+                    format!("Commission of {} was already set in epoch {}", self.operator, epoch_of(height))
+                ));
+            }
+            
+            let change = commission_rate.abs_diff(self.commission_rate);
+            if change > MAX_COMMISSION_CHANGE {
+                return Err(BlockchainError::InvalidTransaction(
+                    format!("Commission change of {} bps exceeds the limit of {} bps per epoch", change, MAX_COMMISSION_CHANGE)
+                ));
+            }
+            
+            self.commission_rate = commission_rate;
+            self.commission_changed_at = height;
+        }
+        
+        if let Some(payout_address) = edit.payout_address {
+            // A change already paid from is settled before the next is made
+            self.payout_address = self.payout_address_at(height).to_string();
+            self.pending_payout_address = (payout_address != self.payout_address).then_some(payout_address);
+            self.payout_changed_at = height;
+        }
+        
+        if let Some(moniker) = edit.moniker {
+            self.moniker = moniker;
+        }
+        if let Some(website) = edit.website {
+            self.website = website;
+        }
+        Ok(())
+    }
+}
+
+fn validate_moniker(moniker: &str) -> Result<()> {
+    if moniker.trim().is_empty() || moniker.len() > MAX_MONIKER_LENGTH {
+        return Err(BlockchainError::InvalidTransaction(
+            format!("Moniker must be 1 to {} bytes", MAX_MONIKER_LENGTH)
+        ));
+    }
+    Ok(())
+}
+
+fn validate_website(website: &str) -> Result<()> {
+    if website.len() > MAX_WEBSITE_LENGTH {
+        return Err(BlockchainError::InvalidTransaction(
+            format!("Website must be at most {} bytes", MAX_WEBSITE_LENGTH)
+        ));
+    }
+    Ok(())
+}
+
+fn validate_payout_address(payout_address: &str) -> Result<()> {
+    if signature::parse_address(payout_address).is_none() {
+        return Err(BlockchainError::InvalidTransaction(
+            format!("Payout address {} is not a public key address", payout_address)
+        ));
+    }
+    Ok(())
+}
+
+fn validate_commission_rate(commission_rate: u32) -> Result<()> {
+    if commission_rate > MAX_COMMISSION_RATE {
+        return Err(BlockchainError::InvalidTransaction(
+            format!("Commission rate {} bps exceeds {} bps", commission_rate, MAX_COMMISSION_RATE)
+        ));
+    }
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,160 @@
+//! Validator sets committed to by the chain
+//!
+//! From the activation height set with the genesis block (see
+//! `genesis::get_validator_set_height`), the first block of each epoch
+//! commits to the validators active in that epoch: its header's
+//! `validator_set_hash` is the hash of the set chosen from the state its
+//! parent left, by `ValidatorSelection`. Blocks committing to any other set,
+//! and blocks that commit when they shouldn't, are rejected. The genesis
+//! block never commits, and neither does a chain without an activation
+//! height.
+//!
+//! A set's hash is the hash of its binary encoding (see `wire`), so a
+//! light client holding a set can check it against a header without
+//! replaying any blocks. The consensus crate proves each epoch's set from
+//! the previous one with finality votes for the committing block.
+
+use serde::{Deserialize, Serialize};
+
+use crate::governance::GovernedParameter;
+use crate::hashing::{self, HashDomain};
+use crate::rlp::RlpItem;
+use crate::state::State;
+use crate::units::GENX;
+use crate::validator::EPOCH_LENGTH;
+use crate::wire::{self, Wire, WireError};
+use crate::Hash;
+
+/// A validator of an epoch's active set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorSetMember {
+    /// Address that registered the validator
+    pub operator: String,
+    
+    /// Address of the key the validator signs with
+    pub consensus_key: String,
+    
+    /// Stake the validator held when the set was chosen
+    pub stake: u64,
+}
+
+/// The validators active in an epoch, highest stake first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorSet {
+    /// Epoch the set is active in
+    pub epoch: u64,
+    
+    /// Active validators, by stake then operator address
+    pub members: Vec<ValidatorSetMember>,
+}
+
+impl ValidatorSet {
+    /// Gets the hash the first block of the epoch commits to
+    pub fn hash(&self) -> Hash {
+        hashing::hash_in(HashDomain::ValidatorSet, &self.to_bytes())
+    }
+    
+    /// Gets the member operated by an address
+    pub fn member(&self, operator: &str) -> Option<&ValidatorSetMember> {
+        self.members.iter().find(|member| member.operator == operator)
+    }
+    
+    /// Gets the stake of all the members together
+    pub fn total_stake(&self) -> u64 {
+        self.members.iter().fold(0u64, |total, member| total.saturating_add(member.stake))
+    }
+}
+
+/// Gets the height of the first block of an epoch, which commits to its validator set
+pub fn first_height(epoch: u64) -> u64 {
+    epoch.saturating_mul(EPOCH_LENGTH)
+}
+
+/// How the validators active in an epoch are chosen from the registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorSelection {
+    /// Least stake a validator must hold to be chosen
+    pub min_stake: u64,
+    
+    /// Most validators chosen
+    pub set_size: usize,
+}
+
+impl Default for ValidatorSelection {
+    fn default() -> Self {
+        Self { min_stake: 1000 * GENX, set_size: 21 }
+    }
+}
+
+impl ValidatorSelection {
+    /// Gets the selection in effect for the block at a height, with the
+    /// values governance set in place of the configured ones
+    pub fn governed(&self, state: &State, height: u64) -> Self {
+        Self {
+            min_stake: state.governed_value(GovernedParameter::MinStake, height).unwrap_or(self.min_stake),
+            set_size: state
+                .governed_value(GovernedParameter::ValidatorSetSize, height)
+                .map_or(self.set_size, |size| size as usize),
+        }
+    }
+    
+    /// Chooses the validators of an epoch from a state's registry
+    ///
+    /// These are the `set_size` registered validators holding the most
+    /// stake, of those holding at least `min_stake`, as governed at the
+    /// epoch's first block. Validators with equal stakes are ordered by
+    /// operator address.
+    pub fn select(&self, state: &State, epoch: u64) -> ValidatorSet {
+        let selection = self.governed(state, first_height(epoch));
+        let mut validators = state.get_validators();
+        validators.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.operator.cmp(&b.0.operator)));
+        
+        let members = validators
+            .into_iter()
+            .filter(|(_, stake)| *stake >= selection.min_stake)
+            .take(selection.set_size)
+            .map(|(info, stake)| ValidatorSetMember {
+                operator: info.operator.clone(),
+                consensus_key: info.consensus_key.clone(),
+                stake,
+            })
+            .collect();
+        ValidatorSet { epoch, members }
+    }
+}
+
+impl Wire for ValidatorSetMember {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            wire::string(&self.operator),
+            wire::string(&self.consensus_key),
+            RlpItem::uint(self.stake as u128),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self, WireError> {
+        let fields = wire::fields(item, 3)?;
+        Ok(Self {
+            operator: wire::decode_string(&fields[0])?,
+            consensus_key: wire::decode_string(&fields[1])?,
+            stake: fields[2].as_u64()?,
+        })
+    }
+}
+
+impl Wire for ValidatorSet {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            RlpItem::uint(self.epoch as u128),
+            RlpItem::List(self.members.iter().map(Wire::to_rlp).collect()),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self, WireError> {
+        let fields = wire::fields(item, 2)?;
+        Ok(Self {
+            epoch: fields[0].as_u64()?,
+            members: fields[1].as_list()?.iter().map(ValidatorSetMember::from_rlp).collect::<Result<_, _>>()?,
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+//! Cache of transactions whose signatures have been verified
+//!
+//! A transaction's signature is usually checked three times: when the node
+//! admits it to its mempool, when a validator packs it into a block, and
+//! when that block is validated on its way into the chain. Validating with
+//! a `VerifiedTxCache` (see `Transaction::validate_cached` and
+//! `Block::validate_cached`) verifies it only the first time.
+//!
+//! Entries are keyed by transaction ID and hold the signature that
+//! verified, so a transaction only hits the cache if its ID matches its
+//! contents, which validation checks first, and it carries the same
+//! signature. That structural check is cheap next to a signature and is
+//! always repeated, so a transaction forged to reuse a cached ID still
+//! fails. Results never go stale, since a transaction's ID and signature
+//! decide them alone, so entries are never invalidated; the cache holds a
+//! bounded number and evicts the least recently used. Transactions imported
+//! from Ethereum are never cached, since their ID is the hash of the raw
+//! transaction rather than of their fields.
+//!
+//! `Blockchain::validate_chain` audits every block without the cache.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::transaction::Transaction;
+use crate::{Bytes, TxHash};
+
+/// Entries held by a cache unless configured otherwise
+pub const DEFAULT_CAPACITY: usize = 16 * 1024;
+
+/// How often signed transactions were found in a cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Lookups finding the transaction's signature verified
+    pub hits: u64,
+    
+    /// Lookups not finding it, each followed by verifying the signature
+    pub misses: u64,
+}
+
+/// Bounded set of transactions whose signatures verified, safe to share between threads
+#[derive(Debug)]
+pub struct VerifiedTxCache {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Most transactions held
+    capacity: usize,
+    
+    /// Signature that verified for each transaction ID, and when it was last used
+    signatures: HashMap<TxHash, (Bytes, u64)>,
+    
+    /// Transaction IDs with when they were used, least recent first
+    ///
+    /// An ID is pushed again each time it's used, so entries older than
+    /// the one in `signatures` are stale and skipped.
+    order: VecDeque<(TxHash, u64)>,
+    
+    /// Use counter, standing in for time
+    clock: u64,
+    
+    stats: CacheStats,
+}
+
+impl VerifiedTxCache {
+    /// Creates a cache holding at most `capacity` transactions
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: Mutex::new(Entries { capacity, ..Entries::default() }) }
+    }
+    
+    /// Gets the number of transactions in the cache
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().signatures.len()
+    }
+    
+    /// Checks whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// Gets the most transactions the cache holds
+    pub fn capacity(&self) -> usize {
+        self.entries.lock().unwrap().capacity
+    }
+    
+    /// Changes the most transactions the cache holds, evicting the least recently used beyond it
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.capacity = capacity;
+        entries.evict();
+    }
+    
+    /// Gets how often signed transactions were looked up and found
+    pub fn stats(&self) -> CacheStats {
+        self.entries.lock().unwrap().stats
+    }
+    
+    /// Checks whether a transaction's signature has verified
+    ///
+    /// Only meaningful once the transaction's ID has been checked against its contents.
+    pub(crate) fn contains(&self, tx: &Transaction) -> bool {
+        let (Some(signature), None) = (&tx.signature, &tx.eth_raw) else {
+            return false;
+        };
+        
+        let mut entries = self.entries.lock().unwrap();
+        let hit = entries.signatures.get(&tx.id).is_some_and(|(verified, _)| verified == signature);
+        if hit {
+            entries.stats.hits += 1;
+            entries.touch(tx.id, None);
+        } else {
+            entries.stats.misses += 1;
+        }
+        hit
+    }
+    
+    /// Records that a transaction's signature verified
+    pub(crate) fn insert(&self, tx: &Transaction) {
+        let (Some(signature), None) = (&tx.signature, &tx.eth_raw) else {
+            return;
+        };
+        
+        let mut entries = self.entries.lock().unwrap();
+        if entries.capacity > 0 {
+            entries.touch(tx.id, Some(signature.clone()));
+            entries.evict();
+        }
+    }
+}
+
+impl Entries {
+    /// Marks a transaction as just used, first setting its signature if given
+    fn touch(&mut self, tx_id: TxHash, signature: Option<Bytes>) {
+        self.clock += 1;
+        let used = self.clock;
+        match (self.signatures.get_mut(&tx_id), signature) {
+            (Some(entry), signature) => {
+                entry.1 = used;
+                if let Some(signature) = signature {
+                    entry.0 = signature;
+                }
+            }
+            (None, Some(signature)) => {
+                self.signatures.insert(tx_id, (signature, used));
+            }
+            (None, None) => return,
+        }
+        self.order.push_back((tx_id, used));
+        
+        // Drop stale uses once they outnumber the live ones
+        if self.order.len() > 2 * self.signatures.len() + 64 {
+            let signatures = &self.signatures;
+            self.order.retain(|(id, used)| signatures.get(id).is_some_and(|entry| entry.1 == *used));
+        }
+    }
+    
+    /// Evicts the least recently used transactions beyond the capacity
+    fn evict(&mut self) {
+        while self.signatures.len() > self.capacity {
+            let Some((tx_id, used)) = self.order.pop_front() else {
+                break;
+            };
+            if self.signatures.get(&tx_id).is_some_and(|entry| entry.1 == used) {
+                self.signatures.remove(&tx_id);
+            }
+        }
+    }
+}
+
+impl Default for VerifiedTxCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
\ No newline at end of file
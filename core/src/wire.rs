@@ -0,0 +1,353 @@
+//! Canonical binary encoding of blocks, transactions and receipts
+//!
+//! Nodes exchange blocks, transactions and receipts in this form. Each type
+//! encodes as an RLP list of its fields in declaration order, with:
+//!
+//! - integers as RLP integers, and booleans as 0 or 1;
+//! - strings as their UTF-8 bytes;
+//! - hashes as exactly 32 bytes;
+//! - optional fields as a list of zero or one item.
+//!
+//! The exception is a block header's `validator_set_hash`, which only the
+//! first block of an epoch carries (see `validator_set`): it's appended as a
+//! ninth field when set and left out otherwise, so headers from before
+//! validator sets were committed to keep their encoding. Transactions'
+//! and receipts' memos are appended the same way, as a list of a tag, 0
+//! for a plain memo and 1 for a commitment, and the memo or hash. A
+//! transaction with a nonzero nonce has its memo appended as an optional
+//! field instead, followed by the nonce.
+//!
+//! RLP decoding accepts canonical input only and these rules leave no
+//! choices of their own, so every value has exactly one encoding and
+//! decoding then re-encoding gives back the same bytes. Transaction IDs and
+//! block hashes are still computed over the JSON form.
+
+use thiserror::Error;
+
+use crate::block::{Block, BlockHeader};
+use crate::memo::MemoField;
+use crate::receipt::{Log, Receipt};
+use crate::rlp::{self, RlpError, RlpItem};
+use crate::transaction::{Transaction, TransactionType};
+use crate::{BlockHash, Bytes, TxHash};
+
+/// Error decoding a value from its binary encoding
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WireError {
+    #[error("Invalid RLP: {0}")]
+    Rlp(#[from] RlpError),
+    
+    #[error("Expected {expected} fields, got {got}")]
+    FieldCount { expected: usize, got: usize },
+    
+    #[error("Invalid {0}")]
+    InvalidValue(&'static str),
+}
+
+type Result<T> = std::result::Result<T, WireError>;
+
+/// Type with a canonical binary encoding
+pub trait Wire: Sized {
+    /// Converts the value to its RLP item
+    fn to_rlp(&self) -> RlpItem;
+    
+    /// Reads a value from its RLP item
+    fn from_rlp(item: &RlpItem) -> Result<Self>;
+    
+    /// Encodes the value
+    fn to_bytes(&self) -> Vec<u8> {
+        rlp::encode(&self.to_rlp())
+    }
+    
+    /// Decodes input holding exactly one value
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_rlp(&rlp::decode(data)?)
+    }
+}
+
+impl Wire for Transaction {
+    fn to_rlp(&self) -> RlpItem {
+        let mut fields = vec![
+            hash(&self.id.0),
+            RlpItem::uint(transaction_type_tag(self.tx_type) as u128),
+            RlpItem::uint(self.timestamp as u128),
+            string(&self.sender),
+            string(&self.recipient),
+            RlpItem::uint(self.amount as u128),
+            RlpItem::uint(self.fee as u128),
+            optional(self.data.as_ref().map(bytes)),
+            RlpItem::uint(self.gas_limit as u128),
+            RlpItem::uint(self.gas_price as u128),
+            optional(self.signature.as_ref().map(bytes)),
+            optional(self.eth_raw.as_ref().map(bytes)),
+        ];
+        // The memo and nonce are left out when absent, so other transactions encode as they did
+        if self.nonce != 0 {
+            fields.push(optional(self.memo.as_ref().map(memo)));
+            fields.push(RlpItem::uint(self.nonce as u128));
+        } else {
+            fields.extend(self.memo.as_ref().map(memo));
+        }
+        RlpItem::List(fields)
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self> {
+        let fields = item.as_list()?;
+        if !matches!(fields.len(), 12..=14) {
+            return Err(WireError::FieldCount { expected: 12, got: fields.len() });
+        }
+        let (memo, nonce) = match fields.len() {
+            14 => {
+                let nonce = fields[13].as_u64()?;
+                if nonce == 0 {
+                    return Err(WireError::InvalidValue("nonce"));
+                }
+                (decode_optional(&fields[12])?, nonce)
+            }
+            _ => (fields.get(12), 0),
+        };
+        Ok(Self {
+            id: TxHash(decode_hash(&fields[0])?),
+            tx_type: transaction_type(fields[1].as_u64()?)?,
+            timestamp: fields[2].as_u64()?,
+            nonce,
+            sender: decode_string(&fields[3])?,
+            recipient: decode_string(&fields[4])?,
+            amount: fields[5].as_u64()?,
+            fee: fields[6].as_u64()?,
+            data: decode_optional(&fields[7])?.map(decode_bytes).transpose()?,
+            gas_limit: fields[8].as_u64()?,
+            gas_price: fields[9].as_u64()?,
+            signature: decode_optional(&fields[10])?.map(decode_bytes).transpose()?,
+            eth_raw: decode_optional(&fields[11])?.map(decode_bytes).transpose()?,
+            memo: memo.map(decode_memo).transpose()?,
+        })
+    }
+}
+
+impl Wire for BlockHeader {
+    fn to_rlp(&self) -> RlpItem {
+        let mut fields = vec![
+            RlpItem::uint(self.version as u128),
+            RlpItem::uint(self.height as u128),
+            RlpItem::uint(self.timestamp as u128),
+            hash(&self.prev_hash.0),
+            hash(&self.merkle_root),
+            string(&self.validator),
+            RlpItem::uint(self.base_fee as u128),
+            optional(self.signature.as_ref().map(bytes)),
+        ];
+        fields.extend(self.validator_set_hash.as_ref().map(hash));
+        RlpItem::List(fields)
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self> {
+        let fields = item.as_list()?;
+        if !matches!(fields.len(), 8 | 9) {
+            return Err(WireError::FieldCount { expected: 8, got: fields.len() });
+        }
+        Ok(Self {
+            version: u32::try_from(fields[0].as_u64()?).map_err(|_| WireError::InvalidValue("block version"))?,
+            height: fields[1].as_u64()?,
+            timestamp: fields[2].as_u64()?,
+            prev_hash: BlockHash(decode_hash(&fields[3])?),
+            merkle_root: decode_hash(&fields[4])?,
+            validator: decode_string(&fields[5])?,
+            base_fee: fields[6].as_u64()?,
+            signature: decode_optional(&fields[7])?.map(decode_bytes).transpose()?,
+            validator_set_hash: fields.get(8).map(decode_hash).transpose()?,
+        })
+    }
+}
+
+impl Wire for Block {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            self.header().to_rlp(),
+            RlpItem::List(self.transactions.iter().map(Wire::to_rlp).collect()),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self> {
+        let fields = fields(item, 2)?;
+        let header = BlockHeader::from_rlp(&fields[0])?;
+        let transactions = fields[1].as_list()?.iter().map(Transaction::from_rlp).collect::<Result<_>>()?;
+        Ok(Block::from_parts(header, transactions))
+    }
+}
+
+impl Wire for Log {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            string(&self.address),
+            RlpItem::List(self.topics.iter().map(hash).collect()),
+            RlpItem::Bytes(self.data.clone()),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self> {
+        let fields = fields(item, 3)?;
+        Ok(Self {
+            address: decode_string(&fields[0])?,
+            topics: fields[1].as_list()?.iter().map(decode_hash).collect::<Result<_>>()?,
+            data: fields[2].as_bytes()?.to_vec(),
+        })
+    }
+}
+
+impl Wire for Receipt {
+    fn to_rlp(&self) -> RlpItem {
+        let mut fields = vec![
+            hash(&self.tx_id.0),
+            RlpItem::uint(self.block_height as u128),
+            boolean(self.success),
+            RlpItem::uint(self.gas_used as u128),
+            RlpItem::uint(self.cumulative_gas_used as u128),
+            optional(self.contract_address.as_deref().map(string)),
+            RlpItem::List(self.logs.iter().map(Wire::to_rlp).collect()),
+            optional(self.revert_reason.as_deref().map(string)),
+            RlpItem::List(self.destroyed_contracts.iter().map(|address| string(address)).collect()),
+        ];
+        fields.extend(self.memo.as_ref().map(memo));
+        RlpItem::List(fields)
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self> {
+        let fields = item.as_list()?;
+        if !matches!(fields.len(), 9 | 10) {
+            return Err(WireError::FieldCount { expected: 9, got: fields.len() });
+        }
+        Ok(Self {
+            tx_id: TxHash(decode_hash(&fields[0])?),
+            block_height: fields[1].as_u64()?,
+            success: decode_boolean(&fields[2])?,
+            gas_used: fields[3].as_u64()?,
+            cumulative_gas_used: fields[4].as_u64()?,
+            contract_address: decode_optional(&fields[5])?.map(decode_string).transpose()?,
+            logs: fields[6].as_list()?.iter().map(Log::from_rlp).collect::<Result<_>>()?,
+            revert_reason: decode_optional(&fields[7])?.map(decode_string).transpose()?,
+            destroyed_contracts: fields[8].as_list()?.iter().map(decode_string).collect::<Result<_>>()?,
+            memo: fields.get(9).map(decode_memo).transpose()?,
+        })
+    }
+}
+
+/// Gets the fields of a list, which must have `count` of them
+pub fn fields(item: &RlpItem, count: usize) -> Result<&[RlpItem]> {
+    let fields = item.as_list()?;
+    if fields.len() != count {
+        return Err(WireError::FieldCount { expected: count, got: fields.len() });
+    }
+    Ok(fields)
+}
+
+/// Encodes a string
+pub fn string(value: &str) -> RlpItem {
+    RlpItem::Bytes(value.as_bytes().to_vec())
+}
+
+/// Decodes a string, which must be valid UTF-8
+pub fn decode_string(item: &RlpItem) -> Result<String> {
+    String::from_utf8(item.as_bytes()?.to_vec()).map_err(|_| WireError::InvalidValue("UTF-8 string"))
+}
+
+/// Encodes a hash
+pub fn hash(value: &[u8; 32]) -> RlpItem {
+    RlpItem::Bytes(value.to_vec())
+}
+
+/// Decodes a hash, which must be exactly 32 bytes
+pub fn decode_hash(item: &RlpItem) -> Result<[u8; 32]> {
+    item.as_bytes()?.try_into().map_err(|_| WireError::InvalidValue("hash"))
+}
+
+/// Encodes a byte string
+pub fn bytes(value: &Bytes) -> RlpItem {
+    RlpItem::Bytes(value.0.clone())
+}
+
+/// Decodes a byte string
+pub fn decode_bytes(item: &RlpItem) -> Result<Bytes> {
+    Ok(Bytes(item.as_bytes()?.to_vec()))
+}
+
+/// Encodes a boolean
+pub fn boolean(value: bool) -> RlpItem {
+    RlpItem::uint(value as u128)
+}
+
+/// Decodes a boolean, which must be 0 or 1
+pub fn decode_boolean(item: &RlpItem) -> Result<bool> {
+    match item.as_u64()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(WireError::InvalidValue("boolean")),
+    }
+}
+
+/// Encodes an optional field
+pub fn optional(value: Option<RlpItem>) -> RlpItem {
+    RlpItem::List(value.into_iter().collect())
+}
+
+/// Decodes an optional field, a list of at most one item
+pub fn decode_optional(item: &RlpItem) -> Result<Option<&RlpItem>> {
+    match item.as_list()? {
+        [] => Ok(None),
+        [value] => Ok(Some(value)),
+        _ => Err(WireError::InvalidValue("optional field")),
+    }
+}
+
+/// Encodes a memo as its tag and content
+fn memo(value: &MemoField) -> RlpItem {
+    match value {
+        MemoField::Plain(memo) => RlpItem::List(vec![RlpItem::uint(0), string(memo)]),
+        MemoField::Committed(commitment) => RlpItem::List(vec![RlpItem::uint(1), hash(commitment)]),
+    }
+}
+
+/// Decodes a memo
+fn decode_memo(item: &RlpItem) -> Result<MemoField> {
+    let fields = fields(item, 2)?;
+    match fields[0].as_u64()? {
+        0 => Ok(MemoField::Plain(decode_string(&fields[1])?)),
+        1 => Ok(MemoField::Committed(decode_hash(&fields[1])?)),
+        _ => Err(WireError::InvalidValue("memo kind")),
+    }
+}
+
+fn transaction_type_tag(tx_type: TransactionType) -> u8 {
+    match tx_type {
+        TransactionType::Transfer => 0,
+        TransactionType::ContractDeploy => 1,
+        TransactionType::ContractCall => 2,
+        TransactionType::Stake => 3,
+        TransactionType::Unstake => 4,
+        TransactionType::RegisterValidator => 5,
+        TransactionType::EditValidator => 6,
+        TransactionType::SubmitProposal => 7,
+        TransactionType::Vote => 8,
+        TransactionType::SubmitEvidence => 9,
+        TransactionType::CancelSlash => 10,
+        TransactionType::SetExecutionPolicy => 11,
+    }
+}
+
+fn transaction_type(tag: u64) -> Result<TransactionType> {
+    match tag {
+        0 => Ok(TransactionType::Transfer),
+        1 => Ok(TransactionType::ContractDeploy),
+        2 => Ok(TransactionType::ContractCall),
+        3 => Ok(TransactionType::Stake),
+        4 => Ok(TransactionType::Unstake),
+        5 => Ok(TransactionType::RegisterValidator),
+        6 => Ok(TransactionType::EditValidator),
+        7 => Ok(TransactionType::SubmitProposal),
+        8 => Ok(TransactionType::Vote),
+        9 => Ok(TransactionType::SubmitEvidence),
+        10 => Ok(TransactionType::CancelSlash),
+        11 => Ok(TransactionType::SetExecutionPolicy),
+        _ => Err(WireError::InvalidValue("transaction type")),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,98 @@
+//! Checks addresses and amounts are checked when made, serialize as their plain forms, and the old untyped APIs still agree
+//!
+//! Run with `cargo test -p core --features testutil --test address`. Makes
+//! addresses from well formed strings and from empty, too long and badly
+//! written ones, checking each of the latter is refused with the error
+//! saying why, by every conversion and when deserializing. Checks addresses
+//! serialize as their string and amounts as their base units. Then checks
+//! the deprecated untyped transaction, balance and stake APIs give what the
+//! typed ones do on a test chain.
+
+use core::address::{AddressError, MAX_ADDRESS_LEN};
+use core::chainbuilder::TestChain;
+use core::transaction::Transaction;
+use core::units::GENX;
+use core::{Address, Amount};
+
+/// Seed of the chain built
+const SEED: u64 = 197;
+
+/// Checks well formed addresses are taken as they are, by every conversion
+#[test]
+fn check_well_formed() {
+    let longest = "a".repeat(MAX_ADDRESS_LEN);
+    for text in ["GENX_DEVELOPMENT_FUND", "0x52908400098527886E0F7030069857D2E4169EE7", "node-1.example", "x", longest.as_str()] {
+        let address = Address::new(text).unwrap();
+        assert_eq!(address.as_str(), text);
+        assert_eq!(address, text);
+        assert_eq!(address.to_string(), text);
+        assert_eq!(text.parse::<Address>().unwrap(), address);
+        assert_eq!(Address::try_from(text).unwrap(), address);
+        assert_eq!(Address::try_from(text.to_string()).unwrap(), address);
+        assert_eq!(String::from(address), text);
+    }
+}
+
+/// Checks empty, too long and badly written addresses are refused by every conversion, with why
+#[test]
+fn check_refused() {
+    let too_long = "a".repeat(MAX_ADDRESS_LEN + 1);
+    let cases = [
+        ("", AddressError::Empty, 1110),
+        (too_long.as_str(), AddressError::TooLong(too_long.clone()), 1111),
+        ("5 GENX", AddressError::InvalidCharacter { address: "5 GENX".to_string(), character: ' ' }, 1112),
+        ("GENX/alice", AddressError::InvalidCharacter { address: "GENX/alice".to_string(), character: '/' }, 1112),
+        ("GENXé", AddressError::InvalidCharacter { address: "GENXé".to_string(), character: 'é' }, 1112),
+    ];
+    for (text, error, code) in cases {
+        assert_eq!(Address::new(text), Err(error.clone()));
+        assert_eq!(text.parse::<Address>(), Err(error.clone()));
+        assert_eq!(Address::try_from(text), Err(error.clone()));
+        assert_eq!(error.error_code(), code);
+        let json = serde_json::to_string(text).unwrap();
+        let refused = serde_json::from_str::<Address>(&json).unwrap_err();
+        assert!(refused.to_string().contains(&error.to_string()), "{}", refused);
+    }
+}
+
+/// Checks addresses serialize as their string and amounts as their base units
+#[test]
+fn check_serde() {
+    let address = Address::new("GENX_TREASURY").unwrap();
+    assert_eq!(serde_json::to_string(&address).unwrap(), "\"GENX_TREASURY\"");
+    assert_eq!(serde_json::from_str::<Address>("\"GENX_TREASURY\"").unwrap(), address);
+    
+    let amount = Amount::from_base_units(GENX + GENX / 4);
+    assert_eq!(serde_json::to_string(&amount).unwrap(), "125000000");
+    assert_eq!(serde_json::from_str::<Amount>("125000000").unwrap(), amount);
+    assert_eq!(amount.to_string(), "1.25 GENX");
+    assert_eq!("1.25".parse::<Amount>().unwrap(), amount);
+    assert_eq!(Amount::from_genx(u64::MAX), None);
+}
+
+/// Checks the deprecated untyped APIs agree with the typed ones
+#[test]
+#[allow(deprecated)]
+fn check_untyped_shims() {
+    let chain = TestChain::new(SEED);
+    let (alice, bob) = (Address::new(chain.address("alice")).unwrap(), Address::new(chain.address("bob")).unwrap());
+    
+    let typed = Transaction::new(alice.clone(), bob.clone(), Amount::from_base_units(5 * GENX), Amount::from_base_units(1_000), None).unwrap();
+    let untyped = Transaction::new_untyped(alice.to_string(), bob.to_string(), 5 * GENX, 1_000, None).unwrap();
+    assert_eq!(
+        (&typed.sender, &typed.recipient, typed.amount, typed.fee, typed.tx_type),
+        (&untyped.sender, &untyped.recipient, untyped.amount, untyped.fee, untyped.tx_type)
+    );
+    
+    assert_eq!(chain.blockchain().get_balance_untyped(alice.as_str()).unwrap(), chain.blockchain().get_balance(&alice).unwrap().base_units());
+    let state = chain.blockchain().get_state();
+    let mut state = state.lock().unwrap();
+    assert_eq!(state.get_balance_untyped(alice.as_str()), state.get_balance(&alice).base_units());
+    assert_eq!(state.get_balance(&alice), Amount::from_base_units(chain.expected_balance("alice")));
+    
+    state.update_validator_stake_untyped(bob.to_string(), 7 * GENX);
+    assert_eq!(state.get_validator_stake(&bob), Amount::from_base_units(7 * GENX));
+    assert_eq!(state.get_validator_stake_untyped(bob.as_str()), 7 * GENX);
+    state.update_validator_stake(bob.clone(), Amount::from_base_units(3 * GENX));
+    assert_eq!(state.get_validator_stake_untyped(bob.as_str()), 3 * GENX);
+}
\ No newline at end of file
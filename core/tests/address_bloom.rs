@@ -0,0 +1,136 @@
+//! Checks the address blooms never miss a used address and keep near their false positive rate
+//!
+//! Run with `cargo test -p core --features testutil --test address_bloom`.
+//! Builds a chain paying fresh addresses over several epochs, with blooms
+//! sized for fewer addresses than an epoch uses so they're rebuilt larger,
+//! then checks every address used is possibly used and confirmed at its
+//! first block, that addresses never used are possibly used at about the
+//! configured rate at most, and that rollbacks and the block store keep up.
+
+use std::collections::BTreeMap;
+
+use core::address_bloom::BloomParams;
+use core::block_store::BlockStore;
+use core::chainbuilder::TestChain;
+use core::validator::{epoch_of, EPOCH_LENGTH};
+use core::Address;
+
+/// Seed of the chain built
+const SEED: u64 = 11;
+
+/// Blooms sized for fewer addresses than each epoch uses
+const PARAMS: BloomParams = BloomParams { capacity: 50, false_positive_rate: 0.01 };
+
+/// Blocks built, spanning three epochs
+const BLOCKS: u64 = 250;
+
+/// Addresses never used checked for false positives
+const UNUSED: usize = 20_000;
+
+/// Factor of the configured false positive rate a bloom's may reach
+const SLACK: f64 = 1.5;
+
+/// Builds the chain, returning it with the height each paid address was first used at
+fn build() -> (TestChain, BTreeMap<String, u64>) {
+    let mut chain = TestChain::new(SEED);
+    chain.blockchain_mut().set_bloom_params(PARAMS).unwrap();
+    let mut first_used = BTreeMap::new();
+    for block in 1..=BLOCKS {
+        let paid: Vec<String> = (0..2).map(|i| format!("user-{}-{}", block, i)).collect();
+        chain.with_block(|b| {
+            for address in &paid {
+                b.transfer("alice", address, 1);
+            }
+            b
+        });
+        for address in paid {
+            first_used.entry(address).or_insert(block);
+        }
+    }
+    // Funded in the genesis block
+    first_used.insert(chain.address("alice"), 0);
+    (chain, first_used)
+}
+
+/// Checks every address used is possibly used and confirmed at its first block
+#[test]
+fn check_used() {
+    let (chain, first_used) = build();
+    let blockchain = chain.blockchain();
+    for (address, height) in &first_used {
+        let address = Address::new(address.as_str()).unwrap();
+        assert!(blockchain.address_possibly_used(&address), "{} was used", address);
+        assert_eq!(blockchain.address_first_used(&address), Some(*height), "{}", address);
+    }
+    
+    // Each epoch used more addresses than the blooms were sized for, so they were rebuilt larger
+    for epoch in 0..=epoch_of(BLOCKS) {
+        let bloom = blockchain.address_bloom(epoch).unwrap();
+        assert!(bloom.items() > PARAMS.capacity, "epoch {} holds {} addresses", epoch, bloom.items());
+        assert!(!bloom.is_full());
+    }
+}
+
+/// Checks addresses never used are possibly used at about the configured rate at most in each epoch
+///
+/// A bloom near its capacity may claim a little more than the rate, and
+/// the rate measured on a sample varies, so both get some slack.
+#[test]
+fn check_false_positive_rate() {
+    let (chain, _) = build();
+    let blockchain = chain.blockchain();
+    let unused: Vec<String> = (0..UNUSED).map(|i| format!("never-used-{}", i)).collect();
+    for epoch in 0..=epoch_of(BLOCKS) {
+        let bloom = blockchain.address_bloom(epoch).unwrap();
+        let false_positives = unused.iter().filter(|address| bloom.contains(address)).count();
+        let rate = false_positives as f64 / UNUSED as f64;
+        assert!(rate <= PARAMS.false_positive_rate * SLACK, "epoch {} has a false positive rate of {}", epoch, rate);
+        assert!(bloom.false_positive_rate() <= PARAMS.false_positive_rate * SLACK);
+    }
+    
+    // A false positive is never confirmed
+    for address in unused.iter().take(1_000) {
+        let address = Address::new(address.as_str()).unwrap();
+        assert_eq!(blockchain.address_first_used(&address), None);
+    }
+}
+
+/// Checks the addresses only rolled back blocks used are no longer confirmed
+#[test]
+fn check_rollback() {
+    let (mut chain, first_used) = build();
+    let height = BLOCKS - 60;
+    chain.blockchain_mut().rollback_to(height).unwrap();
+    let blockchain = chain.blockchain();
+    for (address, first) in &first_used {
+        let address = Address::new(address.as_str()).unwrap();
+        if *first <= height {
+            assert!(blockchain.address_possibly_used(&address));
+            assert_eq!(blockchain.address_first_used(&address), Some(*first));
+        } else {
+            assert_eq!(blockchain.address_first_used(&address), None, "{} was rolled back", address);
+        }
+    }
+    assert!(blockchain.address_bloom(epoch_of(BLOCKS)).is_none(), "the last epoch was rolled back entirely");
+}
+
+/// Checks the blooms are saved with the blocks
+#[test]
+fn check_store() {
+    let (chain, _) = build();
+    let dir = std::env::temp_dir().join(format!("genx-address-bloom-{}", std::process::id()));
+    let mut blockchain = TestChain::new(SEED).into_blockchain();
+    blockchain.set_bloom_params(PARAMS).unwrap();
+    blockchain.set_block_store(dir.clone()).unwrap();
+    for block in chain.blocks().into_iter().skip(1) {
+        blockchain.add_block(block.clone()).unwrap();
+    }
+    
+    let store = BlockStore::open_existing(&dir).unwrap();
+    for epoch in 0..=epoch_of(BLOCKS) {
+        assert_eq!(store.get_bloom(epoch).unwrap().as_ref(), blockchain.address_bloom(epoch));
+    }
+    blockchain.rollback_to(epoch_of(BLOCKS) * EPOCH_LENGTH - 1).unwrap();
+    assert_eq!(store.get_bloom(epoch_of(BLOCKS)).unwrap(), None);
+    let _ = std::fs::remove_dir_all(&dir);
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+//! Checks transfers whose amounts overflow a balance are refused
+//!
+//! Run with `cargo test -p core --features testutil --test balances`.
+//! Applies a transfer of `u64::MAX` plus a fee, whose total overflows, to a
+//! state and in a block, and checks each is refused without changing any
+//! balance, then checks minting past the largest balance is refused too.
+
+use core::chainbuilder::TestChain;
+use core::state::State;
+use core::transaction::Transaction;
+use core::units::{Amount, GENX};
+use core::{Address, BlockchainError};
+
+/// Seed of the chain built
+const SEED: u64 = 37;
+
+/// Checks a state refuses a transfer whose amount and fee add up past `u64::MAX`
+#[test]
+fn check_state() {
+    let (alice, bob) = (Address::new("GENX_ALICE").unwrap(), Address::new("GENX_BOB").unwrap());
+    let mut state = State::new();
+    state.apply_transaction(&Transaction::new_coinbase(alice.to_string(), 100 * GENX).unwrap()).unwrap();
+    
+    let tx = Transaction::new(alice.clone(), bob.clone(), Amount::from_base_units(u64::MAX), Amount::from_base_units(1), None).unwrap();
+    let error = state.apply_transaction(&tx).unwrap_err();
+    assert!(matches!(error, BlockchainError::InvalidTransaction(ref message) if message.contains("overflows")), "{}", error);
+    assert_eq!(state.get_balance(&alice).base_units(), 100 * GENX);
+    assert_eq!(state.get_balance(&bob).base_units(), 0);
+    assert_eq!(state.get_nonce(&alice), 0);
+}
+
+/// Checks a state refuses minting that would overflow a balance or the total supply
+#[test]
+fn check_mint() {
+    let alice = Address::new("GENX_ALICE").unwrap();
+    let mut state = State::new();
+    state.apply_transaction(&Transaction::new_coinbase(alice.to_string(), u64::MAX).unwrap()).unwrap();
+    
+    let error = state.apply_transaction(&Transaction::new_coinbase(alice.to_string(), 1).unwrap()).unwrap_err();
+    assert!(matches!(error, BlockchainError::InvalidTransaction(ref message) if message.contains("overflows")), "{}", error);
+    assert_eq!(state.get_balance(&alice).base_units(), u64::MAX);
+}
+
+/// Checks a chain refuses a block holding a transfer of `u64::MAX` plus a fee
+#[test]
+fn check_block() {
+    let mut chain = TestChain::new(SEED);
+    let (alice, bob) = (chain.account("alice").address.clone(), chain.account("bob").address.clone());
+    let tx = Transaction::new(alice.clone(), bob.clone(), Amount::from_base_units(u64::MAX), Amount::from_base_units(1), None).unwrap();
+    let block = chain.next_block(|b| b.transaction(tx));
+    
+    let error = chain.blockchain_mut().add_block(block).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::InvalidTransaction(message) if message.contains("overflows")), "{}", error);
+    assert_eq!(chain.height(), 0);
+    assert_eq!(chain.blockchain().get_balance(&alice).unwrap().base_units(), chain.expected_balance("alice"));
+    assert_eq!(chain.blockchain().get_balance(&bob).unwrap().base_units(), chain.expected_balance("bob"));
+}
\ No newline at end of file
@@ -0,0 +1,58 @@
+//! Checks a chain finds its blocks by hash
+//!
+//! Run with `cargo test -p core --features testutil --test block_index`.
+//! Builds a few blocks, looks each up by its hash, genesis included, then
+//! checks unknown hashes and the hashes of rolled back blocks aren't found.
+
+use core::chainbuilder::TestChain;
+use core::units::GENX;
+use core::BlockHash;
+
+/// Seed of the chain built
+const SEED: u64 = 19;
+
+/// Builds a chain of a transfer and three empty blocks
+fn build() -> TestChain {
+    let mut chain = TestChain::new(SEED);
+    chain.with_block(|b| b.transfer("alice", "bob", GENX));
+    chain.with_empty_blocks(3);
+    chain
+}
+
+/// Checks every block of the chain, genesis included, is found by its hash
+#[test]
+fn check_lookup() {
+    let chain = build();
+    for height in 0..=chain.height() {
+        let block = chain.blockchain().get_block_by_height(height).unwrap();
+        let hash = block.hash().unwrap();
+        assert_eq!(chain.blockchain().get_height_by_hash(&hash), Some(height));
+        let found = chain.blockchain().get_block_by_hash(&hash).expect("a block of the chain isn't found by its hash");
+        assert_eq!(found.hash().unwrap(), hash);
+    }
+
+}
+
+/// Checks a hash of no block finds nothing
+#[test]
+fn check_unknown() {
+    let chain = build();
+    let unknown = BlockHash([7; 32]);
+    assert!(chain.blockchain().get_block_by_hash(&unknown).is_none());
+    assert_eq!(chain.blockchain().get_height_by_hash(&unknown), None);
+
+}
+
+/// Checks rolled back blocks are forgotten, and those replacing them found
+#[test]
+fn check_rollback() {
+    let mut chain = build();
+    let removed = chain.blockchain_mut().rollback_to(2).unwrap();
+    assert_eq!(removed.len(), 2);
+    for block in &removed {
+        assert!(chain.blockchain().get_block_by_hash(&block.hash().unwrap()).is_none(), "a rolled back block is still found");
+    }
+    chain.with_block(|b| b.transfer("bob", "carol", GENX));
+    let tip = chain.blockchain().get_latest_block().unwrap().hash().unwrap();
+    assert_eq!(chain.blockchain().get_height_by_hash(&tip), Some(3));
+}
\ No newline at end of file
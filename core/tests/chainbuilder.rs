@@ -0,0 +1,94 @@
+//! Checks that test chains are deterministic and keep their expected balances
+//!
+//! Run with `cargo test -p core --features testutil --test chainbuilder`.
+//! Builds the same chain twice from a seed and compares the blocks' bytes,
+//! then checks the expected balances against the chain's after transfers,
+//! rewards and deployments, and that the blocks replay onto a fresh chain.
+
+use core::chain::Blockchain;
+use core::chainbuilder::{TestChain, TestChainConfig};
+use core::units::GENX;
+use core::wire::Wire;
+
+/// Seed of the chains built
+const SEED: u64 = 7;
+
+/// Builds a few blocks of transfers and a deployment
+fn build(seed: u64) -> TestChain {
+    let mut chain = TestChain::new(seed);
+    chain
+        .with_block(|b| b.transfer("alice", "bob", 5 * GENX).transfer("bob", "carol", GENX))
+        .with_block(|b| b.deploy("carol", vec![0x60, 0x00]))
+        .with_empty_blocks(2);
+    chain
+}
+
+/// Checks that the same seed and blocks give the same bytes, and another seed other keys
+#[test]
+fn check_deterministic() {
+    let (first, second) = (build(SEED), build(SEED));
+    let encode = |chain: &TestChain| chain.blocks().iter().map(|block| block.to_bytes()).collect::<Vec<_>>();
+    assert_eq!(encode(&first), encode(&second), "chains built from seed {} differ", SEED);
+    assert_eq!(first.blockchain().get_latest_hash(), second.blockchain().get_latest_hash());
+    
+    let other = build(SEED + 1);
+    assert_ne!(first.account("alice").address, other.account("alice").address);
+    assert_ne!(encode(&first), encode(&other));
+}
+
+/// Checks the expected balances follow transfers, fees, rewards and deployments
+#[test]
+fn check_balances() {
+    let mut chain = build(SEED);
+    chain.assert_balances();
+    
+    let fee = chain.config().transfer_fee;
+    assert_eq!(chain.expected_balance("alice"), 995 * GENX - fee);
+    assert_eq!(chain.expected_balance("bob"), 1004 * GENX - fee);
+    assert_eq!(chain.contracts().len(), 1);
+    
+    // Identical transfers in one block get their own IDs
+    chain.with_block(|b| b.transfer("alice", "bob", 1).transfer("alice", "bob", 1));
+    let block = chain.blockchain().get_latest_block().unwrap();
+    let transfers: Vec<_> = block.transactions.iter().filter(|tx| chain.account("alice").address == tx.sender).collect();
+    assert_eq!(transfers.len(), 2);
+    assert_ne!(transfers[0].id, transfers[1].id);
+    assert_eq!(chain.expected_balance("alice"), 995 * GENX - 2 - 3 * fee);
+    chain.assert_balances();
+    
+    // Rewards go to the validator
+    assert!(chain.expected_balance("validator") > 0);
+}
+
+/// Checks several validators propose blocks in turn
+#[test]
+fn check_validators_take_turns() {
+    let config = TestChainConfig {
+        validators: vec![("v1".to_string(), 1_000 * GENX), ("v2".to_string(), 1_000 * GENX)],
+        ..TestChainConfig::default()
+    };
+    let mut chain = TestChain::with_config(SEED, config);
+    chain.with_empty_blocks(4);
+    let proposers: Vec<_> = chain.blocks()[1..].iter().map(|block| block.header().validator.clone()).collect();
+    let (v1, v2) = (chain.account("v1").address.to_string(), chain.account("v2").address.to_string());
+    assert_eq!(proposers, vec![v1.clone(), v2.clone(), v1, v2]);
+    chain.assert_balances();
+}
+
+/// Checks a block overdrawing an account can be built but isn't added
+#[test]
+fn check_rejected_block() {
+    let mut chain = TestChain::new(SEED);
+    let block = chain.next_block(|b| b.transfer("alice", "bob", 2_000 * GENX));
+    assert!(chain.blockchain_mut().add_block(block).is_err());
+    assert_eq!(chain.height(), 0);
+}
+
+/// Checks the blocks replay onto a fresh chain
+#[test]
+fn check_replay() {
+    let chain = build(SEED);
+    let blocks = chain.blocks().into_iter().cloned();
+    let replayed = Blockchain::from_blocks(blocks, None).unwrap();
+    assert_eq!(replayed.get_latest_hash(), chain.blockchain().get_latest_hash());
+}
\ No newline at end of file
@@ -0,0 +1,102 @@
+//! Checks deploying a contract through a block yields its address and receipt
+//!
+//! Run with `cargo test -p core --test contract_deploy`. Applies a block of
+//! two deployments to a state with a stand-in engine that stores the init
+//! code as the contract, one succeeding and one failing, and checks the
+//! receipts carry the derived contract address only on success, the gas
+//! used and its running total, and that the fee is charged and the
+//! endowment moved only to the contract that was created.
+
+use core::block::{Block, BlockHeader};
+use core::executor::{ContractExecutor, ExecutionOutcome};
+use core::state::{ContractAccount, State, StateAccess};
+use core::transaction::Transaction;
+use core::units::{Amount, GENX};
+use core::{Address, BlockHash, Result};
+
+/// Gas each deployment uses
+const DEPLOY_GAS: u64 = 50_000;
+
+/// Gas limit of each deployment
+const GAS_LIMIT: u64 = 100_000;
+
+/// Base fee of the block and the tip paid on top of it per unit of gas
+const BASE_FEE: u64 = 10;
+const TIP: u64 = 2;
+
+/// Value endowed to each contract
+const ENDOWMENT: u64 = 1_000;
+
+/// First byte of init code the stand-in engine fails to deploy
+const FAILING: u8 = 0xfe;
+
+/// Engine storing init code as the contract, failing code that starts with `FAILING`
+#[derive(Debug)]
+struct StandIn;
+
+impl ContractExecutor for StandIn {
+    fn deploy(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        let code = tx.data.as_ref().map(|data| data.0.clone()).unwrap_or_default();
+        if code.first() == Some(&FAILING) {
+            return Ok(ExecutionOutcome { gas_used: DEPLOY_GAS, ..Default::default() });
+        }
+        
+        let address = tx.contract_address();
+        let contract = ContractAccount { code, metadata: Vec::new(), creator: tx.sender.clone(), deployed_at: header.height };
+        state.insert_contract(&address, contract);
+        Ok(ExecutionOutcome { success: true, gas_used: DEPLOY_GAS, contract_address: Some(address), ..Default::default() })
+    }
+    
+    fn call(&mut self, _: &Transaction, _: &BlockHeader, _: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        unreachable!("the block makes no calls")
+    }
+}
+
+/// Checks a successful and a failed deployment are applied with their receipts
+#[test]
+fn check_deploy() {
+    let (alice, validator) = (Address::new("GENX_ALICE").unwrap(), Address::new("GENX_VALIDATOR").unwrap());
+    let mut state = State::new();
+    state.apply_transaction(&Transaction::new_coinbase(alice.to_string(), 100 * GENX).unwrap()).unwrap();
+    
+    let deploy = |code: Vec<u8>, nonce: u64| {
+        Transaction::new_contract_deploy(alice.to_string(), ENDOWMENT, code, GAS_LIMIT, BASE_FEE + TIP)
+            .and_then(|tx| tx.with_nonce(nonce))
+            .unwrap()
+    };
+    let (deployed, failed) = (deploy(vec![0x60, 0x00], 0), deploy(vec![FAILING], 1));
+    let transactions = vec![deployed.clone(), failed.clone()];
+    let block = Block::new(1, BlockHash::default(), transactions, validator.to_string(), BASE_FEE).unwrap();
+    
+    let burned = state.get_total_burned();
+    let receipts = state.apply_block_with_executor(&block, Some(&mut StandIn)).unwrap();
+    assert_eq!(receipts.len(), 2);
+    
+    // The successful deployment names the address derived from its transaction
+    let address = deployed.contract_address();
+    let receipt = &receipts[0];
+    assert_eq!((receipt.tx_id, receipt.block_height), (deployed.id, 1));
+    assert!(receipt.success);
+    assert_eq!(receipt.contract_address.as_deref(), Some(address.as_str()));
+    assert_eq!((receipt.gas_used, receipt.cumulative_gas_used), (DEPLOY_GAS, DEPLOY_GAS));
+    
+    let contract = state.get_contract(&address).expect("the deployed contract isn't stored");
+    assert_eq!(contract.code, [0x60, 0x00]);
+    assert_eq!((contract.creator.as_str(), contract.deployed_at), (alice.as_str(), 1));
+    assert_eq!(state.get_balance(&Address::new(address).unwrap()), Amount::from_base_units(ENDOWMENT));
+    
+    // The failed one names no address, stores nothing and keeps its endowment
+    let receipt = &receipts[1];
+    assert_eq!(receipt.tx_id, failed.id);
+    assert!(!receipt.success);
+    assert_eq!(receipt.contract_address, None);
+    assert_eq!((receipt.gas_used, receipt.cumulative_gas_used), (DEPLOY_GAS, 2 * DEPLOY_GAS));
+    let address = failed.contract_address();
+    assert!(state.get_contract(&address).is_none(), "a failed deployment stored a contract");
+    assert_eq!(state.get_balance(&Address::new(address).unwrap()), Amount::from_base_units(0));
+    
+    // Both used their nonces and paid for the gas they used: the base fee burned, the tip to the validator
+    assert_eq!(state.get_nonce(&alice), 2);
+    assert_eq!(state.get_total_burned() - burned, 2 * DEPLOY_GAS * BASE_FEE);
+    assert_eq!(state.get_balance(&validator), Amount::from_base_units(2 * DEPLOY_GAS * TIP));
+}
\ No newline at end of file
@@ -0,0 +1,94 @@
+//! Checks that decoding untrusted input never panics and stays within memory bounds
+//!
+//! Run with `cargo test -p core --features testutil --test decoding`.
+//! Feeds `CASES` generated inputs, then every regression input, to each of
+//! the crate's decoders, checking that it returns and that what it holds
+//! at once stays under `MAX_GROWTH` times the input, plus `SLACK` bytes.
+//! Set `DECODING_SEED` to start from another seed.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use core::testutil::{self, Decoder, Generator};
+
+/// Generated cases checked on each run
+const CASES: u64 = 2000;
+
+/// Most bytes a decoder may hold at once for each byte of input
+///
+/// A list of one-byte strings decodes to an item of 32 bytes, holding one
+/// more on the heap, for each byte.
+const MAX_GROWTH: usize = 40;
+
+/// Bytes a decoder may hold at once whatever its input
+const SLACK: usize = 256 * 1024;
+
+/// Allocator keeping count of the bytes allocated, and the most at once
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Held while decoding, so one test's allocations aren't counted against another's
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Decodes an input, checking the decoder returns within its memory bound
+fn check(decoder: Decoder, input: &[u8]) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    testutil::assert_decodes(decoder, input);
+    
+    let held = PEAK.load(Ordering::Relaxed) - before;
+    let bound = input.len() * MAX_GROWTH + SLACK;
+    assert!(
+        held <= bound,
+        "The {} decoder held {} bytes decoding {} bytes, more than {}",
+        decoder.0,
+        held,
+        input.len(),
+        bound
+    );
+}
+
+/// Checks every decoder on the generated inputs
+#[test]
+fn check_generated() {
+    let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let seed = std::env::var("DECODING_SEED").ok().and_then(|seed| seed.parse().ok()).unwrap_or(0);
+    let decoders = testutil::decoders();
+    for case in seed..seed + CASES {
+        let input = testutil::fuzz_input(&mut Generator::new(case));
+        for &decoder in &decoders {
+            check(decoder, &input);
+        }
+    }
+}
+
+/// Checks every decoder on the inputs of past failures
+#[test]
+fn check_regressions() {
+    let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let decoders = testutil::decoders();
+    for (_, input) in &testutil::decode_regressions() {
+        for &decoder in &decoders {
+            check(decoder, input);
+        }
+    }
+}
\ No newline at end of file
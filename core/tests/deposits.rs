@@ -0,0 +1,209 @@
+//! Checks contract storage locks refundable deposits from the senders storing it
+//!
+//! Run with `cargo test -p core --test deposits`. Applies blocks of contract
+//! transactions to a state with a stand-in engine, whose calls write, clear
+//! or self-destruct as their data says. Checks deploying locks the code
+//! deposit from the deployer and writing an empty slot locks a slot deposit
+//! from the caller, both reported as locked rather than in the balance, and
+//! that clearing the slot or destroying the contract refunds the original
+//! payers whoever does it. Then checks an execution whose sender can't lock
+//! the deposit fails and pays its fee, and a deployment whose sender can't
+//! hold the deposit for its code is refused before it runs.
+
+use core::block::{Block, BlockHeader};
+use core::executor::{ContractExecutor, ExecutionOutcome};
+use core::receipt::Receipt;
+use core::state::{ContractAccount, State, StateAccess};
+use core::transaction::Transaction;
+use core::units::GENX;
+use core::{Address, BlockHash, BlockchainError, Result};
+
+/// Gas each execution uses
+const GAS: u64 = 30_000;
+
+/// Gas limit of each transaction
+const GAS_LIMIT: u64 = 100_000;
+
+/// Base fee of the blocks, which every transaction pays exactly
+const BASE_FEE: u64 = 10;
+
+/// Size of the code deployed
+const CODE_SIZE: usize = 100;
+
+/// Call data operations of the stand-in engine: write `[op, key, value..]`, clear `[op, key]` or self-destruct `[op]`
+const WRITE: u8 = 1;
+const CLEAR: u8 = 2;
+const DESTROY: u8 = 3;
+
+/// Engine storing init code as the contract, and doing what its call data says
+#[derive(Debug)]
+struct StandIn;
+
+impl ContractExecutor for StandIn {
+    fn deploy(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        let address = tx.contract_address();
+        let code = tx.data.as_ref().map(|data| data.0.clone()).unwrap_or_default();
+        let contract = ContractAccount { code, metadata: Vec::new(), creator: tx.sender.clone(), deployed_at: header.height };
+        state.insert_contract(&address, contract);
+        Ok(ExecutionOutcome { success: true, gas_used: GAS, contract_address: Some(address), ..Default::default() })
+    }
+    
+    fn call(&mut self, tx: &Transaction, _: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        let data = tx.data.as_ref().map(|data| data.0.clone()).unwrap_or_default();
+        let mut destroyed_contracts = Vec::new();
+        match data[0] {
+            WRITE => state.set_storage(&tx.recipient, vec![data[1]], Some(data[2..].to_vec())),
+            CLEAR => state.set_storage(&tx.recipient, vec![data[1]], None),
+            DESTROY => {
+                state.remove_contract(&tx.recipient);
+                destroyed_contracts.push(tx.recipient.clone());
+            }
+            op => panic!("unknown operation {}", op),
+        }
+        Ok(ExecutionOutcome { success: true, gas_used: GAS, destroyed_contracts, ..Default::default() })
+    }
+}
+
+/// A state the test accounts are funded in, applying one block at a time
+struct Chain {
+    state: State,
+    height: u64,
+}
+
+impl Chain {
+    /// Funds each account with an amount
+    fn new(funds: &[(&str, u64)]) -> Self {
+        let mut state = State::new();
+        for (account, amount) in funds {
+            state.apply_transaction(&Transaction::new_coinbase(account.to_string(), *amount).unwrap()).unwrap();
+        }
+        Self { state, height: 0 }
+    }
+    
+    /// Applies a block of one transaction, returning its receipt
+    fn apply(&mut self, tx: Transaction) -> Result<Receipt> {
+        let nonce = self.state.get_nonce(&Address::new(tx.sender.as_str())?);
+        let tx = tx.with_nonce(nonce)?;
+        let block = Block::new(self.height + 1, BlockHash::default(), vec![tx], "GENX_VALIDATOR".to_string(), BASE_FEE)?;
+        let mut receipts = self.state.apply_block_with_executor(&block, Some(&mut StandIn))?;
+        self.height += 1;
+        Ok(receipts.remove(0))
+    }
+    
+    /// Deploys code from an account, returning the contract's address
+    fn deploy(&mut self, from: &str) -> String {
+        let receipt = self.apply(deploy(from)).unwrap();
+        receipt.contract_address.unwrap()
+    }
+    
+    /// Calls a contract from an account with call data, returning the receipt
+    fn call(&mut self, from: &str, contract: &str, data: &[u8]) -> Receipt {
+        let tx = Transaction::new_contract_call(from.to_string(), contract.to_string(), 0, data.to_vec(), GAS_LIMIT, BASE_FEE).unwrap();
+        self.apply(tx).unwrap()
+    }
+    
+    /// Gets an account's balance and what it has locked in deposits
+    fn funds(&self, account: &str) -> (u64, u64) {
+        let address = Address::new(account).unwrap();
+        (self.state.get_balance(&address).base_units(), self.state.get_locked_balance(&address).base_units())
+    }
+    
+    /// Gets the payer of the deposit for a slot of a contract's storage, if any
+    fn slot_payer(&self, contract: &str, key: u8) -> Option<String> {
+        let deposits = self.state.get_contract_deposits(contract)?;
+        deposits.slots.get(&vec![key]).map(|deposit| deposit.payer.clone())
+    }
+}
+
+/// Makes a deployment of `CODE_SIZE` bytes of code
+fn deploy(from: &str) -> Transaction {
+    Transaction::new_contract_deploy(from.to_string(), 0, vec![0x60; CODE_SIZE], GAS_LIMIT, BASE_FEE).unwrap()
+}
+
+/// Checks deploying and writing lock deposits from the senders, and clearing refunds the payer
+#[test]
+fn check_write_and_clear() {
+    let mut chain = Chain::new(&[("GENX_ALICE", 10 * GENX), ("GENX_BOB", 10 * GENX)]);
+    let rates = chain.state.deposit_rates();
+    assert!(rates.per_slot > 0 && rates.per_code_byte > 0);
+    let fee = GAS * BASE_FEE;
+    
+    let contract = chain.deploy("GENX_ALICE");
+    let code_deposit = rates.code(CODE_SIZE);
+    assert_eq!(chain.funds("GENX_ALICE"), (10 * GENX - fee - code_deposit, code_deposit));
+    let deposits = chain.state.get_contract_deposits(&contract).unwrap();
+    assert_eq!(deposits.code.as_ref().map(|deposit| (deposit.payer.as_str(), deposit.amount)), Some(("GENX_ALICE", code_deposit)));
+    
+    // Writing an empty slot locks a deposit from the caller
+    assert!(chain.call("GENX_BOB", &contract, &[WRITE, 7, 1, 2, 3]).success);
+    assert_eq!(chain.funds("GENX_BOB"), (10 * GENX - fee - rates.per_slot, rates.per_slot));
+    assert_eq!(chain.slot_payer(&contract, 7).as_deref(), Some("GENX_BOB"));
+    assert_eq!(chain.state.get_contract_deposits(&contract).unwrap().total(), code_deposit + rates.per_slot);
+    
+    // Overwriting it locks nothing more, from anyone
+    assert!(chain.call("GENX_ALICE", &contract, &[WRITE, 7, 4]).success);
+    assert_eq!(chain.funds("GENX_ALICE"), (10 * GENX - 2 * fee - code_deposit, code_deposit));
+    assert_eq!(chain.slot_payer(&contract, 7).as_deref(), Some("GENX_BOB"));
+    
+    // Clearing it refunds the payer, whoever clears it
+    assert!(chain.call("GENX_ALICE", &contract, &[CLEAR, 7]).success);
+    assert_eq!(chain.funds("GENX_BOB"), (10 * GENX - fee, 0));
+    assert_eq!(chain.funds("GENX_ALICE"), (10 * GENX - 3 * fee - code_deposit, code_deposit));
+    assert_eq!(chain.slot_payer(&contract, 7), None);
+    assert!(chain.state.get_storage(&contract, &[7]).is_none());
+}
+
+/// Checks a contract destroying itself refunds the deposits for its code and storage to their payers
+#[test]
+fn check_self_destruct() {
+    let mut chain = Chain::new(&[("GENX_ALICE", 10 * GENX), ("GENX_BOB", 10 * GENX)]);
+    let (rates, fee) = (chain.state.deposit_rates(), GAS * BASE_FEE);
+    let contract = chain.deploy("GENX_ALICE");
+    assert!(chain.call("GENX_BOB", &contract, &[WRITE, 1, 1]).success);
+    assert!(chain.call("GENX_BOB", &contract, &[WRITE, 2, 2]).success);
+    assert!(chain.call("GENX_ALICE", &contract, &[WRITE, 3, 3]).success);
+    assert_eq!(chain.funds("GENX_BOB").1, 2 * rates.per_slot);
+    assert_eq!(chain.funds("GENX_ALICE").1, rates.code(CODE_SIZE) + rates.per_slot);
+    
+    let receipt = chain.call("GENX_BOB", &contract, &[DESTROY]);
+    assert_eq!(receipt.destroyed_contracts, vec![contract.clone()]);
+    assert!(chain.state.get_contract_deposits(&contract).is_none());
+    assert_eq!(chain.funds("GENX_ALICE"), (10 * GENX - 2 * fee, 0));
+    assert_eq!(chain.funds("GENX_BOB"), (10 * GENX - 3 * fee, 0));
+}
+
+/// Checks a sender that can't lock a deposit fails its execution, or for a deployment is refused before it runs
+#[test]
+fn check_uncovered_deposit() {
+    let max_fee = GAS_LIMIT * BASE_FEE;
+    let mut chain = Chain::new(&[("GENX_ALICE", 10 * GENX)]);
+    let rates = chain.state.deposit_rates();
+    let code_deposit = rates.code(CODE_SIZE);
+    
+    // Enough for the fee, but not the slot: the call fails and pays its fee
+    let contract = chain.deploy("GENX_ALICE");
+    let funds = max_fee + rates.per_slot / 2;
+    chain.state.apply_transaction(&Transaction::new_coinbase("GENX_CAROL".to_string(), funds).unwrap()).unwrap();
+    let receipt = chain.call("GENX_CAROL", &contract, &[WRITE, 9, 9]);
+    assert!(!receipt.success);
+    assert!(receipt.revert_reason.as_deref().unwrap_or_default().contains("Storage deposit not covered"), "{:?}", receipt.revert_reason);
+    assert_eq!(chain.funds("GENX_CAROL"), (funds - GAS * BASE_FEE, 0));
+    assert!(chain.state.get_storage(&contract, &[9]).is_none());
+    assert_eq!(chain.slot_payer(&contract, 9), None);
+    
+    // A deployment one unit short of holding the code deposit refuses its block before it runs
+    let required = max_fee + code_deposit;
+    let mut chain = Chain::new(&[("GENX_DAVE", required - 1)]);
+    let error = chain.apply(deploy("GENX_DAVE")).unwrap_err();
+    assert!(
+        matches!(error.root(), BlockchainError::InsufficientBalance { address, required: needed, available } if address == "GENX_DAVE" && *needed == required && *available == required - 1),
+        "{:?}",
+        error
+    );
+    assert_eq!(chain.funds("GENX_DAVE"), (required - 1, 0));
+    
+    // One more unit, and it's deployed
+    let mut chain = Chain::new(&[("GENX_DAVE", required)]);
+    chain.deploy("GENX_DAVE");
+    assert_eq!(chain.funds("GENX_DAVE"), (max_fee - GAS * BASE_FEE, code_deposit));
+}
\ No newline at end of file
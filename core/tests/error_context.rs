@@ -0,0 +1,71 @@
+//! Checks errors applying a block say which block and transaction failed
+//!
+//! Run with `cargo test -p core --features testutil --test error_context`.
+//! Builds a chain up to block 41, then a block 42 whose transaction #7
+//! overdraws its sender, and checks the chain's error names both the block
+//! and the transaction, in its message and its context, keeping the code of
+//! the insufficient balance beneath.
+
+use core::chainbuilder::TestChain;
+use core::units::GENX;
+use core::{BlockchainError, ErrorContext, WithContext};
+
+/// Seed of the chain built
+const SEED: u64 = 42;
+
+/// Height of the block that fails
+const FAILING_HEIGHT: u64 = 42;
+
+/// Index of the transaction that fails in its block
+const FAILING_INDEX: usize = 7;
+
+/// Checks a failure on transaction #7 of block 42 surfaces both
+#[test]
+fn check_block_failure() {
+    let mut chain = TestChain::new(SEED);
+    chain.with_empty_blocks(FAILING_HEIGHT - 1);
+    
+    // Transfers that apply, then carol's that can't, at index 7 after the coinbases
+    let coinbases = chain.next_block(|b| b).transactions.len();
+    let block = chain.next_block(|b| {
+        for amount in 1..=(FAILING_INDEX - coinbases) as u64 {
+            b.transfer("alice", "bob", amount);
+        }
+        b.transfer("carol", "bob", 2_000 * GENX)
+    });
+    let tx = &block.transactions[FAILING_INDEX];
+    assert_eq!(tx.sender, chain.account("carol").address.to_string(), "the overdraft isn't transaction #{}", FAILING_INDEX);
+    let (tx_id, block_hash) = (tx.id, block.hash().unwrap());
+    
+    let error = chain.blockchain_mut().add_block(block).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::InsufficientBalance { .. }), "{}", error);
+    assert_eq!(error.error_code(), 1007);
+    assert_eq!(chain.height(), FAILING_HEIGHT - 1);
+    
+    let context = error.context().expect("the error has no context");
+    assert_eq!(context.height, Some(FAILING_HEIGHT));
+    assert_eq!(context.block_hash, Some(block_hash));
+    assert_eq!(context.tx_index, Some(FAILING_INDEX));
+    assert_eq!(context.tx_id, Some(tx_id));
+    
+    let message = error.to_string();
+    for part in [format!("block {} {}", FAILING_HEIGHT, block_hash), format!("transaction #{} {}", FAILING_INDEX, tx_id)] {
+        assert!(message.contains(&part), "{} doesn't name {}", message, part);
+    }
+}
+
+/// Checks outer contexts only fill in what inner ones left out, and wrap the error once
+#[test]
+fn check_merge() {
+    let result: core::Result<()> = Err(BlockchainError::ParentMismatch);
+    let error = result
+        .with_ctx(ErrorContext::transaction(3, [1; 32].into()))
+        .with_ctx(ErrorContext { tx_index: Some(4), ..ErrorContext::height(9) })
+        .unwrap_err();
+    assert!(matches!(error, BlockchainError::WithContext { ref source, .. } if matches!(**source, BlockchainError::ParentMismatch)));
+    
+    let context = error.context().unwrap();
+    assert_eq!((context.height, context.tx_index, context.block_hash), (Some(9), Some(3), None));
+    assert_eq!(error.error_code(), BlockchainError::ParentMismatch.error_code());
+    assert!(error.to_string().ends_with(&format!("(block 9, transaction #3 {})", context.tx_id.unwrap())), "{}", error);
+}
\ No newline at end of file
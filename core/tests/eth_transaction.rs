@@ -0,0 +1,128 @@
+//! Checks signed Ethereum transactions decode and import as Ethereum signs them
+//!
+//! Run with `cargo test -p core --test eth_transaction`. Decodes the signed
+//! transaction of EIP-155's example and checks its fields, the signing hash
+//! including the chain ID and the sender recovered from it, then imports it
+//! and checks the GENX transaction validates against the raw transaction it
+//! carries. Altered, non-canonical and unsupported encodings are refused.
+
+use core::eth_transaction::{EthTransaction, EthTransactionError, EthTransactionType};
+use core::rlp::{self, RlpError, RlpItem};
+use core::signature::SignatureScheme;
+use core::transaction::TransactionType;
+
+/// The EIP-155 example, signed for chain 1 with the key whose every byte is 0x46
+const EIP155_RAW: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+                          8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276\
+                          a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+/// Signing hash of the EIP-155 example, over its fields and chain 1
+const EIP155_HASH: &str = "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53";
+
+/// Address of the EIP-155 example's sender
+const EIP155_SENDER: &str = "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+
+/// Recipient of the EIP-155 example
+const EIP155_TO: [u8; 20] = [0x35; 20];
+
+/// Gets the EIP-155 example's raw transaction
+fn raw() -> Vec<u8> {
+    hex::decode(EIP155_RAW).unwrap()
+}
+
+/// Re-encodes the example's fields after changing them
+fn with_fields(change: impl FnOnce(&mut Vec<RlpItem>)) -> Vec<u8> {
+    let mut fields = rlp::decode(&raw()).unwrap().as_list().unwrap().to_vec();
+    change(&mut fields);
+    rlp::encode(&RlpItem::List(fields))
+}
+
+/// Checks the example's fields, signing hash and sender
+#[test]
+fn check_decode() {
+    let raw = raw();
+    assert_eq!(rlp::encode(&rlp::decode(&raw).unwrap()), raw);
+    
+    let tx = EthTransaction::decode(&raw).unwrap();
+    assert_eq!(tx.tx_type, EthTransactionType::Legacy);
+    assert_eq!(tx.chain_id, Some(1));
+    assert_eq!(tx.nonce, 9);
+    assert_eq!(tx.gas_price, 20_000_000_000);
+    assert_eq!(tx.max_priority_fee_per_gas, None);
+    assert_eq!(tx.gas_limit, 21_000);
+    assert_eq!(tx.to, Some(EIP155_TO));
+    assert_eq!(tx.value, 1_000_000_000_000_000_000);
+    assert!(tx.data.is_empty());
+    assert_eq!(tx.signature.recovery_id(), 0);
+    assert_eq!(tx.raw(), raw.as_slice());
+    
+    assert_eq!(hex::encode(tx.signing_hash()), EIP155_HASH);
+    assert_eq!(hex::encode(tx.recover_sender().unwrap().evm_address()), EIP155_SENDER);
+}
+
+/// Checks the example imports as a transfer from the sender's secp256k1 account
+#[test]
+fn check_import() {
+    let eth = EthTransaction::decode(&raw()).unwrap();
+    let recipient = format!("0x{}", hex::encode(EIP155_TO));
+    let tx = eth.to_transaction(recipient.clone()).unwrap();
+    
+    let sender = SignatureScheme::Secp256k1.address(&eth.recover_sender().unwrap().to_compressed()).unwrap();
+    assert_eq!(tx.sender, sender);
+    assert_eq!(tx.recipient, recipient);
+    assert_eq!(tx.id, eth.hash());
+    assert_eq!(tx.tx_type, TransactionType::Transfer);
+    assert_eq!((tx.nonce, tx.amount), (9, 100_000_000));
+    assert_eq!((tx.gas_limit, tx.gas_price, tx.fee), (21_000, 2, 42_000));
+    assert_eq!(tx.eth_raw.as_deref(), Some(raw().as_slice()));
+    tx.validate().unwrap();
+    
+    // The recipient has to be the account `to` names
+    assert!(matches!(
+        eth.to_transaction("GENX_ALICE".to_string()),
+        Err(EthTransactionError::RecipientMismatch { .. })
+    ));
+    
+    // Changing a field once imported no longer matches the raw transaction
+    let mut altered = tx.clone();
+    altered.amount += 1;
+    assert!(altered.validate().is_err(), "an altered import validated");
+}
+
+/// Checks altered, non-canonical and unsupported encodings are refused
+#[test]
+fn check_refused() {
+    // Another value is signed by someone else
+    let altered = with_fields(|fields| fields[4] = RlpItem::uint(2_000_000_000_000_000_000));
+    let tx = EthTransaction::decode(&altered).unwrap();
+    assert_ne!(hex::encode(tx.recover_sender().unwrap().evm_address()), EIP155_SENDER);
+    
+    // Integers with leading zeros and short form bytes in long form
+    let padded = with_fields(|fields| fields[0] = RlpItem::Bytes(vec![0x00, 0x09]));
+    assert!(matches!(EthTransaction::decode(&padded), Err(EthTransactionError::Rlp(RlpError::LeadingZeros))));
+    let mut long_form = raw();
+    long_form.splice(0..2, [0xf9, 0x00, 0x6c]);
+    assert!(matches!(EthTransaction::decode(&long_form), Err(EthTransactionError::Rlp(RlpError::NonCanonical(_)))));
+    
+    // Truncated and trailing input
+    let raw = raw();
+    assert!(matches!(EthTransaction::decode(&raw[..raw.len() - 1]), Err(EthTransactionError::Rlp(RlpError::UnexpectedEnd))));
+    let mut trailing = raw.clone();
+    trailing.push(0x00);
+    assert!(matches!(EthTransaction::decode(&trailing), Err(EthTransactionError::Rlp(RlpError::TrailingBytes(1)))));
+    
+    // A `v` neither 27, 28 nor from a chain ID, a missing field, and an unknown type
+    let bad_v = with_fields(|fields| fields[6] = RlpItem::uint(29));
+    assert!(matches!(EthTransaction::decode(&bad_v), Err(EthTransactionError::Malformed(_))));
+    let short = with_fields(|fields| {
+        fields.pop();
+    });
+    assert!(matches!(EthTransaction::decode(&short), Err(EthTransactionError::Malformed(_))));
+    assert_eq!(EthTransaction::decode(&[0x01]).unwrap_err(), EthTransactionError::UnsupportedType(0x01));
+    
+    // Contract creation has no recipient to import to
+    let creation = with_fields(|fields| fields[3] = RlpItem::Bytes(Vec::new()));
+    let tx = EthTransaction::decode(&creation).unwrap();
+    assert_eq!(tx.to, None);
+    assert_eq!(tx.to_transaction("GENX_ALICE".to_string()).unwrap_err(), EthTransactionError::ContractCreation);
+}
\ No newline at end of file
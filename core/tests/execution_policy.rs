@@ -0,0 +1,211 @@
+//! Checks the execution policy is enforced as blocks are applied
+//!
+//! Run with `cargo test -p core --features testutil --test execution_policy`.
+//! Builds a test chain whose genesis state restricts deployment, with a
+//! stand-in engine that stores deployed code and succeeds every call, then
+//! checks blocks deploying from an unlisted address are rejected, calls a
+//! contract's allowlist denies revert with the policy's error, the kill
+//! switch takes effect at its activation height, and updates are logged in
+//! their receipts and undone with their blocks.
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use core::block::BlockHeader;
+use core::chainbuilder::TestChain;
+use core::execution_policy::{ExecutionPolicy, PolicyUpdate, PolicyViolation, POLICY_ADDRESS};
+use core::executor::{ContractExecutor, ExecutionOutcome};
+use core::receipt::Receipt;
+use core::state::{ContractAccount, State, StateAccess};
+use core::transaction::Transaction;
+use core::{BlockchainError, Result};
+
+/// Seed of the chains built
+const SEED: u64 = 5;
+
+/// Gas each call succeeds with
+const CALL_GAS: u64 = 30_000;
+
+/// Engine storing deployed code and succeeding every call
+#[derive(Debug)]
+struct StandIn;
+
+impl ContractExecutor for StandIn {
+    fn deploy(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        let address = tx.contract_address();
+        let contract = ContractAccount {
+            code: vec![0x00],
+            metadata: Vec::new(),
+            creator: tx.sender.clone(),
+            deployed_at: header.height,
+        };
+        state.insert_contract(&address, contract);
+        Ok(ExecutionOutcome { success: true, gas_used: CALL_GAS, contract_address: Some(address), ..Default::default() })
+    }
+    
+    fn call(&mut self, _: &Transaction, _: &BlockHeader, _: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        Ok(ExecutionOutcome { success: true, gas_used: CALL_GAS, ..Default::default() })
+    }
+}
+
+/// Builds a chain whose genesis state lets alice administer the policy and only alice and bob deploy
+fn build() -> TestChain {
+    let mut chain = TestChain::new(SEED);
+    let (alice, bob) = (chain.address("alice"), chain.address("bob"));
+    let policy = ExecutionPolicy {
+        admins: BTreeSet::from([alice.clone()]),
+        deployers: Some(BTreeSet::from([alice, bob])),
+        ..ExecutionPolicy::default()
+    };
+    chain.blockchain().get_state().lock().unwrap().set_execution_policy(policy);
+    chain.blockchain_mut().set_contract_executor(Arc::new(Mutex::new(StandIn)));
+    chain
+}
+
+/// Deploys a contract from an account, returning its address
+fn deploy(chain: &mut TestChain, from: &str) -> String {
+    chain.with_block(|b| b.deploy(from, vec![0x60, 0x00]));
+    let contracts = chain.contracts();
+    contracts[contracts.len() - 1].clone()
+}
+
+/// Adds a block of a call, returning its receipt
+fn call(chain: &mut TestChain, from: &str, contract: &str) -> Receipt {
+    chain.with_block(|b| b.call(from, contract, vec![0x01]));
+    latest_receipt(chain)
+}
+
+/// Adds a block of a policy update, returning its receipt
+fn update(chain: &mut TestChain, from: &str, update: &PolicyUpdate) -> Receipt {
+    let tx = Transaction::new_set_execution_policy(chain.address(from), update, chain.config().transfer_fee).unwrap();
+    chain.with_block(|b| b.transaction(tx));
+    latest_receipt(chain)
+}
+
+/// Checks a block carrying a policy update its sender may not make is rejected
+fn assert_update_rejected(chain: &mut TestChain, from: &str, update: &PolicyUpdate) -> BlockchainError {
+    let tx = Transaction::new_set_execution_policy(chain.address(from), update, chain.config().transfer_fee).unwrap();
+    let height = chain.height();
+    let block = chain.next_block(|b| b.transaction(tx));
+    let error = chain.blockchain_mut().add_block(block).unwrap_err();
+    assert_eq!(chain.height(), height);
+    error
+}
+
+/// Gets the receipt of the last transaction of the latest block
+fn latest_receipt(chain: &TestChain) -> Receipt {
+    let receipts = chain.blockchain().get_block_receipts(chain.height()).unwrap();
+    receipts[receipts.len() - 1].clone()
+}
+
+/// Checks deploying from an address off the allowlist invalidates the block, and the admission check agrees
+#[test]
+fn check_deployment() {
+    let mut chain = build();
+    deploy(&mut chain, "alice");
+    deploy(&mut chain, "bob");
+    
+    let block = chain.next_block(|b| b.deploy("carol", vec![0x60, 0x00]));
+    let state = chain.blockchain().get_state();
+    let denied = state.lock().unwrap().check_execution_policy(&block.transactions[block.transactions.len() - 1], chain.height() + 1);
+    assert!(matches!(denied, Err(BlockchainError::PolicyDenied { violation: PolicyViolation::NotDeployer, .. })));
+    
+    let error = chain.blockchain_mut().add_block(block).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::PolicyDenied { violation: PolicyViolation::NotDeployer, .. }), "{}", error);
+    assert_eq!(error.error_code(), 1027);
+    assert_eq!(chain.height(), 2);
+    
+    // Opening deployment lets carol deploy
+    update(&mut chain, "alice", &PolicyUpdate::SetDeployers { deployers: None });
+    deploy(&mut chain, "carol");
+}
+
+/// Checks a caller a contract's allowlist denies reverts with the policy's error, paying for its gas
+#[test]
+fn check_call_allowlist() {
+    let mut chain = build();
+    let contract = deploy(&mut chain, "alice");
+    assert!(call(&mut chain, "carol", &contract).success);
+    
+    // Only the creator sets the allowlist, admin or not
+    let callers = Some(BTreeSet::from([chain.address("bob")]));
+    let set_callers = PolicyUpdate::SetCallers { contract: contract.clone(), callers };
+    let error = assert_update_rejected(&mut chain, "bob", &set_callers);
+    assert!(matches!(error.root(), BlockchainError::PolicyDenied { violation: PolicyViolation::NotCreator { .. }, .. }));
+    update(&mut chain, "alice", &set_callers);
+    
+    let allowed = call(&mut chain, "bob", &contract);
+    assert!(allowed.success);
+    assert_eq!(allowed.gas_used, CALL_GAS);
+    
+    let carol = chain.account("carol").address.clone();
+    let balance = chain.blockchain().get_balance(&carol).unwrap().base_units();
+    let denied = call(&mut chain, "carol", &contract);
+    let tx = &chain.blockchain().get_latest_block().unwrap().transactions.last().unwrap().clone();
+    assert!(!denied.success);
+    assert_eq!(denied.gas_used, tx.gas_limit);
+    let reason = denied.revert_reason.unwrap();
+    assert!(reason.starts_with("Execution policy denies") && reason.contains("not allowed to call"), "{}", reason);
+    let charged = balance - chain.blockchain().get_balance(&carol).unwrap().base_units();
+    assert_eq!(charged, tx.fee_for_gas(tx.gas_limit));
+    
+    // Lifting the allowlist lets carol call again
+    update(&mut chain, "alice", &PolicyUpdate::SetCallers { contract: contract.clone(), callers: None });
+    assert!(call(&mut chain, "carol", &contract).success);
+}
+
+/// Checks the kill switch disables a contract from its activation height, for every caller
+#[test]
+fn check_kill_switch() {
+    let mut chain = build();
+    let contract = deploy(&mut chain, "bob");
+    
+    let activation_height = chain.height() + 3;
+    let disable = PolicyUpdate::Disable { contract: contract.clone(), activation_height };
+    let error = assert_update_rejected(&mut chain, "bob", &disable);
+    assert!(matches!(error.root(), BlockchainError::PolicyDenied { violation: PolicyViolation::NotAdmin, .. }));
+    let now = PolicyUpdate::Disable { contract: contract.clone(), activation_height: chain.height() + 1 };
+    assert!(matches!(assert_update_rejected(&mut chain, "alice", &now).root(), BlockchainError::InvalidTransaction(_)));
+    update(&mut chain, "alice", &disable);
+    
+    assert!(call(&mut chain, "bob", &contract).success);
+    assert_eq!(chain.height(), activation_height - 1);
+    for caller in ["bob", "alice"] {
+        let receipt = call(&mut chain, caller, &contract);
+        assert!(!receipt.success);
+        let reason = receipt.revert_reason.unwrap();
+        assert!(reason.ends_with(&format!("{} is disabled since height {}", contract, activation_height)), "{}", reason);
+    }
+    
+    update(&mut chain, "alice", &PolicyUpdate::Enable { contract: contract.clone() });
+    assert!(call(&mut chain, "bob", &contract).success);
+}
+
+/// Checks updates are logged in their receipts, encoded with the state and undone with their blocks
+#[test]
+fn check_updates() {
+    let mut chain = build();
+    let contract = deploy(&mut chain, "alice");
+    let disable = PolicyUpdate::Disable { contract: contract.clone(), activation_height: 100 };
+    let receipt = update(&mut chain, "alice", &disable);
+    assert!(receipt.success);
+    assert_eq!(receipt.logs.len(), 1);
+    assert_eq!(receipt.logs[0].address, POLICY_ADDRESS);
+    assert_eq!(receipt.logs[0].topics, vec![disable.topic()]);
+    assert_eq!(PolicyUpdate::from_data(&receipt.logs[0].data).unwrap(), disable);
+    
+    let policy = chain.blockchain().get_state().lock().unwrap().get_execution_policy().clone();
+    assert_eq!(policy.disabled.get(&contract), Some(&100));
+    let encoded = chain.blockchain().get_state().lock().unwrap().encode_canonical();
+    assert_eq!(State::decode_canonical(&encoded).unwrap().get_execution_policy(), &policy);
+    
+    let height = chain.height();
+    chain.blockchain_mut().rollback_to(height - 1).unwrap();
+    let state = chain.blockchain().get_state();
+    assert!(state.lock().unwrap().get_execution_policy().disabled.is_empty());
+    
+    // The public chain's policy restricts nothing and adds nothing to the state's encoding
+    let open = State::new();
+    assert!(open.get_execution_policy().is_open());
+    assert!(State::decode_canonical(&open.encode_canonical()).unwrap().get_execution_policy().is_open());
+}
\ No newline at end of file
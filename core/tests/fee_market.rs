@@ -0,0 +1,78 @@
+//! Checks the base fee follows the EIP-1559 adjustment
+//!
+//! Run with `cargo test -p core --test fee_market`. Computes the base fee
+//! after parents at, above and below the gas target and after empty ones,
+//! with Ethereum's 30M gas limit and a 1 gwei base fee, then runs sequences
+//! of full and empty blocks to check the base fee keeps rising under full
+//! blocks, falls to `MIN_BASE_FEE` under empty ones and stays there.
+
+use core::fee_market::{gas_target, next_base_fee, BASE_FEE_CHANGE_DENOMINATOR, MIN_BASE_FEE};
+
+/// Gas limit of each block, Ethereum's
+const GAS_LIMIT: u64 = 30_000_000;
+
+/// Base fee of the parent in the single-block cases, 1 gwei
+const BASE_FEE: u64 = 1_000_000_000;
+
+/// Checks a block's base fee moves with how far its parent was from the target, by up to 1/8
+#[test]
+fn check_adjustment() {
+    let target = gas_target(GAS_LIMIT);
+    assert_eq!(target, 15_000_000);
+    
+    // At the target nothing changes
+    assert_eq!(next_base_fee(BASE_FEE, target, GAS_LIMIT), BASE_FEE);
+    
+    // Above it the base fee rises in proportion, by 1/8 for a full block
+    assert_eq!(next_base_fee(BASE_FEE, GAS_LIMIT, GAS_LIMIT), 1_125_000_000);
+    assert_eq!(next_base_fee(BASE_FEE, target + target / 2, GAS_LIMIT), 1_062_500_000);
+    assert_eq!(next_base_fee(BASE_FEE, target + 1, GAS_LIMIT), BASE_FEE + 8);
+    
+    // Below it the base fee falls in proportion, by 1/8 for an empty block
+    assert_eq!(next_base_fee(BASE_FEE, 0, GAS_LIMIT), 875_000_000);
+    assert_eq!(next_base_fee(BASE_FEE, target / 2, GAS_LIMIT), 937_500_000);
+    assert_eq!(next_base_fee(BASE_FEE, target - 1, GAS_LIMIT), BASE_FEE - 8);
+    
+    // Without a target there's nothing to adjust to
+    assert_eq!(next_base_fee(BASE_FEE, 0, 1), BASE_FEE);
+    
+    // A huge base fee neither overflows nor wraps
+    assert_eq!(next_base_fee(u64::MAX, GAS_LIMIT, GAS_LIMIT), u64::MAX);
+    assert_eq!(next_base_fee(u64::MAX, 0, GAS_LIMIT), u64::MAX - u64::MAX / BASE_FEE_CHANGE_DENOMINATOR);
+}
+
+/// Checks base fees too small to change by 1/8 move by one, stopping at the minimum
+#[test]
+fn check_small_base_fees() {
+    assert_eq!(next_base_fee(7, GAS_LIMIT, GAS_LIMIT), 8);
+    assert_eq!(next_base_fee(7, 0, GAS_LIMIT), 6);
+    assert_eq!(next_base_fee(MIN_BASE_FEE, GAS_LIMIT, GAS_LIMIT), MIN_BASE_FEE + 1);
+    
+    // The base fee is clamped at the minimum, which an empty block doesn't lower
+    assert_eq!(next_base_fee(MIN_BASE_FEE + 1, 0, GAS_LIMIT), MIN_BASE_FEE);
+    assert_eq!(next_base_fee(MIN_BASE_FEE, 0, GAS_LIMIT), MIN_BASE_FEE);
+    assert_eq!(next_base_fee(MIN_BASE_FEE, GAS_LIMIT / 4, GAS_LIMIT), MIN_BASE_FEE);
+    assert_eq!(next_base_fee(MIN_BASE_FEE, gas_target(GAS_LIMIT), GAS_LIMIT), MIN_BASE_FEE);
+}
+
+/// Checks sustained full blocks keep raising the base fee and empty ones bring it down to the minimum
+#[test]
+fn check_sequences() {
+    let mut base_fee = MIN_BASE_FEE;
+    for _ in 0..200 {
+        let next = next_base_fee(base_fee, GAS_LIMIT, GAS_LIMIT);
+        assert!(next > base_fee, "a full block didn't raise the base fee from {}", base_fee);
+        base_fee = next;
+    }
+    assert!(base_fee > BASE_FEE, "200 full blocks only raised the base fee to {}", base_fee);
+    
+    let mut blocks = 0;
+    while base_fee > MIN_BASE_FEE {
+        let next = next_base_fee(base_fee, 0, GAS_LIMIT);
+        assert!(next < base_fee, "an empty block didn't lower the base fee from {}", base_fee);
+        base_fee = next;
+        blocks += 1;
+    }
+    assert!(blocks < 1_000, "the base fee took {} empty blocks to reach the minimum", blocks);
+    assert_eq!(next_base_fee(base_fee, 0, GAS_LIMIT), MIN_BASE_FEE);
+}
\ No newline at end of file
@@ -0,0 +1,101 @@
+//! Checks each hash algorithm and domain against golden vectors, and that domains never collide
+//!
+//! Run with `cargo test -p core --test hashing`. Hashes `abc` and the empty
+//! string with each bare algorithm and compares them with their published
+//! test vectors, then hashes `abc` in every domain and compares it with
+//! SHA-256 of the domain's tag, a zero byte and the data, computed apart
+//! from the code under test. Then checks the same bytes hash differently
+//! in every domain, so a transaction ID can never equal a block hash, and
+//! that transactions and blocks hash in their own domains.
+
+use std::collections::HashSet;
+
+use core::block::Block;
+use core::hashing::{self, HashAlgorithm, HashDomain, Hasher};
+use core::transaction::{Transaction, TransactionType};
+
+/// Published vectors of `abc` and the empty string for each algorithm
+const ALGORITHM_VECTORS: [(HashAlgorithm, &str, &str); 3] = [
+    (
+        HashAlgorithm::Sha256,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+    ),
+    (
+        HashAlgorithm::Keccak256,
+        "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45",
+        "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+    ),
+    (
+        HashAlgorithm::Blake3,
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+        "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+    ),
+];
+
+/// Golden hashes of `abc` in each domain, SHA-256 of the tag, a zero byte and `abc`
+const DOMAIN_VECTORS: [(HashDomain, &str); 16] = [
+    (HashDomain::Transaction, "8456dd98a5353035515097ee69884ee38f51ea98413634f29aac92c59273cdf4"),
+    (HashDomain::BlockHeader, "487b5c2312e2b5261d19062b367c1401ec3fe187239d8c4e427ddb54d8ddc7e4"),
+    (HashDomain::TransactionRoot, "15f717a7c015623370be1e9c1bbb469d7907f5f96260ce9dd886ad6a1150dce6"),
+    (HashDomain::StorageRoot, "ac058b8c5b972d20ad65505f58f12b8167ada8b75793d44f32724bce14a658d2"),
+    (HashDomain::ContractAddress, "195a007bd79796539192a14b391cddf84698867774bf7078b168fdf34845444f"),
+    (HashDomain::EvmAddress, "d5e58d2b1ab6160770c0e6b483e1295d834da123a63b2c9e763675c874726b6c"),
+    (HashDomain::MemoCommitment, "59d7c063818adc28b6159c1f9cd1fb2de40e084cf8f5d11c5c348a13e8bfa134"),
+    (HashDomain::StateChunk, "0141a6867774414c7f21ace3bbc8324074f70d914e178b0bb140bd0f925c96b9"),
+    (HashDomain::StateRoot, "4158a79567e44280d577e248c7f05e5a3625b54ab0966bf0181c38cdcb6973f3"),
+    (HashDomain::BlockFilter, "189c8d195590531929a9c6cc89dd91b133b241445050df3ff79c39021862aac7"),
+    (HashDomain::FilterHeader, "4249a31cbac63fa4a59bad38c2ff91fd0d4b21b80a23e570f48be81c17648049"),
+    (HashDomain::FilterItem, "ee26c23c8f8a925ce3a36794bdf23f8c5a298fa13348d8167dbcb734b13f4ebc"),
+    (HashDomain::ValidatorSet, "897dacf5e6615c9e8b26ef55d22ab95f00c532eb4d56410f8ab1663c5f6d547a"),
+    (HashDomain::FinalityVote, "7975b120b89a972d1ed421cfb1c73a022053941dcd8bf275c4bb4d78961fdfb0"),
+    (HashDomain::AddressBloom, "aa79e1913b15eee458ebcbe6419e0eb1e994af4589495d307269c4d6cf672036"),
+    (HashDomain::AddressAnnouncement, "2b0b860646a2faf76206efb1b64d042df674e976ce27a83da9c09b69468d54fa"),
+];
+
+/// Checks each algorithm gives its published vectors, fed at once or in pieces
+#[test]
+fn check_algorithms() {
+    for (algorithm, abc, empty) in ALGORITHM_VECTORS {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(b"a").update(b"").update(b"bc");
+        assert_eq!(hex::encode(hasher.finalize()), abc, "{}", algorithm.name());
+        assert_eq!(hex::encode(Hasher::new(algorithm).finalize()), empty, "{}", algorithm.name());
+    }
+    assert_eq!(hex::encode(hashing::sha256(b"abc")), ALGORITHM_VECTORS[0].1);
+    assert_eq!(hex::encode(hashing::keccak256(b"abc")), ALGORITHM_VECTORS[1].1);
+    assert_eq!(hex::encode(hashing::blake3(b"abc")), ALGORITHM_VECTORS[2].1);
+}
+
+/// Checks every domain hashes to its golden vector, and none gives another's hash
+#[test]
+fn check_domains() {
+    let pinned: Vec<HashDomain> = DOMAIN_VECTORS.iter().map(|(domain, _)| *domain).collect();
+    assert_eq!(pinned, HashDomain::ALL, "every domain is pinned, in order");
+    
+    let mut hashes = HashSet::new();
+    for (domain, golden) in DOMAIN_VECTORS {
+        assert_eq!(domain.algorithm(), HashAlgorithm::Sha256, "{} is consensus", domain.tag());
+        assert!(!domain.tag().as_bytes().contains(&0));
+        let hash = hashing::hash_in(domain, b"abc");
+        assert_eq!(hex::encode(hash), golden, "{}", domain.tag());
+        assert!(hashes.insert(hash), "{} collides", domain.tag());
+        assert_ne!(hash, hashing::sha256(b"abc"));
+    }
+}
+
+/// Checks transaction IDs and block hashes are hashes in their own domains
+#[test]
+fn check_ids() {
+    let tx = Transaction::new_with_type(TransactionType::Transfer, "GENX_A".to_string(), "GENX_B".to_string(), 5, 1, None, 0, 0).unwrap();
+    assert_eq!(tx.id, hashing::hash_tx_canonical(&tx).unwrap());
+    let block = Block::genesis(vec![tx.clone()], 1).unwrap();
+    let block_hash = block.hash().unwrap();
+    assert_eq!(block_hash, hashing::hash_block_header(block.header()).unwrap());
+    
+    // The header's bytes hashed as a transaction don't give its hash
+    let header = serde_json::to_vec(block.header()).unwrap();
+    assert_eq!(block_hash.0, hashing::hash_in(HashDomain::BlockHeader, &header));
+    assert_ne!(block_hash.0, hashing::hash_in(HashDomain::Transaction, &header));
+    assert_ne!(tx.id.0, block_hash.0);
+}
\ No newline at end of file
@@ -0,0 +1,109 @@
+//! Checks hashes and byte strings are hex in JSON and raw bytes on the wire
+//!
+//! Run with `cargo test -p core --test hex_json`. Pins the JSON form of a
+//! known transaction, receipt and block header, where hashes and payloads
+//! are `0x`-prefixed hex strings, and the binary encoding of the
+//! transaction, which carries the same values as raw bytes, then checks
+//! both round-trip.
+
+use serde_json::json;
+
+use core::block::Block;
+use core::receipt::{Log, Receipt};
+use core::transaction::{Transaction, TransactionType};
+use core::wire::Wire;
+use core::{BlockHash, Bytes, TxHash};
+
+/// Builds the known transaction
+fn transaction() -> Transaction {
+    Transaction {
+        id: TxHash::from([0x11; 32]),
+        tx_type: TransactionType::Transfer,
+        timestamp: 1_700_000_000,
+        nonce: 3,
+        sender: "GENX_ALICE".to_string(),
+        recipient: "GENX_BOB".to_string(),
+        amount: 500_000_000,
+        fee: 1_000,
+        data: Some(Bytes::from(vec![0xde, 0xad, 0xbe, 0xef])),
+        gas_limit: 0,
+        gas_price: 0,
+        signature: Some(Bytes::from(vec![0x22; 64])),
+        eth_raw: None,
+        memo: None,
+    }
+}
+
+/// Checks the JSON form of the known transaction, and that it parses back
+#[test]
+fn check_transaction_json() {
+    let tx = transaction();
+    let json = serde_json::to_value(&tx).unwrap();
+    assert_eq!(json, json!({
+        "id": format!("0x{}", "11".repeat(32)),
+        "tx_type": "Transfer",
+        "timestamp": 1_700_000_000,
+        "nonce": 3,
+        "sender": "GENX_ALICE",
+        "recipient": "GENX_BOB",
+        "amount": 500_000_000,
+        "fee": 1_000,
+        "data": "0xdeadbeef",
+        "gas_limit": 0,
+        "gas_price": 0,
+        "signature": format!("0x{}", "22".repeat(64)),
+    }));
+    
+    let parsed: Transaction = serde_json::from_value(json).unwrap();
+    assert_eq!(serde_json::to_string(&parsed).unwrap(), serde_json::to_string(&tx).unwrap());
+    assert_eq!(parsed.id, tx.id);
+    
+    // Hex parses in either case, with or without its prefix, but only at the right length
+    assert_eq!(format!("0x{}", "AB".repeat(32)).parse::<TxHash>().unwrap(), TxHash::from([0xab; 32]));
+    assert_eq!("11".repeat(32).parse::<TxHash>().unwrap(), tx.id);
+    for text in [format!("0x{}", "11".repeat(31)), format!("0x{}", "11".repeat(33)), format!("0x{}", "zz".repeat(32))] {
+        assert!(text.parse::<TxHash>().is_err(), "{} parsed", text);
+    }
+}
+
+/// Checks the binary encoding of the known transaction holds its hashes and payloads as raw bytes, unchanged
+#[test]
+fn check_transaction_wire() {
+    let tx = transaction();
+    let encoded = Wire::to_bytes(&tx);
+    assert_eq!(hex::encode(&encoded), WIRE);
+    
+    let contains = |bytes: &[u8]| encoded.windows(bytes.len()).any(|window| window == bytes);
+    assert!(contains(&[0x11; 32]) && contains(&[0x22; 64]) && contains(&[0xde, 0xad, 0xbe, 0xef]));
+    assert!(!contains(b"0x"));
+    
+    let decoded = <Transaction as Wire>::from_bytes(&encoded).unwrap();
+    assert_eq!(Wire::to_bytes(&decoded), encoded);
+    assert_eq!((decoded.id, decoded.data, decoded.signature), (tx.id, tx.data, tx.signature));
+}
+
+/// Checks receipts and block headers give their hashes, topics and log data as hex
+#[test]
+fn check_receipt_and_header_json() {
+    let mut receipt = Receipt::new(TxHash::from([0x33; 32]), 7);
+    receipt.logs.push(Log { address: "GENX_CONTRACT_TOKEN".to_string(), topics: vec![[0x44; 32]], data: vec![0x01, 0x02] });
+    let json = serde_json::to_value(&receipt).unwrap();
+    assert_eq!(json["tx_id"], json!(format!("0x{}", "33".repeat(32))));
+    assert_eq!(json["logs"][0]["topics"], json!([format!("0x{}", "44".repeat(32))]));
+    assert_eq!(json["logs"][0]["data"], json!("0x0102"));
+    let parsed: Receipt = serde_json::from_value(json).unwrap();
+    assert_eq!((parsed.tx_id, &parsed.logs[0].topics, &parsed.logs[0].data), (receipt.tx_id, &receipt.logs[0].topics, &receipt.logs[0].data));
+    
+    let block = Block::new(1, BlockHash::from([0x55; 32]), vec![transaction()], "GENX_VALIDATOR".to_string(), 0).unwrap();
+    let header = serde_json::to_value(block.header()).unwrap();
+    assert_eq!(header["prev_hash"], json!(format!("0x{}", "55".repeat(32))));
+    let merkle_root = header["merkle_root"].as_str().unwrap();
+    assert!(merkle_root.starts_with("0x") && merkle_root.len() == 66, "{}", merkle_root);
+}
+
+/// Binary encoding of the known transaction, byte for byte what it was with bare arrays and vectors
+const WIRE: &str = concat!(
+    "f892a0111111111111111111111111111111111111111111111111111111111111111180846553f1008a47454e585f414c49434588",
+    "47454e585f424f42841dcd65008203e8c584deadbeef8080f842b840222222222222222222222222222222222222222222222222",
+    "22222222222222222222222222222222222222222222222222222222222222222222222222222222c0c003",
+);
\ No newline at end of file
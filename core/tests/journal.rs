@@ -0,0 +1,159 @@
+//! Checks reverted executions and rolled back blocks leave no storage behind
+//!
+//! Run with `cargo test -p core --test journal`. Applies blocks of random
+//! calls to a stand-in engine that writes storage slots in nested frames,
+//! some frames and some calls reverting, and checks the contract's storage
+//! is exactly what applying only the successful writes gives. Then rolls
+//! the blocks back newest first, checking each undoes the state to what it
+//! was before the block.
+
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use core::block::{Block, BlockHeader};
+use core::executor::{ContractExecutor, ExecutionOutcome};
+use core::state::{BlockUndo, ContractAccount, State, StateAccess};
+use core::state_sync;
+use core::transaction::Transaction;
+use core::units::GENX;
+use core::{Address, BlockHash, Hash, Result};
+
+/// Seed of the random calls
+const SEED: u64 = 47;
+
+/// Blocks applied
+const BLOCKS: u64 = 12;
+
+/// Most calls in a block, and writes in a call
+const MAX_CALLS: usize = 6;
+const MAX_WRITES: usize = 5;
+
+/// Gas limit and price of every call
+const GAS_LIMIT: u64 = 100_000;
+const GAS_PRICE: u64 = 10;
+
+/// Gas each execution uses
+const CALL_GAS: u64 = 21_000;
+
+/// Slots the calls write, so later writes overwrite and clear earlier ones
+const SLOTS: u8 = 8;
+
+/// Engine whose calls write slots as their data says, in a frame each
+///
+/// A call's data is a byte saying whether the call succeeds, then a
+/// `(slot, value, frame succeeds)` triple per write. A value of 0 clears
+/// the slot. Each write is made in a nested checkpoint, as a call to
+/// another contract would be, and reverted with it if its frame fails.
+#[derive(Debug)]
+struct StandIn;
+
+impl ContractExecutor for StandIn {
+    fn deploy(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        let address = tx.contract_address();
+        let contract = ContractAccount { code: vec![0x00], metadata: Vec::new(), creator: tx.sender.clone(), deployed_at: header.height };
+        state.insert_contract(&address, contract);
+        Ok(ExecutionOutcome { success: true, gas_used: CALL_GAS, contract_address: Some(address), ..Default::default() })
+    }
+    
+    fn call(&mut self, tx: &Transaction, _: &BlockHeader, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        let data = &tx.data.as_ref().expect("calls carry their writes").0;
+        for write in data[1..].chunks(3) {
+            state.checkpoint();
+            let value = (write[1] != 0).then(|| vec![write[1]]);
+            state.set_storage(&tx.recipient, vec![write[0]], value);
+            if write[2] == 1 {
+                state.commit();
+            } else {
+                state.revert();
+            }
+        }
+        Ok(ExecutionOutcome { success: data[0] == 1, gas_used: CALL_GAS, ..Default::default() })
+    }
+}
+
+/// Draws a call's data, applying the writes it keeps to `expected`
+fn call_data(rng: &mut StdRng, expected: &mut HashMap<u8, u8>) -> Vec<u8> {
+    let succeeds = rng.gen_bool(0.6);
+    let mut data = vec![u8::from(succeeds)];
+    let mut kept = Vec::new();
+    for _ in 0..rng.gen_range(1..=MAX_WRITES) {
+        let (slot, value, frame_succeeds) = (rng.gen_range(0..SLOTS), rng.gen_range(0..4u8), rng.gen_bool(0.7));
+        data.extend_from_slice(&[slot, value, u8::from(frame_succeeds)]);
+        if frame_succeeds {
+            kept.push((slot, value));
+        }
+    }
+    if succeeds {
+        for (slot, value) in kept {
+            match value {
+                0 => expected.remove(&slot),
+                value => expected.insert(slot, value),
+            };
+        }
+    }
+    data
+}
+
+/// Applies a block the way the chain does, returning its receipts' outcomes and what undoes it
+fn apply(state: &mut State, height: u64, transactions: Vec<Transaction>) -> (Vec<bool>, BlockUndo) {
+    let block = Block::new(height, BlockHash::default(), transactions, "GENX_VALIDATOR".to_string(), GAS_PRICE).unwrap();
+    state.checkpoint();
+    let receipts = state.apply_block_with_executor(&block, Some(&mut StandIn)).unwrap();
+    (receipts.iter().map(|receipt| receipt.success).collect(), state.commit_block())
+}
+
+/// Gets a contract's storage as slots and values
+fn storage(state: &State, contract: &str) -> HashMap<u8, u8> {
+    state
+        .get_contract_storage(contract)
+        .map(|storage| storage.iter().map(|(key, value)| (key[0], value[0])).collect())
+        .unwrap_or_default()
+}
+
+/// Checks storage holds only the successful writes of successful calls, and rolling blocks back restores each earlier state
+#[test]
+fn check_journal() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let alice = Address::new("GENX_ALICE").unwrap();
+    let mut state = State::new();
+    state.apply_transaction(&Transaction::new_coinbase(alice.to_string(), 1_000_000 * GENX).unwrap()).unwrap();
+    
+    let deploy = Transaction::new_contract_deploy(alice.to_string(), 0, vec![0x00], GAS_LIMIT, GAS_PRICE).unwrap();
+    let contract = deploy.contract_address();
+    let (outcomes, _) = apply(&mut state, 1, vec![deploy]);
+    assert_eq!(outcomes, [true]);
+    
+    let mut expected = HashMap::new();
+    let mut nonce = 1;
+    let mut history: Vec<(Hash, HashMap<u8, u8>, BlockUndo)> = Vec::new();
+    for height in 2..2 + BLOCKS {
+        let before = (state_sync::state_root(&state), storage(&state, &contract));
+        let mut calls = Vec::new();
+        let mut succeeds = Vec::new();
+        for _ in 0..rng.gen_range(1..=MAX_CALLS) {
+            let data = call_data(&mut rng, &mut expected);
+            succeeds.push(data[0] == 1);
+            let call = Transaction::new_contract_call(alice.to_string(), contract.clone(), 0, data, GAS_LIMIT, GAS_PRICE)
+                .and_then(|tx| tx.with_nonce(nonce))
+                .unwrap();
+            calls.push(call);
+            nonce += 1;
+        }
+        
+        let (outcomes, undo) = apply(&mut state, height, calls);
+        assert_eq!(outcomes, succeeds, "block {}", height);
+        assert_eq!(storage(&state, &contract), expected, "storage after block {}", height);
+        history.push((before.0, before.1, undo));
+    }
+    
+    // Rolling back restores every block's starting state, storage included
+    while let Some((root, storage_before, undo)) = history.pop() {
+        state.rollback_block(undo);
+        assert_eq!(storage(&state, &contract), storage_before, "storage after rolling back to {} blocks", history.len());
+        assert_eq!(state_sync::state_root(&state), root);
+    }
+    assert!(storage(&state, &contract).is_empty());
+    assert_eq!(state.get_nonce(&alice), 1);
+}
\ No newline at end of file
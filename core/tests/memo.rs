@@ -0,0 +1,99 @@
+//! Checks transfers carry memos in plain or committed to, within their size and paid for by the byte
+//!
+//! Run with `cargo test -p core --features testutil --test memo`. Has alice
+//! send bob transfers with memos on a test chain, and checks one of
+//! `MAX_MEMO_SIZE` bytes is included while one a byte longer is refused.
+//! A committed memo lands in the receipt as its hash, which the memo
+//! revealed off the chain verifies against and any other doesn't. A fee
+//! a base unit short of `MEMO_FEE_PER_BYTE` for each memo byte is refused,
+//! and the exact one is charged in full.
+
+use core::chainbuilder::TestChain;
+use core::memo::{MemoField, MAX_MEMO_SIZE, MEMO_FEE_PER_BYTE};
+use core::transaction::{Transaction, TransactionType};
+use core::units::GENX;
+use core::Address;
+
+/// Seed of the chains built
+const SEED: u64 = 181;
+
+/// Memo committed to, a customer number with a random suffix
+const MEMO: &str = "customer-20931:5f1c9e07a2";
+
+/// Makes a transfer from alice to bob carrying a memo and paying a fee
+fn transfer(chain: &TestChain, memo: MemoField, fee: u64) -> Transaction {
+    Transaction::new_with_type(TransactionType::Transfer, chain.address("alice"), chain.address("bob"), GENX, fee, None, 0, 0)
+        .unwrap()
+        .with_memo(memo)
+        .unwrap()
+}
+
+/// Makes a plain memo of a number of bytes
+fn plain(len: usize) -> MemoField {
+    MemoField::Plain("m".repeat(len))
+}
+
+/// Adds a block of a transfer with a memo, returning the transfer, or why the chain refused it
+fn send(chain: &mut TestChain, memo: MemoField, fee: u64) -> Result<Transaction, String> {
+    let tx = transfer(chain, memo, fee);
+    let block = chain.next_block(|b| b.transaction(tx));
+    chain.blockchain_mut().add_block(block.clone()).map_err(|e| e.to_string())?;
+    Ok(block.transactions.last().unwrap().clone())
+}
+
+/// Gets alice's balance, in base units
+fn alice_balance(chain: &TestChain) -> u64 {
+    chain.blockchain().get_balance(&Address::new(chain.address("alice")).unwrap()).unwrap().base_units()
+}
+
+/// Checks a memo of `MAX_MEMO_SIZE` bytes is included and a longer one refused
+#[test]
+fn check_size() {
+    let mut chain = TestChain::new(SEED);
+    let fee = MEMO_FEE_PER_BYTE * (MAX_MEMO_SIZE as u64 + 1);
+    let error = send(&mut chain, plain(MAX_MEMO_SIZE + 1), fee).unwrap_err();
+    assert!(error.contains("exceeds the 256 byte limit"), "{}", error);
+    assert_eq!(chain.height(), 0);
+    
+    let tx = send(&mut chain, plain(MAX_MEMO_SIZE), fee).unwrap();
+    assert_eq!(tx.memo, Some(plain(MAX_MEMO_SIZE)));
+    assert_eq!(chain.blockchain().get_receipt(&tx.id).unwrap().memo, Some(plain(MAX_MEMO_SIZE)));
+}
+
+/// Checks a committed memo verifies against the memo revealed and no other
+#[test]
+fn check_commitment() {
+    let mut chain = TestChain::new(SEED);
+    let committed = MemoField::commit(MEMO);
+    assert_eq!(committed.len(), 32);
+    let tx = send(&mut chain, committed.clone(), MEMO_FEE_PER_BYTE * 32).unwrap();
+    let memo = chain.blockchain().get_receipt(&tx.id).unwrap().memo.clone().unwrap();
+    assert_eq!(memo, committed);
+    assert!(matches!(memo, MemoField::Committed(_)), "only the hash is on the chain");
+    assert!(memo.verify(MEMO));
+    for wrong in ["customer-20931", "customer-20931:5f1c9e07a3", "", &MEMO.to_uppercase()] {
+        assert!(!memo.verify(wrong), "{}", wrong);
+    }
+    
+    // The memo is part of the ID the signature covers
+    let mut tampered = tx.clone();
+    tampered.memo = Some(MemoField::commit("customer-66666:5f1c9e07a2"));
+    assert_ne!(tampered.calculate_hash().unwrap(), tx.id);
+}
+
+/// Checks a memo's bytes count toward the fee, a base unit short being refused
+#[test]
+fn check_fee() {
+    let mut chain = TestChain::new(SEED);
+    let memo = plain(100);
+    let memo_fee = 100 * MEMO_FEE_PER_BYTE;
+    assert!(memo_fee > chain.config().transfer_fee);
+    assert_eq!(transfer(&chain, memo.clone(), 0).memo_fee(), memo_fee);
+    
+    let error = send(&mut chain, memo.clone(), memo_fee - 1).unwrap_err();
+    assert!(error.contains("doesn't pay for a memo of 100 bytes"), "{}", error);
+    
+    let before = alice_balance(&chain);
+    send(&mut chain, memo, memo_fee).unwrap();
+    assert_eq!(alice_balance(&chain), before - GENX - memo_fee);
+}
\ No newline at end of file
@@ -0,0 +1,121 @@
+//! Checks transactions are only applied with their senders' next nonces
+//!
+//! Run with `cargo test -p core --features testutil --test nonces`. Applies
+//! transfers to a state out of order, with a gap and again once applied,
+//! and checks each is refused without changing anything, then checks a
+//! chain refuses a block replaying an applied transaction and forgets the
+//! nonces its rolled back blocks used.
+
+use core::block::Block;
+use core::chainbuilder::TestChain;
+use core::state::State;
+use core::transaction::Transaction;
+use core::units::{Amount, GENX};
+use core::{Address, BlockchainError};
+
+/// Seed of the chain built
+const SEED: u64 = 29;
+
+/// Checks a state applies each sender's transactions in nonce order, once
+#[test]
+fn check_state() {
+    let (alice, bob) = (Address::new("GENX_ALICE").unwrap(), Address::new("GENX_BOB").unwrap());
+    let mut state = State::new();
+    state.apply_transaction(&Transaction::new_coinbase(alice.to_string(), 100 * GENX).unwrap()).unwrap();
+    
+    let transfer = |nonce: u64| {
+        Transaction::new(alice.clone(), bob.clone(), Amount::from_base_units(GENX), Amount::from_base_units(1), None)
+            .and_then(|tx| tx.with_nonce(nonce))
+            .unwrap()
+    };
+    let refused = |state: &mut State, tx: &Transaction, expected: u64| {
+        let before = (state.get_nonce(&alice), state.get_balance(&alice), state.get_balance(&bob));
+        match state.apply_transaction(tx) {
+            Err(BlockchainError::InvalidNonce { address, expected: got_expected, got }) => {
+                assert_eq!((address.as_str(), got_expected, got), (alice.as_str(), expected, tx.nonce));
+            }
+            other => panic!("nonce {} wasn't refused as invalid: {:?}", tx.nonce, other),
+        }
+        assert_eq!((state.get_nonce(&alice), state.get_balance(&alice), state.get_balance(&bob)), before);
+    };
+    
+    let first = transfer(0);
+    assert_eq!(state.get_nonce(&alice), 0);
+    state.apply_transaction(&first).unwrap();
+    assert_eq!(state.get_nonce(&alice), 1);
+    assert_eq!(state.get_nonce(&bob), 0, "receiving used a nonce");
+    
+    // Replaying the applied transaction
+    refused(&mut state, &first, 1);
+    
+    // A gap, then the transactions in order
+    refused(&mut state, &transfer(2), 1);
+    state.apply_transaction(&transfer(1)).unwrap();
+    state.apply_transaction(&transfer(2)).unwrap();
+    
+    // Out of order: the later one is refused until the earlier one is applied
+    let (fourth, fifth) = (transfer(3), transfer(4));
+    refused(&mut state, &fifth, 3);
+    state.apply_transaction(&fourth).unwrap();
+    state.apply_transaction(&fifth).unwrap();
+    refused(&mut state, &fourth, 5);
+    assert_eq!(state.get_balance(&bob), Amount::from_base_units(5 * GENX));
+    
+    // Nonces are part of the state's canonical encoding
+    let decoded = State::decode_canonical(&state.encode_canonical()).unwrap();
+    assert_eq!(decoded.get_nonce(&alice), 5);
+    assert_eq!(decoded.encode_canonical(), state.encode_canonical());
+}
+
+/// Checks the nonce is covered by the ID, so it can't be changed once signed
+#[test]
+fn check_id() {
+    let chain = TestChain::new(SEED);
+    let alice = chain.account("alice");
+    let mut tx = Transaction::new(
+        alice.address.clone(),
+        chain.account("bob").address.clone(),
+        Amount::from_base_units(GENX),
+        Amount::from_base_units(1),
+        None,
+    )
+    .unwrap();
+    let unnonced = tx.id;
+    tx = tx.with_nonce(1).unwrap();
+    assert_ne!(tx.id, unnonced, "the nonce isn't part of the ID");
+    alice.sign(&mut tx).unwrap();
+    tx.validate().unwrap();
+    
+    tx.nonce = 2;
+    assert!(matches!(tx.validate(), Err(BlockchainError::InvalidTransactionId { .. })), "a changed nonce passes validation");
+}
+
+/// Checks a chain refuses a replayed transaction and rolls back the nonces of removed blocks
+#[test]
+fn check_chain() {
+    let mut chain = TestChain::new(SEED);
+    chain.with_block(|b| b.transfer("alice", "bob", GENX).transfer("alice", "carol", GENX));
+    let replayed = chain.blocks().last().unwrap().transactions.iter().find(|tx| tx.recipient == chain.address("bob")).unwrap().clone();
+    let alice = chain.account("alice").address.clone();
+    assert_eq!(chain.blockchain().get_nonce(&alice).unwrap(), 2);
+    
+    // The next block as the builder makes it, with the transfer to bob again
+    let empty = chain.next_block(|b| b);
+    let mut transactions = empty.transactions.clone();
+    transactions.push(replayed);
+    let header = empty.header();
+    let mut block = Block::new(header.height, header.prev_hash, transactions, header.validator.clone(), header.base_fee).unwrap();
+    block.header_mut().timestamp = header.timestamp;
+    block.header_mut().validator_set_hash = header.validator_set_hash;
+    chain.account("validator").sign_header(block.header_mut()).unwrap();
+    
+    let error = chain.blockchain_mut().add_block(block).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::InvalidNonce { expected: 2, got: 0, .. }), "{}", error);
+    assert_eq!(error.error_code(), 1028);
+    assert_eq!(chain.height(), 1);
+    
+    chain.with_block(|b| b.transfer("alice", "bob", GENX));
+    assert_eq!(chain.blockchain().get_nonce(&alice).unwrap(), 3);
+    chain.blockchain_mut().rollback_to(1).unwrap();
+    assert_eq!(chain.blockchain().get_nonce(&alice).unwrap(), 2);
+}
\ No newline at end of file
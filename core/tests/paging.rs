@@ -0,0 +1,126 @@
+//! Checks walking a listing page by page returns each item once, whatever is inserted meanwhile
+//!
+//! Run with `cargo test -p core --test paging`. Registers 1000 validators,
+//! many of them staking the same, and walks their listing by address and
+//! by stake in pages of `LIMIT`, registering more validators halfway
+//! through. Checks every validator registered before the walk is returned
+//! exactly once and in order, the ones registered after the cursor are
+//! returned too and those before it aren't. Then checks a cursor of one
+//! listing, or that isn't a cursor at all, is refused by another.
+
+use core::paging::{Cursor, PageRequest};
+use core::state::State;
+use core::units::{Amount, GENX};
+use core::validator::{ValidatorRegistration, ValidatorSort};
+use core::{Address, BlockchainError};
+
+/// Validators registered before the walk
+const VALIDATORS: usize = 1000;
+
+/// Items a page holds, not dividing `VALIDATORS`
+const LIMIT: usize = 37;
+
+/// Page after which more validators are registered
+const INSERTED_AFTER_PAGE: usize = 13;
+
+/// Distinct stakes among the validators, so most tie with others
+const STAKES: u64 = 10;
+
+/// Registers a validator staking some GENX
+fn register(state: &mut State, name: &str, stake: u64) {
+    let operator = Address::new(name).unwrap();
+    let registration = ValidatorRegistration {
+        moniker: name.to_string(),
+        website: String::new(),
+        commission_rate: 0,
+        consensus_key: format!("{}_KEY", name),
+        payout_address: None,
+    };
+    state.register_validator(&operator, registration, 0).unwrap();
+    state.update_validator_stake(operator, Amount::from_base_units(stake * GENX));
+}
+
+/// Creates a state of `VALIDATORS` validators, returning their addresses in order
+fn state() -> (State, Vec<String>) {
+    let mut state = State::new();
+    let names: Vec<String> = (0..VALIDATORS).map(|i| format!("GENX_V{:04}", i)).collect();
+    for (i, name) in names.iter().enumerate() {
+        register(&mut state, name, 1 + i as u64 % STAKES);
+    }
+    (state, names)
+}
+
+/// Walks a listing of the validators, calling `insert` after `INSERTED_AFTER_PAGE` pages, returning the addresses and stakes returned
+fn walk(state: &mut State, sort: ValidatorSort, mut insert: impl FnMut(&mut State)) -> Vec<(String, u64)> {
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    for page in 1.. {
+        let request = PageRequest::new(LIMIT).after(cursor);
+        let listed = state.list_validators(sort, &request).unwrap();
+        assert!(listed.items.len() <= LIMIT);
+        seen.extend(listed.items.iter().map(|(validator, stake)| (validator.operator.clone(), *stake)));
+        cursor = listed.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+        if page == INSERTED_AFTER_PAGE {
+            insert(state);
+        }
+    }
+    seen
+}
+
+/// Checks walking the validators by address returns each once, and those inserted after the cursor
+#[test]
+fn check_by_address() {
+    let (mut state, names) = state();
+    let mut inserted = None;
+    let seen = walk(&mut state, ValidatorSort::Address, |state| {
+        // One sorting before the cursor, one at the end and one just after the cursor
+        let cursor = format!("GENX_V{:04}", INSERTED_AFTER_PAGE * LIMIT - 1);
+        let after = format!("{}a", cursor);
+        for name in ["GENX_V0000a", "GENX_V9999", after.as_str()] {
+            register(state, name, 1);
+        }
+        inserted = Some(after);
+    });
+    
+    let seen: Vec<String> = seen.into_iter().map(|(address, _)| address).collect();
+    let mut expected = names;
+    expected.push(inserted.unwrap());
+    expected.push("GENX_V9999".to_string());
+    expected.sort();
+    assert_eq!(seen, expected);
+}
+
+/// Checks walking the validators by stake returns each once, highest stake first and ties by address
+#[test]
+fn check_by_stake() {
+    let (mut state, names) = state();
+    let seen = walk(&mut state, ValidatorSort::Stake, |state| {
+        // Staking above every validator already listed, then below every one
+        register(state, "GENX_RICH", STAKES + 1);
+        register(state, "GENX_POOR", 0);
+    });
+    
+    let mut expected: Vec<(String, u64)> = names.iter().enumerate().map(|(i, name)| (name.clone(), (1 + i as u64 % STAKES) * GENX)).collect();
+    expected.push(("GENX_POOR".to_string(), 0));
+    expected.sort_by(|(a, a_stake), (b, b_stake)| b_stake.cmp(a_stake).then(b.cmp(a)));
+    assert_eq!(seen.len(), VALIDATORS + 1);
+    assert_eq!(seen, expected);
+}
+
+/// Checks a cursor of another listing, or not a cursor at all, is refused
+#[test]
+fn check_foreign_cursor() {
+    let (state, _) = state();
+    let by_address = state.list_validators(ValidatorSort::Address, &PageRequest::new(LIMIT)).unwrap();
+    assert_eq!(by_address.total_estimate, Some(VALIDATORS as u64));
+    let cursor = by_address.next_cursor.unwrap();
+    assert!(state.list_validators(ValidatorSort::Address, &PageRequest::new(LIMIT).after(Some(cursor.clone()))).is_ok());
+    
+    for cursor in [cursor, "not a cursor".parse::<Cursor>().unwrap()] {
+        let error = state.list_validators(ValidatorSort::Stake, &PageRequest::new(LIMIT).after(Some(cursor))).unwrap_err();
+        assert!(matches!(error, BlockchainError::InvalidCursor(_)), "{:?}", error);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,198 @@
+//! Checks a data directory's saved blocks verify offline, and a corrupt one is pinpointed
+//!
+//! Run with `cargo test -p core --features testutil --test replay`. Builds
+//! a test chain saving its blocks to a temporary data directory and
+//! checks replaying them verifies every block and ends on the state root
+//! the tip records, over the whole chain or a range of it. Then corrupts
+//! one stored block at a time, in each of the ways a damaged or tampered
+//! store can be, and checks the report names its height and the kind of
+//! failure.
+
+use std::fs;
+use std::path::PathBuf;
+
+use core::block::Block;
+use core::block_store::{BlockStore, BLOCKS_DIR};
+use core::chain::Blockchain;
+use core::chainbuilder::{TestChain, TestChainConfig};
+use core::replay::{FailureKind, VerificationReport, VerifyOptions};
+
+/// Seed of the chain built
+const SEED: u64 = 113;
+
+/// Blocks of transfers the chain is built with
+const BLOCKS: u64 = 6;
+
+/// Height of the block corrupted
+const CORRUPTED: u64 = 3;
+
+/// A chain whose blocks are saved to a data directory of its own
+struct Saved {
+    chain: TestChain,
+    data_dir: PathBuf,
+}
+
+impl Drop for Saved {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+impl Saved {
+    /// Builds the chain, saving its blocks as they're added
+    ///
+    /// The validator stakes nothing, so the state is what the blocks make.
+    fn new(name: &str) -> Self {
+        let data_dir = std::env::temp_dir().join(format!("genx-replay-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&data_dir);
+        let config = TestChainConfig { validators: vec![("validator".to_string(), 0)], ..TestChainConfig::default() };
+        let mut chain = TestChain::with_config(SEED, config);
+        chain.blockchain_mut().set_block_store(data_dir.join(BLOCKS_DIR)).unwrap();
+        for height in 1..=BLOCKS {
+            chain.with_block(|b| b.transfer("alice", "bob", 1_000 * height).transfer("bob", "carol", 300));
+        }
+        Self { chain, data_dir }
+    }
+    
+    /// Opens the chain's store
+    fn store(&self) -> BlockStore {
+        BlockStore::open_existing(self.data_dir.join(BLOCKS_DIR)).unwrap()
+    }
+    
+    /// Verifies the data directory
+    fn verify(&self, options: &VerifyOptions) -> VerificationReport {
+        Blockchain::verify_data_dir(&self.data_dir, options).unwrap()
+    }
+    
+    /// Verifies the data directory, returning the height and kind of the first failure
+    fn failure(&self) -> (u64, FailureKind) {
+        let report = self.verify(&VerifyOptions::default());
+        let divergence = report.divergence.as_ref().unwrap_or_else(|| panic!("the store verified: {:?}", report));
+        (divergence.height, divergence.kind)
+    }
+    
+    /// Stores a changed copy of the block at `CORRUPTED` in its place
+    fn replace(&self, change: impl FnOnce(&mut Block)) {
+        let mut block = self.chain.blocks()[CORRUPTED as usize].clone();
+        change(&mut block);
+        self.store().put_block(&block).unwrap();
+    }
+}
+
+/// Checks an untouched store verifies whole, ending on the tip's state root
+#[test]
+fn check_valid() {
+    let saved = Saved::new("valid");
+    let report = saved.verify(&VerifyOptions::default());
+    assert!(report.is_valid(), "{:?}", report.divergence);
+    assert_eq!((report.from, report.to, report.tip.height), (0, BLOCKS, BLOCKS));
+    assert_eq!((report.blocks_replayed, report.blocks_checked), (BLOCKS + 1, BLOCKS + 1));
+    assert_eq!(report.state_root, report.tip.state_root);
+    
+    // Every transaction is validated; the transfers' signatures are verified
+    let transactions: u64 = saved.chain.blocks().iter().map(|block| block.transactions.len() as u64).sum();
+    assert_eq!(report.transactions_verified, transactions);
+    assert!(report.signatures_verified >= 2 * BLOCKS, "{:?}", report);
+    assert_eq!(report.tip.block_hash, saved.chain.blocks()[BLOCKS as usize].hash().unwrap());
+    
+    let genesis_hash = Some(saved.chain.blocks()[0].hash().unwrap());
+    assert!(saved.verify(&VerifyOptions { genesis_hash, ..VerifyOptions::default() }).is_valid());
+}
+
+/// Checks a range is checked in full while the blocks before it are only replayed
+#[test]
+fn check_range() {
+    let saved = Saved::new("range");
+    let report = saved.verify(&VerifyOptions { from: 2, to: Some(4), ..VerifyOptions::default() });
+    assert!(report.is_valid(), "{:?}", report.divergence);
+    assert_eq!((report.from, report.to), (2, 4));
+    assert_eq!((report.blocks_replayed, report.blocks_checked), (5, 3));
+    let transactions: u64 = saved.chain.blocks()[2..=4].iter().map(|block| block.transactions.len() as u64).sum();
+    assert_eq!(report.transactions_verified, transactions);
+    
+    // A range past the tip stops at it, and an empty one is refused
+    let report = saved.verify(&VerifyOptions { from: 5, to: Some(BLOCKS + 10), ..VerifyOptions::default() });
+    assert_eq!(report.to, BLOCKS);
+    assert!(report.is_valid());
+    assert!(Blockchain::verify_data_dir(&saved.data_dir, &VerifyOptions { from: BLOCKS + 1, ..VerifyOptions::default() }).is_err());
+    
+    // Corruption after the range goes unnoticed; before it, it's still found
+    saved.replace(|block| block.transactions[2].amount += 1);
+    assert!(saved.verify(&VerifyOptions { from: 0, to: Some(CORRUPTED - 1), ..VerifyOptions::default() }).is_valid());
+    let report = saved.verify(&VerifyOptions { from: CORRUPTED + 1, ..VerifyOptions::default() });
+    assert_eq!(report.divergence.map(|divergence| (divergence.height, divergence.kind)), Some((CORRUPTED, FailureKind::MerkleRoot)));
+}
+
+/// Checks each kind of corruption of a stored block is reported at its height
+#[test]
+fn check_corrupt_block() {
+    // A transaction changed under the block's merkle root
+    let saved = Saved::new("merkle");
+    saved.replace(|block| block.transactions[2].amount += 1);
+    assert_eq!(saved.failure(), (CORRUPTED, FailureKind::MerkleRoot));
+    
+    // A transaction changed, with the block rebuilt around it, but not signed again by its sender
+    let saved = Saved::new("signature");
+    saved.replace(|block| {
+        let mut transactions = block.transactions.clone();
+        transactions[2].amount += 1;
+        transactions[2].id = transactions[2].calculate_hash().unwrap();
+        *block = rebuild(&saved.chain, block, transactions);
+    });
+    assert_eq!(saved.failure(), (CORRUPTED, FailureKind::Signature));
+    
+    // The validator keeping the whole reward
+    let saved = Saved::new("reward");
+    saved.replace(|block| {
+        let mut transactions = block.transactions.clone();
+        transactions.remove(1);
+        transactions[0].amount = core::rewards::block_reward(CORRUPTED);
+        transactions[0].id = transactions[0].calculate_hash().unwrap();
+        *block = rebuild(&saved.chain, block, transactions);
+    });
+    assert_eq!(saved.failure(), (CORRUPTED, FailureKind::Reward));
+    
+    // A block from elsewhere in the chain
+    let saved = Saved::new("linkage");
+    let other = saved.chain.blocks()[CORRUPTED as usize + 1].clone();
+    fs::copy(saved.store().block_path(CORRUPTED + 1), saved.store().block_path(CORRUPTED)).unwrap();
+    assert_eq!(saved.store().get_block(CORRUPTED).unwrap().unwrap().hash().unwrap(), other.hash().unwrap());
+    assert_eq!(saved.failure(), (CORRUPTED, FailureKind::Linkage));
+    
+    // Bytes that aren't a block, and no block at all
+    let saved = Saved::new("bytes");
+    fs::write(saved.store().block_path(CORRUPTED), b"not a block").unwrap();
+    assert_eq!(saved.failure(), (CORRUPTED, FailureKind::CorruptBlock));
+    let saved = Saved::new("missing");
+    saved.store().remove_block(CORRUPTED).unwrap();
+    assert_eq!(saved.failure(), (CORRUPTED, FailureKind::MissingBlock));
+}
+
+/// Checks a tip naming another block or state root than the replay's is reported at the tip
+#[test]
+fn check_corrupt_tip() {
+    let saved = Saved::new("tip-root");
+    let store = saved.store();
+    let tip = store.tip().unwrap().unwrap();
+    store.set_tip(&core::block_store::StoredTip { state_root: [7; 32], ..tip.clone() }).unwrap();
+    assert_eq!(saved.failure(), (BLOCKS, FailureKind::StateRoot));
+    
+    let block_hash = saved.chain.blocks()[1].hash().unwrap();
+    store.set_tip(&core::block_store::StoredTip { block_hash, ..tip.clone() }).unwrap();
+    assert_eq!(saved.failure(), (BLOCKS, FailureKind::TipHash));
+    
+    store.set_tip(&tip).unwrap();
+    assert!(saved.verify(&VerifyOptions::default()).is_valid());
+    let genesis_hash = Some(block_hash);
+    let report = saved.verify(&VerifyOptions { genesis_hash, ..VerifyOptions::default() });
+    assert_eq!(report.divergence.map(|divergence| (divergence.height, divergence.kind)), Some((0, FailureKind::Genesis)));
+}
+
+/// Rebuilds a block with other transactions, signed by its validator
+fn rebuild(chain: &TestChain, built: &Block, transactions: Vec<core::transaction::Transaction>) -> Block {
+    let header = built.header().clone();
+    let mut block = Block::new(header.height, header.prev_hash, transactions, header.validator, header.base_fee).unwrap();
+    block.header_mut().timestamp = header.timestamp;
+    chain.account("validator").sign_header(block.header_mut()).unwrap();
+    block
+}
\ No newline at end of file
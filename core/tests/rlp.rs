@@ -0,0 +1,161 @@
+//! Checks RLP against Ethereum's examples, round trips and malformed input
+//!
+//! Run with `cargo test -p core --test rlp`. Encodes and decodes the
+//! examples of Ethereum's RLP specification, round-trips random nested
+//! items, and checks truncated, trailing, non-canonical and too deeply
+//! nested input is refused with the error describing it.
+
+use core::rlp::{self, RlpError, RlpItem, MAX_DEPTH};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Seed of the random items
+const SEED: u64 = 23;
+
+/// Sentence of the specification's long string example, 56 bytes
+const LOREM: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+
+/// Builds a byte string item
+fn bytes(data: &[u8]) -> RlpItem {
+    RlpItem::from(data)
+}
+
+/// Builds a list item
+fn list(items: Vec<RlpItem>) -> RlpItem {
+    RlpItem::from(items)
+}
+
+/// Checks an item encodes to the given hex and decodes back from it
+fn check_encoding(item: &RlpItem, expected: &str) {
+    let encoded = rlp::encode(item);
+    assert_eq!(hex::encode(&encoded), expected, "{:?}", item);
+    assert_eq!(&rlp::decode(&encoded).unwrap(), item);
+}
+
+/// Checks the examples of Ethereum's RLP specification
+#[test]
+fn check_examples() {
+    check_encoding(&bytes(b"dog"), "83646f67");
+    check_encoding(&list(vec![bytes(b"cat"), bytes(b"dog")]), "c88363617483646f67");
+    check_encoding(&bytes(b""), "80");
+    check_encoding(&list(Vec::new()), "c0");
+    check_encoding(&RlpItem::uint(0), "80");
+    check_encoding(&bytes(&[0x00]), "00");
+    check_encoding(&RlpItem::uint(15), "0f");
+    check_encoding(&RlpItem::uint(1024), "820400");
+    check_encoding(&bytes(&[0x80]), "8180");
+    
+    // The set theoretical representation of three: [ [], [[]], [ [], [[]] ] ]
+    let empty = || list(Vec::new());
+    let three = list(vec![empty(), list(vec![empty()]), list(vec![empty(), list(vec![empty()])])]);
+    check_encoding(&three, "c7c0c1c0c3c0c1c0");
+    
+    // Past 55 bytes the length takes bytes of its own
+    check_encoding(&bytes(LOREM), &format!("b838{}", hex::encode(LOREM)));
+    check_encoding(&bytes(&[0xaa; 1024]), &format!("b90400{}", "aa".repeat(1024)));
+    let long_list = list(vec![bytes(&[0xbb; 60])]);
+    check_encoding(&long_list, &format!("f83eb83c{}", "bb".repeat(60)));
+}
+
+/// Builds a random item nesting up to `depth` lists deep
+fn random_item(rng: &mut StdRng, depth: usize) -> RlpItem {
+    if depth == 0 || rng.gen_bool(0.5) {
+        // Lengths around both the single-byte and the 55-byte boundaries
+        let len = match rng.gen_range(0..4) {
+            0 => 0,
+            1 => 1,
+            2 => rng.gen_range(50..60),
+            _ => rng.gen_range(0..300),
+        };
+        let mut data = vec![0u8; len];
+        rng.fill(&mut data[..]);
+        bytes(&data)
+    } else {
+        let len = rng.gen_range(0..6);
+        list((0..len).map(|_| random_item(rng, depth - 1)).collect())
+    }
+}
+
+/// Checks random items decode to themselves and re-encode to the same bytes
+#[test]
+fn check_round_trips() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for _ in 0..256 {
+        let item = random_item(&mut rng, 4);
+        let encoded = rlp::encode(&item);
+        let decoded = rlp::decode(&encoded).unwrap();
+        assert_eq!(decoded, item);
+        assert_eq!(rlp::encode(&decoded), encoded);
+        
+        // Items decode one after the other, leaving the bytes after them
+        let mut two = encoded.clone();
+        two.extend_from_slice(&encoded);
+        let (first, rest) = rlp::decode_item(&two).unwrap();
+        assert_eq!((first, rest), (item, encoded.as_slice()));
+    }
+    
+    for value in [0u128, 1, 0x7f, 0x80, 0xff, 0x100, u64::MAX as u128, u128::MAX] {
+        assert_eq!(rlp::decode(&rlp::encode(&RlpItem::uint(value))).unwrap().as_uint(), Ok(value));
+    }
+}
+
+/// Decodes hex expected to be refused, returning the error
+fn refused(digits: &str) -> RlpError {
+    rlp::decode(&hex::decode(digits).unwrap()).unwrap_err()
+}
+
+/// Checks truncated, trailing and non-canonical input is refused
+#[test]
+fn check_malformed() {
+    // Input ending early
+    assert_eq!(rlp::decode(&[]), Err(RlpError::UnexpectedEnd));
+    assert_eq!(refused("83646f"), RlpError::UnexpectedEnd);
+    assert_eq!(refused("b8"), RlpError::UnexpectedEnd);
+    assert_eq!(refused("b90400aa"), RlpError::UnexpectedEnd);
+    assert_eq!(refused("c883636174"), RlpError::UnexpectedEnd);
+    
+    // A list item longer than the list holding it
+    assert_eq!(refused("c283646f67"), RlpError::UnexpectedEnd);
+    
+    // A length too large for any input, and bytes left over after the item
+    assert_eq!(refused("bfffffffffffffffff"), RlpError::UnexpectedEnd);
+    assert_eq!(refused("8000"), RlpError::TrailingBytes(1));
+    assert_eq!(refused("c0c0"), RlpError::TrailingBytes(1));
+    
+    // Encodings other than the shortest
+    assert!(matches!(refused("8105"), RlpError::NonCanonical(_)));
+    assert!(matches!(refused("817f"), RlpError::NonCanonical(_)));
+    assert!(matches!(refused("b803646f67"), RlpError::NonCanonical(_)));
+    assert!(matches!(refused(&format!("b90038{}", hex::encode(LOREM))), RlpError::NonCanonical(_)));
+    assert!(matches!(refused("f803c0c0c0"), RlpError::NonCanonical(_)));
+    assert!(matches!(refused("c3c28105"), RlpError::NonCanonical(_)));
+}
+
+/// Wraps the empty list in `levels - 1` more lists
+fn nested(levels: usize) -> RlpItem {
+    (1..levels).fold(list(Vec::new()), |item, _| list(vec![item]))
+}
+
+/// Checks lists nest up to `MAX_DEPTH` deep and no deeper
+#[test]
+fn check_depth() {
+    let deepest = nested(MAX_DEPTH);
+    assert_eq!(rlp::decode(&rlp::encode(&deepest)).unwrap(), deepest);
+    assert_eq!(rlp::decode(&rlp::encode(&nested(MAX_DEPTH + 1))), Err(RlpError::TooDeep));
+}
+
+/// Checks integers must be canonical and fit the type read
+#[test]
+fn check_integers() {
+    assert_eq!(rlp::decode(&hex::decode("820001").unwrap()).unwrap().as_uint(), Err(RlpError::LeadingZeros));
+    assert_eq!(bytes(&[0x00]).as_uint(), Err(RlpError::LeadingZeros));
+    assert_eq!(bytes(&[0x01; 17]).as_uint(), Err(RlpError::IntegerTooLarge(17)));
+    assert_eq!(RlpItem::uint(u64::MAX as u128 + 1).as_u64(), Err(RlpError::IntegerTooLarge(9)));
+    assert_eq!(list(Vec::new()).as_uint(), Err(RlpError::ExpectedBytes));
+    assert_eq!(bytes(b"dog").as_list(), Err(RlpError::ExpectedList));
+    
+    let mut word = [0u8; 32];
+    word[30..].copy_from_slice(&[0x04, 0x00]);
+    assert_eq!(RlpItem::uint(1024).as_word(), Ok(word));
+    assert_eq!(bytes(&[0x01; 33]).as_word(), Err(RlpError::IntegerTooLarge(33)));
+}
\ No newline at end of file
@@ -0,0 +1,125 @@
+//! Checks secp256k1 signatures against known answers and round trips
+//!
+//! Run with `cargo test -p core --test secp256k1`. Signs the EIP-155
+//! example transaction's hash with its key and checks the deterministic
+//! signature and the key's address match the published ones, then signs
+//! with random keys and checks each signature verifies, recovers its key
+//! with either `s`, and is refused once altered.
+
+use core::secp256k1::{PublicKey, SecretKey, Signature};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// Seed of the random keys and hashes
+const SEED: u64 = 17;
+
+/// Order of the curve's base point, big-endian
+const N: &str = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141";
+
+/// Key of the EIP-155 example, every byte 0x46
+const EIP155_KEY: [u8; 32] = [0x46; 32];
+
+/// Signing hash of the EIP-155 example transaction
+const EIP155_HASH: &str = "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53";
+
+/// `r` and `s` of the EIP-155 example's signature, whose `v` of 37 on chain 1 is recovery ID 0
+const EIP155_R: &str = "28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276";
+const EIP155_S: &str = "67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+/// Address of the EIP-155 example key
+const EIP155_ADDRESS: &str = "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+
+/// Decodes hex of a fixed length
+fn bytes<const L: usize>(digits: &str) -> [u8; L] {
+    hex::decode(digits).unwrap().try_into().unwrap()
+}
+
+/// Gets `N - s`, the other valid `s` of a signature
+fn negate(s: &[u8; 32]) -> [u8; 32] {
+    let n: [u8; 32] = bytes(N);
+    let mut result = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let difference = n[i] as i16 - s[i] as i16 - borrow;
+        borrow = (difference < 0) as i16;
+        result[i] = difference.rem_euclid(256) as u8;
+    }
+    result
+}
+
+/// Checks signing the EIP-155 example gives the published signature, deterministically
+#[test]
+fn check_eip155() {
+    let hash = bytes(EIP155_HASH);
+    let address: [u8; 20] = bytes(EIP155_ADDRESS);
+    let key = SecretKey::from_bytes(&EIP155_KEY).unwrap();
+    assert_eq!(key.public_key().evm_address(), address);
+    
+    let signature = key.sign(&hash);
+    assert_eq!(signature, Signature::from_parts(bytes(EIP155_R), bytes(EIP155_S), 0).unwrap());
+    assert_eq!(key.sign(&hash), signature, "signing isn't deterministic");
+    assert!(key.public_key().verify(&hash, &signature));
+    assert_eq!(signature.recover(&hash).unwrap().evm_address(), address);
+}
+
+/// Checks signatures of random keys verify, recover their key and fail once altered
+#[test]
+fn check_round_trips() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for _ in 0..32 {
+        let key = SecretKey::generate(&mut rng);
+        let public_key = key.public_key();
+        let mut hash = [0u8; 32];
+        rng.fill_bytes(&mut hash);
+        
+        assert_eq!(SecretKey::from_bytes(&key.to_bytes()).unwrap().to_bytes(), key.to_bytes());
+        assert_eq!(PublicKey::from_bytes(&public_key.to_compressed()).unwrap(), public_key);
+        assert_eq!(PublicKey::from_bytes(&public_key.to_uncompressed()).unwrap(), public_key);
+        
+        let signature = key.sign(&hash);
+        let bytes = signature.to_bytes();
+        assert!(public_key.verify(&hash, &signature));
+        assert_eq!(Signature::from_bytes(&bytes).unwrap(), signature);
+        assert_eq!(signature.recover(&hash), Some(public_key));
+        
+        // Signing gives the low `s`; the high one only recovers, with the other parity
+        let s: [u8; 32] = bytes[32..64].try_into().unwrap();
+        assert!(s < negate(&s), "signing gave the high s");
+        let high = Signature::from_parts(bytes[..32].try_into().unwrap(), negate(&s), signature.recovery_id() ^ 1).unwrap();
+        assert!(!public_key.verify(&hash, &high), "a high s verifies");
+        assert_eq!(high.recover(&hash), Some(public_key));
+        
+        let flipped = Signature::from_parts(bytes[..32].try_into().unwrap(), s, signature.recovery_id() ^ 1).unwrap();
+        assert_ne!(flipped.recover(&hash), Some(public_key));
+        
+        let mut other = hash;
+        other[0] ^= 1;
+        assert!(!public_key.verify(&other, &signature), "a signature verifies another hash");
+        assert_ne!(signature.recover(&other), Some(public_key));
+    }
+}
+
+/// Checks keys and signatures out of range or of the wrong size are refused
+#[test]
+fn check_malformed() {
+    let hash = bytes(EIP155_HASH);
+    let (r, s, n) = (bytes(EIP155_R), bytes(EIP155_S), bytes(N));
+    assert!(SecretKey::from_bytes(&[0; 32]).is_err());
+    assert!(SecretKey::from_bytes(&n).is_err());
+    assert!(SecretKey::from_bytes(&[1; 31]).is_err());
+    
+    let public_key = SecretKey::from_bytes(&EIP155_KEY).unwrap().public_key();
+    let mut off_curve = public_key.to_uncompressed();
+    off_curve[64] ^= 1;
+    assert!(PublicKey::from_bytes(&off_curve).is_err());
+    assert!(PublicKey::from_bytes(&public_key.to_compressed()[1..]).is_err());
+    
+    assert!(Signature::from_parts(r, s, 4).is_err());
+    assert!(Signature::from_bytes(&[0; 64]).is_err());
+    let zero = Signature::from_parts([0; 32], s, 0).unwrap();
+    assert!(!public_key.verify(&hash, &zero));
+    assert_eq!(zero.recover(&hash), None);
+    let overflowing = Signature::from_parts(r, n, 0).unwrap();
+    assert!(!public_key.verify(&hash, &overflowing));
+    assert_eq!(overflowing.recover(&hash), None);
+}
\ No newline at end of file
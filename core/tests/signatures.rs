@@ -0,0 +1,151 @@
+//! Checks transactions are only valid with their sender's signature
+//!
+//! Run with `cargo test -p core --features testutil --test signatures`.
+//! Signs a transfer, then tampers with its amount, drops or truncates its
+//! signature and signs it with another account's key, and checks each is
+//! refused by `Transaction::validate` and in a block, while coinbase
+//! transactions need no signature. A transfer from a genesis fund, whose
+//! address holds no key to sign with, is refused too, and so are blocks
+//! not signed by their registered validator's key.
+
+use core::block::Block;
+use core::genesis;
+use core::chainbuilder::TestChain;
+use core::transaction::Transaction;
+use core::types::Bytes;
+use core::units::{Amount, GENX};
+use core::{Address, BlockchainError};
+
+/// Seed of the chain built
+const SEED: u64 = 13;
+
+/// Signs a transfer from alice to bob
+fn signed_transfer(chain: &TestChain) -> Transaction {
+    let mut tx = Transaction::new(
+        chain.account("alice").address.clone(),
+        chain.account("bob").address.clone(),
+        Amount::from_base_units(GENX),
+        Amount::from_base_units(1),
+        None,
+    )
+    .unwrap();
+    chain.account("alice").sign(&mut tx).unwrap();
+    tx
+}
+
+/// Checks `validate` refuses tampered, unsigned and wrongly signed transactions
+#[test]
+fn check_validate() {
+    let chain = TestChain::new(SEED);
+    let tx = signed_transfer(&chain);
+    tx.validate().unwrap();
+    
+    // The amount changed after signing, leaving the ID as signed
+    let mut tampered = tx.clone();
+    tampered.amount += 1;
+    assert!(matches!(tampered.validate(), Err(BlockchainError::InvalidTransactionId { .. })), "a tampered amount passes");
+    
+    // The ID recomputed too, so the signature is of another ID
+    tampered.id = tampered.calculate_hash().unwrap();
+    assert!(matches!(tampered.validate(), Err(BlockchainError::InvalidSignature { .. })), "a tampered amount passes with its ID");
+    
+    let mut unsigned = tx.clone();
+    unsigned.signature = None;
+    assert!(matches!(unsigned.validate(), Err(BlockchainError::MissingSignature { tx_id }) if tx_id == tx.id));
+    
+    let mut truncated = tx.clone();
+    truncated.signature = Some(Bytes(tx.signature.as_ref().unwrap().0[..32].to_vec()));
+    assert!(matches!(truncated.validate(), Err(BlockchainError::InvalidSignature { .. })), "a truncated signature passes");
+    
+    let mut forged = tx.clone();
+    chain.account("bob").sign(&mut forged).unwrap();
+    assert!(matches!(forged.validate(), Err(BlockchainError::InvalidSignature { .. })), "bob's signature passes as alice's");
+    
+    let coinbase = Transaction::new_coinbase(chain.address("alice"), GENX).unwrap();
+    assert!(coinbase.signature.is_none());
+    coinbase.validate().unwrap();
+}
+
+/// Checks a chain refuses a block holding a transfer whose amount was changed after signing
+#[test]
+fn check_block() {
+    let mut chain = TestChain::new(SEED);
+    let mut built = chain.next_block(|b| b.transfer("alice", "bob", GENX));
+    let tx = built.transactions.iter_mut().find(|tx| tx.sender == chain.address("alice")).unwrap();
+    tx.amount = 500 * GENX;
+    tx.id = tx.calculate_hash().unwrap();
+    let tampered_id = tx.id;
+    
+    let header = built.header();
+    let mut block = Block::new(header.height, header.prev_hash, built.transactions.clone(), header.validator.clone(), header.base_fee).unwrap();
+    block.header_mut().timestamp = header.timestamp;
+    block.header_mut().validator_set_hash = header.validator_set_hash;
+    
+    let error = chain.blockchain_mut().add_block(block).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::InvalidSignature { tx_id, .. } if *tx_id == tampered_id), "{}", error);
+    assert_eq!(error.error_code(), 1016);
+    assert_eq!(chain.height(), 0);
+    let bob = chain.account("bob").address.clone();
+    assert_eq!(chain.blockchain().get_balance(&bob).unwrap().base_units(), 1_000 * GENX);
+}
+
+/// Checks an unsigned transfer out of a keyless genesis fund is refused in a block
+#[test]
+fn check_genesis_fund() {
+    let mut blockchain = genesis::initialize_blockchain().unwrap();
+    let fund = Address::new("GENX_DEVELOPMENT_FUND").unwrap();
+    let balance = blockchain.get_balance(&fund).unwrap();
+    
+    let thief = Address::new("thief").unwrap();
+    let tx = Transaction::new(fund.clone(), thief, Amount::from_base_units(GENX), Amount::from_base_units(1), None).unwrap();
+    assert!(tx.signature.is_none());
+    assert!(matches!(tx.validate(), Err(BlockchainError::InvalidSignature { .. })), "an unsigned transfer from {} passes", fund);
+    
+    let genesis = blockchain.get_latest_block().unwrap();
+    let (genesis_hash, base_fee) = (genesis.hash().unwrap(), genesis.header().base_fee);
+    let block = Block::new(1, genesis_hash, vec![tx.clone()], "anyone".to_string(), base_fee).unwrap();
+    let error = blockchain.add_block(block).unwrap_err();
+    assert!(matches!(error.root(), BlockchainError::InvalidSignature { tx_id, .. } if *tx_id == tx.id), "{}", error);
+    assert_eq!(blockchain.get_latest_block().unwrap().header().height, 0);
+    assert_eq!(blockchain.get_balance(&fund).unwrap(), balance);
+}
+
+/// Checks a chain refuses blocks its validator didn't sign, or that name no registered validator
+#[test]
+fn check_block_signer() {
+    let mut chain = TestChain::new(SEED);
+    let block = chain.next_block(|b| b.transfer("alice", "bob", GENX));
+    assert_eq!(block.header().validator, chain.address("validator"));
+    
+    let refused = |chain: &mut TestChain, block: Block| {
+        let error = chain.blockchain_mut().add_block(block).unwrap_err();
+        assert_eq!(chain.height(), 0);
+        error
+    };
+    
+    // Signed by alice in the validator's name
+    let mut forged = block.clone();
+    chain.account("alice").sign_header(forged.header_mut()).unwrap();
+    let error = refused(&mut chain, forged);
+    assert!(matches!(error.root(), BlockchainError::InvalidBlockSignature { validator, .. } if *validator == chain.address("validator")), "{}", error);
+    assert_eq!(error.error_code(), 1029);
+    
+    let mut unsigned = block.clone();
+    unsigned.header_mut().signature = None;
+    let error = refused(&mut chain, unsigned);
+    assert!(matches!(error.root(), BlockchainError::InvalidBlockSignature { .. }), "{}", error);
+    
+    // Signed after it was changed
+    let mut tampered = block.clone();
+    tampered.header_mut().timestamp += 1;
+    let error = refused(&mut chain, tampered);
+    assert!(matches!(error.root(), BlockchainError::InvalidBlockSignature { .. }), "{}", error);
+    
+    // Proposed and signed by alice, who isn't a validator
+    let unregistered = chain.next_block(|b| b.proposed_by("alice"));
+    let error = refused(&mut chain, unregistered);
+    assert!(matches!(error.root(), BlockchainError::UnknownValidator { address } if *address == chain.address("alice")), "{}", error);
+    
+    chain.add_block(block);
+    assert_eq!(chain.height(), 1);
+}
\ No newline at end of file
@@ -0,0 +1,162 @@
+//! Checks slashing evidence expires, and a pending slash is burned after its dispute period unless a supermajority cancels it
+//!
+//! Run with `cargo test -p core --features testutil --test slashing`. Has
+//! the first of four validators sign a second header at height 1 on a test
+//! chain, and alice report it. Checks the report is accepted in the last
+//! block of `EVIDENCE_WINDOW` and refused in the block after. An accepted
+//! report escrows the penalty out of the stake, can't be made twice, and
+//! is burned with the last block of `DISPUTE_PERIOD`. Then checks
+//! approvals from the slashed validator and from stake short of two thirds
+//! of the rest leave a slash pending, while one more cancels it, returning
+//! the penalty and leaving nothing to burn.
+
+use core::chainbuilder::{TestChain, TestChainConfig};
+use core::slashing::{self, Slash, SlashCancellation, SlashStatus, SlashingEvidence, DISPUTE_PERIOD, EVIDENCE_WINDOW};
+use core::transaction::Transaction;
+use core::units::GENX;
+use core::Address;
+
+/// Seed of the chains built
+const SEED: u64 = 199;
+
+/// Stake of each validator
+const STAKE: u64 = 1_000 * GENX;
+
+/// Validators of the chains, the first of them signing conflicting headers
+const VALIDATORS: [&str; 4] = ["v1", "v2", "v3", "v4"];
+
+/// Height the first validator signs conflicting headers at
+const OFFENCE_HEIGHT: u64 = 1;
+
+/// Creates a chain of the four validators, each funded by alice to pay fees
+fn chain() -> TestChain {
+    let config = TestChainConfig {
+        validators: VALIDATORS.iter().map(|name| (name.to_string(), STAKE)).collect(),
+        ..TestChainConfig::default()
+    };
+    let mut chain = TestChain::with_config(SEED, config);
+    chain.with_block(|b| VALIDATORS.iter().fold(b, |b, validator| b.transfer("alice", validator, GENX)));
+    assert_eq!(chain.blocks()[OFFENCE_HEIGHT as usize].header().validator, chain.address("v1"));
+    chain
+}
+
+/// Has the first validator sign a second header at the offence height, differing from the block's only in its time
+fn evidence(chain: &TestChain) -> SlashingEvidence {
+    let first = chain.blocks()[OFFENCE_HEIGHT as usize].header().clone();
+    let mut second = first.clone();
+    second.timestamp += 1;
+    chain.account("v1").sign_header(&mut second).unwrap();
+    SlashingEvidence { first, second }
+}
+
+/// Adds a block of a transaction from an account of the chain, or says why the chain refused it
+fn submit(chain: &mut TestChain, tx: Transaction) -> Result<(), String> {
+    let block = chain.next_block(|b| b.transaction(tx));
+    chain.blockchain_mut().add_block(block).map_err(|e| e.to_string())
+}
+
+/// Adds a block of alice reporting the first validator
+fn report(chain: &mut TestChain) -> Result<(), String> {
+    let tx = Transaction::new_submit_evidence(chain.address("alice"), &evidence(chain), chain.config().transfer_fee).unwrap();
+    submit(chain, tx)
+}
+
+/// Adds a block of a validator approving cancelling a slash
+fn approve(chain: &mut TestChain, who: &str, slash_id: u64) -> Result<(), String> {
+    let tx = Transaction::new_cancel_slash(chain.address(who), &SlashCancellation { slash_id }, chain.config().transfer_fee).unwrap();
+    submit(chain, tx)
+}
+
+/// Gets a slash from the chain's state
+fn slash(chain: &TestChain, id: u64) -> Slash {
+    chain.blockchain().get_state().lock().unwrap().get_slash(id).cloned().unwrap()
+}
+
+/// Gets the stake of a validator of the chain, in base units
+fn stake(chain: &TestChain, who: &str) -> u64 {
+    let address = Address::new(chain.address(who)).unwrap();
+    chain.blockchain().get_state().lock().unwrap().get_validator_stake(&address).base_units()
+}
+
+/// Gets the GENX burned so far, in base units
+fn burned(chain: &TestChain) -> u64 {
+    chain.blockchain().get_state().lock().unwrap().get_total_burned()
+}
+
+/// Checks evidence is accepted up to `EVIDENCE_WINDOW` blocks after the offence and refused after
+#[test]
+fn check_window() {
+    let last = OFFENCE_HEIGHT + EVIDENCE_WINDOW;
+    let mut in_window = chain();
+    in_window.with_empty_blocks(last - 1 - in_window.height());
+    report(&mut in_window).unwrap();
+    assert_eq!(in_window.height(), last);
+    assert_eq!(slash(&in_window, 1).submitted_at, last);
+    
+    let mut expired = chain();
+    expired.with_empty_blocks(last - expired.height());
+    let error = report(&mut expired).unwrap_err();
+    assert!(error.contains(&format!("Evidence about height {} expired at height {}", OFFENCE_HEIGHT, last)), "{}", error);
+    assert_eq!(expired.height(), last);
+    assert_eq!(stake(&expired, "v1"), STAKE);
+}
+
+/// Checks an accepted report escrows the penalty, and it's burned with the last block of the dispute period
+#[test]
+fn check_applied() {
+    let mut chain = chain();
+    report(&mut chain).unwrap();
+    let submitted_at = chain.height();
+    let penalty = slashing::penalty(STAKE);
+    let pending = slash(&chain, 1);
+    assert_eq!((pending.status, pending.penalty, pending.height), (SlashStatus::Pending, penalty, OFFENCE_HEIGHT));
+    assert_eq!(pending.applies_at, submitted_at + DISPUTE_PERIOD);
+    assert_eq!(stake(&chain, "v1"), STAKE - penalty);
+    
+    // The same offence can't be reported again
+    let error = report(&mut chain).unwrap_err();
+    assert!(error.contains("already reported for height 1"), "{}", error);
+    
+    // Nothing is burned until the period's last block
+    chain.with_empty_blocks(pending.applies_at - 1 - chain.height());
+    assert_eq!(slash(&chain, 1).status, SlashStatus::Pending);
+    let before = burned(&chain);
+    chain.with_empty_blocks(1);
+    assert_eq!(slash(&chain, 1).status, SlashStatus::Applied);
+    assert_eq!(burned(&chain), before + penalty);
+    assert_eq!(stake(&chain, "v1"), STAKE - penalty);
+    
+    // An applied slash can't be cancelled
+    let error = approve(&mut chain, "v2", 1).unwrap_err();
+    assert!(error.contains("Slash 1 is no longer pending"), "{}", error);
+}
+
+/// Checks a slash is cancelled once validators holding more than two thirds of the other stake approve
+#[test]
+fn check_cancelled() {
+    let mut chain = chain();
+    report(&mut chain).unwrap();
+    let applies_at = slash(&chain, 1).applies_at;
+    
+    // Only validators approve, and the slashed one's approval doesn't count
+    let error = approve(&mut chain, "alice", 1).unwrap_err();
+    assert!(error.contains("has no stake to approve cancelling with"), "{}", error);
+    approve(&mut chain, "v1", 1).unwrap();
+    approve(&mut chain, "v2", 1).unwrap();
+    
+    // Two of the three others hold exactly two thirds, which isn't enough
+    approve(&mut chain, "v3", 1).unwrap();
+    let pending = slash(&chain, 1);
+    assert_eq!(pending.status, SlashStatus::Pending);
+    assert_eq!(pending.approvals.len(), 3);
+    
+    approve(&mut chain, "v4", 1).unwrap();
+    assert_eq!(slash(&chain, 1).status, SlashStatus::Cancelled);
+    assert_eq!(stake(&chain, "v1"), STAKE);
+    
+    // The end of the period burns nothing
+    let before = burned(&chain);
+    chain.with_empty_blocks(applies_at - chain.height());
+    assert_eq!(slash(&chain, 1).status, SlashStatus::Cancelled);
+    assert_eq!(burned(&chain), before);
+}
\ No newline at end of file
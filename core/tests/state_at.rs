@@ -0,0 +1,93 @@
+//! Checks the state after a past block is rebuilt as it was, within the depth allowed
+//!
+//! Run with `cargo test -p core --features testutil --test state_at`.
+//! Builds a test chain where alice pays bob in one block between empty
+//! ones, and checks their balances and nonces read from the states after
+//! the blocks before, holding and after the transfer are the ones each
+//! had then, while the live state is left as it is. Then checks heights
+//! further back than the configured depth fail with `StateUnavailable`
+//! naming the oldest reachable height, and heights past the tip with
+//! `UnknownBlock`.
+
+use core::chainbuilder::TestChain;
+use core::units::GENX;
+use core::{Address, BlockchainError};
+
+/// Seed of the chain built
+const SEED: u64 = 137;
+
+/// Amount alice pays bob
+const PAID: u64 = 25 * GENX;
+
+/// Height of the block with the transfer
+const TRANSFER: u64 = 3;
+
+/// Empty blocks after the transfer
+const AFTER: u64 = 4;
+
+/// Builds the chain, with empty blocks before and after the transfer
+fn chain() -> TestChain {
+    let mut chain = TestChain::new(SEED);
+    chain.with_empty_blocks(TRANSFER - 1);
+    chain.with_block(|b| b.transfer("alice", "bob", PAID));
+    chain.with_empty_blocks(AFTER);
+    chain
+}
+
+/// Gets an account's balance and nonce after the block at a height
+fn account_at(chain: &TestChain, who: &str, height: u64) -> (u64, u64) {
+    let address = Address::new(chain.address(who)).unwrap();
+    let snapshot = chain.blockchain().state_at(height).unwrap();
+    assert_eq!(snapshot.block_height, height);
+    (snapshot.state.get_balance(&address).base_units(), snapshot.state.get_nonce(&address))
+}
+
+/// Checks balances and nonces at heights straddling a transfer are the ones before and after it
+#[test]
+fn check_straddling_transfer() {
+    let chain = chain();
+    let alice_before = account_at(&chain, "alice", TRANSFER - 1);
+    let bob_before = account_at(&chain, "bob", TRANSFER - 1);
+    let alice_after = account_at(&chain, "alice", TRANSFER);
+    let bob_after = account_at(&chain, "bob", TRANSFER);
+    
+    let fee = alice_before.0 - alice_after.0 - PAID;
+    assert!(fee > 0, "the transfer paid a fee");
+    assert_eq!(bob_after.0 - bob_before.0, PAID);
+    assert_eq!((alice_before.1, alice_after.1), (0, 1));
+    assert_eq!(bob_after.1, bob_before.1);
+    
+    // Nothing changes in the blocks after, up to the live state
+    let latest = chain.height();
+    assert_eq!(account_at(&chain, "alice", TRANSFER + 1), alice_after);
+    assert_eq!(account_at(&chain, "alice", latest), alice_after);
+    assert_eq!(account_at(&chain, "bob", latest), bob_after);
+    assert_eq!(account_at(&chain, "alice", 0), alice_before);
+    
+    // Rebuilding a past state leaves the live one as it was
+    let alice = Address::new(chain.address("alice")).unwrap();
+    assert_eq!(chain.blockchain().get_balance(&alice).unwrap().base_units(), alice_after.0);
+    assert_eq!(chain.blockchain().snapshot().block_height, latest);
+    chain.assert_balances();
+}
+
+/// Checks heights further back than the depth allowed, or past the tip, are refused
+#[test]
+fn check_depth() {
+    let mut chain = chain();
+    let latest = chain.height();
+    assert_eq!(chain.blockchain().oldest_state_height(), 0);
+    
+    chain.blockchain_mut().set_max_state_depth(AFTER);
+    let oldest = latest - AFTER;
+    assert_eq!(oldest, TRANSFER);
+    assert_eq!(chain.blockchain().oldest_state_height(), oldest);
+    assert!(chain.blockchain().state_at(oldest).is_ok());
+    match chain.blockchain().state_at(oldest - 1) {
+        Err(BlockchainError::StateUnavailable { height, oldest: reachable }) => assert_eq!((height, reachable), (oldest - 1, oldest)),
+        other => panic!("{:?}", other.map(|snapshot| snapshot.block_height)),
+    }
+    
+    assert!(matches!(chain.blockchain().state_at(latest + 1), Err(BlockchainError::UnknownBlock { height }) if height == latest + 1));
+    assert_eq!(chain.blockchain().state_at(latest).unwrap().block_height, latest);
+}
\ No newline at end of file
@@ -0,0 +1,162 @@
+//! Checks block rewards are split with the treasury as the consensus rules require
+//!
+//! Run with `cargo test -p core --features testutil --test treasury`.
+//! Checks the split rounds the treasury's share down and always adds up to
+//! the reward, follows the rules' activation heights and refuses malformed
+//! schedules, then builds a test chain and checks the treasury and the
+//! validator were paid exactly their shares and the supply grew by the
+//! rewards less the burned fees. Blocks whose coinbase lets the validator
+//! keep the treasury's share, or pays it elsewhere, are refused.
+
+use core::block::Block;
+use core::chainbuilder::TestChain;
+use core::rewards::{self, RewardSchedule, TreasuryRule, BASIS_POINTS, HALVING_INTERVAL};
+use core::transaction::Transaction;
+use core::Address;
+
+/// Seed of the chain built
+const SEED: u64 = 103;
+
+/// Blocks of transfers the chain is built with
+const BLOCKS: u64 = 6;
+
+/// Makes a rule paying a share to a treasury from a height on
+fn rule(activation_height: u64, address: &str, share_bps: u32) -> TreasuryRule {
+    TreasuryRule { activation_height, address: address.to_string(), share_bps }
+}
+
+/// Checks shares round down, add up to the reward and change only at activation heights
+#[test]
+fn check_split() {
+    let schedule = RewardSchedule::new(vec![
+        rule(0, "GENX_TREASURY_A", 1_000),
+        rule(10, "GENX_TREASURY_B", 3_333),
+        rule(20, "GENX_TREASURY_C", 0),
+        rule(30, "GENX_TREASURY_D", BASIS_POINTS),
+    ])
+    .unwrap();
+    
+    for height in 1..40 {
+        let (reward, split) = (rewards::block_reward(height), schedule.split(height));
+        let rule = schedule.rule_at(height);
+        assert_eq!(rule.activation_height, height / 10 * 10, "height {}", height);
+        assert_eq!(split.validator + split.treasury, reward, "height {}", height);
+        assert_eq!(split.treasury as u128, reward as u128 * rule.share_bps as u128 / BASIS_POINTS as u128);
+    }
+    assert_eq!(schedule.rule_at(9).address, "GENX_TREASURY_A");
+    assert_eq!(schedule.rule_at(10).address, "GENX_TREASURY_B");
+    
+    // A reward that doesn't divide evenly leaves the remainder with the validator
+    let schedule = RewardSchedule::new(vec![rule(0, "GENX_TREASURY", 3_333)]).unwrap();
+    let height = 10 * HALVING_INTERVAL;
+    let reward = rewards::block_reward(height);
+    let split = schedule.split(height);
+    assert_eq!(reward, 4_882_812);
+    assert_eq!((split.validator, split.treasury), (3_255_371, 1_627_441));
+    
+    // A share coming to nothing gets no coinbase transaction
+    let payout = "GENX_PAYOUT";
+    let schedule = RewardSchedule::new(vec![rule(0, "GENX_TREASURY", 0), rule(5, "GENX_TREASURY", BASIS_POINTS)]).unwrap();
+    let recipients = |height: u64| -> Vec<(String, u64)> {
+        let coinbases = schedule.coinbase_transactions(height, payout).unwrap();
+        coinbases.into_iter().map(|tx| (tx.recipient, tx.amount)).collect()
+    };
+    assert_eq!(recipients(1), vec![(payout.to_string(), rewards::block_reward(1))]);
+    assert_eq!(recipients(5), vec![("GENX_TREASURY".to_string(), rewards::block_reward(5))]);
+}
+
+/// Checks schedules that don't start at genesis, go backwards or hold invalid rules are refused
+#[test]
+fn check_bad_schedules() {
+    let schedules = [
+        vec![],
+        vec![rule(1, "GENX_TREASURY", 1_000)],
+        vec![rule(0, "GENX_TREASURY", 1_000), rule(10, "GENX_TREASURY", 500), rule(10, "GENX_TREASURY", 200)],
+        vec![rule(0, "GENX_TREASURY", 1_000), rule(10, "GENX_TREASURY", 500), rule(5, "GENX_TREASURY", 200)],
+        vec![rule(0, "GENX_TREASURY", BASIS_POINTS + 1)],
+        vec![rule(0, "", 1_000)],
+    ];
+    for rules in schedules {
+        assert!(RewardSchedule::new(rules.clone()).is_err(), "{:?}", rules);
+    }
+}
+
+/// Checks the treasury and the validator got their shares of every block and the supply grew by the rewards
+#[test]
+fn check_supply() {
+    let mut chain = TestChain::new(SEED);
+    let balance = |chain: &TestChain, address: &str| {
+        chain.blockchain().get_state().lock().unwrap().get_balance(&Address::new(address).unwrap()).base_units()
+    };
+    let supply = |chain: &TestChain| {
+        let state = chain.blockchain().get_state();
+        let state = state.lock().unwrap();
+        (state.get_total_supply(), state.get_total_burned())
+    };
+    let treasury = chain.blockchain().reward_schedule().rule_at(1).address.clone();
+    let validator = chain.address("validator");
+    let before = (balance(&chain, &treasury), balance(&chain, &validator), supply(&chain));
+    
+    for height in 1..=BLOCKS {
+        chain.with_block(|b| b.transfer("alice", "bob", 1_000 * height));
+    }
+    
+    let schedule = chain.blockchain().reward_schedule();
+    let splits: Vec<_> = (1..=BLOCKS).map(|height| schedule.split(height)).collect();
+    let treasury_share: u64 = splits.iter().map(|split| split.treasury).sum();
+    let validator_share: u64 = splits.iter().map(|split| split.validator).sum();
+    let rewards: u64 = (1..=BLOCKS).map(rewards::block_reward).sum();
+    assert!(treasury_share > 0);
+    assert_eq!(treasury_share + validator_share, rewards);
+    assert_eq!(balance(&chain, &treasury) - before.0, treasury_share);
+    assert_eq!(balance(&chain, &validator) - before.1, validator_share);
+    
+    let ((supply_before, burned_before), (supply_after, burned_after)) = (before.2, supply(&chain));
+    assert_eq!(burned_after - burned_before, BLOCKS * chain.config().transfer_fee);
+    assert_eq!(supply_after, supply_before + rewards - (burned_after - burned_before));
+    chain.assert_balances();
+}
+
+/// Checks blocks keeping the treasury's share, leaving it out or paying it elsewhere are refused
+#[test]
+fn check_kept_share() {
+    let mut chain = TestChain::new(SEED);
+    chain.with_empty_blocks(1);
+    let built = chain.next_block(|b| b.transfer("alice", "bob", 5_000));
+    let reward = rewards::block_reward(built.header().height);
+    
+    // The validator's coinbase paying the whole reward, with no treasury coinbase
+    let mut kept = built.transactions[0].clone();
+    kept.amount = reward;
+    kept.id = kept.calculate_hash().unwrap();
+    let mut elsewhere = built.transactions[1].clone();
+    elsewhere.recipient = chain.address("carol");
+    elsewhere.id = elsewhere.calculate_hash().unwrap();
+    let rest: Vec<Transaction> = built.transactions[2..].to_vec();
+    
+    let coinbases = [
+        vec![kept],
+        vec![built.transactions[0].clone()],
+        vec![built.transactions[0].clone(), elsewhere],
+        vec![built.transactions[1].clone(), built.transactions[0].clone()],
+    ];
+    for coinbases in coinbases {
+        let transactions = coinbases.into_iter().chain(rest.iter().cloned()).collect();
+        let block = rebuild(&chain, &built, transactions);
+        let error = chain.blockchain_mut().add_block(block).unwrap_err().to_string();
+        assert!(error.contains("Coinbase pays"), "{}", error);
+    }
+    
+    assert_eq!(chain.height(), 1);
+    chain.add_block(built);
+    chain.assert_balances();
+}
+
+/// Rebuilds a block with other transactions, signed by its validator
+fn rebuild(chain: &TestChain, built: &Block, transactions: Vec<Transaction>) -> Block {
+    let header = built.header().clone();
+    let mut block = Block::new(header.height, header.prev_hash, transactions, header.validator, header.base_fee).unwrap();
+    block.header_mut().timestamp = header.timestamp;
+    chain.account("validator").sign_header(block.header_mut()).unwrap();
+    block
+}
\ No newline at end of file
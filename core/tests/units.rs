@@ -0,0 +1,70 @@
+//! Checks GENX amounts are read and written exactly
+//!
+//! Run with `cargo test -p core --test units`. Reads amounts with more
+//! digits after the point than `DECIMALS` and past the largest balance,
+//! checking each is refused with the error saying why, and round-trips
+//! random and boundary amounts through `format_genx` and `parse_genx`.
+
+use core::units::{format_genx, parse_genx, Amount, AmountError, DECIMALS, GENX};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Seed of the random amounts
+const SEED: u64 = 43;
+
+/// Number of random amounts round-tripped
+const CASES: usize = 10_000;
+
+/// Checks digits past `DECIMALS` are refused unless they're zeros
+#[test]
+fn check_precision() {
+    let smallest = format!("0.{}1", "0".repeat(DECIMALS as usize - 1));
+    assert_eq!(parse_genx(&smallest), Ok(1));
+    
+    let too_precise = format!("0.{}1", "0".repeat(DECIMALS as usize));
+    assert_eq!(parse_genx(&too_precise), Err(AmountError::TooPrecise { amount: too_precise.clone(), decimals: DECIMALS }));
+    assert_eq!(parse_genx("1.123456789"), Err(AmountError::TooPrecise { amount: "1.123456789".to_string(), decimals: DECIMALS }));
+    
+    let padded = format!("1.5{}", "0".repeat(3 * DECIMALS as usize));
+    assert_eq!(parse_genx(&padded), Ok(GENX + GENX / 2), "zeros past the last decimal change nothing");
+}
+
+/// Checks amounts past `u64::MAX` base units are refused as overflowing
+#[test]
+fn check_overflow() {
+    let largest = format_genx(u64::MAX);
+    assert_eq!(parse_genx(&largest), Ok(u64::MAX));
+    
+    let whole = u64::MAX / GENX;
+    let overflows = [
+        // One base unit more than the largest amount
+        format!("{}.{:0width$}", whole, u64::MAX % GENX + 1, width = DECIMALS as usize),
+        // Whole GENX past the largest, which fit in a u64 before scaling
+        format!("{}", whole + 1),
+        // More digits than a u64 has
+        "184467440737095516160".to_string(),
+        "99999999999999999999999999.5".to_string(),
+    ];
+    for amount in overflows {
+        assert_eq!(parse_genx(&amount), Err(AmountError::Overflow(amount.clone())), "{}", amount);
+    }
+    assert!("184467440737095516160".parse::<Amount>().is_err());
+}
+
+/// Checks every amount written reads back as itself, and writes with no trailing zeros
+#[test]
+fn check_roundtrip() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let boundaries = [0, 1, GENX - 1, GENX, GENX + 1, GENX / 2, u64::MAX / GENX * GENX, u64::MAX - 1, u64::MAX];
+    let random = (0..CASES).map(|case| match case % 3 {
+        0 => rng.gen(),
+        1 => rng.gen_range(0..1_000) * GENX,
+        _ => rng.gen_range(0..1_000 * GENX),
+    });
+    for units in boundaries.into_iter().chain(random) {
+        let written = format_genx(units);
+        assert_eq!(parse_genx(&written), Ok(units), "{} written as {}", units, written);
+        assert!(!written.contains('.') || !written.ends_with('0'), "{} has trailing zeros", written);
+        assert_eq!(Amount::from_base_units(units).to_string().parse::<Amount>(), Ok(Amount::from_base_units(units)));
+    }
+}
\ No newline at end of file
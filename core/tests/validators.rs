@@ -0,0 +1,176 @@
+//! Checks validators register and edit their metadata through transactions
+//!
+//! Run with `cargo test -p core --features testutil --test validators`.
+//! Registers an account as a validator on a test chain and checks the
+//! registry holds its metadata, then that registering twice or with a
+//! taken consensus key, out of bounds metadata and commission changes
+//! beyond `MAX_COMMISSION_CHANGE` or more than once an epoch are refused.
+
+use core::chainbuilder::TestChain;
+use core::transaction::Transaction;
+use core::validator::{
+    ValidatorEdit, ValidatorRegistration, EPOCH_LENGTH, MAX_COMMISSION_CHANGE, MAX_COMMISSION_RATE, MAX_MONIKER_LENGTH,
+};
+use core::Address;
+
+/// Seed of the chain built
+const SEED: u64 = 79;
+
+/// Commission carol registers with, in basis points
+const COMMISSION: u32 = 500;
+
+/// Makes a registration signing with a consensus key
+fn registration(moniker: &str, commission_rate: u32, consensus_key: String) -> ValidatorRegistration {
+    ValidatorRegistration {
+        moniker: moniker.to_string(),
+        website: "https://carol.example".to_string(),
+        commission_rate,
+        consensus_key,
+        payout_address: None,
+    }
+}
+
+/// Makes a transaction registering an account of the chain
+fn register(chain: &TestChain, who: &str, registration: &ValidatorRegistration) -> Transaction {
+    Transaction::new_register_validator(chain.address(who), registration, chain.config().transfer_fee).unwrap()
+}
+
+/// Makes a transaction editing the validator of an account of the chain
+fn edit(chain: &TestChain, who: &str, edit: &ValidatorEdit) -> Transaction {
+    Transaction::new_edit_validator(chain.address(who), edit, chain.config().transfer_fee).unwrap()
+}
+
+/// Adds a block of one transaction
+fn add(chain: &mut TestChain, tx: Transaction) {
+    chain.with_block(|b| b.transaction(tx));
+}
+
+/// Gets why the chain rejects a block of one transaction
+fn rejected(chain: &mut TestChain, tx: Transaction) -> String {
+    let block = chain.next_block(|b| b.transaction(tx));
+    chain.blockchain_mut().add_block(block).unwrap_err().to_string()
+}
+
+/// Gets a balance, in base units
+fn balance(chain: &TestChain, address: &str) -> u64 {
+    chain.blockchain().get_state().lock().unwrap().get_balance(&Address::new(address).unwrap()).base_units()
+}
+
+/// Creates a chain where carol registered as a validator in block 1
+fn registered() -> TestChain {
+    let mut chain = TestChain::new(SEED);
+    let tx = register(&chain, "carol", &registration("carol", COMMISSION, chain.address("carol")));
+    add(&mut chain, tx);
+    chain
+}
+
+/// Checks a registration puts the validator's metadata in the registry, charging its fee
+#[test]
+fn check_register() {
+    let mut chain = TestChain::new(SEED);
+    let carol = chain.address("carol");
+    let before = balance(&chain, &carol);
+    let tx = register(&chain, "carol", &registration("carol", COMMISSION, carol.clone()));
+    add(&mut chain, tx);
+    assert_eq!(balance(&chain, &carol), before - chain.config().transfer_fee);
+    
+    let state = chain.blockchain().get_state();
+    let state = state.lock().unwrap();
+    let validator = state.get_validator(&carol).expect("carol is registered");
+    assert_eq!(validator.operator, carol);
+    assert_eq!(validator.moniker, "carol");
+    assert_eq!(validator.website, "https://carol.example");
+    assert_eq!(validator.commission_rate, COMMISSION);
+    assert_eq!(validator.consensus_key, carol);
+    assert_eq!(validator.payout_address, carol);
+    assert_eq!(validator.registered_at, 1);
+    
+    let operators: Vec<&str> = state.get_validators().iter().map(|(validator, _)| validator.operator.as_str()).collect();
+    let mut expected = vec![carol.as_str(), chain.validators()[0].address.as_str()];
+    expected.sort();
+    assert_eq!(operators, expected);
+}
+
+/// Checks an operator can't register twice, nor take another validator's consensus key
+#[test]
+fn check_register_twice() {
+    let mut chain = registered();
+    let again = register(&chain, "carol", &registration("carol again", COMMISSION, chain.address("carol")));
+    let error = rejected(&mut chain, again);
+    assert!(error.contains("already registered"), "{}", error);
+    
+    let validator = chain.validators()[0].address.to_string();
+    let taken = register(&chain, "bob", &registration("bob", COMMISSION, validator));
+    let error = rejected(&mut chain, taken);
+    assert!(error.contains("already in use"), "{}", error);
+    
+    let bob = chain.address("bob");
+    let tx = register(&chain, "bob", &registration("bob", COMMISSION, bob.clone()));
+    add(&mut chain, tx);
+    assert_eq!(chain.blockchain().get_state().lock().unwrap().get_validator(&bob).unwrap().moniker, "bob");
+}
+
+/// Checks registrations with out of bounds metadata or commission, and edits changing nothing, are refused
+#[test]
+fn check_bounds() {
+    let mut chain = TestChain::new(SEED);
+    let carol = chain.address("carol");
+    let long_moniker = "c".repeat(MAX_MONIKER_LENGTH + 1);
+    for bad in [
+        registration(&long_moniker, COMMISSION, carol.clone()),
+        registration(" ", COMMISSION, carol.clone()),
+        registration("carol", MAX_COMMISSION_RATE + 1, carol.clone()),
+        registration("carol", COMMISSION, "GENX_NOT_A_KEY".to_string()),
+    ] {
+        assert!(bad.validate().is_err());
+        let tx = register(&chain, "carol", &bad);
+        rejected(&mut chain, tx);
+    }
+    assert!(chain.blockchain().get_state().lock().unwrap().get_validator(&carol).is_none());
+    
+    let mut chain = registered();
+    let tx = edit(&chain, "carol", &ValidatorEdit::default());
+    let error = rejected(&mut chain, tx);
+    assert!(error.contains("changes nothing"), "{}", error);
+    let unregistered = ValidatorEdit { moniker: Some("bob".to_string()), ..ValidatorEdit::default() };
+    let tx = edit(&chain, "bob", &unregistered);
+    let error = rejected(&mut chain, tx);
+    assert!(error.contains("not registered"), "{}", error);
+}
+
+/// Checks edits change the metadata straight away, and the commission once an epoch by at most the cap
+#[test]
+fn check_edit() {
+    let mut chain = registered();
+    let carol = chain.address("carol");
+    let validator = |chain: &TestChain| chain.blockchain().get_state().lock().unwrap().get_validator(&carol).unwrap().clone();
+    let commission = |rate| ValidatorEdit { commission_rate: Some(rate), ..ValidatorEdit::default() };
+    
+    let renamed = ValidatorEdit { moniker: Some("carol's node".to_string()), website: Some("ops@carol.example".to_string()), ..ValidatorEdit::default() };
+    let tx = edit(&chain, "carol", &renamed);
+    add(&mut chain, tx);
+    assert_eq!(validator(&chain).moniker, "carol's node");
+    assert_eq!(validator(&chain).website, "ops@carol.example");
+    
+    let tx = edit(&chain, "carol", &commission(COMMISSION + 1));
+    let error = rejected(&mut chain, tx);
+    assert!(error.contains("already set in epoch 0"), "{}", error);
+    
+    chain.with_empty_blocks(EPOCH_LENGTH - 1 - chain.height());
+    let tx = edit(&chain, "carol", &commission(COMMISSION + MAX_COMMISSION_CHANGE + 1));
+    let error = rejected(&mut chain, tx);
+    assert!(error.contains("exceeds the limit"), "{}", error);
+    let tx = edit(&chain, "carol", &commission(COMMISSION + MAX_COMMISSION_CHANGE));
+    add(&mut chain, tx);
+    assert_eq!(validator(&chain).commission_rate, COMMISSION + MAX_COMMISSION_CHANGE);
+    assert_eq!(validator(&chain).commission_changed_at, EPOCH_LENGTH);
+    
+    let tx = edit(&chain, "carol", &commission(COMMISSION));
+    
+    let error = rejected(&mut chain, tx);
+    assert!(error.contains("already set in epoch 1"), "{}", error);
+    chain.with_empty_blocks(2 * EPOCH_LENGTH - 1 - chain.height());
+    let tx = edit(&chain, "carol", &commission(COMMISSION));
+    add(&mut chain, tx);
+    assert_eq!(validator(&chain).commission_rate, COMMISSION);
+}
\ No newline at end of file
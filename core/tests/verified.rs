@@ -0,0 +1,123 @@
+//! Checks signatures verified once are skipped after, and only for the very transactions verified
+//!
+//! Run with `cargo test -p core --features testutil --test verified`.
+//! Admits a block's transfers through the chain's verified transaction
+//! cache, as the mempool does, then adds the block and checks none of their
+//! signatures was verified again, and that auditing the chain bypasses the
+//! cache. Then checks transactions forged to reuse a cached ID or signature
+//! still fail validation, and that a full cache evicts the transaction used
+//! least recently.
+
+use core::chainbuilder::TestChain;
+use core::transaction::{Transaction, TransactionType};
+use core::units::GENX;
+use core::verified::{CacheStats, VerifiedTxCache};
+use core::BlockchainError;
+
+/// Seed of the chain built
+const SEED: u64 = 149;
+
+/// Makes transfers between the test accounts, signed, in a block for the chain
+fn transfers(chain: &TestChain) -> core::block::Block {
+    chain.next_block(|b| b.transfer("alice", "bob", GENX).transfer("bob", "carol", 2 * GENX).transfer("carol", "alice", 3 * GENX))
+}
+
+/// Makes a transfer from alice to bob, signed
+fn transfer(chain: &TestChain, amount: u64) -> Transaction {
+    let mut tx = Transaction::new_with_type(TransactionType::Transfer, chain.address("alice"), chain.address("bob"), amount, 1_000, None, 0, 0).unwrap();
+    chain.account("alice").sign(&mut tx).unwrap();
+    tx
+}
+
+/// Checks a block whose transactions were admitted before is added without verifying their signatures again
+#[test]
+fn check_block_of_admitted() {
+    let mut chain = TestChain::new(SEED);
+    let cache = chain.blockchain().verified_tx_cache();
+    let block = transfers(&chain);
+    let signed = block.transactions.iter().filter(|tx| tx.signature.is_some()).count() as u64;
+    assert_eq!(signed, 3);
+    
+    // Admission verifies each once
+    let before = cache.stats();
+    for tx in &block.transactions {
+        tx.validate_cached(&cache).unwrap();
+    }
+    assert_eq!(cache.stats(), CacheStats { hits: before.hits, misses: before.misses + signed });
+    
+    // The block then finds every one verified
+    chain.add_block(block);
+    assert_eq!(cache.stats(), CacheStats { hits: before.hits + signed, misses: before.misses + signed });
+    
+    // Auditing the chain verifies everything again, without the cache
+    let audited = cache.stats();
+    chain.blockchain().validate_chain().unwrap();
+    assert_eq!(cache.stats(), audited);
+    chain.assert_balances();
+}
+
+/// Checks transactions forged to reuse a cached ID or signature fail validation
+#[test]
+fn check_forged() {
+    let chain = TestChain::new(SEED);
+    let cache = VerifiedTxCache::new(16);
+    let tx = transfer(&chain, GENX);
+    tx.validate_cached(&cache).unwrap();
+    tx.validate_cached(&cache).unwrap();
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+    
+    // Other contents under the cached ID and signature
+    let mut forged = tx.clone();
+    forged.amount = 100 * GENX;
+    assert!(forged.validate_cached(&cache).is_err());
+    assert!(forged.validate().is_err());
+    
+    // Other contents with their own ID, under the cached signature
+    forged.id = forged.calculate_hash().unwrap();
+    let error = forged.validate_cached(&cache).unwrap_err();
+    assert!(matches!(error, BlockchainError::InvalidSignature { tx_id, .. } if tx_id == forged.id), "{:?}", error);
+    
+    // The cached ID and contents under another signature
+    let mut forged = tx.clone();
+    forged.signature = transfer(&chain, 2 * GENX).signature;
+    let error = forged.validate_cached(&cache).unwrap_err();
+    assert!(matches!(error, BlockchainError::InvalidSignature { .. }), "{:?}", error);
+    
+    // None of them was cached, nor displaced the transaction
+    assert_eq!(cache.len(), 1);
+    tx.validate_cached(&cache).unwrap();
+    assert_eq!(cache.stats().hits, 2);
+}
+
+/// Checks a full cache evicts the transaction used least recently
+#[test]
+fn check_eviction() {
+    let chain = TestChain::new(SEED);
+    let cache = VerifiedTxCache::new(2);
+    let (first, second, third) = (transfer(&chain, GENX), transfer(&chain, 2 * GENX), transfer(&chain, 3 * GENX));
+    first.validate_cached(&cache).unwrap();
+    second.validate_cached(&cache).unwrap();
+    
+    // Using the first makes the second the least recent
+    first.validate_cached(&cache).unwrap();
+    third.validate_cached(&cache).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3 });
+    
+    first.validate_cached(&cache).unwrap();
+    third.validate_cached(&cache).unwrap();
+    assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 3 });
+    second.validate_cached(&cache).unwrap();
+    assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 4 });
+    
+    // Shrinking it keeps the latest used
+    cache.set_capacity(1);
+    assert_eq!(cache.len(), 1);
+    second.validate_cached(&cache).unwrap();
+    assert_eq!(cache.stats(), CacheStats { hits: 4, misses: 4 });
+    
+    // An empty one caches nothing
+    let disabled = VerifiedTxCache::new(0);
+    first.validate_cached(&disabled).unwrap();
+    assert!(disabled.is_empty());
+}
\ No newline at end of file
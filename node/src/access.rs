@@ -0,0 +1,307 @@
+//! Access control for the RPC server
+//!
+//! `AccessConfig`, part of `RpcConfig`, restricts what clients of the RPC
+//! server may do over HTTP. Requests handled in-process, through
+//! `RpcHandler::handle_request`, are never restricted.
+//!
+//! - Methods matching `denied_methods`, or not matching `allowed_methods`
+//!   when it's set, fail as unknown methods (`METHOD_NOT_FOUND`).
+//! - With an `auth_token` set, the `privileged_methods` need it as an
+//!   `Authorization: Bearer <token>` header, and fail with `UNAUTHORIZED`
+//!   without it. By default those are the `admin_` methods, the methods
+//!   submitting transactions and `debug_validateBlock`. Without one, anyone
+//!   who can reach the server may call them, so a server listening on
+//!   anything but a loopback address refuses to start without a token.
+//! - `ip_rate_limit` bounds the requests each client IP makes, and
+//!   `method_rate_limits` the calls it makes of given methods, each with a
+//!   token bucket. Throttled requests are answered
+//!   `429 Too Many Requests`; throttled calls fail with `LIMIT_EXCEEDED`,
+//!   which is also answered with a 429 unless the call is part of a batch.
+//! - With `cors_allowed_origins` set, browsers may call from those origins,
+//!   or from any with `*`, and requests from other origins are refused with
+//!   `403 Forbidden`. Without it, no CORS headers are sent, so browsers
+//!   can't read responses from pages on other origins.
+//!
+//! Method patterns ending in `_`, such as `admin_`, match a whole namespace;
+//! any other pattern matches a method by name. Refusals are logged and
+//! counted in the `genx_rpc_rejections_total` metric, by reason.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::eth::{EthError, Result};
+use crate::metrics::Metrics;
+
+/// Most token buckets kept, by client IP and method pattern
+pub const MAX_BUCKETS: usize = 65_536;
+
+/// RPC access control configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessConfig {
+    /// Token privileged methods need as a bearer token, none if unset
+    pub auth_token: Option<String>,
+    
+    /// Methods that need `auth_token`, by pattern
+    pub privileged_methods: Vec<String>,
+    
+    /// Origins browsers may call from, `*` for any
+    pub cors_allowed_origins: Vec<String>,
+    
+    /// Methods served, by pattern, all of them if unset
+    pub allowed_methods: Option<Vec<String>>,
+    
+    /// Methods never served, by pattern
+    pub denied_methods: Vec<String>,
+    
+    /// Requests each client IP may make, unlimited if unset
+    pub ip_rate_limit: Option<RateLimit>,
+    
+    /// Calls each client IP may make of the methods matching each pattern
+    ///
+    /// A method counts against the longest pattern it matches only, and all
+    /// the methods of a namespace pattern share its limit.
+    pub method_rate_limits: BTreeMap<String, RateLimit>,
+}
+
+impl Default for AccessConfig {
+    fn default() -> Self {
+        Self {
+            auth_token: None,
+            privileged_methods: vec![
+                "admin_".to_string(),
+                "genx_sendTransaction".to_string(),
+                "eth_sendRawTransaction".to_string(),
+                "debug_validateBlock".to_string(),
+            ],
+            cors_allowed_origins: Vec::new(),
+            allowed_methods: None,
+            denied_methods: Vec::new(),
+            ip_rate_limit: None,
+            method_rate_limits: BTreeMap::new(),
+        }
+    }
+}
+
+impl AccessConfig {
+    /// Checks the configuration of a server listening on `listen_addr`
+    ///
+    /// Every rate limit must let some requests through, and only a server
+    /// listening on a loopback address, which other hosts can't reach, may
+    /// go without an `auth_token`.
+    pub fn validate(&self, listen_addr: SocketAddr) -> std::result::Result<(), String> {
+        if self.auth_token.as_deref().is_none_or(str::is_empty) && !listen_addr.ip().is_loopback() {
+            return Err(format!("an auth_token must be set to serve RPC on {}, which isn't a loopback address", listen_addr));
+        }
+        let limits = self.ip_rate_limit.iter().map(|limit| ("ip_rate_limit", limit))
+            .chain(self.method_rate_limits.iter().map(|(pattern, limit)| (pattern.as_str(), limit)));
+        for (name, limit) in limits {
+            if !(limit.per_second > 0.0 && limit.per_second.is_finite()) || limit.burst == 0 {
+                return Err(format!("rate limit of {} must have a positive rate and burst", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Token bucket limit on requests
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Requests allowed per second, on average
+    pub per_second: f64,
+    
+    /// Requests allowed in a row after a pause
+    pub burst: u32,
+}
+
+/// HTTP client of the RPC server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    /// Address the client connected from
+    pub ip: IpAddr,
+    
+    /// Bearer token the request carried, if any
+    pub token: Option<String>,
+}
+
+/// Requests a client may still make without waiting
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    
+    /// Whether the last request was refused, so a flood is logged once
+    throttled: bool,
+}
+
+impl Bucket {
+    fn full(limit: &RateLimit, now: Instant) -> Self {
+        Self { tokens: f64::from(limit.burst), updated: now, throttled: false }
+    }
+    
+    /// Gets the tokens the bucket holds at `now`
+    fn level(&self, limit: &RateLimit, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * limit.per_second).min(f64::from(limit.burst))
+    }
+    
+    /// Takes a token if there is one, returning whether there was
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        self.tokens = self.level(limit, now);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.throttled = false;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Enforces an `AccessConfig` on the requests a server receives
+#[derive(Debug)]
+pub struct AccessControl {
+    config: AccessConfig,
+    
+    /// Token buckets by client IP and method pattern, none for the IP's own limit
+    buckets: Mutex<HashMap<(IpAddr, Option<String>), Bucket>>,
+    
+    metrics: Arc<Metrics>,
+}
+
+impl AccessControl {
+    /// Creates access control enforcing a configuration, counting refusals in `metrics`
+    pub fn new(config: AccessConfig, metrics: Arc<Metrics>) -> Self {
+        Self { config, buckets: Mutex::new(HashMap::new()), metrics }
+    }
+    
+    /// Gets the configuration enforced
+    pub fn config(&self) -> &AccessConfig {
+        &self.config
+    }
+    
+    /// Checks a request's `Origin` header, returning the CORS headers to answer it with
+    ///
+    /// Returns none if the origin isn't allowed.
+    pub fn check_origin(&self, origin: Option<&str>, ip: IpAddr) -> Option<Vec<(&'static str, String)>> {
+        let allowed = &self.config.cors_allowed_origins;
+        let origin = match origin {
+            Some(origin) if !allowed.is_empty() => origin,
+            _ => return Some(Vec::new()),
+        };
+        
+        if allowed.iter().any(|allowed| allowed == "*") {
+            Some(vec![("Access-Control-Allow-Origin", "*".to_string())])
+        } else if allowed.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin)) {
+            Some(vec![("Access-Control-Allow-Origin", origin.to_string()), ("Vary", "Origin".to_string())])
+        } else {
+            self.refuse("origin", ip, &format!("origin {} isn't allowed", origin));
+            None
+        }
+    }
+    
+    /// Counts a request against its client IP's rate limit, returning whether it's within it
+    pub fn admit(&self, ip: IpAddr) -> bool {
+        match self.config.ip_rate_limit {
+            Some(limit) => self.take(ip, None, &limit),
+            None => true,
+        }
+    }
+    
+    /// Checks whether a client may call a method, counting the call against its rate limit
+    pub fn check_call(&self, method: &str, client: &Client) -> Result<()> {
+        let denied = self.config.denied_methods.iter().any(|pattern| matches(pattern, method));
+        let allowed = self.config.allowed_methods.as_ref().is_none_or(|patterns| patterns.iter().any(|pattern| matches(pattern, method)));
+        if denied || !allowed {
+            self.refuse("disabled_method", client.ip, &format!("{} is disabled", method));
+            return Err(EthError::MethodNotFound(method.to_string()));
+        }
+        
+        if let Some(token) = &self.config.auth_token {
+            let privileged = self.config.privileged_methods.iter().any(|pattern| matches(pattern, method));
+            let authorized = client.token.as_deref().is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()));
+            if privileged && !authorized {
+                self.refuse("unauthorized", client.ip, &format!("{} needs authorization", method));
+                return Err(EthError::Unauthorized(method.to_string()));
+            }
+        }
+        
+        let limited = self.config.method_rate_limits
+            .iter()
+            .filter(|(pattern, _)| matches(pattern, method))
+            .max_by_key(|(pattern, _)| pattern.len());
+        if let Some((pattern, limit)) = limited {
+            if !self.take(client.ip, Some(pattern), limit) {
+                return Err(EthError::LimitExceeded(method.to_string()));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Takes a token from a client's bucket for a method pattern, logging when a flood starts
+    fn take(&self, ip: IpAddr, pattern: Option<&str>, limit: &RateLimit) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let key = (ip, pattern.map(str::to_string));
+        if !buckets.contains_key(&key) && buckets.len() >= MAX_BUCKETS {
+            evict(&mut buckets, &self.config, now);
+        }
+        
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket::full(limit, now));
+        if bucket.take(limit, now) {
+            return true;
+        }
+        let first = !bucket.throttled;
+        bucket.throttled = true;
+        drop(buckets);
+        
+        self.metrics.record_rpc_rejection("rate_limited");
+        if first {
+            eprintln!("RPC: throttling {} for {}", ip, pattern.map(|pattern| format!("calls of {}", pattern)).unwrap_or_else(|| "requests".to_string()));
+        }
+        false
+    }
+    
+    /// Logs and counts a refused request
+    fn refuse(&self, reason: &'static str, ip: IpAddr, detail: &str) {
+        self.metrics.record_rpc_rejection(reason);
+        eprintln!("RPC: refused a request from {}: {}", ip, detail);
+    }
+}
+
+/// Checks whether a method pattern matches a method
+pub fn matches(pattern: &str, method: &str) -> bool {
+    if pattern.ends_with('_') {
+        method.starts_with(pattern)
+    } else {
+        pattern == method
+    }
+}
+
+/// Makes room for a bucket, dropping those that have refilled or else the least recently used
+fn evict(buckets: &mut HashMap<(IpAddr, Option<String>), Bucket>, config: &AccessConfig, now: Instant) {
+    let limit_of = |pattern: &Option<String>| match pattern {
+        Some(pattern) => config.method_rate_limits.get(pattern).copied(),
+        None => config.ip_rate_limit,
+    };
+    buckets.retain(|(_, pattern), bucket| {
+        limit_of(pattern).is_some_and(|limit| bucket.level(&limit, now) < f64::from(limit.burst))
+    });
+    
+    if buckets.len() >= MAX_BUCKETS {
+        let oldest = buckets.iter().min_by_key(|(_, bucket)| bucket.updated).map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            buckets.remove(&oldest);
+        }
+    }
+}
+
+/// Compares two byte strings in time independent of where they differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
\ No newline at end of file
@@ -0,0 +1,274 @@
+//! Peer addresses learned from peer exchange
+//!
+//! Peers answer `GetPeers` with `Addresses`: announcements in which nodes
+//! say where they can be dialed, each signed by the node's identity key
+//! (see `NodeIdentity`) and naming the node by the key's address, so it
+//! can be passed on from peer to peer without any of them being able to
+//! change where it says the node is. A node signs its announcements the
+//! first time it gives them out, and again once they're
+//! `ANNOUNCEMENT_REFRESH` old.
+//!
+//! Anyone can make a key and announce addresses of their own, so the
+//! `AddressBook` a node keeps announcements in takes at most
+//! `MAX_NEW_ADDRESSES_PER_HOUR` addresses it didn't know from each peer an
+//! hour, and `MAX_ADDRESSES` in all. Announcements signed more than
+//! `ANNOUNCEMENT_TTL` ago are forgotten. The addresses the node reached a
+//! peer at itself, by completing the handshake over a connection it dialed
+//! there, are confirmed, and come before those only announced when it
+//! chooses where to dial.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use ctb_core::hashing::{HashDomain, Hasher};
+use ctb_core::rlp::{self, RlpItem};
+use ctb_core::signature::{self, SignatureScheme};
+use ctb_core::wire;
+use ctb_core::{Bytes, Hash};
+
+use crate::message::AddressAnnouncement;
+
+/// Age at which a node signs its announcements again
+pub const ANNOUNCEMENT_REFRESH: Duration = Duration::from_secs(30 * 60);
+
+/// Age at which announcements are forgotten, and confirmations with them
+pub const ANNOUNCEMENT_TTL: Duration = Duration::from_secs(3 * 60 * 60);
+
+/// Furthest in the future an announcement may be dated, for clocks running ahead
+pub const MAX_CLOCK_DRIFT: Duration = Duration::from_secs(10 * 60);
+
+/// Most addresses the book didn't know it takes from one peer an hour
+pub const MAX_NEW_ADDRESSES_PER_HOUR: usize = 32;
+
+/// Most addresses in the book
+pub const MAX_ADDRESSES: usize = 4096;
+
+/// Span the new addresses from each peer are counted over
+const RATE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Why an announcement isn't taken
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AddressError {
+    #[error("Announcement of {addr} isn't signed by {node_id}")]
+    Forged { node_id: String, addr: SocketAddr },
+    
+    #[error("Announcement of {addr} signed at {timestamp} is stale")]
+    Stale { addr: SocketAddr, timestamp: u64 },
+    
+    #[error("Announcement of {addr} is dated {timestamp}, in the future")]
+    FromFuture { addr: SocketAddr, timestamp: u64 },
+    
+    #[error("Peer {peer} announced more than {limit} new addresses this hour")]
+    RateLimited { peer: String, limit: usize },
+    
+    #[error("The address book holds {0} addresses already")]
+    Full(usize),
+    
+    #[error("Invalid identity key: {0}")]
+    InvalidIdentityKey(String),
+}
+
+/// Gets the hash an announcement signs
+///
+/// That's the hash, in the `AddressAnnouncement` domain, of the RLP list
+/// of the node ID, the address as text and the timestamp.
+pub fn signing_hash(node_id: &str, addr: &SocketAddr, timestamp: u64) -> Hash {
+    let message = rlp::encode(&RlpItem::List(vec![
+        wire::string(node_id),
+        wire::string(&addr.to_string()),
+        RlpItem::uint(timestamp as u128),
+    ]));
+    let mut hasher = Hasher::for_domain(HashDomain::AddressAnnouncement);
+    hasher.update(&message);
+    hasher.finalize()
+}
+
+/// Checks that an announcement is signed by the key its node ID is the address of
+pub fn verify(announcement: &AddressAnnouncement) -> Result<(), AddressError> {
+    let hash = signing_hash(&announcement.node_id, &announcement.addr, announcement.timestamp);
+    signature::verify(&announcement.node_id, &hash, &announcement.signature).map_err(|_| AddressError::Forged {
+        node_id: announcement.node_id.clone(),
+        addr: announcement.addr,
+    })
+}
+
+/// Ed25519 key a node signs its address announcements with
+#[derive(Clone)]
+pub struct NodeIdentity {
+    secret_key: Vec<u8>,
+    node_id: String,
+}
+
+impl NodeIdentity {
+    /// Makes a new random identity
+    pub fn generate() -> Self {
+        Self::from_secret_key(rand::random::<[u8; 32]>().to_vec()).expect("Any 32 bytes are an Ed25519 secret key")
+    }
+    
+    /// Takes an identity from its secret key in hex
+    pub fn from_hex(secret_key: &str) -> Result<Self, AddressError> {
+        let secret_key = hex::decode(secret_key).map_err(|e| AddressError::InvalidIdentityKey(e.to_string()))?;
+        Self::from_secret_key(secret_key)
+    }
+    
+    fn from_secret_key(secret_key: Vec<u8>) -> Result<Self, AddressError> {
+        let node_id = signature::address_of(SignatureScheme::Ed25519, &secret_key)
+            .map_err(|e| AddressError::InvalidIdentityKey(e.to_string()))?;
+        Ok(Self { secret_key, node_id })
+    }
+    
+    /// Gets the address of the identity's key, which names the node in its announcements
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+    
+    /// Signs an announcement that the node can be dialed at an address
+    pub fn announce(&self, addr: SocketAddr, timestamp: u64) -> AddressAnnouncement {
+        let hash = signing_hash(&self.node_id, &addr, timestamp);
+        let signature = signature::sign(SignatureScheme::Ed25519, &self.secret_key, &hash)
+            .expect("The identity's key was checked when it was made");
+        AddressAnnouncement { node_id: self.node_id.clone(), addr, timestamp, signature: Bytes(signature) }
+    }
+}
+
+/// What the book knows of an address
+#[derive(Debug, Clone, Default)]
+struct Entry {
+    /// Latest announcement of the address, by the node that first announced it
+    announcement: Option<AddressAnnouncement>,
+    
+    /// When the node last completed a handshake with a peer it dialed at the address
+    confirmed_at: Option<u64>,
+}
+
+impl Entry {
+    /// Gets when the address was last announced or confirmed
+    fn last_heard(&self) -> u64 {
+        let announced = self.announcement.as_ref().map_or(0, |announcement| announcement.timestamp);
+        announced.max(self.confirmed_at.unwrap_or(0))
+    }
+}
+
+/// Addresses peers announced and the node confirmed, see the module docs
+///
+/// Times are Unix timestamps in seconds.
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    entries: HashMap<SocketAddr, Entry>,
+    
+    /// Start of each peer's current `RATE_WINDOW`, with how many new addresses it gave in it
+    windows: HashMap<String, (u64, usize)>,
+}
+
+impl AddressBook {
+    /// Creates an empty book
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Takes an announcement a peer passed on, returning whether its address is new to the book
+    ///
+    /// An announcement of an address already announced only replaces the
+    /// one before it if it's newer and by the same node. The address of an
+    /// announcement refused for the peer's rate isn't checked any further.
+    pub fn insert(&mut self, peer: &str, announcement: AddressAnnouncement, now: u64) -> Result<bool, AddressError> {
+        let (addr, timestamp) = (announcement.addr, announcement.timestamp);
+        if timestamp > now + MAX_CLOCK_DRIFT.as_secs() {
+            return Err(AddressError::FromFuture { addr, timestamp });
+        }
+        if now.saturating_sub(timestamp) >= ANNOUNCEMENT_TTL.as_secs() {
+            return Err(AddressError::Stale { addr, timestamp });
+        }
+        
+        if let Some(entry) = self.entries.get_mut(&addr) {
+            let replaces = entry.announcement.as_ref().is_none_or(|known| {
+                known.node_id == announcement.node_id && known.timestamp < timestamp
+            });
+            if replaces {
+                verify(&announcement)?;
+                entry.announcement = Some(announcement);
+            }
+            return Ok(false);
+        }
+        
+        if self.entries.len() >= MAX_ADDRESSES {
+            return Err(AddressError::Full(self.entries.len()));
+        }
+        let window = self.windows.entry(peer.to_string()).or_insert((now, 0));
+        if now.saturating_sub(window.0) >= RATE_WINDOW.as_secs() {
+            *window = (now, 0);
+        }
+        if window.1 >= MAX_NEW_ADDRESSES_PER_HOUR {
+            return Err(AddressError::RateLimited { peer: peer.to_string(), limit: MAX_NEW_ADDRESSES_PER_HOUR });
+        }
+        // Checked last, so forged announcements don't count against the peer's rate
+        verify(&announcement)?;
+        window.1 += 1;
+        self.entries.insert(addr, Entry { announcement: Some(announcement), confirmed_at: None });
+        Ok(true)
+    }
+    
+    /// Notes that the node completed a handshake with a peer it dialed at an address
+    pub fn confirm(&mut self, addr: SocketAddr, now: u64) {
+        self.entries.entry(addr).or_default().confirmed_at = Some(now);
+    }
+    
+    /// Checks whether the node reached a peer at an address itself
+    pub fn is_confirmed(&self, addr: &SocketAddr) -> bool {
+        self.entries.get(addr).is_some_and(|entry| entry.confirmed_at.is_some())
+    }
+    
+    /// Checks whether the book has an address
+    pub fn contains(&self, addr: &SocketAddr) -> bool {
+        self.entries.contains_key(addr)
+    }
+    
+    /// Forgets announcements and confirmations `ANNOUNCEMENT_TTL` old, returning how many addresses went with them
+    pub fn expire(&mut self, now: u64) -> usize {
+        let ttl = ANNOUNCEMENT_TTL.as_secs();
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| {
+            if entry.announcement.as_ref().is_some_and(|announcement| now.saturating_sub(announcement.timestamp) >= ttl) {
+                entry.announcement = None;
+            }
+            if entry.confirmed_at.is_some_and(|confirmed_at| now.saturating_sub(confirmed_at) >= ttl) {
+                entry.confirmed_at = None;
+            }
+            entry.announcement.is_some() || entry.confirmed_at.is_some()
+        });
+        self.windows.retain(|_, (start, _)| now.saturating_sub(*start) < RATE_WINDOW.as_secs());
+        before - self.entries.len()
+    }
+    
+    /// Gets the addresses in the order they're best dialed in
+    ///
+    /// Confirmed addresses come first, then those only announced, each the
+    /// most recently heard of first and ties in address order.
+    pub fn dial_order(&self) -> Vec<SocketAddr> {
+        let mut addrs: Vec<(&SocketAddr, &Entry)> = self.entries.iter().collect();
+        addrs.sort_by_key(|(addr, entry)| (entry.confirmed_at.is_none(), Reverse(entry.last_heard()), **addr));
+        addrs.into_iter().map(|(addr, _)| *addr).collect()
+    }
+    
+    /// Gets the announcements to pass on, those of confirmed addresses first, as `dial_order` goes
+    pub fn announcements(&self) -> Vec<AddressAnnouncement> {
+        self.dial_order()
+            .iter()
+            .filter_map(|addr| self.entries[addr].announcement.clone())
+            .collect()
+    }
+    
+    /// Gets the number of addresses in the book
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    /// Checks whether the book has no addresses
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,230 @@
+//! Admin JSON-RPC methods, for operators to control a running node
+//!
+//! These methods are only served on the admin listener, `admin_listen_addr`
+//! in `RpcConfig`, which by default only accepts local connections; the
+//! public listener answers them as unknown methods. Every change takes
+//! effect straight away, without restarting the node, and is logged.
+//!
+//! | Method                   | Params                                   | Result                                                  |
+//! |--------------------------|------------------------------------------|---------------------------------------------------------|
+//! | `admin_nodeInfo`         | none                                     | version, chain ID, genesis hash, data directory, uptime |
+//! | `admin_addPeer`          | address, as `host:port`                  | whether it wasn't connected or known already            |
+//! | `admin_removePeer`       | peer ID                                  | whether it was connected                                |
+//! | `admin_listPeers`        | optional page                            | page of connected `Peer`s, highest first                |
+//! | `admin_setLogLevel`      | level, such as `info`                    | the previous level                                      |
+//! | `admin_startValidating`  | none                                     | whether the node wasn't validating already              |
+//! | `admin_stopValidating`   | none                                     | whether the node was validating                         |
+//! | `admin_getMempoolLimits` | none                                     | the `MempoolLimits`                                     |
+//! | `admin_setMempoolLimits` | any `MempoolLimits` fields, as an object | `{"limits": limits, "evicted": n}`                      |
+//! | `admin_banAddress`       | address, optional reason                 | whether it wasn't banned already                        |
+//! | `admin_unbanAddress`     | address                                  | whether it was banned                                   |
+//! | `admin_listBans`         | none                                     | `Ban`s, by address                                      |
+//! | `admin_watchAddress`     | address                                  | whether it wasn't watched already                       |
+//! | `admin_unwatchAddress`   | address                                  | whether it was watched                                  |
+//! | `admin_listWatched`      | none                                     | watched addresses, in order                             |
+//! | `admin_reloadConfig`     | optional path of the configuration file  | `ReloadReport` of the fields applied and rejected       |
+//!
+//! Mempool limits left out of `admin_setMempoolLimits` keep their values.
+//! Lowering the capacity evicts the lowest priority pending transactions,
+//! while new data size limits only apply to transactions added from then
+//! on. Log levels are `off`, `error`, `warn`, `info`, `debug` and `trace`.
+//! The ban methods manage the node's admission policy (see `policy`), and
+//! the watch methods the addresses whose balance changes are announced (see
+//! `watch`).
+//! `admin_listPeers` pages like the `genx_list...` methods (see `rpc`),
+//! sorting by `height`, `last_seen` or `node_id`.
+//! `admin_reloadConfig` reads the file the node's configuration was last
+//! read from unless given another, see `reload`.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::LevelFilter;
+use serde_json::{json, Value};
+
+use ctb_core::chain::Blockchain;
+
+use consensus::{ConsensusEngine, MempoolLimits};
+
+use crate::eth::{self, EthError, Result};
+use crate::network::{NetworkManager, PeerSort};
+use crate::policy::AdmissionPolicy;
+use crate::reload::{ConfigReloader, ReloadError};
+use crate::rpc;
+use crate::watch::AddressWatcher;
+
+/// Handler for the admin methods of a node
+#[derive(Clone)]
+pub struct AdminApi {
+    node_id: String,
+    chain_id: u64,
+    data_dir: String,
+    started_at: Instant,
+    blockchain: Arc<Mutex<Blockchain>>,
+    consensus: Arc<Mutex<ConsensusEngine>>,
+    network: Arc<Mutex<NetworkManager>>,
+    policy: Arc<AdmissionPolicy>,
+    watcher: Arc<AddressWatcher>,
+    validating: Arc<AtomicBool>,
+    reloader: ConfigReloader,
+}
+
+impl AdminApi {
+    /// Creates a handler over a node's components
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        node_id: String,
+        chain_id: u64,
+        data_dir: String,
+        started_at: Instant,
+        blockchain: Arc<Mutex<Blockchain>>,
+        consensus: Arc<Mutex<ConsensusEngine>>,
+        network: Arc<Mutex<NetworkManager>>,
+        policy: Arc<AdmissionPolicy>,
+        watcher: Arc<AddressWatcher>,
+        validating: Arc<AtomicBool>,
+        reloader: ConfigReloader,
+    ) -> Self {
+        Self { node_id, chain_id, data_dir, started_at, blockchain, consensus, network, policy, watcher, validating, reloader }
+    }
+    
+    /// Runs an admin method with its positional parameters
+    pub fn call(&self, method: &str, params: &[Value]) -> Result<Value> {
+        match method {
+            "admin_nodeInfo" => Ok(self.node_info()),
+            "admin_addPeer" => {
+                let address = eth::param_str(params, 0, "address")?;
+                let address = SocketAddr::from_str(address)
+                    .map_err(|e| EthError::InvalidParams(format!("invalid address {}: {}", address, e)))?;
+                
+                let network = self.network.lock().unwrap();
+                let added = network.should_dial(address) && network.add_known_address(address);
+                println!("Admin: added peer {}", address);
+                Ok(Value::Bool(added))
+            }
+            "admin_removePeer" => {
+                let peer_id = eth::param_str(params, 0, "peer ID")?;
+                let removed = self.network.lock().unwrap().disconnect_peer(peer_id).is_ok();
+                println!("Admin: removed peer {}", peer_id);
+                Ok(Value::Bool(removed))
+            }
+            "admin_listPeers" => {
+                let request = rpc::param_page(params, 0)?;
+                let sort: PeerSort = rpc::param_sort(params, 0)?;
+                Ok(json!(self.network.lock().unwrap().list_peers(sort, &request)?))
+            }
+            "admin_setLogLevel" => {
+                let level = eth::param_str(params, 0, "level")?;
+                let level = LevelFilter::from_str(level)
+                    .map_err(|_| EthError::InvalidParams(format!("invalid log level {}", level)))?;
+                
+                let previous = log::max_level();
+                log::set_max_level(level);
+                println!("Admin: set the log level from {} to {}", previous, level);
+                Ok(json!(previous.as_str().to_lowercase()))
+            }
+            "admin_startValidating" => {
+                let started = !self.validating.swap(true, Ordering::SeqCst);
+                println!("Admin: started validating");
+                Ok(Value::Bool(started))
+            }
+            "admin_stopValidating" => {
+                let stopped = self.validating.swap(false, Ordering::SeqCst);
+                println!("Admin: stopped validating");
+                Ok(Value::Bool(stopped))
+            }
+            "admin_getMempoolLimits" => {
+                let limits = self.consensus.lock().unwrap().mempool_limits();
+                serde_json::to_value(limits).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "admin_setMempoolLimits" => {
+                let changes = params
+                    .first()
+                    .and_then(Value::as_object)
+                    .ok_or_else(|| EthError::InvalidParams("missing limits".to_string()))?;
+                
+                let mut consensus = self.consensus.lock().unwrap();
+                let mut limits = serde_json::to_value(consensus.mempool_limits()).map_err(|e| EthError::Server(e.to_string()))?;
+                for (name, value) in changes {
+                    match limits.get_mut(name) {
+                        Some(limit) => *limit = value.clone(),
+                        None => return Err(EthError::InvalidParams(format!("unknown mempool limit {}", name))),
+                    }
+                }
+                let limits: MempoolLimits = serde_json::from_value(limits)
+                    .map_err(|e| EthError::InvalidParams(format!("invalid mempool limits: {}", e)))?;
+                
+                let evicted = consensus.set_mempool_limits(limits);
+                println!("Admin: set the mempool limits to {:?}, evicting {} transactions", limits, evicted.len());
+                Ok(json!({ "limits": limits, "evicted": evicted.len() }))
+            }
+            "admin_banAddress" => {
+                let address = eth::param_str(params, 0, "address")?;
+                let reason = match params.get(1) {
+                    None | Some(Value::Null) => "banned over RPC",
+                    Some(reason) => reason
+                        .as_str()
+                        .ok_or_else(|| EthError::InvalidParams(format!("invalid reason {}", reason)))?,
+                };
+                let banned = self.policy.ban(address, reason).map_err(|e| EthError::Server(format!("failed to save banlist: {}", e)))?;
+                Ok(Value::Bool(banned))
+            }
+            "admin_unbanAddress" => {
+                let address = eth::param_str(params, 0, "address")?;
+                let unbanned = self.policy.unban(address).map_err(|e| EthError::Server(format!("failed to save banlist: {}", e)))?;
+                Ok(Value::Bool(unbanned))
+            }
+            "admin_listBans" => serde_json::to_value(self.policy.bans()).map_err(|e| EthError::Server(e.to_string())),
+            "admin_watchAddress" => {
+                let address = eth::param_str(params, 0, "address")?;
+                let watched = self.watcher.watch(address).map_err(|e| EthError::Server(format!("failed to save watch list: {}", e)))?;
+                Ok(Value::Bool(watched))
+            }
+            "admin_unwatchAddress" => {
+                let address = eth::param_str(params, 0, "address")?;
+                let unwatched = self.watcher.unwatch(address).map_err(|e| EthError::Server(format!("failed to save watch list: {}", e)))?;
+                Ok(Value::Bool(unwatched))
+            }
+            "admin_listWatched" => Ok(json!(self.watcher.watched())),
+            "admin_reloadConfig" => {
+                let path = match params.first() {
+                    None | Some(Value::Null) => None,
+                    Some(path) => Some(PathBuf::from(
+                        path.as_str().ok_or_else(|| EthError::InvalidParams(format!("invalid path {}", path)))?,
+                    )),
+                };
+                let report = self.reloader.reload(path.as_deref()).map_err(|e| match e {
+                    ReloadError::NoConfigFile | ReloadError::InvalidValue { .. } => EthError::InvalidParams(e.to_string()),
+                    ReloadError::Read { .. } | ReloadError::Parse { .. } => EthError::Server(e.to_string()),
+                })?;
+                println!("Admin: reloaded the configuration");
+                serde_json::to_value(report).map_err(|e| EthError::Server(e.to_string()))
+            }
+            method => Err(EthError::MethodNotFound(method.to_string())),
+        }
+    }
+    
+    /// Checks whether the node is producing blocks
+    pub fn is_validating(&self) -> bool {
+        self.validating.load(Ordering::SeqCst)
+    }
+    
+    /// `admin_nodeInfo()`
+    fn node_info(&self) -> Value {
+        let genesis_hash = self.blockchain.lock().unwrap().get_block_by_height(0).and_then(|genesis| genesis.hash().ok());
+        
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "node_id": self.node_id,
+            "chain_id": self.chain_id,
+            "genesis_hash": genesis_hash,
+            "data_dir": self.data_dir,
+            "uptime": self.started_at.elapsed().as_secs(),
+            "validating": self.is_validating(),
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,341 @@
+//! Catching up with a peer's chain
+//!
+//! A node that hears of a block it can't add to its tip, of a peer ahead of
+//! it, or of a peer whose chain weighs more than its own, syncs with that
+//! peer through a `BlockSync`. It sends a block locator (see `locator`):
+//! blocks of its chain from the tip down, ever further apart, to the lowest
+//! block it could still reorganize from. The peer answers with its headers
+//! from the highest of them its own chain has, and the node asks for more,
+//! at most `MAX_HEADERS` at a time, up to the height the peer announced,
+//! finds the height the peer's chain forks off its own at, and asks for the
+//! blocks above it. However long ago two halves of a partitioned network
+//! went their own ways, one exchange finds a block both chains share, so
+//! long as it's above the locator's lowest block.
+//! Once they have all arrived they are handed back as a `SyncedBranch`, to
+//! be added to the tip or, when the fork is below the tip, to replace the
+//! node's blocks above it if the fork choice prefers the branch (see
+//! `Node::reorganize`).
+//!
+//! A node syncs with one peer at a time. Requests unanswered after
+//! `REQUEST_RETRY` are sent again. A peer whose headers don't chain up, or
+//! whose chain shares none of the locator's blocks, ends the sync with an
+//! error, as does one that sends nothing asked for in `SYNC_TIMEOUT`.
+//! Either way the node can start again with another peer.
+//!
+//! Like `snapshot_sync`, it only turns messages into messages; sending and
+//! receiving them is left to the caller.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use ctb_core::block::{Block, BlockHeader};
+use ctb_core::hashing;
+use ctb_core::BlockHash;
+
+use crate::message::{NetworkMessage, MAX_HEADERS, MAX_LOCATOR};
+
+/// How long a request may go unanswered before it's sent again
+pub const REQUEST_RETRY: Duration = Duration::from_secs(2);
+
+/// How long a sync may go without receiving anything asked for before it's abandoned
+pub const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Error syncing with a peer
+#[derive(Debug, Error)]
+pub enum BlockSyncError {
+    #[error("Peer {peer} sent {reason}")]
+    Misbehaved { peer: String, reason: String },
+    
+    #[error("Peer {peer}'s chain shares no block with this node's from height {floor} up")]
+    Unrelated { peer: String, floor: u64 },
+    
+    #[error("Peer {peer} stopped answering")]
+    TimedOut { peer: String },
+}
+
+/// Result type for block sync
+pub type Result<T> = std::result::Result<T, BlockSyncError>;
+
+/// Blocks downloaded from a peer, forking off the node's chain after `fork_height`
+#[derive(Debug, Clone)]
+pub struct SyncedBranch {
+    /// Peer the blocks came from
+    pub peer: String,
+    
+    /// Height of the last block the branch shares with the node's chain
+    pub fork_height: u64,
+    
+    /// Blocks above the fork, in order
+    pub blocks: Vec<Arc<Block>>,
+}
+
+/// What a sync is waiting for
+#[derive(Debug)]
+enum Stage {
+    /// Headers from the highest block of the locator the peer has
+    Locating { locator: Vec<(u64, BlockHash)> },
+    
+    /// Headers from a height on
+    Headers { from: u64 },
+    
+    /// The blocks above the fork, by hash in chain order, and those received
+    Blocks {
+        fork_height: u64,
+        hashes: Vec<BlockHash>,
+        received: HashMap<BlockHash, Arc<Block>>,
+    },
+}
+
+/// A sync with one peer
+#[derive(Debug)]
+struct Session {
+    peer: String,
+    
+    /// Height the peer announced
+    height: u64,
+    
+    stage: Stage,
+    
+    /// When something asked for last arrived, or the sync started, in milliseconds
+    active_at: u64,
+    
+    /// When the outstanding requests were last sent, in milliseconds
+    requested_at: u64,
+}
+
+/// Downloads the blocks a node is missing from one peer at a time
+#[derive(Debug, Default)]
+pub struct BlockSync {
+    session: Option<Session>,
+}
+
+impl BlockSync {
+    /// Creates a sync that isn't syncing with anyone
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Gets the peer being synced with, if any
+    pub fn peer(&self) -> Option<&str> {
+        self.session.as_ref().map(|session| session.peer.as_str())
+    }
+    
+    /// Starts syncing with a peer that announced a chain up to `height`, sending it a locator of the node's chain
+    ///
+    /// Returns the headers request to send it, or `None` if a sync is
+    /// already running or the locator is empty. Whether the peer is worth
+    /// syncing with is left to the caller. `now` is in milliseconds.
+    pub fn start(&mut self, peer: &str, height: u64, locator: Vec<(u64, BlockHash)>, now: u64) -> Option<NetworkMessage> {
+        if self.session.is_some() || locator.is_empty() {
+            return None;
+        }
+        let request = NetworkMessage::GetBranchHeaders(locator.clone());
+        self.session = Some(Session {
+            peer: peer.to_string(),
+            height,
+            stage: Stage::Locating { locator },
+            active_at: now,
+            requested_at: now,
+        });
+        Some(request)
+    }
+    
+    /// Gets the requests to send again, with the peer to send them to
+    ///
+    /// Fails, ending the sync, if nothing asked for has arrived for
+    /// `SYNC_TIMEOUT`.
+    pub fn poll(&mut self, now: u64) -> Result<Vec<(String, NetworkMessage)>> {
+        let Some(session) = self.session.as_mut() else {
+            return Ok(Vec::new());
+        };
+        if now.saturating_sub(session.active_at) >= SYNC_TIMEOUT.as_millis() as u64 {
+            let peer = session.peer.clone();
+            self.session = None;
+            return Err(BlockSyncError::TimedOut { peer });
+        }
+        if now.saturating_sub(session.requested_at) < REQUEST_RETRY.as_millis() as u64 {
+            return Ok(Vec::new());
+        }
+        
+        session.requested_at = now;
+        let requests = match &session.stage {
+            Stage::Locating { locator } => vec![NetworkMessage::GetBranchHeaders(locator.clone())],
+            Stage::Headers { from } => vec![headers_request(*from, session.height)],
+            Stage::Blocks { hashes, received, .. } => hashes
+                .iter()
+                .filter(|hash| !received.contains_key(*hash))
+                .map(|hash| NetworkMessage::GetBlock(*hash))
+                .collect(),
+        };
+        Ok(requests.into_iter().map(|request| (session.peer.clone(), request)).collect())
+    }
+    
+    /// Ends the sync with a peer, as when it disconnects
+    pub fn remove_peer(&mut self, peer: &str) {
+        if self.peer() == Some(peer) {
+            self.session = None;
+        }
+    }
+    
+    /// Handles headers from a peer, returning the requests to send it next
+    ///
+    /// `local_hash` gets the hash of the node's block at a height. Headers
+    /// from peers not being synced with are ignored. The first headers must
+    /// start at a block of the locator, or be none if the peer's chain has
+    /// none of them. Once the fork is found, the blocks above it are
+    /// requested; if the peer has none the node lacks, the sync ends. Errors
+    /// end the sync.
+    pub fn handle_headers(
+        &mut self,
+        peer: &str,
+        headers: &[BlockHeader],
+        local_hash: impl Fn(u64) -> Option<BlockHash>,
+        now: u64,
+    ) -> Result<Vec<NetworkMessage>> {
+        let Some(session) = self.session.as_mut().filter(|session| session.peer == peer) else {
+            return Ok(Vec::new());
+        };
+        let from = match &session.stage {
+            Stage::Locating { locator } => {
+                let Some(first) = headers.first() else {
+                    let floor = locator.last().map_or(0, |(height, _)| *height);
+                    self.session = None;
+                    return Err(BlockSyncError::Unrelated { peer: peer.to_string(), floor });
+                };
+                let located = hashing::hash_block_header(first).is_ok_and(|hash| locator.contains(&(first.height, hash)));
+                if !located {
+                    self.session = None;
+                    return Err(BlockSyncError::Misbehaved { peer: peer.to_string(), reason: "headers off the locator".to_string() });
+                }
+                first.height
+            }
+            Stage::Headers { from } => *from,
+            Stage::Blocks { .. } => return Ok(Vec::new()),
+        };
+        let target = session.height;
+        
+        let fork = match find_fork(peer, from, headers, &local_hash) {
+            Ok(fork) => fork,
+            Err(e) => {
+                self.session = None;
+                return Err(e);
+            }
+        };
+        
+        match fork {
+            // Everything the peer sent is already in the chain; ask for more if it capped the reply
+            None => {
+                let next = from + headers.len() as u64;
+                if headers.len() as u64 == MAX_HEADERS && next <= target {
+                    session.stage = Stage::Headers { from: next };
+                    session.active_at = now;
+                    session.requested_at = now;
+                    Ok(vec![headers_request(next, target)])
+                } else {
+                    self.session = None;
+                    Ok(Vec::new())
+                }
+            }
+            Some((fork_height, hashes)) => {
+                let requests = hashes.iter().map(|hash| NetworkMessage::GetBlock(*hash)).collect();
+                session.stage = Stage::Blocks { fork_height, hashes, received: HashMap::new() };
+                session.active_at = now;
+                session.requested_at = now;
+                Ok(requests)
+            }
+        }
+    }
+    
+    /// Handles a block from a peer, returning the branch once all its blocks have arrived
+    ///
+    /// Blocks that weren't requested are ignored.
+    pub fn handle_block(&mut self, peer: &str, block: Arc<Block>, now: u64) -> Option<SyncedBranch> {
+        let session = self.session.as_mut().filter(|session| session.peer == peer)?;
+        let Stage::Blocks { hashes, received, .. } = &mut session.stage else {
+            return None;
+        };
+        let hash = block.hash().ok()?;
+        if !hashes.contains(&hash) {
+            return None;
+        }
+        received.insert(hash, block);
+        session.active_at = now;
+        if received.len() < hashes.len() {
+            return None;
+        }
+        
+        let session = self.session.take()?;
+        let Stage::Blocks { fork_height, hashes, mut received } = session.stage else {
+            return None;
+        };
+        let blocks = hashes.iter().filter_map(|hash| received.remove(hash)).collect();
+        Some(SyncedBranch { peer: session.peer, fork_height, blocks })
+    }
+}
+
+/// Makes a block locator of a chain whose tip is at `tip`, naming no block below `floor`
+///
+/// Names the blocks at the tip, then one, two, four and so on below it,
+/// down to `floor`, which is always named: a peer's chain forking off
+/// anywhere above the floor shares a block of the locator at most as far
+/// below the fork as the fork is below the tip. `hash_at` gets the hash of
+/// the chain's block at a height. At most `MAX_LOCATOR` blocks are named.
+pub fn locator(tip: u64, floor: u64, hash_at: impl Fn(u64) -> Option<BlockHash>) -> Vec<(u64, BlockHash)> {
+    let mut heights = Vec::new();
+    let mut offset = 0u64;
+    while tip.saturating_sub(offset) > floor && heights.len() < MAX_LOCATOR - 1 {
+        heights.push(tip - offset);
+        offset = if offset == 0 { 1 } else { offset.saturating_mul(2) };
+    }
+    heights.push(floor.min(tip));
+    heights.into_iter().filter_map(|height| hash_at(height).map(|hash| (height, hash))).collect()
+}
+
+/// Makes the request for the headers from `from` up to `to`, capped at `MAX_HEADERS`
+///
+/// `to` is the height a peer announced, so it may be as high as `u64::MAX`.
+fn headers_request(from: u64, to: u64) -> NetworkMessage {
+    NetworkMessage::GetHeaders(from..to.saturating_add(1).min(from.saturating_add(MAX_HEADERS)))
+}
+
+/// Finds where a peer's headers, starting at `from`, fork off the local chain
+///
+/// Returns the height of the last shared block and the hashes of the
+/// peer's blocks above it, or `None` if all of them are shared.
+fn find_fork(
+    peer: &str,
+    from: u64,
+    headers: &[BlockHeader],
+    local_hash: &impl Fn(u64) -> Option<BlockHash>,
+) -> Result<Option<(u64, Vec<BlockHash>)>> {
+    let misbehaved = |reason: &str| BlockSyncError::Misbehaved { peer: peer.to_string(), reason: reason.to_string() };
+    if headers.is_empty() {
+        return Err(misbehaved("no headers"));
+    }
+    
+    let mut hashes = Vec::with_capacity(headers.len());
+    for (expected_height, header) in (from..).zip(headers) {
+        if header.height != expected_height {
+            return Err(misbehaved("headers out of order"));
+        }
+        if let Some(previous) = hashes.last() {
+            if header.prev_hash != *previous {
+                return Err(misbehaved("headers that don't chain up"));
+            }
+        }
+        let hash = hashing::hash_block_header(header).map_err(|_| misbehaved("an unhashable header"))?;
+        hashes.push(hash);
+    }
+    
+    let Some(diverged) = (0..hashes.len()).find(|&i| local_hash(from + i as u64) != Some(hashes[i])) else {
+        return Ok(None);
+    };
+    // Headers after the first ones start within the chain, so they must at least build on it
+    if diverged == 0 && (from == 0 || local_hash(from - 1) != Some(headers[0].prev_hash)) {
+        return Err(misbehaved("headers that don't build on the chain"));
+    }
+    Ok(Some((from + diverged as u64 - 1, hashes.split_off(diverged))))
+}
\ No newline at end of file
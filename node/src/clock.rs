@@ -0,0 +1,32 @@
+//! The time a node goes by
+//!
+//! Block production and the protocol's timeouts read the time from a
+//! `Clock` rather than the system, so a simulation (see `sim`) can run
+//! nodes on a virtual time of its own.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Gets the current time in milliseconds since the Unix epoch
+    fn now_millis(&self) -> u64;
+    
+    /// Gets the current time as a Unix timestamp in seconds
+    fn now(&self) -> u64 {
+        self.now_millis() / 1000
+    }
+}
+
+/// The system's clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_millis() as u64
+    }
+}
\ No newline at end of file
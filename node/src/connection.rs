@@ -0,0 +1,334 @@
+//! Lifecycle of a connection to a peer
+//!
+//! Each connection is a `Connection`, moving through the states of
+//! `ConnectionState`:
+//!
+//! - `Connected` once it's made, then `HandshakeSent` once the node's
+//!   handshake is on its way. The peer's handshake must arrive within
+//!   `HANDSHAKE_TIMEOUT` of connecting.
+//! - `Established` once it does. The node pings the peer, and something,
+//!   the pong if nothing else, must arrive within `FIRST_MESSAGE_TIMEOUT`.
+//! - `Active` once it does, or `Syncing` while headers or blocks asked of
+//!   the peer are outstanding.
+//! - `Closing` once the node gives up on the connection, freeing its slot.
+//!
+//! Requests a peer must answer, such as `GetHeaders`, are tracked until
+//! the answer arrives, each with a deadline for its kind (see
+//! `response_deadline`); those a peer may rightly leave unanswered, such as
+//! for a transaction it doesn't have, aren't. Each request left unanswered
+//! past its deadline costs the peer `TIMEOUT_PENALTY` of its score and each
+//! answered one wins a point back, up to 0. A peer whose score falls to
+//! `MIN_SCORE` is disconnected.
+//!
+//! Like `block_sync`, a connection keeps time only by the milliseconds it's
+//! given, read from the node's clock (see `clock`), so simulations run it
+//! on virtual time. Every transition is logged with its reason.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::NetworkMessage;
+
+/// How long after connecting the peer's handshake may take to arrive
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long after the handshake the peer's first message may take to arrive
+pub const FIRST_MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Score a peer loses for each request it leaves unanswered past its deadline
+pub const TIMEOUT_PENALTY: i32 = 10;
+
+/// Score at which a peer is disconnected
+pub const MIN_SCORE: i32 = -50;
+
+/// Where a connection is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Connected, the node's handshake not sent yet
+    Connected,
+    
+    /// Waiting for the peer's handshake
+    HandshakeSent,
+    
+    /// Handshaken, waiting for the peer's first message
+    Established,
+    
+    /// Waiting for headers or blocks asked of the peer
+    Syncing,
+    
+    /// Exchanging messages
+    Active,
+    
+    /// Given up on, its slot free
+    Closing,
+}
+
+impl ConnectionState {
+    /// Gets the name of the state, as logged
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Connected => "connected",
+            Self::HandshakeSent => "handshake_sent",
+            Self::Established => "established",
+            Self::Syncing => "syncing",
+            Self::Active => "active",
+            Self::Closing => "closing",
+        }
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a peer failed to send in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// Its handshake, within `HANDSHAKE_TIMEOUT` of connecting
+    Handshake,
+    
+    /// Its first message, within `FIRST_MESSAGE_TIMEOUT` of the handshake
+    FirstMessage,
+    
+    /// The answer to a request, by its deadline
+    Request { request: &'static str },
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Handshake => write!(f, "no handshake in {:?}", HANDSHAKE_TIMEOUT),
+            Self::FirstMessage => write!(f, "no message in {:?} after the handshake", FIRST_MESSAGE_TIMEOUT),
+            Self::Request { request } => write!(f, "{} unanswered", request),
+        }
+    }
+}
+
+/// Gets the kind of message answering a request and how long the peer has to send it
+///
+/// `None` for messages that aren't requests, and for requests a peer may
+/// leave unanswered, such as for a block, transaction or receipts it doesn't
+/// have. Blocks are asked for once headers name them, so those are answered.
+pub fn response_deadline(request: &NetworkMessage) -> Option<(&'static str, Duration)> {
+    match request {
+        NetworkMessage::Ping(_) => Some(("pong", Duration::from_secs(10))),
+        NetworkMessage::GetPeers => Some(("addresses", Duration::from_secs(10))),
+        NetworkMessage::GetHeaders(_) | NetworkMessage::GetBranchHeaders(_) => Some(("headers", Duration::from_secs(10))),
+        NetworkMessage::GetBlock(_) => Some(("block", Duration::from_secs(20))),
+        NetworkMessage::GetFilterHeaders(_) => Some(("filter headers", Duration::from_secs(10))),
+        NetworkMessage::GetFilters(_) => Some(("filters", Duration::from_secs(20))),
+        _ => None,
+    }
+}
+
+/// Checks whether a request is part of syncing with the peer
+fn is_sync_request(request: &'static str) -> bool {
+    matches!(request, "get headers" | "get branch headers" | "get block")
+}
+
+/// A request awaiting its answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRequest {
+    /// Kind of the request
+    pub request: &'static str,
+    
+    /// Kind of the message answering it
+    pub response: &'static str,
+    
+    /// When the answer is due, in milliseconds
+    pub deadline: u64,
+}
+
+/// A connection to a peer, see the module documentation
+#[derive(Debug, Clone)]
+pub struct Connection {
+    /// Address of the other end
+    address: SocketAddr,
+    
+    /// Whether this node dialed the connection
+    outbound: bool,
+    
+    /// Node ID the peer's handshake gave, once it arrived
+    node_id: Option<String>,
+    
+    state: ConnectionState,
+    
+    /// When the connection entered its state, in milliseconds
+    entered_at: u64,
+    
+    /// Requests awaiting their answers, oldest first
+    pending: VecDeque<PendingRequest>,
+    
+    /// Score of the peer, 0 at best
+    score: i32,
+}
+
+impl Connection {
+    /// Creates a connection just made at `now`, in milliseconds
+    pub fn new(address: SocketAddr, outbound: bool, now: u64) -> Self {
+        let connection = Self {
+            address,
+            outbound,
+            node_id: None,
+            state: ConnectionState::Connected,
+            entered_at: now,
+            pending: VecDeque::new(),
+            score: 0,
+        };
+        println!(
+            "Connection to {} is {}: {}",
+            address,
+            connection.state,
+            if outbound { "dialed" } else { "accepted" }
+        );
+        connection
+    }
+    
+    /// Gets the address of the other end
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+    
+    /// Checks whether this node dialed the connection
+    pub fn is_outbound(&self) -> bool {
+        self.outbound
+    }
+    
+    /// Gets the node ID of the peer, once its handshake arrived
+    pub fn node_id(&self) -> Option<&str> {
+        self.node_id.as_deref()
+    }
+    
+    /// Gets where the connection is in its lifecycle
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+    
+    /// Gets the score of the peer, 0 at best
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+    
+    /// Gets the requests awaiting their answers, oldest first
+    pub fn pending_requests(&self) -> impl Iterator<Item = &PendingRequest> + '_ {
+        self.pending.iter()
+    }
+    
+    /// Notes that the node's handshake was sent
+    pub fn handshake_sent(&mut self, now: u64) {
+        if self.state == ConnectionState::Connected {
+            self.transition(ConnectionState::HandshakeSent, now, "handshake sent");
+        }
+    }
+    
+    /// Notes that the peer's handshake arrived, giving its node ID
+    pub fn handshake_received(&mut self, node_id: &str, now: u64) {
+        if matches!(self.state, ConnectionState::Connected | ConnectionState::HandshakeSent) {
+            self.node_id = Some(node_id.to_string());
+            self.transition(ConnectionState::Established, now, "handshake received");
+        }
+    }
+    
+    /// Notes that a message was sent to the peer, tracking it if it's a request the peer must answer
+    pub fn message_sent(&mut self, message: &NetworkMessage, now: u64) {
+        if self.state == ConnectionState::Closing {
+            return;
+        }
+        let Some((response, deadline)) = response_deadline(message) else {
+            return;
+        };
+        let request = message.kind();
+        self.pending.push_back(PendingRequest { request, response, deadline: now + deadline.as_millis() as u64 });
+        if is_sync_request(request) && matches!(self.state, ConnectionState::Established | ConnectionState::Active) {
+            self.transition(ConnectionState::Syncing, now, request);
+        }
+    }
+    
+    /// Notes that a message arrived from the peer, answering its oldest request of the kind if any
+    pub fn message_received(&mut self, message: &NetworkMessage, now: u64) {
+        let kind = message.kind();
+        if let Some(index) = self.pending.iter().position(|pending| pending.response == kind) {
+            self.pending.remove(index);
+            self.score = (self.score + 1).min(0);
+        }
+        
+        match self.state {
+            ConnectionState::Established => self.transition(ConnectionState::Active, now, kind),
+            ConnectionState::Syncing if !self.syncing() => self.transition(ConnectionState::Active, now, kind),
+            _ => {}
+        }
+    }
+    
+    /// Gives up on the connection
+    pub fn close(&mut self, now: u64, reason: &str) {
+        if self.state != ConnectionState::Closing {
+            self.pending.clear();
+            self.transition(ConnectionState::Closing, now, reason);
+        }
+    }
+    
+    /// Checks the connection's timeouts at `now`, returning those that ran out
+    ///
+    /// Requests past their deadline are dropped, each costing the peer
+    /// `TIMEOUT_PENALTY`. Whether the connection should then be closed is
+    /// up to `should_close`.
+    pub fn poll(&mut self, now: u64) -> Vec<Timeout> {
+        let waited = now.saturating_sub(self.entered_at);
+        let state_timeout = match self.state {
+            ConnectionState::Connected | ConnectionState::HandshakeSent
+                if waited >= HANDSHAKE_TIMEOUT.as_millis() as u64 => Some(Timeout::Handshake),
+            ConnectionState::Established if waited >= FIRST_MESSAGE_TIMEOUT.as_millis() as u64 => {
+                Some(Timeout::FirstMessage)
+            }
+            _ => None,
+        };
+        
+        let mut timeouts: Vec<Timeout> = state_timeout.into_iter().collect();
+        let (expired, pending): (VecDeque<_>, VecDeque<_>) = self.pending.drain(..).partition(|pending| pending.deadline <= now);
+        self.pending = pending;
+        for pending in expired {
+            self.score = self.score.saturating_sub(TIMEOUT_PENALTY);
+            timeouts.push(Timeout::Request { request: pending.request });
+        }
+        if self.state == ConnectionState::Syncing && !self.syncing() {
+            self.transition(ConnectionState::Active, now, "sync requests timed out");
+        }
+        timeouts
+    }
+    
+    /// Checks whether the connection should be closed after timeouts, giving the reason
+    pub fn should_close(&self, timeouts: &[Timeout]) -> Option<String> {
+        if let Some(timeout) = timeouts.iter().find(|timeout| !matches!(timeout, Timeout::Request { .. })) {
+            return Some(timeout.to_string());
+        }
+        if self.score <= MIN_SCORE {
+            return Some(format!("score {} after requests timed out", self.score));
+        }
+        None
+    }
+    
+    /// Checks whether headers or blocks asked of the peer are outstanding
+    fn syncing(&self) -> bool {
+        self.pending.iter().any(|pending| is_sync_request(pending.request))
+    }
+    
+    fn transition(&mut self, state: ConnectionState, now: u64, reason: &str) {
+        println!(
+            "Connection to {} ({}) {} -> {}: {}",
+            self.address,
+            self.node_id.as_deref().unwrap_or("unknown"),
+            self.state,
+            state,
+            reason
+        );
+        self.state = state;
+        self.entered_at = now;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,234 @@
+//! Development network: a chain of the node's own, with blocks made on demand
+//!
+//! A node with `dev_mode` set doesn't join a network. It runs a chain of its
+//! own from a fixed genesis block, for developing and testing contracts and
+//! tools against, and makes a block whenever asked rather than when the
+//! clock says:
+//!
+//! - `genesis` funds `DEV_ACCOUNTS` accounts with `DEV_ACCOUNT_BALANCE`
+//!   each, besides the usual allocations, and registers a single validator
+//!   with the minimum stake. Their keys are derived from fixed seeds and
+//!   published by `accounts` and `dev_accounts`, so they're the same for
+//!   everyone: never send real funds to them.
+//! - The node produces every block for that validator, signed with its
+//!   key (see `signer`), one slot after its parent, so block `n` is stamped `DEV_GENESIS_TIME` plus `n` block
+//!   times. Blocks, and the transactions' receipts, are then the same
+//!   every run given the same transactions.
+//! - With automining on, as it is at first, a block is made as soon as a
+//!   transaction is admitted, so it's included by the time its submission
+//!   returns. Otherwise blocks are only made by `dev_mine` or `evm_mine`.
+//! - Nothing is saved to the data directory, and peers are neither dialed
+//!   nor accepted.
+//!
+//! The development methods are served alongside the node's other JSON-RPC
+//! methods (see `rpc`):
+//!
+//! | Method            | Params                                  | Result                                        |
+//! |-------------------|-----------------------------------------|-----------------------------------------------|
+//! | `dev_mine`        | optional number of blocks, 1 if none    | hashes of the blocks made, oldest first       |
+//! | `evm_mine`        | none                                    | `"0x0"`, as Ethereum development tools expect |
+//! | `dev_setBalance`  | address, balance in base units          | the previous balance                          |
+//! | `dev_setAutomine` | whether to make a block per transaction | whether automining was on                     |
+//! | `dev_accounts`    | none                                    | `DevAccount`s, funded ones in order           |
+//!
+//! `dev_setBalance` changes the state outside of any block, see
+//! `Blockchain::override_state`; the total supply follows.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use ctb_core::block::Block;
+use ctb_core::chain::Blockchain;
+use ctb_core::genesis;
+use ctb_core::hashing;
+use ctb_core::signature::{self, SignatureScheme};
+use ctb_core::transaction::Transaction;
+use ctb_core::units::{Amount, GENX};
+use ctb_core::validator::ValidatorRegistration;
+use ctb_core::{Address, BlockchainError, Result};
+
+use consensus::signer::LocalSigner;
+use consensus::ConsensusParams;
+
+use crate::eth::{self, EthError};
+use crate::protocol::Protocol;
+use crate::rpc;
+
+/// Timestamp of the development genesis block, 2024-01-01 00:00:00 UTC
+pub const DEV_GENESIS_TIME: u64 = 1_704_067_200;
+
+/// Number of funded development accounts
+pub const DEV_ACCOUNTS: usize = 10;
+
+/// Balance each development account starts with, in base units
+pub const DEV_ACCOUNT_BALANCE: u64 = 10_000 * GENX;
+
+/// Most blocks a single `dev_mine` makes
+pub const MAX_MINED_BLOCKS: u64 = 1000;
+
+/// Seed the development validator's key is derived from
+const VALIDATOR_SEED: &str = "genx-dev-validator";
+
+/// A development account, whose key anyone can derive
+#[derive(Debug, Clone, Serialize)]
+pub struct DevAccount {
+    /// Address of the account's key
+    pub address: Address,
+    
+    /// Ed25519 secret key, in hex
+    pub secret_key: String,
+}
+
+impl DevAccount {
+    /// Derives the account of a seed
+    fn derive(seed: &str) -> Self {
+        let secret_key = hashing::blake3(seed.as_bytes());
+        let address = signature::address_of(SignatureScheme::Ed25519, &secret_key)
+            .ok()
+            .and_then(|address| Address::new(address).ok())
+            .expect("Ed25519 keys derive well formed addresses");
+        Self { address, secret_key: hex::encode(secret_key) }
+    }
+    
+    /// Signs a transaction sent by the account
+    pub fn sign(&self, tx: &mut Transaction) -> Result<()> {
+        let secret_key = hex::decode(&self.secret_key).expect("secret keys are hex");
+        tx.sign(&secret_key)
+    }
+}
+
+/// Gets the funded development accounts, in order
+pub fn accounts() -> Vec<DevAccount> {
+    (0..DEV_ACCOUNTS).map(|index| DevAccount::derive(&format!("genx-dev-account-{}", index))).collect()
+}
+
+/// Gets the development validator, which produces every block
+pub fn validator() -> DevAccount {
+    DevAccount::derive(VALIDATOR_SEED)
+}
+
+/// Gets a signer with the development validator's key, which the chain needs its blocks signed with
+pub fn signer() -> LocalSigner {
+    LocalSigner::from_hex(SignatureScheme::Ed25519, &validator().secret_key).expect("the development validator's key is an Ed25519 key")
+}
+
+/// Creates the development chain, holding only its genesis block
+///
+/// The validator is staked with `params.min_stake`, which no transaction
+/// records, so it's staked again whenever the chain is created.
+pub fn genesis(params: &ConsensusParams) -> Result<Blockchain> {
+    let mut transactions = Vec::new();
+    let allocations = genesis::create_genesis_block()?.transactions;
+    let funding = accounts()
+        .into_iter()
+        .map(|account| Transaction::new_coinbase(account.address.to_string(), DEV_ACCOUNT_BALANCE))
+        .collect::<Result<Vec<_>>>()?;
+    for tx in allocations.into_iter().chain(funding) {
+        transactions.push(stamp(tx)?);
+    }
+    
+    let validator = validator();
+    let registration = ValidatorRegistration {
+        moniker: "dev".to_string(),
+        website: String::new(),
+        commission_rate: 0,
+        consensus_key: validator.address.to_string(),
+        payout_address: None,
+    };
+    let mut registration = stamp(Transaction::new_register_validator(validator.address.to_string(), &registration, 0)?)?;
+    validator.sign(&mut registration)?;
+    transactions.push(registration);
+    
+    let mut block = Block::genesis(transactions, genesis::get_initial_base_fee())?;
+    block.header_mut().timestamp = DEV_GENESIS_TIME;
+    let mut blockchain = Blockchain::new(block)?;
+    blockchain.override_state(|state| state.update_validator_stake(validator.address, Amount::from_base_units(params.min_stake)));
+    Ok(blockchain)
+}
+
+/// Stamps a genesis transaction with the genesis time
+fn stamp(mut tx: Transaction) -> Result<Transaction> {
+    tx.timestamp = DEV_GENESIS_TIME;
+    tx.id = tx.calculate_hash()?;
+    Ok(tx)
+}
+
+/// Makes blocks on demand and serves the development methods
+#[derive(Clone)]
+pub struct DevApi {
+    protocol: Protocol,
+    params: ConsensusParams,
+    
+    /// Whether a block is made for each transaction admitted
+    automine: Arc<AtomicBool>,
+}
+
+impl DevApi {
+    /// Creates a handler over a node's protocol, automining
+    pub(crate) fn new(protocol: Protocol, params: ConsensusParams) -> Self {
+        Self { protocol, params, automine: Arc::new(AtomicBool::new(true)) }
+    }
+    
+    /// Makes a block, one slot after the latest, with the transactions waiting
+    pub fn mine_block(&self) -> Result<Arc<Block>> {
+        let time = {
+            let blockchain = self.protocol.blockchain.lock().unwrap();
+            let parent_time = blockchain.get_latest_block().map_or(DEV_GENESIS_TIME, |parent| parent.header().timestamp);
+            parent_time + self.params.slot_clock(&blockchain).slot_duration()
+        };
+        self.protocol
+            .produce_block_at(time)?
+            .ok_or_else(|| BlockchainError::StateError("The node isn't validating".to_string()))
+    }
+    
+    /// Makes a block if automining is on, after a transaction was admitted
+    pub fn transaction_added(&self) {
+        if !self.automine.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = self.mine_block() {
+            eprintln!("Failed to mine a block for the transaction added: {}", e);
+        }
+    }
+    
+    /// Runs a development method with its positional parameters
+    pub fn call(&self, method: &str, params: &[Value]) -> eth::Result<Value> {
+        match method {
+            "dev_mine" => {
+                let count = rpc::param_u64(params, 0, "number of blocks")?.unwrap_or(1);
+                if count > MAX_MINED_BLOCKS {
+                    return Err(EthError::InvalidParams(format!("at most {} blocks are mined at once", MAX_MINED_BLOCKS)));
+                }
+                let hashes = (0..count).map(|_| self.mine_block()?.hash()).collect::<Result<Vec<_>>>()?;
+                Ok(json!(hashes))
+            }
+            "evm_mine" => {
+                self.mine_block()?;
+                Ok(json!("0x0"))
+            }
+            "dev_setBalance" => {
+                let address = eth::param_address(params, 0)?;
+                let balance = rpc::param_u64(params, 1, "balance")?.ok_or_else(|| EthError::InvalidParams("missing balance".to_string()))?;
+                let previous = self.protocol.blockchain.lock().unwrap().override_state(|state| {
+                    let previous = state.get_balance(&address);
+                    state.override_balance(&address, Amount::from_base_units(balance));
+                    previous
+                });
+                println!("Dev: set the balance of {} to {}", address, balance);
+                Ok(json!(previous))
+            }
+            "dev_setAutomine" => {
+                let automine = params
+                    .first()
+                    .and_then(Value::as_bool)
+                    .ok_or_else(|| EthError::InvalidParams("missing whether to automine".to_string()))?;
+                Ok(Value::Bool(self.automine.swap(automine, Ordering::SeqCst)))
+            }
+            "dev_accounts" => Ok(json!(accounts())),
+            method => Err(EthError::MethodNotFound(method.to_string())),
+        }
+    }
+}
\ No newline at end of file
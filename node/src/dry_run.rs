@@ -0,0 +1,304 @@
+//! Checking whether the node would accept a block, without adding it
+//!
+//! Validators and external block builders ask the node, before they
+//! broadcast a block, whether it would accept it. `BlockValidator` runs the
+//! checks `Blockchain::add_block` does, and those of the consensus engine,
+//! against the chain as it is, and reports every check with whether it
+//! passed rather than stopping at the first failure:
+//!
+//! | Check           | Passes when                                                         |
+//! |-----------------|---------------------------------------------------------------------|
+//! | `structure`     | the transactions are valid and match the merkle root                |
+//! | `parent`        | the block extends the latest block                                  |
+//! | `proposer`      | the validator is the one scheduled for the slot of the timestamp    |
+//! | `signature`     | the block's signature, if any, verifies with the consensus key      |
+//! | `base_fee`      | the base fee is the one the fee market sets                         |
+//! | `reward`        | the coinbase mints the reward and pays it where the rules say       |
+//! | `ordering`      | the transactions are in canonical order, where that's required      |
+//! | `validator_set` | the block commits to the validator set it must, if any              |
+//! | `execution`     | every transaction applies to the state                              |
+//! | `gas`           | the transactions use no more than the block gas limit               |
+//! | `state_root`    | the resulting state has the root given to compare with              |
+//!
+//! A node whose consensus engine has no active validators yet can't tell
+//! who is scheduled, and skips the proposer check. A block must be signed
+//! by the registered consensus key of its validator, over the hash of its
+//! header without the signature, as the chain requires; until a validator
+//! registers there's no key to check, and the signature check is skipped.
+//! Headers don't commit to state roots (see `ctb_core::state_sync`), so the
+//! state root check only runs when the caller gives the root it expects;
+//! the report always gives the root the block would leave.
+//!
+//! The block is applied to a copy of the state: neither the chain, its
+//! state, the mempool nor the verified transaction cache change. Served as
+//! `debug_validateBlock` (see `rpc`), which needs authorization when the
+//! node has an `auth_token` (see `access`).
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::block::Block;
+use ctb_core::chain::Blockchain;
+use ctb_core::ordering;
+use ctb_core::state_sync;
+use ctb_core::{BlockHash, Hash};
+
+use consensus::ConsensusEngine;
+
+/// Rule a block is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockCheck {
+    Structure,
+    Parent,
+    Proposer,
+    Signature,
+    BaseFee,
+    Reward,
+    Ordering,
+    ValidatorSet,
+    Execution,
+    Gas,
+    StateRoot,
+}
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    
+    /// Not run, because it doesn't apply to the block or a check it needs failed
+    Skipped,
+}
+
+/// Outcome of one check, with what was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub check: BlockCheck,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Outcome of checking a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Height of the block
+    pub height: u64,
+    
+    /// Hash of the block, if it could be hashed
+    pub block_hash: Option<BlockHash>,
+    
+    /// Whether no check failed, so the block would be added
+    pub accepted: bool,
+    
+    /// Every check, in the order run
+    pub checks: Vec<CheckResult>,
+    
+    /// Gas the transactions used, if they applied
+    pub gas_used: Option<u64>,
+    
+    /// Root of the state the block would leave, if its transactions applied
+    #[serde(with = "ctb_core::types::hex_serde::option")]
+    pub state_root: Option<Hash>,
+}
+
+impl ValidationReport {
+    /// Gets the outcome of a check
+    pub fn check(&self, check: BlockCheck) -> Option<&CheckResult> {
+        self.checks.iter().find(|result| result.check == check)
+    }
+    
+    /// Gets the checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|result| result.status == CheckStatus::Failed)
+    }
+    
+    fn record(&mut self, check: BlockCheck, outcome: Result<String, String>) {
+        let (status, detail) = match outcome {
+            Ok(detail) => (CheckStatus::Passed, detail),
+            Err(detail) => (CheckStatus::Failed, detail),
+        };
+        self.accepted &= status != CheckStatus::Failed;
+        self.checks.push(CheckResult { check, status, detail });
+    }
+    
+    fn skip(&mut self, check: BlockCheck, detail: impl Into<String>) {
+        self.checks.push(CheckResult { check, status: CheckStatus::Skipped, detail: detail.into() });
+    }
+}
+
+/// Runs a node's checks on blocks without adding them
+#[derive(Clone)]
+pub struct BlockValidator {
+    blockchain: Arc<Mutex<Blockchain>>,
+    consensus: Arc<Mutex<ConsensusEngine>>,
+}
+
+impl BlockValidator {
+    /// Creates a validator checking blocks against a node's chain and consensus engine
+    pub(crate) fn new(blockchain: Arc<Mutex<Blockchain>>, consensus: Arc<Mutex<ConsensusEngine>>) -> Self {
+        Self { blockchain, consensus }
+    }
+    
+    /// Checks a block against the chain as it is, comparing the state it leaves with `expected_state_root` if given
+    pub fn validate(&self, block: &Block, expected_state_root: Option<Hash>) -> ValidationReport {
+        let header = block.header();
+        let mut report = ValidationReport {
+            height: header.height,
+            block_hash: block.hash().ok(),
+            accepted: true,
+            checks: Vec::new(),
+            gas_used: None,
+            state_root: None,
+        };
+        
+        // The consensus engine locks the chain, so it's asked first
+        let scheduled = {
+            let consensus = self.consensus.lock().unwrap();
+            let slot = consensus.slot_clock().slot_at(header.timestamp);
+            (!consensus.active_validators().is_empty())
+                .then(|| consensus.proposer_for_slot(slot).map(|proposer| (slot, proposer.address.clone())))
+        };
+        
+        let blockchain = self.blockchain.lock().unwrap();
+        let structure = block.validate();
+        report.record(
+            BlockCheck::Structure,
+            structure.as_ref().map(|()| format!("{} transactions", block.transactions.len())).map_err(|e| e.to_string()),
+        );
+        
+        let (latest_height, latest_hash) = (blockchain.get_latest_height(), blockchain.get_latest_hash());
+        report.record(
+            BlockCheck::Parent,
+            if header.height != latest_height + 1 {
+                Err(format!("Height {} doesn't follow the latest block's, {}", header.height, latest_height))
+            } else if header.prev_hash != latest_hash {
+                Err(format!("Parent {} isn't the latest block, {}", header.prev_hash, latest_hash))
+            } else {
+                Ok(format!("Extends block {}", latest_hash))
+            },
+        );
+        
+        match scheduled {
+            None => report.skip(BlockCheck::Proposer, "No active validators to schedule proposers from"),
+            Some(scheduled) => report.record(
+                BlockCheck::Proposer,
+                match scheduled {
+                    Ok((slot, proposer)) if proposer == header.validator => Ok(format!("Scheduled for slot {}", slot)),
+                    Ok((slot, proposer)) => Err(format!("{} is scheduled for slot {}, not {}", proposer, slot, header.validator)),
+                    Err(e) => Err(e.to_string()),
+                },
+            ),
+        }
+        
+        let (consensus_key, any_registered, payout_address) = {
+            let state = blockchain.get_state();
+            let state = state.lock().unwrap();
+            (
+                state.get_validator(&header.validator).map(|info| info.consensus_key.clone()),
+                !state.get_validators().is_empty(),
+                state.get_payout_address(&header.validator, header.height),
+            )
+        };
+        match consensus_key {
+            None if !any_registered => report.skip(BlockCheck::Signature, "No validator is registered yet"),
+            None => report.record(BlockCheck::Signature, Err(format!("{} isn't a registered validator", header.validator))),
+            Some(key) => report.record(
+                BlockCheck::Signature,
+                header.verify_signature(&key).map_err(|e| e.to_string()).map(|()| format!("Signed by {}", key)),
+            ),
+        }
+        
+        let base_fee = blockchain.next_base_fee();
+        report.record(
+            BlockCheck::BaseFee,
+            if header.base_fee == base_fee {
+                Ok(format!("Base fee {}", base_fee))
+            } else {
+                Err(format!("Base fee {} instead of {}", header.base_fee, base_fee))
+            },
+        );
+        
+        report.record(
+            BlockCheck::Reward,
+            blockchain
+                .reward_schedule()
+                .check_block(block, &payout_address)
+                .map(|()| format!("Pays the validator at {}", payout_address))
+                .map_err(|e| e.to_string()),
+        );
+        
+        if blockchain.orders_transactions(header.height) {
+            report.record(
+                BlockCheck::Ordering,
+                ordering::check_order(&block.transactions).map(|()| "Canonical order".to_string()).map_err(|e| e.to_string()),
+            );
+        } else {
+            report.skip(BlockCheck::Ordering, "Canonical order isn't required at this height");
+        }
+        
+        let expected_set = blockchain.next_validator_set().map(|set| set.hash());
+        report.record(
+            BlockCheck::ValidatorSet,
+            match (expected_set, header.validator_set_hash) {
+                (None, None) => Ok("Commits to no validator set, as it mustn't".to_string()),
+                (Some(expected), Some(committed)) if expected == committed => {
+                    Ok(format!("Commits to validator set 0x{}", hex::encode(expected)))
+                }
+                (Some(expected), _) => Err(format!("Must commit to validator set 0x{}", hex::encode(expected))),
+                (None, Some(_)) => Err("Must not commit to a validator set".to_string()),
+            },
+        );
+        
+        if structure.is_err() {
+            report.skip(BlockCheck::Execution, "Block is malformed");
+            report.skip(BlockCheck::Gas, "Block is malformed");
+            report.skip(BlockCheck::StateRoot, "Block is malformed");
+            return report;
+        }
+        let (receipts, state_after) = match blockchain.apply_to_copy(block) {
+            Ok(applied) => applied,
+            Err(e) => {
+                report.record(BlockCheck::Execution, Err(e.to_string()));
+                report.skip(BlockCheck::Gas, "Transactions don't apply");
+                report.skip(BlockCheck::StateRoot, "Transactions don't apply");
+                return report;
+            }
+        };
+        let failed = receipts.iter().filter(|receipt| !receipt.success).count();
+        report.record(
+            BlockCheck::Execution,
+            Ok(format!("{} transactions applied, {} of them failed", receipts.len(), failed)),
+        );
+        
+        let gas_used = receipts.last().map_or(0, |receipt| receipt.cumulative_gas_used);
+        let gas_limit = blockchain.get_block_gas_limit();
+        report.gas_used = Some(gas_used);
+        report.record(
+            BlockCheck::Gas,
+            if gas_used <= gas_limit {
+                Ok(format!("{} gas of {}", gas_used, gas_limit))
+            } else {
+                Err(format!("{} gas exceeds the limit of {}", gas_used, gas_limit))
+            },
+        );
+        
+        let state_root = state_sync::state_root(&state_after);
+        report.state_root = Some(state_root);
+        match expected_state_root {
+            None => report.skip(BlockCheck::StateRoot, "No state root given to compare with"),
+            Some(expected) => report.record(
+                BlockCheck::StateRoot,
+                if expected == state_root {
+                    Ok(format!("State root 0x{}", hex::encode(state_root)))
+                } else {
+                    Err(format!("State root 0x{} instead of 0x{}", hex::encode(state_root), hex::encode(expected)))
+                },
+            ),
+        }
+        report
+    }
+}
\ No newline at end of file
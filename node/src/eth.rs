@@ -0,0 +1,782 @@
+//! Ethereum JSON-RPC compatibility layer
+//!
+//! Wallets, scripts and explorers written for Ethereum only speak its
+//! JSON-RPC dialect. `EthApi` answers a subset of the `eth_` namespace from
+//! the node's chain and contract state, translating between GENX values and
+//! the conventions those clients expect:
+//!
+//! - Addresses are 20-byte hex. Contracts map to their `GENX_CONTRACT_`
+//!   address and other hex addresses to the `0x` account of the same name,
+//!   which is where contract payments land. Named GENX accounts, such as
+//!   genesis allocations, only appear as the hash-derived EVM address of
+//!   `to_evm_address` and can't be looked up by it; pass the GENX address
+//!   itself instead, which every method accepts.
+//! - GENX amounts have 8 decimals and ether 18, so balances, values and gas
+//!   prices are reported in units of 10^-18 GENX. Values sent in must be
+//!   whole multiples of 10^10 of those units.
+//! - `eth_sendRawTransaction` takes a signed legacy or EIP-1559 Ethereum
+//!   transaction for this chain ID and imports it as described in
+//!   `ctb_core::eth_transaction`; the transaction hash it returns is the
+//!   Ethereum one. It also takes the hex of a JSON-serialized, signed GENX
+//!   transaction. The `r` and `s` fields of a transaction are the two halves
+//!   of an ed25519 signature with `v` 0, or the `r` and `s` of a secp256k1
+//!   signature with `v` its recovery ID.
+//! - A transaction's `nonce` is its GENX nonce, which for an imported
+//!   Ethereum transaction is the one it was signed with.
+//! - Only the latest state is kept, so state queries accept the `latest`
+//!   and `pending` tags or the current height, and nothing older.
+//! - Deployments estimated with `eth_estimateGas` carry a serialized
+//!   `DeployPayload` as their data, not bare init code.
+//!
+//! Unknown methods fail with the JSON-RPC "method not found" error and bad
+//! parameters with "invalid params"; reverts use code 3 with the revert
+//! data, as Ethereum clients do.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use ctb_core::block::Block;
+use ctb_core::chain::{Blockchain, SnapshotHandle};
+use ctb_core::eth_transaction::{EthTransaction, EthTransactionType, WEI_PER_UNIT};
+use ctb_core::receipt::{IndexedLog, Log, LogFilter, Receipt};
+use ctb_core::state::State;
+use ctb_core::transaction::{to_evm_address, Transaction, TransactionType, CONTRACT_ADDRESS_PREFIX};
+use ctb_core::units::DECIMALS;
+use ctb_core::verified::VerifiedTxCache;
+use ctb_core::{Address, BlockHash, BlockchainError, ErrorContext, Hash, TxHash};
+
+use consensus::ConsensusEngine;
+
+use smartcontracts::evm::ExecutionStatus;
+use smartcontracts::reader::ContractReader;
+use smartcontracts::tracer::NoopTracer;
+use smartcontracts::ContractError;
+
+use crate::policy::AdmissionPolicy;
+
+/// Chain ID reported unless configured otherwise, the ASCII bytes of `GENX`
+pub const DEFAULT_CHAIN_ID: u64 = smartcontracts::DEFAULT_CHAIN_ID;
+
+/// JSON-RPC error code of a request that isn't a valid request object
+pub const INVALID_REQUEST: i64 = -32600;
+
+/// JSON-RPC error code of an unknown or unsupported method
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code of malformed method parameters
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// Error code Ethereum nodes use for failures while serving a valid request
+pub const SERVER_ERROR: i64 = -32000;
+
+/// Error code Ethereum nodes use for calls that revert
+pub const EXECUTION_REVERTED: i64 = 3;
+
+/// Error code of a call to a privileged method without the node's authorization token
+pub const UNAUTHORIZED: i64 = -32001;
+
+/// Error code of a call over a rate limit, as EIP-1474 has it
+pub const LIMIT_EXCEEDED: i64 = -32005;
+
+/// Ethereum JSON-RPC error types
+#[derive(Debug, Error)]
+pub enum EthError {
+    #[error("invalid request")]
+    InvalidRequest,
+    
+    #[error("the method {0} does not exist/is not available")]
+    MethodNotFound(String),
+    
+    #[error("invalid params: {0}")]
+    InvalidParams(String),
+    
+    #[error("the method {0} needs authorization")]
+    Unauthorized(String),
+    
+    #[error("rate limit exceeded for {0}")]
+    LimitExceeded(String),
+    
+    #[error("execution reverted{}", .reason.as_deref().map(|reason| format!(": {}", reason)).unwrap_or_default())]
+    Reverted {
+        /// Decoded revert reason, if any
+        reason: Option<String>,
+        
+        /// Raw revert data
+        data: Vec<u8>,
+    },
+    
+    #[error("{0}")]
+    Server(String),
+    
+    #[error("{message}")]
+    Failed {
+        /// Stable numeric code of the underlying error, see `BlockchainError::error_code`
+        error_code: u32,
+        
+        /// Human-readable description
+        message: String,
+        
+        /// Where the underlying error happened, see `BlockchainError::context`
+        context: Option<Box<ErrorContext>>,
+    },
+}
+
+impl EthError {
+    /// Gets the JSON-RPC error code of the error
+    pub fn code(&self) -> i64 {
+        match self {
+            EthError::InvalidRequest => INVALID_REQUEST,
+            EthError::MethodNotFound(_) => METHOD_NOT_FOUND,
+            EthError::InvalidParams(_) => INVALID_PARAMS,
+            EthError::Unauthorized(_) => UNAUTHORIZED,
+            EthError::LimitExceeded(_) => LIMIT_EXCEEDED,
+            EthError::Reverted { .. } => EXECUTION_REVERTED,
+            EthError::Server(_) | EthError::Failed { .. } => SERVER_ERROR,
+        }
+    }
+    
+    /// Builds the error object of a JSON-RPC response
+    fn to_json(&self) -> Value {
+        match self {
+            EthError::Reverted { data, .. } => json!({ "code": self.code(), "message": self.to_string(), "data": bytes(data) }),
+            EthError::Failed { error_code, context, .. } => {
+                let mut data = json!({ "errorCode": error_code });
+                if let Some(context) = context {
+                    data["context"] = json!(context);
+                }
+                json!({ "code": self.code(), "message": self.to_string(), "data": data })
+            }
+            _ => json!({ "code": self.code(), "message": self.to_string() }),
+        }
+    }
+}
+
+impl From<ContractError> for EthError {
+    fn from(e: ContractError) -> Self {
+        match e {
+            ContractError::Reverted { reason, data } => EthError::Reverted { reason, data },
+            e => EthError::Failed { error_code: e.error_code(), message: e.to_string(), context: None },
+        }
+    }
+}
+
+impl From<BlockchainError> for EthError {
+    fn from(e: BlockchainError) -> Self {
+        EthError::Failed { error_code: e.error_code(), message: e.to_string(), context: e.context().cloned().map(Box::new) }
+    }
+}
+
+/// Result type for Ethereum JSON-RPC methods
+pub type Result<T> = std::result::Result<T, EthError>;
+
+/// Serves Ethereum JSON-RPC requests from a node's state
+pub struct EthApi {
+    blockchain: Arc<Mutex<Blockchain>>,
+    consensus: Arc<Mutex<ConsensusEngine>>,
+    contract_reader: ContractReader,
+    snapshots: SnapshotHandle,
+    chain_id: u64,
+    verified_txs: Arc<VerifiedTxCache>,
+    policy: Arc<AdmissionPolicy>,
+}
+
+impl EthApi {
+    /// Creates an API over a node's chain, transaction pool and contract state
+    pub(crate) fn new(
+        blockchain: Arc<Mutex<Blockchain>>,
+        consensus: Arc<Mutex<ConsensusEngine>>,
+        contract_reader: ContractReader,
+        snapshots: SnapshotHandle,
+        policy: Arc<AdmissionPolicy>,
+        chain_id: u64,
+    ) -> Self {
+        let verified_txs = blockchain.lock().unwrap().verified_tx_cache();
+        Self { blockchain, consensus, contract_reader, snapshots, chain_id, verified_txs, policy }
+    }
+    
+    /// Handles a JSON-RPC request or batch of requests, returning the response to send back
+    pub fn handle_request(&self, request: &Value) -> Value {
+        handle_json_rpc(request, |method, params| self.call(method, params))
+    }
+    
+    /// Runs a method with its positional parameters
+    pub fn call(&self, method: &str, params: &[Value]) -> Result<Value> {
+        match method {
+            "eth_chainId" => Ok(quantity(self.chain_id)),
+            "net_version" => Ok(Value::String(self.chain_id.to_string())),
+            "eth_blockNumber" => Ok(quantity(self.snapshots.latest().block_height)),
+            "eth_getBalance" => self.get_balance(params),
+            "eth_getBlockByNumber" => self.get_block_by_number(params),
+            "eth_getTransactionByHash" => self.get_transaction_by_hash(params),
+            "eth_getTransactionReceipt" => self.get_transaction_receipt(params),
+            "eth_call" => self.call_contract(params),
+            "eth_estimateGas" => self.estimate_gas(params),
+            "eth_sendRawTransaction" => self.send_raw_transaction(params),
+            "eth_getLogs" => self.get_logs(params),
+            method => Err(EthError::MethodNotFound(method.to_string())),
+        }
+    }
+    
+    /// `eth_getBalance(address, block)`
+    ///
+    /// Past blocks are read from reconstructed states (see
+    /// `Blockchain::state_at`), so only recent ones are available.
+    fn get_balance(&self, params: &[Value]) -> Result<Value> {
+        let mut snapshot = self.snapshots.latest();
+        let height = block_number(params.get(1), snapshot.block_height)?;
+        if height != snapshot.block_height {
+            snapshot = self.blockchain.lock().unwrap().state_at(height)?;
+        }
+        
+        let address = account_name(&snapshot.state, param_str(params, 0, "address")?)?;
+        let address = Address::new(address).map_err(|e| EthError::InvalidParams(e.to_string()))?;
+        Ok(quantity(to_wei(snapshot.state.get_balance(&address).base_units())))
+    }
+    
+    /// `eth_getBlockByNumber(block, full_transactions)`
+    fn get_block_by_number(&self, params: &[Value]) -> Result<Value> {
+        let full = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+        let blockchain = self.blockchain.lock().unwrap();
+        let latest = blockchain.get_latest_block().map_or(0, |block| block.header().height);
+        let height = block_number(params.first(), latest)?;
+        
+        let Some(block) = blockchain.get_block_by_height(height) else {
+            return Ok(Value::Null);
+        };
+        let hash = block_hash(block)?;
+        
+        let transactions = block
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                if full {
+                    transaction_json(tx, Some((block, &hash, index)), self.chain_id)
+                } else {
+                    Value::String(bytes(tx.id))
+                }
+            })
+            .collect::<Vec<_>>();
+        
+        Ok(json!({
+            "number": quantity(block.header().height),
+            "hash": bytes(hash),
+            "parentHash": bytes(block.header().prev_hash),
+            "nonce": "0x0000000000000000",
+            "mixHash": bytes([0u8; 32]),
+            "sha3Uncles": bytes([0u8; 32]),
+            "logsBloom": bytes([0u8; 256]),
+            "transactionsRoot": bytes(block.header().merkle_root),
+            "stateRoot": bytes([0u8; 32]),
+            "receiptsRoot": bytes([0u8; 32]),
+            "miner": evm_address(&block.header().validator),
+            "difficulty": "0x0",
+            "totalDifficulty": "0x0",
+            "extraData": "0x",
+            "size": quantity(serde_json::to_vec(block).map_or(0, |encoded| encoded.len() as u64)),
+            "gasLimit": quantity(blockchain.get_block_gas_limit()),
+            "gasUsed": quantity(blockchain.get_block_gas_used(height).unwrap_or(0)),
+            "baseFeePerGas": quantity(to_wei(block.header().base_fee)),
+            "timestamp": quantity(block.header().timestamp),
+            "transactions": transactions,
+            "uncles": [],
+        }))
+    }
+    
+    /// `eth_getTransactionByHash(hash)`
+    ///
+    /// Only transactions included in a block are found.
+    fn get_transaction_by_hash(&self, params: &[Value]) -> Result<Value> {
+        let tx_id = TxHash::from(param_hash(params, 0)?);
+        let blockchain = self.blockchain.lock().unwrap();
+        
+        match find_transaction(&blockchain, &tx_id) {
+            Some((block, index)) => {
+                let hash = block_hash(block)?;
+                Ok(transaction_json(&block.transactions[index], Some((block, &hash, index)), self.chain_id))
+            }
+            None => Ok(Value::Null),
+        }
+    }
+    
+    /// `eth_getTransactionReceipt(hash)`
+    fn get_transaction_receipt(&self, params: &[Value]) -> Result<Value> {
+        let tx_id = TxHash::from(param_hash(params, 0)?);
+        let blockchain = self.blockchain.lock().unwrap();
+        
+        let receipt = blockchain.find_receipt(&tx_id)?;
+        let (Some((block, index)), Some(receipt)) = (find_transaction(&blockchain, &tx_id), receipt) else {
+            return Ok(Value::Null);
+        };
+        let hash = block_hash(block)?;
+        let tx = &block.transactions[index];
+        
+        // Logs are numbered across the whole block
+        let first_log_index: usize = block.transactions[..index]
+            .iter()
+            .filter_map(|tx| blockchain.get_receipt(&tx.id))
+            .map(|receipt| receipt.logs.len())
+            .sum();
+        let logs = receipt
+            .logs
+            .iter()
+            .enumerate()
+            .map(|(i, log)| log_json(log, block.header().height, &hash, &tx.id, index, first_log_index + i))
+            .collect::<Vec<_>>();
+        
+        Ok(json!({
+            "transactionHash": bytes(tx.id),
+            "transactionIndex": quantity(index as u64),
+            "blockHash": bytes(hash),
+            "blockNumber": quantity(block.header().height),
+            "from": evm_address(&tx.sender),
+            "to": recipient(tx),
+            "cumulativeGasUsed": quantity(receipt.cumulative_gas_used),
+            "gasUsed": quantity(receipt.gas_used),
+            "effectiveGasPrice": quantity(to_wei(effective_gas_price(tx, receipt))),
+            "contractAddress": receipt.contract_address.as_deref().map(evm_address),
+            "logs": logs,
+            "logsBloom": bytes([0u8; 256]),
+            "status": if receipt.success { "0x1" } else { "0x0" },
+            "type": "0x0",
+        }))
+    }
+    
+    /// `eth_call(call, block)`
+    fn call_contract(&self, params: &[Value]) -> Result<Value> {
+        let snapshot = self.snapshots.latest();
+        check_latest(params.get(1), snapshot.block_height)?;
+        
+        let call = CallRequest::parse(params.first(), &snapshot.state)?;
+        let Some(to) = call.to else {
+            return Err(EthError::InvalidParams("eth_call requires a `to` address".to_string()));
+        };
+        
+        // Like Ethereum, calling an account without code does nothing
+        if !snapshot.state.is_contract(&to) && !smartcontracts::precompiles::is_precompile(&to_evm_address(&to)) {
+            return Ok(Value::String("0x".to_string()));
+        }
+        
+        let gas_limit = call.gas.unwrap_or_else(|| self.blockchain.lock().unwrap().get_block_gas_limit());
+        let result = self.contract_reader.trace_call(&to, &call.data, &call.from, gas_limit, &snapshot, &mut NoopTracer)?;
+        
+        match result.status {
+            ExecutionStatus::Success => Ok(Value::String(bytes(&result.return_data))),
+            ExecutionStatus::Revert => Err(ContractError::reverted(result.return_data).into()),
+        }
+    }
+    
+    /// `eth_estimateGas(call)`
+    ///
+    /// A call without `to` estimates a deployment, one to a contract a
+    /// contract call and any other a plain transfer.
+    fn estimate_gas(&self, params: &[Value]) -> Result<Value> {
+        let snapshot = self.snapshots.latest();
+        let call = CallRequest::parse(params.first(), &snapshot.state)?;
+        
+        let (tx_type, recipient) = match call.to {
+            None => (TransactionType::ContractDeploy, String::new()),
+            Some(to) if snapshot.state.is_contract(&to) => (TransactionType::ContractCall, to),
+            Some(to) => (TransactionType::Transfer, to),
+        };
+        let block_gas_limit = self.blockchain.lock().unwrap().get_block_gas_limit();
+        let gas_cap = call.gas.map_or(block_gas_limit, |gas| gas.min(block_gas_limit));
+        
+        let tx = Transaction::new_with_type(tx_type, call.from, recipient, call.value, 0, Some(call.data), gas_cap, 0)
+            ?;
+        let gas = self.contract_reader.estimate_gas(&tx, gas_cap, &snapshot)?;
+        Ok(quantity(gas))
+    }
+    
+    /// `eth_sendRawTransaction(data)`
+    ///
+    /// `data` is a signed Ethereum transaction, or the hex of a
+    /// JSON-serialized, signed GENX transaction.
+    fn send_raw_transaction(&self, params: &[Value]) -> Result<Value> {
+        let raw = param_bytes(params, 0)?;
+        let tx = if raw.first() == Some(&b'{') {
+            serde_json::from_slice(&raw).map_err(|e| {
+                EthError::InvalidParams(format!("expected a JSON-serialized GENX transaction: {}", e))
+            })?
+        } else {
+            self.import_transaction(&raw)?
+        };
+        self.policy.check(&tx).map_err(|e| EthError::Server(e.to_string()))?;
+        tx.validate_cached(&self.verified_txs)?;
+        
+        let id = tx.id;
+        self.consensus.lock().unwrap().add_transaction(tx)?;
+        Ok(Value::String(bytes(id)))
+    }
+    
+    /// Imports a signed Ethereum transaction
+    ///
+    /// The transaction must be signed for this chain, which rules out legacy
+    /// transactions without EIP-155 replay protection.
+    fn import_transaction(&self, raw: &[u8]) -> Result<Transaction> {
+        let eth = EthTransaction::decode(raw).map_err(|e| EthError::InvalidParams(e.to_string()))?;
+        if eth.chain_id != Some(self.chain_id) {
+            return Err(EthError::InvalidParams(match eth.chain_id {
+                Some(chain_id) => format!("transaction is for chain {}, not {}", chain_id, self.chain_id),
+                None => "transaction is not replay-protected; sign it with EIP-155".to_string(),
+            }));
+        }
+        
+        let to = eth.to.ok_or_else(|| EthError::InvalidParams("contract creation is not supported".to_string()))?;
+        let recipient = account_name(&self.snapshots.latest().state, &bytes(to))?;
+        eth.to_transaction(recipient).map_err(|e| EthError::InvalidParams(e.to_string()))
+    }
+    
+    /// `eth_getLogs(filter)`
+    ///
+    /// Supports `fromBlock`, `toBlock`, `address` (one or a list) and
+    /// `topics` (with `null` wildcards and lists of alternatives).
+    fn get_logs(&self, params: &[Value]) -> Result<Value> {
+        let filter = params.first().unwrap_or(&Value::Null);
+        if filter.get("blockHash").is_some_and(|hash| !hash.is_null()) {
+            return Err(EthError::InvalidParams("blockHash filters are not supported".to_string()));
+        }
+        
+        let snapshot = self.snapshots.latest();
+        let blockchain = self.blockchain.lock().unwrap();
+        let latest = blockchain.get_latest_block().map_or(0, |block| block.header().height);
+        let from_block = block_number(filter.get("fromBlock"), latest)?;
+        let to_block = block_number(filter.get("toBlock"), latest)?;
+        
+        let addresses = match filter.get("address") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(addresses)) => addresses
+                .iter()
+                .map(|address| account_name(&snapshot.state, address.as_str().unwrap_or_default()))
+                .collect::<Result<Vec<_>>>()?,
+            Some(address) => vec![account_name(&snapshot.state, address.as_str().unwrap_or_default())?],
+        };
+        let topics = match filter.get("topics") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(topics)) => topics.iter().map(topic_alternatives).collect::<Result<Vec<_>>>()?,
+            Some(_) => return Err(EthError::InvalidParams("topics must be an array".to_string())),
+        };
+        
+        // Fetch the whole range so logs can be numbered within their blocks
+        let range = LogFilter { from_block: Some(from_block), to_block: Some(to_block), ..Default::default() };
+        let mut positions = BlockPositions::default();
+        let mut logs = Vec::new();
+        
+        for entry in blockchain.get_logs(&range)? {
+            let (block_hash, tx_index, log_index) = positions.next(&blockchain, &entry)?;
+            
+            let address_matches = addresses.is_empty() || addresses.contains(&entry.log.address);
+            let topics_match = topics.iter().enumerate().all(|(i, alternatives)| {
+                alternatives.is_empty() || entry.log.topics.get(i).is_some_and(|topic| alternatives.contains(topic))
+            });
+            if address_matches && topics_match {
+                logs.push(log_json(&entry.log, entry.block_height, &block_hash, &entry.tx_id, tx_index, log_index));
+            }
+        }
+        
+        Ok(Value::Array(logs))
+    }
+}
+
+/// Parameters of `eth_call` and `eth_estimateGas`
+struct CallRequest {
+    from: String,
+    to: Option<String>,
+    gas: Option<u64>,
+    value: u64,
+    data: Vec<u8>,
+}
+
+impl CallRequest {
+    fn parse(call: Option<&Value>, state: &State) -> Result<Self> {
+        let call = call
+            .filter(|call| call.is_object())
+            .ok_or_else(|| EthError::InvalidParams("expected a call object".to_string()))?;
+        let field = |name: &str| call.get(name).filter(|value| !value.is_null());
+        
+        let from = match field("from") {
+            Some(from) => account_name(state, from.as_str().unwrap_or_default())?,
+            None => format!("0x{}", hex::encode([0u8; 20])),
+        };
+        let to = field("to").map(|to| account_name(state, to.as_str().unwrap_or_default())).transpose()?;
+        let gas = field("gas").map(parse_quantity).transpose()?.map(|gas| gas.min(u64::MAX as u128) as u64);
+        let value = field("value").map(parse_quantity).transpose()?.map(from_wei).transpose()?.unwrap_or(0);
+        
+        // Clients send the call data as either `input` or the older `data`
+        let data = match field("input").or_else(|| field("data")) {
+            Some(data) => decode_hex(data.as_str().unwrap_or_default())?,
+            None => Vec::new(),
+        };
+        
+        Ok(Self { from, to, gas, value, data })
+    }
+}
+
+/// Tracks where each log of a range sits in its block
+#[derive(Default)]
+struct BlockPositions {
+    height: Option<u64>,
+    block_hash: BlockHash,
+    tx_indexes: HashMap<TxHash, usize>,
+    next_log_index: usize,
+}
+
+impl BlockPositions {
+    /// Returns the hash of a log's block, its transaction's index and the log's index in the block
+    fn next(&mut self, blockchain: &Blockchain, entry: &IndexedLog) -> Result<(BlockHash, usize, usize)> {
+        if self.height != Some(entry.block_height) {
+            let block = blockchain
+                .get_block_by_height(entry.block_height)
+                .ok_or(BlockchainError::UnknownBlock { height: entry.block_height })?;
+            
+            self.height = Some(entry.block_height);
+            self.block_hash = block_hash(block)?;
+            self.tx_indexes = block.transactions.iter().enumerate().map(|(index, tx)| (tx.id, index)).collect();
+            self.next_log_index = 0;
+        }
+        
+        let log_index = self.next_log_index;
+        self.next_log_index += 1;
+        Ok((self.block_hash, self.tx_indexes.get(&entry.tx_id).copied().unwrap_or(0), log_index))
+    }
+}
+
+/// Handles a JSON-RPC request or batch of requests with a method dispatcher
+pub(crate) fn handle_json_rpc(request: &Value, call: impl Fn(&str, &[Value]) -> Result<Value>) -> Value {
+    match request {
+        Value::Array(batch) if !batch.is_empty() => {
+            Value::Array(batch.iter().map(|request| handle_single(request, &call)).collect())
+        }
+        request => handle_single(request, &call),
+    }
+}
+
+/// Handles a single JSON-RPC request
+fn handle_single(request: &Value, call: &impl Fn(&str, &[Value]) -> Result<Value>) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some(method) => {
+            let params = match request.get("params") {
+                None | Some(Value::Null) => &[][..],
+                Some(Value::Array(params)) => params.as_slice(),
+                Some(_) => return error_response(id, &EthError::InvalidParams("params must be an array".to_string())),
+            };
+            call(method, params)
+        }
+        None => Err(EthError::InvalidRequest),
+    };
+    
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_response(id, &e),
+    }
+}
+
+/// Builds an error response
+pub(crate) fn error_response(id: Value, error: &EthError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error.to_json() })
+}
+
+/// Builds the JSON of a transaction, along with its block and index in it if included
+fn transaction_json(tx: &Transaction, included: Option<(&Block, &BlockHash, usize)>, chain_id: u64) -> Value {
+    let signature = tx.signature.as_deref().unwrap_or_default();
+    let (r, rest) = signature.split_at(signature.len().min(32));
+    let (s, v) = rest.split_at(rest.len().min(32));
+    
+    // Imported Ethereum transactions report what they were signed with
+    let eth = tx.eth_raw.as_deref().and_then(|raw| EthTransaction::decode(raw).ok());
+    let tx_type = match &eth {
+        Some(eth) if eth.tx_type == EthTransactionType::Eip1559 => 2u64,
+        _ => 0,
+    };
+    
+    json!({
+        "hash": bytes(tx.id),
+        "nonce": quantity(tx.nonce),
+        "blockHash": included.map(|(_, hash, _)| bytes(hash)),
+        "blockNumber": included.map(|(block, _, _)| quantity(block.header().height)),
+        "transactionIndex": included.map(|(_, _, index)| quantity(index as u64)),
+        "from": evm_address(&tx.sender),
+        "to": recipient(tx),
+        "value": quantity(to_wei(tx.amount)),
+        "gas": quantity(tx.gas_limit),
+        "gasPrice": quantity(to_wei(tx.gas_price)),
+        "input": bytes(tx.data.as_deref().unwrap_or_default()),
+        "type": quantity(tx_type),
+        "chainId": quantity(chain_id),
+        "v": quantity(v.first().copied().unwrap_or(0)),
+        "r": quantity_bytes(r),
+        "s": quantity_bytes(s),
+    })
+}
+
+/// Builds the JSON of a log
+fn log_json(log: &Log, block_height: u64, block_hash: &BlockHash, tx_id: &TxHash, tx_index: usize, log_index: usize) -> Value {
+    json!({
+        "address": evm_address(&log.address),
+        "topics": log.topics.iter().map(bytes).collect::<Vec<_>>(),
+        "data": bytes(&log.data),
+        "blockNumber": quantity(block_height),
+        "blockHash": bytes(block_hash),
+        "transactionHash": bytes(tx_id),
+        "transactionIndex": quantity(tx_index as u64),
+        "logIndex": quantity(log_index as u64),
+        "removed": false,
+    })
+}
+
+/// Finds the block a transaction was included in and its index there
+pub(crate) fn find_transaction<'a>(blockchain: &'a Blockchain, tx_id: &TxHash) -> Option<(&'a Block, usize)> {
+    let block = blockchain.get_block_by_height(blockchain.get_transaction_height(tx_id)?)?;
+    let index = block.transactions.iter().position(|tx| tx.id == *tx_id)?;
+    Some((block, index))
+}
+
+/// Gets the gas price a transaction actually paid
+fn effective_gas_price(tx: &Transaction, receipt: &Receipt) -> u64 {
+    match receipt.gas_used {
+        0 => tx.gas_price,
+        gas_used => tx.fee_for_gas(gas_used) / gas_used,
+    }
+}
+
+/// Gets the `to` of a transaction, which deployments don't have
+fn recipient(tx: &Transaction) -> Value {
+    match tx.tx_type {
+        TransactionType::ContractDeploy => Value::Null,
+        _ => Value::String(evm_address(&tx.recipient)),
+    }
+}
+
+fn block_hash(block: &Block) -> Result<BlockHash> {
+    Ok(block.hash()?)
+}
+
+/// Maps an address given by a client to the GENX account it names
+///
+/// A hex address is a contract if one is deployed at it and otherwise the
+/// `0x` account of that name; anything else is taken as a GENX address.
+pub(crate) fn account_name(state: &State, address: &str) -> Result<String> {
+    let Some(hex_part) = address.strip_prefix("0x") else {
+        if address.is_empty() {
+            return Err(EthError::InvalidParams("expected an address".to_string()));
+        }
+        return Ok(address.to_string());
+    };
+    
+    match hex::decode(hex_part) {
+        Ok(bytes) if bytes.len() == 20 => {
+            let hex_part = hex::encode(bytes);
+            let contract = format!("{}{}", CONTRACT_ADDRESS_PREFIX, hex_part);
+            Ok(if state.is_contract(&contract) { contract } else { format!("0x{}", hex_part) })
+        }
+        _ => Err(EthError::InvalidParams(format!("invalid address {}", address))),
+    }
+}
+
+/// Formats a GENX address as a 20-byte hex address
+fn evm_address(address: &str) -> String {
+    bytes(to_evm_address(address))
+}
+
+/// Resolves a block number or tag to a height
+fn block_number(block: Option<&Value>, latest: u64) -> Result<u64> {
+    match block {
+        None | Some(Value::Null) => Ok(latest),
+        Some(Value::String(tag)) if tag == "latest" || tag == "pending" => Ok(latest),
+        Some(Value::String(tag)) if tag == "earliest" => Ok(0),
+        Some(number) => u64::try_from(parse_quantity(number)?)
+            .map_err(|_| EthError::InvalidParams(format!("invalid block number {}", number))),
+    }
+}
+
+/// Checks that a state query asks for the latest block, the only one whose state is kept
+fn check_latest(block: Option<&Value>, latest: u64) -> Result<()> {
+    let height = block_number(block, latest)?;
+    if height != latest {
+        return Err(EthError::Server(format!("state at block {} is not available; only the latest block {} is", height, latest)));
+    }
+    Ok(())
+}
+
+/// Parses one position of a `topics` filter: a topic, a list of alternatives, or `null` for any
+fn topic_alternatives(topics: &Value) -> Result<Vec<Hash>> {
+    match topics {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(alternatives) => alternatives.iter().map(parse_hash).collect(),
+        topic => Ok(vec![parse_hash(topic)?]),
+    }
+}
+
+/// Converts a GENX amount to units of 10^-18 GENX
+fn to_wei(amount: u64) -> u128 {
+    amount as u128 * WEI_PER_UNIT
+}
+
+/// Converts units of 10^-18 GENX to a GENX amount, which must be exact
+fn from_wei(wei: u128) -> Result<u64> {
+    if !wei.is_multiple_of(WEI_PER_UNIT) {
+        return Err(EthError::InvalidParams(format!("value {} is finer than the {} decimals GENX amounts have", wei, DECIMALS)));
+    }
+    u64::try_from(wei / WEI_PER_UNIT).map_err(|_| EthError::InvalidParams(format!("value {} is too large", wei)))
+}
+
+/// Formats a number as a hex quantity
+fn quantity(value: impl Into<u128>) -> Value {
+    Value::String(format!("{:#x}", value.into()))
+}
+
+/// Formats big-endian bytes as a hex quantity, without leading zeros
+fn quantity_bytes(value: &[u8]) -> String {
+    let digits = hex::encode(value);
+    match digits.trim_start_matches('0') {
+        "" => "0x0".to_string(),
+        digits => format!("0x{}", digits),
+    }
+}
+
+/// Formats bytes as `0x`-prefixed hex
+pub(crate) fn bytes(value: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(value))
+}
+
+fn parse_quantity(value: &Value) -> Result<u128> {
+    value
+        .as_str()
+        .and_then(|text| text.strip_prefix("0x"))
+        .and_then(|digits| u128::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| EthError::InvalidParams(format!("invalid quantity {}", value)))
+}
+
+fn parse_hash(value: &Value) -> Result<Hash> {
+    decode_hex(value.as_str().unwrap_or_default())?
+        .try_into()
+        .map_err(|_| EthError::InvalidParams(format!("invalid hash {}", value)))
+}
+
+pub(crate) fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    hex::decode(text.strip_prefix("0x").unwrap_or(text)).map_err(|e| EthError::InvalidParams(format!("invalid hex data: {}", e)))
+}
+
+pub(crate) fn param_str<'a>(params: &'a [Value], index: usize, name: &str) -> Result<&'a str> {
+    params
+        .get(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| EthError::InvalidParams(format!("missing {}", name)))
+}
+
+pub(crate) fn param_address(params: &[Value], index: usize) -> Result<Address> {
+    Address::new(param_str(params, index, "address")?).map_err(|e| EthError::InvalidParams(e.to_string()))
+}
+
+pub(crate) fn param_hash(params: &[Value], index: usize) -> Result<Hash> {
+    parse_hash(params.get(index).unwrap_or(&Value::Null))
+}
+
+fn param_bytes(params: &[Value], index: usize) -> Result<Vec<u8>> {
+    decode_hex(param_str(params, index, "data")?)
+}
\ No newline at end of file
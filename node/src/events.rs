@@ -0,0 +1,236 @@
+//! Event bus for things operators and other components should hear about
+//!
+//! Components publish `NodeEvent`s to the node's bus and any number of
+//! subscribers receive every event published after they subscribed. A
+//! subscriber that falls more than `EVENT_BUFFER_SIZE` events behind misses
+//! the oldest ones and is told how many it missed.
+
+use serde::Serialize;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+use ctb_core::block::Block;
+use ctb_core::chain::ReorgRecord;
+use ctb_core::state_diff::BalanceChange;
+use ctb_core::transaction::Transaction;
+use ctb_core::validator_set::{ValidatorSet, ValidatorSetMember};
+use ctb_core::{BlockHash, Hash, TxHash};
+
+/// Events buffered for each subscriber
+pub const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Something that happened on the node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum NodeEvent {
+    /// The canonical chain switched to a competing branch
+    ReorgOccurred {
+        /// Hash of the latest block before the switch
+        old_tip: BlockHash,
+        
+        /// Hash of the latest block after the switch
+        new_tip: BlockHash,
+        
+        /// Height of the last block both branches share
+        fork_height: u64,
+        
+        /// Number of blocks removed from the old branch
+        depth: u64,
+        
+        /// Number of transactions of the removed blocks that the new branch doesn't include
+        dropped_txs: usize,
+        
+        /// Number of the dropped transactions returned to the mempool
+        requeued_txs: usize,
+    },
+    
+    /// The chain switched to a heavier branch synced from a peer, as when a partition heals
+    ///
+    /// Published after the `ReorgOccurred` of the switch.
+    ChainHealed {
+        /// Node ID of the peer the branch came from
+        peer: String,
+        
+        /// Height of the last block both branches share
+        common_ancestor: u64,
+        
+        /// Number of blocks rolled back
+        rolled_back: u64,
+        
+        /// Number of blocks of the branch applied
+        applied: u64,
+        
+        /// Hash of the latest block before the switch
+        old_tip: BlockHash,
+        
+        /// Hash of the latest block after the switch
+        new_tip: BlockHash,
+    },
+    
+    /// A peer's branch was refused for reverting a finalized checkpoint, and the peer banned
+    BranchRefused {
+        /// Node ID of the peer
+        peer: String,
+        
+        /// Height the branch forks off the chain at, if the peer's headers showed it
+        fork_height: Option<u64>,
+        
+        /// Height of the latest finalized checkpoint
+        finalized_height: u64,
+    },
+    
+    /// A pending transaction was dropped because a block left its sender unable to pay for it
+    ///
+    /// Typically another transaction spending the same funds was confirmed
+    /// first, so this one would fail if it were ever included.
+    TransactionDropped {
+        /// ID of the dropped transaction
+        tx_id: TxHash,
+        
+        /// Address of its sender
+        sender: String,
+        
+        /// Height of the block that left the sender short
+        block_height: u64,
+    },
+    
+    /// A block started an epoch, committing to its validator set
+    ValidatorSetChanged {
+        /// Epoch the set is active in
+        epoch: u64,
+        
+        /// Height of the block committing to the set
+        block_height: u64,
+        
+        /// Hash of the set, as the block's header holds it
+        #[serde(with = "ctb_core::types::hex_serde")]
+        validator_set_hash: Hash,
+        
+        /// Validators of the set, highest stake first
+        validators: Vec<ValidatorSetMember>,
+    },
+    
+    /// A block changed the balance of a watched address, see `watch`
+    ///
+    /// Rolling the block back emits the opposite change, with `removed` set.
+    AddressActivity {
+        /// Watched address
+        address: String,
+        
+        /// Change to the balance, in base units
+        delta: i128,
+        
+        /// Balance after the change
+        new_balance: u64,
+        
+        /// Transactions of the block sent from or to the address
+        tx_ids: Vec<TxHash>,
+        
+        /// Height of the block
+        height: u64,
+        
+        /// Hash of the block
+        block_hash: BlockHash,
+        
+        /// Whether the block was rolled back
+        removed: bool,
+    },
+}
+
+impl NodeEvent {
+    /// Creates the event for a reorganization, of whose dropped transactions `requeued_txs` were returned to the mempool
+    pub fn reorg(record: &ReorgRecord, requeued_txs: usize) -> Self {
+        NodeEvent::ReorgOccurred {
+            old_tip: record.old_tip,
+            new_tip: record.new_tip,
+            fork_height: record.fork_height,
+            depth: record.depth,
+            dropped_txs: record.dropped_txs,
+            requeued_txs,
+        }
+    }
+    
+    /// Creates the event for a branch synced from `peer` that the chain switched to, applying `applied` blocks
+    pub fn chain_healed(peer: &str, record: &ReorgRecord, applied: u64) -> Self {
+        NodeEvent::ChainHealed {
+            peer: peer.to_string(),
+            common_ancestor: record.fork_height,
+            rolled_back: record.depth,
+            applied,
+            old_tip: record.old_tip,
+            new_tip: record.new_tip,
+        }
+    }
+    
+    /// Creates the event for a branch from `peer` refused for forking off below the finalized checkpoint
+    pub fn branch_refused(peer: &str, fork_height: Option<u64>, finalized_height: u64) -> Self {
+        NodeEvent::BranchRefused { peer: peer.to_string(), fork_height, finalized_height }
+    }
+    
+    /// Creates the event for a pending transaction that the block at `block_height` left unfunded
+    pub fn transaction_dropped(tx: &Transaction, block_height: u64) -> Self {
+        NodeEvent::TransactionDropped {
+            tx_id: tx.id,
+            sender: tx.sender.clone(),
+            block_height,
+        }
+    }
+    
+    /// Creates the event for the validator set the block at `block_height` committed to
+    pub fn validator_set_changed(validator_set: &ValidatorSet, block_height: u64) -> Self {
+        NodeEvent::ValidatorSetChanged {
+            epoch: validator_set.epoch,
+            block_height,
+            validator_set_hash: validator_set.hash(),
+            validators: validator_set.members.clone(),
+        }
+    }
+    
+    /// Creates the event for a block's change to a watched address's balance
+    pub fn address_activity(block: &Block, block_hash: &BlockHash, change: &BalanceChange, removed: bool) -> Self {
+        let tx_ids = block
+            .transactions
+            .iter()
+            .filter(|tx| tx.sender == change.address || tx.recipient == change.address)
+            .map(|tx| tx.id)
+            .collect();
+        NodeEvent::AddressActivity {
+            address: change.address.clone(),
+            delta: i128::from(change.new) - i128::from(change.old),
+            new_balance: change.new,
+            tx_ids,
+            height: block.header().height,
+            block_hash: *block_hash,
+            removed,
+        }
+    }
+}
+
+/// Broadcasts node events to subscribers
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: Sender<NodeEvent>,
+}
+
+impl EventBus {
+    /// Creates a bus without subscribers
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self { sender }
+    }
+    
+    /// Subscribes to the events published from now on
+    pub fn subscribe(&self) -> Receiver<NodeEvent> {
+        self.sender.subscribe()
+    }
+    
+    /// Publishes an event, returning how many subscribers will receive it
+    pub fn publish(&self, event: NodeEvent) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
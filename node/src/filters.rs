@@ -0,0 +1,101 @@
+//! Index of the compact block filters a node serves to light wallets
+//!
+//! The `FilterIndex` keeps the filter of every block in the chain (see
+//! `ctb_core::block_filter`) and the header chaining it to the filters before
+//! it, by height. It's built from the chain when the node starts and kept
+//! up to date as a `ChainListener`: blocks added push their filters, and
+//! blocks rolled back drop theirs, so a reorganization changes the headers
+//! from the fork on.
+//!
+//! Filters cover the addresses of the contracts that emitted logs, which
+//! the node only knows for blocks whose logs it kept. A block whose logs
+//! were pruned before the node started gets a filter of its transactions'
+//! addresses alone.
+//!
+//! Peers ask for headers and filters with `GetFilterHeaders` and
+//! `GetFilters` (see `message`), and RPC clients with
+//! `genx_getFilterHeaders` and `genx_getFilters` (see `rpc`).
+
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+
+use ctb_core::block::Block;
+use ctb_core::block_filter::BlockFilter;
+use ctb_core::chain::{Blockchain, ChainListener};
+use ctb_core::receipt::IndexedLog;
+use ctb_core::{BlockHash, Hash};
+
+/// Filters of the blocks in the chain, by height
+#[derive(Debug)]
+pub struct FilterIndex {
+    /// Filter of each block and its header, from the genesis block on
+    entries: RwLock<Vec<(Arc<BlockFilter>, Hash)>>,
+}
+
+impl FilterIndex {
+    /// Creates the index of a chain's blocks
+    pub fn new(blockchain: &Blockchain) -> Self {
+        let index = Self { entries: RwLock::new(Vec::new()) };
+        for height in 0..=blockchain.get_latest_height() {
+            let Some(block) = blockchain.get_block_by_height(height) else {
+                break;
+            };
+            let Ok(block_hash) = block.hash() else {
+                break;
+            };
+            let receipts = blockchain.get_block_receipts(height).unwrap_or_default();
+            let logs = receipts.iter().flat_map(|receipt| &receipt.logs);
+            index.push(height, BlockFilter::for_block(block, &block_hash, logs));
+        }
+        index
+    }
+    
+    /// Gets the height of the latest block with a filter and its filter's header
+    pub fn tip(&self) -> Option<(u64, Hash)> {
+        let entries = self.entries.read().unwrap();
+        entries.last().map(|(_, header)| (entries.len() as u64 - 1, *header))
+    }
+    
+    /// Gets the filter headers of a range of heights, stopping at the first height without a filter
+    pub fn headers(&self, range: Range<u64>) -> Vec<Hash> {
+        self.entries_in(range, |(_, header)| *header)
+    }
+    
+    /// Gets the filters of a range of heights, stopping at the first height without one
+    pub fn filters(&self, range: Range<u64>) -> Vec<BlockFilter> {
+        self.entries_in(range, |(filter, _)| BlockFilter::clone(filter))
+    }
+    
+    fn entries_in<T>(&self, range: Range<u64>, map: impl Fn(&(Arc<BlockFilter>, Hash)) -> T) -> Vec<T> {
+        let entries = self.entries.read().unwrap();
+        let start = usize::try_from(range.start).unwrap_or(usize::MAX).min(entries.len());
+        let end = usize::try_from(range.end).unwrap_or(usize::MAX).clamp(start, entries.len());
+        entries[start..end].iter().map(map).collect()
+    }
+    
+    /// Adds the filter of the block at a height, replacing those from that height on
+    fn push(&self, height: u64, filter: BlockFilter) {
+        let mut entries = self.entries.write().unwrap();
+        let height = usize::try_from(height).unwrap_or(usize::MAX);
+        if height > entries.len() {
+            eprintln!("Filters: no filter below height {}, skipped its block's", height);
+            return;
+        }
+        entries.truncate(height);
+        let previous = entries.last().map_or([0; 32], |(_, header)| *header);
+        let header = filter.header(&previous);
+        entries.push((Arc::new(filter), header));
+    }
+}
+
+impl ChainListener for FilterIndex {
+    fn block_added(&self, block: &Block, block_hash: &BlockHash, logs: &[IndexedLog]) {
+        let filter = BlockFilter::for_block(block, block_hash, logs.iter().map(|indexed| &indexed.log));
+        self.push(block.header().height, filter);
+    }
+    
+    fn block_removed(&self, block: &Block, _block_hash: &BlockHash, _logs: &[IndexedLog]) {
+        let height = usize::try_from(block.header().height).unwrap_or(usize::MAX);
+        self.entries.write().unwrap().truncate(height);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,394 @@
+//! Checking the data directory when the node starts, and repairing it
+//!
+//! A node killed mid-write, or whose disk flips a bit, may find its data
+//! directory corrupt when it restarts. Before trusting anything saved there,
+//! `Node::start` runs `check`:
+//!
+//! - The block store's tip must parse and name the block stored at its height.
+//! - Blocks must decode, sit at their heights and link to their parents, back
+//!   to the chain's genesis block. A quick check walks the latest
+//!   `quick_depth` blocks; a full one walks them all and validates every
+//!   block's transactions too.
+//! - Finalized checkpoints at stored heights must be the blocks stored there.
+//! - The production journal, finality checkpoints and reorganization
+//!   history must parse.
+//!
+//! The stored blocks are then loaded into the chain up to the latest one
+//! that checked out (see `restore`), which rebuilds everything derived from
+//! them: the state, receipts, logs, transaction lookups and filters. None
+//! of those are saved, so there's no saved index to go stale. A block that
+//! fails to apply ends the chain at its parent, and a chain whose state
+//! root isn't the tip's is reported too.
+//!
+//! A directory with any issue is only used once repaired, either because
+//! `auto_repair` is set or because the operator passed `--repair` to
+//! `genx node run`; otherwise the node refuses to start. Repairing moves
+//! the tip down to the latest verified block, removes the blocks above it
+//! and renames files that don't parse to `<file>.corrupt`, logging every
+//! block and file discarded. The node then syncs the discarded blocks from
+//! its peers again. A store whose genesis block isn't the chain's belongs
+//! to another chain and is never repaired.
+//!
+//! Setting the production journal aside forgets which blocks the node
+//! produced before it stopped, so a validator could produce a second block
+//! at a height it produced at. Operators of validators should stop
+//! producing (see `admin_setValidating`) until those heights are finalized.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use ctb_core::block::Block;
+use ctb_core::block_store::{BlockStore, BLOCKS_DIR};
+use ctb_core::chain::{Blockchain, ReorgRecord};
+use ctb_core::state_sync;
+use ctb_core::{BlockHash, Result};
+
+use consensus::finality::FinalizedCheckpoint;
+
+use crate::journal::{ProducedBlock, JOURNAL_FILE};
+use crate::{FINALITY_FILE, REORG_HISTORY_FILE};
+
+/// Extension given to files set aside because they don't parse
+pub const CORRUPT_EXTENSION: &str = "corrupt";
+
+/// How thoroughly the data directory is checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityMode {
+    /// Walk the latest blocks only
+    Quick,
+    
+    /// Walk every block, validating its transactions
+    Full,
+}
+
+/// Startup integrity check configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntegrityConfig {
+    /// How thoroughly the data directory is checked
+    pub mode: IntegrityMode,
+    
+    /// Latest blocks a quick check walks
+    pub quick_depth: u64,
+    
+    /// Whether issues are repaired without `--repair`
+    pub auto_repair: bool,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            mode: IntegrityMode::Quick,
+            quick_depth: 128,
+            auto_repair: false,
+        }
+    }
+}
+
+/// What's wrong with part of the data directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The stored genesis block isn't the chain's
+    Genesis,
+    
+    /// The tip can't be read, or doesn't match the stored blocks
+    Tip,
+    
+    /// A block is missing, doesn't decode or is invalid
+    Block,
+    
+    /// A block doesn't link to the one stored below it
+    Linkage,
+    
+    /// A block doesn't apply to the chain below it
+    Execution,
+    
+    /// A finalized checkpoint isn't the block stored at its height
+    Checkpoint,
+    
+    /// A journal doesn't parse
+    Journal,
+}
+
+/// Something wrong found by a check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IntegrityIssue {
+    pub kind: IssueKind,
+    
+    /// Height of the block concerned, if any
+    pub height: Option<u64>,
+    
+    pub detail: String,
+}
+
+/// Block removed from the store by a repair
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiscardedBlock {
+    pub height: u64,
+    
+    /// Hash of the block, if it could be read
+    pub block_hash: Option<BlockHash>,
+}
+
+/// Outcome of checking, and possibly repairing, a data directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IntegrityReport {
+    pub mode: IntegrityMode,
+    
+    /// Height of the tip stored, if the store has one
+    pub stored_height: Option<u64>,
+    
+    /// Height of the latest block that checked out
+    pub verified_height: u64,
+    
+    /// Blocks walked by the check
+    pub blocks_checked: u64,
+    
+    /// Everything found wrong, in the order found
+    pub issues: Vec<IntegrityIssue>,
+    
+    /// Blocks removed by a repair
+    pub discarded_blocks: Vec<DiscardedBlock>,
+    
+    /// Journals that don't parse
+    pub corrupt_files: Vec<PathBuf>,
+    
+    /// Files set aside by a repair, as renamed
+    pub discarded_files: Vec<PathBuf>,
+}
+
+impl IntegrityReport {
+    /// Whether nothing was found wrong
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+    
+    /// Whether the directory can be repaired, which it can't if it belongs to another chain
+    pub fn is_repairable(&self) -> bool {
+        !self.issues.iter().any(|issue| issue.kind == IssueKind::Genesis)
+    }
+    
+    fn issue(&mut self, kind: IssueKind, height: Option<u64>, detail: impl Into<String>) {
+        self.issues.push(IntegrityIssue { kind, height, detail: detail.into() });
+    }
+    
+    /// Records an issue with the block at a height, so only the blocks below it are verified
+    fn block_issue(&mut self, kind: IssueKind, height: u64, detail: impl Into<String>) {
+        self.issue(kind, Some(height), detail);
+        self.verified_height = self.verified_height.min(height.saturating_sub(1));
+    }
+}
+
+/// Checks a data directory of the chain starting with `genesis_hash`, without changing it
+pub fn check(data_dir: &Path, genesis_hash: BlockHash, config: &IntegrityConfig) -> IntegrityReport {
+    let mut report = IntegrityReport {
+        mode: config.mode,
+        stored_height: None,
+        verified_height: 0,
+        blocks_checked: 0,
+        issues: Vec::new(),
+        discarded_blocks: Vec::new(),
+        corrupt_files: Vec::new(),
+        discarded_files: Vec::new(),
+    };
+    
+    check_journal::<ProducedBlock>(&mut report, &data_dir.join(JOURNAL_FILE));
+    let checkpoints = check_journal::<FinalizedCheckpoint>(&mut report, &data_dir.join(FINALITY_FILE));
+    check_journal::<ReorgRecord>(&mut report, &data_dir.join(REORG_HISTORY_FILE));
+    
+    let Ok(store) = BlockStore::open_existing(data_dir.join(BLOCKS_DIR)) else {
+        return report;
+    };
+    
+    // Without a readable tip, the blocks stored from genesis up are walked;
+    // without any tip, nothing stored was ever the chain's
+    let (tip, top) = match store.tip() {
+        Ok(Some(tip)) => {
+            let top = tip.height;
+            (Some(tip), top)
+        }
+        Ok(None) => return report,
+        Err(e) => {
+            report.issue(IssueKind::Tip, None, e.to_string());
+            (None, (0..).take_while(|height| store.block_path(*height).exists()).last().unwrap_or(0))
+        }
+    };
+    report.stored_height = tip.as_ref().map(|tip| tip.height);
+    report.verified_height = top;
+    
+    let from = match config.mode {
+        IntegrityMode::Quick => top.saturating_sub(config.quick_depth.max(1)),
+        IntegrityMode::Full => 0,
+    };
+    let mut parent: Option<BlockHash> = None;
+    let mut hashes = std::collections::HashMap::new();
+    for height in from..=top {
+        report.blocks_checked += 1;
+        let block = match store.get_block(height) {
+            Ok(Some(block)) => block,
+            Ok(None) => {
+                report.block_issue(IssueKind::Block, height, format!("Block {} is missing", height));
+                break;
+            }
+            Err(e) => {
+                report.block_issue(IssueKind::Block, height, e.to_string());
+                break;
+            }
+        };
+        if let Err(detail) = check_block(&block, height, config.mode) {
+            report.block_issue(IssueKind::Block, height, detail);
+            break;
+        }
+        let hash = match block.hash() {
+            Ok(hash) => hash,
+            Err(e) => {
+                report.block_issue(IssueKind::Block, height, e.to_string());
+                break;
+            }
+        };
+        
+        if height == 0 && hash != genesis_hash {
+            report.block_issue(IssueKind::Genesis, 0, format!("Genesis block {} isn't the chain's, {}", hash, genesis_hash));
+            break;
+        }
+        if let Some(parent) = parent {
+            if block.header().prev_hash != parent {
+                report.block_issue(
+                    IssueKind::Linkage,
+                    height,
+                    format!("Block {} names parent {}, but block {} is {}", height, block.header().prev_hash, height - 1, parent),
+                );
+                break;
+            }
+        }
+        parent = Some(hash);
+        hashes.insert(height, hash);
+    }
+    
+    if let (Some(tip), Some(hash)) = (&tip, hashes.get(&top)) {
+        if tip.block_hash != *hash {
+            report.block_issue(IssueKind::Tip, top, format!("Tip names block {}, but block {} is {}", tip.block_hash, top, hash));
+        }
+    }
+    
+    // Checkpoints below the walked blocks are compared with what's stored
+    for checkpoint in checkpoints.unwrap_or_default() {
+        if checkpoint.height > report.verified_height {
+            continue;
+        }
+        let stored = match hashes.get(&checkpoint.height) {
+            Some(hash) => Some(*hash),
+            None => store.get_block(checkpoint.height).ok().flatten().and_then(|block| block.hash().ok()),
+        };
+        if stored != Some(checkpoint.block_hash) {
+            report.block_issue(
+                IssueKind::Checkpoint,
+                checkpoint.height,
+                format!("Block {} finalized at height {} isn't the one stored", checkpoint.block_hash, checkpoint.height),
+            );
+        }
+    }
+    report
+}
+
+/// Loads the verified blocks of a store into a chain holding only its genesis block
+///
+/// Stops at the first block that doesn't apply, recording the issue, so
+/// the chain ends at the latest block that did.
+pub fn restore(data_dir: &Path, blockchain: &mut Blockchain, report: &mut IntegrityReport) -> Result<()> {
+    let Ok(store) = BlockStore::open_existing(data_dir.join(BLOCKS_DIR)) else {
+        return Ok(());
+    };
+    
+    let verified_height = report.verified_height;
+    for height in blockchain.get_latest_height() + 1..=verified_height {
+        let block = store.get_block(height)?.ok_or_else(|| {
+            ctb_core::BlockchainError::StateError(format!("Block {} disappeared from the store", height))
+        })?;
+        if let Err(e) = blockchain.add_block(block) {
+            report.block_issue(IssueKind::Execution, height, e.to_string());
+            return Ok(());
+        }
+    }
+    
+    // Only the tip stored at the verified height says what the state should be
+    if let Ok(Some(tip)) = store.tip() {
+        let state_root = state_sync::state_root(&blockchain.get_state().lock().unwrap());
+        if tip.height == blockchain.get_latest_height() && tip.state_root != state_root {
+            report.issue(
+                IssueKind::Tip,
+                Some(tip.height),
+                format!("Tip names state root 0x{}, but the blocks leave 0x{}", hex::encode(tip.state_root), hex::encode(state_root)),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Sets aside the journals that don't parse and removes the blocks stored above the verified height
+///
+/// The store's tip must have moved down to the verified height already,
+/// as `Blockchain::set_block_store` moves it, so the blocks up to the tip
+/// stay a whole chain if the node stops halfway.
+pub fn repair(data_dir: &Path, report: &mut IntegrityReport) -> Result<()> {
+    for path in report.corrupt_files.clone() {
+        let aside = path.with_extension(format!("json.{}", CORRUPT_EXTENSION));
+        fs::rename(&path, &aside)?;
+        report.discarded_files.push(aside);
+    }
+    
+    let Ok(store) = BlockStore::open_existing(data_dir.join(BLOCKS_DIR)) else {
+        return Ok(());
+    };
+    for height in stored_heights(&store).into_iter().filter(|height| *height > report.verified_height) {
+        let block_hash = store.get_block(height).ok().flatten().and_then(|block| block.hash().ok());
+        store.remove_block(height)?;
+        report.discarded_blocks.push(DiscardedBlock { height, block_hash });
+    }
+    Ok(())
+}
+
+/// Parses a journal, if it exists, recording an issue if it doesn't parse
+fn check_journal<T: DeserializeOwned>(report: &mut IntegrityReport, path: &Path) -> Option<Vec<T>> {
+    if !path.exists() {
+        return None;
+    }
+    let parsed = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(entries) => Some(entries),
+        Err(e) => {
+            report.issue(IssueKind::Journal, None, format!("{}: {}", path.display(), e));
+            report.corrupt_files.push(path.to_path_buf());
+            None
+        }
+    }
+}
+
+/// Checks a stored block on its own, at the thoroughness of a mode
+fn check_block(block: &Block, height: u64, mode: IntegrityMode) -> std::result::Result<(), String> {
+    if block.header().height != height {
+        return Err(format!("Block stored at height {} is block {}", height, block.header().height));
+    }
+    if mode == IntegrityMode::Full {
+        block.validate().map_err(|e| format!("Block {}: {}", height, e))?;
+    }
+    Ok(())
+}
+
+/// Gets the heights of the blocks in a store, in ascending order
+fn stored_heights(store: &BlockStore) -> Vec<u64> {
+    let mut heights: Vec<u64> = fs::read_dir(store.dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".block")?.parse().ok())
+        .collect();
+    heights.sort_unstable();
+    heights
+}
\ No newline at end of file
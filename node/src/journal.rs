@@ -0,0 +1,159 @@
+//! Journal of the blocks a validating node produced
+//!
+//! A node that crashes after producing a block but before it's saved with
+//! the chain would, once restarted, produce a second, different block at
+//! the same height: equivocating, which gets validators slashed. So before
+//! a produced block is added to the chain or leaves the node, its height
+//! and hash are written to `production.json` in the data directory and
+//! synced to disk, then the block itself is.
+//!
+//! While the journal holds a block at the height the node would produce
+//! next, the node produces no other block there: it adds and announces the
+//! journaled block again if its body was saved and it still extends the
+//! tip, and otherwise waits for the height to be filled by another
+//! validator. Entries are dropped as their heights are finalized.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::block::Block;
+use ctb_core::BlockHash;
+
+/// Name of the file the journal is saved to, in the data directory
+pub const JOURNAL_FILE: &str = "production.json";
+
+/// A block the node produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProducedBlock {
+    /// Height of the block
+    pub height: u64,
+    
+    /// Hash of the block
+    pub block_hash: BlockHash,
+    
+    /// The block, once saved after its hash
+    pub block: Option<Block>,
+}
+
+/// Blocks the node produced at heights not finalized yet
+#[derive(Debug, Default)]
+pub struct ProductionJournal {
+    /// Produced blocks, by height
+    entries: BTreeMap<u64, ProducedBlock>,
+    
+    /// File the journal is saved to, once opened
+    path: Option<PathBuf>,
+}
+
+impl ProductionJournal {
+    /// Creates a journal kept in memory only, until it's opened
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Saves the journal to a file from now on, first loading what a previous run saved there
+    pub fn open(&mut self, path: PathBuf) -> io::Result<()> {
+        if path.exists() {
+            let saved: Vec<ProducedBlock> = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            for entry in saved {
+                self.entries.entry(entry.height).or_insert(entry);
+            }
+            println!("Loaded {} produced blocks from {}", self.entries.len(), path.display());
+        }
+        self.path = Some(path);
+        self.save()
+    }
+    
+    /// Gets the block produced at a height, if any
+    pub fn get(&self, height: u64) -> Option<&ProducedBlock> {
+        self.entries.get(&height)
+    }
+    
+    /// Records that a block is being produced at a height, before anything else is done with it
+    ///
+    /// Fails if a different block was produced at the height already.
+    pub fn record_hash(&mut self, height: u64, block_hash: BlockHash) -> io::Result<()> {
+        match self.entries.get(&height) {
+            Some(entry) if entry.block_hash == block_hash => return Ok(()),
+            Some(entry) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("block {} was already produced at height {}", entry.block_hash, height),
+                ))
+            }
+            None => {}
+        }
+        self.entries.insert(height, ProducedBlock { height, block_hash, block: None });
+        self.save()
+    }
+    
+    /// Saves a produced block whose hash was recorded
+    pub fn record_block(&mut self, block: &Block) -> io::Result<()> {
+        let height = block.header().height;
+        let block_hash = block.hash().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        match self.entries.get_mut(&height) {
+            Some(entry) if entry.block_hash == block_hash => entry.block = Some(block.clone()),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no block {} recorded at height {}", block_hash, height),
+                ))
+            }
+        }
+        self.save()
+    }
+    
+    /// Forgets the block produced at a height, which no other node has seen
+    pub fn discard(&mut self, height: u64) -> io::Result<()> {
+        if self.entries.remove(&height).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+    
+    /// Drops the blocks produced at heights up to the finalized one, returning how many were dropped
+    pub fn prune(&mut self, finalized_height: u64) -> io::Result<usize> {
+        let kept = self.entries.split_off(&(finalized_height + 1));
+        let pruned = std::mem::replace(&mut self.entries, kept).len();
+        if pruned > 0 {
+            self.save()?;
+        }
+        Ok(pruned)
+    }
+    
+    /// Saves the journal and syncs it to disk before returning
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&self.entries.values().collect::<Vec<_>>())?;
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        sync_dir(path)
+    }
+}
+
+/// Syncs the directory holding a file, so a rename into it survives a crash
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
\ No newline at end of file
@@ -3,22 +3,93 @@
 //! This module integrates the core blockchain, consensus engine, and
 //! networking layer to create a complete blockchain node.
 
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use std::time::{Duration, Instant};
 
-use core::block::Block;
-use core::chain::Blockchain;
-use core::transaction::Transaction;
-use core::{BlockchainError, Result};
+use ctb_core::block::Block;
+use ctb_core::chain::{Blockchain, ReorgRecord, SnapshotHandle};
+use ctb_core::confirmation::{ChainTip, ConfirmationStatus, DEFAULT_SAFE_CONFIRMATIONS};
+use ctb_core::fee_market::{EstimatedBlocks, FeeHistogram, FeeRate};
+use ctb_core::paging::{Page, PageRequest};
+use ctb_core::receipt::{IndexedLog, LogFilter, Receipt};
+use ctb_core::state_sync::{Snapshot, SnapshotInfo};
+use ctb_core::transaction::Transaction;
+use ctb_core::verified::VerifiedTxCache;
+use ctb_core::block_filter::BlockFilter;
+use ctb_core::units::Amount;
+use ctb_core::{Address, BlockchainError, Hash, Result, TxHash};
 
 use consensus::ConsensusEngine;
 use consensus::ConsensusParams;
 use consensus::finality::FinalityManager;
+use consensus::fork_choice::ForkChoice;
 use consensus::pos::PoSConsensus;
+use consensus::validator::Validator;
 
+use smartcontracts::{ContractEngine, ContractError, FunctionABI, GasConfig};
+use smartcontracts::evm::ExecutionStatus;
+use smartcontracts::inspect::DeploymentCursor;
+use smartcontracts::reader::ContractReader;
+use smartcontracts::solidity::CompileOptions;
+use smartcontracts::verification::{VerificationResult, VerifiedSource};
+use smartcontracts::tracer::StructLogger;
+use smartcontracts::Result as ContractResult;
+
+use serde::{Deserialize, Serialize};
+
+use wallet::api::{CallOutcome, ChainClient, GasEstimate, TransactionRecord};
+use wallet::filter_sync::{FilterSource, FilteredBlock};
+
+pub mod access;
+pub mod address_book;
+pub mod admin;
+pub mod block_sync;
+pub mod clock;
+pub mod connection;
+pub mod dev;
+pub mod dry_run;
+pub mod eth;
+pub mod events;
+pub mod filters;
+pub mod integrity;
+pub mod journal;
+pub mod message;
+pub mod metrics;
 pub mod network;
+pub mod policy;
+pub mod propagation;
+mod protocol;
+pub mod pruning;
+pub mod reload;
+pub mod rest;
+pub mod rpc;
+pub mod signer;
+#[cfg(feature = "testutil")]
+pub mod sim;
+pub mod snapshot_sync;
+pub mod subscriptions;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+pub mod watch;
+
+/// Most storage slots a single RPC returns
+pub const MAX_STORAGE_PAGE_SIZE: usize = 1024;
+
+/// Most contracts a single RPC lists
+pub const MAX_CONTRACT_PAGE_SIZE: usize = 256;
+
+/// Name of the file the chain's reorganization history is saved to, in the data directory
+pub const REORG_HISTORY_FILE: &str = "reorgs.json";
+
+/// Name of the file the finalized checkpoints are saved to, in the data directory
+pub const FINALITY_FILE: &str = "finality.json";
 
 /// Node configuration
+///
+/// Fields missing when deserializing take their default values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NodeConfig {
     /// Node's public key (identity)
     pub node_id: String,
@@ -35,8 +106,53 @@ pub struct NodeConfig {
     /// Whether this node is a validator
     pub is_validator: bool,
     
-    /// Validator's private key (if this is a validator node)
+    /// Validator's consensus key in hex, for the `local` signer backend (see `signer`)
     pub validator_key: Option<String>,
+    
+    /// Address of the validator this node produces blocks and votes for
+    ///
+    /// Without one, a validating node produces blocks for whichever
+    /// validator proposes each slot and doesn't vote for checkpoints.
+    pub validator_address: Option<String>,
+    
+    /// Chain ID reported to Ethereum tooling, see `eth`
+    pub chain_id: u64,
+    
+    /// JSON-RPC server configuration, see `rpc`
+    pub rpc_config: rpc::RpcConfig,
+    
+    /// Transaction sender banlist and allowlist, see `policy`
+    pub policy_config: policy::PolicyConfig,
+    
+    /// Addresses whose balance changes are announced, see `watch`
+    pub watch_config: watch::WatchConfig,
+    
+    /// Number of latest blocks whose receipts and logs are kept, see `pruning`
+    ///
+    /// `None` keeps them forever, as archival nodes do.
+    pub receipt_retention: Option<u64>,
+    
+    /// Most blocks undone to answer a query about a past state, see `Blockchain::state_at`
+    pub max_state_depth: u64,
+    
+    /// Most transactions whose verified signatures are remembered, see `ctb_core::verified`
+    pub verified_tx_cache_size: usize,
+    
+    /// Most verbose level of messages logged, such as `info`
+    ///
+    /// `None` leaves the level as it is. See `admin_setLogLevel`.
+    pub log_level: Option<String>,
+    
+    /// How the data directory is checked at startup and whether it's repaired, see `integrity`
+    pub integrity_config: integrity::IntegrityConfig,
+    
+    /// Where the consensus key blocks and votes are signed with is kept, see `signer`
+    pub signer_config: signer::SignerConfig,
+    
+    /// Whether the node runs a development chain of its own, making blocks on demand, see `dev`
+    ///
+    /// The node must be created with the chain `dev::genesis` makes.
+    pub dev_mode: bool,
 }
 
 impl Default for NodeConfig {
@@ -48,12 +164,25 @@ impl Default for NodeConfig {
             consensus_params: ConsensusParams::default(),
             is_validator: false,
             validator_key: None,
+            validator_address: None,
+            chain_id: eth::DEFAULT_CHAIN_ID,
+            rpc_config: rpc::RpcConfig::default(),
+            policy_config: policy::PolicyConfig::default(),
+            watch_config: watch::WatchConfig::default(),
+            receipt_retention: None,
+            max_state_depth: ctb_core::chain::DEFAULT_MAX_STATE_DEPTH,
+            verified_tx_cache_size: ctb_core::verified::DEFAULT_CAPACITY,
+            log_level: None,
+            integrity_config: integrity::IntegrityConfig::default(),
+            signer_config: signer::SignerConfig::default(),
+            dev_mode: false,
         }
     }
 }
 
 /// Node state
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NodeState {
     /// Node is initializing
     Initializing,
@@ -82,58 +211,261 @@ pub struct Node {
     /// Finality manager
     finality: Arc<Mutex<FinalityManager>>,
     
+    /// Active validator set and validator performance metrics
+    pos: Arc<Mutex<PoSConsensus>>,
+    
     /// Network manager
     network: Arc<Mutex<network::NetworkManager>>,
     
-    /// Current node state
-    state: NodeState,
+    /// Smart contract engine, shared with the blockchain for block execution
+    contracts: Arc<Mutex<ContractEngine>>,
+    
+    /// Read handle to the contract engine for queries
+    contract_reader: ContractReader,
+    
+    /// Latest state snapshot, which queries run against
+    snapshots: SnapshotHandle,
+    
+    /// Transactions whose signatures have verified, shared with block validation
+    verified_txs: Arc<VerifiedTxCache>,
+    
+    /// Live subscriptions of WebSocket clients
+    subscriptions: Arc<subscriptions::SubscriptionManager>,
+    
+    /// Which senders' transactions are admitted to the mempool
+    policy: Arc<policy::AdmissionPolicy>,
+    
+    /// Bus the node's events are published on
+    events: events::EventBus,
+    
+    /// Addresses whose balance changes are announced
+    watcher: Arc<watch::AddressWatcher>,
+    
+    /// Compact filters of the chain's blocks, served to light wallets
+    filters: Arc<filters::FilterIndex>,
+    
+    /// Metrics served to Prometheus
+    metrics: Arc<metrics::Metrics>,
+    
+    /// State snapshots served to syncing peers
+    snapshot_server: Arc<snapshot_sync::SnapshotServer>,
+    
+    /// Current node state, shared with the node loop and the RPC server
+    state: Arc<RwLock<NodeState>>,
+    
+    /// JSON-RPC server, while the node is running
+    rpc_server: Option<rpc::RpcServer>,
+    
+    /// JSON-RPC server of the `admin_` methods, while the node is running
+    admin_server: Option<rpc::RpcServer>,
+    
+    /// Whether the node produces blocks, which operators can change at runtime
+    validating: Arc<AtomicBool>,
+    
+    /// Handling of peers' messages and the node loop's work, see `protocol`
+    protocol: protocol::Protocol,
+    
+    /// Block production on demand, in dev mode
+    dev: Option<dev::DevApi>,
     
-    /// Mempool (pending transactions)
-    mempool: Vec<Transaction>,
+    /// Applies the configuration file again while the node runs, see `reload`
+    reloader: reload::ConfigReloader,
     
-    /// Last block production attempt time
-    last_block_attempt: Instant,
+    /// When the node was started
+    started_at: Instant,
 }
 
 impl Node {
     /// Creates a new node with the given configuration
-    pub fn new(config: NodeConfig, blockchain: Blockchain) -> Self {
+    pub fn new(config: NodeConfig, mut blockchain: Blockchain) -> Self {
+        // Create the contract engine and let the blockchain execute contract transactions with it
+        let mut engine = ContractEngine::new(GasConfig::default());
+        engine.set_chain_id(config.chain_id);
+        let contract_reader = engine.reader();
+        let contracts = Arc::new(Mutex::new(engine));
+        blockchain.set_contract_executor(contracts.clone());
+        blockchain.set_max_state_depth(config.max_state_depth);
+        
+        // Queries run against snapshots so they don't wait for blocks being applied
+        let snapshots = blockchain.snapshot_handle();
+        let verified_txs = blockchain.verified_tx_cache();
+        verified_txs.set_capacity(config.verified_tx_cache_size);
+        
+        // Subscribers are notified as blocks are added and rolled back
+        let subscriptions = Arc::new(subscriptions::SubscriptionManager::new());
+        blockchain.add_listener(subscriptions.clone());
+        
+        // So are watchers of addresses whose balances the blocks change
+        let events = events::EventBus::new();
+        let watchlist_path = std::path::Path::new(&config.data_dir).join(watch::WATCHLIST_FILE);
+        let watcher = Arc::new(watch::AddressWatcher::new(&config.watch_config, Some(watchlist_path), events.clone(), subscriptions.clone()));
+        blockchain.add_listener(watcher.clone());
+        
+        // And the index of the blocks' filters
+        let filters = Arc::new(filters::FilterIndex::new(&blockchain));
+        blockchain.add_listener(filters.clone());
         let blockchain = Arc::new(Mutex::new(blockchain));
         
+        // A development node produces every block, for the development validator
+        let validator_address = if config.dev_mode {
+            Some(dev::validator().address.to_string())
+        } else {
+            config.validator_address.clone()
+        };
+        
         // Create the consensus engine
-        let consensus = ConsensusEngine::new(blockchain.clone(), config.consensus_params.clone());
+        let mut consensus = ConsensusEngine::new(blockchain.clone(), config.consensus_params.clone());
+        if let Some(address) = &validator_address {
+            consensus.set_local_validator(address.clone());
+        }
+        if config.dev_mode {
+            consensus.set_signer(Arc::new(dev::signer()));
+        }
         let consensus = Arc::new(Mutex::new(consensus));
         
         // Create the finality manager
         let finality = FinalityManager::new(config.consensus_params.clone());
         let finality = Arc::new(Mutex::new(finality));
         
+        // Competing branches are weighed by stake, never reverting finalized blocks
+        blockchain.lock().unwrap().set_fork_choice(Arc::new(ForkChoice::new(finality.clone())));
+        
+        // Track validator performance
+        let pos = Arc::new(Mutex::new(PoSConsensus::new(config.consensus_params.clone())));
+        
         // Create the network manager
         let mut network_config = config.network_config.clone();
         network_config.node_id = config.node_id.clone();
         let network = network::NetworkManager::new(network_config);
         let network = Arc::new(Mutex::new(network));
         
+        let banlist_path = std::path::Path::new(&config.data_dir).join(policy::BANLIST_FILE);
+        let policy = Arc::new(policy::AdmissionPolicy::new(&config.policy_config, Some(banlist_path)));
+        let validating = Arc::new(AtomicBool::new(config.is_validator || config.dev_mode));
+        let metrics = Arc::new(metrics::Metrics::new());
+        let snapshot_server = Arc::new(snapshot_sync::SnapshotServer::new());
+        
+        let protocol = protocol::Protocol {
+            blockchain: blockchain.clone(),
+            consensus: consensus.clone(),
+            finality: finality.clone(),
+            pos: pos.clone(),
+            network: network.clone(),
+            policy: policy.clone(),
+            verified_txs: verified_txs.clone(),
+            events: events.clone(),
+            metrics: metrics.clone(),
+            snapshot_server: snapshot_server.clone(),
+            filters: filters.clone(),
+            validating: validating.clone(),
+            validator_address,
+            checkpoint_interval: config.consensus_params.checkpoint_interval,
+            receipt_retention: config.receipt_retention,
+            fetched_receipts: Arc::new(Mutex::new(pruning::FetchedReceipts::new())),
+            journal: Arc::new(Mutex::new(journal::ProductionJournal::new())),
+            propagation: Arc::new(Mutex::new(propagation::PropagationTracker::new())),
+            clock: Arc::new(clock::SystemClock),
+            sync: Arc::new(Mutex::new(block_sync::BlockSync::new())),
+            status_sent: Arc::new(Mutex::new((ctb_core::BlockHash::default(), 0))),
+            dev_mode: config.dev_mode,
+        };
+        let dev = config.dev_mode.then(|| dev::DevApi::new(protocol.clone(), config.consensus_params.clone()));
+        
+        let reloader = reload::ConfigReloader::new(
+            config.clone(),
+            blockchain.clone(),
+            consensus.clone(),
+            network.clone(),
+            verified_txs.clone(),
+        );
+        
         Self {
             config,
             blockchain,
             consensus,
             finality,
+            pos,
             network,
-            state: NodeState::Initializing,
-            mempool: Vec::new(),
-            last_block_attempt: Instant::now(),
+            contracts,
+            contract_reader,
+            snapshots,
+            verified_txs,
+            subscriptions,
+            policy,
+            events,
+            watcher,
+            filters,
+            metrics,
+            snapshot_server,
+            state: Arc::new(RwLock::new(NodeState::Initializing)),
+            rpc_server: None,
+            admin_server: None,
+            validating,
+            protocol,
+            dev,
+            reloader,
+            started_at: Instant::now(),
         }
     }
     
     /// Starts the node
+    // Nothing else locks the network until it has started, so holding it while it starts is safe
+    #[allow(clippy::await_holding_lock)]
     pub async fn start(&mut self) -> Result<()> {
         println!("Starting node {}...", self.config.node_id);
+        self.started_at = Instant::now();
+        
+        if let Some(level) = &self.config.log_level {
+            let level = reload::parse_log_level(level).map_err(|e| BlockchainError::StateError(e.to_string()))?;
+            log::set_max_level(level);
+        }
         
-        // Initialize the consensus engine
+        // Restore the bans made while the node last ran
+        self.policy.load().map_err(|e| BlockchainError::StateError(format!("Failed to load banlist: {}", e)))?;
+        
+        // Keep watching the addresses watched while the node last ran, announcing their activity
+        self.watcher.load().map_err(|e| BlockchainError::StateError(format!("Failed to load watch list: {}", e)))?;
+        if let Some(url) = &self.config.watch_config.webhook_url {
+            let endpoint = url.parse::<watch::WebhookEndpoint>().map_err(BlockchainError::StateError)?;
+            let backoff = Duration::from_millis(self.config.watch_config.webhook_backoff_ms);
+            self.watcher.start_webhook(endpoint, self.config.watch_config.webhook_max_attempts, backoff);
+        }
+        
+        // A development chain starts from its genesis block every run, so nothing of it is saved
+        if !self.config.dev_mode {
+            // Trust nothing saved while the node last ran before it's checked
+            self.open_data_dir()?;
+            
+            // Never produce a second block at a height produced at before a crash
+            let journal = std::path::Path::new(&self.config.data_dir).join(journal::JOURNAL_FILE);
+            self.protocol.journal.lock().unwrap().open(journal)
+                .map_err(|e| BlockchainError::StateError(format!("Failed to open production journal: {}", e)))?;
+            
+            // Keep the reorganization history across restarts
+            let reorg_log = std::path::Path::new(&self.config.data_dir).join(REORG_HISTORY_FILE);
+            self.blockchain.lock().unwrap().set_reorg_log(reorg_log)?;
+        }
+        
+        // Initialize the consensus engine, signing with the configured signer unless one was set
         {
             let mut consensus = self.consensus.lock().unwrap();
             consensus.initialize()?;
+            if self.config.is_validator && consensus.signer().is_none() {
+                let signer = signer::open(&self.config.signer_config, self.config.validator_key.as_deref(), None)
+                    .map_err(|e| BlockchainError::StateError(format!("Failed to open the signer: {}", e)))?;
+                if let Some(signer) = signer {
+                    consensus.set_signer(signer);
+                }
+            }
+        }
+        
+        // Start tracking the validators registered in the current state
+        {
+            let validators = self.snapshots.latest().state.get_validators()
+                .into_iter()
+                .map(|(info, stake)| Validator::from_registry(info, stake))
+                .collect();
+            self.pos.lock().unwrap().update_validator_set(validators);
         }
         
         // Initialize the finality manager with the genesis block
@@ -145,16 +477,53 @@ impl Node {
             
             let mut finality = self.finality.lock().unwrap();
             finality.initialize_with_genesis(genesis)?;
+            
+            // Never revert checkpoints finalized while the node last ran
+            if !self.config.dev_mode {
+                finality.set_store(std::path::Path::new(&self.config.data_dir).join(FINALITY_FILE))?;
+            }
         }
         
-        // Start the network manager
-        {
+        // Start the network manager, unless the node keeps to a development chain of its own
+        if !self.config.dev_mode {
             let mut network = self.network.lock().unwrap();
-            network.start().await?;
+            if let Some(identity_key) = &self.config.network_config.identity_key {
+                let identity = address_book::NodeIdentity::from_hex(identity_key)
+                    .map_err(|e| BlockchainError::StateError(e.to_string()))?;
+                network.set_identity(identity);
+            }
+            network.start().await.map_err(|e| BlockchainError::StateError(e.to_string()))?;
+        }
+        
+        // Serve JSON-RPC
+        if self.config.rpc_config.enabled {
+            let server = rpc::RpcServer::start(&self.config.rpc_config, self.rpc_handler()).await
+                .map_err(|e| BlockchainError::StateError(format!("Failed to start RPC server: {}", e)))?;
+            println!("Serving JSON-RPC on {}", server.local_addr());
+            self.rpc_server = Some(server);
+            
+            if self.config.rpc_config.admin_enabled {
+                let server = rpc::RpcServer::start_admin(&self.config.rpc_config, self.admin_handler()).await
+                    .map_err(|e| BlockchainError::StateError(format!("Failed to start admin RPC server: {}", e)))?;
+                println!("Serving admin JSON-RPC on {}", server.local_addr());
+                self.admin_server = Some(server);
+            }
+        }
+        
+        if self.config.dev_mode {
+            println!("Running a development chain; its accounts' keys are public, never send real funds to them:");
+            for account in dev::accounts() {
+                println!("  {} {}", account.address, account.secret_key);
+            }
+        }
+        
+        // Add and announce again a block produced before a crash but never saved with the chain
+        if self.is_validating() {
+            self.protocol.resume_production()?;
         }
         
         // Set the node state to syncing
-        self.state = NodeState::Syncing;
+        *self.state.write().unwrap() = NodeState::Syncing;
         
         // Start the main node loop
         self.run_node_loop();
@@ -164,11 +533,8 @@ impl Node {
     
     /// Runs the main node loop
     fn run_node_loop(&mut self) {
-        let blockchain = self.blockchain.clone();
-        let consensus = self.consensus.clone();
-        let finality = self.finality.clone();
-        let network = self.network.clone();
-        let config = self.config.clone();
+        let protocol = self.protocol.clone();
+        let state = self.state.clone();
         
         tokio::spawn(async move {
             let mut block_interval = tokio::time::interval(Duration::from_secs(1));
@@ -176,66 +542,607 @@ impl Node {
             loop {
                 block_interval.tick().await;
                 
-                // Try to produce a block if we're a validator
-                if config.is_validator {
-                    let mut consensus_guard = consensus.lock().unwrap();
-                    if let Ok(Some(new_block)) = consensus_guard.try_produce_block() {
-                        // We produced a new block
-                        println!("Produced new block: {}", new_block);
-                        
-                        // Add the block to the blockchain
-                        let mut blockchain_guard = blockchain.lock().unwrap();
-                        if let Err(e) = blockchain_guard.add_block(new_block.clone()) {
-                            eprintln!("Failed to add produced block: {}", e);
-                            continue;
-                        }
-                        
-                        // Broadcast the new block to the network
-                        // In a real implementation, we would serialize and broadcast the block here
-                    }
+                if *state.read().unwrap() == NodeState::ShuttingDown {
+                    break;
                 }
                 
-                // Process incoming blocks and transactions
-                // In a real implementation, we would handle incoming messages here
-                
-                // Check for finality
-                // In a real implementation, we would check for block finality here
+                // Produce a block if it's time, and catch up with peers ahead
+                protocol.tick();
             }
         });
     }
     
+    /// Produces a block if the node is validating and it's time at `now`, a Unix timestamp in seconds
+    ///
+    /// The node loop does this every second with its clock's time. The
+    /// block is added to the chain and announced to the peers; see
+    /// `ConsensusEngine::try_produce_block_at` for when one is produced.
+    pub fn try_produce_block_at(&self, now: u64) -> Result<Option<Arc<Block>>> {
+        self.protocol.produce_block_at(now)
+    }
+    
+    /// Does what the node loop does every second, at the node's clock's time
+    ///
+    /// Produces a block if it's time, starts syncing with the peer furthest
+    /// ahead, if any, and prunes receipts and logs past the retention and
+    /// the production journal's finalized blocks; see `protocol`.
+    pub fn tick(&self) {
+        self.protocol.tick();
+    }
+    
+    /// Handles a message from a connected peer, sending any replies through the network manager
+    pub fn handle_message(&self, peer_id: &str, message: message::NetworkMessage) {
+        self.protocol.handle_message(peer_id, message);
+    }
+    
+    /// Asks a peer for the receipts of the block at a height, which this node pruned
+    ///
+    /// Once they arrive they can be read with `fetched_receipts`.
+    pub fn request_receipts(&self, peer_id: &str, height: u64) {
+        self.protocol.request_receipts(peer_id, height);
+    }
+    
+    /// Gets what happened to a recent block at this node and when, see `propagation`
+    pub fn propagation_timeline(&self, block_hash: &ctb_core::BlockHash) -> Option<propagation::BlockTimeline> {
+        self.protocol.propagation.lock().unwrap().timeline(block_hash).cloned()
+    }
+    
+    /// Gets the receipts of a pruned block fetched from a peer, see `request_receipts`
+    pub fn fetched_receipts(&self, height: u64) -> Option<Vec<Receipt>> {
+        self.protocol.fetched_receipts.lock().unwrap().get(height).map(<[Receipt]>::to_vec)
+    }
+    
+    /// Reads the time from a clock rather than the system's, from the next tick on
+    ///
+    /// Must be set before the node starts to affect the node loop.
+    pub fn set_clock(&mut self, clock: Arc<dyn clock::Clock>) {
+        self.network.lock().unwrap().set_clock(clock.clone());
+        self.protocol.clock = clock;
+    }
+    
+    /// Sends messages to peers through a transport, see `network::Transport`
+    pub fn set_transport(&self, transport: Arc<dyn network::Transport>) {
+        self.network.lock().unwrap().set_transport(transport);
+    }
+    
+    /// Checks whether the node is producing blocks
+    pub fn is_validating(&self) -> bool {
+        self.validating.load(Ordering::SeqCst)
+    }
+    
     /// Adds a transaction to the mempool
+    ///
+    /// The consensus engine's pending pool is the node's only mempool.
+    /// Transactions from senders the admission policy refuses are rejected;
+    /// those admitted are relayed to the peers.
     pub fn add_transaction(&mut self, transaction: Transaction) -> Result<()> {
-        // Validate the transaction
-        transaction.validate()?;
-        
-        // Add to mempool
-        self.mempool.push(transaction.clone());
-        
-        // Add to consensus engine
-        {
-            let mut consensus = self.consensus.lock().unwrap();
-            consensus.add_transaction(transaction);
+        self.protocol.add_transaction(Arc::new(transaction), None)?;
+        if let Some(dev) = &self.dev {
+            dev.transaction_added();
         }
-        
         Ok(())
     }
     
+    /// Makes a block with the transactions waiting, one slot after the latest block
+    ///
+    /// Only development nodes make blocks on demand, see `dev`.
+    pub fn dev_mine_block(&self) -> Result<Arc<Block>> {
+        match &self.dev {
+            Some(dev) => dev.mine_block(),
+            None => Err(BlockchainError::StateError("The node isn't in dev mode".to_string())),
+        }
+    }
+    
+    /// Checks whether a transaction is waiting in the mempool
+    pub fn is_pending(&self, id: &TxHash) -> bool {
+        self.consensus.lock().unwrap().mempool().contains(id)
+    }
+    
+    /// Gets the fees of the transactions waiting in the mempool
+    pub fn fee_histogram(&self) -> FeeHistogram {
+        self.consensus.lock().unwrap().mempool().fee_histogram().clone()
+    }
+    
+    /// Estimates how many blocks a transaction paying a rate waits to be included
+    pub fn estimate_inclusion(&self, rate: FeeRate) -> EstimatedBlocks {
+        self.consensus.lock().unwrap().estimate_inclusion(rate)
+    }
+    
+    /// Checks whether the block would be added to the chain, without adding it
+    ///
+    /// See `dry_run`; the state the block leaves is compared with
+    /// `expected_state_root` if given.
+    pub fn validate_block_dry_run(&self, block: &Block, expected_state_root: Option<ctb_core::Hash>) -> dry_run::ValidationReport {
+        self.block_validator().validate(block, expected_state_root)
+    }
+    
+    /// Gets the checker of blocks against this node's chain, see `dry_run`
+    pub fn block_validator(&self) -> dry_run::BlockValidator {
+        dry_run::BlockValidator::new(self.blockchain.clone(), self.consensus.clone())
+    }
+    
+    /// Adds a block received from a peer to the chain
+    ///
+    /// The admission policy doesn't apply: blocks are accepted whoever sent
+    /// their transactions, which then leave the mempool.
+    pub fn import_block(&self, block: impl Into<Arc<Block>>) -> Result<()> {
+        self.protocol.import_block(block.into())
+    }
+    
+    /// Switches the chain to a competing branch that forks off after `fork_height`
+    ///
+    /// See `Blockchain::reorganize`. The transactions the switch drops are
+    /// returned to the mempool where the admission policy and the mempool
+    /// accept them, and a `ReorgOccurred` event is published with how many were.
+    pub fn reorganize(&self, fork_height: u64, blocks: Vec<Arc<Block>>) -> Result<ReorgRecord> {
+        self.protocol.reorganize(fork_height, blocks)
+    }
+    
+    /// Gets the bus the node's events are published on
+    pub fn event_bus(&self) -> &events::EventBus {
+        &self.events
+    }
+    
+    /// Gets the metrics served to Prometheus
+    pub fn metrics(&self) -> Arc<metrics::Metrics> {
+        self.metrics.clone()
+    }
+    
+    /// Takes a snapshot of the state after the latest block and serves it to peers
+    pub fn create_snapshot(&self) -> Result<SnapshotInfo> {
+        let snapshot = self.snapshots.latest();
+        let block_hash = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let block = blockchain.get_block_by_height(snapshot.block_height).ok_or_else(|| {
+                BlockchainError::StateError(format!("Missing block at height {}", snapshot.block_height))
+            })?;
+            block.hash()?
+        };
+        
+        // Encoding the state can take a while, so it's done without the chain locked
+        let snapshot = Snapshot::new(snapshot.block_height, block_hash, &snapshot.state)?;
+        Ok(self.snapshot_server.add(snapshot))
+    }
+    
+    /// Gets the server answering peers' snapshot requests
+    pub fn snapshot_server(&self) -> Arc<snapshot_sync::SnapshotServer> {
+        self.snapshot_server.clone()
+    }
+    
+    /// Gets the admission policy deciding whose transactions enter the mempool
+    pub fn admission_policy(&self) -> Arc<policy::AdmissionPolicy> {
+        self.policy.clone()
+    }
+    
     /// Gets the current blockchain height
     pub fn get_height(&self) -> u64 {
         let blockchain = self.blockchain.lock().unwrap();
         blockchain.get_latest_height()
     }
     
+    /// Gets the contract event logs matching a filter
+    ///
+    /// Fails with `BlockchainError::Pruned` for ranges whose logs were pruned.
+    #[deprecated(note = "use `list_logs` to page through them")]
+    pub fn get_logs(&self, filter: &LogFilter) -> Result<Vec<IndexedLog>> {
+        let blockchain = self.blockchain.lock().unwrap();
+        blockchain.get_logs(filter)
+    }
+    
+    /// Gets a page of the contract event logs matching a filter, see `Blockchain::list_logs`
+    pub fn list_logs(&self, filter: &LogFilter, request: &PageRequest) -> Result<Page<IndexedLog>> {
+        let blockchain = self.blockchain.lock().unwrap();
+        blockchain.list_logs(filter, request)
+    }
+    
+    /// Gets the watch list of addresses whose balance changes are announced
+    pub fn watcher(&self) -> Arc<watch::AddressWatcher> {
+        self.watcher.clone()
+    }
+    
+    /// Gets the index of the compact block filters served to light wallets
+    pub fn filter_index(&self) -> Arc<filters::FilterIndex> {
+        self.filters.clone()
+    }
+    
+    /// Returns the subscription manager that WebSocket connections register with
+    pub fn subscriptions(&self) -> Arc<subscriptions::SubscriptionManager> {
+        self.subscriptions.clone()
+    }
+    
+    /// Calls a contract function against the current state without a transaction
+    ///
+    /// Runs against the state after the latest block. Nothing is persisted.
+    /// If the call reverts the error carries the decoded revert reason.
+    pub fn call_contract(
+        &self,
+        contract_address: &str,
+        function_signature: &[u8; 4],
+        arguments: &[u8],
+        sender: &str,
+        gas_limit: u64,
+    ) -> ContractResult<Vec<u8>> {
+        let snapshot = self.snapshots.latest();
+        self.contract_reader.call_static(contract_address, function_signature, arguments, sender, gas_limit, &snapshot)
+    }
+    
+    /// Estimates the gas a transaction needs; handler for the `contract_estimateGas` RPC
+    ///
+    /// Contract transactions are executed against the state after the latest
+    /// block with up to the block gas limit; nothing is persisted. If the
+    /// transaction would revert the error carries the decoded revert reason.
+    pub fn estimate_gas(&self, tx: &Transaction) -> ContractResult<u64> {
+        let gas_cap = self.blockchain.lock().unwrap().get_block_gas_limit();
+        let snapshot = self.snapshots.latest();
+        self.contract_reader.estimate_gas(tx, gas_cap, &snapshot)
+    }
+    
+    /// Traces a call to a contract against the current state; handler for the `debug_traceCall` RPC
+    ///
+    /// Nothing is persisted. Returns a JSON object with the gas used, whether
+    /// the call failed (and why), the return data and the executed steps as
+    /// recorded by a `StructLogger`. A call that reverts or runs out of gas
+    /// still returns its trace; only a call that can't start fails.
+    pub fn debug_trace_call(
+        &self,
+        contract_address: &str,
+        input: &[u8],
+        sender: &str,
+        gas_limit: u64,
+    ) -> ContractResult<serde_json::Value> {
+        let snapshot = self.snapshots.latest();
+        let mut logger = StructLogger::default();
+        let result = self.contract_reader.trace_call(contract_address, input, sender, gas_limit, &snapshot, &mut logger);
+        
+        let (gas, failed, error, return_value) = match result {
+            Ok(result) => (
+                result.gas_used,
+                result.status == ExecutionStatus::Revert,
+                None,
+                result.return_data,
+            ),
+            Err(e @ ContractError::StateError(_)) => return Err(e),
+            Err(e) => (gas_limit, true, Some(e.to_string()), Vec::new()),
+        };
+        
+        Ok(serde_json::json!({
+            "gas": gas,
+            "failed": failed,
+            "error": error,
+            "return_value": format!("0x{}", hex::encode(return_value)),
+            "truncated": logger.is_truncated(),
+            "struct_logs": logger.into_steps(),
+        }))
+    }
+    
+    /// Gets a deployed contract; handler for the contract query RPC
+    ///
+    /// Returns a JSON object with the contract's creator, deployment height,
+    /// code size, interface and whether its source is verified, or `None`
+    /// if no contract is deployed at the address.
+    pub fn get_contract(&self, address: &str) -> Option<serde_json::Value> {
+        let snapshot = self.snapshots.latest();
+        let contract = self.contract_reader.get_contract(address, &snapshot)?;
+        let verified = self.contract_reader.get_verified_source(address, &snapshot);
+        
+        Some(serde_json::json!({
+            "address": contract.address,
+            "creator": contract.creator,
+            "deployed_at": contract.deployed_at,
+            "code_size": contract.bytecode.len(),
+            "abi": contract.abi,
+            "events": contract.events,
+            "verified": verified.is_some(),
+            "contract_name": verified.map(|source| source.contract_name.clone()),
+        }))
+    }
+    
+    /// Reads a slot of a contract's storage; handler for the `contract_getStorageAt` RPC
+    ///
+    /// Returns the value as 32-byte hex, which is zero for unset slots.
+    pub fn get_storage_at(&self, address: &str, key: &[u8; 32]) -> String {
+        let snapshot = self.snapshots.latest();
+        let value = self.contract_reader.storage_at(address, key, &snapshot).unwrap_or_else(|| vec![0u8; 32]);
+        format!("0x{}", hex::encode(value))
+    }
+    
+    /// Dumps a deployed contract; handler for the `contract_dump` RPC
+    ///
+    /// Includes up to `storage_limit` storage slots, capped at
+    /// `MAX_STORAGE_PAGE_SIZE`; the rest can be listed with `get_storage_entries`
+    /// from the dump's `next_key`.
+    pub fn dump_contract(&self, address: &str, storage_limit: usize) -> ContractResult<serde_json::Value> {
+        let snapshot = self.snapshots.latest();
+        let dump = self.contract_reader.dump_contract(address, storage_limit.min(MAX_STORAGE_PAGE_SIZE), &snapshot)?;
+        
+        serde_json::to_value(dump).map_err(|e| ContractError::StateError(e.to_string()))
+    }
+    
+    /// Lists a contract's storage from `start_key` on; handler for the `contract_getStorageEntries` RPC
+    ///
+    /// Returns up to `limit` slots, capped at `MAX_STORAGE_PAGE_SIZE`, and the key to continue from.
+    pub fn get_storage_entries(
+        &self,
+        address: &str,
+        start_key: Option<&[u8; 32]>,
+        limit: usize,
+    ) -> ContractResult<serde_json::Value> {
+        let snapshot = self.snapshots.latest();
+        let page = self.contract_reader.storage_entries(address, start_key, limit.min(MAX_STORAGE_PAGE_SIZE), &snapshot)?;
+        
+        serde_json::to_value(page).map_err(|e| ContractError::StateError(e.to_string()))
+    }
+    
+    /// Lists the contracts an account deployed; handler for the `contract_getContractsByCreator` RPC
+    ///
+    /// Returns up to `limit` contracts, capped at `MAX_CONTRACT_PAGE_SIZE`,
+    /// ordered by deployment height and then address, and the cursor to continue from.
+    pub fn get_contracts_by_creator(
+        &self,
+        creator: &str,
+        start: Option<&DeploymentCursor>,
+        limit: usize,
+    ) -> ContractResult<serde_json::Value> {
+        let snapshot = self.snapshots.latest();
+        let page = self.contract_reader.get_contracts_by_creator(creator, start, limit.min(MAX_CONTRACT_PAGE_SIZE), &snapshot);
+        
+        serde_json::to_value(page).map_err(|e| ContractError::StateError(e.to_string()))
+    }
+    
+    /// Lists the contracts deployed in a block; handler for the `contract_getContractsInBlock` RPC
+    ///
+    /// Returns up to `limit` contracts, capped at `MAX_CONTRACT_PAGE_SIZE`, in
+    /// address order from `start` on, and the cursor to continue from.
+    pub fn get_contracts_in_block(
+        &self,
+        height: u64,
+        start: Option<&str>,
+        limit: usize,
+    ) -> ContractResult<serde_json::Value> {
+        let snapshot = self.snapshots.latest();
+        let page = self.contract_reader.get_contracts_in_block(height, start, limit.min(MAX_CONTRACT_PAGE_SIZE), &snapshot);
+        
+        serde_json::to_value(page).map_err(|e| ContractError::StateError(e.to_string()))
+    }
+    
+    /// Lists all deployed contracts; handler for the `contract_listContracts` RPC
+    ///
+    /// Paginated like `get_contracts_by_creator`.
+    pub fn list_contracts(&self, start: Option<&DeploymentCursor>, limit: usize) -> ContractResult<serde_json::Value> {
+        let snapshot = self.snapshots.latest();
+        let page = self.contract_reader.list_contracts(start, limit.min(MAX_CONTRACT_PAGE_SIZE), &snapshot);
+        
+        serde_json::to_value(page).map_err(|e| ContractError::StateError(e.to_string()))
+    }
+    
+    /// Verifies the Solidity source of a deployed contract
+    ///
+    /// On success the source is kept by this node and served by
+    /// `get_verified_source`; it isn't part of the chain.
+    pub fn verify_contract_source(
+        &self,
+        address: &str,
+        source: &str,
+        compile_options: &CompileOptions,
+    ) -> ContractResult<VerificationResult> {
+        let snapshot = self.snapshots.latest();
+        self.contract_reader.verify_source(address, source, compile_options, &snapshot)
+    }
+    
+    /// Gets the verified source of a deployed contract
+    pub fn get_verified_source(&self, address: &str) -> Option<Arc<VerifiedSource>> {
+        let snapshot = self.snapshots.latest();
+        self.contract_reader.get_verified_source(address, &snapshot)
+    }
+    
     /// Gets the latest finalized block height
     pub fn get_finalized_height(&self) -> u64 {
         let finality = self.finality.lock().unwrap();
         finality.get_latest_finalized_height()
     }
     
+    /// Gets the smart contract engine
+    pub fn get_contract_engine(&self) -> Arc<Mutex<ContractEngine>> {
+        self.contracts.clone()
+    }
+    
+    /// Gets a client the wallet can use to query this node
+    pub fn wallet_client(&self) -> Arc<dyn ChainClient> {
+        Arc::new(self.node_client())
+    }
+    
+    /// Gets a source of block filters a light wallet can sync with, see `wallet::filter_sync`
+    pub fn filter_client(&self) -> Arc<dyn FilterSource> {
+        Arc::new(self.node_client())
+    }
+    
+    fn node_client(&self) -> NodeClient {
+        NodeClient {
+            blockchain: self.blockchain.clone(),
+            consensus: self.consensus.clone(),
+            contract_reader: self.contract_reader.clone(),
+            snapshots: self.snapshots.clone(),
+            verified_txs: self.verified_txs.clone(),
+            policy: self.policy.clone(),
+            filters: self.filters.clone(),
+            finality: self.finality.clone(),
+        }
+    }
+    
+    /// Gets a handler for the Ethereum JSON-RPC methods this node supports
+    pub fn eth_api(&self) -> eth::EthApi {
+        eth::EthApi::new(
+            self.blockchain.clone(),
+            self.consensus.clone(),
+            self.contract_reader.clone(),
+            self.snapshots.clone(),
+            self.policy.clone(),
+            self.config.chain_id,
+        )
+    }
+    
+    /// Gets a handler for the block explorer REST API, see `rest`
+    pub fn rest_api(&self) -> rest::RestApi {
+        rest::RestApi::new(
+            self.blockchain.clone(),
+            self.snapshots.clone(),
+            self.pos.clone(),
+            self.wallet_client(),
+        )
+    }
+    
+    /// Gets a handler for all JSON-RPC methods this node serves, see `rpc`
+    pub fn rpc_handler(&self) -> rpc::RpcHandler {
+        let handler = rpc::RpcHandler::new(
+            self.config.node_id.clone(),
+            self.config.chain_id,
+            self.state.clone(),
+            self.blockchain.clone(),
+            self.finality.clone(),
+            self.network.clone(),
+            self.metrics.clone(),
+            self.filters.clone(),
+            self.wallet_client(),
+            self.eth_api(),
+            self.rest_api(),
+        );
+        let handler = if self.config.rpc_config.debug_enabled {
+            handler.with_debug(self.protocol.propagation.clone(), self.block_validator())
+        } else {
+            handler
+        };
+        match &self.dev {
+            Some(dev) => handler.with_dev(dev.clone()),
+            None => handler,
+        }
+    }
+    
+    /// Gets a handler for the admin JSON-RPC methods, see `admin`
+    pub fn admin_api(&self) -> admin::AdminApi {
+        admin::AdminApi::new(
+            self.config.node_id.clone(),
+            self.config.chain_id,
+            self.config.data_dir.clone(),
+            self.started_at,
+            self.blockchain.clone(),
+            self.consensus.clone(),
+            self.network.clone(),
+            self.policy.clone(),
+            self.watcher.clone(),
+            self.validating.clone(),
+            self.reloader.clone(),
+        )
+    }
+    
+    /// Gets a handler for all JSON-RPC methods this node serves, the `admin_` methods included
+    pub fn admin_handler(&self) -> rpc::RpcHandler {
+        self.rpc_handler().with_admin(self.admin_api())
+    }
+    
+    /// Reads the configuration file again, applying what can change while the node runs
+    ///
+    /// See `reload` for which fields those are; the report lists the
+    /// changed fields applied and those rejected for needing a restart.
+    pub fn reload_config(&self, path: &std::path::Path) -> std::result::Result<reload::ReloadReport, reload::ReloadError> {
+        self.reloader.reload(Some(path))
+    }
+    
+    /// Checks the data directory, loads the blocks saved there into the chain and saves new blocks to it
+    ///
+    /// Called by `start`, on a chain holding only its genesis block. A
+    /// directory found corrupt is repaired if `integrity_config.auto_repair`
+    /// is set, and otherwise the node refuses to start; see `integrity`.
+    pub fn open_data_dir(&self) -> Result<integrity::IntegrityReport> {
+        let data_dir = std::path::Path::new(&self.config.data_dir);
+        let integrity_config = &self.config.integrity_config;
+        let genesis_hash = self.blockchain.lock().unwrap().get_block_by_height(0)
+            .ok_or_else(|| BlockchainError::StateError("Genesis block not found".to_string()))?
+            .hash()?;
+        
+        // The chain is only loaded up to the latest block that checked out
+        let mut report = integrity::check(data_dir, genesis_hash, integrity_config);
+        integrity::restore(data_dir, &mut self.blockchain.lock().unwrap(), &mut report)?;
+        for issue in &report.issues {
+            eprintln!("Integrity check of {} failed: {}", data_dir.display(), issue.detail);
+        }
+        if !report.is_clean() {
+            if !report.is_repairable() {
+                return Err(BlockchainError::StateError(format!(
+                    "Data directory {} belongs to another chain",
+                    data_dir.display()
+                )));
+            }
+            if !integrity_config.auto_repair {
+                return Err(BlockchainError::StateError(format!(
+                    "Data directory {} is corrupt; run the node with --repair to truncate it to block {}",
+                    data_dir.display(),
+                    report.verified_height
+                )));
+            }
+        }
+        
+        // Save the blocks so they can be verified offline (see `ctb_core::replay`), moving the tip to the chain's
+        let block_store = data_dir.join(ctb_core::block_store::BLOCKS_DIR);
+        self.blockchain.lock().unwrap().set_block_store(block_store)?;
+        
+        if !report.is_clean() {
+            integrity::repair(data_dir, &mut report)?;
+            println!("Repaired {}, truncating the chain to block {}", data_dir.display(), report.verified_height);
+            for discarded in &report.discarded_blocks {
+                match &discarded.block_hash {
+                    Some(hash) => println!("Discarded block {} ({})", discarded.height, hash),
+                    None => println!("Discarded unreadable block {}", discarded.height),
+                }
+            }
+            for path in &report.discarded_files {
+                println!("Set aside {}", path.display());
+            }
+        }
+        println!("Loaded {} blocks from {}", self.blockchain.lock().unwrap().get_latest_height(), data_dir.display());
+        Ok(report)
+    }
+    
+    /// Signs the blocks the node produces and its finality votes with a signer, see `signer`
+    ///
+    /// Set before `start`, it's used in place of the one `signer_config`
+    /// names, as for a keystore unlocked with a password read at startup.
+    pub fn set_signer(&self, signer: Arc<dyn consensus::signer::BlockSigner>) {
+        self.consensus.lock().unwrap().set_signer(signer);
+    }
+    
+    /// Repairs the data directory at startup if it's corrupt, whatever `integrity_config.auto_repair` says
+    pub fn repair_data_dir(&mut self) {
+        self.config.integrity_config.auto_repair = true;
+    }
+    
+    /// Notes the file the node's configuration was read from, which `admin_reloadConfig` reads by default
+    pub fn set_config_path(&self, path: std::path::PathBuf) {
+        self.reloader.set_path(path);
+    }
+    
+    /// Gets the configuration the node runs with, as reloads have left it
+    pub fn running_config(&self) -> NodeConfig {
+        self.reloader.config()
+    }
+    
+    /// Gets the address the JSON-RPC server listens on, if it's running
+    pub fn rpc_addr(&self) -> Option<std::net::SocketAddr> {
+        self.rpc_server.as_ref().map(|server| server.local_addr())
+    }
+    
+    /// Gets the address the admin JSON-RPC server listens on, if it's running
+    pub fn admin_addr(&self) -> Option<std::net::SocketAddr> {
+        self.admin_server.as_ref().map(|server| server.local_addr())
+    }
+    
+    /// Gets the addresses the node listens for peers on, with the ports bound once started
+    pub fn listen_addrs(&self) -> Vec<std::net::SocketAddr> {
+        self.network.lock().unwrap().listen_addrs().to_vec()
+    }
+    
     /// Gets the current node state
     pub fn get_state(&self) -> NodeState {
-        self.state.clone()
+        self.state.read().unwrap().clone()
     }
     
     /// Gets the number of connected peers
@@ -244,11 +1151,181 @@ impl Node {
         network.peer_count()
     }
     
+    /// Gets the node's open connections, in order of address, see `connection`
+    pub fn connections(&self) -> Vec<connection::Connection> {
+        self.network.lock().unwrap().connections()
+    }
+    
     /// Stops the node
     pub fn stop(&mut self) {
         println!("Stopping node {}...", self.config.node_id);
-        self.state = NodeState::ShuttingDown;
+        *self.state.write().unwrap() = NodeState::ShuttingDown;
+        
+        // Stop serving RPC; the node loop exits on its next tick
+        for mut server in self.rpc_server.take().into_iter().chain(self.admin_server.take()) {
+            server.shutdown();
+        }
+        self.watcher.stop_webhook();
         
         // In a real implementation, we would gracefully shut down all components here
     }
+}
+
+/// Wallet access to a node's chain state
+struct NodeClient {
+    blockchain: Arc<Mutex<Blockchain>>,
+    consensus: Arc<Mutex<ConsensusEngine>>,
+    contract_reader: ContractReader,
+    snapshots: SnapshotHandle,
+    verified_txs: Arc<VerifiedTxCache>,
+    policy: Arc<policy::AdmissionPolicy>,
+    filters: Arc<filters::FilterIndex>,
+    finality: Arc<Mutex<FinalityManager>>,
+}
+
+impl NodeClient {
+    /// Locks the chain, getting its tip with it
+    ///
+    /// The finalized height is read first, so the finality lock is never
+    /// taken while the chain's is held.
+    fn lock_with_tip(&self) -> (MutexGuard<'_, Blockchain>, ChainTip) {
+        let finalized_height = self.finality.lock().unwrap().get_latest_finalized_height();
+        let blockchain = self.blockchain.lock().unwrap();
+        let tip = ChainTip::new(blockchain.get_latest_height(), finalized_height);
+        (blockchain, tip)
+    }
+}
+
+impl ChainClient for NodeClient {
+    fn call_contract(
+        &self,
+        contract: &str,
+        selector: &[u8; 4],
+        arguments: &[u8],
+        sender: &str,
+    ) -> Result<CallOutcome> {
+        let gas_limit = self.blockchain.lock().unwrap().get_block_gas_limit();
+        let snapshot = self.snapshots.latest();
+        match self.contract_reader.call_static(contract, selector, arguments, sender, gas_limit, &snapshot) {
+            Ok(output) => Ok(CallOutcome::Returned(output)),
+            Err(ContractError::Reverted { reason, data }) => Ok(CallOutcome::Reverted { reason, data }),
+            Err(e) => Err(BlockchainError::StateError(e.to_string())),
+        }
+    }
+    
+    fn get_contract_abi(&self, contract: &str) -> Result<Option<Vec<FunctionABI>>> {
+        let snapshot = self.snapshots.latest();
+        Ok(self.contract_reader.get_contract(contract, &snapshot).map(|contract| contract.abi.clone()))
+    }
+    
+    fn estimate_gas(&self, tx: &Transaction) -> Result<GasEstimate> {
+        let gas_cap = self.blockchain.lock().unwrap().get_block_gas_limit();
+        let snapshot = self.snapshots.latest();
+        match self.contract_reader.estimate_gas(tx, gas_cap, &snapshot) {
+            Ok(gas) => Ok(GasEstimate::Gas(gas)),
+            Err(ContractError::Reverted { reason, .. }) => Ok(GasEstimate::Reverted(reason)),
+            Err(e) => Err(BlockchainError::StateError(e.to_string())),
+        }
+    }
+    
+    fn next_base_fee(&self) -> u64 {
+        self.blockchain.lock().unwrap().next_base_fee()
+    }
+    
+    fn fee_histogram(&self) -> Result<FeeHistogram> {
+        Ok(self.consensus.lock().unwrap().mempool().fee_histogram().clone())
+    }
+    
+    fn estimate_inclusion(&self, rate: FeeRate) -> Result<EstimatedBlocks> {
+        Ok(self.consensus.lock().unwrap().estimate_inclusion(rate))
+    }
+    
+    fn get_balance(&self, address: &str) -> Result<u64> {
+        let address = Address::new(address)?;
+        self.blockchain.lock().unwrap().get_balance(&address).map(Amount::base_units)
+    }
+    
+    fn get_nonce(&self, address: &str) -> Result<u64> {
+        let address = Address::new(address)?;
+        self.blockchain.lock().unwrap().get_nonce(&address)
+    }
+    
+    fn submit_transaction(&self, tx: &Transaction) -> Result<TxHash> {
+        self.policy.check(tx).map_err(|e| BlockchainError::InvalidTransaction(e.to_string()))?;
+        tx.validate_cached(&self.verified_txs)?;
+        self.consensus.lock().unwrap().add_transaction(tx.clone())?;
+        Ok(tx.id)
+    }
+    
+    fn get_history(&self, address: &str, limit: usize) -> Result<Vec<TransactionRecord>> {
+        let (blockchain, tip) = self.lock_with_tip();
+        let history = blockchain
+            .get_transactions_for_address(address, limit)
+            .into_iter()
+            .map(|(block_height, tx)| history_record(&blockchain, &tip, block_height, tx))
+            .collect();
+        Ok(history)
+    }
+    
+    fn get_history_page(&self, address: &str, request: &PageRequest) -> Result<Page<TransactionRecord>> {
+        let (blockchain, tip) = self.lock_with_tip();
+        let page = blockchain.list_transactions_for_address(address, request)?;
+        Ok(page.map(|(block_height, tx)| history_record(&blockchain, &tip, block_height, tx)))
+    }
+    
+    fn get_transaction(&self, tx_id: &TxHash) -> Result<Option<TransactionRecord>> {
+        let (blockchain, tip) = self.lock_with_tip();
+        Ok(eth::find_transaction(&blockchain, tx_id)
+            .map(|(block, index)| history_record(&blockchain, &tip, block.header().height, &block.transactions[index])))
+    }
+    
+    fn address_possibly_used(&self, address: &str) -> Result<bool> {
+        let address = Address::new(address)?;
+        Ok(self.blockchain.lock().unwrap().address_possibly_used(&address))
+    }
+    
+    fn address_first_used(&self, address: &str) -> Result<Option<u64>> {
+        let address = Address::new(address)?;
+        Ok(self.blockchain.lock().unwrap().address_first_used(&address))
+    }
+    
+    fn chain_tip(&self) -> Result<ChainTip> {
+        Ok(self.lock_with_tip().1)
+    }
+    
+    fn get_transaction_status(&self, tx_id: &TxHash, safe_confirmations: u64) -> Result<ConfirmationStatus> {
+        let (blockchain, tip) = self.lock_with_tip();
+        let included_at = eth::find_transaction(&blockchain, tx_id).map(|(block, _)| block.header().height);
+        Ok(tip.status(included_at, safe_confirmations))
+    }
+}
+
+/// Builds the history record of a transaction included at a height, judged at the tip by `DEFAULT_SAFE_CONFIRMATIONS`
+fn history_record(blockchain: &Blockchain, tip: &ChainTip, block_height: u64, tx: &Transaction) -> TransactionRecord {
+    let receipt = blockchain.get_receipt(&tx.id);
+    TransactionRecord {
+        block_height,
+        block_timestamp: blockchain.get_block_by_height(block_height).map_or(0, |block| block.header().timestamp),
+        success: receipt.is_none_or(|receipt| receipt.success),
+        fee_paid: receipt.map_or(tx.fee, |receipt| tx.fee_for_gas(receipt.gas_used)),
+        transaction: tx.clone(),
+        status: tip.status(Some(block_height), DEFAULT_SAFE_CONFIRMATIONS),
+    }
+}
+
+impl FilterSource for NodeClient {
+    fn get_filter_headers(&self, start: u64, count: u64) -> Result<Vec<Hash>> {
+        Ok(self.filters.headers(start..start.saturating_add(count.min(message::MAX_HEADERS))))
+    }
+    
+    fn get_filters(&self, start: u64, count: u64) -> Result<Vec<BlockFilter>> {
+        Ok(self.filters.filters(start..start.saturating_add(count.min(message::MAX_FILTERS))))
+    }
+    
+    fn get_block(&self, height: u64) -> Result<FilteredBlock> {
+        let blockchain = self.blockchain.lock().unwrap();
+        let block = blockchain.get_block_by_height(height).ok_or(BlockchainError::UnknownBlock { height })?.clone();
+        let receipts = blockchain.get_block_receipts(height)?.into_iter().cloned().collect();
+        Ok(FilteredBlock { block, receipts })
+    }
 }
\ No newline at end of file
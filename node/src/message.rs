@@ -0,0 +1,823 @@
+//! Messages exchanged between nodes
+//!
+//! Each message travels as a frame of a protocol version byte, a tag byte
+//! naming the message, and the message's payload in the canonical binary
+//! encoding of `ctb_core::wire`. Payload sizes are capped per message and
+//! checked before anything is decoded, so a peer can't make a node
+//! allocate more than the cap of the message it claims to send.
+//!
+//! Frames with a tag this version doesn't know decode to
+//! `NetworkMessage::Unknown` rather than failing, so newer nodes can add
+//! messages that older ones skip.
+
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use consensus::finality::FinalityVote;
+use ctb_core::block::{Block, BlockHeader};
+use ctb_core::block_filter::BlockFilter;
+use ctb_core::receipt::Receipt;
+use ctb_core::rlp::{self, RlpItem};
+use ctb_core::state_sync::{SnapshotInfo, SnapshotManifest, SNAPSHOT_CHUNK_SIZE};
+use ctb_core::transaction::Transaction;
+use ctb_core::wire::{self, Wire, WireError};
+use ctb_core::{BlockHash, Bytes, Hash, TxHash};
+
+/// Version of the protocol this node speaks, the first byte of every frame
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Largest frame accepted, including the version and tag bytes
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Most headers requested or sent in one message
+pub const MAX_HEADERS: u64 = 2000;
+
+/// Most block filters requested or sent in one message
+pub const MAX_FILTERS: u64 = 1000;
+
+/// Most peer addresses sent in one message
+pub const MAX_PEERS: usize = 1000;
+
+/// Most address announcements sent in one message
+pub const MAX_ANNOUNCEMENTS: usize = 256;
+
+/// Most listening addresses a node gives in its handshake
+pub const MAX_LISTEN_ADDRS: usize = 8;
+
+/// Most state snapshots offered in one message
+pub const MAX_SNAPSHOTS: usize = 16;
+
+/// Most blocks a block locator names, see `block_sync::locator`
+pub const MAX_LOCATOR: usize = 64;
+
+const KIB: usize = 1024;
+const MIB: usize = 1024 * KIB;
+
+/// Largest payload of a control message, such as a handshake or ping
+const CONTROL_LIMIT: usize = 4 * KIB;
+
+/// Largest payload of a message carrying a block
+const BLOCK_LIMIT: usize = 8 * MIB;
+
+/// Largest payload of a message carrying a transaction
+const TRANSACTION_LIMIT: usize = 256 * KIB;
+
+/// Largest payload of a message carrying a block's receipts
+const RECEIPTS_LIMIT: usize = 8 * MIB;
+
+/// Largest payload of a message carrying headers
+const HEADERS_LIMIT: usize = 2 * MIB;
+
+/// Largest payload of a message carrying block filters
+const FILTERS_LIMIT: usize = 8 * MIB;
+
+/// Largest payload of a message carrying peer addresses
+const PEERS_LIMIT: usize = 64 * KIB;
+
+/// Largest payload of a message carrying a snapshot manifest
+const MANIFEST_LIMIT: usize = MIB;
+
+/// Largest payload of a message carrying a snapshot chunk
+const CHUNK_LIMIT: usize = SNAPSHOT_CHUNK_SIZE + KIB;
+
+/// Tags identifying each message in a frame
+mod tag {
+    pub const HANDSHAKE: u8 = 0x00;
+    pub const PING: u8 = 0x01;
+    pub const PONG: u8 = 0x02;
+    pub const GET_PEERS: u8 = 0x03;
+    pub const PEERS: u8 = 0x04;
+    pub const DISCONNECT: u8 = 0x05;
+    pub const STATUS: u8 = 0x06;
+    pub const ADDRESSES: u8 = 0x07;
+    pub const NEW_BLOCK: u8 = 0x10;
+    pub const GET_BLOCK: u8 = 0x11;
+    pub const BLOCK: u8 = 0x12;
+    pub const GET_HEADERS: u8 = 0x13;
+    pub const HEADERS: u8 = 0x14;
+    pub const GET_RECEIPTS: u8 = 0x15;
+    pub const RECEIPTS: u8 = 0x16;
+    pub const GET_FILTER_HEADERS: u8 = 0x17;
+    pub const FILTER_HEADERS: u8 = 0x18;
+    pub const GET_FILTERS: u8 = 0x19;
+    pub const FILTERS: u8 = 0x1a;
+    pub const GET_BRANCH_HEADERS: u8 = 0x1b;
+    pub const NEW_TRANSACTION: u8 = 0x20;
+    pub const GET_TRANSACTION: u8 = 0x21;
+    pub const TRANSACTION: u8 = 0x22;
+    pub const CHECKPOINT_VOTE: u8 = 0x30;
+    pub const GET_SNAPSHOTS: u8 = 0x40;
+    pub const SNAPSHOTS: u8 = 0x41;
+    pub const GET_SNAPSHOT_MANIFEST: u8 = 0x42;
+    pub const SNAPSHOT_MANIFEST: u8 = 0x43;
+    pub const GET_SNAPSHOT_CHUNK: u8 = 0x44;
+    pub const SNAPSHOT_CHUNK: u8 = 0x45;
+}
+
+/// Error decoding a frame received from a peer
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DecodeError {
+    #[error("Frame is missing its version or tag")]
+    Truncated,
+    
+    #[error("Unsupported protocol version {0}")]
+    UnsupportedVersion(u8),
+    
+    #[error("{kind} payload of {size} bytes exceeds the {limit} byte limit")]
+    TooLarge { kind: &'static str, size: usize, limit: usize },
+    
+    #[error("Invalid {kind} payload: {source}")]
+    Invalid {
+        kind: &'static str,
+        #[source]
+        source: WireError,
+    },
+}
+
+/// First message on a connection, introducing the node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeData {
+    /// Node's ID (public key)
+    pub node_id: String,
+    
+    /// Height of the node's latest block
+    pub height: u64,
+    
+    /// Hash of the node's latest block
+    pub best_hash: BlockHash,
+    
+    /// When the handshake was sent
+    pub timestamp: u64,
+    
+    /// Random value the node sends in all its handshakes, so it can tell
+    /// when it's connected to itself
+    pub nonce: u64,
+    
+    /// Addresses the node can be dialed at, at most `MAX_LISTEN_ADDRS`
+    pub listen_addrs: Vec<SocketAddr>,
+    
+    /// Address the node sees the connection's other end at, if it knows it
+    pub observed_addr: Option<SocketAddr>,
+}
+
+impl HandshakeData {
+    /// Tells the other end of the connection the address it's seen at
+    pub fn with_observed_addr(mut self, addr: SocketAddr) -> Self {
+        self.observed_addr = Some(addr);
+        self
+    }
+}
+
+/// Payload of a ping and of the pong answering it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingData {
+    /// Value the pong echoes back
+    pub nonce: u64,
+}
+
+/// Where a node's chain is, as it tells its peers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainStatus {
+    /// Height of the node's latest block
+    pub height: u64,
+    
+    /// Hash of the node's latest block
+    pub best_hash: BlockHash,
+    
+    /// Weight of the node's chain by its fork choice rule, see `Blockchain::chain_weight`
+    pub weight: u128,
+}
+
+/// A node's signed statement that it can be dialed at an address, see `address_book`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressAnnouncement {
+    /// Address of the node's identity key, which signs the announcement
+    pub node_id: String,
+    
+    /// Address the node can be dialed at
+    pub addr: SocketAddr,
+    
+    /// When the announcement was signed, as a Unix timestamp in seconds
+    pub timestamp: u64,
+    
+    /// Node's signature of the announcement, see `address_book::signing_hash`
+    pub signature: Bytes,
+}
+
+/// Why a node closes a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The node no longer wants the connection
+    Requested,
+    
+    /// The node has no room for another peer
+    TooManyPeers,
+    
+    /// The node keeps another connection to the same peer instead
+    Duplicate,
+    
+    /// The connection leads back to the node itself
+    SelfConnection,
+    
+    /// The peer didn't send what it had to in time, see `connection`
+    Timeout,
+    
+    /// The node refuses the peer for a while, for misbehaving
+    Banned,
+}
+
+impl DisconnectReason {
+    fn code(self) -> u8 {
+        match self {
+            Self::Requested => 0,
+            Self::TooManyPeers => 1,
+            Self::Duplicate => 2,
+            Self::SelfConnection => 3,
+            Self::Timeout => 4,
+            Self::Banned => 5,
+        }
+    }
+    
+    fn from_code(code: u64) -> Result<Self, WireError> {
+        match code {
+            0 => Ok(Self::Requested),
+            1 => Ok(Self::TooManyPeers),
+            2 => Ok(Self::Duplicate),
+            3 => Ok(Self::SelfConnection),
+            4 => Ok(Self::Timeout),
+            5 => Ok(Self::Banned),
+            _ => Err(WireError::InvalidValue("disconnect reason")),
+        }
+    }
+}
+
+/// Message exchanged between nodes
+#[derive(Debug, Clone)]
+pub enum NetworkMessage {
+    /// Handshake for initial connection
+    Handshake(HandshakeData),
+    
+    /// Ping to check the connection
+    Ping(PingData),
+    
+    /// Response to a ping
+    Pong(PingData),
+    
+    /// Request for peers
+    GetPeers,
+    
+    /// Response with peers, unsigned, from nodes that predate `Addresses`
+    ///
+    /// Addresses anyone could have made up aren't taken, so it's ignored.
+    Peers(Vec<SocketAddr>),
+    
+    /// Response with peers, each address announced by the node found there
+    Addresses(Vec<AddressAnnouncement>),
+    
+    /// Notice that the sender is closing the connection
+    Disconnect(DisconnectReason),
+    
+    /// The sender's tip and the weight of its chain
+    Status(ChainStatus),
+    
+    /// New block announcement
+    NewBlock(Arc<Block>),
+    
+    /// Request for a specific block
+    GetBlock(BlockHash),
+    
+    /// Response with a block
+    Block(Arc<Block>),
+    
+    /// Request for the headers of a range of heights
+    GetHeaders(Range<u64>),
+    
+    /// Response with headers
+    Headers(Vec<BlockHeader>),
+    
+    /// Request for the headers from the highest of some blocks, by height and hash, the responder's chain has
+    ///
+    /// Answered with `Headers`, starting at that block, or with none if the
+    /// chain has none of them.
+    GetBranchHeaders(Vec<(u64, BlockHash)>),
+    
+    /// Request for the receipts of the block at a height
+    GetReceipts(u64),
+    
+    /// Response with the receipts of the block at a height, in the order of its transactions
+    Receipts { height: u64, receipts: Vec<Receipt> },
+    
+    /// Request for the block filter headers of a range of heights
+    GetFilterHeaders(Range<u64>),
+    
+    /// Response with the block filter headers of the heights from `start` on
+    FilterHeaders { start: u64, headers: Vec<Hash> },
+    
+    /// Request for the block filters of a range of heights
+    GetFilters(Range<u64>),
+    
+    /// Response with the block filters of the heights from `start` on
+    Filters { start: u64, filters: Vec<BlockFilter> },
+    
+    /// New transaction announcement
+    NewTransaction(Arc<Transaction>),
+    
+    /// Request for a specific transaction
+    GetTransaction(TxHash),
+    
+    /// Response with a transaction
+    Transaction(Arc<Transaction>),
+    
+    /// Validator's vote for a checkpoint
+    CheckpointVote(FinalityVote),
+    
+    /// Request for the state snapshots a node serves
+    GetSnapshots,
+    
+    /// Response with the state snapshots served
+    Snapshots(Vec<SnapshotInfo>),
+    
+    /// Request for the manifest of the snapshot at a height
+    GetSnapshotManifest(u64),
+    
+    /// Response with a snapshot manifest
+    SnapshotManifest(SnapshotManifest),
+    
+    /// Request for a chunk of the snapshot at a height
+    GetSnapshotChunk { height: u64, index: u32 },
+    
+    /// Response with a snapshot chunk
+    SnapshotChunk { height: u64, index: u32, data: Bytes },
+    
+    /// Message with a tag this version doesn't know, kept undecoded
+    Unknown { tag: u8, payload: Bytes },
+}
+
+impl NetworkMessage {
+    /// Gets the name of the message, for logs and errors
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Unknown { .. } => "unknown",
+            _ => kind(self.tag()),
+        }
+    }
+    
+    /// Encodes the message as a frame
+    pub fn encode(&self) -> Vec<u8> {
+        let payload = match self {
+            Self::Handshake(handshake) => handshake.to_bytes(),
+            Self::Ping(ping) | Self::Pong(ping) => ping.to_bytes(),
+            Self::GetPeers => rlp::encode(&RlpItem::List(Vec::new())),
+            Self::Peers(addresses) => rlp::encode(&RlpItem::List(addresses.iter().map(encode_addr).collect())),
+            Self::Addresses(announcements) => rlp::encode(&RlpItem::List(announcements.iter().map(Wire::to_rlp).collect())),
+            Self::Disconnect(reason) => rlp::encode(&RlpItem::List(vec![RlpItem::uint(reason.code() as u128)])),
+            Self::Status(status) => status.to_bytes(),
+            Self::NewBlock(block) | Self::Block(block) => block.to_bytes(),
+            Self::GetBlock(hash) => rlp::encode(&wire::hash(&hash.0)),
+            Self::GetHeaders(range) => rlp::encode(&RlpItem::List(vec![
+                RlpItem::uint(range.start as u128),
+                RlpItem::uint(range.end as u128),
+            ])),
+            Self::Headers(headers) => rlp::encode(&RlpItem::List(headers.iter().map(Wire::to_rlp).collect())),
+            Self::GetBranchHeaders(locator) => rlp::encode(&RlpItem::List(
+                locator
+                    .iter()
+                    .map(|(height, hash)| RlpItem::List(vec![RlpItem::uint(*height as u128), wire::hash(&hash.0)]))
+                    .collect(),
+            )),
+            Self::GetReceipts(height) => rlp::encode(&RlpItem::List(vec![RlpItem::uint(*height as u128)])),
+            Self::Receipts { height, receipts } => rlp::encode(&RlpItem::List(vec![
+                RlpItem::uint(*height as u128),
+                RlpItem::List(receipts.iter().map(Wire::to_rlp).collect()),
+            ])),
+            Self::GetFilterHeaders(range) | Self::GetFilters(range) => rlp::encode(&RlpItem::List(vec![
+                RlpItem::uint(range.start as u128),
+                RlpItem::uint(range.end as u128),
+            ])),
+            Self::FilterHeaders { start, headers } => rlp::encode(&RlpItem::List(vec![
+                RlpItem::uint(*start as u128),
+                RlpItem::List(headers.iter().map(wire::hash).collect()),
+            ])),
+            Self::Filters { start, filters } => rlp::encode(&RlpItem::List(vec![
+                RlpItem::uint(*start as u128),
+                RlpItem::List(filters.iter().map(Wire::to_rlp).collect()),
+            ])),
+            Self::NewTransaction(tx) | Self::Transaction(tx) => tx.to_bytes(),
+            Self::GetTransaction(id) => rlp::encode(&wire::hash(&id.0)),
+            Self::CheckpointVote(vote) => vote.to_bytes(),
+            Self::GetSnapshots => rlp::encode(&RlpItem::List(Vec::new())),
+            Self::Snapshots(snapshots) => rlp::encode(&RlpItem::List(snapshots.iter().map(Wire::to_rlp).collect())),
+            Self::GetSnapshotManifest(height) => rlp::encode(&RlpItem::List(vec![RlpItem::uint(*height as u128)])),
+            Self::SnapshotManifest(manifest) => manifest.to_bytes(),
+            Self::GetSnapshotChunk { height, index } => rlp::encode(&RlpItem::List(vec![
+                RlpItem::uint(*height as u128),
+                RlpItem::uint(*index as u128),
+            ])),
+            Self::SnapshotChunk { height, index, data } => rlp::encode(&RlpItem::List(vec![
+                RlpItem::uint(*height as u128),
+                RlpItem::uint(*index as u128),
+                wire::bytes(data),
+            ])),
+            Self::Unknown { payload, .. } => payload.0.clone(),
+        };
+        
+        let mut frame = Vec::with_capacity(2 + payload.len());
+        frame.push(PROTOCOL_VERSION);
+        frame.push(self.tag());
+        frame.extend_from_slice(&payload);
+        frame
+    }
+    
+    /// Decodes a frame, checking the payload against its message's size cap first
+    pub fn decode(frame: &[u8]) -> Result<Self, DecodeError> {
+        let [version, tag, payload @ ..] = frame else {
+            return Err(DecodeError::Truncated);
+        };
+        if *version != PROTOCOL_VERSION {
+            return Err(DecodeError::UnsupportedVersion(*version));
+        }
+        
+        let kind = kind(*tag);
+        let limit = payload_limit(*tag);
+        if payload.len() > limit {
+            return Err(DecodeError::TooLarge { kind, size: payload.len(), limit });
+        }
+        
+        Self::decode_payload(*tag, payload).map_err(|source| DecodeError::Invalid { kind, source })
+    }
+    
+    fn decode_payload(tag: u8, payload: &[u8]) -> Result<Self, WireError> {
+        let message = match tag {
+            tag::HANDSHAKE => Self::Handshake(HandshakeData::from_bytes(payload)?),
+            tag::PING => Self::Ping(PingData::from_bytes(payload)?),
+            tag::PONG => Self::Pong(PingData::from_bytes(payload)?),
+            tag::GET_PEERS => {
+                wire::fields(&rlp::decode(payload)?, 0)?;
+                Self::GetPeers
+            }
+            tag::PEERS => {
+                let item = rlp::decode(payload)?;
+                let addresses = bounded_list(&item, MAX_PEERS)?
+                    .iter()
+                    .map(decode_addr)
+                    .collect::<Result<_, _>>()?;
+                Self::Peers(addresses)
+            }
+            tag::ADDRESSES => {
+                let item = rlp::decode(payload)?;
+                let announcements = bounded_list(&item, MAX_ANNOUNCEMENTS)?
+                    .iter()
+                    .map(AddressAnnouncement::from_rlp)
+                    .collect::<Result<_, _>>()?;
+                Self::Addresses(announcements)
+            }
+            tag::DISCONNECT => {
+                let item = rlp::decode(payload)?;
+                Self::Disconnect(DisconnectReason::from_code(wire::fields(&item, 1)?[0].as_u64()?)?)
+            }
+            tag::STATUS => Self::Status(ChainStatus::from_bytes(payload)?),
+            tag::NEW_BLOCK => Self::NewBlock(Arc::new(Block::from_bytes(payload)?)),
+            tag::GET_BLOCK => Self::GetBlock(BlockHash(wire::decode_hash(&rlp::decode(payload)?)?)),
+            tag::BLOCK => Self::Block(Arc::new(Block::from_bytes(payload)?)),
+            tag::GET_HEADERS => Self::GetHeaders(bounded_range(payload, MAX_HEADERS)?),
+            tag::HEADERS => {
+                let item = rlp::decode(payload)?;
+                let headers = bounded_list(&item, MAX_HEADERS as usize)?
+                    .iter()
+                    .map(BlockHeader::from_rlp)
+                    .collect::<Result<_, _>>()?;
+                Self::Headers(headers)
+            }
+            tag::GET_BRANCH_HEADERS => {
+                let item = rlp::decode(payload)?;
+                let locator = bounded_list(&item, MAX_LOCATOR)?
+                    .iter()
+                    .map(|entry| {
+                        let fields = wire::fields(entry, 2)?;
+                        Ok((fields[0].as_u64()?, BlockHash(wire::decode_hash(&fields[1])?)))
+                    })
+                    .collect::<Result<_, WireError>>()?;
+                Self::GetBranchHeaders(locator)
+            }
+            tag::GET_RECEIPTS => {
+                let item = rlp::decode(payload)?;
+                Self::GetReceipts(wire::fields(&item, 1)?[0].as_u64()?)
+            }
+            tag::RECEIPTS => {
+                let item = rlp::decode(payload)?;
+                let fields = wire::fields(&item, 2)?;
+                Self::Receipts {
+                    height: fields[0].as_u64()?,
+                    receipts: fields[1].as_list()?.iter().map(Receipt::from_rlp).collect::<Result<_, _>>()?,
+                }
+            }
+            tag::GET_FILTER_HEADERS => Self::GetFilterHeaders(bounded_range(payload, MAX_HEADERS)?),
+            tag::FILTER_HEADERS => {
+                let item = rlp::decode(payload)?;
+                let fields = wire::fields(&item, 2)?;
+                Self::FilterHeaders {
+                    start: fields[0].as_u64()?,
+                    headers: bounded_list(&fields[1], MAX_HEADERS as usize)?
+                        .iter()
+                        .map(wire::decode_hash)
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            tag::GET_FILTERS => Self::GetFilters(bounded_range(payload, MAX_FILTERS)?),
+            tag::FILTERS => {
+                let item = rlp::decode(payload)?;
+                let fields = wire::fields(&item, 2)?;
+                Self::Filters {
+                    start: fields[0].as_u64()?,
+                    filters: bounded_list(&fields[1], MAX_FILTERS as usize)?
+                        .iter()
+                        .map(BlockFilter::from_rlp)
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            tag::NEW_TRANSACTION => Self::NewTransaction(Arc::new(Transaction::from_bytes(payload)?)),
+            tag::GET_TRANSACTION => Self::GetTransaction(TxHash(wire::decode_hash(&rlp::decode(payload)?)?)),
+            tag::TRANSACTION => Self::Transaction(Arc::new(Transaction::from_bytes(payload)?)),
+            tag::CHECKPOINT_VOTE => Self::CheckpointVote(FinalityVote::from_bytes(payload)?),
+            tag::GET_SNAPSHOTS => {
+                wire::fields(&rlp::decode(payload)?, 0)?;
+                Self::GetSnapshots
+            }
+            tag::SNAPSHOTS => {
+                let item = rlp::decode(payload)?;
+                let snapshots = bounded_list(&item, MAX_SNAPSHOTS)?
+                    .iter()
+                    .map(SnapshotInfo::from_rlp)
+                    .collect::<Result<_, _>>()?;
+                Self::Snapshots(snapshots)
+            }
+            tag::GET_SNAPSHOT_MANIFEST => {
+                let item = rlp::decode(payload)?;
+                Self::GetSnapshotManifest(wire::fields(&item, 1)?[0].as_u64()?)
+            }
+            tag::SNAPSHOT_MANIFEST => Self::SnapshotManifest(SnapshotManifest::from_bytes(payload)?),
+            tag::GET_SNAPSHOT_CHUNK => {
+                let item = rlp::decode(payload)?;
+                let fields = wire::fields(&item, 2)?;
+                Self::GetSnapshotChunk { height: fields[0].as_u64()?, index: chunk_index(&fields[1])? }
+            }
+            tag::SNAPSHOT_CHUNK => {
+                let item = rlp::decode(payload)?;
+                let fields = wire::fields(&item, 3)?;
+                Self::SnapshotChunk {
+                    height: fields[0].as_u64()?,
+                    index: chunk_index(&fields[1])?,
+                    data: wire::decode_bytes(&fields[2])?,
+                }
+            }
+            _ => Self::Unknown { tag, payload: Bytes(payload.to_vec()) },
+        };
+        Ok(message)
+    }
+    
+    fn tag(&self) -> u8 {
+        match self {
+            Self::Handshake(_) => tag::HANDSHAKE,
+            Self::Ping(_) => tag::PING,
+            Self::Pong(_) => tag::PONG,
+            Self::GetPeers => tag::GET_PEERS,
+            Self::Peers(_) => tag::PEERS,
+            Self::Addresses(_) => tag::ADDRESSES,
+            Self::Disconnect(_) => tag::DISCONNECT,
+            Self::Status(_) => tag::STATUS,
+            Self::NewBlock(_) => tag::NEW_BLOCK,
+            Self::GetBlock(_) => tag::GET_BLOCK,
+            Self::Block(_) => tag::BLOCK,
+            Self::GetHeaders(_) => tag::GET_HEADERS,
+            Self::Headers(_) => tag::HEADERS,
+            Self::GetBranchHeaders(_) => tag::GET_BRANCH_HEADERS,
+            Self::GetReceipts(_) => tag::GET_RECEIPTS,
+            Self::Receipts { .. } => tag::RECEIPTS,
+            Self::GetFilterHeaders(_) => tag::GET_FILTER_HEADERS,
+            Self::FilterHeaders { .. } => tag::FILTER_HEADERS,
+            Self::GetFilters(_) => tag::GET_FILTERS,
+            Self::Filters { .. } => tag::FILTERS,
+            Self::NewTransaction(_) => tag::NEW_TRANSACTION,
+            Self::GetTransaction(_) => tag::GET_TRANSACTION,
+            Self::Transaction(_) => tag::TRANSACTION,
+            Self::CheckpointVote(_) => tag::CHECKPOINT_VOTE,
+            Self::GetSnapshots => tag::GET_SNAPSHOTS,
+            Self::Snapshots(_) => tag::SNAPSHOTS,
+            Self::GetSnapshotManifest(_) => tag::GET_SNAPSHOT_MANIFEST,
+            Self::SnapshotManifest(_) => tag::SNAPSHOT_MANIFEST,
+            Self::GetSnapshotChunk { .. } => tag::GET_SNAPSHOT_CHUNK,
+            Self::SnapshotChunk { .. } => tag::SNAPSHOT_CHUNK,
+            Self::Unknown { tag, .. } => *tag,
+        }
+    }
+}
+
+impl Wire for HandshakeData {
+    fn to_rlp(&self) -> RlpItem {
+        let mut fields = vec![
+            wire::string(&self.node_id),
+            RlpItem::uint(self.height as u128),
+            wire::hash(&self.best_hash.0),
+            RlpItem::uint(self.timestamp as u128),
+            RlpItem::uint(self.nonce as u128),
+        ];
+        // Nodes that predate the addresses end their handshakes after the nonce
+        if !self.listen_addrs.is_empty() || self.observed_addr.is_some() {
+            fields.push(RlpItem::List(self.listen_addrs.iter().map(encode_addr).collect()));
+            fields.push(wire::optional(self.observed_addr.as_ref().map(encode_addr)));
+        }
+        RlpItem::List(fields)
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self, WireError> {
+        let fields = item.as_list()?;
+        if !matches!(fields.len(), 5 | 7) {
+            return Err(WireError::FieldCount { expected: 5, got: fields.len() });
+        }
+        let (listen_addrs, observed_addr) = match fields.get(5..) {
+            Some([listen_addrs, observed_addr]) => {
+                let listen_addrs = bounded_list(listen_addrs, MAX_LISTEN_ADDRS)?
+                    .iter()
+                    .map(decode_addr)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let observed_addr = wire::decode_optional(observed_addr)?.map(decode_addr).transpose()?;
+                // Empty trailing fields aren't encoded, so they can't be either
+                if listen_addrs.is_empty() && observed_addr.is_none() {
+                    return Err(WireError::InvalidValue("handshake addresses"));
+                }
+                (listen_addrs, observed_addr)
+            }
+            _ => (Vec::new(), None),
+        };
+        Ok(Self {
+            node_id: wire::decode_string(&fields[0])?,
+            height: fields[1].as_u64()?,
+            best_hash: BlockHash(wire::decode_hash(&fields[2])?),
+            timestamp: fields[3].as_u64()?,
+            nonce: fields[4].as_u64()?,
+            listen_addrs,
+            observed_addr,
+        })
+    }
+}
+
+impl Wire for ChainStatus {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            RlpItem::uint(self.height as u128),
+            wire::hash(&self.best_hash.0),
+            RlpItem::uint(self.weight),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self, WireError> {
+        let fields = wire::fields(item, 3)?;
+        Ok(Self {
+            height: fields[0].as_u64()?,
+            best_hash: BlockHash(wire::decode_hash(&fields[1])?),
+            weight: fields[2].as_uint()?,
+        })
+    }
+}
+
+impl Wire for AddressAnnouncement {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![
+            wire::string(&self.node_id),
+            encode_addr(&self.addr),
+            RlpItem::uint(self.timestamp as u128),
+            wire::bytes(&self.signature),
+        ])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self, WireError> {
+        let fields = wire::fields(item, 4)?;
+        Ok(Self {
+            node_id: wire::decode_string(&fields[0])?,
+            addr: decode_addr(&fields[1])?,
+            timestamp: fields[2].as_u64()?,
+            signature: wire::decode_bytes(&fields[3])?,
+        })
+    }
+}
+
+impl Wire for PingData {
+    fn to_rlp(&self) -> RlpItem {
+        RlpItem::List(vec![RlpItem::uint(self.nonce as u128)])
+    }
+    
+    fn from_rlp(item: &RlpItem) -> Result<Self, WireError> {
+        let fields = wire::fields(item, 1)?;
+        Ok(Self { nonce: fields[0].as_u64()? })
+    }
+}
+
+/// Encodes a peer address, as its text
+fn encode_addr(address: &SocketAddr) -> RlpItem {
+    wire::string(&address.to_string())
+}
+
+/// Decodes a peer address
+fn decode_addr(item: &RlpItem) -> Result<SocketAddr, WireError> {
+    wire::decode_string(item)?
+        .parse()
+        .map_err(|_| WireError::InvalidValue("peer address"))
+}
+
+/// Gets the items of a list holding at most `max` of them
+fn bounded_list(item: &RlpItem, max: usize) -> Result<&[RlpItem], WireError> {
+    let items = item.as_list()?;
+    if items.len() > max {
+        return Err(WireError::InvalidValue("list length"));
+    }
+    Ok(items)
+}
+
+/// Decodes a range of heights spanning at most `max` of them
+fn bounded_range(payload: &[u8], max: u64) -> Result<Range<u64>, WireError> {
+    let item = rlp::decode(payload)?;
+    let fields = wire::fields(&item, 2)?;
+    let range = fields[0].as_u64()?..fields[1].as_u64()?;
+    if range.start > range.end || range.end - range.start > max {
+        return Err(WireError::InvalidValue("height range"));
+    }
+    Ok(range)
+}
+
+/// Decodes the index of a snapshot chunk
+fn chunk_index(item: &RlpItem) -> Result<u32, WireError> {
+    u32::try_from(item.as_u64()?).map_err(|_| WireError::InvalidValue("snapshot chunk index"))
+}
+
+/// Gets the name of the message with a tag
+fn kind(tag: u8) -> &'static str {
+    match tag {
+        tag::HANDSHAKE => "handshake",
+        tag::PING => "ping",
+        tag::PONG => "pong",
+        tag::GET_PEERS => "get peers",
+        tag::PEERS => "peers",
+        tag::ADDRESSES => "addresses",
+        tag::DISCONNECT => "disconnect",
+        tag::STATUS => "status",
+        tag::NEW_BLOCK => "new block",
+        tag::GET_BLOCK => "get block",
+        tag::BLOCK => "block",
+        tag::GET_HEADERS => "get headers",
+        tag::HEADERS => "headers",
+        tag::GET_BRANCH_HEADERS => "get branch headers",
+        tag::GET_RECEIPTS => "get receipts",
+        tag::RECEIPTS => "receipts",
+        tag::GET_FILTER_HEADERS => "get filter headers",
+        tag::FILTER_HEADERS => "filter headers",
+        tag::GET_FILTERS => "get filters",
+        tag::FILTERS => "filters",
+        tag::NEW_TRANSACTION => "new transaction",
+        tag::GET_TRANSACTION => "get transaction",
+        tag::TRANSACTION => "transaction",
+        tag::CHECKPOINT_VOTE => "checkpoint vote",
+        tag::GET_SNAPSHOTS => "get snapshots",
+        tag::SNAPSHOTS => "snapshots",
+        tag::GET_SNAPSHOT_MANIFEST => "get snapshot manifest",
+        tag::SNAPSHOT_MANIFEST => "snapshot manifest",
+        tag::GET_SNAPSHOT_CHUNK => "get snapshot chunk",
+        tag::SNAPSHOT_CHUNK => "snapshot chunk",
+        _ => "unknown",
+    }
+}
+
+/// Gets the largest payload accepted for the message with a tag
+fn payload_limit(tag: u8) -> usize {
+    match tag {
+        tag::NEW_BLOCK | tag::BLOCK => BLOCK_LIMIT,
+        tag::NEW_TRANSACTION | tag::TRANSACTION => TRANSACTION_LIMIT,
+        tag::HEADERS | tag::FILTER_HEADERS => HEADERS_LIMIT,
+        tag::RECEIPTS => RECEIPTS_LIMIT,
+        tag::FILTERS => FILTERS_LIMIT,
+        tag::PEERS | tag::ADDRESSES => PEERS_LIMIT,
+        tag::SNAPSHOT_MANIFEST => MANIFEST_LIMIT,
+        tag::SNAPSHOT_CHUNK => CHUNK_LIMIT,
+        tag::HANDSHAKE
+        | tag::PING
+        | tag::PONG
+        | tag::GET_PEERS
+        | tag::DISCONNECT
+        | tag::STATUS
+        | tag::GET_BLOCK
+        | tag::GET_HEADERS
+        | tag::GET_BRANCH_HEADERS
+        | tag::GET_RECEIPTS
+        | tag::GET_FILTER_HEADERS
+        | tag::GET_FILTERS
+        | tag::GET_TRANSACTION
+        | tag::CHECKPOINT_VOTE
+        | tag::GET_SNAPSHOTS
+        | tag::SNAPSHOTS
+        | tag::GET_SNAPSHOT_MANIFEST
+        | tag::GET_SNAPSHOT_CHUNK => CONTROL_LIMIT,
+        _ => MAX_FRAME_SIZE - 2,
+    }
+}
\ No newline at end of file
@@ -0,0 +1,281 @@
+//! Node metrics in the Prometheus text exposition format
+//!
+//! Served at `GET /metrics` on the RPC server's address (see `rpc`).
+//! Counters only ever increase and start from zero when the node starts.
+//!
+//! Block and transaction propagation is timed in milliseconds (see
+//! `propagation`): how long blocks and transactions took to arrive, to be
+//! validated and to be applied, and which peers delivered blocks first.
+//!
+//! Healing the chain after a partition (see `block_sync`) counts the
+//! branches switched to, the blocks they rolled back and applied, and the
+//! height of the last common ancestor, and peers banned for offering
+//! branches below finality are counted too.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use ctb_core::chain::PruneStats;
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Upper bounds of the reorganization depth buckets
+pub const REORG_DEPTH_BUCKETS: [u64; 6] = [1, 2, 4, 8, 32, 128];
+
+/// Upper bounds of the propagation time buckets, in milliseconds
+pub const LATENCY_BUCKETS: [u64; 10] = [10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Observations counted by bucket
+#[derive(Debug)]
+struct Histogram {
+    /// Upper bounds of the buckets
+    bounds: &'static [u64],
+    
+    /// Observations by bucket, the last counting those above every bound
+    buckets: Vec<AtomicU64>,
+    
+    /// Total of all observations
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self { bounds, buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(), sum: AtomicU64::new(0) }
+    }
+    
+    fn observe(&self, value: u64) {
+        let bucket = self.bounds.iter().position(|&bound| value <= bound).unwrap_or(self.bounds.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+    
+    fn count(&self) -> u64 {
+        self.buckets.iter().map(|count| count.load(Ordering::Relaxed)).sum()
+    }
+    
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        
+        // Buckets are cumulative
+        let mut count = 0;
+        for (bound, observations) in self.bounds.iter().zip(&self.buckets) {
+            count += observations.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+        }
+        count += self.buckets[self.bounds.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum.load(Ordering::Relaxed));
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// Metrics collected by a node
+#[derive(Debug)]
+pub struct Metrics {
+    /// Reorganizations by number of blocks removed
+    reorgs: Histogram,
+    
+    /// Receipts pruned
+    pruned_receipts: AtomicU64,
+    
+    /// Logs pruned from the log index
+    pruned_logs: AtomicU64,
+    
+    /// Size of the receipts and logs pruned in their binary encoding
+    pruned_bytes: AtomicU64,
+    
+    /// Branches synced from peers that the chain switched to
+    heals: AtomicU64,
+    
+    /// Blocks rolled back switching to synced branches
+    heal_rolled_back: AtomicU64,
+    
+    /// Blocks applied switching to synced branches
+    heal_applied: AtomicU64,
+    
+    /// Height of the last block shared with the latest synced branch switched to
+    heal_common_ancestor: AtomicU64,
+    
+    /// Peers banned
+    peers_banned: AtomicU64,
+    
+    /// Time from blocks' timestamps to their first arrival from a peer
+    block_propagation: Histogram,
+    
+    /// Time from blocks' first arrival to their validation
+    block_validation: Histogram,
+    
+    /// Time from blocks' validation to their being added to the chain
+    block_apply: Histogram,
+    
+    /// Time from transactions' timestamps to their admission from a peer
+    transaction_propagation: Histogram,
+    
+    /// Blocks each peer delivered before any other, by node ID
+    first_seen: Mutex<BTreeMap<String, u64>>,
+    
+    /// RPC requests and calls refused, by reason (see `access`)
+    rpc_rejections: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            reorgs: Histogram::new(&REORG_DEPTH_BUCKETS),
+            pruned_receipts: AtomicU64::new(0),
+            pruned_logs: AtomicU64::new(0),
+            pruned_bytes: AtomicU64::new(0),
+            heals: AtomicU64::new(0),
+            heal_rolled_back: AtomicU64::new(0),
+            heal_applied: AtomicU64::new(0),
+            heal_common_ancestor: AtomicU64::new(0),
+            peers_banned: AtomicU64::new(0),
+            block_propagation: Histogram::new(&LATENCY_BUCKETS),
+            block_validation: Histogram::new(&LATENCY_BUCKETS),
+            block_apply: Histogram::new(&LATENCY_BUCKETS),
+            transaction_propagation: Histogram::new(&LATENCY_BUCKETS),
+            first_seen: Mutex::new(BTreeMap::new()),
+            rpc_rejections: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Metrics {
+    /// Creates metrics with every counter at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Counts a reorganization that removed `depth` blocks
+    pub fn record_reorg(&self, depth: u64) {
+        self.reorgs.observe(depth);
+    }
+    
+    /// Gets the number of reorganizations counted
+    pub fn reorg_count(&self) -> u64 {
+        self.reorgs.count()
+    }
+    
+    /// Counts a switch to a branch synced from a peer, forking off after `common_ancestor`
+    pub fn record_heal(&self, common_ancestor: u64, rolled_back: u64, applied: u64) {
+        self.heals.fetch_add(1, Ordering::Relaxed);
+        self.heal_rolled_back.fetch_add(rolled_back, Ordering::Relaxed);
+        self.heal_applied.fetch_add(applied, Ordering::Relaxed);
+        self.heal_common_ancestor.store(common_ancestor, Ordering::Relaxed);
+    }
+    
+    /// Gets the number of switches to synced branches counted
+    pub fn heal_count(&self) -> u64 {
+        self.heals.load(Ordering::Relaxed)
+    }
+    
+    /// Counts a banned peer
+    pub fn record_ban(&self) {
+        self.peers_banned.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Gets the number of peers banned
+    pub fn peers_banned(&self) -> u64 {
+        self.peers_banned.load(Ordering::Relaxed)
+    }
+    
+    /// Records that a block first arrived `millis` after its timestamp, from `peer`
+    pub fn record_block_arrival(&self, peer: &str, millis: u64) {
+        self.block_propagation.observe(millis);
+        *self.first_seen.lock().unwrap().entry(peer.to_string()).or_default() += 1;
+    }
+    
+    /// Records that a block was validated `millis` after it arrived
+    pub fn record_block_validation(&self, millis: u64) {
+        self.block_validation.observe(millis);
+    }
+    
+    /// Records that a block was added to the chain `millis` after it was validated
+    pub fn record_block_apply(&self, millis: u64) {
+        self.block_apply.observe(millis);
+    }
+    
+    /// Records that a transaction was admitted from a peer `millis` after its timestamp
+    pub fn record_transaction_arrival(&self, millis: u64) {
+        self.transaction_propagation.observe(millis);
+    }
+    
+    /// Gets the number of blocks each peer delivered before any other, by node ID
+    pub fn blocks_first_seen(&self) -> BTreeMap<String, u64> {
+        self.first_seen.lock().unwrap().clone()
+    }
+    
+    /// Counts what pruning receipts and logs dropped
+    pub fn record_prune(&self, stats: &PruneStats) {
+        self.pruned_receipts.fetch_add(stats.receipts, Ordering::Relaxed);
+        self.pruned_logs.fetch_add(stats.logs, Ordering::Relaxed);
+        self.pruned_bytes.fetch_add(stats.bytes, Ordering::Relaxed);
+    }
+    
+    /// Gets the size of the receipts and logs pruned, in bytes
+    pub fn pruned_bytes(&self) -> u64 {
+        self.pruned_bytes.load(Ordering::Relaxed)
+    }
+    
+    /// Counts an RPC request or call refused for a reason
+    pub fn record_rpc_rejection(&self, reason: &'static str) {
+        *self.rpc_rejections.lock().unwrap().entry(reason).or_default() += 1;
+    }
+    
+    /// Gets the number of RPC requests and calls refused, by reason
+    pub fn rpc_rejections(&self) -> BTreeMap<&'static str, u64> {
+        self.rpc_rejections.lock().unwrap().clone()
+    }
+    
+    /// Renders every metric in the text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.reorgs.render(&mut out, "genx_chain_reorgs", "Chain reorganizations by number of blocks removed");
+        
+        let counters = [
+            ("genx_pruned_receipts_total", "Receipts pruned", &self.pruned_receipts),
+            ("genx_pruned_logs_total", "Logs pruned from the log index", &self.pruned_logs),
+            ("genx_pruned_bytes_total", "Bytes of receipts and logs pruned", &self.pruned_bytes),
+            ("genx_chain_heals_total", "Branches synced from peers that the chain switched to", &self.heals),
+            ("genx_chain_heal_blocks_rolled_back_total", "Blocks rolled back switching to synced branches", &self.heal_rolled_back),
+            ("genx_chain_heal_blocks_applied_total", "Blocks applied switching to synced branches", &self.heal_applied),
+            ("genx_peers_banned_total", "Peers banned", &self.peers_banned),
+        ];
+        for (name, help, counter) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+        
+        out.push_str("# HELP genx_chain_heal_common_ancestor_height Height of the last block shared with the latest synced branch switched to\n");
+        out.push_str("# TYPE genx_chain_heal_common_ancestor_height gauge\n");
+        let _ = writeln!(out, "genx_chain_heal_common_ancestor_height {}", self.heal_common_ancestor.load(Ordering::Relaxed));
+        
+        let latencies = [
+            (&self.block_propagation, "genx_block_propagation_ms", "Milliseconds from blocks' timestamps to their first arrival from a peer"),
+            (&self.block_validation, "genx_block_validation_ms", "Milliseconds from blocks' first arrival to their validation"),
+            (&self.block_apply, "genx_block_apply_ms", "Milliseconds from blocks' validation to their being added to the chain"),
+            (&self.transaction_propagation, "genx_transaction_propagation_ms", "Milliseconds from transactions' timestamps to their admission from a peer"),
+        ];
+        for (histogram, name, help) in latencies {
+            histogram.render(&mut out, name, help);
+        }
+        
+        out.push_str("# HELP genx_blocks_first_seen_total Blocks each peer delivered before any other\n");
+        out.push_str("# TYPE genx_blocks_first_seen_total counter\n");
+        for (peer, count) in self.first_seen.lock().unwrap().iter() {
+            let _ = writeln!(out, "genx_blocks_first_seen_total{{peer=\"{}\"}} {}", peer.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"), count);
+        }
+        
+        out.push_str("# HELP genx_rpc_rejections_total RPC requests and calls refused by access control\n");
+        out.push_str("# TYPE genx_rpc_rejections_total counter\n");
+        for (reason, count) in self.rpc_rejections.lock().unwrap().iter() {
+            let _ = writeln!(out, "genx_rpc_rejections_total{{reason=\"{}\"}} {}", reason, count);
+        }
+        out
+    }
+}
\ No newline at end of file
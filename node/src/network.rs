@@ -2,19 +2,82 @@
 //!
 //! This module handles peer discovery, connection management, and
 //! message passing between nodes in the blockchain network.
+//!
+//! Connected peers are keyed by the node ID from their handshake, so a node
+//! holds at most one connection to each peer. When two nodes dial each other
+//! at once, both keep the connection dialed by the node with the smaller ID
+//! and close the other with `Disconnect(Duplicate)`, so they settle on the
+//! same one without further messages. Every handshake a node sends carries
+//! the same random nonce; receiving its own nonce means the connection leads
+//! back to itself, and its address isn't dialed again.
+//!
+//! Each connection moves through the lifecycle of `connection`, from
+//! `open_connection` through the handshake to `Closing`, and is closed once
+//! the peer leaves its handshake, its first message or too many requests
+//! unanswered past their deadlines; `check_connections` checks, every tick,
+//! and frees the slots of those closed. Connections are counted against
+//! `max_peers` from the moment they're opened.
+//!
+//! Messages go out through a `Transport` once one is set, as an in-memory
+//! network does in simulations (see `sim`), and otherwise to the network
+//! handler.
+//!
+//! A node listens on every address of `NetworkConfig::listen_addrs`, so it
+//! can take connections over both IPv4 and IPv6, or on several interfaces.
+//! Its handshakes carry the addresses it can be dialed at, so peers that
+//! connected in can dial it later, and the address each peer is seen at.
+//! The addresses a node advertises in peer exchange are its configured
+//! `external_addr`; failing that, the address `OBSERVED_ADDR_CONFIRMATIONS`
+//! peers agree they see it at, with the port it listens on; failing that,
+//! the addresses it listens on.
+//!
+//! A peer caught misbehaving, such as offering a branch that reverts a
+//! finalized checkpoint, is banned: disconnected with `Disconnect(Banned)`
+//! and its handshakes refused for `BAN_DURATION`.
+//!
+//! Peer exchange only passes on addresses their nodes signed, keeping
+//! those it learns in an `AddressBook` (see `address_book`). A node signs
+//! the addresses it advertises with its identity key, set by
+//! `NetworkConfig::identity_key` or made anew every start, whose address
+//! names the node in its announcements; handshakes still carry the node
+//! ID it's configured with. The addresses the node dialed and completed a
+//! handshake at come first among those `dial_candidates` gives.
 
 use std::collections::{HashMap, HashSet};
-use std::net::{IpAddr, SocketAddr};
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex, RwLock};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use thiserror::Error;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpListener;
 use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::watch;
 use tokio::time;
 
+use ctb_core::paging::{self, Page, PageRequest, SortOrder};
+use ctb_core::BlockHash;
+
+use crate::address_book::{AddressBook, AddressError, NodeIdentity, ANNOUNCEMENT_REFRESH};
+use crate::clock::{Clock, SystemClock};
+use crate::connection::{self, Connection};
+use crate::message::{
+    AddressAnnouncement, ChainStatus, DecodeError, DisconnectReason, HandshakeData, NetworkMessage, PingData,
+    MAX_ANNOUNCEMENTS, MAX_LISTEN_ADDRS,
+};
+
+/// Distinct peers that must see this node at the same address before it's advertised
+pub const OBSERVED_ADDR_CONFIRMATIONS: usize = 2;
+
+/// Most distinct addresses peers are remembered to see this node at
+const MAX_OBSERVED_ADDRS: usize = 16;
+
+/// How long a banned peer's handshakes are refused
+pub const BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+
 /// Network error types
+///
+/// Numbered from 5000, see `error_code`.
 #[derive(Debug, Error)]
 pub enum NetworkError {
     #[error("IO error: {0}")]
@@ -31,13 +94,51 @@ pub enum NetworkError {
     
     #[error("Message error: {0}")]
     MessageError(String),
+    
+    #[error("Decode error: {0}")]
+    DecodeError(#[from] DecodeError),
+    
+    #[error("Peer {node_id} is not connected")]
+    UnknownPeer { node_id: String },
+    
+    #[error("Peer {node_id} didn't answer in time")]
+    PeerTimeout { node_id: String },
+    
+    #[error("The message channel is closed")]
+    ChannelClosed,
+}
+
+impl NetworkError {
+    /// Gets the stable numeric code of the error
+    pub fn error_code(&self) -> u32 {
+        match self {
+            NetworkError::IoError(_) => 5000,
+            NetworkError::SerializationError(_) => 5001,
+            NetworkError::ConnectionError(_) => 5002,
+            NetworkError::PeerError(_) => 5003,
+            NetworkError::MessageError(_) => 5004,
+            NetworkError::DecodeError(_) => 5005,
+            NetworkError::UnknownPeer { .. } => 5006,
+            NetworkError::PeerTimeout { .. } => 5007,
+            NetworkError::ChannelClosed => 5008,
+        }
+    }
 }
 
 /// Result type for network operations
 pub type Result<T> = std::result::Result<T, NetworkError>;
 
+/// Carries encoded messages to connected peers
+///
+/// Sending doesn't wait and delivery isn't guaranteed: a frame to a peer
+/// that can't be reached is dropped.
+pub trait Transport: Send + Sync {
+    /// Sends a frame (see `NetworkMessage::encode`) to a peer, by node ID
+    fn send(&self, peer_id: &str, frame: Vec<u8>);
+}
+
 /// Represents a peer in the network
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Peer {
     /// Peer's network address
     pub address: SocketAddr,
@@ -51,72 +152,129 @@ pub struct Peer {
     /// Peer's reported blockchain height
     pub height: u64,
     
-    /// Whether this is an outbound connection
-    pub outbound: bool,
-}
-
-/// Network message types
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum MessageType {
-    /// Handshake message for initial connection
-    Handshake,
-    
-    /// Ping message to check connection
-    Ping,
+    /// Hash of the peer's latest block, as it last reported
+    #[serde(default)]
+    pub best_hash: BlockHash,
     
-    /// Pong response to ping
-    Pong,
+    /// Weight of the peer's chain, as it last reported, 0 until it sends its status
+    #[serde(default)]
+    pub weight: u128,
     
-    /// Request for peers
-    GetPeers,
-    
-    /// Response with peers
-    Peers,
-    
-    /// New block announcement
-    NewBlock,
-    
-    /// Request for a specific block
-    GetBlock,
+    /// Whether this is an outbound connection
+    pub outbound: bool,
     
-    /// Response with a block
-    Block,
+    /// Listening address of this node the peer connected to, for inbound connections
+    #[serde(default)]
+    pub local_addr: Option<SocketAddr>,
     
-    /// New transaction announcement
-    NewTransaction,
+    /// Addresses the peer can be dialed at, as its handshake gave them
+    #[serde(default)]
+    pub listen_addrs: Vec<SocketAddr>,
+}
+
+impl Peer {
+    /// Gets the addresses the peer can be dialed at
+    ///
+    /// Those it gave, or else the address of an outbound connection to
+    /// it; the source address of an inbound connection is rarely one it
+    /// listens on.
+    pub fn dialable_addrs(&self) -> Vec<SocketAddr> {
+        if !self.listen_addrs.is_empty() {
+            self.listen_addrs.clone()
+        } else if self.outbound {
+            vec![self.address]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// What listings of peers are sorted by
+///
+/// Peers are scored only to disconnect those leaving requests unanswered
+/// (see `connection`), so the best peers to sync from are those whose
+/// chains are highest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerSort {
+    /// Reported height, highest first by default
+    #[default]
+    Height,
     
-    /// Request for a specific transaction
-    GetTransaction,
+    /// When last seen, most recently first by default
+    LastSeen,
     
-    /// Response with a transaction
-    Transaction,
+    /// Node ID, in order by default
+    NodeId,
 }
 
-/// Network message structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Message {
-    /// Message type
-    pub message_type: MessageType,
+impl PeerSort {
+    /// Gets the name of the listing sorted this way, which its cursors are tied to
+    pub fn listing(&self) -> &'static str {
+        match self {
+            Self::Height => "peers_by_height",
+            Self::LastSeen => "peers_by_last_seen",
+            Self::NodeId => "peers_by_node_id",
+        }
+    }
     
-    /// Message payload
-    pub payload: Vec<u8>,
+    /// Gets the direction the listing is sorted in unless a request says otherwise
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            Self::Height | Self::LastSeen => SortOrder::Descending,
+            Self::NodeId => SortOrder::Ascending,
+        }
+    }
     
-    /// Sender's node ID
-    pub sender: String,
+    /// Gets the sort key of a peer, ties broken by node ID
+    pub fn key(&self, peer: &Peer) -> (u64, String) {
+        match self {
+            Self::Height => (peer.height, peer.node_id.clone()),
+            Self::LastSeen => (peer.last_seen, peer.node_id.clone()),
+            Self::NodeId => (0, peer.node_id.clone()),
+        }
+    }
+}
+
+/// What to do with a connection once its peer's handshake arrives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandshakeOutcome {
+    /// The connection is kept
+    ///
+    /// `replaced` is an earlier connection to the same peer it takes the
+    /// place of, to be sent `Disconnect(Duplicate)` and closed.
+    Accepted { replaced: Option<Peer> },
     
-    /// Message timestamp
-    pub timestamp: u64,
+    /// The connection is to be sent `Disconnect(reason)` and closed
+    Rejected(DisconnectReason),
 }
 
 /// Network configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NetworkConfig {
-    /// Local node's listening address
-    pub listen_addr: SocketAddr,
+    /// Addresses the node listens on, one or several
+    ///
+    /// `listen_addr`, a single address, is taken too, as configurations
+    /// written before several were supported have it.
+    #[serde(alias = "listen_addr", deserialize_with = "one_or_many")]
+    pub listen_addrs: Vec<SocketAddr>,
+    
+    /// Address peers are told to dial this node at, if it differs from those it listens on
+    ///
+    /// Nodes behind NAT or a proxy set it to their public address. Without
+    /// it, the address peers observe is advertised once enough agree.
+    pub external_addr: Option<SocketAddr>,
     
     /// Local node's ID (public key)
     pub node_id: String,
     
+    /// Secret key in hex the node signs the addresses it advertises with, see `address_book`
+    ///
+    /// Without one, a new key is made every start, and the node is known
+    /// by a new identity to peers it gives its addresses.
+    pub identity_key: Option<String>,
+    
     /// Bootstrap peers to connect to
     pub bootstrap_peers: Vec<SocketAddr>,
     
@@ -133,8 +291,10 @@ pub struct NetworkConfig {
 impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
-            listen_addr: "127.0.0.1:8333".parse().unwrap(),
+            listen_addrs: vec!["127.0.0.1:8333".parse().unwrap()],
+            external_addr: None,
             node_id: "default_node_id".to_string(),
+            identity_key: None,
             bootstrap_peers: vec![],
             max_peers: 50,
             discovery_interval: 60,
@@ -143,49 +303,127 @@ impl Default for NetworkConfig {
     }
 }
 
+/// Deserializes a single address or a list of them
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<SocketAddr>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(SocketAddr),
+        Many(Vec<SocketAddr>),
+    }
+    
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(addr) => vec![addr],
+        OneOrMany::Many(addrs) => addrs,
+    })
+}
+
 /// Manages the P2P network for the blockchain
 pub struct NetworkManager {
     /// Network configuration
     config: NetworkConfig,
     
-    /// Connected peers
+    /// Connected peers, by node ID
     peers: Arc<RwLock<HashMap<String, Peer>>>,
     
-    /// Known peer addresses
-    known_addresses: Arc<RwLock<HashSet<SocketAddr>>>,
+    /// Known peer addresses, with the node ID found there once learned
+    known_addresses: Arc<RwLock<HashMap<SocketAddr, Option<String>>>>,
+    
+    /// Addresses learned from peer exchange and confirmed by handshakes
+    address_book: Arc<RwLock<AddressBook>>,
+    
+    /// Key this node signs its address announcements with
+    identity: NodeIdentity,
+    
+    /// This node's announcements of the addresses it advertises, as last signed
+    announcements: Arc<Mutex<Vec<AddressAnnouncement>>>,
+    
+    /// Nonce sent in this node's handshakes
+    handshake_nonce: u64,
+    
+    /// Addresses the node's listeners are bound to, once started
+    bound_addrs: Vec<SocketAddr>,
+    
+    /// Addresses peers see this node at, each with the node IDs of the peers seeing it there
+    observed_addrs: Arc<RwLock<HashMap<SocketAddr, HashSet<String>>>>,
+    
+    /// Banned peers, by node ID, with when their bans end in milliseconds
+    banned: Arc<RwLock<HashMap<String, u64>>>,
     
     /// Channel for sending messages to the network handler
-    message_sender: Option<Sender<(Message, Option<String>)>>,
+    message_sender: Option<Sender<(NetworkMessage, Option<String>)>>,
     
-    /// Last discovery time
-    last_discovery: Instant,
+    /// Transport messages are sent through instead of the network handler, if set
+    transport: Option<Arc<dyn Transport>>,
+    
+    /// Open connections, by the address of their other end
+    connections: Arc<RwLock<HashMap<SocketAddr, Connection>>>,
+    
+    /// Clock the connections' timeouts are read from
+    clock: Arc<dyn Clock>,
+    
+    /// Seconds between discovery rounds, followed by the discovery task as it changes
+    discovery_interval: watch::Sender<u64>,
 }
 
 impl NetworkManager {
     /// Creates a new network manager with the given configuration
     pub fn new(config: NetworkConfig) -> Self {
+        let (discovery_interval, _) = watch::channel(config.discovery_interval);
         Self {
             config,
             peers: Arc::new(RwLock::new(HashMap::new())),
-            known_addresses: Arc::new(RwLock::new(HashSet::new())),
+            known_addresses: Arc::new(RwLock::new(HashMap::new())),
+            address_book: Arc::new(RwLock::new(AddressBook::new())),
+            identity: NodeIdentity::generate(),
+            announcements: Arc::new(Mutex::new(Vec::new())),
+            handshake_nonce: rand::random(),
+            bound_addrs: Vec::new(),
+            observed_addrs: Arc::new(RwLock::new(HashMap::new())),
+            banned: Arc::new(RwLock::new(HashMap::new())),
             message_sender: None,
-            last_discovery: Instant::now(),
+            transport: None,
+            connections: Arc::new(RwLock::new(HashMap::new())),
+            clock: Arc::new(SystemClock),
+            discovery_interval,
         }
     }
     
+    /// Changes the most peers connected at once
+    ///
+    /// Only new connections are refused once there are as many; peers
+    /// already connected over the limit stay connected.
+    pub fn set_max_peers(&mut self, max_peers: usize) {
+        self.config.max_peers = max_peers;
+    }
+    
+    /// Changes the seconds between peer discovery rounds, from the next round on
+    pub fn set_discovery_interval(&mut self, seconds: u64) {
+        self.config.discovery_interval = seconds;
+        self.discovery_interval.send_replace(seconds);
+    }
+    
     /// Starts the network manager
+    ///
+    /// Every listening address is bound before anything else starts, so
+    /// the start fails if any of them can't be.
     pub async fn start(&mut self) -> Result<()> {
+        let mut listeners = Vec::new();
+        for addr in &self.config.listen_addrs {
+            let listener = TcpListener::bind(addr).await?;
+            let local_addr = listener.local_addr()?;
+            println!("Listening on {}", local_addr);
+            self.bound_addrs.push(local_addr);
+            listeners.push(listener);
+        }
+        
         // Create a channel for message passing
         let (tx, rx) = mpsc::channel(100);
         self.message_sender = Some(tx.clone());
         
         // Start the network handler
-        let peers = self.peers.clone();
-        let known_addresses = self.known_addresses.clone();
-        let config = self.config.clone();
-        
         tokio::spawn(async move {
-            if let Err(e) = Self::run_network_handler(config, peers, known_addresses, rx).await {
+            if let Err(e) = Self::run_network_handler(listeners, rx).await {
                 eprintln!("Network handler error: {}", e);
             }
         });
@@ -203,26 +441,18 @@ impl NetworkManager {
     
     /// Runs the main network handler
     async fn run_network_handler(
-        config: NetworkConfig,
-        peers: Arc<RwLock<HashMap<String, Peer>>>,
-        known_addresses: Arc<RwLock<HashSet<SocketAddr>>>,
-        mut rx: Receiver<(Message, Option<String>)>,
+        listeners: Vec<TcpListener>,
+        mut rx: Receiver<(NetworkMessage, Option<String>)>,
     ) -> Result<()> {
-        // Start listening for incoming connections
-        let listener = TcpListener::bind(config.listen_addr).await?;
-        println!("Listening on {}", config.listen_addr);
+        // Accept incoming connections on every listening address
+        for listener in listeners {
+            tokio::spawn(Self::accept_connections(listener));
+        }
         
         loop {
             tokio::select! {
-                // Accept incoming connections
-                Ok((socket, addr)) = listener.accept() => {
-                    println!("Accepted connection from {}", addr);
-                    // Handle the connection
-                    // In a real implementation, we would spawn a task to handle this connection
-                }
-                
                 // Process outgoing messages
-                Some((message, target)) = rx.recv() => {
+                Some(_) = rx.recv() => {
                     // Send the message to the target peer or broadcast to all peers
                     // In a real implementation, we would handle message sending here
                 }
@@ -236,17 +466,96 @@ impl NetworkManager {
         }
     }
     
-    /// Connects to a peer at the given address
-    pub async fn connect_to_peer(&self, addr: SocketAddr) -> Result<()> {
-        // Check if we're already connected to this peer
-        {
-            let peers = self.peers.read().unwrap();
-            for peer in peers.values() {
-                if peer.address == addr {
-                    return Ok(());
+    /// Accepts connections on a listener, each knowing the address it reached
+    async fn accept_connections(listener: TcpListener) {
+        let Ok(local_addr) = listener.local_addr() else {
+            return;
+        };
+        loop {
+            match listener.accept().await {
+                Ok((_socket, addr)) => {
+                    println!("Accepted connection from {} on {}", addr, local_addr);
+                    // Handle the connection
+                    // In a real implementation, we would spawn a task to handle this connection,
+                    // accepting its handshake with `local_addr` as the address it reached
                 }
+                Err(e) => eprintln!("Cannot accept a connection on {}: {}", local_addr, e),
             }
         }
+    }
+    
+    /// Gets the addresses the node listens on, with the ports bound once started
+    pub fn listen_addrs(&self) -> &[SocketAddr] {
+        if self.bound_addrs.is_empty() {
+            &self.config.listen_addrs
+        } else {
+            &self.bound_addrs
+        }
+    }
+    
+    /// Gets the addresses peers are told to dial this node at
+    ///
+    /// The configured external address if any, else the address enough
+    /// peers see this node at, else the bound listening addresses that can
+    /// be dialed. Nothing is advertised before the node starts listening,
+    /// unless an external address is configured.
+    pub fn advertised_addrs(&self) -> Vec<SocketAddr> {
+        if let Some(external_addr) = self.config.external_addr {
+            return vec![external_addr];
+        }
+        if let Some(observed_addr) = self.observed_addr() {
+            return vec![observed_addr];
+        }
+        let mut addrs: Vec<SocketAddr> = self.bound_addrs.iter()
+            .filter(|addr| !addr.ip().is_unspecified() && addr.port() != 0)
+            .copied()
+            .collect();
+        addrs.truncate(MAX_LISTEN_ADDRS);
+        addrs
+    }
+    
+    /// Gets the address most peers see this node at, once `OBSERVED_ADDR_CONFIRMATIONS` agree
+    ///
+    /// Ties go to the smallest address, so the choice doesn't depend on
+    /// the order peers connected in.
+    pub fn observed_addr(&self) -> Option<SocketAddr> {
+        let observed_addrs = self.observed_addrs.read().unwrap();
+        observed_addrs.iter()
+            .filter(|(_, peers)| peers.len() >= OBSERVED_ADDR_CONFIRMATIONS)
+            .max_by(|(a, a_peers), (b, b_peers)| a_peers.len().cmp(&b_peers.len()).then(b.cmp(a)))
+            .map(|(addr, _)| *addr)
+    }
+    
+    /// Records the address a peer sees this node at
+    ///
+    /// The peer sees the IP address; the port is that of the listening
+    /// address the connection reached, or of one of the same family for an
+    /// outbound connection, whose source port isn't listened on.
+    fn observe_addr(&self, node_id: &str, observed_addr: SocketAddr, local_addr: Option<SocketAddr>) {
+        if self.config.external_addr.is_some() || observed_addr.ip().is_unspecified() {
+            return;
+        }
+        let port = local_addr
+            .or_else(|| self.listen_addrs().iter().find(|addr| addr.is_ipv4() == observed_addr.is_ipv4()).copied())
+            .map(|addr| addr.port())
+            .filter(|port| *port != 0);
+        let Some(port) = port else {
+            return;
+        };
+        let addr = SocketAddr::new(observed_addr.ip(), port);
+        let mut observed_addrs = self.observed_addrs.write().unwrap();
+        if !observed_addrs.contains_key(&addr) && observed_addrs.len() >= MAX_OBSERVED_ADDRS {
+            return;
+        }
+        observed_addrs.entry(addr).or_default().insert(node_id.to_string());
+    }
+    
+    /// Connects to a peer at the given address
+    pub async fn connect_to_peer(&self, addr: SocketAddr) -> Result<()> {
+        // Check if we're already connected to this peer, or the address is our own
+        if !self.should_dial(addr) {
+            return Ok(());
+        }
         
         // Connect to the peer
         println!("Connecting to peer at {}", addr);
@@ -255,85 +564,559 @@ impl NetworkManager {
         // and perform a handshake with the peer
         
         // Add the peer to our known addresses
+        self.add_known_address(addr);
+        
+        Ok(())
+    }
+    
+    /// Remembers a peer address to dial, returning whether it wasn't known already
+    pub fn add_known_address(&self, addr: SocketAddr) -> bool {
+        let mut known_addresses = self.known_addresses.write().unwrap();
+        if known_addresses.contains_key(&addr) {
+            return false;
+        }
+        known_addresses.insert(addr, None);
+        true
+    }
+    
+    /// Gets the key this node signs its address announcements with
+    pub fn identity(&self) -> &NodeIdentity {
+        &self.identity
+    }
+    
+    /// Signs this node's address announcements with another key from now on
+    pub fn set_identity(&mut self, identity: NodeIdentity) {
+        self.identity = identity;
+        self.announcements.lock().unwrap().clear();
+    }
+    
+    /// Gets this node's announcements of the addresses it advertises
+    ///
+    /// They're signed again once `ANNOUNCEMENT_REFRESH` old, or when the
+    /// addresses advertised change.
+    pub fn local_announcements(&self) -> Vec<AddressAnnouncement> {
+        let now = self.clock.now();
+        let addrs = self.advertised_addrs();
+        let mut announcements = self.announcements.lock().unwrap();
+        let current = announcements.len() == addrs.len()
+            && announcements.iter().zip(&addrs).all(|(announcement, addr)| {
+                announcement.addr == *addr && now.saturating_sub(announcement.timestamp) < ANNOUNCEMENT_REFRESH.as_secs()
+            });
+        if !current {
+            *announcements = addrs.into_iter().map(|addr| self.identity.announce(addr, now)).collect();
+        }
+        announcements.clone()
+    }
+    
+    /// Gets the announcements to answer `GetPeers` with: this node's, then those in its address book
+    pub fn announcements(&self) -> Vec<AddressAnnouncement> {
+        let mut announcements = self.local_announcements();
+        announcements.extend(self.address_book.read().unwrap().announcements());
+        announcements.truncate(MAX_ANNOUNCEMENTS);
+        announcements
+    }
+    
+    /// Takes the announcements a peer sent into the address book, returning how many addresses were new
+    ///
+    /// Announcements the book refuses (see `AddressBook::insert`) are
+    /// dropped and returned with why; those of this node are skipped.
+    pub fn add_announcements(&self, peer_id: &str, announcements: Vec<AddressAnnouncement>) -> (usize, Vec<AddressError>) {
+        let now = self.clock.now();
+        let mut address_book = self.address_book.write().unwrap();
+        let (mut added, mut refused) = (0, Vec::new());
+        for announcement in announcements {
+            if announcement.node_id == self.identity.node_id() {
+                continue;
+            }
+            match address_book.insert(peer_id, announcement, now) {
+                Ok(true) => added += 1,
+                Ok(false) => {}
+                Err(e) => refused.push(e),
+            }
+        }
+        (added, refused)
+    }
+    
+    /// Forgets the addresses last announced or confirmed `ANNOUNCEMENT_TTL` ago, returning how many
+    pub fn expire_addresses(&self) -> usize {
+        self.address_book.write().unwrap().expire(self.clock.now())
+    }
+    
+    /// Gets the addresses worth dialing, in the order they're best dialed in
+    ///
+    /// Those the node completed a handshake at first, then those set by
+    /// the operator or given by peers for themselves, then those only
+    /// announced in peer exchange. Addresses `should_dial` rules out are
+    /// left out.
+    pub fn dial_candidates(&self) -> Vec<SocketAddr> {
+        let (confirmed, mut known, announced) = {
+            let address_book = self.address_book.read().unwrap();
+            let (confirmed, announced): (Vec<_>, Vec<_>) = address_book
+                .dial_order()
+                .into_iter()
+                .partition(|addr| address_book.is_confirmed(addr));
+            let known: Vec<SocketAddr> = self.known_addresses.read().unwrap()
+                .keys()
+                .filter(|addr| !address_book.contains(addr))
+                .copied()
+                .collect();
+            (confirmed, known, announced)
+        };
+        known.sort();
+        confirmed.into_iter()
+            .chain(known)
+            .chain(announced)
+            .filter(|addr| self.should_dial(*addr))
+            .collect()
+    }
+    
+    /// Creates the handshake this node sends on a new connection
+    pub fn handshake(&self, height: u64, best_hash: BlockHash) -> HandshakeData {
+        HandshakeData {
+            node_id: self.config.node_id.clone(),
+            height,
+            best_hash,
+            timestamp: ctb_core::current_timestamp(),
+            nonce: self.handshake_nonce,
+            listen_addrs: self.advertised_addrs(),
+            observed_addr: None,
+        }
+    }
+    
+    /// Registers a connection whose peer has sent its handshake
+    ///
+    /// A handshake carrying this node's nonce is from the node itself, and
+    /// the connection is rejected, as it is if the peer is banned. A second connection to a connected peer
+    /// is kept only if it was dialed by whichever of the two nodes has the
+    /// smaller ID, replacing the first; otherwise it's rejected, so a
+    /// connection dialed in the same direction as an existing one never
+    /// replaces it. A new peer is rejected once `max_peers` are connected.
+    /// Node IDs are taken as the peers claim them.
+    ///
+    /// The address an outbound connection was dialed at is remembered with
+    /// the node ID found there, so it isn't dialed again while that node is
+    /// connected, and so are the listening addresses the peer gives, so a
+    /// peer that connected in can be dialed once it's gone. The address of
+    /// an accepted outbound connection is confirmed in the address book.
+    /// `local_addr` is the listening address an inbound connection reached,
+    /// `None` for outbound ones.
+    pub fn accept_handshake(
+        &self,
+        address: SocketAddr,
+        local_addr: Option<SocketAddr>,
+        outbound: bool,
+        handshake: &HandshakeData,
+    ) -> HandshakeOutcome {
+        let self_connection = handshake.nonce == self.handshake_nonce;
+        if outbound {
+            let node_id = if self_connection { &self.config.node_id } else { &handshake.node_id };
+            let mut known_addresses = self.known_addresses.write().unwrap();
+            known_addresses.insert(address, Some(node_id.clone()));
+        }
+        if self_connection {
+            return HandshakeOutcome::Rejected(DisconnectReason::SelfConnection);
+        }
+        if self.is_banned(&handshake.node_id) {
+            return HandshakeOutcome::Rejected(DisconnectReason::Banned);
+        }
+        
+        let listen_addrs: Vec<SocketAddr> = handshake.listen_addrs.iter().take(MAX_LISTEN_ADDRS).copied().collect();
         {
             let mut known_addresses = self.known_addresses.write().unwrap();
-            known_addresses.insert(addr);
+            for addr in &listen_addrs {
+                // An address found to lead back to this node stays known as such
+                let node_id = known_addresses.entry(*addr).or_default();
+                if node_id.as_ref() != Some(&self.config.node_id) {
+                    *node_id = Some(handshake.node_id.clone());
+                }
+            }
+        }
+        if let Some(observed_addr) = handshake.observed_addr {
+            self.observe_addr(&handshake.node_id, observed_addr, local_addr);
         }
         
-        Ok(())
+        // Outbound connections win the tie-break if this node has the smaller ID
+        let preferred_outbound = self.config.node_id < handshake.node_id;
+        let mut peers = self.peers.write().unwrap();
+        if let Some(existing) = peers.get(&handshake.node_id) {
+            if existing.outbound == preferred_outbound || outbound != preferred_outbound {
+                return HandshakeOutcome::Rejected(DisconnectReason::Duplicate);
+            }
+        } else if peers.len() >= self.config.max_peers {
+            return HandshakeOutcome::Rejected(DisconnectReason::TooManyPeers);
+        }
+        
+        let peer = Peer {
+            address,
+            node_id: handshake.node_id.clone(),
+            last_seen: ctb_core::current_timestamp(),
+            height: handshake.height,
+            best_hash: handshake.best_hash,
+            weight: 0,
+            outbound,
+            local_addr: if outbound { None } else { local_addr },
+            listen_addrs,
+        };
+        let replaced = peers.insert(handshake.node_id.clone(), peer);
+        if outbound {
+            self.address_book.write().unwrap().confirm(address, self.clock.now());
+        }
+        HandshakeOutcome::Accepted { replaced }
+    }
+    
+    /// Checks whether an address may lead to a node this node isn't connected to
+    ///
+    /// Addresses that led back to this node, or to a node still connected,
+    /// aren't worth dialing.
+    pub fn should_dial(&self, addr: SocketAddr) -> bool {
+        let known_addresses = self.known_addresses.read().unwrap();
+        let peers = self.peers.read().unwrap();
+        match known_addresses.get(&addr) {
+            Some(Some(node_id)) => *node_id != self.config.node_id && !peers.contains_key(node_id),
+            _ => !peers.values().any(|peer| peer.address == addr),
+        }
     }
     
     /// Starts the peer discovery process
     fn start_discovery(&self) {
-        let peers = self.peers.clone();
-        let known_addresses = self.known_addresses.clone();
-        let config = self.config.clone();
+        let mut discovery_interval = self.discovery_interval.subscribe();
         let tx = self.message_sender.clone().unwrap();
         
         tokio::spawn(async move {
-            let mut interval = time::interval(Duration::from_secs(config.discovery_interval));
+            let mut interval = time::interval(Duration::from_secs(*discovery_interval.borrow_and_update()));
             
             loop {
-                interval.tick().await;
-                
-                // Request peers from our connected peers
-                let message = Message {
-                    message_type: MessageType::GetPeers,
-                    payload: vec![],
-                    sender: config.node_id.clone(),
-                    timestamp: core::current_timestamp(),
-                };
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    changed = discovery_interval.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        let period = Duration::from_secs(*discovery_interval.borrow_and_update());
+                        interval = time::interval_at(time::Instant::now() + period, period);
+                        continue;
+                    }
+                }
                 
-                // Broadcast the message to all peers
-                let _ = tx.send((message, None)).await;
+                // Request peers from all our connected peers
+                let _ = tx.send((NetworkMessage::GetPeers, None)).await;
                 
                 // In a real implementation, we would also try to connect to new peers here
             }
         });
     }
     
+    /// Sends messages through a transport from now on, rather than the network handler
+    pub fn set_transport(&mut self, transport: Arc<dyn Transport>) {
+        self.transport = Some(transport);
+    }
+    
+    /// Reads the connections' timeouts from a clock from now on, see `clock`
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+    
+    /// Tracks a connection just made, returning whether there's a slot for it
+    ///
+    /// A connection is refused once `max_peers` are open, handshaken or
+    /// not, or if one to the same address is.
+    pub fn open_connection(&self, address: SocketAddr, outbound: bool) -> bool {
+        let mut connections = self.connections.write().unwrap();
+        if connections.len() >= self.config.max_peers || connections.contains_key(&address) {
+            return false;
+        }
+        connections.insert(address, Connection::new(address, outbound, self.clock.now_millis()));
+        true
+    }
+    
+    /// Notes that this node's handshake was sent over a connection
+    pub fn handshake_sent(&self, address: SocketAddr) {
+        if let Some(connection) = self.connections.write().unwrap().get_mut(&address) {
+            connection.handshake_sent(self.clock.now_millis());
+        }
+    }
+    
+    /// Registers the handshake a connection's peer sent, see `accept_handshake`
+    ///
+    /// A connection not opened with `open_connection` is taken to be
+    /// inbound. An accepted connection is established, any it replaces
+    /// closed, and the peer pinged, so its first message is due; a rejected
+    /// one is closed, its `Disconnect` left to the caller.
+    pub fn handshake_received(
+        &self,
+        address: SocketAddr,
+        local_addr: Option<SocketAddr>,
+        handshake: &HandshakeData,
+    ) -> HandshakeOutcome {
+        let now = self.clock.now_millis();
+        let outbound = {
+            let mut connections = self.connections.write().unwrap();
+            connections.entry(address).or_insert_with(|| Connection::new(address, false, now)).is_outbound()
+        };
+        
+        let outcome = self.accept_handshake(address, local_addr, outbound, handshake);
+        let mut connections = self.connections.write().unwrap();
+        match &outcome {
+            HandshakeOutcome::Accepted { replaced } => {
+                if let Some(replaced) = replaced.as_ref().filter(|replaced| replaced.address != address) {
+                    if let Some(mut connection) = connections.remove(&replaced.address) {
+                        connection.close(now, "replaced by a preferred connection");
+                    }
+                }
+                if let Some(connection) = connections.get_mut(&address) {
+                    connection.handshake_received(&handshake.node_id, now);
+                }
+                drop(connections);
+                self.post(&NetworkMessage::Ping(PingData { nonce: now }), Some(&handshake.node_id), None);
+            }
+            HandshakeOutcome::Rejected(reason) => {
+                if let Some(mut connection) = connections.remove(&address) {
+                    connection.close(now, &format!("handshake rejected: {:?}", reason));
+                }
+            }
+        }
+        outcome
+    }
+    
+    /// Notes that a message arrived from a peer
+    ///
+    /// It may answer a request the peer was sent, or be the first message
+    /// the connection waits for. A `Disconnect` closes the connection.
+    pub fn message_received(&self, peer_id: &str, message: &NetworkMessage) {
+        let now = self.clock.now_millis();
+        let Some(address) = self.peers.read().unwrap().get(peer_id).map(|peer| peer.address) else {
+            return;
+        };
+        let mut connections = self.connections.write().unwrap();
+        if let NetworkMessage::Disconnect(reason) = message {
+            if let Some(mut connection) = connections.remove(&address) {
+                connection.close(now, &format!("disconnected by the peer: {:?}", reason));
+            }
+            drop(connections);
+            self.peers.write().unwrap().remove(peer_id);
+            return;
+        }
+        if let Some(connection) = connections.get_mut(&address) {
+            connection.message_received(message, now);
+        }
+    }
+    
+    /// Checks every connection's timeouts, closing and returning those that ran out
+    ///
+    /// Connections are checked in order of address. The peer of each one
+    /// closed is sent `Disconnect(Timeout)`, if it was handshaken, and
+    /// forgotten, freeing the connection's slot.
+    pub fn check_connections(&self) -> Vec<Connection> {
+        let now = self.clock.now_millis();
+        let mut closed = Vec::new();
+        {
+            let mut connections = self.connections.write().unwrap();
+            let mut addresses: Vec<SocketAddr> = connections.keys().copied().collect();
+            addresses.sort();
+            for address in addresses {
+                let connection = connections.get_mut(&address).unwrap();
+                let timeouts = connection.poll(now);
+                for timeout in &timeouts {
+                    eprintln!("Connection to {} timed out: {}", address, timeout);
+                }
+                if let Some(reason) = connection.should_close(&timeouts) {
+                    let mut connection = connections.remove(&address).unwrap();
+                    connection.close(now, &reason);
+                    closed.push(connection);
+                }
+            }
+        }
+        
+        for connection in &closed {
+            let Some(node_id) = connection.node_id() else {
+                continue;
+            };
+            let is_peer = self.peers.read().unwrap().get(node_id).is_some_and(|peer| peer.address == connection.address());
+            if is_peer {
+                self.post(&NetworkMessage::Disconnect(DisconnectReason::Timeout), Some(node_id), None);
+                self.peers.write().unwrap().remove(node_id);
+            }
+        }
+        closed
+    }
+    
+    /// Gets the connection to a connected peer
+    pub fn connection(&self, peer_id: &str) -> Option<Connection> {
+        let address = self.peers.read().unwrap().get(peer_id)?.address;
+        self.connections.read().unwrap().get(&address).cloned()
+    }
+    
+    /// Gets the open connections, in order of address
+    pub fn connections(&self) -> Vec<Connection> {
+        let mut connections: Vec<Connection> = self.connections.read().unwrap().values().cloned().collect();
+        connections.sort_by_key(Connection::address);
+        connections
+    }
+    
+    /// Gets the number of open connections, handshaken or not
+    pub fn connection_count(&self) -> usize {
+        self.connections.read().unwrap().len()
+    }
+    
+    /// Notes a message sent to its targets' connections, tracking it if the peers must answer it
+    fn track_sent(&self, message: &NetworkMessage, peer_id: Option<&str>, except: Option<&str>) {
+        if connection::response_deadline(message).is_none() {
+            return;
+        }
+        let now = self.clock.now_millis();
+        let peers = self.peers.read().unwrap();
+        let mut connections = self.connections.write().unwrap();
+        let targets = peers.values().filter(|peer| match peer_id {
+            Some(peer_id) => peer.node_id == peer_id,
+            None => Some(peer.node_id.as_str()) != except,
+        });
+        for peer in targets {
+            if let Some(connection) = connections.get_mut(&peer.address) {
+                connection.message_sent(message, now);
+            }
+        }
+    }
+    
+    /// Sends a message to a peer, or to every connected peer but `except`, without waiting
+    ///
+    /// Through the transport, peers are sent the message in order of node
+    /// ID. Without one, the message is queued for the network handler and
+    /// dropped if its queue is full.
+    pub fn post(&self, message: &NetworkMessage, peer_id: Option<&str>, except: Option<&str>) {
+        self.track_sent(message, peer_id, except);
+        let Some(transport) = &self.transport else {
+            if let Some(tx) = &self.message_sender {
+                let _ = tx.try_send((message.clone(), peer_id.map(str::to_string)));
+            }
+            return;
+        };
+        
+        let frame = message.encode();
+        match peer_id {
+            Some(peer_id) => transport.send(peer_id, frame),
+            None => {
+                let mut peer_ids: Vec<String> = self.peers.read().unwrap().keys().cloned().collect();
+                peer_ids.sort();
+                for peer_id in peer_ids.iter().filter(|peer_id| Some(peer_id.as_str()) != except) {
+                    transport.send(peer_id, frame.clone());
+                }
+            }
+        }
+    }
+    
     /// Broadcasts a message to all connected peers
-    pub async fn broadcast_message(&self, message: Message) -> Result<()> {
+    pub async fn broadcast_message(&self, message: NetworkMessage) -> Result<()> {
+        if self.transport.is_some() {
+            self.post(&message, None, None);
+            return Ok(());
+        }
         if let Some(tx) = &self.message_sender {
-            tx.send((message, None)).await.map_err(|e| {
-                NetworkError::MessageError(format!("Failed to send message: {}", e))
-            })?;
+            tx.send((message, None)).await.map_err(|_| NetworkError::ChannelClosed)?;
         }
         
         Ok(())
     }
     
     /// Sends a message to a specific peer
-    pub async fn send_message(&self, message: Message, peer_id: &str) -> Result<()> {
+    pub async fn send_message(&self, message: NetworkMessage, peer_id: &str) -> Result<()> {
+        if self.transport.is_some() {
+            self.post(&message, Some(peer_id), None);
+            return Ok(());
+        }
         if let Some(tx) = &self.message_sender {
-            tx.send((message, Some(peer_id.to_string()))).await.map_err(|e| {
-                NetworkError::MessageError(format!("Failed to send message: {}", e))
-            })?;
+            tx.send((message, Some(peer_id.to_string()))).await.map_err(|_| NetworkError::ChannelClosed)?;
         }
         
         Ok(())
     }
     
-    /// Gets all connected peers
+    /// Gets a copy of all connected peers
+    ///
+    /// Prefer `for_each_peer` to just read them.
+    #[deprecated(note = "copies every peer; use `list_peers` to page through them or `for_each_peer` to read them")]
     pub fn get_peers(&self) -> Vec<Peer> {
         let peers = self.peers.read().unwrap();
         peers.values().cloned().collect()
     }
     
+    /// Gets a page of the connected peers
+    ///
+    /// Heights and times change as peers announce blocks, so a peer may
+    /// move while a client pages by them (see `ctb_core::paging`).
+    pub fn list_peers(&self, sort: PeerSort, request: &PageRequest) -> ctb_core::Result<Page<Peer>> {
+        let peers = self.peers.read().unwrap();
+        let page = paging::paginate(peers.values(), sort.listing(), request, sort.default_order(), |peer| sort.key(peer))?;
+        Ok(page.map(Peer::clone))
+    }
+    
+    /// Calls `f` with each connected peer, without copying them
+    ///
+    /// The peer table is locked while `f` runs, so it must not block.
+    pub fn for_each_peer(&self, f: impl FnMut(&Peer)) {
+        let peers = self.peers.read().unwrap();
+        peers.values().for_each(f);
+    }
+    
+    /// Records the height a peer's chain reached, as it announced, and that it was just seen
+    ///
+    /// Returns whether the peer is connected.
+    pub fn update_peer_height(&self, peer_id: &str, height: u64, now: u64) -> bool {
+        let mut peers = self.peers.write().unwrap();
+        let Some(peer) = peers.get_mut(peer_id) else {
+            return false;
+        };
+        peer.height = height;
+        peer.last_seen = now;
+        true
+    }
+    
+    /// Records where a peer's chain is, as its status said, and that it was just seen
+    ///
+    /// Returns whether the peer is connected.
+    pub fn update_peer_status(&self, peer_id: &str, status: &ChainStatus, now: u64) -> bool {
+        let mut peers = self.peers.write().unwrap();
+        let Some(peer) = peers.get_mut(peer_id) else {
+            return false;
+        };
+        peer.height = status.height;
+        peer.best_hash = status.best_hash;
+        peer.weight = status.weight;
+        peer.last_seen = now;
+        true
+    }
+    
     /// Gets the number of connected peers
     pub fn peer_count(&self) -> usize {
         let peers = self.peers.read().unwrap();
         peers.len()
     }
     
-    /// Disconnects from a peer
+    /// Disconnects from a peer, closing its connection
     pub fn disconnect_peer(&self, peer_id: &str) -> Result<()> {
         let mut peers = self.peers.write().unwrap();
-        if peers.remove(peer_id).is_some() {
+        if let Some(peer) = peers.remove(peer_id) {
+            if let Some(mut connection) = self.connections.write().unwrap().remove(&peer.address) {
+                connection.close(self.clock.now_millis(), "disconnected");
+            }
             println!("Disconnected from peer {}", peer_id);
             Ok(())
         } else {
-            Err(NetworkError::PeerError(format!("Peer {} not found", peer_id)))
+            Err(NetworkError::UnknownPeer { node_id: peer_id.to_string() })
         }
     }
+    
+    /// Bans a peer for `BAN_DURATION`, sending it `Disconnect(Banned)` and disconnecting it if it's connected
+    pub fn ban_peer(&self, peer_id: &str, reason: &str) {
+        let until = self.clock.now_millis() + BAN_DURATION.as_millis() as u64;
+        self.banned.write().unwrap().insert(peer_id.to_string(), until);
+        println!("Banned peer {} for {}", peer_id, reason);
+        self.post(&NetworkMessage::Disconnect(DisconnectReason::Banned), Some(peer_id), None);
+        let _ = self.disconnect_peer(peer_id);
+    }
+    
+    /// Checks whether a peer is banned
+    pub fn is_banned(&self, peer_id: &str) -> bool {
+        let now = self.clock.now_millis();
+        self.banned.read().unwrap().get(peer_id).is_some_and(|&until| now < until)
+    }
 }
\ No newline at end of file
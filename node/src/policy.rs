@@ -0,0 +1,182 @@
+//! Admission policy for transaction senders
+//!
+//! Operators can ban sender addresses, and on private chains can limit
+//! which senders are accepted at all with an allowlist. The policy is
+//! checked wherever a transaction enters this node's mempool: submitted
+//! over RPC, sent through the Ethereum API or relayed by a peer.
+//!
+//! This is mempool policy, not consensus. Blocks are accepted whoever sent
+//! their transactions, so a banned sender's transactions still take
+//! effect once another validator includes them.
+//!
+//! Bans made at runtime are saved to `banlist.json` in the data directory
+//! and loaded again when the node starts, along with those in the config.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use ctb_core::transaction::Transaction;
+
+/// Name of the file bans are saved to, in the data directory
+pub const BANLIST_FILE: &str = "banlist.json";
+
+/// Admission policy configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    /// Senders banned from the mempool
+    pub banned: Vec<String>,
+    
+    /// Only senders accepted into the mempool, if set
+    pub allowlist: Option<Vec<String>>,
+}
+
+/// A banned sender address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    /// Banned address
+    pub address: String,
+    
+    /// Why the address was banned
+    pub reason: String,
+    
+    /// When the address was banned
+    pub banned_at: u64,
+}
+
+/// Reason a transaction was refused
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PolicyError {
+    #[error("Sender {address} is banned: {reason}")]
+    Banned { address: String, reason: String },
+    
+    #[error("Sender {0} is not on the allowlist")]
+    NotAllowed(String),
+}
+
+/// Banlist and allowlist of transaction senders, shared by the node's admission paths
+#[derive(Debug)]
+pub struct AdmissionPolicy {
+    /// Banned senders, by address
+    bans: RwLock<BTreeMap<String, Ban>>,
+    
+    /// Only senders accepted, if set
+    allowlist: Option<HashSet<String>>,
+    
+    /// File bans are saved to, if they're saved at all
+    path: Option<PathBuf>,
+}
+
+impl AdmissionPolicy {
+    /// Creates a policy from the configuration, saving bans to `path` if given
+    pub fn new(config: &PolicyConfig, path: Option<PathBuf>) -> Self {
+        let banned_at = ctb_core::current_timestamp();
+        let bans = config
+            .banned
+            .iter()
+            .map(|address| {
+                let ban = Ban { address: address.clone(), reason: "banned in config".to_string(), banned_at };
+                (address.clone(), ban)
+            })
+            .collect();
+        
+        Self {
+            bans: RwLock::new(bans),
+            allowlist: config.allowlist.as_ref().map(|allowlist| allowlist.iter().cloned().collect()),
+            path,
+        }
+    }
+    
+    /// Loads the bans saved by a previous run, keeping those already made
+    pub fn load(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !path.exists() {
+            return Ok(());
+        }
+        
+        let saved: Vec<Ban> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut bans = self.bans.write().unwrap();
+        for ban in saved {
+            bans.entry(ban.address.clone()).or_insert(ban);
+        }
+        println!("Loaded {} banned senders from {}", bans.len(), path.display());
+        Ok(())
+    }
+    
+    /// Checks whether a transaction's sender may enter the mempool, logging refusals
+    pub fn check(&self, tx: &Transaction) -> Result<(), PolicyError> {
+        let result = self.check_sender(&tx.sender);
+        if let Err(e) = &result {
+            println!("Refused transaction {}: {}", tx.id, e);
+        }
+        result
+    }
+    
+    /// Checks whether a sender may have transactions enter the mempool
+    pub fn check_sender(&self, sender: &str) -> Result<(), PolicyError> {
+        if let Some(ban) = self.bans.read().unwrap().get(sender) {
+            return Err(PolicyError::Banned { address: ban.address.clone(), reason: ban.reason.clone() });
+        }
+        match &self.allowlist {
+            Some(allowlist) if !allowlist.contains(sender) => Err(PolicyError::NotAllowed(sender.to_string())),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Bans a sender, returning whether it wasn't banned already
+    ///
+    /// Transactions already in the mempool are left there. The ban applies
+    /// even if saving it fails, until the node restarts.
+    pub fn ban(&self, address: &str, reason: &str) -> io::Result<bool> {
+        let mut bans = self.bans.write().unwrap();
+        if bans.contains_key(address) {
+            return Ok(false);
+        }
+        
+        let ban = Ban { address: address.to_string(), reason: reason.to_string(), banned_at: ctb_core::current_timestamp() };
+        bans.insert(address.to_string(), ban);
+        self.save(&bans)?;
+        println!("Banned sender {}: {}", address, reason);
+        Ok(true)
+    }
+    
+    /// Lifts a sender's ban, returning whether it was banned
+    pub fn unban(&self, address: &str) -> io::Result<bool> {
+        let mut bans = self.bans.write().unwrap();
+        if bans.remove(address).is_none() {
+            return Ok(false);
+        }
+        
+        self.save(&bans)?;
+        println!("Unbanned sender {}", address);
+        Ok(true)
+    }
+    
+    /// Gets the banned senders, by address
+    pub fn bans(&self) -> Vec<Ban> {
+        self.bans.read().unwrap().values().cloned().collect()
+    }
+    
+    /// Saves the bans, replacing the file atomically
+    fn save(&self, bans: &BTreeMap<String, Ban>) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&bans.values().collect::<Vec<_>>())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)
+    }
+}
\ No newline at end of file
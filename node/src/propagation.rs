@@ -0,0 +1,190 @@
+//! Timelines of how blocks reach and leave a node
+//!
+//! To see how long blocks take to travel, a node notes when each recent
+//! block was produced, received from each peer, validated, applied and sent
+//! to each peer, reading its clock (see `clock`) at each step. The first
+//! peer a block was received from is the one that delivered it fastest.
+//!
+//! Timelines are kept for the latest `MAX_TRACKED_BLOCKS` blocks seen, each
+//! naming at most `MAX_TRACKED_PEERS` peers a block was received from and
+//! as many it was sent to, so tracking costs a few clock reads per block
+//! and a bounded amount of memory. The durations derived from them are also
+//! recorded in the node's metrics (see `metrics`), and a timeline can be
+//! read over RPC with `debug_tracePropagation` when the debug methods are
+//! enabled (see `rpc`).
+//!
+//! How long a block took to arrive is measured from its timestamp, which
+//! is in whole seconds and on its producer's clock, so it's only as
+//! accurate as that and the two clocks being in sync.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+
+use ctb_core::block::Block;
+use ctb_core::BlockHash;
+
+/// Most blocks whose timelines are kept
+pub const MAX_TRACKED_BLOCKS: usize = 256;
+
+/// Most peers a timeline notes a block being received from, and as many sent to
+pub const MAX_TRACKED_PEERS: usize = 64;
+
+/// When a block was received from or sent to a peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeerTime {
+    /// Node ID of the peer
+    pub peer: String,
+    
+    /// Time, in milliseconds since the Unix epoch
+    pub at: u64,
+}
+
+/// What happened to a block at a node, and when
+///
+/// Times are in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockTimeline {
+    /// Hash of the block
+    pub block_hash: BlockHash,
+    
+    /// Height of the block
+    pub height: u64,
+    
+    /// The block's timestamp
+    pub block_time: u64,
+    
+    /// When this node produced the block, if it did
+    pub produced_at: Option<u64>,
+    
+    /// Peers the block was received from, first one first
+    pub received: Vec<PeerTime>,
+    
+    /// When the block passed validation
+    pub validated_at: Option<u64>,
+    
+    /// When the block was added to the chain
+    pub applied_at: Option<u64>,
+    
+    /// Peers the block was sent to, each the first time it was
+    pub sent: Vec<PeerTime>,
+}
+
+impl BlockTimeline {
+    /// Gets the peer the block was first received from, if any and the node didn't produce it
+    pub fn first_seen_from(&self) -> Option<&str> {
+        if self.produced_at.is_some() {
+            return None;
+        }
+        self.received.first().map(|received| received.peer.as_str())
+    }
+}
+
+/// Timelines of the latest blocks a node has seen
+#[derive(Debug, Default)]
+pub struct PropagationTracker {
+    /// Timelines, by block hash
+    timelines: HashMap<BlockHash, BlockTimeline>,
+    
+    /// Hashes of the blocks tracked, oldest first
+    order: VecDeque<BlockHash>,
+}
+
+impl PropagationTracker {
+    /// Creates a tracker with no timelines
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Notes that the node produced a block at `now`
+    pub fn produced(&mut self, block: &Block, now: u64) {
+        if let Some(timeline) = self.timeline_mut(block) {
+            timeline.produced_at.get_or_insert(now);
+        }
+    }
+    
+    /// Notes that a block was received from a peer at `now`
+    ///
+    /// Returns whether the peer is the first the block was received from.
+    pub fn received(&mut self, block: &Block, peer: &str, now: u64) -> bool {
+        let Some(timeline) = self.timeline_mut(block) else {
+            return false;
+        };
+        let first = timeline.received.is_empty() && timeline.produced_at.is_none();
+        note_peer(&mut timeline.received, peer, now);
+        first
+    }
+    
+    /// Notes that a block passed validation at `now`
+    ///
+    /// Returns how long that was after the block was first received, if it
+    /// was received from a peer and not validated before.
+    pub fn validated(&mut self, block: &Block, now: u64) -> Option<u64> {
+        let timeline = self.timeline_mut(block)?;
+        if timeline.validated_at.is_some() {
+            return None;
+        }
+        timeline.validated_at = Some(now);
+        timeline.received.first().map(|received| now.saturating_sub(received.at))
+    }
+    
+    /// Notes that a block was added to the chain at `now`
+    ///
+    /// Returns how long that was after the block was validated, if it was
+    /// validated apart from being added and not added before.
+    pub fn applied(&mut self, block: &Block, now: u64) -> Option<u64> {
+        let timeline = self.timeline_mut(block)?;
+        if timeline.applied_at.is_some() {
+            return None;
+        }
+        timeline.applied_at = Some(now);
+        timeline.validated_at.map(|validated_at| now.saturating_sub(validated_at))
+    }
+    
+    /// Notes that a block was sent to peers at `now`
+    pub fn sent<'a>(&mut self, block: &Block, peers: impl IntoIterator<Item = &'a str>, now: u64) {
+        if let Some(timeline) = self.timeline_mut(block) {
+            for peer in peers {
+                note_peer(&mut timeline.sent, peer, now);
+            }
+        }
+    }
+    
+    /// Gets the timeline of a block, if it's among the latest seen
+    pub fn timeline(&self, block_hash: &BlockHash) -> Option<&BlockTimeline> {
+        self.timelines.get(block_hash)
+    }
+    
+    /// Gets the timeline of a block, starting one if it has none
+    ///
+    /// The oldest timeline makes room once `MAX_TRACKED_BLOCKS` are kept.
+    fn timeline_mut(&mut self, block: &Block) -> Option<&mut BlockTimeline> {
+        let block_hash = block.hash().ok()?;
+        if !self.timelines.contains_key(&block_hash) {
+            if self.order.len() == MAX_TRACKED_BLOCKS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.timelines.remove(&oldest);
+                }
+            }
+            self.order.push_back(block_hash);
+            self.timelines.insert(block_hash, BlockTimeline {
+                block_hash,
+                height: block.header().height,
+                block_time: block.header().timestamp.saturating_mul(1000),
+                produced_at: None,
+                received: Vec::new(),
+                validated_at: None,
+                applied_at: None,
+                sent: Vec::new(),
+            });
+        }
+        self.timelines.get_mut(&block_hash)
+    }
+}
+
+/// Adds a peer to a list of them, unless it's there already or the list is full
+fn note_peer(peers: &mut Vec<PeerTime>, peer: &str, now: u64) {
+    if peers.len() < MAX_TRACKED_PEERS && !peers.iter().any(|noted| noted.peer == peer) {
+        peers.push(PeerTime { peer: peer.to_string(), at: now });
+    }
+}
\ No newline at end of file
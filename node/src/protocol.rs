@@ -0,0 +1,767 @@
+//! How a node deals with its peers
+//!
+//! The `Protocol` handles the messages peers send a node, and does the work
+//! the node loop repeats every second, sending through the node's network
+//! manager:
+//!
+//! - Blocks the node produces or adds are announced to its peers with
+//!   `NewBlock`. An announced block on top of the node's tip is added and
+//!   announced to the other peers in turn, as is one competing with the
+//!   node's blocks on a parent it has, if it's preferred to them; one from
+//!   a peer ahead that fits neither starts a sync with that peer (see
+//!   `block_sync`). Between competing branches the stake behind each
+//!   decides (see `consensus::fork_choice`).
+//! - Every tick the node tells its peers its tip and the weight of its
+//!   chain with `Status`, if the tip changed or `STATUS_INTERVAL` passed.
+//!   A peer whose tip the node doesn't have and whose chain claims more
+//!   weight is synced with, as after a partition heals, the two halves of
+//!   the network having gone their own ways; failing one, the peer furthest
+//!   ahead is. The sync finds the common ancestor however deep (see
+//!   `block_sync`), and the branch replaces the node's blocks above it if
+//!   the fork choice prefers it. Branches are only looked for above the
+//!   latest finalized checkpoint: a peer whose branch forks off below it,
+//!   or that shares no block with the node's chain from it up, is banned
+//!   (see `network`). Switching to a synced branch and banning are
+//!   published as events and counted in the metrics.
+//! - Transactions the mempool admits are relayed the same way.
+//! - A validating node with a validator address votes for each checkpoint
+//!   block it adds (see `consensus::finality`) and gossips the vote; votes
+//!   from peers are counted and passed on the first time they're seen.
+//!   Nodes have no validator keys yet, so votes aren't signed, and a vote
+//!   naming an active validator is taken as it is.
+//! - Headers, blocks, transactions, receipts, snapshots, block filters and
+//!   their headers (see `filters`), and peer addresses are served on
+//!   request. Peer addresses go as announcements their nodes signed, and
+//!   those peers send are checked into the node's address book (see
+//!   `address_book`), whose stale addresses are forgotten every tick.
+//! - Receipts and logs past the node's retention are pruned every tick (see
+//!   `pruning`).
+//! - Every message from a peer is noted on its connection first, and every
+//!   tick connections whose timeouts ran out are closed (see `connection`).
+//! - When recent blocks are produced, received, validated, applied and
+//!   sent is noted, with the durations recorded in the metrics (see
+//!   `propagation`).
+//! - Blocks the node produces are journaled before they're added or
+//!   announced, and a block journaled at the next height is the only one
+//!   the node produces there (see `journal`).
+//!
+//! Time is read from the node's clock (see `clock`), except by development
+//! nodes, which only produce blocks on demand (see `dev`).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ctb_core::block::Block;
+use ctb_core::chain::{Blockchain, ReorgRecord, MAX_ROLLBACK_DEPTH};
+use ctb_core::transaction::Transaction;
+use ctb_core::verified::VerifiedTxCache;
+use ctb_core::{BlockHash, BlockchainError, Bytes, ErrorContext, Result, WithContext};
+
+use consensus::finality::{FinalityManager, FinalityVote};
+use consensus::pos::PoSConsensus;
+use consensus::ConsensusEngine;
+
+use crate::block_sync::{self, BlockSync, BlockSyncError, SyncedBranch};
+use crate::clock::Clock;
+use crate::message::{ChainStatus, NetworkMessage, MAX_FILTERS, MAX_HEADERS};
+use crate::network::NetworkManager;
+use crate::journal::ProductionJournal;
+use crate::propagation::PropagationTracker;
+use crate::{events, filters, metrics, policy, pruning, snapshot_sync};
+
+/// Longest the node goes without telling its peers its status while its tip stays the same
+pub(crate) const STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A node's side of its conversations with peers
+///
+/// Cheap to clone: clones share the node's components.
+#[derive(Clone)]
+pub(crate) struct Protocol {
+    pub(crate) blockchain: Arc<Mutex<Blockchain>>,
+    pub(crate) consensus: Arc<Mutex<ConsensusEngine>>,
+    pub(crate) finality: Arc<Mutex<FinalityManager>>,
+    pub(crate) pos: Arc<Mutex<PoSConsensus>>,
+    pub(crate) network: Arc<Mutex<NetworkManager>>,
+    pub(crate) policy: Arc<policy::AdmissionPolicy>,
+    pub(crate) verified_txs: Arc<VerifiedTxCache>,
+    pub(crate) events: events::EventBus,
+    pub(crate) metrics: Arc<metrics::Metrics>,
+    pub(crate) snapshot_server: Arc<snapshot_sync::SnapshotServer>,
+    pub(crate) filters: Arc<filters::FilterIndex>,
+    pub(crate) validating: Arc<AtomicBool>,
+    
+    /// Validator the node produces blocks and votes for, if set
+    pub(crate) validator_address: Option<String>,
+    
+    /// Heights of the blocks votes are cast for
+    pub(crate) checkpoint_interval: u64,
+    
+    /// Number of latest blocks whose receipts and logs are kept, all if `None`
+    pub(crate) receipt_retention: Option<u64>,
+    
+    pub(crate) fetched_receipts: Arc<Mutex<pruning::FetchedReceipts>>,
+    pub(crate) journal: Arc<Mutex<ProductionJournal>>,
+    pub(crate) propagation: Arc<Mutex<PropagationTracker>>,
+    
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) sync: Arc<Mutex<BlockSync>>,
+    
+    /// Tip last told to the peers with `Status`, and when, in milliseconds
+    pub(crate) status_sent: Arc<Mutex<(BlockHash, u64)>>,
+    
+    /// Whether blocks are only produced on demand, see `dev`
+    pub(crate) dev_mode: bool,
+}
+
+impl Protocol {
+    /// Does the node's periodic work: producing a block if it's time, pruning, telling its status and syncing
+    pub(crate) fn tick(&self) {
+        let now = self.clock.now_millis();
+        if !self.dev_mode {
+            if let Ok(Some(new_block)) = self.produce_block_at(now / 1000) {
+                println!("Produced new block: {}", new_block);
+            }
+        }
+        self.prune();
+        self.network.lock().unwrap().expire_addresses();
+        let closed = self.network.lock().unwrap().check_connections();
+        for peer in closed.iter().filter_map(|connection| connection.node_id()) {
+            self.sync.lock().unwrap().remove_peer(peer);
+        }
+        
+        self.send_status(now);
+        
+        let polled = self.sync.lock().unwrap().poll(now);
+        match polled {
+            Ok(requests) => requests.into_iter().for_each(|(peer, request)| self.send(&peer, request)),
+            Err(e) => self.sync_failed(e),
+        }
+        self.sync_with_best_peer();
+    }
+    
+    /// Tells every peer the node's tip and the weight of its chain, if the tip changed or `STATUS_INTERVAL` passed
+    fn send_status(&self, now: u64) {
+        let status = {
+            let blockchain = self.blockchain.lock().unwrap();
+            ChainStatus {
+                height: blockchain.get_latest_height(),
+                best_hash: blockchain.get_latest_hash(),
+                weight: blockchain.chain_weight(),
+            }
+        };
+        {
+            let mut sent = self.status_sent.lock().unwrap();
+            if sent.0 == status.best_hash && now.saturating_sub(sent.1) < STATUS_INTERVAL.as_millis() as u64 {
+                return;
+            }
+            *sent = (status.best_hash, now);
+        }
+        self.network.lock().unwrap().post(&NetworkMessage::Status(status), None, None);
+    }
+    
+    /// Syncs with the peer claiming the heaviest chain, if it outweighs the node's and the node lacks its tip, or else the peer furthest ahead
+    ///
+    /// Among equals, the peer with the smallest ID is picked.
+    fn sync_with_best_peer(&self) {
+        if self.sync.lock().unwrap().peer().is_some() {
+            return;
+        }
+        let mut peers = Vec::new();
+        self.network.lock().unwrap().for_each_peer(|peer| peers.push((peer.node_id.clone(), peer.height, peer.best_hash, peer.weight)));
+        
+        let target = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let has_tip = |height, hash| blockchain.get_block_by_height(height).and_then(|block| block.hash().ok()) == Some(hash);
+            let (local_height, local_weight) = (blockchain.get_latest_height(), blockchain.chain_weight());
+            let heaviest = peers
+                .iter()
+                .filter(|(_, height, best_hash, weight)| *weight > local_weight && !has_tip(*height, *best_hash))
+                .min_by(|a, b| b.3.cmp(&a.3).then_with(|| a.0.cmp(&b.0)));
+            let highest = peers
+                .iter()
+                .filter(|(_, height, ..)| *height > local_height)
+                .min_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            heaviest.or(highest).map(|(peer, height, ..)| (peer.clone(), *height))
+        };
+        if let Some((peer, height)) = target {
+            self.start_sync(&peer, height);
+        }
+    }
+    
+    /// Produces a block if the node is validating and it's time at `now`, announcing it
+    ///
+    /// If a block was journaled at the next height, that block is added
+    /// and announced again instead, whatever the time, or nothing is
+    /// produced if it can't be; see `resume_production`.
+    pub(crate) fn produce_block_at(&self, now: u64) -> Result<Option<Arc<Block>>> {
+        if !self.validating.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        
+        // Held until the block is added, so blocks are never produced and resumed at once
+        let mut journal = self.journal.lock().unwrap();
+        let next_height = self.blockchain.lock().unwrap().get_latest_height() + 1;
+        if journal.get(next_height).is_some() {
+            return self.resume(&journal);
+        }
+        
+        let new_block = {
+            let mut consensus = self.consensus.lock().unwrap();
+            let Some(new_block) = consensus.try_produce_block_at(now)? else {
+                return Ok(None);
+            };
+            // Shared from here on rather than copied
+            let new_block = Arc::new(new_block);
+            let height = new_block.header().height;
+            self.propagation.lock().unwrap().produced(&new_block, self.clock.now_millis());
+            
+            // Journal the block before anything else can see it, its hash first
+            journal.record_hash(height, new_block.hash()?)?;
+            journal.record_block(&new_block)?;
+            
+            let mut blockchain = self.blockchain.lock().unwrap();
+            let parent_time = blockchain.get_latest_block().map_or(0, |parent| parent.header().timestamp);
+            if let Err(e) = blockchain.add_block(Arc::clone(&new_block)) {
+                eprintln!("Failed to add produced block: {}", e);
+                // Nobody has seen the block, so another may be produced in its place
+                journal.discard(height)?;
+                return Err(e);
+            }
+            self.pos.lock().unwrap().record_block(&consensus.slot_clock(), parent_time, &new_block);
+            self.update_jailing(&blockchain);
+            self.propagation.lock().unwrap().applied(&new_block, self.clock.now_millis());
+            new_block
+        };
+        drop(journal);
+        
+        self.announce(&new_block, None);
+        self.publish_validator_set(&new_block);
+        self.vote(&new_block);
+        Ok(Some(new_block))
+    }
+    
+    /// Adds and announces the block journaled at the next height, if it still extends the tip
+    ///
+    /// Returns the block if it was added. A block whose body wasn't saved,
+    /// or whose parent is no longer the tip, is left in the journal and
+    /// keeps the node from producing at its height until another block
+    /// fills it.
+    pub(crate) fn resume_production(&self) -> Result<Option<Arc<Block>>> {
+        let journal = self.journal.lock().unwrap();
+        self.resume(&journal)
+    }
+    
+    fn resume(&self, journal: &ProductionJournal) -> Result<Option<Arc<Block>>> {
+        let (next_height, tip_hash) = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let tip_hash = blockchain.get_latest_block().map(Block::hash).transpose()?.unwrap_or_default();
+            (blockchain.get_latest_height() + 1, tip_hash)
+        };
+        let Some(produced) = journal.get(next_height).cloned() else {
+            return Ok(None);
+        };
+        let block = match produced.block {
+            Some(block) if block.header().prev_hash == tip_hash => Arc::new(block),
+            _ => return Ok(None),
+        };
+        
+        println!("Resuming block {} produced at height {} before a restart", produced.block_hash, next_height);
+        self.import_block(Arc::clone(&block))?;
+        self.announce(&block, None);
+        Ok(Some(block))
+    }
+    
+    /// Adds a block to the chain, voting for it if it's a checkpoint
+    pub(crate) fn import_block(&self, block: Arc<Block>) -> Result<()> {
+        // Validated before the chain is locked, to be timed apart from
+        // being applied; the chain's own check then finds the signatures verified
+        block.validate_cached(&self.verified_txs).with_ctx(ErrorContext::block(&block))?;
+        let validated = self.propagation.lock().unwrap().validated(&block, self.clock.now_millis());
+        if let Some(millis) = validated {
+            self.metrics.record_block_validation(millis);
+        }
+        
+        let parent_time = {
+            let mut blockchain = self.blockchain.lock().unwrap();
+            let parent_time = blockchain.get_latest_block().map_or(0, |parent| parent.header().timestamp);
+            blockchain.add_block(Arc::clone(&block))?;
+            parent_time
+        };
+        let applied = self.propagation.lock().unwrap().applied(&block, self.clock.now_millis());
+        if let Some(millis) = applied {
+            self.metrics.record_block_apply(millis);
+        }
+        
+        let (unfunded, clock) = {
+            let mut consensus = self.consensus.lock().unwrap();
+            let unfunded = consensus.on_block_connected(&block);
+            (unfunded, consensus.slot_clock())
+        };
+        self.publish_dropped(&unfunded, block.header().height);
+        self.publish_validator_set(&block);
+        self.pos.lock().unwrap().record_block(&clock, parent_time, &block);
+        self.update_jailing(&self.blockchain.lock().unwrap());
+        self.vote(&block);
+        Ok(())
+    }
+    
+    /// Switches the chain to a competing branch, voting for its checkpoints
+    pub(crate) fn reorganize(&self, fork_height: u64, blocks: Vec<Arc<Block>>) -> Result<ReorgRecord> {
+        let (record, dropped) = self.blockchain.lock().unwrap().reorganize(fork_height, blocks.clone())?;
+        {
+            let now = self.clock.now_millis();
+            let mut propagation = self.propagation.lock().unwrap();
+            for block in &blocks {
+                propagation.applied(block, now);
+            }
+        }
+        
+        let (requeued, clock) = {
+            let mut consensus = self.consensus.lock().unwrap();
+            for block in &blocks {
+                let unfunded = consensus.on_block_connected(block);
+                self.publish_dropped(&unfunded, block.header().height);
+                self.publish_validator_set(block);
+            }
+            let requeued = dropped
+                .into_iter()
+                .filter(|tx| self.policy.check_sender(&tx.sender).is_ok())
+                .filter(|tx| consensus.add_transaction(tx.clone()).is_ok())
+                .count();
+            (requeued, consensus.slot_clock())
+        };
+        
+        {
+            let fork_time = self.blockchain.lock().unwrap().get_block_by_height(fork_height).map_or(0, |fork| fork.header().timestamp);
+            let mut pos = self.pos.lock().unwrap();
+            let parent_times = std::iter::once(fork_time).chain(blocks.iter().map(|block| block.header().timestamp));
+            for (block, parent_time) in blocks.iter().zip(parent_times) {
+                pos.record_block(&clock, parent_time, block);
+            }
+        }
+        self.update_jailing(&self.blockchain.lock().unwrap());
+        for block in &blocks {
+            self.vote(block);
+        }
+        
+        self.metrics.record_reorg(record.depth);
+        self.events.publish(events::NodeEvent::reorg(&record, requeued));
+        Ok(record)
+    }
+    
+    /// Jails the validators whose uptime fell short in the previous epoch, see `consensus::pos`
+    fn update_jailing(&self, blockchain: &Blockchain) {
+        let newly_jailed = self.pos.lock().unwrap().update_jailing(blockchain);
+        for validator in newly_jailed {
+            println!("Jailed validator {} for missing too many slots in the previous epoch", validator);
+        }
+    }
+    
+    /// Tells subscribers about pending transactions the block at `height` left unfunded
+    fn publish_dropped(&self, unfunded: &[Arc<Transaction>], height: u64) {
+        for tx in unfunded {
+            self.events.publish(events::NodeEvent::transaction_dropped(tx, height));
+        }
+    }
+    
+    /// Publishes the validator set a block committed to, if it started an epoch that does
+    fn publish_validator_set(&self, block: &Block) {
+        if block.header().validator_set_hash.is_none() {
+            return;
+        }
+        let epoch = ctb_core::validator::epoch_of(block.header().height);
+        if let Some(validator_set) = self.blockchain.lock().unwrap().get_validator_set(epoch) {
+            self.events.publish(events::NodeEvent::validator_set_changed(&validator_set, block.header().height));
+        }
+    }
+    
+    /// Adds a transaction to the mempool and relays it to every peer but `from`
+    pub(crate) fn add_transaction(&self, transaction: Arc<Transaction>, from: Option<&str>) -> Result<()> {
+        self.policy.check(&transaction).map_err(|e| BlockchainError::InvalidTransaction(e.to_string()))?;
+        
+        // Validate the transaction, remembering its signature verified for when its block arrives
+        transaction.validate_cached(&self.verified_txs)?;
+        
+        self.consensus.lock().unwrap().add_transaction(Arc::clone(&transaction))?;
+        if from.is_some() {
+            let millis = self.clock.now_millis().saturating_sub(transaction.timestamp.saturating_mul(1000));
+            self.metrics.record_transaction_arrival(millis);
+        }
+        self.network.lock().unwrap().post(&NetworkMessage::NewTransaction(transaction), None, from);
+        Ok(())
+    }
+    
+    /// Handles a message from a peer
+    ///
+    /// Messages from banned peers are ignored.
+    pub(crate) fn handle_message(&self, peer: &str, message: NetworkMessage) {
+        let now = self.clock.now_millis();
+        {
+            let network = self.network.lock().unwrap();
+            if network.is_banned(peer) {
+                return;
+            }
+            network.message_received(peer, &message);
+        }
+        match message {
+            NetworkMessage::Ping(ping) => self.send(peer, NetworkMessage::Pong(ping)),
+            NetworkMessage::GetPeers => {
+                let announcements = self.network.lock().unwrap().announcements();
+                self.send(peer, NetworkMessage::Addresses(announcements));
+            }
+            NetworkMessage::Addresses(announcements) => {
+                let (_, refused) = self.network.lock().unwrap().add_announcements(peer, announcements);
+                if let Some(e) = refused.first() {
+                    eprintln!("Dropped {} addresses from {}, such as: {}", refused.len(), peer, e);
+                }
+            }
+            NetworkMessage::Status(status) => {
+                self.network.lock().unwrap().update_peer_status(peer, &status, self.clock.now());
+                self.sync_with_best_peer();
+            }
+            NetworkMessage::NewBlock(block) => self.handle_new_block(peer, block),
+            NetworkMessage::GetBlock(hash) => {
+                if let Some(block) = self.find_block(&hash) {
+                    self.send(peer, NetworkMessage::Block(block));
+                }
+            }
+            NetworkMessage::Block(block) => {
+                self.trace_received(&block, peer, now);
+                let branch = self.sync.lock().unwrap().handle_block(peer, block, now);
+                if let Some(branch) = branch {
+                    self.apply_branch(branch);
+                }
+            }
+            NetworkMessage::GetHeaders(range) => {
+                let headers = {
+                    let blockchain = self.blockchain.lock().unwrap();
+                    let end = range.end.min(range.start.saturating_add(MAX_HEADERS));
+                    (range.start..end)
+                        .map_while(|height| blockchain.get_block_by_height(height))
+                        .map(|block| block.header().clone())
+                        .collect()
+                };
+                self.send(peer, NetworkMessage::Headers(headers));
+            }
+            NetworkMessage::GetBranchHeaders(locator) => {
+                let headers = {
+                    let blockchain = self.blockchain.lock().unwrap();
+                    let hash_at = |height| blockchain.get_block_by_height(height).and_then(|block| block.hash().ok());
+                    // From the highest block of the locator the chain has, or none
+                    let start = locator.iter().filter(|(height, hash)| hash_at(*height) == Some(*hash)).map(|(height, _)| *height).max();
+                    start.map_or_else(Vec::new, |start| {
+                        (start..start.saturating_add(MAX_HEADERS))
+                            .map_while(|height| blockchain.get_block_by_height(height))
+                            .map(|block| block.header().clone())
+                            .collect()
+                    })
+                };
+                self.send(peer, NetworkMessage::Headers(headers));
+            }
+            NetworkMessage::Headers(headers) => {
+                let requests = {
+                    let blockchain = self.blockchain.lock().unwrap();
+                    let local_hash = |height| blockchain.get_block_by_height(height).and_then(|block| block.hash().ok());
+                    self.sync.lock().unwrap().handle_headers(peer, &headers, local_hash, now)
+                };
+                match requests {
+                    Ok(requests) => requests.into_iter().for_each(|request| self.send(peer, request)),
+                    Err(e) => self.sync_failed(e),
+                }
+            }
+            NetworkMessage::NewTransaction(transaction) => {
+                let _ = self.add_transaction(transaction, Some(peer));
+            }
+            NetworkMessage::GetTransaction(id) => {
+                let transaction = self.consensus.lock().unwrap().mempool().get(&id).cloned();
+                if let Some(transaction) = transaction {
+                    self.send(peer, NetworkMessage::Transaction(transaction));
+                }
+            }
+            NetworkMessage::GetReceipts(height) => {
+                let receipts = self
+                    .blockchain
+                    .lock()
+                    .unwrap()
+                    .get_block_receipts(height)
+                    .map(|receipts| receipts.into_iter().cloned().collect());
+                if let Ok(receipts) = receipts {
+                    self.send(peer, NetworkMessage::Receipts { height, receipts });
+                }
+            }
+            NetworkMessage::Receipts { height, receipts } => {
+                let block = self.blockchain.lock().unwrap().get_shared_block(height);
+                if let Some(block) = block {
+                    self.fetched_receipts.lock().unwrap().accept(&block, receipts);
+                }
+            }
+            NetworkMessage::GetFilterHeaders(range) => {
+                let end = range.end.min(range.start.saturating_add(MAX_HEADERS));
+                let headers = self.filters.headers(range.start..end);
+                self.send(peer, NetworkMessage::FilterHeaders { start: range.start, headers });
+            }
+            NetworkMessage::GetFilters(range) => {
+                let end = range.end.min(range.start.saturating_add(MAX_FILTERS));
+                let filters = self.filters.filters(range.start..end);
+                self.send(peer, NetworkMessage::Filters { start: range.start, filters });
+            }
+            NetworkMessage::Disconnect(_) => self.sync.lock().unwrap().remove_peer(peer),
+            NetworkMessage::CheckpointVote(vote) => {
+                if self.count_vote(&vote) {
+                    self.network.lock().unwrap().post(&NetworkMessage::CheckpointVote(vote), None, Some(peer));
+                }
+            }
+            message => {
+                if let Some(reply) = self.snapshot_server.handle(&message) {
+                    self.send(peer, reply);
+                }
+            }
+        }
+    }
+    
+    /// Asks a peer for the receipts of the block at a height
+    pub(crate) fn request_receipts(&self, peer: &str, height: u64) {
+        self.fetched_receipts.lock().unwrap().request(height);
+        self.send(peer, NetworkMessage::GetReceipts(height));
+    }
+    
+    /// Prunes the receipts and logs of finalized blocks past the retention, and the journal's finalized blocks
+    fn prune(&self) {
+        let finalized_height = self.finality.lock().unwrap().get_latest_finalized_height();
+        if let Err(e) = self.journal.lock().unwrap().prune(finalized_height) {
+            eprintln!("Failed to prune the production journal: {}", e);
+        }
+        let mut blockchain = self.blockchain.lock().unwrap();
+        let Some(height) = pruning::prune_height(self.receipt_retention, blockchain.get_latest_height(), finalized_height) else {
+            return;
+        };
+        if height > blockchain.pruned_height() {
+            self.metrics.record_prune(&blockchain.prune_receipts(height));
+        }
+    }
+    
+    /// Adds an announced block on top of the tip or in place of the node's blocks, or syncs with a peer ahead
+    ///
+    /// An announced block no higher than the tip replaces the blocks above
+    /// its parent if the parent is the node's and the fork choice prefers
+    /// it; otherwise it's ignored.
+    fn handle_new_block(&self, peer: &str, block: Arc<Block>) {
+        self.trace_received(&block, peer, self.clock.now_millis());
+        let height = block.header().height;
+        self.network.lock().unwrap().update_peer_height(peer, height, self.clock.now());
+        let Ok(hash) = block.hash() else {
+            return;
+        };
+        
+        let (local_height, tip_hash, known, parent_known) = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let hash_at = |height| blockchain.get_block_by_height(height).and_then(|block| block.hash().ok());
+            let local_height = blockchain.get_latest_height();
+            let parent_known = height > 0 && hash_at(height - 1) == Some(block.header().prev_hash);
+            (local_height, hash_at(local_height), hash_at(height) == Some(hash), parent_known)
+        };
+        if known {
+            return;
+        }
+        
+        if height == local_height + 1 && Some(block.header().prev_hash) == tip_hash {
+            match self.import_block(Arc::clone(&block)) {
+                Ok(()) => self.announce(&block, Some(peer)),
+                Err(e) => eprintln!("Rejected block {} from {}: {}", height, peer, e),
+            }
+        } else if height <= local_height && parent_known {
+            match self.reorganize(height - 1, vec![Arc::clone(&block)]) {
+                Ok(_) => self.announce(&block, Some(peer)),
+                Err(BlockchainError::BranchNotPreferred { .. }) => {}
+                Err(e) => eprintln!("Rejected competing block {} from {}: {}", height, peer, e),
+            }
+        } else if height > local_height {
+            self.start_sync(peer, height);
+        }
+    }
+    
+    /// Adds the blocks of a finished sync, if they extend the chain or make a branch the fork choice prefers
+    ///
+    /// A branch forking off below the latest finalized checkpoint is
+    /// refused and its peer banned. One the fork choice doesn't prefer
+    /// leaves the peer's claims distrusted, so it isn't synced with again
+    /// until it announces more.
+    fn apply_branch(&self, branch: SyncedBranch) {
+        let Some(last) = branch.blocks.last().cloned() else {
+            return;
+        };
+        let local_height = self.blockchain.lock().unwrap().get_latest_height();
+        
+        if branch.fork_height == local_height {
+            for block in branch.blocks {
+                if let Err(e) = self.import_block(block) {
+                    eprintln!("Rejected block synced from {}: {}", branch.peer, e);
+                    return;
+                }
+            }
+        } else if branch.fork_height < local_height {
+            let finalized_height = self.finality.lock().unwrap().get_latest_finalized_height();
+            if branch.fork_height < finalized_height {
+                self.refuse_branch(&branch.peer, Some(branch.fork_height), finalized_height);
+                return;
+            }
+            let applied = branch.blocks.len() as u64;
+            match self.reorganize(branch.fork_height, branch.blocks) {
+                Ok(record) => {
+                    println!(
+                        "Switched to the branch synced from {}: {} blocks rolled back to height {}, {} applied",
+                        branch.peer, record.depth, record.fork_height, applied
+                    );
+                    self.metrics.record_heal(record.fork_height, record.depth, applied);
+                    self.events.publish(events::NodeEvent::chain_healed(&branch.peer, &record, applied));
+                }
+                Err(BlockchainError::BranchNotPreferred { .. }) => {
+                    self.distrust(&branch.peer);
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Failed to switch to the branch synced from {}: {}", branch.peer, e);
+                    return;
+                }
+            }
+        } else {
+            return;
+        }
+        self.announce(&last, Some(&branch.peer));
+    }
+    
+    /// Starts syncing with a peer announcing a chain up to `height`, unless a sync is running
+    ///
+    /// The peer is sent a locator of the chain down to the latest finalized
+    /// checkpoint, or as deep as blocks can be rolled back if that's higher.
+    fn start_sync(&self, peer: &str, height: u64) {
+        if self.sync.lock().unwrap().peer().is_some() {
+            return;
+        }
+        let finalized_height = self.finality.lock().unwrap().get_latest_finalized_height();
+        let locator = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let tip = blockchain.get_latest_height();
+            let floor = finalized_height.max(tip.saturating_sub(MAX_ROLLBACK_DEPTH));
+            block_sync::locator(tip, floor, |height| blockchain.get_block_by_height(height).and_then(|block| block.hash().ok()))
+        };
+        let request = self.sync.lock().unwrap().start(peer, height, locator, self.clock.now_millis());
+        if let Some(request) = request {
+            self.send(peer, request);
+        }
+    }
+    
+    /// Distrusts the chain the peer announced after syncing with it failed
+    ///
+    /// A peer sharing no block with the chain from the latest finalized
+    /// checkpoint up has a branch reverting it, and is banned.
+    fn sync_failed(&self, error: BlockSyncError) {
+        eprintln!("Block sync failed: {}", error);
+        if let BlockSyncError::Unrelated { peer, floor } = &error {
+            let finalized_height = self.finality.lock().unwrap().get_latest_finalized_height();
+            if *floor <= finalized_height {
+                self.refuse_branch(peer, None, finalized_height);
+                return;
+            }
+        }
+        let (BlockSyncError::Misbehaved { peer, .. } | BlockSyncError::Unrelated { peer, .. } | BlockSyncError::TimedOut { peer }) = &error;
+        self.distrust(peer);
+    }
+    
+    /// Stops trusting the chain a peer announced
+    ///
+    /// It's synced with again once it announces another block or status.
+    fn distrust(&self, peer: &str) {
+        let status = ChainStatus { height: 0, best_hash: BlockHash::default(), weight: 0 };
+        self.network.lock().unwrap().update_peer_status(peer, &status, self.clock.now());
+    }
+    
+    /// Refuses a peer's branch reverting the finalized checkpoint at `finalized_height`, banning the peer
+    fn refuse_branch(&self, peer: &str, fork_height: Option<u64>, finalized_height: u64) {
+        self.sync.lock().unwrap().remove_peer(peer);
+        let reason = format!("offering a branch reverting the finalized checkpoint at height {}", finalized_height);
+        self.network.lock().unwrap().ban_peer(peer, &reason);
+        self.metrics.record_ban();
+        self.events.publish(events::NodeEvent::branch_refused(peer, fork_height, finalized_height));
+    }
+    
+    /// Votes for a block if it's a checkpoint and the node validates, and gossips the vote
+    fn vote(&self, block: &Block) {
+        let Some(validator) = &self.validator_address else {
+            return;
+        };
+        let height = block.header().height;
+        if !self.validating.load(Ordering::SeqCst) || height == 0 || height.checked_rem(self.checkpoint_interval) != Some(0) {
+            return;
+        }
+        let Ok(block_hash) = block.hash() else {
+            return;
+        };
+        
+        // Signed by the validator's signer, if the node has one
+        let signer = self.consensus.lock().unwrap().signer();
+        let vote = match signer {
+            Some(signer) => match FinalityVote::sign_with(height, block_hash, validator.clone(), signer.as_ref()) {
+                Ok(vote) => vote,
+                Err(e) => {
+                    eprintln!("Not voting for checkpoint {}: {}", height, e);
+                    return;
+                }
+            },
+            None => FinalityVote { height, block_hash, validator: validator.clone(), signature: Bytes(Vec::new()) },
+        };
+        if self.count_vote(&vote) {
+            self.network.lock().unwrap().post(&NetworkMessage::CheckpointVote(vote), None, None);
+        }
+    }
+    
+    /// Counts a vote towards its checkpoint, returning whether it's new
+    ///
+    /// Votes from validators that aren't active, and for another block than
+    /// the checkpoint's, aren't counted.
+    fn count_vote(&self, vote: &FinalityVote) -> bool {
+        let Some(validator) = self.consensus.lock().unwrap().active_validator(&vote.validator).cloned() else {
+            return false;
+        };
+        let mut finality = self.finality.lock().unwrap();
+        let seen = finality
+            .get_checkpoint(vote.height, &vote.block_hash)
+            .is_some_and(|checkpoint| checkpoint.votes.contains(&vote.validator));
+        !seen && finality.add_checkpoint_vote(vote.height, vote.block_hash, &validator).is_ok()
+    }
+    
+    /// Finds a block of the chain by its hash
+    fn find_block(&self, hash: &BlockHash) -> Option<Arc<Block>> {
+        let blockchain = self.blockchain.lock().unwrap();
+        blockchain.get_height_by_hash(hash).and_then(|height| blockchain.get_shared_block(height))
+    }
+    
+    /// Notes that a block arrived from a peer, recording how long it took if no other peer delivered it first
+    fn trace_received(&self, block: &Block, peer: &str, now: u64) {
+        if self.propagation.lock().unwrap().received(block, peer, now) {
+            self.metrics.record_block_arrival(peer, now.saturating_sub(block.header().timestamp.saturating_mul(1000)));
+        }
+    }
+    
+    /// Announces a block to every peer but `except`
+    fn announce(&self, block: &Arc<Block>, except: Option<&str>) {
+        let now = self.clock.now_millis();
+        let mut peers = Vec::new();
+        {
+            let network = self.network.lock().unwrap();
+            network.post(&NetworkMessage::NewBlock(Arc::clone(block)), None, except);
+            network.for_each_peer(|peer| {
+                if Some(peer.node_id.as_str()) != except {
+                    peers.push(peer.node_id.clone());
+                }
+            });
+        }
+        peers.sort();
+        self.propagation.lock().unwrap().sent(block, peers.iter().map(String::as_str), now);
+    }
+    
+    fn send(&self, peer: &str, message: NetworkMessage) {
+        self.network.lock().unwrap().post(&message, Some(peer), None);
+    }
+}
\ No newline at end of file
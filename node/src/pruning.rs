@@ -0,0 +1,83 @@
+//! Pruning receipts and logs, and fetching pruned ones from archival peers
+//!
+//! Receipts and the log index grow faster than the blocks on a busy chain.
+//! A node with a `NodeConfig::receipt_retention` of N keeps the receipts
+//! and logs of its latest N blocks, and every tick drops those of older
+//! blocks once they're finalized (see `prune_height`). Queries reaching
+//! below what's kept fail with `BlockchainError::Pruned`; transactions
+//! themselves can still be found.
+//!
+//! Archival nodes keep everything, the default, and answer `GetReceipts`
+//! requests for a block's receipts. A pruned node asks for them with
+//! `Node::request_receipts`, and keeps the answers that match the
+//! transactions of its own block in a `FetchedReceipts`, the latest
+//! `MAX_FETCHED_BLOCKS` blocks requested at most. Receipts aren't committed
+//! to by block headers, so beyond that match they're taken on trust.
+
+use std::collections::{BTreeMap, HashSet};
+
+use ctb_core::block::Block;
+use ctb_core::receipt::Receipt;
+
+/// Most blocks whose fetched receipts are kept
+pub const MAX_FETCHED_BLOCKS: usize = 64;
+
+/// Gets the height to prune receipts and logs below, if any
+///
+/// That's the lowest of the `retention` latest blocks, but no higher than
+/// the finalized height, so receipts are never pruned from blocks a
+/// reorganization could still replace.
+pub fn prune_height(retention: Option<u64>, latest_height: u64, finalized_height: u64) -> Option<u64> {
+    let retention = retention?;
+    Some((latest_height + 1).saturating_sub(retention).min(finalized_height))
+}
+
+/// Receipts of pruned blocks fetched from peers
+#[derive(Debug, Default)]
+pub struct FetchedReceipts {
+    /// Heights asked for and not received yet
+    requested: HashSet<u64>,
+    
+    /// Receipts received, by block height
+    blocks: BTreeMap<u64, Vec<Receipt>>,
+}
+
+impl FetchedReceipts {
+    /// Creates an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Notes that the receipts of the block at a height were asked for
+    pub fn request(&mut self, height: u64) {
+        self.requested.insert(height);
+    }
+    
+    /// Keeps the receipts received for a block, if they were asked for and match its transactions
+    ///
+    /// Returns whether they were kept. The blocks with the lowest heights
+    /// make room once `MAX_FETCHED_BLOCKS` are kept.
+    pub fn accept(&mut self, block: &Block, receipts: Vec<Receipt>) -> bool {
+        let height = block.header().height;
+        let matches = receipts.len() == block.transactions.len()
+            && block
+                .transactions
+                .iter()
+                .zip(&receipts)
+                .all(|(tx, receipt)| receipt.tx_id == tx.id && receipt.block_height == height);
+        if !matches || !self.requested.remove(&height) {
+            return false;
+        }
+        
+        self.blocks.insert(height, receipts);
+        while self.blocks.len() > MAX_FETCHED_BLOCKS {
+            self.blocks.pop_first();
+        }
+        true
+    }
+    
+    /// Gets the receipts fetched for the block at a height
+    pub fn get(&self, height: u64) -> Option<&[Receipt]> {
+        self.blocks.get(&height).map(Vec::as_slice)
+    }
+}
\ No newline at end of file
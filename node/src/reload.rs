@@ -0,0 +1,254 @@
+//! Reloading a running node's configuration
+//!
+//! `Node::reload_config` reads the configuration file again and compares it
+//! with the configuration the node runs with. Operators can also trigger it
+//! with `admin_reloadConfig` (see `admin`), or by sending SIGHUP to
+//! `genx node run`. Changed fields listed in `RELOADABLE` are applied to the
+//! node's components straight away:
+//!
+//! | Field                                         | Takes effect                                                |
+//! |-----------------------------------------------|-------------------------------------------------------------|
+//! | `log_level`                                   | at once                                                     |
+//! | `consensus_params.mempool_capacity`           | at once, evicting the lowest priority transactions over it  |
+//! | `consensus_params.max_relay_data_size`        | for transactions added from then on                         |
+//! | `consensus_params.max_relay_deploy_data_size` | for transactions added from then on                         |
+//! | `network_config.max_peers`                    | for connections made from then on                           |
+//! | `network_config.discovery_interval`           | from the next discovery round                               |
+//! | `max_state_depth`                             | for queries from then on                                    |
+//! | `verified_tx_cache_size`                      | at once, forgetting the least recently used transactions    |
+//!
+//! Changes to any other field, such as `data_dir` or `chain_id`, need a
+//! restart: they're reported as rejected and the node keeps running with
+//! the values it has. The genesis block is read from the data directory, so
+//! it can't change without one either. A file that can't be read, parsed
+//! or that sets an invalid value applies nothing.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use log::LevelFilter;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use ctb_core::chain::Blockchain;
+use ctb_core::verified::VerifiedTxCache;
+
+use consensus::ConsensusEngine;
+
+use crate::network::NetworkManager;
+use crate::NodeConfig;
+
+/// Fields that can change without restarting the node, as dotted paths
+pub const RELOADABLE: &[&str] = &[
+    "log_level",
+    "consensus_params.mempool_capacity",
+    "consensus_params.max_relay_data_size",
+    "consensus_params.max_relay_deploy_data_size",
+    "network_config.max_peers",
+    "network_config.discovery_interval",
+    "max_state_depth",
+    "verified_tx_cache_size",
+];
+
+/// Reason a configuration couldn't be reloaded
+#[derive(Debug, Error)]
+pub enum ReloadError {
+    #[error("No configuration file to reload")]
+    NoConfigFile,
+    
+    #[error("Cannot read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    
+    #[error("{}: {reason}", path.display())]
+    Parse { path: PathBuf, reason: String },
+    
+    #[error("Invalid {field}: {reason}")]
+    InvalidValue { field: &'static str, reason: String },
+}
+
+/// What a reload changed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReloadReport {
+    /// Changed fields that were applied
+    pub applied: Vec<String>,
+    
+    /// Changed fields that need a restart, and were left as they were
+    pub rejected: Vec<String>,
+}
+
+/// Lists the fields whose values differ between two configurations, as dotted paths
+///
+/// Fields holding objects are compared field by field, and any other
+/// values as a whole.
+pub fn changed_fields(running: &NodeConfig, loaded: &NodeConfig) -> Vec<String> {
+    fn compare(prefix: &str, running: &Value, loaded: &Value, changed: &mut Vec<String>) {
+        match (running, loaded) {
+            (Value::Object(running), Value::Object(loaded)) => {
+                let mut names: Vec<&String> = running.keys().chain(loaded.keys()).collect();
+                names.sort();
+                names.dedup();
+                for name in names {
+                    let path = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+                    compare(&path, running.get(name).unwrap_or(&Value::Null), loaded.get(name).unwrap_or(&Value::Null), changed);
+                }
+            }
+            _ if running != loaded => changed.push(prefix.to_string()),
+            _ => {}
+        }
+    }
+    
+    let mut changed = Vec::new();
+    let running = serde_json::to_value(running).unwrap_or(Value::Null);
+    let loaded = serde_json::to_value(loaded).unwrap_or(Value::Null);
+    compare("", &running, &loaded, &mut changed);
+    changed
+}
+
+/// Applies reloaded configurations to a running node's components
+#[derive(Clone)]
+pub struct ConfigReloader {
+    /// Configuration the node runs with, as reloads leave it
+    config: Arc<Mutex<NodeConfig>>,
+    
+    /// File the configuration was last read from, if known
+    path: Arc<Mutex<Option<PathBuf>>>,
+    
+    blockchain: Arc<Mutex<Blockchain>>,
+    consensus: Arc<Mutex<ConsensusEngine>>,
+    network: Arc<Mutex<NetworkManager>>,
+    verified_txs: Arc<VerifiedTxCache>,
+}
+
+impl ConfigReloader {
+    /// Creates a reloader over a node's components, running with `config`
+    pub(crate) fn new(
+        config: NodeConfig,
+        blockchain: Arc<Mutex<Blockchain>>,
+        consensus: Arc<Mutex<ConsensusEngine>>,
+        network: Arc<Mutex<NetworkManager>>,
+        verified_txs: Arc<VerifiedTxCache>,
+    ) -> Self {
+        Self {
+            config: Arc::new(Mutex::new(config)),
+            path: Arc::new(Mutex::new(None)),
+            blockchain,
+            consensus,
+            network,
+            verified_txs,
+        }
+    }
+    
+    /// Gets the configuration the node runs with
+    pub fn config(&self) -> NodeConfig {
+        self.config.lock().unwrap().clone()
+    }
+    
+    /// Gets the file the configuration was last read from, if known
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path.lock().unwrap().clone()
+    }
+    
+    /// Notes the file the configuration was read from, which `reload` reads by default
+    pub fn set_path(&self, path: PathBuf) {
+        *self.path.lock().unwrap() = Some(path);
+    }
+    
+    /// Reads a configuration file and applies it, see `apply`
+    ///
+    /// Without a path, the file the configuration was last read from is read again.
+    pub fn reload(&self, path: Option<&Path>) -> Result<ReloadReport, ReloadError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => self.path().ok_or(ReloadError::NoConfigFile)?,
+        };
+        let contents = fs::read_to_string(&path).map_err(|source| ReloadError::Read { path: path.clone(), source })?;
+        let loaded: NodeConfig = serde_json::from_str(&contents)
+            .map_err(|e| ReloadError::Parse { path: path.clone(), reason: e.to_string() })?;
+        
+        let report = self.apply(loaded)?;
+        self.set_path(path);
+        Ok(report)
+    }
+    
+    /// Applies the reloadable fields of a configuration that differ from the running one
+    ///
+    /// Other fields that differ are rejected and keep their running values.
+    pub fn apply(&self, loaded: NodeConfig) -> Result<ReloadReport, ReloadError> {
+        let log_level = loaded.log_level.as_deref().map(parse_log_level).transpose()?;
+        if loaded.network_config.discovery_interval == 0 {
+            return Err(ReloadError::InvalidValue {
+                field: "network_config.discovery_interval",
+                reason: "must be at least a second".to_string(),
+            });
+        }
+        
+        let mut config = self.config.lock().unwrap();
+        let mut report = ReloadReport::default();
+        for field in changed_fields(&config, &loaded) {
+            if RELOADABLE.contains(&field.as_str()) {
+                report.applied.push(field);
+            } else {
+                report.rejected.push(field);
+            }
+        }
+        let applied = |field: &str| report.applied.iter().any(|applied| applied == field);
+        
+        if applied("log_level") {
+            if let Some(level) = log_level {
+                log::set_max_level(level);
+            }
+            config.log_level = loaded.log_level.clone();
+        }
+        
+        if applied("consensus_params.mempool_capacity")
+            || applied("consensus_params.max_relay_data_size")
+            || applied("consensus_params.max_relay_deploy_data_size")
+        {
+            config.consensus_params.mempool_capacity = loaded.consensus_params.mempool_capacity;
+            config.consensus_params.max_relay_data_size = loaded.consensus_params.max_relay_data_size;
+            config.consensus_params.max_relay_deploy_data_size = loaded.consensus_params.max_relay_deploy_data_size;
+            
+            let limits = config.consensus_params.mempool_limits();
+            let evicted = self.consensus.lock().unwrap().set_mempool_limits(limits);
+            println!("Reload: set the mempool limits to {:?}, evicting {} transactions", limits, evicted.len());
+        }
+        
+        if applied("network_config.max_peers") {
+            config.network_config.max_peers = loaded.network_config.max_peers;
+            self.network.lock().unwrap().set_max_peers(config.network_config.max_peers);
+        }
+        if applied("network_config.discovery_interval") {
+            config.network_config.discovery_interval = loaded.network_config.discovery_interval;
+            self.network.lock().unwrap().set_discovery_interval(config.network_config.discovery_interval);
+        }
+        
+        if applied("max_state_depth") {
+            config.max_state_depth = loaded.max_state_depth;
+            self.blockchain.lock().unwrap().set_max_state_depth(config.max_state_depth);
+        }
+        if applied("verified_tx_cache_size") {
+            config.verified_tx_cache_size = loaded.verified_tx_cache_size;
+            self.verified_txs.set_capacity(config.verified_tx_cache_size);
+        }
+        
+        if !report.applied.is_empty() {
+            println!("Reload: applied {}", report.applied.join(", "));
+        }
+        if !report.rejected.is_empty() {
+            println!("Reload: {} can't change without a restart", report.rejected.join(", "));
+        }
+        Ok(report)
+    }
+}
+
+/// Parses a log level, such as `info`
+pub fn parse_log_level(level: &str) -> Result<LevelFilter, ReloadError> {
+    LevelFilter::from_str(level).map_err(|_| ReloadError::InvalidValue {
+        field: "log_level",
+        reason: format!("unknown level {}", level),
+    })
+}
\ No newline at end of file
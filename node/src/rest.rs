@@ -0,0 +1,735 @@
+//! Block explorer REST API
+//!
+//! Serves read-only `GET` routes on the RPC server's address, for web
+//! explorers that would rather not speak JSON-RPC:
+//!
+//! | Route                                    | Result                                                          |
+//! |------------------------------------------|-----------------------------------------------------------------|
+//! | `/blocks?start=&limit=`                  | block summaries, newest first from height `start`               |
+//! | `/blocks/range?from=&to=&offset=&limit=` | summaries of the blocks made between two times, oldest first    |
+//! | `/blocks/at/{timestamp}`                 | the latest block made at or before a time, as `/block`          |
+//! | `/block/{height or hash}`                | a block with its hash and gas used                              |
+//! | `/tx/{hash}?confirmations=`              | an included transaction with its block, receipt and status      |
+//! | `/txs/range?from=&to=&offset=&limit=`    | transactions of the blocks made between two times, oldest first |
+//! | `/address/{address}?cursor=&limit=`      | balances, nonce and a page of history, newest first             |
+//! | `/validators?sort=&order=&cursor=&limit=`| a page of registered validators with stake and metrics          |
+//! | `/validators/{address}/uptime?from=&to=` | a validator's kept and missed slots between two times           |
+//! | `/reorgs?limit=`                         | the most recent chain reorganizations, newest first             |
+//! | `/supply`                                | maximum and circulating supply                                  |
+//! | `/search?q=`                             | the block, transaction or address a query names                 |
+//!
+//! Bodies are JSON, with hashes, addresses and binary data as hex as in
+//! their serde forms. Unknown routes and missing blocks, transactions and
+//! addresses are 404s, receipts that were pruned 410s and bad parameters
+//! 400s, with an `{"error": message}` body. Paginated routes set `X-Total-Count` where the total is known and
+//! a `Link` header with `rel="next"` while there are more entries.
+//!
+//! `/address` and `/validators` page by cursor (see `ctb_core::paging`): their
+//! bodies carry the `next_cursor` to pass as `cursor` for the next page,
+//! null on the last one, and `/validators` the `total_estimate`. Validators
+//! are sorted by `stake`, highest first, or by `address`, and `order` is
+//! `ascending` or `descending`. `/address` still takes the `offset` it was
+//! paged by before, though entries added meanwhile shift offset pages.
+//!
+//! Times are Unix timestamps in seconds, and time ranges include both
+//! ends. `from` defaults to the start of the chain and `to` to its tip.
+//!
+//! Transactions and history entries carry their `ConfirmationStatus`
+//! (see `ctb_core::confirmation`), safe once their block has `confirmations`
+//! confirmations, which `/tx` and `/address` take, and
+//! `DEFAULT_SAFE_CONFIRMATIONS` if not given.
+//!
+//! An address's `locked` amount is what it has locked in deposits for
+//! contract storage, which isn't part of its `balance` (see `ctb_core::deposit`).
+//!
+//! A validator's uptime is derived from the chain alone (see
+//! `consensus::uptime`), so every node answers alike; completed epochs are
+//! cached, so long ranges only walk the blocks of the epochs at their ends.
+//!
+//! An address's `nonce` is the one its next transaction must have (see
+//! `ctb_core::state::State::get_nonce`).
+//!
+//! Handlers copy what they need out of the chain and release its lock
+//! before building the response.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use ctb_core::block::Block;
+use ctb_core::chain::{Blockchain, SnapshotHandle};
+use ctb_core::confirmation::DEFAULT_SAFE_CONFIRMATIONS;
+use ctb_core::paging::{Page, PageRequest, SortOrder};
+use ctb_core::signature::SignatureScheme;
+use ctb_core::units::{format_genx, Amount};
+use ctb_core::validator::ValidatorSort;
+use ctb_core::{Address, BlockHash, BlockchainError, TxHash};
+
+use consensus::pos::PoSConsensus;
+use consensus::uptime::UptimeStats;
+
+use wallet::api::ChainClient;
+
+use crate::eth;
+
+/// Block summaries `/blocks` returns when a request doesn't give a limit
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Most entries a single paginated request returns
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Error answering a REST request
+#[derive(Debug, Error)]
+pub enum RestError {
+    #[error("{0}")]
+    BadRequest(String),
+    
+    #[error("{0} not found")]
+    NotFound(String),
+    
+    #[error("{0}")]
+    Gone(String),
+    
+    #[error("{0}")]
+    Server(String),
+}
+
+impl RestError {
+    /// Gets the HTTP status code of the error
+    pub fn status(&self) -> u16 {
+        match self {
+            RestError::BadRequest(_) => 400,
+            RestError::NotFound(_) => 404,
+            RestError::Gone(_) => 410,
+            RestError::Server(_) => 500,
+        }
+    }
+}
+
+/// Response to a REST request
+#[derive(Debug, Clone)]
+pub struct RestResponse {
+    /// HTTP status code
+    pub status: u16,
+    
+    /// Headers besides those every response has
+    pub headers: Vec<(&'static str, String)>,
+    
+    /// JSON body
+    pub body: Value,
+}
+
+impl RestResponse {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, headers: Vec::new(), body }
+    }
+    
+    fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+    
+    /// Gets the HTTP status line of the response, such as `404 Not Found`
+    pub fn status_line(&self) -> &'static str {
+        match self.status {
+            200 => "200 OK",
+            400 => "400 Bad Request",
+            404 => "404 Not Found",
+            410 => "410 Gone",
+            _ => "500 Internal Server Error",
+        }
+    }
+}
+
+impl From<RestError> for RestResponse {
+    fn from(e: RestError) -> Self {
+        Self { status: e.status(), headers: Vec::new(), body: json!({ "error": e.to_string() }) }
+    }
+}
+
+type Result<T> = std::result::Result<T, RestError>;
+
+/// Answers block explorer requests from a node's chain
+pub struct RestApi {
+    blockchain: Arc<Mutex<Blockchain>>,
+    snapshots: SnapshotHandle,
+    pos: Arc<Mutex<PoSConsensus>>,
+    client: Arc<dyn ChainClient>,
+}
+
+impl RestApi {
+    /// Creates an API over a node's chain, validator metrics and wallet client
+    pub(crate) fn new(
+        blockchain: Arc<Mutex<Blockchain>>,
+        snapshots: SnapshotHandle,
+        pos: Arc<Mutex<PoSConsensus>>,
+        client: Arc<dyn ChainClient>,
+    ) -> Self {
+        Self { blockchain, snapshots, pos, client }
+    }
+    
+    /// Handles a `GET` of a request target, a path with an optional query string
+    pub fn handle(&self, target: &str) -> RestResponse {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = Query::parse(query);
+        let segments: Vec<String> = path.split('/').filter(|segment| !segment.is_empty()).map(percent_decode).collect();
+        
+        let result = match segments.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["blocks"] => self.blocks(&query),
+            ["blocks", "range"] => self.blocks_range(&query),
+            ["blocks", "at", timestamp] => self.block_at(timestamp),
+            ["block", id] => self.block(id),
+            ["tx", hash] => self.transaction(hash, &query),
+            ["txs", "range"] => self.transactions_range(&query),
+            ["address", address] => self.address(address, &query),
+            ["validators"] => self.validators(&query),
+            ["validators", address, "uptime"] => self.validator_uptime(address, &query),
+            ["supply"] => Ok(self.supply()),
+            ["reorgs"] => self.reorgs(&query),
+            ["search"] => self.search(&query),
+            _ => Err(RestError::NotFound(format!("Route {}", path))),
+        };
+        result.unwrap_or_else(RestResponse::from)
+    }
+    
+    /// `GET /blocks?start=&limit=`
+    fn blocks(&self, query: &Query) -> Result<RestResponse> {
+        let limit = query.limit()?;
+        let (latest, summaries) = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let latest = blockchain.get_latest_height();
+            let start = query.u64("start")?.unwrap_or(latest).min(latest);
+            
+            let summaries = (0..=start)
+                .rev()
+                .take(limit)
+                .filter_map(|height| blockchain.get_block_by_height(height))
+                .map(|block| BlockSummary::new(block, blockchain.get_block_gas_used(block.header().height)))
+                .collect::<Result<Vec<_>>>()?;
+            (latest, summaries)
+        };
+        
+        let next = summaries.last().map(|summary| summary.height).filter(|&height| height > 0);
+        let mut response = RestResponse::ok(json!({ "blocks": summaries.iter().map(BlockSummary::to_json).collect::<Vec<_>>() }))
+            .header("X-Total-Count", (latest + 1).to_string());
+        if let Some(height) = next {
+            response = response.header("Link", format!("</blocks?start={}&limit={}>; rel=\"next\"", height - 1, limit));
+        }
+        Ok(response)
+    }
+    
+    /// `GET /blocks/range?from=&to=&offset=&limit=`
+    fn blocks_range(&self, query: &Query) -> Result<RestResponse> {
+        let (from, to) = query.time_range()?;
+        let offset = query.u64("offset")?.unwrap_or(0) as usize;
+        let limit = query.limit()?;
+        let (blocks, total) = self.blocks_in_time_range(from, to, offset, limit)?;
+        
+        let mut response = RestResponse::ok(json!({ "blocks": blocks })).header("X-Total-Count", total.to_string());
+        if (offset.saturating_add(limit) as u64) < total {
+            response = response.header("Link", format!("</blocks/range?from={}&to={}&offset={}&limit={}>; rel=\"next\"", from, to, offset + limit, limit));
+        }
+        Ok(response)
+    }
+    
+    /// `GET /blocks/at/{timestamp}`
+    fn block_at(&self, timestamp: &str) -> Result<RestResponse> {
+        let timestamp = timestamp.parse::<u64>().map_err(|_| RestError::BadRequest(format!("Invalid timestamp {}", timestamp)))?;
+        let height = self
+            .blockchain
+            .lock()
+            .unwrap()
+            .find_block_by_time(timestamp)
+            .ok_or_else(|| RestError::NotFound(format!("Block at or before {}", timestamp)))?;
+        self.block(&height.to_string())
+    }
+    
+    /// Gets a page of the summaries of the blocks made from `from` to `to`, with the number of them
+    pub(crate) fn blocks_in_time_range(&self, from: u64, to: u64, offset: usize, limit: usize) -> Result<(Vec<Value>, u64)> {
+        let blockchain = self.blockchain.lock().unwrap();
+        let heights = blockchain.heights_in_time_range(from, to);
+        let total = heights.end - heights.start;
+        
+        let summaries = heights
+            .skip(offset)
+            .take(limit)
+            .filter_map(|height| blockchain.get_block_by_height(height))
+            .map(|block| BlockSummary::new(block, blockchain.get_block_gas_used(block.header().height)).map(|summary| summary.to_json()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((summaries, total))
+    }
+    
+    /// `GET /block/{height or hash}`
+    fn block(&self, id: &str) -> Result<RestResponse> {
+        let (block, hash, gas_used) = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let height = match id.parse::<u64>() {
+                Ok(height) => Some(height),
+                Err(_) => {
+                    let hash = id.parse::<BlockHash>().map_err(|e| RestError::BadRequest(format!("Invalid block {}: {}", id, e)))?;
+                    blockchain.get_height_by_hash(&hash)
+                }
+            };
+            let block = height
+                .and_then(|height| blockchain.get_shared_block(height))
+                .ok_or_else(|| RestError::NotFound(format!("Block {}", id)))?;
+            let hash = block_hash(&block)?;
+            let gas_used = blockchain.get_block_gas_used(block.header().height);
+            (block, hash, gas_used)
+        };
+        
+        Ok(RestResponse::ok(json!({
+            "hash": hash,
+            "gas_used": gas_used,
+            "block": &*block,
+        })))
+    }
+    
+    /// `GET /tx/{hash}?confirmations=`
+    ///
+    /// Only transactions included in a block are found.
+    fn transaction(&self, hash: &str, query: &Query) -> Result<RestResponse> {
+        let confirmations = query.confirmations()?;
+        let tx_id = hash.parse::<TxHash>().map_err(|e| RestError::BadRequest(format!("Invalid transaction hash {}: {}", hash, e)))?;
+        let (tx, block_hash, block_height, index, receipt) = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let (block, index) = eth::find_transaction(&blockchain, &tx_id).ok_or_else(|| RestError::NotFound(format!("Transaction {}", hash)))?;
+            (
+                block.transactions[index].clone(),
+                self::block_hash(block)?,
+                block.header().height,
+                index,
+                blockchain.find_receipt(&tx_id).map_err(|e| RestError::Gone(e.to_string()))?.cloned(),
+            )
+        };
+        
+        let tip = self.client.chain_tip().map_err(|e| RestError::Server(e.to_string()))?;
+        
+        Ok(RestResponse::ok(json!({
+            "block_hash": block_hash,
+            "block_height": block_height,
+            "index": index,
+            "transaction": tx,
+            "receipt": receipt,
+            "status": tip.status(Some(block_height), confirmations),
+        })))
+    }
+    
+    /// `GET /txs/range?from=&to=&offset=&limit=`
+    fn transactions_range(&self, query: &Query) -> Result<RestResponse> {
+        let (from, to) = query.time_range()?;
+        let offset = query.u64("offset")?.unwrap_or(0) as usize;
+        let limit = query.limit()?;
+        let (transactions, more) = self.transactions_in_time_range(from, to, offset, limit);
+        
+        let mut response = RestResponse::ok(json!({ "transactions": transactions }));
+        if more {
+            response = response.header("Link", format!("</txs/range?from={}&to={}&offset={}&limit={}>; rel=\"next\"", from, to, offset + limit, limit));
+        }
+        Ok(response)
+    }
+    
+    /// Gets a page of the transactions of the blocks made from `from` to `to`, and whether there are more
+    ///
+    /// Each comes with its block's height and timestamp and its index in the block.
+    pub(crate) fn transactions_in_time_range(&self, from: u64, to: u64, offset: usize, limit: usize) -> (Vec<Value>, bool) {
+        let blockchain = self.blockchain.lock().unwrap();
+        
+        // One more than the page tells whether there's a next page
+        let mut transactions = blockchain
+            .get_transactions_in_time_range(from, to)
+            .skip(offset)
+            .take(limit.saturating_add(1))
+            .map(|(block_height, index, tx)| {
+                let block_timestamp = blockchain.get_block_by_height(block_height).map(|block| block.header().timestamp);
+                json!({
+                    "block_height": block_height,
+                    "block_timestamp": block_timestamp,
+                    "index": index,
+                    "transaction": tx,
+                })
+            })
+            .collect::<Vec<_>>();
+        let more = transactions.len() > limit;
+        transactions.truncate(limit);
+        (transactions, more)
+    }
+    
+    /// `GET /address/{address}?cursor=&limit=&confirmations=`, or `?offset=&limit=&confirmations=`
+    fn address(&self, address: &str, query: &Query) -> Result<RestResponse> {
+        let offset = query.u64("offset")?.map(|offset| offset as usize);
+        let confirmations = query.confirmations()?;
+        let request = query.page()?;
+        let limit = request.limit;
+        
+        let snapshot = self.snapshots.latest();
+        let account = eth::account_name(&snapshot.state, address).map_err(|e| RestError::BadRequest(e.to_string()))?;
+        let account = Address::new(account).map_err(|e| RestError::BadRequest(e.to_string()))?;
+        let balance = snapshot.state.get_balance(&account).base_units();
+        let locked = snapshot.state.get_locked_balance(&account).base_units();
+        let nonce = snapshot.state.get_nonce(&account);
+        let contract = snapshot.state.is_contract(&account);
+        
+        let (mut history, next) = match offset {
+            Some(offset) => {
+                // One more than the page tells whether there's a next page
+                let history = self
+                    .client
+                    .get_history(&account, offset.saturating_add(limit).saturating_add(1))
+                    .map_err(|e| RestError::Server(e.to_string()))?;
+                let more = history.len() > offset.saturating_add(limit);
+                let history = history.into_iter().skip(offset).take(limit).collect::<Vec<_>>();
+                (Page { items: history, next_cursor: None, total_estimate: None }, more.then(|| format!("offset={}", offset + limit)))
+            }
+            None => {
+                let page = self.client.get_history_page(&account, &request).map_err(page_error)?;
+                let next = page.next_cursor.as_ref().map(|cursor| format!("cursor={}", cursor));
+                (page, next)
+            }
+        };
+        let first_page = offset.unwrap_or(0) == 0 && request.cursor.is_none();
+        if !history.items.is_empty() {
+            let tip = self.client.chain_tip().map_err(|e| RestError::Server(e.to_string()))?;
+            for record in &mut history.items {
+                record.judge(&tip, confirmations);
+            }
+        }
+        
+        if balance == 0 && locked == 0 && !contract && history.items.is_empty() && first_page && !is_address(&account) {
+            return Err(RestError::NotFound(format!("Address {}", address)));
+        }
+        
+        let mut response = RestResponse::ok(json!({
+            "address": account,
+            "balance": balance,
+            "balance_genx": format_genx(balance),
+            "locked": locked,
+            "locked_genx": format_genx(locked),
+            "nonce": nonce,
+            "contract": contract,
+            "transactions": history.items,
+            "next_cursor": history.next_cursor,
+        }));
+        if let Some(next) = next {
+            let confirmations = query.get("confirmations").map(|value| format!("&confirmations={}", value)).unwrap_or_default();
+            response = response.header("Link", format!("</address/{}?{}&limit={}{}>; rel=\"next\"", account, next, limit, confirmations));
+        }
+        Ok(response)
+    }
+    
+    /// `GET /validators?sort=&order=&cursor=&limit=`
+    ///
+    /// Lists a page of the registered validators, highest stake first by
+    /// default. Only those in the active set have performance metrics.
+    fn validators(&self, query: &Query) -> Result<RestResponse> {
+        let sort: ValidatorSort = query.sort()?;
+        let request = query.page()?;
+        
+        let (epoch, active, jailed) = {
+            let pos = self.pos.lock().unwrap();
+            let metrics = pos.get_validator_metrics();
+            let active = pos
+                .get_active_validators()
+                .iter()
+                .map(|validator| (validator.address.clone(), (validator.last_block_produced, metrics.get(&validator.address).cloned())))
+                .collect::<HashMap<_, _>>();
+            let jailed = active.keys().filter(|address| pos.is_jailed(address)).cloned().collect::<Vec<_>>();
+            (pos.get_current_epoch(), active, jailed)
+        };
+        
+        let snapshot = self.snapshots.latest();
+        let registered = snapshot.state.list_validators(sort, &request).map_err(page_error)?;
+        
+        let validators = registered
+            .items
+            .into_iter()
+            .map(|(info, stake)| {
+                let (last_block_produced, metrics) = match active.get(&info.operator) {
+                    Some((last_block_produced, metrics)) => (Some(*last_block_produced), metrics.as_ref()),
+                    None => (None, None),
+                };
+                json!({
+                    "address": info.operator,
+                    "moniker": info.moniker,
+                    "website": info.website,
+                    "commission_rate": info.commission_rate,
+                    "consensus_key": info.consensus_key,
+                    "registered_at": info.registered_at,
+                    "commission_changed_at": info.commission_changed_at,
+                    "stake": stake,
+                    "active": active.contains_key(&info.operator),
+                    "jailed": jailed.contains(&info.operator),
+                    "last_block_produced": last_block_produced,
+                    "blocks_produced": metrics.map(|metrics| metrics.blocks_produced()),
+                    "blocks_missed": metrics.map(|metrics| metrics.blocks_missed()),
+                    "uptime": metrics.map(|metrics| metrics.uptime()),
+                    "last_seen": metrics.map(|metrics| metrics.last_seen()),
+                })
+            })
+            .collect::<Vec<_>>();
+        
+        let mut response = RestResponse::ok(json!({
+            "epoch": epoch,
+            "validators": validators,
+            "next_cursor": registered.next_cursor,
+            "total_estimate": registered.total_estimate,
+        }));
+        if let Some(total) = registered.total_estimate {
+            response = response.header("X-Total-Count", total.to_string());
+        }
+        if let Some(cursor) = &registered.next_cursor {
+            response = response.header("Link", format!("</validators?{}cursor={}&limit={}>; rel=\"next\"", query.sort_and_order(), cursor, request.limit));
+        }
+        Ok(response)
+    }
+    
+    /// `GET /validators/{address}/uptime?from=&to=`
+    ///
+    /// Counts the slots a validator kept and missed in the blocks made
+    /// between two times, with its streaks of missed slots. Registered
+    /// validators without slots in the range have none of either.
+    fn validator_uptime(&self, address: &str, query: &Query) -> Result<RestResponse> {
+        let (from, to) = query.time_range()?;
+        let (heights, stats, jailed) = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let heights = blockchain.heights_in_time_range(from, to);
+            let mut pos = self.pos.lock().unwrap();
+            let stats = pos.uptime(&blockchain, heights.clone()).remove(address);
+            (heights, stats, pos.is_jailed(address))
+        };
+        let stats = match stats {
+            Some(stats) => stats,
+            None if self.snapshots.latest().state.get_validator(address).is_some() => UptimeStats::default(),
+            None => return Err(RestError::NotFound(format!("Validator {}", address))),
+        };
+        
+        let (first, last) = if heights.is_empty() { (None, None) } else { (Some(heights.start), Some(heights.end - 1)) };
+        Ok(RestResponse::ok(json!({
+            "address": address,
+            "from_height": first,
+            "to_height": last,
+            "produced": stats.produced,
+            "missed": stats.missed,
+            "uptime": stats.uptime(),
+            "longest_missed_streak": stats.longest_missed_streak,
+            "trailing_missed": stats.trailing_missed,
+            "jailed": jailed,
+        })))
+    }
+    
+    /// `GET /reorgs?limit=`
+    fn reorgs(&self, query: &Query) -> Result<RestResponse> {
+        let limit = query.limit()?;
+        let reorgs = self.blockchain.lock().unwrap().reorg_history(limit);
+        Ok(RestResponse::ok(json!({ "reorgs": reorgs })))
+    }
+    
+    /// `GET /supply`
+    fn supply(&self) -> RestResponse {
+        let snapshot = self.snapshots.latest();
+        RestResponse::ok(json!({
+            "height": snapshot.block_height,
+            "max_supply": ctb_core::genesis::get_max_supply(),
+            "circulating_supply": snapshot.state.get_total_supply(),
+        }))
+    }
+    
+    /// `GET /search?q=`
+    ///
+    /// A number is a block height and a 32-byte hash a transaction or block
+    /// hash; anything else is tried as an address.
+    fn search(&self, query: &Query) -> Result<RestResponse> {
+        let q = query.get("q").map(str::trim).filter(|q| !q.is_empty()).ok_or_else(|| RestError::BadRequest("Missing query q".to_string()))?;
+        let found = |kind: &str, id: String| {
+            let path = format!("/{}/{}", kind, id);
+            Ok(RestResponse::ok(json!({ "type": kind, "id": id, "path": path })))
+        };
+        
+        if let Ok(height) = q.parse::<u64>() {
+            let exists = self.blockchain.lock().unwrap().get_block_by_height(height).is_some();
+            return if exists { found("block", height.to_string()) } else { Err(RestError::NotFound(format!("Block {}", height))) };
+        }
+        
+        if let Ok(tx_id) = q.parse::<TxHash>() {
+            let blockchain = self.blockchain.lock().unwrap();
+            if blockchain.get_transaction_height(&tx_id).is_some() {
+                return found("tx", tx_id.to_string());
+            }
+            if let Some(height) = blockchain.get_height_by_hash(&BlockHash(tx_id.0)) {
+                return found("block", height.to_string());
+            }
+            return Err(RestError::NotFound(format!("Transaction or block {}", q)));
+        }
+        
+        let snapshot = self.snapshots.latest();
+        let account = eth::account_name(&snapshot.state, q).map_err(|_| RestError::NotFound(format!("Address {}", q)))?;
+        let known = is_address(&account)
+            || snapshot.state.is_contract(&account)
+            || Address::new(account.as_str()).is_ok_and(|account| snapshot.state.get_balance(&account) > Amount::ZERO)
+            || !self.client.get_history(&account, 1).map_err(|e| RestError::Server(e.to_string()))?.is_empty();
+        if known {
+            return found("address", account);
+        }
+        Err(RestError::NotFound(format!("Block, transaction or address {}", q)))
+    }
+}
+
+/// Block as listed by `/blocks`
+struct BlockSummary {
+    height: u64,
+    hash: BlockHash,
+    prev_hash: BlockHash,
+    timestamp: u64,
+    validator: String,
+    transaction_count: usize,
+    gas_used: Option<u64>,
+    base_fee: u64,
+}
+
+impl BlockSummary {
+    fn new(block: &Block, gas_used: Option<u64>) -> Result<Self> {
+        Ok(Self {
+            height: block.header().height,
+            hash: block_hash(block)?,
+            prev_hash: block.header().prev_hash,
+            timestamp: block.header().timestamp,
+            validator: block.header().validator.clone(),
+            transaction_count: block.transactions.len(),
+            gas_used,
+            base_fee: block.header().base_fee,
+        })
+    }
+    
+    fn to_json(&self) -> Value {
+        json!({
+            "height": self.height,
+            "hash": self.hash,
+            "prev_hash": self.prev_hash,
+            "timestamp": self.timestamp,
+            "validator": self.validator,
+            "transaction_count": self.transaction_count,
+            "gas_used": self.gas_used,
+            "base_fee": self.base_fee,
+        })
+    }
+}
+
+/// Query string parameters
+struct Query(Vec<(String, String)>);
+
+impl Query {
+    fn parse(query: &str) -> Self {
+        Self(
+            query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (percent_decode(name), percent_decode(value))
+                })
+                .collect(),
+        )
+    }
+    
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+    
+    fn u64(&self, name: &str) -> Result<Option<u64>> {
+        self.get(name)
+            .map(|value| value.parse().map_err(|_| RestError::BadRequest(format!("Invalid {} {}", name, value))))
+            .transpose()
+    }
+    
+    /// Gets the confirmations a transaction needs to be safe, `DEFAULT_SAFE_CONFIRMATIONS` if not given
+    fn confirmations(&self) -> Result<u64> {
+        Ok(self.u64("confirmations")?.unwrap_or(DEFAULT_SAFE_CONFIRMATIONS))
+    }
+    
+    /// Gets the `from` and `to` times of a time range, defaulting to the whole chain
+    fn time_range(&self) -> Result<(u64, u64)> {
+        Ok((self.u64("from")?.unwrap_or(0), self.u64("to")?.unwrap_or(u64::MAX)))
+    }
+    
+    /// Gets the page size, `DEFAULT_PAGE_SIZE` if not given and at most `MAX_PAGE_SIZE`
+    fn limit(&self) -> Result<usize> {
+        match self.u64("limit")? {
+            None => Ok(DEFAULT_PAGE_SIZE),
+            Some(0) => Err(RestError::BadRequest("limit must be positive".to_string())),
+            Some(limit) => Ok((limit as usize).min(MAX_PAGE_SIZE)),
+        }
+    }
+    
+    /// Gets the page a cursor-paged route is asked for, from `limit`, `cursor` and `order`
+    fn page(&self) -> Result<PageRequest> {
+        let mut request = PageRequest::new(self.limit()?);
+        request.cursor = self.get("cursor").filter(|cursor| !cursor.is_empty()).and_then(|cursor| cursor.parse().ok());
+        request.order = self.named::<SortOrder>("order")?;
+        Ok(request)
+    }
+    
+    /// Gets what a listing is sorted by, from `sort`, its default if not given
+    fn sort<S: DeserializeOwned + Default>(&self) -> Result<S> {
+        Ok(self.named("sort")?.unwrap_or_default())
+    }
+    
+    /// Gets the `sort=` and `order=` parameters given, to repeat in the link to the next page
+    fn sort_and_order(&self) -> String {
+        ["sort", "order"]
+            .iter()
+            .filter_map(|name| self.get(name).map(|value| format!("{}={}&", name, value)))
+            .collect()
+    }
+    
+    /// Gets a parameter naming one of an enum's variants, in their serde form
+    fn named<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        self.get(name)
+            .map(|value| {
+                serde_json::from_value(Value::String(value.to_string()))
+                    .map_err(|_| RestError::BadRequest(format!("Invalid {} {}", name, value)))
+            })
+            .transpose()
+    }
+}
+
+/// Decodes `%XX` escapes and `+` in a path segment or query parameter
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() && bytes[i + 1..i + 3].iter().all(u8::is_ascii_hexdigit) => {
+                let hex_part = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                decoded.push(u8::from_str_radix(hex_part, 16).unwrap_or_default());
+                i += 3;
+                continue;
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Checks whether an address is well-formed, encoding a key or 20 bytes of hex
+fn is_address(address: &str) -> bool {
+    SignatureScheme::from_address(address).is_some()
+        || address.strip_prefix("0x").is_some_and(|hex_part| hex_part.len() == 40)
+}
+
+/// Converts the error of a cursor-paged listing, a 400 for a bad cursor
+fn page_error(e: BlockchainError) -> RestError {
+    match e {
+        BlockchainError::InvalidCursor(_) => RestError::BadRequest(e.to_string()),
+        e => RestError::Server(e.to_string()),
+    }
+}
+
+fn block_hash(block: &Block) -> Result<BlockHash> {
+    block.hash().map_err(|e| RestError::Server(e.to_string()))
+}
\ No newline at end of file
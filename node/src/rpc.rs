@@ -0,0 +1,1009 @@
+//! JSON-RPC server for the GENX node
+//!
+//! Serves JSON-RPC 2.0 over HTTP: each request is a `POST` whose body is a
+//! request object or a batch of them. Methods prefixed `genx_` expose the
+//! node's own view of the chain and are what the wallet and the `genx`
+//! command line use; every other method is handed to the Ethereum API (see
+//! `eth`). Errors use the same codes as the Ethereum methods.
+//!
+//! | Method                            | Params                                 | Result                                           |
+//! |-----------------------------------|----------------------------------------|--------------------------------------------------|
+//! | `genx_status`                     | none                                   | node ID, state, heights, peers, chain ID         |
+//! | `genx_getBalance`                 | address                                | balance in base units                            |
+//! | `genx_getLockedBalance`           | address                                | base units locked in storage deposits            |
+//! | `genx_getNonce`                   | address                                | nonce its next transaction must have             |
+//! | `genx_sendTransaction`            | signed transaction                     | transaction ID                                   |
+//! | `genx_getTransactionHistory`      | address, optional limit, confirmations | `TransactionRecord`s, newest first               |
+//! | `genx_listTransactionHistory`     | address, optional page, confirmations  | page of `TransactionRecord`s, newest first       |
+//! | `genx_getTransaction`             | transaction ID, optional confirmations | its `TransactionRecord`, or null if not included |
+//! | `genx_getTransactionStatus`       | transaction ID, optional confirmations | its `ConfirmationStatus`                         |
+//! | `genx_addressPossiblyUsed`        | address                                | false if it was never used, see below            |
+//! | `genx_getAddressFirstUse`         | address                                | height of its first block, or null if unused     |
+//! | `genx_nextBaseFee`                | none                                   | base fee of the next block                       |
+//! | `genx_feeHistogram`               | none                                   | `FeeBucket`s of the mempool, highest rate first  |
+//! | `genx_estimateInclusion`          | fee rate, e.g. `{"gas_price": n}`      | `EstimatedBlocks` of a transaction paying it     |
+//! | `genx_call`                       | contract, selector, arguments, sender  | hex return data                                  |
+//! | `genx_getContractAbi`             | contract                               | the contract's `FunctionABI`s, or null           |
+//! | `genx_estimateGas`                | transaction                            | `{"gas": n}` or `{"reverted": reason}`           |
+//! | `genx_getBlockByTime`             | timestamp                              | height of the latest block made by then, or null |
+//! | `genx_getBlocksInTimeRange`       | from, to, optional offset and limit    | `{"blocks": summaries, "total": n}`              |
+//! | `genx_getTransactionsInTimeRange` | from, to, optional offset and limit    | `{"transactions": [...], "more": bool}`          |
+//! | `genx_getStateDiff`               | from, to, optional address and paging  | `{"changes": [...], "more": bool}`               |
+//! | `genx_getBalanceAt`               | address, height                        | balance after the block at that height           |
+//! | `genx_getValidatorsAt`            | height                                 | registered validators and stakes, highest first  |
+//! | `genx_getStorageAt`               | contract, hex slot, height             | hex slot value, or null if empty                 |
+//! | `genx_getProposals`               | none                                   | governance `Proposal`s, oldest first; deprecated |
+//! | `genx_listProposals`              | optional page                          | page of `Proposal`s, oldest first                |
+//! | `genx_listValidators`             | optional page                          | page of validators, highest stake first          |
+//! | `genx_listCheckpoints`            | optional page                          | page of finality checkpoints, lowest first       |
+//! | `genx_listLogs`                   | `LogFilter`, optional page             | page of `IndexedLog`s, oldest first              |
+//! | `genx_getProposal`                | proposal ID                            | the `Proposal`, or null                          |
+//! | `genx_getGovernedParameters`      | optional height                        | values governance set, by parameter              |
+//! | `genx_getFilterHeaders`           | start height, count                    | `{"start": n, "headers": [...]}`                 |
+//! | `genx_getFilters`                 | start height, count                    | `BlockFilter`s from the start height on          |
+//! | `genx_getBlockWithReceipts`       | height                                 | `{"block": block, "receipts": [...]}`            |
+//! | `genx_getSupplyInfo`              | none                                   | supply minted, burned and yet to be minted       |
+//!
+//! Transaction records carry a `ConfirmationStatus` (see
+//! `ctb_core::confirmation`): pending, included, safe once the block has the
+//! caller's number of confirmations, `DEFAULT_SAFE_CONFIRMATIONS` if the
+//! optional `confirmations` parameter isn't given, or finalized.
+//!
+//! The `genx_list...` methods return a page of a listing as
+//! `{"items": [...], "next_cursor": c, "total_estimate": n}` (see
+//! `ctb_core::paging`). A page is requested with an object of optional fields:
+//! `limit` (50 by default, at most 1000), the `cursor` of the previous page,
+//! the `order`, `ascending` or `descending`, and for validators the `sort`,
+//! `stake` or `address`. Cursors only work with the listing and sort they
+//! came from.
+//!
+//! Time ranges include both ends and are paged like the REST API's
+//! `/blocks/range` and `/txs/range` routes, oldest first.
+//!
+//! State diffs list the `StateChange`s from the state after block `from` to
+//! the state after block `to`, optionally only those of one address. Both
+//! heights must be within the last `MAX_ROLLBACK_DEPTH` blocks, and the
+//! changes are paged like time ranges.
+//!
+//! `genx_getGovernedParameters` gives the value of each governed parameter
+//! in effect for the block at a height, the next one by default, or null if
+//! governance hasn't changed it from the configured value (see
+//! `ctb_core::governance`).
+//!
+//! `genx_addressPossiblyUsed` only reads the chain's address blooms (see
+//! `ctb_core::address_bloom`): false means the address never sent or received
+//! a transaction, true that it may have, which `genx_getAddressFirstUse`
+//! confirms, more slowly.
+//!
+//! Light wallets sync with the block filter methods (see `filters` and
+//! `wallet::filter_sync`). They return at most `MAX_HEADERS` headers and
+//! `MAX_FILTERS` filters, fewer past the latest block.
+//!
+//! `genx_getSupplyInfo` accounts for every GENX as of the latest block: the
+//! `genesis_allocation` plus the block rewards `emitted` since, less what
+//! was `burned`, is the `total_supply`. Both allocations and rewards are
+//! summed from the coinbase transactions of the blocks, not the state, and
+//! what the schedule had the blocks mint but they left out is `forfeited`.
+//! Fees are burned, but for the tips of gas fees. The validator rewards
+//! pool was allocated at genesis, so what it holds is minted but not
+//! circulating: `circulating_supply` is the total supply less the
+//! `rewards_pool_balance`. `remaining_unminted` is the subsidy the blocks
+//! after the latest will mint until rewards run out (see
+//! `consensus::emission`), and `next_halving_height` the height of the next
+//! block whose reward halves, null once they have.
+//!
+//! The `...At` methods read the state after the block at a past height,
+//! which is reconstructed by undoing the blocks after it. Only heights
+//! within the node's `max_state_depth` of the latest block can be read.
+//!
+//! A `genx_call` that reverts fails with the same error as a reverted
+//! `eth_call`, carrying the reason and the revert data.
+//!
+//! The `admin_` methods are only served on a separate listener, at
+//! `admin_listen_addr`, and let operators control the node (see `admin`).
+//!
+//! The `debug_` methods are only served with `debug_enabled` on:
+//!
+//! | Method                    | Params                                  | Result                                                  |
+//! |---------------------------|-----------------------------------------|---------------------------------------------------------|
+//! | `debug_tracePropagation`  | block hash                              | the block's `BlockTimeline`, or null if it isn't recent |
+//! | `debug_validateBlock`     | hex binary block, optional state root   | `ValidationReport` of the checks the block passes       |
+//!
+//! `debug_validateBlock` checks a block as the node would before adding it,
+//! without adding it (see `dry_run`). The block is given in its binary
+//! encoding (see `ctb_core::wire`), and the state root, if given, is compared
+//! with that of the state the block would leave.
+//!
+//! Development nodes also serve the `dev_` methods and `evm_mine`, which
+//! make blocks on demand and set balances (see `dev`). Each transaction
+//! `genx_sendTransaction` or `eth_sendRawTransaction` submits to one is
+//! included in a block of its own before the method returns, unless
+//! automining was turned off.
+//!
+//! `GET` requests are answered by the block explorer REST API on the same
+//! address (see `rest`) unless `rest_enabled` is off, except for
+//! `GET /metrics`, which serves the node's metrics to Prometheus (see
+//! `metrics`).
+//!
+//! Access to the server can be restricted with `access`: an authorization
+//! token for privileged methods, CORS origins, rate limits, and which
+//! methods are served at all (see `access`). Browsers' `OPTIONS` preflight
+//! requests are answered from it.
+//!
+//! Each connection carries a single request and is closed after the
+//! response.
+
+use std::cmp::Reverse;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+use ctb_core::block::Block;
+use ctb_core::confirmation::DEFAULT_SAFE_CONFIRMATIONS;
+use ctb_core::fee_market::FeeRate;
+use ctb_core::chain::Blockchain;
+use ctb_core::genesis;
+use ctb_core::governance::GovernedParameter;
+use ctb_core::paging::PageRequest;
+use ctb_core::receipt::LogFilter;
+use ctb_core::rewards;
+use ctb_core::state::StateSnapshot;
+use ctb_core::transaction::Transaction;
+use ctb_core::validator::ValidatorSort;
+use ctb_core::wire::Wire;
+use ctb_core::{Address, BlockHash, BlockchainError, TxHash};
+
+use consensus::emission;
+use consensus::finality::FinalityManager;
+
+use wallet::api::{CallOutcome, ChainClient, GasEstimate, TransactionRecord};
+use wallet::filter_sync::{FilterHeaders, FilteredBlock};
+
+use crate::access::{AccessConfig, AccessControl, Client};
+use crate::admin::AdminApi;
+use crate::dev::DevApi;
+use crate::dry_run::BlockValidator;
+use crate::eth::{self, EthApi, EthError, Result};
+use crate::filters::FilterIndex;
+use crate::message::{MAX_FILTERS, MAX_HEADERS};
+use crate::metrics::{self, Metrics};
+use crate::network::NetworkManager;
+use crate::propagation::PropagationTracker;
+use crate::rest::{self, RestApi, RestError, RestResponse};
+use crate::NodeState;
+
+/// JSON-RPC error code of a body that isn't valid JSON
+pub const PARSE_ERROR: i64 = -32700;
+
+/// History entries returned when a request doesn't give a limit
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+/// Most history entries a single request returns
+pub const MAX_HISTORY_LIMIT: usize = 1000;
+
+/// Longest the request line and headers of a request may be
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// Time a client has to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// RPC server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RpcConfig {
+    /// Whether the node serves RPC at all
+    pub enabled: bool,
+    
+    /// Address to listen on
+    pub listen_addr: SocketAddr,
+    
+    /// Largest request body accepted, in bytes
+    pub max_request_size: usize,
+    
+    /// Whether `GET` requests are answered by the REST API, see `rest`
+    pub rest_enabled: bool,
+    
+    /// Whether the node serves the `admin_` methods, see `admin`
+    pub admin_enabled: bool,
+    
+    /// Address the `admin_` methods are served on, apart from the rest
+    pub admin_listen_addr: SocketAddr,
+    
+    /// Whether the node serves the `debug_` methods
+    pub debug_enabled: bool,
+    
+    /// What HTTP clients may call, see `access`
+    pub access: AccessConfig,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            listen_addr: "127.0.0.1:8545".parse().unwrap(),
+            max_request_size: 1024 * 1024,
+            rest_enabled: true,
+            admin_enabled: true,
+            admin_listen_addr: "127.0.0.1:8546".parse().unwrap(),
+            debug_enabled: false,
+            access: AccessConfig::default(),
+        }
+    }
+}
+
+/// Dispatches JSON-RPC requests to a node
+#[derive(Clone)]
+pub struct RpcHandler {
+    node_id: String,
+    chain_id: u64,
+    state: Arc<RwLock<NodeState>>,
+    blockchain: Arc<Mutex<Blockchain>>,
+    finality: Arc<Mutex<FinalityManager>>,
+    network: Arc<Mutex<NetworkManager>>,
+    metrics: Arc<Metrics>,
+    filters: Arc<FilterIndex>,
+    client: Arc<dyn ChainClient>,
+    eth: Arc<EthApi>,
+    rest: Arc<RestApi>,
+    admin: Option<Arc<AdminApi>>,
+    propagation: Option<Arc<Mutex<PropagationTracker>>>,
+    block_validator: Option<Arc<BlockValidator>>,
+    dev: Option<Arc<DevApi>>,
+}
+
+impl RpcHandler {
+    /// Creates a handler over a node's components
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        node_id: String,
+        chain_id: u64,
+        state: Arc<RwLock<NodeState>>,
+        blockchain: Arc<Mutex<Blockchain>>,
+        finality: Arc<Mutex<FinalityManager>>,
+        network: Arc<Mutex<NetworkManager>>,
+        metrics: Arc<Metrics>,
+        filters: Arc<FilterIndex>,
+        client: Arc<dyn ChainClient>,
+        eth: EthApi,
+        rest: RestApi,
+    ) -> Self {
+        Self {
+            node_id,
+            chain_id,
+            state,
+            blockchain,
+            finality,
+            network,
+            metrics,
+            filters,
+            client,
+            eth: Arc::new(eth),
+            rest: Arc::new(rest),
+            admin: None,
+            propagation: None,
+            block_validator: None,
+            dev: None,
+        }
+    }
+    
+    /// Serves the `admin_` methods too, which are otherwise unknown
+    pub(crate) fn with_admin(mut self, admin: AdminApi) -> Self {
+        self.admin = Some(Arc::new(admin));
+        self
+    }
+    
+    /// Serves the `debug_` methods too, which are otherwise unknown
+    pub(crate) fn with_debug(mut self, propagation: Arc<Mutex<PropagationTracker>>, block_validator: BlockValidator) -> Self {
+        self.propagation = Some(propagation);
+        self.block_validator = Some(Arc::new(block_validator));
+        self
+    }
+    
+    /// Serves the `dev_` methods and `evm_mine` too, mining transactions submitted as automining says
+    pub(crate) fn with_dev(mut self, dev: DevApi) -> Self {
+        self.dev = Some(Arc::new(dev));
+        self
+    }
+    
+    /// Renders the node's metrics for `GET /metrics`
+    pub fn render_metrics(&self) -> String {
+        self.metrics.render()
+    }
+    
+    /// Handles a REST `GET` of a request target
+    pub fn handle_rest(&self, target: &str) -> RestResponse {
+        self.rest.handle(target)
+    }
+    
+    /// Handles a JSON-RPC request or batch of requests, returning the response to send back
+    pub fn handle_request(&self, request: &Value) -> Value {
+        eth::handle_json_rpc(request, |method, params| self.call(method, params))
+    }
+    
+    /// Handles a JSON-RPC request or batch of requests from an HTTP client, as `access` allows
+    pub fn handle_request_from(&self, request: &Value, access: &AccessControl, client: &Client) -> Value {
+        eth::handle_json_rpc(request, |method, params| {
+            access.check_call(method, client)?;
+            self.call(method, params)
+        })
+    }
+    
+    /// Runs a method with its positional parameters
+    pub fn call(&self, method: &str, params: &[Value]) -> Result<Value> {
+        match method {
+            "genx_status" => Ok(self.status()),
+            "genx_getBalance" => {
+                let address = eth::param_str(params, 0, "address")?;
+                Ok(json!(self.client.get_balance(address).map_err(server_error)?))
+            }
+            "genx_getLockedBalance" => {
+                let address = eth::param_address(params, 0)?;
+                let snapshot = self.blockchain.lock().unwrap().snapshot();
+                Ok(json!(snapshot.state.get_locked_balance(&address)))
+            }
+            "genx_getNonce" => {
+                let address = eth::param_str(params, 0, "address")?;
+                Ok(json!(self.client.get_nonce(address).map_err(server_error)?))
+            }
+            "genx_sendTransaction" => {
+                let tx = param_transaction(params)?;
+                let id = self.client.submit_transaction(&tx).map_err(server_error)?;
+                if let Some(dev) = &self.dev {
+                    dev.transaction_added();
+                }
+                Ok(Value::String(id.to_string()))
+            }
+            "genx_getTransactionHistory" => {
+                let address = eth::param_str(params, 0, "address")?;
+                let limit = match params.get(1) {
+                    None | Some(Value::Null) => DEFAULT_HISTORY_LIMIT,
+                    Some(limit) => limit
+                        .as_u64()
+                        .map(|limit| (limit as usize).min(MAX_HISTORY_LIMIT))
+                        .ok_or_else(|| EthError::InvalidParams(format!("invalid limit {}", limit)))?,
+                };
+                let mut history = self.client.get_history(address, limit).map_err(server_error)?;
+                self.judge(&mut history, params, 2)?;
+                serde_json::to_value(history).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "genx_listTransactionHistory" => {
+                let address = eth::param_str(params, 0, "address")?;
+                let request = param_page(params, 1)?;
+                let mut page = self.client.get_history_page(address, &request).map_err(server_error)?;
+                self.judge(&mut page.items, params, 2)?;
+                serde_json::to_value(page).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "genx_getTransaction" => {
+                let tx_id = TxHash::from(eth::param_hash(params, 0)?);
+                let mut record = self.client.get_transaction(&tx_id).map_err(server_error)?;
+                self.judge(record.as_mut_slice(), params, 1)?;
+                serde_json::to_value(record).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "genx_getTransactionStatus" => {
+                let tx_id = TxHash::from(eth::param_hash(params, 0)?);
+                let confirmations = param_u64(params, 1, "confirmations")?.unwrap_or(DEFAULT_SAFE_CONFIRMATIONS);
+                let status = self.client.get_transaction_status(&tx_id, confirmations).map_err(server_error)?;
+                serde_json::to_value(status).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "genx_addressPossiblyUsed" => {
+                let address = eth::param_str(params, 0, "address")?;
+                Ok(json!(self.client.address_possibly_used(address).map_err(server_error)?))
+            }
+            "genx_getAddressFirstUse" => {
+                let address = eth::param_str(params, 0, "address")?;
+                Ok(json!(self.client.address_first_used(address).map_err(server_error)?))
+            }
+            "genx_nextBaseFee" => Ok(json!(self.client.next_base_fee())),
+            "genx_feeHistogram" => {
+                let histogram = self.client.fee_histogram().map_err(server_error)?;
+                serde_json::to_value(histogram).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "genx_estimateInclusion" => {
+                let rate = params.first().ok_or_else(|| EthError::InvalidParams("missing fee rate".to_string()))?;
+                let rate: FeeRate =
+                    serde_json::from_value(rate.clone()).map_err(|e| EthError::InvalidParams(format!("invalid fee rate: {}", e)))?;
+                let estimate = self.client.estimate_inclusion(rate).map_err(server_error)?;
+                serde_json::to_value(estimate).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "genx_call" => {
+                let contract = eth::param_str(params, 0, "contract")?;
+                let selector: [u8; 4] = eth::decode_hex(eth::param_str(params, 1, "selector")?)?
+                    .try_into()
+                    .map_err(|_| EthError::InvalidParams("selector must be 4 bytes".to_string()))?;
+                let arguments = eth::decode_hex(eth::param_str(params, 2, "arguments")?)?;
+                let sender = eth::param_str(params, 3, "sender")?;
+                
+                match self.client.call_contract(contract, &selector, &arguments, sender).map_err(server_error)? {
+                    CallOutcome::Returned(output) => Ok(Value::String(eth::bytes(output))),
+                    CallOutcome::Reverted { reason, data } => Err(EthError::Reverted { reason, data }),
+                }
+            }
+            "genx_getContractAbi" => {
+                let contract = eth::param_str(params, 0, "contract")?;
+                let abi = self.client.get_contract_abi(contract).map_err(server_error)?;
+                serde_json::to_value(abi).map_err(|e| EthError::Server(e.to_string()))
+            }
+            "genx_estimateGas" => {
+                let tx = param_transaction(params)?;
+                match self.client.estimate_gas(&tx).map_err(server_error)? {
+                    GasEstimate::Gas(gas) => Ok(json!({ "gas": gas })),
+                    GasEstimate::Reverted(reason) => Ok(json!({ "reverted": reason })),
+                }
+            }
+            "genx_getBlockByTime" => {
+                let timestamp = param_u64(params, 0, "timestamp")?.ok_or_else(|| EthError::InvalidParams("missing timestamp".to_string()))?;
+                Ok(json!(self.blockchain.lock().unwrap().find_block_by_time(timestamp)))
+            }
+            "genx_getBlocksInTimeRange" => {
+                let (from, to, offset, limit) = param_time_range(params)?;
+                let (blocks, total) = self.rest.blocks_in_time_range(from, to, offset, limit).map_err(rest_error)?;
+                Ok(json!({ "blocks": blocks, "total": total }))
+            }
+            "genx_getTransactionsInTimeRange" => {
+                let (from, to, offset, limit) = param_time_range(params)?;
+                let (transactions, more) = self.rest.transactions_in_time_range(from, to, offset, limit);
+                Ok(json!({ "transactions": transactions, "more": more }))
+            }
+            "genx_getStateDiff" => {
+                let from = param_u64(params, 0, "from")?.ok_or_else(|| EthError::InvalidParams("missing from".to_string()))?;
+                let to = param_u64(params, 1, "to")?.ok_or_else(|| EthError::InvalidParams("missing to".to_string()))?;
+                let address = match params.get(2) {
+                    None | Some(Value::Null) => None,
+                    Some(address) => Some(
+                        address
+                            .as_str()
+                            .ok_or_else(|| EthError::InvalidParams(format!("invalid address {}", address)))?,
+                    ),
+                };
+                let offset = param_u64(params, 3, "offset")?.unwrap_or(0) as usize;
+                let limit = match param_u64(params, 4, "limit")? {
+                    None => rest::DEFAULT_PAGE_SIZE,
+                    Some(0) => return Err(EthError::InvalidParams("limit must be positive".to_string())),
+                    Some(limit) => (limit as usize).min(rest::MAX_PAGE_SIZE),
+                };
+                
+                let mut changes: Vec<_> = self
+                    .blockchain
+                    .lock()
+                    .unwrap()
+                    .state_changes(from, to, address)
+                    .map_err(|e| EthError::InvalidParams(e.to_string()))?
+                    .skip(offset)
+                    .take(limit + 1)
+                    .collect();
+                let more = changes.len() > limit;
+                changes.truncate(limit);
+                Ok(json!({ "changes": changes, "more": more }))
+            }
+            "genx_getBalanceAt" => {
+                let address = eth::param_address(params, 0)?;
+                let snapshot = self.state_at(params, 1)?;
+                Ok(json!(snapshot.state.get_balance(&address)))
+            }
+            "genx_getValidatorsAt" => {
+                let snapshot = self.state_at(params, 0)?;
+                let mut validators = snapshot.state.get_validators();
+                validators.sort_by_key(|(_, stake)| Reverse(*stake));
+                let validators = validators
+                    .into_iter()
+                    .map(|(info, stake)| json!({ "address": info.operator, "moniker": info.moniker, "stake": stake }))
+                    .collect::<Vec<_>>();
+                Ok(Value::Array(validators))
+            }
+            "genx_getStorageAt" => {
+                let contract = eth::param_str(params, 0, "contract")?;
+                let slot = eth::decode_hex(eth::param_str(params, 1, "slot")?)?;
+                let snapshot = self.state_at(params, 2)?;
+                let value = snapshot.state.get_contract_storage(contract).and_then(|storage| storage.get(&slot));
+                Ok(value.map_or(Value::Null, |value| Value::String(eth::bytes(value))))
+            }
+            "genx_getProposals" => {
+                let snapshot = self.blockchain.lock().unwrap().snapshot();
+                Ok(json!(snapshot.state.get_proposals().collect::<Vec<_>>()))
+            }
+            "genx_listProposals" => {
+                let request = param_page(params, 0)?;
+                let snapshot = self.blockchain.lock().unwrap().snapshot();
+                Ok(json!(snapshot.state.list_proposals(&request)?))
+            }
+            "genx_listValidators" => {
+                let request = param_page(params, 0)?;
+                let sort: ValidatorSort = param_sort(params, 0)?;
+                let snapshot = self.blockchain.lock().unwrap().snapshot();
+                let page = snapshot.state.list_validators(sort, &request)?.map(|(info, stake)| {
+                    json!({ "address": info.operator, "moniker": info.moniker, "stake": stake })
+                });
+                Ok(json!(page))
+            }
+            "genx_listCheckpoints" => {
+                let request = param_page(params, 0)?;
+                let finality = self.finality.lock().unwrap();
+                let page = finality.list_checkpoints(&request)?.map(|checkpoint| {
+                    let mut votes: Vec<_> = checkpoint.votes.iter().collect();
+                    votes.sort();
+                    json!({
+                        "height": checkpoint.height,
+                        "block_hash": checkpoint.block_hash,
+                        "votes": votes,
+                        "finalized": checkpoint.finalized,
+                    })
+                });
+                Ok(json!(page))
+            }
+            "genx_listLogs" => {
+                let filter = params.first().ok_or_else(|| EthError::InvalidParams("missing filter".to_string()))?;
+                let filter: LogFilter = serde_json::from_value(filter.clone())
+                    .map_err(|e| EthError::InvalidParams(format!("invalid filter: {}", e)))?;
+                let request = param_page(params, 1)?;
+                Ok(json!(self.blockchain.lock().unwrap().list_logs(&filter, &request)?))
+            }
+            "genx_getProposal" => {
+                let id = param_u64(params, 0, "proposal ID")?.ok_or_else(|| EthError::InvalidParams("missing proposal ID".to_string()))?;
+                let snapshot = self.blockchain.lock().unwrap().snapshot();
+                Ok(json!(snapshot.state.get_proposal(id)))
+            }
+            "genx_getGovernedParameters" => {
+                let snapshot = self.blockchain.lock().unwrap().snapshot();
+                let height = param_u64(params, 0, "height")?.unwrap_or(snapshot.block_height + 1);
+                let values: serde_json::Map<_, _> = GovernedParameter::ALL
+                    .iter()
+                    .map(|parameter| (parameter.key().to_string(), json!(snapshot.state.governed_value(*parameter, height))))
+                    .collect();
+                Ok(Value::Object(values))
+            }
+            "genx_getFilterHeaders" => {
+                let (start, end) = param_height_range(params, MAX_HEADERS)?;
+                Ok(json!(FilterHeaders { start, headers: self.filters.headers(start..end) }))
+            }
+            "genx_getFilters" => {
+                let (start, end) = param_height_range(params, MAX_FILTERS)?;
+                Ok(json!(self.filters.filters(start..end)))
+            }
+            "genx_getBlockWithReceipts" => {
+                let height = param_u64(params, 0, "height")?.ok_or_else(|| EthError::InvalidParams("missing height".to_string()))?;
+                let blockchain = self.blockchain.lock().unwrap();
+                let block = blockchain.get_block_by_height(height).ok_or(BlockchainError::UnknownBlock { height })?.clone();
+                let receipts = blockchain.get_block_receipts(height)?.into_iter().cloned().collect();
+                Ok(json!(FilteredBlock { block, receipts }))
+            }
+            "genx_getSupplyInfo" => {
+                let blockchain = self.blockchain.lock().unwrap();
+                let snapshot = blockchain.snapshot();
+                let height = snapshot.block_height;
+                let genesis_allocation = blockchain.emission_between(0, 0)?;
+                let emitted = if height == 0 { 0 } else { blockchain.emission_between(1, height)? };
+                let scheduled = emission::remaining_emission(1).saturating_sub(emission::remaining_emission(height + 1));
+                let total_supply = snapshot.state.get_total_supply();
+                let burned = snapshot.state.get_total_burned();
+                let rewards_pool = Address::new(genesis::get_validator_rewards_address()).map_err(BlockchainError::from)?;
+                let rewards_pool_balance = snapshot.state.get_balance(&rewards_pool).base_units();
+                Ok(json!({
+                    "height": height,
+                    "max_supply": genesis::get_max_supply(),
+                    "genesis_allocation": genesis_allocation,
+                    "emitted": emitted,
+                    "forfeited": scheduled.saturating_sub(emitted),
+                    "burned": burned,
+                    "total_supply": total_supply,
+                    "circulating_supply": total_supply.saturating_sub(rewards_pool_balance),
+                    "rewards_pool_balance": rewards_pool_balance,
+                    "remaining_unminted": emission::remaining_emission(height + 1),
+                    "block_reward": rewards::block_reward(height + 1),
+                    "next_halving_height": emission::next_halving_height(height),
+                }))
+            }
+            "debug_tracePropagation" => match &self.propagation {
+                Some(propagation) => {
+                    let block_hash = BlockHash::from(eth::param_hash(params, 0)?);
+                    Ok(json!(propagation.lock().unwrap().timeline(&block_hash)))
+                }
+                None => Err(EthError::MethodNotFound(method.to_string())),
+            },
+            "debug_validateBlock" => match &self.block_validator {
+                Some(block_validator) => {
+                    let encoding = eth::decode_hex(eth::param_str(params, 0, "block")?)?;
+                    let block = Block::from_bytes(&encoding).map_err(|e| EthError::InvalidParams(format!("invalid block: {}", e)))?;
+                    let expected_state_root = match params.get(1) {
+                        None | Some(Value::Null) => None,
+                        Some(_) => Some(eth::param_hash(params, 1)?),
+                    };
+                    Ok(json!(block_validator.validate(&block, expected_state_root)))
+                }
+                None => Err(EthError::MethodNotFound(method.to_string())),
+            },
+            method if method.starts_with("admin_") => match &self.admin {
+                Some(admin) => admin.call(method, params),
+                None => Err(EthError::MethodNotFound(method.to_string())),
+            },
+            method if method.starts_with("dev_") || method == "evm_mine" => match &self.dev {
+                Some(dev) => dev.call(method, params),
+                None => Err(EthError::MethodNotFound(method.to_string())),
+            },
+            "eth_sendRawTransaction" => {
+                let hash = self.eth.call(method, params)?;
+                if let Some(dev) = &self.dev {
+                    dev.transaction_added();
+                }
+                Ok(hash)
+            }
+            method => self.eth.call(method, params),
+        }
+    }
+    
+    /// Judges records again by the confirmations given at `index` of the parameters, if given
+    fn judge(&self, records: &mut [TransactionRecord], params: &[Value], index: usize) -> Result<()> {
+        let Some(confirmations) = param_u64(params, index, "confirmations")? else {
+            return Ok(());
+        };
+        let tip = self.client.chain_tip().map_err(server_error)?;
+        for record in records {
+            record.judge(&tip, confirmations);
+        }
+        Ok(())
+    }
+    
+    /// Gets the state after the block at the height given at `index` of the parameters
+    fn state_at(&self, params: &[Value], index: usize) -> Result<StateSnapshot> {
+        let height = param_u64(params, index, "height")?.ok_or_else(|| EthError::InvalidParams("missing height".to_string()))?;
+        self.blockchain.lock().unwrap().state_at(height).map_err(EthError::from)
+    }
+    
+    /// `genx_status()`
+    fn status(&self) -> Value {
+        let (height, latest_hash) = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let latest_hash = blockchain.get_latest_block().and_then(|block| block.hash().ok());
+            (blockchain.get_latest_height(), latest_hash)
+        };
+        let (finalized_height, finality) = {
+            let finality = self.finality.lock().unwrap();
+            (finality.get_latest_finalized_height(), finality.memory_stats())
+        };
+        
+        json!({
+            "node_id": self.node_id,
+            "state": *self.state.read().unwrap(),
+            "height": height,
+            "latest_hash": latest_hash,
+            "finalized_height": finalized_height,
+            "finality": finality,
+            "peer_count": self.network.lock().unwrap().peer_count(),
+            "chain_id": self.chain_id,
+        })
+    }
+}
+
+/// Running RPC server
+pub struct RpcServer {
+    local_addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl RpcServer {
+    /// Starts serving requests on the configured address
+    pub async fn start(config: &RpcConfig, handler: RpcHandler) -> io::Result<Self> {
+        Self::serve(config.listen_addr, config, config.rest_enabled, handler).await
+    }
+    
+    /// Starts serving requests on the configured admin address
+    ///
+    /// `GET` requests aren't answered there, so `handler` should be one
+    /// that serves the `admin_` methods.
+    pub async fn start_admin(config: &RpcConfig, handler: RpcHandler) -> io::Result<Self> {
+        Self::serve(config.admin_listen_addr, config, false, handler).await
+    }
+    
+    async fn serve(listen_addr: SocketAddr, config: &RpcConfig, rest_enabled: bool, handler: RpcHandler) -> io::Result<Self> {
+        config.access.validate(listen_addr).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let access = Arc::new(AccessControl::new(config.access.clone(), handler.metrics.clone()));
+        let max_request_size = config.max_request_size;
+        
+        let listener = TcpListener::bind(listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (shutdown, mut stopped) = oneshot::channel();
+        
+        let handler = Arc::new(handler);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            let handler = handler.clone();
+                            let access = access.clone();
+                            tokio::spawn(async move {
+                                let connection = Connection { ip: peer.ip(), access, max_request_size, rest_enabled };
+                                if let Err(e) = serve_connection(stream, handler, connection).await {
+                                    eprintln!("RPC connection error: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) => eprintln!("RPC accept error: {}", e),
+                    },
+                }
+            }
+        });
+        
+        Ok(Self { local_addr, shutdown: Some(shutdown) })
+    }
+    
+    /// Gets the address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+    
+    /// Stops accepting connections; requests already being served complete
+    pub fn shutdown(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+impl Drop for RpcServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// How a server answers a connection
+struct Connection {
+    /// Address of the client
+    ip: IpAddr,
+    
+    access: Arc<AccessControl>,
+    max_request_size: usize,
+    rest_enabled: bool,
+}
+
+/// Reads one HTTP request from a connection and answers it
+async fn serve_connection(mut stream: TcpStream, handler: Arc<RpcHandler>, connection: Connection) -> io::Result<()> {
+    let allow = if connection.rest_enabled { "GET, POST" } else { "POST" };
+    let read = read_request(&mut stream, connection.max_request_size, connection.rest_enabled);
+    let (request, head) = match tokio::time::timeout(REQUEST_TIMEOUT, read).await {
+        Ok(request) => request?,
+        Err(_) => return write_response(&mut stream, "408 Request Timeout", &[], "").await,
+    };
+    
+    let access = connection.access;
+    let mut headers = match access.check_origin(head.origin.as_deref(), connection.ip) {
+        Some(headers) => headers,
+        None => return write_response(&mut stream, "403 Forbidden", &[], "").await,
+    };
+    if !access.admit(connection.ip) {
+        headers.push(("Retry-After", "1".to_string()));
+        return write_response(&mut stream, "429 Too Many Requests", &headers, "").await;
+    }
+    
+    let body = match request {
+        HttpRequest::Post(body) => body,
+        HttpRequest::Preflight => {
+            headers.extend([
+                ("Access-Control-Allow-Methods", allow.to_string()),
+                ("Access-Control-Allow-Headers", "Content-Type, Authorization".to_string()),
+                ("Access-Control-Max-Age", "600".to_string()),
+            ]);
+            return write_response(&mut stream, "204 No Content", &headers, "").await;
+        }
+        HttpRequest::Get(target) if target == "/metrics" => {
+            headers.push(("Content-Type", metrics::CONTENT_TYPE.to_string()));
+            return write_response(&mut stream, "200 OK", &headers, &handler.render_metrics()).await;
+        }
+        HttpRequest::Get(target) => {
+            let mut response = tokio::task::spawn_blocking(move || handler.handle_rest(&target))
+                .await
+                .map_err(io::Error::other)?;
+            response.headers.extend(headers);
+            return write_response(&mut stream, response.status_line(), &response.headers, &response.body.to_string()).await;
+        }
+        HttpRequest::Rejected(status) if status.starts_with("405") => {
+            headers.push(("Allow", allow.to_string()));
+            return write_response(&mut stream, status, &headers, "").await;
+        }
+        HttpRequest::Rejected(status) => return write_response(&mut stream, status, &headers, "").await,
+    };
+    
+    let client = Client { ip: connection.ip, token: head.token };
+    let response = match serde_json::from_slice::<Value>(&body) {
+        Ok(request) => tokio::task::spawn_blocking(move || handler.handle_request_from(&request, &access, &client))
+            .await
+            .map_err(io::Error::other)?,
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": PARSE_ERROR, "message": format!("parse error: {}", e) },
+        }),
+    };
+    
+    // A single call refused by access control gets the matching HTTP status too
+    let status = match response.pointer("/error/code").and_then(Value::as_i64) {
+        Some(eth::LIMIT_EXCEEDED) => {
+            headers.push(("Retry-After", "1".to_string()));
+            "429 Too Many Requests"
+        }
+        Some(eth::UNAUTHORIZED) => {
+            headers.push(("WWW-Authenticate", "Bearer".to_string()));
+            "401 Unauthorized"
+        }
+        _ => "200 OK",
+    };
+    write_response(&mut stream, status, &headers, &response.to_string()).await
+}
+
+/// Outcome of reading an HTTP request
+enum HttpRequest {
+    /// A `POST` with its body
+    Post(Vec<u8>),
+    
+    /// A `GET` of a request target
+    Get(String),
+    
+    /// An `OPTIONS` request, which browsers send before cross-origin requests
+    Preflight,
+    
+    /// A request that is answered with the given status and no body
+    Rejected(&'static str),
+}
+
+/// Headers of a request that access control looks at
+#[derive(Default)]
+struct RequestHead {
+    /// `Origin` header, sent by browsers
+    origin: Option<String>,
+    
+    /// Token of a bearer `Authorization` header
+    token: Option<String>,
+}
+
+async fn read_request(stream: &mut TcpStream, max_request_size: usize, rest_enabled: bool) -> io::Result<(HttpRequest, RequestHead)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    
+    // Read until the end of the headers
+    let header_end = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Ok((HttpRequest::Rejected("431 Request Header Fields Too Large"), RequestHead::default()));
+        }
+        
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed mid-request"));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+    
+    let head = String::from_utf8_lossy(&buffer[..header_end]);
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let headers: Vec<(&str, &str)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let header = |name: &str| headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| *value);
+    let request_head = RequestHead {
+        origin: header("origin").map(str::to_string),
+        token: header("authorization")
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim().to_string()),
+    };
+    
+    let mut request_line = request_line.split(' ');
+    match (request_line.next().unwrap_or_default(), request_line.next()) {
+        ("POST", _) => {}
+        ("GET", Some(target)) if rest_enabled => return Ok((HttpRequest::Get(target.to_string()), request_head)),
+        ("OPTIONS", _) => return Ok((HttpRequest::Preflight, request_head)),
+        _ => return Ok((HttpRequest::Rejected("405 Method Not Allowed"), request_head)),
+    }
+    
+    let content_length = match header("content-length").map(str::parse::<usize>) {
+        Some(Ok(length)) => length,
+        Some(Err(_)) => return Ok((HttpRequest::Rejected("400 Bad Request"), request_head)),
+        None => return Ok((HttpRequest::Rejected("411 Length Required"), request_head)),
+    };
+    if content_length > max_request_size {
+        return Ok((HttpRequest::Rejected("413 Payload Too Large"), request_head));
+    }
+    
+    let mut body = buffer.split_off(header_end);
+    body.truncate(content_length);
+    if body.len() < content_length {
+        let start = body.len();
+        body.resize(content_length, 0);
+        stream.read_exact(&mut body[start..]).await?;
+    }
+    
+    Ok((HttpRequest::Post(body), request_head))
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, headers: &[(&str, String)], body: &str) -> io::Result<()> {
+    let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n", status, body.len());
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !body.is_empty() && !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Content-Type")) {
+        response.push_str("Content-Type: application/json\r\n");
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn param_transaction(params: &[Value]) -> Result<Transaction> {
+    let tx = params.first().ok_or_else(|| EthError::InvalidParams("missing transaction".to_string()))?;
+    serde_json::from_value(tx.clone()).map_err(|e| EthError::InvalidParams(format!("invalid transaction: {}", e)))
+}
+
+/// Gets an optional integer parameter
+pub(crate) fn param_u64(params: &[Value], index: usize, name: &str) -> Result<Option<u64>> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| EthError::InvalidParams(format!("invalid {} {}", name, value))),
+    }
+}
+
+/// Gets an optional page parameter, an object of `PageRequest` fields
+pub(crate) fn param_page(params: &[Value], index: usize) -> Result<PageRequest> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(PageRequest::default()),
+        Some(page @ Value::Object(_)) => {
+            serde_json::from_value(page.clone()).map_err(|e| EthError::InvalidParams(format!("invalid page: {}", e)))
+        }
+        Some(page) => Err(EthError::InvalidParams(format!("invalid page {}", page))),
+    }
+}
+
+/// Gets the `sort` field of an optional page parameter, the listing's default sort if it's missing
+pub(crate) fn param_sort<S: DeserializeOwned + Default>(params: &[Value], index: usize) -> Result<S> {
+    match params.get(index).and_then(|page| page.get("sort")) {
+        None | Some(Value::Null) => Ok(S::default()),
+        Some(sort) => serde_json::from_value(sort.clone()).map_err(|e| EthError::InvalidParams(format!("invalid sort {}: {}", sort, e))),
+    }
+}
+
+/// Gets the heights a block filter query covers, from its start and count parameters, at most `max`
+fn param_height_range(params: &[Value], max: u64) -> Result<(u64, u64)> {
+    let start = param_u64(params, 0, "start")?.ok_or_else(|| EthError::InvalidParams("missing start".to_string()))?;
+    let count = param_u64(params, 1, "count")?.ok_or_else(|| EthError::InvalidParams("missing count".to_string()))?;
+    Ok((start, start.saturating_add(count.min(max))))
+}
+
+/// Gets the `from`, `to`, `offset` and `limit` parameters of a time range query
+fn param_time_range(params: &[Value]) -> Result<(u64, u64, usize, usize)> {
+    let from = param_u64(params, 0, "from")?.ok_or_else(|| EthError::InvalidParams("missing from".to_string()))?;
+    let to = param_u64(params, 1, "to")?.ok_or_else(|| EthError::InvalidParams("missing to".to_string()))?;
+    let offset = param_u64(params, 2, "offset")?.unwrap_or(0) as usize;
+    let limit = match param_u64(params, 3, "limit")? {
+        None => rest::DEFAULT_PAGE_SIZE,
+        Some(0) => return Err(EthError::InvalidParams("limit must be positive".to_string())),
+        Some(limit) => (limit as usize).min(rest::MAX_PAGE_SIZE),
+    };
+    Ok((from, to, offset, limit))
+}
+
+fn rest_error(e: RestError) -> EthError {
+    match e {
+        RestError::BadRequest(message) => EthError::InvalidParams(message),
+        e => EthError::Server(e.to_string()),
+    }
+}
+
+fn server_error(e: ctb_core::BlockchainError) -> EthError {
+    EthError::from(e)
+}
\ No newline at end of file
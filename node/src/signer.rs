@@ -0,0 +1,501 @@
+//! Where a validating node's consensus key is kept
+//!
+//! The consensus engine signs the blocks the node produces, and the node
+//! its finality votes, with a `consensus::signer::BlockSigner` built from
+//! `NodeConfig::signer_config`:
+//!
+//! | Backend    | Key                                                                    |
+//! |------------|------------------------------------------------------------------------|
+//! | `local`    | `validator_key`, a secret key in hex held in memory                    |
+//! | `keystore` | an account of an encrypted wallet file, unlocked with its password     |
+//! | `remote`   | held by a signer service the node asks over TCP, see `RemoteSigner`    |
+//!
+//! `genx node run` reads the keystore's password from `GENX_SIGNER_PASSWORD`,
+//! or prompts for it, at startup.
+//!
+//! # Remote signing protocol
+//!
+//! The node connects to the signer and writes a request as a line of JSON,
+//! which the signer answers with another:
+//!
+//! | Field     | Request                                                               |
+//! |-----------|-----------------------------------------------------------------------|
+//! | `token`   | the signer's token, if it has one                                     |
+//! | `kind`    | `block_header`, `finality_vote` or `public_key`                       |
+//! | `height`  | height of the block signed or voted for                               |
+//! | `payload` | in hex, the header's binary encoding without its signature (see `ctb_core::wire`), or `FinalityVote::signing_message` |
+//!
+//! The answer holds the `signature` in hex, the `public_key`, or an
+//! `error` and its `code` (see `SignerError::error_code`). Tokens are
+//! compared in constant time but travel in the clear: run signers on
+//! loopback, a private network or behind a tunnel.
+//!
+//! A signer never signs two different payloads of a kind at one height,
+//! which would get its validator slashed, but signs the same one again, so
+//! a node retrying after a timeout gets its signature. Heights signed at
+//! are kept in a file if the signer has one, and only the latest
+//! `HISTORY_DEPTH` of them: heights below those are refused outright.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use ctb_core::block::BlockHeader;
+use ctb_core::hashing;
+use ctb_core::rlp;
+use ctb_core::signature::SignatureScheme;
+use ctb_core::wire::{self, Wire};
+use ctb_core::{BlockHash, Bytes, Hash};
+
+use consensus::finality::FinalityVote;
+use consensus::signer::{block_signing_hash, BlockSigner, LocalSigner, SignerError, SigningKind};
+
+use wallet::{Wallet, WalletError};
+
+use crate::access::constant_time_eq;
+
+/// Heights of each kind a remote signer remembers signing at
+pub const HISTORY_DEPTH: u64 = 1024;
+
+/// Where the consensus key is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerBackend {
+    Local,
+    Keystore,
+    Remote,
+}
+
+/// Signer configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignerConfig {
+    /// Where the consensus key is kept
+    pub backend: SignerBackend,
+    
+    /// Scheme of `validator_key`, for the `local` backend
+    pub scheme: SignatureScheme,
+    
+    /// Wallet file holding the consensus key, for the `keystore` backend
+    pub keystore_path: Option<String>,
+    
+    /// Account of the wallet whose key is the consensus key, for the `keystore` backend
+    pub keystore_account: Option<String>,
+    
+    /// Address of the signer service, such as `127.0.0.1:9700`, for the `remote` backend
+    pub remote_addr: Option<String>,
+    
+    /// Token the signer service requires, if any
+    pub remote_token: Option<String>,
+    
+    /// Time the signer service has to connect and answer, in milliseconds
+    pub remote_timeout_ms: u64,
+}
+
+impl Default for SignerConfig {
+    fn default() -> Self {
+        Self {
+            backend: SignerBackend::Local,
+            scheme: SignatureScheme::Ed25519,
+            keystore_path: None,
+            keystore_account: None,
+            remote_addr: None,
+            remote_token: None,
+            remote_timeout_ms: 2000,
+        }
+    }
+}
+
+/// Builds the signer a configuration names, if it names one
+///
+/// The `local` backend without a `validator_key` names none. The
+/// `keystore` backend needs the keystore's `password`, and is `Locked`
+/// without it.
+pub fn open(config: &SignerConfig, validator_key: Option<&str>, password: Option<&str>) -> Result<Option<Arc<dyn BlockSigner>>, SignerError> {
+    let missing = |field: &str| SignerError::Rejected(format!("signer_config.{} isn't set", field));
+    let signer: Arc<dyn BlockSigner> = match config.backend {
+        SignerBackend::Local => match validator_key {
+            Some(key) => Arc::new(LocalSigner::from_hex(config.scheme, key)?),
+            None => return Ok(None),
+        },
+        SignerBackend::Keystore => {
+            let path = config.keystore_path.as_ref().ok_or_else(|| missing("keystore_path"))?;
+            let account = config.keystore_account.as_ref().ok_or_else(|| missing("keystore_account"))?;
+            Arc::new(KeystoreSigner::open(Path::new(path), account, password.ok_or(SignerError::Locked)?)?)
+        }
+        SignerBackend::Remote => {
+            let addr = config.remote_addr.as_ref().ok_or_else(|| missing("remote_addr"))?;
+            Arc::new(RemoteSigner::new(addr, config.remote_token.clone(), Duration::from_millis(config.remote_timeout_ms)))
+        }
+    };
+    Ok(Some(signer))
+}
+
+/// Signer with the key of an account of an encrypted wallet file
+pub struct KeystoreSigner {
+    wallet: Mutex<Wallet>,
+    address: String,
+}
+
+impl KeystoreSigner {
+    /// Opens a wallet file read-only and unlocks it, to sign with the key of one of its accounts
+    pub fn open(path: &Path, address: &str, password: &str) -> Result<Self, SignerError> {
+        let keystore_error = |e: WalletError| match e {
+            WalletError::IncorrectPassword => SignerError::Locked,
+            e => SignerError::Rejected(format!("{}: {}", path.display(), e)),
+        };
+        let mut wallet = Wallet::load_readonly(path.to_path_buf()).map_err(keystore_error)?;
+        wallet.unlock(password).map_err(keystore_error)?;
+        if wallet.get_account(address).is_none() {
+            return Err(SignerError::Rejected(format!("{} holds no account {}", path.display(), address)));
+        }
+        Ok(Self { wallet: Mutex::new(wallet), address: address.to_string() })
+    }
+    
+    fn sign(&self, hash: &Hash) -> Result<Bytes, SignerError> {
+        self.wallet
+            .lock()
+            .unwrap()
+            .sign_message(&self.address, hash)
+            .map(Bytes)
+            .map_err(|e| SignerError::Rejected(e.to_string()))
+    }
+}
+
+impl fmt::Debug for KeystoreSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeystoreSigner").field("address", &self.address).finish_non_exhaustive()
+    }
+}
+
+impl BlockSigner for KeystoreSigner {
+    fn public_key(&self) -> Result<String, SignerError> {
+        Ok(self.address.clone())
+    }
+    
+    fn sign_block_header(&self, header: &BlockHeader) -> Result<Bytes, SignerError> {
+        let hash = block_signing_hash(header).map_err(|e| SignerError::Rejected(e.to_string()))?;
+        self.sign(&hash)
+    }
+    
+    fn sign_finality_vote(&self, height: u64, block_hash: &BlockHash) -> Result<Bytes, SignerError> {
+        self.sign(&FinalityVote::signing_hash(height, block_hash))
+    }
+}
+
+/// Signer asking a signer service over TCP, see the module docs
+///
+/// Each request opens a connection, which must connect and be answered
+/// within the timeout, or the signer is `Unavailable`. Its public key is
+/// asked for once, when first needed.
+#[derive(Debug)]
+pub struct RemoteSigner {
+    addr: String,
+    token: Option<String>,
+    timeout: Duration,
+    public_key: OnceLock<String>,
+}
+
+impl RemoteSigner {
+    /// Creates a signer asking the service at an address, without connecting yet
+    pub fn new(addr: &str, token: Option<String>, timeout: Duration) -> Self {
+        Self { addr: addr.to_string(), token, timeout, public_key: OnceLock::new() }
+    }
+    
+    /// Sends a request and reads the answer, failing with the error it holds if any
+    fn request(&self, kind: &str, height: u64, payload: &[u8]) -> Result<Value, SignerError> {
+        let unavailable = |e: io::Error| SignerError::Unavailable(format!("{}: {}", self.addr, e));
+        let addr = self.addr.to_socket_addrs().map_err(unavailable)?.next()
+            .ok_or_else(|| SignerError::Unavailable(format!("{} resolves to no address", self.addr)))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout).map_err(unavailable)?;
+        stream.set_read_timeout(Some(self.timeout)).map_err(unavailable)?;
+        stream.set_write_timeout(Some(self.timeout)).map_err(unavailable)?;
+        
+        let request = json!({
+            "token": self.token,
+            "kind": kind,
+            "height": height,
+            "payload": format!("0x{}", hex::encode(payload)),
+        });
+        writeln!(stream, "{}", request).map_err(unavailable)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).map_err(unavailable)?;
+        if line.is_empty() {
+            return Err(SignerError::Unavailable(format!("{} closed the connection", self.addr)));
+        }
+        
+        let answer: Value = serde_json::from_str(&line).map_err(|e| SignerError::Rejected(format!("Malformed answer: {}", e)))?;
+        match answer["error"].as_str() {
+            None => Ok(answer),
+            Some(error) if answer["code"].as_u64() == Some(2201) => match SigningKind::from_name(kind) {
+                Some(kind) => Err(SignerError::DoubleSign { kind, height }),
+                None => Err(SignerError::Rejected(error.to_string())),
+            },
+            Some(error) => Err(SignerError::Rejected(error.to_string())),
+        }
+    }
+    
+    fn sign(&self, kind: SigningKind, height: u64, payload: &[u8]) -> Result<Bytes, SignerError> {
+        let answer = self.request(kind.name(), height, payload)?;
+        answer["signature"]
+            .as_str()
+            .and_then(|signature| hex::decode(signature.trim_start_matches("0x")).ok())
+            .map(Bytes)
+            .ok_or_else(|| SignerError::Rejected("Answer holds no signature".to_string()))
+    }
+}
+
+impl BlockSigner for RemoteSigner {
+    fn public_key(&self) -> Result<String, SignerError> {
+        if let Some(public_key) = self.public_key.get() {
+            return Ok(public_key.clone());
+        }
+        let answer = self.request("public_key", 0, &[])?;
+        let public_key = answer["public_key"]
+            .as_str()
+            .ok_or_else(|| SignerError::Rejected("Answer holds no public key".to_string()))?;
+        Ok(self.public_key.get_or_init(|| public_key.to_string()).clone())
+    }
+    
+    fn sign_block_header(&self, header: &BlockHeader) -> Result<Bytes, SignerError> {
+        let unsigned = BlockHeader { signature: None, ..header.clone() };
+        self.sign(SigningKind::BlockHeader, header.height, &unsigned.to_bytes())
+    }
+    
+    fn sign_finality_vote(&self, height: u64, block_hash: &BlockHash) -> Result<Bytes, SignerError> {
+        self.sign(SigningKind::FinalityVote, height, &FinalityVote::signing_message(height, block_hash))
+    }
+}
+
+/// Payload signed at a height
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SignedPayload {
+    kind: SigningKind,
+    height: u64,
+    #[serde(with = "ctb_core::types::hex_serde")]
+    payload_hash: Hash,
+}
+
+/// Heights a signer signed at, refusing to sign anything else at them
+#[derive(Debug, Default)]
+pub struct SigningHistory {
+    /// Hash of the payload signed, by kind and height
+    signed: BTreeMap<(SigningKind, u64), Hash>,
+    
+    /// Lowest height of each kind signed at that's still remembered
+    floors: BTreeMap<SigningKind, u64>,
+    
+    /// File the history is saved to, if any
+    path: Option<PathBuf>,
+}
+
+/// What a history's file holds
+#[derive(Serialize, Deserialize)]
+struct SavedHistory {
+    signed: Vec<SignedPayload>,
+    floors: BTreeMap<SigningKind, u64>,
+}
+
+impl SigningHistory {
+    /// Creates a history kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Creates a history saved to a file, first loading what the file holds
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let mut history = Self { path: Some(path.clone()), ..Self::default() };
+        if path.exists() {
+            let saved: SavedHistory = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            history.floors = saved.floors;
+            for entry in saved.signed {
+                history.signed.insert((entry.kind, entry.height), entry.payload_hash);
+            }
+        }
+        Ok(history)
+    }
+    
+    /// Records that a payload is about to be signed, failing if another was signed at its height
+    ///
+    /// Once saved, the payload may be signed: the record is synced to disk first.
+    pub fn record(&mut self, kind: SigningKind, height: u64, payload: &[u8]) -> Result<(), SignerError> {
+        let payload_hash = hashing::sha256(payload);
+        let refused = SignerError::DoubleSign { kind, height };
+        if self.floors.get(&kind).is_some_and(|floor| height < *floor) {
+            return Err(refused);
+        }
+        match self.signed.get(&(kind, height)) {
+            Some(signed) if *signed == payload_hash => return Ok(()),
+            Some(_) => return Err(refused),
+            None => {}
+        }
+        
+        self.signed.insert((kind, height), payload_hash);
+        let latest = self.signed.keys().filter(|(signed_kind, _)| *signed_kind == kind).map(|(_, height)| *height).max().unwrap_or(height);
+        if latest >= HISTORY_DEPTH {
+            let floor = latest + 1 - HISTORY_DEPTH;
+            self.signed.retain(|(signed_kind, height), _| *signed_kind != kind || *height >= floor);
+            self.floors.insert(kind, floor);
+        }
+        self.save().map_err(|e| SignerError::Unavailable(format!("Failed to save the signing history: {}", e)))
+    }
+    
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let saved = SavedHistory {
+            signed: self.signed.iter().map(|((kind, height), payload_hash)| SignedPayload { kind: *kind, height: *height, payload_hash: *payload_hash }).collect(),
+            floors: self.floors.clone(),
+        };
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(serde_json::to_string_pretty(&saved)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+/// What a signer service does with requests, whatever carries them
+pub struct SignerService {
+    signer: Arc<dyn BlockSigner>,
+    token: Option<String>,
+    history: Mutex<SigningHistory>,
+}
+
+impl SignerService {
+    /// Creates a service signing with a signer, for clients giving `token` if set
+    pub fn new(signer: Arc<dyn BlockSigner>, token: Option<String>, history: SigningHistory) -> Self {
+        Self { signer, token, history: Mutex::new(history) }
+    }
+    
+    /// Answers a request line with the line to send back
+    pub fn handle_line(&self, line: &str) -> String {
+        let answer = match self.handle(line) {
+            Ok(answer) => answer,
+            Err(e) => json!({ "error": e.to_string(), "code": e.error_code() }),
+        };
+        answer.to_string()
+    }
+    
+    fn handle(&self, line: &str) -> Result<Value, SignerError> {
+        let malformed = |what: &str| SignerError::Rejected(format!("Malformed request: {}", what));
+        let request: Value = serde_json::from_str(line).map_err(|e| malformed(&e.to_string()))?;
+        if let Some(token) = &self.token {
+            let given = request["token"].as_str().unwrap_or_default();
+            if !constant_time_eq(given.as_bytes(), token.as_bytes()) {
+                return Err(SignerError::Rejected("Unauthorized".to_string()));
+            }
+        }
+        
+        let kind = request["kind"].as_str().ok_or_else(|| malformed("no kind"))?;
+        if kind == "public_key" {
+            return Ok(json!({ "public_key": self.signer.public_key()? }));
+        }
+        let kind = SigningKind::from_name(kind).ok_or_else(|| malformed("unknown kind"))?;
+        let height = request["height"].as_u64().ok_or_else(|| malformed("no height"))?;
+        let payload = request["payload"]
+            .as_str()
+            .and_then(|payload| hex::decode(payload.trim_start_matches("0x")).ok())
+            .ok_or_else(|| malformed("no payload"))?;
+        
+        // The payload is checked to be what's signed before it's recorded
+        let sign: Box<dyn FnOnce() -> Result<Bytes, SignerError>> = match kind {
+            SigningKind::BlockHeader => {
+                let header = BlockHeader::from_bytes(&payload).map_err(|e| malformed(&e.to_string()))?;
+                if header.height != height || header.signature.is_some() {
+                    return Err(malformed("header isn't an unsigned header at the height"));
+                }
+                Box::new(move || self.signer.sign_block_header(&header))
+            }
+            SigningKind::FinalityVote => {
+                let block_hash = decode_vote(&payload, height).ok_or_else(|| malformed("payload isn't a vote at the height"))?;
+                Box::new(move || self.signer.sign_finality_vote(height, &block_hash))
+            }
+        };
+        self.history.lock().unwrap().record(kind, height, &payload)?;
+        Ok(json!({ "signature": format!("0x{}", hex::encode(sign()?.into_vec())) }))
+    }
+    
+    /// Serves a connection, answering each request line until the client closes it
+    pub fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            writeln!(writer, "{}", self.handle_line(&line?))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the block hash of a vote's signing message, if it's one at a height
+fn decode_vote(payload: &[u8], height: u64) -> Option<BlockHash> {
+    let item = rlp::decode(payload).ok()?;
+    let fields = wire::fields(&item, 2).ok()?;
+    let block_hash = BlockHash(wire::decode_hash(&fields[1]).ok()?);
+    (fields[0].as_u64().ok()? == height && FinalityVote::signing_message(height, &block_hash) == payload).then_some(block_hash)
+}
+
+/// Signer service listening for nodes on a TCP port, see the module docs
+pub struct SignerServer {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SignerServer {
+    /// Starts serving on an address, one thread per connection
+    pub fn start(addr: SocketAddr, service: Arc<SignerService>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let service = service.clone();
+                thread::spawn(move || {
+                    if let Err(e) = service.serve(stream) {
+                        eprintln!("Signer connection failed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self { local_addr, stopped, thread: Some(thread) })
+    }
+    
+    /// Gets the address the signer listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+    
+    /// Stops accepting connections
+    pub fn stop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes the listener up to notice
+        let _ = TcpStream::connect(self.local_addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SignerServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
\ No newline at end of file
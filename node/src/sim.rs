@@ -0,0 +1,491 @@
+//! Simulated networks of nodes, for testing nodes together
+//!
+//! Built with the `testutil` feature. A `Simulation` runs a few nodes in
+//! the process, all sharing a genesis block and validator set, each
+//! validator signing its blocks with a key derived from its address (see
+//! `validator_key`), connected to
+//! each other by an in-memory network in place of TCP (see
+//! `network::Transport`) and reading the time from one `VirtualClock`.
+//! Nothing runs on its own: `step` delivers the next message due or, when
+//! none is due before the next tick, moves the clock on to the tick and
+//! ticks every node in turn, as the node loop would (see `Node::tick`).
+//! Given the same configuration and calls, a simulation makes the same
+//! blocks at the same virtual times and delivers the same messages, however
+//! long it simulates, in a fraction of that time.
+//!
+//! Nodes connect as they would over TCP, handshakes and all (see
+//! `connection`), except that handshakes take no time, so connections can
+//! time out and be closed; healing a partition reconnects nodes that gave
+//! up on each other, as redialing would.
+//!
+//! A message takes its link's latency to arrive. It may be dropped at the
+//! configured rate, and is dropped if a partition separates its sender and
+//! receiver when it's sent or when it arrives.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use consensus::signer::LocalSigner;
+use consensus::validator::Validator;
+use consensus::ConsensusParams;
+use ctb_core::chain::Blockchain;
+use ctb_core::signature::{self, SignatureScheme};
+use ctb_core::validator::ValidatorRegistration;
+use ctb_core::hashing;
+use ctb_core::units::Amount;
+use ctb_core::{Address, BlockHash, Result};
+
+use crate::clock::Clock;
+use crate::message::NetworkMessage;
+use crate::network::{NetworkConfig, Transport};
+use crate::{Node, NodeConfig};
+
+/// Port every simulated node pretends to listen on
+const SIM_PORT: u16 = 8333;
+
+/// Clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct VirtualClock {
+    millis: AtomicU64,
+}
+
+impl VirtualClock {
+    /// Creates a clock reading `millis` milliseconds since the Unix epoch
+    pub fn new(millis: u64) -> Self {
+        Self { millis: AtomicU64::new(millis) }
+    }
+    
+    /// Moves the clock on by `millis` milliseconds
+    pub fn advance(&self, millis: u64) {
+        self.millis.fetch_add(millis, Ordering::SeqCst);
+    }
+    
+    /// Sets the clock, which must not go back
+    pub fn set(&self, millis: u64) {
+        self.millis.fetch_max(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now_millis(&self) -> u64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+}
+
+/// Shape of a simulation
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Number of nodes
+    pub nodes: usize,
+    
+    /// Number of nodes that validate, the first ones, each for a validator of its own
+    pub validators: usize,
+    
+    /// Consensus parameters of every node
+    pub consensus_params: ConsensusParams,
+    
+    /// Milliseconds a message takes between any two nodes, unless set for their link
+    pub latency: u64,
+    
+    /// Milliseconds between ticks of the nodes
+    pub tick_interval: u64,
+    
+    /// Seed of the random drops
+    pub seed: u64,
+    
+    /// Receipt retention of each node by index, see `NodeConfig::receipt_retention`
+    ///
+    /// Nodes past the end of the list keep every receipt.
+    pub receipt_retention: Vec<Option<u64>>,
+    
+    /// Stake of each validator by index, in base units
+    ///
+    /// Validators past the end of the list have the minimum stake.
+    pub stakes: Vec<u64>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            nodes: 4,
+            validators: 4,
+            consensus_params: ConsensusParams::default(),
+            latency: 50,
+            tick_interval: 1000,
+            seed: 0,
+            receipt_retention: Vec::new(),
+            stakes: Vec::new(),
+        }
+    }
+}
+
+/// Counts of the messages a simulation's network carried
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    /// Messages sent
+    pub sent: u64,
+    
+    /// Messages delivered
+    pub delivered: u64,
+    
+    /// Messages dropped at random or by a partition
+    pub dropped: u64,
+}
+
+/// Gets the node ID of the simulation's node at an index
+pub fn node_id(index: usize) -> String {
+    format!("node-{}", index)
+}
+
+/// Gets the address of the validator the simulation's node at an index validates for
+pub fn validator_address(index: usize) -> String {
+    format!("validator-{}", index)
+}
+
+/// Gets the secret consensus key of the validator the simulation's node at an index validates for
+pub fn validator_key(index: usize) -> Vec<u8> {
+    hashing::blake3(validator_address(index).as_bytes()).to_vec()
+}
+
+/// Gets the address the simulation's node at an index pretends to listen on
+pub fn node_address(index: usize) -> SocketAddr {
+    let index = index as u32 + 1;
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::from(0x0a00_0000 | index)), SIM_PORT)
+}
+
+/// A message on its way
+#[derive(Debug)]
+struct Envelope {
+    from: usize,
+    to: usize,
+    frame: Vec<u8>,
+}
+
+/// The in-memory network between a simulation's nodes
+#[derive(Debug)]
+struct SimNetwork {
+    /// Messages on their way, by delivery time and then order sent
+    in_flight: BTreeMap<(u64, u64), Envelope>,
+    next_sequence: u64,
+    
+    /// Node indexes by node ID
+    indexes: HashMap<String, usize>,
+    
+    latency: u64,
+    link_latencies: HashMap<(usize, usize), u64>,
+    
+    /// Side of the partition each node is on, while partitioned
+    sides: Option<Vec<usize>>,
+    
+    drop_rate: f64,
+    rng: StdRng,
+    stats: NetworkStats,
+}
+
+impl SimNetwork {
+    fn connected(&self, from: usize, to: usize) -> bool {
+        self.sides.as_ref().is_none_or(|sides| sides[from] == sides[to])
+    }
+    
+    fn send(&mut self, from: usize, peer_id: &str, frame: Vec<u8>, now: u64) {
+        self.stats.sent += 1;
+        let Some(&to) = self.indexes.get(peer_id) else {
+            self.stats.dropped += 1;
+            return;
+        };
+        if !self.connected(from, to) || (self.drop_rate > 0.0 && self.rng.gen_bool(self.drop_rate)) {
+            self.stats.dropped += 1;
+            return;
+        }
+        
+        let latency = self.link_latencies.get(&(from, to)).copied().unwrap_or(self.latency);
+        self.in_flight.insert((now + latency, self.next_sequence), Envelope { from, to, frame });
+        self.next_sequence += 1;
+    }
+}
+
+/// A node's connection to the simulated network
+struct SimTransport {
+    index: usize,
+    network: Arc<Mutex<SimNetwork>>,
+    clock: Arc<VirtualClock>,
+}
+
+impl Transport for SimTransport {
+    fn send(&self, peer_id: &str, frame: Vec<u8>) {
+        let now = self.clock.now_millis();
+        self.network.lock().unwrap().send(self.index, peer_id, frame, now);
+    }
+}
+
+/// Nodes on a simulated network, run step by step
+pub struct Simulation {
+    nodes: Vec<Node>,
+    network: Arc<Mutex<SimNetwork>>,
+    clock: Arc<VirtualClock>,
+    tick_interval: u64,
+    
+    /// Time of the next tick, in milliseconds
+    next_tick: u64,
+}
+
+impl Simulation {
+    /// Creates the nodes, every one connected to every other, at the genesis block's time
+    ///
+    /// Every validator has its stake in the genesis state, see `SimConfig::stakes`.
+    pub fn new(config: SimConfig) -> Result<Self> {
+        let genesis = ctb_core::genesis::create_genesis_block()?;
+        let clock = Arc::new(VirtualClock::new(genesis.header().timestamp * 1000));
+        let network = Arc::new(Mutex::new(SimNetwork {
+            in_flight: BTreeMap::new(),
+            next_sequence: 0,
+            indexes: (0..config.nodes).map(|index| (node_id(index), index)).collect(),
+            latency: config.latency,
+            link_latencies: HashMap::new(),
+            sides: None,
+            drop_rate: 0.0,
+            rng: StdRng::seed_from_u64(config.seed),
+            stats: NetworkStats::default(),
+        }));
+        
+        let mut nodes = Vec::with_capacity(config.nodes);
+        for index in 0..config.nodes {
+            let blockchain = Blockchain::new(genesis.clone())?;
+            {
+                let state = blockchain.get_state();
+                let mut state = state.lock().unwrap();
+                for validator in 0..config.validators {
+                    let address = Address::new(validator_address(validator))?;
+                    let registration = ValidatorRegistration {
+                        moniker: address.to_string(),
+                        website: String::new(),
+                        commission_rate: 0,
+                        consensus_key: signature::address_of(SignatureScheme::Ed25519, &validator_key(validator))
+                            .map_err(|e| ctb_core::BlockchainError::StateError(e.to_string()))?,
+                        payout_address: None,
+                    };
+                    state.register_validator(&address, registration, 0)?;
+                    let stake = config.stakes.get(validator).copied().unwrap_or(config.consensus_params.min_stake);
+                    state.update_validator_stake(address, Amount::from_base_units(stake));
+                }
+            }
+            
+            let validating = index < config.validators;
+            let node_config = NodeConfig {
+                node_id: node_id(index),
+                consensus_params: config.consensus_params.clone(),
+                is_validator: validating,
+                validator_address: validating.then(|| validator_address(index)),
+                receipt_retention: config.receipt_retention.get(index).copied().flatten(),
+                network_config: NetworkConfig {
+                    external_addr: Some(node_address(index)),
+                    ..NetworkConfig::default()
+                },
+                ..NodeConfig::default()
+            };
+            let mut node = Node::new(node_config, blockchain);
+            node.set_clock(clock.clone());
+            node.set_transport(Arc::new(SimTransport { index, network: network.clone(), clock: clock.clone() }));
+            if validating {
+                let signer = LocalSigner::new(SignatureScheme::Ed25519, validator_key(index))
+                    .map_err(|e| ctb_core::BlockchainError::StateError(e.to_string()))?;
+                node.set_signer(Arc::new(signer));
+            }
+            
+            // What `Node::start` does, short of networking and serving
+            node.consensus.lock().unwrap().initialize()?;
+            let validators = node.blockchain.lock().unwrap().get_state().lock().unwrap().get_validators()
+                .into_iter()
+                .map(|(info, stake)| Validator::from_registry(info, stake))
+                .collect();
+            node.pos.lock().unwrap().update_validator_set(validators);
+            node.finality.lock().unwrap().initialize_with_genesis(&genesis)?;
+            nodes.push(node);
+        }
+        
+        let next_tick = clock.now_millis() + config.tick_interval;
+        let simulation = Self { nodes, network, clock, tick_interval: config.tick_interval, next_tick };
+        for index in 0..simulation.nodes.len() {
+            for peer_index in (0..simulation.nodes.len()).filter(|peer_index| *peer_index != index) {
+                simulation.connect(index, peer_index);
+            }
+        }
+        Ok(simulation)
+    }
+    
+    /// Connects a node to a peer, dialed by whichever has the smaller index, unless it's connected
+    ///
+    /// The peer's handshake gives its current height and tip.
+    fn connect(&self, index: usize, peer_index: usize) {
+        let peer = &self.nodes[peer_index];
+        let handshake = {
+            let blockchain = peer.blockchain.lock().unwrap();
+            let height = blockchain.get_latest_height();
+            let best_hash = blockchain.get_latest_block().and_then(|block| block.hash().ok()).unwrap_or_default();
+            peer.network.lock().unwrap().handshake(height, best_hash)
+        };
+        let network = self.nodes[index].network.lock().unwrap();
+        let address = node_address(peer_index);
+        if network.open_connection(address, index < peer_index) {
+            network.handshake_sent(address);
+            network.handshake_received(address, None, &handshake);
+        }
+    }
+    
+    /// Gets the nodes, by index
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+    
+    /// Gets the node at an index
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+    
+    /// Gets the clock the nodes read
+    pub fn clock(&self) -> &Arc<VirtualClock> {
+        &self.clock
+    }
+    
+    /// Gets the current virtual time, in milliseconds
+    pub fn now(&self) -> u64 {
+        self.clock.now_millis()
+    }
+    
+    /// Delivers the next message due before the next tick, or else ticks every node
+    pub fn step(&mut self) {
+        let next = {
+            let mut network = self.network.lock().unwrap();
+            match network.in_flight.first_key_value() {
+                Some((&(deliver_at, _), _)) if deliver_at <= self.next_tick => {
+                    let (_, envelope) = network.in_flight.pop_first().unwrap();
+                    let connected = network.connected(envelope.from, envelope.to);
+                    if connected {
+                        network.stats.delivered += 1;
+                    } else {
+                        network.stats.dropped += 1;
+                    }
+                    Some((deliver_at, connected.then_some(envelope)))
+                }
+                _ => None,
+            }
+        };
+        
+        match next {
+            Some((deliver_at, envelope)) => {
+                self.clock.set(deliver_at);
+                let Some(envelope) = envelope else {
+                    return;
+                };
+                match NetworkMessage::decode(&envelope.frame) {
+                    Ok(message) => self.nodes[envelope.to].handle_message(&node_id(envelope.from), message),
+                    Err(e) => eprintln!("Undecodable message from {}: {}", node_id(envelope.from), e),
+                }
+            }
+            None => {
+                self.clock.set(self.next_tick);
+                self.next_tick += self.tick_interval;
+                for node in &self.nodes {
+                    node.tick();
+                }
+            }
+        }
+    }
+    
+    /// Runs the simulation for `millis` milliseconds of virtual time
+    pub fn run_for(&mut self, millis: u64) {
+        let until = self.now() + millis;
+        while self.now() < until {
+            self.step();
+        }
+    }
+    
+    /// Runs the simulation until `done` holds, for at most `millis` milliseconds, returning whether it held
+    pub fn run_until(&mut self, millis: u64, mut done: impl FnMut(&Self) -> bool) -> bool {
+        let until = self.now() + millis;
+        while !done(self) {
+            if self.now() >= until {
+                return false;
+            }
+            self.step();
+        }
+        true
+    }
+    
+    /// Splits the nodes into sides that can't reach each other
+    ///
+    /// Each group of node indexes is a side; nodes in no group are a side
+    /// each. Messages already on their way across are dropped when they
+    /// arrive.
+    pub fn partition(&self, groups: &[&[usize]]) {
+        let mut sides: Vec<usize> = (groups.len()..groups.len() + self.nodes.len()).collect();
+        for (side, group) in groups.iter().enumerate() {
+            for &index in group.iter() {
+                sides[index] = side;
+            }
+        }
+        self.network.lock().unwrap().sides = Some(sides);
+    }
+    
+    /// Ends any partition, reconnecting nodes whose connections to each other were closed
+    pub fn heal(&self) {
+        self.network.lock().unwrap().sides = None;
+        for index in 0..self.nodes.len() {
+            for peer_index in (0..self.nodes.len()).filter(|peer_index| *peer_index != index) {
+                self.connect(index, peer_index);
+            }
+        }
+    }
+    
+    /// Opens a connection at a node with an address, as if dialed or accepted, returning whether there was a slot
+    ///
+    /// No handshake arrives over it unless the address is a node's and a
+    /// partition heals.
+    pub fn open_connection(&self, index: usize, address: SocketAddr, outbound: bool) -> bool {
+        self.nodes[index].network.lock().unwrap().open_connection(address, outbound)
+    }
+    
+    /// Sets the latency of the link between two nodes, both ways, in milliseconds
+    pub fn set_latency(&self, a: usize, b: usize, millis: u64) {
+        let mut network = self.network.lock().unwrap();
+        network.link_latencies.insert((a, b), millis);
+        network.link_latencies.insert((b, a), millis);
+    }
+    
+    /// Drops each message sent from now on with probability `rate`
+    pub fn set_drop_rate(&self, rate: f64) {
+        self.network.lock().unwrap().drop_rate = rate.clamp(0.0, 1.0);
+    }
+    
+    /// Gets the counts of the messages the network carried
+    pub fn stats(&self) -> NetworkStats {
+        self.network.lock().unwrap().stats
+    }
+    
+    /// Gets the height of every node's chain
+    pub fn heights(&self) -> Vec<u64> {
+        self.nodes.iter().map(Node::get_height).collect()
+    }
+    
+    /// Gets the hash of every node's latest block
+    pub fn tips(&self) -> Vec<BlockHash> {
+        self.nodes
+            .iter()
+            .map(|node| {
+                let blockchain = node.blockchain.lock().unwrap();
+                blockchain.get_latest_block().and_then(|block| block.hash().ok()).unwrap_or_default()
+            })
+            .collect()
+    }
+    
+    /// Checks whether every node has the same latest block
+    pub fn converged(&self) -> bool {
+        let tips = self.tips();
+        tips.windows(2).all(|pair| pair[0] == pair[1])
+    }
+}
\ No newline at end of file
@@ -0,0 +1,322 @@
+//! Serving and downloading state snapshots
+//!
+//! A node keeps its latest snapshots (see `ctb_core::state_sync`) in a
+//! `SnapshotServer`, which answers peers' snapshot requests. A new node
+//! chooses the snapshot to sync to by its summary, which must come from a
+//! source it trusts, and hands it to a `SnapshotSync`. That asks one peer for
+//! the manifest and checks it against the root, then spreads the chunk
+//! requests over every peer serving the snapshot, at most
+//! `MAX_CHUNK_REQUESTS_PER_PEER` to each at a time.
+//!
+//! A peer that sends a manifest not matching the root or a chunk not
+//! matching its hash is dropped from the sync and reported, so the node can
+//! penalize it, and whatever it was asked for is requested from another
+//! peer. Requests unanswered after `REQUEST_TIMEOUT` are sent again too.
+//!
+//! Both sides only turn messages into messages; sending and receiving them
+//! is left to the caller.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use ctb_core::state::State;
+use ctb_core::state_sync::{self, Snapshot, SnapshotInfo, SnapshotManifest};
+use ctb_core::{BlockchainError, Bytes};
+
+use crate::message::{NetworkMessage, MAX_SNAPSHOTS};
+
+/// Most snapshots a node serves at once, the oldest being dropped first
+pub const MAX_SERVED_SNAPSHOTS: usize = 2;
+
+/// Most chunk requests a syncing node has outstanding with one peer
+pub const MAX_CHUNK_REQUESTS_PER_PEER: usize = 4;
+
+/// How long a request may go unanswered before it's sent again
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error syncing a snapshot
+#[derive(Debug, Error)]
+pub enum SnapshotSyncError {
+    #[error("Peer {peer} sent {reason}")]
+    Misbehaved { peer: String, reason: String },
+    
+    #[error("Snapshot is incomplete: {received} of {total} chunks received")]
+    Incomplete { received: usize, total: usize },
+    
+    #[error("Chain error: {0}")]
+    Chain(#[from] BlockchainError),
+}
+
+/// Result type for snapshot sync
+pub type Result<T> = std::result::Result<T, SnapshotSyncError>;
+
+/// Snapshots a node serves to its peers
+#[derive(Debug, Default)]
+pub struct SnapshotServer {
+    /// Served snapshots, oldest first
+    snapshots: Mutex<VecDeque<Arc<Snapshot>>>,
+}
+
+impl SnapshotServer {
+    /// Creates a server without snapshots
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Starts serving a snapshot, in place of any at the same height
+    pub fn add(&self, snapshot: Snapshot) -> SnapshotInfo {
+        let info = snapshot.info();
+        let mut snapshots = self.snapshots.lock().unwrap();
+        snapshots.retain(|served| served.info().height != info.height);
+        snapshots.push_back(Arc::new(snapshot));
+        while snapshots.len() > MAX_SERVED_SNAPSHOTS.min(MAX_SNAPSHOTS) {
+            snapshots.pop_front();
+        }
+        info
+    }
+    
+    /// Gets the summaries of the served snapshots, oldest first
+    pub fn snapshots(&self) -> Vec<SnapshotInfo> {
+        self.snapshots.lock().unwrap().iter().map(|snapshot| snapshot.info()).collect()
+    }
+    
+    /// Answers a snapshot request
+    ///
+    /// Returns `None` for other messages and for requests about snapshots
+    /// that aren't served.
+    pub fn handle(&self, message: &NetworkMessage) -> Option<NetworkMessage> {
+        match message {
+            NetworkMessage::GetSnapshots => Some(NetworkMessage::Snapshots(self.snapshots())),
+            NetworkMessage::GetSnapshotManifest(height) => {
+                let snapshot = self.get(*height)?;
+                Some(NetworkMessage::SnapshotManifest(snapshot.manifest().clone()))
+            }
+            NetworkMessage::GetSnapshotChunk { height, index } => {
+                let snapshot = self.get(*height)?;
+                let data = Bytes(snapshot.chunk(*index)?.to_vec());
+                Some(NetworkMessage::SnapshotChunk { height: *height, index: *index, data })
+            }
+            _ => None,
+        }
+    }
+    
+    fn get(&self, height: u64) -> Option<Arc<Snapshot>> {
+        let snapshots = self.snapshots.lock().unwrap();
+        snapshots.iter().find(|snapshot| snapshot.info().height == height).cloned()
+    }
+}
+
+/// Something a syncing node asked a peer for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Request {
+    Manifest,
+    Chunk(u32),
+}
+
+/// Downloads a snapshot from the peers serving it
+#[derive(Debug)]
+pub struct SnapshotSync {
+    /// The snapshot being downloaded
+    target: SnapshotInfo,
+    
+    /// Peers serving the snapshot, in the order requests go round them
+    peers: Vec<String>,
+    
+    /// Index in `peers` of the next peer to ask
+    next_peer: usize,
+    
+    /// The snapshot's manifest, once received
+    manifest: Option<SnapshotManifest>,
+    
+    /// Outstanding requests, with the peer asked and when
+    requests: HashMap<Request, (String, Instant)>,
+    
+    /// Chunks received so far, by index
+    chunks: Vec<Option<Vec<u8>>>,
+    
+    /// Number of chunks received
+    received: usize,
+}
+
+impl SnapshotSync {
+    /// Starts syncing to a snapshot, whose summary must be trusted
+    pub fn new(target: SnapshotInfo) -> Self {
+        Self {
+            target,
+            peers: Vec::new(),
+            next_peer: 0,
+            manifest: None,
+            requests: HashMap::new(),
+            chunks: Vec::new(),
+            received: 0,
+        }
+    }
+    
+    /// Gets the summary of the snapshot being downloaded
+    pub fn target(&self) -> &SnapshotInfo {
+        &self.target
+    }
+    
+    /// Adds a peer if it serves the snapshot, given the snapshots it offered
+    ///
+    /// Returns whether the peer serves it.
+    pub fn add_peer(&mut self, peer: &str, offered: &[SnapshotInfo]) -> bool {
+        if !offered.contains(&self.target) {
+            return false;
+        }
+        if !self.peers.iter().any(|known| known == peer) {
+            self.peers.push(peer.to_string());
+        }
+        true
+    }
+    
+    /// Stops asking a peer, sending its outstanding requests to others
+    pub fn remove_peer(&mut self, peer: &str) {
+        self.peers.retain(|known| known != peer);
+        self.requests.retain(|_, (asked, _)| asked != peer);
+        if self.next_peer >= self.peers.len() {
+            self.next_peer = 0;
+        }
+    }
+    
+    /// Gets the peers serving the snapshot
+    pub fn peers(&self) -> &[String] {
+        &self.peers
+    }
+    
+    /// Gets the requests to send now, each with the peer to send it to
+    ///
+    /// Requests that timed out are sent again, possibly to other peers.
+    pub fn poll(&mut self, now: Instant) -> Vec<(String, NetworkMessage)> {
+        self.requests.retain(|_, (_, sent)| now.saturating_duration_since(*sent) < REQUEST_TIMEOUT);
+        
+        let mut load: HashMap<String, usize> = HashMap::new();
+        for (peer, _) in self.requests.values() {
+            *load.entry(peer.clone()).or_default() += 1;
+        }
+        
+        let mut requests = Vec::new();
+        let wanted: Vec<Request> = match &self.manifest {
+            None => vec![Request::Manifest],
+            Some(_) => (0..self.target.chunk_count)
+                .filter(|&index| self.chunks[index as usize].is_none())
+                .map(Request::Chunk)
+                .collect(),
+        };
+        for request in wanted {
+            if self.requests.contains_key(&request) {
+                continue;
+            }
+            let Some(peer) = self.pick_peer(&load) else {
+                break;
+            };
+            
+            *load.entry(peer.clone()).or_default() += 1;
+            self.requests.insert(request, (peer.clone(), now));
+            let height = self.target.height;
+            let message = match request {
+                Request::Manifest => NetworkMessage::GetSnapshotManifest(height),
+                Request::Chunk(index) => NetworkMessage::GetSnapshotChunk { height, index },
+            };
+            requests.push((peer, message));
+        }
+        requests
+    }
+    
+    /// Handles a message from a peer
+    ///
+    /// Manifests and chunks that weren't asked of the peer are ignored. One
+    /// that doesn't match the snapshot gets the peer removed from the sync
+    /// and returns `SnapshotSyncError::Misbehaved`.
+    pub fn handle(&mut self, peer: &str, message: &NetworkMessage) -> Result<()> {
+        match message {
+            NetworkMessage::Snapshots(offered) => {
+                self.add_peer(peer, offered);
+            }
+            NetworkMessage::SnapshotManifest(manifest) => {
+                if manifest.height != self.target.height || !self.take_request(Request::Manifest, peer) {
+                    return Ok(());
+                }
+                if !manifest.matches(&self.target) {
+                    return Err(self.misbehaved(peer, "a manifest that doesn't match the snapshot root".to_string()));
+                }
+                
+                self.chunks = vec![None; manifest.chunk_hashes.len()];
+                self.manifest = Some(manifest.clone());
+            }
+            NetworkMessage::SnapshotChunk { height, index, data } => {
+                if *height != self.target.height || !self.take_request(Request::Chunk(*index), peer) {
+                    return Ok(());
+                }
+                let Some(manifest) = &self.manifest else {
+                    return Ok(());
+                };
+                if state_sync::chunk_hash(data) != manifest.chunk_hashes[*index as usize] {
+                    return Err(self.misbehaved(peer, format!("chunk {} not matching its hash", index)));
+                }
+                
+                let chunk = &mut self.chunks[*index as usize];
+                if chunk.is_none() {
+                    self.received += 1;
+                }
+                *chunk = Some(data.0.clone());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    
+    /// Gets the number of chunks received and the number in the snapshot
+    pub fn progress(&self) -> (usize, usize) {
+        (self.received, self.target.chunk_count as usize)
+    }
+    
+    /// Checks whether every chunk has been received
+    pub fn is_complete(&self) -> bool {
+        self.manifest.is_some() && self.received == self.chunks.len()
+    }
+    
+    /// Rebuilds the state from the downloaded chunks
+    pub fn finish(self) -> Result<State> {
+        let (received, total) = self.progress();
+        match self.manifest {
+            Some(manifest) if received == total => {
+                let chunks: Vec<Vec<u8>> = self.chunks.into_iter().flatten().collect();
+                Ok(state_sync::restore_state(&manifest, &chunks)?)
+            }
+            _ => Err(SnapshotSyncError::Incomplete { received, total }),
+        }
+    }
+    
+    /// Removes the outstanding request if it was sent to the peer, returning whether it was
+    fn take_request(&mut self, request: Request, peer: &str) -> bool {
+        match self.requests.get(&request) {
+            Some((asked, _)) if asked == peer => {
+                self.requests.remove(&request);
+                true
+            }
+            _ => false,
+        }
+    }
+    
+    /// Chooses the next peer, going round them, that can take another request
+    fn pick_peer(&mut self, load: &HashMap<String, usize>) -> Option<String> {
+        for _ in 0..self.peers.len() {
+            let peer = &self.peers[self.next_peer];
+            self.next_peer = (self.next_peer + 1) % self.peers.len();
+            if load.get(peer).copied().unwrap_or(0) < MAX_CHUNK_REQUESTS_PER_PEER {
+                return Some(peer.clone());
+            }
+        }
+        None
+    }
+    
+    /// Removes a peer that sent something invalid and builds the error reporting it
+    fn misbehaved(&mut self, peer: &str, reason: String) -> SnapshotSyncError {
+        self.remove_peer(peer);
+        SnapshotSyncError::Misbehaved { peer: peer.to_string(), reason }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,304 @@
+//! Live subscriptions to chain events for WebSocket clients
+//!
+//! Each WebSocket connection registers here and gets a channel of
+//! notifications; the socket handler forwards whatever arrives on it and
+//! passes the client's `subscribe` and `unsubscribe` requests to
+//! `handle_request`. Notifications are produced as the blockchain adds and
+//! rolls back blocks, which the manager observes as a `ChainListener`.
+//!
+//! The `logs` subscription takes the same `LogFilter` as `get_logs` and
+//! delivers each matching log with the hash and height of its block and the
+//! ID of its transaction. When a block is rolled back its logs are
+//! delivered again with `removed: true`.
+//!
+//! The `addressActivity` subscription takes an optional list of addresses
+//! and delivers the `AddressActivity` events of the node's watched
+//! addresses (see `watch`), only those of the listed addresses if given.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use ctb_core::block::Block;
+use ctb_core::chain::ChainListener;
+use ctb_core::receipt::{IndexedLog, LogFilter};
+use ctb_core::BlockHash;
+
+use crate::events::NodeEvent;
+
+/// Most subscriptions a single connection may hold
+pub const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 32;
+
+/// Notifications buffered for a connection before it is dropped as too slow
+pub const NOTIFICATION_BUFFER_SIZE: usize = 1024;
+
+/// Subscription error types
+#[derive(Debug, Error)]
+pub enum SubscriptionError {
+    #[error("Unknown connection {0}")]
+    UnknownConnection(u64),
+    
+    #[error("Unknown subscription {0}")]
+    UnknownSubscription(u64),
+    
+    #[error("Unsupported subscription: {0}")]
+    UnsupportedSubscription(String),
+    
+    #[error("Invalid parameters: {0}")]
+    InvalidParams(String),
+    
+    #[error("A connection may hold at most {0} subscriptions")]
+    TooManySubscriptions(usize),
+}
+
+/// Result type for subscription operations
+pub type Result<T> = std::result::Result<T, SubscriptionError>;
+
+/// A client connection and what it is subscribed to
+#[derive(Debug)]
+struct Connection {
+    /// Channel the connection's notifications are sent on
+    sender: Sender<Value>,
+    
+    /// Log filters by subscription ID
+    logs: HashMap<u64, LogFilter>,
+    
+    /// Addresses of `addressActivity` subscriptions by ID, all watched addresses if `None`
+    address_activity: HashMap<u64, Option<HashSet<String>>>,
+}
+
+impl Connection {
+    fn subscription_count(&self) -> usize {
+        self.logs.len() + self.address_activity.len()
+    }
+}
+
+/// Subscriptions of all connected clients
+#[derive(Debug)]
+pub struct SubscriptionManager {
+    /// Open connections by ID
+    connections: Mutex<HashMap<u64, Connection>>,
+    
+    /// Next connection or subscription ID to hand out
+    next_id: AtomicU64,
+}
+
+impl SubscriptionManager {
+    /// Creates a manager without connections
+    pub fn new() -> Self {
+        Self {
+            connections: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+    
+    /// Registers a new connection, returning its ID and the channel its notifications arrive on
+    pub fn connect(&self) -> (u64, Receiver<Value>) {
+        let (sender, receiver) = mpsc::channel(NOTIFICATION_BUFFER_SIZE);
+        let id = self.next_id();
+        
+        let connection = Connection { sender, logs: HashMap::new(), address_activity: HashMap::new() };
+        self.connections.lock().unwrap().insert(id, connection);
+        (id, receiver)
+    }
+    
+    /// Removes a connection along with its subscriptions
+    pub fn disconnect(&self, connection: u64) {
+        self.connections.lock().unwrap().remove(&connection);
+    }
+    
+    /// Subscribes a connection to logs matching a filter, returning the subscription ID
+    pub fn subscribe_logs(&self, connection: u64, filter: LogFilter) -> Result<u64> {
+        let mut connections = self.connections.lock().unwrap();
+        let connection = connections
+            .get_mut(&connection)
+            .ok_or(SubscriptionError::UnknownConnection(connection))?;
+        
+        if connection.subscription_count() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+            return Err(SubscriptionError::TooManySubscriptions(MAX_SUBSCRIPTIONS_PER_CONNECTION));
+        }
+        
+        let id = self.next_id();
+        connection.logs.insert(id, filter);
+        Ok(id)
+    }
+    
+    /// Subscribes a connection to the activity of watched addresses, only `addresses` if given
+    pub fn subscribe_address_activity(&self, connection: u64, addresses: Option<HashSet<String>>) -> Result<u64> {
+        let mut connections = self.connections.lock().unwrap();
+        let connection = connections
+            .get_mut(&connection)
+            .ok_or(SubscriptionError::UnknownConnection(connection))?;
+        
+        if connection.subscription_count() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+            return Err(SubscriptionError::TooManySubscriptions(MAX_SUBSCRIPTIONS_PER_CONNECTION));
+        }
+        
+        let id = self.next_id();
+        connection.address_activity.insert(id, addresses);
+        Ok(id)
+    }
+    
+    /// Cancels one of a connection's subscriptions
+    pub fn unsubscribe(&self, connection: u64, subscription: u64) -> Result<()> {
+        let mut connections = self.connections.lock().unwrap();
+        let connection = connections
+            .get_mut(&connection)
+            .ok_or(SubscriptionError::UnknownConnection(connection))?;
+        
+        let removed = connection.logs.remove(&subscription).is_some()
+            || connection.address_activity.remove(&subscription).is_some();
+        if removed {
+            Ok(())
+        } else {
+            Err(SubscriptionError::UnknownSubscription(subscription))
+        }
+    }
+    
+    /// Handles a JSON-RPC request received on a connection, returning the response to send back
+    ///
+    /// Supports `subscribe` with params `["logs", filter]` or
+    /// `["addressActivity", addresses]` and `unsubscribe` with params
+    /// `[subscription_id]`.
+    pub fn handle_request(&self, connection: u64, request: &Value) -> Value {
+        let params = request.get("params").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+        
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some("subscribe") => self.subscribe(connection, params).map(Value::from),
+            Some("unsubscribe") => match params.first().and_then(Value::as_u64) {
+                Some(subscription) => self.unsubscribe(connection, subscription).map(|()| Value::Bool(true)),
+                None => Err(SubscriptionError::InvalidParams("expected a subscription ID".to_string())),
+            },
+            method => Err(SubscriptionError::UnsupportedSubscription(format!(
+                "unknown method {}",
+                method.unwrap_or("(none)")
+            ))),
+        };
+        
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => json!({ "jsonrpc": "2.0", "id": id, "error": { "message": e.to_string() } }),
+        }
+    }
+    
+    /// Subscribes a connection from `subscribe` request parameters
+    fn subscribe(&self, connection: u64, params: &[Value]) -> Result<u64> {
+        match params.first().and_then(Value::as_str) {
+            Some("logs") => {
+                let filter = match params.get(1) {
+                    Some(filter) => serde_json::from_value(filter.clone())
+                        .map_err(|e| SubscriptionError::InvalidParams(e.to_string()))?,
+                    None => LogFilter::default(),
+                };
+                self.subscribe_logs(connection, filter)
+            }
+            Some("addressActivity") => {
+                let addresses = match params.get(1) {
+                    None | Some(Value::Null) => None,
+                    Some(addresses) => Some(
+                        serde_json::from_value(addresses.clone()).map_err(|e| SubscriptionError::InvalidParams(e.to_string()))?,
+                    ),
+                };
+                self.subscribe_address_activity(connection, addresses)
+            }
+            Some(kind) => Err(SubscriptionError::UnsupportedSubscription(kind.to_string())),
+            None => Err(SubscriptionError::InvalidParams("expected a subscription kind".to_string())),
+        }
+    }
+    
+    /// Sends every log subscriber the logs of a block that match its filter
+    ///
+    /// A connection whose channel is full or closed is dropped, so a client
+    /// that stops reading can't make the node buffer without bound.
+    fn notify_logs(&self, block_hash: &BlockHash, logs: &[IndexedLog], removed: bool) {
+        if logs.is_empty() {
+            return;
+        }
+        
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|_, connection| {
+            for (subscription, filter) in &connection.logs {
+                for entry in logs.iter().filter(|entry| filter.matches_indexed(entry)) {
+                    let notification = log_notification(*subscription, block_hash, entry, removed);
+                    if connection.sender.try_send(notification).is_err() {
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+    }
+    
+    /// Sends an `AddressActivity` event to the subscribers of its address
+    ///
+    /// Connections that can't keep up are dropped, as with logs.
+    pub fn notify_address_activity(&self, event: &NodeEvent) {
+        let NodeEvent::AddressActivity { address, .. } = event else {
+            return;
+        };
+        let result = serde_json::to_value(event).unwrap_or(Value::Null);
+        
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|_, connection| {
+            for (subscription, addresses) in &connection.address_activity {
+                if addresses.as_ref().is_some_and(|addresses| !addresses.contains(address)) {
+                    continue;
+                }
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "subscription",
+                    "params": { "subscription": subscription, "result": result },
+                });
+                if connection.sender.try_send(notification).is_err() {
+                    return false;
+                }
+            }
+            true
+        });
+    }
+    
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl Default for SubscriptionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChainListener for SubscriptionManager {
+    fn block_added(&self, _block: &Block, block_hash: &BlockHash, logs: &[IndexedLog]) {
+        self.notify_logs(block_hash, logs, false);
+    }
+    
+    fn block_removed(&self, _block: &Block, block_hash: &BlockHash, logs: &[IndexedLog]) {
+        self.notify_logs(block_hash, logs, true);
+    }
+}
+
+/// Builds the notification delivering a log to a subscription
+fn log_notification(subscription: u64, block_hash: &BlockHash, entry: &IndexedLog, removed: bool) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "subscription",
+        "params": {
+            "subscription": subscription,
+            "result": {
+                "block_hash": block_hash,
+                "block_height": entry.block_height,
+                "tx_id": entry.tx_id,
+                "address": entry.log.address,
+                "topics": entry.log.topics.iter().map(|topic| format!("0x{}", hex::encode(topic))).collect::<Vec<_>>(),
+                "data": format!("0x{}", hex::encode(&entry.log.data)),
+                "removed": removed,
+            },
+        },
+    })
+}
\ No newline at end of file
@@ -0,0 +1,782 @@
+//! Generators and checks for testing network frames
+//!
+//! Built with the `testutil` feature, on top of `ctb_core::testutil`. `message`
+//! makes any `NetworkMessage`, within the caps `decode` enforces, from a
+//! `ctb_core::testutil::Generator`, and `assert_roundtrip_frame` checks that a
+//! message's frame decodes and encodes back to the same bytes. `fixtures`
+//! holds the golden hashes of a few frames.
+//!
+//! `check_decoding` feeds generated frames from `fuzz_frame`, then the
+//! frames of `decode_regressions`, to the frame decoder, checking that
+//! nothing a peer sends makes it or the sync it starts panic. The crate's
+//! `encoding` test runs `check_encodings` over generated messages and
+//! `check_fixtures`, and its `decoding` test runs `check_decoding`.
+//!
+//! `MockRemoteSigner` serves the remote signing protocol of `signer` with
+//! a delay, and can be taken down, for testing nodes signing remotely.
+//!
+//! `check_devnet` runs a development node (see `dev`) twice, deploying a
+//! contract and calling it only through JSON-RPC requests each time, and
+//! checks both runs make the same blocks in well under a second. The
+//! crate's `devnet` test runs it.
+//!
+//! `check_partition_recovery` splits a simulated network (see `sim`) 2/2,
+//! lets both halves build their own chains and heals it, checking every
+//! node ends up on the heavier half's chain; `check_finality_ban` checks a
+//! peer offering a chain that reverts a finalized checkpoint is banned.
+//! The crate's `partition` test runs both.
+//!
+//! `check_forged_announcements`, `check_announcement_flood` and
+//! `check_dial_preference` check the address book of `address_book`:
+//! announcements not signed by the node they name are dropped, a peer
+//! flooding a node with addresses only gets its hourly share of them in,
+//! and addresses confirmed by a handshake are dialed first. The crate's
+//! `address_book` test runs them.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde_json::{json, Value};
+
+use consensus::finality::FinalityVote;
+use consensus::signer::BlockSigner;
+use consensus::ConsensusParams;
+
+use ctb_core::block_filter::BlockFilter;
+use ctb_core::state_sync::{SnapshotInfo, SnapshotManifest, MAX_SNAPSHOT_CHUNKS};
+use ctb_core::testutil::{self, Decoder, Fixture, Generator};
+use ctb_core::transaction::Transaction;
+use ctb_core::{BlockHash, Bytes, TxHash};
+
+use smartcontracts::{abi, ABIParameter, DeployPayload, FunctionABI};
+
+use crate::address_book::{
+    AddressBook, AddressError, NodeIdentity, ANNOUNCEMENT_TTL, MAX_CLOCK_DRIFT, MAX_NEW_ADDRESSES_PER_HOUR,
+};
+use crate::block_sync::BlockSync;
+use crate::dev::{self, DEV_GENESIS_TIME};
+use crate::events::NodeEvent;
+use crate::message::{
+    AddressAnnouncement, ChainStatus, DisconnectReason, HandshakeData, NetworkMessage, PingData, MAX_ANNOUNCEMENTS,
+    MAX_FILTERS, MAX_HEADERS, MAX_LISTEN_ADDRS, MAX_LOCATOR, MAX_SNAPSHOTS, PROTOCOL_VERSION,
+};
+use crate::network::{HandshakeOutcome, NetworkConfig, NetworkManager};
+use crate::rpc::{RpcConfig, RpcHandler};
+use crate::signer::{SignerService, SigningHistory};
+use crate::clock::Clock;
+use crate::sim::{self, SimConfig, Simulation, VirtualClock};
+use crate::{Node, NodeConfig};
+
+/// Number of kinds of message `message` makes, `Unknown` included
+const MESSAGE_KINDS: u32 = 31;
+
+/// Init code of a contract storing 42 in slot 0, whose `value()` returns slot 0, as does every other call
+const STORED_VALUE_INIT_CODE: [u8; 28] = [
+    0x60, 0x2a, 0x60, 0x00, 0x55, // SSTORE(0, 42)
+    0x60, 0x0b, 0x60, 0x11, 0x60, 0x00, 0x39, // CODECOPY(0, 17, 11), the code below
+    0x60, 0x0b, 0x60, 0x00, 0xf3, // RETURN(0, 11)
+    0x60, 0x00, 0x54, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3, // MSTORE(0, SLOAD(0)), RETURN(0, 32)
+];
+
+/// Gas limit of the transactions `check_devnet` sends
+const DEVNET_GAS_LIMIT: u64 = 1_000_000;
+
+/// Milliseconds `check_partition_recovery` keeps the network split, long
+/// enough for the lighter half to build a chain deeper than syncs used to
+/// look for a fork, but not so deep its blocks can't be rolled back
+const PARTITION_MILLIS: u64 = 1_500_000;
+
+/// Time the clocks of the address book checks start at, in milliseconds
+const ANNOUNCEMENT_CLOCK_MILLIS: u64 = 1_700_000_000_000;
+
+/// Makes a message of any kind
+///
+/// Lists hold up to `max_transactions` items and chunks up to
+/// `max_data_len` bytes, so messages stay within their size caps.
+pub fn message(generator: &mut Generator) -> NetworkMessage {
+    let max_items = generator.config().max_transactions;
+    match generator.rng().gen_range(0..MESSAGE_KINDS) {
+        0 => NetworkMessage::Handshake(HandshakeData {
+            node_id: generator.string(),
+            height: generator.u64(),
+            best_hash: BlockHash(generator.hash()),
+            timestamp: generator.u64(),
+            nonce: generator.u64(),
+            listen_addrs: {
+                let count = generator.rng().gen_range(0..=MAX_LISTEN_ADDRS);
+                (0..count).map(|_| socket_addr(generator)).collect()
+            },
+            observed_addr: generator.rng().gen::<bool>().then(|| socket_addr(generator)),
+        }),
+        1 => NetworkMessage::Ping(PingData { nonce: generator.u64() }),
+        2 => NetworkMessage::Pong(PingData { nonce: generator.u64() }),
+        3 => NetworkMessage::GetPeers,
+        4 => {
+            let count = generator.rng().gen_range(0..=max_items);
+            NetworkMessage::Peers((0..count).map(|_| socket_addr(generator)).collect())
+        }
+        5 => {
+            const REASONS: [DisconnectReason; 6] = [
+                DisconnectReason::Requested,
+                DisconnectReason::TooManyPeers,
+                DisconnectReason::Duplicate,
+                DisconnectReason::SelfConnection,
+                DisconnectReason::Timeout,
+                DisconnectReason::Banned,
+            ];
+            NetworkMessage::Disconnect(REASONS[generator.rng().gen_range(0..REASONS.len())])
+        }
+        6 => NetworkMessage::NewBlock(Arc::new(generator.block())),
+        7 => NetworkMessage::GetBlock(BlockHash(generator.hash())),
+        8 => NetworkMessage::Block(Arc::new(generator.block())),
+        9 => NetworkMessage::GetHeaders(height_range(generator, MAX_HEADERS)),
+        10 => {
+            let count = generator.rng().gen_range(0..=max_items);
+            NetworkMessage::Headers((0..count).map(|_| generator.block().header().clone()).collect())
+        }
+        11 => NetworkMessage::NewTransaction(Arc::new(generator.transaction())),
+        12 => NetworkMessage::GetTransaction(TxHash(generator.hash())),
+        13 => NetworkMessage::Transaction(Arc::new(generator.transaction())),
+        14 => NetworkMessage::CheckpointVote(FinalityVote {
+            height: generator.u64(),
+            block_hash: BlockHash(generator.hash()),
+            validator: generator.string(),
+            signature: Bytes(generator.bytes(64)),
+        }),
+        15 => NetworkMessage::GetSnapshots,
+        16 => {
+            let count = generator.rng().gen_range(0..=MAX_SNAPSHOTS.min(max_items));
+            NetworkMessage::Snapshots((0..count).map(|_| snapshot_info(generator)).collect())
+        }
+        17 => NetworkMessage::GetSnapshotManifest(generator.u64()),
+        18 => {
+            let count = generator.rng().gen_range(1..=max_items.max(1));
+            NetworkMessage::SnapshotManifest(SnapshotManifest {
+                height: generator.u64(),
+                block_hash: BlockHash(generator.hash()),
+                chunk_hashes: (0..count).map(|_| generator.hash()).collect(),
+            })
+        }
+        19 => NetworkMessage::GetSnapshotChunk { height: generator.u64(), index: generator.rng().gen() },
+        20 => {
+            let max_len = generator.config().max_data_len;
+            NetworkMessage::SnapshotChunk {
+                height: generator.u64(),
+                index: generator.rng().gen(),
+                data: Bytes(generator.bytes(max_len)),
+            }
+        }
+        21 => NetworkMessage::GetReceipts(generator.u64()),
+        22 => {
+            let count = generator.rng().gen_range(0..=max_items);
+            NetworkMessage::Receipts { height: generator.u64(), receipts: (0..count).map(|_| generator.receipt()).collect() }
+        }
+        23 => NetworkMessage::GetFilterHeaders(height_range(generator, MAX_HEADERS)),
+        24 => {
+            let count = generator.rng().gen_range(0..=max_items);
+            NetworkMessage::FilterHeaders { start: generator.u64(), headers: (0..count).map(|_| generator.hash()).collect() }
+        }
+        25 => NetworkMessage::GetFilters(height_range(generator, MAX_FILTERS)),
+        26 => {
+            let count = generator.rng().gen_range(0..=max_items);
+            NetworkMessage::Filters { start: generator.u64(), filters: (0..count).map(|_| block_filter(generator)).collect() }
+        }
+        27 => NetworkMessage::Status(ChainStatus {
+            height: generator.u64(),
+            best_hash: BlockHash(generator.hash()),
+            weight: generator.rng().gen(),
+        }),
+        28 => {
+            let count = generator.rng().gen_range(0..=MAX_LOCATOR);
+            NetworkMessage::GetBranchHeaders((0..count).map(|_| (generator.u64(), BlockHash(generator.hash()))).collect())
+        }
+        29 => {
+            let count = generator.rng().gen_range(0..=MAX_ANNOUNCEMENTS.min(max_items));
+            NetworkMessage::Addresses((0..count).map(|_| AddressAnnouncement {
+                node_id: generator.string(),
+                addr: socket_addr(generator),
+                timestamp: generator.u64(),
+                signature: Bytes(generator.bytes(64)),
+            }).collect())
+        }
+        _ => {
+            let max_len = generator.config().max_data_len;
+            NetworkMessage::Unknown {
+                tag: generator.rng().gen_range(0x80..=0xff),
+                payload: Bytes(generator.bytes(max_len)),
+            }
+        }
+    }
+}
+
+/// Checks that a message's frame decodes and encodes back the same, returning the decoded message
+pub fn assert_roundtrip_frame(message: &NetworkMessage) -> NetworkMessage {
+    let frame = message.encode();
+    let decoded = NetworkMessage::decode(&frame)
+        .unwrap_or_else(|e| panic!("Cannot decode a {} frame: {}", message.kind(), e));
+    if decoded.encode() != frame {
+        panic!("{} frame encodes differently after decoding: {}", message.kind(), hex::encode(&frame));
+    }
+    decoded
+}
+
+/// Gets the fixtures of network frames
+pub fn fixtures() -> Vec<Fixture> {
+    let handshake = NetworkMessage::Handshake(HandshakeData {
+        node_id: "GENX_FIXTURE_NODE".to_string(),
+        height: 42,
+        best_hash: BlockHash([0x11; 32]),
+        timestamp: 1_700_000_000,
+        nonce: 0x0123_4567_89ab_cdef,
+        listen_addrs: Vec::new(),
+        observed_addr: None,
+    });
+    let addressed_handshake = NetworkMessage::Handshake(HandshakeData {
+        node_id: "GENX_FIXTURE_NODE".to_string(),
+        height: 42,
+        best_hash: BlockHash([0x11; 32]),
+        timestamp: 1_700_000_000,
+        nonce: 0x0123_4567_89ab_cdef,
+        listen_addrs: vec![
+            SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 30303)),
+            SocketAddr::from((Ipv6Addr::LOCALHOST, 30303)),
+        ],
+        observed_addr: Some(SocketAddr::from((Ipv4Addr::new(10, 0, 0, 2), 30303))),
+    });
+    let peers = NetworkMessage::Peers(vec![
+        SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 30303)),
+        SocketAddr::from((Ipv6Addr::LOCALHOST, 30304)),
+    ]);
+    let vote = NetworkMessage::CheckpointVote(FinalityVote {
+        height: 100,
+        block_hash: BlockHash([0x33; 32]),
+        validator: "GENX_FIXTURE_VALIDATOR".to_string(),
+        signature: Bytes(vec![0x44; 64]),
+    });
+    
+    vec![
+        Fixture {
+            name: "handshake frame",
+            encoding: handshake.encode(),
+            golden_hash: "3e4ed092db0ad4833c2c60366029cc82d935c70567d3f2acc067adbec1c3183c",
+        },
+        Fixture {
+            name: "handshake frame with addresses",
+            encoding: addressed_handshake.encode(),
+            golden_hash: "e5ed3950ef1e914acc00c5f85321c63c40e92cef63589772401e0e7895ee7e55",
+        },
+        Fixture {
+            name: "peers frame",
+            encoding: peers.encode(),
+            golden_hash: "b0d4708fca255bbcfd374d60c91d70916d1d1985c0ddaa888cf9cf0401dd78c0",
+        },
+        Fixture {
+            name: "checkpoint vote frame",
+            encoding: vote.encode(),
+            golden_hash: "e7b38fbca0c98d92a508e1ead3714ac3099d189f30d3746f54f657833ff330c7",
+        },
+    ]
+}
+
+/// Checks every fixture of network frames against its golden hash
+pub fn check_fixtures() {
+    for fixture in fixtures() {
+        testutil::assert_hash_stable(&fixture);
+    }
+}
+
+/// Round-trips the frames of `cases` generated messages
+///
+/// Each case uses its own seed, counting up from `seed`.
+pub fn check_encodings(seed: u64, cases: u64) {
+    for case in seed..seed + cases {
+        assert_roundtrip_frame(&message(&mut Generator::new(case)));
+    }
+}
+
+/// Gets the decoder of network frames
+///
+/// A frame that decodes to a message announcing a height starts a block
+/// sync to it, as it would with the node.
+pub fn decoders() -> Vec<Decoder> {
+    vec![("frame", |frame| {
+        let height = match NetworkMessage::decode(frame) {
+            Ok(NetworkMessage::Handshake(handshake)) => handshake.height,
+            Ok(NetworkMessage::NewBlock(block) | NetworkMessage::Block(block)) => block.header().height,
+            _ => return,
+        };
+        let _ = BlockSync::new().start("peer", height, vec![(0, BlockHash::default())], 0);
+    })]
+}
+
+/// Makes a frame: a generated message's mutated, or random bytes after a supported version
+pub fn fuzz_frame(generator: &mut Generator) -> Vec<u8> {
+    if generator.rng().gen_ratio(1, 4) {
+        let max_len = generator.config().max_data_len;
+        return [vec![PROTOCOL_VERSION], generator.bytes(max_len)].concat();
+    }
+    let frame = message(generator).encode();
+    generator.mutate(frame)
+}
+
+/// Gets the frames that made the decoder or what follows it fail, checked on every run
+pub fn decode_regressions() -> Vec<(&'static str, Vec<u8>)> {
+    // Syncing to it used to overflow the end of the headers requested,
+    // which without overflow checks wrapped round to a range ending at 0
+    let mut block = Generator::new(0).block();
+    block.header_mut().height = u64::MAX;
+    let wrapped = Range { start: 90, end: 0 };
+    
+    vec![
+        ("block announced at the largest height", NetworkMessage::NewBlock(Arc::new(block)).encode()),
+        ("headers range ending before it starts", NetworkMessage::GetHeaders(wrapped).encode()),
+    ]
+}
+
+/// Feeds `cases` generated frames, then the regression frames, to the frame decoder
+///
+/// Each case uses its own seed, counting up from `seed`.
+pub fn check_decoding(seed: u64, cases: u64) {
+    let frames = (seed..seed + cases).map(|case| fuzz_frame(&mut Generator::new(case)));
+    for frame in frames.chain(decode_regressions().into_iter().map(|(_, frame)| frame)) {
+        for decoder in decoders() {
+            testutil::assert_decodes(decoder, &frame);
+        }
+    }
+}
+
+/// Makes a peer address, IPv4 or IPv6
+fn socket_addr(generator: &mut Generator) -> SocketAddr {
+    let port = generator.rng().gen();
+    if generator.rng().gen() {
+        SocketAddr::from((Ipv4Addr::from(generator.rng().gen::<u32>()), port))
+    } else {
+        SocketAddr::from((Ipv6Addr::from(generator.rng().gen::<u128>()), port))
+    }
+}
+
+/// Makes a range of heights spanning at most `max` of them
+fn height_range(generator: &mut Generator, max: u64) -> Range<u64> {
+    let start = generator.u64();
+    let len = generator.rng().gen_range(0..=max).min(u64::MAX - start);
+    start..start + len
+}
+
+/// Makes a block filter, whose data needn't decode
+fn block_filter(generator: &mut Generator) -> BlockFilter {
+    let max_len = generator.config().max_data_len;
+    BlockFilter {
+        block_hash: BlockHash(generator.hash()),
+        items: generator.rng().gen(),
+        data: Bytes(generator.bytes(max_len)),
+    }
+}
+
+/// Makes the summary of a snapshot
+fn snapshot_info(generator: &mut Generator) -> SnapshotInfo {
+    SnapshotInfo {
+        height: generator.u64(),
+        block_hash: BlockHash(generator.hash()),
+        root: generator.hash(),
+        chunk_count: generator.rng().gen_range(1..=MAX_SNAPSHOT_CHUNKS),
+    }
+}
+
+/// Remote signer for tests, answering as a `SignerService` does after a delay, unless it's down
+pub struct MockRemoteSigner {
+    addr: SocketAddr,
+    latency_ms: Arc<AtomicU64>,
+    down: Arc<AtomicBool>,
+    requests: Arc<AtomicUsize>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl MockRemoteSigner {
+    /// Starts serving on a free loopback port, signing with a signer for clients giving `token` if set
+    pub fn start(signer: Arc<dyn BlockSigner>, token: Option<String>) -> Self {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("bind a loopback port");
+        let mock = Self {
+            addr: listener.local_addr().expect("local address"),
+            latency_ms: Arc::new(AtomicU64::new(0)),
+            down: Arc::new(AtomicBool::new(false)),
+            requests: Arc::new(AtomicUsize::new(0)),
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+        let service = Arc::new(SignerService::new(signer, token, SigningHistory::new()));
+        let (latency_ms, down, requests, stopped) = (mock.latency_ms.clone(), mock.down.clone(), mock.requests.clone(), mock.stopped.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                // Down, connections are closed unanswered
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if down.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let (service, latency_ms, requests) = (service.clone(), latency_ms.clone(), requests.clone());
+                thread::spawn(move || {
+                    let Ok(mut writer) = stream.try_clone() else {
+                        return;
+                    };
+                    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                        requests.fetch_add(1, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(latency_ms.load(Ordering::SeqCst)));
+                        if writeln!(writer, "{}", service.handle_line(&line)).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        mock
+    }
+    
+    /// Gets the address the signer listens on, for `SignerConfig::remote_addr`
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+    
+    /// Delays every answer from now on
+    pub fn set_latency(&self, latency: Duration) {
+        self.latency_ms.store(latency.as_millis() as u64, Ordering::SeqCst);
+    }
+    
+    /// Takes the signer down, closing connections unanswered, or brings it back up
+    pub fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::SeqCst);
+    }
+    
+    /// Gets the number of requests received while up
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockRemoteSigner {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// Runs a development node twice, deploying, calling and checking a contract only through JSON-RPC
+///
+/// Panics if a check fails, the runs make different blocks, or they take
+/// a second or more between them.
+pub fn check_devnet() {
+    let started = Instant::now();
+    let tip = devnet_run();
+    assert_eq!(devnet_run(), tip, "every run makes the same blocks");
+    assert!(started.elapsed() < Duration::from_secs(1), "the runs took {:?}", started.elapsed());
+}
+
+/// Runs a development node through its checks, returning the hash of the latest block
+fn devnet_run() -> Value {
+    let config = NodeConfig {
+        dev_mode: true,
+        data_dir: std::env::temp_dir().join("genx-devnet-check").display().to_string(),
+        rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+        ..NodeConfig::default()
+    };
+    let block_time = config.consensus_params.block_time;
+    let blockchain = dev::genesis(&config.consensus_params).expect("the development chain is valid");
+    let runtime = tokio::runtime::Runtime::new().expect("start a runtime");
+    let mut node = Node::new(config, blockchain);
+    runtime.block_on(node.start()).expect("the development node starts");
+    let rpc = node.rpc_handler();
+    
+    let accounts = devnet_call(&rpc, "dev_accounts", json!([]));
+    let sender = accounts[0]["address"].as_str().expect("accounts have addresses").to_string();
+    let secret_key = hex::decode(accounts[0]["secret_key"].as_str().expect("accounts have keys")).expect("keys are hex");
+    let base_fee = devnet_call(&rpc, "genx_nextBaseFee", json!([])).as_u64().expect("the base fee is a number");
+    
+    // Deployed in a block of its own before the submission returns
+    let selector = abi::function_selector("value", &[]).expect("compute the selector");
+    let value = FunctionABI {
+        name: "value".to_string(),
+        inputs: Vec::new(),
+        outputs: vec![ABIParameter { name: String::new(), param_type: "uint256".to_string() }],
+        constant: true,
+        signature: selector,
+    };
+    let payload = DeployPayload {
+        init_code: STORED_VALUE_INIT_CODE.to_vec(),
+        abi: vec![value],
+        events: Vec::new(),
+        constructor_args: Vec::new(),
+    };
+    let data = payload.to_bytes().expect("the payload encodes");
+    let mut deploy = Transaction::new_contract_deploy(sender.clone(), 0, data, DEVNET_GAS_LIMIT, base_fee).expect("build the deployment");
+    deploy.timestamp = DEV_GENESIS_TIME;
+    deploy.id = deploy.calculate_hash().expect("hash the deployment");
+    deploy.sign(&secret_key).expect("sign the deployment");
+    let id = devnet_call(&rpc, "genx_sendTransaction", json!([deploy]));
+    let record = devnet_call(&rpc, "genx_getTransaction", json!([id]));
+    assert_eq!((&record["block_height"], &record["success"]), (&json!(1), &json!(true)), "{}", record);
+    
+    let returned = devnet_call(&rpc, "genx_call", json!([deploy.contract_address(), format!("0x{}", hex::encode(selector)), "0x", sender]));
+    assert_eq!(returned, json!(format!("0x{:064x}", 42)));
+    
+    // Without automining, blocks are only made when asked for, a block time apart
+    assert_eq!(devnet_call(&rpc, "dev_setAutomine", json!([false])), json!(true));
+    assert_eq!(devnet_call(&rpc, "evm_mine", json!([])), json!("0x0"));
+    let mined = devnet_call(&rpc, "dev_mine", json!([2]));
+    assert_eq!(mined.as_array().map(Vec::len), Some(2));
+    let status = devnet_call(&rpc, "genx_status", json!([]));
+    assert_eq!(status["height"], json!(4));
+    assert_eq!(status["latest_hash"], mined[1]);
+    
+    // Four confirmations are safe to a caller asking for four, not by the default six
+    let deployed = devnet_call(&rpc, "genx_getTransactionStatus", json!([id]));
+    assert_eq!(deployed, json!({"status": "included", "height": 1, "confirmations": 4}));
+    let record = devnet_call(&rpc, "genx_getTransaction", json!([id, 4]));
+    assert_eq!(record["status"], json!({"status": "safe", "height": 1, "confirmations": 4}));
+    for height in 1..=4u64 {
+        let block = devnet_call(&rpc, "genx_getBlockWithReceipts", json!([height]));
+        assert_eq!(block["block"]["header"]["timestamp"], json!(DEV_GENESIS_TIME + height * block_time), "block {}", height);
+    }
+    
+    let recipient = accounts[1]["address"].as_str().expect("accounts have addresses");
+    assert_eq!(devnet_call(&rpc, "dev_setBalance", json!([recipient, 7])), json!(dev::DEV_ACCOUNT_BALANCE));
+    assert_eq!(devnet_call(&rpc, "genx_getBalance", json!([recipient])), json!(7));
+    
+    node.stop();
+    status["latest_hash"].clone()
+}
+
+/// Makes a JSON-RPC request of a development node, returning its result
+fn devnet_call(rpc: &RpcHandler, method: &str, params: Value) -> Value {
+    let response = rpc.handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }));
+    assert!(response.get("error").is_none(), "{} failed: {}", method, response);
+    response["result"].clone()
+}
+
+/// Splits four simulated validators 2/2, the first two holding three times the stake of the others, and heals them
+///
+/// Panics unless both halves build chains of their own, the lighter one
+/// more than 64 blocks long, and once healed every node converges on the
+/// heavier half's chain, announces the switch and keeps making blocks.
+pub fn check_partition_recovery() {
+    let min_stake = ConsensusParams::default().min_stake;
+    let config = SimConfig { stakes: vec![3 * min_stake, 3 * min_stake, min_stake, min_stake], ..SimConfig::default() };
+    let mut sim = Simulation::new(config).expect("the simulation starts");
+    assert!(sim.run_until(60_000, |sim| sim.heights()[0] >= 3 && sim.converged()), "the nodes agree before the split");
+    let split_height = sim.heights()[0];
+    
+    sim.partition(&[&[0, 1], &[2, 3]]);
+    sim.run_for(PARTITION_MILLIS);
+    let (tips, heights) = (sim.tips(), sim.heights());
+    assert!(tips[0] == tips[1] && tips[2] == tips[3] && tips[0] != tips[2], "each half has a chain of its own: {:?}", heights);
+    assert!(heights[2] - split_height > 64, "the lighter half built {} blocks", heights[2] - split_height);
+    let weights: Vec<u128> = sim.nodes().iter().map(|node| node.blockchain.lock().unwrap().chain_weight()).collect();
+    assert!(weights[0] > weights[2], "the first half's chain is heavier: {:?}", weights);
+    let (heavy_height, heavy_tip) = (heights[0], tips[0]);
+    
+    let mut events = sim.node(2).event_bus().subscribe();
+    sim.heal();
+    assert!(sim.run_until(120_000, Simulation::converged), "the nodes converge once healed: {:?}", sim.heights());
+    for node in sim.nodes() {
+        let blockchain = node.blockchain.lock().unwrap();
+        let hash = blockchain.get_block_by_height(heavy_height).and_then(|block| block.hash().ok());
+        assert_eq!(hash, Some(heavy_tip), "every node is on the heavier chain");
+    }
+    
+    let healed = std::iter::from_fn(|| events.try_recv().ok()).find_map(|event| match event {
+        NodeEvent::ChainHealed { peer, common_ancestor, rolled_back, applied, .. } => Some((peer, common_ancestor, rolled_back, applied)),
+        _ => None,
+    });
+    let (peer, common_ancestor, rolled_back, applied) = healed.expect("the lighter half announces switching chains");
+    assert!(peer == sim::node_id(0) || peer == sim::node_id(1), "the branch came from the heavier half, not {}", peer);
+    assert!(common_ancestor >= split_height && common_ancestor < split_height + 5, "the chains fork at {}", common_ancestor);
+    assert_eq!(rolled_back, heights[2] - common_ancestor);
+    assert!(applied >= heavy_height - common_ancestor, "{} blocks applied", applied);
+    assert!(sim.node(2).metrics().heal_count() >= 1 && sim.node(0).metrics().heal_count() == 0);
+    
+    // No node is stuck
+    let height = sim.heights()[0];
+    assert!(sim.run_until(60_000, |sim| sim.converged() && sim.heights()[0] >= height + 3), "the nodes keep making blocks together");
+}
+
+/// Checks a node bans a peer claiming a heavier chain that shares no block with its own from the finalized checkpoint up
+///
+/// Panics unless the peer is banned, refused when it reconnects, and
+/// the others keep making blocks.
+pub fn check_finality_ban() {
+    let mut sim = Simulation::new(SimConfig::default()).expect("the simulation starts");
+    let finalized = sim.run_until(1_000_000, |sim| sim.node(0).get_finalized_height() > 0);
+    assert!(finalized, "a checkpoint is finalized");
+    
+    // A status no chain could back, then no headers for the locator it's sent
+    let peer = sim::node_id(1);
+    let status = ChainStatus { height: sim.heights()[0] + 1, best_hash: BlockHash([0xee; 32]), weight: u128::MAX };
+    let mut events = sim.node(0).event_bus().subscribe();
+    sim.node(0).handle_message(&peer, NetworkMessage::Status(status));
+    sim.node(0).handle_message(&peer, NetworkMessage::Headers(Vec::new()));
+    
+    let network = sim.node(0).network.clone();
+    assert!(network.lock().unwrap().is_banned(&peer), "the peer is banned");
+    assert_eq!(sim.node(0).metrics().peers_banned(), 1);
+    let refused = std::iter::from_fn(|| events.try_recv().ok()).any(|event| matches!(event, NodeEvent::BranchRefused { peer: refused, .. } if refused == peer));
+    assert!(refused, "the node announces refusing the branch");
+    
+    sim.heal();
+    let mut connected = Vec::new();
+    network.lock().unwrap().for_each_peer(|connected_peer| connected.push(connected_peer.node_id.clone()));
+    assert!(!connected.contains(&peer), "the banned peer can't reconnect: {:?}", connected);
+    let height = sim.heights()[0];
+    assert!(sim.run_until(60_000, |sim| sim.heights()[0] >= height + 3), "the node keeps making blocks");
+}
+
+/// Makes the `index`th address of a test network, the same every time
+fn test_addr(index: usize) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::from(0x0a00_0000 + index as u32), 30303))
+}
+
+/// Makes a network manager reading the time from a clock of its own, started at `ANNOUNCEMENT_CLOCK_MILLIS`
+fn clocked_network() -> (NetworkManager, Arc<VirtualClock>) {
+    let clock = Arc::new(VirtualClock::new(ANNOUNCEMENT_CLOCK_MILLIS));
+    let mut network = NetworkManager::new(NetworkConfig::default());
+    network.set_clock(clock.clone());
+    (network, clock)
+}
+
+/// Checks that announcements not signed by the node they name are dropped, and stale ones with them
+///
+/// Panics if an announcement with its address, timestamp, node ID or
+/// signature changed is taken, or one from a node's own key isn't.
+pub fn check_forged_announcements() {
+    let now = ANNOUNCEMENT_CLOCK_MILLIS / 1000;
+    let (honest, forger) = (NodeIdentity::generate(), NodeIdentity::generate());
+    let genuine = honest.announce(test_addr(0), now);
+    let mut garbled = genuine.clone();
+    garbled.signature.0[0] ^= 1;
+    let forged = [
+        ("moved", AddressAnnouncement { addr: test_addr(1), ..genuine.clone() }),
+        ("redated", AddressAnnouncement { timestamp: now - 1, ..genuine.clone() }),
+        ("impersonated", AddressAnnouncement { node_id: honest.node_id().to_string(), ..forger.announce(test_addr(2), now) }),
+        ("unsigned", AddressAnnouncement { signature: Bytes(Vec::new()), ..genuine.clone() }),
+        ("garbled", garbled),
+    ];
+    let mut book = AddressBook::new();
+    for (name, announcement) in forged.iter().cloned() {
+        let refused = book.insert("peer", announcement, now);
+        assert!(matches!(refused, Err(AddressError::Forged { .. })), "the {} announcement is dropped: {:?}", name, refused);
+    }
+    assert!(book.is_empty(), "no forged address is kept");
+    
+    assert_eq!(book.insert("peer", genuine.clone(), now), Ok(true));
+    let refreshed = AddressAnnouncement { timestamp: now + 1, ..genuine.clone() };
+    assert!(matches!(book.insert("peer", refreshed, now), Err(AddressError::Forged { .. })), "a forged refresh is dropped");
+    assert_eq!(book.announcements(), vec![genuine.clone()], "the genuine announcement stays");
+    
+    let stale = honest.announce(test_addr(3), now - ANNOUNCEMENT_TTL.as_secs());
+    assert!(matches!(book.insert("peer", stale, now), Err(AddressError::Stale { .. })));
+    let future = honest.announce(test_addr(4), now + MAX_CLOCK_DRIFT.as_secs() + 1);
+    assert!(matches!(book.insert("peer", future, now), Err(AddressError::FromFuture { .. })));
+    
+    // Through a node, which keeps the genuine announcement among the forged ones and passes it on
+    let (network, _) = clocked_network();
+    let sent = forged.into_iter().map(|(_, announcement)| announcement).chain([genuine.clone()]).collect();
+    let (added, refused) = network.add_announcements("peer", sent);
+    assert_eq!((added, refused.len()), (1, 5));
+    assert_eq!(network.announcements(), vec![genuine]);
+    
+    // Between simulated nodes, each passing on its own announcement
+    let mut sim = Simulation::new(SimConfig::default()).expect("the simulation starts");
+    sim.run_for(1_000);
+    sim.node(1).handle_message(&sim::node_id(0), NetworkMessage::GetPeers);
+    sim.run_for(1_000);
+    let identity = sim.node(1).network.lock().unwrap().identity().node_id().to_string();
+    let learned = sim.node(0).network.lock().unwrap().announcements();
+    let announced = learned.iter().any(|announcement| announcement.node_id == identity && announcement.addr == sim::node_address(1));
+    assert!(announced, "the node learns its peer's own announcement: {:?}", learned);
+}
+
+/// Checks that a peer sending a flood of validly signed announcements only gets `MAX_NEW_ADDRESSES_PER_HOUR` of them in an hour
+///
+/// Panics if more are taken from the peer, if another peer's share is
+/// taken with it, or if the addresses aren't forgotten once stale.
+pub fn check_announcement_flood() {
+    let (network, clock) = clocked_network();
+    let now = clock.now();
+    let flood: Vec<AddressAnnouncement> = (0..MAX_ANNOUNCEMENTS)
+        .map(|index| NodeIdentity::generate().announce(test_addr(index), now))
+        .collect();
+    
+    let (added, refused) = network.add_announcements("flooder", flood.clone());
+    assert_eq!(added, MAX_NEW_ADDRESSES_PER_HOUR);
+    assert_eq!(refused.len(), MAX_ANNOUNCEMENTS - MAX_NEW_ADDRESSES_PER_HOUR);
+    assert!(refused.iter().all(|e| matches!(e, AddressError::RateLimited { .. })), "the rest are refused for the rate");
+    
+    // More of the same, or new, that hour get nothing more in
+    let more: Vec<AddressAnnouncement> = (MAX_ANNOUNCEMENTS..2 * MAX_ANNOUNCEMENTS)
+        .map(|index| NodeIdentity::generate().announce(test_addr(index), now))
+        .collect();
+    clock.advance(30 * 60 * 1000);
+    for announcements in [flood.clone(), more.clone(), flood.clone()] {
+        assert_eq!(network.add_announcements("flooder", announcements).0, 0, "the flooder's share is spent");
+    }
+    
+    // Another peer has its share
+    let honest = NodeIdentity::generate().announce(test_addr(3 * MAX_ANNOUNCEMENTS), clock.now());
+    assert_eq!(network.add_announcements("honest", vec![honest]).0, 1);
+    assert_eq!(network.dial_candidates().len(), MAX_NEW_ADDRESSES_PER_HOUR + 1);
+    
+    // The next hour, another share
+    clock.advance(30 * 60 * 1000);
+    assert_eq!(network.add_announcements("flooder", more).0, MAX_NEW_ADDRESSES_PER_HOUR);
+    
+    // All forgotten once stale
+    clock.advance(ANNOUNCEMENT_TTL.as_millis() as u64);
+    assert_eq!(network.expire_addresses(), 2 * MAX_NEW_ADDRESSES_PER_HOUR + 1);
+    assert!(network.dial_candidates().is_empty(), "stale addresses aren't dialed");
+}
+
+/// Checks that the addresses a node completed a handshake at are dialed before those only announced
+///
+/// Panics unless the candidates come confirmed first, then those set by
+/// the operator, then those announced, the newest first.
+pub fn check_dial_preference() {
+    let (network, clock) = clocked_network();
+    let now = clock.now();
+    let announced: Vec<AddressAnnouncement> = (0..3)
+        .map(|index| NodeIdentity::generate().announce(test_addr(index), now - index as u64))
+        .collect();
+    assert_eq!(network.add_announcements("peer", announced).0, 3);
+    assert!(network.add_known_address(test_addr(10)));
+    assert_eq!(network.dial_candidates(), vec![test_addr(10), test_addr(0), test_addr(1), test_addr(2)]);
+    
+    // A minute on, dial the oldest announced address and complete the handshake there
+    clock.advance(60_000);
+    let confirmed = test_addr(2);
+    assert!(network.open_connection(confirmed, true));
+    let handshake = HandshakeData {
+        node_id: "dialed".to_string(),
+        height: 0,
+        best_hash: BlockHash::default(),
+        timestamp: now,
+        nonce: 1,
+        listen_addrs: Vec::new(),
+        observed_addr: None,
+    };
+    let outcome = network.accept_handshake(confirmed, None, true, &handshake);
+    assert!(matches!(outcome, HandshakeOutcome::Accepted { .. }), "the handshake is accepted: {:?}", outcome);
+    assert!(!network.dial_candidates().contains(&confirmed), "a connected peer isn't dialed");
+    
+    network.disconnect_peer("dialed").expect("the peer is connected");
+    assert_eq!(network.dial_candidates(), vec![confirmed, test_addr(10), test_addr(0), test_addr(1)]);
+    
+    // The confirmation outlives the announcements made before it
+    clock.advance(ANNOUNCEMENT_TTL.as_millis() as u64 - 30_000);
+    network.expire_addresses();
+    assert_eq!(network.dial_candidates(), vec![confirmed, test_addr(10)]);
+}
\ No newline at end of file
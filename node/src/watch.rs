@@ -0,0 +1,294 @@
+//! Balance change notifications for addresses the node watches
+//!
+//! Explorers and exchanges can have the node tell them when the balances of
+//! given addresses change, without running a wallet. Operators manage the
+//! watch list with `admin_watchAddress`, `admin_unwatchAddress` and
+//! `admin_listWatched` (see `admin`); addresses watched at runtime are
+//! saved to `watchlist.json` in the data directory and loaded again when
+//! the node starts, along with those in the config.
+//!
+//! As each block is added the chain hands its listeners the balances the
+//! block changed, read from the block's undo record (see
+//! `ChainListener::balances_changed`), so no transactions are scanned. Each
+//! change to a watched address becomes an `AddressActivity` event, which is
+//!
+//! - published on the node's event bus (see `events`),
+//! - delivered to WebSocket clients subscribed to `addressActivity` (see
+//!   `subscriptions`),
+//! - and POSTed as JSON to `webhook_url`, if set.
+//!
+//! A block rolled back in a reorganization emits the opposite changes with
+//! `removed` set, so summing the deltas of every event gives the balance.
+//!
+//! Webhook deliveries are made one at a time, in order. A delivery that
+//! fails, or that isn't answered with a 2xx status, is retried up to
+//! `webhook_max_attempts` times in all, waiting `webhook_backoff_ms` before
+//! the first retry and twice as long before each one after it, then
+//! dropped with an error logged. Events arriving while
+//! `WEBHOOK_QUEUE_SIZE` deliveries wait are dropped too.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use ctb_core::block::Block;
+use ctb_core::chain::ChainListener;
+use ctb_core::receipt::IndexedLog;
+use ctb_core::state_diff::BalanceChange;
+use ctb_core::BlockHash;
+
+use crate::events::{EventBus, NodeEvent};
+use crate::subscriptions::SubscriptionManager;
+
+/// Name of the file watched addresses are saved to, in the data directory
+pub const WATCHLIST_FILE: &str = "watchlist.json";
+
+/// Webhook deliveries that may wait before new events are dropped
+pub const WEBHOOK_QUEUE_SIZE: usize = 1024;
+
+/// Time a webhook endpoint has to answer a delivery
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Address watch configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Addresses watched besides those saved
+    pub addresses: Vec<String>,
+    
+    /// URL `AddressActivity` events are POSTed to, such as `http://127.0.0.1:9000/genx`
+    pub webhook_url: Option<String>,
+    
+    /// Most times a webhook delivery is attempted
+    pub webhook_max_attempts: u32,
+    
+    /// Wait before retrying a webhook delivery the first time, in milliseconds
+    pub webhook_backoff_ms: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            addresses: Vec::new(),
+            webhook_url: None,
+            webhook_max_attempts: 5,
+            webhook_backoff_ms: 500,
+        }
+    }
+}
+
+/// Where webhook deliveries are POSTed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookEndpoint {
+    /// Host and port to connect to
+    pub authority: String,
+    
+    /// Request target, starting with `/`
+    pub path: String,
+}
+
+impl FromStr for WebhookEndpoint {
+    type Err = String;
+    
+    /// Parses an `http://` URL; other schemes aren't supported
+    fn from_str(url: &str) -> Result<Self, String> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| format!("webhook URL {} must start with http://", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        if authority.is_empty() {
+            return Err(format!("webhook URL {} has no host", url));
+        }
+        
+        let authority = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+        Ok(Self { authority, path: path.to_string() })
+    }
+}
+
+/// Watch list of a node, turning block application into `AddressActivity` events
+#[derive(Debug)]
+pub struct AddressWatcher {
+    /// Watched addresses
+    watched: RwLock<BTreeSet<String>>,
+    
+    /// File watched addresses are saved to, if they're saved at all
+    path: Option<PathBuf>,
+    
+    events: EventBus,
+    subscriptions: Arc<SubscriptionManager>,
+    
+    /// Queue of webhook deliveries, while a webhook is delivering
+    webhook: Mutex<Option<Sender<NodeEvent>>>,
+}
+
+impl AddressWatcher {
+    /// Creates a watcher of the configured addresses, saving the list to `path` if given
+    pub fn new(config: &WatchConfig, path: Option<PathBuf>, events: EventBus, subscriptions: Arc<SubscriptionManager>) -> Self {
+        Self {
+            watched: RwLock::new(config.addresses.iter().cloned().collect()),
+            path,
+            events,
+            subscriptions,
+            webhook: Mutex::new(None),
+        }
+    }
+    
+    /// Loads the addresses saved by a previous run, keeping those already watched
+    pub fn load(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !path.exists() {
+            return Ok(());
+        }
+        
+        let saved: Vec<String> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut watched = self.watched.write().unwrap();
+        watched.extend(saved);
+        println!("Watching {} addresses from {}", watched.len(), path.display());
+        Ok(())
+    }
+    
+    /// Watches an address, returning whether it wasn't watched already
+    ///
+    /// The address is watched even if saving the list fails, until the node restarts.
+    pub fn watch(&self, address: &str) -> io::Result<bool> {
+        let mut watched = self.watched.write().unwrap();
+        if !watched.insert(address.to_string()) {
+            return Ok(false);
+        }
+        
+        self.save(&watched)?;
+        println!("Watching address {}", address);
+        Ok(true)
+    }
+    
+    /// Stops watching an address, returning whether it was watched
+    pub fn unwatch(&self, address: &str) -> io::Result<bool> {
+        let mut watched = self.watched.write().unwrap();
+        if !watched.remove(address) {
+            return Ok(false);
+        }
+        
+        self.save(&watched)?;
+        println!("Stopped watching address {}", address);
+        Ok(true)
+    }
+    
+    /// Gets the watched addresses, in order
+    pub fn watched(&self) -> Vec<String> {
+        self.watched.read().unwrap().iter().cloned().collect()
+    }
+    
+    /// Starts POSTing events to a webhook, replacing any webhook delivering already
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn start_webhook(&self, endpoint: WebhookEndpoint, max_attempts: u32, backoff: Duration) {
+        let (sender, receiver) = mpsc::channel(WEBHOOK_QUEUE_SIZE);
+        tokio::spawn(deliver(endpoint, receiver, max_attempts.max(1), backoff));
+        *self.webhook.lock().unwrap() = Some(sender);
+    }
+    
+    /// Stops POSTing events to the webhook once the deliveries queued are made
+    pub fn stop_webhook(&self) {
+        self.webhook.lock().unwrap().take();
+    }
+    
+    /// Saves the watched addresses, replacing the file atomically
+    fn save(&self, watched: &BTreeSet<String>) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let contents = serde_json::to_string_pretty(watched)?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+impl ChainListener for AddressWatcher {
+    fn block_added(&self, _block: &Block, _block_hash: &BlockHash, _logs: &[IndexedLog]) {}
+    
+    fn block_removed(&self, _block: &Block, _block_hash: &BlockHash, _logs: &[IndexedLog]) {}
+    
+    fn balances_changed(&self, block: &Block, block_hash: &BlockHash, changes: &[BalanceChange], removed: bool) {
+        let watched = self.watched.read().unwrap();
+        for change in changes.iter().filter(|change| watched.contains(&change.address)) {
+            let event = NodeEvent::address_activity(block, block_hash, change, removed);
+            self.subscriptions.notify_address_activity(&event);
+            if let Some(webhook) = &*self.webhook.lock().unwrap() {
+                if webhook.try_send(event.clone()).is_err() {
+                    eprintln!("Webhook: queue full, dropped the activity of {} at height {}", change.address, block.header().height);
+                }
+            }
+            self.events.publish(event);
+        }
+    }
+}
+
+/// Delivers queued events to a webhook until the queue is closed
+async fn deliver(endpoint: WebhookEndpoint, mut receiver: Receiver<NodeEvent>, max_attempts: u32, backoff: Duration) {
+    while let Some(event) = receiver.recv().await {
+        let body = serde_json::to_string(&event).unwrap_or_default();
+        let mut delay = backoff;
+        for attempt in 1..=max_attempts {
+            let error = match tokio::time::timeout(WEBHOOK_TIMEOUT, post(&endpoint, &body)).await {
+                Ok(Ok(())) => break,
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "timed out".to_string(),
+            };
+            if attempt == max_attempts {
+                eprintln!("Webhook: dropped a delivery to {} after {} attempts: {}", endpoint.authority, attempt, error);
+            } else {
+                tokio::time::sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+        }
+    }
+}
+
+/// POSTs a JSON body to a webhook, failing unless it answers with a 2xx status
+async fn post(endpoint: &WebhookEndpoint, body: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect(&endpoint.authority).await?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.authority,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+    
+    // Only the status line matters
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 512];
+    while !response.contains(&b'\n') {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        response.extend_from_slice(&chunk[..read]);
+    }
+    
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().and_then(|line| line.split(' ').nth(1)).unwrap_or_default();
+    if status.starts_with('2') && status.len() == 3 {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("endpoint answered {}", response.lines().next().unwrap_or("nothing"))))
+    }
+}
\ No newline at end of file
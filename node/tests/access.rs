@@ -0,0 +1,64 @@
+//! Checks an RPC server reachable from other hosts won't start without an auth token
+//!
+//! Run with `cargo test -p node --test access`. Starts RPC servers on
+//! loopback and wildcard addresses, with and without an `auth_token`, and
+//! checks only the one other hosts could reach without a token is refused,
+//! including when a node starts it.
+
+use std::io::ErrorKind;
+
+use ctb_core::genesis;
+
+use node::access::AccessConfig;
+use node::rpc::{RpcConfig, RpcServer};
+use node::{Node, NodeConfig};
+
+/// Configures an RPC server on `listen_addr`, with an auth token if given
+fn rpc_config(listen_addr: &str, auth_token: Option<&str>) -> RpcConfig {
+    RpcConfig {
+        listen_addr: listen_addr.parse().unwrap(),
+        admin_enabled: false,
+        access: AccessConfig { auth_token: auth_token.map(str::to_string), ..AccessConfig::default() },
+        ..RpcConfig::default()
+    }
+}
+
+/// Creates a node over a fresh mainnet genesis, serving RPC as configured
+fn node(name: &str, rpc_config: RpcConfig) -> Node {
+    let blockchain = genesis::initialize_blockchain().expect("the genesis block is valid");
+    let data_dir = std::env::temp_dir().join(format!("genx-access-{}-{}", name, std::process::id()));
+    Node::new(NodeConfig { rpc_config, data_dir: data_dir.display().to_string(), ..NodeConfig::default() }, blockchain)
+}
+
+/// Checks a server on a non-loopback address needs a token, and one on a loopback address doesn't
+#[test]
+fn check_listen_addresses() {
+    let runtime = tokio::runtime::Runtime::new().expect("start a runtime");
+    let handler = node("servers", rpc_config("127.0.0.1:0", None)).rpc_handler();
+    
+    for listen_addr in ["0.0.0.0:0", "[::]:0"] {
+        for auth_token in [None, Some("")] {
+            let config = rpc_config(listen_addr, auth_token);
+            let error = runtime.block_on(RpcServer::start(&config, handler.clone())).err().expect("no token, no server");
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+            assert!(error.to_string().contains("auth_token"), "{}", error);
+        }
+    }
+    
+    for config in [rpc_config("0.0.0.0:0", Some("secret")), rpc_config("127.0.0.1:0", None), rpc_config("[::1]:0", None)] {
+        let mut server = runtime.block_on(RpcServer::start(&config, handler.clone()))
+            .unwrap_or_else(|e| panic!("serve on {}: {}", config.listen_addr, e));
+        server.shutdown();
+    }
+}
+
+/// Checks a node serving RPC on every interface without a token refuses to start
+#[test]
+fn check_node_refuses() {
+    let runtime = tokio::runtime::Runtime::new().expect("start a runtime");
+    let mut node = node("node", rpc_config("0.0.0.0:0", None));
+    let error = runtime.block_on(node.start()).expect_err("the node refuses to start");
+    assert!(error.to_string().contains("auth_token"), "{}", error);
+    node.stop();
+    let _ = std::fs::remove_dir_all(std::env::temp_dir().join(format!("genx-access-node-{}", std::process::id())));
+}
\ No newline at end of file
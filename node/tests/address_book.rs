@@ -0,0 +1,26 @@
+//! Checks the address book resists poisoning by gossiped addresses
+//!
+//! Run with `cargo test -p node --features testutil --test address_book`.
+//! Checks announcements not signed by the node they name are dropped, a
+//! peer flooding a node with addresses only gets its hourly share of them
+//! in, and addresses confirmed by a handshake are dialed first.
+
+use node::testutil;
+
+/// Checks forged and stale announcements are dropped
+#[test]
+fn check_forged_announcements() {
+    testutil::check_forged_announcements();
+}
+
+/// Checks a flooding peer only gets its hourly share of new addresses in
+#[test]
+fn check_announcement_flood() {
+    testutil::check_announcement_flood();
+}
+
+/// Checks addresses confirmed by a handshake are dialed before the others
+#[test]
+fn check_dial_preference() {
+    testutil::check_dial_preference();
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+//! Checks the admin methods change a running node without a restart
+//!
+//! Run with `cargo test -p node --features testutil --test admin`. Runs two
+//! simulated validators, has the first stop validating through
+//! `admin_stopValidating` and checks it makes no blocks for several slots
+//! while it keeps following the other's, then starts it again and checks
+//! its blocks resume. Also checks the mempool limits change as set, and
+//! that the public handler doesn't serve the admin methods.
+
+use serde_json::{json, Value};
+
+use consensus::ConsensusParams;
+use node::rpc::RpcHandler;
+use node::sim::{self, SimConfig, Simulation};
+
+/// Block times the first validator is left stopped, and then running
+const SLOTS: u64 = 12;
+
+/// Makes a JSON-RPC request, returning the response
+fn call(handler: &RpcHandler, method: &str, params: Value) -> Value {
+    handler.handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+}
+
+/// Makes a JSON-RPC request that must succeed, returning its result
+fn result(handler: &RpcHandler, method: &str, params: Value) -> Value {
+    let response = call(handler, method, params);
+    assert!(response.get("error").is_none(), "{} failed: {}", method, response);
+    response["result"].clone()
+}
+
+/// Gets the validators of the blocks after `from` up to the first node's tip
+fn proposers(sim: &Simulation, from: u64) -> Vec<String> {
+    let handler = sim.node(0).rpc_handler();
+    (from + 1..=sim.heights()[0])
+        .map(|height| {
+            let block = result(&handler, "genx_getBlockWithReceipts", json!([height]));
+            block["block"]["header"]["validator"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+/// Checks a validator told to stop makes no blocks but keeps up, and makes them again once told to start
+#[test]
+fn check_validating() {
+    let config = SimConfig { nodes: 2, validators: 2, ..SimConfig::default() };
+    let slot_millis = config.consensus_params.block_time * 1000;
+    let mut sim = Simulation::new(config).unwrap();
+    assert!(sim.run_until(60_000, |sim| sim.heights()[0] >= 4 && sim.converged()), "{:?}", sim.heights());
+    let admin = sim.node(0).admin_handler();
+    let first = sim::validator_address(0);
+    
+    assert_eq!(result(&admin, "admin_stopValidating", json!([])), json!(true));
+    assert_eq!(result(&admin, "admin_stopValidating", json!([])), json!(false));
+    assert_eq!(result(&admin, "admin_nodeInfo", json!([]))["validating"], json!(false));
+    assert!(!sim.node(0).is_validating());
+    
+    // Only the other validator's blocks are made, and the stopped one follows them
+    let stopped_at = sim.heights()[0];
+    sim.run_for(SLOTS * slot_millis);
+    let stopped = proposers(&sim, stopped_at);
+    assert!(!stopped.is_empty(), "the other validator kept making blocks");
+    assert!(!stopped.contains(&first), "{:?}", stopped);
+    assert!(sim.run_until(slot_millis, Simulation::converged), "{:?}", sim.heights());
+    
+    assert_eq!(result(&admin, "admin_startValidating", json!([])), json!(true));
+    assert_eq!(result(&admin, "admin_nodeInfo", json!([]))["validating"], json!(true));
+    let started_at = sim.heights()[0];
+    sim.run_for(SLOTS * slot_millis);
+    let started = proposers(&sim, started_at);
+    assert!(started.contains(&first), "{:?}", started);
+    assert!(sim.run_until(slot_millis, Simulation::converged), "{:?}", sim.heights());
+}
+
+/// Checks mempool limits set through the admin methods read back, leaving the others as they were
+#[test]
+fn check_mempool_limits() {
+    let sim = Simulation::new(SimConfig { nodes: 1, validators: 1, ..SimConfig::default() }).unwrap();
+    let admin = sim.node(0).admin_handler();
+    let params = ConsensusParams::default();
+    let limits = result(&admin, "admin_getMempoolLimits", json!([]));
+    assert_eq!(limits["max_relay_data_size"], json!(params.max_relay_data_size));
+    
+    let set = result(&admin, "admin_setMempoolLimits", json!([{ "capacity": 10, "max_relay_data_size": 512 }]));
+    assert_eq!(set["evicted"], json!(0));
+    let expected = json!({
+        "capacity": 10,
+        "max_relay_data_size": 512,
+        "max_relay_deploy_data_size": limits["max_relay_deploy_data_size"],
+    });
+    assert_eq!(set["limits"], expected);
+    assert_eq!(result(&admin, "admin_getMempoolLimits", json!([])), expected);
+    
+    for params in [json!([{ "size": 10 }]), json!([{ "capacity": "many" }]), json!([])] {
+        assert!(call(&admin, "admin_setMempoolLimits", params.clone())["error"].is_object(), "{}", params);
+    }
+    assert_eq!(result(&admin, "admin_getMempoolLimits", json!([])), expected);
+}
+
+/// Checks the public handler answers the admin methods as unknown
+#[test]
+fn check_public_handler() {
+    let sim = Simulation::new(SimConfig { nodes: 1, validators: 1, ..SimConfig::default() }).unwrap();
+    let public = sim.node(0).rpc_handler();
+    for method in ["admin_nodeInfo", "admin_stopValidating", "admin_setMempoolLimits"] {
+        assert!(call(&public, method, json!([]))["error"].is_object(), "{}", method);
+    }
+    assert!(sim.node(0).is_validating());
+    
+    let info = result(&sim.node(0).admin_handler(), "admin_nodeInfo", json!([]));
+    assert_eq!(info["node_id"], json!(sim::node_id(0)));
+    assert_eq!(info["version"], json!(env!("CARGO_PKG_VERSION")));
+    assert!(info["genesis_hash"].is_string(), "{}", info);
+}
\ No newline at end of file
@@ -0,0 +1,147 @@
+//! Checks the admission policy keeps senders out of the mempool, not out of blocks
+//!
+//! Run with `cargo test -p node --features testutil --test banlist`. Bans
+//! a sender over the admin RPC methods and checks its transaction is
+//! refused by the mempool while another sender's is taken, then that a
+//! block another validator made with both is still imported and moves the
+//! banned sender's funds. Also checks bans are saved for the next run and
+//! an allowlist refuses everyone not on it.
+
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use ctb_core::block::Block;
+use ctb_core::chain::Blockchain;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::transaction::Transaction;
+
+use node::policy::{AdmissionPolicy, PolicyConfig, BANLIST_FILE};
+use node::rpc::{RpcConfig, RpcHandler};
+use node::{Node, NodeConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 71;
+
+/// Amount each sender transfers, in base units
+const AMOUNT: u64 = 1_000;
+
+/// A chain, and the next block on it, of a transfer from bob and one from alice
+struct Pending {
+    blockchain: Blockchain,
+    block: Block,
+    from_bob: Transaction,
+    from_alice: Transaction,
+    
+    /// Addresses of alice and bob
+    alice: String,
+    bob: String,
+    
+    /// Balance bob has before the block, in base units
+    bob_balance: u64,
+}
+
+impl Pending {
+    fn new() -> Self {
+        let mut chain = TestChain::new(SEED);
+        chain.with_empty_blocks(2);
+        let block = chain.next_block(|b| b.transfer("bob", "carol", AMOUNT).transfer("alice", "carol", AMOUNT));
+        let (alice, bob) = (chain.address("alice"), chain.address("bob"));
+        let sent_by = |sender: &str| block.transactions.iter().find(|tx| tx.sender == sender).unwrap().clone();
+        Self {
+            from_bob: sent_by(&bob),
+            from_alice: sent_by(&alice),
+            bob_balance: chain.expected_balance("bob"),
+            blockchain: chain.into_blockchain(),
+            block,
+            alice,
+            bob,
+        }
+    }
+}
+
+/// Gets the data directory of a test's node
+fn data_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("genx-banlist-{}-{}", name, std::process::id()))
+}
+
+/// Creates a node over a chain, with its own data directory
+fn node(name: &str, blockchain: Blockchain, policy_config: PolicyConfig) -> Node {
+    let data_dir = data_dir(name);
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let config = NodeConfig {
+        rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+        data_dir: data_dir.display().to_string(),
+        policy_config,
+        ..NodeConfig::default()
+    };
+    Node::new(config, blockchain)
+}
+
+/// Calls a JSON-RPC method, returning its result
+fn call(handler: &RpcHandler, method: &str, params: Value) -> Value {
+    let response = handler.handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }));
+    assert!(response.get("error").is_none(), "{}: {}", method, response);
+    response["result"].clone()
+}
+
+/// Checks a banned sender is refused by the mempool, and its transaction still mined by another validator
+#[test]
+fn check_banned_sender() {
+    let pending = Pending::new();
+    let mut node = node("banned", pending.blockchain, PolicyConfig::default());
+    let admin = node.admin_handler();
+    
+    assert_eq!(call(&admin, "admin_banAddress", json!([pending.bob, "spamming"])), json!(true));
+    assert_eq!(call(&admin, "admin_banAddress", json!([pending.bob])), json!(false));
+    let bans = call(&admin, "admin_listBans", json!([]));
+    assert_eq!(bans.as_array().unwrap().len(), 1);
+    assert_eq!(bans[0]["address"], json!(pending.bob));
+    assert_eq!(bans[0]["reason"], json!("spamming"));
+    
+    let error = node.add_transaction(pending.from_bob.clone()).unwrap_err();
+    assert!(error.to_string().contains("banned"), "{}", error);
+    assert!(!node.is_pending(&pending.from_bob.id));
+    node.add_transaction(pending.from_alice.clone()).unwrap();
+    assert!(node.is_pending(&pending.from_alice.id));
+    
+    node.import_block(pending.block).unwrap();
+    assert!(!node.is_pending(&pending.from_alice.id));
+    let balance = call(&admin, "genx_getBalance", json!([pending.bob]));
+    assert!(balance.as_u64().unwrap() <= pending.bob_balance - AMOUNT);
+    
+    assert_eq!(call(&admin, "admin_unbanAddress", json!([pending.bob])), json!(true));
+    assert_eq!(call(&admin, "admin_unbanAddress", json!([pending.bob])), json!(false));
+    assert_eq!(call(&admin, "admin_listBans", json!([])), json!([]));
+}
+
+/// Checks bans made over RPC are saved to the data directory and loaded on the next run
+#[test]
+fn check_saved_bans() {
+    let pending = Pending::new();
+    let node = node("saved", pending.blockchain, PolicyConfig::default());
+    call(&node.admin_handler(), "admin_banAddress", json!([pending.bob, "spamming"]));
+    
+    let policy = AdmissionPolicy::new(&PolicyConfig::default(), Some(data_dir("saved").join(BANLIST_FILE)));
+    assert!(policy.check_sender(&pending.bob).is_ok());
+    policy.load().unwrap();
+    assert!(policy.check_sender(&pending.bob).is_err());
+    assert_eq!(policy.bans()[0].reason, "spamming");
+}
+
+/// Checks an allowlist refuses senders not on it, and bans in the config apply to those on it
+#[test]
+fn check_allowlist() {
+    let pending = Pending::new();
+    let config = PolicyConfig { banned: vec![pending.alice.clone()], allowlist: Some(vec![pending.alice.clone()]) };
+    let mut node = node("allowlist", pending.blockchain, config);
+    
+    let error = node.add_transaction(pending.from_bob.clone()).unwrap_err();
+    assert!(error.to_string().contains("allowlist"), "{}", error);
+    let error = node.add_transaction(pending.from_alice.clone()).unwrap_err();
+    assert!(error.to_string().contains("banned in config"), "{}", error);
+    
+    call(&node.admin_handler(), "admin_unbanAddress", json!([pending.alice]));
+    node.add_transaction(pending.from_alice.clone()).unwrap();
+    assert!(node.is_pending(&pending.from_alice.id));
+}
\ No newline at end of file
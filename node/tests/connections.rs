@@ -0,0 +1,174 @@
+//! Checks nodes keep one connection to each peer and none to themselves
+//!
+//! Run with `cargo test -p node --test connections`. Has two network
+//! managers dial each other at once and delivers the four handshakes in
+//! every order, checking both keep the same single connection, the one
+//! dialed by the node with the smaller ID. Then checks a node dialing its
+//! own address drops the connection and doesn't dial it again, and an
+//! address known to lead to a connected peer isn't dialed either.
+
+use std::net::SocketAddr;
+
+use ctb_core::BlockHash;
+
+use node::message::{DisconnectReason, HandshakeData, NetworkMessage};
+use node::network::{HandshakeOutcome, NetworkConfig, NetworkManager};
+
+/// Node with the smaller ID, whose dialed connection wins the tie-break
+const NODE_A: &str = "node-a";
+
+/// Node with the larger ID
+const NODE_B: &str = "node-b";
+
+/// Parses an address
+fn addr(addr: &str) -> SocketAddr {
+    addr.parse().unwrap()
+}
+
+/// A node's network manager, with the addresses it listens on
+struct Side {
+    network: NetworkManager,
+    listen_addrs: Vec<SocketAddr>,
+}
+
+impl Side {
+    /// Creates the network manager of a node listening on the addresses
+    fn new(node_id: &str, listen_addrs: &[&str]) -> Self {
+        let listen_addrs: Vec<SocketAddr> = listen_addrs.iter().map(|listen_addr| addr(listen_addr)).collect();
+        let config = NetworkConfig { node_id: node_id.to_string(), listen_addrs: listen_addrs.clone(), ..NetworkConfig::default() };
+        Self { network: NetworkManager::new(config), listen_addrs }
+    }
+    
+    /// Makes the handshake the node sends, giving its listening addresses
+    fn handshake(&self) -> HandshakeData {
+        HandshakeData { listen_addrs: self.listen_addrs.clone(), ..self.network.handshake(0, BlockHash::default()) }
+    }
+    
+    /// Dials an address, sending the handshake over the new connection
+    fn dial(&self, address: SocketAddr) {
+        assert!(self.network.open_connection(address, true));
+        self.network.handshake_sent(address);
+    }
+    
+    /// Accepts a connection from an address
+    fn accept(&self, address: SocketAddr) {
+        assert!(self.network.open_connection(address, false));
+    }
+    
+    /// Gets the peers' node IDs with the addresses and directions of their connections
+    fn peers(&self) -> Vec<(String, SocketAddr, bool)> {
+        let mut peers = Vec::new();
+        self.network.for_each_peer(|peer| peers.push((peer.node_id.clone(), peer.address, peer.outbound)));
+        peers
+    }
+}
+
+/// Lists every order of `count` items
+fn orders(count: usize) -> Vec<Vec<usize>> {
+    if count == 0 {
+        return vec![Vec::new()];
+    }
+    let mut all = Vec::new();
+    for order in orders(count - 1) {
+        for position in 0..=order.len() {
+            let mut order = order.clone();
+            order.insert(position, count - 1);
+            all.push(order);
+        }
+    }
+    all
+}
+
+/// Checks both nodes keep the connection dialed by the smaller ID, whatever order the handshakes arrive in
+#[test]
+fn check_cross_dial() {
+    // Each connection's far end is the other node's listening address or an ephemeral port of it
+    let (a_port, b_port) = (addr("10.0.0.1:50001"), addr("10.0.0.2:50002"));
+    
+    for order in orders(4) {
+        let a = Side::new(NODE_A, &["10.0.0.1:30303"]);
+        let b = Side::new(NODE_B, &["10.0.0.2:30303"]);
+        let (a_listen, b_listen) = (a.listen_addrs[0], b.listen_addrs[0]);
+        a.dial(b_listen);
+        b.accept(a_port);
+        b.dial(a_listen);
+        a.accept(b_port);
+        
+        let (a_handshake, b_handshake) = (a.handshake(), b.handshake());
+        let deliveries = [
+            (0, b_listen, None, &b_handshake),
+            (1, a_port, Some(b_listen), &a_handshake),
+            (1, a_listen, None, &a_handshake),
+            (0, b_port, Some(a_listen), &b_handshake),
+        ];
+        let mut dropped = [0, 0];
+        for &index in &order {
+            let (side, address, local_addr, handshake) = deliveries[index];
+            let outcome = [&a, &b][side].network.handshake_received(address, local_addr, handshake);
+            match outcome {
+                HandshakeOutcome::Accepted { replaced: None } => {}
+                HandshakeOutcome::Accepted { replaced: Some(_) } | HandshakeOutcome::Rejected(DisconnectReason::Duplicate) => {
+                    dropped[side] += 1;
+                }
+                outcome => panic!("order {:?}: {:?}", order, outcome),
+            }
+        }
+        
+        assert_eq!(a.peers(), vec![(NODE_B.to_string(), b_listen, true)], "order {:?}", order);
+        assert_eq!(b.peers(), vec![(NODE_A.to_string(), a_port, false)], "order {:?}", order);
+        assert_eq!((a.network.connection_count(), b.network.connection_count()), (1, 1), "order {:?}", order);
+        assert_eq!(dropped, [1, 1], "order {:?}: each side drops one connection", order);
+    }
+}
+
+/// Checks a node dialing itself drops the connection and doesn't dial the address again
+#[test]
+fn check_self_connection() {
+    let a = Side::new(NODE_A, &["10.0.0.1:30303"]);
+    let a_listen = a.listen_addrs[0];
+    let a_port = addr("10.0.0.1:50001");
+    assert!(a.network.should_dial(a_listen));
+    a.dial(a_listen);
+    a.accept(a_port);
+    
+    let handshake = a.handshake();
+    for (address, local_addr) in [(a_listen, None), (a_port, Some(a_listen))] {
+        let outcome = a.network.handshake_received(address, local_addr, &handshake);
+        assert!(matches!(outcome, HandshakeOutcome::Rejected(DisconnectReason::SelfConnection)), "{:?}", outcome);
+    }
+    assert!(a.peers().is_empty());
+    assert_eq!(a.network.connection_count(), 0);
+    assert!(!a.network.should_dial(a_listen), "the address leads back to the node");
+    
+    // A peer claiming the node's address doesn't make it dialable again
+    let b = Side::new(NODE_B, &["10.0.0.2:30303"]);
+    let b_listen = b.listen_addrs[0];
+    a.dial(b_listen);
+    let handshake = HandshakeData { listen_addrs: vec![b_listen, a_listen], ..b.handshake() };
+    assert!(matches!(a.network.handshake_received(b_listen, None, &handshake), HandshakeOutcome::Accepted { .. }));
+    assert!(!a.network.should_dial(a_listen));
+}
+
+/// Checks addresses of a connected peer aren't dialed, and a second connection the same way is refused
+#[test]
+fn check_known_addresses() {
+    let a = Side::new(NODE_A, &["10.0.0.1:30303"]);
+    let b = Side::new(NODE_B, &["10.0.0.2:30303", "[fd00::2]:30303"]);
+    let (b_listen, b_other) = (b.listen_addrs[0], b.listen_addrs[1]);
+    a.dial(b_listen);
+    assert!(matches!(a.network.handshake_received(b_listen, None, &b.handshake()), HandshakeOutcome::Accepted { replaced: None }));
+    assert!(!a.network.should_dial(b_listen));
+    assert!(!a.network.should_dial(b_other), "the peer's other address leads to it too");
+    
+    // Dialed at its other address anyway, the peer is already connected the same way
+    a.dial(b_other);
+    let outcome = a.network.handshake_received(b_other, None, &b.handshake());
+    assert!(matches!(outcome, HandshakeOutcome::Rejected(DisconnectReason::Duplicate)), "{:?}", outcome);
+    assert_eq!(a.peers(), vec![(NODE_B.to_string(), b_listen, true)]);
+    assert_eq!(a.network.connection_count(), 1);
+    
+    // Once the peer's gone, its addresses are worth dialing again
+    a.network.message_received(NODE_B, &NetworkMessage::Disconnect(DisconnectReason::Requested));
+    assert!(a.peers().is_empty());
+    assert!(a.network.should_dial(b_listen) && a.network.should_dial(b_other));
+}
\ No newline at end of file
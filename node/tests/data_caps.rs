@@ -0,0 +1,146 @@
+//! Checks transaction data is capped and large payloads pay more than their size
+//!
+//! Run with `cargo test -p node --features testutil --test data_caps`.
+//! Checks data just under, at and just over `MAX_DATA_SIZE`, and for
+//! deployments `MAX_DEPLOY_DATA_SIZE`, against transaction validation and
+//! stricter relay caps against mempool admission, then that a block holding
+//! one over-cap transaction is refused whole and that the gas charged for
+//! data grows faster than the data past `DATA_SOFT_LIMIT`.
+
+use std::sync::{Arc, Mutex};
+
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::genesis;
+use ctb_core::transaction::{Transaction, TransactionType, MAX_DATA_SIZE, MAX_DEPLOY_DATA_SIZE};
+
+use consensus::{ConsensusEngine, ConsensusParams};
+use smartcontracts::{ContractEngine, GasConfig, DATA_SOFT_LIMIT};
+
+/// Seed of the chain built
+const SEED: u64 = 107;
+
+/// Gas limit of the contract transactions, enough for the largest data
+const GAS_LIMIT: u64 = 30_000_000;
+
+/// Contract the calls are addressed to; none is deployed, as only data is checked
+const CONTRACT: &str = "GENX_CONTRACT_0000000000000000000000000000000000000000";
+
+/// Relay cap of a strict node on the data of transactions other than deployments
+const RELAY_DATA_SIZE: usize = 1024;
+
+/// Relay cap of a strict node on the data of deployments
+const RELAY_DEPLOY_DATA_SIZE: usize = 4096;
+
+/// Builds a chain whose contract transactions may use a whole block's gas
+fn chain() -> TestChain {
+    TestChain::with_config(SEED, TestChainConfig { contract_gas_limit: GAS_LIMIT, ..TestChainConfig::default() })
+}
+
+/// Makes a transaction of a type carrying `len` bytes of data, signed by alice
+fn transaction(chain: &TestChain, tx_type: TransactionType, len: usize, nonce: u64) -> Transaction {
+    let (sender, data) = (chain.address("alice"), vec![0x5a; len]);
+    let base_fee = chain.blockchain().next_base_fee();
+    let tx = match tx_type {
+        TransactionType::ContractDeploy => Transaction::new_contract_deploy(sender, 0, data, GAS_LIMIT, base_fee),
+        _ => Transaction::new_contract_call(sender, CONTRACT.to_string(), 0, data, GAS_LIMIT, base_fee),
+    };
+    let mut tx = tx.and_then(|tx| tx.with_nonce(nonce)).unwrap();
+    chain.account("alice").sign(&mut tx).unwrap();
+    tx
+}
+
+/// Checks transactions are valid up to their type's cap, and not a byte over
+#[test]
+fn check_caps() {
+    let chain = chain();
+    for (tx_type, cap) in [(TransactionType::ContractCall, MAX_DATA_SIZE), (TransactionType::ContractDeploy, MAX_DEPLOY_DATA_SIZE)] {
+        for len in [cap - 1, cap] {
+            let tx = transaction(&chain, tx_type, len, 0);
+            assert_eq!(tx.max_data_size(), cap);
+            assert!(tx.validate().is_ok(), "{:?} with {} bytes: {:?}", tx_type, len, tx.validate());
+        }
+        let error = transaction(&chain, tx_type, cap + 1, 0).validate().unwrap_err().to_string();
+        assert!(error.contains(&format!("exceeds the {} byte limit", cap)), "{:?}: {}", tx_type, error);
+    }
+    
+    // Deployment data is allowed past the cap of other transactions
+    assert!(transaction(&chain, TransactionType::ContractDeploy, MAX_DATA_SIZE + 1, 0).validate().is_ok());
+}
+
+/// Checks the mempool admits data up to the stricter of a node's relay cap and the consensus cap
+#[test]
+fn check_relay_caps() {
+    let strict = ConsensusParams {
+        max_relay_data_size: RELAY_DATA_SIZE,
+        max_relay_deploy_data_size: RELAY_DEPLOY_DATA_SIZE,
+        ..ConsensusParams::default()
+    };
+    let lax = ConsensusParams {
+        max_relay_data_size: 2 * MAX_DATA_SIZE,
+        max_relay_deploy_data_size: 2 * MAX_DEPLOY_DATA_SIZE,
+        ..ConsensusParams::default()
+    };
+    let caps = [
+        (strict, [(TransactionType::ContractCall, RELAY_DATA_SIZE), (TransactionType::ContractDeploy, RELAY_DEPLOY_DATA_SIZE)]),
+        (lax, [(TransactionType::ContractCall, MAX_DATA_SIZE), (TransactionType::ContractDeploy, MAX_DEPLOY_DATA_SIZE)]),
+    ];
+    for (params, caps) in caps {
+        // Transactions are signed with the keys of a second chain just like the engine's
+        let signer = chain();
+        let mut engine = ConsensusEngine::new(Arc::new(Mutex::new(chain().into_blockchain())), params);
+        let mut nonce = 0;
+        for (tx_type, cap) in caps {
+            for len in [cap - 1, cap] {
+                engine.add_transaction(transaction(&signer, tx_type, len, nonce)).unwrap();
+                nonce += 1;
+            }
+            let error = engine.add_transaction(transaction(&signer, tx_type, cap + 1, nonce)).unwrap_err().to_string();
+            assert!(error.contains(&format!("relay limit of {} bytes", cap)), "{:?}: {}", tx_type, error);
+        }
+        assert_eq!(engine.mempool().len(), 4);
+    }
+}
+
+/// Checks a block holding an over-cap transaction is refused with everything in it
+#[test]
+fn check_block_refused() {
+    let mut chain = chain();
+    chain.with_block(|b| b.call("alice", CONTRACT, vec![0x5a; MAX_DATA_SIZE]));
+    let (height, balances) = (chain.height(), chain.expected_balances().clone());
+    
+    let block = chain.next_block(|b| b.transfer("bob", "carol", 1_000).call("alice", CONTRACT, vec![0x5a; MAX_DATA_SIZE + 1]));
+    let error = chain.blockchain_mut().add_block(block).unwrap_err().to_string();
+    assert!(error.contains("byte limit"), "{}", error);
+    assert_eq!(chain.height(), height);
+    assert_eq!(chain.expected_balances(), &balances);
+    chain.assert_balances();
+    
+    let block = chain.next_block(|b| b.transfer("bob", "carol", 1_000).call("alice", CONTRACT, vec![0x5a; MAX_DATA_SIZE]));
+    chain.add_block(block);
+    chain.assert_balances();
+}
+
+/// Checks data gas is linear up to the soft limit and grows faster past it, leaving deployments at the cap room in a block
+#[test]
+fn check_pricing() {
+    let gas = GasConfig::default();
+    for len in [0, 1, DATA_SOFT_LIMIT - 1, DATA_SOFT_LIMIT] {
+        assert_eq!(gas.data_gas(len), len as u64 * gas.data_cost, "{} bytes", len);
+    }
+    
+    // Each further kilobyte past the soft limit costs more than the one before
+    let kilobytes: Vec<u64> = (0..=(MAX_DEPLOY_DATA_SIZE - DATA_SOFT_LIMIT) / 1024)
+        .map(|kilobyte| gas.data_gas(DATA_SOFT_LIMIT + kilobyte * 1024))
+        .collect();
+    let steps: Vec<u64> = kilobytes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    assert!(steps.windows(2).all(|pair| pair[1] > pair[0]), "{:?}", steps);
+    assert!(gas.data_gas(2 * DATA_SOFT_LIMIT) > 2 * gas.data_gas(DATA_SOFT_LIMIT));
+    
+    // Intrinsic gas charges it, and a deployment at the cap still fits in a block
+    let chain = chain();
+    let engine = ContractEngine::new(gas.clone());
+    let call = transaction(&chain, TransactionType::ContractCall, MAX_DATA_SIZE, 0);
+    assert_eq!(engine.intrinsic_gas(&call), gas.base_cost + gas.data_gas(MAX_DATA_SIZE));
+    let deploy = transaction(&chain, TransactionType::ContractDeploy, MAX_DEPLOY_DATA_SIZE, 0);
+    assert!(engine.intrinsic_gas(&deploy) <= genesis::get_block_gas_limit());
+}
\ No newline at end of file
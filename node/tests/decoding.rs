@@ -0,0 +1,18 @@
+//! Checks that decoding frames from peers never panics
+//!
+//! Run with `cargo test -p node --features testutil --test decoding`.
+//! Feeds `CASES` generated frames, then every regression frame, to the
+//! frame decoder and the block sync a decoded frame starts. Set
+//! `DECODING_SEED` to start from another seed.
+
+use node::testutil;
+
+/// Generated cases checked on each run
+const CASES: u64 = 2000;
+
+/// Checks generated and regression frames decode without panicking
+#[test]
+fn check_decoding() {
+    let seed = std::env::var("DECODING_SEED").ok().and_then(|seed| seed.parse().ok()).unwrap_or(0);
+    testutil::check_decoding(seed, CASES);
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+//! Checks a development node makes the same blocks every run
+//!
+//! Run with `cargo test -p node --features testutil --test devnet`. Runs a
+//! development node twice, deploying a contract and calling it only
+//! through JSON-RPC requests, and checks both runs make the same blocks in
+//! well under a second.
+
+use node::testutil;
+
+/// Checks the development node deploys, calls and mines deterministically
+#[test]
+fn check_devnet() {
+    testutil::check_devnet();
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+//! Checks a node's dry run of a block pinpoints what's wrong with it, and changes nothing
+//!
+//! Run with `cargo test -p node --features testutil --test dry_run`. Has a
+//! node check a valid block of a transfer, the same block with its coinbase
+//! minting a base unit too much, and the valid block against a state root
+//! it doesn't leave. Checks the first passes every check that runs, and
+//! each of the others fails the one check it breaks alone. The node's tip
+//! never moves and the transfer stays in its mempool, until the valid block
+//! is imported for real.
+
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::hashing;
+use ctb_core::state_sync;
+use ctb_core::units::GENX;
+use ctb_core::Hash;
+use node::dry_run::{BlockCheck, CheckStatus, ValidationReport};
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+
+/// Seed of the chains built
+const SEED: u64 = 191;
+
+/// A node on a fresh test chain, its data directory removed once the test is done
+struct Setup {
+    node: Node,
+    data_dir: String,
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+impl Setup {
+    fn new() -> Self {
+        let data_dir = std::env::temp_dir().join(format!("genx-dry-run-{}", std::process::id())).display().to_string();
+        let config = NodeConfig {
+            data_dir: data_dir.clone(),
+            rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+            ..NodeConfig::default()
+        };
+        Self { node: Node::new(config, TestChain::new(SEED).into_blockchain()), data_dir }
+    }
+}
+
+/// Gets the checks of a report that failed
+fn failed(report: &ValidationReport) -> Vec<BlockCheck> {
+    report.failures().map(|result| result.check).collect()
+}
+
+/// Builds the block again with its coinbase minting a base unit more, signed by its proposer
+fn overpaying(chain: &TestChain, block: &Block) -> Block {
+    let mut transactions = block.transactions.clone();
+    transactions[0].amount += 1;
+    transactions[0].id = hashing::hash_tx_canonical(&transactions[0]).unwrap();
+    let header = block.header();
+    let mut overpaying = Block::new(header.height, header.prev_hash, transactions, header.validator.clone(), header.base_fee).unwrap();
+    let rebuilt = overpaying.header_mut();
+    rebuilt.timestamp = header.timestamp;
+    rebuilt.validator_set_hash = header.validator_set_hash;
+    let proposer = chain.validators().iter().find(|validator| validator.address == header.validator).unwrap();
+    proposer.sign_header(rebuilt).unwrap();
+    overpaying
+}
+
+/// Checks a valid block passes, and ones with a wrong reward or state root fail that check alone, leaving the node as it was
+#[test]
+fn check_reports() {
+    let mut setup = Setup::new();
+    let mut chain = TestChain::new(SEED);
+    let block = chain.next_block(|b| b.transfer("alice", "bob", GENX));
+    let transfer = block.transactions.last().unwrap().clone();
+    setup.node.add_transaction(transfer.clone()).unwrap();
+    let expected_root: Hash = {
+        chain.add_block(block.clone());
+        let state = chain.blockchain().get_state();
+        let state = state.lock().unwrap();
+        state_sync::state_root(&state)
+    };
+    
+    // Every check that runs passes, the state root among them
+    let report = setup.node.validate_block_dry_run(&block, Some(expected_root));
+    assert!(report.accepted, "{:?}", report);
+    assert_eq!(failed(&report), []);
+    assert_eq!(report.block_hash, Some(block.hash().unwrap()));
+    assert_eq!(report.state_root, Some(expected_root));
+    for check in [BlockCheck::Structure, BlockCheck::Parent, BlockCheck::Reward, BlockCheck::Execution, BlockCheck::Gas, BlockCheck::StateRoot] {
+        assert_eq!(report.check(check).unwrap().status, CheckStatus::Passed, "{:?}", check);
+    }
+    
+    // A coinbase minting too much fails the reward check only, its state compared with nothing
+    let report = setup.node.validate_block_dry_run(&overpaying(&chain, &block), None);
+    assert!(!report.accepted);
+    assert_eq!(failed(&report), [BlockCheck::Reward], "{:?}", report);
+    assert_eq!(report.check(BlockCheck::StateRoot).unwrap().status, CheckStatus::Skipped);
+    assert_ne!(report.state_root, Some(expected_root));
+    
+    // A state root the block doesn't leave fails that check only, the report giving the right one
+    let report = setup.node.validate_block_dry_run(&block, Some([0xab; 32]));
+    assert_eq!(failed(&report), [BlockCheck::StateRoot], "{:?}", report);
+    assert_eq!(report.state_root, Some(expected_root));
+    assert!(report.check(BlockCheck::StateRoot).unwrap().detail.contains(&hex::encode(expected_root)));
+    
+    // None of it moved the tip or took the transfer out of the mempool
+    assert_eq!(setup.node.get_height(), 0);
+    assert!(setup.node.is_pending(&transfer.id));
+    setup.node.import_block(block).unwrap();
+    assert_eq!(setup.node.get_height(), 1);
+    assert!(!setup.node.is_pending(&transfer.id));
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+//! Checks that network frames round-trip and haven't changed
+//!
+//! Run with `cargo test -p node --features testutil --test encoding`.
+//! Round-trips the frames of `CASES` generated messages, then checks every
+//! fixture against its golden hash. Set `ENCODING_SEED` to start from
+//! another seed; a failure names the seed of the case that failed.
+
+use node::testutil;
+
+/// Generated cases checked on each run
+const CASES: u64 = 500;
+
+/// Checks the frames of generated messages round-trip
+#[test]
+fn check_generated() {
+    let seed = std::env::var("ENCODING_SEED").ok().and_then(|seed| seed.parse().ok()).unwrap_or(0);
+    testutil::check_encodings(seed, CASES);
+}
+
+/// Checks every fixture against its golden hash
+#[test]
+fn check_fixtures() {
+    testutil::check_fixtures();
+}
\ No newline at end of file
@@ -0,0 +1,144 @@
+//! Checks failures come back as the typed error variants and codes they're meant to
+//!
+//! Run with `cargo test -p node --features testutil --test errors`. Drives
+//! the failure paths of each crate, the chain refusing tampered blocks and
+//! overspending, the consensus engine without validators or given a
+//! transaction twice, a call to no contract, a locked wallet and an unknown
+//! peer, and checks the variant each returns along with its fields and
+//! numeric code, rather than its message. Then checks the RPC reports the
+//! code of the error behind a failure as `data.errorCode`.
+
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+
+use consensus::{ConsensusEngine, ConsensusParams};
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::state::State;
+use ctb_core::transaction::{Transaction, TransactionType};
+use ctb_core::units::{Amount, GENX};
+use ctb_core::{Address, BlockchainError};
+use node::network::{NetworkConfig, NetworkError, NetworkManager};
+use node::sim::{SimConfig, Simulation};
+use smartcontracts::{ContractEngine, ContractError, GasConfig};
+use wallet::api::WalletApi;
+use wallet::WalletError;
+
+/// Seed of the chain built
+const SEED: u64 = 139;
+
+/// Password of the wallet made
+const PASSWORD: &str = "Typed-errors-42";
+
+/// Contract the calls are addressed to; none is deployed
+const NOWHERE: &str = "GENX_CONTRACT_0000000000000000000000000000000000000000";
+
+/// Offers the chain its next block with a change, returning the error it's refused with
+///
+/// The error carries where it happened; its root is the variant checked.
+fn refused(chain: &mut TestChain, change: impl FnOnce(&mut Block)) -> BlockchainError {
+    let mut block = chain.next_block(|b| b.transfer("alice", "bob", GENX));
+    change(&mut block);
+    let error = chain.blockchain_mut().add_block(block).unwrap_err();
+    assert!(error.context().is_some(), "{:?}", error);
+    assert_eq!(error.error_code(), error.root().error_code(), "the context keeps the code");
+    error
+}
+
+/// Checks the chain refuses tampered blocks and overspending with their own variants
+#[test]
+fn check_chain_errors() {
+    let mut chain = TestChain::new(SEED);
+    chain.with_block(|b| b.transfer("alice", "bob", GENX));
+    let height = chain.height();
+    
+    let error = refused(&mut chain, |block| block.header_mut().height += 1);
+    assert!(matches!(error.root(), BlockchainError::UnexpectedHeight { expected, got } if *expected == height + 1 && *got == height + 2), "{:?}", error);
+    assert_eq!(error.error_code(), 1009);
+    
+    let error = refused(&mut chain, |block| block.header_mut().prev_hash.0[0] ^= 1);
+    assert!(matches!(error.root(), BlockchainError::ParentMismatch), "{:?}", error);
+    assert_eq!(error.error_code(), 1010);
+    
+    let error = refused(&mut chain, |block| block.transactions[1].amount += 1);
+    assert!(matches!(error.root(), BlockchainError::InvalidMerkleRoot), "{:?}", error);
+    assert_eq!(error.error_code(), 1011);
+    
+    let error = chain.blockchain().state_at(height + 5).map(|_| ()).unwrap_err();
+    assert!(matches!(error, BlockchainError::UnknownBlock { height: unknown } if unknown == height + 5), "{:?}", error);
+    assert_eq!(error.error_code(), 1008);
+    
+    let (alice, bob) = (Address::new(chain.address("alice")).unwrap(), Address::new(chain.address("bob")).unwrap());
+    let available = chain.blockchain().get_balance(&alice).unwrap();
+    let error = chain.blockchain().create_transaction(alice.clone(), bob, available, Amount::from_base_units(1), None).unwrap_err();
+    match &error {
+        BlockchainError::InsufficientBalance { address, required, available: balance } => {
+            assert_eq!((address.as_str(), *required, *balance), (alice.as_str(), available.base_units() + 1, available.base_units()));
+        }
+        error => panic!("{:?}", error),
+    }
+    assert_eq!(error.error_code(), 1007);
+    
+    let unsigned = Transaction::new_with_type(TransactionType::Transfer, chain.address("alice"), chain.address("bob"), GENX, 1_000, None, 0, 0).unwrap();
+    let error = unsigned.verify_signature().unwrap_err();
+    assert!(matches!(error, BlockchainError::MissingSignature { tx_id } if tx_id == unsigned.id), "{:?}", error);
+    assert_eq!(error.error_code(), 1015);
+}
+
+/// Checks consensus errors keep their codes once they're blockchain errors
+#[test]
+fn check_consensus_errors() {
+    let signer = TestChain::new(SEED);
+    let mut engine = ConsensusEngine::new(Arc::new(Mutex::new(TestChain::new(SEED).into_blockchain())), ConsensusParams::default());
+    
+    // Validators are only known once the engine is initialized
+    let error = engine.proposer_for_slot(1).map(|_| ()).unwrap_err();
+    assert!(matches!(error, BlockchainError::Consensus { code: 2003, .. }), "{:?}", error);
+    assert_eq!(error.error_code(), 2003);
+    
+    let mut tx = Transaction::new_with_type(TransactionType::Transfer, signer.address("alice"), signer.address("bob"), GENX, 1_000, None, 0, 0).unwrap();
+    signer.account("alice").sign(&mut tx).unwrap();
+    engine.add_transaction(tx.clone()).unwrap();
+    let error = engine.add_transaction(tx).unwrap_err();
+    assert!(matches!(error, BlockchainError::Consensus { code: 2100, .. }), "{:?}", error);
+}
+
+/// Checks a call to no contract, a locked wallet and an unknown peer fail with their variants
+#[test]
+fn check_other_errors() {
+    let engine = ContractEngine::new(GasConfig::default());
+    let error = engine.call_static(NOWHERE, &[0; 4], &[], NOWHERE, 100_000, &State::new()).unwrap_err();
+    assert!(matches!(&error, ContractError::ContractNotFound { address } if address == NOWHERE), "{:?}", error);
+    assert_eq!(error.error_code(), 3010);
+    
+    let path = std::env::temp_dir().join(format!("genx-errors-{}.json", std::process::id()));
+    let api = WalletApi::create_wallet(path.clone(), PASSWORD).unwrap();
+    api.create_account("holder").unwrap();
+    api.lock().unwrap();
+    let error = api.create_account("locked").unwrap_err();
+    assert!(matches!(error, WalletError::Locked), "{:?}", error);
+    assert_eq!(error.error_code(), 4010);
+    let error = api.unlock("Not-the-password-1").unwrap_err();
+    assert!(matches!(error, WalletError::IncorrectPassword), "{:?}", error);
+    assert_eq!(error.error_code(), 4011);
+    api.unlock(PASSWORD).unwrap();
+    let error = api.create_payment_request(NOWHERE, GENX, "order-1").unwrap_err();
+    assert!(matches!(&error, WalletError::UnknownAccount { address } if address == NOWHERE), "{:?}", error);
+    assert_eq!(error.error_code(), 4012);
+    let _ = std::fs::remove_file(&path);
+    
+    let network = NetworkManager::new(NetworkConfig::default());
+    let error = network.disconnect_peer("nobody").unwrap_err();
+    assert!(matches!(&error, NetworkError::UnknownPeer { node_id } if node_id == "nobody"), "{:?}", error);
+    assert_eq!(error.error_code(), 5006);
+}
+
+/// Checks the RPC reports the code of the error behind a failure
+#[test]
+fn check_rpc_error_code() {
+    let sim = Simulation::new(SimConfig { nodes: 1, validators: 1, ..SimConfig::default() }).unwrap();
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "genx_getBlockWithReceipts", "params": [1_000] });
+    let response = sim.node(0).rpc_handler().handle_request(&request);
+    assert_eq!(response["error"]["data"]["errorCode"], json!(1008), "{}", response);
+}
\ No newline at end of file
@@ -0,0 +1,211 @@
+//! Checks the `eth_` JSON-RPC methods with the requests Ethereum clients send
+//!
+//! Run with `cargo test -p node --features testutil --test eth`. Builds a
+//! chain where alice creates a token and sends bob some, then sends the
+//! node the requests in `tests/fixtures/eth_requests.json`, shaped the way
+//! ethers.js, web3.js, viem and MetaMask send them, and checks the answers
+//! use the hex quantities, wei amounts and error codes those clients expect.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::eth_transaction::WEI_PER_UNIT;
+use ctb_core::transaction::{to_evm_address, Transaction, TransactionType};
+use ctb_core::Address;
+
+use node::eth::{EXECUTION_REVERTED, INVALID_PARAMS, METHOD_NOT_FOUND};
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+use smartcontracts::abi::{self, Value as AbiValue};
+use smartcontracts::evm::contract_address;
+use smartcontracts::token::{self, TokenParams, TOKEN_FACTORY_ADDRESS};
+use smartcontracts::u256::U256;
+use smartcontracts::{ContractEngine, GasConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 59;
+
+/// Tokens alice creates, and sends bob
+const SUPPLY: u64 = 1_000;
+const SENT: u64 = 100;
+
+/// A node over the chain, with what the fixtures' placeholders stand for
+struct EthChain {
+    node: Node,
+    placeholders: Vec<(&'static str, String)>,
+    transfer: Transaction,
+    token: String,
+    alice_balance: u64,
+}
+
+impl EthChain {
+    /// Builds the chain: alice creates the token in block 1 and sends bob some in block 2
+    fn new() -> Self {
+        let mut chain = TestChain::new(SEED);
+        chain.blockchain_mut().set_contract_executor(Arc::new(Mutex::new(ContractEngine::new(GasConfig::default()))));
+        let params = TokenParams { name: "Test Token".to_string(), symbol: "TST".to_string(), decimals: 0, initial_supply: U256::from(SUPPLY) };
+        let factory = contract_address(&TOKEN_FACTORY_ADDRESS);
+        let block = chain.next_block(|b| b.call("alice", &factory, params.to_call_data().unwrap()));
+        let token = contract_call(&block.transactions).contract_address();
+        chain.add_block(block);
+        
+        let (alice, bob) = (chain.address("alice"), chain.address("bob"));
+        let transfer_to_bob = call_data(token::TRANSFER_SELECTOR, &[address(&bob), AbiValue::Uint(U256::from(SENT))]);
+        let block = chain.next_block(|b| b.call("alice", &token, transfer_to_bob));
+        let transfer = contract_call(&block.transactions).clone();
+        chain.add_block(block);
+        
+        let word = |account: &str| format!("0x{}", hex::encode(U256::from_be_slice(&to_evm_address(account)).to_be_bytes()));
+        let placeholders = vec![
+            ("$TRANSFER_TOO_MUCH", hex_data(&call_data(token::TRANSFER_SELECTOR, &[address(&alice), AbiValue::Uint(U256::from(SENT + 1))]))),
+            ("$TRANSFER_TO_CAROL", hex_data(&call_data(token::TRANSFER_SELECTOR, &[address(&chain.address("carol")), AbiValue::Uint(U256::from(SENT))]))),
+            ("$TRANSFER_TOPIC", hex_data(&abi::keccak256(b"Transfer(address,address,uint256)"))),
+            ("$TRANSFER_TX", hex_data(transfer.id.as_ref())),
+            ("$APPROVAL_TOPIC", hex_data(&abi::keccak256(b"Approval(address,address,uint256)"))),
+            ("$BALANCE_OF_BOB", hex_data(&call_data(token::BALANCE_OF_SELECTOR, &[address(&bob)]))),
+            ("$BOB_TOPIC", word(&bob)),
+            ("$TOKEN", hex_data(&to_evm_address(&token))),
+            ("$ALICE", alice.clone()),
+            ("$BOB", bob),
+        ];
+        let alice_balance = chain.blockchain().get_state().lock().unwrap().get_balance(&Address::new(alice.as_str()).unwrap()).base_units();
+        let config = NodeConfig { rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() }, ..NodeConfig::default() };
+        Self { node: Node::new(config, chain.into_blockchain()), placeholders, transfer, token, alice_balance }
+    }
+    
+    /// Sends the node a fixture request, returning the response
+    fn send(&self, name: &str) -> Value {
+        let mut requests = std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/eth_requests.json")).unwrap();
+        for (placeholder, value) in &self.placeholders {
+            requests = requests.replace(placeholder, value);
+        }
+        let requests: HashMap<String, Value> = serde_json::from_str(&requests).unwrap();
+        self.node.eth_api().handle_request(&requests[name])
+    }
+    
+    /// Sends a fixture request that should succeed, returning its result
+    fn result(&self, name: &str) -> Value {
+        let response = self.send(name);
+        assert_eq!(response["jsonrpc"], json!("2.0"));
+        assert!(response.get("error").is_none(), "{} failed: {}", name, response);
+        response["result"].clone()
+    }
+    
+    /// Sends a fixture request that should fail, returning its error code
+    fn error_code(&self, name: &str) -> i64 {
+        let response = self.send(name);
+        assert!(response.get("result").is_none(), "{} succeeded: {}", name, response);
+        response["error"]["code"].as_i64().unwrap()
+    }
+}
+
+/// Finds the contract call among a block's transactions
+fn contract_call(transactions: &[Transaction]) -> &Transaction {
+    transactions.iter().find(|tx| tx.tx_type == TransactionType::ContractCall).unwrap()
+}
+
+/// Builds the call data of an ERC-20 function
+fn call_data(selector: [u8; 4], args: &[AbiValue]) -> Vec<u8> {
+    let function = token::erc20_functions().into_iter().find(|function| function.signature == selector).unwrap();
+    let mut data = selector.to_vec();
+    data.extend(abi::encode(&function.inputs, args).unwrap());
+    data
+}
+
+/// Gets an account's EVM address as an argument
+fn address(account: &str) -> AbiValue {
+    AbiValue::Address(to_evm_address(account))
+}
+
+/// Formats bytes as `0x`-prefixed hex
+fn hex_data(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Formats a number as a hex quantity
+fn quantity(value: impl Into<u128>) -> Value {
+    json!(format!("{:#x}", value.into()))
+}
+
+/// Checks the chain ID, height, balances and blocks
+#[test]
+fn check_chain() {
+    let chain = EthChain::new();
+    let chain_id = NodeConfig::default().chain_id;
+    assert_eq!(chain.result("ethers_chain_id"), quantity(chain_id));
+    assert_eq!(chain.result("web3_net_version"), json!(chain_id.to_string()));
+    assert_eq!(chain.result("ethers_block_number"), quantity(2u64));
+    
+    assert_eq!(chain.result("ethers_get_balance"), quantity(u128::from(chain.alice_balance) * WEI_PER_UNIT));
+    
+    let block = chain.result("web3_get_block");
+    assert_eq!(block["number"], quantity(2u64));
+    let transactions = block["transactions"].as_array().unwrap();
+    let transfer = transactions.iter().find(|tx| tx["hash"] == json!(hex_data(chain.transfer.id.as_ref()))).unwrap();
+    assert_eq!(transfer["blockNumber"], quantity(2u64));
+    assert_eq!(transfer["input"], json!(hex_data(chain.transfer.data.as_deref().unwrap())));
+    
+    let latest = chain.result("ethers_get_block");
+    assert_eq!((&latest["hash"], &latest["number"]), (&block["hash"], &block["number"]));
+    assert!(latest["transactions"].as_array().unwrap().iter().all(Value::is_string), "hashes only: {}", latest);
+}
+
+/// Checks a mined transaction, its receipt and the logs it left
+#[test]
+fn check_transaction() {
+    let chain = EthChain::new();
+    let hash = json!(hex_data(chain.transfer.id.as_ref()));
+    let tx = chain.result("ethers_get_transaction");
+    assert_eq!((&tx["hash"], &tx["blockNumber"], &tx["nonce"]), (&hash, &quantity(2u64), &quantity(1u64)));
+    assert_eq!(tx["to"], json!(hex_data(&to_evm_address(&chain.token))));
+    
+    let receipt = chain.result("ethers_get_receipt");
+    assert_eq!((&receipt["transactionHash"], &receipt["status"]), (&hash, &json!("0x1")));
+    assert_eq!(receipt["logs"].as_array().unwrap().len(), 1);
+    let log = &receipt["logs"][0];
+    assert_eq!(log["data"], json!(hex_data(&U256::from(SENT).to_be_bytes())));
+    
+    // Both filters find the transfer to bob, and nothing else
+    for name in ["ethers_get_logs", "web3_get_logs"] {
+        let logs = chain.result(name);
+        assert_eq!(logs.as_array().unwrap().len(), 1, "{}: {}", name, logs);
+        assert_eq!((&logs[0]["transactionHash"], &logs[0]["topics"]), (&hash, &log["topics"]));
+    }
+}
+
+/// Checks calls and gas estimates, and that reverts carry their reason
+#[test]
+fn check_calls() {
+    let chain = EthChain::new();
+    let balance = json!(hex_data(&U256::from(SENT).to_be_bytes()));
+    assert_eq!(chain.result("ethers_call"), balance);
+    assert_eq!(chain.result("viem_call"), balance);
+    
+    // Carol's first tokens cost what bob's did, plus the margin
+    let number = |value: Value| u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+    let gas = number(chain.result("ethers_estimate_gas"));
+    let gas_used = number(chain.result("ethers_get_receipt")["gasUsed"].clone());
+    assert!(gas >= gas_used && gas <= gas_used + gas_used * smartcontracts::DEFAULT_GAS_ESTIMATE_MARGIN / 100, "{} for {}", gas, gas_used);
+    
+    let response = chain.send("ethers_estimate_reverting");
+    assert_eq!(response["error"]["code"], json!(EXECUTION_REVERTED));
+    assert!(response["error"]["message"].as_str().unwrap().contains("ERC20: transfer amount exceeds balance"), "{}", response);
+}
+
+/// Checks unknown methods and malformed parameters get JSON-RPC error codes, and batches get a response each
+#[test]
+fn check_errors_and_batches() {
+    let chain = EthChain::new();
+    assert_eq!(chain.error_code("metamask_unsupported"), METHOD_NOT_FOUND);
+    assert_eq!(chain.error_code("ethers_bad_params"), INVALID_PARAMS);
+    
+    let responses = chain.send("viem_batch");
+    let responses = responses.as_array().unwrap();
+    assert_eq!(responses.len(), 2);
+    assert_eq!((&responses[0]["id"], &responses[1]["id"]), (&json!(17), &json!(18)));
+    assert_eq!(responses[1]["result"], quantity(2u64));
+}
\ No newline at end of file
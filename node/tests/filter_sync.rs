@@ -0,0 +1,91 @@
+//! Checks a watch-only wallet syncing by block filters fetches only the blocks touching its address
+//!
+//! Run with `cargo test -p node --features testutil --test filter_sync`.
+//! Builds a chain of 200 blocks, each holding transfers between other
+//! accounts, where only the genesis block and two others touch dave. A
+//! `FilterSync` of dave's address against a node of the chain downloads
+//! every filter but fetches about three full blocks, the false positives
+//! among them holding nothing, and ends with dave's history and balance.
+//! Syncing again after more blocks downloads only their filters.
+
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::units::GENX;
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+use wallet::filter_sync::FilterSync;
+
+/// Seed of the chain built
+const SEED: u64 = 179;
+
+/// Blocks of the chain, the genesis block included
+const BLOCKS: u64 = 200;
+
+/// Heights of the blocks after the genesis block touching dave
+const RELEVANT: [u64; 2] = [50, 150];
+
+/// Most blocks whose filters may match without touching dave
+const MAX_FALSE_POSITIVES: u64 = 2;
+
+/// Configures the default accounts and dave
+fn config() -> TestChainConfig {
+    let mut config = TestChainConfig::default();
+    config.accounts.push(("dave".to_string(), 100 * GENX));
+    config
+}
+
+/// Adds a block of transfers, touching dave at the relevant heights only
+fn add_block(chain: &mut TestChain) {
+    let height = chain.height() + 1;
+    chain.with_block(|b| {
+        b.transfer("alice", "bob", GENX).transfer("bob", "carol", GENX);
+        match RELEVANT.iter().position(|relevant| *relevant == height) {
+            Some(0) => b.transfer("alice", "dave", 10 * GENX),
+            Some(_) => b.transfer("dave", "carol", 5 * GENX),
+            None => b,
+        }
+    });
+}
+
+/// Checks syncing by filters fetches the blocks touching the address and few others, and finds its history
+#[test]
+fn check_fetches_relevant() {
+    let mut chain = TestChain::with_config(SEED, config());
+    while chain.height() + 1 < BLOCKS {
+        add_block(&mut chain);
+    }
+    let dave = chain.address("dave");
+    let config = NodeConfig {
+        data_dir: std::env::temp_dir().join(format!("genx-filter-sync-{}", std::process::id())).display().to_string(),
+        rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+        ..NodeConfig::default()
+    };
+    let blocks: Vec<_> = chain.blocks().into_iter().skip(1).cloned().collect();
+    let node = Node::new(config.clone(), TestChain::with_config(SEED, self::config()).into_blockchain());
+    for block in blocks {
+        node.import_block(block).unwrap();
+    }
+    
+    let mut sync = FilterSync::new([dave.clone()]);
+    assert_eq!(sync.sync(node.filter_client().as_ref()).unwrap(), 3);
+    let stats = sync.stats();
+    assert_eq!(stats.filters_downloaded, BLOCKS);
+    assert_eq!(stats.blocks_fetched - stats.false_positives, 3);
+    assert!(stats.false_positives <= MAX_FALSE_POSITIVES, "{:?}", stats);
+    assert_eq!(stats.restarts, 0);
+    
+    let heights: Vec<u64> = sync.history(10).iter().map(|record| record.block_height).collect();
+    assert_eq!(heights, [RELEVANT[1], RELEVANT[0], 0]);
+    assert_eq!(sync.synced_height(), Some(BLOCKS - 1));
+    assert_eq!(sync.balance(&dave), chain.expected_balance("dave"));
+    
+    // Syncing again downloads only the new blocks' filters
+    for _ in 0..5 {
+        add_block(&mut chain);
+        node.import_block(chain.blocks().last().copied().unwrap().clone()).unwrap();
+    }
+    assert_eq!(sync.sync(node.filter_client().as_ref()).unwrap(), 0);
+    assert_eq!(sync.stats().filters_downloaded, BLOCKS + 5);
+    assert_eq!(sync.synced_height(), Some(BLOCKS + 4));
+    
+    let _ = std::fs::remove_dir_all(&config.data_dir);
+}
\ No newline at end of file
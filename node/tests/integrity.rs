@@ -0,0 +1,100 @@
+//! Checks a node finding its tip block corrupt at startup refuses to start, or repairs to the block below and syncs again
+//!
+//! Run with `cargo test -p node --features testutil --test integrity`. Has a
+//! node save `BLOCKS` blocks to its data directory, then overwrites the tip
+//! block's file and the production journal with garbage. A node started on
+//! the directory without `--repair` refuses to. One repairing it loads the
+//! chain up to the block below the tip, reports the tip block discarded and
+//! the journal set aside, and takes the tip block again from a peer.
+
+use std::path::{Path, PathBuf};
+
+use ctb_core::block::Block;
+use ctb_core::block_store::BLOCKS_DIR;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::units::GENX;
+use node::integrity::{IssueKind, CORRUPT_EXTENSION};
+use node::journal::JOURNAL_FILE;
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+
+/// Seed of the chains built
+const SEED: u64 = 193;
+
+/// Blocks saved above the genesis block
+const BLOCKS: u64 = 5;
+
+/// A data directory, removed once the test is done
+struct DataDir(PathBuf);
+
+impl Drop for DataDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Creates a node on a fresh test chain using a data directory
+fn node(data_dir: &Path, repair: bool) -> Node {
+    let config = NodeConfig {
+        data_dir: data_dir.display().to_string(),
+        rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+        ..NodeConfig::default()
+    };
+    let mut node = Node::new(config, TestChain::new(SEED).into_blockchain());
+    if repair {
+        node.repair_data_dir();
+    }
+    node
+}
+
+/// Checks a corrupt tip block stops the node unless it repairs, which truncates to the block below so it can sync again
+#[test]
+fn check_repair_to_parent() {
+    let data_dir = DataDir(std::env::temp_dir().join(format!("genx-integrity-{}", std::process::id())));
+    let _ = std::fs::remove_dir_all(&data_dir.0);
+    let mut chain = TestChain::new(SEED);
+    let blocks: Vec<Block> = (0..BLOCKS).map(|_| {
+        let block = chain.next_block(|b| b.transfer("alice", "bob", GENX));
+        chain.add_block(block.clone());
+        block
+    }).collect();
+    {
+        let saving = node(&data_dir.0, false);
+        assert!(saving.open_data_dir().unwrap().is_clean());
+        for block in &blocks {
+            saving.import_block(block.clone()).unwrap();
+        }
+    }
+    let reopened = node(&data_dir.0, false);
+    let report = reopened.open_data_dir().unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+    assert_eq!(reopened.get_height(), BLOCKS);
+    
+    // The tip block and the journal are overwritten
+    std::fs::write(data_dir.0.join(BLOCKS_DIR).join(format!("{}.block", BLOCKS)), b"\x00garbage").unwrap();
+    let journal = data_dir.0.join(JOURNAL_FILE);
+    std::fs::write(&journal, b"{not json").unwrap();
+    
+    let error = node(&data_dir.0, false).open_data_dir().unwrap_err().to_string();
+    assert!(error.contains("--repair") && error.contains(&format!("block {}", BLOCKS - 1)), "{}", error);
+    
+    // Repairing keeps the blocks below the tip, and says what it discarded
+    let repaired = node(&data_dir.0, true);
+    let report = repaired.open_data_dir().unwrap();
+    assert_eq!(report.verified_height, BLOCKS - 1);
+    assert_eq!(repaired.get_height(), BLOCKS - 1);
+    let discarded: Vec<u64> = report.discarded_blocks.iter().map(|block| block.height).collect();
+    assert_eq!(discarded, [BLOCKS]);
+    assert!(report.issues.iter().any(|issue| issue.kind == IssueKind::Block && issue.height == Some(BLOCKS)), "{:?}", report.issues);
+    assert!(report.issues.iter().any(|issue| issue.kind == IssueKind::Journal), "{:?}", report.issues);
+    assert!(!journal.exists());
+    assert!(journal.with_extension(format!("json.{}", CORRUPT_EXTENSION)).exists());
+    
+    // The tip block is taken again, and the directory is clean from then on
+    repaired.import_block(blocks[BLOCKS as usize - 1].clone()).unwrap();
+    assert_eq!(repaired.get_height(), BLOCKS);
+    drop(repaired);
+    let restarted = node(&data_dir.0, false);
+    assert!(restarted.open_data_dir().unwrap().is_clean());
+    assert_eq!(restarted.get_height(), BLOCKS);
+}
\ No newline at end of file
@@ -0,0 +1,179 @@
+//! Checks a validator restarted after a crash never signs a second block at a height
+//!
+//! Run with `cargo test -p node --features testutil --test journal`. Has
+//! a validating node on the development chain (see `node::dev`) produce a
+//! block holding a transfer, then restarts it on its data directory with
+//! the saved blocks gone, as when the node crashed before saving the block.
+//! The restarted node adds the journaled block again, rather than producing
+//! an empty one of its own. When the crash came between journaling the
+//! block's hash and its body, the node produces nothing at that height
+//! until another validator's block fills it.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+
+use consensus::ConsensusParams;
+use ctb_core::block::Block;
+use ctb_core::block_store::BLOCKS_DIR;
+use ctb_core::transaction::{Transaction, TransactionType};
+use ctb_core::units::GENX;
+use ctb_core::BlockHash;
+
+use node::dev::{self, DEV_GENESIS_TIME};
+use node::journal::{ProductionJournal, JOURNAL_FILE};
+use node::network::NetworkConfig;
+use node::rpc::RpcConfig;
+use node::sim::VirtualClock;
+use node::{Node, NodeConfig};
+
+/// A data directory removed once the test is done
+struct DataDir(PathBuf);
+
+impl Drop for DataDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+impl DataDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("genx-journal-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+    
+    /// Removes the blocks saved, as if the node crashed before saving them
+    fn lose_blocks(&self) {
+        std::fs::remove_dir_all(self.0.join(BLOCKS_DIR)).unwrap();
+    }
+}
+
+/// A started validating node, stopped when dropped
+///
+/// Its clock stays at the genesis time, so it only produces blocks when told to.
+struct Running {
+    node: Node,
+    _runtime: Runtime,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.node.stop();
+    }
+}
+
+impl Running {
+    /// Starts the development validator on a data directory, from the development genesis block
+    fn start(data_dir: &DataDir) -> Self {
+        let config = NodeConfig {
+            is_validator: true,
+            validator_address: Some(dev::validator().address.to_string()),
+            data_dir: data_dir.0.display().to_string(),
+            rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+            network_config: NetworkConfig { listen_addrs: vec!["127.0.0.1:0".parse().unwrap()], ..NetworkConfig::default() },
+            ..NodeConfig::default()
+        };
+        let blockchain = dev::genesis(&config.consensus_params).unwrap();
+        let runtime = Runtime::new().unwrap();
+        let mut node = Node::new(config, blockchain);
+        node.set_clock(Arc::new(VirtualClock::new(DEV_GENESIS_TIME * 1000)));
+        node.set_signer(Arc::new(dev::signer()));
+        runtime.block_on(node.start()).unwrap();
+        Self { node, _runtime: runtime }
+    }
+    
+    /// Produces the block at a height, in its slot
+    fn produce(&self, height: u64) -> Option<Arc<Block>> {
+        let slot = ConsensusParams::default().block_time;
+        self.node.try_produce_block_at(DEV_GENESIS_TIME + height * slot).unwrap()
+    }
+    
+    /// Gets the block at a height as JSON, or null if there's none
+    fn block(&self, height: u64) -> Value {
+        let response = self.node.rpc_handler().handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": "genx_getBlockWithReceipts", "params": [height] }));
+        response["result"]["block"].clone()
+    }
+    
+    /// Gets the height of the node's latest block
+    fn height(&self) -> u64 {
+        self.node.wallet_client().chain_tip().unwrap().height
+    }
+}
+
+/// Makes a transfer between development accounts, signed
+fn transfer() -> Transaction {
+    let accounts = dev::accounts();
+    let (sender, recipient) = (&accounts[0], &accounts[1]);
+    let tx = Transaction::new_with_type(TransactionType::Transfer, sender.address.to_string(), recipient.address.to_string(), GENX, GENX / 100, None, 0, 0);
+    let mut tx = tx.unwrap();
+    tx.timestamp = DEV_GENESIS_TIME;
+    tx.id = tx.calculate_hash().unwrap();
+    sender.sign(&mut tx).unwrap();
+    tx
+}
+
+/// Checks a node restarted without its saved blocks adds the very block it produced again
+#[test]
+fn check_resumed_block() {
+    let data_dir = DataDir::new("resumed");
+    let tx = transfer();
+    let produced = {
+        let mut running = Running::start(&data_dir);
+        running.node.add_transaction(tx.clone()).unwrap();
+        running.produce(1).unwrap();
+        running.block(1)
+    };
+    assert!(produced["transactions"].as_array().unwrap().iter().any(|included| included["id"] == json!(tx.id)), "{}", produced);
+    
+    // The journal brings the block back on start, in place of an empty one
+    data_dir.lose_blocks();
+    let running = Running::start(&data_dir);
+    assert_eq!(running.height(), 1);
+    assert_eq!(running.block(1), produced);
+    assert!(running.produce(1).is_none());
+    
+    // Production carries on above it, and another restart changes nothing
+    let next = running.produce(2).unwrap();
+    assert_eq!(next.header().height, 2);
+    drop(running);
+    
+    // Again, and the next block comes back when it would be produced
+    data_dir.lose_blocks();
+    let running = Running::start(&data_dir);
+    assert_eq!(running.block(1), produced);
+    assert_eq!(running.produce(3).unwrap().hash().unwrap(), next.hash().unwrap());
+    assert_eq!(running.height(), 2);
+}
+
+/// Checks a node that crashed after journaling only a block's hash produces nothing at its height until another block fills it
+#[test]
+fn check_hash_only() {
+    let other = DataDir::new("other");
+    let filler = {
+        let mut running = Running::start(&other);
+        running.node.add_transaction(transfer()).unwrap();
+        running.produce(1).unwrap()
+    };
+    
+    let data_dir = DataDir::new("hash-only");
+    let mut journal = ProductionJournal::new();
+    journal.open(data_dir.0.join(JOURNAL_FILE)).unwrap();
+    journal.record_hash(1, BlockHash([0x5a; 32])).unwrap();
+    drop(journal);
+    
+    let running = Running::start(&data_dir);
+    for slot in 1..4 {
+        assert!(running.produce(slot).is_none(), "nothing is signed at a height journaled without its block");
+    }
+    assert_eq!(running.height(), 0);
+    assert!(running.block(1).is_null());
+    
+    // Once another validator's block fills the height, production resumes above it
+    running.node.import_block(filler.clone()).unwrap();
+    assert_eq!(running.block(1), serde_json::to_value(filler.as_ref()).unwrap());
+    assert_eq!(running.produce(2).unwrap().header().height, 2);
+}
\ No newline at end of file
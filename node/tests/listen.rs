@@ -0,0 +1,124 @@
+//! Checks a node listens on IPv4 and IPv6 at once, and advertises its external address to peers
+//!
+//! Run with `cargo test -p node --test listen`. Starts a network manager
+//! listening on `127.0.0.1` and `[::1]` with an external address, and
+//! checks connections reach both listeners, peers connecting to either are
+//! recorded with the one they reached, and the only address the node
+//! answers `GetPeers` with for itself is the external one. Then checks a node without an external address
+//! advertises its bound addresses until two peers see it at the same one,
+//! and that a start fails if any listening address can't be bound.
+
+use std::net::{SocketAddr, TcpStream};
+
+use tokio::runtime::Runtime;
+
+use ctb_core::BlockHash;
+use node::address_book;
+use node::message::HandshakeData;
+use node::network::{HandshakeOutcome, NetworkConfig, NetworkManager};
+
+/// Address the node is dialed at from outside, as a NAT maps it
+const EXTERNAL_ADDR: &str = "203.0.113.5:30303";
+
+/// Public address peers see the node without an external address at
+const OBSERVED_IP: &str = "198.51.100.7";
+
+/// Parses an address
+fn addr(addr: &str) -> SocketAddr {
+    addr.parse().unwrap()
+}
+
+/// Starts a network manager listening on the loopback addresses of both families, on ports of their own
+fn start(runtime: &Runtime, external_addr: Option<SocketAddr>) -> NetworkManager {
+    let config = NetworkConfig {
+        node_id: "node".to_string(),
+        listen_addrs: vec![addr("127.0.0.1:0"), addr("[::1]:0")],
+        external_addr,
+        ..NetworkConfig::default()
+    };
+    let mut network = NetworkManager::new(config);
+    runtime.block_on(network.start()).unwrap();
+    network
+}
+
+/// Has a peer connect in to a listening address, seeing the node at `observed_addr`
+fn connect_in(network: &NetworkManager, peer: &str, from: SocketAddr, local_addr: SocketAddr, observed_addr: SocketAddr) {
+    assert!(network.open_connection(from, false));
+    let handshake = HandshakeData {
+        node_id: peer.to_string(),
+        height: 0,
+        best_hash: BlockHash::default(),
+        timestamp: 0,
+        nonce: 0,
+        listen_addrs: vec![from],
+        observed_addr: Some(observed_addr),
+    };
+    let outcome = network.accept_handshake(from, Some(local_addr), false, &handshake);
+    assert!(matches!(outcome, HandshakeOutcome::Accepted { .. }), "{:?}", outcome);
+}
+
+/// Gets the addresses the node answers `GetPeers` with for itself, checking they're signed
+fn advertised(network: &NetworkManager) -> Vec<SocketAddr> {
+    network.announcements().iter().map(|announcement| {
+        address_book::verify(announcement).unwrap();
+        assert_eq!(announcement.node_id, network.identity().node_id());
+        announcement.addr
+    }).collect()
+}
+
+/// Checks both listeners take connections, and only the external address is advertised
+#[test]
+fn check_dual_stack() {
+    let runtime = Runtime::new().unwrap();
+    let network = start(&runtime, Some(addr(EXTERNAL_ADDR)));
+    let bound = network.listen_addrs().to_vec();
+    assert_eq!(bound.len(), 2);
+    let (v4, v6) = (bound[0], bound[1]);
+    assert!(v4.is_ipv4() && v4.ip().is_loopback() && v4.port() != 0, "{}", v4);
+    assert!(v6.is_ipv6() && v6.ip().is_loopback() && v6.port() != 0, "{}", v6);
+    for listen_addr in [v4, v6] {
+        TcpStream::connect(listen_addr).unwrap();
+    }
+    
+    // Each peer is known by the listener it reached, and seeing the node elsewhere changes nothing
+    connect_in(&network, "peer-v4", addr("127.0.0.1:40001"), v4, addr("127.0.0.1:40000"));
+    connect_in(&network, "peer-v6", addr("[::1]:40001"), v6, addr("[::1]:40000"));
+    let mut peers = Vec::new();
+    network.for_each_peer(|peer| peers.push((peer.node_id.clone(), peer.local_addr)));
+    peers.sort();
+    assert_eq!(peers, [("peer-v4".to_string(), Some(v4)), ("peer-v6".to_string(), Some(v6))]);
+    assert_eq!(network.observed_addr(), None);
+    
+    assert_eq!(advertised(&network), [addr(EXTERNAL_ADDR)]);
+    assert_eq!(network.handshake(0, BlockHash::default()).listen_addrs, [addr(EXTERNAL_ADDR)]);
+}
+
+/// Checks a node without an external address advertises its bound addresses, then the one two peers see it at
+#[test]
+fn check_observed() {
+    let runtime = Runtime::new().unwrap();
+    let network = start(&runtime, None);
+    let bound = network.listen_addrs().to_vec();
+    assert_eq!(advertised(&network), bound);
+    
+    // One peer isn't enough, a second seeing the same address is
+    let observed_addr = SocketAddr::new(OBSERVED_IP.parse().unwrap(), 1);
+    connect_in(&network, "peer-1", addr("198.51.100.20:40001"), bound[0], observed_addr);
+    assert_eq!(advertised(&network), bound);
+    connect_in(&network, "peer-2", addr("198.51.100.21:40001"), bound[0], observed_addr);
+    assert_eq!(advertised(&network), [SocketAddr::new(observed_addr.ip(), bound[0].port())]);
+}
+
+/// Checks a start fails if a listening address is taken
+#[test]
+fn check_bind_fails() {
+    let runtime = Runtime::new().unwrap();
+    let network = start(&runtime, None);
+    let config = NetworkConfig {
+        node_id: "other".to_string(),
+        listen_addrs: vec![addr("127.0.0.1:0"), network.listen_addrs()[1]],
+        ..NetworkConfig::default()
+    };
+    let mut other = NetworkManager::new(config);
+    assert!(runtime.block_on(other.start()).is_err());
+}
\ No newline at end of file
@@ -0,0 +1,187 @@
+//! Checks every network message round-trips and bad frames are refused
+//!
+//! Run with `cargo test -p node --features testutil --test messages`.
+//! Builds one message of each kind, `Unknown` included, and checks its
+//! frame decodes to the same message under the same name. Then checks
+//! frames of another version, cut short or over their message's cap are
+//! refused, and that random payloads under every tag never panic the
+//! decoder.
+
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::Range;
+use std::sync::Arc;
+
+use consensus::finality::FinalityVote;
+use ctb_core::block_filter::BlockFilter;
+use ctb_core::state_sync::{SnapshotInfo, SnapshotManifest};
+use ctb_core::testutil::Generator;
+use ctb_core::{BlockHash, Bytes, TxHash};
+use node::message::{
+    AddressAnnouncement, ChainStatus, DecodeError, DisconnectReason, HandshakeData, NetworkMessage, PingData,
+    MAX_HEADERS, MAX_LOCATOR, MAX_PEERS, PROTOCOL_VERSION,
+};
+use node::testutil;
+
+/// Seed of the generator making the blocks, transactions and random payloads
+const SEED: u64 = 67;
+
+/// Number of kinds of message, `Unknown` included
+const MESSAGE_KINDS: usize = 31;
+
+/// Tag no message of this version has
+const UNKNOWN_TAG: u8 = 0xee;
+
+/// Random payloads tried under each tag
+const RANDOM_PAYLOADS: usize = 8;
+
+/// Longest random payload tried
+const RANDOM_PAYLOAD_LEN: usize = 256;
+
+/// Largest payload of a ping, as of any control message
+const CONTROL_LIMIT: usize = 4 * 1024;
+
+/// Makes one message of each kind
+fn messages(generator: &mut Generator) -> Vec<NetworkMessage> {
+    let addr = SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 30303));
+    let addr6 = SocketAddr::from((Ipv6Addr::LOCALHOST, 30304));
+    vec![
+        NetworkMessage::Handshake(HandshakeData {
+            node_id: "GENX_NODE".to_string(),
+            height: 42,
+            best_hash: BlockHash([0x11; 32]),
+            timestamp: 1_700_000_000,
+            nonce: 7,
+            listen_addrs: vec![addr, addr6],
+            observed_addr: Some(addr),
+        }),
+        NetworkMessage::Ping(PingData { nonce: 1 }),
+        NetworkMessage::Pong(PingData { nonce: 1 }),
+        NetworkMessage::GetPeers,
+        NetworkMessage::Peers(vec![addr, addr6]),
+        NetworkMessage::Addresses(vec![AddressAnnouncement {
+            node_id: "GENX_NODE".to_string(),
+            addr,
+            timestamp: 1_700_000_000,
+            signature: Bytes(vec![0x22; 64]),
+        }]),
+        NetworkMessage::Disconnect(DisconnectReason::Banned),
+        NetworkMessage::Status(ChainStatus { height: 42, best_hash: BlockHash([0x11; 32]), weight: u128::MAX }),
+        NetworkMessage::NewBlock(Arc::new(generator.block())),
+        NetworkMessage::GetBlock(BlockHash([0x33; 32])),
+        NetworkMessage::Block(Arc::new(generator.block())),
+        NetworkMessage::GetHeaders(10..10 + MAX_HEADERS),
+        NetworkMessage::Headers(vec![generator.block().header().clone(), generator.block().header().clone()]),
+        NetworkMessage::GetBranchHeaders(vec![(9, BlockHash([0x44; 32])), (1, BlockHash([0x55; 32]))]),
+        NetworkMessage::GetReceipts(42),
+        NetworkMessage::Receipts { height: 42, receipts: vec![generator.receipt(), generator.receipt()] },
+        NetworkMessage::GetFilterHeaders(0..100),
+        NetworkMessage::FilterHeaders { start: 100, headers: vec![[0x66; 32], [0x77; 32]] },
+        NetworkMessage::GetFilters(0..100),
+        NetworkMessage::Filters {
+            start: 100,
+            filters: vec![BlockFilter { block_hash: BlockHash([0x88; 32]), items: 3, data: Bytes(vec![0x99; 12]) }],
+        },
+        NetworkMessage::NewTransaction(Arc::new(generator.transaction())),
+        NetworkMessage::GetTransaction(TxHash([0xaa; 32])),
+        NetworkMessage::Transaction(Arc::new(generator.transaction())),
+        NetworkMessage::CheckpointVote(FinalityVote {
+            height: 100,
+            block_hash: BlockHash([0xbb; 32]),
+            validator: "GENX_VALIDATOR".to_string(),
+            signature: Bytes(vec![0xcc; 64]),
+        }),
+        NetworkMessage::GetSnapshots,
+        NetworkMessage::Snapshots(vec![SnapshotInfo {
+            height: 1000,
+            block_hash: BlockHash([0xdd; 32]),
+            root: [0xee; 32],
+            chunk_count: 4,
+        }]),
+        NetworkMessage::GetSnapshotManifest(1000),
+        NetworkMessage::SnapshotManifest(SnapshotManifest {
+            height: 1000,
+            block_hash: BlockHash([0xdd; 32]),
+            chunk_hashes: vec![[0x01; 32], [0x02; 32]],
+        }),
+        NetworkMessage::GetSnapshotChunk { height: 1000, index: u32::MAX },
+        NetworkMessage::SnapshotChunk { height: 1000, index: 3, data: Bytes(vec![0xff; 1024]) },
+        NetworkMessage::Unknown { tag: UNKNOWN_TAG, payload: Bytes(vec![1, 2, 3]) },
+    ]
+}
+
+/// Checks a message of every kind decodes from its frame to the same message
+#[test]
+fn check_every_kind() {
+    let messages = messages(&mut Generator::new(SEED));
+    let kinds: HashSet<_> = messages.iter().map(NetworkMessage::kind).collect();
+    assert_eq!(kinds.len(), MESSAGE_KINDS);
+    
+    for message in &messages {
+        let frame = message.encode();
+        assert_eq!(frame[0], PROTOCOL_VERSION);
+        let decoded = testutil::assert_roundtrip_frame(message);
+        assert_eq!(decoded.kind(), message.kind());
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", message));
+    }
+}
+
+/// Checks a frame with an unknown tag is kept whole, and frames of another version or cut short are refused
+#[test]
+fn check_unknown_and_truncated() {
+    let frame = [vec![PROTOCOL_VERSION, UNKNOWN_TAG], b"from a newer node".to_vec()].concat();
+    match NetworkMessage::decode(&frame).unwrap() {
+        NetworkMessage::Unknown { tag, payload } => {
+            assert_eq!(tag, UNKNOWN_TAG);
+            assert_eq!(payload.0, b"from a newer node");
+        }
+        message => panic!("Unknown tag decoded to a {} message", message.kind()),
+    }
+    
+    let mut frame = NetworkMessage::Ping(PingData { nonce: 1 }).encode();
+    frame[0] = PROTOCOL_VERSION + 1;
+    assert_eq!(NetworkMessage::decode(&frame).unwrap_err(), DecodeError::UnsupportedVersion(PROTOCOL_VERSION + 1));
+    
+    assert_eq!(NetworkMessage::decode(&[]).unwrap_err(), DecodeError::Truncated);
+    assert_eq!(NetworkMessage::decode(&[PROTOCOL_VERSION]).unwrap_err(), DecodeError::Truncated);
+}
+
+/// Checks payloads over their message's cap, and lists and ranges over theirs, are refused
+#[test]
+fn check_caps() {
+    let ping = NetworkMessage::Ping(PingData { nonce: 1 }).encode();
+    let frame = [&ping[..2], &vec![0; CONTROL_LIMIT + 1]].concat();
+    assert_eq!(
+        NetworkMessage::decode(&frame).unwrap_err(),
+        DecodeError::TooLarge { kind: "ping", size: CONTROL_LIMIT + 1, limit: CONTROL_LIMIT }
+    );
+    
+    let too_many = [
+        NetworkMessage::GetHeaders(0..MAX_HEADERS + 1),
+        NetworkMessage::Peers(vec![SocketAddr::from((Ipv4Addr::LOCALHOST, 30303)); MAX_PEERS + 1]),
+        NetworkMessage::GetBranchHeaders(vec![(0, BlockHash::default()); MAX_LOCATOR + 1]),
+    ];
+    for message in too_many {
+        match NetworkMessage::decode(&message.encode()) {
+            Err(DecodeError::Invalid { kind, .. }) => assert_eq!(kind, message.kind()),
+            result => panic!("Over-long {} message decoded: {:?}", message.kind(), result.map(|m| m.kind())),
+        }
+    }
+    
+    let backwards = NetworkMessage::GetFilters(Range { start: 100, end: 10 }).encode();
+    assert!(matches!(NetworkMessage::decode(&backwards), Err(DecodeError::Invalid { kind: "get filters", .. })));
+}
+
+/// Checks random payloads under every tag decode or fail without panicking
+#[test]
+fn check_random_payloads() {
+    let mut generator = Generator::new(SEED);
+    for tag in 0..=u8::MAX {
+        for _ in 0..RANDOM_PAYLOADS {
+            let frame = [vec![PROTOCOL_VERSION, tag], generator.bytes(RANDOM_PAYLOAD_LEN)].concat();
+            if let Ok(message) = NetworkMessage::decode(&frame) {
+                assert_eq!(message.encode()[..2], frame[..2]);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+//! Checks a simulated network heals after a partition, never past finality
+//!
+//! Run with `cargo test -p node --features testutil --test partition`.
+//! Splits four simulated validators 2/2 and heals them, checking every
+//! node ends up on the heavier half's chain, then checks a peer offering a
+//! chain that reverts a finalized checkpoint is banned.
+
+use node::testutil;
+
+/// Checks every node converges on the heavier half's chain once healed
+#[test]
+fn check_partition_recovery() {
+    testutil::check_partition_recovery();
+}
+
+/// Checks a peer claiming a heavier chain below the finalized checkpoint is banned
+#[test]
+fn check_finality_ban() {
+    testutil::check_finality_ban();
+}
\ No newline at end of file
@@ -0,0 +1,106 @@
+//! Checks nodes time how blocks reach them and credit the peers that deliver them first
+//!
+//! Run with `cargo test -p node --features testutil --test propagation`.
+//! Runs simulated nodes, the first the only validator, on a line of fast
+//! links with slow ones alongside, so each block reaches every node fastest
+//! through its neighbour on the line. Checks each node's timeline of a block
+//! has it received, validated and added in that order, some milliseconds
+//! after it was produced, first from that neighbour, and that the metrics
+//! credit the neighbour with every block. Then checks the timelines aren't
+//! served over RPC unless the debug methods are enabled.
+
+use serde_json::{json, Value};
+
+use ctb_core::block::Block;
+use ctb_core::BlockHash;
+use node::propagation::BlockTimeline;
+use node::sim::{self, SimConfig, Simulation};
+
+/// Milliseconds a message takes over a fast link, between neighbours on the line
+const FAST: u64 = 40;
+
+/// Milliseconds a message takes over a slow link, between nodes further apart
+const SLOW: u64 = 600;
+
+/// Height of the block whose timelines are checked
+const HEIGHT: u64 = 2;
+
+/// Runs four nodes on the line 0-1-2-3 until every one has the block after `HEIGHT`
+fn simulation() -> Simulation {
+    let mut sim = Simulation::new(SimConfig { nodes: 4, validators: 1, latency: SLOW, ..SimConfig::default() }).unwrap();
+    for node in 0..3 {
+        sim.set_latency(node, node + 1, FAST);
+    }
+    let done = sim.run_until(60_000, |sim| sim.heights().iter().all(|height| *height > HEIGHT));
+    assert!(done, "{:?}", sim.heights());
+    sim
+}
+
+/// Gets the hash of the block at `HEIGHT`
+fn block_hash(sim: &Simulation) -> BlockHash {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "genx_getBlockWithReceipts", "params": [HEIGHT] });
+    let response = sim.node(0).rpc_handler().handle_request(&request);
+    let block: Block = serde_json::from_value(response["result"]["block"].clone()).unwrap();
+    block.hash().unwrap()
+}
+
+/// Checks each node's timeline has the block first from its neighbour, some milliseconds after it was produced
+#[test]
+fn check_timelines() {
+    let sim = simulation();
+    let hash = block_hash(&sim);
+    let timelines: Vec<BlockTimeline> = (0..4).map(|node| sim.node(node).propagation_timeline(&hash).unwrap()).collect();
+    
+    // The producer sent it to every peer, and what they relayed back doesn't count as seen first
+    let produced_at = timelines[0].produced_at.unwrap();
+    assert_eq!(timelines[0].first_seen_from(), None);
+    let sent: Vec<&str> = timelines[0].sent.iter().map(|sent| sent.peer.as_str()).collect();
+    assert_eq!(sent, vec![sim::node_id(1), sim::node_id(2), sim::node_id(3)]);
+    assert!(produced_at >= timelines[0].block_time, "{:?}", timelines[0]);
+    
+    let mut previous = produced_at;
+    for (node, timeline) in timelines.iter().enumerate().skip(1) {
+        assert_eq!((timeline.height, timeline.produced_at), (HEIGHT, None));
+        assert_eq!(timeline.first_seen_from(), Some(sim::node_id(node - 1).as_str()), "{:?}", timeline);
+        
+        // Each hop down the line takes at least a fast link, and less than the slow one straight from the producer
+        let received_at = timeline.received[0].at;
+        assert!(received_at >= previous + FAST, "{} after {}", received_at, previous);
+        assert!(received_at - produced_at < SLOW * node as u64, "{:?}", timeline);
+        let (validated_at, applied_at) = (timeline.validated_at.unwrap(), timeline.applied_at.unwrap());
+        assert!(received_at <= validated_at && validated_at <= applied_at, "{:?}", timeline);
+        previous = received_at;
+    }
+}
+
+/// Checks the metrics credit each node's neighbour with the blocks it delivered first, and time them
+#[test]
+fn check_first_seen_metrics() {
+    let sim = simulation();
+    for node in 1..4 {
+        let metrics = sim.node(node).metrics();
+        let first_seen = metrics.blocks_first_seen();
+        let neighbour = sim::node_id(node - 1);
+        assert_eq!(first_seen.keys().collect::<Vec<_>>(), vec![&neighbour], "{:?}", first_seen);
+        assert!(first_seen[&neighbour] > HEIGHT);
+        
+        let rendered = metrics.render();
+        assert!(rendered.contains(&format!("genx_blocks_first_seen_total{{peer=\"{}\"}} {}", neighbour, first_seen[&neighbour])), "{}", rendered);
+        for histogram in ["genx_block_propagation_ms", "genx_block_validation_ms", "genx_block_apply_ms"] {
+            assert!(rendered.contains(&format!("{}_count", histogram)), "{}", histogram);
+        }
+    }
+    
+    // The producer delivered its blocks to everyone, so no peer delivered any to it
+    assert!(sim.node(0).metrics().blocks_first_seen().is_empty());
+}
+
+/// Checks timelines aren't served over RPC unless the debug methods are enabled
+#[test]
+fn check_debug_disabled() {
+    let sim = simulation();
+    let hash = block_hash(&sim);
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "debug_tracePropagation", "params": [hash.to_string()] });
+    let response: Value = sim.node(1).rpc_handler().handle_request(&request);
+    assert_eq!(response["error"]["code"], json!(-32601), "{}", response);
+}
\ No newline at end of file
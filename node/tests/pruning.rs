@@ -0,0 +1,120 @@
+//! Checks a node past its receipt retention prunes old receipts and logs, and fetches them from an archival peer
+//!
+//! Run with `cargo test -p node --features testutil --test pruning`.
+//! Runs simulated validators, all archival but the last, which keeps the
+//! receipts and logs of its latest `RETENTION` blocks, until the chain is
+//! well past the window and finalized. Queries for blocks inside the window
+//! are answered by both, queries below it fail with `Pruned` on the pruned
+//! node only, while the transactions themselves are still found. The pruned
+//! node counts what it reclaimed, and gets the receipts of a pruned block
+//! from an archival peer.
+
+use serde_json::{json, Value};
+
+use consensus::ConsensusParams;
+use ctb_core::paging::{Page, PageRequest};
+use ctb_core::receipt::{IndexedLog, LogFilter, Receipt};
+use ctb_core::BlockchainError;
+use node::rpc::RpcHandler;
+use node::sim::{self, SimConfig, Simulation};
+
+/// Latest blocks whose receipts and logs the pruned node keeps
+const RETENTION: u64 = 4;
+
+/// Blocks between checkpoints, few so the chain finalizes soon
+const CHECKPOINT_INTERVAL: u64 = 4;
+
+/// Finalized height the chain is run to
+const FINALIZED: u64 = 12;
+
+/// Index of the pruned node
+const PRUNED: usize = 3;
+
+/// Makes a JSON-RPC request, returning the response
+fn call(handler: &RpcHandler, method: &str, params: Value) -> Value {
+    handler.handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+}
+
+/// Runs the simulation until the pruned node has finalized past `FINALIZED` and pruned
+fn simulation() -> Simulation {
+    let config = SimConfig {
+        consensus_params: ConsensusParams { checkpoint_interval: CHECKPOINT_INTERVAL, ..ConsensusParams::default() },
+        receipt_retention: vec![None, None, None, Some(RETENTION)],
+        ..SimConfig::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let finalized = sim.run_until(1_000_000, |sim| sim.node(PRUNED).get_finalized_height() >= FINALIZED);
+    assert!(finalized, "{:?}", sim.heights());
+    
+    // One more tick prunes up to the finalized height
+    let tick = SimConfig::default().tick_interval;
+    sim.run_for(tick);
+    sim
+}
+
+/// Gets the receipts of the block at a height from a node
+fn receipts(sim: &Simulation, node: usize, height: u64) -> Value {
+    call(&sim.node(node).rpc_handler(), "genx_getBlockWithReceipts", json!([height]))
+}
+
+/// Lists the first page of a node's logs from a height on
+fn logs(sim: &Simulation, node: usize, from: u64) -> ctb_core::Result<Page<IndexedLog>> {
+    sim.node(node).list_logs(&LogFilter { from_block: Some(from), ..LogFilter::default() }, &PageRequest::default())
+}
+
+/// Checks receipts and logs inside the window are kept, and those below it pruned only on the pruned node
+#[test]
+fn check_window() {
+    let sim = simulation();
+    let height = sim.heights()[PRUNED];
+    assert!(height >= FINALIZED && FINALIZED - RETENTION > 1, "{}", height);
+    
+    // Inside the window, both answer
+    for node in [0, PRUNED] {
+        let response = receipts(&sim, node, height);
+        assert!(!response["result"]["receipts"].as_array().unwrap().is_empty(), "{}", response);
+        assert!(logs(&sim, node, height + 1 - RETENTION).is_ok());
+    }
+    
+    // Below it, only the archival node does
+    for old in [1, FINALIZED - RETENTION] {
+        assert!(receipts(&sim, 0, old)["result"]["receipts"].is_array());
+        let response = receipts(&sim, PRUNED, old);
+        assert!(response["error"]["message"].as_str().unwrap().contains("pruned"), "{}", response);
+    }
+    assert!(logs(&sim, 0, 1).is_ok());
+    assert!(matches!(logs(&sim, PRUNED, 1), Err(BlockchainError::Pruned(_))));
+    
+    // Transactions of pruned blocks are still found, but not their receipts
+    let block = receipts(&sim, 0, 1)["result"]["block"].clone();
+    let hash = block["transactions"][0]["id"].clone();
+    let handler = sim.node(PRUNED).rpc_handler();
+    let tx = call(&handler, "eth_getTransactionByHash", json!([hash]));
+    assert!(tx["result"].is_object(), "{}", tx);
+    assert!(call(&handler, "eth_getTransactionReceipt", json!([hash]))["error"].is_object());
+    assert!(call(&sim.node(0).rpc_handler(), "eth_getTransactionReceipt", json!([hash]))["result"].is_object());
+    
+    // Only the pruned node reclaimed space, and says so
+    assert!(sim.node(PRUNED).metrics().pruned_bytes() > 0);
+    assert_eq!(sim.node(0).metrics().pruned_bytes(), 0);
+    assert!(sim.node(PRUNED).metrics().render().contains("genx_pruned_receipts_total"));
+}
+
+/// Checks the pruned node fetches a pruned block's receipts from an archival peer, and not from another pruned one
+#[test]
+fn check_fetch() {
+    let mut sim = simulation();
+    let archived: Vec<Receipt> = serde_json::from_value(receipts(&sim, 0, 1)["result"]["receipts"].clone()).unwrap();
+    assert!(sim.node(PRUNED).fetched_receipts(1).is_none());
+    
+    sim.node(PRUNED).request_receipts(&sim::node_id(0), 1);
+    assert!(sim.run_until(10_000, |sim| sim.node(PRUNED).fetched_receipts(1).is_some()));
+    let fetched = sim.node(PRUNED).fetched_receipts(1).unwrap();
+    let ids = |receipts: &[Receipt]| receipts.iter().map(|receipt| (receipt.tx_id, receipt.gas_used, receipt.success)).collect::<Vec<_>>();
+    assert_eq!(ids(&fetched), ids(&archived));
+    
+    // An archival node asking the pruned one gets nothing
+    sim.node(0).request_receipts(&sim::node_id(PRUNED), 2);
+    sim.run_for(10_000);
+    assert!(sim.node(0).fetched_receipts(2).is_none());
+}
\ No newline at end of file
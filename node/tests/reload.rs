@@ -0,0 +1,134 @@
+//! Checks a node applies reloadable settings from its configuration file without a restart
+//!
+//! Run with `cargo test -p node --features testutil --test reload`. Fills
+//! a node's mempool to its capacity, so another transaction is refused,
+//! then raises the capacity in the configuration file and reloads it: the
+//! transaction is admitted. Lowering the capacity again evicts the lowest
+//! priority ones, while a changed `data_dir` in the same file is rejected
+//! and keeps its running value. A file setting an invalid value applies
+//! nothing, and `admin_reloadConfig` reloads the file last read.
+
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::transaction::{Transaction, TransactionType};
+use ctb_core::units::GENX;
+use ctb_core::TxHash;
+use node::reload::ReloadError;
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 151;
+
+/// Mempool capacity the node starts with
+const CAPACITY: usize = 2;
+
+/// A node with a configuration file, both removed once the test is done
+struct Reloading {
+    node: Node,
+    chain: TestChain,
+    config: NodeConfig,
+    path: PathBuf,
+}
+
+impl Drop for Reloading {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+        let _ = std::fs::remove_dir_all(&self.config.data_dir);
+    }
+}
+
+impl Reloading {
+    fn new(name: &str) -> Self {
+        let chain = TestChain::new(SEED);
+        let path = std::env::temp_dir().join(format!("genx-reload-{}-{}.json", std::process::id(), name));
+        let data_dir = std::env::temp_dir().join(format!("genx-reload-{}-{}", std::process::id(), name));
+        let mut config = NodeConfig {
+            data_dir: data_dir.display().to_string(),
+            rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+            ..NodeConfig::default()
+        };
+        config.consensus_params.mempool_capacity = CAPACITY;
+        let node = Node::new(config.clone(), TestChain::new(SEED).into_blockchain());
+        let reloading = Self { node, chain, config, path };
+        reloading.write(|_| {});
+        reloading.node.set_config_path(reloading.path.clone());
+        reloading
+    }
+    
+    /// Writes the configuration file, with changes to the node's initial configuration
+    fn write(&self, change: impl FnOnce(&mut NodeConfig)) {
+        let mut config = self.config.clone();
+        change(&mut config);
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+    }
+    
+    /// Makes a transfer to carol paying a fee, signed
+    fn transfer(&self, from: &str, fee: u64) -> Transaction {
+        let mut tx = Transaction::new_with_type(TransactionType::Transfer, self.chain.address(from), self.chain.address("carol"), GENX, fee, None, 0, 0).unwrap();
+        self.chain.account(from).sign(&mut tx).unwrap();
+        tx
+    }
+    
+    /// Lists which of the transactions are pending
+    fn pending(&self, ids: &[TxHash]) -> Vec<bool> {
+        ids.iter().map(|id| self.node.is_pending(id)).collect()
+    }
+}
+
+/// Checks raising the mempool capacity admits a transaction the full pool refused, and lowering it evicts
+#[test]
+fn check_mempool_capacity() {
+    let mut reloading = Reloading::new("capacity");
+    let (low, high) = (reloading.transfer("alice", 1_000), reloading.transfer("bob", 5_000));
+    let ids = [low.id, high.id];
+    reloading.node.add_transaction(high).unwrap();
+    reloading.node.add_transaction(reloading.transfer("carol", 3_000)).unwrap();
+    
+    // Full, and the transaction has the lowest priority
+    assert!(reloading.node.add_transaction(low.clone()).is_err());
+    assert_eq!(reloading.pending(&ids), [false, true]);
+    
+    reloading.write(|config| config.consensus_params.mempool_capacity = CAPACITY + 1);
+    let report = reloading.node.reload_config(&reloading.path).unwrap();
+    assert_eq!(report.applied, ["consensus_params.mempool_capacity"]);
+    assert!(report.rejected.is_empty());
+    reloading.node.add_transaction(low).unwrap();
+    assert_eq!(reloading.pending(&ids), [true, true]);
+    
+    // Lowering it evicts all but the highest priority; the data directory can't change
+    reloading.write(|config| {
+        config.consensus_params.mempool_capacity = 1;
+        config.data_dir = "/elsewhere".to_string();
+    });
+    let report = reloading.node.reload_config(&reloading.path).unwrap();
+    assert_eq!((report.applied, report.rejected), (vec!["consensus_params.mempool_capacity".to_string()], vec!["data_dir".to_string()]));
+    assert_eq!(reloading.pending(&ids), [false, true]);
+    let running = reloading.node.running_config();
+    assert_eq!((running.consensus_params.mempool_capacity, running.data_dir), (1, reloading.config.data_dir.clone()));
+}
+
+/// Checks a file setting an invalid value applies nothing, and the admin method reloads the file last read
+#[test]
+fn check_invalid_and_admin() {
+    let reloading = Reloading::new("invalid");
+    reloading.write(|config| {
+        config.consensus_params.mempool_capacity = CAPACITY + 5;
+        config.log_level = Some("loudest".to_string());
+    });
+    let error = reloading.node.reload_config(&reloading.path).unwrap_err();
+    assert!(matches!(error, ReloadError::InvalidValue { field: "log_level", .. }), "{:?}", error);
+    assert_eq!(reloading.node.running_config().consensus_params.mempool_capacity, CAPACITY);
+    
+    reloading.write(|config| {
+        config.consensus_params.mempool_capacity = CAPACITY + 5;
+        config.log_level = Some("debug".to_string());
+    });
+    let response = reloading.node.admin_handler().handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": "admin_reloadConfig", "params": [] }));
+    assert_eq!(response["result"], json!({ "applied": ["consensus_params.mempool_capacity", "log_level"], "rejected": [] }), "{}", response);
+    assert_eq!(reloading.node.running_config().consensus_params.mempool_capacity, CAPACITY + 5);
+    log::set_max_level(log::LevelFilter::Info);
+}
\ No newline at end of file
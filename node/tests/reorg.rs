@@ -0,0 +1,180 @@
+//! Checks chain reorganizations are reported and their dropped transactions requeued
+//!
+//! Run with `cargo test -p node --features testutil --test reorg`. Builds
+//! two branches off the same chain, the longer sharing one transaction
+//! with the other, and switches a node from the shorter to the longer.
+//! Checks the `ReorgOccurred` event, the metrics, the `/reorgs` route and
+//! the saved history describe the switch, and that the transactions only
+//! the old branch held are back in the mempool.
+
+use std::sync::Arc;
+
+use serde_json::json;
+
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::transaction::Transaction;
+use ctb_core::BlockHash;
+
+use node::events::NodeEvent;
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+
+/// Seed of the chains built
+const SEED: u64 = 83;
+
+/// Height of the last block both branches share
+const FORK_HEIGHT: u64 = 2;
+
+/// Two branches off the same chain
+struct Branches {
+    /// Chain on the old branch
+    old: TestChain,
+    
+    /// Blocks of the new branch, above the fork
+    new_branch: Vec<Arc<Block>>,
+    
+    /// Transaction both branches hold
+    shared: Transaction,
+    
+    /// Transactions only the old branch holds
+    dropped: Vec<Transaction>,
+}
+
+impl Branches {
+    /// Builds the branches: two blocks of transfers, and three blocks holding only the first transfer
+    fn new() -> Self {
+        let mut old = TestChain::new(SEED);
+        let mut new = TestChain::new(SEED);
+        old.with_empty_blocks(FORK_HEIGHT);
+        new.with_empty_blocks(FORK_HEIGHT);
+        
+        old.with_block(|b| b.transfer("alice", "bob", 1_000).transfer("bob", "carol", 500));
+        old.with_block(|b| b.transfer("carol", "alice", 200));
+        new.with_block(|b| b.transfer("alice", "bob", 1_000));
+        new.with_empty_blocks(2);
+        
+        let above_fork = |chain: &TestChain| chain.blocks()[FORK_HEIGHT as usize + 1..].iter().map(|&block| block.clone()).collect::<Vec<_>>();
+        let transfers = |block: &Block| block.transactions.iter().filter(|tx| tx.sender != "COINBASE").cloned().collect::<Vec<_>>();
+        let (old_blocks, new_blocks) = (above_fork(&old), above_fork(&new));
+        let shared = transfers(&old_blocks[0])[0].clone();
+        assert_eq!(transfers(&new_blocks[0]).iter().map(|tx| tx.id).collect::<Vec<_>>(), vec![shared.id]);
+        let dropped = old_blocks.iter().flat_map(transfers).filter(|tx| tx.id != shared.id).collect();
+        
+        Self { old, new_branch: new_blocks.into_iter().map(Arc::new).collect(), shared, dropped }
+    }
+    
+    fn old_tip(&self) -> BlockHash {
+        self.old.blocks().last().unwrap().hash().unwrap()
+    }
+    
+    fn new_tip(&self) -> BlockHash {
+        self.new_branch.last().unwrap().hash().unwrap()
+    }
+}
+
+/// A node on the old branch of `Branches`
+struct Fork {
+    node: Node,
+    old_tip: BlockHash,
+    new_tip: BlockHash,
+    new_branch: Vec<Arc<Block>>,
+    shared: Transaction,
+    dropped: Vec<Transaction>,
+}
+
+impl Fork {
+    fn new(name: &str) -> Self {
+        let branches = Branches::new();
+        let data_dir = std::env::temp_dir().join(format!("genx-reorg-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let config = NodeConfig {
+            rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+            data_dir: data_dir.display().to_string(),
+            ..NodeConfig::default()
+        };
+        Self {
+            old_tip: branches.old_tip(),
+            new_tip: branches.new_tip(),
+            node: Node::new(config, branches.old.into_blockchain()),
+            new_branch: branches.new_branch,
+            shared: branches.shared,
+            dropped: branches.dropped,
+        }
+    }
+}
+
+/// Checks switching branches publishes the event, counts the switch and requeues what only the old branch held
+#[test]
+fn check_reorg_event() {
+    let fork = Fork::new("event");
+    let mut events = fork.node.event_bus().subscribe();
+    let record = fork.node.reorganize(FORK_HEIGHT, fork.new_branch.clone()).unwrap();
+    
+    assert_eq!(record.old_tip, fork.old_tip);
+    assert_eq!(record.new_tip, fork.new_tip);
+    assert_eq!(record.fork_height, FORK_HEIGHT);
+    assert_eq!(record.depth, 2);
+    assert_eq!(record.dropped_txs, fork.dropped.len());
+    
+    let reorgs: Vec<NodeEvent> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter(|event| matches!(event, NodeEvent::ReorgOccurred { .. }))
+        .collect();
+    assert_eq!(
+        reorgs,
+        vec![NodeEvent::ReorgOccurred {
+            old_tip: fork.old_tip,
+            new_tip: fork.new_tip,
+            fork_height: FORK_HEIGHT,
+            depth: 2,
+            dropped_txs: 2,
+            requeued_txs: 2,
+        }]
+    );
+    
+    for tx in &fork.dropped {
+        assert!(fork.node.is_pending(&tx.id), "dropped transaction {} isn't pending", tx.id);
+    }
+    assert!(!fork.node.is_pending(&fork.shared.id));
+    
+    let metrics = fork.node.metrics();
+    assert_eq!(metrics.reorg_count(), 1);
+    let rendered = metrics.render();
+    assert!(rendered.contains("genx_chain_reorgs_bucket{le=\"1\"} 0"), "{}", rendered);
+    assert!(rendered.contains("genx_chain_reorgs_bucket{le=\"2\"} 1"), "{}", rendered);
+    assert!(rendered.contains("genx_chain_reorgs_sum 2"), "{}", rendered);
+}
+
+/// Checks a branch the chain doesn't prefer is refused without an event
+#[test]
+fn check_refused_branch() {
+    let fork = Fork::new("refused");
+    let mut events = fork.node.event_bus().subscribe();
+    fork.node.reorganize(FORK_HEIGHT, fork.new_branch[..2].to_vec()).unwrap_err();
+    
+    assert!(std::iter::from_fn(|| events.try_recv().ok()).all(|event| !matches!(event, NodeEvent::ReorgOccurred { .. })));
+    assert_eq!(fork.node.metrics().reorg_count(), 0);
+    assert_eq!(fork.node.rest_api().handle("/reorgs").body, json!({ "reorgs": [] }));
+}
+
+/// Checks the switch is listed at `/reorgs`, and kept in the history saved for the next run
+#[test]
+fn check_history() {
+    let fork = Fork::new("history");
+    let record = fork.node.reorganize(FORK_HEIGHT, fork.new_branch.clone()).unwrap();
+    assert_eq!(fork.node.rest_api().handle("/reorgs?limit=5").body, json!({ "reorgs": [record] }));
+    
+    let path = std::env::temp_dir().join(format!("genx-reorg-history-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let branches = Branches::new();
+    let mut blockchain = branches.old.into_blockchain();
+    blockchain.set_reorg_log(path.clone()).unwrap();
+    let (record, dropped) = blockchain.reorganize(FORK_HEIGHT, branches.new_branch).unwrap();
+    assert_eq!(dropped.iter().map(|tx| tx.id).collect::<Vec<_>>(), branches.dropped.iter().map(|tx| tx.id).collect::<Vec<_>>());
+    assert_eq!(blockchain.reorg_history(5), vec![record.clone()]);
+    
+    let mut restarted = TestChain::new(SEED).into_blockchain();
+    assert!(restarted.reorg_history(5).is_empty());
+    restarted.set_reorg_log(path).unwrap();
+    assert_eq!(restarted.reorg_history(5), vec![record]);
+}
\ No newline at end of file
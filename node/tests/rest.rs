@@ -0,0 +1,190 @@
+//! Checks the block explorer REST routes against an in-process chain
+//!
+//! Run with `cargo test -p node --features testutil --test rest`. Builds a
+//! chain of a few dozen blocks of transfers and checks the blocks, block,
+//! transaction, address, validator, supply and search routes answer from
+//! it, following the `Link` headers of paginated routes to their end, and
+//! that unknown things are 404s and malformed ones 400s.
+
+use serde_json::{json, Value};
+
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::confirmation::DEFAULT_SAFE_CONFIRMATIONS;
+use ctb_core::TxHash;
+
+use node::rest::RestResponse;
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 61;
+
+/// Blocks after genesis
+const BLOCKS: u64 = 36;
+
+/// An in-process node over the chain, with what the builder knew of it
+struct Explorer {
+    node: Node,
+    blocks: Vec<Block>,
+    transfers: Vec<(u64, TxHash)>,
+    
+    /// Addresses of bob, carol and the validator
+    bob: String,
+    carol: String,
+    validator: String,
+    
+    /// Balance the builder expects bob to have
+    bob_balance: u64,
+    
+    /// Total supply after the last block
+    total_supply: u64,
+}
+
+impl Explorer {
+    /// Builds the chain: every third block has alice pay bob and bob pay carol
+    fn new() -> Self {
+        let mut chain = TestChain::new(SEED);
+        for height in 1..=BLOCKS {
+            if height % 3 == 0 {
+                chain.with_block(|b| b.transfer("alice", "bob", 10_000).transfer("bob", "carol", 1_000));
+            } else {
+                chain.with_empty_blocks(1);
+            }
+        }
+        let blocks: Vec<Block> = chain.blocks().into_iter().cloned().collect();
+        let transfers = blocks[1..]
+            .iter()
+            .flat_map(|block| block.transactions.iter().filter(|tx| tx.sender != "COINBASE").map(|tx| (block.header().height, tx.id)))
+            .collect();
+        let total_supply = chain.blockchain().get_state().lock().unwrap().get_total_supply();
+        
+        let config = NodeConfig { rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() }, ..NodeConfig::default() };
+        Self {
+            bob: chain.address("bob"),
+            carol: chain.address("carol"),
+            validator: chain.address("validator"),
+            bob_balance: chain.expected_balance("bob"),
+            node: Node::new(config, chain.into_blockchain()),
+            blocks,
+            transfers,
+            total_supply,
+        }
+    }
+    
+    fn get(&self, target: &str) -> RestResponse {
+        self.node.rest_api().handle(target)
+    }
+    
+    /// Gets a route that should answer, returning its body
+    fn body(&self, target: &str) -> Value {
+        let response = self.get(target);
+        assert_eq!(response.status, 200, "{}: {}", target, response.body);
+        response.body
+    }
+    
+    /// Follows a paginated route's `Link` headers, returning every page's body
+    fn pages(&self, target: &str) -> Vec<Value> {
+        let mut pages = Vec::new();
+        let mut target = Some(target.to_string());
+        while let Some(next) = target {
+            let response = self.get(&next);
+            assert_eq!(response.status, 200, "{}: {}", next, response.body);
+            target = header(&response, "Link").map(|link| link[1..link.find('>').unwrap()].to_string());
+            pages.push(response.body);
+        }
+        pages
+    }
+}
+
+/// Gets a response header
+fn header<'a>(response: &'a RestResponse, name: &str) -> Option<&'a str> {
+    response.headers.iter().find(|(header, _)| *header == name).map(|(_, value)| value.as_str())
+}
+
+/// Checks `/blocks` lists every block newest first, a page at a time, and `/block` finds each by height and hash
+#[test]
+fn check_blocks() {
+    let explorer = Explorer::new();
+    let response = explorer.get("/blocks?limit=10");
+    assert_eq!(header(&response, "X-Total-Count"), Some((BLOCKS + 1).to_string().as_str()));
+    
+    let pages = explorer.pages("/blocks?limit=10");
+    assert_eq!(pages.len(), 4);
+    let heights: Vec<u64> = pages.iter().flat_map(|page| page["blocks"].as_array().unwrap().clone()).map(|block| block["height"].as_u64().unwrap()).collect();
+    assert_eq!(heights, (0..=BLOCKS).rev().collect::<Vec<_>>());
+    assert_eq!(explorer.body("/blocks?start=4&limit=100")["blocks"].as_array().unwrap().len(), 5);
+    
+    for (height, block) in explorer.blocks.iter().enumerate().step_by(7) {
+        let hash = block.hash().unwrap();
+        let by_height = explorer.body(&format!("/block/{}", height));
+        assert_eq!(by_height["hash"], json!(hash));
+        assert_eq!(by_height["block"]["header"]["height"], json!(height));
+        assert_eq!(explorer.body(&format!("/block/{}", hash)), by_height);
+    }
+    assert_eq!(explorer.get(&format!("/block/{}", BLOCKS + 1)).status, 404);
+    assert_eq!(explorer.get("/block/0xnothex").status, 400);
+    assert_eq!(explorer.get("/blocks/sideways").status, 404);
+}
+
+/// Checks `/tx` finds every transfer in its block with its receipt
+#[test]
+fn check_transactions() {
+    let explorer = Explorer::new();
+    assert_eq!(explorer.transfers.len() as u64, BLOCKS / 3 * 2);
+    for (height, tx_id) in &explorer.transfers {
+        let body = explorer.body(&format!("/tx/{}", tx_id));
+        assert_eq!((&body["block_height"], &body["transaction"]["id"]), (&json!(height), &json!(tx_id)));
+        let confirmations = BLOCKS - height + 1;
+        let status = if confirmations >= DEFAULT_SAFE_CONFIRMATIONS { "safe" } else { "included" };
+        assert_eq!(body["status"], json!({ "status": status, "height": height, "confirmations": confirmations }));
+    }
+    assert_eq!(explorer.get(&format!("/tx/{}", TxHash::from([0x77; 32]))).status, 404);
+    assert_eq!(explorer.get("/tx/12").status, 400);
+}
+
+/// Checks `/address` has the balances and nonce the chain expects, and pages through the history
+#[test]
+fn check_addresses() {
+    let explorer = Explorer::new();
+    let bob = &explorer.bob;
+    let body = explorer.body(&format!("/address/{}", bob));
+    assert_eq!((&body["balance"], &body["nonce"]), (&json!(explorer.bob_balance), &json!(BLOCKS / 3)));
+    
+    // Bob got a genesis allocation, then received and sent a transfer in each of those blocks
+    let pages = explorer.pages(&format!("/address/{}?limit=5", bob));
+    let history: Vec<Value> = pages.iter().flat_map(|page| page["transactions"].as_array().unwrap().clone()).collect();
+    assert_eq!(history.len() as u64, 1 + BLOCKS / 3 * 2);
+    assert!(pages[..pages.len() - 1].iter().all(|page| page["transactions"].as_array().unwrap().len() == 5));
+    
+    assert_eq!(explorer.get("/address/GENX_NOBODY_HERE").status, 404);
+    assert_eq!(explorer.get("/address/not%20an%20address").status, 400);
+}
+
+/// Checks `/validators`, `/supply` and `/search` answer from the chain's state
+#[test]
+fn check_validators_supply_and_search() {
+    let explorer = Explorer::new();
+    let validators = explorer.body("/validators");
+    let listed = validators["validators"].as_array().unwrap();
+    assert!(listed.iter().any(|entry| entry["address"] == json!(explorer.validator) && entry["stake"].as_u64() > Some(0)), "{}", validators);
+    
+    let supply = explorer.body("/supply");
+    assert_eq!((&supply["height"], &supply["circulating_supply"]), (&json!(BLOCKS), &json!(explorer.total_supply)));
+    
+    let (_, tx_id) = explorer.transfers[0];
+    let block_hash = explorer.blocks[5].hash().unwrap();
+    let carol = &explorer.carol;
+    for (q, kind, id) in [
+        ("12".to_string(), "block", "12".to_string()),
+        (tx_id.to_string(), "tx", tx_id.to_string()),
+        (block_hash.to_string(), "block", "5".to_string()),
+        (carol.clone(), "address", carol.clone()),
+    ] {
+        let found = explorer.body(&format!("/search?q={}", q));
+        assert_eq!((&found["type"], &found["id"]), (&json!(kind), &json!(id)), "{}", q);
+    }
+    assert_eq!(explorer.get(&format!("/search?q={}", BLOCKS + 1)).status, 404);
+    assert_eq!(explorer.get(&format!("/search?q={}", TxHash::from([0x77; 32]))).status, 404);
+    assert_eq!(explorer.get("/search").status, 400);
+}
\ No newline at end of file
@@ -0,0 +1,168 @@
+//! Checks a new node downloads a state snapshot from its peers over loopback
+//!
+//! Run with `cargo test -p node --features testutil --test snapshot_sync`.
+//! Three nodes on the same chain, with a few megabytes of synthetic
+//! accounts, each take a snapshot and serve it over a TCP connection on
+//! the loopback interface, one of them corrupting every chunk it sends. A
+//! syncing side spreads its requests over all three, drops the corrupt
+//! peer and fetches its chunks again from the others, and rebuilds the
+//! state, whose root must be the trusted one. The chain can't start from a
+//! height other than genesis yet, so the blocks the chain goes on to make
+//! are applied to the rebuilt state, which must keep up with the chain's.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::state::State;
+use ctb_core::state_sync::{self, SNAPSHOT_CHUNK_SIZE};
+use ctb_core::{Address, Amount};
+
+use node::message::NetworkMessage;
+use node::rpc::RpcConfig;
+use node::snapshot_sync::{SnapshotServer, SnapshotSync, SnapshotSyncError};
+use node::{Node, NodeConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 101;
+
+/// Accounts set up outside any block to make the state a few megabytes
+const SYNTHETIC_ACCOUNTS: u64 = 150_000;
+
+/// Blocks the chain makes after the snapshot
+const BLOCKS_AFTER: u64 = 5;
+
+/// Peer corrupting the chunks it serves
+const CORRUPT_PEER: &str = "peer-2";
+
+/// Builds the chain every node starts on: the synthetic accounts, then a block of transfers
+fn chain() -> TestChain {
+    let mut chain = TestChain::new(SEED);
+    chain.blockchain_mut().override_state(|state| {
+        for index in 0..SYNTHETIC_ACCOUNTS {
+            let address = Address::new(format!("GENX_SYNTHETIC_{:06}", index)).unwrap();
+            state.override_balance(&address, Amount::from_base_units(index + 1));
+        }
+    });
+    chain.with_block(|b| b.transfer("alice", "bob", 1_000).transfer("bob", "carol", 500));
+    chain
+}
+
+/// Serves a node's snapshots to one connection on the loopback interface, returning its address
+///
+/// Frames go each way with their length before them. A corrupting server
+/// flips a bit of every chunk it sends.
+fn serve(server: Arc<SnapshotServer>, corrupt: bool) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        while let Some(frame) = read_frame(&mut stream) {
+            let request = NetworkMessage::decode(&frame).unwrap();
+            let reply = match server.handle(&request) {
+                Some(NetworkMessage::SnapshotChunk { height, index, mut data }) if corrupt => {
+                    data.0[0] ^= 1;
+                    NetworkMessage::SnapshotChunk { height, index, data }
+                }
+                Some(reply) => reply,
+                None => panic!("No reply to {}", request.kind()),
+            };
+            write_frame(&mut stream, &reply.encode());
+        }
+    });
+    address
+}
+
+/// Reads a frame, or `None` once the other side closed the connection
+fn read_frame(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).ok()?;
+    let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut frame).ok()?;
+    Some(frame)
+}
+
+/// Writes a frame with its length before it
+fn write_frame(stream: &mut TcpStream, frame: &[u8]) {
+    stream.write_all(&(frame.len() as u32).to_be_bytes()).unwrap();
+    stream.write_all(frame).unwrap();
+}
+
+/// Sends a request to a peer and waits for its reply
+fn request(stream: &mut TcpStream, message: &NetworkMessage) -> NetworkMessage {
+    write_frame(stream, &message.encode());
+    NetworkMessage::decode(&read_frame(stream).unwrap()).unwrap()
+}
+
+/// Checks a snapshot downloads from several peers despite a corrupt one, and the chain carries on from it
+#[test]
+fn check_snapshot_sync() {
+    let mut reference = chain();
+    let config = || NodeConfig { rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() }, ..NodeConfig::default() };
+    let nodes: Vec<Node> = (0..3).map(|_| Node::new(config(), chain().into_blockchain())).collect();
+    let infos: Vec<_> = nodes.iter().map(|node| node.create_snapshot().unwrap()).collect();
+    assert!(infos.iter().all(|info| *info == infos[0]), "{:?}", infos);
+    
+    // The root is trusted as the reference chain's
+    let target = infos[0];
+    let reference_state = reference.blockchain().get_state().lock().unwrap().clone();
+    assert_eq!(target.root, state_sync::state_root(&reference_state));
+    assert_eq!(target.height, reference.height());
+    assert!(target.chunk_count >= 3, "state of {} bytes", reference_state.encode_canonical().len());
+    
+    let mut peers: Vec<(String, TcpStream)> = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            let peer = format!("peer-{}", index);
+            let address = serve(node.snapshot_server(), peer == CORRUPT_PEER);
+            (peer, TcpStream::connect(address).unwrap())
+        })
+        .collect();
+    
+    let mut sync = SnapshotSync::new(target);
+    for (peer, stream) in &mut peers {
+        let offered = request(stream, &NetworkMessage::GetSnapshots);
+        sync.handle(peer, &offered).unwrap();
+    }
+    assert_eq!(sync.peers().len(), 3);
+    
+    let (mut served, mut misbehaved) = (vec![0; peers.len()], Vec::new());
+    let mut rounds = 0;
+    while !sync.is_complete() {
+        rounds += 1;
+        assert!(rounds <= 100, "sync stalled at {:?}", sync.progress());
+        for (peer, message) in sync.poll(Instant::now()) {
+            let index = peers.iter().position(|(known, _)| *known == peer).unwrap();
+            let reply = request(&mut peers[index].1, &message);
+            match sync.handle(&peer, &reply) {
+                Ok(()) => served[index] += matches!(reply, NetworkMessage::SnapshotChunk { .. }) as usize,
+                Err(SnapshotSyncError::Misbehaved { peer, .. }) => misbehaved.push(peer),
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+    
+    // Chunks came from both honest peers; the corrupt one was dropped at its first chunk
+    assert_eq!(misbehaved, vec![CORRUPT_PEER.to_string()]);
+    assert!(!sync.peers().iter().any(|peer| peer == CORRUPT_PEER));
+    assert!(served[0] > 0 && served[1] > 0, "{:?}", served);
+    assert_eq!(served[2], 0);
+    assert_eq!(served.iter().sum::<usize>(), target.chunk_count as usize);
+    
+    let mut state: State = sync.finish().unwrap();
+    assert_eq!(state_sync::state_root(&state), target.root);
+    assert!(state.encode_canonical().len() > 2 * SNAPSHOT_CHUNK_SIZE);
+    
+    // The chain goes on, and the rebuilt state follows it block by block
+    for height in 1..=BLOCKS_AFTER {
+        reference.with_block(|b| if height % 2 == 0 { b.transfer("carol", "alice", 100 * height) } else { b });
+        state.apply_block(reference.blocks()[reference.height() as usize]).unwrap();
+        let reference_state = reference.blockchain().get_state().lock().unwrap().clone();
+        assert_eq!(state_sync::state_root(&state), state_sync::state_root(&reference_state), "height {}", reference.height());
+    }
+    reference.assert_balances();
+}
\ No newline at end of file
@@ -0,0 +1,242 @@
+//! Checks state diffs list exactly what changed between two heights
+//!
+//! Run with `cargo test -p node --features testutil --test state_diff`.
+//! Builds three blocks of transfers, a slash taking part of the validator's
+//! stake and writes to a contract's storage, one of them later undone, and
+//! checks the diff over them against the balances, stakes and slots read
+//! before and after: every change is listed once, in order, and nothing
+//! else is, whether the diff is read whole, by address or a page at a time
+//! through `genx_getStateDiff`.
+
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+
+use ctb_core::chain::MAX_ROLLBACK_DEPTH;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::slashing::{self, SlashingEvidence};
+use ctb_core::state_diff::StateChange;
+use ctb_core::transaction::Transaction;
+use ctb_core::{Address, BlockchainError, Bytes};
+
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+use smartcontracts::evm::opcode;
+use smartcontracts::u256::U256;
+use smartcontracts::{ContractEngine, DeployPayload, GasConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 97;
+
+/// Sent from alice to bob in the first block of the range
+const SENT: u64 = 5_000;
+
+/// Sent from bob to carol in the same block
+const SENT_ON: u64 = 2_000;
+
+/// A chain with a storage contract, and the height the range of blocks to diff starts after
+struct Diffed {
+    chain: TestChain,
+    contract: String,
+    from: u64,
+    treasury: String,
+    before: Vec<u64>,
+}
+
+impl Diffed {
+    /// Deploys the contract and sets slot 1, then builds the three blocks of the range
+    ///
+    /// The range moves tokens between the accounts, has alice report the
+    /// validator for signing two headers at height 1, rewrites slot 1, sets
+    /// slot 3 and sets slot 2 only to clear it again.
+    fn new() -> Self {
+        let mut chain = TestChain::new(SEED);
+        chain.blockchain_mut().set_contract_executor(Arc::new(Mutex::new(ContractEngine::new(GasConfig::default()))));
+        let payload = DeployPayload { init_code: init_code(&storage_code()), abi: Vec::new(), events: Vec::new(), constructor_args: Vec::new() };
+        chain.with_block(|b| b.deploy("carol", payload.to_bytes().unwrap()));
+        let contract = chain.contracts()[0].clone();
+        chain.with_block(|b| b.call("carol", &contract, store(7, 1)));
+        
+        let from = chain.height();
+        let treasury = chain.blockchain().reward_schedule().rule_at(from).address.clone();
+        let mut diffed = Self { chain, contract, from, treasury, before: Vec::new() };
+        diffed.before = diffed.balances();
+        
+        let contract = diffed.contract.clone();
+        let chain = &mut diffed.chain;
+        chain.with_block(|b| b.transfer("alice", "bob", SENT).transfer("bob", "carol", SENT_ON).call("carol", &contract, store(5, 2)));
+        let evidence = evidence(chain);
+        let report = Transaction::new_submit_evidence(chain.address("alice"), &evidence, chain.config().transfer_fee).unwrap();
+        chain.with_block(|b| b.transaction(report).call("carol", &contract, store(9, 1)));
+        chain.with_block(|b| b.call("carol", &contract, store(0, 2)).call("carol", &contract, store(4, 3)));
+        diffed
+    }
+    
+    /// Gets the accounts whose balances are compared: the three, the validator, the treasury and the contract
+    fn accounts(&self) -> Vec<String> {
+        let mut accounts: Vec<String> = ["alice", "bob", "carol", "validator"].iter().map(|name| self.chain.address(name)).collect();
+        accounts.extend([self.treasury.clone(), self.contract.clone()]);
+        accounts
+    }
+    
+    /// Reads the balances of the accounts compared at the chain's height
+    fn balances(&self) -> Vec<u64> {
+        let state = self.chain.blockchain().get_state();
+        let state = state.lock().unwrap();
+        self.accounts().iter().map(|account| state.get_balance(&Address::new(account).unwrap()).base_units()).collect()
+    }
+    
+    /// Lists the changes the range should make, in the diff's order
+    fn expected(&self) -> Vec<StateChange> {
+        let mut balances: Vec<StateChange> = self
+            .accounts()
+            .into_iter()
+            .zip(self.before.iter().zip(self.balances()))
+            .filter(|(_, (&old, new))| old != *new)
+            .map(|(address, (&old, new))| StateChange::Balance { address, old, new })
+            .collect();
+        balances.sort_by(|a, b| a.address().cmp(b.address()));
+        
+        let stake = self.chain.config().validators[0].1;
+        let slot = |value: u64| Bytes(U256::from(value).to_be_bytes().to_vec());
+        let mut changes = balances;
+        changes.push(StateChange::Stake {
+            validator: self.chain.address("validator"),
+            old: stake,
+            new: stake - slashing::penalty(stake),
+        });
+        changes.push(StateChange::Storage { address: self.contract.clone(), slot: slot(1), old: Some(slot(7)), new: Some(slot(9)) });
+        changes.push(StateChange::Storage { address: self.contract.clone(), slot: slot(3), old: None, new: Some(slot(4)) });
+        changes
+    }
+}
+
+/// Assembles a contract storing the call data's first word in the slot its second word names
+fn storage_code() -> Vec<u8> {
+    vec![opcode::PUSH1, 0x00, opcode::CALLDATALOAD, opcode::PUSH1, 0x20, opcode::CALLDATALOAD, opcode::SSTORE]
+}
+
+/// Builds init code returning `runtime` as the contract's code
+fn init_code(runtime: &[u8]) -> Vec<u8> {
+    let mut code = vec![
+        opcode::PUSH1, runtime.len() as u8, opcode::DUP1, opcode::PUSH1, 11, opcode::PUSH1, 0x00, opcode::CODECOPY,
+        opcode::PUSH1, 0x00, opcode::RETURN,
+    ];
+    code.extend_from_slice(runtime);
+    code
+}
+
+/// Builds call data storing a value in a slot
+fn store(value: u64, slot: u64) -> Vec<u8> {
+    [U256::from(value).to_be_bytes(), U256::from(slot).to_be_bytes()].concat()
+}
+
+/// Has the validator sign a second header at height 1, differing from the block's only in its time
+fn evidence(chain: &TestChain) -> SlashingEvidence {
+    let first = chain.blocks()[1].header().clone();
+    let mut second = first.clone();
+    second.timestamp += 1;
+    chain.validators()[0].sign_header(&mut second).unwrap();
+    SlashingEvidence { first, second }
+}
+
+/// Checks the diff over the range lists every change and nothing else
+#[test]
+fn check_range() {
+    let diffed = Diffed::new();
+    let blockchain = diffed.chain.blockchain();
+    let diff = blockchain.state_diff(diffed.from, diffed.chain.height(), None).unwrap();
+    assert_eq!((diff.from_height, diff.to_height), (diffed.from, diffed.from + 3));
+    assert_eq!(diff.changes, diffed.expected());
+    
+    // Transfers and fees are followed exactly; the contract's balance didn't change
+    let balance = |who: &str| diff.changes.iter().find_map(|change| match change {
+        StateChange::Balance { address, old, new } if *address == diffed.chain.address(who) => Some((*old, *new)),
+        _ => None,
+    });
+    let fee = diffed.chain.config().transfer_fee;
+    let (old, new) = balance("alice").unwrap();
+    assert_eq!(old - new, SENT + 2 * fee);
+    let (old, new) = balance("bob").unwrap();
+    assert_eq!(new - old, SENT - SENT_ON - fee);
+    assert!(diff.changes.iter().all(|change| !matches!(change, StateChange::Balance { address, .. } if *address == diffed.contract)));
+    
+    // Parts of the range read the same way, and the diff over nothing is empty
+    let tip = diffed.chain.height();
+    let first = blockchain.state_diff(diffed.from, diffed.from + 1, Some(&diffed.contract)).unwrap();
+    assert_eq!(first.changes.len(), 1, "{:?}", first.changes);
+    let last = blockchain.state_diff(tip - 1, tip, Some(&diffed.contract)).unwrap();
+    assert_eq!(last.changes.len(), 2, "{:?}", last.changes);
+    assert!(blockchain.state_diff(tip, tip, None).unwrap().changes.is_empty());
+}
+
+/// Checks the diff for an address lists exactly the changes to it
+#[test]
+fn check_address() {
+    let diffed = Diffed::new();
+    let (from, to) = (diffed.from, diffed.chain.height());
+    let expected = diffed.expected();
+    let validator = diffed.chain.address("validator");
+    
+    let mut addresses: Vec<&str> = expected.iter().map(StateChange::address).collect();
+    addresses.dedup();
+    for address in addresses {
+        let diff = diffed.chain.blockchain().state_diff(from, to, Some(address)).unwrap();
+        let wanted: Vec<StateChange> = expected.iter().filter(|change| change.address() == address).cloned().collect();
+        assert_eq!(diff.changes, wanted, "{}", address);
+    }
+    
+    let diff = diffed.chain.blockchain().state_diff(from, to, Some(&validator)).unwrap();
+    assert!(matches!(diff.changes[..], [StateChange::Balance { .. }, StateChange::Stake { .. }]), "{:?}", diff.changes);
+    assert!(diffed.chain.blockchain().state_diff(from, to, Some("GENX_NOBODY")).unwrap().changes.is_empty());
+}
+
+/// Checks ranges that are backwards, past the tip or older than the undo records are refused
+#[test]
+fn check_bad_ranges() {
+    let mut diffed = Diffed::new();
+    let tip = diffed.chain.height();
+    let blockchain = diffed.chain.blockchain();
+    assert!(blockchain.state_diff(tip, tip - 1, None).is_err());
+    assert!(blockchain.state_diff(diffed.from, tip + 1, None).is_err());
+    
+    diffed.chain.with_empty_blocks(MAX_ROLLBACK_DEPTH);
+    let tip = diffed.chain.height();
+    let blockchain = diffed.chain.blockchain();
+    assert!(matches!(
+        blockchain.state_diff(diffed.from, tip, None),
+        Err(BlockchainError::BeyondRollbackDepth { max_depth: MAX_ROLLBACK_DEPTH })
+    ));
+    assert!(blockchain.state_diff(tip - MAX_ROLLBACK_DEPTH, tip, None).is_ok());
+}
+
+/// Checks `genx_getStateDiff` pages through the same changes and filters by address
+#[test]
+fn check_rpc_pages() {
+    let diffed = Diffed::new();
+    let (from, to, contract) = (diffed.from, diffed.chain.height(), diffed.contract.clone());
+    let expected = serde_json::to_value(diffed.expected()).unwrap();
+    let config = NodeConfig { rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() }, ..NodeConfig::default() };
+    let node = Node::new(config, diffed.chain.into_blockchain());
+    let handler = node.rpc_handler();
+    let call = |params: Value| handler.handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": "genx_getStateDiff", "params": params }));
+    
+    let mut changes = Vec::new();
+    loop {
+        let response = call(json!([from, to, null, changes.len(), 3]));
+        let page = &response["result"];
+        assert!(page["changes"].as_array().unwrap().len() <= 3, "{}", response);
+        changes.extend(page["changes"].as_array().unwrap().iter().cloned());
+        if page["more"] == json!(false) {
+            break;
+        }
+    }
+    assert_eq!(Value::Array(changes), expected);
+    
+    let response = call(json!([from, to, contract]));
+    assert_eq!(response["result"]["changes"].as_array().unwrap().len(), 2, "{}", response);
+    assert_eq!(response["result"]["more"], json!(false));
+    for params in [json!([to, from]), json!([from, to, null, 0, 0]), json!([from])] {
+        assert!(call(params.clone())["error"].is_object(), "{}", params);
+    }
+}
\ No newline at end of file
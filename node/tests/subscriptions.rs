@@ -0,0 +1,135 @@
+//! Checks `logs` subscriptions deliver the logs of mined blocks
+//!
+//! Run with `cargo test -p node --features testutil --test subscriptions`.
+//! Mines a block whose token emits both a `Transfer` and an `Approval`
+//! event, with connections subscribed by topic, and checks each gets only
+//! the event it asked for, that a rolled back block's logs come again as
+//! removed, and that connections can't hold subscriptions without bound.
+
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use tokio::sync::mpsc::Receiver;
+
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::receipt::LogFilter;
+use ctb_core::transaction::{to_evm_address, TransactionType};
+
+use node::subscriptions::{SubscriptionError, SubscriptionManager, MAX_SUBSCRIPTIONS_PER_CONNECTION};
+use smartcontracts::abi::{self, Value as AbiValue};
+use smartcontracts::evm::contract_address;
+use smartcontracts::token::{self, TokenParams, TOKEN_FACTORY_ADDRESS};
+use smartcontracts::u256::U256;
+use smartcontracts::{ContractEngine, GasConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 53;
+
+/// Signatures of the token's events
+const TRANSFER: &str = "Transfer(address,address,uint256)";
+const APPROVAL: &str = "Approval(address,address,uint256)";
+
+/// Builds a chain executing contracts, with alice's token created in block 1, returning the token's address
+fn token_chain(subscriptions: Arc<SubscriptionManager>) -> (TestChain, String) {
+    let mut chain = TestChain::new(SEED);
+    chain.blockchain_mut().set_contract_executor(Arc::new(Mutex::new(ContractEngine::new(GasConfig::default()))));
+    chain.blockchain_mut().add_listener(subscriptions);
+    
+    let params = TokenParams { name: "Test Token".to_string(), symbol: "TST".to_string(), decimals: 0, initial_supply: U256::from(1_000u64) };
+    let factory = contract_address(&TOKEN_FACTORY_ADDRESS);
+    let block = chain.next_block(|b| b.call("alice", &factory, params.to_call_data().unwrap()));
+    let create = block.transactions.iter().find(|tx| tx.tx_type == TransactionType::ContractCall).unwrap();
+    let token = create.contract_address();
+    chain.add_block(block);
+    (chain, token)
+}
+
+/// Builds the call data of an ERC-20 function
+fn call_data(selector: [u8; 4], args: &[AbiValue]) -> Vec<u8> {
+    let function = token::erc20_functions().into_iter().find(|function| function.signature == selector).unwrap();
+    let mut data = selector.to_vec();
+    data.extend(abi::encode(&function.inputs, args).unwrap());
+    data
+}
+
+/// Gets an event's first topic in the form notifications carry it
+fn topic(signature: &str) -> String {
+    format!("0x{}", hex::encode(abi::keccak256(signature.as_bytes())))
+}
+
+/// Takes every notification waiting on a connection
+fn drain(notifications: &mut Receiver<Value>) -> Vec<Value> {
+    std::iter::from_fn(|| notifications.try_recv().ok()).collect()
+}
+
+/// Checks each subscription gets only the events its topic filter matches, removed again on rollback
+#[test]
+fn check_topic_filter() {
+    let subscriptions = Arc::new(SubscriptionManager::new());
+    let (mut chain, token) = token_chain(subscriptions.clone());
+    
+    let (transfers, mut transfer_notifications) = subscriptions.connect();
+    let request = json!({ "id": 1, "method": "subscribe", "params": ["logs", { "topics": [topic(TRANSFER)] }] });
+    let response = subscriptions.handle_request(transfers, &request);
+    let subscription = response["result"].as_u64().unwrap_or_else(|| panic!("{}", response));
+    
+    let (approvals, mut approval_notifications) = subscriptions.connect();
+    let filter: LogFilter = serde_json::from_value(json!({ "address": token, "topics": [topic(APPROVAL)] })).unwrap();
+    subscriptions.subscribe_logs(approvals, filter).unwrap();
+    
+    let bob = to_evm_address(&chain.address("bob"));
+    let carol = to_evm_address(&chain.address("carol"));
+    chain.with_block(|b| {
+        b.call("alice", &token, call_data(token::TRANSFER_SELECTOR, &[AbiValue::Address(bob), AbiValue::Uint(U256::from(100u64))]))
+            .call("alice", &token, call_data(token::APPROVE_SELECTOR, &[AbiValue::Address(carol), AbiValue::Uint(U256::from(50u64))]))
+    });
+    let block_hash = chain.blockchain().get_latest_block().unwrap().hash().unwrap();
+    
+    let delivered = drain(&mut transfer_notifications);
+    assert_eq!(delivered.len(), 1, "{:?}", delivered);
+    let result = &delivered[0]["params"]["result"];
+    assert_eq!(delivered[0]["params"]["subscription"], json!(subscription));
+    assert_eq!(result["topics"][0], json!(topic(TRANSFER)));
+    assert_eq!(result["address"], json!(token));
+    assert_eq!((&result["block_height"], &result["removed"]), (&json!(2), &json!(false)));
+    assert_eq!(result["block_hash"], serde_json::to_value(block_hash).unwrap());
+    
+    let delivered = drain(&mut approval_notifications);
+    assert_eq!(delivered.len(), 1, "{:?}", delivered);
+    assert_eq!(delivered[0]["params"]["result"]["topics"][0], json!(topic(APPROVAL)));
+    
+    // Rolling the block back sends its logs again, marked removed
+    chain.blockchain_mut().rollback_to(1).unwrap();
+    let removed = drain(&mut transfer_notifications);
+    assert_eq!(removed.len(), 1, "{:?}", removed);
+    assert_eq!(removed[0]["params"]["result"]["removed"], json!(true));
+    assert_eq!(removed[0]["params"]["result"]["tx_id"], result["tx_id"]);
+    
+    // An unsubscribed connection hears nothing more
+    subscriptions.unsubscribe(approvals, 0).expect_err("no such subscription");
+    let response = subscriptions.handle_request(transfers, &json!({ "id": 2, "method": "unsubscribe", "params": [subscription] }));
+    assert_eq!(response["result"], json!(true));
+    chain.with_block(|b| b.call("alice", &token, call_data(token::TRANSFER_SELECTOR, &[AbiValue::Address(bob), AbiValue::Uint(U256::ONE)])));
+    assert!(drain(&mut transfer_notifications).is_empty());
+}
+
+/// Checks a connection holds at most the allowed number of subscriptions
+#[test]
+fn check_subscription_limit() {
+    let subscriptions = SubscriptionManager::new();
+    let (connection, _notifications) = subscriptions.connect();
+    let ids: Vec<u64> = (0..MAX_SUBSCRIPTIONS_PER_CONNECTION)
+        .map(|_| subscriptions.subscribe_logs(connection, LogFilter::default()).unwrap())
+        .collect();
+    
+    let error = subscriptions.subscribe_logs(connection, LogFilter::default()).expect_err("the connection is full");
+    assert!(matches!(error, SubscriptionError::TooManySubscriptions(MAX_SUBSCRIPTIONS_PER_CONNECTION)));
+    let response = subscriptions.handle_request(connection, &json!({ "id": 1, "method": "subscribe", "params": ["addressActivity"] }));
+    assert!(response.get("error").is_some(), "{}", response);
+    
+    // Other connections have limits of their own, and cancelling frees a place
+    let (other, _other_notifications) = subscriptions.connect();
+    subscriptions.subscribe_logs(other, LogFilter::default()).unwrap();
+    subscriptions.unsubscribe(connection, ids[0]).unwrap();
+    subscriptions.subscribe_logs(connection, LogFilter::default()).unwrap();
+}
\ No newline at end of file
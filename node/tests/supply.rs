@@ -0,0 +1,80 @@
+//! Checks the supply reported by `genx_getSupplyInfo` adds up
+//!
+//! Run with `cargo test -p node --features testutil --test supply`. Builds
+//! a chain of transfers paying flat fees, with a block forfeiting its
+//! reward, and checks the figures the node reports reconcile with the
+//! blocks, the reward schedule and the state.
+
+use serde_json::{json, Value};
+
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::rewards;
+
+use node::{Node, NodeConfig};
+use node::rpc::RpcConfig;
+
+/// Seed of the chain built
+const SEED: u64 = 41;
+
+/// Checks the genesis allocation plus what blocks emitted, less what was burned, is the total supply
+#[test]
+fn check_supply() {
+    let mut chain = TestChain::new(SEED);
+    chain
+        .with_block(|block| block.transfer("alice", "bob", 5_000))
+        .with_empty_blocks(2)
+        .with_block(|block| block.transfer("bob", "carol", 2_000).transfer("carol", "alice", 1_000));
+    let fee = chain.config().transfer_fee;
+    let (genesis_allocation, total_supply) = {
+        let state = chain.blockchain().get_state();
+        let state = state.lock().unwrap();
+        assert_eq!(state.get_total_burned(), 3 * fee, "flat fees are burned");
+        (chain.blockchain().emission_between(0, 0).unwrap(), state.get_total_supply())
+    };
+    
+    let height = chain.height();
+    let scheduled: u64 = (1..=height).map(rewards::block_reward).sum();
+    let info = supply_info(&Node::new(config(), chain.into_blockchain()));
+    assert_eq!(info["height"], json!(height));
+    assert_eq!(info["genesis_allocation"], json!(genesis_allocation));
+    assert_eq!(info["emitted"], json!(scheduled), "every block minted its reward");
+    assert_eq!(info["forfeited"], json!(0));
+    assert_eq!(info["burned"], json!(3 * fee));
+    assert_eq!(info["total_supply"], json!(total_supply));
+    assert_eq!(genesis_allocation + scheduled - 3 * fee, total_supply);
+}
+
+/// Checks a block leaving out its coinbase transactions counts as forfeiting its reward, not emitting it
+#[test]
+fn check_forfeited() {
+    let mut chain = TestChain::new(SEED);
+    chain.with_empty_blocks(1);
+    let built = chain.next_block(|block| block.transfer("alice", "bob", 5_000));
+    let header = built.header().clone();
+    let transactions = built.transactions.into_iter().filter(|tx| tx.sender != "COINBASE").collect();
+    let mut block = Block::new(header.height, header.prev_hash, transactions, header.validator, header.base_fee).unwrap();
+    block.header_mut().timestamp = header.timestamp;
+    chain.account("validator").sign_header(block.header_mut()).unwrap();
+    chain.add_block(block);
+    let fee = chain.config().transfer_fee;
+    
+    let info = supply_info(&Node::new(config(), chain.into_blockchain()));
+    let reward = rewards::block_reward(1);
+    assert_eq!(info["emitted"], json!(reward));
+    assert_eq!(info["forfeited"], json!(rewards::block_reward(2)));
+    let genesis_allocation = info["genesis_allocation"].as_u64().unwrap();
+    assert_eq!(info["total_supply"], json!(genesis_allocation + reward - fee));
+}
+
+/// Configures a node serving requests only through its handler
+fn config() -> NodeConfig {
+    NodeConfig { rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() }, ..NodeConfig::default() }
+}
+
+/// Requests the supply info of a node
+fn supply_info(node: &Node) -> Value {
+    let response = node.rpc_handler().handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": "genx_getSupplyInfo", "params": [] }));
+    assert!(response.get("error").is_none(), "genx_getSupplyInfo failed: {}", response);
+    response["result"].clone()
+}
\ No newline at end of file
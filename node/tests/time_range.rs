@@ -0,0 +1,216 @@
+//! Checks blocks and transactions are found by the times their blocks were made
+//!
+//! Run with `cargo test -p node --features testutil --test time_range`.
+//! Builds a chain of blocks a few seconds apart, with a long gap in the
+//! middle, and checks the chain's time index, the `/blocks/at`,
+//! `/blocks/range` and `/txs/range` routes and the `genx_` time range
+//! methods include both ends of a range, find nothing before genesis and
+//! the tip after it, and page through everything in between.
+
+use serde_json::{json, Value};
+
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::TxHash;
+
+use node::rest::RestResponse;
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+
+/// Seed of the chain built
+const SEED: u64 = 73;
+
+/// Blocks after genesis
+const BLOCKS: u64 = 24;
+
+/// Height of the block made after the gap
+const GAP_HEIGHT: u64 = 12;
+
+/// Seconds of the gap before `GAP_HEIGHT`, by which it and the blocks after are stamped later
+const GAP: u64 = 3_600;
+
+/// A chain, the times its blocks were made and its transactions, by height
+struct TimedChain {
+    chain: TestChain,
+    times: Vec<u64>,
+    transactions: Vec<(u64, TxHash)>,
+}
+
+impl TimedChain {
+    /// Builds the chain: alice pays bob in every other block
+    fn new() -> Self {
+        let mut chain = TestChain::new(SEED);
+        for height in 1..=BLOCKS {
+            let gap = if height >= GAP_HEIGHT { GAP } else { 0 };
+            chain.with_block(|b| {
+                let timestamp = b.timestamp() + gap;
+                b.at(timestamp);
+                if height % 2 == 0 {
+                    b.transfer("alice", "bob", 1_000);
+                }
+                b
+            });
+        }
+        let times = chain.blocks().iter().map(|block| block.header().timestamp).collect();
+        let transactions = chain
+            .blocks()
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(|tx| (block.header().height, tx.id)))
+            .collect();
+        Self { chain, times, transactions }
+    }
+    
+    /// Gets the heights of the blocks made from `from` to `to`, by scanning every block
+    fn heights(&self, from: u64, to: u64) -> Vec<u64> {
+        (0..=BLOCKS).filter(|&height| (from..=to).contains(&self.times[height as usize])).collect()
+    }
+    
+    /// Gets the transactions of the blocks made from `from` to `to`, by scanning every block
+    fn transactions(&self, from: u64, to: u64) -> Vec<(u64, TxHash)> {
+        let heights = self.heights(from, to);
+        self.transactions.iter().filter(|(height, _)| heights.contains(height)).cloned().collect()
+    }
+    
+    /// Gets ranges of times to query: a block's time on each end, a second either side of one, and both around the gap
+    fn ranges(&self) -> Vec<(u64, u64)> {
+        let t = |height: u64| self.times[height as usize];
+        vec![
+            (t(3), t(7)),
+            (t(3) + 1, t(7)),
+            (t(3), t(7) - 1),
+            (t(3) - 1, t(7) + 1),
+            (t(5), t(5)),
+            (t(GAP_HEIGHT - 1) + 1, t(GAP_HEIGHT) - 1),
+            (t(GAP_HEIGHT - 2), t(GAP_HEIGHT + 2)),
+            (0, t(0) - 1),
+            (0, u64::MAX),
+            (t(BLOCKS) + 1, u64::MAX),
+            (t(7), t(3)),
+        ]
+    }
+}
+
+/// Gets a response header
+fn header<'a>(response: &'a RestResponse, name: &str) -> Option<&'a str> {
+    response.headers.iter().find(|(header, _)| *header == name).map(|(_, value)| value.as_str())
+}
+
+/// Follows a paginated route's `Link` headers, returning every page's entries under `key`
+fn pages(node: &Node, target: &str, key: &str) -> Vec<Value> {
+    let mut entries = Vec::new();
+    let mut target = Some(target.to_string());
+    while let Some(next) = target {
+        let response = node.rest_api().handle(&next);
+        assert_eq!(response.status, 200, "{}: {}", next, response.body);
+        target = header(&response, "Link").map(|link| link[1..link.find('>').unwrap()].to_string());
+        entries.extend(response.body[key].as_array().unwrap().iter().cloned());
+    }
+    entries
+}
+
+/// Creates a node over the chain
+fn node(chain: TestChain) -> Node {
+    let config = NodeConfig { rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() }, ..NodeConfig::default() };
+    Node::new(config, chain.into_blockchain())
+}
+
+/// Checks the chain finds the tip as of a time, and the blocks and transactions made in a range, as a scan would
+#[test]
+fn check_chain_index() {
+    let timed = TimedChain::new();
+    let blockchain = timed.chain.blockchain();
+    
+    assert_eq!(blockchain.find_block_by_time(0), None);
+    assert_eq!(blockchain.find_block_by_time(timed.times[0] - 1), None);
+    for height in 0..=BLOCKS {
+        let time = timed.times[height as usize];
+        assert_eq!(blockchain.find_block_by_time(time), Some(height));
+        assert_eq!(blockchain.find_block_by_time(time + 1), Some(height));
+    }
+    assert_eq!(blockchain.find_block_by_time(timed.times[GAP_HEIGHT as usize] - 1), Some(GAP_HEIGHT - 1));
+    assert_eq!(blockchain.find_block_by_time(u64::MAX), Some(BLOCKS));
+    
+    for (from, to) in timed.ranges() {
+        let heights: Vec<u64> = blockchain.get_blocks_in_time_range(from, to).map(|block| block.header().height).collect();
+        assert_eq!(heights, timed.heights(from, to), "blocks from {} to {}", from, to);
+        assert_eq!(blockchain.heights_in_time_range(from, to).collect::<Vec<_>>(), heights);
+        
+        let transactions: Vec<(u64, TxHash)> = blockchain.get_transactions_in_time_range(from, to).map(|(height, _, tx)| (height, tx.id)).collect();
+        assert_eq!(transactions, timed.transactions(from, to), "transactions from {} to {}", from, to);
+        for (height, index, tx) in blockchain.get_transactions_in_time_range(from, to) {
+            assert_eq!(blockchain.get_block_by_height(height).unwrap().transactions[index].id, tx.id);
+        }
+    }
+}
+
+/// Checks the REST routes page through the blocks and transactions of each range, and find blocks by time
+#[test]
+fn check_rest_routes() {
+    let timed = TimedChain::new();
+    let (times, ranges) = (timed.times.clone(), timed.ranges());
+    let expected: Vec<_> = ranges.iter().map(|&(from, to)| (timed.heights(from, to), timed.transactions(from, to))).collect();
+    let node = node(timed.chain);
+    
+    for ((from, to), (heights, transactions)) in ranges.into_iter().zip(expected) {
+        let response = node.rest_api().handle(&format!("/blocks/range?from={}&to={}&limit=3", from, to));
+        assert_eq!(header(&response, "X-Total-Count"), Some(heights.len().to_string().as_str()));
+        let blocks = pages(&node, &format!("/blocks/range?from={}&to={}&limit=3", from, to), "blocks");
+        let block_heights: Vec<u64> = blocks.iter().map(|block| block["height"].as_u64().unwrap()).collect();
+        assert_eq!(block_heights, heights, "blocks from {} to {}", from, to);
+        
+        let txs = pages(&node, &format!("/txs/range?from={}&to={}&limit=2", from, to), "transactions");
+        let found: Vec<(u64, TxHash)> = txs
+            .iter()
+            .map(|tx| (tx["block_height"].as_u64().unwrap(), serde_json::from_value(tx["transaction"]["id"].clone()).unwrap()))
+            .collect();
+        assert_eq!(found, transactions, "transactions from {} to {}", from, to);
+        for tx in &txs {
+            assert_eq!(tx["block_timestamp"], json!(times[tx["block_height"].as_u64().unwrap() as usize]));
+        }
+    }
+    
+    let gap_time = times[GAP_HEIGHT as usize];
+    let response = node.rest_api().handle(&format!("/blocks/at/{}", gap_time - 1));
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body["block"]["header"]["height"], json!(GAP_HEIGHT - 1));
+    assert_eq!(node.rest_api().handle(&format!("/blocks/at/{}", gap_time)).body["block"]["header"]["height"], json!(GAP_HEIGHT));
+    assert_eq!(node.rest_api().handle(&format!("/blocks/at/{}", u64::MAX)).body["block"]["header"]["height"], json!(BLOCKS));
+    assert_eq!(node.rest_api().handle(&format!("/blocks/at/{}", times[0] - 1)).status, 404);
+    assert_eq!(node.rest_api().handle("/blocks/at/yesterday").status, 400);
+    assert_eq!(node.rest_api().handle("/blocks/range?from=soon").status, 400);
+    
+    let all = pages(&node, "/blocks/range", "blocks");
+    assert_eq!(all.len() as u64, BLOCKS + 1);
+}
+
+/// Checks the `genx_` time range methods answer as the REST routes do
+#[test]
+fn check_rpc_methods() {
+    let timed = TimedChain::new();
+    let times = timed.times.clone();
+    let (from, to) = (times[3], times[GAP_HEIGHT as usize]);
+    let (heights, transactions) = (timed.heights(from, to), timed.transactions(from, to));
+    let node = node(timed.chain);
+    let handler = node.rpc_handler();
+    let call = |method: &str, params: Value| {
+        let response = handler.handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }));
+        assert!(response.get("error").is_none(), "{}: {}", method, response);
+        response["result"].clone()
+    };
+    
+    assert_eq!(call("genx_getBlockByTime", json!([times[0] - 1])), Value::Null);
+    assert_eq!(call("genx_getBlockByTime", json!([times[GAP_HEIGHT as usize] - 1])), json!(GAP_HEIGHT - 1));
+    
+    let blocks = call("genx_getBlocksInTimeRange", json!([from, to, 2, 3]));
+    assert_eq!(blocks["total"], json!(heights.len()));
+    let page: Vec<u64> = blocks["blocks"].as_array().unwrap().iter().map(|block| block["height"].as_u64().unwrap()).collect();
+    assert_eq!(page, heights[2..5]);
+    
+    let first = call("genx_getTransactionsInTimeRange", json!([from, to, 0, transactions.len() - 1]));
+    assert_eq!(first["more"], json!(true));
+    let last = call("genx_getTransactionsInTimeRange", json!([from, to, transactions.len() - 1, 10]));
+    assert_eq!(last["more"], json!(false));
+    assert_eq!(last["transactions"].as_array().unwrap().len(), 1);
+    
+    let response = handler.handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": "genx_getBlocksInTimeRange", "params": [from] }));
+    assert!(response["error"]["message"].as_str().unwrap().contains("missing to"), "{}", response);
+}
\ No newline at end of file
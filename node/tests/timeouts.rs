@@ -0,0 +1,129 @@
+//! Checks simulated connections time out in each state they wait in, and their slots are reclaimed
+//!
+//! Run with `cargo test -p node --features testutil --test timeouts`. Opens
+//! a connection at a simulated node that no handshake ever arrives over,
+//! and checks it's closed once `HANDSHAKE_TIMEOUT` passes, freeing its
+//! address to be connected again. Then partitions two nodes the moment
+//! they're handshaken, so neither hears from the other, and checks both
+//! close their connections once `FIRST_MESSAGE_TIMEOUT` passes and connect
+//! again when the partition heals. Then drops every message once a node
+//! that fell behind asks the validator for headers, and checks it scores
+//! the validator down for each request left past its deadline until it
+//! disconnects it, freeing the slot for the two to connect again.
+
+use std::net::SocketAddr;
+
+use node::connection::{self, ConnectionState, FIRST_MESSAGE_TIMEOUT, HANDSHAKE_TIMEOUT, MIN_SCORE, TIMEOUT_PENALTY};
+use node::message::NetworkMessage;
+use node::sim::{self, SimConfig, Simulation};
+
+/// Milliseconds between ticks, when timeouts are checked
+const TICK: u64 = 1000;
+
+/// Address of a connection no handshake arrives over
+const SILENT: &str = "192.0.2.7:30303";
+
+/// Creates a simulation of a validator and a node following it
+fn simulation() -> Simulation {
+    Simulation::new(SimConfig { nodes: 2, validators: 1, tick_interval: TICK, ..SimConfig::default() }).unwrap()
+}
+
+/// Gets the state of a node's connection to an address, if open
+fn state(sim: &Simulation, index: usize, address: SocketAddr) -> Option<ConnectionState> {
+    sim.node(index).connections().iter().find(|connection| connection.address() == address).map(|connection| connection.state())
+}
+
+/// Checks a connection no handshake arrives over is closed after `HANDSHAKE_TIMEOUT`, and its address can connect again
+#[test]
+fn check_handshake_timeout() {
+    let mut sim = simulation();
+    let silent: SocketAddr = SILENT.parse().unwrap();
+    let opened_at = sim.now();
+    assert!(sim.open_connection(0, silent, false));
+    assert!(!sim.open_connection(0, silent, false), "the address holds its slot");
+    assert_eq!(state(&sim, 0, silent), Some(ConnectionState::Connected));
+    
+    sim.run_for(HANDSHAKE_TIMEOUT.as_millis() as u64 - TICK);
+    assert_eq!(state(&sim, 0, silent), Some(ConnectionState::Connected));
+    sim.run_until(2 * TICK, |sim| state(sim, 0, silent).is_none());
+    assert_eq!(state(&sim, 0, silent), None);
+    assert!(sim.now() - opened_at >= HANDSHAKE_TIMEOUT.as_millis() as u64);
+    
+    // The peer is untouched, and the slot is free again
+    assert_eq!(state(&sim, 0, sim::node_address(1)), Some(ConnectionState::Active));
+    assert_eq!(sim.node(0).connections().len(), 1);
+    assert!(sim.open_connection(0, silent, true));
+}
+
+/// Checks handshaken nodes that never hear from each other close their connections after `FIRST_MESSAGE_TIMEOUT`
+#[test]
+fn check_first_message_timeout() {
+    let mut sim = simulation();
+    sim.partition(&[&[0], &[1]]);
+    for (index, peer) in [(0, 1), (1, 0)] {
+        assert_eq!(state(&sim, index, sim::node_address(peer)), Some(ConnectionState::Established));
+    }
+    
+    sim.run_for(FIRST_MESSAGE_TIMEOUT.as_millis() as u64 - TICK);
+    for (index, peer) in [(0, 1), (1, 0)] {
+        assert_eq!(state(&sim, index, sim::node_address(peer)), Some(ConnectionState::Established));
+    }
+    sim.run_for(2 * TICK);
+    for index in 0..2 {
+        assert!(sim.node(index).connections().is_empty(), "{:?}", sim.node(index).connections());
+        assert_eq!(sim.node(index).get_peer_count(), 0);
+    }
+    
+    // Healed, they connect again and hear from each other
+    sim.heal();
+    sim.run_for(TICK);
+    for (index, peer) in [(0, 1), (1, 0)] {
+        let state = state(&sim, index, sim::node_address(peer));
+        assert!(matches!(state, Some(ConnectionState::Active | ConnectionState::Syncing)), "{:?}", state);
+        assert_eq!(sim.node(index).get_peer_count(), 1);
+    }
+}
+
+/// Checks a peer that stops answering is scored down for each request past its deadline, then disconnected
+#[test]
+fn check_request_timeouts() {
+    let mut sim = simulation();
+    let validator = sim::node_address(0);
+    let connection = |sim: &Simulation| sim.node(1).connections().into_iter().find(|connection| connection.address() == validator);
+    
+    // The follower falls behind while cut off, then asks the validator for headers once it hears of them
+    sim.run_for(TICK);
+    sim.partition(&[&[0], &[1]]);
+    sim.run_for(30 * TICK);
+    sim.heal();
+    assert!(sim.run_until(10 * TICK, |sim| connection(sim).is_some_and(|connection| connection.state() == ConnectionState::Syncing)));
+    let asked_at = sim.now();
+    let (request, deadline) = connection::response_deadline(&NetworkMessage::GetHeaders(Default::default())).unwrap();
+    assert_eq!(request, "headers");
+    
+    // From then on nothing it's sent arrives, and every request it makes costs the validator
+    sim.set_drop_rate(1.0);
+    let mut scores = vec![0];
+    let disconnected = sim.run_until(120 * TICK, |sim| match connection(sim) {
+        Some(connection) => {
+            if scores.last() != Some(&connection.score()) {
+                scores.push(connection.score());
+            }
+            false
+        }
+        None => true,
+    });
+    assert!(disconnected, "{:?}", scores);
+    assert!(sim.now() - asked_at >= deadline.as_millis() as u64);
+    assert!(scores.windows(2).all(|pair| pair[1] < pair[0] && (pair[0] - pair[1]) % TIMEOUT_PENALTY == 0), "{:?}", scores);
+    assert!(*scores.last().unwrap() > MIN_SCORE, "disconnected on reaching it: {:?}", scores);
+    assert!(scores.len() > 1, "{:?}", scores);
+    
+    // Its slot is reclaimed, and the validator can connect again
+    assert_eq!(sim.node(1).get_peer_count(), 0);
+    assert!(sim.node(1).connections().is_empty(), "{:?}", sim.node(1).connections());
+    sim.set_drop_rate(0.0);
+    sim.heal();
+    assert!(sim.run_until(60 * TICK, |sim| sim.converged()), "{:?}", sim.heights());
+    assert_eq!(sim.node(1).get_peer_count(), 1);
+}
\ No newline at end of file
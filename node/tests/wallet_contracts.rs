@@ -0,0 +1,194 @@
+//! Checks the wallet calls and reads contracts by function name through a node
+//!
+//! Run with `cargo test -p node --features testutil --test wallet_contracts`.
+//! Starts an in-process development node (see `node::dev`) where a funded
+//! account creates a token and gives some to an account of a wallet, then
+//! connects the wallet to the node. The wallet reads `balanceOf`, sends
+//! tokens with `transfer` in a transaction the node mines in its next
+//! block, and reads the balances again. Calls whose arguments don't fit the ABI fail with
+//! `AbiMismatch`, calls the token reverts with `TransactionReverted`
+//! carrying its reason, and calls to no contract with `ContractNotFound`.
+
+use std::path::PathBuf;
+
+use serde_json::json;
+use tokio::runtime::Runtime;
+
+use ctb_core::transaction::{to_evm_address, Transaction};
+use ctb_core::units::GENX;
+
+use node::dev::{self, DevAccount, DEV_GENESIS_TIME};
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+use smartcontracts::abi::{self, Value};
+use smartcontracts::evm::contract_address;
+use smartcontracts::token::{self, TokenParams, TOKEN_FACTORY_ADDRESS};
+use smartcontracts::u256::U256;
+use wallet::api::WalletApi;
+use wallet::WalletError;
+
+/// Password of the wallet made
+const PASSWORD: &str = "Wallet-contracts-42";
+
+/// Tokens alice creates
+const SUPPLY: u64 = 1_000;
+
+/// Tokens alice gives the wallet's account
+const HELD: u64 = 300;
+
+/// Tokens the wallet sends bob
+const SENT: u64 = 120;
+
+/// Gas limit of the creator's transactions
+const GAS_LIMIT: u64 = 1_000_000;
+
+/// A development node with the token, and a wallet connected to it
+struct Setup {
+    node: Node,
+    api: WalletApi,
+    holder: String,
+    bob: String,
+    token: String,
+    path: PathBuf,
+    _runtime: Runtime,
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        self.node.stop();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Setup {
+    /// Starts the node, creates the token and funds the wallet's account with tokens and coins
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("genx-wallet-contracts-{}-{}.json", std::process::id(), name));
+        let mut api = WalletApi::create_wallet(path.clone(), PASSWORD).unwrap();
+        api.unlock(PASSWORD).unwrap();
+        let holder = api.create_account("holder").unwrap();
+        
+        let config = NodeConfig {
+            dev_mode: true,
+            data_dir: std::env::temp_dir().join(format!("genx-wallet-contracts-{}", name)).display().to_string(),
+            rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+            ..NodeConfig::default()
+        };
+        let blockchain = dev::genesis(&config.consensus_params).unwrap();
+        let runtime = Runtime::new().unwrap();
+        let mut node = Node::new(config, blockchain);
+        runtime.block_on(node.start()).unwrap();
+        
+        // Each transaction is mined as it's admitted
+        let accounts = dev::accounts();
+        let (creator, bob) = (&accounts[0], accounts[1].address.to_string());
+        let params = TokenParams { name: "Test Token".to_string(), symbol: "TST".to_string(), decimals: 0, initial_supply: U256::from(SUPPLY) };
+        let create = send(&mut node, creator, &contract_address(&TOKEN_FACTORY_ADDRESS), params.to_call_data().unwrap());
+        let token = create.contract_address();
+        let give = call_data(token::TRANSFER_SELECTOR, &[address(&holder), amount(HELD)]);
+        send(&mut node, creator, &token, give);
+        let funded = node.rpc_handler().handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": "dev_setBalance", "params": [holder, 10 * GENX] }));
+        assert!(funded.get("error").is_none(), "{}", funded);
+        
+        api.set_client(node.wallet_client());
+        Self { node, api, holder, bob, token, path, _runtime: runtime }
+    }
+    
+    /// Reads an account's token balance through the ABI
+    fn balance_of(&self, account: &str) -> Vec<Value> {
+        self.api.read_contract(&self.token, "balanceOf", vec![address(account)]).unwrap()
+    }
+
+}
+
+/// Has a development account call a contract, returning the transaction once it's mined
+fn send(node: &mut Node, sender: &DevAccount, contract: &str, data: Vec<u8>) -> Transaction {
+    let base_fee = node.wallet_client().next_base_fee();
+    let mut tx = Transaction::new_contract_call(sender.address.to_string(), contract.to_string(), 0, data, GAS_LIMIT, base_fee).unwrap();
+    tx.nonce = node.wallet_client().get_nonce(sender.address.as_str()).unwrap();
+    tx.timestamp = DEV_GENESIS_TIME;
+    tx.id = tx.calculate_hash().unwrap();
+    sender.sign(&mut tx).unwrap();
+    node.add_transaction(tx.clone()).unwrap();
+    assert!(!node.is_pending(&tx.id));
+    tx
+}
+
+/// Makes an address argument for an account
+fn address(account: &str) -> Value {
+    Value::Address(to_evm_address(account))
+}
+
+/// Makes an amount argument
+fn amount(tokens: u64) -> Value {
+    Value::Uint(U256::from(tokens))
+}
+
+/// Builds the call data of an ERC-20 function
+fn call_data(selector: [u8; 4], args: &[Value]) -> Vec<u8> {
+    let function = token::erc20_functions().into_iter().find(|function| function.signature == selector).unwrap();
+    let mut data = selector.to_vec();
+    data.extend(abi::encode(&function.inputs, args).unwrap());
+    data
+}
+
+/// Checks tokens sent with `transfer` show in both accounts' `balanceOf` once the transaction is in a block
+#[test]
+fn check_transfer() {
+    let setup = Setup::new("transfer");
+    assert_eq!(setup.balance_of(&setup.holder), vec![amount(HELD)]);
+    assert_eq!(setup.balance_of(&setup.bob), vec![amount(0)]);
+    
+    let args = vec![address(&setup.bob), amount(SENT)];
+    let id = setup.api.call_contract(&setup.holder, &setup.token, "transfer", args, 0, None).unwrap();
+    assert!(setup.node.is_pending(&id));
+    assert_eq!(setup.balance_of(&setup.bob), vec![amount(0)], "nothing moves before the block");
+    
+    setup.node.dev_mine_block().unwrap();
+    assert!(!setup.node.is_pending(&id));
+    assert_eq!(setup.balance_of(&setup.holder), vec![amount(HELD - SENT)]);
+    assert_eq!(setup.balance_of(&setup.bob), vec![amount(SENT)]);
+    let total = setup.api.read_contract(&setup.token, "totalSupply", Vec::new()).unwrap();
+    assert_eq!(total, vec![amount(SUPPLY)]);
+}
+
+/// Checks arguments of the wrong count or type, or an unknown function, are ABI mismatches
+#[test]
+fn check_abi_mismatch() {
+    let setup = Setup::new("mismatch");
+    let (token, holder, bob) = (&setup.token, &setup.holder, &setup.bob);
+    let calls = [
+        ("transfer", vec![address(bob)]),
+        ("transfer", vec![address(bob), amount(SENT), amount(SENT)]),
+        ("transfer", vec![amount(SENT), address(bob)]),
+        ("transfer", vec![address(bob), Value::Bool(true)]),
+        ("mint", vec![address(bob), amount(SENT)]),
+    ];
+    for (function, args) in calls {
+        let error = setup.api.call_contract(holder, token, function, args.clone(), 0, None).unwrap_err();
+        assert!(matches!(error, WalletError::AbiMismatch(_)), "{}({:?}): {}", function, args, error);
+    }
+    let error = setup.api.read_contract(token, "balanceOf", Vec::new()).unwrap_err();
+    assert!(matches!(error, WalletError::AbiMismatch(_)), "{}", error);
+    assert_eq!(setup.balance_of(holder), vec![amount(HELD)]);
+}
+
+/// Checks calls the token reverts fail with its reason, and calls to no contract say so
+#[test]
+fn check_reverted() {
+    let setup = Setup::new("reverted");
+    let (token, holder, bob) = (&setup.token, &setup.holder, &setup.bob);
+    let error = setup.api.call_contract(holder, token, "transfer", vec![address(bob), amount(HELD + 1)], 0, None).unwrap_err();
+    match error {
+        WalletError::TransactionReverted(reason) => assert!(reason.contains("transfer amount exceeds balance"), "{}", reason),
+        error => panic!("{}", error),
+    }
+    let zero = Value::Address([0u8; 20]);
+    let error = setup.api.call_contract(holder, token, "transfer", vec![zero, amount(1)], 0, None).unwrap_err();
+    assert!(matches!(&error, WalletError::TransactionReverted(reason) if reason.contains("zero address")), "{}", error);
+    
+    let nowhere = "GENX_CONTRACT_0000000000000000000000000000000000000000";
+    let error = setup.api.read_contract(nowhere, "balanceOf", vec![address(holder)]).unwrap_err();
+    assert!(matches!(&error, WalletError::ContractNotFound(contract) if contract == nowhere), "{}", error);
+    assert_eq!(setup.balance_of(holder), vec![amount(HELD)]);
+}
\ No newline at end of file
@@ -0,0 +1,201 @@
+//! Checks transactions created at once from one account get distinct nonces and never overspend
+//!
+//! Run with `cargo test -p node --features testutil --test wallet_reservations`.
+//! Connects a wallet to an in-process development node (see `node::dev`)
+//! where one of its accounts is funded, and has many threads send from that
+//! account at once. Every transaction gets its own nonce, the nonces follow
+//! one another, and the node mines them all, in nonce order. When the balance
+//! only covers some of the sends, the others fail with `InsufficientFunds`
+//! and nothing mined overspends; cancelling a transaction in flight frees
+//! what it reserved and its nonce.
+
+use std::path::PathBuf;
+use std::thread;
+
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+
+use ctb_core::transaction::Transaction;
+use ctb_core::units::{Amount, GENX};
+use ctb_core::Address;
+
+use node::dev;
+use node::rpc::RpcConfig;
+use node::{Node, NodeConfig};
+use wallet::api::WalletApi;
+use wallet::WalletError;
+
+/// Password of the wallets made
+const PASSWORD: &str = "Wallet-reservations-42";
+
+/// Transactions sent at once
+const SENDS: u64 = 16;
+
+/// Amount of each transaction
+const SENT: u64 = 10 * GENX;
+
+/// Most fee each transaction pays
+const FEE: u64 = GENX / 100;
+
+/// A development node, and a wallet connected to it with a funded account
+struct Setup {
+    node: Node,
+    api: WalletApi,
+    holder: Address,
+    recipient: Address,
+    path: PathBuf,
+    _runtime: Runtime,
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        self.node.stop();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Setup {
+    /// Starts the node and gives the wallet's account `funds`
+    fn new(name: &str, funds: u64) -> Self {
+        let path = std::env::temp_dir().join(format!("genx-wallet-reservations-{}-{}.json", std::process::id(), name));
+        let mut api = WalletApi::create_wallet(path.clone(), PASSWORD).unwrap();
+        api.unlock(PASSWORD).unwrap();
+        let holder: Address = api.create_account("holder").unwrap().parse().unwrap();
+        
+        let config = NodeConfig {
+            dev_mode: true,
+            data_dir: std::env::temp_dir().join(format!("genx-wallet-reservations-{}", name)).display().to_string(),
+            rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+            ..NodeConfig::default()
+        };
+        let blockchain = dev::genesis(&config.consensus_params).unwrap();
+        let runtime = Runtime::new().unwrap();
+        let mut node = Node::new(config, blockchain);
+        runtime.block_on(node.start()).unwrap();
+        api.set_client(node.wallet_client());
+        
+        let setup = Self { node, api, holder, recipient: dev::accounts()[1].address.clone(), path, _runtime: runtime };
+        setup.call("dev_setBalance", json!([setup.holder, funds]));
+        setup
+    }
+    
+    /// Makes a JSON-RPC request of the node that must succeed, returning its result
+    fn call(&self, method: &str, params: Value) -> Value {
+        let response = self.node.rpc_handler().handle_request(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }));
+        assert!(response.get("error").is_none(), "{} failed: {}", method, response);
+        response["result"].clone()
+    }
+    
+    /// Creates a transfer from the funded account, reserving it
+    fn transfer(&self) -> wallet::Result<Transaction> {
+        self.api.create_transaction(&self.holder, &self.recipient, Amount::from_base_units(SENT), Amount::from_base_units(FEE), None)
+    }
+    
+    /// Sends transfers from every thread at once, returning what each thread got
+    fn send_at_once(&self, threads: u64) -> Vec<wallet::Result<Transaction>> {
+        thread::scope(|scope| {
+            let sends: Vec<_> = (0..threads)
+                .map(|_| scope.spawn(|| {
+                    let tx = self.transfer()?;
+                    self.api.send_transaction(&tx)?;
+                    Ok(tx)
+                }))
+                .collect();
+            sends.into_iter().map(|send| send.join().unwrap()).collect()
+        })
+    }
+    
+    /// Mines blocks until none of the transactions is waiting, returning the nonces of the funded account's in the order mined
+    fn mine(&self, transactions: &[Transaction]) -> Vec<u64> {
+        let mut nonces = Vec::new();
+        for _ in 0..=transactions.len() {
+            if !transactions.iter().any(|tx| self.node.is_pending(&tx.id)) {
+                break;
+            }
+            let block = self.node.dev_mine_block().unwrap();
+            let receipts = self.call("genx_getBlockWithReceipts", json!([block.header().height]))["receipts"].clone();
+            assert!(receipts.as_array().unwrap().iter().all(|receipt| receipt["success"] != json!(false)), "{}", receipts);
+            nonces.extend(block.transactions.iter().filter(|tx| tx.sender == self.holder.as_str()).map(|tx| tx.nonce));
+        }
+        assert!(transactions.iter().all(|tx| !self.node.is_pending(&tx.id)), "every transaction is mined");
+        nonces
+    }
+    
+    /// Gets an account's confirmed balance
+    fn balance(&self, address: &Address) -> u64 {
+        self.call("genx_getBalance", json!([address])).as_u64().unwrap()
+    }
+}
+
+/// Checks transactions sent at once from one account get sequential nonces and are all mined
+#[test]
+fn check_concurrent_sends() {
+    let setup = Setup::new("concurrent", 1_000 * GENX);
+    let before = setup.balance(&setup.recipient);
+    let sent: Vec<Transaction> = setup.send_at_once(SENDS).into_iter().map(Result::unwrap).collect();
+    
+    let mut nonces: Vec<u64> = sent.iter().map(|tx| tx.nonce).collect();
+    nonces.sort_unstable();
+    assert_eq!(nonces, (0..SENDS).collect::<Vec<_>>());
+    let reserved: Vec<u64> = setup.api.pending_transactions(setup.holder.as_str()).iter().map(|reservation| reservation.nonce).collect();
+    assert_eq!(reserved, nonces);
+    assert!(sent.iter().all(|tx| setup.node.is_pending(&tx.id)));
+    
+    // Blocks take them all, in nonce order
+    assert_eq!(setup.mine(&sent), nonces);
+    assert_eq!(setup.balance(&setup.recipient) - before, SENDS * SENT);
+    assert_eq!(setup.api.release_confirmed(setup.holder.as_str()).unwrap(), SENDS as usize);
+    assert!(setup.api.pending_transactions(setup.holder.as_str()).is_empty());
+    
+    // The next ones carry on from the chain's nonce
+    let next: Vec<Transaction> = setup.send_at_once(2).into_iter().map(Result::unwrap).collect();
+    let mut nonces: Vec<u64> = next.iter().map(|tx| tx.nonce).collect();
+    nonces.sort_unstable();
+    assert_eq!(nonces, vec![SENDS, SENDS + 1]);
+    assert_eq!(setup.mine(&next), nonces);
+}
+
+/// Checks sends at once that the balance only partly covers fail without overspending
+#[test]
+fn check_concurrent_overspend() {
+    let covered = 5;
+    let setup = Setup::new("overspend", covered * (SENT + FEE) + SENT / 2);
+    let results = setup.send_at_once(SENDS);
+    let sent: Vec<Transaction> = results.iter().filter_map(|result| result.as_ref().ok()).cloned().collect();
+    assert_eq!(sent.len() as u64, covered);
+    for error in results.iter().filter_map(|result| result.as_ref().err()) {
+        match error {
+            WalletError::InsufficientFunds { in_flight, available, .. } => {
+                assert_eq!(*in_flight, covered * (SENT + FEE));
+                assert!(in_flight + SENT + FEE > *available);
+            }
+            error => panic!("{}", error),
+        }
+    }
+    
+    let mut nonces: Vec<u64> = sent.iter().map(|tx| tx.nonce).collect();
+    nonces.sort_unstable();
+    assert_eq!(nonces, (0..covered).collect::<Vec<_>>());
+    assert_eq!(setup.mine(&sent), nonces);
+    assert!(setup.balance(&setup.holder) < SENT + FEE);
+}
+
+/// Checks cancelling a transaction in flight frees what it reserved, and the next one takes its nonce
+#[test]
+fn check_cancel() {
+    let setup = Setup::new("cancel", SENT + FEE + SENT / 2);
+    let first = setup.transfer().unwrap();
+    let error = setup.transfer().unwrap_err();
+    assert!(matches!(error, WalletError::InsufficientFunds { in_flight, .. } if in_flight == SENT + FEE), "{}", error);
+    
+    assert!(setup.api.cancel_pending(&first.id));
+    assert!(!setup.api.cancel_pending(&first.id));
+    assert!(setup.api.pending_transactions(setup.holder.as_str()).is_empty());
+    let second = setup.transfer().unwrap();
+    assert_eq!(second.nonce, first.nonce);
+    assert_ne!(second.id, first.id);
+    
+    setup.api.send_transaction(&second).unwrap();
+    assert_eq!(setup.mine(std::slice::from_ref(&second)), vec![second.nonce]);
+    assert_eq!(setup.api.release_confirmed(setup.holder.as_str()).unwrap(), 1);
+}
\ No newline at end of file
@@ -0,0 +1,201 @@
+//! Checks a node announces the balance changes of watched addresses, and takes them back on a reorganization
+//!
+//! Run with `cargo test -p node --features testutil --test watch`. Has a
+//! node watch alice and carol, with a webhook whose endpoint fails the
+//! first delivery, then imports blocks of transfers touching only alice.
+//! Checks the event bus gets exactly one `AddressActivity` for each of
+//! alice's changes, with her new balance, and the webhook gets the same
+//! payloads once each, the failed delivery retried. Then switches to a
+//! branch paying carol instead, and checks alice's changes are announced
+//! again as removed, with the opposite deltas, before carol's.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast::Receiver;
+
+use ctb_core::block::Block;
+use ctb_core::chainbuilder::TestChain;
+use ctb_core::units::GENX;
+use ctb_core::Address;
+use node::events::NodeEvent;
+use node::rpc::RpcConfig;
+use node::watch::WebhookEndpoint;
+use node::{Node, NodeConfig};
+
+/// Seed of the chains built
+const SEED: u64 = 167;
+
+/// Attempts of each webhook delivery
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before retrying a webhook delivery
+const BACKOFF: Duration = Duration::from_millis(20);
+
+/// Webhook endpoint recording the bodies POSTed to it, failing the first delivery with a 503
+#[derive(Clone, Default)]
+struct Endpoint {
+    bodies: Arc<Mutex<Vec<Value>>>,
+    attempts: Arc<Mutex<usize>>,
+}
+
+impl Endpoint {
+    /// Serves on a local port in the background, returning it
+    fn serve(&self) -> WebhookEndpoint {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/genx", listener.local_addr().unwrap());
+        let endpoint = self.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                let body = read_body(&mut stream);
+                let mut attempts = endpoint.attempts.lock().unwrap();
+                *attempts += 1;
+                let status = if *attempts == 1 {
+                    "503 Service Unavailable"
+                } else {
+                    endpoint.bodies.lock().unwrap().push(serde_json::from_str(&body).unwrap());
+                    "200 OK"
+                };
+                let _ = stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes());
+            }
+        });
+        url.parse().unwrap()
+    }
+    
+    /// Waits for a number of bodies to be delivered, returning them
+    fn delivered(&self, count: usize) -> Vec<Value> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while self.bodies.lock().unwrap().len() < count && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        
+        // Nothing more arrives
+        std::thread::sleep(BACKOFF * 4);
+        self.bodies.lock().unwrap().clone()
+    }
+}
+
+/// Reads the body of an HTTP request
+fn read_body(stream: &mut std::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 1024];
+    loop {
+        let read = stream.read(&mut chunk).unwrap();
+        request.extend_from_slice(&chunk[..read]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length: usize = head.lines().find_map(|line| line.strip_prefix("Content-Length: ")).unwrap().parse().unwrap();
+            if body.len() >= length || read == 0 {
+                return body.to_string();
+            }
+        }
+    }
+}
+
+/// Takes the `AddressActivity` events published so far, as JSON
+fn activity(events: &mut Receiver<NodeEvent>) -> Vec<Value> {
+    let mut activity = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if matches!(event, NodeEvent::AddressActivity { .. }) {
+            activity.push(serde_json::to_value(&event).unwrap());
+        }
+    }
+    activity
+}
+
+/// Gets an account's balance on a chain
+fn balance(chain: &TestChain, who: &str) -> u64 {
+    chain.blockchain().get_balance(&Address::new(chain.address(who)).unwrap()).unwrap().base_units()
+}
+
+/// Checks watched addresses' changes are announced once each on the bus and webhook, and taken back on a reorganization
+#[test]
+fn check_activity() {
+    let runtime = Runtime::new().unwrap();
+    let mut chain = TestChain::new(SEED);
+    let config = NodeConfig {
+        data_dir: std::env::temp_dir().join(format!("genx-watch-{}", std::process::id())).display().to_string(),
+        rpc_config: RpcConfig { enabled: false, ..RpcConfig::default() },
+        ..NodeConfig::default()
+    };
+    let node = Node::new(config.clone(), TestChain::new(SEED).into_blockchain());
+    let watcher = node.watcher();
+    for who in ["alice", "carol"] {
+        assert!(watcher.watch(&chain.address(who)).unwrap());
+    }
+    let endpoint = Endpoint::default();
+    {
+        let _runtime = runtime.enter();
+        watcher.start_webhook(endpoint.serve(), MAX_ATTEMPTS, BACKOFF);
+    }
+    let mut events = node.event_bus().subscribe();
+    
+    // Alice sends bob two transfers, one a block; carol isn't touched
+    let alice = chain.address("alice");
+    let fee = chain.config().transfer_fee;
+    let mut expected = Vec::new();
+    for amount in [GENX, 2 * GENX] {
+        let block = chain.next_block(|b| b.transfer("alice", "bob", amount));
+        let (height, block_hash) = (block.header().height, block.hash().unwrap());
+        let tx_id = block.transactions.last().unwrap().id;
+        chain.add_block(block.clone());
+        node.import_block(block).unwrap();
+        expected.push(json!({
+            "type": "AddressActivity",
+            "address": alice,
+            "delta": -((amount + fee) as i128),
+            "new_balance": balance(&chain, "alice"),
+            "tx_ids": [tx_id],
+            "height": height,
+            "block_hash": block_hash,
+            "removed": false,
+        }));
+    }
+    assert_eq!(activity(&mut events), expected);
+    assert_eq!(endpoint.delivered(2), expected, "each delivered once, the first after a retry");
+    assert_eq!(*endpoint.attempts.lock().unwrap(), 3);
+    
+    // A longer branch paying carol takes alice's changes back, newest first
+    let mut branch = TestChain::new(SEED);
+    let mut branch_blocks: Vec<Arc<Block>> = Vec::new();
+    for _ in 0..3 {
+        let block = branch.next_block(|b| b.transfer("bob", "carol", GENX));
+        branch.add_block(block.clone());
+        branch_blocks.push(Arc::new(block));
+    }
+    node.reorganize(0, branch_blocks.clone()).unwrap();
+    
+    let mut reverted: Vec<Value> = expected.iter().rev().cloned().collect();
+    let mut before = balance(&chain, "alice");
+    for (event, amount) in reverted.iter_mut().zip([2 * GENX, GENX]) {
+        before += amount + fee;
+        event["delta"] = json!((amount + fee) as i128);
+        event["new_balance"] = json!(before);
+        event["removed"] = json!(true);
+    }
+    let carol = chain.address("carol");
+    let mut carol_balance = balance(&TestChain::new(SEED), "carol");
+    for block in &branch_blocks {
+        carol_balance += GENX;
+        reverted.push(json!({
+            "type": "AddressActivity",
+            "address": carol,
+            "delta": GENX as i128,
+            "new_balance": carol_balance,
+            "tx_ids": [block.transactions.last().unwrap().id],
+            "height": block.header().height,
+            "block_hash": block.hash().unwrap(),
+            "removed": false,
+        }));
+    }
+    assert_eq!(activity(&mut events), reverted);
+    assert_eq!(endpoint.delivered(2 + reverted.len())[2..], reverted[..]);
+    assert_eq!(before, balance(&TestChain::new(SEED), "alice"), "the deltas sum to nothing");
+    
+    watcher.stop_webhook();
+    let _ = std::fs::remove_dir_all(&config.data_dir);
+}
\ No newline at end of file
@@ -0,0 +1,823 @@
+//! Solidity ABI encoding and decoding
+//!
+//! This module converts typed values to and from the Solidity contract ABI
+//! format so that callers don't have to build call data by hand, and
+//! encodes and decodes the logs emitted by Solidity events.
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::hashing;
+use ctb_core::receipt::Log;
+use ctb_core::Hash;
+
+use crate::u256::U256;
+use crate::{ABIParameter, ContractError, EventABI, EventParameter, FunctionABI, Result};
+
+/// Size of a single ABI word in bytes
+const WORD_SIZE: usize = 32;
+
+/// A typed value that can be passed to or returned from a contract
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    /// Unsigned integer (uint8 .. uint256)
+    Uint(U256),
+    
+    /// Signed integer (int8 .. int256), stored as its two's complement word
+    Int(U256),
+    
+    /// 20-byte address
+    Address([u8; 20]),
+    
+    /// Boolean
+    Bool(bool),
+    
+    /// Dynamic byte array
+    Bytes(Vec<u8>),
+    
+    /// UTF-8 string
+    String(String),
+    
+    /// Fixed-size byte array (bytes1 .. bytes32)
+    FixedBytes(Vec<u8>),
+    
+    /// Dynamic or fixed-size array
+    Array(Vec<Value>),
+    
+    /// Tuple of values
+    Tuple(Vec<Value>),
+}
+
+/// Parsed form of an ABI parameter type string
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamType {
+    /// uintN
+    Uint(usize),
+    
+    /// intN
+    Int(usize),
+    
+    /// address
+    Address,
+    
+    /// bool
+    Bool,
+    
+    /// bytes
+    Bytes,
+    
+    /// string
+    String,
+    
+    /// bytesN
+    FixedBytes(usize),
+    
+    /// T[]
+    Array(Box<ParamType>),
+    
+    /// T[N]
+    FixedArray(Box<ParamType>, usize),
+    
+    /// (T1,T2,...)
+    Tuple(Vec<ParamType>),
+}
+
+impl ParamType {
+    /// Parses a Solidity type string such as `uint256`, `string[]` or `(address,bool)`
+    pub fn parse(type_str: &str) -> Result<Self> {
+        let type_str = type_str.trim();
+        
+        // Array suffixes bind last, so peel them off first
+        if type_str.ends_with(']') {
+            let open = type_str.rfind('[').ok_or_else(|| {
+                ContractError::AbiError(format!("Invalid array type: {}", type_str))
+            })?;
+            let inner = Self::parse(&type_str[..open])?;
+            let size = &type_str[open + 1..type_str.len() - 1];
+            
+            return if size.is_empty() {
+                Ok(ParamType::Array(Box::new(inner)))
+            } else {
+                let size = size.parse::<usize>().map_err(|_| {
+                    ContractError::AbiError(format!("Invalid array size in type: {}", type_str))
+                })?;
+                Ok(ParamType::FixedArray(Box::new(inner), size))
+            };
+        }
+        
+        if type_str.starts_with('(') && type_str.ends_with(')') {
+            let components = split_tuple_components(&type_str[1..type_str.len() - 1])?;
+            let types = components
+                .into_iter()
+                .map(Self::parse)
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(ParamType::Tuple(types));
+        }
+        
+        match type_str {
+            "address" => return Ok(ParamType::Address),
+            "bool" => return Ok(ParamType::Bool),
+            "bytes" => return Ok(ParamType::Bytes),
+            "string" => return Ok(ParamType::String),
+            "uint" => return Ok(ParamType::Uint(256)),
+            "int" => return Ok(ParamType::Int(256)),
+            _ => {}
+        }
+        
+        if let Some(bits) = type_str.strip_prefix("uint") {
+            return Ok(ParamType::Uint(parse_int_bits(type_str, bits)?));
+        }
+        
+        if let Some(bits) = type_str.strip_prefix("int") {
+            return Ok(ParamType::Int(parse_int_bits(type_str, bits)?));
+        }
+        
+        if let Some(size) = type_str.strip_prefix("bytes") {
+            let size = size.parse::<usize>().ok().filter(|s| (1..=32).contains(s)).ok_or_else(|| {
+                ContractError::AbiError(format!("Invalid fixed bytes type: {}", type_str))
+            })?;
+            return Ok(ParamType::FixedBytes(size));
+        }
+        
+        Err(ContractError::AbiError(format!("Unsupported ABI type: {}", type_str)))
+    }
+    
+    /// Checks whether values of this type are encoded in the tail section
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(inner, _) => inner.is_dynamic(),
+            ParamType::Tuple(types) => types.iter().any(|t| t.is_dynamic()),
+            _ => false,
+        }
+    }
+    
+    /// Size of this type's head in bytes
+    fn head_size(&self) -> usize {
+        if self.is_dynamic() {
+            return WORD_SIZE;
+        }
+        
+        match self {
+            ParamType::FixedArray(inner, size) => inner.head_size() * size,
+            ParamType::Tuple(types) => types.iter().map(|t| t.head_size()).sum(),
+            _ => WORD_SIZE,
+        }
+    }
+    
+    /// Canonical type string, as used in function signatures
+    pub fn canonical(&self) -> String {
+        match self {
+            ParamType::Uint(bits) => format!("uint{}", bits),
+            ParamType::Int(bits) => format!("int{}", bits),
+            ParamType::Address => "address".to_string(),
+            ParamType::Bool => "bool".to_string(),
+            ParamType::Bytes => "bytes".to_string(),
+            ParamType::String => "string".to_string(),
+            ParamType::FixedBytes(size) => format!("bytes{}", size),
+            ParamType::Array(inner) => format!("{}[]", inner.canonical()),
+            ParamType::FixedArray(inner, size) => format!("{}[{}]", inner.canonical(), size),
+            ParamType::Tuple(types) => format!(
+                "({})",
+                types.iter().map(|t| t.canonical()).collect::<Vec<_>>().join(",")
+            ),
+        }
+    }
+}
+
+/// Parses the bit width of an `intN`/`uintN` type
+fn parse_int_bits(type_str: &str, bits: &str) -> Result<usize> {
+    bits.parse::<usize>()
+        .ok()
+        .filter(|b| *b > 0 && *b <= 256 && b % 8 == 0)
+        .ok_or_else(|| ContractError::AbiError(format!("Invalid integer type: {}", type_str)))
+}
+
+/// Splits the inside of a tuple type on top-level commas
+fn split_tuple_components(inner: &str) -> Result<Vec<&str>> {
+    let mut components = Vec::new();
+    if inner.trim().is_empty() {
+        return Ok(components);
+    }
+    
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                components.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        
+        if depth < 0 {
+            return Err(ContractError::AbiError(format!("Unbalanced tuple type: ({})", inner)));
+        }
+    }
+    
+    if depth != 0 {
+        return Err(ContractError::AbiError(format!("Unbalanced tuple type: ({})", inner)));
+    }
+    
+    components.push(&inner[start..]);
+    Ok(components)
+}
+
+/// Parses the types of a parameter list
+pub fn parse_params(params: &[ABIParameter]) -> Result<Vec<ParamType>> {
+    params.iter().map(|p| ParamType::parse(&p.param_type)).collect()
+}
+
+/// Builds the canonical signature of a function, e.g. `transfer(address,uint256)`
+pub fn function_signature(name: &str, params: &[ABIParameter]) -> Result<String> {
+    let types = parse_params(params)?;
+    Ok(format!(
+        "{}({})",
+        name,
+        types.iter().map(|t| t.canonical()).collect::<Vec<_>>().join(",")
+    ))
+}
+
+/// Parses a contract ABI in the standard JSON format produced by solc
+///
+/// Functions and events are returned; constructors, fallbacks and custom
+/// errors are skipped. Tuple parameters are flattened into their canonical
+/// `(T1,T2,...)` type strings.
+pub fn parse_abi_json(json: &str) -> Result<(Vec<FunctionABI>, Vec<EventABI>)> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(json)
+        .map_err(|e| ContractError::AbiError(format!("Invalid ABI JSON: {}", e)))?;
+    
+    let mut functions = Vec::new();
+    let mut events = Vec::new();
+    
+    for entry in &entries {
+        let name = entry["name"].as_str().unwrap_or_default().to_string();
+        
+        match entry["type"].as_str().unwrap_or("function") {
+            "function" => {
+                let inputs = parse_json_params(&entry["inputs"])?;
+                let outputs = parse_json_params(&entry["outputs"])?;
+                let constant = matches!(entry["stateMutability"].as_str(), Some("view") | Some("pure"))
+                    || entry["constant"].as_bool().unwrap_or(false);
+                let signature = function_selector(&name, &inputs)?;
+                
+                functions.push(FunctionABI { name, inputs, outputs, constant, signature });
+            }
+            "event" => {
+                let params = entry["inputs"].as_array().cloned().unwrap_or_default();
+                let mut inputs = Vec::with_capacity(params.len());
+                for param in &params {
+                    let parsed = parse_json_param(param)?;
+                    inputs.push(EventParameter {
+                        name: parsed.name,
+                        param_type: parsed.param_type,
+                        indexed: param["indexed"].as_bool().unwrap_or(false),
+                    });
+                }
+                
+                events.push(EventABI {
+                    name,
+                    inputs,
+                    anonymous: entry["anonymous"].as_bool().unwrap_or(false),
+                });
+            }
+            _ => {}
+        }
+    }
+    
+    Ok((functions, events))
+}
+
+/// Parses a JSON parameter list
+fn parse_json_params(params: &serde_json::Value) -> Result<Vec<ABIParameter>> {
+    match params.as_array() {
+        Some(params) => params.iter().map(parse_json_param).collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Parses a single JSON parameter, expanding tuple components
+fn parse_json_param(param: &serde_json::Value) -> Result<ABIParameter> {
+    let name = param["name"].as_str().unwrap_or_default().to_string();
+    let type_str = param["type"].as_str().ok_or_else(|| {
+        ContractError::AbiError(format!("Parameter '{}' has no type", name))
+    })?;
+    
+    // `tuple`, `tuple[]`, `tuple[2][]`, ... take their shape from the components
+    let param_type = match type_str.strip_prefix("tuple") {
+        Some(suffix) => {
+            let components = parse_json_params(&param["components"])?;
+            let types: Vec<&str> = components.iter().map(|c| c.param_type.as_str()).collect();
+            format!("({}){}", types.join(","), suffix)
+        }
+        None => type_str.to_string(),
+    };
+    
+    // Validate the type so that errors surface when the ABI is loaded
+    ParamType::parse(&param_type)?;
+    
+    Ok(ABIParameter { name, param_type })
+}
+
+/// Computes the Keccak-256 hash used for Solidity selectors and event topics
+pub fn keccak256(data: &[u8]) -> Hash {
+    hashing::keccak256(data)
+}
+
+/// Computes the 4-byte selector of a function, e.g. `0xa9059cbb` for `transfer(address,uint256)`
+pub fn function_selector(name: &str, params: &[ABIParameter]) -> Result<[u8; 4]> {
+    let hash = keccak256(function_signature(name, params)?.as_bytes());
+    Ok([hash[0], hash[1], hash[2], hash[3]])
+}
+
+/// Builds the canonical signature of an event, e.g. `Transfer(address,address,uint256)`
+pub fn event_signature(event: &EventABI) -> Result<String> {
+    let types = event
+        .inputs
+        .iter()
+        .map(|p| ParamType::parse(&p.param_type))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!(
+        "{}({})",
+        event.name,
+        types.iter().map(|t| t.canonical()).collect::<Vec<_>>().join(",")
+    ))
+}
+
+/// Computes the topic identifying an event (the hash of its signature)
+pub fn event_topic(event: &EventABI) -> Result<Hash> {
+    Ok(keccak256(event_signature(event)?.as_bytes()))
+}
+
+/// Finds the event a log was emitted by, matching on its first topic
+///
+/// Anonymous events carry no signature topic and are never matched.
+pub fn find_event<'a>(events: &'a [EventABI], log: &Log) -> Option<&'a EventABI> {
+    let topic0 = log.topics.first()?;
+    events
+        .iter()
+        .filter(|event| !event.anonymous)
+        .find(|event| event_topic(event).map(|topic| topic == *topic0).unwrap_or(false))
+}
+
+/// Encodes event values into log topics and data
+///
+/// Indexed `string` and `bytes` values are stored as the hash of their
+/// contents; indexed arrays and tuples are not supported.
+pub fn encode_event(event: &EventABI, values: &[Value]) -> Result<(Vec<Hash>, Vec<u8>)> {
+    if event.inputs.len() != values.len() {
+        return Err(ContractError::AbiError(format!(
+            "Expected {} event values, got {}",
+            event.inputs.len(),
+            values.len()
+        )));
+    }
+    
+    let mut topics = Vec::new();
+    if !event.anonymous {
+        topics.push(event_topic(event)?);
+    }
+    
+    let mut data_types = Vec::new();
+    let mut data_values = Vec::new();
+    for (param, value) in event.inputs.iter().zip(values) {
+        let param_type = ParamType::parse(&param.param_type)?;
+        type_check(&param_type, value)?;
+        
+        if param.indexed {
+            topics.push(encode_topic(&param_type, value)?);
+        } else {
+            data_types.push(param_type);
+            data_values.push(value.clone());
+        }
+    }
+    
+    Ok((topics, encode_tuple(&data_types, &data_values)))
+}
+
+/// Decodes the values of an event from a log, in declaration order
+///
+/// Indexed dynamic values can't be recovered from their hash and are
+/// returned as `Value::FixedBytes` holding the topic.
+pub fn decode_event(event: &EventABI, log: &Log) -> Result<Vec<Value>> {
+    let mut topics = log.topics.iter();
+    if !event.anonymous {
+        let expected = event_topic(event)?;
+        if topics.next() != Some(&expected) {
+            return Err(ContractError::AbiError(format!(
+                "Log was not emitted by event {}",
+                event.name
+            )));
+        }
+    }
+    
+    let types = event
+        .inputs
+        .iter()
+        .map(|p| ParamType::parse(&p.param_type))
+        .collect::<Result<Vec<_>>>()?;
+    let data_types: Vec<ParamType> = event
+        .inputs
+        .iter()
+        .zip(&types)
+        .filter(|(param, _)| !param.indexed)
+        .map(|(_, param_type)| param_type.clone())
+        .collect();
+    let mut data_values = decode_tuple(&data_types, &log.data, 0)?.into_iter();
+    
+    let mut values = Vec::with_capacity(types.len());
+    for (param, param_type) in event.inputs.iter().zip(&types) {
+        let value = if param.indexed {
+            let topic = topics.next().ok_or_else(|| {
+                ContractError::AbiError(format!("Missing topic for '{}'", param.name))
+            })?;
+            match param_type {
+                ParamType::Uint(_) | ParamType::Int(_) | ParamType::Address | ParamType::Bool
+                | ParamType::FixedBytes(_) => decode_value(param_type, topic, 0)?,
+                _ => Value::FixedBytes(topic.to_vec()),
+            }
+        } else {
+            data_values.next().ok_or_else(|| {
+                ContractError::AbiError(format!("Missing data for '{}'", param.name))
+            })?
+        };
+        values.push(value);
+    }
+    
+    Ok(values)
+}
+
+/// Encodes an indexed event value as a topic
+fn encode_topic(param_type: &ParamType, value: &Value) -> Result<Hash> {
+    match (param_type, value) {
+        (ParamType::Bytes, Value::Bytes(bytes)) => Ok(keccak256(bytes)),
+        (ParamType::String, Value::String(string)) => Ok(keccak256(string.as_bytes())),
+        (ParamType::Uint(_), _) | (ParamType::Int(_), _) | (ParamType::Address, _)
+        | (ParamType::Bool, _) | (ParamType::FixedBytes(_), _) => {
+            let mut topic = [0u8; WORD_SIZE];
+            topic.copy_from_slice(&encode_value(param_type, value));
+            Ok(topic)
+        }
+        _ => Err(ContractError::AbiError(format!(
+            "Indexed parameters of type {} are not supported",
+            param_type.canonical()
+        ))),
+    }
+}
+
+/// Selector of the `Error(string)` revert payload produced by `require` and `revert`
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Selector of the `Panic(uint256)` revert payload produced by failed assertions
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Decodes the reason from revert data
+///
+/// Understands the standard `Error(string)` and `Panic(uint256)` payloads;
+/// returns `None` for empty or custom revert data.
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    if data.len() < 4 {
+        return None;
+    }
+    
+    let (selector, payload) = data.split_at(4);
+    if selector == ERROR_SELECTOR {
+        match decode_tuple(&[ParamType::String], payload, 0).ok()?.pop()? {
+            Value::String(reason) => Some(reason),
+            _ => None,
+        }
+    } else if selector == PANIC_SELECTOR {
+        let code = U256::from_be_bytes(&read_word(payload, 0).ok()?);
+        let description = match code.as_u64() {
+            Some(0x01) => "assertion failed",
+            Some(0x11) => "arithmetic overflow or underflow",
+            Some(0x12) => "division or modulo by zero",
+            Some(0x21) => "invalid enum value",
+            Some(0x22) => "invalid storage byte array encoding",
+            Some(0x31) => "pop on empty array",
+            Some(0x32) => "array index out of bounds",
+            Some(0x41) => "out of memory",
+            Some(0x51) => "call to zero-initialized function",
+            _ => "unknown panic",
+        };
+        let code = code.as_u64().map(|c| format!("0x{:02x}", c)).unwrap_or_else(|| code.to_string());
+        Some(format!("Panic({}): {}", code, description))
+    } else {
+        None
+    }
+}
+
+/// Encodes values according to the given parameter list
+pub fn encode(params: &[ABIParameter], values: &[Value]) -> Result<Vec<u8>> {
+    if params.len() != values.len() {
+        return Err(ContractError::AbiError(format!(
+            "Expected {} arguments, got {}",
+            params.len(),
+            values.len()
+        )));
+    }
+    
+    let types = parse_params(params)?;
+    for ((param, param_type), value) in params.iter().zip(&types).zip(values) {
+        type_check(param_type, value).map_err(|e| match e {
+            ContractError::AbiError(msg) => {
+                ContractError::AbiError(format!("Argument '{}': {}", param.name, msg))
+            }
+            other => other,
+        })?;
+    }
+    
+    Ok(encode_tuple(&types, values))
+}
+
+/// Decodes data according to the given parameter list
+pub fn decode(params: &[ABIParameter], data: &[u8]) -> Result<Vec<Value>> {
+    let types = parse_params(params)?;
+    decode_tuple(&types, data, 0)
+}
+
+/// Checks that a value matches a parameter type
+fn type_check(param_type: &ParamType, value: &Value) -> Result<()> {
+    let mismatch = || {
+        ContractError::AbiError(format!(
+            "Type mismatch: expected {}, got {:?}",
+            param_type.canonical(),
+            value
+        ))
+    };
+    
+    match (param_type, value) {
+        (ParamType::Uint(bits), Value::Uint(v)) => {
+            if v.bits() as usize > *bits {
+                return Err(ContractError::AbiError(format!(
+                    "Value {} does not fit in uint{}",
+                    v, bits
+                )));
+            }
+            Ok(())
+        }
+        (ParamType::Int(bits), Value::Int(v)) => {
+            if !fits_signed(v, *bits) {
+                return Err(ContractError::AbiError(format!(
+                    "Value {} does not fit in int{}",
+                    v, bits
+                )));
+            }
+            Ok(())
+        }
+        (ParamType::Address, Value::Address(_)) => Ok(()),
+        (ParamType::Bool, Value::Bool(_)) => Ok(()),
+        (ParamType::Bytes, Value::Bytes(_)) => Ok(()),
+        (ParamType::String, Value::String(_)) => Ok(()),
+        (ParamType::FixedBytes(size), Value::FixedBytes(bytes)) => {
+            if bytes.len() != *size {
+                return Err(ContractError::AbiError(format!(
+                    "Expected {} bytes for bytes{}, got {}",
+                    size,
+                    size,
+                    bytes.len()
+                )));
+            }
+            Ok(())
+        }
+        (ParamType::Array(inner), Value::Array(items)) => {
+            items.iter().try_for_each(|item| type_check(inner, item))
+        }
+        (ParamType::FixedArray(inner, size), Value::Array(items)) => {
+            if items.len() != *size {
+                return Err(ContractError::AbiError(format!(
+                    "Expected {} elements for {}, got {}",
+                    size,
+                    param_type.canonical(),
+                    items.len()
+                )));
+            }
+            items.iter().try_for_each(|item| type_check(inner, item))
+        }
+        (ParamType::Tuple(types), Value::Tuple(items)) => {
+            if items.len() != types.len() {
+                return Err(ContractError::AbiError(format!(
+                    "Expected {} tuple components for {}, got {}",
+                    types.len(),
+                    param_type.canonical(),
+                    items.len()
+                )));
+            }
+            types.iter().zip(items).try_for_each(|(t, item)| type_check(t, item))
+        }
+        _ => Err(mismatch()),
+    }
+}
+
+/// Checks that a two's complement word is a valid signed integer of the given width
+fn fits_signed(value: &U256, bits: usize) -> bool {
+    if bits == 256 {
+        return true;
+    }
+    
+    // Every bit from the sign bit upwards must match the sign bit
+    let bytes = value.to_be_bytes();
+    let negative = value.is_negative();
+    let fill = if negative { 0xff } else { 0x00 };
+    let value_bytes = bits / 8;
+    
+    bytes[..32 - value_bytes].iter().all(|b| *b == fill)
+        && (bytes[32 - value_bytes] & 0x80 != 0) == negative
+}
+
+/// Encodes a sequence of values using the head/tail layout
+fn encode_tuple(types: &[ParamType], values: &[Value]) -> Vec<u8> {
+    let heads_size: usize = types.iter().map(|t| t.head_size()).sum();
+    let mut heads = Vec::with_capacity(heads_size);
+    let mut tails = Vec::new();
+    
+    for (param_type, value) in types.iter().zip(values) {
+        let encoded = encode_value(param_type, value);
+        if param_type.is_dynamic() {
+            let offset = heads_size + tails.len();
+            heads.extend_from_slice(&U256::from_u64(offset as u64).to_be_bytes());
+            tails.extend_from_slice(&encoded);
+        } else {
+            heads.extend_from_slice(&encoded);
+        }
+    }
+    
+    heads.extend_from_slice(&tails);
+    heads
+}
+
+/// Encodes a single, already type-checked value
+fn encode_value(param_type: &ParamType, value: &Value) -> Vec<u8> {
+    match (param_type, value) {
+        (_, Value::Uint(v)) | (_, Value::Int(v)) => v.to_be_bytes().to_vec(),
+        (_, Value::Address(address)) => {
+            let mut word = vec![0u8; WORD_SIZE];
+            word[12..].copy_from_slice(address);
+            word
+        }
+        (_, Value::Bool(b)) => U256::from_u64(*b as u64).to_be_bytes().to_vec(),
+        (_, Value::FixedBytes(bytes)) => pad_right(bytes),
+        (_, Value::Bytes(bytes)) => encode_dynamic_bytes(bytes),
+        (_, Value::String(s)) => encode_dynamic_bytes(s.as_bytes()),
+        (ParamType::Array(inner), Value::Array(items)) => {
+            let mut encoded = U256::from_u64(items.len() as u64).to_be_bytes().to_vec();
+            let types = vec![(**inner).clone(); items.len()];
+            encoded.extend_from_slice(&encode_tuple(&types, items));
+            encoded
+        }
+        (ParamType::FixedArray(inner, _), Value::Array(items)) => {
+            let types = vec![(**inner).clone(); items.len()];
+            encode_tuple(&types, items)
+        }
+        (ParamType::Tuple(types), Value::Tuple(items)) => encode_tuple(types, items),
+        _ => unreachable!("value was type-checked before encoding"),
+    }
+}
+
+/// Encodes a length-prefixed, right-padded byte string
+fn encode_dynamic_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = U256::from_u64(bytes.len() as u64).to_be_bytes().to_vec();
+    encoded.extend_from_slice(&pad_right(bytes));
+    encoded
+}
+
+/// Right-pads data with zeros to a multiple of the word size
+fn pad_right(bytes: &[u8]) -> Vec<u8> {
+    let padded_len = bytes.len().div_ceil(WORD_SIZE) * WORD_SIZE;
+    let mut padded = bytes.to_vec();
+    padded.resize(padded_len, 0);
+    padded
+}
+
+/// Reads the word at the given offset
+fn read_word(data: &[u8], offset: usize) -> Result<[u8; 32]> {
+    let end = offset.checked_add(WORD_SIZE).filter(|end| *end <= data.len()).ok_or_else(|| {
+        ContractError::AbiError(format!(
+            "Data too short: need 32 bytes at offset {}, have {} bytes",
+            offset,
+            data.len()
+        ))
+    })?;
+    
+    let mut word = [0u8; 32];
+    word.copy_from_slice(&data[offset..end]);
+    Ok(word)
+}
+
+/// Reads a word that holds an offset or length
+fn read_usize(data: &[u8], offset: usize) -> Result<usize> {
+    U256::from_be_bytes(&read_word(data, offset)?).as_usize().ok_or_else(|| {
+        ContractError::AbiError(format!("Offset or length at {} is out of range", offset))
+    })
+}
+
+/// Decodes a sequence of values laid out from `base`
+fn decode_tuple(types: &[ParamType], data: &[u8], base: usize) -> Result<Vec<Value>> {
+    let mut values = Vec::with_capacity(types.len());
+    let mut head = base;
+    
+    for param_type in types {
+        let value = if param_type.is_dynamic() {
+            let offset = read_usize(data, head)?;
+            let start = base.checked_add(offset).ok_or_else(|| {
+                ContractError::AbiError("Offset overflow".to_string())
+            })?;
+            decode_value(param_type, data, start)?
+        } else {
+            decode_value(param_type, data, head)?
+        };
+        
+        head += param_type.head_size();
+        values.push(value);
+    }
+    
+    Ok(values)
+}
+
+/// Decodes a single value located at `offset`
+fn decode_value(param_type: &ParamType, data: &[u8], offset: usize) -> Result<Value> {
+    match param_type {
+        ParamType::Uint(bits) => {
+            let value = U256::from_be_bytes(&read_word(data, offset)?);
+            if value.bits() as usize > *bits {
+                return Err(ContractError::AbiError(format!(
+                    "Value {} does not fit in uint{}",
+                    value, bits
+                )));
+            }
+            Ok(Value::Uint(value))
+        }
+        ParamType::Int(bits) => {
+            let value = U256::from_be_bytes(&read_word(data, offset)?);
+            if !fits_signed(&value, *bits) {
+                return Err(ContractError::AbiError(format!(
+                    "Value {} does not fit in int{}",
+                    value, bits
+                )));
+            }
+            Ok(Value::Int(value))
+        }
+        ParamType::Address => {
+            let word = read_word(data, offset)?;
+            if word[..12].iter().any(|b| *b != 0) {
+                return Err(ContractError::AbiError("Address has non-zero padding".to_string()));
+            }
+            let mut address = [0u8; 20];
+            address.copy_from_slice(&word[12..]);
+            Ok(Value::Address(address))
+        }
+        ParamType::Bool => {
+            let value = U256::from_be_bytes(&read_word(data, offset)?);
+            match value.as_u64() {
+                Some(0) => Ok(Value::Bool(false)),
+                Some(1) => Ok(Value::Bool(true)),
+                _ => Err(ContractError::AbiError(format!("Invalid bool value: {}", value))),
+            }
+        }
+        ParamType::FixedBytes(size) => {
+            let word = read_word(data, offset)?;
+            Ok(Value::FixedBytes(word[..*size].to_vec()))
+        }
+        ParamType::Bytes => Ok(Value::Bytes(decode_dynamic_bytes(data, offset)?)),
+        ParamType::String => {
+            let bytes = decode_dynamic_bytes(data, offset)?;
+            let s = String::from_utf8(bytes).map_err(|e| {
+                ContractError::AbiError(format!("Invalid UTF-8 string: {}", e))
+            })?;
+            Ok(Value::String(s))
+        }
+        ParamType::Array(inner) => {
+            let len = read_usize(data, offset)?;
+            // Every element takes at least one word, which bounds the length by the data size
+            if len > data.len() / WORD_SIZE {
+                return Err(ContractError::AbiError(format!(
+                    "Array length {} exceeds available data",
+                    len
+                )));
+            }
+            let types = vec![(**inner).clone(); len];
+            Ok(Value::Array(decode_tuple(&types, data, offset + WORD_SIZE)?))
+        }
+        ParamType::FixedArray(inner, size) => {
+            let types = vec![(**inner).clone(); *size];
+            Ok(Value::Array(decode_tuple(&types, data, offset)?))
+        }
+        ParamType::Tuple(types) => Ok(Value::Tuple(decode_tuple(types, data, offset)?)),
+    }
+}
+
+/// Decodes a length-prefixed byte string
+fn decode_dynamic_bytes(data: &[u8], offset: usize) -> Result<Vec<u8>> {
+    let len = read_usize(data, offset)?;
+    let start = offset + WORD_SIZE;
+    let end = start.checked_add(len).filter(|end| *end <= data.len()).ok_or_else(|| {
+        ContractError::AbiError(format!(
+            "Byte string of length {} at offset {} exceeds available data",
+            len, offset
+        ))
+    })?;
+    
+    Ok(data[start..end].to_vec())
+}
\ No newline at end of file
@@ -0,0 +1,1498 @@
+//! EVM (Ethereum Virtual Machine) implementation
+//!
+//! This module implements a minimal bytecode interpreter covering the core
+//! opcode set: stack, arithmetic, memory, storage, control flow, call data,
+//! environment access, event logs and calls between contracts.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::receipt::Log;
+use ctb_core::transaction::CONTRACT_ADDRESS_PREFIX;
+pub use ctb_core::transaction::to_evm_address;
+use ctb_core::Hash;
+
+use crate::abi::keccak256;
+use crate::precompiles;
+use crate::token::{self, Ledger};
+use crate::tracer::{FrameEnter, FrameExit, NoopTracer, Step, Tracer};
+use crate::u256::U256;
+use crate::{ContractError, GasConfig, Result};
+
+/// Maximum number of items on the stack
+const STACK_LIMIT: usize = 1024;
+
+/// Maximum memory size a single execution may allocate (in bytes)
+const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// Maximum depth of nested calls
+const CALL_DEPTH_LIMIT: usize = 1024;
+
+/// Opcode values understood by the interpreter
+pub mod opcode {
+    pub const STOP: u8 = 0x00;
+    pub const ADD: u8 = 0x01;
+    pub const MUL: u8 = 0x02;
+    pub const SUB: u8 = 0x03;
+    pub const DIV: u8 = 0x04;
+    pub const SDIV: u8 = 0x05;
+    pub const MOD: u8 = 0x06;
+    pub const SMOD: u8 = 0x07;
+    pub const ADDMOD: u8 = 0x08;
+    pub const MULMOD: u8 = 0x09;
+    pub const EXP: u8 = 0x0a;
+    pub const SIGNEXTEND: u8 = 0x0b;
+    pub const LT: u8 = 0x10;
+    pub const GT: u8 = 0x11;
+    pub const SLT: u8 = 0x12;
+    pub const SGT: u8 = 0x13;
+    pub const EQ: u8 = 0x14;
+    pub const ISZERO: u8 = 0x15;
+    pub const AND: u8 = 0x16;
+    pub const OR: u8 = 0x17;
+    pub const XOR: u8 = 0x18;
+    pub const NOT: u8 = 0x19;
+    pub const BYTE: u8 = 0x1a;
+    pub const SHL: u8 = 0x1b;
+    pub const SHR: u8 = 0x1c;
+    pub const SAR: u8 = 0x1d;
+    pub const KECCAK256: u8 = 0x20;
+    pub const ADDRESS: u8 = 0x30;
+    pub const BALANCE: u8 = 0x31;
+    pub const ORIGIN: u8 = 0x32;
+    pub const CALLER: u8 = 0x33;
+    pub const CALLVALUE: u8 = 0x34;
+    pub const CALLDATALOAD: u8 = 0x35;
+    pub const CALLDATASIZE: u8 = 0x36;
+    pub const CALLDATACOPY: u8 = 0x37;
+    pub const CODESIZE: u8 = 0x38;
+    pub const CODECOPY: u8 = 0x39;
+    pub const GASPRICE: u8 = 0x3a;
+    pub const EXTCODESIZE: u8 = 0x3b;
+    pub const RETURNDATASIZE: u8 = 0x3d;
+    pub const RETURNDATACOPY: u8 = 0x3e;
+    pub const BLOCKHASH: u8 = 0x40;
+    pub const TIMESTAMP: u8 = 0x42;
+    pub const NUMBER: u8 = 0x43;
+    pub const CHAINID: u8 = 0x46;
+    pub const SELFBALANCE: u8 = 0x47;
+    pub const POP: u8 = 0x50;
+    pub const MLOAD: u8 = 0x51;
+    pub const MSTORE: u8 = 0x52;
+    pub const MSTORE8: u8 = 0x53;
+    pub const SLOAD: u8 = 0x54;
+    pub const SSTORE: u8 = 0x55;
+    pub const JUMP: u8 = 0x56;
+    pub const JUMPI: u8 = 0x57;
+    pub const PC: u8 = 0x58;
+    pub const MSIZE: u8 = 0x59;
+    pub const GAS: u8 = 0x5a;
+    pub const JUMPDEST: u8 = 0x5b;
+    pub const PUSH0: u8 = 0x5f;
+    pub const PUSH1: u8 = 0x60;
+    pub const PUSH32: u8 = 0x7f;
+    pub const DUP1: u8 = 0x80;
+    pub const DUP16: u8 = 0x8f;
+    pub const SWAP1: u8 = 0x90;
+    pub const SWAP16: u8 = 0x9f;
+    pub const LOG0: u8 = 0xa0;
+    pub const LOG4: u8 = 0xa4;
+    pub const CREATE: u8 = 0xf0;
+    pub const CALL: u8 = 0xf1;
+    pub const RETURN: u8 = 0xf3;
+    pub const DELEGATECALL: u8 = 0xf4;
+    pub const CREATE2: u8 = 0xf5;
+    pub const STATICCALL: u8 = 0xfa;
+    pub const REVERT: u8 = 0xfd;
+    pub const INVALID: u8 = 0xfe;
+    pub const SELFDESTRUCT: u8 = 0xff;
+    
+    /// Gets the mnemonic of an opcode, or "UNKNOWN" if the interpreter doesn't support it
+    pub fn name(op: u8) -> &'static str {
+        const PUSH: [&str; 32] = [
+            "PUSH1", "PUSH2", "PUSH3", "PUSH4", "PUSH5", "PUSH6", "PUSH7", "PUSH8",
+            "PUSH9", "PUSH10", "PUSH11", "PUSH12", "PUSH13", "PUSH14", "PUSH15", "PUSH16",
+            "PUSH17", "PUSH18", "PUSH19", "PUSH20", "PUSH21", "PUSH22", "PUSH23", "PUSH24",
+            "PUSH25", "PUSH26", "PUSH27", "PUSH28", "PUSH29", "PUSH30", "PUSH31", "PUSH32",
+        ];
+        const DUP: [&str; 16] = [
+            "DUP1", "DUP2", "DUP3", "DUP4", "DUP5", "DUP6", "DUP7", "DUP8",
+            "DUP9", "DUP10", "DUP11", "DUP12", "DUP13", "DUP14", "DUP15", "DUP16",
+        ];
+        const SWAP: [&str; 16] = [
+            "SWAP1", "SWAP2", "SWAP3", "SWAP4", "SWAP5", "SWAP6", "SWAP7", "SWAP8",
+            "SWAP9", "SWAP10", "SWAP11", "SWAP12", "SWAP13", "SWAP14", "SWAP15", "SWAP16",
+        ];
+        const LOG: [&str; 5] = ["LOG0", "LOG1", "LOG2", "LOG3", "LOG4"];
+        
+        match op {
+            STOP => "STOP",
+            ADD => "ADD",
+            MUL => "MUL",
+            SUB => "SUB",
+            DIV => "DIV",
+            SDIV => "SDIV",
+            MOD => "MOD",
+            SMOD => "SMOD",
+            ADDMOD => "ADDMOD",
+            MULMOD => "MULMOD",
+            EXP => "EXP",
+            SIGNEXTEND => "SIGNEXTEND",
+            LT => "LT",
+            GT => "GT",
+            SLT => "SLT",
+            SGT => "SGT",
+            EQ => "EQ",
+            ISZERO => "ISZERO",
+            AND => "AND",
+            OR => "OR",
+            XOR => "XOR",
+            NOT => "NOT",
+            BYTE => "BYTE",
+            SHL => "SHL",
+            SHR => "SHR",
+            SAR => "SAR",
+            KECCAK256 => "KECCAK256",
+            ADDRESS => "ADDRESS",
+            BALANCE => "BALANCE",
+            ORIGIN => "ORIGIN",
+            CALLER => "CALLER",
+            CALLVALUE => "CALLVALUE",
+            CALLDATALOAD => "CALLDATALOAD",
+            CALLDATASIZE => "CALLDATASIZE",
+            CALLDATACOPY => "CALLDATACOPY",
+            CODESIZE => "CODESIZE",
+            CODECOPY => "CODECOPY",
+            GASPRICE => "GASPRICE",
+            EXTCODESIZE => "EXTCODESIZE",
+            RETURNDATASIZE => "RETURNDATASIZE",
+            RETURNDATACOPY => "RETURNDATACOPY",
+            BLOCKHASH => "BLOCKHASH",
+            TIMESTAMP => "TIMESTAMP",
+            NUMBER => "NUMBER",
+            CHAINID => "CHAINID",
+            SELFBALANCE => "SELFBALANCE",
+            POP => "POP",
+            MLOAD => "MLOAD",
+            MSTORE => "MSTORE",
+            MSTORE8 => "MSTORE8",
+            SLOAD => "SLOAD",
+            SSTORE => "SSTORE",
+            JUMP => "JUMP",
+            JUMPI => "JUMPI",
+            PC => "PC",
+            MSIZE => "MSIZE",
+            GAS => "GAS",
+            JUMPDEST => "JUMPDEST",
+            PUSH0 => "PUSH0",
+            PUSH1..=PUSH32 => PUSH[(op - PUSH1) as usize],
+            DUP1..=DUP16 => DUP[(op - DUP1) as usize],
+            SWAP1..=SWAP16 => SWAP[(op - SWAP1) as usize],
+            LOG0..=LOG4 => LOG[(op - LOG0) as usize],
+            CREATE => "CREATE",
+            CALL => "CALL",
+            RETURN => "RETURN",
+            DELEGATECALL => "DELEGATECALL",
+            CREATE2 => "CREATE2",
+            STATICCALL => "STATICCALL",
+            REVERT => "REVERT",
+            INVALID => "INVALID",
+            SELFDESTRUCT => "SELFDESTRUCT",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+/// Environment information available to executing code
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionContext {
+    /// Address of the executing contract
+    pub address: [u8; 20],
+    
+    /// Address of the account that called the contract
+    pub caller: [u8; 20],
+    
+    /// Address of the account that sent the transaction, the caller of the outermost frame
+    pub origin: [u8; 20],
+    
+    /// Value (in GENX base units) sent with the call
+    pub value: u64,
+    
+    /// Height of the block the call is executed in
+    pub block_number: u64,
+    
+    /// Timestamp of the block the call is executed in
+    pub timestamp: u64,
+    
+    /// ID of the chain the call is executed on
+    pub chain_id: u64,
+}
+
+/// Outcome of a completed execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionStatus {
+    /// Execution finished with STOP, RETURN or SELFDESTRUCT; storage changes were committed
+    Success,
+    
+    /// Execution finished with REVERT; storage changes were discarded
+    Revert,
+}
+
+/// Result of a completed execution
+#[derive(Debug, Clone)]
+pub struct ExecutionResult {
+    /// How execution finished
+    pub status: ExecutionStatus,
+    
+    /// Return data, or revert data if execution reverted
+    pub return_data: Vec<u8>,
+    
+    /// Gas consumed after refunds
+    pub gas_used: u64,
+    
+    /// Logs emitted during execution; empty if execution reverted
+    pub logs: Vec<Log>,
+    
+    /// State changes for the host to apply; empty if execution reverted
+    pub changes: StateChanges,
+}
+
+/// State changes made by a successful execution
+///
+/// The interpreter never writes to the host directly; the caller applies
+/// these changes once it decides to keep the execution's effects.
+#[derive(Debug, Clone, Default)]
+pub struct StateChanges {
+    /// Final values of written storage slots
+    pub storage: Vec<StorageWrite>,
+    
+    /// Value transfers as (from, to, amount), in execution order
+    pub transfers: Vec<([u8; 20], [u8; 20], u64)>,
+    
+    /// Contracts that executed SELFDESTRUCT, in execution order
+    ///
+    /// They are removed after the storage writes and transfers are applied.
+    pub destroyed: Vec<[u8; 20]>,
+}
+
+/// Final value of a storage slot written during execution
+#[derive(Debug, Clone)]
+pub struct StorageWrite {
+    /// Contract owning the storage
+    pub address: [u8; 20],
+    
+    /// Slot key
+    pub key: Vec<u8>,
+    
+    /// New value; `None` clears the slot
+    pub value: Option<Vec<u8>>,
+}
+
+/// Read access to the accounts and contracts an execution can reach
+pub trait Host {
+    /// Code of the contract deployed at an address, if any
+    fn code(&self, address: &[u8; 20]) -> Option<&[u8]>;
+    
+    /// Committed value of a contract's storage slot
+    fn storage(&self, address: &[u8; 20], key: &[u8]) -> Option<&[u8]>;
+    
+    /// Balance of an account
+    fn balance(&self, address: &[u8; 20]) -> u64;
+}
+
+/// Converts a 20-byte EVM address to the blockchain address of the contract living there
+pub fn contract_address(evm_address: &[u8; 20]) -> String {
+    format!("{}{}", CONTRACT_ADDRESS_PREFIX, hex::encode(evm_address))
+}
+
+/// Converts a 20-byte address to a stack word
+fn address_to_word(address: &[u8; 20]) -> U256 {
+    U256::from_be_slice(address)
+}
+
+/// Converts a stack word to a 20-byte address (its low 20 bytes)
+fn word_to_address(word: &U256) -> [u8; 20] {
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&word.to_be_bytes()[12..]);
+    address
+}
+
+/// Returns the fixed gas cost of an opcode from the schedule
+///
+/// Dynamic components (memory expansion, copies, EXP exponent size and
+/// storage writes) are charged separately while the opcode executes.
+fn static_gas_cost(op: u8, config: &GasConfig) -> u64 {
+    match op {
+        opcode::STOP | opcode::RETURN | opcode::REVERT | opcode::SSTORE => 0,
+        
+        opcode::LOG0..=opcode::LOG4 => config.log_cost,
+        
+        opcode::CALL | opcode::DELEGATECALL | opcode::STATICCALL => config.call_cost,
+        
+        opcode::SELFDESTRUCT => config.selfdestruct_cost,
+        
+        opcode::ADD | opcode::SUB | opcode::LT | opcode::GT | opcode::SLT | opcode::SGT
+        | opcode::EQ | opcode::ISZERO | opcode::AND | opcode::OR | opcode::XOR | opcode::NOT
+        | opcode::BYTE | opcode::SHL | opcode::SHR | opcode::SAR | opcode::CALLDATALOAD
+        | opcode::CALLDATACOPY | opcode::CODECOPY | opcode::RETURNDATACOPY | opcode::MLOAD | opcode::MSTORE | opcode::MSTORE8 => config.very_low_cost,
+        opcode::PUSH1..=opcode::PUSH32 | opcode::DUP1..=opcode::DUP16 | opcode::SWAP1..=opcode::SWAP16 => {
+            config.very_low_cost
+        }
+        
+        opcode::MUL | opcode::DIV | opcode::SDIV | opcode::MOD | opcode::SMOD | opcode::SIGNEXTEND => {
+            config.low_cost
+        }
+        
+        opcode::ADDMOD | opcode::MULMOD | opcode::JUMP => config.mid_cost,
+        
+        opcode::JUMPI | opcode::EXP => config.high_cost,
+        
+        opcode::KECCAK256 => config.keccak_cost,
+        
+        opcode::SLOAD | opcode::BALANCE | opcode::EXTCODESIZE => config.storage_read_cost,
+        
+        opcode::SELFBALANCE => config.low_cost,
+        
+        _ => config.step_cost,
+    }
+}
+
+/// Collects the positions of all JUMPDEST instructions (skipping PUSH data)
+fn find_jump_destinations(bytecode: &[u8]) -> HashSet<usize> {
+    let mut destinations = HashSet::new();
+    let mut pc = 0;
+    
+    while pc < bytecode.len() {
+        let op = bytecode[pc];
+        if op == opcode::JUMPDEST {
+            destinations.insert(pc);
+        }
+        
+        if (opcode::PUSH1..=opcode::PUSH32).contains(&op) {
+            pc += (op - opcode::PUSH1) as usize + 1;
+        }
+        
+        pc += 1;
+    }
+    
+    destinations
+}
+
+/// Change recorded so that it can be undone when a frame fails
+enum JournalEntry {
+    /// A storage slot was written; holds the slot's previous uncommitted value
+    StorageChanged {
+        address: [u8; 20],
+        key: Vec<u8>,
+        previous: Option<Vec<u8>>,
+    },
+    
+    /// Value moved between accounts
+    Transfer {
+        from: [u8; 20],
+        to: [u8; 20],
+        value: u64,
+    },
+    
+    /// A log was emitted
+    Log,
+    
+    /// The refund counter changed; holds its previous value
+    Refund(u64),
+    
+    /// A contract was marked for destruction
+    Destroyed([u8; 20]),
+}
+
+/// Changes shared by all frames of an execution, layered over the host
+///
+/// Every change is journaled; a failing frame rolls the journal back to the
+/// checkpoint taken when it was entered, leaving its caller's changes intact.
+struct World<'h> {
+    /// Committed state
+    host: &'h dyn Host,
+    
+    /// Uncommitted storage writes, by contract and key
+    storage: HashMap<([u8; 20], Vec<u8>), Vec<u8>>,
+    
+    /// Uncommitted balance changes
+    balance_changes: HashMap<[u8; 20], i128>,
+    
+    /// Logs emitted so far
+    logs: Vec<Log>,
+    
+    /// Gas refunded at the end of a successful execution
+    gas_refund: u64,
+    
+    /// Contracts marked for destruction
+    destroyed: HashSet<[u8; 20]>,
+    
+    /// Undo log
+    journal: Vec<JournalEntry>,
+}
+
+impl<'h> World<'h> {
+    fn new(host: &'h dyn Host) -> Self {
+        Self {
+            host,
+            storage: HashMap::new(),
+            balance_changes: HashMap::new(),
+            logs: Vec::new(),
+            gas_refund: 0,
+            destroyed: HashSet::new(),
+            journal: Vec::new(),
+        }
+    }
+    
+    /// Reads a storage slot, seeing uncommitted writes
+    fn storage_load(&self, address: &[u8; 20], key: &[u8]) -> U256 {
+        self.storage
+            .get(&(*address, key.to_vec()))
+            .map(|value| value.as_slice())
+            .or_else(|| self.host.storage(address, key))
+            .map(U256::from_be_slice)
+            .unwrap_or(U256::ZERO)
+    }
+    
+    /// Writes a storage slot
+    fn storage_store(&mut self, address: [u8; 20], key: Vec<u8>, value: Vec<u8>) {
+        let previous = self.storage.insert((address, key.clone()), value);
+        self.journal.push(JournalEntry::StorageChanged { address, key, previous });
+    }
+    
+    /// Balance of an account, including uncommitted transfers
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        let change = self.balance_changes.get(address).copied().unwrap_or(0);
+        (self.host.balance(address) as i128 + change) as u64
+    }
+    
+    /// Moves value between accounts, returning false if the sender can't cover it
+    fn transfer(&mut self, from: [u8; 20], to: [u8; 20], value: u64) -> bool {
+        if self.balance(&from) < value {
+            return false;
+        }
+        
+        *self.balance_changes.entry(from).or_insert(0) -= value as i128;
+        *self.balance_changes.entry(to).or_insert(0) += value as i128;
+        self.journal.push(JournalEntry::Transfer { from, to, value });
+        true
+    }
+    
+    /// Records a log
+    fn push_log(&mut self, log: Log) {
+        self.logs.push(log);
+        self.journal.push(JournalEntry::Log);
+    }
+    
+    /// Adds to the refund counter
+    fn add_refund(&mut self, amount: u64) {
+        self.journal.push(JournalEntry::Refund(self.gas_refund));
+        self.gas_refund += amount;
+    }
+    
+    /// Marks a contract for destruction, returning false if it already was
+    ///
+    /// The contract stays callable until the execution's changes are applied.
+    fn destroy(&mut self, address: [u8; 20]) -> bool {
+        if !self.destroyed.insert(address) {
+            return false;
+        }
+        
+        self.journal.push(JournalEntry::Destroyed(address));
+        true
+    }
+    
+    /// Marks the current position in the journal
+    fn checkpoint(&self) -> usize {
+        self.journal.len()
+    }
+    
+    /// Undoes every change made since the checkpoint
+    fn revert_to(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            match self.journal.pop() {
+                Some(JournalEntry::StorageChanged { address, key, previous }) => match previous {
+                    Some(value) => {
+                        self.storage.insert((address, key), value);
+                    }
+                    None => {
+                        self.storage.remove(&(address, key));
+                    }
+                },
+                Some(JournalEntry::Transfer { from, to, value }) => {
+                    *self.balance_changes.entry(from).or_insert(0) += value as i128;
+                    *self.balance_changes.entry(to).or_insert(0) -= value as i128;
+                }
+                Some(JournalEntry::Log) => {
+                    self.logs.pop();
+                }
+                Some(JournalEntry::Refund(previous)) => self.gas_refund = previous,
+                Some(JournalEntry::Destroyed(address)) => {
+                    self.destroyed.remove(&address);
+                }
+                None => break,
+            }
+        }
+    }
+    
+    /// Collects the surviving changes for the host to apply; zero values clear the slot
+    fn into_changes(self) -> StateChanges {
+        let mut transfers = Vec::new();
+        let mut destroyed = Vec::new();
+        for entry in &self.journal {
+            match entry {
+                JournalEntry::Transfer { from, to, value } => transfers.push((*from, *to, *value)),
+                JournalEntry::Destroyed(address) => destroyed.push(*address),
+                _ => {}
+            }
+        }
+        
+        let storage = self
+            .storage
+            .into_iter()
+            .map(|((address, key), value)| StorageWrite {
+                address,
+                key,
+                value: if value.iter().all(|b| *b == 0) { None } else { Some(value) },
+            })
+            .collect();
+        
+        StateChanges { storage, transfers, destroyed }
+    }
+}
+
+/// Kind of message call made to another contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallKind {
+    /// CALL: runs the callee's code against its own storage, optionally sending value
+    Call,
+    
+    /// DELEGATECALL: runs the callee's code in the caller's context and storage
+    DelegateCall,
+    
+    /// STATICCALL: like CALL without value, forbidding state modification
+    StaticCall,
+}
+
+/// Call made by a frame that is waiting for the callee to finish
+struct PendingCall {
+    /// Journal position to roll back to if the callee fails
+    checkpoint: usize,
+    
+    /// Gas given to the callee, including any stipend
+    callee_gas: u64,
+    
+    /// Offset of the memory region receiving the callee's return data
+    ret_offset: usize,
+    
+    /// Length of the memory region receiving the callee's return data
+    ret_len: usize,
+}
+
+/// Reason a frame stopped running
+enum Interrupt<'a> {
+    /// The frame halted with STOP, RETURN or REVERT
+    Halt(ExecutionStatus, Vec<u8>),
+    
+    /// The frame called another contract and resumes once the callee halts
+    Call(Box<Machine<'a>>),
+}
+
+/// Interpreter state for a single call frame
+///
+/// Frames don't call each other recursively: a CALL suspends the caller and
+/// hands the callee back to `execute`, which keeps the frames on the heap so
+/// deep call chains can't exhaust the native stack.
+struct Machine<'a> {
+    /// Gas schedule
+    gas_config: &'a GasConfig,
+    
+    /// Code being executed
+    bytecode: &'a [u8],
+    
+    /// Call data
+    input: Vec<u8>,
+    
+    /// Execution environment; storage accesses go to `context.address`
+    context: ExecutionContext,
+    
+    /// Whether state modifications are forbidden
+    is_static: bool,
+    
+    /// Number of calls between the outermost frame and this one
+    depth: usize,
+    
+    /// Valid jump destinations
+    jump_destinations: HashSet<usize>,
+    
+    /// Operand stack
+    stack: Vec<U256>,
+    
+    /// Linear memory
+    memory: Vec<u8>,
+    
+    /// Program counter
+    pc: usize,
+    
+    /// Gas limit for this frame
+    gas_limit: u64,
+    
+    /// Gas consumed so far
+    gas_used: u64,
+    
+    /// Data returned by the most recent call made from this frame
+    return_data: Vec<u8>,
+    
+    /// Call this frame is waiting on, if any
+    pending_call: Option<PendingCall>,
+}
+
+impl<'a> Machine<'a> {
+    fn new(
+        gas_config: &'a GasConfig,
+        bytecode: &'a [u8],
+        input: Vec<u8>,
+        context: ExecutionContext,
+        gas_limit: u64,
+        is_static: bool,
+        depth: usize,
+    ) -> Self {
+        Self {
+            gas_config,
+            bytecode,
+            input,
+            context,
+            is_static,
+            depth,
+            jump_destinations: find_jump_destinations(bytecode),
+            stack: Vec::new(),
+            memory: Vec::new(),
+            pc: 0,
+            gas_limit,
+            gas_used: 0,
+            return_data: Vec::new(),
+            pending_call: None,
+        }
+    }
+    
+    /// Charges gas, failing if the limit would be exceeded
+    fn charge_gas(&mut self, amount: u64) -> Result<()> {
+        let total = self.gas_used.saturating_add(amount);
+        if total > self.gas_limit {
+            self.gas_used = self.gas_limit;
+            return Err(ContractError::OutOfGas { required: total, available: self.gas_limit });
+        }
+        
+        self.gas_used = total;
+        Ok(())
+    }
+    
+    /// Pushes a word onto the stack
+    fn push(&mut self, value: U256) -> Result<()> {
+        if self.stack.len() >= STACK_LIMIT {
+            return Err(ContractError::StackOverflow);
+        }
+        
+        self.stack.push(value);
+        Ok(())
+    }
+    
+    /// Pops a word from the stack
+    fn pop(&mut self) -> Result<U256> {
+        self.stack.pop().ok_or(ContractError::StackUnderflow { pc: self.pc })
+    }
+    
+    /// Pops a word that is used as a memory offset or length
+    fn pop_usize(&mut self) -> Result<usize> {
+        let value = self.pop()?;
+        self.word_to_usize(value)
+    }
+    
+    /// Pops a memory offset and then a length
+    ///
+    /// An empty range touches no memory, so its offset is ignored however
+    /// large and comes back as zero.
+    fn pop_range(&mut self) -> Result<(usize, usize)> {
+        let offset = self.pop()?;
+        let len = self.pop_usize()?;
+        if len == 0 {
+            return Ok((0, 0));
+        }
+        Ok((self.word_to_usize(offset)?, len))
+    }
+    
+    /// Converts a word used as a memory offset or length
+    fn word_to_usize(&self, value: U256) -> Result<usize> {
+        value.as_usize().ok_or_else(|| {
+            ContractError::ExecutionError(format!("Value {} is out of range at pc {}", value, self.pc))
+        })
+    }
+    
+    /// Grows memory so that `offset + len` bytes are addressable
+    fn expand_memory(&mut self, offset: usize, len: usize) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        
+        let end = offset.checked_add(len).filter(|end| *end <= MEMORY_LIMIT).ok_or(ContractError::MemoryLimitExceeded)?;
+        
+        // Memory always grows in whole words
+        let new_size = end.div_ceil(32) * 32;
+        if new_size > self.memory.len() {
+            let cost = self.memory_cost(new_size / 32) - self.memory_cost(self.memory.len() / 32);
+            self.charge_gas(cost)?;
+            self.memory.resize(new_size, 0);
+        }
+        
+        Ok(())
+    }
+    
+    /// Total gas cost of a memory of the given number of words
+    ///
+    /// The cost is linear in the word count plus a quadratic term, so very
+    /// large allocations quickly become unaffordable.
+    fn memory_cost(&self, words: usize) -> u64 {
+        let words = words as u64;
+        words * self.gas_config.memory_word_cost + words * words / 512
+    }
+    
+    /// Charges the per-word cost of copying `len` bytes
+    fn charge_copy(&mut self, len: usize) -> Result<()> {
+        let words = len.div_ceil(32) as u64;
+        self.charge_gas(words.saturating_mul(self.gas_config.copy_word_cost))
+    }
+    
+    /// Pops destination, offset and length and copies that range of `source` into memory
+    ///
+    /// Bytes past the end of `source` read as zero; an empty copy ignores its destination.
+    fn copy_to_memory(&mut self, source: &[u8]) -> Result<()> {
+        let dest = self.pop()?;
+        let offset = self.pop()?;
+        let len = self.pop_usize()?;
+        let dest = if len == 0 { 0 } else { self.word_to_usize(dest)? };
+        self.charge_copy(len)?;
+        self.expand_memory(dest, len)?;
+        
+        for i in 0..len {
+            self.memory[dest + i] = offset
+                .as_usize()
+                .and_then(|o| o.checked_add(i))
+                .and_then(|pos| source.get(pos))
+                .copied()
+                .unwrap_or(0);
+        }
+        
+        Ok(())
+    }
+    
+    /// Reads a word from memory
+    fn memory_load(&mut self, offset: usize) -> Result<U256> {
+        self.expand_memory(offset, 32)?;
+        let mut word = [0u8; 32];
+        word.copy_from_slice(&self.memory[offset..offset + 32]);
+        Ok(U256::from_be_bytes(&word))
+    }
+    
+    /// Copies a range of memory out
+    fn memory_slice(&mut self, offset: usize, len: usize) -> Result<Vec<u8>> {
+        self.expand_memory(offset, len)?;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(self.memory[offset..offset + len].to_vec())
+    }
+    
+    /// Reads a slot of the current contract's storage, seeing writes made earlier in this execution
+    fn storage_load(&self, world: &World, key: &U256) -> U256 {
+        world.storage_load(&self.context.address, &key.to_be_bytes())
+    }
+    
+    /// Reads a storage slot for SLOAD, whose gas was charged with the opcode
+    fn sload<T: Tracer>(&self, world: &World, tracer: &mut T, key: &U256) -> U256 {
+        let value = self.storage_load(world, key);
+        tracer.storage_read(&self.context.address, key, &value);
+        value
+    }
+    
+    /// Writes a storage slot for SSTORE, charging for the write
+    fn sstore<T: Tracer>(&mut self, world: &mut World<'a>, tracer: &mut T, key: U256, value: U256) -> Result<()> {
+        self.require_non_static()?;
+        
+        // Setting a zero slot costs more; clearing a slot earns a refund
+        let current = self.storage_load(world, &key);
+        if current.is_zero() && !value.is_zero() {
+            self.charge_gas(self.gas_config.storage_cost)?;
+        } else {
+            self.charge_gas(self.gas_config.storage_reset_cost)?;
+        }
+        
+        if !current.is_zero() && value.is_zero() {
+            world.add_refund(self.gas_config.storage_clear_refund);
+        }
+        
+        tracer.storage_write(&self.context.address, &key, &value);
+        world.storage_store(
+            self.context.address,
+            key.to_be_bytes().to_vec(),
+            value.to_be_bytes().to_vec(),
+        );
+        Ok(())
+    }
+    
+    /// Records a log emitted by the current contract
+    fn emit_log(&mut self, world: &mut World<'a>, topics: Vec<Hash>, data: Vec<u8>) {
+        world.push_log(Log {
+            address: contract_address(&self.context.address),
+            topics,
+            data,
+        });
+    }
+    
+    /// Fails if the frame is not allowed to modify state
+    fn require_non_static(&self) -> Result<()> {
+        if self.is_static {
+            return Err(ContractError::StaticStateChange { pc: self.pc });
+        }
+        Ok(())
+    }
+    
+    /// Sends the current contract's balance to `beneficiary` and marks the contract for destruction
+    ///
+    /// The first destruction of a contract in an execution earns a refund.
+    /// If the beneficiary is the contract itself its balance stays on the
+    /// account, which outlives the contract.
+    fn selfdestruct(&mut self, world: &mut World<'a>, beneficiary: [u8; 20]) -> Result<()> {
+        self.require_non_static()?;
+        
+        let address = self.context.address;
+        let balance = world.balance(&address);
+        if balance > 0 && beneficiary != address {
+            world.transfer(address, beneficiary, balance);
+        }
+        
+        if world.destroy(address) {
+            world.add_refund(self.gas_config.selfdestruct_refund);
+        }
+        Ok(())
+    }
+    
+    /// Starts a call to another contract
+    ///
+    /// Returns the callee's frame if code has to run; otherwise the call has
+    /// already completed and its result is on the stack. All but 1/64 of the
+    /// remaining gas can be forwarded.
+    fn call<T: Tracer>(&mut self, world: &mut World<'a>, kind: CallKind, tracer: &mut T) -> Result<Option<Machine<'a>>> {
+        let requested_gas = self.pop()?;
+        let target = word_to_address(&self.pop()?);
+        let value = if kind == CallKind::Call { self.pop()? } else { U256::ZERO };
+        let (args_offset, args_len) = self.pop_range()?;
+        let (ret_offset, ret_len) = self.pop_range()?;
+        
+        let value = value.as_u64().ok_or_else(|| {
+            ContractError::ExecutionError(format!("Call value {} is out of range at pc {}", value, self.pc))
+        })?;
+        if value > 0 {
+            self.require_non_static()?;
+            self.charge_gas(self.gas_config.call_value_cost)?;
+        }
+        
+        let input = self.memory_slice(args_offset, args_len)?;
+        self.expand_memory(ret_offset, ret_len)?;
+        self.return_data.clear();
+        
+        let available = self.gas_limit - self.gas_used;
+        let forwarded = requested_gas
+            .as_u64()
+            .unwrap_or(u64::MAX)
+            .min(available - available / 64);
+        self.charge_gas(forwarded)?;
+        
+        // Value transfers come with a stipend so the callee can at least log
+        let callee_gas = if value > 0 { forwarded + self.gas_config.call_stipend } else { forwarded };
+        
+        let checkpoint = world.checkpoint();
+        if self.depth >= CALL_DEPTH_LIMIT
+            || (value > 0 && !world.transfer(self.context.address, target, value))
+        {
+            self.gas_used -= forwarded;
+            self.push(U256::ZERO)?;
+            return Ok(None);
+        }
+        
+        let context = match kind {
+            CallKind::Call | CallKind::StaticCall => ExecutionContext {
+                address: target,
+                caller: self.context.address,
+                value,
+                ..self.context.clone()
+            },
+            CallKind::DelegateCall => self.context.clone(),
+        };
+        let frame = FrameEnter {
+            depth: self.depth + 1,
+            kind,
+            caller: context.caller,
+            address: context.address,
+            code_address: target,
+            value: context.value,
+            input: &input,
+            gas_limit: callee_gas,
+        };
+        
+        // Precompiles run natively and complete immediately
+        if let Some(result) = precompiles::run(&target, &input, callee_gas, self.gas_config) {
+            tracer.enter_frame(&frame);
+            let (outcome, gas_used) = match result {
+                Ok(result) => (Ok((ExecutionStatus::Success, result.output)), result.gas_used),
+                Err(e) => (Err(e), callee_gas),
+            };
+            tracer.exit_frame(&FrameExit { depth: self.depth + 1, gas_used, outcome: &outcome });
+            
+            self.pending_call = Some(PendingCall {
+                checkpoint,
+                callee_gas,
+                ret_offset,
+                ret_len,
+            });
+            self.finish_call(world, outcome, gas_used);
+            return Ok(None);
+        }
+        
+        // Calls to accounts without code just move value
+        let code = match world.host.code(&target) {
+            Some(code) => code,
+            None => {
+                self.gas_used -= forwarded;
+                self.push(U256::ONE)?;
+                return Ok(None);
+            }
+        };
+        
+        tracer.enter_frame(&frame);
+        
+        self.pending_call = Some(PendingCall {
+            checkpoint,
+            callee_gas,
+            ret_offset,
+            ret_len,
+        });
+        
+        Ok(Some(Machine::new(
+            self.gas_config,
+            code,
+            input,
+            context,
+            callee_gas,
+            self.is_static || kind == CallKind::StaticCall,
+            self.depth + 1,
+        )))
+    }
+    
+    /// Completes the pending call with the callee's outcome, pushing 1 on success and 0 on failure
+    ///
+    /// A failed or reverted callee only unwinds its own changes; the caller
+    /// keeps running and decides for itself whether to revert.
+    fn finish_call(
+        &mut self,
+        world: &mut World<'a>,
+        outcome: Result<(ExecutionStatus, Vec<u8>)>,
+        callee_gas_used: u64,
+    ) {
+        let call = self.pending_call.take().expect("finish_call without a pending call");
+        
+        // An exceptional halt consumes all forwarded gas; a revert only what was used
+        let (success, data, gas_left) = match outcome {
+            Ok((ExecutionStatus::Success, data)) => (true, data, call.callee_gas - callee_gas_used),
+            Ok((ExecutionStatus::Revert, data)) => (false, data, call.callee_gas - callee_gas_used),
+            Err(_) => (false, Vec::new(), 0),
+        };
+        
+        if !success {
+            world.revert_to(call.checkpoint);
+        }
+        self.gas_used = self.gas_used.saturating_sub(gas_left);
+        
+        // The return region was allocated when the call started
+        let copy_len = call.ret_len.min(data.len());
+        self.memory[call.ret_offset..call.ret_offset + copy_len].copy_from_slice(&data[..copy_len]);
+        self.return_data = data;
+        
+        // The call popped at least six items, so there is room for the result
+        self.stack.push(U256::from_u64(success as u64));
+    }
+    
+    /// Jumps to a destination, checking that it is a JUMPDEST
+    fn jump(&mut self, destination: U256) -> Result<()> {
+        match destination.as_usize() {
+            Some(dest) if self.jump_destinations.contains(&dest) => {
+                self.pc = dest;
+                Ok(())
+            }
+            _ => Err(ContractError::InvalidJump { destination, pc: self.pc }),
+        }
+    }
+    
+    /// Pops two operands and pushes the result of a binary operation
+    fn binary_op(&mut self, op: impl FnOnce(U256, U256) -> U256) -> Result<()> {
+        let a = self.pop()?;
+        let b = self.pop()?;
+        self.push(op(a, b))
+    }
+    
+    /// Runs until the code halts or calls another contract
+    fn run<T: Tracer>(&mut self, world: &mut World<'a>, tracer: &mut T) -> Result<Interrupt<'a>> {
+        if token::is_native_token(self.bytecode) {
+            return self.run_native_token(world, tracer);
+        }
+        
+        loop {
+            // Running off the end of the code is an implicit STOP
+            let op = match self.bytecode.get(self.pc) {
+                Some(op) => *op,
+                None => return Ok(Interrupt::Halt(ExecutionStatus::Success, Vec::new())),
+            };
+            
+            tracer.step(&Step {
+                depth: self.depth,
+                pc: self.pc,
+                opcode: op,
+                gas_remaining: self.gas_limit - self.gas_used,
+                stack: &self.stack,
+                memory_size: self.memory.len(),
+            });
+            
+            let gas_before = self.gas_used;
+            let interrupt = self.step(op, world, tracer);
+            tracer.step_end(self.gas_used - gas_before);
+            
+            if let Some(interrupt) = interrupt? {
+                return Ok(interrupt);
+            }
+        }
+    }
+    
+    /// Runs a native token contract in place of bytecode
+    fn run_native_token<T: Tracer>(&mut self, world: &mut World<'a>, tracer: &mut T) -> Result<Interrupt<'a>> {
+        let input = std::mem::take(&mut self.input);
+        let caller = self.context.caller;
+        let value = self.context.value;
+        
+        let mut ledger = NativeLedger { machine: self, world, tracer };
+        let (status, data) = token::call(&mut ledger, caller, value, &input)?;
+        Ok(Interrupt::Halt(status, data))
+    }
+    
+    /// Executes a single instruction
+    ///
+    /// Returns an interrupt if the frame halted or called another contract.
+    /// Always inlined into `run`, which keeps untraced execution as fast as
+    /// a single dispatch loop.
+    #[inline(always)]
+    fn step<T: Tracer>(&mut self, op: u8, world: &mut World<'a>, tracer: &mut T) -> Result<Option<Interrupt<'a>>> {
+        self.charge_gas(static_gas_cost(op, self.gas_config))?;
+        
+        let mut next_pc = self.pc + 1;
+        
+        match op {
+            opcode::STOP => return Ok(Some(Interrupt::Halt(ExecutionStatus::Success, Vec::new()))),
+            
+            opcode::ADD => self.binary_op(|a, b| a.wrapping_add(b))?,
+            opcode::MUL => self.binary_op(|a, b| a.wrapping_mul(b))?,
+            opcode::SUB => self.binary_op(|a, b| a.wrapping_sub(b))?,
+            opcode::DIV => self.binary_op(|a, b| a.div_rem(b).0)?,
+            opcode::SDIV => self.binary_op(|a, b| a.signed_div(b))?,
+            opcode::MOD => self.binary_op(|a, b| a.div_rem(b).1)?,
+            opcode::SMOD => self.binary_op(|a, b| a.signed_rem(b))?,
+            opcode::ADDMOD => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+                let m = self.pop()?;
+                self.push(a.add_mod(b, m))?;
+            }
+            opcode::MULMOD => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+                let m = self.pop()?;
+                self.push(a.mul_mod(b, m))?;
+            }
+            opcode::EXP => {
+                let base = self.pop()?;
+                let exponent = self.pop()?;
+                let exponent_bytes = (exponent.bits() as u64).div_ceil(8);
+                self.charge_gas(exponent_bytes * self.gas_config.exp_byte_cost)?;
+                self.push(base.wrapping_pow(exponent))?;
+            }
+            opcode::SIGNEXTEND => self.binary_op(|b, x| x.sign_extend(b))?,
+            
+            opcode::LT => self.binary_op(|a, b| U256::from_u64((a < b) as u64))?,
+            opcode::GT => self.binary_op(|a, b| U256::from_u64((a > b) as u64))?,
+            opcode::SLT => self.binary_op(|a, b| {
+                U256::from_u64((a.signed_cmp(&b) == std::cmp::Ordering::Less) as u64)
+            })?,
+            opcode::SGT => self.binary_op(|a, b| {
+                U256::from_u64((a.signed_cmp(&b) == std::cmp::Ordering::Greater) as u64)
+            })?,
+            opcode::EQ => self.binary_op(|a, b| U256::from_u64((a == b) as u64))?,
+            opcode::ISZERO => {
+                let a = self.pop()?;
+                self.push(U256::from_u64(a.is_zero() as u64))?;
+            }
+            opcode::AND => self.binary_op(|a, b| a & b)?,
+            opcode::OR => self.binary_op(|a, b| a | b)?,
+            opcode::XOR => self.binary_op(|a, b| a ^ b)?,
+            opcode::NOT => {
+                let a = self.pop()?;
+                self.push(!a)?;
+            }
+            opcode::BYTE => self.binary_op(|i, x| x.byte(i))?,
+            opcode::SHL => self.binary_op(|shift, x| {
+                x.shift_left(shift.as_usize().unwrap_or(usize::MAX))
+            })?,
+            opcode::SHR => self.binary_op(|shift, x| {
+                x.shift_right(shift.as_usize().unwrap_or(usize::MAX))
+            })?,
+            opcode::SAR => self.binary_op(|shift, x| {
+                x.sar(shift.as_usize().unwrap_or(usize::MAX))
+            })?,
+            
+            opcode::KECCAK256 => {
+                let (offset, len) = self.pop_range()?;
+                let words = len.div_ceil(32) as u64;
+                self.charge_gas(words.saturating_mul(self.gas_config.keccak_word_cost))?;
+                let data = self.memory_slice(offset, len)?;
+                self.push(U256::from_be_bytes(&keccak256(&data)))?;
+            }
+            
+            opcode::ADDRESS => self.push(address_to_word(&self.context.address))?,
+            opcode::BALANCE => {
+                let address = word_to_address(&self.pop()?);
+                self.push(U256::from_u64(world.balance(&address)))?;
+            }
+            opcode::ORIGIN => self.push(address_to_word(&self.context.origin))?,
+            opcode::CALLER => self.push(address_to_word(&self.context.caller))?,
+            opcode::CALLVALUE => self.push(U256::from_u64(self.context.value))?,
+            opcode::CALLDATALOAD => {
+                let offset = self.pop()?;
+                let mut word = [0u8; 32];
+                if let Some(offset) = offset.as_usize() {
+                    for (i, byte) in word.iter_mut().enumerate() {
+                        if let Some(b) = offset.checked_add(i).and_then(|pos| self.input.get(pos)) {
+                            *byte = *b;
+                        }
+                    }
+                }
+                self.push(U256::from_be_bytes(&word))?;
+            }
+            opcode::CALLDATASIZE => self.push(U256::from_u64(self.input.len() as u64))?,
+            opcode::CALLDATACOPY => {
+                let source = std::mem::take(&mut self.input);
+                let copied = self.copy_to_memory(&source);
+                self.input = source;
+                copied?;
+            }
+            opcode::CODESIZE => self.push(U256::from_u64(self.bytecode.len() as u64))?,
+            opcode::EXTCODESIZE => {
+                let address = word_to_address(&self.pop()?);
+                let size = world.host.code(&address).map_or(0, |code| code.len());
+                self.push(U256::from_u64(size as u64))?;
+            }
+            opcode::RETURNDATASIZE => self.push(U256::from_u64(self.return_data.len() as u64))?,
+            opcode::RETURNDATACOPY => {
+                // Unlike the other copies, reading past the end of the return data fails
+                let offset = self.stack.len().checked_sub(2).map(|i| self.stack[i]);
+                let len = self.stack.len().checked_sub(3).map(|i| self.stack[i]);
+                let in_bounds = match (offset.and_then(|o| o.as_usize()), len.and_then(|l| l.as_usize())) {
+                    (Some(offset), Some(len)) => offset.checked_add(len).is_some_and(|end| end <= self.return_data.len()),
+                    _ => false,
+                };
+                if !in_bounds {
+                    return Err(ContractError::ExecutionError(format!(
+                        "Return data read out of bounds at pc {}",
+                        self.pc
+                    )));
+                }
+                
+                let source = std::mem::take(&mut self.return_data);
+                let copied = self.copy_to_memory(&source);
+                self.return_data = source;
+                copied?;
+            }
+            opcode::CODECOPY => {
+                let source = self.bytecode;
+                self.copy_to_memory(source)?;
+            }
+            opcode::TIMESTAMP => self.push(U256::from_u64(self.context.timestamp))?,
+            opcode::NUMBER => self.push(U256::from_u64(self.context.block_number))?,
+            opcode::CHAINID => self.push(U256::from_u64(self.context.chain_id))?,
+            opcode::SELFBALANCE => self.push(U256::from_u64(world.balance(&self.context.address)))?,
+            
+            // Gas is priced in the block's fee market, block hashes aren't kept
+            // where the interpreter can reach them and contracts are only
+            // created by deploy transactions
+            opcode::GASPRICE | opcode::BLOCKHASH | opcode::CREATE | opcode::CREATE2 => {
+                return Err(ContractError::UnsupportedOpcode { name: opcode::name(op), pc: self.pc });
+            }
+            
+            opcode::POP => {
+                self.pop()?;
+            }
+            opcode::MLOAD => {
+                let offset = self.pop_usize()?;
+                let value = self.memory_load(offset)?;
+                self.push(value)?;
+            }
+            opcode::MSTORE => {
+                let offset = self.pop_usize()?;
+                let value = self.pop()?;
+                self.expand_memory(offset, 32)?;
+                self.memory[offset..offset + 32].copy_from_slice(&value.to_be_bytes());
+            }
+            opcode::MSTORE8 => {
+                let offset = self.pop_usize()?;
+                let value = self.pop()?;
+                self.expand_memory(offset, 1)?;
+                self.memory[offset] = value.low_u64() as u8;
+            }
+            opcode::SLOAD => {
+                let key = self.pop()?;
+                let value = self.sload(world, tracer, &key);
+                self.push(value)?;
+            }
+            opcode::SSTORE => {
+                let key = self.pop()?;
+                let value = self.pop()?;
+                self.sstore(world, tracer, key, value)?;
+            }
+            opcode::JUMP => {
+                let destination = self.pop()?;
+                self.jump(destination)?;
+                return Ok(None);
+            }
+            opcode::JUMPI => {
+                let destination = self.pop()?;
+                let condition = self.pop()?;
+                if !condition.is_zero() {
+                    self.jump(destination)?;
+                    return Ok(None);
+                }
+            }
+            opcode::PC => self.push(U256::from_u64(self.pc as u64))?,
+            opcode::MSIZE => self.push(U256::from_u64(self.memory.len() as u64))?,
+            opcode::GAS => self.push(U256::from_u64(self.gas_limit - self.gas_used))?,
+            opcode::JUMPDEST => {}
+            
+            opcode::PUSH0 => self.push(U256::ZERO)?,
+            opcode::PUSH1..=opcode::PUSH32 => {
+                let size = (op - opcode::PUSH1) as usize + 1;
+                let start = self.pc + 1;
+                let end = (start + size).min(self.bytecode.len());
+                
+                // Immediate data past the end of the code reads as zeros
+                let mut data = self.bytecode[start.min(end)..end].to_vec();
+                data.resize(size, 0);
+                self.push(U256::from_be_slice(&data))?;
+                next_pc = start + size;
+            }
+            opcode::DUP1..=opcode::DUP16 => {
+                let depth = (op - opcode::DUP1) as usize + 1;
+                if self.stack.len() < depth {
+                    return Err(ContractError::StackUnderflow { pc: self.pc });
+                }
+                let value = self.stack[self.stack.len() - depth];
+                self.push(value)?;
+            }
+            opcode::SWAP1..=opcode::SWAP16 => {
+                let depth = (op - opcode::SWAP1) as usize + 1;
+                if self.stack.len() <= depth {
+                    return Err(ContractError::StackUnderflow { pc: self.pc });
+                }
+                let top = self.stack.len() - 1;
+                self.stack.swap(top, top - depth);
+            }
+            
+            opcode::LOG0..=opcode::LOG4 => {
+                self.require_non_static()?;
+                let (offset, len) = self.pop_range()?;
+                let topic_count = (op - opcode::LOG0) as usize;
+                
+                let mut topics: Vec<Hash> = Vec::with_capacity(topic_count);
+                for _ in 0..topic_count {
+                    topics.push(self.pop()?.to_be_bytes());
+                }
+                
+                // The base cost was charged with the opcode
+                self.charge_gas(
+                    topic_count as u64 * self.gas_config.log_topic_cost
+                        + (len as u64).saturating_mul(self.gas_config.log_data_cost),
+                )?;
+                let data = self.memory_slice(offset, len)?;
+                self.emit_log(world, topics, data);
+            }
+            
+            opcode::CALL | opcode::DELEGATECALL | opcode::STATICCALL => {
+                let kind = match op {
+                    opcode::CALL => CallKind::Call,
+                    opcode::DELEGATECALL => CallKind::DelegateCall,
+                    _ => CallKind::StaticCall,
+                };
+                if let Some(callee) = self.call(world, kind, tracer)? {
+                    self.pc = next_pc;
+                    return Ok(Some(Interrupt::Call(Box::new(callee))));
+                }
+            }
+            
+            opcode::RETURN => {
+                let (offset, len) = self.pop_range()?;
+                let data = self.memory_slice(offset, len)?;
+                return Ok(Some(Interrupt::Halt(ExecutionStatus::Success, data)));
+            }
+            opcode::REVERT => {
+                let (offset, len) = self.pop_range()?;
+                let data = self.memory_slice(offset, len)?;
+                return Ok(Some(Interrupt::Halt(ExecutionStatus::Revert, data)));
+            }
+            opcode::SELFDESTRUCT => {
+                let beneficiary = word_to_address(&self.pop()?);
+                self.selfdestruct(world, beneficiary)?;
+                return Ok(Some(Interrupt::Halt(ExecutionStatus::Success, Vec::new())));
+            }
+            
+            _ => {
+                return Err(ContractError::InvalidOpcode { opcode: op, pc: self.pc });
+            }
+        }
+        
+        self.pc = next_pc;
+        Ok(None)
+    }
+}
+
+/// Gives a native contract running in a frame access to its storage and logs
+///
+/// Storage and logs cost the same gas as the SLOAD, SSTORE and LOG
+/// instructions a bytecode contract would use.
+struct NativeLedger<'m, 'a, 'w, T> {
+    machine: &'m mut Machine<'a>,
+    world: &'w mut World<'a>,
+    tracer: &'m mut T,
+}
+
+impl<T: Tracer> Ledger for NativeLedger<'_, '_, '_, T> {
+    fn load(&mut self, key: &U256) -> Result<U256> {
+        self.machine.charge_gas(self.machine.gas_config.storage_read_cost)?;
+        Ok(self.machine.sload(self.world, self.tracer, key))
+    }
+    
+    fn store(&mut self, key: U256, value: U256) -> Result<()> {
+        self.machine.sstore(self.world, self.tracer, key, value)
+    }
+    
+    fn log(&mut self, topics: Vec<Hash>, data: Vec<u8>) -> Result<()> {
+        let config = self.machine.gas_config;
+        self.machine.require_non_static()?;
+        self.machine.charge_gas(
+            config.log_cost
+                + topics.len() as u64 * config.log_topic_cost
+                + data.len() as u64 * config.log_data_cost,
+        )?;
+        self.machine.emit_log(self.world, topics, data);
+        Ok(())
+    }
+}
+
+/// Executes EVM bytecode
+///
+/// `context.address` is the contract whose storage the code runs against.
+/// Nothing is written to `host`: if execution succeeds the returned
+/// `changes` and logs describe its effects, otherwise they are empty.
+/// Running out of gas fails with `ContractError::OutOfGas`.
+pub fn execute(
+    bytecode: &[u8],
+    input: &[u8],
+    host: &dyn Host,
+    context: &ExecutionContext,
+    gas_limit: u64,
+    gas_config: &GasConfig,
+) -> Result<ExecutionResult> {
+    execute_traced(bytecode, input, host, context, gas_limit, gas_config, &mut NoopTracer)
+}
+
+/// Executes EVM bytecode, reporting every step to `tracer`
+///
+/// Behaves exactly like `execute`. The interpreter is generic over the
+/// tracer, so `execute` (which passes `NoopTracer`) compiles to code with
+/// no tracing calls left in it.
+pub fn execute_traced<T: Tracer>(
+    bytecode: &[u8],
+    input: &[u8],
+    host: &dyn Host,
+    context: &ExecutionContext,
+    gas_limit: u64,
+    gas_config: &GasConfig,
+    tracer: &mut T,
+) -> Result<ExecutionResult> {
+    let mut world = World::new(host);
+    let mut frames = vec![Machine::new(gas_config, bytecode, input.to_vec(), context.clone(), gas_limit, false, 0)];
+    
+    tracer.enter_frame(&FrameEnter {
+        depth: 0,
+        kind: CallKind::Call,
+        caller: context.caller,
+        address: context.address,
+        code_address: context.address,
+        value: context.value,
+        input,
+        gas_limit,
+    });
+    
+    // Run the innermost frame; when it halts, hand its outcome to its caller
+    let (status, return_data, mut gas_used) = loop {
+        let frame = frames.last_mut().expect("the outermost frame is always present");
+        let outcome = match frame.run(&mut world, tracer) {
+            Ok(Interrupt::Call(callee)) => {
+                frames.push(*callee);
+                continue;
+            }
+            Ok(Interrupt::Halt(status, data)) => Ok((status, data)),
+            Err(e) => Err(e),
+        };
+        
+        let finished = frames.pop().expect("a frame just ran");
+        tracer.exit_frame(&FrameExit {
+            depth: finished.depth,
+            gas_used: finished.gas_used,
+            outcome: &outcome,
+        });
+        
+        match frames.last_mut() {
+            Some(caller) => caller.finish_call(&mut world, outcome, finished.gas_used),
+            None => {
+                let (status, data) = outcome?;
+                break (status, data, finished.gas_used);
+            }
+        }
+    };
+    
+    // Keep the changes and apply refunds (capped at half the gas used) only on success
+    if status != ExecutionStatus::Success {
+        return Ok(ExecutionResult {
+            status,
+            return_data,
+            gas_used,
+            logs: Vec::new(),
+            changes: StateChanges::default(),
+        });
+    }
+    
+    gas_used -= world.gas_refund.min(gas_used / 2);
+    let logs = std::mem::take(&mut world.logs);
+    
+    Ok(ExecutionResult {
+        status,
+        return_data,
+        gas_used,
+        logs,
+        changes: world.into_changes(),
+    })
+}
\ No newline at end of file
@@ -0,0 +1,133 @@
+//! Structured views of deployed contracts
+//!
+//! Explorers and debugging tools read contract storage through these types
+//! rather than the state's internal maps. They serialize with byte strings
+//! as `0x`-prefixed hex, so they can be returned from RPC handlers as-is.
+//! Storage is always listed in ascending key order, and contracts by
+//! deployment height and then address, which makes pagination deterministic
+//! across calls and nodes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EventABI, FunctionABI};
+
+/// One slot of a contract's storage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageEntry {
+    /// Slot key, 32 bytes
+    #[serde(with = "hex_bytes")]
+    pub key: Vec<u8>,
+    
+    /// Slot value, 32 bytes
+    #[serde(with = "hex_bytes")]
+    pub value: Vec<u8>,
+}
+
+/// A range of a contract's storage, in ascending key order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoragePage {
+    /// The slots in the range
+    pub entries: Vec<StorageEntry>,
+    
+    /// Key to start the next page at; `None` on the last page
+    #[serde(default, with = "hex_bytes::option")]
+    pub next_key: Option<Vec<u8>>,
+}
+
+/// Everything known about a deployed contract
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractDump {
+    /// Contract address
+    pub address: String,
+    
+    /// Runtime code
+    #[serde(with = "hex_bytes")]
+    pub code: Vec<u8>,
+    
+    /// Contract functions
+    pub abi: Vec<FunctionABI>,
+    
+    /// Contract events
+    pub events: Vec<EventABI>,
+    
+    /// Address of the account that deployed the contract
+    pub creator: String,
+    
+    /// Height of the block the contract was deployed in
+    pub deployed_at: u64,
+    
+    /// Number of storage slots in use
+    pub storage_size: usize,
+    
+    /// The first storage slots; continue from `next_key` for the rest
+    pub storage: StoragePage,
+}
+
+/// A deployed contract as listed by creator or block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractDeployment {
+    /// Contract address
+    pub address: String,
+    
+    /// Address of the account that deployed the contract
+    pub creator: String,
+    
+    /// Height of the block the contract was deployed in
+    pub deployed_at: u64,
+}
+
+/// Position in a listing of contracts to continue from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentCursor {
+    /// Deployment height of the first contract to list
+    pub deployed_at: u64,
+    
+    /// Address of the first contract to list
+    pub address: String,
+}
+
+/// A range of deployed contracts, ordered by deployment height and then address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentPage {
+    /// The contracts in the range
+    pub contracts: Vec<ContractDeployment>,
+    
+    /// Where the next page starts; `None` on the last page
+    #[serde(default)]
+    pub next: Option<DeploymentCursor>,
+}
+
+/// Serializes bytes as `0x`-prefixed hex
+pub(crate) mod hex_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        hex::decode(text.trim_start_matches("0x")).map_err(de::Error::custom)
+    }
+    
+    /// Like the parent module, for optional bytes
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        
+        pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+            match bytes {
+                Some(bytes) => super::serialize(bytes, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+        
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+            match Option::<String>::deserialize(deserializer)? {
+                Some(text) => hex::decode(text.trim_start_matches("0x"))
+                    .map(Some)
+                    .map_err(serde::de::Error::custom),
+                None => Ok(None),
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -3,22 +3,40 @@
 //! This module implements a Solidity-compatible smart contract execution
 //! environment with gas estimation and EVM compatibility.
 
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use core::state::State;
-use core::transaction::Transaction;
-use core::{BlockchainError, Result as CoreResult};
+use ctb_core::block::BlockHeader;
+use ctb_core::executor::{ContractExecutor, ExecutionOutcome};
+use ctb_core::receipt::Log;
+use ctb_core::state::{ContractAccount, StateAccess};
+use ctb_core::transaction::{Transaction, TransactionType};
+use ctb_core::{BlockchainError, Result as CoreResult};
+
+pub mod abi;
+pub mod evm;
+pub mod inspect;
+pub mod precompiles;
+pub mod reader;
+pub mod solidity;
+pub mod token;
+pub mod tracer;
+pub mod u256;
+pub mod verification;
 
 /// Smart contract error types
+///
+/// Numbered from 3000, see `error_code`.
 #[derive(Debug, Error)]
 pub enum ContractError {
     #[error("Compilation error: {0}")]
     CompilationError(String),
     
+    #[error("Compilation failed: {}", .0.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("; "))]
+    CompilationFailed(Vec<solidity::Diagnostic>),
+    
     #[error("Execution error: {0}")]
     ExecutionError(String),
     
@@ -28,13 +46,97 @@ pub enum ContractError {
     #[error("State error: {0}")]
     StateError(String),
     
+    #[error("ABI error: {0}")]
+    AbiError(String),
+    
+    #[error("Contract code size {size} exceeds the limit of {limit} bytes")]
+    CodeSizeExceeded { size: usize, limit: usize },
+    
+    #[error("Init code size {size} exceeds the limit of {limit} bytes")]
+    InitCodeSizeExceeded { size: usize, limit: usize },
+    
+    #[error("Contract code may not start with the reserved 0xEF byte")]
+    ReservedCodePrefix,
+    
+    #[error("Execution reverted: {}", .reason.as_deref().unwrap_or("no reason given"))]
+    Reverted {
+        /// Decoded `Error(string)` or `Panic(uint256)` reason, if the revert data carries one
+        reason: Option<String>,
+        
+        /// Raw revert data
+        data: Vec<u8>,
+    },
+    
     #[error("Blockchain error: {0}")]
     BlockchainError(#[from] BlockchainError),
+    
+    #[error("Contract {address} not found")]
+    ContractNotFound { address: String },
+    
+    #[error("Out of gas: {required} needed, {available} available")]
+    OutOfGas { required: u64, available: u64 },
+    
+    #[error("Stack overflow")]
+    StackOverflow,
+    
+    #[error("Stack underflow at pc {pc}")]
+    StackUnderflow { pc: usize },
+    
+    #[error("Invalid opcode 0x{opcode:02x} at pc {pc}")]
+    InvalidOpcode { opcode: u8, pc: usize },
+    
+    #[error("Invalid jump destination {destination} at pc {pc}")]
+    InvalidJump { destination: u256::U256, pc: usize },
+    
+    #[error("State modification in a static call at pc {pc}")]
+    StaticStateChange { pc: usize },
+    
+    #[error("Memory limit exceeded")]
+    MemoryLimitExceeded,
+    
+    #[error("Unsupported opcode {name} at pc {pc}")]
+    UnsupportedOpcode { name: &'static str, pc: usize },
 }
 
 /// Result type for smart contract operations
 pub type Result<T> = std::result::Result<T, ContractError>;
 
+impl ContractError {
+    /// Gets the stable numeric code of the error; wrapped blockchain errors keep theirs
+    pub fn error_code(&self) -> u32 {
+        match self {
+            ContractError::CompilationError(_) => 3000,
+            ContractError::CompilationFailed(_) => 3001,
+            ContractError::ExecutionError(_) => 3002,
+            ContractError::GasError(_) => 3003,
+            ContractError::StateError(_) => 3004,
+            ContractError::AbiError(_) => 3005,
+            ContractError::CodeSizeExceeded { .. } => 3006,
+            ContractError::InitCodeSizeExceeded { .. } => 3007,
+            ContractError::ReservedCodePrefix => 3008,
+            ContractError::Reverted { .. } => 3009,
+            ContractError::ContractNotFound { .. } => 3010,
+            ContractError::OutOfGas { .. } => 3011,
+            ContractError::StackOverflow => 3012,
+            ContractError::StackUnderflow { .. } => 3013,
+            ContractError::InvalidOpcode { .. } => 3014,
+            ContractError::InvalidJump { .. } => 3015,
+            ContractError::StaticStateChange { .. } => 3016,
+            ContractError::MemoryLimitExceeded => 3017,
+            ContractError::UnsupportedOpcode { .. } => 3018,
+            ContractError::BlockchainError(e) => e.error_code(),
+        }
+    }
+    
+    /// Builds a `Reverted` error from revert data, decoding its reason
+    pub fn reverted(data: Vec<u8>) -> Self {
+        ContractError::Reverted {
+            reason: abi::decode_revert_reason(&data),
+            data,
+        }
+    }
+}
+
 /// Represents a compiled smart contract
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contract {
@@ -47,6 +149,10 @@ pub struct Contract {
     /// Contract ABI (Application Binary Interface)
     pub abi: Vec<FunctionABI>,
     
+    /// Events the contract can emit
+    #[serde(default)]
+    pub events: Vec<EventABI>,
+    
     /// Contract creator's address
     pub creator: String,
     
@@ -54,6 +160,51 @@ pub struct Contract {
     pub deployed_at: u64,
 }
 
+impl Contract {
+    /// Loads a contract from the account stored in the state
+    pub fn from_account(address: &str, account: &ContractAccount) -> Result<Self> {
+        let metadata: ContractMetadata = serde_json::from_slice(&account.metadata).map_err(|e| {
+            ContractError::StateError(format!("Invalid metadata for contract {}: {}", address, e))
+        })?;
+        
+        Ok(Self {
+            address: address.to_string(),
+            bytecode: account.code.clone(),
+            abi: metadata.abi,
+            events: metadata.events,
+            creator: account.creator.clone(),
+            deployed_at: account.deployed_at,
+        })
+    }
+    
+    /// Builds the account under which the contract is stored in the state
+    pub fn to_account(&self) -> Result<ContractAccount> {
+        let metadata = ContractMetadata {
+            abi: self.abi.clone(),
+            events: self.events.clone(),
+        };
+        let metadata = serde_json::to_vec(&metadata).map_err(|e| {
+            ContractError::StateError(format!("Failed to serialize contract metadata: {}", e))
+        })?;
+        
+        Ok(ContractAccount {
+            code: self.bytecode.clone(),
+            metadata,
+            creator: self.creator.clone(),
+            deployed_at: self.deployed_at,
+        })
+    }
+}
+
+/// Contract interface kept alongside the code in the state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ContractMetadata {
+    abi: Vec<FunctionABI>,
+    
+    #[serde(default)]
+    events: Vec<EventABI>,
+}
+
 /// Represents a function in a contract's ABI
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionABI {
@@ -83,6 +234,32 @@ pub struct ABIParameter {
     pub param_type: String,
 }
 
+/// Represents an event in a contract's ABI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventABI {
+    /// Event name
+    pub name: String,
+    
+    /// Event parameters, in declaration order
+    pub inputs: Vec<EventParameter>,
+    
+    /// Whether the event is anonymous (emitted without a signature topic)
+    pub anonymous: bool,
+}
+
+/// Represents a parameter of an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventParameter {
+    /// Parameter name
+    pub name: String,
+    
+    /// Parameter type
+    pub param_type: String,
+    
+    /// Whether the parameter is stored in a topic rather than the data
+    pub indexed: bool,
+}
+
 /// Gas cost configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GasConfig {
@@ -92,14 +269,104 @@ pub struct GasConfig {
     /// Cost per byte of transaction data
     pub data_cost: u64,
     
-    /// Cost per computational step
+    /// Cost per computational step (environment reads, POP, JUMPDEST, ...)
     pub step_cost: u64,
     
-    /// Cost for contract deployment
+    /// Base cost for contract deployment
     pub deployment_cost: u64,
     
-    /// Cost for storage operations
+    /// Additional deployment cost per byte of the deployed contract code
+    pub code_deposit_cost: u64,
+    
+    /// Cost for storage operations (SSTORE setting a zero slot to non-zero)
     pub storage_cost: u64,
+    
+    /// Cost of very cheap opcodes (ADD, SUB, comparisons, bitwise, PUSH, DUP, SWAP, ...)
+    pub very_low_cost: u64,
+    
+    /// Cost of cheap opcodes (MUL, DIV, MOD, SIGNEXTEND, ...)
+    pub low_cost: u64,
+    
+    /// Cost of medium opcodes (ADDMOD, MULMOD, JUMP)
+    pub mid_cost: u64,
+    
+    /// Cost of expensive opcodes (JUMPI, EXP base cost)
+    pub high_cost: u64,
+    
+    /// Additional EXP cost per byte of the exponent
+    pub exp_byte_cost: u64,
+    
+    /// Cost of reading a storage slot (SLOAD), or another account's balance or code size (BALANCE, EXTCODESIZE)
+    pub storage_read_cost: u64,
+    
+    /// Cost of SSTORE when the slot is already non-zero or stays zero
+    pub storage_reset_cost: u64,
+    
+    /// Refund granted when SSTORE clears a non-zero slot
+    pub storage_clear_refund: u64,
+    
+    /// Linear cost per 32-byte word of memory expansion
+    pub memory_word_cost: u64,
+    
+    /// Cost per 32-byte word copied by CALLDATACOPY and similar opcodes
+    pub copy_word_cost: u64,
+    
+    /// Base cost of hashing memory with KECCAK256
+    pub keccak_cost: u64,
+    
+    /// Additional KECCAK256 cost per 32-byte word hashed
+    pub keccak_word_cost: u64,
+    
+    /// Base cost of emitting a log
+    pub log_cost: u64,
+    
+    /// Additional log cost per topic
+    pub log_topic_cost: u64,
+    
+    /// Additional log cost per byte of data
+    pub log_data_cost: u64,
+    
+    /// Base cost of calling another contract
+    pub call_cost: u64,
+    
+    /// Additional cost of a call that transfers value
+    pub call_value_cost: u64,
+    
+    /// Free gas given to the callee of a call that transfers value
+    pub call_stipend: u64,
+    
+    /// Cost of SELFDESTRUCT
+    pub selfdestruct_cost: u64,
+    
+    /// Refund granted when a contract self-destructs, once per contract and transaction; 0 disables it
+    pub selfdestruct_refund: u64,
+    
+    /// Cost of the ecrecover precompile
+    pub ecrecover_cost: u64,
+    
+    /// Base cost of the sha256 precompile
+    pub sha256_cost: u64,
+    
+    /// Additional sha256 precompile cost per 32-byte word of input
+    pub sha256_word_cost: u64,
+    
+    /// Base cost of the ripemd160 precompile
+    pub ripemd160_cost: u64,
+    
+    /// Additional ripemd160 precompile cost per 32-byte word of input
+    pub ripemd160_word_cost: u64,
+    
+    /// Base cost of the identity precompile
+    pub identity_cost: u64,
+    
+    /// Additional identity precompile cost per 32-byte word of input
+    pub identity_word_cost: u64,
+    
+    /// Base cost of the ed25519-verify precompile
+    pub ed25519_verify_cost: u64,
+    
+    /// Additional ed25519-verify precompile cost per 32-byte word of input
+    pub ed25519_verify_word_cost: u64,
 }
 
 impl Default for GasConfig {
@@ -109,21 +376,139 @@ impl Default for GasConfig {
             data_cost: 68,
             step_cost: 1,
             deployment_cost: 32_000,
+            code_deposit_cost: 200,
             storage_cost: 20_000,
+            very_low_cost: 3,
+            low_cost: 5,
+            mid_cost: 8,
+            high_cost: 10,
+            exp_byte_cost: 50,
+            storage_read_cost: 800,
+            storage_reset_cost: 5_000,
+            storage_clear_refund: 15_000,
+            memory_word_cost: 3,
+            copy_word_cost: 3,
+            keccak_cost: 30,
+            keccak_word_cost: 6,
+            log_cost: 375,
+            log_topic_cost: 375,
+            log_data_cost: 8,
+            call_cost: 700,
+            call_value_cost: 9_000,
+            call_stipend: 2_300,
+            selfdestruct_cost: 5_000,
+            selfdestruct_refund: 24_000,
+            ecrecover_cost: 3_000,
+            sha256_cost: 60,
+            sha256_word_cost: 12,
+            ripemd160_cost: 600,
+            ripemd160_word_cost: 120,
+            identity_cost: 15,
+            identity_word_cost: 3,
+            ed25519_verify_cost: 2_000,
+            ed25519_verify_word_cost: 12,
         }
     }
 }
 
+impl GasConfig {
+    /// Gas charged for `len` bytes of transaction data
+    ///
+    /// Each byte costs `data_cost`, and the bytes beyond `DATA_SOFT_LIMIT`
+    /// add `data_cost` for every `DATA_QUADRATIC_DIVISOR` of their square,
+    /// so each byte past the threshold costs more than the one before.
+    /// With the default costs, a deployment of `MAX_DEPLOY_DATA_SIZE` bytes
+    /// still fits in a block.
+    pub fn data_gas(&self, len: usize) -> u64 {
+        let excess = len.saturating_sub(DATA_SOFT_LIMIT) as u128;
+        let weighted_len = len as u128 + excess * excess / DATA_QUADRATIC_DIVISOR;
+        (weighted_len * self.data_cost as u128).min(u64::MAX as u128) as u64
+    }
+}
+
+/// Payload carried in the data field of a ContractDeploy transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployPayload {
+    /// Init code, run once at deployment; its return data becomes the contract's code
+    pub init_code: Vec<u8>,
+    
+    /// Contract ABI
+    pub abi: Vec<FunctionABI>,
+    
+    /// Events the contract can emit
+    #[serde(default)]
+    pub events: Vec<EventABI>,
+    
+    /// ABI-encoded constructor arguments, appended to the init code
+    pub constructor_args: Vec<u8>,
+}
+
+impl DeployPayload {
+    /// Serializes the payload for use as transaction data
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| {
+            ContractError::StateError(format!("Failed to serialize deploy payload: {}", e))
+        })
+    }
+    
+    /// Parses a payload from transaction data
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).map_err(|e| {
+            ContractError::StateError(format!("Invalid deploy payload: {}", e))
+        })
+    }
+}
+
+/// Default limit on the size of deployed contract code in bytes
+pub const MAX_CODE_SIZE: usize = 24 * 1024;
+
+/// Transaction data size beyond which each byte costs more than the last
+pub const DATA_SOFT_LIMIT: usize = 32 * 1024;
+
+/// Divides the square of the data beyond `DATA_SOFT_LIMIT` in its extra cost
+pub const DATA_QUADRATIC_DIVISOR: u128 = 256 * 1024;
+
+/// Default safety margin added to gas estimates, in percent
+pub const DEFAULT_GAS_ESTIMATE_MARGIN: u64 = 10;
+
+/// Chain ID exposed by the CHAINID opcode unless configured otherwise, the ASCII bytes of `GENX`
+pub const DEFAULT_CHAIN_ID: u64 = 0x4745_4e58;
+
+/// First byte of native contract code, which deployed bytecode may not start with
+const RESERVED_CODE_PREFIX: u8 = 0xef;
+
+/// Block that contract code runs in, as exposed by the NUMBER and TIMESTAMP opcodes
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BlockContext {
+    pub(crate) height: u64,
+    pub(crate) timestamp: u64,
+}
+
 /// Manages smart contract compilation, deployment, and execution
+///
+/// The engine holds no contracts itself: code, metadata and storage live in
+/// the state it is given, so they persist and replay along with the chain.
+/// Read-only queries that shouldn't wait for block execution go through a
+/// `ContractReader` from `reader()`.
+#[derive(Debug, Clone)]
 pub struct ContractEngine {
     /// Gas configuration
     gas_config: GasConfig,
     
-    /// Deployed contracts
-    contracts: HashMap<String, Contract>,
+    /// Block that calls are currently executed in
+    block: BlockContext,
+    
+    /// Largest contract code a deployment may store, in bytes
+    max_code_size: usize,
     
-    /// Contract state (address -> storage)
-    contract_state: HashMap<String, HashMap<Vec<u8>, Vec<u8>>>,
+    /// Percentage added to gas estimates
+    gas_estimate_margin: u64,
+    
+    /// Chain ID exposed to executing contracts
+    chain_id: u64,
+    
+    /// Decoded contracts, shared with the engine's readers
+    registry: Arc<reader::ContractRegistry>,
 }
 
 impl ContractEngine {
@@ -131,77 +516,547 @@ impl ContractEngine {
     pub fn new(gas_config: GasConfig) -> Self {
         Self {
             gas_config,
-            contracts: HashMap::new(),
-            contract_state: HashMap::new(),
+            block: BlockContext::default(),
+            max_code_size: MAX_CODE_SIZE,
+            gas_estimate_margin: DEFAULT_GAS_ESTIMATE_MARGIN,
+            chain_id: DEFAULT_CHAIN_ID,
+            registry: Arc::new(reader::ContractRegistry::default()),
         }
     }
     
-    /// Compiles a Solidity contract
+    /// Gets a handle for running read-only queries concurrently with block execution
+    ///
+    /// The reader shares decoded contracts with the engine but copies its
+    /// configuration, so later changes to the engine's settings don't reach it.
+    pub fn reader(&self) -> reader::ContractReader {
+        reader::ContractReader::new(self.clone())
+    }
+    
+    /// Sets the largest contract code a deployment may store
+    ///
+    /// Init code may be up to twice this size.
+    pub fn set_max_code_size(&mut self, max_code_size: usize) {
+        self.max_code_size = max_code_size;
+    }
+    
+    /// Gets the largest contract code a deployment may store
+    pub fn max_code_size(&self) -> usize {
+        self.max_code_size
+    }
+    
+    /// Gets the largest init code a deployment may run
+    pub fn max_init_code_size(&self) -> usize {
+        self.max_code_size.saturating_mul(2)
+    }
+    
+    /// Sets the percentage `estimate_gas` adds to the least gas a transaction needs
+    ///
+    /// The margin covers state changing between estimation and inclusion.
+    pub fn set_gas_estimate_margin(&mut self, percent: u64) {
+        self.gas_estimate_margin = percent;
+    }
+    
+    /// Sets the chain ID exposed to executing contracts
+    pub fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = chain_id;
+    }
+    
+    /// Sets the block height and timestamp exposed to executing contracts
+    pub fn set_block_context(&mut self, block_height: u64, block_timestamp: u64) {
+        self.block = BlockContext { height: block_height, timestamp: block_timestamp };
+    }
+    
+    /// Compiles a Solidity contract with the default compiler options
     pub fn compile_contract(&self, source_code: &str) -> Result<(Vec<u8>, Vec<FunctionABI>)> {
-        // In a real implementation, this would use solc or a similar compiler
-        // to compile the Solidity source code to EVM bytecode
-        
-        // For now, we'll just return dummy bytecode and ABI
-        let bytecode = vec![0x60, 0x80, 0x60, 0x40, 0x52]; // Dummy bytecode
-        
-        // Create a dummy ABI with a single function
-        let function = FunctionABI {
-            name: "transfer".to_string(),
-            inputs: vec![
-                ABIParameter {
-                    name: "to".to_string(),
-                    param_type: "address".to_string(),
-                },
-                ABIParameter {
-                    name: "amount".to_string(),
-                    param_type: "uint256".to_string(),
-                },
-            ],
-            outputs: vec![
-                ABIParameter {
-                    name: "".to_string(),
-                    param_type: "bool".to_string(),
-                },
-            ],
-            constant: false,
-            signature: [0xa9, 0x05, 0x9c, 0xbb], // transfer(address,uint256)
-        };
-        
-        let abi = vec![function];
-        
-        Ok((bytecode, abi))
+        solidity::compile(source_code)
     }
     
-    /// Deploys a contract to the blockchain
+    /// Deploys a contract directly into `state`, bypassing consensus
+    ///
+    /// Contracts deployed this way exist only in the given state; on-chain
+    /// deployments go through `deploy_from_transaction`.
     pub fn deploy_contract(
         &mut self,
         bytecode: Vec<u8>,
         abi: Vec<FunctionABI>,
         creator: String,
         block_height: u64,
+        state: &mut dyn StateAccess,
     ) -> Result<String> {
+        if bytecode.len() > self.max_code_size {
+            return Err(ContractError::CodeSizeExceeded { size: bytecode.len(), limit: self.max_code_size });
+        }
+        if bytecode.first() == Some(&RESERVED_CODE_PREFIX) {
+            return Err(ContractError::ReservedCodePrefix);
+        }
+        
         // Generate a contract address
-        let address = format!("GENX_CONTRACT_{:x}", rand::random::<u64>());
+        let address = evm::contract_address(&rand::random::<[u8; 20]>());
         
         // Create the contract
         let contract = Contract {
             address: address.clone(),
             bytecode,
             abi,
+            events: Vec::new(),
             creator,
             deployed_at: block_height,
         };
         
         // Store the contract
-        self.contracts.insert(address.clone(), contract);
-        
-        // Initialize contract state
-        self.contract_state.insert(address.clone(), HashMap::new());
+        state.insert_contract(&address, contract.to_account()?);
         
         Ok(address)
     }
     
+    /// Deploys a contract from a ContractDeploy transaction included in a block
+    ///
+    /// The init code runs with the transaction's gas limit and its return data
+    /// becomes the contract's code, which costs `code_deposit_cost` per byte
+    /// to store. A failed deployment is reported in the outcome rather than as
+    /// an error, since the transaction still pays its fee.
+    ///
+    /// Init code over `max_init_code_size` isn't run at all. Code over
+    /// `max_code_size` isn't stored and the deployment fails, consuming all
+    /// its gas, with a `CodeSizeExceeded` reason. So does code starting with
+    /// the reserved `0xEF` byte, which marks native contracts.
+    pub fn deploy_from_transaction(
+        &mut self,
+        tx: &Transaction,
+        block_height: u64,
+        block_timestamp: u64,
+        state: &mut dyn StateAccess,
+    ) -> Result<ExecutionOutcome> {
+        self.set_block_context(block_height, block_timestamp);
+        self.deploy_at(self.block, tx, state)
+    }
+    
+    /// Executes a ContractDeploy transaction like `deploy_from_transaction` in the given block
+    fn deploy_at(&self, block: BlockContext, tx: &Transaction, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        if tx.tx_type != TransactionType::ContractDeploy {
+            return Err(ContractError::StateError(
+                format!("Transaction {} is not a contract deployment", hex::encode(tx.id))
+            ));
+        }
+        
+        let failed = |gas_used| ExecutionOutcome { success: false, gas_used, ..Default::default() };
+        
+        // Running out of gas before execution starts still consumes the whole limit
+        let intrinsic_gas = self.intrinsic_gas(tx);
+        if tx.gas_limit < intrinsic_gas {
+            return Ok(failed(tx.gas_limit));
+        }
+        
+        let payload = match tx.data.as_deref().map(DeployPayload::from_bytes) {
+            Some(Ok(payload)) => payload,
+            _ => return Ok(failed(intrinsic_gas)),
+        };
+        
+        let init_code_size = payload.init_code.len() + payload.constructor_args.len();
+        if init_code_size > self.max_init_code_size() {
+            let error = ContractError::InitCodeSizeExceeded { size: init_code_size, limit: self.max_init_code_size() };
+            return Ok(ExecutionOutcome {
+                revert_reason: Some(error.to_string()),
+                ..failed(intrinsic_gas)
+            });
+        }
+        
+        // The address is derived from the transaction, so it can't already be taken
+        // unless the same transaction is replayed
+        let address = tx.contract_address();
+        if state.get_contract(&address).is_some() {
+            return Ok(failed(intrinsic_gas));
+        }
+        
+        let context = evm::ExecutionContext {
+            address: evm::to_evm_address(&address),
+            caller: evm::to_evm_address(&tx.sender),
+            origin: evm::to_evm_address(&tx.sender),
+            value: tx.amount,
+            block_number: block.height,
+            timestamp: block.timestamp,
+            chain_id: self.chain_id,
+        };
+        
+        // Constructor arguments follow the init code, where CODECOPY can reach them
+        let mut init_code = payload.init_code;
+        init_code.extend_from_slice(&payload.constructor_args);
+        
+        let host = StateHost { state: &*state };
+        let result = match evm::execute(
+            &init_code,
+            &[],
+            &host,
+            &context,
+            tx.gas_limit - intrinsic_gas,
+            &self.gas_config,
+        ) {
+            Ok(result) => result,
+            Err(_) => return Ok(failed(tx.gas_limit)),
+        };
+        let gas_used = intrinsic_gas + result.gas_used;
+        
+        if result.status != evm::ExecutionStatus::Success {
+            return Ok(ExecutionOutcome {
+                revert_reason: abi::decode_revert_reason(&result.return_data),
+                return_data: result.return_data,
+                ..failed(gas_used)
+            });
+        }
+        
+        let code_size = result.return_data.len();
+        let error = if code_size > self.max_code_size {
+            Some(ContractError::CodeSizeExceeded { size: code_size, limit: self.max_code_size })
+        } else if result.return_data.first() == Some(&RESERVED_CODE_PREFIX) {
+            Some(ContractError::ReservedCodePrefix)
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            return Ok(ExecutionOutcome {
+                revert_reason: Some(error.to_string()),
+                ..failed(tx.gas_limit)
+            });
+        }
+        
+        // Storing the code is paid from what's left of the gas limit
+        let gas_used = gas_used + self.gas_config.code_deposit_cost * code_size as u64;
+        if gas_used > tx.gas_limit {
+            return Ok(failed(tx.gas_limit));
+        }
+        
+        let contract = Contract {
+            address: address.clone(),
+            bytecode: result.return_data,
+            abi: payload.abi,
+            events: payload.events,
+            creator: tx.sender.clone(),
+            deployed_at: block.height,
+        };
+        
+        let destroyed_contracts = result.changes.destroyed.iter().map(evm::contract_address).collect();
+        self.apply_changes(Some(&contract), result.changes, state)?;
+        
+        Ok(ExecutionOutcome {
+            success: true,
+            gas_used,
+            contract_address: Some(address),
+            return_data: Vec::new(),
+            logs: result.logs,
+            revert_reason: None,
+            destroyed_contracts,
+        })
+    }
+    
+    /// Calls a deployed contract from a ContractCall transaction included in a block
+    ///
+    /// The transaction data is the call data (selector and ABI-encoded
+    /// arguments). A failed or reverted call is reported in the outcome and
+    /// leaves the contract's storage untouched. A call to an address without
+    /// a contract, such as one that self-destructed, runs no code and
+    /// succeeds, only paying the intrinsic gas.
+    pub fn call_from_transaction(
+        &mut self,
+        tx: &Transaction,
+        block_height: u64,
+        block_timestamp: u64,
+        state: &mut dyn StateAccess,
+    ) -> Result<ExecutionOutcome> {
+        self.set_block_context(block_height, block_timestamp);
+        self.call_at(self.block, tx, state)
+    }
+    
+    /// Executes a ContractCall transaction like `call_from_transaction` in the given block
+    fn call_at(&self, block: BlockContext, tx: &Transaction, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        let failed = |gas_used| ExecutionOutcome { success: false, gas_used, ..Default::default() };
+        
+        let intrinsic_gas = self.intrinsic_gas(tx);
+        if tx.gas_limit < intrinsic_gas {
+            return Ok(failed(tx.gas_limit));
+        }
+        
+        let recipient = evm::to_evm_address(&tx.recipient);
+        if recipient == token::TOKEN_FACTORY_ADDRESS {
+            return self.create_token(tx, intrinsic_gas, block.height, state);
+        }
+        
+        let is_precompile = precompiles::is_precompile(&recipient);
+        if !is_precompile && state.get_contract(&tx.recipient).is_none() {
+            return Ok(ExecutionOutcome { success: true, gas_used: intrinsic_gas, ..Default::default() });
+        }
+        
+        let input = tx.data.clone().unwrap_or_default();
+        let execution_gas = tx.gas_limit - intrinsic_gas;
+        let result = self.run_code_traced(
+            block,
+            &tx.recipient,
+            &input,
+            &tx.sender,
+            tx.amount,
+            execution_gas,
+            &*state,
+            &mut tracer::NoopTracer,
+        );
+        
+        match result {
+            Ok(result) => {
+                let success = result.status == evm::ExecutionStatus::Success;
+                let destroyed_contracts = result.changes.destroyed.iter().map(evm::contract_address).collect();
+                self.apply_changes(None, result.changes, state)?;
+                Ok(ExecutionOutcome {
+                    success,
+                    gas_used: intrinsic_gas + result.gas_used,
+                    contract_address: None,
+                    revert_reason: if success { None } else { abi::decode_revert_reason(&result.return_data) },
+                    return_data: result.return_data,
+                    logs: result.logs,
+                    destroyed_contracts,
+                })
+            }
+            Err(_) => Ok(failed(tx.gas_limit)),
+        }
+    }
+    
+    /// Creates a native token from a ContractCall transaction to the token factory
+    ///
+    /// The token is stored at the address derived from the transaction and
+    /// its initial supply credited to the sender. Creating it costs the
+    /// deployment base cost plus the storage it initializes and the
+    /// `Transfer` event minting the supply.
+    fn create_token(
+        &self,
+        tx: &Transaction,
+        intrinsic_gas: u64,
+        block_height: u64,
+        state: &mut dyn StateAccess,
+    ) -> Result<ExecutionOutcome> {
+        let failed = |gas_used, reason: Option<String>| ExecutionOutcome {
+            success: false,
+            gas_used,
+            revert_reason: reason,
+            ..Default::default()
+        };
+        
+        if tx.amount > 0 {
+            return Ok(failed(intrinsic_gas, Some("The token factory does not accept value".to_string())));
+        }
+        let params = match token::TokenParams::from_call_data(tx.data.as_deref().unwrap_or_default()) {
+            Ok(params) => params,
+            Err(e) => return Ok(failed(intrinsic_gas, Some(e.to_string()))),
+        };
+        
+        let address = tx.contract_address();
+        if state.get_contract(&address).is_some() {
+            return Ok(failed(intrinsic_gas, None));
+        }
+        
+        let token_address = evm::to_evm_address(&address);
+        let owner = evm::to_evm_address(&tx.sender);
+        let storage = token::initial_storage(&params, &owner);
+        let mut logs = Vec::new();
+        
+        let mut gas_used = intrinsic_gas
+            + self.gas_config.deployment_cost
+            + self.gas_config.storage_cost * storage.len() as u64;
+        if !params.initial_supply.is_zero() {
+            let (topics, data) = token::transfer_event(&[0u8; 20], &owner, params.initial_supply);
+            gas_used += self.gas_config.log_cost
+                + topics.len() as u64 * self.gas_config.log_topic_cost
+                + data.len() as u64 * self.gas_config.log_data_cost;
+            logs.push(Log { address: address.clone(), topics, data });
+        }
+        if gas_used > tx.gas_limit {
+            return Ok(failed(tx.gas_limit, None));
+        }
+        
+        let contract = Contract {
+            address: address.clone(),
+            bytecode: token::NATIVE_TOKEN_CODE.to_vec(),
+            abi: token::erc20_functions(),
+            events: token::erc20_events(),
+            creator: tx.sender.clone(),
+            deployed_at: block_height,
+        };
+        let changes = evm::StateChanges {
+            storage: storage
+                .into_iter()
+                .map(|(key, value)| evm::StorageWrite {
+                    address: token_address,
+                    key: key.to_be_bytes().to_vec(),
+                    value: Some(value.to_be_bytes().to_vec()),
+                })
+                .collect(),
+            transfers: Vec::new(),
+            destroyed: Vec::new(),
+        };
+        self.apply_changes(Some(&contract), changes, state)?;
+        
+        let mut return_data = vec![0u8; 12];
+        return_data.extend_from_slice(&token_address);
+        Ok(ExecutionOutcome {
+            success: true,
+            gas_used,
+            contract_address: Some(address),
+            return_data,
+            logs,
+            revert_reason: None,
+            destroyed_contracts: Vec::new(),
+        })
+    }
+    
+    /// Runs a contract's code with the given call data against `state`
+    ///
+    /// `state` is only read; the caller decides whether to apply the result's changes.
+    fn run_code(
+        &self,
+        contract_address: &str,
+        input: &[u8],
+        sender: &str,
+        value: u64,
+        gas_limit: u64,
+        state: &dyn StateAccess,
+    ) -> Result<evm::ExecutionResult> {
+        self.run_code_traced(self.block, contract_address, input, sender, value, gas_limit, state, &mut tracer::NoopTracer)
+    }
+    
+    /// Runs a contract's code like `run_code` in the given block, reporting execution to `tracer`
+    #[allow(clippy::too_many_arguments)]
+    fn run_code_traced<T: tracer::Tracer>(
+        &self,
+        block: BlockContext,
+        contract_address: &str,
+        input: &[u8],
+        sender: &str,
+        value: u64,
+        gas_limit: u64,
+        state: &dyn StateAccess,
+        tracer: &mut T,
+    ) -> Result<evm::ExecutionResult> {
+        // Precompiles run natively and never touch state
+        if let Some(result) = precompiles::run(&evm::to_evm_address(contract_address), input, gas_limit, &self.gas_config) {
+            let result = result?;
+            return Ok(evm::ExecutionResult {
+                status: evm::ExecutionStatus::Success,
+                return_data: result.output,
+                gas_used: result.gas_used,
+                logs: Vec::new(),
+                changes: evm::StateChanges::default(),
+            });
+        }
+        
+        let contract = state.get_contract(contract_address).ok_or_else(|| {
+            ContractError::ContractNotFound { address: contract_address.to_string() }
+        })?;
+        
+        let context = evm::ExecutionContext {
+            address: evm::to_evm_address(contract_address),
+            caller: evm::to_evm_address(sender),
+            origin: evm::to_evm_address(sender),
+            value,
+            block_number: block.height,
+            timestamp: block.timestamp,
+            chain_id: self.chain_id,
+        };
+        
+        evm::execute_traced(
+            &contract.code,
+            input,
+            &StateHost { state },
+            &context,
+            gas_limit,
+            &self.gas_config,
+            tracer,
+        )
+    }
+    
+    /// Applies the storage writes, value transfers and contract destructions of a
+    /// successful execution, storing the contract it created first, if any
+    ///
+    /// The changes are applied inside a state checkpoint, so a transfer that
+    /// fails halfway leaves nothing behind.
+    fn apply_changes(
+        &self,
+        created: Option<&Contract>,
+        changes: evm::StateChanges,
+        state: &mut dyn StateAccess,
+    ) -> Result<()> {
+        let created = match created {
+            Some(contract) => Some((contract.address.as_str(), contract.to_account()?)),
+            None => None,
+        };
+        
+        state.checkpoint();
+        let result = self.write_changes(created, changes, state);
+        match result {
+            Ok(()) => state.commit(),
+            Err(_) => state.revert(),
+        }
+        
+        result
+    }
+    
+    /// Writes a contract and execution changes to the state, see `apply_changes`
+    fn write_changes(
+        &self,
+        created: Option<(&str, ContractAccount)>,
+        changes: evm::StateChanges,
+        state: &mut dyn StateAccess,
+    ) -> Result<()> {
+        // Transfers to the new contract need it to exist already
+        if let Some((address, account)) = created {
+            state.insert_contract(address, account);
+        }
+        
+        for write in changes.storage {
+            state.set_storage(&evm::contract_address(&write.address), write.key, write.value);
+        }
+        
+        for (from, to, value) in changes.transfers {
+            let from = account_name(&*state, &from);
+            let to = account_name(&*state, &to);
+            state.transfer(&from, &to, value)?;
+        }
+        
+        for address in changes.destroyed {
+            self.remove_contract(&evm::contract_address(&address), state)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Builds call data from a function selector and encoded arguments
+    ///
+    /// Precompiles have no ABI, so their call data is passed through unchecked.
+    fn call_data(
+        &self,
+        contract_address: &str,
+        function_signature: &[u8; 4],
+        arguments: &[u8],
+        state: &dyn StateAccess,
+    ) -> Result<Vec<u8>> {
+        if !precompiles::is_precompile(&evm::to_evm_address(contract_address)) {
+            // Get the contract
+            let contract = self.load_contract(contract_address, state)?;
+            
+            // Find the function in the ABI
+            if !contract.abi.iter().any(|f| f.signature == *function_signature) {
+                return Err(ContractError::ExecutionError(format!("Function with signature {:?} not found", function_signature)));
+            }
+        }
+        
+        // Build the call data: selector followed by the encoded arguments
+        let mut input = Vec::with_capacity(4 + arguments.len());
+        input.extend_from_slice(function_signature);
+        input.extend_from_slice(arguments);
+        Ok(input)
+    }
+    
     /// Executes a contract function
+    ///
+    /// Execution is bounded by `gas_limit`; on success the return data and the
+    /// gas actually used (after refunds) are returned so the fee can be charged.
+    /// A revert fails with `ContractError::Reverted` and leaves storage untouched.
+    #[allow(clippy::too_many_arguments)]
     pub fn execute_function(
         &mut self,
         contract_address: &str,
@@ -209,138 +1064,494 @@ impl ContractEngine {
         arguments: &[u8],
         sender: &str,
         value: u64,
-        state: &mut State,
+        gas_limit: u64,
+        state: &mut dyn StateAccess,
+    ) -> Result<(Vec<u8>, u64)> {
+        let input = self.call_data(contract_address, function_signature, arguments, &*state)?;
+        
+        // Execute the bytecode against the contract's storage
+        let result = self.run_code(
+            contract_address,
+            &input,
+            sender,
+            value,
+            gas_limit,
+            &*state,
+        )?;
+        
+        match result.status {
+            evm::ExecutionStatus::Success => {
+                self.apply_changes(None, result.changes, state)?;
+                Ok((result.return_data, result.gas_used))
+            }
+            evm::ExecutionStatus::Revert => Err(ContractError::reverted(result.return_data)),
+        }
+    }
+    
+    /// Executes a contract function without persisting any storage changes
+    ///
+    /// Used for read-only queries; whatever the call changes is discarded.
+    /// A revert fails with `ContractError::Reverted` carrying the decoded reason.
+    pub fn call_static(
+        &self,
+        contract_address: &str,
+        function_signature: &[u8; 4],
+        arguments: &[u8],
+        sender: &str,
+        gas_limit: u64,
+        state: &dyn StateAccess,
     ) -> Result<Vec<u8>> {
-        // Get the contract
-        let contract = self.contracts.get(contract_address).ok_or_else(|| {
-            ContractError::StateError(format!("Contract {} not found", contract_address))
+        self.call_static_at(self.block, contract_address, function_signature, arguments, sender, gas_limit, state)
+    }
+    
+    /// Executes a contract function like `call_static` in the given block
+    #[allow(clippy::too_many_arguments)]
+    fn call_static_at(
+        &self,
+        block: BlockContext,
+        contract_address: &str,
+        function_signature: &[u8; 4],
+        arguments: &[u8],
+        sender: &str,
+        gas_limit: u64,
+        state: &dyn StateAccess,
+    ) -> Result<Vec<u8>> {
+        let input = self.call_data(contract_address, function_signature, arguments, state)?;
+        let result = self.run_code_traced(block, contract_address, &input, sender, 0, gas_limit, state, &mut tracer::NoopTracer)?;
+        
+        match result.status {
+            evm::ExecutionStatus::Success => Ok(result.return_data),
+            evm::ExecutionStatus::Revert => Err(ContractError::reverted(result.return_data)),
+        }
+    }
+    
+    /// Runs call data against a contract without persisting anything, reporting execution to `tracer`
+    ///
+    /// Unlike `call_static`, a revert isn't an error: the result's status says
+    /// how the call ended, so the trace of a reverting call can still be inspected.
+    /// Calls to precompiles run natively and produce no steps.
+    pub fn trace_call<T: tracer::Tracer>(
+        &self,
+        contract_address: &str,
+        input: &[u8],
+        sender: &str,
+        gas_limit: u64,
+        state: &dyn StateAccess,
+        tracer: &mut T,
+    ) -> Result<evm::ExecutionResult> {
+        self.run_code_traced(self.block, contract_address, input, sender, 0, gas_limit, state, tracer)
+    }
+    
+    /// Calls a contract function by name, ABI-encoding the arguments and decoding the result
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_by_name(
+        &mut self,
+        contract_address: &str,
+        function_name: &str,
+        values: &[abi::Value],
+        sender: &str,
+        value: u64,
+        gas_limit: u64,
+        state: &mut dyn StateAccess,
+    ) -> Result<Vec<abi::Value>> {
+        // Look up the function in the contract's ABI
+        let contract = self.load_contract(contract_address, &*state)?;
+        
+        let function = contract.abi.iter().find(|f| f.name == function_name).cloned().ok_or_else(|| {
+            ContractError::AbiError(format!(
+                "Function '{}' not found in contract {}", function_name, contract_address
+            ))
         })?;
         
-        // Find the function in the ABI
-        let function = contract.abi.iter().find(|f| f.signature == *function_signature).ok_or_else(|| {
-            ContractError::ExecutionError(format!("Function with signature {:?} not found", function_signature))
+        // Encode the arguments and execute with the function's selector
+        let arguments = abi::encode(&function.inputs, values).map_err(|e| match e {
+            ContractError::AbiError(msg) => ContractError::AbiError(format!("{}: {}", function_name, msg)),
+            other => other,
         })?;
         
-        // In a real implementation, this would execute the EVM bytecode
-        // with the given arguments and return the result
+        let (output, _gas_used) = self.execute_function(
+            contract_address,
+            &function.signature,
+            &arguments,
+            sender,
+            value,
+            gas_limit,
+            state,
+        )?;
         
-        // For now, we'll just return a dummy result
-        let result = if function.name == "transfer" {
-            // Simulate a transfer function
-            // In a real implementation, this would update the contract state
-            vec![0x01] // true
-        } else {
-            vec![0x00] // false
-        };
+        // Decode the return data
+        abi::decode(&function.outputs, &output)
+    }
+    
+    /// Gas a contract transaction consumes before any code runs
+    ///
+    /// Covers the base transaction cost, the transaction data and memo
+    /// (see `GasConfig::data_gas`) and, for deployments, the cost of
+    /// creating the contract.
+    pub fn intrinsic_gas(&self, tx: &Transaction) -> u64 {
+        let data_len = tx.data_len().saturating_add(tx.memo_len());
+        let mut gas = self.gas_config.base_cost.saturating_add(self.gas_config.data_gas(data_len));
+        
+        if tx.tx_type == TransactionType::ContractDeploy {
+            gas += self.gas_config.deployment_cost;
+        }
         
-        Ok(result)
+        gas
+    }
+    
+    /// Estimates the gas a transaction needs by executing it against `state`
+    ///
+    /// Contract transactions run in the current block with up to `gas_cap`
+    /// gas, and a binary search finds the least gas limit they succeed
+    /// with; the estimate adds the engine's safety margin to that, but
+    /// never exceeds `gas_cap`. A transaction that fails even with
+    /// `gas_cap` gas is an error, `ContractError::Reverted` with the reason
+    /// if it reverted. Other transactions run no code and need only their
+    /// intrinsic gas.
+    ///
+    /// Value moves as it would when the transaction is included, but fees
+    /// aren't charged. Every change is undone, so `state` is left as it was.
+    pub fn estimate_gas(&self, tx: &Transaction, gas_cap: u64, state: &mut dyn StateAccess) -> Result<u64> {
+        self.estimate_gas_at(self.block, tx, gas_cap, state)
     }
     
-    /// Estimates the gas cost for a transaction
-    pub fn estimate_gas(
+    /// Estimates the gas a transaction needs like `estimate_gas` in the given block
+    pub(crate) fn estimate_gas_at(
         &self,
+        block: BlockContext,
         tx: &Transaction,
+        gas_cap: u64,
+        state: &mut dyn StateAccess,
     ) -> Result<u64> {
-        let mut gas = self.gas_config.base_cost;
+        match tx.tx_type {
+            TransactionType::ContractDeploy => {
+                // Init code that's too large never runs, whatever the gas
+                let payload = DeployPayload::from_bytes(tx.data.as_deref().unwrap_or_default())?;
+                let init_code_size = payload.init_code.len() + payload.constructor_args.len();
+                if init_code_size > self.max_init_code_size() {
+                    return Err(ContractError::InitCodeSizeExceeded { size: init_code_size, limit: self.max_init_code_size() });
+                }
+            }
+            TransactionType::ContractCall => {}
+            _ => return Ok(self.intrinsic_gas(tx)),
+        }
         
-        // Add cost for transaction data
-        if let Some(data) = &tx.data {
-            gas += data.len() as u64 * self.gas_config.data_cost;
-            
-            // Check if this is a contract deployment
-            if tx.recipient.starts_with("GENX_CONTRACT_") {
-                gas += self.gas_config.deployment_cost;
-            } else {
-                // This is a contract function call
-                // In a real implementation, we would analyze the function
-                // and estimate its gas cost more accurately
-                gas += 100_000; // Arbitrary function call cost
+        let outcome = self.simulate(block, tx, gas_cap, state)?;
+        if !outcome.success {
+            // Exceptional failures consume all the gas; reverts and rejected code don't, or give a reason
+            if outcome.revert_reason.is_none() && outcome.gas_used >= gas_cap {
+                return Err(ContractError::GasError(format!(
+                    "Transaction fails even with the gas cap of {}", gas_cap
+                )));
+            }
+            return Err(ContractError::Reverted {
+                reason: outcome.revert_reason,
+                data: outcome.return_data,
+            });
+        }
+        
+        // Refunds are only paid at the end and calls hold back 1/64 of the
+        // remaining gas, so a transaction can need more gas than it uses
+        let used = outcome.gas_used;
+        let mut needed = used;
+        if !self.simulate(block, tx, used, state)?.success {
+            let (mut failing, mut succeeding) = (used, gas_cap);
+            while succeeding - failing > 1 {
+                let limit = failing + (succeeding - failing) / 2;
+                if self.simulate(block, tx, limit, state)?.success {
+                    succeeding = limit;
+                } else {
+                    failing = limit;
+                }
             }
+            needed = succeeding;
         }
         
-        Ok(gas)
+        Ok(needed.saturating_add(needed * self.gas_estimate_margin / 100).min(gas_cap))
     }
     
-    /// Gets a contract by its address
-    pub fn get_contract(&self, address: &str) -> Option<&Contract> {
-        self.contracts.get(address)
+    /// Executes a transaction with the given gas limit against `state`, then undoes its changes
+    fn simulate(
+        &self,
+        block: BlockContext,
+        tx: &Transaction,
+        gas_limit: u64,
+        state: &mut dyn StateAccess,
+    ) -> Result<ExecutionOutcome> {
+        let tx = Transaction { gas_limit, ..tx.clone() };
+        
+        state.checkpoint();
+        let outcome = self.execute_transaction(block, &tx, state);
+        state.revert();
+        
+        outcome
     }
     
-    /// Gets all deployed contracts
-    pub fn get_contracts(&self) -> &HashMap<String, Contract> {
-        &self.contracts
+    /// Executes a contract transaction, moving its value the way block application does
+    fn execute_transaction(&self, block: BlockContext, tx: &Transaction, state: &mut dyn StateAccess) -> Result<ExecutionOutcome> {
+        if tx.tx_type == TransactionType::ContractDeploy {
+            // The endowment only moves once the contract exists
+            let outcome = self.deploy_at(block, tx, state)?;
+            if let (true, Some(address)) = (outcome.success, &outcome.contract_address) {
+                state.transfer(&tx.sender, address, tx.amount)?;
+            }
+            return Ok(outcome);
+        }
+        
+        state.transfer(&tx.sender, &tx.recipient, tx.amount)?;
+        self.call_at(block, tx, state)
     }
     
-    /// Gets the state of a contract
-    pub fn get_contract_state(&self, address: &str) -> Option<&HashMap<Vec<u8>, Vec<u8>>> {
-        self.contract_state.get(address)
+    /// Gets the contract deployed at an address in `state`
+    pub fn get_contract(&self, address: &str, state: &dyn StateAccess) -> Option<Arc<Contract>> {
+        self.load_contract(address, state).ok()
     }
-}
-
-/// Solidity compiler interface
-pub mod solidity {
-    use super::*;
-    
-    /// Compiles a Solidity source file
-    pub fn compile(source: &str) -> Result<(Vec<u8>, Vec<FunctionABI>)> {
-        // In a real implementation, this would call the solc compiler
-        // and parse its output
-        
-        // For now, we'll just return dummy bytecode and ABI
-        let bytecode = vec![0x60, 0x80, 0x60, 0x40, 0x52]; // Dummy bytecode
-        
-        // Create a dummy ABI with a single function
-        let function = FunctionABI {
-            name: "transfer".to_string(),
-            inputs: vec![
-                ABIParameter {
-                    name: "to".to_string(),
-                    param_type: "address".to_string(),
-                },
-                ABIParameter {
-                    name: "amount".to_string(),
-                    param_type: "uint256".to_string(),
-                },
-            ],
-            outputs: vec![
-                ABIParameter {
-                    name: "".to_string(),
-                    param_type: "bool".to_string(),
-                },
-            ],
-            constant: false,
-            signature: [0xa9, 0x05, 0x9c, 0xbb], // transfer(address,uint256)
-        };
+    
+    /// Verifies a contract's Solidity source against its deployed code
+    ///
+    /// The source is compiled with `compile_options`, which must match the
+    /// settings the contract was originally compiled with, so this needs the
+    /// `solc` feature. If a contract in the source compiles to the deployed
+    /// runtime code the source is stored, see `get_verified_source`;
+    /// otherwise the result says how each contract differs.
+    pub fn verify_source(
+        &self,
+        address: &str,
+        source: &str,
+        compile_options: &solidity::CompileOptions,
+        state: &dyn StateAccess,
+    ) -> Result<verification::VerificationResult> {
+        let output = solidity::compile_with_options(source, compile_options)?;
+        self.verify_compiled(address, source, compile_options, &output, state)
+    }
+    
+    /// Verifies a contract like `verify_source`, given the compiler's output for the source
+    pub fn verify_compiled(
+        &self,
+        address: &str,
+        source: &str,
+        compile_options: &solidity::CompileOptions,
+        output: &solidity::CompilerOutput,
+        state: &dyn StateAccess,
+    ) -> Result<verification::VerificationResult> {
+        let account = state.get_contract(address).ok_or_else(|| {
+            ContractError::ContractNotFound { address: address.to_string() }
+        })?;
+        
+        match verification::verify(&account.code, source, compile_options, output) {
+            Ok(verified) => {
+                let contract_name = verified.contract_name.clone();
+                self.registry.store_verified(address, verified);
+                Ok(verification::VerificationResult::Verified { contract_name })
+            }
+            Err(mismatches) if mismatches.is_empty() => Err(ContractError::CompilationError(
+                "Source contains no deployable contract".to_string(),
+            )),
+            Err(mismatches) => Ok(verification::VerificationResult::Mismatch(mismatches)),
+        }
+    }
+    
+    /// Gets the verified source of the contract deployed at an address in `state`
+    pub fn get_verified_source(&self, address: &str, state: &dyn StateAccess) -> Option<Arc<verification::VerifiedSource>> {
+        self.registry.verified_source(address, state)
+    }
+    
+    /// Reads a slot of a contract's storage in `state`
+    ///
+    /// Returns `None` for slots that were never written or were cleared, and
+    /// for addresses without a contract.
+    pub fn storage_at(&self, address: &str, key: &[u8; 32], state: &dyn StateAccess) -> Option<Vec<u8>> {
+        state.get_storage(address, key).map(|value| value.to_vec())
+    }
+    
+    /// Lists a contract's storage in ascending key order
+    ///
+    /// Returns up to `limit` slots with keys from `start_key` on (or from the
+    /// first key), along with the key to continue from.
+    pub fn storage_entries(
+        &self,
+        address: &str,
+        start_key: Option<&[u8; 32]>,
+        limit: usize,
+        state: &dyn StateAccess,
+    ) -> Result<inspect::StoragePage> {
+        if state.get_contract(address).is_none() {
+            return Err(ContractError::ContractNotFound { address: address.to_string() });
+        }
+        
+        let mut keys: Vec<&Vec<u8>> = state
+            .get_contract_storage(address)
+            .into_iter()
+            .flat_map(|storage| storage.keys())
+            .filter(|key| start_key.is_none_or(|start| key.as_slice() >= start.as_slice()))
+            .collect();
         
-        let abi = vec![function];
+        // Only the page and the key after it need sorting
+        if keys.len() > limit {
+            keys.select_nth_unstable(limit);
+            keys.truncate(limit + 1);
+        }
+        keys.sort_unstable();
+        let next_key = if keys.len() > limit { keys.pop().cloned() } else { None };
+        
+        let entries = keys
+            .into_iter()
+            .map(|key| inspect::StorageEntry {
+                key: key.clone(),
+                value: state.get_storage(address, key).unwrap_or_default().to_vec(),
+            })
+            .collect();
         
-        Ok((bytecode, abi))
+        Ok(inspect::StoragePage { entries, next_key })
     }
-}
-
-/// EVM (Ethereum Virtual Machine) implementation
-pub mod evm {
-    use super::*;
     
-    /// Executes EVM bytecode
-    pub fn execute(
-        bytecode: &[u8],
-        input: &[u8],
-        state: &mut HashMap<Vec<u8>, Vec<u8>>,
-    ) -> Result<Vec<u8>> {
-        // In a real implementation, this would execute the EVM bytecode
-        // with the given input and state
+    /// Lists the contracts deployed by an account that are still deployed
+    ///
+    /// Returns up to `limit` contracts, ordered by deployment height and then
+    /// address, from `start` on (or from the first one), along with where the
+    /// next page starts.
+    pub fn get_contracts_by_creator(
+        &self,
+        creator: &str,
+        start: Option<&inspect::DeploymentCursor>,
+        limit: usize,
+        state: &dyn StateAccess,
+    ) -> inspect::DeploymentPage {
+        let start = start.map(|cursor| (cursor.deployed_at, cursor.address.as_str()));
+        deployment_page(state.contracts_by_creator(creator, start, limit.saturating_add(1)), limit, state)
+    }
+    
+    /// Lists the contracts deployed in a block that are still deployed
+    ///
+    /// Returns up to `limit` contracts in address order, from the address
+    /// `start` on (or from the first one), along with where the next page starts.
+    pub fn get_contracts_in_block(
+        &self,
+        height: u64,
+        start: Option<&str>,
+        limit: usize,
+        state: &dyn StateAccess,
+    ) -> inspect::DeploymentPage {
+        let contracts = state
+            .list_contracts(Some((height, start.unwrap_or_default())), limit.saturating_add(1))
+            .into_iter()
+            .take_while(|(deployed_at, _)| *deployed_at == height)
+            .collect();
+        deployment_page(contracts, limit, state)
+    }
+    
+    /// Lists all deployed contracts, paginated like `get_contracts_by_creator`
+    pub fn list_contracts(
+        &self,
+        start: Option<&inspect::DeploymentCursor>,
+        limit: usize,
+        state: &dyn StateAccess,
+    ) -> inspect::DeploymentPage {
+        let start = start.map(|cursor| (cursor.deployed_at, cursor.address.as_str()));
+        deployment_page(state.list_contracts(start, limit.saturating_add(1)), limit, state)
+    }
+    
+    /// Gets a contract's code, interface, deployment details and first `storage_limit` storage slots
+    pub fn dump_contract(&self, address: &str, storage_limit: usize, state: &dyn StateAccess) -> Result<inspect::ContractDump> {
+        let contract = self.load_contract(address, state)?;
+        let storage = self.storage_entries(address, None, storage_limit, state)?;
         
-        // For now, we'll just return a dummy result
-        Ok(vec![0x01]) // true
+        Ok(inspect::ContractDump {
+            address: contract.address.clone(),
+            code: contract.bytecode.clone(),
+            abi: contract.abi.clone(),
+            events: contract.events.clone(),
+            creator: contract.creator.clone(),
+            deployed_at: contract.deployed_at,
+            storage_size: state.get_contract_storage(address).map_or(0, |storage| storage.len()),
+            storage,
+        })
     }
     
-    /// Calculates the gas cost for EVM operations
-    pub fn calculate_gas(bytecode: &[u8]) -> Result<u64> {
-        // In a real implementation, this would analyze the bytecode
-        // and calculate its gas cost
+    /// Removes a contract's code and storage from `state`
+    ///
+    /// Afterwards the address behaves like an account without code: calls to
+    /// it succeed without running anything. Its balance is kept. The decoded
+    /// contract is dropped from the registry; a verified source is kept, but
+    /// only served again if a rollback restores the contract.
+    pub fn remove_contract(&self, address: &str, state: &mut dyn StateAccess) -> Result<()> {
+        if state.remove_contract(address).is_none() {
+            return Err(ContractError::ContractNotFound { address: address.to_string() });
+        }
         
-        // For now, we'll just return a dummy gas cost
-        Ok(100_000)
+        self.registry.remove(address);
+        Ok(())
+    }
+    
+    /// Loads the contract deployed at an address, failing if there is none
+    fn load_contract(&self, address: &str, state: &dyn StateAccess) -> Result<Arc<Contract>> {
+        self.registry.load(address, state)
+    }
+
+}
+
+/// Gives the interpreter read access to deployed contracts and chain state
+struct StateHost<'a> {
+    state: &'a dyn StateAccess,
+}
+
+impl evm::Host for StateHost<'_> {
+    fn code(&self, address: &[u8; 20]) -> Option<&[u8]> {
+        self.state
+            .get_contract(&evm::contract_address(address))
+            .map(|contract| contract.code.as_slice())
+    }
+    
+    fn storage(&self, address: &[u8; 20], key: &[u8]) -> Option<&[u8]> {
+        self.state.get_storage(&evm::contract_address(address), key)
+    }
+    
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.state.get_balance(&account_name(self.state, address))
+    }
+}
+
+/// Maps an EVM address to the account holding its balance
+///
+/// Contracts use their contract address; any other address is credited
+/// under its `0x`-prefixed hex form.
+fn account_name(state: &dyn StateAccess, address: &[u8; 20]) -> String {
+    let contract = evm::contract_address(address);
+    if state.get_contract(&contract).is_some() {
+        contract
+    } else {
+        format!("0x{}", hex::encode(address))
+    }
+}
+
+/// Builds a page from up to `limit + 1` listed contracts, the last of which starts the next page
+fn deployment_page(mut contracts: Vec<(u64, String)>, limit: usize, state: &dyn StateAccess) -> inspect::DeploymentPage {
+    let next = if contracts.len() > limit {
+        contracts.pop().map(|(deployed_at, address)| inspect::DeploymentCursor { deployed_at, address })
+    } else {
+        None
+    };
+    
+    let contracts = contracts
+        .into_iter()
+        .map(|(deployed_at, address)| inspect::ContractDeployment {
+            creator: state.get_contract(&address).map(|contract| contract.creator.clone()).unwrap_or_default(),
+            address,
+            deployed_at,
+        })
+        .collect();
+    
+    inspect::DeploymentPage { contracts, next }
+}
+
+impl ContractExecutor for ContractEngine {
+    fn deploy(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> CoreResult<ExecutionOutcome> {
+        self.deploy_from_transaction(tx, header.height, header.timestamp, state)
+            .map_err(|e| BlockchainError::StateError(e.to_string()))
+    }
+    
+    fn call(&mut self, tx: &Transaction, header: &BlockHeader, state: &mut dyn StateAccess) -> CoreResult<ExecutionOutcome> {
+        self.call_from_transaction(tx, header.height, header.timestamp, state)
+            .map_err(|e| BlockchainError::StateError(e.to_string()))
     }
 }
\ No newline at end of file
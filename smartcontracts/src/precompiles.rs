@@ -0,0 +1,181 @@
+//! Precompiled contracts
+//!
+//! Addresses `0x01` to `0xffff` are reserved for contracts implemented
+//! natively by the engine rather than in bytecode. Calls to them (from a
+//! transaction or from another contract) run the native implementation and
+//! are charged the gas defined for it in `GasConfig`.
+//!
+//! | Address  | Name           | Input                        | Output                        |
+//! |----------|----------------|------------------------------|-------------------------------|
+//! | `0x01`   | ecrecover      | see `ECRECOVER_ADDRESS`      | signer's address, left-padded |
+//! | `0x02`   | sha256         | any bytes                    | 32-byte digest                |
+//! | `0x03`   | ripemd160      | any bytes                    | 20-byte digest, left-padded   |
+//! | `0x04`   | identity       | any bytes                    | the input                     |
+//! | `0x0100` | ed25519-verify | see `ED25519_VERIFY_ADDRESS` | word: 1 if valid, 0 otherwise |
+//!
+//! The token factory at `0x0200` is native too, but it creates contracts and
+//! is only handled for transactions; see the `token` module.
+
+use ed25519_dalek::{PublicKey, Signature};
+use ripemd::{Digest, Ripemd160};
+
+use ctb_core::hashing;
+use ctb_core::secp256k1;
+
+use crate::token::TOKEN_FACTORY_ADDRESS;
+use crate::{ContractError, GasConfig, Result};
+
+/// Address of the secp256k1 public key recovery precompile
+///
+/// Input layout, zero-padded to 128 bytes:
+///
+/// | Bytes      | Content                      |
+/// |------------|------------------------------|
+/// | `0..32`    | message hash                 |
+/// | `32..64`   | `v`: 27 or 28, as a word     |
+/// | `64..96`   | `r`                          |
+/// | `96..128`  | `s`                          |
+///
+/// Returns the 20-byte Ethereum address of the signing key, left-padded to
+/// a word, which for a secp256k1 account is its EVM address. An invalid
+/// signature returns no data rather than failing the call, as in Ethereum.
+pub const ECRECOVER_ADDRESS: [u8; 20] = precompile_address(0x01);
+
+/// Address of the SHA-256 precompile
+pub const SHA256_ADDRESS: [u8; 20] = precompile_address(0x02);
+
+/// Address of the RIPEMD-160 precompile
+pub const RIPEMD160_ADDRESS: [u8; 20] = precompile_address(0x03);
+
+/// Address of the identity (data copy) precompile
+pub const IDENTITY_ADDRESS: [u8; 20] = precompile_address(0x04);
+
+/// Address of the ed25519 signature verification precompile
+///
+/// Input layout:
+///
+/// | Bytes    | Content                                        |
+/// |----------|------------------------------------------------|
+/// | `0..32`  | public key (the hex part of a `GENX` address)  |
+/// | `32..96` | signature                                      |
+/// | `96..`   | signed message                                 |
+///
+/// Returns a 32-byte word holding 1 if the signature is valid for the
+/// message and key, and 0 otherwise (including for malformed keys or
+/// signatures). Input shorter than 96 bytes makes the call fail.
+/// Verification is strict, so malleable signatures are rejected.
+pub const ED25519_VERIFY_ADDRESS: [u8; 20] = precompile_address(0x0100);
+
+/// Result of running a precompile
+#[derive(Debug, Clone)]
+pub struct PrecompileOutput {
+    /// Data returned to the caller
+    pub output: Vec<u8>,
+    
+    /// Gas consumed
+    pub gas_used: u64,
+}
+
+/// Builds the address of the precompile with the given number
+pub(crate) const fn precompile_address(number: u16) -> [u8; 20] {
+    let mut address = [0u8; 20];
+    address[18] = (number >> 8) as u8;
+    address[19] = number as u8;
+    address
+}
+
+/// Checks whether an address lies in the range reserved for precompiles
+pub fn is_precompile(address: &[u8; 20]) -> bool {
+    address[..18].iter().all(|b| *b == 0) && (address[18] != 0 || address[19] != 0)
+}
+
+/// Runs the precompile at `address`, if there is one
+///
+/// Returns `None` for addresses that aren't a precompile. Reserved addresses
+/// without an implementation behave like empty accounts, and calls to the
+/// token factory fail. Running out of gas is an error, which consumes all
+/// the gas given to the call.
+pub fn run(address: &[u8; 20], input: &[u8], gas_limit: u64, config: &GasConfig) -> Option<Result<PrecompileOutput>> {
+    let (base_cost, word_cost) = match *address {
+        ECRECOVER_ADDRESS => (config.ecrecover_cost, 0),
+        SHA256_ADDRESS => (config.sha256_cost, config.sha256_word_cost),
+        RIPEMD160_ADDRESS => (config.ripemd160_cost, config.ripemd160_word_cost),
+        IDENTITY_ADDRESS => (config.identity_cost, config.identity_word_cost),
+        ED25519_VERIFY_ADDRESS => (config.ed25519_verify_cost, config.ed25519_verify_word_cost),
+        TOKEN_FACTORY_ADDRESS => {
+            return Some(Err(ContractError::ExecutionError(
+                "The token factory can only be called by a transaction".to_string(),
+            )));
+        }
+        _ if is_precompile(address) => return Some(Ok(PrecompileOutput { output: Vec::new(), gas_used: 0 })),
+        _ => return None,
+    };
+    
+    let words = input.len().div_ceil(32) as u64;
+    let gas_used = base_cost.saturating_add(words.saturating_mul(word_cost));
+    if gas_used > gas_limit {
+        return Some(Err(ContractError::OutOfGas { required: gas_used, available: gas_limit }));
+    }
+    
+    let output = match *address {
+        ECRECOVER_ADDRESS => Ok(ecrecover(input)),
+        SHA256_ADDRESS => Ok(hashing::sha256(input).to_vec()),
+        RIPEMD160_ADDRESS => {
+            let mut output = vec![0u8; 12];
+            output.extend_from_slice(&Ripemd160::digest(input));
+            Ok(output)
+        }
+        IDENTITY_ADDRESS => Ok(input.to_vec()),
+        _ => ed25519_verify(input),
+    };
+    
+    Some(output.map(|output| PrecompileOutput { output, gas_used }))
+}
+
+/// Recovers a signer's address from input laid out as described on `ECRECOVER_ADDRESS`
+fn ecrecover(input: &[u8]) -> Vec<u8> {
+    let mut padded = [0u8; 128];
+    let len = input.len().min(128);
+    padded[..len].copy_from_slice(&input[..len]);
+    
+    let hash: [u8; 32] = padded[..32].try_into().unwrap();
+    let (v, r, s) = (&padded[32..64], &padded[64..96], &padded[96..128]);
+    if v[..31].iter().any(|b| *b != 0) || !matches!(v[31], 27 | 28) {
+        return Vec::new();
+    }
+    
+    let key = secp256k1::Signature::from_parts(r.try_into().unwrap(), s.try_into().unwrap(), v[31] - 27)
+        .ok()
+        .and_then(|signature| signature.recover(&hash));
+    
+    match key {
+        Some(key) => {
+            let mut output = vec![0u8; 12];
+            output.extend_from_slice(&key.evm_address());
+            output
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Verifies an ed25519 signature laid out as described on `ED25519_VERIFY_ADDRESS`
+fn ed25519_verify(input: &[u8]) -> Result<Vec<u8>> {
+    if input.len() < 96 {
+        return Err(ContractError::ExecutionError(format!(
+            "ed25519-verify input must be at least 96 bytes, got {}",
+            input.len()
+        )));
+    }
+    
+    let (public_key, rest) = input.split_at(32);
+    let (signature, message) = rest.split_at(64);
+    
+    let valid = match (PublicKey::from_bytes(public_key), Signature::try_from(signature)) {
+        (Ok(public_key), Ok(signature)) => public_key.verify_strict(message, &signature).is_ok(),
+        _ => false,
+    };
+    
+    let mut word = vec![0u8; 32];
+    word[31] = valid as u8;
+    Ok(word)
+}
\ No newline at end of file
@@ -0,0 +1,211 @@
+//! Concurrent read access to contracts
+//!
+//! Block application needs the engine exclusively, but queries don't need
+//! to wait for it. Deployed contracts never change, so their code and
+//! decoded metadata can be shared freely; only storage is mutable, and that
+//! is owned by the state being applied. A `ContractReader` runs queries
+//! against a `StateSnapshot` instead, from as many threads as needed.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use ctb_core::state::{ContractAccount, State, StateAccess, StateSnapshot};
+use ctb_core::transaction::Transaction;
+
+use crate::inspect::{ContractDump, DeploymentCursor, DeploymentPage, StoragePage};
+use crate::solidity::CompileOptions;
+use crate::verification::{VerificationResult, VerifiedSource};
+use crate::{abi, evm, tracer, BlockContext, Contract, ContractEngine, ContractError, Result};
+
+/// A decoded contract along with the account it was decoded from
+type RegistryEntry = (Arc<ContractAccount>, Arc<Contract>);
+
+/// Contracts decoded from the state, shared by an engine and its readers
+///
+/// Decoding a contract's metadata on every call would dominate cheap
+/// queries. An entry is only used while the state holds the very account it
+/// was decoded from, so a contract removed by a rollback is never served.
+/// Verified sources are kept here too, and only served while the contract
+/// at their address still has the code they were verified against.
+#[derive(Debug, Default)]
+pub(crate) struct ContractRegistry {
+    contracts: RwLock<HashMap<String, RegistryEntry>>,
+    verified: RwLock<HashMap<String, Arc<VerifiedSource>>>,
+}
+
+impl ContractRegistry {
+    /// Loads the contract deployed at an address, failing if there is none
+    pub(crate) fn load(&self, address: &str, state: &dyn StateAccess) -> Result<Arc<Contract>> {
+        let account = state.get_contract(address).ok_or_else(|| {
+            ContractError::ContractNotFound { address: address.to_string() }
+        })?;
+        
+        if let Some((decoded_from, contract)) = self.contracts.read().unwrap().get(address) {
+            if Arc::ptr_eq(decoded_from, account) {
+                return Ok(contract.clone());
+            }
+        }
+        
+        let contract = Arc::new(Contract::from_account(address, account)?);
+        self.contracts
+            .write()
+            .unwrap()
+            .insert(address.to_string(), (account.clone(), contract.clone()));
+        Ok(contract)
+    }
+    
+    /// Drops the decoded contract at an address
+    pub(crate) fn remove(&self, address: &str) {
+        self.contracts.write().unwrap().remove(address);
+    }
+    
+    /// Gets the verified source of the contract deployed at an address
+    pub(crate) fn verified_source(&self, address: &str, state: &dyn StateAccess) -> Option<Arc<VerifiedSource>> {
+        let account = state.get_contract(address)?;
+        let verified = self.verified.read().unwrap().get(address)?.clone();
+        
+        (verified.code_hash == abi::keccak256(&account.code)).then_some(verified)
+    }
+    
+    /// Stores the verified source of a contract, replacing any earlier one
+    pub(crate) fn store_verified(&self, address: &str, source: VerifiedSource) {
+        self.verified.write().unwrap().insert(address.to_string(), Arc::new(source));
+    }
+}
+
+/// Cheap, cloneable handle for read-only contract queries
+///
+/// Created by `ContractEngine::reader`, with the engine's configuration at
+/// that time. Queries run against a state snapshot and see the block height
+/// and timestamp it was taken at; they never lock the engine.
+#[derive(Debug, Clone)]
+pub struct ContractReader {
+    engine: Arc<ContractEngine>,
+}
+
+impl ContractReader {
+    pub(crate) fn new(engine: ContractEngine) -> Self {
+        Self { engine: Arc::new(engine) }
+    }
+    
+    /// Gets the contract deployed at an address in a snapshot
+    pub fn get_contract(&self, address: &str, snapshot: &StateSnapshot) -> Option<Arc<Contract>> {
+        self.engine.get_contract(address, &*snapshot.state)
+    }
+    
+    /// Reads a slot of a contract's storage in a snapshot
+    pub fn storage_at(&self, address: &str, key: &[u8; 32], snapshot: &StateSnapshot) -> Option<Vec<u8>> {
+        self.engine.storage_at(address, key, &*snapshot.state)
+    }
+    
+    /// Lists a contract's storage in a snapshot, see `ContractEngine::storage_entries`
+    pub fn storage_entries(
+        &self,
+        address: &str,
+        start_key: Option<&[u8; 32]>,
+        limit: usize,
+        snapshot: &StateSnapshot,
+    ) -> Result<StoragePage> {
+        self.engine.storage_entries(address, start_key, limit, &*snapshot.state)
+    }
+    
+    /// Dumps a contract in a snapshot, see `ContractEngine::dump_contract`
+    pub fn dump_contract(&self, address: &str, storage_limit: usize, snapshot: &StateSnapshot) -> Result<ContractDump> {
+        self.engine.dump_contract(address, storage_limit, &*snapshot.state)
+    }
+    
+    /// Lists the contracts an account deployed in a snapshot, see `ContractEngine::get_contracts_by_creator`
+    pub fn get_contracts_by_creator(
+        &self,
+        creator: &str,
+        start: Option<&DeploymentCursor>,
+        limit: usize,
+        snapshot: &StateSnapshot,
+    ) -> DeploymentPage {
+        self.engine.get_contracts_by_creator(creator, start, limit, &*snapshot.state)
+    }
+    
+    /// Lists the contracts deployed in a block in a snapshot, see `ContractEngine::get_contracts_in_block`
+    pub fn get_contracts_in_block(&self, height: u64, start: Option<&str>, limit: usize, snapshot: &StateSnapshot) -> DeploymentPage {
+        self.engine.get_contracts_in_block(height, start, limit, &*snapshot.state)
+    }
+    
+    /// Lists all deployed contracts in a snapshot, see `ContractEngine::list_contracts`
+    pub fn list_contracts(&self, start: Option<&DeploymentCursor>, limit: usize, snapshot: &StateSnapshot) -> DeploymentPage {
+        self.engine.list_contracts(start, limit, &*snapshot.state)
+    }
+    
+    /// Verifies a contract's source against a snapshot, see `ContractEngine::verify_source`
+    pub fn verify_source(
+        &self,
+        address: &str,
+        source: &str,
+        compile_options: &CompileOptions,
+        snapshot: &StateSnapshot,
+    ) -> Result<VerificationResult> {
+        self.engine.verify_source(address, source, compile_options, &*snapshot.state)
+    }
+    
+    /// Gets the verified source of a contract in a snapshot
+    pub fn get_verified_source(&self, address: &str, snapshot: &StateSnapshot) -> Option<Arc<VerifiedSource>> {
+        self.engine.get_verified_source(address, &*snapshot.state)
+    }
+    
+    /// Executes a contract function against a snapshot, see `ContractEngine::call_static`
+    pub fn call_static(
+        &self,
+        contract_address: &str,
+        function_signature: &[u8; 4],
+        arguments: &[u8],
+        sender: &str,
+        gas_limit: u64,
+        snapshot: &StateSnapshot,
+    ) -> Result<Vec<u8>> {
+        self.engine.call_static_at(
+            block_context(snapshot),
+            contract_address,
+            function_signature,
+            arguments,
+            sender,
+            gas_limit,
+            &*snapshot.state,
+        )
+    }
+    
+    /// Estimates a transaction's gas against a snapshot, see `ContractEngine::estimate_gas`
+    ///
+    /// The transaction runs against a copy of the snapshot's state.
+    pub fn estimate_gas(&self, tx: &Transaction, gas_cap: u64, snapshot: &StateSnapshot) -> Result<u64> {
+        let mut state = State::clone(&snapshot.state);
+        self.engine.estimate_gas_at(block_context(snapshot), tx, gas_cap, &mut state)
+    }
+    
+    /// Traces call data against a snapshot, see `ContractEngine::trace_call`
+    pub fn trace_call<T: tracer::Tracer>(
+        &self,
+        contract_address: &str,
+        input: &[u8],
+        sender: &str,
+        gas_limit: u64,
+        snapshot: &StateSnapshot,
+        tracer: &mut T,
+    ) -> Result<evm::ExecutionResult> {
+        self.engine.run_code_traced(
+            block_context(snapshot),
+            contract_address,
+            input,
+            sender,
+            0,
+            gas_limit,
+            &*snapshot.state,
+            tracer,
+        )
+    }
+}
+
+fn block_context(snapshot: &StateSnapshot) -> BlockContext {
+    BlockContext {
+        height: snapshot.block_height,
+        timestamp: snapshot.block_timestamp,
+    }
+}
\ No newline at end of file
@@ -0,0 +1,356 @@
+//! Solidity compiler interface
+//!
+//! Contracts are compiled by a locally installed `solc`, driven through its
+//! standard JSON interface. The backend is only built with the `solc`
+//! feature; without it every compilation fails with a "compiler unavailable"
+//! error rather than producing placeholder bytecode.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ContractError, DeployPayload, EventABI, FunctionABI, Result};
+
+/// Name under which the source is passed to the compiler
+pub const SOURCE_FILE_NAME: &str = "Contract.sol";
+
+/// Compiler settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompileOptions {
+    /// Path to the solc binary; when unset, a binary installed for `version`
+    /// under `~/.svm` is preferred over the `solc` found on the PATH
+    pub solc_path: Option<PathBuf>,
+    
+    /// Exact compiler version to use, e.g. `0.8.19`
+    pub version: Option<String>,
+    
+    /// Whether to run the optimizer
+    pub optimize: bool,
+    
+    /// Number of runs the optimizer tunes for
+    pub optimizer_runs: u32,
+    
+    /// Target EVM version, e.g. `paris`; the compiler default when unset
+    pub evm_version: Option<String>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            solc_path: None,
+            version: None,
+            optimize: false,
+            optimizer_runs: 200,
+            evm_version: None,
+        }
+    }
+}
+
+/// Severity of a compiler diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// Error or warning reported by the compiler
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Severity of the diagnostic
+    pub severity: Severity,
+    
+    /// Compiler message
+    pub message: String,
+    
+    /// Source file the diagnostic refers to
+    pub file: Option<String>,
+    
+    /// Line in the source file (1-based)
+    pub line: Option<usize>,
+    
+    /// Column in the source file (1-based)
+    pub column: Option<usize>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file)?;
+            if let (Some(line), Some(column)) = (self.line, self.column) {
+                write!(f, ":{}:{}", line, column)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{:?}: {}", self.severity, self.message)
+    }
+}
+
+/// A contract produced by the compiler
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompiledContract {
+    /// Contract name
+    pub name: String,
+    
+    /// Creation (init) code
+    pub bytecode: Vec<u8>,
+    
+    /// Runtime code, as returned by the init code
+    pub deployed_bytecode: Vec<u8>,
+    
+    /// Contract functions
+    pub abi: Vec<FunctionABI>,
+    
+    /// Contract events
+    pub events: Vec<EventABI>,
+}
+
+impl CompiledContract {
+    /// Builds the payload of a ContractDeploy transaction for this contract
+    pub fn deploy_payload(&self, constructor_args: Vec<u8>) -> DeployPayload {
+        DeployPayload {
+            init_code: self.bytecode.clone(),
+            abi: self.abi.clone(),
+            constructor_args,
+            events: self.events.clone(),
+        }
+    }
+}
+
+/// Result of a successful compilation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompilerOutput {
+    /// Compiled contracts, in source order
+    pub contracts: Vec<CompiledContract>,
+    
+    /// Warnings and informational messages
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Compiles a Solidity source file with default options
+///
+/// Returns the creation code and ABI of the last deployable contract in the
+/// source, which by convention is the main one.
+pub fn compile(source: &str) -> Result<(Vec<u8>, Vec<FunctionABI>)> {
+    let output = compile_with_options(source, &CompileOptions::default())?;
+    
+    let contract = output
+        .contracts
+        .into_iter()
+        .rev()
+        .find(|contract| !contract.bytecode.is_empty())
+        .ok_or_else(|| {
+            ContractError::CompilationError("Source contains no deployable contract".to_string())
+        })?;
+    
+    Ok((contract.bytecode, contract.abi))
+}
+
+/// Compiles a Solidity source file
+///
+/// Compiler errors are returned as `ContractError::CompilationFailed` with
+/// their source locations; warnings are included in the output.
+pub fn compile_with_options(source: &str, options: &CompileOptions) -> Result<CompilerOutput> {
+    #[cfg(feature = "solc")]
+    {
+        backend::compile(source, options)
+    }
+    
+    #[cfg(not(feature = "solc"))]
+    {
+        let _ = (source, options);
+        Err(ContractError::CompilationError(
+            "Solidity compiler unavailable: built without the `solc` feature".to_string(),
+        ))
+    }
+}
+
+#[cfg(feature = "solc")]
+mod backend {
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+    
+    use serde_json::{json, Value};
+    
+    use super::*;
+    use crate::abi;
+    
+    /// Runs solc on the source and parses its standard JSON output
+    pub(super) fn compile(source: &str, options: &CompileOptions) -> Result<CompilerOutput> {
+        let solc = resolve_solc(options)?;
+        
+        let mut settings = json!({
+            "optimizer": {
+                "enabled": options.optimize,
+                "runs": options.optimizer_runs,
+            },
+            "outputSelection": {
+                "*": {
+                    "*": ["abi", "evm.bytecode.object", "evm.deployedBytecode.object"],
+                },
+            },
+        });
+        if let Some(evm_version) = &options.evm_version {
+            settings["evmVersion"] = json!(evm_version);
+        }
+        
+        let input = json!({
+            "language": "Solidity",
+            "sources": {
+                SOURCE_FILE_NAME: { "content": source },
+            },
+            "settings": settings,
+        });
+        
+        let output = run(&solc, &["--standard-json"], Some(&input.to_string()))?;
+        let output: Value = serde_json::from_str(&output).map_err(|e| {
+            ContractError::CompilationError(format!("Invalid compiler output: {}", e))
+        })?;
+        
+        parse_output(source, &output)
+    }
+    
+    /// Finds the solc binary to run and checks its version
+    fn resolve_solc(options: &CompileOptions) -> Result<PathBuf> {
+        let solc = match (&options.solc_path, &options.version) {
+            (Some(path), _) => path.clone(),
+            (None, Some(version)) => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".svm").join(version).join(format!("solc-{}", version)))
+                .filter(|path| path.is_file())
+                .unwrap_or_else(|| PathBuf::from("solc")),
+            (None, None) => PathBuf::from("solc"),
+        };
+        
+        if let Some(version) = &options.version {
+            let reported = run(&solc, &["--version"], None)?;
+            if !reported.contains(&format!("Version: {}+", version)) {
+                return Err(ContractError::CompilationError(format!(
+                    "solc version mismatch: wanted {}, {} reports {}",
+                    version,
+                    solc.display(),
+                    reported.lines().last().unwrap_or_default().trim()
+                )));
+            }
+        }
+        
+        Ok(solc)
+    }
+    
+    /// Runs the compiler, feeding `stdin` to it, and returns its standard output
+    fn run(solc: &PathBuf, args: &[&str], stdin: Option<&str>) -> Result<String> {
+        let unavailable = |e: std::io::Error| {
+            ContractError::CompilationError(format!(
+                "Solidity compiler unavailable: failed to run {}: {}",
+                solc.display(),
+                e
+            ))
+        };
+        
+        let mut child = Command::new(solc)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(unavailable)?;
+        
+        if let Some(stdin) = stdin {
+            if let Some(mut pipe) = child.stdin.take() {
+                pipe.write_all(stdin.as_bytes()).map_err(unavailable)?;
+            }
+        }
+        
+        let output = child.wait_with_output().map_err(unavailable)?;
+        if !output.status.success() {
+            return Err(ContractError::CompilationError(format!(
+                "{} exited with {}: {}",
+                solc.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+    
+    /// Converts the standard JSON output into contracts and diagnostics
+    fn parse_output(source: &str, output: &Value) -> Result<CompilerOutput> {
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        
+        for error in output["errors"].as_array().into_iter().flatten() {
+            let diagnostic = parse_diagnostic(source, error);
+            match diagnostic.severity {
+                Severity::Error => errors.push(diagnostic),
+                _ => warnings.push(diagnostic),
+            }
+        }
+        
+        if !errors.is_empty() {
+            return Err(ContractError::CompilationFailed(errors));
+        }
+        
+        let mut contracts = Vec::new();
+        for (name, contract) in output["contracts"][SOURCE_FILE_NAME].as_object().into_iter().flatten() {
+            let (functions, events) = abi::parse_abi_json(&contract["abi"].to_string())?;
+            
+            contracts.push(CompiledContract {
+                name: name.clone(),
+                bytecode: decode_object(&contract["evm"]["bytecode"]["object"])?,
+                deployed_bytecode: decode_object(&contract["evm"]["deployedBytecode"]["object"])?,
+                abi: functions,
+                events,
+            });
+        }
+        
+        Ok(CompilerOutput { contracts, warnings })
+    }
+    
+    /// Converts a compiler error entry into a diagnostic, resolving its byte offset to a line and column
+    fn parse_diagnostic(source: &str, error: &Value) -> Diagnostic {
+        let severity = match error["severity"].as_str() {
+            Some("error") => Severity::Error,
+            Some("warning") => Severity::Warning,
+            _ => Severity::Info,
+        };
+        
+        let location = &error["sourceLocation"];
+        let file = location["file"].as_str().map(str::to_string);
+        let position = location["start"]
+            .as_u64()
+            .and_then(|start| usize::try_from(start).ok())
+            .filter(|start| file.as_deref() == Some(SOURCE_FILE_NAME) && *start <= source.len())
+            .map(|start| {
+                let before = &source.as_bytes()[..start];
+                let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+                let column = start - before.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1) + 1;
+                (line, column)
+            });
+        
+        Diagnostic {
+            severity,
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+            file,
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+        }
+    }
+    
+    /// Decodes a hex bytecode object
+    fn decode_object(object: &Value) -> Result<Vec<u8>> {
+        let object = object.as_str().unwrap_or_default();
+        
+        // Unlinked library placeholders can't be deployed as-is
+        if object.contains("__") {
+            return Err(ContractError::CompilationError(
+                "Bytecode references unlinked libraries".to_string(),
+            ));
+        }
+        
+        hex::decode(object.trim_start_matches("0x")).map_err(|e| {
+            ContractError::CompilationError(format!("Invalid bytecode in compiler output: {}", e))
+        })
+    }
+}
\ No newline at end of file
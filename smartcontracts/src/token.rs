@@ -0,0 +1,447 @@
+//! Native ERC-20 tokens
+//!
+//! Fungible tokens can be created without writing Solidity: a ContractCall
+//! transaction to the token factory at `TOKEN_FACTORY_ADDRESS`, carrying the
+//! call data of `createToken(string,string,uint8,uint256)` (see
+//! `TokenParams::to_call_data`), stores a new token contract at the address
+//! derived from the transaction and credits the initial supply to the sender.
+//! The receipt reports the token's address.
+//!
+//! Token contracts expose the standard ERC-20 ABI and events, so wallets and
+//! other contracts use them like any Solidity token. Their code is the
+//! `NATIVE_TOKEN_CODE` marker rather than bytecode: the interpreter
+//! recognizes it and runs the implementation below, which keeps its state
+//! in the contract's storage with the same layout as OpenZeppelin's ERC20:
+//!
+//! | Slot | Content                                              |
+//! |------|------------------------------------------------------|
+//! | 0    | `mapping(address => uint256)` balances               |
+//! | 1    | `mapping(address => mapping(address => uint256))` allowances |
+//! | 2    | total supply                                         |
+//! | 3    | name, as a short string                              |
+//! | 4    | symbol, as a short string                            |
+//! | 5    | decimals                                             |
+//!
+//! Reading and writing this storage and emitting events cost the same gas
+//! as the equivalent instructions in a bytecode contract.
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::Hash;
+
+use crate::abi::{self, Value};
+use crate::evm::ExecutionStatus;
+use crate::precompiles::precompile_address;
+use crate::u256::U256;
+use crate::{ABIParameter, ContractError, EventABI, EventParameter, FunctionABI, Result};
+
+/// Address of the token factory
+///
+/// It lies in the range reserved for native contracts but, since it creates
+/// contracts, it can only be called by transactions; calls from contracts fail.
+pub const TOKEN_FACTORY_ADDRESS: [u8; 20] = precompile_address(0x0200);
+
+/// Code stored for native token contracts
+///
+/// It starts with the reserved `0xEF` byte, which deployed bytecode can't,
+/// so only the factory can create contracts carrying it.
+pub const NATIVE_TOKEN_CODE: &[u8] = b"\xefERC20";
+
+/// Longest name or symbol a token can have, in bytes
+pub const MAX_NAME_LENGTH: usize = 31;
+
+/// Selector of `totalSupply()`
+pub const TOTAL_SUPPLY_SELECTOR: [u8; 4] = [0x18, 0x16, 0x0d, 0xdd];
+
+/// Selector of `balanceOf(address)`
+pub const BALANCE_OF_SELECTOR: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Selector of `transfer(address,uint256)`
+pub const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+/// Selector of `transferFrom(address,address,uint256)`
+pub const TRANSFER_FROM_SELECTOR: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+
+/// Selector of `approve(address,uint256)`
+pub const APPROVE_SELECTOR: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+
+/// Selector of `allowance(address,address)`
+pub const ALLOWANCE_SELECTOR: [u8; 4] = [0xdd, 0x62, 0xed, 0x3e];
+
+/// Selector of `name()`
+pub const NAME_SELECTOR: [u8; 4] = [0x06, 0xfd, 0xde, 0x03];
+
+/// Selector of `symbol()`
+pub const SYMBOL_SELECTOR: [u8; 4] = [0x95, 0xd8, 0x9b, 0x41];
+
+/// Selector of `decimals()`
+pub const DECIMALS_SELECTOR: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+
+/// Storage slots, see the module documentation
+const BALANCES_SLOT: u64 = 0;
+const ALLOWANCES_SLOT: u64 = 1;
+const TOTAL_SUPPLY_SLOT: u64 = 2;
+const NAME_SLOT: u64 = 3;
+const SYMBOL_SLOT: u64 = 4;
+const DECIMALS_SLOT: u64 = 5;
+
+/// Checks whether contract code is the native token marker
+pub fn is_native_token(code: &[u8]) -> bool {
+    code == NATIVE_TOKEN_CODE
+}
+
+/// Parameters of a token created through the factory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenParams {
+    /// Token name, at most `MAX_NAME_LENGTH` bytes
+    pub name: String,
+    
+    /// Token symbol, at most `MAX_NAME_LENGTH` bytes
+    pub symbol: String,
+    
+    /// Number of decimals the token's amounts are displayed with
+    pub decimals: u8,
+    
+    /// Amount credited to the creator
+    pub initial_supply: U256,
+}
+
+impl TokenParams {
+    /// Builds the factory call data creating this token
+    pub fn to_call_data(&self) -> Result<Vec<u8>> {
+        let function = create_token_function();
+        let mut data = function.signature.to_vec();
+        data.extend(abi::encode(
+            &function.inputs,
+            &[
+                Value::String(self.name.clone()),
+                Value::String(self.symbol.clone()),
+                Value::Uint(U256::from_u64(self.decimals as u64)),
+                Value::Uint(self.initial_supply),
+            ],
+        )?);
+        Ok(data)
+    }
+    
+    /// Parses factory call data
+    pub fn from_call_data(data: &[u8]) -> Result<Self> {
+        let function = create_token_function();
+        if data.len() < 4 || data[..4] != function.signature {
+            return Err(ContractError::AbiError("Token factory call data must call createToken".to_string()));
+        }
+        
+        let params = match abi::decode(&function.inputs, &data[4..])?.as_slice() {
+            [Value::String(name), Value::String(symbol), Value::Uint(decimals), Value::Uint(initial_supply)] => Self {
+                name: name.clone(),
+                symbol: symbol.clone(),
+                decimals: decimals.low_u64() as u8,
+                initial_supply: *initial_supply,
+            },
+            _ => return Err(ContractError::AbiError("Malformed createToken arguments".to_string())),
+        };
+        
+        for value in [&params.name, &params.symbol] {
+            if value.len() > MAX_NAME_LENGTH {
+                return Err(ContractError::AbiError(format!(
+                    "Token name or symbol '{}' is longer than {} bytes", value, MAX_NAME_LENGTH
+                )));
+            }
+        }
+        
+        Ok(params)
+    }
+}
+
+/// Gets the ABI of the factory's `createToken` function
+pub fn create_token_function() -> FunctionABI {
+    let inputs = vec![
+        param("name", "string"),
+        param("symbol", "string"),
+        param("decimals", "uint8"),
+        param("initialSupply", "uint256"),
+    ];
+    let signature = abi::function_selector("createToken", &inputs).expect("createToken has valid parameter types");
+    
+    FunctionABI {
+        name: "createToken".to_string(),
+        inputs,
+        outputs: vec![param("token", "address")],
+        constant: false,
+        signature,
+    }
+}
+
+/// Gets the standard ERC-20 functions every native token exposes
+pub fn erc20_functions() -> Vec<FunctionABI> {
+    let function = |name: &str, inputs: Vec<ABIParameter>, output: &str, constant: bool, signature: [u8; 4]| FunctionABI {
+        name: name.to_string(),
+        inputs,
+        outputs: vec![param("", output)],
+        constant,
+        signature,
+    };
+    
+    vec![
+        function("name", vec![], "string", true, NAME_SELECTOR),
+        function("symbol", vec![], "string", true, SYMBOL_SELECTOR),
+        function("decimals", vec![], "uint8", true, DECIMALS_SELECTOR),
+        function("totalSupply", vec![], "uint256", true, TOTAL_SUPPLY_SELECTOR),
+        function("balanceOf", vec![param("account", "address")], "uint256", true, BALANCE_OF_SELECTOR),
+        function(
+            "allowance",
+            vec![param("owner", "address"), param("spender", "address")],
+            "uint256",
+            true,
+            ALLOWANCE_SELECTOR,
+        ),
+        function(
+            "transfer",
+            vec![param("to", "address"), param("value", "uint256")],
+            "bool",
+            false,
+            TRANSFER_SELECTOR,
+        ),
+        function(
+            "transferFrom",
+            vec![param("from", "address"), param("to", "address"), param("value", "uint256")],
+            "bool",
+            false,
+            TRANSFER_FROM_SELECTOR,
+        ),
+        function(
+            "approve",
+            vec![param("spender", "address"), param("value", "uint256")],
+            "bool",
+            false,
+            APPROVE_SELECTOR,
+        ),
+    ]
+}
+
+/// Gets the standard ERC-20 events every native token emits
+pub fn erc20_events() -> Vec<EventABI> {
+    let event = |name: &str, first: &str, second: &str| EventABI {
+        name: name.to_string(),
+        inputs: vec![
+            EventParameter { name: first.to_string(), param_type: "address".to_string(), indexed: true },
+            EventParameter { name: second.to_string(), param_type: "address".to_string(), indexed: true },
+            EventParameter { name: "value".to_string(), param_type: "uint256".to_string(), indexed: false },
+        ],
+        anonymous: false,
+    };
+    
+    vec![event("Transfer", "from", "to"), event("Approval", "owner", "spender")]
+}
+
+fn param(name: &str, param_type: &str) -> ABIParameter {
+    ABIParameter {
+        name: name.to_string(),
+        param_type: param_type.to_string(),
+    }
+}
+
+/// Storage and events of the contract a native token runs as
+pub(crate) trait Ledger {
+    /// Reads a storage slot
+    fn load(&mut self, key: &U256) -> Result<U256>;
+    
+    /// Writes a storage slot
+    fn store(&mut self, key: U256, value: U256) -> Result<()>;
+    
+    /// Emits a log
+    fn log(&mut self, topics: Vec<Hash>, data: Vec<u8>) -> Result<()>;
+}
+
+/// Gets the storage a newly created token starts with, as (key, value) pairs
+pub(crate) fn initial_storage(params: &TokenParams, owner: &[u8; 20]) -> Vec<(U256, U256)> {
+    let mut storage = vec![
+        (U256::from_u64(NAME_SLOT), short_string(&params.name)),
+        (U256::from_u64(SYMBOL_SLOT), short_string(&params.symbol)),
+    ];
+    
+    if params.decimals != 0 {
+        storage.push((U256::from_u64(DECIMALS_SLOT), U256::from_u64(params.decimals as u64)));
+    }
+    if !params.initial_supply.is_zero() {
+        storage.push((U256::from_u64(TOTAL_SUPPLY_SLOT), params.initial_supply));
+        storage.push((balance_key(owner), params.initial_supply));
+    }
+    
+    storage
+}
+
+/// Builds the topics and data of a `Transfer` event
+pub(crate) fn transfer_event(from: &[u8; 20], to: &[u8; 20], value: U256) -> (Vec<Hash>, Vec<u8>) {
+    event("Transfer(address,address,uint256)", from, to, value)
+}
+
+/// Runs a call to a native token
+///
+/// Failures that a Solidity token would revert on revert with an
+/// `Error(string)` reason; running out of gas or writing in a static call
+/// fails the call.
+pub(crate) fn call<L: Ledger>(
+    ledger: &mut L,
+    caller: [u8; 20],
+    value: u64,
+    input: &[u8],
+) -> Result<(ExecutionStatus, Vec<u8>)> {
+    // Like a Solidity contract, reject calls that can't be decoded, without a reason
+    let function = match input.get(..4).and_then(|selector| {
+        erc20_functions().into_iter().find(|f| f.signature == selector)
+    }) {
+        Some(function) => function,
+        None => return Ok((ExecutionStatus::Revert, Vec::new())),
+    };
+    if value > 0 {
+        return Ok((ExecutionStatus::Revert, Vec::new()));
+    }
+    let args = match abi::decode(&function.inputs, &input[4..]) {
+        Ok(args) => args,
+        Err(_) => return Ok((ExecutionStatus::Revert, Vec::new())),
+    };
+    
+    let output = match (function.signature, args.as_slice()) {
+        (NAME_SELECTOR, []) => Value::String(load_short_string(ledger, NAME_SLOT)?),
+        (SYMBOL_SELECTOR, []) => Value::String(load_short_string(ledger, SYMBOL_SLOT)?),
+        (DECIMALS_SELECTOR, []) => Value::Uint(ledger.load(&U256::from_u64(DECIMALS_SLOT))?),
+        (TOTAL_SUPPLY_SELECTOR, []) => Value::Uint(ledger.load(&U256::from_u64(TOTAL_SUPPLY_SLOT))?),
+        (BALANCE_OF_SELECTOR, [Value::Address(account)]) => Value::Uint(ledger.load(&balance_key(account))?),
+        (ALLOWANCE_SELECTOR, [Value::Address(owner), Value::Address(spender)]) => {
+            Value::Uint(ledger.load(&allowance_key(owner, spender))?)
+        }
+        (TRANSFER_SELECTOR, [Value::Address(to), Value::Uint(amount)]) => {
+            if let Err(reason) = transfer(ledger, &caller, to, *amount)? {
+                return Ok(revert(reason));
+            }
+            Value::Bool(true)
+        }
+        (TRANSFER_FROM_SELECTOR, [Value::Address(from), Value::Address(to), Value::Uint(amount)]) => {
+            if let Err(reason) = spend_allowance(ledger, from, &caller, *amount)? {
+                return Ok(revert(reason));
+            }
+            if let Err(reason) = transfer(ledger, from, to, *amount)? {
+                return Ok(revert(reason));
+            }
+            Value::Bool(true)
+        }
+        (APPROVE_SELECTOR, [Value::Address(spender), Value::Uint(amount)]) => {
+            if *spender == [0u8; 20] {
+                return Ok(revert("ERC20: approve to the zero address"));
+            }
+            ledger.store(allowance_key(&caller, spender), *amount)?;
+            let (topics, data) = event("Approval(address,address,uint256)", &caller, spender, *amount);
+            ledger.log(topics, data)?;
+            Value::Bool(true)
+        }
+        _ => return Ok((ExecutionStatus::Revert, Vec::new())),
+    };
+    
+    Ok((ExecutionStatus::Success, abi::encode(&function.outputs, &[output])?))
+}
+
+/// Moves tokens between accounts, giving the revert reason if it isn't allowed
+fn transfer<L: Ledger>(
+    ledger: &mut L,
+    from: &[u8; 20],
+    to: &[u8; 20],
+    amount: U256,
+) -> Result<std::result::Result<(), &'static str>> {
+    if *to == [0u8; 20] {
+        return Ok(Err("ERC20: transfer to the zero address"));
+    }
+    
+    let from_key = balance_key(from);
+    let from_balance = ledger.load(&from_key)?;
+    if from_balance < amount {
+        return Ok(Err("ERC20: transfer amount exceeds balance"));
+    }
+    ledger.store(from_key, from_balance.wrapping_sub(amount))?;
+    
+    // Balances add up to the total supply, so this can't overflow
+    let to_key = balance_key(to);
+    let to_balance = ledger.load(&to_key)?;
+    ledger.store(to_key, to_balance.wrapping_add(amount))?;
+    
+    let (topics, data) = transfer_event(from, to, amount);
+    ledger.log(topics, data)?;
+    Ok(Ok(()))
+}
+
+/// Deducts from the allowance `owner` gave `spender`; the maximum allowance is unlimited
+fn spend_allowance<L: Ledger>(
+    ledger: &mut L,
+    owner: &[u8; 20],
+    spender: &[u8; 20],
+    amount: U256,
+) -> Result<std::result::Result<(), &'static str>> {
+    let key = allowance_key(owner, spender);
+    let allowance = ledger.load(&key)?;
+    if allowance == U256::MAX {
+        return Ok(Ok(()));
+    }
+    if allowance < amount {
+        return Ok(Err("ERC20: insufficient allowance"));
+    }
+    
+    ledger.store(key, allowance.wrapping_sub(amount))?;
+    Ok(Ok(()))
+}
+
+/// Builds revert data carrying an `Error(string)` reason
+fn revert(reason: &str) -> (ExecutionStatus, Vec<u8>) {
+    let mut data = abi::ERROR_SELECTOR.to_vec();
+    data.extend(
+        abi::encode(&[param("", "string")], &[Value::String(reason.to_string())])
+            .expect("a string always encodes as a string"),
+    );
+    (ExecutionStatus::Revert, data)
+}
+
+/// Builds the topics and data of an event with two indexed addresses and a value
+fn event(signature: &str, first: &[u8; 20], second: &[u8; 20], value: U256) -> (Vec<Hash>, Vec<u8>) {
+    let topics = vec![
+        abi::keccak256(signature.as_bytes()),
+        U256::from_be_slice(first).to_be_bytes(),
+        U256::from_be_slice(second).to_be_bytes(),
+    ];
+    (topics, value.to_be_bytes().to_vec())
+}
+
+/// Storage key of a mapping entry, as Solidity lays mappings out
+fn mapping_key(key: &[u8; 32], slot: &[u8; 32]) -> U256 {
+    let mut preimage = [0u8; 64];
+    preimage[..32].copy_from_slice(key);
+    preimage[32..].copy_from_slice(slot);
+    U256::from_be_bytes(&abi::keccak256(&preimage))
+}
+
+fn balance_key(account: &[u8; 20]) -> U256 {
+    mapping_key(
+        &U256::from_be_slice(account).to_be_bytes(),
+        &U256::from_u64(BALANCES_SLOT).to_be_bytes(),
+    )
+}
+
+fn allowance_key(owner: &[u8; 20], spender: &[u8; 20]) -> U256 {
+    let inner = mapping_key(
+        &U256::from_be_slice(owner).to_be_bytes(),
+        &U256::from_u64(ALLOWANCES_SLOT).to_be_bytes(),
+    );
+    mapping_key(&U256::from_be_slice(spender).to_be_bytes(), &inner.to_be_bytes())
+}
+
+/// Encodes a string of up to 31 bytes in one slot, as Solidity does:
+/// the bytes left-aligned and twice the length in the last byte
+fn short_string(value: &str) -> U256 {
+    let mut word = [0u8; 32];
+    word[..value.len()].copy_from_slice(value.as_bytes());
+    word[31] = (value.len() * 2) as u8;
+    U256::from_be_bytes(&word)
+}
+
+fn load_short_string<L: Ledger>(ledger: &mut L, slot: u64) -> Result<String> {
+    let word = ledger.load(&U256::from_u64(slot))?.to_be_bytes();
+    let len = (word[31] / 2) as usize;
+    Ok(String::from_utf8_lossy(&word[..len.min(MAX_NAME_LENGTH)]).into_owned())
+}
\ No newline at end of file
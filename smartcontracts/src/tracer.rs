@@ -0,0 +1,370 @@
+//! Execution tracing
+//!
+//! A `Tracer` is handed to `evm::execute_traced` and observes execution as it
+//! happens: every instruction, every call frame entered and left, and every
+//! storage slot read or written. Tracers only observe; they can't change
+//! the outcome of an execution.
+//!
+//! Two tracers are built in:
+//!
+//! - `StructLogger` records each instruction with the gas, stack and memory
+//!   size before it ran, for stepping through a failing call.
+//! - `SummaryTracer` aggregates gas by opcode category and counts storage
+//!   slot accesses, for finding where a call spends its gas.
+//!
+//! The interpreter is generic over the tracer, so untraced execution (which
+//! uses `NoopTracer`) pays nothing for these hooks.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::evm::{opcode, CallKind, ExecutionStatus};
+use crate::u256::U256;
+use crate::Result;
+
+/// Interpreter state just before an instruction runs
+pub struct Step<'s> {
+    /// Call depth of the frame, 0 for the outermost one
+    pub depth: usize,
+    
+    /// Position of the instruction in the frame's code
+    pub pc: usize,
+    
+    /// The instruction
+    pub opcode: u8,
+    
+    /// Gas left in the frame
+    pub gas_remaining: u64,
+    
+    /// Operand stack, top last
+    pub stack: &'s [U256],
+    
+    /// Size of the frame's memory in bytes
+    pub memory_size: usize,
+}
+
+/// A call frame about to start running
+pub struct FrameEnter<'f> {
+    /// Call depth of the new frame, 0 for the outermost one
+    pub depth: usize,
+    
+    /// How the frame was called; the outermost frame counts as a `Call`
+    pub kind: CallKind,
+    
+    /// Caller as seen by the frame
+    pub caller: [u8; 20],
+    
+    /// Contract whose storage the frame runs against
+    pub address: [u8; 20],
+    
+    /// Contract whose code the frame runs; differs from `address` for delegate calls
+    pub code_address: [u8; 20],
+    
+    /// Value sent with the call
+    pub value: u64,
+    
+    /// Call data
+    pub input: &'f [u8],
+    
+    /// Gas available to the frame
+    pub gas_limit: u64,
+}
+
+/// A call frame that finished running
+pub struct FrameExit<'f> {
+    /// Call depth of the frame
+    pub depth: usize,
+    
+    /// Gas the frame consumed (all of it if it failed)
+    pub gas_used: u64,
+    
+    /// How the frame halted and the data it returned, or why it failed
+    pub outcome: &'f Result<(ExecutionStatus, Vec<u8>)>,
+}
+
+/// Observer of contract execution
+///
+/// Every method defaults to doing nothing, so tracers only implement the
+/// events they care about. Each `step` is followed by exactly one
+/// `step_end`; any frame or storage events caused by the instruction come
+/// in between.
+pub trait Tracer {
+    /// Called before each instruction
+    fn step(&mut self, _step: &Step) {}
+    
+    /// Called after each instruction with the gas it cost
+    ///
+    /// For calls the cost includes the gas forwarded to the callee, minus
+    /// whatever a precompile handed back.
+    fn step_end(&mut self, _gas_cost: u64) {}
+    
+    /// Called when a call frame starts, including the outermost one
+    fn enter_frame(&mut self, _frame: &FrameEnter) {}
+    
+    /// Called when a call frame halts or fails
+    fn exit_frame(&mut self, _frame: &FrameExit) {}
+    
+    /// Called when a frame reads a storage slot
+    fn storage_read(&mut self, _address: &[u8; 20], _key: &U256, _value: &U256) {}
+    
+    /// Called when a frame writes a storage slot
+    fn storage_write(&mut self, _address: &[u8; 20], _key: &U256, _value: &U256) {}
+}
+
+/// Tracer that ignores everything, used when execution isn't traced
+pub struct NoopTracer;
+
+impl Tracer for NoopTracer {}
+
+/// One instruction recorded by `StructLogger`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStep {
+    /// Call depth of the frame, 0 for the outermost one
+    pub depth: usize,
+    
+    /// Position of the instruction in the frame's code
+    pub pc: usize,
+    
+    /// Mnemonic of the instruction
+    pub op: String,
+    
+    /// Gas left before the instruction ran
+    pub gas: u64,
+    
+    /// Gas the instruction cost
+    pub gas_cost: u64,
+    
+    /// Top of the stack before the instruction ran, topmost last
+    pub stack: Vec<String>,
+    
+    /// Size of the frame's memory in bytes
+    pub memory_size: usize,
+}
+
+/// Tracer that records each instruction
+///
+/// At most `limit` steps are kept so tracing a long-running call can't
+/// exhaust memory; `truncated` tells whether any were dropped.
+pub struct StructLogger {
+    limit: usize,
+    stack_depth: usize,
+    steps: Vec<TraceStep>,
+    truncated: bool,
+}
+
+impl StructLogger {
+    /// Default number of steps kept
+    pub const DEFAULT_LIMIT: usize = 100_000;
+    
+    /// Default number of stack items kept per step
+    pub const DEFAULT_STACK_DEPTH: usize = 8;
+    
+    /// Creates a logger keeping up to `limit` steps and the top `stack_depth` stack items of each
+    pub fn new(limit: usize, stack_depth: usize) -> Self {
+        Self {
+            limit,
+            stack_depth,
+            steps: Vec::new(),
+            truncated: false,
+        }
+    }
+    
+    /// Gets the recorded steps
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+    
+    /// Whether steps were dropped because the limit was reached
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+    
+    /// Takes the recorded steps
+    pub fn into_steps(self) -> Vec<TraceStep> {
+        self.steps
+    }
+}
+
+impl Default for StructLogger {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LIMIT, Self::DEFAULT_STACK_DEPTH)
+    }
+}
+
+impl Tracer for StructLogger {
+    fn step(&mut self, step: &Step) {
+        if self.steps.len() >= self.limit {
+            self.truncated = true;
+            return;
+        }
+        
+        let top = step.stack.len().saturating_sub(self.stack_depth);
+        self.steps.push(TraceStep {
+            depth: step.depth,
+            pc: step.pc,
+            op: opcode::name(step.opcode).to_string(),
+            gas: step.gas_remaining,
+            gas_cost: 0,
+            stack: step.stack[top..].iter().map(|word| word.to_string()).collect(),
+            memory_size: step.memory_size,
+        });
+    }
+    
+    fn step_end(&mut self, gas_cost: u64) {
+        // Once truncated, the last step recorded isn't the one that just ran
+        if !self.truncated {
+            if let Some(step) = self.steps.last_mut() {
+                step.gas_cost = gas_cost;
+            }
+        }
+    }
+}
+
+/// Group of related opcodes, used to summarize gas usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum OpcodeCategory {
+    /// Arithmetic, comparison and bitwise operations
+    Arithmetic,
+    
+    /// KECCAK256
+    Hashing,
+    
+    /// Call, transaction and block information
+    Environment,
+    
+    /// PUSH, POP, DUP and SWAP
+    Stack,
+    
+    /// Memory reads and writes
+    Memory,
+    
+    /// Storage reads and writes
+    Storage,
+    
+    /// Jumps, halts and other control flow
+    ControlFlow,
+    
+    /// LOG0 to LOG4
+    Logging,
+    
+    /// Calls to other contracts
+    Calls,
+    
+    /// Opcodes the interpreter doesn't support
+    Other,
+}
+
+impl OpcodeCategory {
+    /// Gets the category an opcode belongs to
+    pub fn of(op: u8) -> Self {
+        match op {
+            opcode::ADD..=opcode::SIGNEXTEND | opcode::LT..=opcode::SAR => Self::Arithmetic,
+            opcode::KECCAK256 => Self::Hashing,
+            opcode::ADDRESS..=opcode::RETURNDATACOPY | opcode::TIMESTAMP | opcode::NUMBER
+            | opcode::CHAINID | opcode::SELFBALANCE | opcode::PC | opcode::MSIZE | opcode::GAS => Self::Environment,
+            opcode::POP | opcode::PUSH0..=opcode::SWAP16 => Self::Stack,
+            opcode::MLOAD | opcode::MSTORE | opcode::MSTORE8 => Self::Memory,
+            opcode::SLOAD | opcode::SSTORE => Self::Storage,
+            opcode::STOP | opcode::JUMP | opcode::JUMPI | opcode::JUMPDEST
+            | opcode::RETURN | opcode::REVERT | opcode::INVALID | opcode::SELFDESTRUCT => Self::ControlFlow,
+            opcode::LOG0..=opcode::LOG4 => Self::Logging,
+            opcode::CALL | opcode::DELEGATECALL | opcode::STATICCALL => Self::Calls,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Accesses to one storage slot counted by `SummaryTracer`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotAccess {
+    /// Contract owning the slot
+    pub address: [u8; 20],
+    
+    /// Storage key
+    pub key: U256,
+    
+    /// Number of SLOADs of the slot
+    pub reads: u64,
+    
+    /// Number of SSTOREs to the slot
+    pub writes: u64,
+}
+
+/// Tracer that summarizes where an execution spends its gas
+///
+/// Gas forwarded to a callee is counted against the instructions the callee
+/// runs, not against the CALL that forwarded it.
+#[derive(Default)]
+pub struct SummaryTracer {
+    gas_by_category: BTreeMap<OpcodeCategory, u64>,
+    slots: HashMap<([u8; 20], U256), SlotAccess>,
+    current_op: u8,
+    forwarded: u64,
+}
+
+impl SummaryTracer {
+    /// Creates an empty summary
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Gets the gas spent on each opcode category
+    pub fn gas_by_category(&self) -> &BTreeMap<OpcodeCategory, u64> {
+        &self.gas_by_category
+    }
+    
+    /// Gets the `n` most accessed storage slots, most accessed first
+    pub fn hottest_slots(&self, n: usize) -> Vec<SlotAccess> {
+        let mut slots: Vec<SlotAccess> = self.slots.values().cloned().collect();
+        
+        // Break ties by slot so the order is deterministic
+        slots.sort_by(|a, b| {
+            (b.reads + b.writes)
+                .cmp(&(a.reads + a.writes))
+                .then_with(|| a.address.cmp(&b.address))
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        slots.truncate(n);
+        slots
+    }
+    
+    fn slot(&mut self, address: &[u8; 20], key: &U256) -> &mut SlotAccess {
+        self.slots.entry((*address, *key)).or_insert_with(|| SlotAccess {
+            address: *address,
+            key: *key,
+            reads: 0,
+            writes: 0,
+        })
+    }
+}
+
+impl Tracer for SummaryTracer {
+    fn step(&mut self, step: &Step) {
+        self.current_op = step.opcode;
+    }
+    
+    fn step_end(&mut self, gas_cost: u64) {
+        let gas_cost = gas_cost.saturating_sub(std::mem::take(&mut self.forwarded));
+        *self.gas_by_category.entry(OpcodeCategory::of(self.current_op)).or_insert(0) += gas_cost;
+    }
+    
+    fn enter_frame(&mut self, frame: &FrameEnter) {
+        if frame.depth > 0 {
+            self.forwarded = frame.gas_limit;
+        }
+    }
+    
+    fn exit_frame(&mut self, _frame: &FrameExit) {
+        // A precompile exits within the CALL, which then only costs what it kept
+        self.forwarded = 0;
+    }
+    
+    fn storage_read(&mut self, address: &[u8; 20], key: &U256, _value: &U256) {
+        self.slot(address, key).reads += 1;
+    }
+    
+    fn storage_write(&mut self, address: &[u8; 20], key: &U256, _value: &U256) {
+        self.slot(address, key).writes += 1;
+    }
+}
\ No newline at end of file
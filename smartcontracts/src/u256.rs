@@ -0,0 +1,450 @@
+//! 256-bit unsigned integer used for EVM words and ABI values
+//!
+//! This module provides a small fixed-width integer type so that contract
+//! values can be represented without pulling in a big-number library.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use serde::{Deserialize, Serialize};
+
+/// 256-bit unsigned integer stored as four little-endian 64-bit limbs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct U256(pub [u64; 4]);
+
+impl U256 {
+    /// The value zero
+    pub const ZERO: U256 = U256([0, 0, 0, 0]);
+    
+    /// The value one
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+    
+    /// The largest representable value (2^256 - 1)
+    pub const MAX: U256 = U256([u64::MAX, u64::MAX, u64::MAX, u64::MAX]);
+    
+    /// Creates a value from a u64
+    pub fn from_u64(value: u64) -> Self {
+        U256([value, 0, 0, 0])
+    }
+    
+    /// Creates a value from a signed integer using two's complement
+    pub fn from_i64(value: i64) -> Self {
+        if value < 0 {
+            U256([value as u64, u64::MAX, u64::MAX, u64::MAX])
+        } else {
+            Self::from_u64(value as u64)
+        }
+    }
+    
+    /// Creates a value from a 32-byte big-endian word
+    pub fn from_be_bytes(bytes: &[u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            let mut chunk = [0u8; 8];
+            chunk.copy_from_slice(&bytes[start..start + 8]);
+            *limb = u64::from_be_bytes(chunk);
+        }
+        U256(limbs)
+    }
+    
+    /// Creates a value from up to 32 big-endian bytes (left-padded with zeros)
+    pub fn from_be_slice(bytes: &[u8]) -> Self {
+        let mut word = [0u8; 32];
+        let len = bytes.len().min(32);
+        word[32 - len..].copy_from_slice(&bytes[bytes.len() - len..]);
+        Self::from_be_bytes(&word)
+    }
+    
+    /// Converts the value to a 32-byte big-endian word
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+    
+    /// Returns the lowest 64 bits of the value
+    pub fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+    
+    /// Returns the value as a u64 if it fits
+    pub fn as_u64(&self) -> Option<u64> {
+        if self.0[1] == 0 && self.0[2] == 0 && self.0[3] == 0 {
+            Some(self.0[0])
+        } else {
+            None
+        }
+    }
+    
+    /// Returns the value as a usize if it fits
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_u64().and_then(|v| usize::try_from(v).ok())
+    }
+    
+    /// Checks whether the value is zero
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0, 0, 0, 0]
+    }
+    
+    /// Returns the number of significant bits in the value
+    pub fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return (i as u32) * 64 + (64 - self.0[i].leading_zeros());
+            }
+        }
+        0
+    }
+    
+    /// Checks whether the value is negative when read as two's complement
+    pub fn is_negative(&self) -> bool {
+        self.0[3] >> 63 == 1
+    }
+    
+    /// Returns the bit at the given index (0 is the least significant bit)
+    pub fn bit(&self, index: usize) -> bool {
+        index < 256 && (self.0[index / 64] >> (index % 64)) & 1 == 1
+    }
+    
+    /// Adds two values, returning the wrapped result and whether it overflowed
+    pub fn overflowing_add(self, other: U256) -> (U256, bool) {
+        let mut result = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        (U256(result), carry)
+    }
+    
+    /// Subtracts two values, returning the wrapped result and whether it underflowed
+    pub fn overflowing_sub(self, other: U256) -> (U256, bool) {
+        let mut result = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+        (U256(result), borrow)
+    }
+    
+    /// Adds two values modulo 2^256
+    pub fn wrapping_add(self, other: U256) -> U256 {
+        self.overflowing_add(other).0
+    }
+    
+    /// Subtracts two values modulo 2^256
+    pub fn wrapping_sub(self, other: U256) -> U256 {
+        self.overflowing_sub(other).0
+    }
+    
+    /// Multiplies two values modulo 2^256
+    pub fn wrapping_mul(self, other: U256) -> U256 {
+        let wide = self.full_mul(other);
+        U256([wide[0], wide[1], wide[2], wide[3]])
+    }
+    
+    /// Negates the value in two's complement
+    pub fn wrapping_neg(self) -> U256 {
+        (!self).wrapping_add(U256::ONE)
+    }
+    
+    /// Multiplies two values into a 512-bit result (little-endian limbs)
+    fn full_mul(self, other: U256) -> [u64; 8] {
+        let mut result = [0u64; 8];
+        for i in 0..4 {
+            let mut carry = 0u128;
+            for j in 0..4 {
+                let current = result[i + j] as u128
+                    + (self.0[i] as u128) * (other.0[j] as u128)
+                    + carry;
+                result[i + j] = current as u64;
+                carry = current >> 64;
+            }
+            result[i + 4] = carry as u64;
+        }
+        result
+    }
+    
+    /// Divides two values, returning the quotient and remainder
+    ///
+    /// Division by zero yields zero for both, matching EVM semantics.
+    pub fn div_rem(self, divisor: U256) -> (U256, U256) {
+        if divisor.is_zero() {
+            return (U256::ZERO, U256::ZERO);
+        }
+        
+        if self < divisor {
+            return (U256::ZERO, self);
+        }
+        
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..self.bits() as usize).rev() {
+            let carry = remainder.bit(255);
+            remainder = remainder.shift_left(1);
+            if self.bit(i) {
+                remainder.0[0] |= 1;
+            }
+            
+            if carry || remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient.0[i / 64] |= 1 << (i % 64);
+            }
+        }
+        
+        (quotient, remainder)
+    }
+    
+    /// Reduces a little-endian multi-limb value modulo `modulus`
+    fn rem_wide(limbs: &[u64], modulus: U256) -> U256 {
+        if modulus.is_zero() {
+            return U256::ZERO;
+        }
+        
+        let mut remainder = U256::ZERO;
+        for i in (0..limbs.len() * 64).rev() {
+            let carry = remainder.bit(255);
+            remainder = remainder.shift_left(1);
+            if (limbs[i / 64] >> (i % 64)) & 1 == 1 {
+                remainder.0[0] |= 1;
+            }
+            
+            if carry || remainder >= modulus {
+                remainder = remainder.wrapping_sub(modulus);
+            }
+        }
+        
+        remainder
+    }
+    
+    /// Computes (self + other) % modulus without intermediate overflow
+    pub fn add_mod(self, other: U256, modulus: U256) -> U256 {
+        let (sum, carry) = self.overflowing_add(other);
+        let limbs = [sum.0[0], sum.0[1], sum.0[2], sum.0[3], carry as u64];
+        Self::rem_wide(&limbs, modulus)
+    }
+    
+    /// Computes (self * other) % modulus without intermediate overflow
+    pub fn mul_mod(self, other: U256, modulus: U256) -> U256 {
+        Self::rem_wide(&self.full_mul(other), modulus)
+    }
+    
+    /// Raises the value to the given power modulo 2^256
+    pub fn wrapping_pow(self, exponent: U256) -> U256 {
+        let mut result = U256::ONE;
+        let mut base = self;
+        for i in 0..exponent.bits() as usize {
+            if exponent.bit(i) {
+                result = result.wrapping_mul(base);
+            }
+            base = base.wrapping_mul(base);
+        }
+        result
+    }
+    
+    /// Shifts left by the given number of bits (zero if shift >= 256)
+    pub fn shift_left(self, shift: usize) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        
+        let limb_shift = shift / 64;
+        let bit_shift = shift % 64;
+        let mut result = [0u64; 4];
+        for (i, limb) in result.iter_mut().enumerate().skip(limb_shift) {
+            *limb = self.0[i - limb_shift] << bit_shift;
+            if bit_shift > 0 && i > limb_shift {
+                *limb |= self.0[i - limb_shift - 1] >> (64 - bit_shift);
+            }
+        }
+        U256(result)
+    }
+    
+    /// Logical right shift by the given number of bits (zero if shift >= 256)
+    pub fn shift_right(self, shift: usize) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        
+        let limb_shift = shift / 64;
+        let bit_shift = shift % 64;
+        let mut result = [0u64; 4];
+        for (i, limb) in result.iter_mut().enumerate().take(4 - limb_shift) {
+            *limb = self.0[i + limb_shift] >> bit_shift;
+            if bit_shift > 0 && i + limb_shift + 1 < 4 {
+                *limb |= self.0[i + limb_shift + 1] << (64 - bit_shift);
+            }
+        }
+        U256(result)
+    }
+    
+    /// Arithmetic right shift, preserving the two's complement sign
+    pub fn sar(self, shift: usize) -> U256 {
+        if !self.is_negative() {
+            return self.shift_right(shift);
+        }
+        
+        if shift >= 256 {
+            return U256::MAX;
+        }
+        
+        self.shift_right(shift) | !(U256::MAX.shift_right(shift))
+    }
+    
+    /// Absolute value of a two's complement number
+    fn abs(self) -> U256 {
+        if self.is_negative() {
+            self.wrapping_neg()
+        } else {
+            self
+        }
+    }
+    
+    /// Signed division, truncating towards zero (zero if divisor is zero)
+    pub fn signed_div(self, divisor: U256) -> U256 {
+        if divisor.is_zero() {
+            return U256::ZERO;
+        }
+        
+        let (quotient, _) = self.abs().div_rem(divisor.abs());
+        if self.is_negative() != divisor.is_negative() {
+            quotient.wrapping_neg()
+        } else {
+            quotient
+        }
+    }
+    
+    /// Signed remainder, taking the sign of the dividend (zero if divisor is zero)
+    pub fn signed_rem(self, divisor: U256) -> U256 {
+        if divisor.is_zero() {
+            return U256::ZERO;
+        }
+        
+        let (_, remainder) = self.abs().div_rem(divisor.abs());
+        if self.is_negative() {
+            remainder.wrapping_neg()
+        } else {
+            remainder
+        }
+    }
+    
+    /// Compares two values as two's complement signed integers
+    pub fn signed_cmp(&self, other: &U256) -> Ordering {
+        match (self.is_negative(), other.is_negative()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => self.cmp(other),
+        }
+    }
+    
+    /// Sign-extends the value from the given byte index (0 is the lowest byte)
+    pub fn sign_extend(self, byte_index: U256) -> U256 {
+        let byte_index = match byte_index.as_usize() {
+            Some(index) if index < 31 => index,
+            _ => return self,
+        };
+        
+        let sign_bit = byte_index * 8 + 7;
+        let mask = U256::MAX.shift_right(255 - sign_bit);
+        if self.bit(sign_bit) {
+            self | !mask
+        } else {
+            self & mask
+        }
+    }
+    
+    /// Returns the byte at the given big-endian index (zero if index >= 32)
+    pub fn byte(&self, index: U256) -> U256 {
+        match index.as_usize() {
+            Some(index) if index < 32 => U256::from_u64(self.to_be_bytes()[index] as u64),
+            _ => U256::ZERO,
+        }
+    }
+}
+
+impl BitAnd for U256 {
+    type Output = U256;
+    
+    fn bitand(self, other: U256) -> U256 {
+        U256([
+            self.0[0] & other.0[0],
+            self.0[1] & other.0[1],
+            self.0[2] & other.0[2],
+            self.0[3] & other.0[3],
+        ])
+    }
+}
+
+impl BitOr for U256 {
+    type Output = U256;
+    
+    fn bitor(self, other: U256) -> U256 {
+        U256([
+            self.0[0] | other.0[0],
+            self.0[1] | other.0[1],
+            self.0[2] | other.0[2],
+            self.0[3] | other.0[3],
+        ])
+    }
+}
+
+impl BitXor for U256 {
+    type Output = U256;
+    
+    fn bitxor(self, other: U256) -> U256 {
+        U256([
+            self.0[0] ^ other.0[0],
+            self.0[1] ^ other.0[1],
+            self.0[2] ^ other.0[2],
+            self.0[3] ^ other.0[3],
+        ])
+    }
+}
+
+impl Not for U256 {
+    type Output = U256;
+    
+    fn not(self) -> U256 {
+        U256([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        Self::from_u64(value)
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for i in (0..4).rev() {
+            match self.0[i].cmp(&other.0[i]) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.to_be_bytes()))
+    }
+}
\ No newline at end of file
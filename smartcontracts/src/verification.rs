@@ -0,0 +1,154 @@
+//! Source verification
+//!
+//! A contract is verified by compiling its source with the settings it was
+//! originally compiled with and checking that one of the resulting contracts
+//! has exactly the deployed runtime code. Solc appends a CBOR-encoded
+//! metadata hash to the code, which changes with things like comments and
+//! file names, so that suffix is left out of the comparison.
+//!
+//! Verified sources are kept by the node rather than the chain: they're
+//! metadata for explorers and don't affect execution.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::Hash;
+
+use crate::abi;
+use crate::solidity::{CompileOptions, CompilerOutput};
+use crate::{EventABI, FunctionABI};
+
+/// How compiled runtime code differs from the deployed code
+///
+/// Offsets and lengths leave out the metadata suffix of both codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BytecodeMismatch {
+    /// The codes have different lengths
+    Length { deployed: usize, compiled: usize },
+    
+    /// The codes have the same length but differ, first at `offset`
+    Content { offset: usize },
+}
+
+impl fmt::Display for BytecodeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Length { deployed, compiled } => {
+                write!(f, "deployed code is {} bytes, compiled code is {} bytes", deployed, compiled)
+            }
+            Self::Content { offset } => write!(f, "codes first differ at byte {}", offset),
+        }
+    }
+}
+
+/// A contract from the source that didn't match the deployed code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractMismatch {
+    /// Name of the contract in the source
+    pub contract_name: String,
+    
+    /// How its runtime code differs
+    pub mismatch: BytecodeMismatch,
+}
+
+/// Outcome of verifying a contract's source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerificationResult {
+    /// The named contract compiles to the deployed code; the source is now stored
+    Verified { contract_name: String },
+    
+    /// No contract in the source compiles to the deployed code
+    Mismatch(Vec<ContractMismatch>),
+}
+
+/// Source code verified against a deployed contract
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedSource {
+    /// Name of the contract in the source
+    pub contract_name: String,
+    
+    /// Solidity source
+    pub source: String,
+    
+    /// Settings the source was compiled with
+    pub compile_options: CompileOptions,
+    
+    /// Functions, as produced by the compiler
+    pub abi: Vec<FunctionABI>,
+    
+    /// Events, as produced by the compiler
+    pub events: Vec<EventABI>,
+    
+    /// Keccak-256 hash of the deployed code the source was verified against
+    pub code_hash: Hash,
+}
+
+/// Checks compiled contracts against deployed runtime code
+///
+/// Returns the source as verified for the first contract whose runtime code
+/// matches, or why each deployable contract didn't.
+pub fn verify(
+    deployed_code: &[u8],
+    source: &str,
+    compile_options: &CompileOptions,
+    output: &CompilerOutput,
+) -> std::result::Result<VerifiedSource, Vec<ContractMismatch>> {
+    let mut mismatches = Vec::new();
+    
+    for contract in output.contracts.iter().filter(|contract| !contract.deployed_bytecode.is_empty()) {
+        match compare_code(deployed_code, &contract.deployed_bytecode) {
+            None => {
+                return Ok(VerifiedSource {
+                    contract_name: contract.name.clone(),
+                    source: source.to_string(),
+                    compile_options: compile_options.clone(),
+                    abi: contract.abi.clone(),
+                    events: contract.events.clone(),
+                    code_hash: abi::keccak256(deployed_code),
+                });
+            }
+            Some(mismatch) => mismatches.push(ContractMismatch {
+                contract_name: contract.name.clone(),
+                mismatch,
+            }),
+        }
+    }
+    
+    Err(mismatches)
+}
+
+/// Compares runtime codes, ignoring their metadata suffixes
+pub fn compare_code(deployed: &[u8], compiled: &[u8]) -> Option<BytecodeMismatch> {
+    let deployed = strip_metadata(deployed);
+    let compiled = strip_metadata(compiled);
+    
+    if deployed.len() != compiled.len() {
+        return Some(BytecodeMismatch::Length {
+            deployed: deployed.len(),
+            compiled: compiled.len(),
+        });
+    }
+    
+    deployed
+        .iter()
+        .zip(compiled)
+        .position(|(a, b)| a != b)
+        .map(|offset| BytecodeMismatch::Content { offset })
+}
+
+/// Removes the metadata solc appends to runtime code, if there is any
+///
+/// The metadata is a CBOR map followed by its length as a big-endian u16.
+pub fn strip_metadata(code: &[u8]) -> &[u8] {
+    if code.len() < 2 {
+        return code;
+    }
+    
+    let length = u16::from_be_bytes([code[code.len() - 2], code[code.len() - 1]]) as usize;
+    match code.len().checked_sub(length + 2) {
+        // A CBOR map has major type 5
+        Some(start) if length > 0 && code[start] >> 5 == 5 => &code[..start],
+        _ => code,
+    }
+}
\ No newline at end of file
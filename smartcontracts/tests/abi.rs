@@ -0,0 +1,198 @@
+//! Checks ABI encoding against the examples of the Solidity ABI specification
+//!
+//! Run with `cargo test -p smartcontracts --test abi`. Encodes the calls the
+//! specification works through, covering static types, dynamic types,
+//! fixed and nested dynamic arrays, and tuples both static and dynamic,
+//! checks the selectors and the words word for word, decodes the words back
+//! to the same values, and checks malformed data is refused.
+
+use smartcontracts::abi::{self, Value};
+use smartcontracts::u256::U256;
+use smartcontracts::ABIParameter;
+
+/// `baz(uint32,bool)` called with 69 and true
+const BAZ: [&str; 2] = [
+    "0000000000000000000000000000000000000000000000000000000000000045",
+    "0000000000000000000000000000000000000000000000000000000000000001",
+];
+
+/// `bar(bytes3[2])` called with "abc" and "def"
+const BAR: [&str; 2] = [
+    "6162630000000000000000000000000000000000000000000000000000000000",
+    "6465660000000000000000000000000000000000000000000000000000000000",
+];
+
+/// `sam(bytes,bool,uint256[])` called with "dave", true and [1, 2, 3]
+const SAM: [&str; 9] = [
+    "0000000000000000000000000000000000000000000000000000000000000060",
+    "0000000000000000000000000000000000000000000000000000000000000001",
+    "00000000000000000000000000000000000000000000000000000000000000a0",
+    "0000000000000000000000000000000000000000000000000000000000000004",
+    "6461766500000000000000000000000000000000000000000000000000000000",
+    "0000000000000000000000000000000000000000000000000000000000000003",
+    "0000000000000000000000000000000000000000000000000000000000000001",
+    "0000000000000000000000000000000000000000000000000000000000000002",
+    "0000000000000000000000000000000000000000000000000000000000000003",
+];
+
+/// `f(uint256,uint32[],bytes10,bytes)` called with 0x123, [0x456, 0x789], "1234567890" and "Hello, world!"
+const F: [&str; 9] = [
+    "0000000000000000000000000000000000000000000000000000000000000123",
+    "0000000000000000000000000000000000000000000000000000000000000080",
+    "3132333435363738393000000000000000000000000000000000000000000000",
+    "00000000000000000000000000000000000000000000000000000000000000e0",
+    "0000000000000000000000000000000000000000000000000000000000000002",
+    "0000000000000000000000000000000000000000000000000000000000000456",
+    "0000000000000000000000000000000000000000000000000000000000000789",
+    "000000000000000000000000000000000000000000000000000000000000000d",
+    "48656c6c6f2c20776f726c642100000000000000000000000000000000000000",
+];
+
+/// `g(uint256[][],string[])` called with [[1, 2], [3]] and ["one", "two", "three"]
+const G: [&str; 20] = [
+    "0000000000000000000000000000000000000000000000000000000000000040",
+    "0000000000000000000000000000000000000000000000000000000000000140",
+    "0000000000000000000000000000000000000000000000000000000000000002",
+    "0000000000000000000000000000000000000000000000000000000000000040",
+    "00000000000000000000000000000000000000000000000000000000000000a0",
+    "0000000000000000000000000000000000000000000000000000000000000002",
+    "0000000000000000000000000000000000000000000000000000000000000001",
+    "0000000000000000000000000000000000000000000000000000000000000002",
+    "0000000000000000000000000000000000000000000000000000000000000001",
+    "0000000000000000000000000000000000000000000000000000000000000003",
+    "0000000000000000000000000000000000000000000000000000000000000003",
+    "0000000000000000000000000000000000000000000000000000000000000060",
+    "00000000000000000000000000000000000000000000000000000000000000a0",
+    "00000000000000000000000000000000000000000000000000000000000000e0",
+    "0000000000000000000000000000000000000000000000000000000000000003",
+    "6f6e650000000000000000000000000000000000000000000000000000000000",
+    "0000000000000000000000000000000000000000000000000000000000000003",
+    "74776f0000000000000000000000000000000000000000000000000000000000",
+    "0000000000000000000000000000000000000000000000000000000000000005",
+    "7468726565000000000000000000000000000000000000000000000000000000",
+];
+
+/// `(uint256,(bool,string))` holding 1 and (true, "hi"), whose dynamic tuple goes in the tail
+/// 
+/// Offsets inside the tuple count from its own start.
+const NESTED: [&str; 6] = [
+    "0000000000000000000000000000000000000000000000000000000000000001",
+    "0000000000000000000000000000000000000000000000000000000000000040",
+    "0000000000000000000000000000000000000000000000000000000000000001",
+    "0000000000000000000000000000000000000000000000000000000000000040",
+    "0000000000000000000000000000000000000000000000000000000000000002",
+    "6869000000000000000000000000000000000000000000000000000000000000",
+];
+
+/// `(int8,(uint16,address))` holding -1 and (0x1234, 0x1111…11), encoded in place
+const STATIC_TUPLE: [&str; 3] = [
+    "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+    "0000000000000000000000000000000000000000000000000000000000001234",
+    "0000000000000000000000001111111111111111111111111111111111111111",
+];
+
+/// Builds a parameter list of the given types
+fn params(types: &[&str]) -> Vec<ABIParameter> {
+    types
+        .iter()
+        .enumerate()
+        .map(|(i, param_type)| ABIParameter { name: format!("arg{}", i), param_type: param_type.to_string() })
+        .collect()
+}
+
+/// Joins hex words into bytes
+fn words(words: &[&str]) -> Vec<u8> {
+    hex::decode(words.concat()).unwrap()
+}
+
+fn uint(value: u64) -> Value {
+    Value::Uint(U256::from_u64(value))
+}
+
+fn uints(values: &[u64]) -> Value {
+    Value::Array(values.iter().copied().map(uint).collect())
+}
+
+fn string(value: &str) -> Value {
+    Value::String(value.to_string())
+}
+
+/// Checks values encode to the expected words and decode back
+fn check(types: &[&str], values: &[Value], expected: &[&str]) {
+    let params = params(types);
+    let encoded = abi::encode(&params, values).unwrap();
+    assert_eq!(hex::encode(&encoded), expected.concat(), "encoding of {:?}", types);
+    assert_eq!(abi::decode(&params, &encoded).unwrap(), values, "decoding of {:?}", types);
+}
+
+/// Checks the calls worked through by the specification, selectors included
+#[test]
+fn check_specification() {
+    let calls: [(&str, &[&str], [u8; 4]); 5] = [
+        ("baz", &["uint32", "bool"], [0xcd, 0xcd, 0x77, 0xc0]),
+        ("bar", &["bytes3[2]"], [0xfc, 0xe3, 0x53, 0xf6]),
+        ("sam", &["bytes", "bool", "uint256[]"], [0xa5, 0x64, 0x3b, 0xf2]),
+        ("f", &["uint256", "uint32[]", "bytes10", "bytes"], [0x8b, 0xe6, 0x52, 0x46]),
+        ("g", &["uint256[][]", "string[]"], [0x22, 0x89, 0xb1, 0x8c]),
+    ];
+    for (name, types, selector) in calls {
+        assert_eq!(abi::function_selector(name, &params(types)).unwrap(), selector, "selector of {}", name);
+    }
+    
+    check(&["uint32", "bool"], &[uint(69), Value::Bool(true)], &BAZ);
+    check(
+        &["bytes3[2]"],
+        &[Value::Array(vec![Value::FixedBytes(b"abc".to_vec()), Value::FixedBytes(b"def".to_vec())])],
+        &BAR,
+    );
+    check(
+        &["bytes", "bool", "uint256[]"],
+        &[Value::Bytes(b"dave".to_vec()), Value::Bool(true), uints(&[1, 2, 3])],
+        &SAM,
+    );
+    check(
+        &["uint256", "uint32[]", "bytes10", "bytes"],
+        &[uint(0x123), uints(&[0x456, 0x789]), Value::FixedBytes(b"1234567890".to_vec()), Value::Bytes(b"Hello, world!".to_vec())],
+        &F,
+    );
+    check(
+        &["uint256[][]", "string[]"],
+        &[
+            Value::Array(vec![uints(&[1, 2]), uints(&[3])]),
+            Value::Array(vec![string("one"), string("two"), string("three")]),
+        ],
+        &G,
+    );
+}
+
+/// Checks tuples are encoded in place when static and in the tail when dynamic
+#[test]
+fn check_tuples() {
+    check(
+        &["uint256", "(bool,string)"],
+        &[uint(1), Value::Tuple(vec![Value::Bool(true), string("hi")])],
+        &NESTED,
+    );
+    check(
+        &["int8", "(uint16,address)"],
+        &[Value::Int(U256::MAX), Value::Tuple(vec![uint(0x1234), Value::Address([0x11; 20])])],
+        &STATIC_TUPLE,
+    );
+}
+
+/// Checks values that don't fit their type and truncated or misdirected data are refused
+#[test]
+fn check_malformed() {
+    assert!(abi::encode(&params(&["uint8"]), &[uint(256)]).is_err());
+    assert!(abi::encode(&params(&["int8"]), &[Value::Int(U256::from_u64(128))]).is_err());
+    assert!(abi::encode(&params(&["bytes3"]), &[Value::FixedBytes(b"abcd".to_vec())]).is_err());
+    assert!(abi::encode(&params(&["bytes3[2]"]), &[Value::Array(vec![Value::FixedBytes(b"abc".to_vec())])]).is_err());
+    
+    let oversized = words(&["0000000000000000000000000000000000000000000000000000000000000100"]);
+    assert!(abi::decode(&params(&["uint8"]), &oversized).is_err(), "an oversized uint8 decodes");
+    let sam = words(&SAM);
+    assert!(abi::decode(&params(&["bytes", "bool", "uint256[]"]), &sam[..sam.len() - 32]).is_err(), "a truncated array decodes");
+    let mut misdirected = sam.clone();
+    misdirected[31] = 0xff;
+    assert!(abi::decode(&params(&["bytes", "bool", "uint256[]"]), &misdirected).is_err(), "an offset past the end decodes");
+}
\ No newline at end of file
@@ -0,0 +1,182 @@
+//! Checks contracts calling each other with CALL, DELEGATECALL and STATICCALL
+//!
+//! Run with `cargo test -p smartcontracts --test calls`. Has a contract
+//! call another that records its caller, checking where the callee's
+//! writes land, what it's told its caller and value are and what it
+//! returns, that a static call can't write, that a reverting callee only
+//! undoes its own writes, and that calls nest no deeper than the limit.
+
+use std::collections::HashMap;
+
+use smartcontracts::evm::{self, opcode, CallKind, ExecutionContext, ExecutionResult, ExecutionStatus, Host, StorageWrite};
+use smartcontracts::u256::U256;
+use smartcontracts::GasConfig;
+
+/// Gas every execution starts with
+const GAS_LIMIT: u64 = 1_000_000;
+
+/// Gas for the recursion check, enough to nest past the limit despite keeping 1/64 at each level
+const DEEP_GAS_LIMIT: u64 = 1_000_000_000_000_000;
+
+/// Deepest a call can nest below the outermost frame
+const CALL_DEPTH_LIMIT: u64 = 1024;
+
+/// Address of the calling contract
+const CALLER: [u8; 20] = [0xca; 20];
+
+/// Address of the called contract
+const CALLEE: [u8; 20] = [0xce; 20];
+
+/// Account sending the transaction
+const SENDER: [u8; 20] = [0x5e; 20];
+
+/// Contract storing its caller in slot 0 and returning its call value
+const RECORD_CALLER: [u8; 13] = [
+    opcode::CALLER, opcode::PUSH1, 0x00, opcode::SSTORE,
+    opcode::CALLVALUE, opcode::PUSH1, 0x00, opcode::MSTORE,
+    opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::RETURN,
+];
+
+/// Contract writing slot 0 and then reverting with the byte 0xee
+const WRITE_AND_REVERT: [u8; 15] = [
+    opcode::PUSH1, 0x01, opcode::PUSH1, 0x00, opcode::SSTORE,
+    opcode::PUSH1, 0xee, opcode::PUSH1, 0x00, opcode::MSTORE8,
+    opcode::PUSH1, 0x01, opcode::PUSH1, 0x00, opcode::REVERT,
+];
+
+/// Contract calling itself, returning one more than the frames below it
+///
+/// The deepest frame's call fails, leaving the return word at 0.
+const RECURSE: &[u8] = &[
+    opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00,
+    opcode::ADDRESS, opcode::GAS, opcode::CALL, opcode::POP,
+    opcode::PUSH1, 0x00, opcode::MLOAD, opcode::PUSH1, 0x01, opcode::ADD, opcode::PUSH1, 0x00, opcode::MSTORE,
+    opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::RETURN,
+];
+
+/// Accounts and contracts kept in memory
+#[derive(Default)]
+struct TestHost {
+    code: HashMap<[u8; 20], Vec<u8>>,
+    balances: HashMap<[u8; 20], u64>,
+}
+
+impl Host for TestHost {
+    fn code(&self, address: &[u8; 20]) -> Option<&[u8]> {
+        self.code.get(address).map(Vec::as_slice)
+    }
+    
+    fn storage(&self, _address: &[u8; 20], _key: &[u8]) -> Option<&[u8]> {
+        None
+    }
+    
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.balances.get(address).copied().unwrap_or(0)
+    }
+}
+
+/// Assembles code making a call of a kind to `CALLEE`, sending `value` if a CALL
+///
+/// It writes 7 to its own slot 1 first, then returns a word of the
+/// callee's return data followed by a word that's 1 if the call succeeded.
+fn caller_code(kind: CallKind, value: u8) -> Vec<u8> {
+    let mut code = vec![opcode::PUSH1, 0x07, opcode::PUSH1, 0x01, opcode::SSTORE];
+    code.extend_from_slice(&[opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00]);
+    if kind == CallKind::Call {
+        code.extend_from_slice(&[opcode::PUSH1, value]);
+    }
+    code.push(opcode::PUSH1 + 19);
+    code.extend_from_slice(&CALLEE);
+    code.push(opcode::GAS);
+    code.push(match kind {
+        CallKind::Call => opcode::CALL,
+        CallKind::DelegateCall => opcode::DELEGATECALL,
+        CallKind::StaticCall => opcode::STATICCALL,
+    });
+    code.extend_from_slice(&[opcode::PUSH1, 0x20, opcode::MSTORE, opcode::PUSH1, 0x40, opcode::PUSH1, 0x00, opcode::RETURN]);
+    code
+}
+
+/// Runs code as `CALLER`, called by the sender, with `callee` deployed at `CALLEE`
+fn run(code: &[u8], callee: &[u8], gas_limit: u64) -> ExecutionResult {
+    let mut host = TestHost::default();
+    host.code.insert(CALLEE, callee.to_vec());
+    host.code.insert(CALLER, code.to_vec());
+    host.balances.insert(CALLER, 100);
+    let context = ExecutionContext { address: CALLER, caller: SENDER, origin: SENDER, ..ExecutionContext::default() };
+    let result = evm::execute(code, &[], &host, &context, gas_limit, &GasConfig::default()).unwrap();
+    assert_eq!(result.status, ExecutionStatus::Success);
+    result
+}
+
+/// Splits what `caller_code` returns into the callee's word and whether the call succeeded
+fn outcome(result: &ExecutionResult) -> (U256, bool) {
+    let succeeded = U256::from_be_slice(&result.return_data[32..64]);
+    (U256::from_be_slice(&result.return_data[..32]), succeeded == U256::ONE)
+}
+
+/// Gets the value written to a slot, as a number, by address and slot
+fn written(writes: &[StorageWrite], address: [u8; 20], slot: u64) -> Option<U256> {
+    let key = U256::from(slot).to_be_bytes();
+    writes
+        .iter()
+        .find(|write| write.address == address && write.key == key)
+        .map(|write| write.value.as_deref().map_or(U256::ZERO, U256::from_be_slice))
+}
+
+/// Checks CALL runs the callee against its own storage, as called by the caller, with the value sent
+#[test]
+fn check_call() {
+    let result = run(&caller_code(CallKind::Call, 5), &RECORD_CALLER, GAS_LIMIT);
+    assert_eq!(outcome(&result), (U256::from(5u64), true));
+    assert_eq!(written(&result.changes.storage, CALLEE, 0), Some(U256::from_be_slice(&CALLER)));
+    assert_eq!(written(&result.changes.storage, CALLER, 1), Some(U256::from(7u64)));
+    assert_eq!(written(&result.changes.storage, CALLER, 0), None);
+    assert_eq!(result.changes.transfers, vec![(CALLER, CALLEE, 5)]);
+    
+    // More value than the caller holds fails the call without running the callee
+    let result = run(&caller_code(CallKind::Call, 101), &RECORD_CALLER, GAS_LIMIT);
+    assert_eq!(outcome(&result), (U256::ZERO, false));
+    assert!(result.changes.transfers.is_empty());
+    assert_eq!(written(&result.changes.storage, CALLEE, 0), None);
+}
+
+/// Checks DELEGATECALL runs the callee's code in the caller's storage, with the caller's own caller and value
+#[test]
+fn check_delegatecall() {
+    let result = run(&caller_code(CallKind::DelegateCall, 0), &RECORD_CALLER, GAS_LIMIT);
+    assert_eq!(outcome(&result), (U256::ZERO, true));
+    assert_eq!(written(&result.changes.storage, CALLER, 0), Some(U256::from_be_slice(&SENDER)));
+    assert_eq!(written(&result.changes.storage, CALLEE, 0), None, "the callee's own storage is untouched");
+}
+
+/// Checks STATICCALL fails a callee that writes, keeping the caller's writes, and lets one that only reads return
+#[test]
+fn check_staticcall() {
+    let result = run(&caller_code(CallKind::StaticCall, 0), &RECORD_CALLER, GAS_LIMIT);
+    assert_eq!(outcome(&result), (U256::ZERO, false));
+    assert_eq!(written(&result.changes.storage, CALLEE, 0), None);
+    assert_eq!(written(&result.changes.storage, CALLER, 1), Some(U256::from(7u64)));
+    
+    let read_only = &RECORD_CALLER[4..];
+    let result = run(&caller_code(CallKind::StaticCall, 0), read_only, GAS_LIMIT);
+    assert_eq!(outcome(&result), (U256::ZERO, true));
+}
+
+/// Checks a reverting callee undoes only its own writes and hands the caller its revert data
+#[test]
+fn check_reverting_callee() {
+    let result = run(&caller_code(CallKind::Call, 0), &WRITE_AND_REVERT, GAS_LIMIT);
+    let (word, succeeded) = outcome(&result);
+    assert!(!succeeded);
+    assert_eq!(word.to_be_bytes()[0], 0xee, "the revert data is copied to the caller");
+    assert_eq!(written(&result.changes.storage, CALLEE, 0), None);
+    assert_eq!(written(&result.changes.storage, CALLER, 1), Some(U256::from(7u64)));
+}
+
+/// Checks calls nest no deeper than the limit, the call past it failing while the frames above carry on
+#[test]
+fn check_depth_limit() {
+    let result = run(RECURSE, &[], DEEP_GAS_LIMIT);
+    assert_eq!(U256::from_be_slice(&result.return_data), U256::from(CALL_DEPTH_LIMIT + 1));
+}
\ No newline at end of file
@@ -0,0 +1,195 @@
+//! Checks the indexes of contracts by creator and by block
+//!
+//! Run with `cargo test -p smartcontracts --test deployments`. Has two
+//! creators deploy contracts across blocks and pages through
+//! `get_contracts_by_creator`, `get_contracts_in_block` and
+//! `list_contracts`, then checks a destroyed contract leaves the indexes
+//! and rolled back blocks take their changes to them along.
+
+use ctb_core::block::Block;
+use ctb_core::state::{BlockUndo, State};
+use ctb_core::transaction::Transaction;
+use ctb_core::units::GENX;
+use ctb_core::BlockHash;
+
+use smartcontracts::evm::opcode;
+use smartcontracts::inspect::{DeploymentCursor, DeploymentPage};
+use smartcontracts::{ContractEngine, DeployPayload, GasConfig};
+
+/// Gas limit and price of every transaction
+const GAS_LIMIT: u64 = 1_000_000;
+const GAS_PRICE: u64 = 1;
+
+/// Accounts deploying contracts
+const ALICE: &str = "GENX_ALICE";
+const BOB: &str = "GENX_BOB";
+
+/// Contract destroying itself when called with any data, and otherwise doing nothing
+const DESTRUCTIBLE: &[u8] = &[
+    opcode::CALLDATASIZE, opcode::PUSH1, 0x05, opcode::JUMPI, opcode::STOP,
+    opcode::JUMPDEST, opcode::PUSH1, 0x00, opcode::SELFDESTRUCT,
+];
+
+/// Deployment heights and addresses of contracts
+type Deployments = Vec<(u64, String)>;
+
+/// State and engine, with blocks applied straight to the state
+struct Chain {
+    state: State,
+    engine: ContractEngine,
+    height: u64,
+    nonces: [u64; 2],
+}
+
+impl Chain {
+    fn new() -> Self {
+        let mut state = State::new();
+        for creator in [ALICE, BOB] {
+            state.apply_transaction(&Transaction::new_coinbase(creator.to_string(), 1_000 * GENX).unwrap()).unwrap();
+        }
+        Self { state, engine: ContractEngine::new(GasConfig::default()), height: 0, nonces: [0; 2] }
+    }
+    
+    /// Gets the next nonce of a sender
+    fn nonce(&mut self, sender: &str) -> u64 {
+        let nonce = &mut self.nonces[usize::from(sender == BOB)];
+        *nonce += 1;
+        *nonce - 1
+    }
+    
+    /// Builds a deployment of `DESTRUCTIBLE` by a creator
+    fn deploy(&mut self, creator: &str) -> Transaction {
+        let payload = DeployPayload { init_code: init_code(DESTRUCTIBLE), abi: Vec::new(), events: Vec::new(), constructor_args: Vec::new() };
+        let nonce = self.nonce(creator);
+        Transaction::new_contract_deploy(creator.to_string(), 0, payload.to_bytes().unwrap(), GAS_LIMIT, GAS_PRICE)
+            .and_then(|tx| tx.with_nonce(nonce))
+            .unwrap()
+    }
+    
+    /// Builds a call destroying a contract
+    fn destroy(&mut self, sender: &str, contract: &str) -> Transaction {
+        let nonce = self.nonce(sender);
+        Transaction::new_contract_call(sender.to_string(), contract.to_string(), 0, vec![1], GAS_LIMIT, GAS_PRICE)
+            .and_then(|tx| tx.with_nonce(nonce))
+            .unwrap()
+    }
+    
+    /// Applies a block the way the chain does, returning what undoes it
+    fn apply(&mut self, txs: Vec<Transaction>) -> BlockUndo {
+        self.height += 1;
+        let block = Block::new(self.height, BlockHash::default(), txs, "GENX_VALIDATOR".to_string(), GAS_PRICE).unwrap();
+        self.state.checkpoint();
+        let receipts = self.state.apply_block_with_executor(&block, Some(&mut self.engine)).unwrap();
+        assert!(receipts.iter().all(|receipt| receipt.success), "{:?}", receipts);
+        self.state.commit_block()
+    }
+    
+    /// Rolls back the last block
+    fn rollback(&mut self, undo: BlockUndo) {
+        self.state.rollback_block(undo);
+        self.height -= 1;
+    }
+    
+    /// Gets every page of a creator's contracts, `limit` at a time
+    fn by_creator(&self, creator: &str, limit: usize) -> Vec<Deployments> {
+        pages(|start| self.engine.get_contracts_by_creator(creator, start, limit, &self.state))
+    }
+    
+    /// Gets every page of a block's contracts, `limit` at a time
+    fn in_block(&self, height: u64, limit: usize) -> Vec<Deployments> {
+        pages(|start| self.engine.get_contracts_in_block(height, start.map(|cursor| cursor.address.as_str()), limit, &self.state))
+    }
+}
+
+/// Follows a paginated listing to its end
+fn pages(list: impl Fn(Option<&DeploymentCursor>) -> DeploymentPage) -> Vec<Deployments> {
+    let mut pages = Vec::new();
+    let mut start = None;
+    loop {
+        let page = list(start.as_ref());
+        pages.push(entries(&page));
+        match page.next {
+            Some(next) => start = Some(next),
+            None => return pages,
+        }
+    }
+}
+
+/// Gets the deployment heights and addresses of a page
+fn entries(page: &DeploymentPage) -> Deployments {
+    page.contracts.iter().map(|contract| (contract.deployed_at, contract.address.clone())).collect()
+}
+
+/// Builds init code returning `runtime` as the contract's code
+fn init_code(runtime: &[u8]) -> Vec<u8> {
+    let mut code = vec![
+        opcode::PUSH1, runtime.len() as u8, opcode::DUP1, opcode::PUSH1, 11, opcode::PUSH1, 0x00, opcode::CODECOPY,
+        opcode::PUSH1, 0x00, opcode::RETURN,
+    ];
+    code.extend_from_slice(runtime);
+    code
+}
+
+/// Deploys alice's first two contracts and bob's first in block 1, bob's second in block 2 and alice's third in block 3
+///
+/// Returns the chain, what undoes block 3, and the contracts' heights and addresses: alice's, then bob's.
+fn deployed() -> (Chain, BlockUndo, Deployments, Deployments) {
+    let mut chain = Chain::new();
+    let txs = vec![chain.deploy(ALICE), chain.deploy(ALICE), chain.deploy(BOB)];
+    let mut alice: Vec<_> = txs[..2].iter().map(|tx| (1, tx.contract_address())).collect();
+    let mut bob = vec![(1, txs[2].contract_address())];
+    chain.apply(txs);
+    
+    let tx = chain.deploy(BOB);
+    bob.push((2, tx.contract_address()));
+    chain.apply(vec![tx]);
+    
+    let tx = chain.deploy(ALICE);
+    alice.push((3, tx.contract_address()));
+    let undo = chain.apply(vec![tx]);
+    
+    alice.sort();
+    (chain, undo, alice, bob)
+}
+
+/// Checks both indexes, and the whole listing, page through every deployment in order
+#[test]
+fn check_indexes() {
+    let (chain, _, alice, bob) = deployed();
+    assert_eq!(chain.by_creator(ALICE, 2), vec![alice[..2].to_vec(), alice[2..].to_vec()]);
+    assert_eq!(chain.by_creator(BOB, 5), vec![bob.clone()]);
+    assert_eq!(chain.by_creator("GENX_CAROL", 5), vec![Vec::new()]);
+    
+    let mut block_1 = [alice[0].clone(), alice[1].clone(), bob[0].clone()];
+    block_1.sort();
+    assert_eq!(chain.in_block(1, 2), vec![block_1[..2].to_vec(), block_1[2..].to_vec()]);
+    assert_eq!(chain.in_block(2, 2), vec![bob[1..].to_vec()]);
+    assert_eq!(chain.in_block(3, 1), vec![alice[2..].to_vec()]);
+    assert_eq!(chain.in_block(4, 1), vec![Vec::new()]);
+    
+    let mut all: Vec<_> = alice.iter().chain(&bob).cloned().collect();
+    all.sort();
+    let listed: Vec<_> = pages(|start| chain.engine.list_contracts(start, 2, &chain.state)).concat();
+    assert_eq!(listed, all);
+}
+
+/// Checks a destroyed contract leaves both indexes, and rolling back blocks undoes their changes to them
+#[test]
+fn check_destroyed_and_rolled_back() {
+    let (mut chain, undo_3, alice, bob) = deployed();
+    let destroy = chain.destroy(BOB, &alice[0].1);
+    let undo_4 = chain.apply(vec![destroy]);
+    assert_eq!(chain.by_creator(ALICE, 5), vec![alice[1..].to_vec()]);
+    let mut block_1 = vec![alice[1].clone(), bob[0].clone()];
+    block_1.sort();
+    assert_eq!(chain.in_block(1, 5), [block_1]);
+    
+    chain.rollback(undo_4);
+    assert_eq!(chain.by_creator(ALICE, 5), vec![alice.clone()]);
+    assert_eq!(chain.in_block(1, 5).concat().len(), 3);
+    
+    chain.rollback(undo_3);
+    assert_eq!(chain.by_creator(ALICE, 5), vec![alice[..2].to_vec()]);
+    assert_eq!(chain.in_block(3, 5), vec![Vec::new()]);
+    assert_eq!(chain.by_creator(BOB, 5), vec![bob]);
+}
\ No newline at end of file
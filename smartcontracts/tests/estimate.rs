@@ -0,0 +1,166 @@
+//! Checks gas estimates against the gas mined transactions use
+//!
+//! Run with `cargo test -p smartcontracts --test estimate`. Estimates calls
+//! to a contract writing and then clearing many storage slots, mines them
+//! with the estimate as their gas limit and compares: the estimate has to
+//! cover what the call needs, including gas refunded only at the end, be
+//! the least that does when there's no margin, and add the margin
+//! otherwise. Reverts give their reason and plain transfers only need
+//! their intrinsic gas.
+
+use ctb_core::block::Block;
+use ctb_core::receipt::Receipt;
+use ctb_core::state::State;
+use ctb_core::transaction::{to_evm_address, Transaction};
+use ctb_core::units::GENX;
+use ctb_core::{Address, Amount, BlockHash};
+
+use smartcontracts::abi::{self, Value};
+use smartcontracts::evm::{contract_address, opcode};
+use smartcontracts::token::{self, TokenParams, TOKEN_FACTORY_ADDRESS};
+use smartcontracts::u256::U256;
+use smartcontracts::{ContractEngine, ContractError, DeployPayload, GasConfig, DEFAULT_GAS_ESTIMATE_MARGIN};
+
+/// Most gas an estimate may run with
+const GAS_CAP: u64 = 10_000_000;
+
+/// Gas price of every transaction
+const GAS_PRICE: u64 = 1;
+
+/// Slots the storage contract writes on every call
+const SLOTS: u8 = 20;
+
+/// Account sending the transactions
+const SENDER: &str = "GENX_ALICE";
+
+/// State, engine and chain height, with blocks applied straight to the state
+struct Chain {
+    state: State,
+    engine: ContractEngine,
+    height: u64,
+    nonce: u64,
+}
+
+impl Chain {
+    fn new(margin: u64) -> Self {
+        let mut state = State::new();
+        state.apply_transaction(&Transaction::new_coinbase(SENDER.to_string(), 1_000 * GENX).unwrap()).unwrap();
+        let mut engine = ContractEngine::new(GasConfig::default());
+        engine.set_gas_estimate_margin(margin);
+        Self { state, engine, height: 0, nonce: 0 }
+    }
+    
+    /// Builds a call from the sender with the next nonce and the gas limit given
+    fn call(&self, contract: &str, data: Vec<u8>, gas_limit: u64) -> Transaction {
+        Transaction::new_contract_call(SENDER.to_string(), contract.to_string(), 0, data, gas_limit, GAS_PRICE)
+            .and_then(|tx| tx.with_nonce(self.nonce))
+            .unwrap()
+    }
+    
+    /// Estimates a transaction's gas against the current state
+    fn estimate(&mut self, tx: &Transaction) -> Result<u64, ContractError> {
+        self.engine.estimate_gas(tx, GAS_CAP, &mut self.state)
+    }
+    
+    /// Mines a block holding one transaction, returning its receipt
+    fn mine(&mut self, tx: Transaction) -> Receipt {
+        self.height += 1;
+        self.nonce += 1;
+        let block = Block::new(self.height, BlockHash::default(), vec![tx], "GENX_VALIDATOR".to_string(), GAS_PRICE).unwrap();
+        self.state.apply_block_with_executor(&block, Some(&mut self.engine)).unwrap().remove(0)
+    }
+    
+    /// Deploys the storage contract, returning its address
+    fn deploy(&mut self) -> String {
+        let payload = DeployPayload { init_code: init_code(&storage_code()), abi: Vec::new(), events: Vec::new(), constructor_args: Vec::new() };
+        let tx = Transaction::new_contract_deploy(SENDER.to_string(), 0, payload.to_bytes().unwrap(), GAS_CAP, GAS_PRICE)
+            .and_then(|tx| tx.with_nonce(self.nonce))
+            .unwrap();
+        let address = tx.contract_address();
+        assert!(self.mine(tx).success);
+        address
+    }
+}
+
+/// Assembles a contract storing the call data's first word in slots 0 to `SLOTS`
+///
+/// A zero word clears the slots, which is refunded.
+fn storage_code() -> Vec<u8> {
+    (0..SLOTS).flat_map(|slot| [opcode::PUSH1, 0x00, opcode::CALLDATALOAD, opcode::PUSH1, slot, opcode::SSTORE]).collect()
+}
+
+/// Builds init code returning `runtime` as the contract's code
+fn init_code(runtime: &[u8]) -> Vec<u8> {
+    let mut code = vec![
+        opcode::PUSH1, runtime.len() as u8, opcode::DUP1, opcode::PUSH1, 11, opcode::PUSH1, 0x00, opcode::CODECOPY,
+        opcode::PUSH1, 0x00, opcode::RETURN,
+    ];
+    code.extend_from_slice(runtime);
+    code
+}
+
+/// Checks the estimate with no margin is the least gas limit the mined call succeeds with
+fn check_least(chain: &mut Chain, contract: &str, value: u64) -> (u64, Receipt) {
+    let data = U256::from(value).to_be_bytes().to_vec();
+    let estimate = chain.estimate(&chain.call(contract, data.clone(), GAS_CAP)).unwrap();
+    
+    // One gas short fails, tried on a copy so the call is only mined once
+    let mut short = Chain { state: chain.state.clone(), engine: chain.engine.clone(), height: chain.height, nonce: chain.nonce };
+    assert!(!short.mine(chain.call(contract, data.clone(), estimate - 1)).success);
+    
+    let receipt = chain.mine(chain.call(contract, data, estimate));
+    assert!(receipt.success, "{:?}", receipt.revert_reason);
+    (estimate, receipt)
+}
+
+/// Checks estimates of storage-heavy calls against the gas they use once mined
+#[test]
+fn check_storage_heavy() {
+    let mut chain = Chain::new(0);
+    let contract = chain.deploy();
+    
+    // Writing fresh slots uses all it needs
+    let (estimate, receipt) = check_least(&mut chain, &contract, 7);
+    assert_eq!(estimate, receipt.gas_used);
+    assert!(receipt.gas_used > u64::from(SLOTS) * GasConfig::default().storage_cost);
+    
+    // Clearing them is refunded after the fact, so it needs more than it ends up using
+    let (estimate, receipt) = check_least(&mut chain, &contract, 0);
+    assert!(estimate > receipt.gas_used);
+}
+
+/// Checks the margin is added on top of what a call needs, within the cap
+#[test]
+fn check_margin() {
+    let mut chain = Chain::new(DEFAULT_GAS_ESTIMATE_MARGIN);
+    let contract = chain.deploy();
+    let data = U256::from(7u64).to_be_bytes().to_vec();
+    let tx = chain.call(&contract, data, GAS_CAP);
+    let estimate = chain.estimate(&tx).unwrap();
+    
+    let receipt = chain.mine(tx);
+    assert_eq!(estimate, receipt.gas_used + receipt.gas_used * DEFAULT_GAS_ESTIMATE_MARGIN / 100);
+    
+    // Estimating left the state as it was, so the mined call wrote the slots itself
+    let storage = chain.state.get_contract_storage(&contract).unwrap();
+    assert_eq!(storage.len(), usize::from(SLOTS));
+}
+
+/// Checks a reverting call gives its reason instead of an estimate, and a transfer needs only its intrinsic gas
+#[test]
+fn check_revert_and_transfer() {
+    let mut chain = Chain::new(DEFAULT_GAS_ESTIMATE_MARGIN);
+    let params = TokenParams { name: "Test Token".to_string(), symbol: "TST".to_string(), decimals: 0, initial_supply: U256::from(10u64) };
+    let create = chain.call(&contract_address(&TOKEN_FACTORY_ADDRESS), params.to_call_data().unwrap(), GAS_CAP);
+    let token = chain.mine(create).contract_address.expect("the receipt names the token");
+    
+    let transfer = token::erc20_functions().into_iter().find(|function| function.signature == token::TRANSFER_SELECTOR).unwrap();
+    let mut data = token::TRANSFER_SELECTOR.to_vec();
+    data.extend(abi::encode(&transfer.inputs, &[Value::Address(to_evm_address("GENX_BOB")), Value::Uint(U256::from(11u64))]).unwrap());
+    let error = chain.estimate(&chain.call(&token, data, GAS_CAP)).expect_err("the transfer exceeds the balance");
+    assert!(matches!(error, ContractError::Reverted { reason: Some(ref reason), .. } if reason.contains("ERC20: transfer amount exceeds balance")), "{}", error);
+    
+    let sender = Address::new(SENDER).unwrap();
+    let tx = Transaction::new(sender, Address::new("GENX_BOB").unwrap(), Amount::from_base_units(5), Amount::from_base_units(1_000), None).unwrap();
+    assert_eq!(chain.estimate(&tx).unwrap(), chain.engine.intrinsic_gas(&tx));
+}
\ No newline at end of file
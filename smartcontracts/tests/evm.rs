@@ -0,0 +1,200 @@
+//! Checks the interpreter runs hand-assembled bytecode
+//!
+//! Run with `cargo test -p smartcontracts --test evm`. Runs a counter
+//! contract twice against a host that keeps its storage, reverts with an
+//! `Error(string)` payload and with an empty range at a huge offset, reads
+//! the environment opcodes, and checks stack, jump and opcode errors halt
+//! execution with their own errors.
+
+use std::collections::HashMap;
+
+use smartcontracts::abi::{self, Value};
+use smartcontracts::evm::{self, opcode, ExecutionContext, ExecutionResult, ExecutionStatus, Host};
+use smartcontracts::u256::U256;
+use smartcontracts::{ABIParameter, ContractError, GasConfig};
+
+/// Gas every execution starts with
+const GAS_LIMIT: u64 = 100_000;
+
+/// Address the code under test runs at
+const CONTRACT: [u8; 20] = [0xc0; 20];
+
+/// Account sending the transaction
+const SENDER: [u8; 20] = [0x5e; 20];
+
+/// Counter keeping its count in slot 0
+///
+/// Called with any input it increments the count; called without it only
+/// reads it. Either way it returns the count as a word.
+const COUNTER: [u8; 24] = [
+    opcode::PUSH1, 0x00, opcode::SLOAD,
+    opcode::CALLDATASIZE, opcode::ISZERO, opcode::PUSH1, 0x0f, opcode::JUMPI,
+    opcode::PUSH1, 0x01, opcode::ADD, opcode::DUP1, opcode::PUSH1, 0x00, opcode::SSTORE,
+    opcode::JUMPDEST,
+    opcode::PUSH1, 0x00, opcode::MSTORE,
+    opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::RETURN,
+];
+
+/// Accounts and contracts kept in memory
+#[derive(Default)]
+struct TestHost {
+    code: HashMap<[u8; 20], Vec<u8>>,
+    storage: HashMap<([u8; 20], Vec<u8>), Vec<u8>>,
+    balances: HashMap<[u8; 20], u64>,
+}
+
+impl Host for TestHost {
+    fn code(&self, address: &[u8; 20]) -> Option<&[u8]> {
+        self.code.get(address).map(Vec::as_slice)
+    }
+    
+    fn storage(&self, address: &[u8; 20], key: &[u8]) -> Option<&[u8]> {
+        self.storage.get(&(*address, key.to_vec())).map(Vec::as_slice)
+    }
+    
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.balances.get(address).copied().unwrap_or(0)
+    }
+}
+
+impl TestHost {
+    /// Keeps the storage writes of a successful execution
+    fn apply(&mut self, result: &ExecutionResult) {
+        for write in &result.changes.storage {
+            let key = (write.address, write.key.clone());
+            match &write.value {
+                Some(value) => self.storage.insert(key, value.clone()),
+                None => self.storage.remove(&key),
+            };
+        }
+    }
+}
+
+/// Context of a call from the sender to the contract
+fn context() -> ExecutionContext {
+    ExecutionContext {
+        address: CONTRACT,
+        caller: SENDER,
+        origin: SENDER,
+        block_number: 7,
+        timestamp: 1_700_000_000,
+        chain_id: smartcontracts::DEFAULT_CHAIN_ID,
+        ..ExecutionContext::default()
+    }
+}
+
+/// Runs code as the contract with the default gas schedule
+fn run(code: &[u8], input: &[u8], host: &TestHost) -> ExecutionResult {
+    evm::execute(code, input, host, &context(), GAS_LIMIT, &GasConfig::default()).unwrap()
+}
+
+/// Runs code expected to fail, returning its error
+fn fail(code: &[u8]) -> ContractError {
+    evm::execute(code, &[], &TestHost::default(), &context(), GAS_LIMIT, &GasConfig::default()).unwrap_err()
+}
+
+/// Reads a returned word as a number
+fn returned(result: &ExecutionResult) -> u64 {
+    assert_eq!(result.status, ExecutionStatus::Success);
+    U256::from_be_slice(&result.return_data).as_u64().unwrap()
+}
+
+/// Checks the counter increments across executions and reads without writing
+#[test]
+fn check_counter() {
+    let mut host = TestHost::default();
+    for expected in 1..=2 {
+        let result = run(&COUNTER, &[1], &host);
+        assert_eq!(returned(&result), expected);
+        assert_eq!(result.changes.storage.len(), 1);
+        let write = &result.changes.storage[0];
+        assert_eq!((write.address, write.key.as_slice()), (CONTRACT, U256::ZERO.to_be_bytes().as_slice()));
+        host.apply(&result);
+    }
+    
+    let read = run(&COUNTER, &[], &host);
+    assert_eq!(returned(&read), 2);
+    assert!(read.changes.storage.is_empty(), "reading the count wrote storage");
+}
+
+/// Checks REVERT returns its data and discards the writes made before it
+#[test]
+fn check_revert() {
+    let params = [ABIParameter { name: "reason".to_string(), param_type: "string".to_string() }];
+    let mut payload = abi::ERROR_SELECTOR.to_vec();
+    payload.extend(abi::encode(&params, &[Value::String("count too high".to_string())]).unwrap());
+    
+    // Writes slot 0, copies the payload appended to the code into memory and reverts with it
+    let len = payload.len() as u8;
+    let mut code = vec![
+        opcode::PUSH1, 0x01, opcode::PUSH1, 0x00, opcode::SSTORE,
+        opcode::PUSH1, len, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00, opcode::CODECOPY,
+        opcode::PUSH1, len, opcode::PUSH1, 0x00, opcode::REVERT,
+    ];
+    let start = code.len() as u8;
+    code[8] = start;
+    code.extend_from_slice(&payload);
+    
+    let result = run(&code, &[], &TestHost::default());
+    assert_eq!(result.status, ExecutionStatus::Revert);
+    assert_eq!(result.return_data, payload);
+    assert!(result.changes.storage.is_empty() && result.logs.is_empty(), "a reverted execution kept its effects");
+    assert!(result.gas_used < GAS_LIMIT, "a revert consumed all gas");
+    let error = ContractError::reverted(result.return_data);
+    assert!(matches!(&error, ContractError::Reverted { reason: Some(reason), .. } if reason == "count too high"), "{}", error);
+    
+    // An empty range touches no memory, however far away its offset
+    let mut empty = vec![opcode::PUSH1, 0x00, opcode::PUSH32];
+    empty.extend_from_slice(&[0xff; 32]);
+    empty.push(opcode::REVERT);
+    let result = run(&empty, &[], &TestHost::default());
+    assert_eq!(result.status, ExecutionStatus::Revert);
+    assert!(result.return_data.is_empty());
+}
+
+/// Checks the environment opcodes read the context and the host
+#[test]
+fn check_environment() {
+    let mut host = TestHost::default();
+    host.balances.insert(CONTRACT, 300);
+    host.balances.insert(SENDER, 1_000);
+    host.code.insert(SENDER, vec![opcode::STOP; 5]);
+    
+    let single = |op: u8| run(&[op, opcode::PUSH1, 0x00, opcode::MSTORE, opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::RETURN], &[], &host);
+    let of_sender = |op: u8| {
+        let mut code = vec![opcode::PUSH1 + 19];
+        code.extend_from_slice(&SENDER);
+        code.extend_from_slice(&[op, opcode::PUSH1, 0x00, opcode::MSTORE, opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::RETURN]);
+        run(&code, &[], &host)
+    };
+    
+    assert_eq!(single(opcode::ORIGIN).return_data[12..], SENDER);
+    assert_eq!(returned(&single(opcode::CHAINID)), smartcontracts::DEFAULT_CHAIN_ID);
+    assert_eq!(returned(&single(opcode::SELFBALANCE)), 300);
+    assert_eq!(returned(&single(opcode::NUMBER)), 7);
+    assert_eq!(returned(&of_sender(opcode::BALANCE)), 1_000);
+    assert_eq!(returned(&of_sender(opcode::EXTCODESIZE)), 5);
+}
+
+/// Checks malformed code halts with the error describing why
+#[test]
+fn check_errors() {
+    assert!(matches!(fail(&[opcode::PUSH1, 0x01, opcode::ADD]), ContractError::StackUnderflow { pc: 2 }));
+    assert!(matches!(fail(&[opcode::SWAP1]), ContractError::StackUnderflow { pc: 0 }));
+    assert!(matches!(fail(&[opcode::PUSH0; 1025]), ContractError::StackOverflow));
+    
+    // Only a JUMPDEST is a destination, and not one inside PUSH data
+    let error = fail(&[opcode::PUSH1, 0x03, opcode::JUMP, opcode::STOP]);
+    assert!(matches!(error, ContractError::InvalidJump { pc: 2, .. }), "{}", error);
+    let error = fail(&[opcode::PUSH1, 0x04, opcode::JUMP, opcode::PUSH1, opcode::JUMPDEST]);
+    assert!(matches!(error, ContractError::InvalidJump { pc: 2, .. }), "{}", error);
+    assert_eq!(error.error_code(), 3015);
+    
+    let error = fail(&[0x0c]);
+    assert!(matches!(error, ContractError::InvalidOpcode { opcode: 0x0c, pc: 0 }), "{}", error);
+    for op in [opcode::GASPRICE, opcode::BLOCKHASH, opcode::CREATE, opcode::CREATE2] {
+        let error = fail(&[op]);
+        assert!(matches!(error, ContractError::UnsupportedOpcode { pc: 0, name } if name == opcode::name(op)), "{}", error);
+        assert_eq!(error.error_code(), 3018);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+pragma solidity ^0.8.19;
+
+contract Broken {
+    function get() public pure returns (uint256) {
+        return missing;
+    }
+}
@@ -0,0 +1,13 @@
+pragma solidity ^0.8.19;
+
+contract Storage {
+    uint256 private value;
+
+    function set(uint256 newValue) public {
+        value = newValue;
+    }
+
+    function get() public view returns (uint256) {
+        return value;
+    }
+}
@@ -0,0 +1,165 @@
+//! Checks the interpreter charges and forwards gas by the schedule
+//!
+//! Run with `cargo test -p smartcontracts --test gas`. Runs code with just
+//! enough gas and one unit less, grows memory to sizes where the quadratic
+//! term shows, and calls a contract that reports the gas it was given, with
+//! and without value, to check all but 1/64 of the remaining gas is
+//! forwarded, value calls add the stipend and a callee running out of gas
+//! leaves its caller running.
+
+use std::collections::HashMap;
+
+use smartcontracts::evm::{self, opcode, ExecutionContext, ExecutionResult, ExecutionStatus, Host};
+use smartcontracts::u256::U256;
+use smartcontracts::{ContractError, GasConfig, Result};
+
+/// Gas the calling tests start with
+const GAS_LIMIT: u64 = 100_000;
+
+/// Address the code under test runs at
+const CONTRACT: [u8; 20] = [0xc0; 20];
+
+/// Address of the contract called
+const CALLEE: [u8; 20] = [0xca; 20];
+
+/// Returns the gas left when it starts, as a word
+const REPORT_GAS: [u8; 9] = [
+    opcode::GAS, opcode::PUSH1, 0x00, opcode::MSTORE,
+    opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::RETURN,
+];
+
+/// Loops until it runs out of gas
+const SPIN: [u8; 4] = [opcode::JUMPDEST, opcode::PUSH1, 0x00, opcode::JUMP];
+
+/// Accounts and contracts kept in memory
+#[derive(Default)]
+struct TestHost {
+    code: HashMap<[u8; 20], Vec<u8>>,
+    balances: HashMap<[u8; 20], u64>,
+}
+
+impl Host for TestHost {
+    fn code(&self, address: &[u8; 20]) -> Option<&[u8]> {
+        self.code.get(address).map(Vec::as_slice)
+    }
+    
+    fn storage(&self, _address: &[u8; 20], _key: &[u8]) -> Option<&[u8]> {
+        None
+    }
+    
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.balances.get(address).copied().unwrap_or(0)
+    }
+}
+
+/// Runs code as the contract
+fn run(code: &[u8], host: &TestHost, gas_limit: u64) -> Result<ExecutionResult> {
+    let context = ExecutionContext { address: CONTRACT, ..ExecutionContext::default() };
+    evm::execute(code, &[], host, &context, gas_limit, &GasConfig::default())
+}
+
+/// Reads a word of the returned data as a number
+fn returned(result: &ExecutionResult, word: usize) -> u64 {
+    assert_eq!(result.status, ExecutionStatus::Success);
+    U256::from_be_slice(&result.return_data[word * 32..(word + 1) * 32]).as_u64().unwrap()
+}
+
+/// Checks code runs with exactly the gas it needs and halts with one unit less
+#[test]
+fn check_out_of_gas() {
+    let host = TestHost::default();
+    
+    // Three very low cost instructions; STOP is free
+    let code = [opcode::PUSH1, 0x01, opcode::PUSH1, 0x02, opcode::ADD, opcode::STOP];
+    assert_eq!(run(&code, &host, 9).unwrap().gas_used, 9);
+    let error = run(&code, &host, 8).unwrap_err();
+    assert!(matches!(error, ContractError::OutOfGas { required: 9, available: 8 }), "{}", error);
+    assert_eq!(error.error_code(), 3011);
+    
+    let error = run(&SPIN, &host, 10_000).unwrap_err();
+    assert!(matches!(error, ContractError::OutOfGas { available: 10_000, .. }), "{}", error);
+}
+
+/// Checks growing memory costs its linear and quadratic terms, and reusing it nothing more
+#[test]
+fn check_memory_expansion() {
+    let config = GasConfig::default();
+    let host = TestHost::default();
+    
+    for words in [1u64, 32, 1024, 4096] {
+        // Stores the last word, so memory grows to exactly `words` words
+        let offset = (words - 1) * 32;
+        let mut code = vec![opcode::PUSH1, 0x00, opcode::PUSH1 + 3];
+        code.extend_from_slice(&(offset as u32).to_be_bytes());
+        code.push(opcode::MSTORE);
+        
+        let memory = words * config.memory_word_cost + words * words / 512;
+        let expected = 3 * config.very_low_cost + memory;
+        assert_eq!(run(&code, &host, GAS_LIMIT).unwrap().gas_used, expected, "memory of {} words", words);
+        
+        // A second store into the same memory costs no more to expand
+        code.extend_from_slice(&code.clone());
+        assert_eq!(run(&code, &host, GAS_LIMIT).unwrap().gas_used, 2 * expected - memory);
+    }
+    
+    // An empty copy at any destination allocates nothing
+    let mut code = vec![opcode::PUSH1, 0x00, opcode::PUSH1, 0x00, opcode::PUSH32];
+    code.extend_from_slice(&[0xff; 32]);
+    code.extend_from_slice(&[opcode::CODECOPY, opcode::MSIZE]);
+    assert_eq!(run(&code, &host, GAS_LIMIT).unwrap().gas_used, 4 * config.very_low_cost + config.step_cost);
+}
+
+/// Builds code calling the callee with the given value
+///
+/// Gas requested is all that is left unless `requested` is given. The code
+/// returns the first word the callee returned, then whether the call succeeded.
+fn caller(value: u8, requested: Option<u8>) -> Vec<u8> {
+    let mut code = vec![
+        opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00, opcode::PUSH1, 0x00,
+        opcode::PUSH1, value, opcode::PUSH1 + 19,
+    ];
+    code.extend_from_slice(&CALLEE);
+    match requested {
+        Some(gas) => code.extend_from_slice(&[opcode::PUSH1, gas]),
+        None => code.push(opcode::GAS),
+    }
+    code.extend_from_slice(&[
+        opcode::CALL, opcode::PUSH1, 0x20, opcode::MSTORE,
+        opcode::PUSH1, 0x40, opcode::PUSH1, 0x00, opcode::RETURN,
+    ]);
+    code
+}
+
+/// Checks calls forward all but 1/64 of the remaining gas, plus the stipend with value
+#[test]
+fn check_call_forwarding() {
+    let config = GasConfig::default();
+    let mut host = TestHost::default();
+    host.balances.insert(CONTRACT, 1_000);
+    host.code.insert(CALLEE, REPORT_GAS.to_vec());
+    
+    // Six pushes and GAS, then CALL and its return region, come before the forwarding
+    let before_call = 6 * config.very_low_cost + config.step_cost + config.call_cost + config.memory_word_cost;
+    
+    // The callee spends one unit on GAS before reading what is left
+    let available = GAS_LIMIT - before_call;
+    let result = run(&caller(0, None), &host, GAS_LIMIT).unwrap();
+    assert_eq!(returned(&result, 0), available - available / 64 - config.step_cost);
+    assert_eq!(returned(&result, 1), 1);
+    
+    let available = GAS_LIMIT - before_call - config.call_value_cost;
+    let result = run(&caller(1, None), &host, GAS_LIMIT).unwrap();
+    assert_eq!(returned(&result, 0), available - available / 64 + config.call_stipend - config.step_cost);
+    assert_eq!(result.changes.transfers, vec![(CONTRACT, CALLEE, 1)]);
+    
+    // Asking for no gas with value still leaves the callee the stipend
+    let result = run(&caller(1, Some(0)), &host, GAS_LIMIT).unwrap();
+    assert_eq!(returned(&result, 0), config.call_stipend - config.step_cost);
+    
+    // A callee spinning out of gas uses up only what it was given
+    host.code.insert(CALLEE, SPIN.to_vec());
+    let result = run(&caller(0, None), &host, GAS_LIMIT).unwrap();
+    assert_eq!(returned(&result, 1), 0, "the call that ran out of gas succeeded");
+    let available = GAS_LIMIT - before_call;
+    assert!(GAS_LIMIT - result.gas_used >= available / 64 - 100, "the caller kept less than 1/64 of its gas");
+}
\ No newline at end of file
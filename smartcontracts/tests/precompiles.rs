@@ -0,0 +1,105 @@
+//! Checks the Ethereum precompiles against known answers and their prices
+//!
+//! Run with `cargo test -p smartcontracts --test precompiles`. Runs
+//! ecrecover on signatures whose signers are published, sha256 and
+//! ripemd160 on the standard test messages and identity on data spanning
+//! several words, and checks each charges Ethereum's price for its input
+//! size, fails when given one unit of gas less, and leaves invalid
+//! signatures returning no data.
+
+use smartcontracts::precompiles::{self, ECRECOVER_ADDRESS, IDENTITY_ADDRESS, RIPEMD160_ADDRESS, SHA256_ADDRESS};
+use smartcontracts::{ContractError, GasConfig};
+
+/// Gas every run starts with
+const GAS_LIMIT: u64 = 100_000;
+
+/// Input of go-ethereum's ecrecover test: hash, `v` of 27, `r` and `s`
+const ECRECOVER_INPUT: &str = "38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e\
+                               000000000000000000000000000000000000000000000000000000000000001b\
+                               38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e\
+                               789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02";
+
+/// Signer of go-ethereum's ecrecover test
+const ECRECOVER_SIGNER: &str = "ceaccac640adf55b2028469bd36ba501f28b699d";
+
+/// The EIP-155 example's signing hash, `v` of 27 for its recovery ID 0, `r` and `s`
+const EIP155_INPUT: &str = "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53\
+                            000000000000000000000000000000000000000000000000000000000000001b\
+                            28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276\
+                            67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+/// Signer of the EIP-155 example
+const EIP155_SIGNER: &str = "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+
+/// Digests of the empty message and of "abc", from FIPS 180-2 and the RIPEMD-160 paper
+const SHA256_EMPTY: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+const SHA256_ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const RIPEMD160_EMPTY: &str = "9c1185a5c5e9fc54612808977ee8f548b2258d31";
+const RIPEMD160_ABC: &str = "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc";
+
+/// Runs a precompile with the default schedule, checking it charges `gas` and fails with less
+fn run(address: &[u8; 20], input: &[u8], gas: u64) -> Vec<u8> {
+    let config = GasConfig::default();
+    let output = precompiles::run(address, input, GAS_LIMIT, &config).unwrap().unwrap();
+    assert_eq!(output.gas_used, gas, "gas of {} input bytes", input.len());
+    assert_eq!(precompiles::run(address, input, gas, &config).unwrap().unwrap().output, output.output);
+    
+    let error = precompiles::run(address, input, gas - 1, &config).unwrap().unwrap_err();
+    assert!(matches!(error, ContractError::OutOfGas { required, available } if required == gas && available == gas - 1), "{}", error);
+    output.output
+}
+
+/// Left-pads a hex digest to a word
+fn word(digits: &str) -> Vec<u8> {
+    let digest = hex::decode(digits).unwrap();
+    let mut word = vec![0u8; 32 - digest.len()];
+    word.extend(digest);
+    word
+}
+
+/// Checks ecrecover returns the published signers and no data for invalid signatures
+#[test]
+fn check_ecrecover() {
+    for (input, signer) in [(ECRECOVER_INPUT, ECRECOVER_SIGNER), (EIP155_INPUT, EIP155_SIGNER)] {
+        let input = hex::decode(input).unwrap();
+        assert_eq!(run(&ECRECOVER_ADDRESS, &input, 3_000), word(signer));
+        
+        // The price is fixed, however long the input
+        let mut longer = input.clone();
+        longer.extend_from_slice(&[0xff; 64]);
+        assert_eq!(run(&ECRECOVER_ADDRESS, &longer, 3_000), word(signer));
+        
+        // `v` other than 27 or 28, zero `r`, and input cut before `s`, read as a zero `s`, recover nothing
+        let mut bad_v = input.clone();
+        bad_v[63] = 29;
+        assert!(run(&ECRECOVER_ADDRESS, &bad_v, 3_000).is_empty());
+        let mut zero_r = input.clone();
+        zero_r[64..96].fill(0);
+        assert!(run(&ECRECOVER_ADDRESS, &zero_r, 3_000).is_empty());
+        assert!(run(&ECRECOVER_ADDRESS, &input[..96], 3_000).is_empty());
+    }
+    assert!(run(&ECRECOVER_ADDRESS, &[], 3_000).is_empty());
+}
+
+/// Checks sha256 and ripemd160 digest the standard messages, charging per word
+#[test]
+fn check_hashes() {
+    assert_eq!(run(&SHA256_ADDRESS, b"", 60), hex::decode(SHA256_EMPTY).unwrap());
+    assert_eq!(run(&SHA256_ADDRESS, b"abc", 60 + 12), hex::decode(SHA256_ABC).unwrap());
+    assert_eq!(run(&RIPEMD160_ADDRESS, b"", 600), word(RIPEMD160_EMPTY));
+    assert_eq!(run(&RIPEMD160_ADDRESS, b"abc", 600 + 120), word(RIPEMD160_ABC));
+    
+    // A partial word is charged as a whole one
+    for (len, words) in [(32, 1), (33, 2), (64, 2), (65, 3)] {
+        run(&SHA256_ADDRESS, &vec![0x61; len], 60 + 12 * words);
+        run(&RIPEMD160_ADDRESS, &vec![0x61; len], 600 + 120 * words);
+    }
+}
+
+/// Checks identity returns its input, charging per word
+#[test]
+fn check_identity() {
+    assert!(run(&IDENTITY_ADDRESS, &[], 15).is_empty());
+    let input: Vec<u8> = (0..=100).collect();
+    assert_eq!(run(&IDENTITY_ADDRESS, &input, 15 + 3 * 4), input);
+}
\ No newline at end of file
@@ -0,0 +1,133 @@
+//! Checks static calls from many threads while blocks apply
+//!
+//! Run with `cargo test -p smartcontracts --test readers`. One thread
+//! applies blocks, each moving a token from alice to bob, publishing a
+//! snapshot after each the way the chain does, while reader threads keep
+//! calling the token through `ContractEngine::reader` against the latest
+//! snapshot. Every answer has to match the snapshot it was read from, and
+//! everything has to finish well within a deadline.
+
+use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use ctb_core::block::Block;
+use ctb_core::state::{State, StateSnapshot};
+use ctb_core::transaction::{to_evm_address, Transaction};
+use ctb_core::units::GENX;
+use ctb_core::BlockHash;
+
+use smartcontracts::abi::{self, Value};
+use smartcontracts::evm::contract_address;
+use smartcontracts::token::{self, TokenParams, TOKEN_FACTORY_ADDRESS};
+use smartcontracts::u256::U256;
+use smartcontracts::reader::ContractReader;
+use smartcontracts::{ContractEngine, GasConfig};
+
+/// Gas limit and price of every transaction and call
+const GAS_LIMIT: u64 = 1_000_000;
+const GAS_PRICE: u64 = 1;
+
+/// Tokens alice starts with
+const SUPPLY: u64 = 1_000;
+
+/// Blocks applied after the one creating the token, each moving one token
+const BLOCKS: u64 = 200;
+
+/// Threads making static calls
+const READERS: usize = 8;
+
+/// Longest the whole run may take before it counts as a deadlock
+const DEADLINE: Duration = Duration::from_secs(120);
+
+const ALICE: &str = "GENX_ALICE";
+const BOB: &str = "GENX_BOB";
+
+/// Applies a block holding one call from alice, publishing the state after it
+fn apply(state: &mut State, engine: &mut ContractEngine, height: u64, contract: &str, data: Vec<u8>, latest: &RwLock<StateSnapshot>) -> Option<String> {
+    let tx = Transaction::new_contract_call(ALICE.to_string(), contract.to_string(), 0, data, GAS_LIMIT, GAS_PRICE)
+        .and_then(|tx| tx.with_nonce(height - 1))
+        .unwrap();
+    let block = Block::new(height, BlockHash::default(), vec![tx], "GENX_VALIDATOR".to_string(), GAS_PRICE).unwrap();
+    let receipts = state.apply_block_with_executor(&block, Some(engine)).unwrap();
+    assert!(receipts[0].success, "{:?}", receipts[0].revert_reason);
+    
+    *latest.write().unwrap() = StateSnapshot {
+        block_height: height,
+        block_timestamp: block.header().timestamp,
+        state: Arc::new(state.clone()),
+    };
+    receipts[0].contract_address.clone()
+}
+
+/// Builds the call data of an ERC-20 function
+fn call_data(selector: [u8; 4], args: &[Value]) -> Vec<u8> {
+    let function = token::erc20_functions().into_iter().find(|function| function.signature == selector).unwrap();
+    let mut data = selector.to_vec();
+    data.extend(abi::encode(&function.inputs, args).unwrap());
+    data
+}
+
+/// Reads an account's token balance in a snapshot
+fn balance_of(reader: &ContractReader, token: &str, account: &str, snapshot: &StateSnapshot) -> u64 {
+    let arguments = call_data(token::BALANCE_OF_SELECTOR, &[Value::Address(to_evm_address(account))])[4..].to_vec();
+    let output = reader.call_static(token, &token::BALANCE_OF_SELECTOR, &arguments, ALICE, GAS_LIMIT, snapshot).unwrap();
+    U256::from_be_slice(&output).as_u64().unwrap()
+}
+
+/// Checks every static call answers from its own snapshot while blocks keep applying
+#[test]
+fn check_parallel_static_calls() {
+    let mut state = State::new();
+    state.apply_transaction(&Transaction::new_coinbase(ALICE.to_string(), 1_000 * GENX).unwrap()).unwrap();
+    let mut engine = ContractEngine::new(GasConfig::default());
+    let latest = Arc::new(RwLock::new(StateSnapshot { block_height: 0, block_timestamp: 0, state: Arc::new(state.clone()) }));
+    
+    let params = TokenParams { name: "Test Token".to_string(), symbol: "TST".to_string(), decimals: 0, initial_supply: U256::from(SUPPLY) };
+    let token = apply(&mut state, &mut engine, 1, &contract_address(&TOKEN_FACTORY_ADDRESS), params.to_call_data().unwrap(), &latest)
+        .expect("the receipt names the token");
+    
+    let (done, finished) = mpsc::channel();
+    let mut readers = Vec::new();
+    for _ in 0..READERS {
+        let (reader, token, latest, done) = (engine.reader(), token.clone(), latest.clone(), done.clone());
+        readers.push(thread::spawn(move || {
+            let mut calls = 0;
+            loop {
+                let snapshot = latest.read().unwrap().clone();
+                let moved = snapshot.block_height - 1;
+                assert!(reader.get_contract(&token, &snapshot).is_some());
+                assert_eq!(balance_of(&reader, &token, BOB, &snapshot), moved);
+                assert_eq!(balance_of(&reader, &token, ALICE, &snapshot), SUPPLY - moved);
+                calls += 1;
+                if moved == BLOCKS {
+                    break;
+                }
+            }
+            done.send(calls).unwrap();
+        }));
+    }
+    
+    let writer = {
+        let (token, latest, done) = (token.clone(), latest.clone(), done.clone());
+        thread::spawn(move || {
+            let transfer = call_data(token::TRANSFER_SELECTOR, &[Value::Address(to_evm_address(BOB)), Value::Uint(U256::ONE)]);
+            for height in 2..=BLOCKS + 1 {
+                apply(&mut state, &mut engine, height, &token, transfer.clone(), &latest);
+            }
+            done.send(0).unwrap();
+        })
+    };
+    drop(done);
+    
+    let mut calls = 0;
+    for _ in 0..=READERS {
+        calls += finished.recv_timeout(DEADLINE).expect("readers and the writer finish before the deadline");
+    }
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert!(calls >= READERS);
+}
\ No newline at end of file
@@ -0,0 +1,164 @@
+//! Checks contracts destroying themselves with SELFDESTRUCT
+//!
+//! Run with `cargo test -p smartcontracts --test selfdestruct`. Deploys a
+//! funded contract that destroys itself when called with any data, then
+//! checks the receipt names it, its balance went to the beneficiary, its
+//! code and storage are gone, calls to it later in the same block and in
+//! the next one succeed without running anything, and the refund follows
+//! the gas configuration.
+
+use ctb_core::block::Block;
+use ctb_core::receipt::Receipt;
+use ctb_core::state::State;
+use ctb_core::transaction::Transaction;
+use ctb_core::units::GENX;
+use ctb_core::{Address, BlockHash};
+
+use smartcontracts::evm::opcode;
+use smartcontracts::{ContractEngine, DeployPayload, GasConfig};
+
+/// Gas limit and price of every transaction
+const GAS_LIMIT: u64 = 1_000_000;
+const GAS_PRICE: u64 = 1;
+
+/// Value the contract is deployed with
+const ENDOWMENT: u64 = 500;
+
+/// Account sending the transactions
+const SENDER: &str = "GENX_ALICE";
+
+/// Address receiving the contract's balance
+const BENEFICIARY: [u8; 20] = [0xbe; 20];
+
+/// Contract destroying itself for `BENEFICIARY` when called with data, and
+/// otherwise setting slot 0 to 1 and returning 42
+const DESTRUCTIBLE: &[u8] = &[
+    opcode::CALLDATASIZE, opcode::PUSH1, 0x13, opcode::JUMPI,
+    opcode::PUSH1, 0x01, opcode::PUSH1, 0x00, opcode::SSTORE,
+    opcode::PUSH1, 0x2a, opcode::PUSH1, 0x00, opcode::MSTORE,
+    opcode::PUSH1, 0x20, opcode::PUSH1, 0x00, opcode::RETURN,
+    opcode::JUMPDEST, opcode::PUSH1 + 19,
+    0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe,
+    0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe, 0xbe,
+    opcode::SELFDESTRUCT,
+];
+
+/// State, engine and chain height, with blocks applied straight to the state
+struct Chain {
+    state: State,
+    engine: ContractEngine,
+    height: u64,
+    nonce: u64,
+}
+
+impl Chain {
+    fn new(gas_config: GasConfig) -> Self {
+        let mut state = State::new();
+        state.apply_transaction(&Transaction::new_coinbase(SENDER.to_string(), 1_000 * GENX).unwrap()).unwrap();
+        Self { state, engine: ContractEngine::new(gas_config), height: 0, nonce: 0 }
+    }
+    
+    /// Builds the next transaction from the sender
+    fn call(&mut self, contract: &str, data: Vec<u8>) -> Transaction {
+        let tx = Transaction::new_contract_call(SENDER.to_string(), contract.to_string(), 0, data, GAS_LIMIT, GAS_PRICE);
+        let tx = tx.and_then(|tx| tx.with_nonce(self.nonce)).unwrap();
+        self.nonce += 1;
+        tx
+    }
+    
+    /// Applies a block holding the transactions, returning their receipts
+    fn apply(&mut self, txs: Vec<Transaction>) -> Vec<Receipt> {
+        self.height += 1;
+        let block = Block::new(self.height, BlockHash::default(), txs, "GENX_VALIDATOR".to_string(), GAS_PRICE).unwrap();
+        let receipts = self.state.apply_block_with_executor(&block, Some(&mut self.engine)).unwrap();
+        assert!(receipts.iter().all(|receipt| receipt.success), "{:?}", receipts);
+        receipts
+    }
+    
+    /// Deploys `DESTRUCTIBLE` with `ENDOWMENT`, returning its address
+    fn deploy(&mut self) -> String {
+        let payload = DeployPayload { init_code: init_code(DESTRUCTIBLE), abi: Vec::new(), events: Vec::new(), constructor_args: Vec::new() };
+        let tx = Transaction::new_contract_deploy(SENDER.to_string(), ENDOWMENT, payload.to_bytes().unwrap(), GAS_LIMIT, GAS_PRICE);
+        let tx = tx.and_then(|tx| tx.with_nonce(self.nonce)).unwrap();
+        self.nonce += 1;
+        let address = tx.contract_address();
+        self.apply(vec![tx]);
+        address
+    }
+    
+    /// Gets the balance of an account, in base units
+    fn balance(&self, account: &str) -> u64 {
+        self.state.get_balance(&Address::new(account).unwrap()).base_units()
+    }
+    
+    /// Checks a call to `contract` runs its code, which writes slot 0
+    fn assert_runs(&mut self, contract: &str) {
+        let tx = self.call(contract, Vec::new());
+        self.apply(vec![tx]);
+        assert!(self.state.get_contract_storage(contract).is_some_and(|storage| !storage.is_empty()));
+    }
+}
+
+/// Builds init code returning `runtime` as the contract's code
+fn init_code(runtime: &[u8]) -> Vec<u8> {
+    let mut code = vec![
+        opcode::PUSH1, runtime.len() as u8, opcode::DUP1, opcode::PUSH1, 11, opcode::PUSH1, 0x00, opcode::CODECOPY,
+        opcode::PUSH1, 0x00, opcode::RETURN,
+    ];
+    code.extend_from_slice(runtime);
+    code
+}
+
+/// Name of the account holding what's sent to `BENEFICIARY`
+fn beneficiary() -> String {
+    format!("0x{}", hex::encode(BENEFICIARY))
+}
+
+/// Checks a call later in the destroying block runs no code, and so does one in the next block
+#[test]
+fn check_destroyed() {
+    let mut chain = Chain::new(GasConfig::default());
+    let contract = chain.deploy();
+    chain.assert_runs(&contract);
+    assert_eq!(chain.balance(&contract), ENDOWMENT);
+    
+    let destroy = chain.call(&contract, vec![1]);
+    let after = chain.call(&contract, Vec::new());
+    let intrinsic_gas = chain.engine.intrinsic_gas(&after);
+    let receipts = chain.apply(vec![destroy, after]);
+    assert_eq!(receipts[0].destroyed_contracts, vec![contract.clone()]);
+    assert!(receipts[1].destroyed_contracts.is_empty());
+    assert_eq!(receipts[1].gas_used, intrinsic_gas);
+    
+    assert!(chain.state.get_contract(&contract).is_none());
+    assert!(chain.state.get_contract_storage(&contract).is_none_or(|storage| storage.is_empty()));
+    assert_eq!(chain.balance(&contract), 0);
+    assert_eq!(chain.balance(&beneficiary()), ENDOWMENT);
+    
+    let later = chain.call(&contract, Vec::new());
+    let receipts = chain.apply(vec![later]);
+    assert_eq!(receipts[0].gas_used, intrinsic_gas);
+    assert!(chain.state.get_contract_storage(&contract).is_none_or(|storage| storage.is_empty()));
+    
+    // Deploying the same code again gives a contract of its own, which runs
+    let contract = chain.deploy();
+    chain.assert_runs(&contract);
+}
+
+/// Checks the refund for destroying a contract is the configured one
+#[test]
+fn check_refund() {
+    let gas_used = |selfdestruct_refund| {
+        let mut chain = Chain::new(GasConfig { selfdestruct_refund, ..GasConfig::default() });
+        let contract = chain.deploy();
+        let destroy = chain.call(&contract, vec![1]);
+        chain.apply(vec![destroy])[0].gas_used
+    };
+    
+    let unrefunded = gas_used(0);
+    assert_eq!(gas_used(100), unrefunded - 100);
+    
+    // The refund is capped at half of the gas execution used
+    let default_refund = GasConfig::default().selfdestruct_refund;
+    assert!(unrefunded - gas_used(default_refund) < default_refund);
+}
\ No newline at end of file
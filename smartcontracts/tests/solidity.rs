@@ -0,0 +1,185 @@
+//! Checks Solidity compilation through solc's standard JSON interface
+//!
+//! Run with `cargo test -p smartcontracts --features solc --test solidity`.
+//! Compiles the sources in `tests/fixtures` with a stand-in for solc that
+//! answers with the recorded standard JSON output next to them, so the
+//! check runs without a compiler installed. The recorded bytecode is hand
+//! assembled to behave like the source, not solc's own output; it is
+//! deployed and called to check the contract parsed from the output works.
+//! A failing source checks errors come back with their line and column.
+//! If a real `solc` is on the PATH the storage contract is compiled with it
+//! too.
+
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use smartcontracts::evm::{self, ExecutionContext, ExecutionResult, ExecutionStatus, Host};
+use smartcontracts::solidity::{self, CompileOptions, CompiledContract, Severity};
+use smartcontracts::u256::U256;
+use smartcontracts::{ContractError, GasConfig};
+
+/// Gas every execution starts with
+const GAS_LIMIT: u64 = 1_000_000;
+
+/// Address the contract is deployed at
+const CONTRACT: [u8; 20] = [0xc0; 20];
+
+/// Selector of `set(uint256)`
+const SET: [u8; 4] = [0x60, 0xfe, 0x47, 0xb1];
+
+/// Selector of `get()`
+const GET: [u8; 4] = [0x6d, 0x4c, 0xe6, 0x3c];
+
+/// Storage of the deployed contract
+#[derive(Default)]
+struct TestHost {
+    storage: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Host for TestHost {
+    fn code(&self, _address: &[u8; 20]) -> Option<&[u8]> {
+        None
+    }
+    
+    fn storage(&self, _address: &[u8; 20], key: &[u8]) -> Option<&[u8]> {
+        self.storage.get(key).map(Vec::as_slice)
+    }
+    
+    fn balance(&self, _address: &[u8; 20]) -> u64 {
+        0
+    }
+}
+
+/// Directory the stand-in compilers of one check are written to, removed when dropped
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("genx-solc-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&path).unwrap();
+        Scratch(path)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Gets the path of a fixture
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+/// Writes a stand-in for solc 0.8.19 answering every compilation with a recorded output
+fn fake_solc(scratch: &Path, output: &str) -> PathBuf {
+    let path = scratch.join(output.replace(".json", "-solc"));
+    let script = format!(
+        "#!/bin/sh\n\
+         if [ \"$1\" = \"--version\" ]; then\n\
+         \techo 'solc, the solidity compiler commandline interface'\n\
+         \techo 'Version: 0.8.19+commit.7dd6d404.Linux.g++'\n\
+         \texit 0\n\
+         fi\n\
+         cat > /dev/null\n\
+         cat '{}'\n",
+        fixture(output).display()
+    );
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// Options running the given compiler, which must report version 0.8.19
+fn options(solc: PathBuf) -> CompileOptions {
+    CompileOptions { solc_path: Some(solc), version: Some("0.8.19".to_string()), ..CompileOptions::default() }
+}
+
+/// Runs code as the contract
+fn run(code: &[u8], input: &[u8], host: &TestHost) -> ExecutionResult {
+    let context = ExecutionContext { address: CONTRACT, ..ExecutionContext::default() };
+    let result = evm::execute(code, input, host, &context, GAS_LIMIT, &GasConfig::default()).unwrap();
+    assert_eq!(result.status, ExecutionStatus::Success);
+    result
+}
+
+/// Deploys a compiled contract, sets its value and reads it back
+fn check_deployed(contract: &CompiledContract) {
+    let mut host = TestHost::default();
+    assert_eq!(run(&contract.bytecode, &[], &host).return_data, contract.deployed_bytecode);
+    
+    let mut input = SET.to_vec();
+    input.extend_from_slice(&U256::from_u64(42).to_be_bytes());
+    for write in run(&contract.deployed_bytecode, &input, &host).changes.storage {
+        host.storage.insert(write.key, write.value.unwrap());
+    }
+    
+    let value = run(&contract.deployed_bytecode, &GET, &host).return_data;
+    assert_eq!(U256::from_be_slice(&value), U256::from_u64(42));
+}
+
+/// Checks the storage contract, its ABI and the compiler's warning are read from the output
+#[test]
+fn check_storage() {
+    let scratch = &Scratch::new("storage").0;
+    let source = fs::read_to_string(fixture("Storage.sol")).unwrap();
+    let output = solidity::compile_with_options(&source, &options(fake_solc(scratch, "storage.json"))).unwrap();
+    
+    assert_eq!(output.contracts.len(), 1);
+    let contract = &output.contracts[0];
+    assert_eq!(contract.name, "Storage");
+    let mut functions: Vec<_> = contract.abi.iter().map(|f| (f.name.as_str(), f.inputs.len(), f.outputs.len())).collect();
+    functions.sort();
+    assert_eq!(functions, [("get", 0, 1), ("set", 1, 0)]);
+    
+    assert_eq!(output.warnings.len(), 1);
+    assert_eq!(output.warnings[0].severity, Severity::Warning);
+    assert!(output.warnings[0].message.contains("SPDX"));
+    
+    check_deployed(contract);
+    
+    // A compiler reporting another version is refused
+    let error = solidity::compile_with_options(
+        &source,
+        &CompileOptions { version: Some("0.8.20".to_string()), ..options(fake_solc(scratch, "storage.json")) },
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("version mismatch"), "{}", error);
+}
+
+/// Checks a compiler error comes back with the line and column it points at
+#[test]
+fn check_errors() {
+    let scratch = &Scratch::new("errors").0;
+    let source = fs::read_to_string(fixture("Broken.sol")).unwrap();
+    let error = solidity::compile_with_options(&source, &options(fake_solc(scratch, "broken.json"))).unwrap_err();
+    
+    let ContractError::CompilationFailed(diagnostics) = &error else {
+        panic!("expected a failed compilation, got {}", error);
+    };
+    assert_eq!(diagnostics.len(), 1);
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(diagnostic.message, "Undeclared identifier.");
+    assert_eq!((diagnostic.line, diagnostic.column), (Some(5), Some(16)));
+    assert_eq!(error.error_code(), 3001);
+    assert!(error.to_string().contains("Contract.sol:5:16"), "{}", error);
+}
+
+/// Compiles the storage contract with an installed solc, if there is one
+#[test]
+fn check_installed() {
+    let source = fs::read_to_string(fixture("Storage.sol")).unwrap();
+    match solidity::compile_with_options(&source, &CompileOptions::default()) {
+        Ok(output) => {
+            let contract = output.contracts.iter().find(|c| c.name == "Storage").expect("solc compiled no Storage contract");
+            check_deployed(contract);
+        }
+        // Without one the recorded output has to do
+        Err(ContractError::CompilationError(message)) if message.contains("unavailable") => {}
+        Err(error) => panic!("solc failed to compile the storage contract: {}", error),
+    }
+}
\ No newline at end of file
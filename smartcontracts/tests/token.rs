@@ -0,0 +1,207 @@
+//! Checks native tokens behave as an ERC-20 test suite expects
+//!
+//! Run with `cargo test -p smartcontracts --test token`. Creates a token
+//! through the factory in a block, then transfers, approves and spends
+//! allowances in blocks of their own, checking balances, allowances and
+//! the total supply read by static calls, the `Transfer` and `Approval`
+//! events, and that transfers past a balance or an allowance revert with
+//! OpenZeppelin's reasons, changing nothing.
+
+use ctb_core::block::Block;
+use ctb_core::receipt::Receipt;
+use ctb_core::state::State;
+use ctb_core::transaction::{to_evm_address, Transaction};
+use ctb_core::units::GENX;
+use ctb_core::BlockHash;
+
+use smartcontracts::abi::{self, Value};
+use smartcontracts::evm::contract_address;
+use smartcontracts::token::{self, TokenParams, TOKEN_FACTORY_ADDRESS};
+use smartcontracts::u256::U256;
+use smartcontracts::{ContractEngine, GasConfig};
+
+/// Gas limit and price of every transaction
+const GAS_LIMIT: u64 = 1_000_000;
+const GAS_PRICE: u64 = 1;
+
+/// Tokens the creator starts with
+const SUPPLY: u64 = 1_000;
+
+/// Accounts sending transactions
+const ALICE: &str = "GENX_ALICE";
+const BOB: &str = "GENX_BOB";
+const CAROL: &str = "GENX_CAROL";
+
+/// A chain of one token, with blocks applied straight to its state
+struct TokenChain {
+    state: State,
+    engine: ContractEngine,
+    height: u64,
+    nonces: [u64; 3],
+    token: String,
+}
+
+impl TokenChain {
+    /// Funds the accounts and has alice create the token
+    fn new() -> Self {
+        let mut state = State::new();
+        for account in [ALICE, BOB, CAROL] {
+            state.apply_transaction(&Transaction::new_coinbase(account.to_string(), 1_000 * GENX).unwrap()).unwrap();
+        }
+        let mut chain = Self { state, engine: ContractEngine::new(GasConfig::default()), height: 0, nonces: [0; 3], token: String::new() };
+        
+        let params = TokenParams { name: "Test Token".to_string(), symbol: "TST".to_string(), decimals: 6, initial_supply: U256::from(SUPPLY) };
+        let receipt = chain.send_to(ALICE, &contract_address(&TOKEN_FACTORY_ADDRESS), params.to_call_data().unwrap());
+        assert!(receipt.success, "{:?}", receipt.revert_reason);
+        chain.token = receipt.contract_address.expect("the receipt names the token");
+        chain
+    }
+    
+    /// Applies a block holding a call from an account, returning its receipt
+    fn send_to(&mut self, sender: &str, contract: &str, data: Vec<u8>) -> Receipt {
+        let index = [ALICE, BOB, CAROL].iter().position(|account| *account == sender).unwrap();
+        let tx = Transaction::new_contract_call(sender.to_string(), contract.to_string(), 0, data, GAS_LIMIT, GAS_PRICE)
+            .and_then(|tx| tx.with_nonce(self.nonces[index]))
+            .unwrap();
+        self.nonces[index] += 1;
+        self.height += 1;
+        
+        let block = Block::new(self.height, BlockHash::default(), vec![tx], "GENX_VALIDATOR".to_string(), GAS_PRICE).unwrap();
+        let mut receipts = self.state.apply_block_with_executor(&block, Some(&mut self.engine)).unwrap();
+        receipts.remove(0)
+    }
+    
+    /// Calls a function of the token from an account
+    fn send(&mut self, sender: &str, selector: [u8; 4], args: &[Value]) -> Receipt {
+        let token = self.token.clone();
+        self.send_to(sender, &token, call_data(selector, args))
+    }
+    
+    /// Reads a number from the token with a static call
+    fn read(&self, selector: [u8; 4], args: &[Value]) -> U256 {
+        let arguments = call_data(selector, args)[4..].to_vec();
+        let output = self.engine.call_static(&self.token, &selector, &arguments, ALICE, GAS_LIMIT, &self.state).unwrap();
+        U256::from_be_slice(&output)
+    }
+    
+    fn balance_of(&self, account: &str) -> u64 {
+        self.read(token::BALANCE_OF_SELECTOR, &[address(account)]).as_u64().unwrap()
+    }
+    
+    fn allowance(&self, owner: &str, spender: &str) -> U256 {
+        self.read(token::ALLOWANCE_SELECTOR, &[address(owner), address(spender)])
+    }
+    
+    fn balances(&self) -> [u64; 3] {
+        [self.balance_of(ALICE), self.balance_of(BOB), self.balance_of(CAROL)]
+    }
+}
+
+/// Builds the call data of an ERC-20 function
+fn call_data(selector: [u8; 4], args: &[Value]) -> Vec<u8> {
+    let function = token::erc20_functions().into_iter().find(|function| function.signature == selector).unwrap();
+    let mut data = selector.to_vec();
+    data.extend(abi::encode(&function.inputs, args).unwrap());
+    data
+}
+
+/// Gets an account's EVM address as an argument
+fn address(account: &str) -> Value {
+    Value::Address(to_evm_address(account))
+}
+
+/// Checks a receipt carries exactly one event, with the signature, addresses and value given
+fn assert_event(receipt: &Receipt, signature: &str, first: &str, second: &str, value: u64) {
+    assert!(receipt.success, "{:?}", receipt.revert_reason);
+    assert_eq!(receipt.logs.len(), 1, "{:?}", receipt.logs);
+    let log = &receipt.logs[0];
+    let word = |account: &str| U256::from_be_slice(&to_evm_address(account)).to_be_bytes();
+    assert_eq!(log.topics, vec![abi::keccak256(signature.as_bytes()), word(first), word(second)]);
+    assert_eq!(log.data, U256::from(value).to_be_bytes().to_vec());
+}
+
+/// Checks a receipt reverted with a reason and no events
+fn assert_reverted(receipt: &Receipt, reason: &str) {
+    assert!(!receipt.success);
+    assert!(receipt.revert_reason.as_deref().is_some_and(|given| given.contains(reason)), "{:?}", receipt.revert_reason);
+    assert!(receipt.logs.is_empty());
+}
+
+/// Checks the token's metadata and that its creator holds the whole supply
+#[test]
+fn check_created() {
+    let chain = TokenChain::new();
+    assert_eq!(chain.read(token::TOTAL_SUPPLY_SELECTOR, &[]), U256::from(SUPPLY));
+    assert_eq!(chain.read(token::DECIMALS_SELECTOR, &[]), U256::from(6u64));
+    assert_eq!(chain.balances(), [SUPPLY, 0, 0]);
+    let symbol = chain.engine.call_static(&chain.token, &token::SYMBOL_SELECTOR, &[], ALICE, GAS_LIMIT, &chain.state).unwrap();
+    assert_eq!(abi::decode(&[smartcontracts::ABIParameter { name: String::new(), param_type: "string".to_string() }], &symbol).unwrap(), [Value::String("TST".to_string())]);
+}
+
+/// Checks transfers move tokens with a `Transfer` event, and ones past the balance or to the zero address revert
+#[test]
+fn check_transfer() {
+    let mut chain = TokenChain::new();
+    let receipt = chain.send(ALICE, token::TRANSFER_SELECTOR, &[address(BOB), Value::Uint(U256::from(100u64))]);
+    assert_event(&receipt, "Transfer(address,address,uint256)", ALICE, BOB, 100);
+    assert_eq!(chain.balances(), [SUPPLY - 100, 100, 0]);
+    
+    let receipt = chain.send(BOB, token::TRANSFER_SELECTOR, &[address(CAROL), Value::Uint(U256::from(101u64))]);
+    assert_reverted(&receipt, "ERC20: transfer amount exceeds balance");
+    let receipt = chain.send(BOB, token::TRANSFER_SELECTOR, &[Value::Address([0; 20]), Value::Uint(U256::from(1u64))]);
+    assert_reverted(&receipt, "ERC20: transfer to the zero address");
+    assert_eq!(chain.balances(), [SUPPLY - 100, 100, 0]);
+    
+    // The whole balance can go, and transfers of nothing still emit
+    let receipt = chain.send(BOB, token::TRANSFER_SELECTOR, &[address(CAROL), Value::Uint(U256::from(100u64))]);
+    assert_event(&receipt, "Transfer(address,address,uint256)", BOB, CAROL, 100);
+    let receipt = chain.send(BOB, token::TRANSFER_SELECTOR, &[address(CAROL), Value::Uint(U256::ZERO)]);
+    assert_event(&receipt, "Transfer(address,address,uint256)", BOB, CAROL, 0);
+    assert_eq!(chain.balances(), [SUPPLY - 100, 0, 100]);
+    assert_eq!(chain.read(token::TOTAL_SUPPLY_SELECTOR, &[]), U256::from(SUPPLY));
+}
+
+/// Checks approvals set allowances with an `Approval` event, and `transferFrom` spends them down to a revert
+#[test]
+fn check_allowance() {
+    let mut chain = TokenChain::new();
+    let receipt = chain.send(ALICE, token::APPROVE_SELECTOR, &[address(CAROL), Value::Uint(U256::from(50u64))]);
+    assert_event(&receipt, "Approval(address,address,uint256)", ALICE, CAROL, 50);
+    assert_eq!(chain.allowance(ALICE, CAROL), U256::from(50u64));
+    assert_eq!(chain.allowance(CAROL, ALICE), U256::ZERO);
+    
+    let receipt = chain.send(CAROL, token::TRANSFER_FROM_SELECTOR, &[address(ALICE), address(BOB), Value::Uint(U256::from(30u64))]);
+    assert_event(&receipt, "Transfer(address,address,uint256)", ALICE, BOB, 30);
+    assert_eq!(chain.allowance(ALICE, CAROL), U256::from(20u64));
+    assert_eq!(chain.balances(), [SUPPLY - 30, 30, 0]);
+    
+    let receipt = chain.send(CAROL, token::TRANSFER_FROM_SELECTOR, &[address(ALICE), address(BOB), Value::Uint(U256::from(21u64))]);
+    assert_reverted(&receipt, "ERC20: insufficient allowance");
+    let receipt = chain.send(BOB, token::TRANSFER_FROM_SELECTOR, &[address(ALICE), address(BOB), Value::Uint(U256::from(1u64))]);
+    assert_reverted(&receipt, "ERC20: insufficient allowance");
+    assert_eq!(chain.allowance(ALICE, CAROL), U256::from(20u64));
+    
+    // Approving again replaces the allowance rather than adding to it
+    let receipt = chain.send(ALICE, token::APPROVE_SELECTOR, &[address(CAROL), Value::Uint(U256::from(5u64))]);
+    assert_event(&receipt, "Approval(address,address,uint256)", ALICE, CAROL, 5);
+    assert_eq!(chain.allowance(ALICE, CAROL), U256::from(5u64));
+    let receipt = chain.send(ALICE, token::APPROVE_SELECTOR, &[Value::Address([0; 20]), Value::Uint(U256::from(5u64))]);
+    assert_reverted(&receipt, "ERC20: approve to the zero address");
+    assert_eq!(chain.balances(), [SUPPLY - 30, 30, 0]);
+}
+
+/// Checks an allowance within the balance but past what the owner holds reverts, and the maximum allowance never runs down
+#[test]
+fn check_unlimited_allowance() {
+    let mut chain = TokenChain::new();
+    assert!(chain.send(ALICE, token::TRANSFER_SELECTOR, &[address(BOB), Value::Uint(U256::from(10u64))]).success);
+    assert!(chain.send(BOB, token::APPROVE_SELECTOR, &[address(CAROL), Value::Uint(U256::MAX)]).success);
+    
+    let receipt = chain.send(CAROL, token::TRANSFER_FROM_SELECTOR, &[address(BOB), address(CAROL), Value::Uint(U256::from(4u64))]);
+    assert_event(&receipt, "Transfer(address,address,uint256)", BOB, CAROL, 4);
+    assert_eq!(chain.allowance(BOB, CAROL), U256::MAX);
+    
+    let receipt = chain.send(CAROL, token::TRANSFER_FROM_SELECTOR, &[address(BOB), address(CAROL), Value::Uint(U256::from(7u64))]);
+    assert_reverted(&receipt, "ERC20: transfer amount exceeds balance");
+    assert_eq!(chain.balances(), [SUPPLY - 10, 6, 4]);
+}
\ No newline at end of file
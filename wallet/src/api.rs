@@ -3,16 +3,186 @@
 //! This module provides a high-level API for wallet operations
 //! that can be used by the UI and other components.
 
+use std::io::Write;
 use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 
-use crate::{Account, Wallet, WalletError, Result};
-use core::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+
+use crate::confirmations::{ConfirmationEvent, ConfirmationTracker};
+use crate::export::{self, ExportFormat, EXPORT_HISTORY_LIMIT};
+use crate::fees::NodeFeeOracle;
+use crate::filter_sync::FilterSync;
+use crate::payments::{PaymentConfig, PaymentEvent, PaymentRequest, PaymentStatus, PaymentWatcher, ReceivedPayment};
+use crate::pending::{PendingLedger, Reservation};
+use crate::password::PasswordPolicy;
+use crate::{parse_address, Account, AccountSort, Wallet, WalletError, Result};
+use ctb_core::block::Block;
+use ctb_core::confirmation::{ChainTip, ConfirmationStatus};
+use ctb_core::fee_market::{EstimatedBlocks, FeeHistogram, FeeRate};
+use ctb_core::memo::MemoField;
+use ctb_core::paging::{Page, PageRequest};
+use ctb_core::signature::SignatureScheme;
+use ctb_core::transaction::{to_evm_address, Transaction};
+use ctb_core::units::Amount;
+use ctb_core::{Address, TxHash};
+use smartcontracts::abi::{self, Value};
+use smartcontracts::FunctionABI;
+
+/// Selector of the ERC-20 `balanceOf(address)` function
+const BALANCE_OF_SELECTOR: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Selector of the ERC-20 `transfer(address,uint256)` function
+const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+/// Gas limit of token transfers sent by the wallet
+pub const TOKEN_TRANSFER_GAS_LIMIT: u64 = 100_000;
+
+/// History entries searched for an account's confirmed transactions
+pub const PENDING_HISTORY_LIMIT: usize = 1000;
+
+/// Connection to a node, used for operations that need chain state
+pub trait ChainClient: Send + Sync {
+    /// Runs a contract function against the current state without recording a transaction
+    fn call_contract(
+        &self,
+        contract: &str,
+        selector: &[u8; 4],
+        arguments: &[u8],
+        sender: &str,
+    ) -> ctb_core::Result<CallOutcome>;
+    
+    /// Gets the functions of the contract deployed at an address, or `None` if there's none
+    fn get_contract_abi(&self, contract: &str) -> ctb_core::Result<Option<Vec<FunctionABI>>>;
+    
+    /// Estimates the gas a transaction needs by executing it against the current state
+    fn estimate_gas(&self, tx: &Transaction) -> ctb_core::Result<GasEstimate>;
+    
+    /// Gets the base fee the next block will charge per unit of gas
+    fn next_base_fee(&self) -> u64;
+    
+    /// Gets the fees of the transactions waiting in the node's mempool
+    fn fee_histogram(&self) -> ctb_core::Result<FeeHistogram>;
+    
+    /// Estimates how many blocks a transaction paying a rate waits to be included
+    fn estimate_inclusion(&self, rate: FeeRate) -> ctb_core::Result<EstimatedBlocks>;
+    
+    /// Gets the balance of an account
+    fn get_balance(&self, address: &str) -> ctb_core::Result<u64>;
+    
+    /// Gets the nonce an account's next transaction must have, see `ctb_core::state::State::get_nonce`
+    fn get_nonce(&self, address: &str) -> ctb_core::Result<u64>;
+    
+    /// Submits a signed transaction for inclusion in a block, returning its ID
+    fn submit_transaction(&self, tx: &Transaction) -> ctb_core::Result<TxHash>;
+    
+    /// Gets the most recent transactions sent or received by an address, newest first
+    fn get_history(&self, address: &str, limit: usize) -> ctb_core::Result<Vec<TransactionRecord>>;
+    
+    /// Gets a page of the transactions sent or received by an address, newest first by default
+    fn get_history_page(&self, address: &str, request: &PageRequest) -> ctb_core::Result<Page<TransactionRecord>>;
+    
+    /// Gets a transaction included in a block, or `None` if it's in none
+    fn get_transaction(&self, tx_id: &TxHash) -> ctb_core::Result<Option<TransactionRecord>>;
+    
+    /// Checks whether an address may have been used, cheaply but with false positives
+    ///
+    /// See `ctb_core::address_bloom`; never false of an address that was used.
+    fn address_possibly_used(&self, address: &str) -> ctb_core::Result<bool>;
+    
+    /// Gets the height of the first block an address was used in, if any
+    fn address_first_used(&self, address: &str) -> ctb_core::Result<Option<u64>>;
+    
+    /// Gets the heights of the latest block and the latest finalized block
+    fn chain_tip(&self) -> ctb_core::Result<ChainTip>;
+    
+    /// Gets how settled a transaction is, needing `safe_confirmations` to be safe
+    ///
+    /// See `ctb_core::confirmation`; a transaction in no block is pending.
+    fn get_transaction_status(&self, tx_id: &TxHash, safe_confirmations: u64) -> ctb_core::Result<ConfirmationStatus> {
+        let record = self.get_transaction(tx_id)?;
+        Ok(self.chain_tip()?.status(record.map(|record| record.block_height), safe_confirmations))
+    }
+}
+
+/// Transaction included in a block, as listed in an account's history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    /// Height of the block the transaction was included in
+    pub block_height: u64,
+    
+    /// Timestamp of the block
+    #[serde(default)]
+    pub block_timestamp: u64,
+    
+    /// Whether the transaction executed successfully
+    pub success: bool,
+    
+    /// Fee the sender was charged, which for contract transactions depends on the gas used
+    #[serde(default)]
+    pub fee_paid: u64,
+    
+    /// The transaction itself
+    pub transaction: Transaction,
+    
+    /// How settled the transaction was when the record was made
+    ///
+    /// Nodes judge it by `ctb_core::confirmation::DEFAULT_SAFE_CONFIRMATIONS`; callers with their
+    /// own threshold judge it again with `judge`.
+    #[serde(default)]
+    pub status: ConfirmationStatus,
+}
+
+impl TransactionRecord {
+    /// Judges the record's status again at a tip, needing `safe_confirmations` to be safe
+    pub fn judge(&mut self, tip: &ChainTip, safe_confirmations: u64) {
+        self.status = tip.status(Some(self.block_height), safe_confirmations);
+    }
+}
+
+/// Outcome of calling a contract function without a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallOutcome {
+    /// The function returned this data
+    Returned(Vec<u8>),
+    
+    /// The function reverted, with the reason it gave if any and the raw revert data
+    Reverted { reason: Option<String>, data: Vec<u8> },
+}
+
+/// Outcome of estimating a transaction's gas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GasEstimate {
+    /// Gas limit to send the transaction with
+    Gas(u64),
+    
+    /// The transaction would revert, with the reason it gave if any
+    Reverted(Option<String>),
+}
 
 /// Wallet API for managing wallets and accounts
 pub struct WalletApi {
     /// The underlying wallet instance
     wallet: Arc<Mutex<Wallet>>,
+    
+    /// Node used for queries against the chain, if connected
+    client: Option<Arc<dyn ChainClient>>,
+    
+    /// Transactions created and not yet known to be confirmed
+    pending: Mutex<PendingLedger>,
+    
+    /// Payment requests waiting to be paid
+    payments: Mutex<PaymentWatcher>,
+    
+    /// Channels payment events are sent to
+    payment_subscribers: Mutex<Vec<Sender<PaymentEvent>>>,
+    
+    /// Statuses of the transactions sent, judged by the wallet's confirmations for safety
+    confirmations: Mutex<ConfirmationTracker>,
+    
+    /// Channels confirmation events are sent to
+    confirmation_subscribers: Mutex<Vec<Sender<ConfirmationEvent>>>,
 }
 
 impl WalletApi {
@@ -20,12 +190,26 @@ impl WalletApi {
     pub fn new(wallet: Wallet) -> Self {
         Self {
             wallet: Arc::new(Mutex::new(wallet)),
+            client: None,
+            pending: Mutex::new(PendingLedger::new()),
+            payments: Mutex::new(PaymentWatcher::new(PaymentConfig::default())),
+            payment_subscribers: Mutex::new(Vec::new()),
+            confirmations: Mutex::new(ConfirmationTracker::default()),
+            confirmation_subscribers: Mutex::new(Vec::new()),
         }
     }
     
     /// Creates a new wallet at the given path
+    ///
+    /// Fails with `WalletError::WeakPassword` if the default password
+    /// policy finds the password weak (see `password`).
     pub fn create_wallet(wallet_path: PathBuf, password: &str) -> Result<Self> {
-        let wallet = Wallet::create(wallet_path, password)?;
+        Self::create_wallet_with_policy(wallet_path, password, &PasswordPolicy::default())
+    }
+    
+    /// Creates a new wallet at the given path, with a password the policy accepts
+    pub fn create_wallet_with_policy(wallet_path: PathBuf, password: &str, policy: &PasswordPolicy) -> Result<Self> {
+        let wallet = Wallet::create_with_policy(wallet_path, password, policy)?;
         Ok(Self::new(wallet))
     }
     
@@ -35,12 +219,32 @@ impl WalletApi {
         Ok(Self::new(wallet))
     }
     
+    /// Loads a wallet from the given path that never writes to it, see `Wallet::load_readonly`
+    pub fn load_wallet_readonly(wallet_path: PathBuf) -> Result<Self> {
+        let wallet = Wallet::load_readonly(wallet_path)?;
+        Ok(Self::new(wallet))
+    }
+    
+    /// Reads the wallet file again, taking in what other processes saved
+    ///
+    /// Call after an operation fails with
+    /// `WalletError::ConcurrentModification`, then make the change again.
+    pub fn reload(&self) -> Result<()> {
+        self.wallet.lock().unwrap().reload()
+    }
+    
     /// Unlocks the wallet with the given password
     pub fn unlock(&self, password: &str) -> Result<()> {
         let mut wallet = self.wallet.lock().unwrap();
         wallet.unlock(password)
     }
     
+    /// Changes the wallet's password, re-encrypting every account's key
+    pub fn change_password(&self, current: &str, new: &str, policy: &PasswordPolicy) -> Result<()> {
+        let mut wallet = self.wallet.lock().unwrap();
+        wallet.change_password(current, new, policy)
+    }
+    
     /// Locks the wallet
     pub fn lock(&self) -> Result<()> {
         let mut wallet = self.wallet.lock().unwrap();
@@ -54,18 +258,37 @@ impl WalletApi {
         wallet.create_account(label)
     }
     
+    /// Creates a new account whose key uses the given signature scheme
+    pub fn create_account_with_scheme(&self, label: &str, scheme: SignatureScheme) -> Result<String> {
+        let mut wallet = self.wallet.lock().unwrap();
+        wallet.create_account_with_scheme(label, scheme)
+    }
+    
     /// Gets all accounts in the wallet
+    #[deprecated(note = "use `list_accounts` to page through them")]
     pub fn get_accounts(&self) -> Result<Vec<Account>> {
         let wallet = self.wallet.lock().unwrap();
         Ok(wallet.get_accounts().into_iter().cloned().collect())
     }
     
+    /// Gets a page of the accounts in the wallet
+    pub fn list_accounts(&self, sort: AccountSort, request: &PageRequest) -> Result<Page<Account>> {
+        let wallet = self.wallet.lock().unwrap();
+        Ok(wallet.list_accounts(sort, request)?.map(Account::clone))
+    }
+    
     /// Gets an account by address
     pub fn get_account(&self, address: &str) -> Result<Option<Account>> {
         let wallet = self.wallet.lock().unwrap();
         Ok(wallet.get_account(address).cloned())
     }
     
+    /// Creates a sync of the accounts' history by block filters, see `filter_sync`
+    pub fn filter_sync(&self) -> FilterSync {
+        let wallet = self.wallet.lock().unwrap();
+        FilterSync::new(wallet.get_accounts().into_iter().map(|account| account.address.clone()))
+    }
+    
     /// Gets the default account
     pub fn get_default_account(&self) -> Result<Option<Account>> {
         let wallet = self.wallet.lock().unwrap();
@@ -79,24 +302,530 @@ impl WalletApi {
     }
     
     /// Creates and signs a transaction
+    ///
+    /// The transaction gets the sender's next nonce, after those of its
+    /// transactions in flight, and what it can spend is reserved until it's confirmed, fails or is cancelled
+    /// (see `pending`), so transactions created at once from one account
+    /// don't conflict. When connected to a node, fails with
+    /// `WalletError::InsufficientFunds` if the sender's confirmed balance
+    /// doesn't cover the transaction on top of those in flight.
     pub fn create_transaction(
+        &self,
+        sender: &Address,
+        recipient: &Address,
+        amount: Amount,
+        fee: Amount,
+        data: Option<Vec<u8>>,
+    ) -> Result<Transaction> {
+        let tx = Transaction::new(sender.clone(), recipient.clone(), amount, fee, data)?;
+        self.reserve_and_sign(tx)
+    }
+    
+    /// Creates and signs a transaction between unchecked addresses, like `create_transaction`
+    #[deprecated(note = "use `WalletApi::create_transaction` with `Address`es")]
+    pub fn create_transaction_untyped(
         &self,
         sender: &str,
         recipient: &str,
-        amount: u64,
-        fee: u64,
+        amount: Amount,
+        fee: Amount,
         data: Option<Vec<u8>>,
     ) -> Result<Transaction> {
-        let wallet = self.wallet.lock().unwrap();
-        wallet.create_transaction(sender, recipient, amount, fee, data)
+        self.create_transaction(&parse_address(sender)?, &parse_address(recipient)?, amount, fee, data)
+    }
+    
+    /// Creates and signs a transfer carrying a memo, like `create_transaction`
+    ///
+    /// Fails unless the memo fits in `ctb_core::memo::MAX_MEMO_SIZE` bytes and
+    /// the fee covers its bytes (see `Transaction::memo_fee`). For a memo
+    /// only the recipient should read, pass `MemoField::commit` of it and
+    /// give them the memo itself, which they check with
+    /// `verify_memo_commitment`.
+    pub fn create_transaction_with_memo(
+        &self,
+        sender: &Address,
+        recipient: &Address,
+        amount: Amount,
+        fee: Amount,
+        memo: MemoField,
+    ) -> Result<Transaction> {
+        let tx = Transaction::new(sender.clone(), recipient.clone(), amount, fee, None)?.with_memo(memo)?;
+        tx.validate_memo()?;
+        self.reserve_and_sign(tx)
+    }
+    
+    /// Checks whether a memo revealed off the chain is the one a transaction carries
+    ///
+    /// True if the transaction commits to the memo, or carries it in plain;
+    /// false if it carries another memo or none. Fails with
+    /// `BlockchainError::UnknownTransaction` if no block includes the
+    /// transaction.
+    pub fn verify_memo_commitment(&self, tx_id: &TxHash, revealed_memo: &str) -> Result<bool> {
+        let record = self.client()?
+            .get_transaction(tx_id)?
+            .ok_or(ctb_core::BlockchainError::UnknownTransaction { tx_id: *tx_id })?;
+        Ok(record.transaction.memo.is_some_and(|memo| memo.verify(revealed_memo)))
+    }
+    
+    /// Gets an account's transactions in flight, in nonce order
+    pub fn pending_transactions(&self, address: &str) -> Vec<Reservation> {
+        self.pending.lock().unwrap().reservations(address)
+    }
+    
+    /// Releases the reservations of an account's transactions that were included in blocks
+    ///
+    /// Transactions that failed in a block are released too. This happens
+    /// whenever the account creates a transaction; returns the number
+    /// released.
+    pub fn release_confirmed(&self, address: &str) -> Result<usize> {
+        let mut pending = self.pending.lock().unwrap();
+        self.release_confirmed_in(&mut pending, address)
+    }
+    
+    /// Releases the reservation of a transaction in flight, returning whether it was in flight
+    ///
+    /// If the node already has the transaction, it may still be included in
+    /// a block, and its spend is then taken from the confirmed balance.
+    /// Unless a later transaction of its sender is in flight, the next one
+    /// created gets its nonce: whichever of the two a block includes first
+    /// uses the nonce, and the other can't be included after it.
+    pub fn cancel_pending(&self, tx_id: &TxHash) -> bool {
+        self.pending.lock().unwrap().release(tx_id).is_some()
+    }
+    
+    /// Marks a transaction in flight as failed, returning whether it was in flight
+    ///
+    /// For transactions the node dropped without including them, as it
+    /// announces with a `TransactionDropped` event once a conflicting spend
+    /// from the same account is confirmed. Their reservations are released.
+    pub fn mark_failed(&self, tx_id: &TxHash) -> bool {
+        self.confirmations.lock().unwrap().untrack(tx_id);
+        self.pending.lock().unwrap().fail(tx_id)
+    }
+    
+    /// Gets an account's transactions marked failed, in nonce order
+    pub fn failed_transactions(&self, address: &str) -> Vec<Reservation> {
+        self.pending.lock().unwrap().failed(address)
+    }
+    
+    /// Creates a request for a payment of `amount` to one of the wallet's accounts
+    ///
+    /// The payer sends a transfer to the account carrying `reference` as its
+    /// data (see `payments`). The request is watched from then on, in the
+    /// blocks given to `process_block`.
+    pub fn create_payment_request(&self, account: &str, amount: u64, reference: &str) -> Result<PaymentRequest> {
+        if self.wallet.lock().unwrap().get_account(account).is_none() {
+            return Err(WalletError::UnknownAccount { address: account.to_string() });
+        }
+        self.payments.lock().unwrap().create(account, amount, reference, ctb_core::current_timestamp())
+    }
+    
+    /// Gets a payment request by its reference
+    pub fn payment_request(&self, reference: &str) -> Option<PaymentRequest> {
+        self.payments.lock().unwrap().request(reference).cloned()
+    }
+    
+    /// Gets the status of a payment request
+    pub fn payment_status(&self, reference: &str) -> Option<PaymentStatus> {
+        self.payments.lock().unwrap().status(reference).cloned()
+    }
+    
+    /// Gets the payments seen for a request, late ones included
+    pub fn received_payments(&self, reference: &str) -> Vec<ReceivedPayment> {
+        self.payments.lock().unwrap().payments(reference)
+    }
+    
+    /// Changes the confirmations payments need and how long new requests stay open
+    pub fn set_payment_config(&self, config: PaymentConfig) {
+        self.payments.lock().unwrap().set_config(config);
+    }
+    
+    /// Subscribes to the changes of payment requests' statuses
+    pub fn subscribe_payments(&self) -> Receiver<PaymentEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.payment_subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+    
+    /// Looks for payments to the wallet's requests in the chain's next block
+    ///
+    /// Blocks must be given in chain order. Returns the requests whose
+    /// status changed, which are also sent to subscribers. When connected,
+    /// the node's finalized height is asked for first, so payments in
+    /// finalized blocks count whatever their confirmations; if the node
+    /// can't say, the last height it gave is used.
+    pub fn process_block(&self, block: &Block) -> Vec<PaymentEvent> {
+        let tip = self.client.as_ref().and_then(|client| client.chain_tip().ok());
+        let events = {
+            let mut payments = self.payments.lock().unwrap();
+            if let Some(tip) = tip {
+                payments.set_finalized_height(tip.finalized_height);
+            }
+            payments.process_block(block)
+        };
+        if !events.is_empty() {
+            self.payment_subscribers.lock().unwrap().retain(|subscriber| {
+                events.iter().all(|event| subscriber.send(event.clone()).is_ok())
+            });
+        }
+        events
+    }
+    
+    /// Gets the confirmations the wallet needs for a transaction to be safe
+    pub fn safe_confirmations(&self) -> u64 {
+        self.confirmations.lock().unwrap().safe_confirmations()
+    }
+    
+    /// Changes the confirmations the wallet needs for a transaction to be safe
+    ///
+    /// Applies to the statuses of its transactions and history from then
+    /// on. Payment requests count their payments by the confirmations of
+    /// their `PaymentConfig` instead.
+    pub fn set_safe_confirmations(&self, safe_confirmations: u64) {
+        self.confirmations.lock().unwrap().set_safe_confirmations(safe_confirmations);
+    }
+    
+    /// Asks the node how settled a transaction is, by the wallet's confirmations for safety
+    pub fn transaction_status(&self, tx_id: &TxHash) -> Result<ConfirmationStatus> {
+        let safe_confirmations = self.safe_confirmations();
+        Ok(self.client()?.get_transaction_status(tx_id, safe_confirmations)?)
+    }
+    
+    /// Subscribes to the moves of the transactions sent between stages, see `confirmations`
+    pub fn subscribe_confirmations(&self) -> Receiver<ConfirmationEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.confirmation_subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+    
+    /// Judges the transactions sent again at the chain's tip
+    ///
+    /// Every transaction sent through `send_transaction` is followed until
+    /// it's finalized or marked failed. Returns the moves between stages,
+    /// which are also sent to subscribers; call after each new block.
+    pub fn refresh_confirmations(&self) -> Result<Vec<ConfirmationEvent>> {
+        let client = self.client()?;
+        let tracked = self.confirmations.lock().unwrap().tracked();
+        let mut heights = Vec::with_capacity(tracked.len());
+        for tx_id in tracked {
+            heights.push((tx_id, client.get_transaction(&tx_id)?.map(|record| record.block_height)));
+        }
+        let tip = client.chain_tip()?;
+        
+        let events: Vec<ConfirmationEvent> = {
+            let mut confirmations = self.confirmations.lock().unwrap();
+            heights.iter().filter_map(|(tx_id, included_at)| confirmations.update(tx_id, *included_at, &tip)).collect()
+        };
+        if !events.is_empty() {
+            self.confirmation_subscribers.lock().unwrap().retain(|subscriber| {
+                events.iter().all(|event| subscriber.send(*event).is_ok())
+            });
+        }
+        Ok(events)
+    }
+    
+    /// Connects the API to a node
+    pub fn set_client(&mut self, client: Arc<dyn ChainClient>) {
+        self.client = Some(client);
+    }
+    
+    /// Gets the token balance of an address by calling the token's `balanceOf`
+    pub fn get_token_balance(&self, token: &str, address: &str) -> Result<u128> {
+        let client = self.client()?;
+        
+        let output = match client.call_contract(token, &BALANCE_OF_SELECTOR, &address_word(address), address)? {
+            CallOutcome::Returned(output) => output,
+            CallOutcome::Reverted { reason, .. } => return Err(reverted(reason)),
+        };
+        if output.len() != 32 {
+            return Err(WalletError::BlockchainError(ctb_core::BlockchainError::StateError(
+                format!("Unexpected balanceOf output of {} bytes", output.len())
+            )));
+        }
+        
+        // Balances above u128::MAX don't fit the wallet's amounts
+        if output[..16].iter().any(|&byte| byte != 0) {
+            return Err(WalletError::AccountError(format!("Token balance of {} is too large", address)));
+        }
+        
+        let mut balance = [0u8; 16];
+        balance.copy_from_slice(&output[16..]);
+        Ok(u128::from_be_bytes(balance))
+    }
+    
+    /// Creates and signs a transaction sending tokens from the default account
+    ///
+    /// The transaction calls the token's `transfer` and pays the next block's
+    /// base fee; the caller submits it.
+    pub fn send_token(&self, token: &str, to: &str, amount: u128) -> Result<Transaction> {
+        let client = self.client()?;
+        let sender = self.get_default_account()?
+            .ok_or(WalletError::NoDefaultAccount)?
+            .address;
+        
+        let mut data = TRANSFER_SELECTOR.to_vec();
+        data.extend_from_slice(&address_word(to));
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&amount.to_be_bytes());
+        
+        let tx = Transaction::new_contract_call(sender, token.to_string(), 0, data, TOKEN_TRANSFER_GAS_LIMIT, client.next_base_fee())?;
+        self.reserve_and_sign(tx)
+    }
+    
+    /// Estimates the gas limit a transaction should be sent with
+    ///
+    /// Fails with `WalletError::TransactionReverted` if the transaction
+    /// would revert against the current state.
+    pub fn estimate_gas(&self, tx: &Transaction) -> Result<u64> {
+        match self.client()?.estimate_gas(tx)? {
+            GasEstimate::Gas(gas) => Ok(gas),
+            GasEstimate::Reverted(reason) => Err(reverted(reason)),
+        }
+    }
+    
+    /// Calls a contract function by name in a transaction, returning the transaction's ID
+    ///
+    /// The function and its argument types come from the contract's ABI,
+    /// loaded from the connected node. The transaction sends `value` from
+    /// `sender`, pays the next block's base fee, and gets `gas`, or the
+    /// estimated gas if `None`. It's signed and submitted to the node.
+    ///
+    /// Fails with `WalletError::AbiMismatch` if the contract has no such
+    /// function or the arguments don't fit it, and with
+    /// `WalletError::TransactionReverted` if the gas is estimated and the
+    /// call would revert.
+    pub fn call_contract(
+        &self,
+        sender: &str,
+        contract: &str,
+        function_name: &str,
+        args: Vec<Value>,
+        value: u64,
+        gas: Option<u64>,
+    ) -> Result<TxHash> {
+        let client = self.client()?;
+        let data = self.encode_call(contract, function_name, &args)?.1;
+        let gas_price = client.next_base_fee();
+        
+        let gas_limit = match gas {
+            Some(gas) => gas,
+            None => {
+                // The node estimates up to its own gas cap, whatever the transaction's limit
+                let unsigned = Transaction::new_contract_call(
+                    sender.to_string(),
+                    contract.to_string(),
+                    value,
+                    data.clone(),
+                    0,
+                    gas_price,
+                )?;
+                self.estimate_gas(&unsigned)?
+            }
+        };
+        
+        let tx = Transaction::new_contract_call(sender.to_string(), contract.to_string(), value, data, gas_limit, gas_price)?;
+        let tx = self.reserve_and_sign(tx)?;
+        self.send_transaction(&tx)
+    }
+    
+    /// Calls a contract function by name against the current state, decoding what it returns
+    ///
+    /// Nothing is recorded on chain. The call is made from the default
+    /// account, if there is one. Fails with `WalletError::AbiMismatch` like
+    /// `call_contract`, and with `WalletError::TransactionReverted` if the
+    /// function reverts.
+    pub fn read_contract(&self, contract: &str, function_name: &str, args: Vec<Value>) -> Result<Vec<Value>> {
+        let client = self.client()?;
+        let (function, data) = self.encode_call(contract, function_name, &args)?;
+        let sender = self.get_default_account()?.map(|account| account.address).unwrap_or_default();
+        
+        match client.call_contract(contract, &function.signature, &data[4..], &sender)? {
+            CallOutcome::Returned(output) => abi::decode(&function.outputs, &output)
+                .map_err(|e| WalletError::AbiMismatch(format!("Cannot decode the output of {}: {}", function.name, e))),
+            CallOutcome::Reverted { reason, .. } => Err(reverted(reason)),
+        }
+    }
+    
+    /// Finds a contract function by name and argument count and encodes a call to it
+    fn encode_call(&self, contract: &str, function_name: &str, args: &[Value]) -> Result<(FunctionABI, Vec<u8>)> {
+        let functions = self.client()?
+            .get_contract_abi(contract)?
+            .ok_or_else(|| WalletError::ContractNotFound(contract.to_string()))?;
+        
+        let mut candidates = functions.into_iter().filter(|function| function.name == function_name).peekable();
+        let Some(first) = candidates.peek().cloned() else {
+            return Err(WalletError::AbiMismatch(format!("Contract {} has no function {}", contract, function_name)));
+        };
+        let function = candidates.find(|function| function.inputs.len() == args.len()).unwrap_or(first);
+        
+        let arguments = abi::encode(&function.inputs, args)
+            .map_err(|e| WalletError::AbiMismatch(format!("{}: {}", function.name, e)))?;
+        let mut data = function.signature.to_vec();
+        data.extend_from_slice(&arguments);
+        Ok((function, data))
+    }
+    
+    /// Submits a signed transaction to the connected node, returning its ID
+    ///
+    /// A transaction the node refuses is no longer in flight.
+    pub fn send_transaction(&self, tx: &Transaction) -> Result<TxHash> {
+        match self.client()?.submit_transaction(tx) {
+            Ok(id) => {
+                self.confirmations.lock().unwrap().track(id);
+                Ok(id)
+            }
+            Err(e) => {
+                self.pending.lock().unwrap().release(&tx.id);
+                Err(e.into())
+            }
+        }
+    }
+    
+    /// Signs a transaction with its sender's next nonce and reserves what it can spend
+    ///
+    /// The ledger stays locked throughout, so transactions created at once
+    /// are checked against the balance, and get their nonces, one after
+    /// another. Without a node, nonces count from zero.
+    fn reserve_and_sign(&self, mut tx: Transaction) -> Result<Transaction> {
+        let mut pending = self.pending.lock().unwrap();
+        
+        let mut confirmed_nonce = 0;
+        if let Some(client) = &self.client {
+            self.release_confirmed_in(&mut pending, &tx.sender)?;
+            let balance = client.get_balance(&tx.sender)?;
+            let in_flight = pending.pending_spend(&tx.sender);
+            let spend = Reservation::of(&tx).spend;
+            if in_flight.saturating_add(spend) > balance {
+                return Err(WalletError::InsufficientFunds {
+                    address: tx.sender.clone(),
+                    required: spend,
+                    in_flight,
+                    available: balance,
+                });
+            }
+            confirmed_nonce = client.get_nonce(&tx.sender)?;
+        }
+        
+        tx.nonce = pending.next_nonce(&tx.sender, confirmed_nonce);
+        tx.timestamp = pending.next_timestamp(&tx.sender, tx.timestamp);
+        tx.id = tx.calculate_hash()?;
+        let tx = self.wallet.lock().unwrap().sign_transaction(tx)?;
+        
+        pending.reserve(&tx);
+        Ok(tx)
+    }
+    
+    /// Releases the reservations of an account's transactions found in its history
+    fn release_confirmed_in(&self, pending: &mut PendingLedger, address: &str) -> Result<usize> {
+        if pending.reservations(address).is_empty() {
+            return Ok(0);
+        }
+        
+        let mut released = 0;
+        for record in self.client()?.get_history(address, PENDING_HISTORY_LIMIT)? {
+            if record.transaction.sender == address && pending.release(&record.transaction.id).is_some() {
+                released += 1;
+            }
+        }
+        Ok(released)
     }
     
-    /// Gets the wallet's balance by querying the blockchain
-    pub fn get_balance(&self, address: &str) -> Result<u64> {
-        // In a real implementation, this would query the blockchain
-        // for the account balance
+    /// Gets the most recent transactions sent or received by an address, newest first
+    #[deprecated(note = "use `list_history` to page through them")]
+    pub fn get_history(&self, address: &str, limit: usize) -> Result<Vec<TransactionRecord>> {
+        let mut history = self.client()?.get_history(address, limit)?;
+        self.judge(&mut history)?;
+        Ok(history)
+    }
+    
+    /// Gets a page of the transactions sent or received by an address, newest first by default
+    ///
+    /// Their statuses are judged by the wallet's confirmations for safety.
+    pub fn list_history(&self, address: &str, request: &PageRequest) -> Result<Page<TransactionRecord>> {
+        let mut page = self.client()?.get_history_page(address, request)?;
+        self.judge(&mut page.items)?;
+        Ok(page)
+    }
+    
+    /// Judges records' statuses again by the wallet's confirmations for safety
+    fn judge(&self, records: &mut [TransactionRecord]) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let tip = self.client()?.chain_tip()?;
+        let safe_confirmations = self.safe_confirmations();
+        for record in records {
+            record.judge(&tip, safe_confirmations);
+        }
+        Ok(())
+    }
+    
+    /// Exports the confirmed transactions of one of the wallet's accounts, or of all of them, for accounting
+    ///
+    /// Only transactions in blocks timestamped from `from` to `to`, both
+    /// included, are written to `out`, one at a time, in chain order, in
+    /// `format` (see `export`). Returns how many entries were written.
+    pub fn export_history(
+        &self,
+        account: Option<&str>,
+        from: Option<u64>,
+        to: Option<u64>,
+        format: &ExportFormat,
+        mut out: impl Write,
+    ) -> Result<usize> {
+        let accounts: Vec<String> = {
+            let wallet = self.wallet.lock().unwrap();
+            match account {
+                Some(account) if wallet.get_account(account).is_none() => {
+                    return Err(WalletError::UnknownAccount { address: account.to_string() });
+                }
+                Some(account) => vec![account.to_string()],
+                None => wallet.get_accounts().into_iter().map(|account| account.address.clone()).collect(),
+            }
+        };
+        
+        let client = self.client()?;
+        let mut entries = Vec::new();
+        for account in &accounts {
+            let history = client.get_history(account, EXPORT_HISTORY_LIMIT)?;
+            let balance = client.get_balance(account)?;
+            entries.extend(export::entries(account, &history, balance));
+        }
         
-        // For now, we'll just return a dummy balance
-        Ok(1000)
+        // Stable, so each account's entries in a block keep their order
+        entries.sort_by_key(|entry| entry.block_height);
+        entries.retain(|entry| {
+            from.is_none_or(|from| entry.timestamp >= from) && to.is_none_or(|to| entry.timestamp <= to)
+        });
+        
+        export::write(format, &entries, &mut out)?;
+        out.flush()?;
+        Ok(entries.len())
+    }
+    
+    fn client(&self) -> Result<&Arc<dyn ChainClient>> {
+        self.client.as_ref().ok_or(WalletError::NotConnected)
     }
+    
+    /// Gets the balance of an address by querying the connected node
+    pub fn get_balance(&self, address: &str) -> Result<Amount> {
+        Ok(Amount::from_base_units(self.client()?.get_balance(address)?))
+    }
+    
+    /// Gets a fee oracle asking the connected node
+    pub fn fee_oracle(&self) -> Result<NodeFeeOracle> {
+        Ok(NodeFeeOracle::new(Arc::clone(self.client()?)))
+    }
+}
+
+/// Builds the error of a call that would revert
+fn reverted(reason: Option<String>) -> WalletError {
+    WalletError::TransactionReverted(reason.unwrap_or_else(|| "no reason given".to_string()))
+}
+
+/// Encodes an address as a left-padded ABI word
+fn address_word(address: &str) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(&to_evm_address(address));
+    word
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+//! Following the wallet's transactions from the mempool to finality
+//!
+//! The `ConfirmationTracker` keeps the `ConfirmationStatus` of each
+//! transaction the wallet sent (see `ctb_core::confirmation`), judged by the
+//! wallet's own number of confirmations for a transaction to be safe. Each
+//! update judges a transaction at the chain's tip again, and makes a
+//! `ConfirmationEvent` if it moved to another stage: when it's included in
+//! a block, when it becomes safe, when it's finalized, and when a
+//! reorganization takes its block out of the chain, demoting it to
+//! pending. New confirmations within a stage make no event. A transaction
+//! that skipped stages between updates, say from pending to safe, makes
+//! one event, of where it is now.
+//!
+//! Finalized transactions can't move again, so they stop being tracked.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::confirmation::{ChainTip, ConfirmationStatus, DEFAULT_SAFE_CONFIRMATIONS};
+use ctb_core::TxHash;
+
+/// Move of a tracked transaction to another stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfirmationEvent {
+    /// ID of the transaction
+    pub tx_id: TxHash,
+    
+    /// Status it moved to
+    pub status: ConfirmationStatus,
+    
+    /// Status it had before
+    pub previous: ConfirmationStatus,
+}
+
+impl ConfirmationEvent {
+    /// Checks whether a reorganization took the transaction out of the chain
+    pub fn is_demotion(&self) -> bool {
+        self.previous.is_included() && !self.status.is_included()
+    }
+}
+
+/// Statuses of the transactions being followed, see the module documentation
+#[derive(Debug)]
+pub struct ConfirmationTracker {
+    /// Confirmations a transaction needs to be safe
+    safe_confirmations: u64,
+    
+    tracked: HashMap<TxHash, ConfirmationStatus>,
+}
+
+impl Default for ConfirmationTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAFE_CONFIRMATIONS)
+    }
+}
+
+impl ConfirmationTracker {
+    /// Creates a tracker counting transactions with `safe_confirmations` as safe
+    pub fn new(safe_confirmations: u64) -> Self {
+        Self { safe_confirmations, tracked: HashMap::new() }
+    }
+    
+    /// Gets the confirmations a transaction needs to be safe
+    pub fn safe_confirmations(&self) -> u64 {
+        self.safe_confirmations
+    }
+    
+    /// Changes the confirmations a transaction needs to be safe, from the next update on
+    pub fn set_safe_confirmations(&mut self, safe_confirmations: u64) {
+        self.safe_confirmations = safe_confirmations;
+    }
+    
+    /// Starts following a transaction, pending until an update finds it in a block
+    pub fn track(&mut self, tx_id: TxHash) {
+        self.tracked.entry(tx_id).or_default();
+    }
+    
+    /// Stops following a transaction, returning its last status if it was followed
+    pub fn untrack(&mut self, tx_id: &TxHash) -> Option<ConfirmationStatus> {
+        self.tracked.remove(tx_id)
+    }
+    
+    /// Gets the last status of a transaction being followed
+    pub fn status(&self, tx_id: &TxHash) -> Option<ConfirmationStatus> {
+        self.tracked.get(tx_id).copied()
+    }
+    
+    /// Gets the transactions being followed, in ID order
+    pub fn tracked(&self) -> Vec<TxHash> {
+        let mut tracked: Vec<TxHash> = self.tracked.keys().copied().collect();
+        tracked.sort();
+        tracked
+    }
+    
+    /// Judges a followed transaction included at a height, or in no block, at a tip
+    ///
+    /// Returns the event of its move if it moved to another stage; nothing
+    /// for a transaction not followed.
+    pub fn update(&mut self, tx_id: &TxHash, included_at: Option<u64>, tip: &ChainTip) -> Option<ConfirmationEvent> {
+        let previous = self.tracked.get_mut(tx_id)?;
+        let status = tip.status(included_at, self.safe_confirmations);
+        let event = (!status.same_stage(previous)).then_some(ConfirmationEvent { tx_id: *tx_id, status, previous: *previous });
+        *previous = status;
+        if status.is_finalized() {
+            self.tracked.remove(tx_id);
+        }
+        event
+    }
+}
\ No newline at end of file
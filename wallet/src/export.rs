@@ -0,0 +1,372 @@
+//! Exporting a wallet's transaction history for accounting
+//!
+//! `WalletApi::export_history` writes the confirmed transactions of one of
+//! the wallet's accounts, or of all of them, as CSV with a choice of columns
+//! (see `Column`), as OFX or as QIF. Each entry is a transaction as one
+//! account sees it: whether it came in, went out or both, from or to whom,
+//! the amount moved and the fee paid, all as amounts of GENX (see
+//! `ctb_core::units`). A transaction that failed in its block moved nothing,
+//! but its sender still paid its fee. A transfer between two of the
+//! wallet's accounts is an entry for each.
+//!
+//! Entries carry the account's running balance after them, computed in
+//! chain order. The history only shows the account's own transactions, so
+//! the running balance works back from the balance the account has now:
+//! changes the history doesn't show, such as storage deposits or transfers
+//! made by contracts, are counted in the balance before the first entry.
+//! Entries are timestamped with the time of their block.
+
+use std::io::{self, Write};
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::api::TransactionRecord;
+use ctb_core::units::format_genx;
+use ctb_core::TxHash;
+
+/// Most history entries of an account exported
+pub const EXPORT_HISTORY_LIMIT: usize = 100_000;
+
+/// Column of a CSV export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    /// The wallet's account the entry is for
+    Account,
+    
+    /// Time of the block, in UTC
+    Timestamp,
+    
+    /// `in`, `out` or `self`
+    Direction,
+    
+    /// The other party to the transaction
+    Counterparty,
+    
+    /// Amount moved, in GENX
+    Amount,
+    
+    /// Amount moved, in base units
+    AmountBaseUnits,
+    
+    /// Fee paid by the account, in GENX
+    Fee,
+    
+    /// ID of the transaction
+    TxId,
+    
+    /// Height of the block
+    BlockHeight,
+    
+    /// The account's balance after the transaction, in GENX
+    RunningBalance,
+}
+
+impl Column {
+    /// Every column, in the order exported by default
+    pub const ALL: &'static [Column] = &[
+        Column::Account,
+        Column::Timestamp,
+        Column::Direction,
+        Column::Counterparty,
+        Column::Amount,
+        Column::AmountBaseUnits,
+        Column::Fee,
+        Column::TxId,
+        Column::BlockHeight,
+        Column::RunningBalance,
+    ];
+    
+    /// Gets the column's name, as written in the header row
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Account => "account",
+            Column::Timestamp => "timestamp",
+            Column::Direction => "direction",
+            Column::Counterparty => "counterparty",
+            Column::Amount => "amount",
+            Column::AmountBaseUnits => "amount_base_units",
+            Column::Fee => "fee",
+            Column::TxId => "tx_id",
+            Column::BlockHeight => "block_height",
+            Column::RunningBalance => "running_balance",
+        }
+    }
+}
+
+/// Format of an export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row, with the given columns
+    Csv(Vec<Column>),
+    
+    /// An OFX 1.02 bank statement for each account
+    Ofx,
+    
+    /// A QIF bank register for each account
+    Qif,
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        ExportFormat::Csv(Column::ALL.to_vec())
+    }
+}
+
+/// How a transaction moved GENX for an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Received by the account
+    In,
+    
+    /// Sent by the account
+    Out,
+    
+    /// Sent by the account to itself
+    #[serde(rename = "self")]
+    SelfTransfer,
+}
+
+impl Direction {
+    /// Gets the direction's name, as exported
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+            Direction::SelfTransfer => "self",
+        }
+    }
+}
+
+/// A transaction as one account sees it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportEntry {
+    /// The wallet's account
+    pub account: String,
+    
+    /// Height of the block the transaction was included in
+    pub block_height: u64,
+    
+    /// Timestamp of the block
+    pub timestamp: u64,
+    
+    /// ID of the transaction
+    pub tx_id: TxHash,
+    
+    /// How the transaction moved GENX for the account
+    pub direction: Direction,
+    
+    /// The other party to the transaction, or the account itself
+    pub counterparty: String,
+    
+    /// Amount moved, nothing if the transaction failed
+    pub amount: u64,
+    
+    /// Fee paid by the account
+    pub fee: u64,
+    
+    /// The account's balance after the transaction, in base units
+    pub balance: i128,
+}
+
+impl ExportEntry {
+    /// Gets how the transaction changed the account's balance, in base units
+    pub fn change(&self) -> i128 {
+        match self.direction {
+            Direction::In => self.amount as i128,
+            Direction::Out => -(self.amount as i128) - self.fee as i128,
+            Direction::SelfTransfer => -(self.fee as i128),
+        }
+    }
+}
+
+/// Makes the entries of an account's history, in chain order
+///
+/// `history` is newest first, as `ChainClient::get_history` returns it,
+/// and `balance` the account's balance now.
+pub fn entries(account: &str, history: &[TransactionRecord], balance: u64) -> Vec<ExportEntry> {
+    let mut entries: Vec<ExportEntry> = history
+        .iter()
+        .rev()
+        .map(|record| {
+            let tx = &record.transaction;
+            let (direction, counterparty) = match (tx.sender == account, tx.recipient == account) {
+                (true, true) => (Direction::SelfTransfer, account),
+                (true, false) => (Direction::Out, tx.recipient.as_str()),
+                _ => (Direction::In, tx.sender.as_str()),
+            };
+            ExportEntry {
+                account: account.to_string(),
+                block_height: record.block_height,
+                timestamp: record.block_timestamp,
+                tx_id: tx.id,
+                direction,
+                counterparty: counterparty.to_string(),
+                amount: if record.success { tx.amount } else { 0 },
+                fee: if direction == Direction::In { 0 } else { record.fee_paid },
+                balance: 0,
+            }
+        })
+        .collect();
+    
+    let mut balance = balance as i128 - entries.iter().map(ExportEntry::change).sum::<i128>();
+    for entry in &mut entries {
+        balance += entry.change();
+        entry.balance = balance;
+    }
+    entries
+}
+
+/// Writes entries in a format
+pub fn write(format: &ExportFormat, entries: &[ExportEntry], out: &mut impl Write) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv(columns) => write_csv(columns, entries, out),
+        ExportFormat::Ofx => write_ofx(entries, out),
+        ExportFormat::Qif => write_qif(entries, out),
+    }
+}
+
+/// Writes entries as CSV, a header row then a row for each entry
+pub fn write_csv(columns: &[Column], entries: &[ExportEntry], out: &mut impl Write) -> io::Result<()> {
+    let header: Vec<&str> = columns.iter().map(|column| column.name()).collect();
+    writeln!(out, "{}", header.join(","))?;
+    
+    for entry in entries {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| match column {
+                Column::Account => csv_field(&entry.account),
+                Column::Timestamp => utc_time(entry.timestamp),
+                Column::Direction => entry.direction.name().to_string(),
+                Column::Counterparty => csv_field(&entry.counterparty),
+                Column::Amount => format_genx(entry.amount),
+                Column::AmountBaseUnits => entry.amount.to_string(),
+                Column::Fee => format_genx(entry.fee),
+                Column::TxId => entry.tx_id.to_string(),
+                Column::BlockHeight => entry.block_height.to_string(),
+                Column::RunningBalance => format_signed(entry.balance),
+            })
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes entries as OFX 1.02, a bank statement for each account
+///
+/// Amounts are signed changes to the account's balance, fees included, in
+/// the currency `GENX`.
+pub fn write_ofx(entries: &[ExportEntry], out: &mut impl Write) -> io::Result<()> {
+    write!(
+        out,
+        "OFXHEADER:100\r\nDATA:OFXSGML\r\nVERSION:102\r\nSECURITY:NONE\r\nENCODING:USASCII\r\nCHARSET:1252\r\n\
+         COMPRESSION:NONE\r\nOLDFILEUID:NONE\r\nNEWFILEUID:NONE\r\n\r\n<OFX>\r\n<BANKMSGSRSV1>\r\n"
+    )?;
+    for (index, account) in accounts(entries).into_iter().enumerate() {
+        let statement: Vec<&ExportEntry> = entries.iter().filter(|entry| entry.account == account).collect();
+        let (first, last) = (statement[0], statement[statement.len() - 1]);
+        write!(
+            out,
+            "<STMTTRNRS>\r\n<TRNUID>{}\r\n<STATUS>\r\n<CODE>0\r\n<SEVERITY>INFO\r\n</STATUS>\r\n<STMTRS>\r\n<CURDEF>GENX\r\n\
+             <BANKACCTFROM>\r\n<BANKID>GENX\r\n<ACCTID>{}\r\n<ACCTTYPE>CHECKING\r\n</BANKACCTFROM>\r\n\
+             <BANKTRANLIST>\r\n<DTSTART>{}\r\n<DTEND>{}\r\n",
+            index + 1,
+            sgml_text(account),
+            ofx_time(first.timestamp),
+            ofx_time(last.timestamp)
+        )?;
+        for entry in statement {
+            write!(
+                out,
+                "<STMTTRN>\r\n<TRNTYPE>{}\r\n<DTPOSTED>{}\r\n<TRNAMT>{}\r\n<FITID>{}\r\n<NAME>{}\r\n<MEMO>Block {}, fee {}\r\n</STMTTRN>\r\n",
+                if entry.change() >= 0 { "CREDIT" } else { "DEBIT" },
+                ofx_time(entry.timestamp),
+                format_signed(entry.change()),
+                entry.tx_id,
+                sgml_text(&entry.counterparty),
+                entry.block_height,
+                format_genx(entry.fee)
+            )?;
+        }
+        write!(
+            out,
+            "</BANKTRANLIST>\r\n<LEDGERBAL>\r\n<BALAMT>{}\r\n<DTASOF>{}\r\n</LEDGERBAL>\r\n</STMTRS>\r\n</STMTTRNRS>\r\n",
+            format_signed(last.balance),
+            ofx_time(last.timestamp)
+        )?;
+    }
+    write!(out, "</BANKMSGSRSV1>\r\n</OFX>\r\n")
+}
+
+/// Writes entries as QIF, a bank register for each account
+///
+/// Amounts are signed changes to the account's balance, fees included.
+pub fn write_qif(entries: &[ExportEntry], out: &mut impl Write) -> io::Result<()> {
+    for account in accounts(entries) {
+        writeln!(out, "!Account\nN{}\nTBank\n^\n!Type:Bank", account)?;
+        for entry in entries.iter().filter(|entry| entry.account == account) {
+            writeln!(
+                out,
+                "D{}\nT{}\nP{}\nN{}\nMBlock {}, fee {}\n^",
+                utc_time(entry.timestamp).split('T').next().unwrap_or_default(),
+                format_signed(entry.change()),
+                entry.counterparty,
+                entry.tx_id,
+                entry.block_height,
+                format_genx(entry.fee)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the accounts of entries, in the order they first appear
+fn accounts(entries: &[ExportEntry]) -> Vec<&str> {
+    let mut accounts: Vec<&str> = Vec::new();
+    for entry in entries {
+        if !accounts.contains(&entry.account.as_str()) {
+            accounts.push(&entry.account);
+        }
+    }
+    accounts
+}
+
+/// Writes signed base units as GENX
+fn format_signed(units: i128) -> String {
+    let genx = format_genx(u64::try_from(units.unsigned_abs()).unwrap_or(u64::MAX));
+    if units < 0 {
+        format!("-{}", genx)
+    } else {
+        genx
+    }
+}
+
+/// Writes a Unix timestamp in UTC, as `2024-01-31T12:00:00Z`
+fn utc_time(timestamp: u64) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default()
+}
+
+/// Writes a Unix timestamp as an OFX date and time in UTC
+fn ofx_time(timestamp: u64) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| time.format("%Y%m%d%H%M%S[0:GMT]").to_string())
+        .unwrap_or_default()
+}
+
+/// Quotes a CSV field if it holds a comma, a quote or a line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Escapes the characters OFX's SGML reserves
+fn sgml_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
\ No newline at end of file
@@ -0,0 +1,110 @@
+//! Choosing fees from the node's mempool
+//!
+//! A `FeeOracle` suggests the rate a transaction should pay to be included
+//! within a number of blocks. The `NodeFeeOracle` asks the connected node:
+//! it reads the node's fee histogram (see `ctb_core::fee_market::FeeHistogram`)
+//! for the rates worth trying, since paying anywhere between two buckets
+//! puts a transaction behind the same transactions, and has the node
+//! estimate when each would be included. It suggests the lowest rate
+//! expected in time, or the highest tried when none is.
+//!
+//! Gas prices start at the next block's base fee, below which a metered
+//! transaction can't be included; flat fees start at zero.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::fee_market::{EstimatedBlocks, FeeRate};
+
+use crate::api::ChainClient;
+use crate::Result;
+
+/// Source of fee suggestions
+pub trait FeeOracle: Send + Sync {
+    /// Suggests the lowest rate expected to be included within `target_blocks` blocks,
+    /// as a gas price if `metered` and a flat fee otherwise
+    fn suggest(&self, metered: bool, target_blocks: u64) -> Result<FeeSuggestion>;
+}
+
+/// Rate suggested by a `FeeOracle`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeSuggestion {
+    /// Rate to pay
+    pub rate: FeeRate,
+    
+    /// When a transaction paying the rate is expected to be included
+    pub estimate: EstimatedBlocks,
+}
+
+impl FeeSuggestion {
+    /// Whether the transaction is expected to be included within `target_blocks` blocks
+    pub fn meets(&self, target_blocks: u64) -> bool {
+        self.estimate.blocks.is_some_and(|blocks| blocks <= target_blocks)
+    }
+}
+
+/// Fee oracle backed by a node's mempool
+pub struct NodeFeeOracle {
+    client: Arc<dyn ChainClient>,
+}
+
+impl NodeFeeOracle {
+    /// Creates an oracle asking a node
+    pub fn new(client: Arc<dyn ChainClient>) -> Self {
+        Self { client }
+    }
+    
+    /// Gets the rates worth trying, lowest first
+    fn candidates(&self, metered: bool) -> Result<Vec<u64>> {
+        let floor = if metered { self.client.next_base_fee() } else { 0 };
+        let histogram = self.client.fee_histogram()?;
+        let mut rates: Vec<u64> = histogram
+            .buckets()
+            .into_iter()
+            .filter(|bucket| bucket.metered == metered)
+            .map(|bucket| bucket.max_rate.saturating_add(1))
+            .filter(|&rate| rate > floor)
+            .collect();
+        rates.push(floor);
+        rates.sort_unstable();
+        rates.dedup();
+        Ok(rates)
+    }
+}
+
+impl FeeOracle for NodeFeeOracle {
+    fn suggest(&self, metered: bool, target_blocks: u64) -> Result<FeeSuggestion> {
+        let rate = |rate| if metered { FeeRate::GasPrice(rate) } else { FeeRate::FlatFee(rate) };
+        let candidates = self.candidates(metered)?;
+        
+        // Paying more never puts more ahead of a transaction, so the rates
+        // expected in time are all those above the lowest one
+        let (mut low, mut high) = (0, candidates.len() - 1);
+        let mut best = None;
+        while low <= high {
+            let middle = low + (high - low) / 2;
+            let suggestion = FeeSuggestion {
+                rate: rate(candidates[middle]),
+                estimate: self.client.estimate_inclusion(rate(candidates[middle]))?,
+            };
+            if suggestion.meets(target_blocks) {
+                best = Some(suggestion);
+                if middle == 0 {
+                    break;
+                }
+                high = middle - 1;
+            } else {
+                low = middle + 1;
+            }
+        }
+        
+        match best {
+            Some(suggestion) => Ok(suggestion),
+            None => {
+                let highest = rate(candidates[candidates.len() - 1]);
+                Ok(FeeSuggestion { rate: highest, estimate: self.client.estimate_inclusion(highest)? })
+            }
+        }
+    }
+}
\ No newline at end of file
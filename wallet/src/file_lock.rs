@@ -0,0 +1,172 @@
+//! Coordination between processes sharing a wallet file
+//!
+//! The command line wallet and a GUI may have the same wallet file open at
+//! once. Each keeps the accounts in memory and writes all of them back when
+//! it saves, so the second to save would drop whatever the first added.
+//! Two things prevent that:
+//!
+//! - Saves hold an advisory lock, a file named after the wallet's with
+//!   `.lock` appended that records the owner's process ID and a heartbeat
+//!   timestamp (see `FileLock`). A process that dies holding the lock
+//!   leaves the file behind; once its heartbeat is `LOCK_STALE_AFTER`
+//!   seconds old, another process takes the lock over.
+//! - A wallet remembers the hash of its file as it last read or wrote it.
+//!   Holding the lock, a save first checks the file on disk still has that
+//!   hash, and fails with `WalletError::ConcurrentModification` rather than
+//!   overwrite another process's changes. `Wallet::reload` reads them in,
+//!   keeping the wallet unlocked, and the change can then be made again.
+//!
+//! The lock only keeps out processes that take it, which every `Wallet`
+//! does. A wallet opened with `Wallet::load_readonly` never writes, so it
+//! never takes the lock either.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, WalletError};
+
+/// Seconds after its last heartbeat that a lock is taken to be abandoned
+pub const LOCK_STALE_AFTER: u64 = 30;
+
+/// Longest time to wait for another process to release a lock
+pub const LOCK_WAIT: Duration = Duration::from_secs(2);
+
+/// Time between attempts to take a lock another process holds
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Process holding a lock, as its lock file records it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    /// ID of the owning process
+    pub pid: u32,
+    
+    /// When the owner last showed it was alive, in seconds since the Unix epoch
+    pub heartbeat: u64,
+}
+
+impl LockOwner {
+    /// Checks whether the owner has gone `LOCK_STALE_AFTER` seconds without a heartbeat at `now`
+    pub fn is_stale(&self, now: u64) -> bool {
+        now.saturating_sub(self.heartbeat) >= LOCK_STALE_AFTER
+    }
+}
+
+/// Advisory lock on a wallet file, released when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    /// Path of the lock file
+    path: PathBuf,
+    
+    /// What the lock file records
+    owner: LockOwner,
+}
+
+impl FileLock {
+    /// Takes the lock on a wallet file, waiting up to `LOCK_WAIT` for another process to release it
+    ///
+    /// Takes over a lock whose owner is stale. Fails with
+    /// `WalletError::WalletBusy` if the lock is still held after waiting.
+    pub fn acquire(wallet_path: &Path) -> Result<Self> {
+        let path = lock_path(wallet_path);
+        let deadline = Instant::now() + LOCK_WAIT;
+        loop {
+            let owner = LockOwner { pid: std::process::id(), heartbeat: ctb_core::current_timestamp() };
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let lock = Self { path, owner };
+                    if let Err(e) = file.write_all(&lock.contents()?) {
+                        remove_if_exists(&lock.path)?;
+                        return Err(e.into());
+                    }
+                    return Ok(lock);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+            
+            let Some(holder) = read_owner(&path)? else {
+                // Released meanwhile
+                continue;
+            };
+            if holder.is_stale(ctb_core::current_timestamp()) {
+                log::warn!("Taking over the lock on {} that process {} abandoned", wallet_path.display(), holder.pid);
+                remove_if_exists(&path)?;
+            } else if Instant::now() >= deadline {
+                return Err(WalletError::WalletBusy { pid: holder.pid });
+            } else {
+                std::thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+        }
+    }
+    
+    /// Gets the process holding the lock on a wallet file, if any, stale or not
+    pub fn owner(wallet_path: &Path) -> Result<Option<LockOwner>> {
+        read_owner(&lock_path(wallet_path))
+    }
+    
+    /// Records that the owner is still alive, for holders that keep the lock a while
+    pub fn heartbeat(&mut self) -> Result<()> {
+        self.owner.heartbeat = ctb_core::current_timestamp();
+        let mut temp_path = self.path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, self.contents()?)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+    
+    fn contents(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(&self.owner).map_err(|e| WalletError::SerializationError(e.to_string()))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // A lock taken over as stale belongs to another process now
+        if matches!(read_owner(&self.path), Ok(Some(owner)) if owner == self.owner) {
+            if let Err(e) = remove_if_exists(&self.path) {
+                log::warn!("Cannot release the wallet lock {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// Gets the path of the lock file of a wallet file
+fn lock_path(wallet_path: &Path) -> PathBuf {
+    let mut path = wallet_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Reads who holds a lock, or `None` if the lock file doesn't exist
+///
+/// A lock file that can't be parsed, such as one its owner is still
+/// writing, belongs to an unknown process whose heartbeat is the file's
+/// modification time.
+fn read_owner(path: &Path) -> Result<Option<LockOwner>> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if let Ok(owner) = serde_json::from_slice(&contents) {
+        return Ok(Some(owner));
+    }
+    let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let heartbeat = modified.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    Ok(Some(LockOwner { pid: 0, heartbeat }))
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
\ No newline at end of file
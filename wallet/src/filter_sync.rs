@@ -0,0 +1,258 @@
+//! Syncing a watch-only wallet with compact block filters
+//!
+//! A wallet keeping its keys offline still wants its history and balances,
+//! without downloading every block to find its few transactions. The
+//! `FilterSync` downloads each block's compact filter instead (see
+//! `ctb_core::block_filter`), tests its addresses against it, and fetches only
+//! the blocks whose filters match. Filters match some blocks that don't
+//! touch the addresses; those are fetched, found to hold nothing relevant
+//! and discarded, counted in `FilterSyncStats::false_positives`.
+//!
+//! A transaction is relevant if it's sent by or to one of the addresses,
+//! deploys a contract at one, or emits logs from one. Balances are summed
+//! from the relevant transactions: each one's recipient gains its amount
+//! and its sender pays the amount and its fee, unless it failed, when only
+//! the fee is paid. Transfers made by contracts don't appear among a
+//! block's transactions, so they aren't counted.
+//!
+//! The filters are checked against their headers, which chain to one
+//! another (see `BlockFilter::header`), and the blocks fetched against the
+//! filters' block hashes, so a node can't slip in a filter or block of
+//! another chain without it failing as `WalletError::InvalidFilter`. A
+//! wallet that trusts no single node can compare the headers of its tip
+//! with several. When the headers no longer extend those synced, the chain
+//! was reorganized and the sync starts over from the genesis block.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::block::Block;
+use ctb_core::block_filter::BlockFilter;
+use ctb_core::confirmation::{ChainTip, ConfirmationStatus, DEFAULT_SAFE_CONFIRMATIONS};
+use ctb_core::receipt::Receipt;
+use ctb_core::transaction::TransactionType;
+use ctb_core::Hash;
+
+use crate::api::TransactionRecord;
+use crate::{Result, WalletError};
+
+/// Most filters downloaded in one request
+pub const FILTER_BATCH_SIZE: u64 = 1000;
+
+/// Node the filters and blocks are downloaded from
+pub trait FilterSource: Send + Sync {
+    /// Gets up to `count` filter headers from the one at height `start` on, fewer past the tip
+    fn get_filter_headers(&self, start: u64, count: u64) -> ctb_core::Result<Vec<Hash>>;
+    
+    /// Gets up to `count` filters from the one at height `start` on, fewer past the tip
+    fn get_filters(&self, start: u64, count: u64) -> ctb_core::Result<Vec<BlockFilter>>;
+    
+    /// Gets the block at a height with the receipts of its transactions
+    fn get_block(&self, height: u64) -> ctb_core::Result<FilteredBlock>;
+}
+
+/// Block fetched because its filter matched, with its transactions' receipts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilteredBlock {
+    /// The block itself
+    pub block: Block,
+    
+    /// Receipts of the block's transactions, in their order
+    pub receipts: Vec<Receipt>,
+}
+
+/// Filter headers of the heights from `start` on, as `genx_getFilterHeaders` returns them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterHeaders {
+    /// Height of the first header
+    pub start: u64,
+    
+    /// Headers, one per height
+    #[serde(with = "ctb_core::types::hex_serde::seq")]
+    pub headers: Vec<Hash>,
+}
+
+/// What syncing has downloaded so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterSyncStats {
+    /// Filters downloaded and checked
+    pub filters_downloaded: u64,
+    
+    /// Full blocks fetched because their filters matched
+    pub blocks_fetched: u64,
+    
+    /// Blocks fetched that held nothing relevant to the addresses
+    pub false_positives: u64,
+    
+    /// Times the sync started over because the chain was reorganized
+    pub restarts: u64,
+}
+
+/// Sync of the history of a set of addresses, by their blocks' filters
+#[derive(Debug, Clone)]
+pub struct FilterSync {
+    /// Addresses synced
+    addresses: BTreeSet<String>,
+    
+    /// Height of the next filter to download
+    next_height: u64,
+    
+    /// Header of the last filter downloaded, zero before the genesis block's
+    last_header: Hash,
+    
+    /// Relevant transactions, oldest first
+    history: Vec<TransactionRecord>,
+    
+    stats: FilterSyncStats,
+}
+
+impl FilterSync {
+    /// Creates a sync of the addresses' history, starting from the genesis block
+    pub fn new(addresses: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            addresses: addresses.into_iter().collect(),
+            next_height: 0,
+            last_header: [0; 32],
+            history: Vec::new(),
+            stats: FilterSyncStats::default(),
+        }
+    }
+    
+    /// Downloads the filters of the blocks added since the last sync, and the blocks they match
+    ///
+    /// Returns the number of relevant transactions found, all of them
+    /// again if the chain was reorganized and the sync started over.
+    pub fn sync(&mut self, source: &dyn FilterSource) -> Result<usize> {
+        let mut found = 0;
+        loop {
+            // The header before the first new one shows whether the chain synced still stands
+            let previous = self.next_height.checked_sub(1);
+            let mut headers = source.get_filter_headers(previous.unwrap_or(0), FILTER_BATCH_SIZE + 1)?;
+            if previous.is_some() {
+                if headers.first() != Some(&self.last_header) {
+                    log::info!("Filter sync: chain reorganized below height {}, syncing again", self.next_height);
+                    self.restart();
+                    found = 0;
+                    continue;
+                }
+                headers.remove(0);
+            }
+            if headers.is_empty() {
+                break;
+            }
+            headers.truncate(FILTER_BATCH_SIZE as usize);
+            
+            let filters = source.get_filters(self.next_height, headers.len() as u64)?;
+            if filters.len() != headers.len() {
+                return Err(WalletError::InvalidFilter(format!(
+                    "{} filters served from height {} for {} headers",
+                    filters.len(),
+                    self.next_height,
+                    headers.len()
+                )));
+            }
+            for (filter, header) in filters.iter().zip(headers) {
+                if filter.header(&self.last_header) != header {
+                    return Err(WalletError::InvalidFilter(format!("Filter at height {} doesn't match its header", self.next_height)));
+                }
+                self.stats.filters_downloaded += 1;
+                if filter.matches_addresses(&self.addresses) {
+                    found += self.fetch_block(source, filter)?;
+                }
+                self.next_height += 1;
+                self.last_header = header;
+            }
+        }
+        
+        if let Some(height) = self.synced_height() {
+            let tip = ChainTip::new(height, 0);
+            for record in &mut self.history {
+                record.judge(&tip, DEFAULT_SAFE_CONFIRMATIONS);
+            }
+        }
+        Ok(found)
+    }
+    
+    /// Gets the addresses synced
+    pub fn addresses(&self) -> &BTreeSet<String> {
+        &self.addresses
+    }
+    
+    /// Gets the height of the last block synced, if any
+    pub fn synced_height(&self) -> Option<u64> {
+        self.next_height.checked_sub(1)
+    }
+    
+    /// Gets what syncing has downloaded so far
+    pub fn stats(&self) -> FilterSyncStats {
+        self.stats
+    }
+    
+    /// Gets the most recent relevant transactions, newest first
+    ///
+    /// Their statuses are judged at the last block synced, by
+    /// `DEFAULT_SAFE_CONFIRMATIONS`; filters don't tell what's finalized,
+    /// so none is.
+    pub fn history(&self, limit: usize) -> Vec<&TransactionRecord> {
+        self.history.iter().rev().take(limit).collect()
+    }
+    
+    /// Gets the balance of an address, as its synced transactions leave it
+    pub fn balance(&self, address: &str) -> u64 {
+        let mut balance = 0u64;
+        for record in &self.history {
+            let tx = &record.transaction;
+            let amount = if record.success { tx.amount } else { 0 };
+            if tx.recipient == address {
+                balance = balance.saturating_add(amount);
+            }
+            if tx.sender == address {
+                balance = balance.saturating_sub(amount.saturating_add(record.fee_paid));
+            }
+        }
+        balance
+    }
+    
+    /// Fetches the block a filter matched, keeping its relevant transactions and returning how many
+    fn fetch_block(&mut self, source: &dyn FilterSource, filter: &BlockFilter) -> Result<usize> {
+        let FilteredBlock { block, receipts } = source.get_block(self.next_height)?;
+        self.stats.blocks_fetched += 1;
+        if block.hash()? != filter.block_hash || block.header().height != self.next_height {
+            return Err(WalletError::InvalidFilter(format!("Block at height {} isn't the one its filter is for", self.next_height)));
+        }
+        
+        let found = self.history.len();
+        for tx in &block.transactions {
+            let receipt = receipts.iter().find(|receipt| receipt.tx_id == tx.id);
+            let relevant = self.addresses.contains(&tx.sender)
+                || self.addresses.contains(&tx.recipient)
+                || (tx.tx_type == TransactionType::ContractDeploy
+                    && self.addresses.contains(&tx.contract_address()))
+                || receipt.is_some_and(|receipt| receipt.logs.iter().any(|log| self.addresses.contains(&log.address)));
+            if relevant {
+                self.history.push(TransactionRecord {
+                    block_height: self.next_height,
+                    block_timestamp: block.header().timestamp,
+                    success: receipt.is_none_or(|receipt| receipt.success),
+                    fee_paid: receipt.map_or(tx.fee, |receipt| tx.fee_for_gas(receipt.gas_used)),
+                    transaction: tx.clone(),
+                    status: ConfirmationStatus::Pending,
+                });
+            }
+        }
+        let found = self.history.len() - found;
+        if found == 0 {
+            self.stats.false_positives += 1;
+        }
+        Ok(found)
+    }
+    
+    /// Forgets everything synced, to sync again from the genesis block
+    fn restart(&mut self) {
+        self.next_height = 0;
+        self.last_header = [0; 32];
+        self.history.clear();
+        self.stats.restarts += 1;
+    }
+}
\ No newline at end of file
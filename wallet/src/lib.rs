@@ -5,19 +5,40 @@
 
 use std::collections::HashMap;
 use std::fs;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use core::transaction::Transaction;
-use core::{BlockchainError, Result as CoreResult};
+use ctb_core::hashing;
+use ctb_core::paging::{self, Page, PageRequest, SortOrder};
+use ctb_core::signature::SignatureScheme;
+use ctb_core::transaction::Transaction;
+use ctb_core::units::Amount;
+use ctb_core::{Address, BlockchainError, Hash};
+use file_lock::FileLock;
+use password::{PasswordPolicy, UnlockThrottle};
 
 // Export the API module
 pub mod api;
+pub mod confirmations;
+pub mod export;
+pub mod fees;
+pub mod file_lock;
+pub mod filter_sync;
+#[cfg(feature = "testutil")]
+pub mod mock;
+pub mod password;
+pub mod payments;
+pub mod pending;
+pub mod recovery;
+#[cfg(feature = "testutil")]
+pub mod testutil;
 
 /// Wallet error types
+///
+/// Numbered from 4000, see `error_code`.
 #[derive(Debug, Error)]
 pub enum WalletError {
     #[error("IO error: {0}")]
@@ -34,6 +55,83 @@ pub enum WalletError {
     
     #[error("Blockchain error: {0}")]
     BlockchainError(#[from] BlockchainError),
+    
+    #[error("Not connected to a node")]
+    NotConnected,
+    
+    #[error("Transaction would revert: {0}")]
+    TransactionReverted(String),
+    
+    #[error("No contract deployed at {0}")]
+    ContractNotFound(String),
+    
+    #[error("ABI mismatch: {0}")]
+    AbiMismatch(String),
+    
+    #[error("Insufficient funds: {address} needs {required} on top of {in_flight} in flight, but its balance is {available}")]
+    InsufficientFunds { address: String, required: u64, in_flight: u64, available: u64 },
+    
+    #[error("Invalid payment request: {0}")]
+    InvalidPaymentRequest(String),
+    
+    #[error("Wallet is locked")]
+    Locked,
+    
+    #[error("Incorrect password")]
+    IncorrectPassword,
+    
+    #[error("Account {address} not found")]
+    UnknownAccount { address: String },
+    
+    #[error("No default account")]
+    NoDefaultAccount,
+    
+    #[error("Password is too weak: it needs {}", .lacking.join("; "))]
+    WeakPassword { lacking: Vec<String> },
+    
+    #[error("Too many wrong passwords; unlocking is locked out for another {retry_after} seconds")]
+    UnlockThrottled { retry_after: u64 },
+    
+    #[error("Invalid block filter: {0}")]
+    InvalidFilter(String),
+    
+    #[error("Wallet file {path} was changed by another process; reload it and try again")]
+    ConcurrentModification { path: String },
+    
+    #[error("Wallet is open read-only")]
+    ReadOnly,
+    
+    #[error("Wallet file is locked by process {pid}")]
+    WalletBusy { pid: u32 },
+}
+
+impl WalletError {
+    /// Gets the stable numeric code of the error; wrapped blockchain errors keep theirs
+    pub fn error_code(&self) -> u32 {
+        match self {
+            WalletError::IoError(_) => 4000,
+            WalletError::SerializationError(_) => 4001,
+            WalletError::KeyError(_) => 4002,
+            WalletError::AccountError(_) => 4003,
+            WalletError::BlockchainError(e) => e.error_code(),
+            WalletError::NotConnected => 4004,
+            WalletError::TransactionReverted(_) => 4005,
+            WalletError::ContractNotFound(_) => 4006,
+            WalletError::AbiMismatch(_) => 4007,
+            WalletError::InsufficientFunds { .. } => 4008,
+            WalletError::InvalidPaymentRequest(_) => 4009,
+            WalletError::Locked => 4010,
+            WalletError::IncorrectPassword => 4011,
+            WalletError::UnknownAccount { .. } => 4012,
+            WalletError::NoDefaultAccount => 4013,
+            WalletError::WeakPassword { .. } => 4014,
+            WalletError::UnlockThrottled { .. } => 4015,
+            WalletError::InvalidFilter(_) => 4016,
+            WalletError::ConcurrentModification { .. } => 4017,
+            WalletError::ReadOnly => 4018,
+            WalletError::WalletBusy { .. } => 4019,
+        }
+    }
 }
 
 /// Result type for wallet operations
@@ -58,6 +156,36 @@ pub struct Account {
     pub created_at: u64,
 }
 
+/// What listings of accounts are sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountSort {
+    /// Creation time, oldest first by default, ties broken by address
+    #[default]
+    CreatedAt,
+    
+    /// Address, in order by default
+    Address,
+}
+
+impl AccountSort {
+    /// Gets the name of the listing sorted this way, which its cursors are tied to
+    pub fn listing(&self) -> &'static str {
+        match self {
+            Self::CreatedAt => "accounts_by_created_at",
+            Self::Address => "accounts_by_address",
+        }
+    }
+    
+    /// Gets the sort key of an account
+    pub fn key(&self, account: &Account) -> (u64, String) {
+        match self {
+            Self::CreatedAt => (account.created_at, account.address.clone()),
+            Self::Address => (0, account.address.clone()),
+        }
+    }
+}
+
 /// Wallet configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletConfig {
@@ -104,6 +232,15 @@ pub struct Wallet {
     
     /// Decryption key (only in memory when unlocked)
     decryption_key: Option<Vec<u8>>,
+    
+    /// Wrong passwords tried in a row, see `password`
+    unlock_throttle: UnlockThrottle,
+    
+    /// Whether the wallet was opened with `load_readonly`, and never writes
+    read_only: bool,
+    
+    /// Hash of the wallet file as the wallet last read or wrote it, see `file_lock`
+    file_hash: Option<Hash>,
 }
 
 impl Wallet {
@@ -116,11 +253,24 @@ impl Wallet {
             wallet_path,
             is_unlocked: false,
             decryption_key: None,
+            unlock_throttle: UnlockThrottle::default(),
+            read_only: false,
+            file_hash: None,
         }
     }
     
     /// Creates a new wallet at the given path
+    ///
+    /// Fails with `WalletError::WeakPassword` if the default password
+    /// policy finds the password weak.
     pub fn create(wallet_path: PathBuf, password: &str) -> Result<Self> {
+        Self::create_with_policy(wallet_path, password, &PasswordPolicy::default())
+    }
+    
+    /// Creates a new wallet at the given path, with a password the policy accepts
+    pub fn create_with_policy(wallet_path: PathBuf, password: &str, policy: &PasswordPolicy) -> Result<Self> {
+        policy.check(password)?;
+        
         // Create the wallet directory if it doesn't exist
         if let Some(parent) = wallet_path.parent() {
             fs::create_dir_all(parent)?;
@@ -142,119 +292,238 @@ impl Wallet {
     
     /// Loads a wallet from the given path
     pub fn load(wallet_path: PathBuf) -> Result<Self> {
-        // Check if the wallet file exists
-        if !wallet_path.exists() {
-            return Err(WalletError::IoError(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Wallet file not found",
-            )));
+        let mut wallet = Self::new(WalletConfig::default(), wallet_path);
+        wallet.read_file()?;
+        Ok(wallet)
+    }
+    
+    /// Loads a wallet from the given path that never writes to it
+    ///
+    /// Accounts can be read and transactions signed, but anything that
+    /// would save, such as creating an account, fails with
+    /// `WalletError::ReadOnly`. Wrong passwords count towards the unlock
+    /// lockout of this wallet alone, as they can't be saved.
+    pub fn load_readonly(wallet_path: PathBuf) -> Result<Self> {
+        let mut wallet = Self::load(wallet_path)?;
+        wallet.read_only = true;
+        Ok(wallet)
+    }
+    
+    /// Reads the wallet file again, taking in what other processes saved
+    ///
+    /// The wallet stays unlocked, unless the file's accounts are now
+    /// encrypted with another password, when it's locked.
+    pub fn reload(&mut self) -> Result<()> {
+        self.read_file()?;
+        
+        let key_matches = self
+            .accounts
+            .values()
+            .next()
+            .is_none_or(|account| self.decrypt_private_key(&account.encrypted_private_key).is_ok());
+        if self.is_unlocked && !key_matches {
+            log::info!("Wallet password was changed by another process; locking the wallet");
+            self.lock();
         }
-        
+        Ok(())
+    }
+    
+    /// Checks whether the wallet was opened with `load_readonly`
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    
+    /// Replaces the wallet's configuration, accounts and wrong passwords with those of its file
+    fn read_file(&mut self) -> Result<()> {
         // Read the wallet file
-        let wallet_data = fs::read_to_string(&wallet_path)?;
+        let wallet_data = match fs::read(&self.wallet_path) {
+            Ok(wallet_data) => wallet_data,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(WalletError::IoError(std::io::Error::new(ErrorKind::NotFound, "Wallet file not found")));
+            }
+            Err(e) => return Err(e.into()),
+        };
         
         // Deserialize the wallet
-        let wallet_json: serde_json::Value = serde_json::from_str(&wallet_data)
+        let wallet_json: serde_json::Value = serde_json::from_slice(&wallet_data)
             .map_err(|e| WalletError::SerializationError(e.to_string()))?;
         
         // Extract the configuration
         let config: WalletConfig = serde_json::from_value(wallet_json["config"].clone())
             .map_err(|e| WalletError::SerializationError(e.to_string()))?;
         
-        // Create the wallet
-        let mut wallet = Self::new(config, wallet_path);
-        
         // Extract the accounts
         let accounts: HashMap<String, Account> = serde_json::from_value(wallet_json["accounts"].clone())
             .map_err(|e| WalletError::SerializationError(e.to_string()))?;
         
-        wallet.accounts = accounts;
-        
-        // Extract the default account
-        if let Some(default) = wallet_json["default_account"].as_str() {
-            wallet.default_account = Some(default.to_string());
-        }
+        // Extract the wrong passwords tried, if any
+        let unlock_throttle = if wallet_json["unlock_throttle"].is_null() {
+            UnlockThrottle::default()
+        } else {
+            serde_json::from_value(wallet_json["unlock_throttle"].clone())
+                .map_err(|e| WalletError::SerializationError(e.to_string()))?
+        };
         
-        Ok(wallet)
+        self.config = config;
+        self.accounts = accounts;
+        self.default_account = wallet_json["default_account"].as_str().map(str::to_string);
+        self.unlock_throttle = unlock_throttle;
+        self.file_hash = Some(hashing::blake3(&wallet_data));
+        Ok(())
     }
     
     /// Unlocks the wallet with the given password
+    ///
+    /// Fails with `WalletError::UnlockThrottled` while too many wrong
+    /// passwords in a row lock unlocking out (see `password`). The lockout
+    /// is kept in the wallet file, so it only holds back guesses made
+    /// through this method, not ones made against the file itself.
     pub fn unlock(&mut self, password: &str) -> Result<()> {
+        self.unlock_at(password, ctb_core::current_timestamp())
+    }
+    
+    /// Unlocks the wallet with the given password at `now`, in seconds since the Unix epoch
+    pub fn unlock_at(&mut self, password: &str, now: u64) -> Result<()> {
         if self.is_unlocked {
             return Ok(());
         }
         
+        self.unlock_throttle.check(now)?;
+        
         // Derive the decryption key from the password
         let decryption_key = Self::derive_key(password);
         
-        // In a real implementation, we would verify the key here
-        // by trying to decrypt a test value
-        
         self.decryption_key = Some(decryption_key);
         self.is_unlocked = true;
         
+        // Verify the key by decrypting an account's private key; a wallet
+        // without accounts has nothing to check against
+        if let Some(account) = self.accounts.values().next() {
+            if self.decrypt_private_key(&account.encrypted_private_key).is_err() {
+                self.lock();
+                let delay = self.unlock_throttle.record_failure(now);
+                if !delay.is_zero() {
+                    log::warn!(
+                        "{} wrong wallet passwords in a row; unlocking is locked out for {} seconds",
+                        self.unlock_throttle.failed_attempts,
+                        delay.as_secs()
+                    );
+                }
+                self.save_throttle()?;
+                return Err(WalletError::IncorrectPassword);
+            }
+        }
+        
+        if self.unlock_throttle != UnlockThrottle::default() {
+            self.unlock_throttle.reset();
+            self.save_throttle()?;
+        }
+        
         Ok(())
     }
     
+    /// Gets the record of wrong passwords tried in a row
+    pub fn unlock_throttle(&self) -> UnlockThrottle {
+        self.unlock_throttle
+    }
+    
+    /// Changes the wallet's password, re-encrypting every account's key
+    ///
+    /// The current password is checked as `unlock` checks it, so wrong ones
+    /// count towards the lockout, and the new one must satisfy the policy.
+    /// The wallet is left unlocked with the new password.
+    pub fn change_password(&mut self, current: &str, new: &str, policy: &PasswordPolicy) -> Result<()> {
+        policy.check(new)?;
+        if self.read_only {
+            return Err(WalletError::ReadOnly);
+        }
+        
+        let previous_key = self.decryption_key.take();
+        self.is_unlocked = false;
+        if let Err(e) = self.unlock(current) {
+            self.is_unlocked = previous_key.is_some();
+            self.decryption_key = previous_key;
+            return Err(e);
+        }
+        
+        self.update(|wallet| {
+            let private_keys = wallet
+                .accounts
+                .iter()
+                .map(|(address, account)| Ok((address.clone(), wallet.decrypt_private_key(&account.encrypted_private_key)?)))
+                .collect::<Result<Vec<_>>>()?;
+            
+            wallet.decryption_key = Some(Self::derive_key(new));
+            for (address, private_key) in private_keys {
+                let encrypted_private_key = wallet.encrypt_private_key(&private_key)?;
+                if let Some(account) = wallet.accounts.get_mut(&address) {
+                    account.encrypted_private_key = encrypted_private_key;
+                }
+            }
+            Ok(())
+        })
+    }
+    
     /// Locks the wallet
     pub fn lock(&mut self) {
         self.decryption_key = None;
         self.is_unlocked = false;
     }
     
-    /// Creates a new account in the wallet
+    /// Creates a new ed25519 account in the wallet
     pub fn create_account(&mut self, label: &str) -> Result<String> {
+        self.create_account_with_scheme(label, SignatureScheme::Ed25519)
+    }
+    
+    /// Creates a new account whose key uses the given signature scheme
+    pub fn create_account_with_scheme(&mut self, label: &str, scheme: SignatureScheme) -> Result<String> {
         if !self.is_unlocked {
-            return Err(WalletError::AccountError("Wallet is locked".to_string()));
-        }
-        
-        // Generate a new key pair
-        let (private_key, public_key) = self.generate_key_pair()?;
-        
-        // Encrypt the private key
-        let encrypted_private_key = self.encrypt_private_key(&private_key)?;
-        
-        // Create the account
-        let account = Account {
-            address: public_key.clone(),
-            encrypted_private_key,
-            label: label.to_string(),
-            is_default: self.accounts.is_empty(), // First account is default
-            created_at: core::current_timestamp(),
-        };
-        
-        // Set as default if it's the first account
-        if account.is_default {
-            self.default_account = Some(public_key.clone());
+            return Err(WalletError::Locked);
         }
         
-        // Add the account to the wallet
-        self.accounts.insert(public_key.clone(), account);
-        
-        // Save the wallet
-        self.save()?;
-        
-        Ok(public_key)
+        self.update(|wallet| {
+            // Generate a new key pair
+            let (private_key, public_key) = wallet.generate_key_pair(scheme)?;
+            
+            // Encrypt the private key
+            let encrypted_private_key = wallet.encrypt_private_key(&private_key)?;
+            
+            // Create the account
+            let account = Account {
+                address: public_key.clone(),
+                encrypted_private_key,
+                label: label.to_string(),
+                is_default: wallet.accounts.is_empty(), // First account is default
+                created_at: ctb_core::current_timestamp(),
+            };
+            
+            // Set as default if it's the first account
+            if account.is_default {
+                wallet.default_account = Some(public_key.clone());
+            }
+            
+            // Add the account to the wallet
+            wallet.accounts.insert(public_key.clone(), account);
+            Ok(public_key)
+        })
     }
     
     /// Sets the default account
     pub fn set_default_account(&mut self, address: &str) -> Result<()> {
         if !self.accounts.contains_key(address) {
-            return Err(WalletError::AccountError(format!("Account {} not found", address)));
+            return Err(WalletError::UnknownAccount { address: address.to_string() });
         }
         
-        // Update the default flag for all accounts
-        for (addr, account) in &mut self.accounts {
-            account.is_default = addr == address;
-        }
-        
-        // Update the default account
-        self.default_account = Some(address.to_string());
-        
-        // Save the wallet
-        self.save()?;
-        
-        Ok(())
+        self.update(|wallet| {
+            // Update the default flag for all accounts
+            for (addr, account) in &mut wallet.accounts {
+                account.is_default = addr == address;
+            }
+            
+            // Update the default account
+            wallet.default_account = Some(address.to_string());
+            Ok(())
+        })
     }
     
     /// Gets all accounts in the wallet
@@ -262,6 +531,11 @@ impl Wallet {
         self.accounts.values().collect()
     }
     
+    /// Gets a page of the accounts in the wallet
+    pub fn list_accounts(&self, sort: AccountSort, request: &PageRequest) -> Result<Page<&Account>> {
+        Ok(paging::paginate(self.accounts.values(), sort.listing(), request, SortOrder::Ascending, |account| sort.key(account))?)
+    }
+    
     /// Gets an account by address
     pub fn get_account(&self, address: &str) -> Option<&Account> {
         self.accounts.get(address)
@@ -276,65 +550,197 @@ impl Wallet {
         }
     }
     
-    /// Creates and signs a transaction
+    /// Creates and signs a transaction with the sender's next nonce
+    ///
+    /// The wallet doesn't know the chain, so the nonce is the caller's to
+    /// find, such as with `api::ChainClient::get_nonce`; `api::WalletApi` does.
     pub fn create_transaction(
+        &self,
+        sender: &Address,
+        recipient: &Address,
+        amount: Amount,
+        fee: Amount,
+        data: Option<Vec<u8>>,
+        nonce: u64,
+    ) -> Result<Transaction> {
+        // Create the transaction
+        let tx = Transaction::new(sender.clone(), recipient.clone(), amount, fee, data)
+            .and_then(|tx| tx.with_nonce(nonce))
+            .map_err(WalletError::BlockchainError)?;
+        
+        self.sign_transaction(tx)
+    }
+    
+    /// Creates and signs a transaction between unchecked addresses
+    #[deprecated(note = "use `Wallet::create_transaction` with `Address`es")]
+    pub fn create_transaction_untyped(
         &self,
         sender: &str,
         recipient: &str,
-        amount: u64,
-        fee: u64,
+        amount: Amount,
+        fee: Amount,
         data: Option<Vec<u8>>,
+        nonce: u64,
+    ) -> Result<Transaction> {
+        self.create_transaction(&parse_address(sender)?, &parse_address(recipient)?, amount, fee, data, nonce)
+    }
+    
+    /// Creates and signs a transaction calling a contract, with the sender's next nonce like `create_transaction`
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_contract_call(
+        &self,
+        sender: &str,
+        contract: &str,
+        amount: u64,
+        data: Vec<u8>,
+        gas_limit: u64,
+        gas_price: u64,
+        nonce: u64,
     ) -> Result<Transaction> {
+        let tx = Transaction::new_contract_call(
+            sender.to_string(),
+            contract.to_string(),
+            amount,
+            data,
+            gas_limit,
+            gas_price,
+        )?
+        .with_nonce(nonce)?;
+        
+        self.sign_transaction(tx)
+    }
+    
+    /// Signs a message with the key of an account, as a validator's keystore does
+    ///
+    /// Messages signed with secp256k1 keys must be 32-byte hashes.
+    pub fn sign_message(&self, address: &str, message: &[u8]) -> Result<Vec<u8>> {
         if !self.is_unlocked {
-            return Err(WalletError::AccountError("Wallet is locked".to_string()));
+            return Err(WalletError::Locked);
         }
         
-        // Check that the sender account exists
-        let account = self.accounts.get(sender).ok_or_else(|| {
-            WalletError::AccountError(format!("Sender account {} not found", sender))
+        let account = self.accounts.get(address).ok_or_else(|| {
+            WalletError::UnknownAccount { address: address.to_string() }
         })?;
+        let scheme = SignatureScheme::from_address(address)
+            .ok_or_else(|| WalletError::KeyError(format!("{} has no signing key", address)))?;
         
-        // Create the transaction
-        let mut tx = Transaction::new(
-            sender.to_string(),
-            recipient.to_string(),
-            amount,
-            fee,
-            data,
-        ).map_err(|e| WalletError::BlockchainError(e))?;
+        let private_key = self.decrypt_private_key(&account.encrypted_private_key)?;
+        ctb_core::signature::sign(scheme, &private_key, message)
+            .map_err(|e| WalletError::KeyError(format!("Signing failed: {}", e)))
+    }
+    
+    /// Signs a transaction with the key of its sender's account
+    fn sign_transaction(&self, mut tx: Transaction) -> Result<Transaction> {
+        if !self.is_unlocked {
+            return Err(WalletError::Locked);
+        }
+        
+        // Check that the sender account exists
+        let account = self.accounts.get(&tx.sender).ok_or_else(|| {
+            WalletError::UnknownAccount { address: tx.sender.clone() }
+        })?;
         
         // Decrypt the private key
         let private_key = self.decrypt_private_key(&account.encrypted_private_key)?;
         
-        // Sign the transaction using ed25519
-        use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature};
-        
-        // Reconstruct the keypair from the private key
-        let secret = SecretKey::from_bytes(&private_key)
-            .map_err(|e| WalletError::KeyError(format!("Invalid private key: {}", e)))?;
+        // Sign the transaction ID with the scheme the sender's address names
+        tx.sign(&private_key)
+            .map_err(|e| WalletError::KeyError(format!("Signing failed: {}", e)))?;
         
-        // Extract the public key from the address (remove the GENX prefix and decode hex)
-        let public_bytes = hex::decode(account.address.trim_start_matches("GENX"))
-            .map_err(|e| WalletError::KeyError(format!("Invalid address format: {}", e)))?;
+        Ok(tx)
+    }
+    
+    /// Changes the accounts and saves them, leaving the wallet as it was if either fails
+    fn update<T>(&mut self, change: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.read_only {
+            return Err(WalletError::ReadOnly);
+        }
         
-        let public = PublicKey::from_bytes(&public_bytes)
-            .map_err(|e| WalletError::KeyError(format!("Invalid public key: {}", e)))?;
+        let accounts = self.accounts.clone();
+        let default_account = self.default_account.clone();
+        let decryption_key = self.decryption_key.clone();
+        let result = change(self).and_then(|value| self.save().map(|()| value));
+        if result.is_err() {
+            self.accounts = accounts;
+            self.default_account = default_account;
+            self.decryption_key = decryption_key;
+        }
+        result
+    }
+    
+    /// Saves the wallet to disk
+    ///
+    /// Fails with `WalletError::ConcurrentModification` if another process
+    /// saved the file since this wallet last read or wrote it (see
+    /// `file_lock`). A wallet that never did, such as a new one, may replace
+    /// any file.
+    fn save(&mut self) -> Result<()> {
+        if self.read_only {
+            return Err(WalletError::ReadOnly);
+        }
         
-        let keypair = Keypair { secret, public };
+        // Serialize to JSON
+        let wallet_data = serde_json::to_string_pretty(&self.file_json())
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
         
-        // Calculate the transaction hash and sign it
-        let tx_hash = tx.calculate_hash().map_err(|e| WalletError::BlockchainError(e))?;
-        let signature = keypair.sign(&tx_hash);
+        let _lock = FileLock::acquire(&self.wallet_path)?;
+        if self.file_hash.is_some() && self.file_hash != self.hash_on_disk()? {
+            return Err(WalletError::ConcurrentModification { path: self.wallet_path.display().to_string() });
+        }
+        self.write_file(wallet_data.as_bytes())
+    }
+    
+    /// Saves the wrong passwords tried, keeping what other processes saved
+    ///
+    /// Unlike other changes, wrong passwords are added to the file even if
+    /// another process changed it, so that no process can try passwords
+    /// without them counting. Read-only wallets don't save them.
+    fn save_throttle(&mut self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
         
-        // Set the signature in the transaction
-        tx.signature = Some(signature.to_bytes().to_vec());
+        let _lock = FileLock::acquire(&self.wallet_path)?;
+        if self.file_hash.is_none() || self.file_hash == self.hash_on_disk()? {
+            let wallet_data = serde_json::to_string_pretty(&self.file_json())
+                .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+            return self.write_file(wallet_data.as_bytes());
+        }
         
-        Ok(tx)
+        // This wallet is out of date, so only the wrong passwords are written into the file
+        let mut wallet_json: serde_json::Value = serde_json::from_slice(&fs::read(&self.wallet_path)?)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+        let fields = wallet_json
+            .as_object_mut()
+            .ok_or_else(|| WalletError::SerializationError("Wallet file isn't a JSON object".to_string()))?;
+        if self.unlock_throttle == UnlockThrottle::default() {
+            fields.remove("unlock_throttle");
+        } else {
+            fields.insert("unlock_throttle".to_string(), serde_json::json!(self.unlock_throttle));
+        }
+        let wallet_data = serde_json::to_string_pretty(&wallet_json)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+        write_atomically(&self.wallet_path, wallet_data.as_bytes())
     }
     
-    /// Saves the wallet to disk
-    fn save(&self) -> Result<()> {
-        // Create a JSON representation of the wallet
+    /// Writes the wallet file, which the caller holds the lock on, and remembers its hash
+    fn write_file(&mut self, wallet_data: &[u8]) -> Result<()> {
+        write_atomically(&self.wallet_path, wallet_data)?;
+        self.file_hash = Some(hashing::blake3(wallet_data));
+        Ok(())
+    }
+    
+    /// Hashes the wallet file as it is on disk, if it exists
+    fn hash_on_disk(&self) -> Result<Option<Hash>> {
+        match fs::read(&self.wallet_path) {
+            Ok(wallet_data) => Ok(Some(hashing::blake3(&wallet_data))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    
+    /// Builds the JSON representation of the wallet that its file holds
+    fn file_json(&self) -> serde_json::Value {
         let mut wallet_json = serde_json::json!({
             "config": self.config,
             "accounts": self.accounts,
@@ -344,30 +750,39 @@ impl Wallet {
             wallet_json["default_account"] = serde_json::Value::String(default.clone());
         }
         
-        // Serialize to JSON
-        let wallet_data = serde_json::to_string_pretty(&wallet_json)
-            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
-        
-        // Write to file
-        fs::write(&self.wallet_path, wallet_data)?;
+        if self.unlock_throttle != UnlockThrottle::default() {
+            wallet_json["unlock_throttle"] = serde_json::json!(self.unlock_throttle);
+        }
         
-        Ok(())
+        wallet_json
     }
     
-    /// Generates a new key pair
-    fn generate_key_pair(&self) -> Result<(Vec<u8>, String)> {
-        use ed25519_dalek::{Keypair, PublicKey, SecretKey};
-        use rand::rngs::OsRng;
+    /// Generates a new key pair, returning the private key and the address
+    fn generate_key_pair(&self, scheme: SignatureScheme) -> Result<(Vec<u8>, String)> {
+        use ed25519_dalek::{PublicKey, SecretKey};
+        use rand::{Rng, rngs::OsRng};
         
         // Generate a new keypair using the OS random number generator
         let mut csprng = OsRng{};
-        let keypair = Keypair::generate(&mut csprng);
+        let (private_key, public_key) = match scheme {
+            SignatureScheme::Ed25519 => {
+                // ed25519-dalek takes an older rand's generators, so the secret is drawn here
+                let secret = SecretKey::from_bytes(&csprng.gen::<[u8; 32]>())
+                    .map_err(|e| WalletError::KeyError(e.to_string()))?;
+                let public = PublicKey::from(&secret);
+                (secret.as_bytes().to_vec(), public.as_bytes().to_vec())
+            }
+            SignatureScheme::Secp256k1 => {
+                let secret = ctb_core::secp256k1::SecretKey::generate(&mut csprng);
+                (secret.to_bytes().to_vec(), secret.public_key().to_compressed().to_vec())
+            }
+        };
         
-        // Extract the private and public keys
-        let private_key = keypair.secret.as_bytes().to_vec();
-        let public_key = format!("GENX{}", hex::encode(keypair.public.as_bytes()));
+        let address = scheme
+            .address(&public_key)
+            .map_err(|e| WalletError::KeyError(e.to_string()))?;
         
-        Ok((private_key, public_key))
+        Ok((private_key, address))
     }
     
     /// Encrypts a private key
@@ -377,7 +792,7 @@ impl Wallet {
         use rand::{Rng, rngs::OsRng};
         
         if !self.is_unlocked || self.decryption_key.is_none() {
-            return Err(WalletError::KeyError("Wallet is locked".to_string()));
+            return Err(WalletError::Locked);
         }
         
         // Get the encryption key
@@ -413,7 +828,7 @@ impl Wallet {
         use aes_gcm::aead::{Aead, NewAead};
         
         if !self.is_unlocked || self.decryption_key.is_none() {
-            return Err(WalletError::KeyError("Wallet is locked".to_string()));
+            return Err(WalletError::Locked);
         }
         
         // Check that the encrypted key is long enough to contain a nonce
@@ -454,9 +869,22 @@ impl Wallet {
         
         // Derive a 32-byte key using PBKDF2 with 10000 iterations
         let mut key = [0u8; 32];
-        pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, 10000, &mut key)
-            .expect("PBKDF2 should not fail");
+        pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, 10000, &mut key);
         
         key.to_vec()
     }
+}
+
+/// Checks an address given as a string
+pub(crate) fn parse_address(address: &str) -> Result<Address> {
+    Address::new(address).map_err(|e| WalletError::BlockchainError(e.into()))
+}
+
+/// Writes a file through a temporary one, so other processes never read it half written
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,451 @@
+//! A chain the tests script, standing in for a node
+//!
+//! Built with the `testutil` feature. A `MockChainClient` implements
+//! `ChainClient` over a chain held in memory, so a `WalletApi` given it with
+//! `set_client` sends, tracks and pays as it would against a node, while
+//! the test decides what the chain does:
+//!
+//! - Balances are seeded with `set_balance`. An account's nonce is the
+//!   number of its transactions in the chain, failed or not; nonces aren't
+//!   checked as transactions are included, so tests can schedule them in
+//!   any order.
+//! - Blocks are only made by `advance_block`, each `BLOCK_TIME` seconds
+//!   after the last. A submitted transaction is included in the block
+//!   `inclusion_delay` blocks after it was submitted, the next one by
+//!   default, unless `confirm_at` or `fail_at` scheduled its block, or the
+//!   delay is `None`, which holds transactions until they're scheduled.
+//!   A transaction its sender can't pay for when its block is made is
+//!   dropped, as a node drops it; so is one given to `drop_transaction`.
+//! - Included transactions move their amount and are charged their most
+//!   fee, and failed ones only the fee.
+//! - `reorganize` takes the blocks above a height out of the chain, as a
+//!   reorganization does, undoing their transfers and returning their
+//!   transactions to those waiting. Blocks up to `finalize`'s height can't
+//!   be taken out.
+//! - `reject_next_submission` has the next submission fail with the error
+//!   given, whose `error_code` the wallet reports, and `set_unavailable`
+//!   has every query fail as an unreachable node's do until it's cleared.
+//! - The fee histogram counts the transactions waiting, as a mempool's
+//!   does, and inclusion is estimated over it with `set_base_fee`'s base
+//!   fee; contract calls, ABIs and gas estimates return what was set.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use ctb_core::block::Block;
+use ctb_core::confirmation::{ChainTip, ConfirmationStatus, DEFAULT_SAFE_CONFIRMATIONS};
+use ctb_core::fee_market::{self, BlockSpace, EstimatedBlocks, FeeHistogram, FeeRate};
+use ctb_core::genesis;
+use ctb_core::paging::{self, Page, PageRequest, SortOrder};
+use ctb_core::transaction::Transaction;
+use ctb_core::{BlockchainError, TxHash};
+use smartcontracts::FunctionABI;
+
+use crate::api::{CallOutcome, ChainClient, GasEstimate, TransactionRecord};
+
+/// Seconds between the blocks `advance_block` makes
+pub const BLOCK_TIME: u64 = 5;
+
+/// Validator the blocks are made by
+pub const MOCK_VALIDATOR: &str = "mock-validator";
+
+/// Most transactions a block includes, as estimates see it
+pub const MAX_BLOCK_TRANSACTIONS: u64 = 1000;
+
+/// Error every query fails with while the chain is unavailable
+pub const UNAVAILABLE: &str = "Mock chain is unavailable";
+
+/// Transaction submitted and not yet included or dropped
+#[derive(Debug, Clone)]
+struct Submitted {
+    tx: Transaction,
+    
+    /// Height of the latest block when it was submitted
+    submitted_at: u64,
+}
+
+/// Block a transaction is scheduled to be included in
+#[derive(Debug, Clone, Copy)]
+struct Scheduled {
+    height: u64,
+    success: bool,
+}
+
+#[derive(Debug)]
+struct MockState {
+    blocks: Vec<Block>,
+    finalized_height: u64,
+    balances: HashMap<String, u64>,
+    
+    /// Included transactions in chain order, keyed by height and index in their block
+    history: Vec<((u64, usize), TransactionRecord)>,
+    
+    /// Transactions waiting, in the order they were submitted
+    pending: Vec<Submitted>,
+    scheduled: HashMap<TxHash, Scheduled>,
+    histogram: FeeHistogram,
+    inclusion_delay: Option<u64>,
+    
+    /// Errors the next submissions fail with, in order
+    rejections: VecDeque<BlockchainError>,
+    unavailable: bool,
+    
+    base_fee: u64,
+    gas_estimate: Option<GasEstimate>,
+    call_outcomes: HashMap<(String, [u8; 4]), CallOutcome>,
+    abis: HashMap<String, Vec<FunctionABI>>,
+}
+
+/// Chain held in memory that tests script, see the module documentation
+#[derive(Debug)]
+pub struct MockChainClient {
+    state: Mutex<MockState>,
+}
+
+impl Default for MockChainClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockChainClient {
+    /// Creates a chain of only a genesis block, timestamped now, where every balance is zero
+    pub fn new() -> Self {
+        let base_fee = genesis::get_initial_base_fee();
+        let mut genesis = Block::genesis(Vec::new(), base_fee).expect("empty genesis block");
+        genesis.header_mut().timestamp = ctb_core::current_timestamp();
+        Self {
+            state: Mutex::new(MockState {
+                blocks: vec![genesis],
+                finalized_height: 0,
+                balances: HashMap::new(),
+                history: Vec::new(),
+                pending: Vec::new(),
+                scheduled: HashMap::new(),
+                histogram: FeeHistogram::new(),
+                inclusion_delay: Some(1),
+                rejections: VecDeque::new(),
+                unavailable: false,
+                base_fee,
+                gas_estimate: None,
+                call_outcomes: HashMap::new(),
+                abis: HashMap::new(),
+            }),
+        }
+    }
+    
+    /// Sets an account's balance, in base units
+    pub fn set_balance(&self, address: &str, balance: u64) {
+        self.state.lock().unwrap().balances.insert(address.to_string(), balance);
+    }
+    
+    /// Gets the height of the latest block
+    pub fn height(&self) -> u64 {
+        self.state.lock().unwrap().blocks.len() as u64 - 1
+    }
+    
+    /// Gets the block at a height
+    pub fn block(&self, height: u64) -> Option<Block> {
+        self.state.lock().unwrap().blocks.get(height as usize).cloned()
+    }
+    
+    /// Gets the transactions waiting to be included, in the order they were submitted
+    pub fn pending_transactions(&self) -> Vec<Transaction> {
+        self.state.lock().unwrap().pending.iter().map(|submitted| submitted.tx.clone()).collect()
+    }
+    
+    /// Sets how many blocks after its submission a transaction is included, or `None` to hold them until scheduled
+    pub fn set_inclusion_delay(&self, blocks: Option<u64>) {
+        self.state.lock().unwrap().inclusion_delay = blocks;
+    }
+    
+    /// Schedules a transaction to be included at a height, or in the next block once it's submitted if that's passed
+    pub fn confirm_at(&self, tx_id: &TxHash, height: u64) {
+        self.state.lock().unwrap().scheduled.insert(*tx_id, Scheduled { height, success: true });
+    }
+    
+    /// Schedules a transaction to be included at a height and fail there, like `confirm_at`
+    pub fn fail_at(&self, tx_id: &TxHash, height: u64) {
+        self.state.lock().unwrap().scheduled.insert(*tx_id, Scheduled { height, success: false });
+    }
+    
+    /// Drops a waiting transaction without including it, returning whether it was waiting
+    pub fn drop_transaction(&self, tx_id: &TxHash) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(index) = state.pending.iter().position(|submitted| submitted.tx.id == *tx_id) else {
+            return false;
+        };
+        let dropped = state.pending.remove(index);
+        state.histogram.remove(&dropped.tx);
+        true
+    }
+    
+    /// Has the next submission not yet rejected fail with an error
+    pub fn reject_next_submission(&self, error: BlockchainError) {
+        self.state.lock().unwrap().rejections.push_back(error);
+    }
+    
+    /// Makes every query fail, as an unreachable node's do, or work again
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.state.lock().unwrap().unavailable = unavailable;
+    }
+    
+    /// Sets the base fee the next blocks charge per unit of gas
+    pub fn set_base_fee(&self, base_fee: u64) {
+        self.state.lock().unwrap().base_fee = base_fee;
+    }
+    
+    /// Sets the estimate of every transaction's gas, which is its gas limit unless set
+    pub fn set_gas_estimate(&self, estimate: GasEstimate) {
+        self.state.lock().unwrap().gas_estimate = Some(estimate);
+    }
+    
+    /// Sets what calling a contract's function returns
+    pub fn set_call_outcome(&self, contract: &str, selector: [u8; 4], outcome: CallOutcome) {
+        self.state.lock().unwrap().call_outcomes.insert((contract.to_string(), selector), outcome);
+    }
+    
+    /// Sets the functions of a contract, deploying it as far as `get_contract_abi` sees
+    pub fn set_contract_abi(&self, contract: &str, abi: Vec<FunctionABI>) {
+        self.state.lock().unwrap().abis.insert(contract.to_string(), abi);
+    }
+    
+    /// Makes the next block, including the transactions due in it, and returns it
+    pub fn advance_block(&self) -> Block {
+        let mut state = self.state.lock().unwrap();
+        let height = state.blocks.len() as u64;
+        
+        let mut included = Vec::new();
+        let mut waiting = Vec::new();
+        for submitted in std::mem::take(&mut state.pending) {
+            let due = match state.scheduled.get(&submitted.tx.id) {
+                Some(scheduled) => scheduled.height <= height,
+                None => state.inclusion_delay.is_some_and(|delay| submitted.submitted_at + delay <= height),
+            };
+            if due {
+                included.push(submitted.tx);
+            } else {
+                waiting.push(submitted);
+            }
+        }
+        state.pending = waiting;
+        
+        let mut transactions = Vec::new();
+        for tx in included {
+            state.histogram.remove(&tx);
+            let success = state.scheduled.remove(&tx.id).is_none_or(|scheduled| scheduled.success);
+            let fee_paid = tx.max_fee();
+            let charged = if success { tx.amount.saturating_add(fee_paid) } else { fee_paid };
+            let balance = state.balances.get(&tx.sender).copied().unwrap_or(0);
+            if balance < charged {
+                continue;
+            }
+            
+            state.balances.insert(tx.sender.clone(), balance - charged);
+            if success {
+                *state.balances.entry(tx.recipient.clone()).or_insert(0) += tx.amount;
+            }
+            let record = TransactionRecord {
+                block_height: height,
+                block_timestamp: 0,
+                success,
+                fee_paid,
+                transaction: tx.clone(),
+                status: ConfirmationStatus::Pending,
+            };
+            state.history.push(((height, transactions.len()), record));
+            transactions.push(tx);
+        }
+        
+        let parent = state.blocks.last().expect("genesis block");
+        let timestamp = parent.header().timestamp + BLOCK_TIME;
+        let parent_hash = parent.hash().expect("mock block hash");
+        let mut block = Block::new(height, parent_hash, transactions, MOCK_VALIDATOR.to_string(), state.base_fee)
+            .expect("mock block");
+        block.header_mut().timestamp = timestamp;
+        for (_, record) in state.history.iter_mut().filter(|(key, _)| key.0 == height) {
+            record.block_timestamp = timestamp;
+        }
+        state.blocks.push(block.clone());
+        block
+    }
+    
+    /// Finalizes the blocks up to a height, at most the latest block's
+    pub fn finalize(&self, height: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.finalized_height = height.min(state.blocks.len() as u64 - 1);
+    }
+    
+    /// Takes the blocks above `fork_height` out of the chain, returning them in order
+    ///
+    /// Their transactions' transfers and fees are undone, and they wait to
+    /// be included again as if submitted at `fork_height`; failed ones are
+    /// scheduled to fail in the next block again. Panics if that would
+    /// take out a finalized block.
+    pub fn reorganize(&self, fork_height: u64) -> Vec<Block> {
+        let mut state = self.state.lock().unwrap();
+        assert!(fork_height >= state.finalized_height, "block {} is finalized", state.finalized_height);
+        if fork_height + 1 >= state.blocks.len() as u64 {
+            return Vec::new();
+        }
+        
+        let removed = state.blocks.split_off(fork_height as usize + 1);
+        let kept = state.history.iter().position(|((height, _), _)| *height > fork_height).unwrap_or(state.history.len());
+        let undone = state.history.split_off(kept);
+        let mut returned = Vec::new();
+        for (_, record) in undone.into_iter().rev() {
+            let tx = record.transaction;
+            *state.balances.entry(tx.sender.clone()).or_insert(0) += record.fee_paid;
+            if record.success {
+                *state.balances.entry(tx.sender.clone()).or_insert(0) += tx.amount;
+                let recipient = state.balances.entry(tx.recipient.clone()).or_insert(0);
+                *recipient = recipient.saturating_sub(tx.amount);
+            } else {
+                state.scheduled.insert(tx.id, Scheduled { height: fork_height + 1, success: false });
+            }
+            returned.push(tx);
+        }
+        
+        // Ahead of those submitted since, as they were submitted before them
+        let mut pending: Vec<Submitted> = returned
+            .into_iter()
+            .rev()
+            .map(|tx| Submitted { tx, submitted_at: fork_height })
+            .collect();
+        for submitted in &pending {
+            state.histogram.add(&submitted.tx);
+        }
+        pending.append(&mut state.pending);
+        state.pending = pending;
+        removed
+    }
+    
+    /// Makes a number of blocks like `advance_block`, returning them in order
+    pub fn advance_blocks(&self, count: u64) -> Vec<Block> {
+        (0..count).map(|_| self.advance_block()).collect()
+    }
+    
+    /// Locks the state to answer a query, failing while the chain is unavailable
+    fn available(&self) -> ctb_core::Result<std::sync::MutexGuard<'_, MockState>> {
+        let state = self.state.lock().unwrap();
+        if state.unavailable {
+            return Err(BlockchainError::StateError(UNAVAILABLE.to_string()));
+        }
+        Ok(state)
+    }
+}
+
+impl MockState {
+    /// Iterates over the records of an address's transactions, in chain order
+    fn records_of<'a>(&'a self, address: &'a str) -> impl DoubleEndedIterator<Item = &'a ((u64, usize), TransactionRecord)> {
+        self.history
+            .iter()
+            .filter(move |(_, record)| record.transaction.sender == address || record.transaction.recipient == address)
+    }
+    
+    /// Copies a record with its status judged at the tip, as a node reports it
+    fn judged(&self, record: &TransactionRecord) -> TransactionRecord {
+        let mut record = record.clone();
+        record.judge(&ChainTip::new(self.blocks.len() as u64 - 1, self.finalized_height), DEFAULT_SAFE_CONFIRMATIONS);
+        record
+    }
+}
+
+impl ChainClient for MockChainClient {
+    fn call_contract(
+        &self,
+        contract: &str,
+        selector: &[u8; 4],
+        _arguments: &[u8],
+        _sender: &str,
+    ) -> ctb_core::Result<CallOutcome> {
+        self.available()?
+            .call_outcomes
+            .get(&(contract.to_string(), *selector))
+            .cloned()
+            .ok_or_else(|| BlockchainError::StateError(format!("No outcome set for calling {}", contract)))
+    }
+    
+    fn get_contract_abi(&self, contract: &str) -> ctb_core::Result<Option<Vec<FunctionABI>>> {
+        Ok(self.available()?.abis.get(contract).cloned())
+    }
+    
+    fn estimate_gas(&self, tx: &Transaction) -> ctb_core::Result<GasEstimate> {
+        Ok(self.available()?.gas_estimate.clone().unwrap_or(GasEstimate::Gas(tx.gas_limit)))
+    }
+    
+    fn next_base_fee(&self) -> u64 {
+        self.state.lock().unwrap().base_fee
+    }
+    
+    fn fee_histogram(&self) -> ctb_core::Result<FeeHistogram> {
+        Ok(self.available()?.histogram.clone())
+    }
+    
+    fn estimate_inclusion(&self, rate: FeeRate) -> ctb_core::Result<EstimatedBlocks> {
+        let state = self.available()?;
+        let space = BlockSpace {
+            gas_limit: genesis::get_block_gas_limit(),
+            max_transactions: MAX_BLOCK_TRANSACTIONS,
+            next_base_fee: state.base_fee,
+            recent_gas_used: Vec::new(),
+        };
+        Ok(fee_market::estimate_inclusion(&state.histogram, rate, &space))
+    }
+    
+    fn get_balance(&self, address: &str) -> ctb_core::Result<u64> {
+        Ok(self.available()?.balances.get(address).copied().unwrap_or(0))
+    }
+    
+    fn get_nonce(&self, address: &str) -> ctb_core::Result<u64> {
+        let state = self.available()?;
+        Ok(state.history.iter().filter(|(_, record)| record.transaction.sender == address).count() as u64)
+    }
+    
+    fn submit_transaction(&self, tx: &Transaction) -> ctb_core::Result<TxHash> {
+        let mut state = self.available()?;
+        if let Some(error) = state.rejections.pop_front() {
+            return Err(error);
+        }
+        let mut known = state.pending.iter().map(|submitted| &submitted.tx).chain(state.history.iter().map(|(_, record)| &record.transaction));
+        if known.any(|known| known.id == tx.id) {
+            return Err(BlockchainError::InvalidTransaction(format!("Transaction {} was already submitted", hex::encode(tx.id))));
+        }
+        
+        let submitted_at = state.blocks.len() as u64 - 1;
+        state.histogram.add(tx);
+        state.pending.push(Submitted { tx: tx.clone(), submitted_at });
+        Ok(tx.id)
+    }
+    
+    fn get_history(&self, address: &str, limit: usize) -> ctb_core::Result<Vec<TransactionRecord>> {
+        let state = self.available()?;
+        Ok(state.records_of(address).rev().take(limit).map(|(_, record)| state.judged(record)).collect())
+    }
+    
+    fn get_history_page(&self, address: &str, request: &PageRequest) -> ctb_core::Result<Page<TransactionRecord>> {
+        let state = self.available()?;
+        let page = paging::paginate(state.records_of(address).cloned(), "address_transactions", request, SortOrder::Descending, |(key, _)| *key)?;
+        Ok(page.map(|(_, record)| state.judged(&record)))
+    }
+    
+    fn get_transaction(&self, tx_id: &TxHash) -> ctb_core::Result<Option<TransactionRecord>> {
+        let state = self.available()?;
+        Ok(state.history.iter().find(|(_, record)| record.transaction.id == *tx_id).map(|(_, record)| state.judged(record)))
+    }
+    
+    fn address_possibly_used(&self, address: &str) -> ctb_core::Result<bool> {
+        Ok(self.address_first_used(address)?.is_some())
+    }
+    
+    fn address_first_used(&self, address: &str) -> ctb_core::Result<Option<u64>> {
+        let state = self.available()?;
+        let first_used = state.records_of(address).next().map(|(_, record)| record.block_height);
+        Ok(first_used)
+    }
+    
+    fn chain_tip(&self) -> ctb_core::Result<ChainTip> {
+        let state = self.available()?;
+        Ok(ChainTip::new(state.blocks.len() as u64 - 1, state.finalized_height))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,138 @@
+//! Password strength rules and throttling of unlock attempts
+//!
+//! A wallet's keys are only as safe as its password, so `Wallet::create`
+//! and `Wallet::change_password` refuse passwords a `PasswordPolicy`
+//! finds weak: by default, shorter than `MIN_PASSWORD_LENGTH` characters
+//! or drawing on fewer than `MIN_CHARACTER_CLASSES` of lowercase letters,
+//! uppercase letters, digits and other characters. The error lists what
+//! the password lacks. `PasswordPolicy::none` accepts anything, for test
+//! environments.
+//!
+//! Guessing is slowed by an `UnlockThrottle`: after `FREE_UNLOCK_ATTEMPTS`
+//! wrong passwords in a row, each further one locks out unlocking for
+//! `BASE_UNLOCK_DELAY`, doubling every time up to `MAX_UNLOCK_DELAY`.
+//! The count and the time of the lockout are saved in the wallet file, so
+//! restarting doesn't reset them; the right password does.
+//!
+//! The throttle only slows guesses made through `Wallet::unlock`. Anyone
+//! who can read the wallet file can try passwords against its encrypted
+//! keys directly, and anyone who can write it can clear the throttle, so
+//! it's the password's strength that protects a copied file.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, WalletError};
+
+/// Fewest characters a password has under the default policy
+pub const MIN_PASSWORD_LENGTH: usize = 12;
+
+/// Fewest kinds of character a password has under the default policy
+pub const MIN_CHARACTER_CLASSES: usize = 3;
+
+/// Wrong passwords in a row allowed before unlocking is locked out
+pub const FREE_UNLOCK_ATTEMPTS: u32 = 3;
+
+/// Lockout after the first wrong password beyond the free ones
+pub const BASE_UNLOCK_DELAY: Duration = Duration::from_secs(2);
+
+/// Longest lockout
+pub const MAX_UNLOCK_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Rules a new password must follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    /// Fewest characters
+    pub min_length: usize,
+    
+    /// Fewest of the four kinds of character: lowercase, uppercase, digits and others
+    pub min_classes: usize,
+}
+
+impl PasswordPolicy {
+    /// A policy accepting any password, even an empty one
+    pub const fn none() -> Self {
+        Self { min_length: 0, min_classes: 0 }
+    }
+    
+    /// Checks a password, failing with `WalletError::WeakPassword` listing what it lacks
+    pub fn check(&self, password: &str) -> Result<()> {
+        let mut lacking = Vec::new();
+        
+        let length = password.chars().count();
+        if length < self.min_length {
+            lacking.push(format!("at least {} characters, not {}", self.min_length, length));
+        }
+        
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_lowercase() && !c.is_uppercase() && !c.is_ascii_digit()),
+        ];
+        let used = classes.iter().filter(|&&used| used).count();
+        if used < self.min_classes {
+            lacking.push(format!(
+                "at least {} of lowercase letters, uppercase letters, digits and symbols, not {}",
+                self.min_classes, used
+            ));
+        }
+        
+        if lacking.is_empty() {
+            Ok(())
+        } else {
+            Err(WalletError::WeakPassword { lacking })
+        }
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self { min_length: MIN_PASSWORD_LENGTH, min_classes: MIN_CHARACTER_CLASSES }
+    }
+}
+
+/// Record of the wrong passwords tried in a row, saved with the wallet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnlockThrottle {
+    /// Wrong passwords tried since the last right one
+    pub failed_attempts: u32,
+    
+    /// Time, in seconds since the Unix epoch, before which unlocking is refused
+    pub locked_until: u64,
+}
+
+impl UnlockThrottle {
+    /// Checks whether unlocking may be tried at `now`, failing with the time left otherwise
+    pub fn check(&self, now: u64) -> Result<()> {
+        match self.locked_until.saturating_sub(now) {
+            0 => Ok(()),
+            retry_after => Err(WalletError::UnlockThrottled { retry_after }),
+        }
+    }
+    
+    /// Notes a wrong password tried at `now`, returning how long unlocking is then locked out for
+    pub fn record_failure(&mut self, now: u64) -> Duration {
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+        let delay = unlock_delay(self.failed_attempts);
+        if !delay.is_zero() {
+            self.locked_until = now.saturating_add(delay.as_secs());
+        }
+        delay
+    }
+    
+    /// Forgets the wrong passwords, after the right one
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Gets how long unlocking is locked out for after a number of wrong passwords in a row
+pub fn unlock_delay(failed_attempts: u32) -> Duration {
+    if failed_attempts <= FREE_UNLOCK_ATTEMPTS {
+        return Duration::ZERO;
+    }
+    let doublings = (failed_attempts - FREE_UNLOCK_ATTEMPTS - 1).min(31);
+    BASE_UNLOCK_DELAY.saturating_mul(1 << doublings).min(MAX_UNLOCK_DELAY)
+}
\ No newline at end of file
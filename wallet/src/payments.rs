@@ -0,0 +1,368 @@
+//! Payment requests, and spotting the payments that settle them
+//!
+//! Wallet accounts aren't derived from a seed, so a merchant can't hand
+//! out a fresh address per order. Instead a request asks for a transfer to
+//! one of the wallet's accounts carrying the request's reference as its
+//! data, its memo, which tells the payments of different requests apart.
+//! Requests can be shared as JSON or as a `genx:` URI (see
+//! `PaymentRequest::to_uri`).
+//!
+//! The `PaymentWatcher` is fed the chain's blocks in order. Transfers to a
+//! request's account with its memo count towards it once they're safe,
+//! having the configured number of confirmations, or finalized (see
+//! `ctb_core::confirmation`), and only if their block was made
+//! before the request expired; later ones are kept as late payments, to
+//! be refunded. A request is paid once what it received covers its
+//! amount, and otherwise ends up underpaid or, having received nothing,
+//! expired once the chain passes its expiry. Each change of status makes
+//! a `PaymentEvent`. Each payment seen carries its own `ConfirmationStatus`,
+//! judged again at every block.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use ctb_core::block::Block;
+use ctb_core::confirmation::{ChainTip, ConfirmationStatus};
+use ctb_core::TxHash;
+
+use crate::{Result, WalletError};
+
+/// Scheme of payment request URIs
+pub const URI_SCHEME: &str = "genx";
+
+/// Longest reference a request may have, in bytes
+pub const MAX_REFERENCE_LEN: usize = 64;
+
+/// How payments to requests are judged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentConfig {
+    /// Blocks a payment's block must have on top of it, itself included, to count
+    pub confirmations: u64,
+    
+    /// Seconds a request stays open after it's created
+    pub expiry: u64,
+}
+
+impl Default for PaymentConfig {
+    fn default() -> Self {
+        Self {
+            confirmations: 6,
+            expiry: 3600,
+        }
+    }
+}
+
+/// Request for a payment to a wallet account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentRequest {
+    /// Account to pay
+    pub account: String,
+    
+    /// Amount asked for, in base units
+    pub amount: u64,
+    
+    /// Reference the payer copies into the transaction's data, such as an order ID
+    pub reference: String,
+    
+    /// When the request was created
+    pub created_at: u64,
+    
+    /// Latest block timestamp a payment counts at
+    pub expires_at: u64,
+}
+
+impl PaymentRequest {
+    /// Gets the data a payment must carry
+    pub fn memo(&self) -> Vec<u8> {
+        self.reference.as_bytes().to_vec()
+    }
+    
+    /// Encodes the request as `genx:<account>?amount=<amount>&reference=<reference>&created=<time>&expires=<time>`
+    pub fn to_uri(&self) -> String {
+        format!(
+            "{}:{}?amount={}&reference={}&created={}&expires={}",
+            URI_SCHEME, self.account, self.amount, self.reference, self.created_at, self.expires_at
+        )
+    }
+    
+    /// Decodes a request from its URI
+    pub fn from_uri(uri: &str) -> Result<Self> {
+        let invalid = |reason: &str| WalletError::InvalidPaymentRequest(format!("{}: {}", reason, uri));
+        
+        let rest = uri
+            .strip_prefix(URI_SCHEME)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .ok_or_else(|| invalid("not a genx: URI"))?;
+        let (account, query) = rest.split_once('?').ok_or_else(|| invalid("no parameters"))?;
+        
+        let mut params = HashMap::new();
+        for param in query.split('&') {
+            let (name, value) = param.split_once('=').ok_or_else(|| invalid("malformed parameter"))?;
+            if params.insert(name, value).is_some() {
+                return Err(invalid("repeated parameter"));
+            }
+        }
+        let mut number = |name: &str| -> Result<u64> {
+            params.remove(name).and_then(|value| value.parse().ok()).ok_or_else(|| invalid(&format!("missing or invalid {}", name)))
+        };
+        
+        let request = Self {
+            account: account.to_string(),
+            amount: number("amount")?,
+            created_at: number("created")?,
+            expires_at: number("expires")?,
+            reference: params.remove("reference").ok_or_else(|| invalid("missing reference"))?.to_string(),
+        };
+        if let Some(name) = params.keys().next() {
+            return Err(invalid(&format!("unknown parameter {}", name)));
+        }
+        validate_reference(&request.reference)?;
+        Ok(request)
+    }
+}
+
+impl fmt::Display for PaymentRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_uri())
+    }
+}
+
+/// Checks that a reference can be carried in a URI as it is
+///
+/// References are 1 to `MAX_REFERENCE_LEN` ASCII letters, digits, `-`,
+/// `_` and `.`.
+pub fn validate_reference(reference: &str) -> Result<()> {
+    let valid = !reference.is_empty()
+        && reference.len() <= MAX_REFERENCE_LEN
+        && reference.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    if !valid {
+        return Err(WalletError::InvalidPaymentRequest(format!(
+            "reference {:?} must be 1 to {} letters, digits, '-', '_' or '.'",
+            reference, MAX_REFERENCE_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Where a payment request stands
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PaymentStatus {
+    /// Nothing received yet
+    Pending,
+    
+    /// Payments were seen but don't all have enough confirmations yet
+    Confirming { received: u64 },
+    
+    /// Less than the amount was received; more can still arrive until the request expires
+    Underpaid { received: u64 },
+    
+    /// The amount was received
+    Paid { received: u64 },
+    
+    /// The request expired without receiving anything
+    Expired,
+}
+
+/// Payment seen for a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceivedPayment {
+    /// ID of the transaction
+    pub tx_id: TxHash,
+    
+    /// Height of the block it was included in
+    pub block_height: u64,
+    
+    /// Amount it transferred
+    pub amount: u64,
+    
+    /// Whether its block was made after the request expired, so it doesn't count
+    pub late: bool,
+    
+    /// How settled it is, as of the latest block processed while the request was open
+    #[serde(default)]
+    pub status: ConfirmationStatus,
+}
+
+/// Change of a payment request's status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentEvent {
+    /// Reference of the request
+    pub reference: String,
+    
+    /// Its new status
+    pub status: PaymentStatus,
+    
+    /// Height of the block that changed it
+    pub block_height: u64,
+}
+
+/// A request and what it received
+#[derive(Debug, Clone)]
+struct Watched {
+    request: PaymentRequest,
+    status: PaymentStatus,
+    payments: Vec<ReceivedPayment>,
+    
+    /// Whether the status is final: paid, or the request expired
+    settled: bool,
+}
+
+/// Payment requests being watched for payments
+#[derive(Debug, Default)]
+pub struct PaymentWatcher {
+    config: PaymentConfig,
+    
+    /// Requests by reference
+    requests: HashMap<String, Watched>,
+    
+    /// Height of the latest block processed
+    height: Option<u64>,
+    
+    /// Height of the chain's latest finalized block, as last told
+    finalized_height: u64,
+}
+
+impl PaymentWatcher {
+    /// Creates a watcher judging payments by `config`
+    pub fn new(config: PaymentConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+    
+    /// Gets how payments are judged
+    pub fn config(&self) -> PaymentConfig {
+        self.config
+    }
+    
+    /// Changes how payments are judged, from the next block processed on
+    pub fn set_config(&mut self, config: PaymentConfig) {
+        self.config = config;
+    }
+    
+    /// Notes the height of the chain's latest finalized block, for the next block processed
+    pub fn set_finalized_height(&mut self, finalized_height: u64) {
+        self.finalized_height = finalized_height;
+    }
+    
+    /// Creates a request for `amount` to `account`, open for the configured expiry from `now`
+    ///
+    /// Fails if the amount is zero, or the reference is invalid or already used.
+    pub fn create(&mut self, account: &str, amount: u64, reference: &str, now: u64) -> Result<PaymentRequest> {
+        validate_reference(reference)?;
+        if amount == 0 {
+            return Err(WalletError::InvalidPaymentRequest("amount must be positive".to_string()));
+        }
+        if self.requests.contains_key(reference) {
+            return Err(WalletError::InvalidPaymentRequest(format!("reference {} is already used", reference)));
+        }
+        
+        let request = PaymentRequest {
+            account: account.to_string(),
+            amount,
+            reference: reference.to_string(),
+            created_at: now,
+            expires_at: now.saturating_add(self.config.expiry),
+        };
+        self.requests.insert(reference.to_string(), Watched {
+            request: request.clone(),
+            status: PaymentStatus::Pending,
+            payments: Vec::new(),
+            settled: false,
+        });
+        Ok(request)
+    }
+    
+    /// Gets a request by its reference
+    pub fn request(&self, reference: &str) -> Option<&PaymentRequest> {
+        self.requests.get(reference).map(|watched| &watched.request)
+    }
+    
+    /// Gets a request's status
+    pub fn status(&self, reference: &str) -> Option<&PaymentStatus> {
+        self.requests.get(reference).map(|watched| &watched.status)
+    }
+    
+    /// Gets the payments seen for a request, late ones included, oldest first
+    pub fn payments(&self, reference: &str) -> Vec<ReceivedPayment> {
+        self.requests.get(reference).map(|watched| watched.payments.clone()).unwrap_or_default()
+    }
+    
+    /// Stops watching a request, returning it
+    pub fn remove(&mut self, reference: &str) -> Option<PaymentRequest> {
+        self.requests.remove(reference).map(|watched| watched.request)
+    }
+    
+    /// Processes the next block of the chain, returning the requests whose status changed
+    ///
+    /// A block at or below the latest processed replaces the blocks from
+    /// its height on, as after a reorganization: payments seen in them are
+    /// forgotten by the requests not yet settled.
+    pub fn process_block(&mut self, block: &Block) -> Vec<PaymentEvent> {
+        let header = block.header();
+        if self.height.is_some_and(|height| header.height <= height) {
+            for watched in self.requests.values_mut().filter(|watched| !watched.settled) {
+                watched.payments.retain(|payment| payment.block_height < header.height);
+            }
+        }
+        self.height = Some(header.height);
+        
+        for tx in &block.transactions {
+            let Some(memo) = &tx.data else {
+                continue;
+            };
+            let Some(watched) = std::str::from_utf8(memo).ok().and_then(|reference| self.requests.get_mut(reference)) else {
+                continue;
+            };
+            if tx.recipient == watched.request.account {
+                watched.payments.push(ReceivedPayment {
+                    tx_id: tx.id,
+                    block_height: header.height,
+                    amount: tx.amount,
+                    late: header.timestamp > watched.request.expires_at,
+                    status: ConfirmationStatus::Pending,
+                });
+            }
+        }
+        
+        let tip = ChainTip::new(header.height, self.finalized_height);
+        let mut events = Vec::new();
+        for (reference, watched) in self.requests.iter_mut().filter(|(_, watched)| !watched.settled) {
+            for payment in &mut watched.payments {
+                payment.status = tip.status(Some(payment.block_height), self.config.confirmations);
+            }
+            let (status, settled) = judge(watched, header.timestamp);
+            watched.settled = settled;
+            if status != watched.status {
+                watched.status = status.clone();
+                events.push(PaymentEvent { reference: reference.clone(), status, block_height: header.height });
+            }
+        }
+        events.sort_by(|a, b| a.reference.cmp(&b.reference));
+        events
+    }
+}
+
+/// Works out a request's status from its payments' at a block's timestamp, and whether it's final
+fn judge(watched: &Watched, timestamp: u64) -> (PaymentStatus, bool) {
+    let counted = watched.payments.iter().filter(|payment| !payment.late);
+    let (confirmed, unconfirmed): (Vec<&ReceivedPayment>, Vec<&ReceivedPayment>) =
+        counted.partition(|payment| payment.status.is_safe());
+    let received = confirmed.iter().fold(0u64, |total, payment| total.saturating_add(payment.amount));
+    let seen = unconfirmed.iter().fold(received, |total, payment| total.saturating_add(payment.amount));
+    
+    if received >= watched.request.amount {
+        return (PaymentStatus::Paid { received }, true);
+    }
+    if !unconfirmed.is_empty() {
+        return (PaymentStatus::Confirming { received: seen }, false);
+    }
+    
+    let expired = timestamp > watched.request.expires_at;
+    match received {
+        0 if expired => (PaymentStatus::Expired, true),
+        0 => (PaymentStatus::Pending, false),
+        received => (PaymentStatus::Underpaid { received }, expired),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,154 @@
+//! Reservations of the transactions a wallet has in flight
+//!
+//! A sender's transactions are applied one nonce after another (see
+//! `ctb_core::state::State::get_nonce`), so one created while others are in
+//! flight needs the nonce after theirs rather than the chain's next, and
+//! each would otherwise be checked against a balance that doesn't count
+//! the others. The `PendingLedger` hands out each account's next nonce
+//! counting its transactions in flight, and keeps what each transaction can spend,
+//! its amount and its most fees, reserved against the account's confirmed
+//! balance until it's released: once it's confirmed, fails or is cancelled.
+//! Transactions that fail before reaching a block, such as those the node
+//! drops once a conflicting spend is confirmed, are remembered as failed,
+//! and their nonces handed out again; each account's timestamps only move
+//! forward, so the transaction taking a failed one's nonce has its own ID.
+
+use std::collections::HashMap;
+
+use ctb_core::transaction::Transaction;
+use ctb_core::TxHash;
+
+/// A transaction in flight and what it can spend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    /// ID of the transaction
+    pub tx_id: TxHash,
+    
+    /// Sender of the transaction
+    pub sender: String,
+    
+    /// Nonce of the transaction, its position among its sender's
+    pub nonce: u64,
+    
+    /// Most the transaction can take from its sender: its amount and its most fees
+    pub spend: u64,
+}
+
+impl Reservation {
+    /// Makes the reservation of a transaction
+    pub fn of(tx: &Transaction) -> Self {
+        Self {
+            tx_id: tx.id,
+            sender: tx.sender.clone(),
+            nonce: tx.nonce,
+            spend: tx.max_cost(),
+        }
+    }
+}
+
+/// An account's transactions in flight
+#[derive(Debug, Default)]
+struct AccountLedger {
+    /// Latest timestamp handed out, kept after its transaction is released
+    last_timestamp: u64,
+    
+    /// Transactions in flight, by ID
+    reservations: HashMap<TxHash, Reservation>,
+    
+    /// Transactions dropped before reaching a block, by ID
+    failed: HashMap<TxHash, Reservation>,
+}
+
+/// Transactions in flight from a wallet's accounts
+#[derive(Debug, Default)]
+pub struct PendingLedger {
+    accounts: HashMap<String, AccountLedger>,
+}
+
+impl PendingLedger {
+    /// Creates an empty ledger
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Gets the nonce for an account's next transaction, given the next one the chain expects
+    ///
+    /// That's `confirmed`, unless transactions in flight have it or later
+    /// ones, in which case it's the one after the latest.
+    pub fn next_nonce(&self, account: &str, confirmed: u64) -> u64 {
+        self.accounts
+            .get(account)
+            .and_then(|ledger| ledger.reservations.values().map(|reservation| reservation.nonce + 1).max())
+            .map_or(confirmed, |next| next.max(confirmed))
+    }
+    
+    /// Gets the timestamp for an account's next transaction created at `now`
+    ///
+    /// That's `now`, unless the account was handed `now` or later already,
+    /// in which case it's the one after the latest, so a transaction taking
+    /// the nonce of one that failed or was cancelled doesn't repeat its ID.
+    pub fn next_timestamp(&self, account: &str, now: u64) -> u64 {
+        match self.accounts.get(account) {
+            Some(ledger) if ledger.last_timestamp >= now => ledger.last_timestamp + 1,
+            _ => now,
+        }
+    }
+    
+    /// Gets the total an account's transactions in flight can spend
+    pub fn pending_spend(&self, account: &str) -> u64 {
+        self.accounts.get(account).map_or(0, |ledger| {
+            ledger.reservations.values().fold(0u64, |total, reservation| total.saturating_add(reservation.spend))
+        })
+    }
+    
+    /// Gets an account's transactions in flight, in nonce order
+    pub fn reservations(&self, account: &str) -> Vec<Reservation> {
+        let mut reservations: Vec<Reservation> = self
+            .accounts
+            .get(account)
+            .map(|ledger| ledger.reservations.values().cloned().collect())
+            .unwrap_or_default();
+        reservations.sort_by_key(|reservation| reservation.nonce);
+        reservations
+    }
+    
+    /// Gets an account's transactions that failed before reaching a block, in nonce order
+    pub fn failed(&self, account: &str) -> Vec<Reservation> {
+        let mut failed: Vec<Reservation> = self
+            .accounts
+            .get(account)
+            .map(|ledger| ledger.failed.values().cloned().collect())
+            .unwrap_or_default();
+        failed.sort_by_key(|reservation| reservation.nonce);
+        failed
+    }
+    
+    /// Checks whether a transaction is in flight
+    pub fn contains(&self, tx_id: &TxHash) -> bool {
+        self.accounts.values().any(|ledger| ledger.reservations.contains_key(tx_id))
+    }
+    
+    /// Reserves what a transaction can spend, its nonce and its timestamp
+    pub fn reserve(&mut self, tx: &Transaction) {
+        let reservation = Reservation::of(tx);
+        let ledger = self.accounts.entry(reservation.sender.clone()).or_default();
+        ledger.last_timestamp = ledger.last_timestamp.max(tx.timestamp);
+        ledger.reservations.insert(reservation.tx_id, reservation);
+    }
+    
+    /// Releases a transaction's reservation, returning it if it was in flight
+    pub fn release(&mut self, tx_id: &TxHash) -> Option<Reservation> {
+        self.accounts.values_mut().find_map(|ledger| ledger.reservations.remove(tx_id))
+    }
+    
+    /// Releases a transaction's reservation and remembers it as failed, returning whether it was in flight
+    pub fn fail(&mut self, tx_id: &TxHash) -> bool {
+        self.accounts.values_mut().any(|ledger| match ledger.reservations.remove(tx_id) {
+            Some(reservation) => {
+                ledger.failed.insert(reservation.tx_id, reservation);
+                true
+            }
+            None => false,
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,102 @@
+//! Finding which of a sequence of addresses were used, as when restoring keys
+//!
+//! The wallet's own accounts aren't derived from a seed (see `payments`),
+//! but keys restored from a wallet that derives them, such as an HD wallet,
+//! are found the way such wallets find theirs: addresses are derived in
+//! order, by index, and checked until `gap_limit` in a row turn out unused.
+//! Addresses handed out but never paid leave gaps, and a used address past
+//! a gap as long as the limit is missed.
+//!
+//! `recover` looks `lookahead` addresses further past such a gap, cheaply:
+//! each address is first checked against the node's address blooms (see
+//! `ctb_core::address_bloom`), and only those the blooms say may have been used
+//! are confirmed by the node's scan of the blocks. The blooms never miss a
+//! used address, so scanning only goes on past the gap limit when they
+//! show activity there, and an address they rule out is never scanned for.
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::ChainClient;
+use crate::Result;
+
+/// Unused addresses in a row after which derived addresses are taken to be unused
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Addresses past the gap limit checked against the address blooms
+pub const DEFAULT_LOOKAHEAD: u32 = 100;
+
+/// Derived address found to be used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsedAddress {
+    /// Index it was derived at
+    pub index: u32,
+    
+    /// The address itself
+    pub address: String,
+    
+    /// Height of the first block it was used in
+    pub first_used: u64,
+}
+
+/// What `recover` found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recovery {
+    /// Addresses found to be used, by index
+    pub used: Vec<UsedAddress>,
+    
+    /// Index of the next address to hand out, one past the last used
+    pub next_index: u32,
+    
+    /// Addresses checked against the address blooms
+    pub checked: u32,
+    
+    /// Addresses the blooms said may have been used that the blocks were scanned for
+    pub scanned: u32,
+}
+
+impl Recovery {
+    /// Gets the addresses the blooms matched that turned out never to have been used
+    pub fn false_positives(&self) -> u32 {
+        self.scanned - self.used.len() as u32
+    }
+}
+
+/// Finds the used addresses among those `derive` gives for indexes from 0 on
+///
+/// Stops once `gap_limit` addresses in a row were unused and the blooms
+/// rule out the `lookahead` addresses after them, or after the last used
+/// address once as many addresses in a row were unused.
+pub fn recover(
+    client: &dyn ChainClient,
+    gap_limit: u32,
+    lookahead: u32,
+    mut derive: impl FnMut(u32) -> Result<String>,
+) -> Result<Recovery> {
+    let mut recovery = Recovery::default();
+    let mut unused_in_row = 0u32;
+    let mut index = 0u32;
+    while unused_in_row < gap_limit.saturating_add(lookahead) {
+        let address = derive(index)?;
+        recovery.checked += 1;
+        let first_used = if client.address_possibly_used(&address)? {
+            recovery.scanned += 1;
+            client.address_first_used(&address)?
+        } else {
+            None
+        };
+        
+        match first_used {
+            Some(first_used) => {
+                recovery.used.push(UsedAddress { index, address, first_used });
+                recovery.next_index = index + 1;
+                unused_in_row = 0;
+            }
+            None => unused_in_row += 1,
+        }
+        index = match index.checked_add(1) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(recovery)
+}
\ No newline at end of file
@@ -0,0 +1,27 @@
+//! Checks that the wallet file format round-trips and hasn't changed
+//!
+//! Run with `cargo test -p wallet --features testutil --test encoding`.
+//! Round-trips `CASES` generated accounts and wallets, the wallets through
+//! files in a temporary directory, then checks the fixtures against their
+//! golden hashes. Set `ENCODING_SEED` to start from another seed.
+
+use wallet::testutil;
+
+/// Generated cases checked on each run
+const CASES: u64 = 200;
+
+/// Checks the generated accounts and wallets round-trip
+#[test]
+fn check_generated() {
+    let seed = std::env::var("ENCODING_SEED").ok().and_then(|seed| seed.parse().ok()).unwrap_or(0);
+    let dir = std::env::temp_dir().join(format!("genx-wallet-encoding-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    testutil::check_encodings(seed, CASES, &dir);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Checks every fixture against its golden hash
+#[test]
+fn check_fixtures() {
+    testutil::check_fixtures();
+}
\ No newline at end of file
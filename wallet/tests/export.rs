@@ -0,0 +1,168 @@
+//! Checks wallet history exports byte for byte, with running balances ending at the chain's
+//!
+//! Run with `cargo test -p wallet --features testutil --test export`.
+//! Exports a fixture history of an account, holding a receipt, a transfer
+//! to itself, a transfer out and a failed one, as CSV with every column,
+//! and compares it with `tests/fixtures/history.csv`. Then has a wallet of
+//! two accounts trade transfers on a `MockChainClient`, one of them
+//! failing, with a credit the history doesn't show, and checks each
+//! account's running balance ends at its balance on the chain, in every
+//! format.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ctb_core::confirmation::ConfirmationStatus;
+use ctb_core::transaction::{Transaction, TransactionType};
+use ctb_core::units::{format_genx, Amount, GENX};
+use ctb_core::Address;
+use wallet::api::{ChainClient, TransactionRecord, WalletApi};
+use wallet::export::{self, Column, ExportFormat};
+use wallet::mock::MockChainClient;
+
+/// Account whose fixture history is exported
+const ALICE: &str = "GENX_FIXTURE_ALICE";
+
+/// The other party to the fixture history
+const BOB: &str = "GENX_FIXTURE_BOB";
+
+/// Timestamp of the fixture history's first block, 2024-01-01T00:00:00Z
+const FIXTURE_TIME: u64 = 1_704_067_200;
+
+/// Fee of each transfer
+const FEE: u64 = GENX / 100;
+
+/// Balance alice starts with on the mock chain
+const FUNDS: u64 = 1_000 * GENX;
+
+/// Password of the wallet made
+const PASSWORD: &str = "Export-history-wallet-42";
+
+/// Makes a record of a transfer confirmed at a height, with a fixed ID
+fn record(height: u64, from: &str, to: &str, amount: u64, success: bool) -> TransactionRecord {
+    let mut tx = Transaction::new_with_type(TransactionType::Transfer, from.to_string(), to.to_string(), amount, FEE, None, 0, 0).unwrap();
+    tx.timestamp = FIXTURE_TIME + height * 60 - 1;
+    tx.id = tx.calculate_hash().unwrap();
+    TransactionRecord {
+        block_height: height,
+        block_timestamp: FIXTURE_TIME + height * 60,
+        success,
+        fee_paid: FEE,
+        transaction: tx,
+        status: ConfirmationStatus::Pending,
+    }
+}
+
+/// Checks a fixture history exports to the golden CSV, byte for byte
+#[test]
+fn check_golden_csv() {
+    // Newest first, as the chain returns it
+    let history = [
+        record(4, ALICE, BOB, 5 * GENX, false),
+        record(3, ALICE, BOB, 25 * GENX / 10, true),
+        record(2, ALICE, ALICE, GENX, true),
+        record(1, BOB, ALICE, 10 * GENX, true),
+    ];
+    let entries = export::entries(ALICE, &history, 100 * GENX);
+    let mut csv = Vec::new();
+    export::write(&ExportFormat::default(), &entries, &mut csv).unwrap();
+    
+    let golden = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/history.csv")).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), String::from_utf8(golden).unwrap());
+    
+    // Chosen columns, in their order
+    let mut csv = Vec::new();
+    export::write_csv(&[Column::BlockHeight, Column::RunningBalance, Column::Direction], &entries, &mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "block_height,running_balance,direction\n1,102.53,in\n2,102.52,self\n3,100.01,out\n4,100,out\n");
+}
+
+/// A wallet of two accounts on a mock chain, its file removed once the test is done
+struct Setup {
+    api: WalletApi,
+    client: Arc<MockChainClient>,
+    alice: Address,
+    bob: Address,
+    path: PathBuf,
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Setup {
+    fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("genx-export-{}-balance.json", std::process::id()));
+        let mut api = WalletApi::create_wallet(path.clone(), PASSWORD).unwrap();
+        api.unlock(PASSWORD).unwrap();
+        let alice: Address = api.create_account("alice").unwrap().parse().unwrap();
+        let bob: Address = api.create_account("bob").unwrap().parse().unwrap();
+        let client = Arc::new(MockChainClient::new());
+        client.set_balance(alice.as_str(), FUNDS);
+        api.set_client(client.clone());
+        Setup { api, client, alice, bob, path }
+    }
+    
+    /// Sends a transfer between the accounts, returning it
+    fn send(&self, from: &Address, to: &Address, amount: u64) -> Transaction {
+        let tx = self.api.create_transaction(from, to, Amount::from_base_units(amount), Amount::from_base_units(FEE), None).unwrap();
+        self.api.send_transaction(&tx).unwrap();
+        tx
+    }
+    
+    /// Exports every account's history as CSV of the account and running balance columns
+    fn balances(&self) -> Vec<(String, String)> {
+        let mut csv = Vec::new();
+        let format = ExportFormat::Csv(vec![Column::Account, Column::RunningBalance]);
+        self.api.export_history(None, None, None, &format, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        csv.lines().skip(1).map(|line| {
+            let (account, balance) = line.split_once(',').unwrap();
+            (account.to_string(), balance.to_string())
+        }).collect()
+    }
+    
+    /// Gets an account's balance on the chain, written as exported
+    fn on_chain(&self, account: &Address) -> String {
+        format_genx(self.api.get_balance(account.as_str()).unwrap().base_units())
+    }
+}
+
+/// Checks each account's running balance ends at its balance on the chain, in chain order
+#[test]
+fn check_running_balance() {
+    let setup = Setup::new();
+    setup.send(&setup.alice, &setup.bob, 100 * GENX);
+    setup.client.advance_block();
+    let failed = setup.send(&setup.alice, &setup.bob, 50 * GENX);
+    setup.client.fail_at(&failed.id, setup.client.height() + 1);
+    setup.send(&setup.bob, &setup.alice, 20 * GENX);
+    setup.client.advance_block();
+    setup.send(&setup.alice, &setup.alice, 7 * GENX);
+    setup.client.advance_block();
+    
+    // Credited outside the history, as a contract would
+    let bob_balance = setup.client.get_balance(setup.bob.as_str()).unwrap();
+    setup.client.set_balance(setup.bob.as_str(), bob_balance + 3 * GENX);
+    
+    let balances = setup.balances();
+    assert_eq!(balances.len(), 7, "{:?}", balances);
+    for account in [&setup.alice, &setup.bob] {
+        let running: Vec<&String> = balances.iter().filter(|(of, _)| of == account.as_str()).map(|(_, balance)| balance).collect();
+        assert_eq!(running.last().unwrap().as_str(), setup.on_chain(account), "{}", account);
+    }
+    let alice: Vec<&str> = balances.iter().filter(|(of, _)| of == setup.alice.as_str()).map(|(_, balance)| balance.as_str()).collect();
+    assert_eq!(alice, ["899.99", "899.98", "919.98", "919.97"]);
+    
+    // The OFX statement's ledger balance and the QIF register's changes agree
+    let mut ofx = Vec::new();
+    setup.api.export_history(Some(setup.alice.as_str()), None, None, &ExportFormat::Ofx, &mut ofx).unwrap();
+    let ofx = String::from_utf8(ofx).unwrap();
+    assert!(ofx.contains(&format!("<BALAMT>{}\r\n", setup.on_chain(&setup.alice))), "{}", ofx);
+    let mut qif = Vec::new();
+    setup.api.export_history(Some(setup.alice.as_str()), None, None, &ExportFormat::Qif, &mut qif).unwrap();
+    let qif = String::from_utf8(qif).unwrap();
+    let changes: Vec<&str> = qif.lines().filter_map(|line| line.strip_prefix('T')).filter(|change| *change != "Bank").collect();
+    assert_eq!(changes, ["-100.01", "-0.01", "20", "-0.01"]);
+}
\ No newline at end of file
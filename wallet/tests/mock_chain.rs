@@ -0,0 +1,297 @@
+//! Checks the wallet against a scripted chain
+//!
+//! Run with `cargo test -p wallet --features testutil --test mock_chain`.
+//! Connects a wallet of two accounts to a `MockChainClient` and scripts
+//! what the chain does with its transactions: confirming them at chosen
+//! heights, failing, dropping or rejecting them, or being unreachable.
+//! Checks the wallet's reservations, history, fee suggestions, payment
+//! events and confirmation statuses follow, through a reorganization.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ctb_core::confirmation::ConfirmationStatus;
+use ctb_core::fee_market::FeeRate;
+use ctb_core::paging::{self, PageRequest};
+use ctb_core::transaction::Transaction;
+use ctb_core::units::{Amount, GENX};
+use ctb_core::{Address, BlockchainError};
+use wallet::api::{ChainClient, WalletApi};
+use wallet::fees::FeeOracle;
+use wallet::mock::{MockChainClient, UNAVAILABLE};
+use wallet::payments::PaymentStatus;
+use wallet::WalletError;
+
+/// Password of the wallets made
+const PASSWORD: &str = "Mock-chain-wallet-42";
+
+/// Balance alice starts with
+const FUNDS: u64 = 1_000 * GENX;
+
+/// Fee of each transfer
+const FEE: u64 = GENX / 100;
+
+/// Wallet of two accounts connected to a chain where alice has `FUNDS`
+struct Setup {
+    api: WalletApi,
+    client: Arc<MockChainClient>,
+    alice: Address,
+    bob: Address,
+    path: PathBuf,
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Makes a wallet in its own temporary file and connects it to a new chain
+fn setup(name: &str) -> Setup {
+    let path = std::env::temp_dir().join(format!("genx-wallet-mock-chain-{}-{}.json", std::process::id(), name));
+    let mut api = WalletApi::create_wallet(path.clone(), PASSWORD).unwrap();
+    api.unlock(PASSWORD).unwrap();
+    let alice: Address = api.create_account("alice").unwrap().parse().unwrap();
+    let bob: Address = api.create_account("bob").unwrap().parse().unwrap();
+    
+    let client = Arc::new(MockChainClient::new());
+    client.set_balance(alice.as_str(), FUNDS);
+    api.set_client(client.clone());
+    Setup { api, client, alice, bob, path }
+}
+
+/// Creates a transfer from alice to bob, reserving it
+fn transfer(setup: &Setup, amount: u64, data: Option<Vec<u8>>) -> wallet::Result<Transaction> {
+    setup.api.create_transaction(&setup.alice, &setup.bob, Amount::from_base_units(amount), Amount::from_base_units(FEE), data)
+}
+
+/// Creates and sends a transfer from alice to bob
+fn send(setup: &Setup, amount: u64) -> Transaction {
+    let tx = transfer(setup, amount, None).unwrap();
+    setup.api.send_transaction(&tx).unwrap();
+    tx
+}
+
+/// Checks a transaction confirmed at a scheduled height stays in flight until then
+#[test]
+fn check_scheduled_confirmation() {
+    let setup = setup("scheduled");
+    setup.client.set_inclusion_delay(None);
+    let tx = send(&setup, 10 * GENX);
+    let height = setup.client.height() + 3;
+    setup.client.confirm_at(&tx.id, height);
+    
+    setup.client.advance_blocks(2);
+    assert_eq!(setup.api.release_confirmed(setup.alice.as_str()).unwrap(), 0);
+    assert_eq!(setup.api.pending_transactions(setup.alice.as_str()).len(), 1);
+    assert!(setup.client.get_transaction(&tx.id).unwrap().is_none());
+    
+    let block = setup.client.advance_block();
+    assert_eq!((block.header().height, block.transactions.len()), (height, 1));
+    let record = setup.client.get_transaction(&tx.id).unwrap().unwrap();
+    assert_eq!((record.block_height, record.success, record.fee_paid), (height, true, FEE));
+    assert_eq!(record.block_timestamp, block.header().timestamp);
+    assert_eq!(setup.api.release_confirmed(setup.alice.as_str()).unwrap(), 1);
+    assert!(setup.api.pending_transactions(setup.alice.as_str()).is_empty());
+    assert_eq!(setup.api.get_balance(setup.alice.as_str()).unwrap().base_units(), FUNDS - 10 * GENX - FEE);
+    assert_eq!(setup.api.get_balance(setup.bob.as_str()).unwrap().base_units(), 10 * GENX);
+}
+
+/// Checks a rejected broadcast reports the node's error code and releases its reservation
+#[test]
+fn check_rejection() {
+    let setup = setup("rejection");
+    setup.client.reject_next_submission(BlockchainError::InvalidTransaction("fee too low".to_string()));
+    
+    // Reserving all of alice's funds twice only works if the first reservation is released
+    let spend_all = FUNDS - FEE;
+    let tx = transfer(&setup, spend_all, None).unwrap();
+    let error = setup.api.send_transaction(&tx).unwrap_err();
+    assert!(matches!(&error, WalletError::BlockchainError(BlockchainError::InvalidTransaction(_))), "{}", error);
+    assert_eq!(error.error_code(), 1001);
+    assert!(setup.api.pending_transactions(setup.alice.as_str()).is_empty());
+    assert!(setup.client.pending_transactions().is_empty());
+    
+    let tx = send(&setup, spend_all);
+    assert!(matches!(transfer(&setup, 1, None), Err(WalletError::InsufficientFunds { .. })));
+    setup.client.advance_block();
+    assert_eq!(setup.client.get_transaction(&tx.id).unwrap().unwrap().block_height, 1);
+}
+
+/// Checks queries fail while the chain is unavailable and work once it's back
+#[test]
+fn check_unavailable() {
+    let setup = setup("unavailable");
+    setup.client.set_unavailable(true);
+    let error = setup.api.get_balance(setup.alice.as_str()).unwrap_err();
+    assert!(error.to_string().contains(UNAVAILABLE), "{}", error);
+    assert_eq!(error.error_code(), 1002);
+    assert!(transfer(&setup, GENX, None).is_err());
+    assert!(setup.api.fee_oracle().unwrap().suggest(false, 1).is_err());
+    
+    setup.client.set_unavailable(false);
+    let tx = send(&setup, GENX);
+    setup.client.advance_block();
+    assert!(setup.client.get_transaction(&tx.id).unwrap().is_some());
+}
+
+/// Checks dropped transactions are marked failed and failed ones only cost their fee
+#[test]
+fn check_dropped_and_failed() {
+    let setup = setup("failures");
+    setup.client.set_inclusion_delay(None);
+    let dropped = send(&setup, 600 * GENX);
+    assert!(matches!(transfer(&setup, 600 * GENX, None), Err(WalletError::InsufficientFunds { .. })));
+    assert!(setup.client.drop_transaction(&dropped.id));
+    assert!(setup.api.mark_failed(&dropped.id));
+    assert_eq!(setup.api.failed_transactions(setup.alice.as_str()).len(), 1);
+    
+    // Its funds are free again, and the chain never includes it
+    let failing = send(&setup, 600 * GENX);
+    setup.client.fail_at(&failing.id, setup.client.height() + 1);
+    let block = setup.client.advance_block();
+    assert_eq!(block.transactions.len(), 1);
+    assert!(setup.client.get_transaction(&dropped.id).unwrap().is_none());
+    
+    let record = setup.client.get_transaction(&failing.id).unwrap().unwrap();
+    assert!(!record.success);
+    assert_eq!(setup.api.release_confirmed(setup.alice.as_str()).unwrap(), 1);
+    assert_eq!(setup.api.get_balance(setup.alice.as_str()).unwrap().base_units(), FUNDS - FEE);
+    assert_eq!(setup.api.get_balance(setup.bob.as_str()).unwrap().base_units(), 0);
+}
+
+/// Checks fee suggestions follow the base fee and the transactions waiting
+#[test]
+fn check_fees() {
+    let setup = setup("fees");
+    setup.client.set_inclusion_delay(None);
+    let oracle = setup.api.fee_oracle().unwrap();
+    let suggestion = oracle.suggest(false, 1).unwrap();
+    assert_eq!((suggestion.rate, suggestion.estimate.blocks), (FeeRate::FlatFee(0), Some(1)));
+    
+    setup.client.set_base_fee(50);
+    let suggestion = oracle.suggest(true, 1).unwrap();
+    assert_eq!((suggestion.rate, suggestion.estimate.blocks), (FeeRate::GasPrice(50), Some(1)));
+    
+    // Transfers waiting are counted ahead of lower fees and not higher ones
+    for _ in 0..3 {
+        send(&setup, GENX);
+    }
+    assert_eq!(setup.client.fee_histogram().unwrap().transactions(), 3);
+    assert_eq!(setup.client.estimate_inclusion(FeeRate::FlatFee(FEE - 1)).unwrap().transactions_ahead, 3);
+    assert_eq!(setup.client.estimate_inclusion(FeeRate::FlatFee(FEE * 2)).unwrap().transactions_ahead, 0);
+    
+    setup.client.set_inclusion_delay(Some(0));
+    setup.client.advance_block();
+    assert_eq!(setup.client.fee_histogram().unwrap().transactions(), 0);
+}
+
+/// Checks a payment to a request is announced to subscribers as it's confirmed
+#[test]
+fn check_payments() {
+    let setup = setup("payments");
+    let amount = 25 * GENX;
+    setup.api.create_payment_request(setup.bob.as_str(), amount, "order-7").unwrap();
+    let events = setup.api.subscribe_payments();
+    
+    let tx = transfer(&setup, amount, Some(b"order-7".to_vec())).unwrap();
+    setup.api.send_transaction(&tx).unwrap();
+    let mut statuses = Vec::new();
+    while setup.api.payment_status("order-7") != Some(PaymentStatus::Paid { received: amount }) {
+        assert!(setup.client.height() < 10, "paid after its confirmations");
+        let block = setup.client.advance_block();
+        statuses.extend(setup.api.process_block(&block).into_iter().map(|event| event.status));
+    }
+    
+    assert_eq!(statuses, vec![PaymentStatus::Confirming { received: amount }, PaymentStatus::Paid { received: amount }]);
+    assert_eq!(setup.client.height(), 6, "included in block 1, six confirmations");
+    let announced: Vec<PaymentStatus> = events.try_iter().map(|event| event.status).collect();
+    assert_eq!(announced, statuses);
+    let received = &setup.api.received_payments("order-7")[0];
+    assert_eq!(received.tx_id, tx.id);
+    assert_eq!(received.status, ConfirmationStatus::Safe { height: 1, confirmations: 6 });
+}
+
+/// Checks paging through an account's history gives what listing it at once does
+#[test]
+fn check_history() {
+    let setup = setup("history");
+    let mut sent = Vec::new();
+    for round in 0..4 {
+        for _ in 0..=round {
+            sent.push(send(&setup, GENX).id);
+        }
+        setup.client.advance_block();
+    }
+    
+    let alice = setup.alice.as_str();
+    #[allow(deprecated)]
+    let newest_first: Vec<_> = setup.api.get_history(alice, usize::MAX).unwrap().into_iter().map(|record| record.transaction.id).collect();
+    sent.reverse();
+    assert_eq!(newest_first, sent);
+    
+    let paged = paging::collect_pages(3, |request| setup.api.list_history(alice, request)).unwrap();
+    assert_eq!(paged.iter().map(|record| record.transaction.id).collect::<Vec<_>>(), sent);
+    let first = setup.api.list_history(setup.bob.as_str(), &PageRequest::new(4)).unwrap();
+    assert_eq!(first.items.len(), 4);
+    assert!(first.items.windows(2).all(|pair| pair[0].block_height >= pair[1].block_height));
+    
+    // Addresses count as used from the block of their first transaction
+    assert_eq!(setup.client.address_first_used(alice).unwrap(), Some(1));
+    assert!(!setup.client.address_possibly_used("GENX00").unwrap());
+}
+
+/// Checks a transaction moves through every stage, and back to pending when a reorganization takes its block out
+#[test]
+fn check_confirmation_stages() {
+    let setup = setup("confirmations");
+    setup.api.set_safe_confirmations(3);
+    let events = setup.api.subscribe_confirmations();
+    let tx = send(&setup, GENX);
+    assert_eq!(setup.api.transaction_status(&tx.id).unwrap(), ConfirmationStatus::Pending);
+    assert!(setup.api.refresh_confirmations().unwrap().is_empty());
+    
+    let stages = || -> Vec<ConfirmationStatus> {
+        setup.api.refresh_confirmations().unwrap().into_iter().map(|event| event.status).collect()
+    };
+    setup.client.advance_block();
+    assert_eq!(stages(), vec![ConfirmationStatus::Included { height: 1, confirmations: 1 }]);
+    setup.client.advance_block();
+    assert!(stages().is_empty(), "a new confirmation doesn't change the stage");
+    assert_eq!(setup.api.transaction_status(&tx.id).unwrap(), ConfirmationStatus::Included { height: 1, confirmations: 2 });
+    
+    // Taken out of the chain, and held back until it's included again
+    setup.client.set_inclusion_delay(None);
+    assert_eq!(setup.client.reorganize(0).len(), 2);
+    let demoted = setup.api.refresh_confirmations().unwrap();
+    assert_eq!(demoted.len(), 1);
+    assert!(demoted[0].is_demotion());
+    assert_eq!(demoted[0].status, ConfirmationStatus::Pending);
+    assert_eq!(setup.api.get_balance(setup.bob.as_str()).unwrap().base_units(), 0);
+    
+    setup.client.set_inclusion_delay(Some(1));
+    setup.client.advance_block();
+    assert_eq!(stages(), vec![ConfirmationStatus::Included { height: 1, confirmations: 1 }]);
+    setup.client.advance_blocks(2);
+    assert_eq!(stages(), vec![ConfirmationStatus::Safe { height: 1, confirmations: 3 }]);
+    
+    // The wallet's threshold is its own: the node goes by the default six
+    let history = setup.api.list_history(setup.bob.as_str(), &PageRequest::new(1)).unwrap();
+    assert_eq!(history.items[0].status, ConfirmationStatus::Safe { height: 1, confirmations: 3 });
+    let record = setup.client.get_transaction(&tx.id).unwrap().unwrap();
+    assert_eq!(record.status, ConfirmationStatus::Included { height: 1, confirmations: 3 });
+    
+    setup.client.finalize(1);
+    assert_eq!(stages(), vec![ConfirmationStatus::Finalized { height: 1, confirmations: 3 }]);
+    setup.client.advance_block();
+    assert!(stages().is_empty(), "finalized transactions aren't followed any more");
+    
+    let announced: Vec<ConfirmationStatus> = events.try_iter().map(|event| event.status).collect();
+    assert_eq!(announced, vec![
+        ConfirmationStatus::Included { height: 1, confirmations: 1 },
+        ConfirmationStatus::Pending,
+        ConfirmationStatus::Included { height: 1, confirmations: 1 },
+        ConfirmationStatus::Safe { height: 1, confirmations: 3 },
+        ConfirmationStatus::Finalized { height: 1, confirmations: 3 },
+    ]);
+}
\ No newline at end of file
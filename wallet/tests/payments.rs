@@ -0,0 +1,187 @@
+//! Checks payment requests settle as paid, underpaid or expired from the blocks paying them
+//!
+//! Run with `cargo test -p wallet --features testutil --test payments`.
+//! Builds a test chain where alice pays bob's requests with transfers
+//! carrying their references, and has a `PaymentWatcher` process each
+//! block. An exact payment is paid once it has enough confirmations, a
+//! payment short of the amount leaves the request underpaid until the rest
+//! arrives or it expires, and a payment in a block made after the request
+//! expired is seen but doesn't count.
+
+use ctb_core::chainbuilder::{TestChain, TestChainConfig};
+use ctb_core::confirmation::ConfirmationStatus;
+use ctb_core::transaction::{Transaction, TransactionType};
+use wallet::payments::{PaymentConfig, PaymentEvent, PaymentRequest, PaymentStatus, PaymentWatcher};
+
+/// Seed of the chain built
+const SEED: u64 = 127;
+
+/// Amount each request asks for
+const AMOUNT: u64 = 50_000;
+
+/// Confirmations payments need
+const CONFIRMATIONS: u64 = 3;
+
+/// Seconds requests stay open
+const EXPIRY: u64 = 60;
+
+/// A chain, and a watcher that has processed its blocks
+struct Setup {
+    chain: TestChain,
+    watcher: PaymentWatcher,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let chain = TestChain::new(SEED);
+        let watcher = PaymentWatcher::new(PaymentConfig { confirmations: CONFIRMATIONS, expiry: EXPIRY });
+        Self { chain, watcher }
+    }
+    
+    /// Requests `AMOUNT` to bob, created at the time of the chain's tip
+    fn request(&mut self, reference: &str) -> PaymentRequest {
+        let now = self.tip_timestamp();
+        self.watcher.create(&self.chain.address("bob"), AMOUNT, reference, now).unwrap()
+    }
+    
+    /// Gets the timestamp of the chain's tip
+    fn tip_timestamp(&self) -> u64 {
+        self.chain.blocks().last().unwrap().header().timestamp
+    }
+    
+    /// Adds a block of payments from alice to accounts, returning the events it caused
+    ///
+    /// The block is made at `timestamp` if given, and a block interval after the tip if not.
+    fn pay(&mut self, payments: &[(&str, u64, &str)], timestamp: Option<u64>) -> Vec<PaymentEvent> {
+        let transactions: Vec<Transaction> = payments.iter().map(|(to, amount, reference)| self.payment(to, *amount, reference)).collect();
+        let timestamp = timestamp.unwrap_or(self.tip_timestamp() + TestChainConfig::default().block_interval);
+        self.chain.with_block(|b| transactions.into_iter().fold(b.at(timestamp), |b, tx| b.transaction(tx)));
+        self.watcher.process_block(self.chain.blocks().last().unwrap())
+    }
+    
+    /// Adds empty blocks, returning the events they caused
+    fn wait(&mut self, blocks: u64) -> Vec<PaymentEvent> {
+        (0..blocks).flat_map(|_| self.pay(&[], None)).collect()
+    }
+    
+    /// Makes a transfer from alice carrying a reference
+    fn payment(&self, to: &str, amount: u64, reference: &str) -> Transaction {
+        let (sender, recipient) = (self.chain.address("alice"), self.chain.address(to));
+        let fee = TestChainConfig::default().transfer_fee;
+        Transaction::new_with_type(TransactionType::Transfer, sender, recipient, amount, fee, Some(reference.as_bytes().to_vec()), 0, 0).unwrap()
+    }
+    
+    fn status(&self, reference: &str) -> PaymentStatus {
+        self.watcher.status(reference).cloned().unwrap()
+    }
+}
+
+/// Gets the statuses events announced for a reference
+fn statuses(events: &[PaymentEvent], reference: &str) -> Vec<PaymentStatus> {
+    events.iter().filter(|event| event.reference == reference).map(|event| event.status.clone()).collect()
+}
+
+/// Checks an exact payment confirms into paid, and payments to another account or reference don't count
+#[test]
+fn check_exact_payment() {
+    let mut setup = Setup::new();
+    let request = setup.request("order-1");
+    assert_eq!(setup.status("order-1"), PaymentStatus::Pending);
+    
+    let events = setup.pay(&[("carol", AMOUNT, "order-1"), ("bob", AMOUNT, "order-2")], None);
+    assert!(events.is_empty(), "{:?}", events);
+    assert!(setup.watcher.payments("order-1").is_empty());
+    
+    let events = setup.pay(&[("bob", AMOUNT, "order-1")], None);
+    let paid_in = setup.chain.height();
+    assert_eq!(statuses(&events, "order-1"), vec![PaymentStatus::Confirming { received: AMOUNT }]);
+    assert!(setup.wait(CONFIRMATIONS - 2).is_empty(), "nothing changes until the last confirmation");
+    let events = setup.wait(1);
+    assert_eq!(events, vec![PaymentEvent { reference: "order-1".to_string(), status: PaymentStatus::Paid { received: AMOUNT }, block_height: paid_in + CONFIRMATIONS - 1 }]);
+    
+    let payments = setup.watcher.payments("order-1");
+    assert_eq!(payments.len(), 1);
+    assert_eq!((payments[0].block_height, payments[0].amount, payments[0].late), (paid_in, AMOUNT, false));
+    assert_eq!(payments[0].status, ConfirmationStatus::Safe { height: paid_in, confirmations: CONFIRMATIONS });
+    
+    // A paid request stays paid, past its expiry too
+    setup.pay(&[], Some(request.expires_at + 1));
+    assert_eq!(setup.status("order-1"), PaymentStatus::Paid { received: AMOUNT });
+    assert!(setup.watcher.create(&request.account, AMOUNT, "order-1", request.created_at).is_err(), "a reference is used once");
+}
+
+/// Checks a payment short of the amount leaves a request underpaid until the rest arrives, or it expires
+#[test]
+fn check_underpayment() {
+    let mut setup = Setup::new();
+    setup.request("order-1");
+    let expiring = setup.request("order-2");
+    
+    let half = AMOUNT / 2;
+    let events = setup.pay(&[("bob", half, "order-1"), ("bob", half, "order-2")], None);
+    assert_eq!(statuses(&events, "order-1"), vec![PaymentStatus::Confirming { received: half }]);
+    let events = setup.wait(CONFIRMATIONS - 1);
+    for reference in ["order-1", "order-2"] {
+        assert_eq!(statuses(&events, reference), vec![PaymentStatus::Underpaid { received: half }]);
+    }
+    
+    // The rest settles the first
+    setup.pay(&[("bob", AMOUNT - half, "order-1")], None);
+    assert_eq!(setup.status("order-1"), PaymentStatus::Confirming { received: AMOUNT });
+    setup.wait(CONFIRMATIONS - 1);
+    assert_eq!(setup.status("order-1"), PaymentStatus::Paid { received: AMOUNT });
+    assert_eq!(setup.watcher.payments("order-1").len(), 2);
+    
+    // The second expires underpaid, and what arrives after doesn't change it
+    let events = setup.pay(&[], Some(expiring.expires_at + 1));
+    assert!(events.is_empty(), "{:?}", events);
+    setup.pay(&[("bob", AMOUNT - half, "order-2")], None);
+    setup.wait(CONFIRMATIONS);
+    assert_eq!(setup.status("order-2"), PaymentStatus::Underpaid { received: half });
+    let lateness: Vec<bool> = setup.watcher.payments("order-2").iter().map(|payment| payment.late).collect();
+    assert_eq!(lateness, vec![false, true]);
+}
+
+/// Checks a payment in a block made after the request expired is seen as late and leaves it expired
+#[test]
+fn check_payment_after_expiry() {
+    let mut setup = Setup::new();
+    let request = setup.request("order-1");
+    setup.wait(2);
+    assert_eq!(setup.status("order-1"), PaymentStatus::Pending);
+    
+    // A payment in the last block before the expiry still counts
+    let on_time = setup.request("order-2");
+    setup.pay(&[("bob", AMOUNT, "order-2")], Some(on_time.expires_at));
+    setup.wait(CONFIRMATIONS - 1);
+    assert_eq!(setup.status("order-2"), PaymentStatus::Paid { received: AMOUNT });
+    assert!(on_time.expires_at > request.expires_at);
+    
+    let late = setup.request("order-3");
+    let events = setup.pay(&[("bob", AMOUNT, "order-1"), ("bob", AMOUNT, "order-3")], Some(late.expires_at + 1));
+    assert_eq!(statuses(&events, "order-1"), Vec::<PaymentStatus>::new(), "expired before the block");
+    assert_eq!(statuses(&events, "order-3"), vec![PaymentStatus::Expired]);
+    setup.wait(CONFIRMATIONS);
+    assert_eq!(setup.status("order-1"), PaymentStatus::Expired);
+    assert_eq!(setup.status("order-3"), PaymentStatus::Expired);
+    
+    let payments = setup.watcher.payments("order-3");
+    assert_eq!(payments.len(), 1);
+    assert!(payments[0].late);
+    assert_eq!(payments[0].amount, AMOUNT);
+}
+
+/// Checks a request survives its URI, and malformed ones are refused
+#[test]
+fn check_uri() {
+    let mut setup = Setup::new();
+    let request = setup.request("order-1");
+    assert_eq!(PaymentRequest::from_uri(&request.to_uri()).unwrap(), request);
+    
+    let uri = request.to_uri();
+    for uri in [uri.replacen("genx:", "http:", 1), uri.replace("&reference=order-1", ""), format!("{}&extra=1", uri), uri.replace("order-1", "order 1")] {
+        assert!(PaymentRequest::from_uri(&uri).is_err(), "{}", uri);
+    }
+    assert!(setup.watcher.create(&request.account, AMOUNT, &"x".repeat(wallet::payments::MAX_REFERENCE_LEN + 1), 0).is_err());
+    assert!(setup.watcher.create(&request.account, 0, "order-9", 0).is_err());
+}
\ No newline at end of file
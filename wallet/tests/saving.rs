@@ -0,0 +1,160 @@
+//! Checks processes sharing a wallet file never lose or half-read each other's saves
+//!
+//! Run with `cargo test -p wallet --test saving`. Opens the same wallet
+//! file twice, as two processes would, and checks a save over a file the
+//! other changed is refused until reloaded, that wrong passwords are saved
+//! regardless, that a live lock holds saves off and a stale one is taken
+//! over, and that racing writers and readers only ever see whole files.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use wallet::file_lock::{FileLock, LockOwner, LOCK_STALE_AFTER};
+use wallet::{Wallet, WalletError};
+
+/// Password of the wallets made
+const PASSWORD: &str = "Shared-wallet-file-42";
+
+/// Accounts each racing writer creates
+const ACCOUNTS_PER_WRITER: usize = 8;
+
+/// Creates a wallet of one account in a directory of its own, returning the path of its file
+fn wallet_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("genx-saving-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("wallet.json");
+    Wallet::create(path.clone(), PASSWORD).expect("create the wallet").create_account("first").expect("create an account");
+    path
+}
+
+/// Opens a wallet file and unlocks it, as another process would
+fn open(path: &Path) -> Wallet {
+    let mut wallet = Wallet::load(path.to_path_buf()).expect("load the wallet");
+    wallet.unlock(PASSWORD).expect("unlock the wallet");
+    wallet
+}
+
+/// Gets the labels of the accounts in a wallet file
+fn labels_on_disk(path: &Path) -> HashSet<String> {
+    Wallet::load(path.to_path_buf()).expect("load the wallet").get_accounts().into_iter().map(|account| account.label.clone()).collect()
+}
+
+/// Checks a save over a file another wallet changed is refused, leaving both as they were, until reloaded
+#[test]
+fn check_concurrent_writer() {
+    let path = wallet_file("concurrent");
+    let mut first = open(&path);
+    let mut second = open(&path);
+    
+    first.create_account("from first").expect("the first save goes through");
+    let on_disk = fs::read(&path).unwrap();
+    let error = second.create_account("from second").unwrap_err();
+    assert!(matches!(error, WalletError::ConcurrentModification { .. }), "{}", error);
+    assert_eq!(fs::read(&path).unwrap(), on_disk, "the refused save wrote nothing");
+    assert_eq!(second.get_accounts().len(), 1, "the refused account isn't kept either");
+    
+    second.reload().expect("reload the wallet");
+    second.create_account("from second").expect("save once reloaded");
+    let expected: HashSet<String> = ["first", "from first", "from second"].iter().map(|label| label.to_string()).collect();
+    assert_eq!(labels_on_disk(&path), expected);
+    assert!(FileLock::owner(&path).unwrap().is_none(), "the lock is released after saving");
+    
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+/// Checks wrong passwords tried on an out of date wallet are saved without undoing the other wallet's changes
+#[test]
+fn check_throttle_on_stale_wallet() {
+    let path = wallet_file("throttle");
+    let mut first = open(&path);
+    let mut stale = Wallet::load(path.clone()).expect("load the wallet");
+    first.create_account("from first").expect("save an account");
+    
+    assert!(matches!(stale.unlock("wrong"), Err(WalletError::IncorrectPassword)));
+    let loaded = Wallet::load(path.clone()).expect("load the wallet");
+    assert_eq!(loaded.unlock_throttle().failed_attempts, 1, "the wrong password counts");
+    assert_eq!(loaded.get_accounts().len(), 2, "the other wallet's account is kept");
+    
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+/// Checks a lock another live process holds keeps saves off, and one whose owner went quiet is taken over
+#[test]
+fn check_held_lock() {
+    let path = wallet_file("lock");
+    let mut wallet = open(&path);
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    
+    let live = LockOwner { pid: u32::MAX, heartbeat: ctb_core::current_timestamp() };
+    fs::write(&lock_path, serde_json::to_vec(&live).unwrap()).unwrap();
+    let on_disk = fs::read(&path).unwrap();
+    let error = wallet.create_account("while locked").unwrap_err();
+    assert!(matches!(error, WalletError::WalletBusy { pid: u32::MAX }), "{}", error);
+    assert_eq!(fs::read(&path).unwrap(), on_disk);
+    assert_eq!(FileLock::owner(&path).unwrap(), Some(live), "another process's lock is left alone");
+    
+    let stale = LockOwner { pid: u32::MAX, heartbeat: live.heartbeat - LOCK_STALE_AFTER };
+    fs::write(&lock_path, serde_json::to_vec(&stale).unwrap()).unwrap();
+    wallet.create_account("after takeover").expect("take the stale lock over");
+    assert!(labels_on_disk(&path).contains("after takeover"));
+    assert!(FileLock::owner(&path).unwrap().is_none());
+    
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+/// Checks racing writers lose no account and readers never see a half written file
+#[test]
+fn check_atomic_replacement() {
+    let path = wallet_file("race");
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let (path, done) = (path.clone(), done.clone());
+        thread::spawn(move || {
+            let mut reads = 0;
+            while !done.load(Ordering::Relaxed) {
+                let contents = fs::read(&path).expect("the wallet file is always there");
+                serde_json::from_slice::<serde_json::Value>(&contents).expect("the wallet file is always whole");
+                reads += 1;
+            }
+            reads
+        })
+    };
+    
+    let writers: Vec<_> = (0..2)
+        .map(|writer| {
+            let path = path.clone();
+            thread::spawn(move || {
+                let mut wallet = open(&path);
+                for index in 0..ACCOUNTS_PER_WRITER {
+                    let label = format!("writer {} account {}", writer, index);
+                    loop {
+                        match wallet.create_account(&label) {
+                            Ok(_) => break,
+                            Err(WalletError::ConcurrentModification { .. }) => wallet.reload().expect("reload the wallet"),
+                            Err(e) => panic!("{} failed: {}", label, e),
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().expect("the writer finishes");
+    }
+    done.store(true, Ordering::Relaxed);
+    assert!(reader.join().expect("the reader finishes") > 0);
+    
+    let labels = labels_on_disk(&path);
+    assert_eq!(labels.len(), 1 + 2 * ACCOUNTS_PER_WRITER, "no account is lost: {:?}", labels);
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    assert!(!Path::new(&temp_path).exists(), "no temporary file is left behind");
+    assert!(FileLock::owner(&path).unwrap().is_none());
+    
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
\ No newline at end of file
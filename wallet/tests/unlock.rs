@@ -0,0 +1,100 @@
+//! Checks wrong passwords lock unlocking out, and the right one resets the lockout
+//!
+//! Run with `cargo test -p wallet --test unlock`. Tries wrong passwords on
+//! a wallet until unlocking is locked out, checks the lockout doubles, is
+//! kept in the wallet file across loads and lifts once its time is up, and
+//! that the right password then forgets the wrong ones.
+
+use std::path::PathBuf;
+
+use wallet::password::{unlock_delay, UnlockThrottle, BASE_UNLOCK_DELAY, FREE_UNLOCK_ATTEMPTS, MAX_UNLOCK_DELAY};
+use wallet::{Wallet, WalletError};
+
+/// Password of the wallets made
+const PASSWORD: &str = "Unlock-throttle-42";
+
+/// Time of the first wrong password, in seconds since the Unix epoch
+const START: u64 = 1_700_000_000;
+
+/// Creates a locked wallet of one account in a directory of its own
+fn locked_wallet(name: &str) -> (Wallet, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("genx-unlock-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("wallet.json");
+    let mut wallet = Wallet::create(path.clone(), PASSWORD).expect("create the wallet");
+    wallet.create_account("main").expect("create an account");
+    wallet.lock();
+    (wallet, path)
+}
+
+/// Checks the free wrong passwords don't lock unlocking out and the next ones do, for twice as long each time
+#[test]
+fn check_lockout() {
+    let (mut wallet, path) = locked_wallet("lockout");
+    for attempt in 1..=FREE_UNLOCK_ATTEMPTS {
+        assert!(matches!(wallet.unlock_at("wrong", START), Err(WalletError::IncorrectPassword)), "attempt {}", attempt);
+        assert_eq!(wallet.unlock_throttle(), UnlockThrottle { failed_attempts: attempt, locked_until: 0 });
+    }
+    
+    let mut now = START;
+    let mut delay = BASE_UNLOCK_DELAY.as_secs();
+    for attempt in FREE_UNLOCK_ATTEMPTS + 1..=FREE_UNLOCK_ATTEMPTS + 3 {
+        assert!(matches!(wallet.unlock_at("wrong", now), Err(WalletError::IncorrectPassword)), "attempt {}", attempt);
+        assert_eq!(wallet.unlock_throttle(), UnlockThrottle { failed_attempts: attempt, locked_until: now + delay });
+        
+        // Even the right password is refused until the lockout ends
+        let error = wallet.unlock_at(PASSWORD, now + delay - 1).unwrap_err();
+        assert!(matches!(error, WalletError::UnlockThrottled { retry_after: 1 }), "{}", error);
+        assert!(matches!(wallet.unlock_at("wrong", now), Err(WalletError::UnlockThrottled { retry_after }) if retry_after == delay));
+        assert_eq!(wallet.unlock_throttle().failed_attempts, attempt, "refused attempts aren't counted");
+        
+        now += delay;
+        delay *= 2;
+    }
+    
+    // The lockout is in the wallet file, so loading the wallet again doesn't lift it
+    let throttle = wallet.unlock_throttle();
+    let mut loaded = Wallet::load(path.clone()).expect("load the wallet");
+    assert_eq!(loaded.unlock_throttle(), throttle);
+    assert!(matches!(loaded.unlock_at(PASSWORD, now - 1), Err(WalletError::UnlockThrottled { retry_after: 1 })));
+    
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
+
+/// Checks the right password, once the lockout is over, forgets the wrong ones in the wallet and its file
+#[test]
+fn check_reset() {
+    let (mut wallet, path) = locked_wallet("reset");
+    for _ in 0..=FREE_UNLOCK_ATTEMPTS {
+        assert!(matches!(wallet.unlock_at("wrong", START), Err(WalletError::IncorrectPassword)));
+    }
+    let locked_until = wallet.unlock_throttle().locked_until;
+    assert_eq!(locked_until, START + BASE_UNLOCK_DELAY.as_secs());
+    
+    wallet.unlock_at(PASSWORD, locked_until).expect("unlock once the lockout ends");
+    assert_eq!(wallet.unlock_throttle(), UnlockThrottle::default());
+    assert_eq!(Wallet::load(path.clone()).expect("load the wallet").unlock_throttle(), UnlockThrottle::default());
+    
+    // Wrong passwords after the reset get the free attempts again
+    wallet.lock();
+    for _ in 0..FREE_UNLOCK_ATTEMPTS {
+        assert!(matches!(wallet.unlock_at("wrong", locked_until), Err(WalletError::IncorrectPassword)));
+    }
+    assert_eq!(wallet.unlock_throttle().locked_until, 0);
+    wallet.unlock_at(PASSWORD, locked_until).expect("unlock within the free attempts");
+    
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
+
+/// Checks the lockout stops doubling at `MAX_UNLOCK_DELAY`, however many wrong passwords are tried
+#[test]
+fn check_longest_lockout() {
+    assert_eq!(unlock_delay(FREE_UNLOCK_ATTEMPTS), std::time::Duration::ZERO);
+    assert_eq!(unlock_delay(FREE_UNLOCK_ATTEMPTS + 1), BASE_UNLOCK_DELAY);
+    assert_eq!(unlock_delay(FREE_UNLOCK_ATTEMPTS + 20), MAX_UNLOCK_DELAY);
+    assert_eq!(unlock_delay(u32::MAX), MAX_UNLOCK_DELAY);
+    
+    let mut throttle = UnlockThrottle { failed_attempts: u32::MAX, locked_until: 0 };
+    assert_eq!(throttle.record_failure(u64::MAX), MAX_UNLOCK_DELAY);
+    assert_eq!(throttle, UnlockThrottle { failed_attempts: u32::MAX, locked_until: u64::MAX });
+}
\ No newline at end of file